@@ -24,6 +24,14 @@ pub enum Action {
     IndexesDelete = actions::INDEXES_DELETE,
     #[serde(rename = "tasks.get")]
     TasksGet = actions::TASKS_GET,
+    #[serde(rename = "tasks.cancel")]
+    TasksCancel = actions::TASKS_CANCEL,
+    #[serde(rename = "tasks.forceReset")]
+    TasksForceReset = actions::TASKS_FORCE_RESET,
+    #[serde(rename = "tasks.readSensitive")]
+    TasksReadSensitive = actions::TASKS_READ_SENSITIVE,
+    #[serde(rename = "tasks.compact")]
+    TasksCompact = actions::TASKS_COMPACT,
     #[serde(rename = "settings.get")]
     SettingsGet = actions::SETTINGS_GET,
     #[serde(rename = "settings.update")]
@@ -32,8 +40,26 @@ pub enum Action {
     StatsGet = actions::STATS_GET,
     #[serde(rename = "dumps.create")]
     DumpsCreate = actions::DUMPS_CREATE,
+    #[serde(rename = "snapshots.create")]
+    SnapshotsCreate = actions::SNAPSHOTS_CREATE,
     #[serde(rename = "version")]
     Version = actions::VERSION,
+    #[serde(rename = "config.get")]
+    ConfigGet = actions::CONFIG_GET,
+    #[serde(rename = "config.update")]
+    ConfigUpdate = actions::CONFIG_UPDATE,
+    #[serde(rename = "namespaces.create")]
+    NamespacesCreate = actions::NAMESPACES_CREATE,
+    #[serde(rename = "namespaces.get")]
+    NamespacesGet = actions::NAMESPACES_GET,
+    #[serde(rename = "namespaces.delete")]
+    NamespacesDelete = actions::NAMESPACES_DELETE,
+    #[serde(rename = "support.bundle")]
+    SupportBundle = actions::SUPPORT_BUNDLE,
+    #[serde(rename = "analytics.get")]
+    AnalyticsGet = actions::ANALYTICS_GET,
+    #[serde(rename = "keys.*")]
+    KeysAll = actions::KEYS_ALL,
     #[serde(rename = "keys.create")]
     KeysAdd = actions::KEYS_CREATE,
     #[serde(rename = "keys.get")]
@@ -58,11 +84,24 @@ impl Action {
             INDEXES_UPDATE => Some(Self::IndexesUpdate),
             INDEXES_DELETE => Some(Self::IndexesDelete),
             TASKS_GET => Some(Self::TasksGet),
+            TASKS_CANCEL => Some(Self::TasksCancel),
+            TASKS_FORCE_RESET => Some(Self::TasksForceReset),
+            TASKS_READ_SENSITIVE => Some(Self::TasksReadSensitive),
+            TASKS_COMPACT => Some(Self::TasksCompact),
             SETTINGS_GET => Some(Self::SettingsGet),
             SETTINGS_UPDATE => Some(Self::SettingsUpdate),
             STATS_GET => Some(Self::StatsGet),
             DUMPS_CREATE => Some(Self::DumpsCreate),
+            SNAPSHOTS_CREATE => Some(Self::SnapshotsCreate),
             VERSION => Some(Self::Version),
+            CONFIG_GET => Some(Self::ConfigGet),
+            CONFIG_UPDATE => Some(Self::ConfigUpdate),
+            NAMESPACES_CREATE => Some(Self::NamespacesCreate),
+            NAMESPACES_GET => Some(Self::NamespacesGet),
+            NAMESPACES_DELETE => Some(Self::NamespacesDelete),
+            SUPPORT_BUNDLE => Some(Self::SupportBundle),
+            ANALYTICS_GET => Some(Self::AnalyticsGet),
+            KEYS_ALL => Some(Self::KeysAll),
             KEYS_CREATE => Some(Self::KeysAdd),
             KEYS_GET => Some(Self::KeysGet),
             KEYS_UPDATE => Some(Self::KeysUpdate),
@@ -71,6 +110,30 @@ impl Action {
         }
     }
 
+    /// Whether the action writes to the database, which a read-only replica rejects.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Self::DocumentsAdd
+                | Self::DocumentsDelete
+                | Self::IndexesAdd
+                | Self::IndexesUpdate
+                | Self::IndexesDelete
+                | Self::TasksCancel
+                | Self::TasksForceReset
+                | Self::TasksCompact
+                | Self::SettingsUpdate
+                | Self::DumpsCreate
+                | Self::SnapshotsCreate
+                | Self::ConfigUpdate
+                | Self::NamespacesCreate
+                | Self::NamespacesDelete
+                | Self::KeysAdd
+                | Self::KeysUpdate
+                | Self::KeysDelete
+        )
+    }
+
     pub fn repr(&self) -> u8 {
         use actions::*;
         match self {
@@ -84,17 +147,45 @@ impl Action {
             Self::IndexesUpdate => INDEXES_UPDATE,
             Self::IndexesDelete => INDEXES_DELETE,
             Self::TasksGet => TASKS_GET,
+            Self::TasksCancel => TASKS_CANCEL,
+            Self::TasksForceReset => TASKS_FORCE_RESET,
+            Self::TasksReadSensitive => TASKS_READ_SENSITIVE,
+            Self::TasksCompact => TASKS_COMPACT,
             Self::SettingsGet => SETTINGS_GET,
             Self::SettingsUpdate => SETTINGS_UPDATE,
             Self::StatsGet => STATS_GET,
             Self::DumpsCreate => DUMPS_CREATE,
+            Self::SnapshotsCreate => SNAPSHOTS_CREATE,
             Self::Version => VERSION,
+            Self::ConfigGet => CONFIG_GET,
+            Self::ConfigUpdate => CONFIG_UPDATE,
+            Self::NamespacesCreate => NAMESPACES_CREATE,
+            Self::NamespacesGet => NAMESPACES_GET,
+            Self::NamespacesDelete => NAMESPACES_DELETE,
+            Self::SupportBundle => SUPPORT_BUNDLE,
+            Self::AnalyticsGet => ANALYTICS_GET,
+            Self::KeysAll => KEYS_ALL,
             Self::KeysAdd => KEYS_CREATE,
             Self::KeysGet => KEYS_GET,
             Self::KeysUpdate => KEYS_UPDATE,
             Self::KeysDelete => KEYS_DELETE,
         }
     }
+
+    /// Returns the actions implied by this action, for wildcard actions like `keys.*`.
+    pub fn expand(&self) -> Vec<Self> {
+        match self {
+            Self::All => Self::into_enum_iter().collect(),
+            Self::KeysAll => vec![
+                Self::KeysAll,
+                Self::KeysAdd,
+                Self::KeysGet,
+                Self::KeysUpdate,
+                Self::KeysDelete,
+            ],
+            other => vec![*other],
+        }
+    }
 }
 
 pub mod actions {
@@ -112,9 +203,22 @@ pub mod actions {
     pub const SETTINGS_UPDATE: u8 = 11;
     pub const STATS_GET: u8 = 12;
     pub const DUMPS_CREATE: u8 = 13;
+    pub const SNAPSHOTS_CREATE: u8 = 14;
     pub const VERSION: u8 = 15;
     pub const KEYS_CREATE: u8 = 16;
     pub const KEYS_GET: u8 = 17;
     pub const KEYS_UPDATE: u8 = 18;
     pub const KEYS_DELETE: u8 = 19;
+    pub const TASKS_CANCEL: u8 = 20;
+    pub const KEYS_ALL: u8 = 21;
+    pub const CONFIG_GET: u8 = 22;
+    pub const CONFIG_UPDATE: u8 = 23;
+    pub const NAMESPACES_CREATE: u8 = 24;
+    pub const NAMESPACES_GET: u8 = 25;
+    pub const NAMESPACES_DELETE: u8 = 26;
+    pub const SUPPORT_BUNDLE: u8 = 27;
+    pub const TASKS_FORCE_RESET: u8 = 28;
+    pub const TASKS_READ_SENSITIVE: u8 = 29;
+    pub const TASKS_COMPACT: u8 = 30;
+    pub const ANALYTICS_GET: u8 = 31;
 }
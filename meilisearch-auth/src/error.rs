@@ -14,6 +14,10 @@ pub enum AuthControllerError {
     InvalidApiKeyActions(Value),
     #[error("`indexes` field value `{0}` is invalid. It should be an array of string representing index names.")]
     InvalidApiKeyIndexes(Value),
+    #[error("`namespaces` field value `{0}` is invalid. It should be an array of string representing namespace names or specified as a null value.")]
+    InvalidApiKeyNamespaces(Value),
+    #[error("`searchFilters` field value `{0}` is invalid. It should be an object associating index names or `*` to filters, a filter being a string or an array of strings and arrays of strings.")]
+    InvalidApiKeySearchFilters(Value),
     #[error("`expiresAt` field value `{0}` is invalid. It should follow the RFC 3339 format to represents a date or datetime in the future or specified as a null value. e.g. 'YYYY-MM-DD' or 'YYYY-MM-DD HH:MM:SS'.")]
     InvalidApiKeyExpiresAt(Value),
     #[error("`description` field value `{0}` is invalid. It should be a string or specified as a null value.")]
@@ -28,6 +32,8 @@ pub enum AuthControllerError {
     ApiKeyNotFound(String),
     #[error("`uid` field value `{0}` is already an existing API key.")]
     ApiKeyAlreadyExists(String),
+    #[error("The filter `{0}` of the search rules of the tenant token is invalid.")]
+    InvalidSearchRulesFilter(Value),
     #[error("The `{0}` field cannot be modified for the given resource.")]
     ImmutableField(String),
     #[error("Internal error: {0}")]
@@ -47,12 +53,15 @@ impl ErrorCode for AuthControllerError {
             Self::MissingParameter(_) => Code::MissingParameter,
             Self::InvalidApiKeyActions(_) => Code::InvalidApiKeyActions,
             Self::InvalidApiKeyIndexes(_) => Code::InvalidApiKeyIndexes,
+            Self::InvalidApiKeyNamespaces(_) => Code::InvalidApiKeyNamespaces,
+            Self::InvalidApiKeySearchFilters(_) => Code::InvalidApiKeySearchFilters,
             Self::InvalidApiKeyExpiresAt(_) => Code::InvalidApiKeyExpiresAt,
             Self::InvalidApiKeyDescription(_) => Code::InvalidApiKeyDescription,
             Self::InvalidApiKeyName(_) => Code::InvalidApiKeyName,
             Self::ApiKeyNotFound(_) => Code::ApiKeyNotFound,
             Self::InvalidApiKeyUid(_) => Code::InvalidApiKeyUid,
             Self::ApiKeyAlreadyExists(_) => Code::ApiKeyAlreadyExists,
+            Self::InvalidSearchRulesFilter(_) => Code::InvalidToken,
             Self::ImmutableField(_) => Code::ImmutableField,
             Self::Internal(_) => Code::Internal,
         }
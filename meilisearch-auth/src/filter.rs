@@ -0,0 +1,565 @@
+//! The compiled filters scoping the searches of the API keys and the tenant tokens.
+//!
+//! A scoping filter is parsed once into a tree of boolean operators over conditions: the search
+//! filters of a key are compiled when the key is created and stored along with it, and the filters
+//! of the search rules of a tenant token are compiled when the token is verified, and kept in a
+//! bounded cache. The conditions are checked by the filter parser of the engine, so that an
+//! invalid filter is rejected before any search.
+//!
+//! The filter of a search is parsed into the same tree, and combined with the scoping filter as an
+//! `AND` of both trees. The combined tree is rendered with every operand between parentheses, so
+//! that no operator of the filter of the search can bind to the conditions of the scoping filter.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use meilisearch_error::{Code, ErrorCode};
+use milli::Filter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// The maximum nesting of the parentheses and `NOT` operators of a filter.
+const MAX_FILTER_DEPTH: usize = 64;
+/// The number of compiled filters of the tenant tokens kept in memory.
+const FILTER_CACHE_CAPACITY: usize = 1024;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid filter: {0}")]
+pub struct FilterError(String);
+
+impl ErrorCode for FilterError {
+    fn error_code(&self) -> Code {
+        Code::Filter
+    }
+}
+
+type Result<T> = std::result::Result<T, FilterError>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompiledFilter {
+    /// A condition on a field or on the geo point, as written in the filter.
+    Condition(String),
+    Not(Box<CompiledFilter>),
+    And(Vec<CompiledFilter>),
+    Or(Vec<CompiledFilter>),
+}
+
+impl CompiledFilter {
+    /// Parses a filter given as a string, or as an array of strings and arrays of strings. `None`
+    /// if the filter is empty.
+    pub fn parse(filter: &Value) -> Result<Option<Self>> {
+        match filter {
+            Value::String(expression) => parse_expression(expression),
+            Value::Array(ands) => {
+                let mut filters = Vec::new();
+                for and in ands {
+                    let filter = match and {
+                        Value::String(expression) => parse_expression(expression)?,
+                        Value::Array(ors) => {
+                            let mut filters = Vec::new();
+                            for or in ors {
+                                match or {
+                                    Value::String(expression) => {
+                                        filters.extend(parse_expression(expression)?)
+                                    }
+                                    value => return Err(invalid_value(value)),
+                                }
+                            }
+                            filters.into_iter().reduce(Self::or)
+                        }
+                        value => return Err(invalid_value(value)),
+                    };
+                    filters.extend(filter);
+                }
+                Ok(filters.into_iter().reduce(Self::and))
+            }
+            value => Err(invalid_value(value)),
+        }
+    }
+
+    /// Parses `filter`, and checks its conditions with the filter parser of the engine.
+    pub fn compile(filter: &Value) -> Result<Option<Self>> {
+        let filter = Self::parse(filter)?;
+        if let Some(ref filter) = filter {
+            Filter::from_str(&filter.to_string()).map_err(|e| FilterError(e.to_string()))?;
+        }
+        Ok(filter)
+    }
+
+    /// Combines two filters such that the documents must match both.
+    pub fn and(self, other: Self) -> Self {
+        let mut ands = match self {
+            Self::And(ands) => ands,
+            filter => vec![filter],
+        };
+        match other {
+            Self::And(other) => ands.extend(other),
+            other => ands.push(other),
+        }
+        Self::And(ands)
+    }
+
+    fn or(self, other: Self) -> Self {
+        let mut ors = match self {
+            Self::Or(ors) => ors,
+            filter => vec![filter],
+        };
+        match other {
+            Self::Or(other) => ors.extend(other),
+            other => ors.push(other),
+        }
+        Self::Or(ors)
+    }
+}
+
+/// Renders the filter for the filter parser of the engine, every operand between parentheses.
+impl fmt::Display for CompiledFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, filters: &[Self], operator: &str| {
+            for (i, filter) in filters.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", operator)?;
+                }
+                write!(f, "({})", filter)?;
+            }
+            Ok(())
+        };
+
+        match self {
+            Self::Condition(condition) => f.write_str(condition),
+            Self::Not(filter) => write!(f, "NOT ({})", filter),
+            Self::And(filters) => join(f, filters, "AND"),
+            Self::Or(filters) => join(f, filters, "OR"),
+        }
+    }
+}
+
+fn invalid_value(value: &Value) -> FilterError {
+    FilterError(format!(
+        "`{}` should be a string, or an array of strings and arrays of strings.",
+        value
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    /// A word, a quoted string or a function call, by its position in the filter.
+    Atom(usize, usize),
+}
+
+fn parse_expression(expression: &str) -> Result<Option<CompiledFilter>> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser {
+        expression,
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let filter = parser.or()?;
+    match parser.peek() {
+        None => Ok(Some(filter)),
+        Some(_) => Err(parser.unexpected()),
+    }
+}
+
+/// Splits the filter into tokens. The quoted strings end at the next quote of the same kind, as
+/// in the filter parser of the engine.
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let bytes = expression.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => i += 1,
+            b'(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            b')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            b'\'' | b'"' => {
+                let start = i;
+                i = quoted_end(expression, i)?;
+                tokens.push(Token::Atom(start, i));
+            }
+            _ => {
+                let start = i;
+                while i < bytes.len()
+                    && !bytes[i].is_ascii_whitespace()
+                    && !matches!(bytes[i], b'(' | b')' | b'\'' | b'"')
+                {
+                    i += 1;
+                }
+                let token = match &expression[start..i] {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    // the arguments of a function, like `_geoRadius(45.4, 2.3, 1000)`.
+                    _ if bytes.get(i) == Some(&b'(') => {
+                        i = call_end(expression, i)?;
+                        Token::Atom(start, i)
+                    }
+                    _ => Token::Atom(start, i),
+                };
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// The end of the quoted string starting at `start`.
+fn quoted_end(expression: &str, start: usize) -> Result<usize> {
+    let quote = expression.as_bytes()[start];
+    match expression.as_bytes()[start + 1..]
+        .iter()
+        .position(|b| *b == quote)
+    {
+        Some(len) => Ok(start + len + 2),
+        None => Err(FilterError(format!(
+            "the quoted string starting at position {} is not closed.",
+            start
+        ))),
+    }
+}
+
+/// The end of the arguments of a function, starting with the parenthesis at `start`.
+fn call_end(expression: &str, start: usize) -> Result<usize> {
+    let bytes = expression.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            }
+            b'\'' | b'"' => {
+                i = quoted_end(expression, i)?;
+                continue;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    Err(FilterError(format!(
+        "the parenthesis at position {} is not closed.",
+        start
+    )))
+}
+
+/// A recursive descent parser of the tokens of a filter. `NOT` binds tighter than `AND`, which
+/// binds tighter than `OR`.
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn unexpected(&self) -> FilterError {
+        match self.peek() {
+            Some(Token::Atom(start, end)) => {
+                FilterError(format!("unexpected `{}`.", &self.expression[start..end]))
+            }
+            Some(Token::Open) => FilterError("unexpected `(`.".to_string()),
+            Some(Token::Close) => FilterError("unexpected `)`.".to_string()),
+            Some(Token::And) => FilterError("unexpected `AND`.".to_string()),
+            Some(Token::Or) => FilterError("unexpected `OR`.".to_string()),
+            Some(Token::Not) => FilterError("unexpected `NOT`.".to_string()),
+            None => FilterError("the filter ends with an operator.".to_string()),
+        }
+    }
+
+    fn or(&mut self) -> Result<CompiledFilter> {
+        let mut filter = self.and()?;
+        while self.peek() == Some(Token::Or) {
+            self.pos += 1;
+            filter = filter.or(self.and()?);
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<CompiledFilter> {
+        let mut filter = self.not()?;
+        while self.peek() == Some(Token::And) {
+            self.pos += 1;
+            filter = filter.and(self.not()?);
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<CompiledFilter> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                let filter = self.nested(Self::not)?;
+                Ok(CompiledFilter::Not(Box::new(filter)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<CompiledFilter> {
+        match self.peek() {
+            Some(Token::Open) => {
+                self.pos += 1;
+                let filter = self.nested(Self::or)?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(filter)
+                    }
+                    _ => Err(FilterError("a parenthesis is not closed.".to_string())),
+                }
+            }
+            Some(Token::Atom(start, mut end)) => {
+                // a condition spans the atoms up to the next operator or parenthesis.
+                while let Some(Token::Atom(_, next_end)) = self.peek() {
+                    end = next_end;
+                    self.pos += 1;
+                }
+                Ok(CompiledFilter::Condition(
+                    self.expression[start..end].to_string(),
+                ))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<CompiledFilter>) -> Result<CompiledFilter> {
+        if self.depth == MAX_FILTER_DEPTH {
+            return Err(FilterError(format!(
+                "the filter is nested more than {} times.",
+                MAX_FILTER_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let filter = parse(self);
+        self.depth -= 1;
+        filter
+    }
+}
+
+struct CachedFilter {
+    /// The filter, to tell it apart from another filter with the same hash.
+    source: String,
+    filter: Option<CompiledFilter>,
+    last_used: u64,
+}
+
+/// The compiled filters of the tenant tokens, by uid of their parent key and hash of the filter.
+/// The least recently used filter is evicted once the cache is full.
+#[derive(Default)]
+pub(crate) struct FilterCache {
+    inner: Mutex<FilterCacheInner>,
+}
+
+#[derive(Default)]
+struct FilterCacheInner {
+    filters: HashMap<(Uuid, u64), CachedFilter>,
+    clock: u64,
+}
+
+impl FilterCache {
+    pub(crate) fn get_or_compile(
+        &self,
+        key_uid: Uuid,
+        filter: &Value,
+    ) -> Result<Option<CompiledFilter>> {
+        let source = filter.to_string();
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        let id = (key_uid, hasher.finish());
+
+        {
+            let mut guard = self.inner.lock().unwrap();
+            let inner = &mut *guard;
+            inner.clock += 1;
+            if let Some(cached) = inner.filters.get_mut(&id) {
+                if cached.source == source {
+                    cached.last_used = inner.clock;
+                    return Ok(cached.filter.clone());
+                }
+            }
+        }
+
+        // the invalid filters are not cached.
+        let compiled = CompiledFilter::compile(filter)?;
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.filters.len() >= FILTER_CACHE_CAPACITY && !inner.filters.contains_key(&id) {
+            let oldest = inner
+                .filters
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                inner.filters.remove(&oldest);
+            }
+        }
+        let last_used = inner.clock;
+        inner.filters.insert(
+            id,
+            CachedFilter {
+                source,
+                filter: compiled.clone(),
+                last_used,
+            },
+        );
+
+        Ok(compiled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(filter: &str) -> CompiledFilter {
+        CompiledFilter::parse(&json!(filter)).unwrap().unwrap()
+    }
+
+    fn condition(condition: &str) -> CompiledFilter {
+        CompiledFilter::Condition(condition.to_string())
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let filter = parse("a = 1 OR b = 2 AND NOT c = 3");
+        assert_eq!(
+            filter,
+            CompiledFilter::Or(vec![
+                condition("a = 1"),
+                CompiledFilter::And(vec![
+                    condition("b = 2"),
+                    CompiledFilter::Not(Box::new(condition("c = 3"))),
+                ]),
+            ])
+        );
+        assert_eq!(filter.to_string(), "(a = 1) OR ((b = 2) AND (NOT (c = 3)))");
+
+        let filter = parse("(a = 1 OR b = 2) AND c 1 TO 5");
+        assert_eq!(filter.to_string(), "((a = 1) OR (b = 2)) AND (c 1 TO 5)");
+
+        // the conditions are kept as written, quotes and functions included.
+        let filter = parse("title = 'AND (OR' AND _geoRadius(45.4, 2.3, 1000)");
+        assert_eq!(
+            filter,
+            CompiledFilter::And(vec![
+                condition("title = 'AND (OR'"),
+                condition("_geoRadius(45.4, 2.3, 1000)"),
+            ])
+        );
+
+        let filter = CompiledFilter::parse(&json!(["a = 1 OR b = 2", ["c = 3", "d = 4"]]));
+        assert_eq!(
+            filter.unwrap().unwrap().to_string(),
+            "((a = 1) OR (b = 2)) AND ((c = 3) OR (d = 4))"
+        );
+
+        assert_eq!(CompiledFilter::parse(&json!("  ")).unwrap(), None);
+        assert_eq!(CompiledFilter::parse(&json!([])).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_filters_are_rejected() {
+        for filter in [
+            json!("a = 1)"),
+            json!("(a = 1"),
+            json!("a = 1 OR"),
+            json!("AND a = 1"),
+            json!("NOT"),
+            json!("()"),
+            json!("title = 'unclosed"),
+            json!("_geoRadius(1, 2, 3"),
+            json!(42),
+            json!([["a = 1", 42]]),
+            json!("(".repeat(MAX_FILTER_DEPTH + 1) + "a = 1" + &")".repeat(MAX_FILTER_DEPTH + 1)),
+        ] {
+            assert!(CompiledFilter::parse(&filter).is_err(), "{}", filter);
+        }
+    }
+
+    /// The filter of a search can't widen the documents of the scoping filter, whatever its
+    /// operators: parsing back the combined filter always gives the scoping filter as an operand
+    /// of the top level `AND`.
+    #[test]
+    fn test_scope_cannot_be_escaped() {
+        let scope = parse("tenant = acme");
+        for filter in [
+            "color = red OR tenant = globex",
+            "NOT tenant = acme OR id > 0",
+            "color = red) OR (tenant = globex",
+            "(color = red)) OR ((tenant = globex",
+            "((color = red OR (tenant = globex AND NOT (id = 1))))",
+            "NOT (NOT (tenant = acme)) OR NOT tenant = acme",
+            "title = ')' OR tenant = globex",
+            "_geoRadius(1, 2, 3) OR tenant = globex",
+        ] {
+            let filter = match CompiledFilter::parse(&json!(filter)) {
+                Ok(filter) => filter.unwrap(),
+                // an unbalanced filter is rejected.
+                Err(_) => continue,
+            };
+            let combined = scope.clone().and(filter.clone());
+            let reparsed = parse(&combined.to_string());
+            assert_eq!(reparsed, combined);
+            match reparsed {
+                CompiledFilter::And(ands) => assert_eq!(ands[0], scope),
+                filter => panic!("`{}` escaped the scope", filter),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cache_evicts_the_least_recently_used_filter() {
+        let cache = FilterCache::default();
+        let uid = Uuid::new_v4();
+        for i in 0..FILTER_CACHE_CAPACITY {
+            let filter = json!(format!("id = {}", i));
+            cache.get_or_compile(uid, &filter).unwrap();
+        }
+        // the first filter is used again, the second one is the least recently used.
+        cache.get_or_compile(uid, &json!("id = 0")).unwrap();
+        cache.get_or_compile(uid, &json!("id = extra")).unwrap();
+
+        let inner = cache.inner.lock().unwrap();
+        assert_eq!(inner.filters.len(), FILTER_CACHE_CAPACITY);
+        let sources: Vec<_> = inner.filters.values().map(|f| f.source.as_str()).collect();
+        assert!(sources.contains(&"\"id = 0\""));
+        assert!(!sources.contains(&"\"id = 1\""));
+        assert!(sources.contains(&"\"id = extra\""));
+        drop(inner);
+
+        assert!(cache.get_or_compile(uid, &json!("id = ")).is_err());
+    }
+}
@@ -1,7 +1,10 @@
 use crate::action::Action;
 use crate::error::{AuthControllerError, Result};
+use crate::filter::CompiledFilter;
 use crate::store::KeyId;
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use serde_json::{from_value, Value};
 use time::format_description::well_known::Rfc3339;
@@ -18,12 +21,38 @@ pub struct Key {
     pub uid: KeyId,
     pub actions: Vec<Action>,
     pub indexes: Vec<String>,
+    /// The namespaces the key is restricted to, on top of its indexes. `None` if the key isn't
+    /// restricted to any namespace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespaces: Option<Vec<String>>,
+    /// The filters added to every search made with the key, by index name or `*` for all the
+    /// indexes of the key.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub search_filters: BTreeMap<String, Value>,
+    /// The compiled `search_filters`, stored along with them and compiled again whenever they
+    /// change. `None` for the keys stored before the filters were compiled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compiled_search_filters: Option<BTreeMap<String, CompiledFilter>>,
     #[serde(with = "time::serde::rfc3339::option")]
     pub expires_at: Option<OffsetDateTime>,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
     pub updated_at: OffsetDateTime,
+    /// The salt of the secret of the key, changed by each rotation. `None` until the key is
+    /// rotated for the first time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_salt: Option<String>,
+    /// The secret the key was rotated from, accepted until the end of the overlap window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_secret: Option<PreviousSecret>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PreviousSecret {
+    pub salt: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub valid_until: OffsetDateTime,
 }
 
 impl Key {
@@ -66,6 +95,20 @@ impl Key {
             })
             .ok_or(AuthControllerError::MissingParameter("indexes"))??;
 
+        let namespaces = match value.get("namespaces") {
+            None | Some(Value::Null) => None,
+            Some(ns) => from_value(ns.clone())
+                .map(Some)
+                .map_err(|_| AuthControllerError::InvalidApiKeyNamespaces(ns.clone()))?,
+        };
+
+        let search_filters = match value.get("searchFilters") {
+            None | Some(Value::Null) => BTreeMap::new(),
+            Some(sf) => from_value::<BTreeMap<String, Value>>(sf.clone())
+                .map_err(|_| AuthControllerError::InvalidApiKeySearchFilters(sf.clone()))?,
+        };
+        let compiled_search_filters = Some(compile_search_filters(&search_filters)?);
+
         let expires_at = value
             .get("expiresAt")
             .map(parse_expiration_date)
@@ -80,12 +123,48 @@ impl Key {
             uid,
             actions,
             indexes,
+            namespaces,
+            search_filters,
+            compiled_search_filters,
             expires_at,
             created_at,
             updated_at,
+            secret_salt: None,
+            previous_secret: None,
         })
     }
 
+    /// Gives the key a new secret. The previous one is still accepted until `valid_until`, so
+    /// that the clients can switch to the new one.
+    pub(crate) fn rotate(&mut self, valid_until: OffsetDateTime) {
+        let salt = Some(Uuid::new_v4().to_string());
+        self.previous_secret = Some(PreviousSecret {
+            salt: std::mem::replace(&mut self.secret_salt, salt),
+            valid_until,
+        });
+        self.updated_at = OffsetDateTime::now_utc();
+    }
+
+    /// The salts of the secrets accepted for the key at `now`: its current one, and the one it
+    /// was rotated from until the end of the overlap window.
+    pub fn accepted_salts(&self, now: OffsetDateTime) -> impl Iterator<Item = Option<&str>> {
+        let previous = self
+            .previous_secret
+            .as_ref()
+            .filter(|previous| now < previous.valid_until)
+            .map(|previous| previous.salt.as_deref());
+        std::iter::once(self.secret_salt.as_deref()).chain(previous)
+    }
+
+    /// The compiled search filters of the key. The filters of a key stored before they were
+    /// compiled are compiled on the fly.
+    pub fn compiled_search_filters(&self) -> Result<BTreeMap<String, CompiledFilter>> {
+        match self.compiled_search_filters {
+            Some(ref compiled) => Ok(compiled.clone()),
+            None => compile_search_filters(&self.search_filters),
+        }
+    }
+
     pub fn update_from_value(&mut self, value: Value) -> Result<()> {
         if let Some(des) = value.get("description") {
             let des = from_value(des.clone())
@@ -111,6 +190,18 @@ impl Key {
             return Err(AuthControllerError::ImmutableField("indexes".to_string()));
         }
 
+        if value.get("namespaces").is_some() {
+            return Err(AuthControllerError::ImmutableField(
+                "namespaces".to_string(),
+            ));
+        }
+
+        if value.get("searchFilters").is_some() {
+            return Err(AuthControllerError::ImmutableField(
+                "searchFilters".to_string(),
+            ));
+        }
+
         if value.get("expiresAt").is_some() {
             return Err(AuthControllerError::ImmutableField("expiresAt".to_string()));
         }
@@ -137,9 +228,14 @@ impl Key {
             uid,
             actions: vec![Action::All],
             indexes: vec!["*".to_string()],
+            namespaces: None,
+            search_filters: BTreeMap::new(),
+            compiled_search_filters: Some(BTreeMap::new()),
             expires_at: None,
             created_at: now,
             updated_at: now,
+            secret_salt: None,
+            previous_secret: None,
         }
     }
 
@@ -152,13 +248,32 @@ impl Key {
             uid,
             actions: vec![Action::Search],
             indexes: vec!["*".to_string()],
+            namespaces: None,
+            search_filters: BTreeMap::new(),
+            compiled_search_filters: Some(BTreeMap::new()),
             expires_at: None,
             created_at: now,
             updated_at: now,
+            secret_salt: None,
+            previous_secret: None,
         }
     }
 }
 
+/// Compiles the search filters of a key, skipping the empty ones. Whether the attributes of a
+/// filter are filterable on its index is checked when the key is used.
+fn compile_search_filters(
+    search_filters: &BTreeMap<String, Value>,
+) -> Result<BTreeMap<String, CompiledFilter>> {
+    let mut compiled = BTreeMap::new();
+    for (index, filter) in search_filters {
+        let filter = CompiledFilter::compile(filter)
+            .map_err(|_| AuthControllerError::InvalidApiKeySearchFilters(filter.clone()))?;
+        compiled.extend(filter.map(|filter| (index.clone(), filter)));
+    }
+    Ok(compiled)
+}
+
 fn parse_expiration_date(value: &Value) -> Result<Option<OffsetDateTime>> {
     match value {
         Value::String(string) => OffsetDateTime::parse(string, &Rfc3339)
@@ -197,3 +312,73 @@ fn parse_expiration_date(value: &Value) -> Result<Option<OffsetDateTime>> {
         _otherwise => Err(AuthControllerError::InvalidApiKeyExpiresAt(value.clone())),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn key_with_filters(search_filters: Value) -> Key {
+        Key::create_from_value(json!({
+            "actions": ["search"],
+            "indexes": ["sales"],
+            "expiresAt": null,
+            "searchFilters": search_filters,
+        }))
+        .unwrap()
+    }
+
+    fn compiled(filter: &str) -> CompiledFilter {
+        CompiledFilter::compile(&json!(filter)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_empty_search_filter() {
+        // an empty filter is skipped, the compiled filters are still up to date.
+        let key = key_with_filters(json!({ "sales": "" }));
+        assert_eq!(key.compiled_search_filters, Some(BTreeMap::new()));
+        assert!(key.compiled_search_filters().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_filters_compiled_on_creation() {
+        let key = key_with_filters(json!({ "sales": "tenant = acme", "*": "public = true" }));
+        assert_eq!(
+            key.compiled_search_filters().unwrap(),
+            BTreeMap::from([
+                ("*".to_string(), compiled("public = true")),
+                ("sales".to_string(), compiled("tenant = acme")),
+            ])
+        );
+
+        // a filter that can't be compiled is rejected along with the key.
+        let invalid = Key::create_from_value(json!({
+            "actions": ["search"],
+            "indexes": ["sales"],
+            "expiresAt": null,
+            "searchFilters": { "sales": 42 },
+        }));
+        assert!(matches!(
+            invalid,
+            Err(AuthControllerError::InvalidApiKeySearchFilters(_))
+        ));
+    }
+
+    #[test]
+    fn test_search_filters_stored_before_compilation() {
+        let key = key_with_filters(json!({ "sales": "tenant = acme" }));
+        let mut stored = serde_json::to_value(&key).unwrap();
+        stored
+            .as_object_mut()
+            .unwrap()
+            .remove("compiled_search_filters");
+
+        let key: Key = serde_json::from_value(stored).unwrap();
+        assert_eq!(key.compiled_search_filters, None);
+        assert_eq!(
+            key.compiled_search_filters().unwrap(),
+            BTreeMap::from([("sales".to_string(), compiled("tenant = acme"))])
+        );
+    }
+}
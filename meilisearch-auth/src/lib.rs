@@ -1,29 +1,42 @@
 mod action;
 mod dump;
 pub mod error;
+mod filter;
 mod key;
 mod store;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
 pub use action::{actions, Action};
 use error::{AuthControllerError, Result};
-pub use key::Key;
+use filter::FilterCache;
+pub use filter::{CompiledFilter, FilterError};
+pub use key::{Key, PreviousSecret};
 use store::generate_key_as_base64;
 pub use store::open_auth_store_env;
 use store::HeedAuthStore;
 
+/// How long the secret of a rotated key is still accepted by default.
+pub const DEFAULT_ROTATION_OVERLAP: Duration = Duration::HOUR;
+
 #[derive(Clone)]
 pub struct AuthController {
     store: Arc<HeedAuthStore>,
     master_key: Option<String>,
+    /// The time a key, or a tenant token, is still accepted after its expiration date, against
+    /// the clock skew between the instance and the clients that set the date.
+    expiration_leeway: Duration,
+    /// How long the secret of a rotated key is still accepted.
+    rotation_overlap: Duration,
+    /// The compiled filters of the search rules of the tenant tokens.
+    filter_cache: Arc<FilterCache>,
 }
 
 impl AuthController {
@@ -37,9 +50,26 @@ impl AuthController {
         Ok(Self {
             store: Arc::new(store),
             master_key: master_key.clone(),
+            expiration_leeway: Duration::ZERO,
+            rotation_overlap: DEFAULT_ROTATION_OVERLAP,
+            filter_cache: Arc::default(),
         })
     }
 
+    pub fn set_expiration_leeway(&mut self, leeway: Duration) -> &mut Self {
+        self.expiration_leeway = leeway;
+        self
+    }
+
+    pub fn set_rotation_overlap(&mut self, overlap: Duration) -> &mut Self {
+        self.rotation_overlap = overlap;
+        self
+    }
+
+    pub fn expiration_leeway(&self) -> Duration {
+        self.expiration_leeway
+    }
+
     pub fn create_key(&self, value: Value) -> Result<Key> {
         let key = Key::create_from_value(value)?;
         match self.store.get_api_key(key.uid)? {
@@ -56,6 +86,15 @@ impl AuthController {
         self.store.put_api_key(key)
     }
 
+    /// Gives the key `uid` a new secret. Its previous secret is still accepted during the
+    /// rotation overlap.
+    pub fn rotate_key(&self, uid: Uuid) -> Result<Key> {
+        let valid_until = OffsetDateTime::now_utc() + self.rotation_overlap;
+        self.store
+            .rotate_api_key(uid, valid_until)?
+            .ok_or_else(|| AuthControllerError::ApiKeyNotFound(uid.to_string()))
+    }
+
     pub fn get_key(&self, uid: Uuid) -> Result<Key> {
         self.store
             .get_api_key(uid)?
@@ -76,16 +115,26 @@ impl AuthController {
             .ok_or_else(|| AuthControllerError::ApiKeyNotFound(encoded_key.to_string()))
     }
 
+    /// The restrictions of the key `uid`, along with the search rules of the tenant token signed
+    /// by the key, if any. The filters of the search rules are compiled, and an invalid filter is
+    /// rejected.
     pub fn get_key_filters(
         &self,
         uid: Uuid,
         search_rules: Option<SearchRules>,
     ) -> Result<AuthFilter> {
-        let mut filters = AuthFilter::default();
+        let mut filters = AuthFilter {
+            key_uid: Some(uid),
+            ..AuthFilter::default()
+        };
         let key = self
             .store
             .get_api_key(uid)?
             .ok_or_else(|| AuthControllerError::ApiKeyNotFound(uid.to_string()))?;
+        let search_rules = search_rules
+            .map(|search_rules| search_rules.compile(uid, &self.filter_cache))
+            .transpose()?;
+        let search_filters = key.compiled_search_filters()?;
 
         if !key.indexes.iter().any(|i| i.as_str() == "*") {
             filters.search_rules = match search_rules {
@@ -106,11 +155,23 @@ impl AuthController {
             filters.search_rules = search_rules;
         }
 
+        filters.search_rules = filters.search_rules.restrict(&search_filters);
+
+        filters.namespaces = key
+            .namespaces
+            .map(|namespaces| namespaces.into_iter().collect());
+
         filters.allow_index_creation = key
             .actions
             .iter()
             .any(|&action| action == Action::IndexesAdd || action == Action::All);
 
+        filters.is_admin = key.actions.contains(&Action::All);
+        filters.read_sensitive_tasks = key
+            .actions
+            .iter()
+            .any(|&action| action == Action::TasksReadSensitive || action == Action::All);
+
         Ok(filters)
     }
 
@@ -130,12 +191,34 @@ impl AuthController {
         self.master_key.as_ref()
     }
 
-    /// Generate a valid key from a key id using the current master key.
+    /// Generate the current secret of a key using the current master key.
     /// Returns None if no master key has been set.
-    pub fn generate_key(&self, uid: Uuid) -> Option<String> {
-        self.master_key
-            .as_ref()
-            .map(|master_key| generate_key_as_base64(uid.as_bytes(), master_key.as_bytes()))
+    pub fn generate_key(&self, key: &Key) -> Option<String> {
+        self.master_key.as_ref().map(|master_key| {
+            generate_key_as_base64(
+                key.uid.as_bytes(),
+                key.secret_salt.as_deref(),
+                master_key.as_bytes(),
+            )
+        })
+    }
+
+    /// The secrets accepted for the key `uid`: its current one, and the one it was rotated from
+    /// during the rotation overlap. Empty if the key doesn't exist or no master key has been set.
+    pub fn accepted_keys(&self, uid: Uuid) -> Result<Vec<String>> {
+        let master_key = match &self.master_key {
+            Some(master_key) => master_key,
+            None => return Ok(Vec::new()),
+        };
+        let key = match self.store.get_api_key(uid)? {
+            Some(key) => key,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(key
+            .accepted_salts(OffsetDateTime::now_utc())
+            .map(|salt| generate_key_as_base64(uid.as_bytes(), salt, master_key.as_bytes()))
+            .collect())
     }
 
     /// Check if the provided key is authorized to make a specific action
@@ -145,6 +228,19 @@ impl AuthController {
         uid: Uuid,
         action: Action,
         index: Option<&str>,
+    ) -> Result<bool> {
+        // The `*` action is expanded when the key is stored, checking it explicitly
+        // also grants the actions that were introduced after the key creation.
+        Ok(self.is_key_authorized_for_action(uid, action, index)?
+            || (action != Action::All
+                && self.is_key_authorized_for_action(uid, Action::All, index)?))
+    }
+
+    fn is_key_authorized_for_action(
+        &self,
+        uid: Uuid,
+        action: Action,
+        index: Option<&str>,
     ) -> Result<bool> {
         match self
             .store
@@ -160,7 +256,7 @@ impl AuthController {
                 None => self.store.prefix_first_expiration_date(uid, action)?,
             }) {
             // check expiration date.
-            Some(Some(exp)) => Ok(OffsetDateTime::now_utc() < exp),
+            Some(Some(exp)) => Ok(OffsetDateTime::now_utc() < exp + self.expiration_leeway),
             // no expiration date.
             Some(None) => Ok(true),
             // action or index forbidden.
@@ -169,9 +265,30 @@ impl AuthController {
     }
 }
 
+#[derive(Clone)]
 pub struct AuthFilter {
     pub search_rules: SearchRules,
     pub allow_index_creation: bool,
+    /// Uid of the API key used to authenticate the request, `None` for the master key
+    /// or when no master key is set.
+    pub key_uid: Option<Uuid>,
+    /// The namespaces the indexes must belong to, `None` if any namespace is allowed. It
+    /// restricts the indexes allowed by the `search_rules` even further.
+    pub namespaces: Option<HashSet<String>>,
+    /// Whether the key is allowed to perform every action, like the master key. Some routes
+    /// reveal more details to such keys.
+    pub is_admin: bool,
+    /// Whether the key is allowed to read the origin of the tasks: the API key, address and user
+    /// agent of the requests that registered them.
+    pub read_sensitive_tasks: bool,
+}
+
+impl AuthFilter {
+    pub fn is_namespace_authorized(&self, namespace: &str) -> bool {
+        self.namespaces
+            .as_ref()
+            .map_or(true, |namespaces| namespaces.contains(namespace))
+    }
 }
 
 impl Default for AuthFilter {
@@ -179,6 +296,10 @@ impl Default for AuthFilter {
         Self {
             search_rules: SearchRules::default(),
             allow_index_creation: true,
+            key_uid: None,
+            namespaces: None,
+            is_admin: true,
+            read_sensitive_tasks: true,
         }
     }
 }
@@ -222,6 +343,79 @@ impl SearchRules {
     }
 }
 
+impl SearchRules {
+    /// Compiles the filters of the rules of a tenant token signed by the key `key_uid`.
+    fn compile(self, key_uid: Uuid, cache: &FilterCache) -> Result<Self> {
+        match self {
+            Self::Set(set) => Ok(Self::Set(set)),
+            Self::Map(map) => {
+                let mut compiled = HashMap::with_capacity(map.len());
+                for (index, rules) in map {
+                    let rules = match rules {
+                        Some(mut rules) => {
+                            if let Some(ref filter) = rules.filter {
+                                rules.compiled_filter =
+                                    cache.get_or_compile(key_uid, filter).map_err(|_| {
+                                        AuthControllerError::InvalidSearchRulesFilter(
+                                            filter.clone(),
+                                        )
+                                    })?;
+                            }
+                            Some(rules)
+                        }
+                        None => None,
+                    };
+                    compiled.insert(index, rules);
+                }
+                Ok(Self::Map(compiled))
+            }
+        }
+    }
+
+    /// Adds the compiled search filters of a key on top of the filters of the rules, so that
+    /// they can't be weakened. The filter of `*` applies to every index, along with the filter of
+    /// the index itself.
+    pub fn restrict(self, search_filters: &BTreeMap<String, CompiledFilter>) -> Self {
+        if search_filters.is_empty() {
+            return self;
+        }
+
+        let mut map = match self {
+            Self::Set(set) => set.into_iter().map(|index| (index, None)).collect(),
+            Self::Map(map) => map,
+        };
+
+        // An index allowed through `*` needs its own rules to get its own filter.
+        if let Some(rules) = map.get("*").cloned() {
+            for index in search_filters.keys() {
+                if !map.contains_key(index) {
+                    map.insert(index.clone(), rules.clone());
+                }
+            }
+        }
+
+        let map = map
+            .into_iter()
+            .map(|(index, rules)| {
+                let mut rules = rules.unwrap_or_default();
+                let index_filter = search_filters.get(&index).filter(|_| index != "*");
+                for filter in [index_filter, search_filters.get("*")]
+                    .into_iter()
+                    .flatten()
+                {
+                    rules.compiled_filter = Some(match rules.compiled_filter.take() {
+                        Some(compiled) => compiled.and(filter.clone()),
+                        None => filter.clone(),
+                    });
+                }
+                (index, Some(rules))
+            })
+            .collect();
+
+        Self::Map(map)
+    }
+}
+
 impl IntoIterator for SearchRules {
     type Item = (String, IndexSearchRules);
     type IntoIter = Box<dyn Iterator<Item = Self::Item>>;
@@ -244,6 +438,10 @@ impl IntoIterator for SearchRules {
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct IndexSearchRules {
     pub filter: Option<serde_json::Value>,
+    /// The filter restricting the searches: the compiled `filter`, along with the search filters
+    /// of the key. Set by `AuthController::get_key_filters`.
+    #[serde(skip)]
+    pub compiled_filter: Option<CompiledFilter>,
 }
 
 fn generate_default_keys(store: &HeedAuthStore) -> Result<()> {
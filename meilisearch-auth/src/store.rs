@@ -7,7 +7,6 @@ use std::path::Path;
 use std::str;
 use std::sync::Arc;
 
-use enum_iterator::IntoEnumIterator;
 use milli::heed::types::{ByteSlice, DecodeIgnore, SerdeJson};
 use milli::heed::{Database, Env, EnvOpenOptions, RwTxn};
 use sha2::{Digest, Sha256};
@@ -74,33 +73,52 @@ impl HeedAuthStore {
     }
 
     pub fn put_api_key(&self, key: Key) -> Result<Key> {
-        let uid = key.uid;
         let mut wtxn = self.env.write_txn()?;
+        self.put_api_key_in(&mut wtxn, &key)?;
+        wtxn.commit()?;
+
+        Ok(key)
+    }
+
+    /// Gives the key `uid` a new secret, the previous one being accepted until `valid_until`.
+    /// Returns `None` if the key doesn't exist.
+    pub fn rotate_api_key(&self, uid: Uuid, valid_until: OffsetDateTime) -> Result<Option<Key>> {
+        let mut wtxn = self.env.write_txn()?;
+        let mut key = match self.keys.get(&wtxn, uid.as_bytes())? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        key.rotate(valid_until);
+        self.put_api_key_in(&mut wtxn, &key)?;
+        wtxn.commit()?;
+
+        Ok(Some(key))
+    }
 
-        self.keys.put(&mut wtxn, uid.as_bytes(), &key)?;
+    fn put_api_key_in(&self, wtxn: &mut RwTxn, key: &Key) -> Result<()> {
+        let uid = key.uid;
+        self.keys.put(wtxn, uid.as_bytes(), key)?;
 
         // delete key from inverted database before refilling it.
-        self.delete_key_from_inverted_db(&mut wtxn, &uid)?;
+        self.delete_key_from_inverted_db(wtxn, &uid)?;
         // create inverted database.
         let db = self.action_keyid_index_expiration;
 
-        let actions = if key.actions.contains(&Action::All) {
-            // if key.actions contains All, we iterate over all actions.
-            Action::into_enum_iter().collect()
-        } else {
-            key.actions.clone()
-        };
+        // wildcard actions like `*` or `keys.*` are expanded into the actions they imply.
+        let mut actions: Vec<_> = key.actions.iter().flat_map(Action::expand).collect();
+        actions.sort_unstable_by_key(Action::repr);
+        actions.dedup();
 
         let no_index_restriction = key.indexes.contains(&"*".to_owned());
         for action in actions {
             if no_index_restriction {
                 // If there is no index restriction we put None.
-                db.put(&mut wtxn, &(&uid, &action, None), &key.expires_at)?;
+                db.put(wtxn, &(&uid, &action, None), &key.expires_at)?;
             } else {
                 // else we create a key for each index.
                 for index in key.indexes.iter() {
                     db.put(
-                        &mut wtxn,
+                        wtxn,
                         &(&uid, &action, Some(index.as_bytes())),
                         &key.expires_at,
                     )?;
@@ -108,9 +126,7 @@ impl HeedAuthStore {
             }
         }
 
-        wtxn.commit()?;
-
-        Ok(key)
+        Ok(())
     }
 
     pub fn get_api_key(&self, uid: Uuid) -> Result<Option<Key>> {
@@ -123,23 +139,19 @@ impl HeedAuthStore {
         encoded_key: &[u8],
         master_key: &[u8],
     ) -> Result<Option<Uuid>> {
+        let now = OffsetDateTime::now_utc();
         let rtxn = self.env.read_txn()?;
-        let uid = self
-            .keys
-            .remap_data_type::<DecodeIgnore>()
-            .iter(&rtxn)?
-            .filter_map(|res| match res {
-                Ok((uid, _))
-                    if generate_key_as_base64(uid, master_key).as_bytes() == encoded_key =>
-                {
-                    let (uid, _) = try_split_array_at(uid)?;
-                    Some(Uuid::from_bytes(*uid))
-                }
-                _ => None,
-            })
-            .next();
+        for result in self.keys.remap_key_type::<DecodeIgnore>().iter(&rtxn)? {
+            let (_, key) = result?;
+            let uid = key.uid;
+            if key.accepted_salts(now).any(|salt| {
+                generate_key_as_base64(uid.as_bytes(), salt, master_key).as_bytes() == encoded_key
+            }) {
+                return Ok(Some(uid));
+            }
+        }
 
-        Ok(uid)
+        Ok(None)
     }
 
     pub fn delete_api_key(&self, uid: Uuid) -> Result<bool> {
@@ -241,8 +253,11 @@ impl<'a> milli::heed::BytesEncode<'a> for KeyIdActionCodec {
     }
 }
 
-pub fn generate_key_as_base64(uid: &[u8], master_key: &[u8]) -> String {
-    let key = [uid, master_key].concat();
+/// Generates the secret of a key from its uid and the salt of its secret, `None` if it was never
+/// rotated.
+pub fn generate_key_as_base64(uid: &[u8], salt: Option<&str>, master_key: &[u8]) -> String {
+    let salt = salt.map_or(&[][..], str::as_bytes);
+    let key = [uid, master_key, salt].concat();
     let sha = Sha256::digest(&key);
     base64::encode_config(sha, base64::URL_SAFE_NO_PAD)
 }
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use actix_web::http::header::RETRY_AFTER;
 use actix_web::{self as aweb, http::StatusCode, HttpResponseBuilder};
 use serde::{Deserialize, Serialize};
 
@@ -20,6 +21,13 @@ pub struct ResponseError {
     error_type: String,
     #[serde(rename = "link")]
     error_link: String,
+    /// The messages of the errors that caused this one, from the closest to the root cause.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    causes: Vec<String>,
+    /// The number of seconds after which the request can be retried, sent in the `Retry-After`
+    /// header.
+    #[serde(skip)]
+    retry_after: Option<u64>,
 }
 
 impl ResponseError {
@@ -30,8 +38,32 @@ impl ResponseError {
             error_code: code.err_code().error_name.to_string(),
             error_type: code.type_(),
             error_link: code.url(),
+            causes: Vec::new(),
+            retry_after: code.retry_after(),
+        }
+    }
+
+    /// The messages of the errors that caused this one, from the closest to the root cause.
+    pub fn causes(&self) -> &[String] {
+        &self.causes
+    }
+}
+
+/// Returns the messages of the chain of sources of `error`. The errors wrapping another one often
+/// repeat its message in theirs, such a message is only kept once.
+fn error_causes(error: &dyn std::error::Error) -> Vec<String> {
+    let mut causes = Vec::new();
+    let mut last = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        let message = error.to_string();
+        if !last.contains(&message) {
+            causes.push(message.clone());
         }
+        last = message;
+        source = error.source();
     }
+    causes
 }
 
 impl fmt::Display for ResponseError {
@@ -53,6 +85,8 @@ where
             error_code: other.error_name(),
             error_type: other.error_type(),
             error_link: other.error_url(),
+            causes: error_causes(&other),
+            retry_after: other.retry_after(),
         }
     }
 }
@@ -60,9 +94,11 @@ where
 impl aweb::error::ResponseError for ResponseError {
     fn error_response(&self) -> aweb::HttpResponse {
         let json = serde_json::to_vec(self).unwrap();
-        HttpResponseBuilder::new(self.status_code())
-            .content_type("application/json")
-            .body(json)
+        let mut response = HttpResponseBuilder::new(self.status_code());
+        if let Some(retry_after) = self.retry_after {
+            response.insert_header((RETRY_AFTER, retry_after));
+        }
+        response.content_type("application/json").body(json)
     }
 
     fn status_code(&self) -> StatusCode {
@@ -92,6 +128,12 @@ pub trait ErrorCode: std::error::Error {
     fn error_type(&self) -> String {
         self.error_code().type_()
     }
+
+    /// returns the number of seconds after which the request can be retried, for the errors
+    /// whose delay depends on their cause rather than on their code.
+    fn retry_after(&self) -> Option<u64> {
+        self.error_code().retry_after()
+    }
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -119,8 +161,15 @@ pub enum Code {
     CreateIndex,
     IndexAlreadyExists,
     IndexNotFound,
+    IndexClosed,
+    IndexBeingCreated,
     InvalidIndexUid,
     InvalidMinWordLengthForTypo,
+    InvalidSettingsExport,
+    InvalidSettingsPreview,
+    NamespaceAlreadyExists,
+    NamespaceNotFound,
+    InvalidNamespace,
 
     // invalid state error
     InvalidState,
@@ -128,11 +177,25 @@ pub enum Code {
     PrimaryKeyAlreadyPresent,
 
     MaxFieldsLimitExceeded,
+    MaxDocumentsLimitReached,
     MissingDocumentId,
     InvalidDocumentId,
 
     Filter,
     Sort,
+    InvalidSearchBoost,
+    InvalidSearchableWeight,
+    InvalidDocumentTransform,
+    InvalidCustomSegmenters,
+    MissingSegmenter,
+    InvalidDocumentsCursor,
+    InvalidPit,
+    PitExpired,
+    InvalidSearchLikeDocuments,
+    InvalidSearchGroupBy,
+    LikeDocumentNotFound,
+    InvalidAggregateMetric,
+    InvalidAggregateField,
 
     BadParameter,
     BadRequest,
@@ -147,9 +210,29 @@ pub enum Code {
     NoSpaceLeftOnDevice,
     DumpNotFound,
     TaskNotFound,
+    TaskNotCancelable,
+    TaskCanceled,
+    TaskAbortedByAdmin,
+    TaskQuarantined,
+    ProcessingBatchNotStuck,
+    BatchNotFound,
+    InvalidIdempotencyKey,
+    TooManyTaskRegistrations,
+    TooManyQueuedJobs,
+    TooManyConcurrentReads,
+    DatabaseUnavailable,
+    IndexTemporarilyUnavailable,
+    ReplicaReadOnly,
+    TaskWaitTimeout,
     PayloadTooLarge,
+    PayloadTimeout,
+    PendingPayloadQuotaExceeded,
     RetrieveDocument,
     SearchDocuments,
+    TooManySearchRequests,
+    SearchTimeout,
+    SettingsPreviewTimeout,
+    InvalidFederatedSearch,
     UnsupportedMediaType,
 
     DumpAlreadyInProgress,
@@ -158,12 +241,25 @@ pub enum Code {
     InvalidContentType,
     MissingContentType,
     MalformedPayload,
+    DocumentOverLimit,
     MissingPayload,
+    DuplicateDocuments,
+    CorruptedPayload,
+    InvalidDocumentEdition,
+    InvalidDocumentFetchUrl,
+    DocumentFetchFailed,
+    UploadNotFound,
+    InvalidContentRange,
+    UploadRangeMismatch,
+    UploadAlreadyCommitted,
+    IncompleteUpload,
 
     ApiKeyNotFound,
     MissingParameter,
     InvalidApiKeyActions,
     InvalidApiKeyIndexes,
+    InvalidApiKeyNamespaces,
+    InvalidApiKeySearchFilters,
     InvalidApiKeyExpiresAt,
     InvalidApiKeyDescription,
     InvalidApiKeyName,
@@ -186,7 +282,16 @@ impl Code {
             IndexAlreadyExists => ErrCode::invalid("index_already_exists", StatusCode::CONFLICT),
             // thrown when requesting an unexisting index
             IndexNotFound => ErrCode::invalid("index_not_found", StatusCode::NOT_FOUND),
+            // thrown when using an index that was closed
+            IndexClosed => ErrCode::invalid("index_closed", StatusCode::CONFLICT),
+            // thrown when using an index that an enqueued task creates
+            IndexBeingCreated => ErrCode::invalid("index_being_created", StatusCode::CONFLICT),
             InvalidIndexUid => ErrCode::invalid("invalid_index_uid", StatusCode::BAD_REQUEST),
+            NamespaceAlreadyExists => {
+                ErrCode::invalid("namespace_already_exists", StatusCode::CONFLICT)
+            }
+            NamespaceNotFound => ErrCode::invalid("namespace_not_found", StatusCode::NOT_FOUND),
+            InvalidNamespace => ErrCode::invalid("invalid_namespace", StatusCode::BAD_REQUEST),
 
             // invalid state error
             InvalidState => ErrCode::internal("invalid_state", StatusCode::INTERNAL_SERVER_ERROR),
@@ -210,6 +315,9 @@ impl Code {
             MaxFieldsLimitExceeded => {
                 ErrCode::invalid("max_fields_limit_exceeded", StatusCode::BAD_REQUEST)
             }
+            MaxDocumentsLimitReached => {
+                ErrCode::invalid("max_documents_limit_reached", StatusCode::BAD_REQUEST)
+            }
             MissingDocumentId => ErrCode::invalid("missing_document_id", StatusCode::BAD_REQUEST),
             InvalidDocumentId => ErrCode::invalid("invalid_document_id", StatusCode::BAD_REQUEST),
 
@@ -217,6 +325,40 @@ impl Code {
             Filter => ErrCode::invalid("invalid_filter", StatusCode::BAD_REQUEST),
             // error related to sorts
             Sort => ErrCode::invalid("invalid_sort", StatusCode::BAD_REQUEST),
+            InvalidSearchBoost => ErrCode::invalid("invalid_search_boost", StatusCode::BAD_REQUEST),
+            InvalidSearchableWeight => {
+                ErrCode::invalid("invalid_searchable_weight", StatusCode::BAD_REQUEST)
+            }
+            InvalidDocumentTransform => {
+                ErrCode::invalid("invalid_document_transform", StatusCode::BAD_REQUEST)
+            }
+            InvalidCustomSegmenters => {
+                ErrCode::invalid("invalid_custom_segmenters", StatusCode::BAD_REQUEST)
+            }
+            MissingSegmenter => {
+                ErrCode::internal("missing_segmenter", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            InvalidDocumentsCursor => {
+                ErrCode::invalid("invalid_documents_cursor", StatusCode::BAD_REQUEST)
+            }
+            InvalidPit => ErrCode::invalid("invalid_pit", StatusCode::BAD_REQUEST),
+            PitExpired => ErrCode::invalid("pit_expired", StatusCode::GONE),
+            InvalidSearchLikeDocuments => {
+                ErrCode::invalid("invalid_search_like_documents", StatusCode::BAD_REQUEST)
+            }
+            InvalidSearchGroupBy => {
+                ErrCode::invalid("invalid_search_group_by", StatusCode::BAD_REQUEST)
+            }
+            LikeDocumentNotFound => {
+                ErrCode::invalid("like_document_not_found", StatusCode::BAD_REQUEST)
+            }
+            // error related to aggregations
+            InvalidAggregateMetric => {
+                ErrCode::invalid("invalid_aggregate_metric", StatusCode::BAD_REQUEST)
+            }
+            InvalidAggregateField => {
+                ErrCode::invalid("invalid_aggregate_field", StatusCode::BAD_REQUEST)
+            }
 
             BadParameter => ErrCode::invalid("bad_parameter", StatusCode::BAD_REQUEST),
             BadRequest => ErrCode::invalid("bad_request", StatusCode::BAD_REQUEST),
@@ -232,15 +374,65 @@ impl Code {
                 ErrCode::authentication("missing_authorization_header", StatusCode::UNAUTHORIZED)
             }
             TaskNotFound => ErrCode::invalid("task_not_found", StatusCode::NOT_FOUND),
+            TaskNotCancelable => ErrCode::invalid("task_not_cancelable", StatusCode::CONFLICT),
+            TaskCanceled => ErrCode::invalid("task_canceled", StatusCode::BAD_REQUEST),
+            TaskAbortedByAdmin => {
+                ErrCode::internal("task_aborted_by_admin", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            TaskQuarantined => ErrCode::internal(
+                "quarantined_after_repeated_failures",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            ProcessingBatchNotStuck => {
+                ErrCode::invalid("processing_batch_not_stuck", StatusCode::CONFLICT)
+            }
+            BatchNotFound => ErrCode::invalid("batch_not_found", StatusCode::NOT_FOUND),
+            InvalidIdempotencyKey => {
+                ErrCode::invalid("invalid_idempotency_key", StatusCode::BAD_REQUEST)
+            }
+            TaskWaitTimeout => ErrCode::internal("task_wait_timeout", StatusCode::GATEWAY_TIMEOUT),
+            TooManyTaskRegistrations => ErrCode::internal(
+                "too_many_task_registrations",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            TooManyQueuedJobs => {
+                ErrCode::internal("too_many_queued_jobs", StatusCode::SERVICE_UNAVAILABLE)
+            }
+            TooManyConcurrentReads => {
+                ErrCode::internal("too_many_concurrent_reads", StatusCode::SERVICE_UNAVAILABLE)
+            }
+            DatabaseUnavailable => {
+                ErrCode::internal("database_unavailable", StatusCode::SERVICE_UNAVAILABLE)
+            }
+            IndexTemporarilyUnavailable => ErrCode::internal(
+                "index_temporarily_unavailable",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            ReplicaReadOnly => ErrCode::invalid("replica_read_only", StatusCode::FORBIDDEN),
             DumpNotFound => ErrCode::invalid("dump_not_found", StatusCode::NOT_FOUND),
             NoSpaceLeftOnDevice => {
                 ErrCode::internal("no_space_left_on_device", StatusCode::INTERNAL_SERVER_ERROR)
             }
             PayloadTooLarge => ErrCode::invalid("payload_too_large", StatusCode::PAYLOAD_TOO_LARGE),
+            PayloadTimeout => ErrCode::invalid("payload_timeout", StatusCode::REQUEST_TIMEOUT),
+            PendingPayloadQuotaExceeded => ErrCode::invalid(
+                "pending_payload_quota_exceeded",
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
             RetrieveDocument => {
                 ErrCode::internal("unretrievable_document", StatusCode::BAD_REQUEST)
             }
             SearchDocuments => ErrCode::internal("search_error", StatusCode::BAD_REQUEST),
+            TooManySearchRequests => {
+                ErrCode::internal("too_many_search_requests", StatusCode::SERVICE_UNAVAILABLE)
+            }
+            SearchTimeout => ErrCode::internal("search_timeout", StatusCode::GATEWAY_TIMEOUT),
+            SettingsPreviewTimeout => {
+                ErrCode::internal("settings_preview_timeout", StatusCode::GATEWAY_TIMEOUT)
+            }
+            InvalidFederatedSearch => {
+                ErrCode::invalid("invalid_federated_search", StatusCode::BAD_REQUEST)
+            }
             UnsupportedMediaType => {
                 ErrCode::invalid("unsupported_media_type", StatusCode::UNSUPPORTED_MEDIA_TYPE)
             }
@@ -256,10 +448,33 @@ impl Code {
                 ErrCode::invalid("missing_content_type", StatusCode::UNSUPPORTED_MEDIA_TYPE)
             }
             MalformedPayload => ErrCode::invalid("malformed_payload", StatusCode::BAD_REQUEST),
+            DocumentOverLimit => ErrCode::invalid("document_over_limit", StatusCode::BAD_REQUEST),
             InvalidContentType => {
                 ErrCode::invalid("invalid_content_type", StatusCode::UNSUPPORTED_MEDIA_TYPE)
             }
             MissingPayload => ErrCode::invalid("missing_payload", StatusCode::BAD_REQUEST),
+            DuplicateDocuments => ErrCode::invalid("duplicate_documents", StatusCode::BAD_REQUEST),
+            CorruptedPayload => {
+                ErrCode::internal("corrupted_payload", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            InvalidDocumentEdition => {
+                ErrCode::invalid("invalid_document_edition", StatusCode::BAD_REQUEST)
+            }
+            InvalidDocumentFetchUrl => {
+                ErrCode::invalid("invalid_document_fetch_url", StatusCode::BAD_REQUEST)
+            }
+            DocumentFetchFailed => {
+                ErrCode::invalid("document_fetch_failed", StatusCode::BAD_REQUEST)
+            }
+            UploadNotFound => ErrCode::invalid("upload_not_found", StatusCode::NOT_FOUND),
+            InvalidContentRange => {
+                ErrCode::invalid("invalid_content_range", StatusCode::BAD_REQUEST)
+            }
+            UploadRangeMismatch => ErrCode::invalid("upload_range_mismatch", StatusCode::CONFLICT),
+            UploadAlreadyCommitted => {
+                ErrCode::invalid("upload_already_committed", StatusCode::CONFLICT)
+            }
+            IncompleteUpload => ErrCode::invalid("incomplete_upload", StatusCode::BAD_REQUEST),
 
             // error related to keys
             ApiKeyNotFound => ErrCode::invalid("api_key_not_found", StatusCode::NOT_FOUND),
@@ -270,6 +485,12 @@ impl Code {
             InvalidApiKeyIndexes => {
                 ErrCode::invalid("invalid_api_key_indexes", StatusCode::BAD_REQUEST)
             }
+            InvalidApiKeyNamespaces => {
+                ErrCode::invalid("invalid_api_key_namespaces", StatusCode::BAD_REQUEST)
+            }
+            InvalidApiKeySearchFilters => {
+                ErrCode::invalid("invalid_api_key_search_filters", StatusCode::BAD_REQUEST)
+            }
             InvalidApiKeyExpiresAt => {
                 ErrCode::invalid("invalid_api_key_expires_at", StatusCode::BAD_REQUEST)
             }
@@ -283,6 +504,12 @@ impl Code {
             InvalidMinWordLengthForTypo => {
                 ErrCode::invalid("invalid_min_word_length_for_typo", StatusCode::BAD_REQUEST)
             }
+            InvalidSettingsExport => {
+                ErrCode::invalid("invalid_settings_export", StatusCode::BAD_REQUEST)
+            }
+            InvalidSettingsPreview => {
+                ErrCode::invalid("invalid_settings_preview", StatusCode::BAD_REQUEST)
+            }
         }
     }
 
@@ -305,6 +532,21 @@ impl Code {
     fn url(&self) -> String {
         format!("https://docs.meilisearch.com/errors#{}", self.name())
     }
+
+    /// return the number of seconds after which a request failing with this `Code` can be
+    /// retried, if it is only failing because of a temporary condition.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Code::TooManySearchRequests => Some(1),
+            Code::TooManyTaskRegistrations => Some(1),
+            // a job, like a snapshot, takes a while to be processed.
+            Code::TooManyQueuedJobs => Some(60),
+            Code::TooManyConcurrentReads => Some(1),
+            Code::DatabaseUnavailable => Some(1),
+            Code::IndexBeingCreated => Some(1),
+            _ => None,
+        }
+    }
 }
 
 /// Internal structure providing a convenient way to create error codes
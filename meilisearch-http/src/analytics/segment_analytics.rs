@@ -247,6 +247,10 @@ impl Segment {
             infos["import_snapshot"] = json!(import_snapshot);
             infos["snapshot_dir"] = json!(snapshots_dir);
             infos["http_addr"] = json!(http_addr);
+            // The secrets are redacted, but whether they are set is not sent either.
+            if let Some(infos) = infos.as_object_mut() {
+                infos.remove("master_key");
+            }
 
             infos
         };
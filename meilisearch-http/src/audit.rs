@@ -0,0 +1,198 @@
+//! Append-only audit log of the administrative operations performed on the instance.
+//!
+//! Each mutating request (except searches) is recorded as one JSON object per line in the audit
+//! log file. Entries are sent to a dedicated writer thread so that request handling never waits
+//! on the disk. When the file grows over the configured size it is rotated: `audit.log` becomes
+//! `audit.log.1`, `audit.log.1` becomes `audit.log.2`, and so on, up to the configured number of
+//! rotated files.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use actix_web::HttpRequest;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use meilisearch_auth::AuthFilter;
+use meilisearch_lib::rotating_file::RotatingFile;
+use meilisearch_lib::tasks::task::TaskId;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::task::SummarizedTaskView;
+use crate::Opt;
+
+/// The audit logs currently opened, by path. A single writer must exist for a given file,
+/// whatever the number of http workers that were configured with it.
+static AUDIT_LOGS: Lazy<Mutex<HashMap<PathBuf, AuditLog>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+    key_uid: Option<Uuid>,
+    method: String,
+    route: String,
+    path: String,
+    index_uid: Option<String>,
+    task_uid: Option<TaskId>,
+    /// The tasks affected by a request acting on several tasks at once.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    task_uids: Vec<TaskId>,
+}
+
+/// Handle to the audit log writer. Does nothing if the audit log is disabled.
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    sender: Option<Sender<AuditEntry>>,
+}
+
+impl AuditLog {
+    /// Returns the audit log configured by the options, spawning its writer if it
+    /// doesn't exist yet.
+    pub fn from_opt(opt: &Opt) -> io::Result<Self> {
+        let path = match opt.audit_log_path {
+            Some(ref path) => path.clone(),
+            None => return Ok(Self::default()),
+        };
+
+        let mut audit_logs = AUDIT_LOGS.lock();
+        if let Some(audit_log) = audit_logs.get(&path) {
+            return Ok(audit_log.clone());
+        }
+
+        let file = RotatingFile::new(
+            path.clone(),
+            opt.audit_log_max_size.get_bytes() as u64,
+            opt.audit_log_max_files,
+        )?;
+        let (sender, receiver) = unbounded();
+        std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || run_writer(file, receiver))?;
+
+        let audit_log = Self {
+            sender: Some(sender),
+        };
+        audit_logs.insert(path, audit_log.clone());
+
+        Ok(audit_log)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Records a request that resulted in the registration of a task.
+    pub fn record_task(&self, req: &HttpRequest, filters: &AuthFilter, task: &SummarizedTaskView) {
+        self.record(req, filters, task.index_uid.clone(), Some(task.task_uid));
+    }
+
+    /// Records a request. The entry is written asynchronously, this method never blocks.
+    pub fn record(
+        &self,
+        req: &HttpRequest,
+        filters: &AuthFilter,
+        index_uid: Option<String>,
+        task_uid: Option<TaskId>,
+    ) {
+        self.record_entry(req, filters, index_uid, task_uid, Vec::new());
+    }
+
+    /// Records a request that affected the tasks `task_uids`.
+    pub fn record_tasks(&self, req: &HttpRequest, filters: &AuthFilter, task_uids: Vec<TaskId>) {
+        self.record_entry(req, filters, None, None, task_uids);
+    }
+
+    fn record_entry(
+        &self,
+        req: &HttpRequest,
+        filters: &AuthFilter,
+        index_uid: Option<String>,
+        task_uid: Option<TaskId>,
+        task_uids: Vec<TaskId>,
+    ) {
+        if let Some(ref sender) = self.sender {
+            let entry = AuditEntry {
+                timestamp: OffsetDateTime::now_utc(),
+                key_uid: filters.key_uid,
+                method: req.method().to_string(),
+                route: req
+                    .match_pattern()
+                    .unwrap_or_else(|| req.path().to_string()),
+                path: req.path().to_string(),
+                index_uid,
+                task_uid,
+                task_uids,
+            };
+
+            // the channel is unbounded, sending can only fail if the writer is gone.
+            if sender.send(entry).is_err() {
+                log::error!("The audit log writer is not running anymore.");
+            }
+        }
+    }
+}
+
+fn run_writer(mut file: RotatingFile, receiver: Receiver<AuditEntry>) {
+    for entry in receiver {
+        if let Err(e) = file.write_json(&entry) {
+            log::error!("Error while writing to the audit log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+
+    use meilisearch_lib::rotating_file::rotated_path;
+
+    use super::*;
+
+    fn entry(task_uid: TaskId) -> AuditEntry {
+        AuditEntry {
+            timestamp: OffsetDateTime::UNIX_EPOCH,
+            key_uid: None,
+            method: "POST".to_string(),
+            route: "/indexes/{index_uid}/documents".to_string(),
+            path: "/indexes/movies/documents".to_string(),
+            index_uid: Some("movies".to_string()),
+            task_uid: Some(task_uid),
+            task_uids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let line_len = serde_json::to_vec(&entry(0)).unwrap().len() as u64 + 1;
+
+        // room for two entries per file, and two rotated files.
+        let mut file = RotatingFile::new(path.clone(), line_len * 2, 2).unwrap();
+        for i in 0..7 {
+            file.write_json(&entry(i)).unwrap();
+        }
+
+        let read_uids = |path: &Path| -> Vec<TaskId> {
+            fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|l| {
+                    let value: serde_json::Value = serde_json::from_str(l).unwrap();
+                    value["taskUid"].as_u64().unwrap() as TaskId
+                })
+                .collect()
+        };
+
+        assert_eq!(read_uids(&path), vec![6]);
+        assert_eq!(read_uids(&rotated_path(&path, 1)), vec![4, 5]);
+        assert_eq!(read_uids(&rotated_path(&path, 2)), vec![2, 3]);
+        assert!(!rotated_path(&path, 3).exists());
+    }
+}
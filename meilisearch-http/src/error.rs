@@ -12,6 +12,20 @@ pub enum MeilisearchHttpError {
         .1.iter().map(|s| format!("`{}`", s)).collect::<Vec<_>>().join(", ")
     )]
     InvalidContentType(String, Vec<String>),
+    #[error("The slow query log is disabled. It can be enabled with the `--slow-query-log-path` option.")]
+    SlowQueryLogDisabled,
+    #[error("The search analytics are disabled. They can be enabled with the `--search-analytics` option.")]
+    SearchAnalyticsDisabled,
+    #[error(
+        "The changefeed is disabled. It can be enabled with the `--enable-changefeed` option."
+    )]
+    ChangefeedDisabled,
+    #[error("At most {max} tasks can be listed at once, but the limit is `{limit}`. Larger listings can be streamed with the `stream=true` parameter, or the `Accept: application/x-ndjson` header.")]
+    TasksLimitTooLarge { limit: usize, max: usize },
+    #[error(
+        "`offset` can't be used with `cursor`: a page starts either at an offset or at a cursor."
+    )]
+    OffsetWithCursor,
 }
 
 impl ErrorCode for MeilisearchHttpError {
@@ -19,6 +33,11 @@ impl ErrorCode for MeilisearchHttpError {
         match self {
             MeilisearchHttpError::MissingContentType(_) => Code::MissingContentType,
             MeilisearchHttpError::InvalidContentType(_, _) => Code::InvalidContentType,
+            MeilisearchHttpError::SlowQueryLogDisabled
+            | MeilisearchHttpError::SearchAnalyticsDisabled
+            | MeilisearchHttpError::ChangefeedDisabled
+            | MeilisearchHttpError::TasksLimitTooLarge { .. } => Code::BadRequest,
+            MeilisearchHttpError::OffsetWithCursor => Code::InvalidDocumentsCursor,
         }
     }
 }
@@ -9,6 +9,8 @@ pub enum AuthenticationError {
     // Triggered on configuration error.
     #[error("An internal error has occurred. `Irretrievable state`.")]
     IrretrievableState,
+    #[error("This instance is a read-only replica, the writes must be sent to its primary.")]
+    ReplicaReadOnly,
 }
 
 impl ErrorCode for AuthenticationError {
@@ -17,6 +19,7 @@ impl ErrorCode for AuthenticationError {
             AuthenticationError::MissingAuthorizationHeader => Code::MissingAuthorizationHeader,
             AuthenticationError::InvalidToken => Code::InvalidToken,
             AuthenticationError::IrretrievableState => Code::Internal,
+            AuthenticationError::ReplicaReadOnly => Code::ReplicaReadOnly,
         }
     }
 }
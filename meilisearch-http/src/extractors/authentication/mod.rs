@@ -9,8 +9,9 @@ use futures::future::err;
 use futures::Future;
 use meilisearch_error::{Code, ResponseError};
 
-use error::AuthenticationError;
+pub use error::AuthenticationError;
 use meilisearch_auth::{AuthController, AuthFilter};
+use meilisearch_lib::MeiliSearch;
 
 pub struct GuardedData<P, D> {
     data: D,
@@ -27,12 +28,18 @@ impl<P, D> GuardedData<P, D> {
         auth: AuthController,
         token: String,
         index: Option<String>,
+        meilisearch: Option<MeiliSearch>,
         data: Option<D>,
     ) -> Result<Self, ResponseError>
     where
         P: Policy + 'static,
     {
-        match Self::authenticate(auth, token, index).await? {
+        match Self::authenticate(auth, token, index.clone()).await? {
+            Some(filters)
+                if !Self::is_namespace_authorized(&filters, meilisearch, index).await? =>
+            {
+                Err(AuthenticationError::InvalidToken.into())
+            }
             Some(filters) => match data {
                 Some(data) => Ok(Self {
                     data,
@@ -62,6 +69,22 @@ impl<P, D> GuardedData<P, D> {
         }
     }
 
+    /// Checks that the index of the request belongs to one of the namespaces the key is
+    /// restricted to. An index that doesn't exist yet belongs to the default namespace.
+    async fn is_namespace_authorized(
+        filters: &AuthFilter,
+        meilisearch: Option<MeiliSearch>,
+        index: Option<String>,
+    ) -> Result<bool, ResponseError> {
+        match (&filters.namespaces, meilisearch, index) {
+            (Some(_), Some(meilisearch), Some(index)) => {
+                let namespace = meilisearch.index_namespace(index).await?;
+                Ok(filters.is_namespace_authorized(&namespace))
+            }
+            _ => Ok(true),
+        }
+    }
+
     async fn authenticate(
         auth: AuthController,
         token: String,
@@ -93,7 +116,12 @@ impl<P: Policy + 'static, D: 'static + Clone> FromRequest for GuardedData<P, D>
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
-        match req.app_data::<AuthController>().cloned() {
+        // a replica rejects the writes of the authenticated requests.
+        let read_only = P::is_write()
+            && req
+                .app_data::<MeiliSearch>()
+                .map_or(false, MeiliSearch::is_replica);
+        let authenticated: Self::Future = match req.app_data::<AuthController>().cloned() {
             Some(auth) => match req
                 .headers()
                 .get("Authorization")
@@ -108,6 +136,7 @@ impl<P: Policy + 'static, D: 'static + Clone> FromRequest for GuardedData<P, D>
                                 auth,
                                 token.to_string(),
                                 index.map(String::from),
+                                req.app_data::<MeiliSearch>().cloned(),
                                 req.app_data::<D>().cloned(),
                             )),
                             None => Box::pin(err(AuthenticationError::InvalidToken.into())),
@@ -120,12 +149,26 @@ impl<P: Policy + 'static, D: 'static + Clone> FromRequest for GuardedData<P, D>
                 None => Box::pin(Self::auth_token(auth, req.app_data::<D>().cloned())),
             },
             None => Box::pin(err(AuthenticationError::IrretrievableState.into())),
+        };
+
+        if read_only {
+            Box::pin(async move {
+                authenticated.await?;
+                Err(AuthenticationError::ReplicaReadOnly.into())
+            })
+        } else {
+            authenticated
         }
     }
 }
 
 pub trait Policy {
     fn authenticate(auth: AuthController, token: &str, index: Option<&str>) -> Option<AuthFilter>;
+
+    /// Whether the guarded route writes to the database.
+    fn is_write() -> bool {
+        false
+    }
 }
 
 pub mod policies {
@@ -197,6 +240,10 @@ pub mod policies {
 
             None
         }
+
+        fn is_write() -> bool {
+            Action::from_repr(A).map_or(false, |action| action.is_write())
+        }
     }
 
     impl<const A: u8> ActionPolicy<A> {
@@ -213,14 +260,16 @@ pub mod policies {
             let uid = extract_key_id(token)?;
             // check if parent key is authorized to do the action.
             if auth.is_key_authorized(uid, Action::Search, index).ok()? {
-                // Check if tenant token is valid.
-                let key = auth.generate_key(uid)?;
-                let data = decode::<Claims>(
-                    token,
-                    &DecodingKey::from_secret(key.as_bytes()),
-                    &tenant_token_validation(),
-                )
-                .ok()?;
+                // Check if tenant token is valid, it may be signed with the secret the key was
+                // rotated from.
+                let data = auth.accepted_keys(uid).ok()?.into_iter().find_map(|key| {
+                    decode::<Claims>(
+                        token,
+                        &DecodingKey::from_secret(key.as_bytes()),
+                        &tenant_token_validation(),
+                    )
+                    .ok()
+                })?;
 
                 // Check index access if an index restriction is provided.
                 if let Some(index) = index {
@@ -231,7 +280,8 @@ pub mod policies {
 
                 // Check if token is expired.
                 if let Some(exp) = data.claims.exp {
-                    if OffsetDateTime::now_utc().unix_timestamp() > exp {
+                    let leeway = auth.expiration_leeway().whole_seconds();
+                    if OffsetDateTime::now_utc().unix_timestamp() > exp + leeway {
                         return None;
                     }
                 }
@@ -128,6 +128,8 @@ gen_seq! { SeqFromRequestFut3; A B C }
 gen_seq! { SeqFromRequestFut4; A B C D }
 gen_seq! { SeqFromRequestFut5; A B C D E }
 gen_seq! { SeqFromRequestFut6; A B C D E F }
+gen_seq! { SeqFromRequestFut7; A B C D E F G }
+gen_seq! { SeqFromRequestFut8; A B C D E F G H }
 
 pin_project! {
     #[project = ExtractProj]
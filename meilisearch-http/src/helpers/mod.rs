@@ -1,3 +1,5 @@
 mod env;
+mod projection;
 
 pub use env::EnvSizer;
+pub use projection::{Projection, UNKNOWN_FIELDS_HEADER};
@@ -0,0 +1,181 @@
+//! Partial responses: a listing asked with `fields` only returns these fields of its results.
+//!
+//! The fields are dot-separated paths, like `details.indexedDocuments`, and a path through an
+//! array applies to each of its elements. The results are projected once serialized, so that a
+//! field hidden from the key, which is never serialized, can't be asked for: it is reported as
+//! unknown, like a field that doesn't exist.
+
+use std::collections::{BTreeMap, HashSet};
+
+use actix_web::HttpResponse;
+use meilisearch_error::{Code, ResponseError};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The header listing the fields asked for that none of the results has.
+pub const UNKNOWN_FIELDS_HEADER: &str = "X-Meili-Unknown-Fields";
+
+/// The fields kept under a value.
+#[derive(Debug, Default)]
+struct Fields {
+    /// The path asked for, if the whole value is kept.
+    path: Option<String>,
+    children: BTreeMap<String, Fields>,
+}
+
+impl Fields {
+    /// Returns the part of `value` made of the fields, `None` if it has none of them. The paths
+    /// of the fields it has are added to `found`.
+    fn project(&self, value: Value, found: &mut HashSet<String>) -> Option<Value> {
+        if let Some(ref path) = self.path {
+            found.insert(path.clone());
+            return Some(value);
+        }
+        match value {
+            Value::Object(object) => {
+                let projected: Map<_, _> = object
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        let value = self.children.get(&key)?.project(value, found)?;
+                        Some((key, value))
+                    })
+                    .collect();
+                (!projected.is_empty()).then(|| Value::Object(projected))
+            }
+            Value::Array(values) => {
+                let projected: Vec<_> = values
+                    .into_iter()
+                    .filter_map(|value| self.project(value, found))
+                    .collect();
+                (!projected.is_empty()).then(|| Value::Array(projected))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Projection {
+    fields: Fields,
+    /// The paths asked for, in their order.
+    paths: Vec<String>,
+}
+
+impl Projection {
+    pub fn new(paths: impl IntoIterator<Item = String>) -> Self {
+        let mut projection = Self::default();
+        for path in paths {
+            let path = path.trim();
+            if path.is_empty() || projection.paths.iter().any(|p| p == path) {
+                continue;
+            }
+            let mut fields = &mut projection.fields;
+            for key in path.split('.') {
+                fields = fields.children.entry(key.to_string()).or_default();
+            }
+            fields.path = Some(path.to_string());
+            projection.paths.push(path.to_string());
+        }
+        projection
+    }
+
+    /// Returns `value` reduced to the fields, along with the paths of the fields it has.
+    pub fn project(
+        &self,
+        value: &impl Serialize,
+    ) -> Result<(Value, HashSet<String>), ResponseError> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))?;
+        let mut found = HashSet::new();
+        let projected = self
+            .fields
+            .project(value, &mut found)
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        Ok((projected, found))
+    }
+
+    /// Builds the response of `listing`, whose `results` are reduced to the fields. The fields
+    /// that none of the results has are listed in the `X-Meili-Unknown-Fields` header.
+    pub fn listing_response(
+        &self,
+        listing: &impl Serialize,
+    ) -> Result<HttpResponse, ResponseError> {
+        let mut listing = serde_json::to_value(listing)
+            .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))?;
+        let mut found = HashSet::new();
+        if let Some(Value::Array(results)) = listing.get_mut("results") {
+            for result in results.iter_mut() {
+                let (projected, result_found) = self.project(result)?;
+                *result = projected;
+                found.extend(result_found);
+            }
+        }
+
+        let unknown: Vec<_> = self
+            .paths
+            .iter()
+            .filter(|path| !found.contains(*path))
+            .map(String::as_str)
+            .collect();
+        let mut response = HttpResponse::Ok();
+        if !unknown.is_empty() {
+            response.insert_header((UNKNOWN_FIELDS_HEADER, unknown.join(",")));
+        }
+        Ok(response.json(listing))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn project(paths: &[&str], value: Value) -> (Value, HashSet<String>) {
+        let projection = Projection::new(paths.iter().map(|path| path.to_string()));
+        projection.project(&value).unwrap()
+    }
+
+    #[test]
+    fn test_projection() {
+        let task = json!({
+            "uid": 1,
+            "status": "succeeded",
+            "details": { "receivedDocuments": 3, "indexedDocuments": 2 },
+            "events": [{ "type": "created", "at": 1 }, { "type": "succeeded", "at": 2 }],
+        });
+
+        let (projected, found) = project(
+            &[
+                "uid",
+                "details.indexedDocuments",
+                "events.type",
+                "missing",
+                "uid.x",
+            ],
+            task.clone(),
+        );
+        assert_eq!(
+            projected,
+            json!({
+                "uid": 1,
+                "details": { "indexedDocuments": 2 },
+                "events": [{ "type": "created" }, { "type": "succeeded" }],
+            })
+        );
+        let mut found: Vec<_> = found.into_iter().collect();
+        found.sort();
+        assert_eq!(found, ["details.indexedDocuments", "events.type", "uid"]);
+
+        // a field asked along with one of its children is kept whole.
+        let (projected, _) = project(&["details.indexedDocuments", "details"], task);
+        assert_eq!(
+            projected,
+            json!({ "details": { "receivedDocuments": 3, "indexedDocuments": 2 } })
+        );
+
+        let (projected, found) = project(&["missing"], json!({ "uid": 1 }));
+        assert_eq!(projected, json!({}));
+        assert!(found.is_empty());
+    }
+}
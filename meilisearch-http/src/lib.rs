@@ -2,12 +2,15 @@
 #[macro_use]
 pub mod error;
 pub mod analytics;
+pub mod audit;
 pub mod task;
 #[macro_use]
 pub mod extractors;
 pub mod helpers;
 pub mod option;
 pub mod routes;
+pub mod search_analytics;
+pub mod slow_query_log;
 
 use std::sync::{atomic::AtomicBool, Arc};
 use std::time::Duration;
@@ -15,18 +18,48 @@ use std::time::Duration;
 use crate::error::MeilisearchHttpError;
 use actix_web::error::JsonPayloadError;
 use analytics::Analytics;
+use audit::AuditLog;
 use error::PayloadError;
 use http::header::CONTENT_TYPE;
 pub use option::Opt;
+use routes::{TaskOriginConfig, TaskResponseConfig};
+use search_analytics::SearchAnalytics;
+use slow_query_log::SlowQueryLog;
 
 use actix_web::{web, HttpRequest};
 
 use extractors::payload::PayloadConfig;
 use meilisearch_auth::AuthController;
+use meilisearch_lib::calendar::{Calendar, DstPolicy, TimeZone};
+use meilisearch_lib::changefeed::ChangefeedConfig;
+use meilisearch_lib::index_controller::IndexTemplates;
+use meilisearch_lib::options::Sensitive;
+use meilisearch_lib::replica::{self, SnapshotSource};
 use meilisearch_lib::MeiliSearch;
 
 pub static AUTOBATCHING_ENABLED: AtomicBool = AtomicBool::new(false);
 
+pub fn setup_auth_controller(opt: &Opt) -> anyhow::Result<AuthController> {
+    let master_key = opt.master_key.clone().map(Sensitive::into_inner);
+    let mut auth_controller = AuthController::new(&opt.db_path, &master_key)?;
+    auth_controller
+        .set_expiration_leeway(time::Duration::seconds(
+            opt.key_expiration_leeway_sec.into(),
+        ))
+        .set_rotation_overlap(time::Duration::seconds(opt.key_rotation_overlap_sec.into()));
+
+    Ok(auth_controller)
+}
+
+/// Loads the latest snapshot of the primary into the data directory if the instance is a replica,
+/// so that it is served from the start, along with its API keys.
+pub async fn setup_replica(opt: &Opt) -> anyhow::Result<()> {
+    if let Some(ref source) = opt.replica_of {
+        replica::load_latest_snapshot(&opt.db_path, &SnapshotSource::parse(source)).await?;
+    }
+    Ok(())
+}
+
 pub fn setup_meilisearch(opt: &Opt) -> anyhow::Result<MeiliSearch> {
     let mut meilisearch = MeiliSearch::builder();
 
@@ -44,10 +77,44 @@ pub fn setup_meilisearch(opt: &Opt) -> anyhow::Result<MeiliSearch> {
         .set_ignore_snapshot_if_db_exists(opt.ignore_snapshot_if_db_exists)
         .set_snapshot_interval(Duration::from_secs(opt.snapshot_interval_sec))
         .set_snapshot_dir(opt.snapshot_dir.clone())
+        .set_verify_snapshot_after_write(opt.verify_after_write)
+        .set_io_budget(opt.snapshot_io_budget_mb.map(|mb| mb * 1024 * 1024))
+        .set_io_adaptive_latency_threshold(
+            opt.snapshot_io_adaptive_latency_ms
+                .map(Duration::from_millis),
+        )
         // dump
         .set_ignore_missing_dump(opt.ignore_missing_dump)
         .set_ignore_dump_if_db_exists(opt.ignore_dump_if_db_exists)
-        .set_dump_dst(opt.dumps_dir.clone());
+        .set_dump_dst(opt.dumps_dir.clone())
+        .set_dump_task_origins(opt.dump_task_origins)
+        .set_ignore_version_mismatch(opt.ignore_version_mismatch)
+        // warm-up
+        .set_enable_warmup(opt.enable_warmup)
+        // search queue
+        .set_max_queued_searches(opt.max_queued_searches)
+        .set_search_queue_max_wait(Duration::from_millis(opt.search_queue_max_wait_ms))
+        // points in time
+        .set_pit_ttl(Duration::from_secs(opt.pit_ttl_sec))
+        .set_max_open_pits_per_index(opt.max_open_pits_per_index)
+        // documents validation
+        .set_max_validation_payload_size(opt.max_validation_payload_size.get_bytes() as usize);
+
+    if let Some(max_concurrent_searches) = opt.max_concurrent_searches {
+        meilisearch.set_max_concurrent_searches(max_concurrent_searches);
+    }
+
+    if let Some(search_timeout_ms) = opt.search_timeout_ms {
+        meilisearch.set_search_timeout(Duration::from_millis(search_timeout_ms));
+    }
+
+    if let Some(payload_timeout_sec) = opt.payload_timeout_sec {
+        meilisearch.set_payload_timeout(Duration::from_secs(payload_timeout_sec));
+    }
+
+    // the last queries are only sampled if the user agreed to share them with the analytics.
+    #[cfg(all(not(debug_assertions), feature = "analytics"))]
+    meilisearch.set_warmup_sample_queries(opt.analytics());
 
     if let Some(ref path) = opt.import_snapshot {
         meilisearch.set_import_snapshot(path.clone());
@@ -61,6 +128,39 @@ pub fn setup_meilisearch(opt: &Opt) -> anyhow::Result<MeiliSearch> {
         meilisearch.set_schedule_snapshot();
     }
 
+    if !opt.snapshot_schedule.is_empty() || !opt.dump_schedule.is_empty() {
+        let tz: TimeZone = opt.schedule_timezone.parse()?;
+        let dst_policy: DstPolicy = opt.schedule_dst_policy.parse()?;
+        if !opt.snapshot_schedule.is_empty() {
+            let calendar = Calendar::new(&opt.snapshot_schedule, tz.clone(), dst_policy)?;
+            meilisearch.set_snapshot_calendar(calendar);
+        }
+        if !opt.dump_schedule.is_empty() {
+            meilisearch.set_dump_calendar(Calendar::new(&opt.dump_schedule, tz, dst_policy)?);
+        }
+    }
+
+    if opt.enable_changefeed {
+        meilisearch.set_changefeed(ChangefeedConfig {
+            max_file_size: opt.changefeed_max_size.get_bytes() as u64,
+            max_files: opt.changefeed_max_files,
+        });
+    }
+
+    if let Some(ref source) = opt.replica_of {
+        meilisearch.set_replica_of(
+            SnapshotSource::parse(source),
+            Duration::from_secs(opt.replica_poll_interval_sec),
+        );
+    }
+
+    if let Some(ref path) = opt.index_templates {
+        meilisearch.set_index_templates(IndexTemplates::from_path(path)?);
+    }
+
+    // the secrets of the options are redacted when they are serialized.
+    meilisearch.set_config(serde_json::to_value(opt)?);
+
     meilisearch.build(
         opt.db_path.clone(),
         opt.indexer_options.clone(),
@@ -76,10 +176,33 @@ pub fn configure_data(
     analytics: Arc<dyn Analytics>,
 ) {
     let http_payload_size_limit = opt.http_payload_size_limit.get_bytes() as usize;
+    let audit_log = AuditLog::from_opt(opt).unwrap_or_else(|e| {
+        log::error!(
+            "Could not open the audit log, audit logging is disabled: {}",
+            e
+        );
+        AuditLog::default()
+    });
+    let slow_query_log = SlowQueryLog::from_opt(opt).unwrap_or_else(|e| {
+        log::error!(
+            "Could not open the slow query log, slow queries are not recorded: {}",
+            e
+        );
+        SlowQueryLog::default()
+    });
     config
         .app_data(data)
         .app_data(auth)
         .app_data(web::Data::from(analytics))
+        .app_data(web::Data::new(audit_log))
+        .app_data(web::Data::new(slow_query_log))
+        .app_data(web::Data::new(SearchAnalytics::from_opt(opt)))
+        .app_data(TaskResponseConfig {
+            legacy_fields: opt.legacy_task_fields,
+        })
+        .app_data(TaskOriginConfig {
+            trusted_proxy_header: opt.trusted_proxy_header.clone(),
+        })
         .app_data(
             web::JsonConfig::default()
                 .content_type(|mime| mime == mime::APPLICATION_JSON)
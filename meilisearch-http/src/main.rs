@@ -1,4 +1,5 @@
 use std::env;
+use std::path::Path;
 use std::sync::Arc;
 
 use actix_web::HttpServer;
@@ -6,7 +7,10 @@ use clap::Parser;
 use meilisearch_auth::AuthController;
 use meilisearch_http::analytics;
 use meilisearch_http::analytics::Analytics;
-use meilisearch_http::{create_app, setup_meilisearch, Opt};
+use meilisearch_http::audit::AuditLog;
+use meilisearch_http::option::Command;
+use meilisearch_http::slow_query_log::SlowQueryLog;
+use meilisearch_http::{create_app, setup_auth_controller, setup_meilisearch, setup_replica, Opt};
 use meilisearch_lib::MeiliSearch;
 
 #[cfg(target_os = "linux")]
@@ -33,6 +37,19 @@ async fn main() -> anyhow::Result<()> {
 
     setup(&opt)?;
 
+    if let Some(ref path) = opt.verify_snapshot {
+        return verify_snapshot(&opt, path);
+    }
+
+    if let Some(Command::Reindex {
+        ref from_dump,
+        ref into,
+        threads,
+    }) = opt.command
+    {
+        return reindex(&opt, from_dump, into, threads);
+    }
+
     match opt.env.as_ref() {
         "production" => {
             if opt.master_key.is_none() {
@@ -45,9 +62,15 @@ async fn main() -> anyhow::Result<()> {
         _ => unreachable!(),
     }
 
+    setup_replica(&opt).await?;
     let meilisearch = setup_meilisearch(&opt)?;
 
-    let auth_controller = AuthController::new(&opt.db_path, &opt.master_key)?;
+    // The audit and slow query logs are opened before the http server starts so that a
+    // misconfiguration prevents the launch instead of silently disabling them.
+    AuditLog::from_opt(&opt)?;
+    SlowQueryLog::from_opt(&opt)?;
+
+    let auth_controller = setup_auth_controller(&opt)?;
 
     #[cfg(all(not(debug_assertions), feature = "analytics"))]
     let (analytics, user) = if !opt.no_analytics {
@@ -65,6 +88,49 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Verifies a snapshot, and prints the report of each of its files.
+fn verify_snapshot(opt: &Opt, path: &Path) -> anyhow::Result<()> {
+    let report = meilisearch_lib::verify_snapshot(
+        path,
+        opt.max_task_db_size.get_bytes() as usize,
+        opt.max_index_size.get_bytes() as usize,
+    )?;
+    print!("{}", report);
+
+    if report.is_valid() {
+        eprintln!("The snapshot {:?} is valid.", path);
+        Ok(())
+    } else {
+        anyhow::bail!("The snapshot {:?} is invalid.", path)
+    }
+}
+
+/// Builds the data directory `into` from the dump `from_dump`, and prints the outcome of each of
+/// its indexes.
+fn reindex(opt: &Opt, from_dump: &Path, into: &Path, threads: usize) -> anyhow::Result<()> {
+    let report = meilisearch_lib::reindex_dump(
+        from_dump,
+        into,
+        threads,
+        opt.max_index_size.get_bytes() as usize,
+        opt.max_task_db_size.get_bytes() as usize,
+        &opt.indexer_options,
+        |index| println!("{}", index),
+    )?;
+    print!("{}", report);
+
+    if report.is_success() {
+        eprintln!("The data directory {:?} is ready.", into);
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "Some indexes of the dump {:?} could not be built, they are missing from {:?}.",
+            from_dump,
+            into
+        )
+    }
+}
+
 async fn run_http(
     data: MeiliSearch,
     auth_controller: AuthController,
@@ -4,8 +4,8 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use byte_unit::Byte;
-use clap::Parser;
-use meilisearch_lib::options::{IndexerOpts, SchedulerConfig};
+use clap::{Parser, Subcommand};
+use meilisearch_lib::options::{IndexerOpts, SchedulerConfig, Sensitive};
 use rustls::{
     server::{
         AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient,
@@ -30,9 +30,18 @@ pub struct Opt {
     pub http_addr: String,
 
     /// The master key allowing you to do everything on the server.
-    #[serde(skip)]
     #[clap(long, env = "MEILI_MASTER_KEY")]
-    pub master_key: Option<String>,
+    pub master_key: Option<Sensitive<String>>,
+
+    /// The time, in seconds, an API key or a tenant token is still accepted after its expiration
+    /// date, to tolerate the clock skew between the instance and the clients.
+    #[clap(long, env = "MEILI_KEY_EXPIRATION_LEEWAY_SEC", default_value = "0")]
+    pub key_expiration_leeway_sec: u32,
+
+    /// The time, in seconds, the previous secret of a rotated API key is still accepted, so that
+    /// the clients can switch to the new one.
+    #[clap(long, env = "MEILI_KEY_ROTATION_OVERLAP_SEC", default_value = "3600")]
+    pub key_rotation_overlap_sec: u32,
 
     /// This environment variable must be set to `production` if you are running in production.
     /// If the server is running in development mode more logs will be displayed,
@@ -126,6 +135,66 @@ pub struct Opt {
     #[clap(long, env = "MEILI_SNAPSHOT_INTERVAL_SEC", default_value = "86400")] // 24h
     pub snapshot_interval_sec: u64,
 
+    /// Schedule the snapshots with cron expressions, like `0 3 * * *` for every day at 03:00,
+    /// separated by `;`. They are evaluated in the `schedule-timezone`.
+    #[clap(
+        long,
+        env = "MEILI_SNAPSHOT_SCHEDULE",
+        use_value_delimiter = true,
+        value_delimiter = ';'
+    )]
+    pub snapshot_schedule: Vec<String>,
+
+    /// Re-read and verify each snapshot after it is written. An invalid snapshot fails instead of
+    /// replacing the previous one.
+    #[clap(long, env = "MEILI_VERIFY_AFTER_WRITE")]
+    pub verify_after_write: bool,
+
+    /// The IO budget of the snapshots and dumps writers, in MiB per second. A snapshot paces its
+    /// writes so that it doesn't compete with the searches for the disk. Unlimited by default.
+    #[clap(long, env = "MEILI_SNAPSHOT_IO_BUDGET_MB")]
+    pub snapshot_io_budget_mb: Option<u64>,
+
+    /// Cuts the IO budget of the snapshots and dumps writers to a quarter while the recent search
+    /// latency exceeds this threshold, in milliseconds.
+    #[clap(
+        long,
+        env = "MEILI_SNAPSHOT_IO_ADAPTIVE_LATENCY_MS",
+        requires = "snapshot-io-budget-mb"
+    )]
+    pub snapshot_io_adaptive_latency_ms: Option<u64>,
+
+    /// Serve the snapshots of a primary instance as a read-only replica. Either the path of the
+    /// snapshot file, or its `http` or `https` url. The replica rejects every write with a
+    /// `replica_read_only` error, and loads the new snapshots of its primary as they are created.
+    #[clap(
+        long,
+        env = "MEILI_REPLICA_OF",
+        conflicts_with_all = &[
+            "import-snapshot",
+            "import-dump",
+            "schedule-snapshot",
+            "snapshot-schedule",
+            "dump-schedule"
+        ]
+    )]
+    pub replica_of: Option<String>,
+
+    /// Defines the time interval, in seconds, between each poll of the snapshot of the primary.
+    #[clap(
+        long,
+        env = "MEILI_REPLICA_POLL_INTERVAL_SEC",
+        requires = "replica-of",
+        default_value = "60"
+    )]
+    pub replica_poll_interval_sec: u64,
+
+    /// Verify the snapshot at the given path and exit, instead of starting the server. The
+    /// process exits with an error if the snapshot is invalid.
+    #[serde(skip)]
+    #[clap(long, conflicts_with = "import-snapshot")]
+    pub verify_snapshot: Option<PathBuf>,
+
     /// Import a dump from the specified path, must be a `.dump` file.
     #[clap(long, conflicts_with = "import-snapshot")]
     pub import_dump: Option<PathBuf>,
@@ -142,10 +211,178 @@ pub struct Opt {
     #[clap(long, env = "MEILI_DUMPS_DIR", default_value = "dumps/")]
     pub dumps_dir: PathBuf,
 
+    /// Schedule the dumps with cron expressions, like `0 3 * * sun` for every Sunday at 03:00,
+    /// separated by `;`. They are evaluated in the `schedule-timezone`.
+    #[clap(
+        long,
+        env = "MEILI_DUMP_SCHEDULE",
+        use_value_delimiter = true,
+        value_delimiter = ';'
+    )]
+    pub dump_schedule: Vec<String>,
+
+    /// The time zone of the snapshot and dump schedules, as a POSIX TZ string like
+    /// `CET-1CEST,M3.5.0,M10.5.0/3`.
+    #[clap(long, env = "MEILI_SCHEDULE_TIMEZONE", default_value = "UTC0")]
+    pub schedule_timezone: String,
+
+    /// What the schedules do with the local times skipped or repeated when the clocks change.
+    /// With `skip`, the skipped times don't fire and the repeated ones fire once. With
+    /// `double-fire`, the skipped times fire when the clocks are turned forward and the repeated
+    /// ones fire twice.
+    #[clap(
+        long,
+        env = "MEILI_SCHEDULE_DST_POLICY",
+        default_value = "skip",
+        possible_values = &["skip", "double-fire"]
+    )]
+    pub schedule_dst_policy: String,
+
+    /// Warm up the indexes on startup and after each settings update, by running their
+    /// `warmupQueries` or, when analytics are enabled, a sample of their last queries.
+    #[clap(long, env = "MEILI_ENABLE_WARMUP")]
+    pub enable_warmup: bool,
+
+    /// The maximum number of searches executed at the same time. Defaults to the number of cores.
+    #[clap(long, env = "MEILI_MAX_CONCURRENT_SEARCHES")]
+    pub max_concurrent_searches: Option<usize>,
+
+    /// The maximum number of searches waiting to be executed. Beyond it, the searches are refused
+    /// with a `503 Service Unavailable`.
+    #[clap(long, env = "MEILI_MAX_QUEUED_SEARCHES", default_value = "1000")]
+    pub max_queued_searches: usize,
+
+    /// The maximum time, in milliseconds, a search waits to be executed before being refused
+    /// with a `503 Service Unavailable`.
+    #[clap(long, env = "MEILI_SEARCH_QUEUE_MAX_WAIT_MS", default_value = "5000")]
+    pub search_queue_max_wait_ms: u64,
+
+    /// The time, in seconds, a point in time opened by a search with `pit` can be searched with
+    /// its token.
+    #[clap(long, env = "MEILI_PIT_TTL_SEC", default_value = "60")]
+    pub pit_ttl_sec: u64,
+
+    /// The maximum number of points in time open on an index at once. Opening one more closes the
+    /// oldest one.
+    #[clap(long, env = "MEILI_MAX_OPEN_PITS_PER_INDEX", default_value = "4")]
+    pub max_open_pits_per_index: usize,
+
+    /// The maximum time, in milliseconds, a search can be executed before being answered with a
+    /// `504 Gateway Timeout`. Unlimited by default.
+    #[clap(long, env = "MEILI_SEARCH_TIMEOUT_MS")]
+    pub search_timeout_ms: Option<u64>,
+
+    /// The maximum time, in seconds, to receive the payload of a document addition before
+    /// answering with a `408 Request Timeout`. Unlimited by default.
+    #[clap(long, env = "MEILI_PAYLOAD_TIMEOUT_SEC")]
+    pub payload_timeout_sec: Option<u64>,
+
+    /// The maximum size, in bytes, of a payload sent to the documents validation route, which is
+    /// converted in whole before being answered.
+    #[clap(
+        long,
+        env = "MEILI_MAX_VALIDATION_PAYLOAD_SIZE",
+        default_value = "10 MiB"
+    )]
+    pub max_validation_payload_size: Byte,
+
     /// Set the log level
     #[clap(long, env = "MEILI_LOG_LEVEL", default_value = "info")]
     pub log_level: String,
 
+    /// Defines the path of the audit log. When set, every mutating request, except searches,
+    /// is recorded in this file as a JSON line.
+    #[clap(long, env = "MEILI_AUDIT_LOG_PATH")]
+    pub audit_log_path: Option<PathBuf>,
+
+    /// The size, in bytes, over which the audit log is rotated.
+    #[clap(long, env = "MEILI_AUDIT_LOG_MAX_SIZE", default_value = "100 MiB")]
+    pub audit_log_max_size: Byte,
+
+    /// The number of rotated audit log files to keep.
+    #[clap(long, env = "MEILI_AUDIT_LOG_MAX_FILES", default_value = "5")]
+    pub audit_log_max_files: usize,
+
+    /// Defines the path of the slow query log. When set, every search slower than
+    /// `slow_query_log_threshold_ms` is recorded in this file as a JSON line.
+    #[clap(long, env = "MEILI_SLOW_QUERY_LOG_PATH")]
+    pub slow_query_log_path: Option<PathBuf>,
+
+    /// The duration, in milliseconds, over which a search is recorded in the slow query log. It
+    /// can be changed at runtime with `PATCH /config`.
+    #[clap(
+        long,
+        env = "MEILI_SLOW_QUERY_LOG_THRESHOLD_MS",
+        default_value = "1000"
+    )]
+    pub slow_query_log_threshold_ms: u64,
+
+    /// The size, in bytes, over which the slow query log is rotated.
+    #[clap(long, env = "MEILI_SLOW_QUERY_LOG_MAX_SIZE", default_value = "100 MiB")]
+    pub slow_query_log_max_size: Byte,
+
+    /// The number of rotated slow query log files to keep.
+    #[clap(long, env = "MEILI_SLOW_QUERY_LOG_MAX_FILES", default_value = "5")]
+    pub slow_query_log_max_files: usize,
+
+    /// Records the documents changed by each applied batch in the changefeed of its index, read
+    /// with the `GET /indexes/{index_uid}/changes` route.
+    #[clap(long, env = "MEILI_ENABLE_CHANGEFEED")]
+    pub enable_changefeed: bool,
+
+    /// The size, in bytes, over which the changefeed of an index is rotated.
+    #[clap(
+        long,
+        env = "MEILI_CHANGEFEED_MAX_SIZE",
+        default_value = "100 MiB",
+        requires = "enable-changefeed"
+    )]
+    pub changefeed_max_size: Byte,
+
+    /// The number of rotated changefeed files to keep for each index. The changes of the older
+    /// files are lost.
+    #[clap(
+        long,
+        env = "MEILI_CHANGEFEED_MAX_FILES",
+        default_value = "5",
+        requires = "enable-changefeed"
+    )]
+    pub changefeed_max_files: usize,
+
+    /// Aggregates the searches of each index in memory, to list their most frequent queries and
+    /// their latency with the `GET /indexes/{index_uid}/analytics/searches` route. The analytics
+    /// only cover the last day, and are lost on restart.
+    #[clap(long, env = "MEILI_SEARCH_ANALYTICS")]
+    pub search_analytics: bool,
+
+    /// Also returns the deprecated `updateId` and `uid` fields, holding the task uid, in the
+    /// responses of the routes registering a task. Only meant to give the clients time to move
+    /// to the `taskUid` field.
+    #[clap(long, env = "MEILI_LEGACY_TASK_FIELDS")]
+    pub legacy_task_fields: bool,
+
+    /// The header holding the IP address of the client when Meilisearch is behind a reverse
+    /// proxy, like `X-Forwarded-For`. The first address of the header is recorded as the IP
+    /// address of the client in the origin of the tasks. Without it, the address of the peer is
+    /// recorded. Only set it if the proxy overwrites the header sent by the clients.
+    #[clap(long, env = "MEILI_TRUSTED_PROXY_HEADER")]
+    pub trusted_proxy_header: Option<String>,
+
+    /// Includes the origins of the tasks in the dumps. They hold the IP addresses and user agents
+    /// of the clients that registered the tasks.
+    #[clap(long, env = "MEILI_DUMP_TASK_ORIGINS")]
+    pub dump_task_origins: bool,
+
+    /// Processes the enqueued tasks registered by a newer version of Meilisearch, whose contents
+    /// this version may misinterpret. Without it, Meilisearch refuses to start when there are some.
+    #[clap(long, env = "MEILI_IGNORE_VERSION_MISMATCH")]
+    pub ignore_version_mismatch: bool,
+
+    /// A JSON file declaring the index templates: the settings applied to the indexes whose uid
+    /// matches a pattern when they are created.
+    #[clap(long, env = "MEILI_INDEX_TEMPLATES", parse(from_os_str))]
+    pub index_templates: Option<PathBuf>,
+
     #[serde(flatten)]
     #[clap(flatten)]
     pub indexer_options: IndexerOpts,
@@ -153,6 +390,30 @@ pub struct Opt {
     #[serde(flatten)]
     #[clap(flatten)]
     pub scheduler_options: SchedulerConfig,
+
+    /// The command to run instead of starting the server.
+    #[serde(skip)]
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Build a data directory from a dump, rebuilding its indexes in parallel, and exit without
+    /// serving any request. The server can then be started on the data directory.
+    Reindex {
+        /// The dump to rebuild the indexes of, a `.dump` file.
+        #[clap(long)]
+        from_dump: PathBuf,
+
+        /// The data directory to create, which must not exist or be empty.
+        #[clap(long)]
+        into: PathBuf,
+
+        /// The number of indexes built at the same time. They share the indexing memory.
+        #[clap(long)]
+        threads: usize,
+    },
 }
 
 impl Opt {
@@ -0,0 +1,172 @@
+//! The support bundle gathers in a single archive the state of the instance that is needed to
+//! investigate an issue, like a stuck indexation.
+//!
+//! The bundle describes the whole instance, whatever the indexes of the key requesting it. It
+//! never contains the content of the documents nor any secret: the configuration is already
+//! redacted, the error messages of the tasks and of the batches, which can quote documents, are
+//! left out, and so are the urls the documents are fetched from, which can embed credentials.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::debug;
+use meilisearch_auth::SearchRules;
+use meilisearch_error::{Code, ResponseError};
+use meilisearch_lib::options::REDACTED;
+use meilisearch_lib::tasks::TaskOrder;
+use meilisearch_lib::MeiliSearch;
+use serde::Serialize;
+use serde_json::Value;
+use time::OffsetDateTime;
+
+use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::slow_query_log::SlowQueryLog;
+use crate::task::{BatchView, TaskView};
+
+use super::{effective_config, stats, version};
+
+/// The number of most recent tasks in the bundle.
+const BUNDLE_TASKS: usize = 1000;
+/// The number of most recent batches in the bundle.
+const BUNDLE_BATCHES: usize = 100;
+/// The number of most recent slow queries in the bundle.
+const BUNDLE_SLOW_QUERIES: usize = 1000;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/support-bundle").route(web::post().to(create_support_bundle)));
+}
+
+async fn create_support_bundle(
+    meilisearch: GuardedData<ActionPolicy<{ actions::SUPPORT_BUNDLE }>, MeiliSearch>,
+    slow_query_log: web::Data<SlowQueryLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let mut files = vec![
+        ("version.json", to_json(&version())?),
+        (
+            "config.json",
+            to_json(&effective_config(&meilisearch, &slow_query_log))?,
+        ),
+        (
+            "stats.json",
+            to_json(&stats(&meilisearch, &SearchRules::default()).await?)?,
+        ),
+        ("queue.json", to_json(&meilisearch.queue_snapshot().await)?),
+    ];
+
+    let tasks = meilisearch
+        .list_tasks(None, Some(BUNDLE_TASKS), None, TaskOrder::Descending)
+        .await?
+        .into_iter()
+        .map(|task| redact_task(serde_json::to_value(TaskView::from(task))?));
+    files.push(("tasks.ndjson", to_ndjson(tasks)?));
+
+    let batches = meilisearch
+        .list_batch_results(None, Some(BUNDLE_BATCHES), None)
+        .await?
+        .into_iter()
+        .map(|batch| redact_batch(serde_json::to_value(BatchView::from(batch))?));
+    files.push(("batches.ndjson", to_ndjson(batches)?));
+
+    let slow_query_log_path = slow_query_log.path().map(Path::to_path_buf);
+    let archive = web::block(move || {
+        if let Some(path) = slow_query_log_path {
+            files.push((
+                "slow-queries.ndjson",
+                last_lines(&path, BUNDLE_SLOW_QUERIES)?,
+            ));
+        }
+        archive(&files)
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(internal_error)?;
+
+    debug!("returns a support bundle of {} bytes", archive.len());
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "meilisearch-support-bundle-{}.tar.gz",
+                now
+            ))],
+        })
+        .body(archive))
+}
+
+fn internal_error(error: impl std::fmt::Display) -> ResponseError {
+    ResponseError::from_msg(error.to_string(), Code::Internal)
+}
+
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>, ResponseError> {
+    serde_json::to_vec_pretty(value).map_err(internal_error)
+}
+
+fn to_ndjson(
+    values: impl Iterator<Item = serde_json::Result<Value>>,
+) -> Result<Vec<u8>, ResponseError> {
+    let mut ndjson = Vec::new();
+    for value in values {
+        serde_json::to_writer(&mut ndjson, &value.map_err(internal_error)?)
+            .map_err(internal_error)?;
+        ndjson.push(b'\n');
+    }
+    Ok(ndjson)
+}
+
+/// Removes the parts of a task that can contain documents or secrets.
+fn redact_task(mut task: Value) -> serde_json::Result<Value> {
+    if let Some(error) = task.get_mut("error").and_then(Value::as_object_mut) {
+        error.remove("message");
+    }
+    if let Some(url) = task.pointer_mut("/details/url") {
+        *url = REDACTED.into();
+    }
+    Ok(task)
+}
+
+/// Removes the parts of a batch that can contain documents.
+fn redact_batch(mut batch: Value) -> serde_json::Result<Value> {
+    if let Some(error) = batch.get_mut("error").and_then(Value::as_object_mut) {
+        error.remove("message");
+    }
+    Ok(batch)
+}
+
+/// Returns the `count` last lines of the file at `path`, nothing if it doesn't exist.
+fn last_lines(path: &Path, count: usize) -> io::Result<Vec<u8>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let lines: Vec<_> = content.lines().collect();
+    let mut last = lines[lines.len().saturating_sub(count)..].join("\n");
+    if !last.is_empty() {
+        last.push('\n');
+    }
+    Ok(last.into_bytes())
+}
+
+/// Returns a gzipped tarball made of `files`.
+fn archive(files: &[(&str, Vec<u8>)]) -> io::Result<Vec<u8>> {
+    let mtime = OffsetDateTime::now_utc().unix_timestamp() as u64;
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, name, content.as_slice())?;
+    }
+    let mut encoder = builder.into_inner()?;
+    encoder.flush()?;
+    encoder.finish()
+}
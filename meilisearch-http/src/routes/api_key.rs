@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::str;
 
 use actix_web::{web, HttpRequest, HttpResponse};
@@ -8,7 +9,9 @@ use uuid::Uuid;
 
 use meilisearch_auth::{error::AuthControllerError, Action, AuthController, Key};
 use meilisearch_error::{Code, ResponseError};
+use meilisearch_lib::MeiliSearch;
 
+use crate::audit::AuditLog;
 use crate::extractors::{
     authentication::{policies::*, GuardedData},
     sequential_extractor::SeqHandler,
@@ -26,25 +29,73 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route(web::get().to(SeqHandler(get_api_key)))
             .route(web::patch().to(SeqHandler(patch_api_key)))
             .route(web::delete().to(SeqHandler(delete_api_key))),
-    );
+    )
+    .service(web::resource("/{key}/rotate").route(web::post().to(SeqHandler(rotate_api_key))));
 }
 
 pub async fn create_api_key(
     auth_controller: GuardedData<ActionPolicy<{ actions::KEYS_CREATE }>, AuthController>,
     body: web::Json<Value>,
-    _req: HttpRequest,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let v = body.into_inner();
+    if let Some(meilisearch) = req.app_data::<MeiliSearch>() {
+        check_search_filters(meilisearch, &v).await?;
+    }
+
+    let filters = auth_controller.filters().clone();
     let res = tokio::task::spawn_blocking(move || -> Result<_, AuthControllerError> {
         let key = auth_controller.create_key(v)?;
         Ok(KeyView::from_key(key, &auth_controller))
     })
     .await
     .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))??;
+    audit.record(&req, &filters, None, None);
 
     Ok(HttpResponse::Created().json(res))
 }
 
+/// Checks the search filters of a new key against the filterable attributes of the existing
+/// indexes they apply to. The filters of the indexes that don't exist yet are checked when the
+/// key is used.
+async fn check_search_filters(meilisearch: &MeiliSearch, key: &Value) -> Result<(), ResponseError> {
+    let search_filters = match key.get("searchFilters") {
+        Some(Value::Object(search_filters)) => search_filters,
+        // a malformed value is rejected when the key is created.
+        _ => return Ok(()),
+    };
+    let key_indexes: Vec<String> = key
+        .get("indexes")
+        .and_then(|indexes| serde_json::from_value(indexes.clone()).ok())
+        .unwrap_or_default();
+
+    for index in meilisearch.list_indexes().await? {
+        if !key_indexes.iter().any(|i| i == "*" || *i == index.uid) {
+            continue;
+        }
+
+        for pattern in [index.uid.as_str(), "*"] {
+            if let Some(filter) = search_filters.get(pattern) {
+                if let Err(e) = meilisearch
+                    .check_filter(index.uid.clone(), filter.clone())
+                    .await
+                {
+                    return Err(ResponseError::from_msg(
+                        format!(
+                            "`searchFilters` field value `{}` is invalid on index `{}`: {}",
+                            filter, index.uid, e
+                        ),
+                        Code::InvalidApiKeySearchFilters,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn list_api_keys(
     auth_controller: GuardedData<ActionPolicy<{ actions::KEYS_GET }>, AuthController>,
     paginate: web::Query<Pagination>,
@@ -87,9 +138,12 @@ pub async fn patch_api_key(
     auth_controller: GuardedData<ActionPolicy<{ actions::KEYS_UPDATE }>, AuthController>,
     body: web::Json<Value>,
     path: web::Path<AuthParam>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let key = path.into_inner().key;
     let body = body.into_inner();
+    let filters = auth_controller.filters().clone();
     let res = tokio::task::spawn_blocking(move || -> Result<_, AuthControllerError> {
         let uid =
             Uuid::parse_str(&key).or_else(|_| auth_controller.get_uid_from_encoded_key(&key))?;
@@ -99,6 +153,7 @@ pub async fn patch_api_key(
     })
     .await
     .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))??;
+    audit.record(&req, &filters, None, None);
 
     Ok(HttpResponse::Ok().json(res))
 }
@@ -106,8 +161,11 @@ pub async fn patch_api_key(
 pub async fn delete_api_key(
     auth_controller: GuardedData<ActionPolicy<{ actions::KEYS_DELETE }>, AuthController>,
     path: web::Path<AuthParam>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let key = path.into_inner().key;
+    let filters = auth_controller.filters().clone();
     tokio::task::spawn_blocking(move || {
         let uid =
             Uuid::parse_str(&key).or_else(|_| auth_controller.get_uid_from_encoded_key(&key))?;
@@ -115,10 +173,35 @@ pub async fn delete_api_key(
     })
     .await
     .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))??;
+    audit.record(&req, &filters, None, None);
 
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Gives a key a new secret, keeping its uid, actions, indexes and expiration date. The previous
+/// secret is still accepted during the rotation overlap, so that the clients can switch.
+pub async fn rotate_api_key(
+    auth_controller: GuardedData<ActionPolicy<{ actions::KEYS_UPDATE }>, AuthController>,
+    path: web::Path<AuthParam>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let key = path.into_inner().key;
+    let filters = auth_controller.filters().clone();
+    let res = tokio::task::spawn_blocking(move || -> Result<_, AuthControllerError> {
+        let uid =
+            Uuid::parse_str(&key).or_else(|_| auth_controller.get_uid_from_encoded_key(&key))?;
+        let key = auth_controller.rotate_key(uid)?;
+
+        Ok(KeyView::from_key(key, &auth_controller))
+    })
+    .await
+    .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))??;
+    audit.record(&req, &filters, None, None);
+
+    Ok(HttpResponse::Ok().json(res))
+}
+
 #[derive(Deserialize)]
 pub struct AuthParam {
     key: String,
@@ -133,17 +216,32 @@ struct KeyView {
     uid: Uuid,
     actions: Vec<Action>,
     indexes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespaces: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    search_filters: BTreeMap<String, Value>,
     #[serde(serialize_with = "time::serde::rfc3339::option::serialize")]
     expires_at: Option<OffsetDateTime>,
     #[serde(serialize_with = "time::serde::rfc3339::serialize")]
     created_at: OffsetDateTime,
     #[serde(serialize_with = "time::serde::rfc3339::serialize")]
     updated_at: OffsetDateTime,
+    /// Until when the secret the key was rotated from is still accepted.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "time::serde::rfc3339::option::serialize"
+    )]
+    previous_key_valid_until: Option<OffsetDateTime>,
 }
 
 impl KeyView {
     fn from_key(key: Key, auth: &AuthController) -> Self {
-        let generated_key = auth.generate_key(key.uid).unwrap_or_default();
+        let generated_key = auth.generate_key(&key).unwrap_or_default();
+        let now = OffsetDateTime::now_utc();
+        let previous_key_valid_until = key
+            .previous_secret
+            .map(|previous| previous.valid_until)
+            .filter(|valid_until| now < *valid_until);
 
         KeyView {
             name: key.name,
@@ -152,9 +250,12 @@ impl KeyView {
             uid: key.uid,
             actions: key.actions,
             indexes: key.indexes,
+            namespaces: key.namespaces,
+            search_filters: key.search_filters,
             expires_at: key.expires_at,
             created_at: key.created_at,
             updated_at: key.updated_at,
+            previous_key_valid_until,
         }
     }
 }
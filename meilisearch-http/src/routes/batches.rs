@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+
+use actix_web::{web, HttpResponse};
+use meilisearch_auth::SearchRules;
+use meilisearch_error::ResponseError;
+use meilisearch_lib::tasks::batch::BatchId;
+use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
+
+use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::extractors::sequential_extractor::SeqHandler;
+use crate::task::{BatchListView, BatchView};
+
+const DEFAULT_LIMIT: fn() -> usize = || 20;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::get().to(SeqHandler(get_batches))))
+        .service(web::resource("/{batch_id}").route(web::get().to(SeqHandler(get_batch))));
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BatchesQuery {
+    #[serde(default = "DEFAULT_LIMIT")]
+    limit: usize,
+    from: Option<BatchId>,
+}
+
+/// The indexes whose batches can be seen with these search rules, or `None` if all the batches
+/// can be seen.
+fn authorized_indexes(search_rules: &SearchRules) -> Option<HashSet<String>> {
+    if search_rules.is_index_authorized("*") {
+        None
+    } else {
+        Some(
+            search_rules
+                .clone()
+                .into_iter()
+                .map(|(index, _policy)| index)
+                .collect(),
+        )
+    }
+}
+
+async fn get_batches(
+    meilisearch: GuardedData<ActionPolicy<{ actions::TASKS_GET }>, MeiliSearch>,
+    params: web::Query<BatchesQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let BatchesQuery { limit, from } = params.into_inner();
+    let indexes = authorized_indexes(&meilisearch.filters().search_rules);
+
+    // We +1 just to know if there is more after this "page" or not.
+    let limit = limit.saturating_add(1);
+
+    let mut results: Vec<_> = meilisearch
+        .list_batch_results(indexes, Some(limit), from)
+        .await?
+        .into_iter()
+        .map(BatchView::from)
+        .collect();
+
+    let next = if results.len() == limit {
+        results.pop().map(|b| b.uid)
+    } else {
+        None
+    };
+
+    let from = results.first().map(|b| b.uid);
+
+    let batches = BatchListView {
+        results,
+        limit: limit.saturating_sub(1),
+        from,
+        next,
+    };
+
+    Ok(HttpResponse::Ok().json(batches))
+}
+
+async fn get_batch(
+    meilisearch: GuardedData<ActionPolicy<{ actions::TASKS_GET }>, MeiliSearch>,
+    batch_id: web::Path<BatchId>,
+) -> Result<HttpResponse, ResponseError> {
+    let indexes = authorized_indexes(&meilisearch.filters().search_rules);
+
+    let batch: BatchView = meilisearch
+        .get_batch_result(batch_id.into_inner(), indexes)
+        .await?
+        .into();
+
+    Ok(HttpResponse::Ok().json(batch))
+}
@@ -1,27 +1,44 @@
 use actix_web::{web, HttpRequest, HttpResponse};
-use log::debug;
 use meilisearch_error::ResponseError;
 use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
 use serde_json::json;
 
 use crate::analytics::Analytics;
+use crate::audit::AuditLog;
 use crate::extractors::authentication::{policies::*, GuardedData};
 use crate::extractors::sequential_extractor::SeqHandler;
-use crate::task::SummarizedTaskView;
+use crate::routes::{task_accepted, task_origin};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("").route(web::post().to(SeqHandler(create_dump))));
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DumpQuery {
+    /// Whether the tasks still waiting to be processed are dumped along with their documents, so
+    /// that the instance the dump is imported in processes them.
+    #[serde(default)]
+    include_pending_tasks: bool,
+}
+
 pub async fn create_dump(
     meilisearch: GuardedData<ActionPolicy<{ actions::DUMPS_CREATE }>, MeiliSearch>,
+    params: web::Query<DumpQuery>,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
-    analytics.publish("Dump Created".to_string(), json!({}), Some(&req));
-
-    let res: SummarizedTaskView = meilisearch.register_dump_task().await?.into();
+    analytics.publish(
+        "Dump Created".to_string(),
+        json!({ "include_pending_tasks": params.include_pending_tasks }),
+        Some(&req),
+    );
 
-    debug!("returns: {:?}", res);
-    Ok(HttpResponse::Accepted().json(res))
+    let origin = task_origin(&req, meilisearch.filters());
+    let task = meilisearch
+        .register_dump_task(Some(origin), params.include_pending_tasks)
+        .await?;
+    Ok(task_accepted(&req, meilisearch.filters(), &audit, task))
 }
@@ -0,0 +1,70 @@
+use actix_web::{web, HttpResponse};
+use log::debug;
+use meilisearch_error::ResponseError;
+use meilisearch_lib::index_controller::{FederatedQuery, DEFAULT_FEDERATED_SEARCH_LIMIT};
+use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::extractors::authentication::{policies::*, AuthenticationError, GuardedData};
+use crate::extractors::sequential_extractor::SeqHandler;
+use crate::routes::indexes::search::{
+    add_search_rules, check_ranking_rules_override, search_query,
+};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::post().to(SeqHandler(federated_search))));
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FederatedSearchRequest {
+    queries: Vec<FederatedQueryRequest>,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "DEFAULT_FEDERATED_SEARCH_LIMIT")]
+    limit: usize,
+}
+
+/// A query of a federated search: the parameters of a search request, and the index to search.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FederatedQueryRequest {
+    index_uid: String,
+    #[serde(flatten)]
+    params: Map<String, Value>,
+}
+
+pub async fn federated_search(
+    meilisearch: GuardedData<ActionPolicy<{ actions::SEARCH }>, MeiliSearch>,
+    params: web::Json<FederatedSearchRequest>,
+) -> Result<HttpResponse, ResponseError> {
+    let FederatedSearchRequest {
+        queries,
+        offset,
+        limit,
+    } = params.into_inner();
+    debug!("federated search called with {} queries", queries.len());
+
+    let mut federated_queries = Vec::with_capacity(queries.len());
+    for FederatedQueryRequest { index_uid, params } in queries {
+        // the key must be allowed to search every index of the request.
+        let search_rules = meilisearch
+            .filters()
+            .search_rules
+            .get_index_search_rules(&index_uid)
+            .ok_or(AuthenticationError::InvalidToken)?;
+
+        let mut query = search_query(&meilisearch, &index_uid, params).await?;
+        check_ranking_rules_override(&query, meilisearch.filters())?;
+        add_search_rules(&mut query, search_rules)?;
+        federated_queries.push(FederatedQuery { index_uid, query });
+    }
+
+    let result = meilisearch
+        .federated_search(federated_queries, offset, limit)
+        .await?;
+
+    debug!("returns: {:?}", result);
+    Ok(HttpResponse::Ok().json(result))
+}
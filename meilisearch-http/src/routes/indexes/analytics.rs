@@ -0,0 +1,43 @@
+use actix_web::{web, HttpResponse};
+use log::debug;
+use meilisearch_error::ResponseError;
+use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
+
+use crate::error::MeilisearchHttpError;
+use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::extractors::sequential_extractor::SeqHandler;
+use crate::search_analytics::SearchAnalytics;
+
+/// The number of queries listed by default in each top.
+const DEFAULT_TOP_LIMIT: usize = 10;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/searches").route(web::get().to(SeqHandler(get_search_analytics))));
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SearchAnalyticsQuery {
+    /// The number of queries listed in each top.
+    limit: Option<usize>,
+}
+
+pub async fn get_search_analytics(
+    meilisearch: GuardedData<ActionPolicy<{ actions::ANALYTICS_GET }>, MeiliSearch>,
+    path: web::Path<String>,
+    params: web::Query<SearchAnalyticsQuery>,
+    search_analytics: web::Data<SearchAnalytics>,
+) -> Result<HttpResponse, ResponseError> {
+    let index_uid = path.into_inner();
+    if !search_analytics.is_enabled() {
+        return Err(MeilisearchHttpError::SearchAnalyticsDisabled.into());
+    }
+    // an unknown index is reported as such, rather than as an index without any search.
+    meilisearch.get_index(index_uid.clone()).await?;
+
+    let limit = params.limit.unwrap_or(DEFAULT_TOP_LIMIT);
+    let analytics = search_analytics.view(&index_uid, limit);
+    debug!("returns: {:?}", analytics);
+    Ok(HttpResponse::Ok().json(analytics))
+}
@@ -0,0 +1,90 @@
+use actix_web::{web, HttpResponse};
+use bytes::Bytes;
+use futures::Stream;
+use log::debug;
+use meilisearch_error::{Code, ResponseError};
+use meilisearch_lib::tasks::batch::BatchId;
+use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
+
+use crate::error::MeilisearchHttpError;
+use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::extractors::sequential_extractor::SeqHandler;
+
+/// The number of records read from the changefeed at once. The changefeed is only read while a
+/// chunk is read, so that a slow client never keeps its files open.
+const STREAM_CHUNK_SIZE: usize = 100;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::get().to(SeqHandler(get_changes))));
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ChangesQuery {
+    /// The batch the records start from, the first one recorded by default.
+    #[serde(default)]
+    from_batch: BatchId,
+    /// The maximum number of records, all of them by default.
+    limit: Option<usize>,
+}
+
+/// Streams the changefeed of an index as newline-delimited JSON, one record per applied batch. The
+/// records of a deleted index are kept, so that the consumers learn about its deletion.
+pub async fn get_changes(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_GET }>, MeiliSearch>,
+    path: web::Path<String>,
+    params: web::Query<ChangesQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    if !meilisearch.is_changefeed_enabled() {
+        return Err(MeilisearchHttpError::ChangefeedDisabled.into());
+    }
+    let ChangesQuery { from_batch, limit } = params.into_inner();
+    let index_uid = path.into_inner();
+    debug!("called with index {}, from batch {}", index_uid, from_batch);
+
+    let changes = stream_changes(
+        MeiliSearch::clone(&meilisearch),
+        index_uid,
+        from_batch,
+        limit,
+    );
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(changes))
+}
+
+/// Streams the records of the changefeed of `index_uid` chunk by chunk, each chunk starting past
+/// the batch of the last record of the previous one.
+fn stream_changes(
+    meilisearch: MeiliSearch,
+    index_uid: String,
+    mut from_batch: BatchId,
+    limit: Option<usize>,
+) -> impl Stream<Item = Result<Bytes, ResponseError>> {
+    async_stream::try_stream! {
+        let mut remaining = limit.unwrap_or(usize::MAX);
+        while remaining > 0 {
+            let chunk_size = remaining.min(STREAM_CHUNK_SIZE);
+            let records = meilisearch
+                .list_changes(index_uid.clone(), from_batch, chunk_size)
+                .await?;
+            let last = records.last().map(|record| record.batch_id);
+            let exhausted = records.len() < chunk_size;
+            remaining -= records.len();
+
+            let mut lines = Vec::new();
+            for record in records {
+                serde_json::to_writer(&mut lines, &record)
+                    .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))?;
+                lines.push(b'\n');
+            }
+            yield Bytes::from(lines);
+
+            match last.and_then(|last| last.checked_add(1)) {
+                Some(next) if !exhausted => from_batch = next,
+                _ => break,
+            }
+        }
+    }
+}
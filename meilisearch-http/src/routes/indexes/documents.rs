@@ -1,5 +1,5 @@
 use actix_web::error::PayloadError;
-use actix_web::http::header::CONTENT_TYPE;
+use actix_web::http::header::{CONTENT_RANGE, CONTENT_TYPE};
 use actix_web::web::Bytes;
 use actix_web::HttpMessage;
 use actix_web::{web, HttpRequest, HttpResponse};
@@ -7,23 +7,28 @@ use bstr::ByteSlice;
 use futures::{Stream, StreamExt};
 use log::debug;
 use meilisearch_error::ResponseError;
+use meilisearch_lib::index::{Document, DocumentEdit, DocumentsCursor, DocumentsDiffMode};
+use meilisearch_lib::index_controller::uploads::{ContentRange, UploadError};
 use meilisearch_lib::index_controller::{DocumentAdditionFormat, Update};
 use meilisearch_lib::milli::update::IndexDocumentsMethod;
 use meilisearch_lib::MeiliSearch;
 use mime::Mime;
 use once_cell::sync::Lazy;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_cs::vec::CS;
 use serde_json::Value;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use crate::analytics::Analytics;
+use crate::audit::AuditLog;
 use crate::error::MeilisearchHttpError;
 use crate::extractors::authentication::{policies::*, GuardedData};
 use crate::extractors::payload::Payload;
 use crate::extractors::sequential_extractor::SeqHandler;
-use crate::routes::{fold_star_or, PaginationView, StarOr};
-use crate::task::SummarizedTaskView;
+use crate::routes::{
+    fold_star_or, register_update, task_accepted, task_origin, PaginationView, StarOr,
+};
 
 static ACCEPTED_CONTENT_TYPE: Lazy<Vec<String>> = Lazy::new(|| {
     vec![
@@ -68,6 +73,12 @@ pub struct DocumentParam {
     document_id: String,
 }
 
+#[derive(Deserialize)]
+pub struct UploadParam {
+    index_uid: String,
+    upload_id: Uuid,
+}
+
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::resource("")
@@ -78,6 +89,19 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     )
     // this route needs to be before the /documents/{document_id} to match properly
     .service(web::resource("/delete-batch").route(web::post().to(SeqHandler(delete_documents))))
+    .service(web::resource("/fetch").route(web::post().to(SeqHandler(fetch_documents))))
+    .service(web::resource("/edit").route(web::post().to(SeqHandler(edit_documents))))
+    .service(web::resource("/validate").route(web::post().to(SeqHandler(validate_documents))))
+    .service(web::resource("/uploads").route(web::post().to(SeqHandler(create_upload))))
+    .service(
+        web::resource("/uploads/{upload_id}")
+            .route(web::get().to(SeqHandler(get_upload)))
+            .route(web::put().to(SeqHandler(append_to_upload))),
+    )
+    .service(
+        web::resource("/uploads/{upload_id}/commit")
+            .route(web::post().to(SeqHandler(commit_upload))),
+    )
     .service(
         web::resource("/{document_id}")
             .route(web::get().to(SeqHandler(get_document)))
@@ -89,6 +113,9 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct GetDocument {
     fields: Option<CS<StarOr<String>>>,
+    /// Include the `_createdAt` and `_updatedAt` metadata of the document, if they are recorded.
+    #[serde(default)]
+    with_metadata: bool,
 }
 
 pub async fn get_document(
@@ -98,11 +125,14 @@ pub async fn get_document(
 ) -> Result<HttpResponse, ResponseError> {
     let index = path.index_uid.clone();
     let id = path.document_id.clone();
-    let GetDocument { fields } = params.into_inner();
+    let GetDocument {
+        fields,
+        with_metadata,
+    } = params.into_inner();
     let attributes_to_retrieve = fields.and_then(fold_star_or);
 
     let document = meilisearch
-        .document(index, id, attributes_to_retrieve)
+        .document(index, id, attributes_to_retrieve, with_metadata)
         .await?;
     debug!("returns: {:?}", document);
     Ok(HttpResponse::Ok().json(document))
@@ -111,27 +141,37 @@ pub async fn get_document(
 pub async fn delete_document(
     meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_DELETE }>, MeiliSearch>,
     path: web::Path<DocumentParam>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let DocumentParam {
         document_id,
         index_uid,
     } = path.into_inner();
     let update = Update::DeleteDocuments(vec![document_id]);
-    let task: SummarizedTaskView = meilisearch.register_update(index_uid, update).await?.into();
-    debug!("returns: {:?}", task);
-    Ok(HttpResponse::Accepted().json(task))
+    register_update(&meilisearch, index_uid, update, &req, &audit).await
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct BrowseQuery {
-    #[serde(default)]
-    offset: usize,
+    offset: Option<usize>,
     #[serde(default = "crate::routes::PAGINATION_DEFAULT_LIMIT")]
     limit: usize,
+    /// The `nextCursor` of the previous page. Unlike an offset, a cursor doesn't skip the
+    /// documents of the previous pages one by one.
+    cursor: Option<String>,
     fields: Option<CS<StarOr<String>>>,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentsPageView {
+    #[serde(flatten)]
+    page: PaginationView<Document>,
+    next_cursor: Option<String>,
+}
+
 pub async fn get_all_documents(
     meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_GET }>, MeiliSearch>,
     path: web::Path<String>,
@@ -141,15 +181,35 @@ pub async fn get_all_documents(
     let BrowseQuery {
         limit,
         offset,
+        cursor,
         fields,
     } = params.into_inner();
     let attributes_to_retrieve = fields.and_then(fold_star_or);
-
-    let (total, documents) = meilisearch
-        .documents(path.into_inner(), offset, limit, attributes_to_retrieve)
+    let cursor = match cursor {
+        Some(_) if offset.is_some() => return Err(MeilisearchHttpError::OffsetWithCursor.into()),
+        Some(cursor) => Some(
+            cursor
+                .parse::<DocumentsCursor>()
+                .map_err(ResponseError::from)?,
+        ),
+        None => None,
+    };
+    let offset = offset.unwrap_or_default();
+
+    let page = meilisearch
+        .documents(
+            path.into_inner(),
+            offset,
+            limit,
+            cursor,
+            attributes_to_retrieve,
+        )
         .await?;
 
-    let ret = PaginationView::new(offset, limit, total as usize, documents);
+    let ret = DocumentsPageView {
+        page: PaginationView::new(offset, limit, page.total as usize, page.documents),
+        next_cursor: page.next_cursor.map(|cursor| cursor.to_string()),
+    };
 
     debug!("returns: {:?}", ret);
     Ok(HttpResponse::Ok().json(ret))
@@ -159,6 +219,21 @@ pub async fn get_all_documents(
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct UpdateDocumentsQuery {
     pub primary_key: Option<String>,
+    /// Enqueue a payload without any document as an already succeeded task, instead of
+    /// refusing it.
+    #[serde(default)]
+    pub allow_empty_payload: bool,
+    /// Fail the task when several documents of the payload have the same primary key, instead
+    /// of deduplicating them.
+    #[serde(default)]
+    pub reject_duplicates: bool,
+    /// Record in the task details how many documents were created and updated, and in the
+    /// `full` mode which documents were replaced.
+    pub return_diff: Option<DocumentsDiffMode>,
+    /// Replace the control characters, the unpaired surrogates and the invalid UTF-8 sequences of
+    /// the payload by U+FFFD, instead of refusing the payload.
+    #[serde(default)]
+    pub sanitize: bool,
 }
 
 pub async fn add_documents(
@@ -168,6 +243,7 @@ pub async fn add_documents(
     body: Payload,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     debug!("called with params: {:?}", params);
     let params = params.into_inner();
@@ -179,19 +255,16 @@ pub async fn add_documents(
         &req,
     );
 
-    let allow_index_creation = meilisearch.filters().allow_index_creation;
-    let task = document_addition(
-        extract_mime_type(&req)?,
+    document_addition(
         meilisearch,
         index_uid,
-        params.primary_key,
+        params,
         body,
         IndexDocumentsMethod::ReplaceDocuments,
-        allow_index_creation,
+        &req,
+        &audit,
     )
-    .await?;
-
-    Ok(HttpResponse::Accepted().json(task))
+    .await
 }
 
 pub async fn update_documents(
@@ -201,8 +274,10 @@ pub async fn update_documents(
     body: Payload,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     debug!("called with params: {:?}", params);
+    let params = params.into_inner();
     let index_uid = path.into_inner();
 
     analytics.update_documents(
@@ -211,69 +286,259 @@ pub async fn update_documents(
         &req,
     );
 
-    let allow_index_creation = meilisearch.filters().allow_index_creation;
-    let task = document_addition(
-        extract_mime_type(&req)?,
+    document_addition(
         meilisearch,
         index_uid,
-        params.into_inner().primary_key,
+        params,
         body,
         IndexDocumentsMethod::UpdateDocuments,
-        allow_index_creation,
+        &req,
+        &audit,
     )
-    .await?;
-
-    Ok(HttpResponse::Accepted().json(task))
+    .await
 }
 
 async fn document_addition(
-    mime_type: Option<Mime>,
     meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
     index_uid: String,
-    primary_key: Option<String>,
+    params: UpdateDocumentsQuery,
     body: Payload,
     method: IndexDocumentsMethod,
-    allow_index_creation: bool,
-) -> Result<SummarizedTaskView, ResponseError> {
-    let format = match mime_type
+    req: &HttpRequest,
+    audit: &AuditLog,
+) -> Result<HttpResponse, ResponseError> {
+    let format = payload_format(req)?;
+    let update = Update::DocumentAddition {
+        payload: Box::new(payload_to_stream(body)),
+        primary_key: params.primary_key,
+        method,
+        format,
+        allow_index_creation: meilisearch.filters().allow_index_creation,
+        allow_empty_payload: params.allow_empty_payload,
+        reject_duplicates: params.reject_duplicates,
+        return_diff: params.return_diff,
+        sanitize: params.sanitize,
+    };
+
+    register_update(&meilisearch, index_uid, update, req, audit).await
+}
+
+/// The format of the documents payload of `req`, from its content type.
+fn payload_format(req: &HttpRequest) -> Result<DocumentAdditionFormat, ResponseError> {
+    let mime_type = extract_mime_type(req)?;
+    match mime_type
         .as_ref()
         .map(|m| (m.type_().as_str(), m.subtype().as_str()))
     {
-        Some(("application", "json")) => DocumentAdditionFormat::Json,
-        Some(("application", "x-ndjson")) => DocumentAdditionFormat::Ndjson,
-        Some(("text", "csv")) => DocumentAdditionFormat::Csv,
-        Some((type_, subtype)) => {
-            return Err(MeilisearchHttpError::InvalidContentType(
-                format!("{}/{}", type_, subtype),
-                ACCEPTED_CONTENT_TYPE.clone(),
-            )
-            .into())
-        }
-        None => {
-            return Err(
-                MeilisearchHttpError::MissingContentType(ACCEPTED_CONTENT_TYPE.clone()).into(),
-            )
-        }
-    };
+        Some(("application", "json")) => Ok(DocumentAdditionFormat::Json),
+        Some(("application", "x-ndjson")) => Ok(DocumentAdditionFormat::Ndjson),
+        Some(("text", "csv")) => Ok(DocumentAdditionFormat::Csv),
+        Some((type_, subtype)) => Err(MeilisearchHttpError::InvalidContentType(
+            format!("{}/{}", type_, subtype),
+            ACCEPTED_CONTENT_TYPE.clone(),
+        )
+        .into()),
+        None => Err(MeilisearchHttpError::MissingContentType(ACCEPTED_CONTENT_TYPE.clone()).into()),
+    }
+}
 
-    let update = Update::DocumentAddition {
-        payload: Box::new(payload_to_stream(body)),
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ValidateDocumentsQuery {
+    primary_key: Option<String>,
+    #[serde(default)]
+    reject_duplicates: bool,
+    #[serde(default)]
+    sanitize: bool,
+}
+
+/// Validates a documents payload with the checks of a document addition, without registering a
+/// task: every document the addition would refuse is reported, with the warnings of the others.
+pub async fn validate_documents(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
+    path: web::Path<String>,
+    params: web::Query<ValidateDocumentsQuery>,
+    body: Payload,
+    req: HttpRequest,
+) -> Result<HttpResponse, ResponseError> {
+    debug!("called with params: {:?}", params);
+    let format = payload_format(&req)?;
+    let ValidateDocumentsQuery {
         primary_key,
-        method,
+        reject_duplicates,
+        sanitize,
+    } = params.into_inner();
+
+    let validation = meilisearch
+        .validate_documents(
+            path.into_inner(),
+            Box::new(payload_to_stream(body)),
+            format,
+            primary_key,
+            reject_duplicates,
+            sanitize,
+        )
+        .await?;
+    debug!("returns: {:?}", validation);
+    Ok(HttpResponse::Ok().json(validation))
+}
+
+fn default_fetch_format() -> DocumentAdditionFormat {
+    DocumentAdditionFormat::Ndjson
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FetchDocumentsRequest {
+    url: String,
+    #[serde(default = "default_fetch_format")]
+    format: DocumentAdditionFormat,
+    primary_key: Option<String>,
+    /// The expected sha256 of the file, hex encoded.
+    checksum: Option<String>,
+}
+
+pub async fn fetch_documents(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
+    path: web::Path<String>,
+    body: web::Json<FetchDocumentsRequest>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    debug!("called with params: {:?}", body);
+    let FetchDocumentsRequest {
+        url,
+        format,
+        primary_key,
+        checksum,
+    } = body.into_inner();
+
+    let update = Update::DocumentAdditionFromUrl {
+        url,
         format,
-        allow_index_creation,
+        checksum,
+        primary_key,
+        method: IndexDocumentsMethod::ReplaceDocuments,
+        allow_index_creation: meilisearch.filters().allow_index_creation,
     };
+    register_update(&meilisearch, path.into_inner(), update, &req, &audit).await
+}
 
-    let task = meilisearch.register_update(index_uid, update).await?.into();
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EditDocumentsRequest {
+    /// The documents to edit, all the documents if `None`.
+    filter: Option<Value>,
+    edits: Vec<DocumentEdit>,
+}
 
-    debug!("returns: {:?}", task);
-    Ok(task)
+pub async fn edit_documents(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
+    path: web::Path<String>,
+    body: web::Json<EditDocumentsRequest>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    debug!("called with params: {:?}", body);
+    let EditDocumentsRequest { filter, edits } = body.into_inner();
+
+    let update = Update::EditDocuments { filter, edits };
+    register_update(&meilisearch, path.into_inner(), update, &req, &audit).await
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CreateUploadRequest {
+    #[serde(default = "default_fetch_format")]
+    format: DocumentAdditionFormat,
+    primary_key: Option<String>,
+}
+
+pub async fn create_upload(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
+    path: web::Path<String>,
+    body: web::Json<CreateUploadRequest>,
+) -> Result<HttpResponse, ResponseError> {
+    debug!("called with params: {:?}", body);
+    let CreateUploadRequest {
+        format,
+        primary_key,
+    } = body.into_inner();
+
+    let allow_index_creation = meilisearch.filters().allow_index_creation;
+    let upload =
+        meilisearch.create_upload(path.into_inner(), format, primary_key, allow_index_creation)?;
+
+    debug!("returns: {:?}", upload);
+    Ok(HttpResponse::Created().json(upload))
+}
+
+pub async fn get_upload(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
+    path: web::Path<UploadParam>,
+) -> Result<HttpResponse, ResponseError> {
+    let UploadParam {
+        index_uid,
+        upload_id,
+    } = path.into_inner();
+    let upload = meilisearch.get_upload(index_uid, upload_id).await?;
+
+    debug!("returns: {:?}", upload);
+    Ok(HttpResponse::Ok().json(upload))
+}
+
+pub async fn append_to_upload(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
+    path: web::Path<UploadParam>,
+    body: Payload,
+    req: HttpRequest,
+) -> Result<HttpResponse, ResponseError> {
+    let range: ContentRange = match req.headers().get(CONTENT_RANGE) {
+        Some(range) => range.as_bytes().as_bstr().to_string().parse()?,
+        None => return Err(UploadError::InvalidContentRange(String::new()).into()),
+    };
+    debug!("called with range: {:?}", range);
+
+    let UploadParam {
+        index_uid,
+        upload_id,
+    } = path.into_inner();
+    let upload = meilisearch
+        .append_to_upload(
+            index_uid,
+            upload_id,
+            range,
+            Box::new(payload_to_stream(body)),
+        )
+        .await?;
+
+    debug!("returns: {:?}", upload);
+    Ok(HttpResponse::Ok().json(upload))
+}
+
+pub async fn commit_upload(
+    meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_ADD }>, MeiliSearch>,
+    path: web::Path<UploadParam>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let UploadParam {
+        index_uid,
+        upload_id,
+    } = path.into_inner();
+    let origin = task_origin(&req, meilisearch.filters());
+    let task = meilisearch
+        .commit_upload(index_uid, upload_id, Some(origin))
+        .await?;
+    Ok(task_accepted(&req, meilisearch.filters(), &audit, task))
 }
 
 pub async fn delete_documents(
     meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_DELETE }>, MeiliSearch>,
     path: web::Path<String>,
     body: web::Json<Vec<Value>>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     debug!("called with params: {:?}", body);
     let ids = body
@@ -286,25 +551,15 @@ pub async fn delete_documents(
         .collect();
 
     let update = Update::DeleteDocuments(ids);
-    let task: SummarizedTaskView = meilisearch
-        .register_update(path.into_inner(), update)
-        .await?
-        .into();
-
-    debug!("returns: {:?}", task);
-    Ok(HttpResponse::Accepted().json(task))
+    register_update(&meilisearch, path.into_inner(), update, &req, &audit).await
 }
 
 pub async fn clear_all_documents(
     meilisearch: GuardedData<ActionPolicy<{ actions::DOCUMENTS_DELETE }>, MeiliSearch>,
     path: web::Path<String>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let update = Update::ClearDocuments;
-    let task: SummarizedTaskView = meilisearch
-        .register_update(path.into_inner(), update)
-        .await?
-        .into();
-
-    debug!("returns: {:?}", task);
-    Ok(HttpResponse::Accepted().json(task))
+    register_update(&meilisearch, path.into_inner(), update, &req, &audit).await
 }
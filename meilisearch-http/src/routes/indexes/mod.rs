@@ -1,19 +1,24 @@
 use actix_web::{web, HttpRequest, HttpResponse};
 use log::debug;
 use meilisearch_error::ResponseError;
-use meilisearch_lib::index_controller::Update;
+use meilisearch_lib::index_controller::{Update, DEFAULT_NAMESPACE};
 use meilisearch_lib::MeiliSearch;
 use serde::{Deserialize, Serialize};
+use serde_cs::vec::CS;
 use serde_json::json;
 use time::OffsetDateTime;
 
 use crate::analytics::Analytics;
-use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::audit::AuditLog;
+use crate::extractors::authentication::{policies::*, AuthenticationError, GuardedData};
 use crate::extractors::sequential_extractor::SeqHandler;
-use crate::task::SummarizedTaskView;
+use crate::helpers::Projection;
 
-use super::Pagination;
+use super::{register_update, Pagination, PAGINATION_DEFAULT_LIMIT};
 
+pub mod aggregate;
+pub mod analytics;
+pub mod changes;
 pub mod documents;
 pub mod search;
 pub mod settings;
@@ -33,28 +38,69 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                     .route(web::delete().to(SeqHandler(delete_index))),
             )
             .service(web::resource("/stats").route(web::get().to(SeqHandler(get_index_stats))))
+            .service(web::resource("/check").route(web::post().to(SeqHandler(check_index))))
+            .service(web::resource("/repair-stats").route(web::post().to(SeqHandler(repair_stats))))
+            .service(web::resource("/rebuild").route(web::post().to(SeqHandler(rebuild_index))))
+            .service(web::resource("/close").route(web::post().to(SeqHandler(close_index))))
+            .service(web::resource("/open").route(web::post().to(SeqHandler(open_index))))
+            .service(web::scope("/aggregate").configure(aggregate::configure))
+            .service(web::scope("/analytics").configure(analytics::configure))
+            .service(web::scope("/changes").configure(changes::configure))
             .service(web::scope("/documents").configure(documents::configure))
             .service(web::scope("/search").configure(search::configure))
             .service(web::scope("/settings").configure(settings::configure)),
     );
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ListIndexesQuery {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "PAGINATION_DEFAULT_LIMIT")]
+    limit: usize,
+    /// Only lists the indexes whose uid or name starts with this prefix.
+    uid_prefix: Option<String>,
+    /// Only returns these fields of the indexes.
+    fields: Option<CS<String>>,
+}
+
 pub async fn list_indexes(
     data: GuardedData<ActionPolicy<{ actions::INDEXES_GET }>, MeiliSearch>,
-    paginate: web::Query<Pagination>,
+    params: web::Query<ListIndexesQuery>,
 ) -> Result<HttpResponse, ResponseError> {
-    let search_rules = &data.filters().search_rules;
-    let indexes: Vec<_> = data.list_indexes().await?;
-    let nb_indexes = indexes.len();
-    let iter = indexes
+    let ListIndexesQuery {
+        offset,
+        limit,
+        uid_prefix,
+        fields,
+    } = params.into_inner();
+    let filters = data.filters();
+    let indexes: Vec<_> = data
+        .list_indexes()
+        .await?
         .into_iter()
-        .filter(|i| search_rules.is_index_authorized(&i.uid));
-    let ret = paginate
-        .into_inner()
-        .auto_paginate_unsized(nb_indexes, iter);
+        .filter(|i| {
+            uid_prefix.as_ref().map_or(true, |prefix| {
+                i.uid.starts_with(prefix.as_str())
+                    || i.name
+                        .as_ref()
+                        .map_or(false, |name| name.starts_with(prefix.as_str()))
+            })
+        })
+        .collect();
+    let nb_indexes = indexes.len();
+    let iter = indexes.into_iter().filter(|i| {
+        filters.search_rules.is_index_authorized(&i.uid)
+            && filters.is_namespace_authorized(i.namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE))
+    });
+    let ret = Pagination { offset, limit }.auto_paginate_unsized(nb_indexes, iter);
 
     debug!("returns: {:?}", ret);
-    Ok(HttpResponse::Ok().json(ret))
+    match fields {
+        Some(fields) => Projection::new(fields).listing_response(&ret),
+        None => Ok(HttpResponse::Ok().json(ret)),
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,28 +108,70 @@ pub async fn list_indexes(
 pub struct IndexCreateRequest {
     uid: String,
     primary_key: Option<String>,
+    /// A free-form name for the index, that is not bound to the rules of the uids.
+    name: Option<String>,
+    /// The namespace to create the index in, the default namespace if missing.
+    namespace: Option<String>,
+    /// Opts the index out of the index templates.
+    #[serde(default)]
+    skip_templates: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IndexCreateQuery {
+    /// Returns the index instead of failing if it already exists.
+    #[serde(default)]
+    if_not_exists: bool,
 }
 
 pub async fn create_index(
     meilisearch: GuardedData<ActionPolicy<{ actions::INDEXES_CREATE }>, MeiliSearch>,
     body: web::Json<IndexCreateRequest>,
+    params: web::Query<IndexCreateQuery>,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let IndexCreateRequest {
-        primary_key, uid, ..
+        primary_key,
+        uid,
+        name,
+        namespace,
+        skip_templates,
     } = body.into_inner();
 
+    if !meilisearch
+        .filters()
+        .is_namespace_authorized(namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE))
+    {
+        return Err(AuthenticationError::InvalidToken.into());
+    }
+
+    let if_not_exists = params.if_not_exists;
+    if if_not_exists && meilisearch.index_exists(uid.clone()).await? {
+        if !meilisearch.filters().search_rules.is_index_authorized(&uid) {
+            return Err(AuthenticationError::InvalidToken.into());
+        }
+        let meta = meilisearch.get_index(uid).await?;
+        debug!("returns: {:?}", meta);
+        return Ok(HttpResponse::Ok().json(meta));
+    }
+
     analytics.publish(
         "Index Created".to_string(),
         json!({ "primary_key": primary_key }),
         Some(&req),
     );
 
-    let update = Update::CreateIndex { primary_key };
-    let task: SummarizedTaskView = meilisearch.register_update(uid, update).await?.into();
-
-    Ok(HttpResponse::Accepted().json(task))
+    let update = Update::CreateIndex {
+        primary_key,
+        name,
+        namespace,
+        if_not_exists,
+        skip_templates,
+    };
+    register_update(&meilisearch, uid, update, &req, &audit).await
 }
 
 #[derive(Debug, Deserialize)]
@@ -92,6 +180,8 @@ pub async fn create_index(
 pub struct UpdateIndexRequest {
     uid: Option<String>,
     primary_key: Option<String>,
+    name: Option<String>,
+    skip_templates: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -122,6 +212,7 @@ pub async fn update_index(
     body: web::Json<UpdateIndexRequest>,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     debug!("called with params: {:?}", body);
     let body = body.into_inner();
@@ -133,34 +224,116 @@ pub async fn update_index(
 
     let update = Update::UpdateIndex {
         primary_key: body.primary_key,
+        name: body.name,
+        skip_templates: body.skip_templates,
     };
 
-    let task: SummarizedTaskView = meilisearch
-        .register_update(path.into_inner(), update)
-        .await?
-        .into();
-
-    debug!("returns: {:?}", task);
-    Ok(HttpResponse::Accepted().json(task))
+    register_update(&meilisearch, path.into_inner(), update, &req, &audit).await
 }
 
 pub async fn delete_index(
     meilisearch: GuardedData<ActionPolicy<{ actions::INDEXES_DELETE }>, MeiliSearch>,
     path: web::Path<String>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let uid = path.into_inner();
     let update = Update::DeleteIndex;
-    let task: SummarizedTaskView = meilisearch.register_update(uid, update).await?.into();
+    register_update(&meilisearch, uid, update, &req, &audit).await
+}
+
+/// Closes the index right away: its tasks wait in the queue until it is opened again.
+pub async fn close_index(
+    meilisearch: GuardedData<ActionPolicy<{ actions::INDEXES_UPDATE }>, MeiliSearch>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ResponseError> {
+    meilisearch.close_index(path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
 
-    Ok(HttpResponse::Accepted().json(task))
+pub async fn open_index(
+    meilisearch: GuardedData<ActionPolicy<{ actions::INDEXES_UPDATE }>, MeiliSearch>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ResponseError> {
+    meilisearch.open_index(path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IndexStatsQuery {
+    /// Adds the breakdown of the index by internal database, reserved to the admin keys.
+    #[serde(default)]
+    detailed: bool,
 }
 
 pub async fn get_index_stats(
     meilisearch: GuardedData<ActionPolicy<{ actions::STATS_GET }>, MeiliSearch>,
     path: web::Path<String>,
+    params: web::Query<IndexStatsQuery>,
 ) -> Result<HttpResponse, ResponseError> {
-    let response = meilisearch.get_index_stats(path.into_inner()).await?;
+    let detailed = params.into_inner().detailed;
+    if detailed && !meilisearch.filters().is_admin {
+        return Err(AuthenticationError::InvalidToken.into());
+    }
+
+    let response = meilisearch
+        .get_index_stats(path.into_inner(), detailed)
+        .await?;
 
     debug!("returns: {:?}", response);
     Ok(HttpResponse::Ok().json(response))
 }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CheckIndexQuery {
+    /// Only checks a sample of each database of the index.
+    #[serde(default)]
+    fast: bool,
+}
+
+pub async fn check_index(
+    meilisearch: GuardedData<ActionPolicy<{ actions::INDEXES_UPDATE }>, MeiliSearch>,
+    path: web::Path<String>,
+    params: web::Query<CheckIndexQuery>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let update = Update::CheckIndex {
+        fast: params.into_inner().fast,
+    };
+    register_update(&meilisearch, path.into_inner(), update, &req, &audit).await
+}
+
+pub async fn repair_stats(
+    meilisearch: GuardedData<ActionPolicy<{ actions::INDEXES_UPDATE }>, MeiliSearch>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    register_update(
+        &meilisearch,
+        path.into_inner(),
+        Update::RepairStats,
+        &req,
+        &audit,
+    )
+    .await
+}
+
+pub async fn rebuild_index(
+    meilisearch: GuardedData<ActionPolicy<{ actions::INDEXES_UPDATE }>, MeiliSearch>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    register_update(
+        &meilisearch,
+        path.into_inner(),
+        Update::RebuildIndex,
+        &req,
+        &audit,
+    )
+    .await
+}
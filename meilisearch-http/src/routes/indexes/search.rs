@@ -1,20 +1,23 @@
+use std::time::Instant;
+
 use actix_web::{web, HttpRequest, HttpResponse};
 use log::debug;
-use meilisearch_auth::IndexSearchRules;
-use meilisearch_error::ResponseError;
-use meilisearch_lib::index::{
-    SearchQuery, DEFAULT_CROP_LENGTH, DEFAULT_CROP_MARKER, DEFAULT_HIGHLIGHT_POST_TAG,
-    DEFAULT_HIGHLIGHT_PRE_TAG, DEFAULT_SEARCH_LIMIT,
-};
+use meilisearch_auth::{AuthFilter, CompiledFilter, IndexSearchRules};
+use meilisearch_error::{Code, ErrorCode, ResponseError};
+use meilisearch_lib::index::{SearchDefaults, SearchQuery, SearchResult};
 use meilisearch_lib::MeiliSearch;
 use serde::Deserialize;
 use serde_cs::vec::CS;
-use serde_json::Value;
+use serde_json::{Map, Value};
+use uuid::Uuid;
 
 use crate::analytics::{Analytics, SearchAggregator};
-use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::error::PayloadError;
+use crate::extractors::authentication::{policies::*, AuthenticationError, GuardedData};
 use crate::extractors::sequential_extractor::SeqHandler;
 use crate::routes::{fold_star_or, StarOr};
+use crate::search_analytics::SearchAnalytics;
+use crate::slow_query_log::SlowQueryLog;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -24,79 +27,188 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     );
 }
 
+/// The parameters of a search request sent as a query string. Only the parameters that are
+/// present are kept, so that the search defaults of the index apply to the others.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct SearchQueryGet {
     q: Option<String>,
+    ignore_stop_words: Option<bool>,
+    prefix_search: Option<String>,
+    like_document_ids: Option<CS<String>>,
     offset: Option<usize>,
     limit: Option<usize>,
     attributes_to_retrieve: Option<CS<StarOr<String>>>,
     attributes_to_crop: Option<CS<StarOr<String>>>,
-    #[serde(default = "DEFAULT_CROP_LENGTH")]
-    crop_length: usize,
+    crop_length: Option<usize>,
     attributes_to_highlight: Option<CS<StarOr<String>>>,
     filter: Option<String>,
     sort: Option<String>,
-    #[serde(default = "Default::default")]
-    show_matches_position: bool,
+    show_matches_position: Option<bool>,
+    show_ranking_score: Option<bool>,
     facets: Option<CS<StarOr<String>>>,
-    #[serde(default = "DEFAULT_HIGHLIGHT_PRE_TAG")]
-    highlight_pre_tag: String,
-    #[serde(default = "DEFAULT_HIGHLIGHT_POST_TAG")]
-    highlight_post_tag: String,
-    #[serde(default = "DEFAULT_CROP_MARKER")]
-    crop_marker: String,
+    highlight_pre_tag: Option<String>,
+    highlight_post_tag: Option<String>,
+    crop_marker: Option<String>,
+    pit: Option<bool>,
+    pit_token: Option<String>,
 }
 
-impl From<SearchQueryGet> for SearchQuery {
+impl From<SearchQueryGet> for Map<String, Value> {
     fn from(other: SearchQueryGet) -> Self {
-        let filter = match other.filter {
-            Some(f) => match serde_json::from_str(&f) {
-                Ok(v) => Some(v),
-                _ => Some(Value::String(f)),
-            },
-            None => None,
-        };
-
-        Self {
-            q: other.q,
-            offset: other.offset,
-            limit: other.limit.unwrap_or_else(DEFAULT_SEARCH_LIMIT),
-            attributes_to_retrieve: other.attributes_to_retrieve.and_then(fold_star_or),
-            attributes_to_crop: other.attributes_to_crop.and_then(fold_star_or),
-            crop_length: other.crop_length,
-            attributes_to_highlight: other.attributes_to_highlight.and_then(fold_star_or),
-            filter,
-            sort: other.sort.map(|attr| fix_sort_query_parameters(&attr)),
-            show_matches_position: other.show_matches_position,
-            facets: other.facets.and_then(fold_star_or),
-            highlight_pre_tag: other.highlight_pre_tag,
-            highlight_post_tag: other.highlight_post_tag,
-            crop_marker: other.crop_marker,
+        // a `*` resets the list to the engine default.
+        fn star_or_list(list: CS<StarOr<String>>) -> Value {
+            fold_star_or::<_, Vec<String>>(list).map_or(Value::Null, Value::from)
         }
+
+        let filter = other.filter.map(|f| match serde_json::from_str(&f) {
+            Ok(v) => v,
+            _ => Value::String(f),
+        });
+
+        let params = [
+            ("q", other.q.map(Value::from)),
+            ("ignoreStopWords", other.ignore_stop_words.map(Value::from)),
+            ("prefixSearch", other.prefix_search.map(Value::from)),
+            (
+                "likeDocumentIds",
+                other
+                    .like_document_ids
+                    .map(|ids| Value::from(ids.into_iter().collect::<Vec<_>>())),
+            ),
+            ("offset", other.offset.map(Value::from)),
+            ("limit", other.limit.map(Value::from)),
+            (
+                "attributesToRetrieve",
+                other.attributes_to_retrieve.map(star_or_list),
+            ),
+            (
+                "attributesToCrop",
+                other.attributes_to_crop.map(star_or_list),
+            ),
+            ("cropLength", other.crop_length.map(Value::from)),
+            (
+                "attributesToHighlight",
+                other.attributes_to_highlight.map(star_or_list),
+            ),
+            ("filter", filter),
+            (
+                "sort",
+                other
+                    .sort
+                    .map(|attr| Value::from(fix_sort_query_parameters(&attr))),
+            ),
+            (
+                "showMatchesPosition",
+                other.show_matches_position.map(Value::from),
+            ),
+            (
+                "showRankingScore",
+                other.show_ranking_score.map(Value::from),
+            ),
+            ("facets", other.facets.map(star_or_list)),
+            ("highlightPreTag", other.highlight_pre_tag.map(Value::from)),
+            (
+                "highlightPostTag",
+                other.highlight_post_tag.map(Value::from),
+            ),
+            ("cropMarker", other.crop_marker.map(Value::from)),
+            ("pit", other.pit.map(Value::from)),
+            ("pitToken", other.pit_token.map(Value::from)),
+        ];
+
+        params
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value?)))
+            .collect()
     }
 }
 
-/// Incorporate search rules in search query
-fn add_search_rules(query: &mut SearchQuery, rules: IndexSearchRules) {
-    query.filter = match (query.filter.take(), rules.filter) {
-        (None, rules_filter) => rules_filter,
-        (filter, None) => filter,
-        (Some(filter), Some(rules_filter)) => {
-            let filter = match filter {
-                Value::Array(filter) => filter,
-                filter => vec![filter],
-            };
-            let rules_filter = match rules_filter {
-                Value::Array(rules_filter) => rules_filter,
-                rules_filter => vec![rules_filter],
-            };
-
-            Some(Value::Array([filter, rules_filter].concat()))
-        }
+/// Builds the search query from the parameters of the request merged over the search defaults
+/// of the index.
+pub(crate) async fn search_query(
+    meilisearch: &MeiliSearch,
+    index_uid: &str,
+    params: Map<String, Value>,
+) -> Result<SearchQuery, ResponseError> {
+    let invalid = |e| PayloadError::from(actix_web::error::JsonPayloadError::Deserialize(e));
+    // the request is checked on its own first, so that an invalid request is reported as such
+    // even if the index doesn't exist.
+    SearchDefaults::default()
+        .query(params.clone())
+        .map_err(invalid)?;
+
+    let defaults = meilisearch.search_defaults(index_uid.to_string()).await?;
+    Ok(defaults.query(params).map_err(invalid)?)
+}
+
+/// Only the admin keys can override the ranking rules of a search.
+pub(crate) fn check_ranking_rules_override(
+    query: &SearchQuery,
+    filters: &AuthFilter,
+) -> Result<(), AuthenticationError> {
+    if query.ranking_rules_override.is_some() && !filters.is_admin {
+        return Err(AuthenticationError::InvalidToken);
+    }
+
+    Ok(())
+}
+
+/// Records the search in the slow query log if it took longer than its threshold. `query` is only
+/// kept when the slow query log is enabled.
+fn record_slow_query<E: ErrorCode>(
+    slow_query_log: &SlowQueryLog,
+    key_uid: Option<Uuid>,
+    index_uid: &str,
+    query: Option<SearchQuery>,
+    started_at: Instant,
+    result: &Result<SearchResult, E>,
+) {
+    if let Some(query) = query {
+        let timed_out = matches!(result, Err(e) if matches!(e.error_code(), Code::SearchTimeout));
+        slow_query_log.record(
+            index_uid,
+            key_uid,
+            &query,
+            started_at.elapsed(),
+            result.as_ref().ok(),
+            timed_out,
+        );
     }
 }
 
+/// Incorporate search rules in search query
+pub(crate) fn add_search_rules(
+    query: &mut SearchQuery,
+    rules: IndexSearchRules,
+) -> Result<(), ResponseError> {
+    query.filter = restrict_filter(query.filter.take(), rules)?;
+    Ok(())
+}
+
+/// Combines a filter with the compiled filter restricting the searches of the key or the tenant
+/// token, if any. The filter is parsed and combined as a tree, so that it can't escape the
+/// restricting filter.
+pub(super) fn restrict_filter(
+    filter: Option<Value>,
+    rules: IndexSearchRules,
+) -> Result<Option<Value>, ResponseError> {
+    let scope = match rules.compiled_filter {
+        Some(scope) => scope,
+        None => return Ok(filter),
+    };
+    let filter = match filter {
+        Some(filter) => CompiledFilter::parse(&filter)?,
+        None => None,
+    };
+    let filter = match filter {
+        Some(filter) => scope.and(filter),
+        None => scope,
+    };
+
+    Ok(Some(Value::String(filter.to_string())))
+}
+
 // TODO: TAMO: split on :asc, and :desc, instead of doing some weird things
 
 /// Transform the sort query parameter into something that matches the post expected format.
@@ -129,25 +241,46 @@ pub async fn search_with_url_query(
     params: web::Query<SearchQueryGet>,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    slow_query_log: web::Data<SlowQueryLog>,
+    search_analytics: web::Data<SearchAnalytics>,
 ) -> Result<HttpResponse, ResponseError> {
+    let started_at = Instant::now();
     debug!("called with params: {:?}", params);
-    let mut query: SearchQuery = params.into_inner().into();
-
     let index_uid = path.into_inner();
+    let mut query = search_query(&meilisearch, &index_uid, params.into_inner().into()).await?;
+
     // Tenant token search_rules.
     if let Some(search_rules) = meilisearch
         .filters()
         .search_rules
         .get_index_search_rules(&index_uid)
     {
-        add_search_rules(&mut query, search_rules);
+        add_search_rules(&mut query, search_rules)?;
     }
 
     let mut aggregate = SearchAggregator::from_query(&query, &req);
+    let logged_query = slow_query_log.is_enabled().then(|| query.clone());
+    let analyzed_q = search_analytics.is_enabled().then(|| query.q.clone());
 
-    let search_result = meilisearch.search(index_uid, query).await;
+    let search_result = meilisearch.search(index_uid.clone(), query).await;
+    record_slow_query(
+        &slow_query_log,
+        meilisearch.filters().key_uid,
+        &index_uid,
+        logged_query,
+        started_at,
+        &search_result,
+    );
     if let Ok(ref search_result) = search_result {
         aggregate.succeed(search_result);
+        if let Some(q) = analyzed_q {
+            search_analytics.record(
+                &index_uid,
+                q.as_deref(),
+                started_at.elapsed(),
+                search_result.estimated_total_hits,
+            );
+        }
     }
     analytics.get_search(aggregate);
 
@@ -160,28 +293,50 @@ pub async fn search_with_url_query(
 pub async fn search_with_post(
     meilisearch: GuardedData<ActionPolicy<{ actions::SEARCH }>, MeiliSearch>,
     path: web::Path<String>,
-    params: web::Json<SearchQuery>,
+    params: web::Json<Map<String, Value>>,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    slow_query_log: web::Data<SlowQueryLog>,
+    search_analytics: web::Data<SearchAnalytics>,
 ) -> Result<HttpResponse, ResponseError> {
-    let mut query = params.into_inner();
+    let started_at = Instant::now();
+    let index_uid = path.into_inner();
+    let mut query = search_query(&meilisearch, &index_uid, params.into_inner()).await?;
+    check_ranking_rules_override(&query, meilisearch.filters())?;
     debug!("search called with params: {:?}", query);
 
-    let index_uid = path.into_inner();
     // Tenant token search_rules.
     if let Some(search_rules) = meilisearch
         .filters()
         .search_rules
         .get_index_search_rules(&index_uid)
     {
-        add_search_rules(&mut query, search_rules);
+        add_search_rules(&mut query, search_rules)?;
     }
 
     let mut aggregate = SearchAggregator::from_query(&query, &req);
+    let logged_query = slow_query_log.is_enabled().then(|| query.clone());
+    let analyzed_q = search_analytics.is_enabled().then(|| query.q.clone());
 
-    let search_result = meilisearch.search(index_uid, query).await;
+    let search_result = meilisearch.search(index_uid.clone(), query).await;
+    record_slow_query(
+        &slow_query_log,
+        meilisearch.filters().key_uid,
+        &index_uid,
+        logged_query,
+        started_at,
+        &search_result,
+    );
     if let Ok(ref search_result) = search_result {
         aggregate.succeed(search_result);
+        if let Some(q) = analyzed_q {
+            search_analytics.record(
+                &index_uid,
+                q.as_deref(),
+                started_at.elapsed(),
+                search_result.estimated_total_hits,
+            );
+        }
     }
     analytics.post_search(aggregate);
 
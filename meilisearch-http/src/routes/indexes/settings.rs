@@ -2,14 +2,28 @@ use log::debug;
 
 use actix_web::{web, HttpRequest, HttpResponse};
 use meilisearch_error::ResponseError;
-use meilisearch_lib::index::{Settings, Unchecked};
+use meilisearch_lib::index::{
+    Settings, SettingsExport, Unchecked, DEFAULT_PREVIEW_LIMIT, DEFAULT_PREVIEW_SAMPLE_SIZE,
+};
 use meilisearch_lib::index_controller::Update;
 use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
 use serde_json::json;
+use time::OffsetDateTime;
 
 use crate::analytics::Analytics;
+use crate::audit::AuditLog;
 use crate::extractors::authentication::{policies::*, GuardedData};
-use crate::task::SummarizedTaskView;
+use crate::routes::register_update;
+
+/// Defers the processing of a settings update to a later date. The settings are still validated
+/// when the update is received.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScheduleQuery {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub schedule_at: Option<OffsetDateTime>,
+}
 
 #[macro_export]
 macro_rules! make_setting_route {
@@ -23,13 +37,17 @@ macro_rules! make_setting_route {
 
             use meilisearch_error::ResponseError;
             use $crate::analytics::Analytics;
+            use $crate::audit::AuditLog;
             use $crate::extractors::authentication::{policies::*, GuardedData};
             use $crate::extractors::sequential_extractor::SeqHandler;
-            use $crate::task::SummarizedTaskView;
+            use $crate::routes::indexes::settings::ScheduleQuery;
 
             pub async fn delete(
                 meilisearch: GuardedData<ActionPolicy<{ actions::SETTINGS_UPDATE }>, MeiliSearch>,
                 index_uid: web::Path<String>,
+                params: web::Query<ScheduleQuery>,
+                req: HttpRequest,
+                audit: web::Data<AuditLog>,
             ) -> Result<HttpResponse, ResponseError> {
                 let settings = Settings {
                     $attr: Setting::Reset,
@@ -41,22 +59,26 @@ macro_rules! make_setting_route {
                     settings,
                     is_deletion: true,
                     allow_index_creation,
+                    scheduled_at: params.schedule_at,
                 };
-                let task: SummarizedTaskView = meilisearch
-                    .register_update(index_uid.into_inner(), update)
-                    .await?
-                    .into();
-
-                debug!("returns: {:?}", task);
-                Ok(HttpResponse::Accepted().json(task))
+                $crate::routes::register_update(
+                    &meilisearch,
+                    index_uid.into_inner(),
+                    update,
+                    &req,
+                    &audit,
+                )
+                .await
             }
 
             pub async fn update(
                 meilisearch: GuardedData<ActionPolicy<{ actions::SETTINGS_UPDATE }>, MeiliSearch>,
                 index_uid: actix_web::web::Path<String>,
                 body: actix_web::web::Json<Option<$type>>,
+                params: web::Query<ScheduleQuery>,
                 req: HttpRequest,
                 $analytics_var: web::Data<dyn Analytics>,
+                audit: web::Data<AuditLog>,
             ) -> std::result::Result<HttpResponse, ResponseError> {
                 let body = body.into_inner();
 
@@ -75,14 +97,16 @@ macro_rules! make_setting_route {
                     settings,
                     is_deletion: false,
                     allow_index_creation,
+                    scheduled_at: params.schedule_at,
                 };
-                let task: SummarizedTaskView = meilisearch
-                    .register_update(index_uid.into_inner(), update)
-                    .await?
-                    .into();
-
-                debug!("returns: {:?}", task);
-                Ok(HttpResponse::Accepted().json(task))
+                $crate::routes::register_update(
+                    &meilisearch,
+                    index_uid.into_inner(),
+                    update,
+                    &req,
+                    &audit,
+                )
+                .await
             }
 
             pub async fn get(
@@ -217,11 +241,11 @@ make_setting_route!(
 make_setting_route!(
     "/searchable-attributes",
     put,
-    Vec<String>,
+    meilisearch_lib::index::SearchableAttributes,
     searchable_attributes,
     "searchableAttributes",
     analytics,
-    |setting: &Option<Vec<String>>, req: &HttpRequest| {
+    |setting: &Option<meilisearch_lib::index::SearchableAttributes>, req: &HttpRequest| {
         use serde_json::json;
 
         analytics.publish(
@@ -229,6 +253,7 @@ make_setting_route!(
             json!({
                 "searchable_attributes": {
                     "total": setting.as_ref().map(|searchable| searchable.len()),
+                    "weighted": setting.as_ref().map(|searchable| searchable.weights().is_some()),
                 },
             }),
             Some(req),
@@ -282,6 +307,102 @@ make_setting_route!(
     }
 );
 
+make_setting_route!(
+    "/warmup-queries",
+    put,
+    Vec<String>,
+    warmup_queries,
+    "warmupQueries"
+);
+
+make_setting_route!(
+    "/max-word-derivations",
+    put,
+    usize,
+    max_word_derivations,
+    "maxWordDerivations"
+);
+
+make_setting_route!(
+    "/max-prefix-expansion",
+    put,
+    usize,
+    max_prefix_expansion,
+    "maxPrefixExpansion"
+);
+
+make_setting_route!(
+    "/max-values-per-facet",
+    put,
+    usize,
+    max_values_per_facet,
+    "maxValuesPerFacet"
+);
+
+make_setting_route!(
+    "/search-defaults",
+    put,
+    meilisearch_lib::index::SearchDefaults,
+    search_defaults,
+    "searchDefaults"
+);
+
+make_setting_route!(
+    "/document-metadata",
+    put,
+    bool,
+    document_metadata,
+    "documentMetadata"
+);
+
+make_setting_route!(
+    "/nested-object-behavior",
+    put,
+    std::collections::BTreeMap<String, meilisearch_lib::index::NestedObjectBehavior>,
+    nested_object_behavior,
+    "nestedObjectBehavior"
+);
+
+make_setting_route!(
+    "/document-transforms",
+    put,
+    Vec<meilisearch_lib::index::DocumentTransform>,
+    document_transforms,
+    "documentTransforms"
+);
+
+make_setting_route!(
+    "/custom-segmenters",
+    put,
+    std::collections::BTreeMap<String, String>,
+    custom_segmenters,
+    "customSegmenters"
+);
+
+make_setting_route!(
+    "/prefix-search",
+    put,
+    meilisearch_lib::index::PrefixSearch,
+    prefix_search,
+    "prefixSearch"
+);
+
+make_setting_route!(
+    "/disable-prefix-on-attributes",
+    put,
+    std::collections::BTreeSet<String>,
+    disable_prefix_on_attributes,
+    "disablePrefixOnAttributes"
+);
+
+make_setting_route!(
+    "/facet-normalization",
+    put,
+    std::collections::BTreeMap<String, meilisearch_lib::index::FacetNormalization>,
+    facet_normalization,
+    "facetNormalization"
+);
+
 macro_rules! generate_configure {
     ($($mod:ident),*) => {
         pub fn configure(cfg: &mut web::ServiceConfig) {
@@ -291,6 +412,8 @@ macro_rules! generate_configure {
                 .route(web::patch().to(SeqHandler(update_all)))
                 .route(web::get().to(SeqHandler(get_all)))
                 .route(web::delete().to(SeqHandler(delete_all))))
+                .service(web::resource("/import").route(web::put().to(SeqHandler(import_all))))
+                .service(web::resource("/preview").route(web::post().to(SeqHandler(preview))))
                 $(.service($mod::resources()))*;
         }
     };
@@ -305,15 +428,29 @@ generate_configure!(
     stop_words,
     synonyms,
     ranking_rules,
-    typo_tolerance
+    typo_tolerance,
+    warmup_queries,
+    max_word_derivations,
+    max_prefix_expansion,
+    max_values_per_facet,
+    search_defaults,
+    document_metadata,
+    nested_object_behavior,
+    document_transforms,
+    custom_segmenters,
+    prefix_search,
+    disable_prefix_on_attributes,
+    facet_normalization
 );
 
 pub async fn update_all(
     meilisearch: GuardedData<ActionPolicy<{ actions::SETTINGS_UPDATE }>, MeiliSearch>,
     index_uid: web::Path<String>,
     body: web::Json<Settings<Unchecked>>,
+    params: web::Query<ScheduleQuery>,
     req: HttpRequest,
     analytics: web::Data<dyn Analytics>,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let settings = body.into_inner();
 
@@ -374,28 +511,113 @@ pub async fn update_all(
         settings,
         is_deletion: false,
         allow_index_creation,
+        scheduled_at: params.schedule_at,
     };
-    let task: SummarizedTaskView = meilisearch
-        .register_update(index_uid.into_inner(), update)
-        .await?
-        .into();
+    register_update(&meilisearch, index_uid.into_inner(), update, &req, &audit).await
+}
 
-    debug!("returns: {:?}", task);
-    Ok(HttpResponse::Accepted().json(task))
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SettingsFormat {
+    /// The canonical, versioned document accepted by `import_all`.
+    Export,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GetSettingsQuery {
+    format: Option<SettingsFormat>,
 }
 
 pub async fn get_all(
     data: GuardedData<ActionPolicy<{ actions::SETTINGS_GET }>, MeiliSearch>,
     index_uid: web::Path<String>,
+    params: web::Query<GetSettingsQuery>,
 ) -> Result<HttpResponse, ResponseError> {
     let settings = data.settings(index_uid.into_inner()).await?;
     debug!("returns: {:?}", settings);
-    Ok(HttpResponse::Ok().json(settings))
+    match params.format {
+        Some(SettingsFormat::Export) => {
+            let export = SettingsExport::new(settings).to_json()?;
+            Ok(HttpResponse::Ok().json(export))
+        }
+        None => Ok(HttpResponse::Ok().json(settings)),
+    }
+}
+
+/// Replaces all the settings of the index with the settings of an export.
+pub async fn import_all(
+    data: GuardedData<ActionPolicy<{ actions::SETTINGS_UPDATE }>, MeiliSearch>,
+    index_uid: web::Path<String>,
+    body: web::Json<SettingsExport<Unchecked>>,
+    params: web::Query<ScheduleQuery>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let settings = body.into_inner().into_settings()?;
+
+    let allow_index_creation = data.filters().allow_index_creation;
+    let update = Update::Settings {
+        settings,
+        is_deletion: false,
+        allow_index_creation,
+        scheduled_at: params.schedule_at,
+    };
+    register_update(&data, index_uid.into_inner(), update, &req, &audit).await
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SettingsPreviewBody {
+    /// The candidate settings, applied over the current settings of the index.
+    settings: Settings<Unchecked>,
+    queries: Vec<String>,
+    /// The number of documents of the index the queries are executed on.
+    #[serde(default = "default_preview_sample_size")]
+    sample_size: usize,
+    /// The number of hits shown for each query.
+    #[serde(default = "default_preview_limit")]
+    limit: usize,
+}
+
+fn default_preview_sample_size() -> usize {
+    DEFAULT_PREVIEW_SAMPLE_SIZE
+}
+
+fn default_preview_limit() -> usize {
+    DEFAULT_PREVIEW_LIMIT
+}
+
+/// Compares the hits of the sample queries with the current settings and with the candidate
+/// settings, on a sample of the documents. The settings of the index are left untouched.
+pub async fn preview(
+    data: GuardedData<ActionPolicy<{ actions::SETTINGS_UPDATE }>, MeiliSearch>,
+    index_uid: web::Path<String>,
+    body: web::Json<SettingsPreviewBody>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let SettingsPreviewBody {
+        settings,
+        queries,
+        sample_size,
+        limit,
+    } = body.into_inner();
+
+    let update = Update::PreviewSettings {
+        settings,
+        queries,
+        sample_size,
+        limit,
+    };
+    register_update(&data, index_uid.into_inner(), update, &req, &audit).await
 }
 
 pub async fn delete_all(
     data: GuardedData<ActionPolicy<{ actions::SETTINGS_UPDATE }>, MeiliSearch>,
     index_uid: web::Path<String>,
+    params: web::Query<ScheduleQuery>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
 ) -> Result<HttpResponse, ResponseError> {
     let settings = Settings::cleared().into_unchecked();
 
@@ -404,12 +626,7 @@ pub async fn delete_all(
         settings,
         is_deletion: true,
         allow_index_creation,
+        scheduled_at: params.schedule_at,
     };
-    let task: SummarizedTaskView = data
-        .register_update(index_uid.into_inner(), update)
-        .await?
-        .into();
-
-    debug!("returns: {:?}", task);
-    Ok(HttpResponse::Accepted().json(task))
+    register_update(&data, index_uid.into_inner(), update, &req, &audit).await
 }
@@ -1,29 +1,54 @@
 use std::str::FromStr;
 
-use actix_web::{web, HttpResponse};
+use actix_web::http::header::{HeaderName, LOCATION, USER_AGENT};
+use actix_web::{web, HttpRequest, HttpResponse};
 use log::debug;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use time::OffsetDateTime;
 
+use meilisearch_auth::{AuthFilter, SearchRules};
 use meilisearch_error::ResponseError;
 use meilisearch_lib::index::{Settings, Unchecked};
+use meilisearch_lib::index_controller::{Stats, Update};
+use meilisearch_lib::tasks::idempotency::{IdempotencyKey, Registration};
+use meilisearch_lib::tasks::task::{Task, TaskId, TaskOrigin};
 use meilisearch_lib::MeiliSearch;
 
+use crate::audit::AuditLog;
+use crate::error::MeilisearchHttpError;
 use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::slow_query_log::{SlowQueryLog, SlowQueryLogStatus};
+use crate::task::{BatchView, SummarizedTaskView, TaskView};
 
+mod admin;
 mod api_key;
+mod batches;
 mod dump;
+mod federated_search;
 pub mod indexes;
+mod namespaces;
+mod snapshot;
 mod tasks;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(web::scope("/tasks").configure(tasks::configure))
+        .service(web::scope("/batches").configure(batches::configure))
         .service(web::resource("/health").route(web::get().to(get_health)))
         .service(web::scope("/keys").configure(api_key::configure))
         .service(web::scope("/dumps").configure(dump::configure))
+        .service(web::scope("/snapshots").configure(snapshot::configure))
+        .service(web::scope("/federated-search").configure(federated_search::configure))
         .service(web::resource("/stats").route(web::get().to(get_stats)))
         .service(web::resource("/version").route(web::get().to(get_version)))
+        .service(
+            web::resource("/config")
+                .route(web::get().to(get_config))
+                .route(web::patch().to(update_config)),
+        )
+        .service(web::scope("/admin").configure(admin::configure))
+        .service(web::scope("/namespaces").configure(namespaces::configure))
         .service(web::scope("/indexes").configure(indexes::configure));
 }
 
@@ -62,6 +87,135 @@ where
         .collect()
 }
 
+/// The header holding the idempotency key of a request registering a task.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "X-Idempotency-Key";
+
+/// Returns the idempotency key sent with `req`, scoped by the API key that sent it.
+pub fn idempotency_key(
+    req: &HttpRequest,
+    filters: &AuthFilter,
+) -> Result<Option<IdempotencyKey>, ResponseError> {
+    match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => {
+            let key = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            Ok(Some(IdempotencyKey::new(filters.key_uid, key)?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Where the routes registering a task read the address of the client from.
+#[derive(Debug, Clone, Default)]
+pub struct TaskOriginConfig {
+    /// The header in which a trusted reverse proxy sets the address of the client. The address
+    /// of the peer is used when it is not set, or when a request doesn't have this header.
+    pub trusted_proxy_header: Option<String>,
+}
+
+/// Returns the origin of the task registered by `req`: the API key, the client address and the
+/// user agent of the request.
+pub fn task_origin(req: &HttpRequest, filters: &AuthFilter) -> TaskOrigin {
+    let forwarded_ip = req
+        .app_data::<TaskOriginConfig>()
+        .and_then(|config| config.trusted_proxy_header.as_deref())
+        .and_then(|header| req.headers().get(header))
+        .and_then(|value| value.to_str().ok())
+        // the proxies append the address they received the request from to the header.
+        .and_then(|value| value.split(',').next())
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty());
+    let client_ip = forwarded_ip.or_else(|| req.peer_addr().map(|addr| addr.ip().to_string()));
+    let user_agent = req
+        .headers()
+        .get(USER_AGENT)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+
+    TaskOrigin {
+        key_uid: filters.key_uid,
+        client_ip,
+        user_agent,
+    }
+}
+
+/// How the routes registering a task describe it in their response.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TaskResponseConfig {
+    /// Also returns the task uid in the deprecated `updateId` and `uid` fields.
+    pub legacy_fields: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacySummarizedTaskView<'a> {
+    #[serde(flatten)]
+    task: &'a SummarizedTaskView,
+    update_id: TaskId,
+    uid: TaskId,
+}
+
+/// The `Location` header pointing at the task `task_uid`.
+fn task_location(task_uid: TaskId) -> (HeaderName, String) {
+    (LOCATION, format!("/tasks/{}", task_uid))
+}
+
+/// Answers `req`, which registered `task`: `202 Accepted` with the summarized task and a
+/// `Location` header pointing at the task. The task is recorded in the audit log.
+pub fn task_accepted(
+    req: &HttpRequest,
+    filters: &AuthFilter,
+    audit: &AuditLog,
+    task: Task,
+) -> HttpResponse {
+    let task = SummarizedTaskView::from(task);
+    audit.record_task(req, filters, &task);
+    debug!("returns: {:?}", task);
+
+    let legacy_fields = req
+        .app_data::<TaskResponseConfig>()
+        .map_or(false, |config| config.legacy_fields);
+
+    let mut response = HttpResponse::Accepted();
+    response.insert_header(task_location(task.task_uid));
+    if legacy_fields {
+        response.json(LegacySummarizedTaskView {
+            task: &task,
+            update_id: task.task_uid,
+            uid: task.task_uid,
+        })
+    } else {
+        response.json(task)
+    }
+}
+
+/// Registers `update` and answers `req`: `202 Accepted` with the summarized task, or `200 OK`
+/// with the whole task if a previous request with the same idempotency key already registered
+/// it. Both answers hold a `Location` header pointing at the task. Only the newly registered
+/// tasks are recorded in the audit log.
+pub async fn register_update<P>(
+    meilisearch: &GuardedData<P, MeiliSearch>,
+    uid: String,
+    update: Update,
+    req: &HttpRequest,
+    audit: &AuditLog,
+) -> Result<HttpResponse, ResponseError> {
+    let key = idempotency_key(req, meilisearch.filters())?;
+    let origin = task_origin(req, meilisearch.filters());
+    let registration = meilisearch
+        .register_update_with_key(uid, update, key, Some(origin))
+        .await?;
+
+    match registration {
+        Registration::Created(task) => Ok(task_accepted(req, meilisearch.filters(), audit, task)),
+        Registration::Replayed(task) => {
+            let location = task_location(task.id);
+            let task = TaskView::from(task);
+
+            debug!("returns: {:?}", task);
+            Ok(HttpResponse::Ok().insert_header(location).json(task))
+        }
+    }
+}
+
 const PAGINATION_DEFAULT_LIMIT: fn() -> usize = || 20;
 
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -228,18 +382,6 @@ pub enum UpdateStatusResponse {
     },
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct IndexUpdateResponse {
-    pub update_id: u64,
-}
-
-impl IndexUpdateResponse {
-    pub fn with_id(update_id: u64) -> Self {
-        Self { update_id }
-    }
-}
-
 /// Always return a 200 with:
 /// ```json
 /// {
@@ -253,13 +395,36 @@ pub async fn running() -> HttpResponse {
 async fn get_stats(
     meilisearch: GuardedData<ActionPolicy<{ actions::STATS_GET }>, MeiliSearch>,
 ) -> Result<HttpResponse, ResponseError> {
-    let search_rules = &meilisearch.filters().search_rules;
-    let response = meilisearch.get_all_stats(search_rules).await?;
+    let response = stats(&meilisearch, &meilisearch.filters().search_rules).await?;
 
     debug!("returns: {:?}", response);
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// The stats of the indexes that can be seen with `search_rules`.
+async fn stats(
+    meilisearch: &MeiliSearch,
+    search_rules: &SearchRules,
+) -> Result<StatsView, ResponseError> {
+    let mut stats = meilisearch.get_all_stats(search_rules).await?;
+    let recent_batches = std::mem::take(&mut stats.recent_batches)
+        .into_iter()
+        .map(BatchView::from)
+        .collect();
+    Ok(StatsView {
+        stats,
+        recent_batches,
+    })
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatsView {
+    #[serde(flatten)]
+    stats: Stats,
+    recent_batches: Vec<BatchView>,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct VersionResponse {
@@ -268,17 +433,74 @@ struct VersionResponse {
     pkg_version: String,
 }
 
+async fn get_config(
+    meilisearch: GuardedData<ActionPolicy<{ actions::CONFIG_GET }>, MeiliSearch>,
+    slow_query_log: web::Data<SlowQueryLog>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(effective_config(&meilisearch, &slow_query_log))
+}
+
+/// The redacted configuration of the instance, with the options changed at runtime.
+fn effective_config(meilisearch: &MeiliSearch, slow_query_log: &SlowQueryLog) -> Value {
+    let mut config = meilisearch.config().clone();
+    // the threshold may have been changed since the launch.
+    if let Some(threshold_ms) = slow_query_log.threshold_ms() {
+        config["slow_query_log_threshold_ms"] = threshold_ms.into();
+    }
+    config
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ConfigUpdate {
+    slow_query_log: Option<SlowQueryLogUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct SlowQueryLogUpdate {
+    threshold_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConfigUpdateView {
+    slow_query_log: SlowQueryLogStatus,
+}
+
+/// Updates the options of the instance that can be changed at runtime.
+async fn update_config(
+    _meilisearch: GuardedData<ActionPolicy<{ actions::CONFIG_UPDATE }>, MeiliSearch>,
+    body: web::Json<ConfigUpdate>,
+    slow_query_log: web::Data<SlowQueryLog>,
+) -> Result<HttpResponse, ResponseError> {
+    debug!("called with params: {:?}", body);
+    if let Some(update) = body.into_inner().slow_query_log {
+        if !slow_query_log.set_threshold_ms(update.threshold_ms) {
+            return Err(MeilisearchHttpError::SlowQueryLogDisabled.into());
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(ConfigUpdateView {
+        slow_query_log: slow_query_log.status(),
+    }))
+}
+
 async fn get_version(
     _meilisearch: GuardedData<ActionPolicy<{ actions::VERSION }>, MeiliSearch>,
 ) -> HttpResponse {
+    HttpResponse::Ok().json(version())
+}
+
+fn version() -> VersionResponse {
     let commit_sha = option_env!("VERGEN_GIT_SHA").unwrap_or("unknown");
     let commit_date = option_env!("VERGEN_GIT_COMMIT_TIMESTAMP").unwrap_or("unknown");
 
-    HttpResponse::Ok().json(VersionResponse {
+    VersionResponse {
         commit_sha: commit_sha.to_string(),
         commit_date: commit_date.to_string(),
         pkg_version: env!("CARGO_PKG_VERSION").to_string(),
-    })
+    }
 }
 
 #[derive(Serialize)]
@@ -0,0 +1,115 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::debug;
+use meilisearch_error::ResponseError;
+use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::analytics::Analytics;
+use crate::audit::AuditLog;
+use crate::extractors::authentication::{policies::*, AuthenticationError, GuardedData};
+use crate::extractors::sequential_extractor::SeqHandler;
+use crate::routes::{task_accepted, task_origin};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("")
+            .route(web::get().to(list_namespaces))
+            .route(web::post().to(SeqHandler(create_namespace))),
+    )
+    .service(
+        web::scope("/{namespace_uid}")
+            .service(
+                web::resource("")
+                    .route(web::get().to(SeqHandler(get_namespace)))
+                    .route(web::delete().to(SeqHandler(delete_namespace))),
+            )
+            .service(web::resource("/stats").route(web::get().to(SeqHandler(get_namespace_stats)))),
+    );
+}
+
+/// Returns an error if the key used by the request is restricted to other namespaces.
+fn check_namespace<P, D>(data: &GuardedData<P, D>, namespace: &str) -> Result<(), ResponseError> {
+    if data.filters().is_namespace_authorized(namespace) {
+        Ok(())
+    } else {
+        Err(AuthenticationError::InvalidToken.into())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NamespaceCreateRequest {
+    uid: String,
+}
+
+pub async fn create_namespace(
+    meilisearch: GuardedData<ActionPolicy<{ actions::NAMESPACES_CREATE }>, MeiliSearch>,
+    body: web::Json<NamespaceCreateRequest>,
+    req: HttpRequest,
+    analytics: web::Data<dyn Analytics>,
+) -> Result<HttpResponse, ResponseError> {
+    let NamespaceCreateRequest { uid } = body.into_inner();
+    check_namespace(&meilisearch, &uid)?;
+
+    analytics.publish("Namespace Created".to_string(), json!({}), Some(&req));
+
+    let namespace = meilisearch.create_namespace(uid).await?;
+    debug!("returns: {:?}", namespace);
+    Ok(HttpResponse::Created().json(namespace))
+}
+
+pub async fn list_namespaces(
+    meilisearch: GuardedData<ActionPolicy<{ actions::NAMESPACES_GET }>, MeiliSearch>,
+) -> Result<HttpResponse, ResponseError> {
+    let filters = meilisearch.filters();
+    let namespaces: Vec<_> = meilisearch
+        .list_namespaces()
+        .await?
+        .into_iter()
+        .filter(|namespace| filters.is_namespace_authorized(&namespace.uid))
+        .collect();
+
+    debug!("returns: {:?}", namespaces);
+    Ok(HttpResponse::Ok().json(namespaces))
+}
+
+pub async fn get_namespace(
+    meilisearch: GuardedData<ActionPolicy<{ actions::NAMESPACES_GET }>, MeiliSearch>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ResponseError> {
+    let uid = path.into_inner();
+    check_namespace(&meilisearch, &uid)?;
+
+    let namespace = meilisearch.get_namespace(uid).await?;
+    debug!("returns: {:?}", namespace);
+    Ok(HttpResponse::Ok().json(namespace))
+}
+
+pub async fn get_namespace_stats(
+    meilisearch: GuardedData<ActionPolicy<{ actions::NAMESPACES_GET }>, MeiliSearch>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ResponseError> {
+    let uid = path.into_inner();
+    check_namespace(&meilisearch, &uid)?;
+
+    let stats = meilisearch.get_namespace_stats(uid).await?;
+    debug!("returns: {:?}", stats);
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+pub async fn delete_namespace(
+    meilisearch: GuardedData<ActionPolicy<{ actions::NAMESPACES_DELETE }>, MeiliSearch>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let uid = path.into_inner();
+    check_namespace(&meilisearch, &uid)?;
+
+    let origin = task_origin(&req, meilisearch.filters());
+    let task = meilisearch
+        .register_namespace_deletion(uid, Some(origin))
+        .await?;
+    Ok(task_accepted(&req, meilisearch.filters(), &audit, task))
+}
@@ -0,0 +1,45 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::debug;
+use meilisearch_error::ResponseError;
+use meilisearch_lib::MeiliSearch;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::analytics::Analytics;
+use crate::audit::AuditLog;
+use crate::extractors::authentication::{policies::*, GuardedData};
+use crate::extractors::sequential_extractor::SeqHandler;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::post().to(SeqHandler(create_snapshot))));
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SnapshotQuery {
+    /// Whether the writes of the snapshot are paced by the IO throttle. A snapshot needed to
+    /// restore an instance is written at full speed with `false`.
+    #[serde(default = "default_throttle")]
+    throttle: bool,
+}
+
+fn default_throttle() -> bool {
+    true
+}
+
+pub async fn create_snapshot(
+    meilisearch: GuardedData<ActionPolicy<{ actions::SNAPSHOTS_CREATE }>, MeiliSearch>,
+    params: web::Query<SnapshotQuery>,
+    req: HttpRequest,
+    analytics: web::Data<dyn Analytics>,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    analytics.publish("Snapshot Created".to_string(), json!({}), Some(&req));
+
+    meilisearch.register_snapshot(params.throttle).await?;
+    audit.record(&req, meilisearch.filters(), None, None);
+
+    let res = json!({ "status": "enqueued" });
+    debug!("returns: {:?}", res);
+    Ok(HttpResponse::Accepted().json(res))
+}
@@ -1,24 +1,46 @@
+use std::collections::HashSet;
+
+use actix_web::http::header;
 use actix_web::{web, HttpRequest, HttpResponse};
-use meilisearch_error::ResponseError;
-use meilisearch_lib::tasks::task::{TaskContent, TaskEvent, TaskId};
-use meilisearch_lib::tasks::TaskFilter;
+use bytes::Bytes;
+use futures::Stream;
+use meilisearch_error::{Code, ResponseError};
+use meilisearch_lib::tasks::task::{Task, TaskEvent, TaskId};
+use meilisearch_lib::tasks::{TaskFilter, TaskOrder};
 use meilisearch_lib::{IndexUid, MeiliSearch};
 use serde::Deserialize;
 use serde_cs::vec::CS;
 use serde_json::json;
 
 use crate::analytics::Analytics;
+use crate::audit::AuditLog;
+use crate::error::MeilisearchHttpError;
 use crate::extractors::authentication::{policies::*, GuardedData};
 use crate::extractors::sequential_extractor::SeqHandler;
+use crate::helpers::Projection;
 use crate::task::{TaskListView, TaskStatus, TaskType, TaskView};
 
-use super::{fold_star_or, StarOr};
+use super::{fold_star_or, task_accepted, task_origin, StarOr};
 
-const DEFAULT_LIMIT: fn() -> usize = || 20;
+const DEFAULT_LIMIT: usize = 20;
+/// The maximum number of tasks listed in a JSON response, which is built in memory at once. Larger
+/// listings must be streamed.
+const MAX_LIMIT: usize = 1000;
+/// The number of tasks read from the store at once when a listing is streamed. The store is only
+/// read while a chunk is read, so that a slow client never keeps a reader of the store.
+const STREAM_CHUNK_SIZE: usize = 100;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("").route(web::get().to(SeqHandler(get_tasks))))
-        .service(web::resource("/{task_id}").route(web::get().to(SeqHandler(get_task))));
+        // registered before `/{task_id}`, which would match its path too.
+        .service(
+            web::resource("/force-reset-processing")
+                .route(web::post().to(SeqHandler(force_reset_processing))),
+        )
+        .service(web::resource("/compact").route(web::post().to(SeqHandler(compact_tasks))))
+        .service(web::resource("/types").route(web::get().to(SeqHandler(get_task_types))))
+        .service(web::resource("/{task_id}").route(web::get().to(SeqHandler(get_task))))
+        .service(web::resource("/{task_id}/cancel").route(web::post().to(SeqHandler(cancel_task))));
 }
 
 #[derive(Deserialize, Debug)]
@@ -28,21 +50,16 @@ pub struct TaskFilterQuery {
     type_: Option<CS<StarOr<TaskType>>>,
     status: Option<CS<StarOr<TaskStatus>>>,
     index_uid: Option<CS<StarOr<IndexUid>>>,
-    #[serde(default = "DEFAULT_LIMIT")]
-    limit: usize,
+    limit: Option<usize>,
+    /// The first listed task, included.
     from: Option<TaskId>,
-}
-
-#[rustfmt::skip]
-fn task_type_matches_content(type_: &TaskType, content: &TaskContent) -> bool {
-    matches!((type_, content),
-          (TaskType::IndexCreation, TaskContent::IndexCreation { .. })
-        | (TaskType::IndexUpdate, TaskContent::IndexUpdate { .. })
-        | (TaskType::IndexDeletion, TaskContent::IndexDeletion { .. })
-        | (TaskType::DocumentAdditionOrUpdate, TaskContent::DocumentAddition { .. })
-        | (TaskType::DocumentDeletion, TaskContent::DocumentDeletion{ .. })
-        | (TaskType::SettingsUpdate, TaskContent::SettingsUpdate { .. })
-    )
+    /// Lists the tasks from the newest one by default, or from the oldest one with `asc`.
+    order: Option<TaskOrder>,
+    /// Streams the tasks as newline-delimited JSON, like the `Accept: application/x-ndjson` header.
+    #[serde(default)]
+    stream: bool,
+    /// Only returns these fields of the tasks.
+    fields: Option<CS<String>>,
 }
 
 #[rustfmt::skip]
@@ -50,13 +67,24 @@ fn task_status_matches_events(status: &TaskStatus, events: &[TaskEvent]) -> bool
     events.last().map_or(false, |event| {
         matches!((status, event),
               (TaskStatus::Enqueued, TaskEvent::Created(_))
-            | (TaskStatus::Processing, TaskEvent::Processing(_) | TaskEvent::Batched { .. })
+            | (TaskStatus::Processing, TaskEvent::Processing(_) | TaskEvent::Batched { .. } | TaskEvent::ChunkProcessed { .. })
             | (TaskStatus::Succeeded, TaskEvent::Succeeded { .. })
             | (TaskStatus::Failed, TaskEvent::Failed { .. }),
         )
     })
 }
 
+/// The view of `task`, `deleted` being the tasks whose index was deleted. The origin of the task
+/// is only shown if `read_sensitive` is set.
+fn task_view(task: Task, deleted: &HashSet<TaskId>, read_sensitive: bool) -> TaskView {
+    let index_deleted = task.index_uid().map(|_| deleted.contains(&task.id));
+    let origin = task.origin.clone().filter(|_| read_sensitive);
+    let mut view = TaskView::from(task);
+    view.index_deleted = index_deleted;
+    view.origin = origin;
+    view
+}
+
 async fn get_tasks(
     meilisearch: GuardedData<ActionPolicy<{ actions::TASKS_GET }>, MeiliSearch>,
     params: web::Query<TaskFilterQuery>,
@@ -75,7 +103,12 @@ async fn get_tasks(
         index_uid,
         limit,
         from,
+        order,
+        stream,
+        fields,
     } = params.into_inner();
+    let order = order.unwrap_or(TaskOrder::Descending);
+    let projection = fields.map(Projection::new);
 
     let search_rules = &meilisearch.filters().search_rules;
 
@@ -113,11 +146,19 @@ async fn get_tasks(
     // Then we complete the task filter with other potential status and types filters.
     let filters = if type_.is_some() || status.is_some() {
         let mut filters = indexes_filters.unwrap_or_default();
+        // The store lists the unfinished tasks without reading the finished ones, so that the
+        // oldest pending task is found right away.
+        let unfinished_only = status.as_ref().map_or(false, |statuses| {
+            statuses
+                .iter()
+                .all(|status| matches!(status, TaskStatus::Enqueued | TaskStatus::Processing))
+        });
+        if unfinished_only {
+            filters.filter_unfinished();
+        }
         filters.filter_fn(move |task| {
             let matches_type = match &type_ {
-                Some(types) => types
-                    .iter()
-                    .any(|t| task_type_matches_content(t, &task.content)),
+                Some(types) => types.contains(&TaskType::of(&task.content)),
                 None => true,
             };
 
@@ -135,16 +176,48 @@ async fn get_tasks(
         indexes_filters
     };
 
+    if stream || accepts_ndjson(&req) {
+        let read_sensitive = meilisearch.filters().read_sensitive_tasks;
+        let tasks = stream_tasks(
+            MeiliSearch::clone(&meilisearch),
+            filters,
+            limit,
+            from,
+            order,
+            read_sensitive,
+            projection,
+        );
+        return Ok(HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .streaming(tasks));
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    if limit > MAX_LIMIT {
+        return Err(MeilisearchHttpError::TasksLimitTooLarge {
+            limit,
+            max: MAX_LIMIT,
+        }
+        .into());
+    }
+
     // We +1 just to know if there is more after this "page" or not.
     let limit = limit.saturating_add(1);
 
-    let mut tasks_results: Vec<_> = meilisearch
-        .list_tasks(filters, Some(limit), from)
-        .await?
+    let tasks = meilisearch
+        .list_tasks(filters, Some(limit), from, order)
+        .await?;
+    let deleted = meilisearch.tasks_of_deleted_indexes(&tasks).await?;
+    let read_sensitive = meilisearch.filters().read_sensitive_tasks;
+    let mut tasks_results: Vec<_> = tasks
         .into_iter()
-        .map(TaskView::from)
+        .map(|task| task_view(task, &deleted, read_sensitive))
         .collect();
 
+    for task in tasks_results.iter_mut() {
+        task.progress = meilisearch.task_progress(task.uid).await;
+    }
+
     // If we were able to fetch the number +1 tasks we asked
     // it means that there is more to come.
     let next = if tasks_results.len() == limit {
@@ -162,7 +235,77 @@ async fn get_tasks(
         next,
     };
 
-    Ok(HttpResponse::Ok().json(tasks))
+    match projection {
+        Some(projection) => projection.listing_response(&tasks),
+        None => Ok(HttpResponse::Ok().json(tasks)),
+    }
+}
+
+/// Whether the request accepts the tasks as newline-delimited JSON.
+fn accepts_ndjson(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map_or(false, |accept| {
+            accept
+                .split(',')
+                .any(|media| media.trim().starts_with("application/x-ndjson"))
+        })
+}
+
+/// Streams the tasks listed with `filters`, one task per line, all of them if there is no `limit`.
+/// The tasks are read from the store chunk by chunk, only when the client consumed the previous
+/// chunk. Each chunk is read with its own transaction, from past the last task of the previous
+/// chunk in `order`. The tasks are reduced to the fields of `projection`, but the unknown fields can't be
+/// reported once the response started.
+fn stream_tasks(
+    meilisearch: MeiliSearch,
+    filters: Option<TaskFilter>,
+    limit: Option<usize>,
+    mut from: Option<TaskId>,
+    order: TaskOrder,
+    read_sensitive: bool,
+    projection: Option<Projection>,
+) -> impl Stream<Item = Result<Bytes, ResponseError>> {
+    async_stream::try_stream! {
+        let mut remaining = limit.unwrap_or(usize::MAX);
+        while remaining > 0 {
+            let chunk_size = remaining.min(STREAM_CHUNK_SIZE);
+            let tasks = meilisearch
+                .list_tasks(filters.clone(), Some(chunk_size), from, order)
+                .await?;
+            let deleted = meilisearch.tasks_of_deleted_indexes(&tasks).await?;
+
+            let last = tasks.last().map(|task| task.id);
+            let exhausted = tasks.len() < chunk_size;
+            remaining -= tasks.len();
+
+            let mut lines = Vec::new();
+            for task in tasks {
+                let mut view = task_view(task, &deleted, read_sensitive);
+                view.progress = meilisearch.task_progress(view.uid).await;
+                match projection {
+                    Some(ref projection) => {
+                        let (view, _) = projection.project(&view)?;
+                        serde_json::to_writer(&mut lines, &view)
+                    }
+                    None => serde_json::to_writer(&mut lines, &view),
+                }
+                .map_err(|e| ResponseError::from_msg(e.to_string(), Code::Internal))?;
+                lines.push(b'\n');
+            }
+            yield Bytes::from(lines);
+
+            let next = match order {
+                TaskOrder::Ascending => last.and_then(|last| last.checked_add(1)),
+                TaskOrder::Descending => last.and_then(|last| last.checked_sub(1)),
+            };
+            match next {
+                Some(next) if !exhausted => from = Some(next),
+                _ => break,
+            }
+        }
+    }
 }
 
 async fn get_task(
@@ -188,10 +331,87 @@ async fn get_task(
         Some(filters)
     };
 
-    let task: TaskView = meilisearch
-        .get_task(task_id.into_inner(), filters)
-        .await?
-        .into();
+    let task = meilisearch.get_task(task_id.into_inner(), filters).await?;
+    let deleted = meilisearch
+        .tasks_of_deleted_indexes(std::slice::from_ref(&task))
+        .await?;
+    let read_sensitive = meilisearch.filters().read_sensitive_tasks;
+    let mut task = task_view(task, &deleted, read_sensitive);
+    task.progress = meilisearch.task_progress(task.uid).await;
 
     Ok(HttpResponse::Ok().json(task))
 }
+
+async fn cancel_task(
+    meilisearch: GuardedData<ActionPolicy<{ actions::TASKS_CANCEL }>, MeiliSearch>,
+    task_id: web::Path<TaskId>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let search_rules = &meilisearch.filters().search_rules;
+    let filters = if search_rules.is_index_authorized("*") {
+        None
+    } else {
+        let mut filters = TaskFilter::default();
+        for (index, _policy) in search_rules.clone() {
+            filters.filter_index(index);
+        }
+        Some(filters)
+    };
+
+    let task = meilisearch
+        .cancel_task(task_id.into_inner(), filters)
+        .await?;
+    audit.record(
+        &req,
+        meilisearch.filters(),
+        task.index_uid().map(String::from),
+        Some(task.id),
+    );
+    let task: TaskView = task.into();
+
+    Ok(HttpResponse::Ok().json(task))
+}
+
+/// Aborts the batch being processed when it stopped showing any sign of life for the configured
+/// grace period, failing its tasks, so that the next batches are processed without restarting
+/// the engine.
+async fn force_reset_processing(
+    meilisearch: GuardedData<ActionPolicy<{ actions::TASKS_FORCE_RESET }>, MeiliSearch>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let tasks = meilisearch.force_reset_processing().await?;
+    let task_uids = tasks.iter().map(|task| task.id).collect();
+    audit.record_tasks(&req, meilisearch.filters(), task_uids);
+
+    log::warn!(
+        "The batch being processed was reset by an administrator, {} tasks were aborted.",
+        tasks.len()
+    );
+    let tasks: Vec<TaskView> = tasks.into_iter().map(TaskView::from).collect();
+
+    Ok(HttpResponse::Ok().json(json!({ "abortedTasks": tasks })))
+}
+
+/// Enqueues the compaction of the tasks database, which gives the pages freed by the deleted tasks
+/// and indexes back to the disk. The tasks can't be read nor registered while it is processed.
+async fn compact_tasks(
+    meilisearch: GuardedData<ActionPolicy<{ actions::TASKS_COMPACT }>, MeiliSearch>,
+    req: HttpRequest,
+    audit: web::Data<AuditLog>,
+) -> Result<HttpResponse, ResponseError> {
+    let origin = task_origin(&req, meilisearch.filters());
+    let task = meilisearch
+        .register_task_store_compaction(Some(origin))
+        .await?;
+    Ok(task_accepted(&req, meilisearch.filters(), &audit, task))
+}
+
+/// Lists the types a task can have, which are the values the `type` filter accepts.
+async fn get_task_types(
+    _meilisearch: GuardedData<ActionPolicy<{ actions::TASKS_GET }>, MeiliSearch>,
+) -> Result<HttpResponse, ResponseError> {
+    let types: Vec<_> = TaskType::ALL.iter().map(TaskType::name).collect();
+    Ok(HttpResponse::Ok().json(json!({ "results": types })))
+}
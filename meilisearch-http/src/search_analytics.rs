@@ -0,0 +1,457 @@
+//! Built-in analytics of the searches: the most frequent queries, the most frequent queries
+//! without any hit, and the latency percentiles of each index, over the last hour and the last day.
+//!
+//! The searches are aggregated in memory, in time buckets that are dropped as they leave the
+//! windows. The queries of a bucket are counted with the space-saving algorithm: a bucket counts
+//! at most `QUERIES_PER_BUCKET` distinct queries, a new query replacing the least frequent one, so
+//! the counts of the rare queries are approximate but the memory is bounded whatever the number of
+//! distinct queries. The latencies are counted in a fixed histogram. Nothing is persisted: the
+//! analytics are lost on restart.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::Opt;
+
+/// The number of distinct queries counted in a bucket.
+const QUERIES_PER_BUCKET: usize = 100;
+/// The queries are truncated to this number of characters before being counted.
+const MAX_QUERY_LENGTH: usize = 256;
+/// The upper bounds of the buckets of the latency histogram, in milliseconds.
+const LATENCY_BOUNDS_MS: [u64; 16] = [
+    1, 2, 5, 10, 20, 50, 100, 200, 300, 500, 750, 1000, 2000, 5000, 10000, 30000,
+];
+
+/// The search analytics, by the path of the database they are aggregated for. The http workers
+/// serving a database share the same analytics.
+static SEARCH_ANALYTICS: Lazy<Mutex<HashMap<PathBuf, SearchAnalytics>>> =
+    Lazy::new(Default::default);
+
+/// Counts the most frequent queries with a bounded number of counters.
+#[derive(Debug, Default)]
+struct TopQueries {
+    counts: HashMap<String, u64>,
+}
+
+impl TopQueries {
+    fn add(&mut self, q: &str) {
+        if let Some(count) = self.counts.get_mut(q) {
+            *count += 1;
+            return;
+        }
+        let mut count = 1;
+        if self.counts.len() >= QUERIES_PER_BUCKET {
+            // the new query inherits the count of the least frequent one, which is an upper bound
+            // of the number of times it was seen.
+            let least = self
+                .counts
+                .iter()
+                .min_by_key(|(_, count)| **count)
+                .map(|(q, count)| (q.clone(), *count));
+            if let Some((least, least_count)) = least {
+                self.counts.remove(&least);
+                count += least_count;
+            }
+        }
+        self.counts.insert(q.to_string(), count);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Latencies {
+    /// The number of searches by bucket, the last bucket being over the last bound.
+    counts: [u64; LATENCY_BOUNDS_MS.len() + 1],
+    max_ms: u64,
+}
+
+impl Latencies {
+    fn add(&mut self, duration: Duration) {
+        let ms = duration.as_millis() as u64;
+        let bucket = LATENCY_BOUNDS_MS.partition_point(|&bound| bound < ms);
+        self.counts[bucket] += 1;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    fn merge(&mut self, other: &Latencies) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self.max_ms = self.max_ms.max(other.max_ms);
+    }
+
+    /// The upper bound of the bucket of the `percentile`th search, `None` without any search.
+    fn percentile(&self, percentile: f64) -> Option<u64> {
+        let total: u64 = self.counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((percentile * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = LATENCY_BOUNDS_MS.get(bucket).copied();
+                return Some(bound.map_or(self.max_ms, |bound| bound.min(self.max_ms)));
+            }
+        }
+        Some(self.max_ms)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    searches: u64,
+    zero_result_searches: u64,
+    queries: TopQueries,
+    zero_result_queries: TopQueries,
+    latencies: Latencies,
+}
+
+/// The buckets of a rolling window, the oldest first.
+#[derive(Debug)]
+struct Window {
+    bucket_secs: i64,
+    len: i64,
+    buckets: VecDeque<(i64, Bucket)>,
+}
+
+impl Window {
+    fn new(bucket_secs: i64, len: i64) -> Self {
+        Self {
+            bucket_secs,
+            len,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    fn bucket_id(&self, at: OffsetDateTime) -> i64 {
+        at.unix_timestamp().div_euclid(self.bucket_secs)
+    }
+
+    fn bucket_mut(&mut self, at: OffsetDateTime) -> &mut Bucket {
+        let id = self.bucket_id(at);
+        while self
+            .buckets
+            .front()
+            .map_or(false, |(first, _)| *first <= id - self.len)
+        {
+            self.buckets.pop_front();
+        }
+        if self.buckets.back().map_or(true, |(last, _)| *last < id) {
+            self.buckets.push_back((id, Bucket::default()));
+        }
+        // a search recorded with a clock that went back is counted in the last bucket.
+        &mut self.buckets.back_mut().unwrap().1
+    }
+
+    fn view(&self, at: OffsetDateTime, limit: usize) -> WindowView {
+        let id = self.bucket_id(at);
+        let buckets = self
+            .buckets
+            .iter()
+            .filter(|(bucket_id, _)| *bucket_id > id - self.len)
+            .map(|(_, bucket)| bucket);
+
+        let mut searches = 0;
+        let mut zero_result_searches = 0;
+        let mut queries: HashMap<&str, u64> = HashMap::new();
+        let mut zero_result_queries: HashMap<&str, u64> = HashMap::new();
+        let mut latencies = Latencies::default();
+        for bucket in buckets {
+            searches += bucket.searches;
+            zero_result_searches += bucket.zero_result_searches;
+            for (q, count) in &bucket.queries.counts {
+                *queries.entry(q).or_default() += count;
+            }
+            for (q, count) in &bucket.zero_result_queries.counts {
+                *zero_result_queries.entry(q).or_default() += count;
+            }
+            latencies.merge(&bucket.latencies);
+        }
+
+        WindowView {
+            searches,
+            zero_result_searches,
+            top_queries: top(queries, limit),
+            top_zero_result_queries: top(zero_result_queries, limit),
+            latency: LatencyView {
+                p50_ms: latencies.percentile(0.5),
+                p90_ms: latencies.percentile(0.9),
+                p99_ms: latencies.percentile(0.99),
+                max_ms: (searches > 0).then(|| latencies.max_ms),
+            },
+        }
+    }
+}
+
+/// The `limit` most frequent queries of `counts`, the most frequent first.
+fn top(counts: HashMap<&str, u64>, limit: usize) -> Vec<QueryCount> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    counts
+        .into_iter()
+        .take(limit)
+        .map(|(q, count)| QueryCount {
+            q: q.to_string(),
+            count,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct IndexAnalytics {
+    last_hour: Window,
+    last_day: Window,
+}
+
+impl Default for IndexAnalytics {
+    fn default() -> Self {
+        Self {
+            // twelve buckets of five minutes.
+            last_hour: Window::new(5 * 60, 12),
+            // twenty-four buckets of an hour.
+            last_day: Window::new(60 * 60, 24),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct QueryCount {
+    pub q: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyView {
+    p50_ms: Option<u64>,
+    p90_ms: Option<u64>,
+    p99_ms: Option<u64>,
+    max_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowView {
+    searches: u64,
+    zero_result_searches: u64,
+    top_queries: Vec<QueryCount>,
+    top_zero_result_queries: Vec<QueryCount>,
+    latency: LatencyView,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchAnalyticsView {
+    index_uid: String,
+    last_hour: WindowView,
+    last_day: WindowView,
+}
+
+/// Handle to the search analytics. Does nothing if the search analytics are disabled.
+#[derive(Clone, Default)]
+pub struct SearchAnalytics {
+    inner: Option<Arc<Mutex<HashMap<String, IndexAnalytics>>>>,
+}
+
+impl SearchAnalytics {
+    /// Returns the search analytics of the database configured by the options, if they are
+    /// enabled.
+    pub fn from_opt(opt: &Opt) -> Self {
+        if !opt.search_analytics {
+            return Self::default();
+        }
+        SEARCH_ANALYTICS
+            .lock()
+            .entry(opt.db_path.clone())
+            .or_insert_with(|| Self {
+                inner: Some(Default::default()),
+            })
+            .clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Records a search of `index_uid` for `q`, which took `duration` and found
+    /// `estimated_total_hits` documents.
+    pub fn record(
+        &self,
+        index_uid: &str,
+        q: Option<&str>,
+        duration: Duration,
+        estimated_total_hits: u64,
+    ) {
+        self.record_at(
+            OffsetDateTime::now_utc(),
+            index_uid,
+            q,
+            duration,
+            estimated_total_hits,
+        )
+    }
+
+    fn record_at(
+        &self,
+        at: OffsetDateTime,
+        index_uid: &str,
+        q: Option<&str>,
+        duration: Duration,
+        estimated_total_hits: u64,
+    ) {
+        let inner = match self.inner {
+            Some(ref inner) => inner,
+            None => return,
+        };
+        // the placeholder searches are counted, but have no query.
+        let q = q
+            .map(|q| q.trim().to_lowercase())
+            .filter(|q| !q.is_empty())
+            .map(|q| q.chars().take(MAX_QUERY_LENGTH).collect::<String>());
+
+        let mut indexes = inner.lock();
+        let index = indexes.entry(index_uid.to_string()).or_default();
+        for window in [&mut index.last_hour, &mut index.last_day] {
+            let bucket = window.bucket_mut(at);
+            bucket.searches += 1;
+            bucket.latencies.add(duration);
+            if let Some(ref q) = q {
+                bucket.queries.add(q);
+            }
+            if estimated_total_hits == 0 {
+                bucket.zero_result_searches += 1;
+                if let Some(ref q) = q {
+                    bucket.zero_result_queries.add(q);
+                }
+            }
+        }
+    }
+
+    /// The analytics of the searches of `index_uid`, with the `limit` most frequent queries.
+    /// Returns `None` if the search analytics are disabled.
+    pub fn view(&self, index_uid: &str, limit: usize) -> Option<SearchAnalyticsView> {
+        self.view_at(OffsetDateTime::now_utc(), index_uid, limit)
+    }
+
+    fn view_at(
+        &self,
+        at: OffsetDateTime,
+        index_uid: &str,
+        limit: usize,
+    ) -> Option<SearchAnalyticsView> {
+        let indexes = self.inner.as_ref()?.lock();
+        let index = indexes.get(index_uid);
+        let empty = IndexAnalytics::default();
+        let index = index.unwrap_or(&empty);
+        Some(SearchAnalyticsView {
+            index_uid: index_uid.to_string(),
+            last_hour: index.last_hour.view(at, limit),
+            last_day: index.last_day.view(at, limit),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn enabled() -> SearchAnalytics {
+        SearchAnalytics {
+            inner: Some(Default::default()),
+        }
+    }
+
+    fn query_counts(counts: &[(&str, u64)]) -> Vec<QueryCount> {
+        counts
+            .iter()
+            .map(|(q, count)| QueryCount {
+                q: q.to_string(),
+                count: *count,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_windows_roll() {
+        let analytics = enabled();
+        let start = datetime!(2022-06-01 12:00 UTC);
+        let ms = Duration::from_millis;
+
+        analytics.record_at(start, "movies", Some("Carol"), ms(3), 2);
+        analytics.record_at(start, "movies", Some(" carol "), ms(8), 2);
+        analytics.record_at(start, "movies", Some("wonder"), ms(40), 0);
+        analytics.record_at(start, "movies", None, ms(150), 0);
+        analytics.record_at(start, "books", Some("carol"), ms(1), 1);
+
+        let view = analytics.view_at(start, "movies", 10).unwrap();
+        assert_eq!(view.last_hour.searches, 4);
+        assert_eq!(view.last_hour.zero_result_searches, 2);
+        assert_eq!(
+            view.last_hour.top_queries,
+            query_counts(&[("carol", 2), ("wonder", 1)])
+        );
+        assert_eq!(
+            view.last_hour.top_zero_result_queries,
+            query_counts(&[("wonder", 1)])
+        );
+        assert_eq!(view.last_hour.latency.p50_ms, Some(10));
+        assert_eq!(view.last_hour.latency.p99_ms, Some(150));
+        assert_eq!(view.last_hour.latency.max_ms, Some(150));
+
+        let view = analytics.view_at(start, "movies", 1).unwrap();
+        assert_eq!(view.last_day.top_queries, query_counts(&[("carol", 2)]));
+
+        // two hours later, the searches left the last hour but not the last day.
+        let later = start + time::Duration::hours(2);
+        analytics.record_at(later, "movies", Some("wonder"), ms(1), 1);
+        let view = analytics.view_at(later, "movies", 10).unwrap();
+        assert_eq!(view.last_hour.searches, 1);
+        assert_eq!(view.last_hour.top_queries, query_counts(&[("wonder", 1)]));
+        assert_eq!(view.last_day.searches, 5);
+        assert_eq!(
+            view.last_day.top_queries,
+            query_counts(&[("carol", 2), ("wonder", 2)])
+        );
+
+        // a day later, nothing is left.
+        let view = analytics
+            .view_at(later + time::Duration::days(1), "movies", 10)
+            .unwrap();
+        assert_eq!(view.last_day.searches, 0);
+        assert_eq!(view.last_day.latency.p50_ms, None);
+        assert!(view.last_day.top_queries.is_empty());
+    }
+
+    #[test]
+    fn test_memory_is_bounded() {
+        let analytics = enabled();
+        let at = datetime!(2022-06-01 12:00 UTC);
+        for _ in 0..20 {
+            analytics.record_at(at, "movies", Some("frequent"), Duration::ZERO, 1);
+        }
+        for i in 0..5 * QUERIES_PER_BUCKET {
+            let q = format!("rare {}", i);
+            analytics.record_at(at, "movies", Some(&q), Duration::ZERO, 1);
+        }
+
+        let indexes = analytics.inner.as_ref().unwrap().lock();
+        let window = &indexes["movies"].last_hour;
+        assert_eq!(window.buckets[0].1.queries.counts.len(), QUERIES_PER_BUCKET);
+        drop(indexes);
+
+        let view = analytics.view_at(at, "movies", 1).unwrap();
+        assert_eq!(view.last_hour.top_queries[0].q, "frequent");
+
+        assert!(SearchAnalytics::default().view("movies", 10).is_none());
+    }
+}
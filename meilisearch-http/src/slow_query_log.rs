@@ -0,0 +1,286 @@
+//! Log of the searches slower than a configurable threshold.
+//!
+//! Each slow search is recorded as one JSON object per line in a dedicated file, rotated like the
+//! audit log, whatever the log level of the instance. Records are sent to a dedicated writer
+//! thread through a bounded channel: when the writer can't keep up, the records are dropped and
+//! counted instead of slowing the searches down further. The threshold can be changed at runtime
+//! with `PATCH /config`.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use meilisearch_lib::index::{SearchQuery, SearchResult};
+use meilisearch_lib::rotating_file::RotatingFile;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::Opt;
+
+/// The number of records waiting to be written over which new records are dropped.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// The slow query logs currently opened, by path. A single writer must exist for a given file,
+/// whatever the number of http workers that were configured with it.
+static SLOW_QUERY_LOGS: Lazy<Mutex<HashMap<PathBuf, SlowQueryLog>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowQueryRecord {
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+    index_uid: String,
+    key_uid: Option<Uuid>,
+    q: Option<String>,
+    filter: Option<Value>,
+    sort: Option<Vec<String>>,
+    facets: Option<Vec<String>>,
+    offset: Option<usize>,
+    limit: usize,
+    /// The time spent answering the request, waiting for a search slot included.
+    duration_ms: u128,
+    /// The time spent by the engine performing the search, unknown if it didn't complete.
+    processing_time_ms: Option<u128>,
+    estimated_total_hits: Option<u64>,
+    truncated_query_expansion: bool,
+    timed_out: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowQueryLogStatus {
+    enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threshold_ms: Option<u64>,
+    /// The number of records dropped because the writer couldn't keep up.
+    dropped_records: u64,
+}
+
+struct Inner {
+    path: PathBuf,
+    sender: Sender<SlowQueryRecord>,
+    threshold_ms: AtomicU64,
+    dropped_records: AtomicU64,
+}
+
+/// Handle to the slow query log writer. Does nothing if the slow query log is disabled.
+#[derive(Clone, Default)]
+pub struct SlowQueryLog {
+    inner: Option<Arc<Inner>>,
+}
+
+impl SlowQueryLog {
+    /// Returns the slow query log configured by the options, spawning its writer if it
+    /// doesn't exist yet.
+    pub fn from_opt(opt: &Opt) -> io::Result<Self> {
+        let path = match opt.slow_query_log_path {
+            Some(ref path) => path.clone(),
+            None => return Ok(Self::default()),
+        };
+
+        let mut slow_query_logs = SLOW_QUERY_LOGS.lock();
+        if let Some(slow_query_log) = slow_query_logs.get(&path) {
+            return Ok(slow_query_log.clone());
+        }
+
+        let file = RotatingFile::new(
+            path.clone(),
+            opt.slow_query_log_max_size.get_bytes() as u64,
+            opt.slow_query_log_max_files,
+        )?;
+        let (sender, receiver) = bounded(CHANNEL_CAPACITY);
+        std::thread::Builder::new()
+            .name("slow-query-log".to_string())
+            .spawn(move || run_writer(file, receiver))?;
+
+        let slow_query_log = Self {
+            inner: Some(Arc::new(Inner {
+                path: path.clone(),
+                sender,
+                threshold_ms: AtomicU64::new(opt.slow_query_log_threshold_ms),
+                dropped_records: AtomicU64::new(0),
+            })),
+        };
+        slow_query_logs.insert(path, slow_query_log.clone());
+
+        Ok(slow_query_log)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// The path of the file the records are currently written to.
+    pub fn path(&self) -> Option<&Path> {
+        self.inner.as_ref().map(|inner| inner.path.as_path())
+    }
+
+    pub fn threshold_ms(&self) -> Option<u64> {
+        let inner = self.inner.as_ref()?;
+        Some(inner.threshold_ms.load(Ordering::Relaxed))
+    }
+
+    /// Sets the threshold over which the searches are recorded. Returns `false` if the slow query
+    /// log is disabled.
+    pub fn set_threshold_ms(&self, threshold_ms: u64) -> bool {
+        match self.inner {
+            Some(ref inner) => {
+                inner.threshold_ms.store(threshold_ms, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn status(&self) -> SlowQueryLogStatus {
+        SlowQueryLogStatus {
+            enabled: self.is_enabled(),
+            path: self.inner.as_ref().map(|inner| inner.path.clone()),
+            threshold_ms: self.threshold_ms(),
+            dropped_records: self
+                .inner
+                .as_ref()
+                .map_or(0, |inner| inner.dropped_records.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Records the search if it took longer than the threshold. The record is written
+    /// asynchronously, this method never blocks.
+    pub fn record(
+        &self,
+        index_uid: &str,
+        key_uid: Option<Uuid>,
+        query: &SearchQuery,
+        duration: Duration,
+        result: Option<&SearchResult>,
+        timed_out: bool,
+    ) {
+        let inner = match self.inner {
+            Some(ref inner) => inner,
+            None => return,
+        };
+
+        if duration.as_millis() < inner.threshold_ms.load(Ordering::Relaxed) as u128 {
+            return;
+        }
+
+        let record = SlowQueryRecord {
+            timestamp: OffsetDateTime::now_utc(),
+            index_uid: index_uid.to_string(),
+            key_uid,
+            q: query.q.clone(),
+            filter: query.filter.clone(),
+            sort: query.sort.clone(),
+            facets: query.facets.clone(),
+            offset: query.offset,
+            limit: query.limit,
+            duration_ms: duration.as_millis(),
+            processing_time_ms: result.map(|r| r.processing_time_ms),
+            estimated_total_hits: result.map(|r| r.estimated_total_hits),
+            truncated_query_expansion: result.map_or(false, |r| r.truncated_query_expansion),
+            timed_out,
+        };
+
+        match inner.sender.try_send(record) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                inner.dropped_records.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                log::error!("The slow query log writer is not running anymore.");
+            }
+        }
+    }
+}
+
+fn run_writer(mut file: RotatingFile, receiver: Receiver<SlowQueryRecord>) {
+    for record in receiver {
+        if let Err(e) = file.write_json(&record) {
+            log::error!("Error while writing to the slow query log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn slow_query_log(threshold_ms: u64) -> (SlowQueryLog, Receiver<SlowQueryRecord>) {
+        let (sender, receiver) = bounded(2);
+        let slow_query_log = SlowQueryLog {
+            inner: Some(Arc::new(Inner {
+                path: PathBuf::from("slow-queries.log"),
+                sender,
+                threshold_ms: AtomicU64::new(threshold_ms),
+                dropped_records: AtomicU64::new(0),
+            })),
+        };
+        (slow_query_log, receiver)
+    }
+
+    #[test]
+    fn test_only_slow_searches_are_recorded() {
+        let (slow_query_log, receiver) = slow_query_log(100);
+        let query = SearchQuery {
+            q: Some("hello".to_string()),
+            ..Default::default()
+        };
+
+        slow_query_log.record(
+            "movies",
+            None,
+            &query,
+            Duration::from_millis(99),
+            None,
+            false,
+        );
+        assert!(receiver.try_recv().is_err());
+
+        slow_query_log.record(
+            "movies",
+            None,
+            &query,
+            Duration::from_millis(100),
+            None,
+            true,
+        );
+        let record = receiver.try_recv().unwrap();
+        assert_eq!(record.index_uid, "movies");
+        assert_eq!(record.q.as_deref(), Some("hello"));
+        assert!(record.timed_out);
+
+        assert!(slow_query_log.set_threshold_ms(1000));
+        slow_query_log.record(
+            "movies",
+            None,
+            &query,
+            Duration::from_millis(100),
+            None,
+            false,
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_records_are_dropped_when_overwhelmed() {
+        let (slow_query_log, _receiver) = slow_query_log(0);
+        let query = SearchQuery::default();
+
+        for _ in 0..5 {
+            slow_query_log.record("movies", None, &query, Duration::ZERO, None, false);
+        }
+
+        // the channel holds two records.
+        assert_eq!(slow_query_log.status().dropped_records, 3);
+    }
+}
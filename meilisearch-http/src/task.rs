@@ -3,17 +3,25 @@ use std::str::FromStr;
 use std::write;
 
 use meilisearch_error::ResponseError;
-use meilisearch_lib::index::{Settings, Unchecked};
-use meilisearch_lib::tasks::batch::BatchId;
+use meilisearch_lib::index::{
+    DocumentEdit, DocumentsDiff, Finding, QueryPreview, Settings, StatsRepairReport,
+    TransformsResult, Unchecked,
+};
+use meilisearch_lib::tasks::batch::{BatchId, BatchOutcome, BatchResult, BatchStopReason};
+use meilisearch_lib::tasks::progress::TaskProgress;
 use meilisearch_lib::tasks::task::{
-    DocumentDeletion, Task, TaskContent, TaskEvent, TaskId, TaskResult,
+    DocumentDeletion, SettingsWarning, Task, TaskContent, TaskEvent, TaskId, TaskOrigin, TaskResult,
 };
+use meilisearch_lib::throttle::ThrottleReport;
 use serde::{Deserialize, Serialize, Serializer};
 use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
 
 use crate::AUTOBATCHING_ENABLED;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The public taxonomy of the tasks, shown in the `type` of a task and used to filter them. These
+/// names are part of the API: a variant can be added, but never renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum TaskType {
     IndexCreation,
@@ -22,19 +30,78 @@ pub enum TaskType {
     DocumentAdditionOrUpdate,
     DocumentDeletion,
     SettingsUpdate,
+    SettingsPreview,
     DumpCreation,
+    DocumentsCompaction,
+    IndexCheck,
+    StatsRepair,
+    IndexRebuild,
+    NamespaceDeletion,
+    DocumentEdition,
+    TaskStoreCompaction,
 }
 
-impl From<TaskContent> for TaskType {
-    fn from(other: TaskContent) -> Self {
-        match other {
+impl TaskType {
+    /// All the task types, as listed by `GET /tasks/types`.
+    pub const ALL: [TaskType; 15] = [
+        TaskType::DocumentAdditionOrUpdate,
+        TaskType::DocumentDeletion,
+        TaskType::DocumentEdition,
+        TaskType::DocumentsCompaction,
+        TaskType::SettingsUpdate,
+        TaskType::SettingsPreview,
+        TaskType::IndexCreation,
+        TaskType::IndexUpdate,
+        TaskType::IndexDeletion,
+        TaskType::IndexCheck,
+        TaskType::StatsRepair,
+        TaskType::IndexRebuild,
+        TaskType::NamespaceDeletion,
+        TaskType::DumpCreation,
+        TaskType::TaskStoreCompaction,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskType::IndexCreation => "indexCreation",
+            TaskType::IndexUpdate => "indexUpdate",
+            TaskType::IndexDeletion => "indexDeletion",
+            TaskType::DocumentAdditionOrUpdate => "documentAdditionOrUpdate",
+            TaskType::DocumentDeletion => "documentDeletion",
+            TaskType::SettingsUpdate => "settingsUpdate",
+            TaskType::SettingsPreview => "settingsPreview",
+            TaskType::DumpCreation => "dumpCreation",
+            TaskType::DocumentsCompaction => "documentsCompaction",
+            TaskType::IndexCheck => "indexCheck",
+            TaskType::StatsRepair => "statsRepair",
+            TaskType::IndexRebuild => "indexRebuild",
+            TaskType::NamespaceDeletion => "namespaceDeletion",
+            TaskType::DocumentEdition => "documentEdition",
+            TaskType::TaskStoreCompaction => "taskStoreCompaction",
+        }
+    }
+
+    /// The type of a task with this `content`. It is the only place a content is mapped to its
+    /// type: the match is exhaustive so that a new kind of task can't be left out of the taxonomy.
+    pub fn of(content: &TaskContent) -> Self {
+        match content {
             TaskContent::IndexCreation { .. } => TaskType::IndexCreation,
             TaskContent::IndexUpdate { .. } => TaskType::IndexUpdate,
             TaskContent::IndexDeletion { .. } => TaskType::IndexDeletion,
-            TaskContent::DocumentAddition { .. } => TaskType::DocumentAdditionOrUpdate,
+            TaskContent::DocumentAddition { .. } | TaskContent::DocumentAdditionFromUrl { .. } => {
+                TaskType::DocumentAdditionOrUpdate
+            }
             TaskContent::DocumentDeletion { .. } => TaskType::DocumentDeletion,
             TaskContent::SettingsUpdate { .. } => TaskType::SettingsUpdate,
+            TaskContent::SettingsPreview { .. } => TaskType::SettingsPreview,
             TaskContent::Dump { .. } => TaskType::DumpCreation,
+            TaskContent::DocumentsCompaction { .. } => TaskType::DocumentsCompaction,
+            TaskContent::IndexCheck { .. } => TaskType::IndexCheck,
+            TaskContent::StatsRepair { .. } => TaskType::StatsRepair,
+            TaskContent::IndexRebuild { .. } => TaskType::IndexRebuild,
+            TaskContent::NamespaceDeletion { .. } => TaskType::NamespaceDeletion,
+            TaskContent::DocumentEdition { .. } => TaskType::DocumentEdition,
+            TaskContent::TaskStoreCompaction => TaskType::TaskStoreCompaction,
         }
     }
 }
@@ -42,29 +109,18 @@ impl From<TaskContent> for TaskType {
 impl FromStr for TaskType {
     type Err = String;
 
-    fn from_str(status: &str) -> Result<Self, String> {
-        if status.eq_ignore_ascii_case("indexCreation") {
-            Ok(TaskType::IndexCreation)
-        } else if status.eq_ignore_ascii_case("indexUpdate") {
-            Ok(TaskType::IndexUpdate)
-        } else if status.eq_ignore_ascii_case("indexDeletion") {
-            Ok(TaskType::IndexDeletion)
-        } else if status.eq_ignore_ascii_case("documentAdditionOrUpdate") {
-            Ok(TaskType::DocumentAdditionOrUpdate)
-        } else if status.eq_ignore_ascii_case("documentDeletion") {
-            Ok(TaskType::DocumentDeletion)
-        } else if status.eq_ignore_ascii_case("settingsUpdate") {
-            Ok(TaskType::SettingsUpdate)
-        } else if status.eq_ignore_ascii_case("dumpCreation") {
-            Ok(TaskType::DumpCreation)
-        } else {
-            Err(format!(
-                "invalid task type `{}`, expecting one of: \
-                indexCreation, indexUpdate, indexDeletion, documentAdditionOrUpdate, \
-                documentDeletion, settingsUpdate, dumpCreation",
-                status
-            ))
-        }
+    fn from_str(type_: &str) -> Result<Self, String> {
+        TaskType::ALL
+            .into_iter()
+            .find(|t| type_.eq_ignore_ascii_case(t.name()))
+            .ok_or_else(|| {
+                let names: Vec<_> = TaskType::ALL.iter().map(TaskType::name).collect();
+                format!(
+                    "invalid task type `{}`, expecting one of: {}",
+                    type_,
+                    names.join(", ")
+                )
+            })
     }
 }
 
@@ -107,14 +163,65 @@ enum TaskDetails {
     DocumentAddition {
         received_documents: usize,
         indexed_documents: Option<u64>,
+        /// Only shown when documents of the payload had the same primary key.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duplicate_documents: Option<u64>,
+        /// Only shown when the task was superseded by a deletion of all the documents.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        superseded_by: Option<TaskId>,
+        /// Only shown when the payload was split into chunks.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total_chunks: Option<usize>,
+        /// Only shown when the payload was split into chunks.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        processed_chunks: Option<usize>,
+        /// Only shown when the addition asked for it with `returnDiff`, once processed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diff: Option<DocumentsDiff>,
+        /// Only shown when the addition asked for it with `sanitize`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sanitized_characters: Option<u64>,
+        /// Only shown when soft limits are set on the documents.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        warnings: Option<u64>,
+        /// Only shown when the index has document transforms, once processed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transforms: Option<TransformsResult>,
+    },
+    #[serde(rename_all = "camelCase")]
+    DocumentFetch {
+        url: String,
+        indexed_documents: Option<u64>,
     },
     #[serde(rename_all = "camelCase")]
     Settings {
         #[serde(flatten)]
         settings: Settings<Unchecked>,
+        /// Only shown when the update was scheduled for later.
+        #[serde(
+            default,
+            with = "time::serde::rfc3339::option",
+            skip_serializing_if = "Option::is_none"
+        )]
+        scheduled_at: Option<OffsetDateTime>,
+        /// Only shown once processed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reindexed_documents: Option<u64>,
+        /// The pending tasks referencing attributes the update makes unfilterable.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<SettingsWarning>,
     },
     #[serde(rename_all = "camelCase")]
-    IndexInfo { primary_key: Option<String> },
+    IndexInfo {
+        primary_key: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        namespace: Option<String>,
+        /// Only shown for the creations that tolerate an existing index, once processed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        already_existed: Option<bool>,
+    },
     #[serde(rename_all = "camelCase")]
     DocumentDeletion {
         received_document_ids: usize,
@@ -123,7 +230,57 @@ enum TaskDetails {
     #[serde(rename_all = "camelCase")]
     ClearAll { deleted_documents: Option<u64> },
     #[serde(rename_all = "camelCase")]
-    Dump { dump_uid: String },
+    Dump {
+        dump_uid: String,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        include_pending_tasks: bool,
+        /// What the IO throttle did to the writes of the dump, once it is created.
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        throttle: Option<ThrottleReport>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Compaction { purged_documents: Option<u64> },
+    #[serde(rename_all = "camelCase")]
+    IndexCheck {
+        fast: bool,
+        findings: Option<Vec<Finding>>,
+    },
+    #[serde(rename_all = "camelCase")]
+    StatsRepair {
+        /// The stats before and after the repair, once it is done.
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        report: Option<StatsRepairReport>,
+    },
+    #[serde(rename_all = "camelCase")]
+    IndexRebuild { rebuilt_documents: Option<u64> },
+    #[serde(rename_all = "camelCase")]
+    SettingsPreview {
+        settings: Settings<Unchecked>,
+        queries: Vec<String>,
+        sample_size: usize,
+        limit: usize,
+        sampled_documents: Option<u64>,
+        /// The first hits of each query, with the current and the candidate settings.
+        previews: Option<Vec<QueryPreview>>,
+    },
+    #[serde(rename_all = "camelCase")]
+    NamespaceDeletion {
+        namespace: String,
+        deleted_indexes: Option<u64>,
+        deleted_documents: Option<u64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    DocumentEdition {
+        filter: Option<serde_json::Value>,
+        edits: Vec<DocumentEdit>,
+        matched_documents: Option<u64>,
+        edited_documents: Option<u64>,
+        /// The number of matching documents left untouched because an edit didn't apply to
+        /// them.
+        invalid_documents: Option<u64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    TaskStoreCompaction { reclaimed_bytes: Option<u64> },
 }
 
 /// Serialize a `time::Duration` as a best effort ISO 8601 while waiting for
@@ -182,7 +339,16 @@ fn serialize_duration<S: Serializer>(
 pub struct TaskView {
     pub uid: TaskId,
     index_uid: Option<String>,
+    /// The uuid of the index when the task was registered, if it existed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index_uuid: Option<Uuid>,
+    /// Whether the index of the task was deleted since, only known when listing tasks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_deleted: Option<bool>,
     status: TaskStatus,
+    /// Only shown while the task is processing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<TaskProgress>,
     #[serde(rename = "type")]
     task_type: TaskType,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -199,6 +365,12 @@ pub struct TaskView {
     finished_at: Option<OffsetDateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]
     batch_uid: Option<Option<BatchId>>,
+    /// Only shown to the keys allowed to read the sensitive details of the tasks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<TaskOrigin>,
+    /// The version of the engine that processed the task, once it is finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engine_version: Option<String>,
 }
 
 impl From<Task> for TaskView {
@@ -206,77 +378,201 @@ impl From<Task> for TaskView {
         let index_uid = task.index_uid().map(String::from);
         let Task {
             id,
+            index_uuid,
             content,
             events,
+            origin: _,
+            engine_version: _,
         } = task;
 
-        let (task_type, mut details) = match content {
+        let processed_chunks = events
+            .iter()
+            .filter(|event| matches!(event, TaskEvent::ChunkProcessed { .. }))
+            .count();
+
+        let task_type = TaskType::of(&content);
+        let mut details = match content {
             TaskContent::DocumentAddition {
-                documents_count, ..
+                documents_count,
+                chunks,
+                sanitized_characters,
+                warnings,
+                ..
             } => {
+                let chunked = !chunks.is_empty();
                 let details = TaskDetails::DocumentAddition {
                     received_documents: documents_count,
                     indexed_documents: None,
+                    duplicate_documents: None,
+                    superseded_by: None,
+                    total_chunks: chunked.then(|| chunks.len() + 1),
+                    processed_chunks: chunked.then(|| processed_chunks),
+                    diff: None,
+                    sanitized_characters,
+                    warnings,
+                    transforms: None,
                 };
 
-                (TaskType::DocumentAdditionOrUpdate, Some(details))
+                Some(details)
             }
             TaskContent::DocumentDeletion {
                 deletion: DocumentDeletion::Ids(ids),
                 ..
-            } => (
-                TaskType::DocumentDeletion,
-                Some(TaskDetails::DocumentDeletion {
-                    received_document_ids: ids.len(),
-                    deleted_documents: None,
-                }),
-            ),
+            } => Some(TaskDetails::DocumentDeletion {
+                received_document_ids: ids.len(),
+                deleted_documents: None,
+            }),
             TaskContent::DocumentDeletion {
                 deletion: DocumentDeletion::Clear,
                 ..
-            } => (
-                TaskType::DocumentDeletion,
-                Some(TaskDetails::ClearAll {
-                    deleted_documents: None,
-                }),
-            ),
-            TaskContent::IndexDeletion { .. } => (
-                TaskType::IndexDeletion,
-                Some(TaskDetails::ClearAll {
-                    deleted_documents: None,
-                }),
-            ),
-            TaskContent::SettingsUpdate { settings, .. } => (
-                TaskType::SettingsUpdate,
-                Some(TaskDetails::Settings { settings }),
-            ),
-            TaskContent::IndexCreation { primary_key, .. } => (
-                TaskType::IndexCreation,
-                Some(TaskDetails::IndexInfo { primary_key }),
-            ),
-            TaskContent::IndexUpdate { primary_key, .. } => (
-                TaskType::IndexUpdate,
-                Some(TaskDetails::IndexInfo { primary_key }),
-            ),
-            TaskContent::Dump { uid } => (
-                TaskType::DumpCreation,
-                Some(TaskDetails::Dump { dump_uid: uid }),
-            ),
+            } => Some(TaskDetails::ClearAll {
+                deleted_documents: None,
+            }),
+            TaskContent::IndexDeletion { .. } => Some(TaskDetails::ClearAll {
+                deleted_documents: None,
+            }),
+            TaskContent::SettingsUpdate {
+                settings,
+                scheduled_at,
+                warnings,
+                ..
+            } => Some(TaskDetails::Settings {
+                settings,
+                scheduled_at,
+                reindexed_documents: None,
+                warnings,
+            }),
+            TaskContent::IndexCreation {
+                primary_key,
+                name,
+                namespace,
+                ..
+            } => Some(TaskDetails::IndexInfo {
+                primary_key,
+                name,
+                namespace,
+                already_existed: None,
+            }),
+            TaskContent::IndexUpdate {
+                primary_key, name, ..
+            } => Some(TaskDetails::IndexInfo {
+                primary_key,
+                name,
+                namespace: None,
+                already_existed: None,
+            }),
+            TaskContent::Dump {
+                uid,
+                include_pending_tasks,
+            } => Some(TaskDetails::Dump {
+                dump_uid: uid,
+                include_pending_tasks,
+                throttle: None,
+            }),
+            TaskContent::DocumentsCompaction { .. } => Some(TaskDetails::Compaction {
+                purged_documents: None,
+            }),
+            TaskContent::IndexCheck { fast, .. } => Some(TaskDetails::IndexCheck {
+                fast,
+                findings: None,
+            }),
+            TaskContent::StatsRepair { .. } => Some(TaskDetails::StatsRepair { report: None }),
+            TaskContent::IndexRebuild { .. } => Some(TaskDetails::IndexRebuild {
+                rebuilt_documents: None,
+            }),
+            TaskContent::SettingsPreview {
+                settings,
+                queries,
+                sample_size,
+                limit,
+                ..
+            } => Some(TaskDetails::SettingsPreview {
+                settings,
+                queries,
+                sample_size,
+                limit,
+                sampled_documents: None,
+                previews: None,
+            }),
+            TaskContent::DocumentAdditionFromUrl { url, .. } => Some(TaskDetails::DocumentFetch {
+                url,
+                indexed_documents: None,
+            }),
+            TaskContent::NamespaceDeletion { namespace } => Some(TaskDetails::NamespaceDeletion {
+                namespace,
+                deleted_indexes: None,
+                deleted_documents: None,
+            }),
+            TaskContent::DocumentEdition { filter, edits, .. } => {
+                Some(TaskDetails::DocumentEdition {
+                    filter,
+                    edits,
+                    matched_documents: None,
+                    edited_documents: None,
+                    invalid_documents: None,
+                })
+            }
+            TaskContent::TaskStoreCompaction => Some(TaskDetails::TaskStoreCompaction {
+                reclaimed_bytes: None,
+            }),
         };
 
         // An event always has at least one event: "Created"
         let (status, error, finished_at) = match events.last().unwrap() {
+            // a document addition split into chunks is processing from its first indexed chunk
+            // on, even while its next chunk waits for its batch.
+            TaskEvent::Created(_) | TaskEvent::Batched { .. } if processed_chunks > 0 => {
+                (TaskStatus::Processing, None, None)
+            }
             TaskEvent::Created(_) => (TaskStatus::Enqueued, None, None),
             TaskEvent::Batched { .. } => (TaskStatus::Enqueued, None, None),
-            TaskEvent::Processing(_) => (TaskStatus::Processing, None, None),
-            TaskEvent::Succeeded { timestamp, result } => {
+            TaskEvent::Processing(_) | TaskEvent::ChunkProcessed { .. } => {
+                (TaskStatus::Processing, None, None)
+            }
+            TaskEvent::Succeeded {
+                timestamp, result, ..
+            } => {
                 match (result, &mut details) {
                     (
                         TaskResult::DocumentAddition {
                             indexed_documents: num,
-                            ..
+                            duplicate_documents: duplicates,
+                            diff: result_diff,
+                            transforms: result_transforms,
                         },
                         Some(TaskDetails::DocumentAddition {
+                            ref mut indexed_documents,
+                            ref mut duplicate_documents,
+                            ref total_chunks,
+                            ref mut processed_chunks,
+                            ref mut diff,
+                            ref mut transforms,
+                            ..
+                        }),
+                    ) => {
+                        indexed_documents.replace(*num);
+                        *duplicate_documents = Some(*duplicates).filter(|n| *n > 0);
+                        *processed_chunks = *total_chunks;
+                        *diff = result_diff.clone();
+                        *transforms = *result_transforms;
+                    }
+                    (
+                        TaskResult::Superseded { superseded_by: id },
+                        Some(TaskDetails::DocumentAddition {
+                            ref mut indexed_documents,
+                            ref mut superseded_by,
+                            ..
+                        }),
+                    ) => {
+                        indexed_documents.replace(0);
+                        superseded_by.replace(*id);
+                    }
+                    (
+                        TaskResult::DocumentAddition {
+                            indexed_documents: num,
+                            ..
+                        },
+                        Some(TaskDetails::DocumentFetch {
                             ref mut indexed_documents,
                             ..
                         }),
@@ -295,6 +591,16 @@ impl From<Task> for TaskView {
                     ) => {
                         deleted_documents.replace(*docs);
                     }
+                    (
+                        TaskResult::DocumentDeletion {
+                            deleted_documents: docs,
+                        },
+                        Some(TaskDetails::Compaction {
+                            ref mut purged_documents,
+                        }),
+                    ) => {
+                        purged_documents.replace(*docs);
+                    }
                     (
                         TaskResult::ClearAll {
                             deleted_documents: docs,
@@ -305,11 +611,121 @@ impl From<Task> for TaskView {
                     ) => {
                         deleted_documents.replace(*docs);
                     }
+                    (
+                        TaskResult::IndexCheck { report },
+                        Some(TaskDetails::IndexCheck {
+                            ref mut findings, ..
+                        }),
+                    ) => {
+                        findings.replace(report.findings.clone());
+                    }
+                    (
+                        TaskResult::StatsRepair { report },
+                        Some(TaskDetails::StatsRepair {
+                            report: ref mut details,
+                        }),
+                    ) => {
+                        details.replace(report.clone());
+                    }
+                    (
+                        TaskResult::IndexRebuild {
+                            rebuilt_documents: docs,
+                        },
+                        Some(TaskDetails::IndexRebuild {
+                            ref mut rebuilt_documents,
+                        }),
+                    ) => {
+                        rebuilt_documents.replace(*docs);
+                    }
+                    (
+                        TaskResult::SettingsPreview { preview },
+                        Some(TaskDetails::SettingsPreview {
+                            ref mut sampled_documents,
+                            ref mut previews,
+                            ..
+                        }),
+                    ) => {
+                        sampled_documents.replace(preview.sampled_documents);
+                        previews.replace(preview.queries.clone());
+                    }
+                    (
+                        TaskResult::SettingsUpdate {
+                            reindexed_documents: docs,
+                        },
+                        Some(TaskDetails::Settings {
+                            ref mut reindexed_documents,
+                            ..
+                        }),
+                    ) => {
+                        reindexed_documents.replace(*docs);
+                    }
+                    (
+                        TaskResult::IndexCreation {
+                            already_existed: existed,
+                        },
+                        Some(TaskDetails::IndexInfo {
+                            ref mut already_existed,
+                            ..
+                        }),
+                    ) => {
+                        already_existed.replace(*existed);
+                    }
+                    (
+                        TaskResult::NamespaceDeletion {
+                            deleted_indexes: indexes,
+                            deleted_documents: docs,
+                        },
+                        Some(TaskDetails::NamespaceDeletion {
+                            ref mut deleted_indexes,
+                            ref mut deleted_documents,
+                            ..
+                        }),
+                    ) => {
+                        deleted_indexes.replace(*indexes);
+                        deleted_documents.replace(*docs);
+                    }
+                    (
+                        TaskResult::DocumentEdition {
+                            matched_documents: matched,
+                            edited_documents: edited,
+                            invalid_documents: invalid,
+                        },
+                        Some(TaskDetails::DocumentEdition {
+                            ref mut matched_documents,
+                            ref mut edited_documents,
+                            ref mut invalid_documents,
+                            ..
+                        }),
+                    ) => {
+                        matched_documents.replace(*matched);
+                        edited_documents.replace(*edited);
+                        invalid_documents.replace(*invalid);
+                    }
+                    (
+                        TaskResult::Dump { throttle: report },
+                        Some(TaskDetails::Dump {
+                            ref mut throttle, ..
+                        }),
+                    ) => {
+                        throttle.replace(*report);
+                    }
+                    (
+                        TaskResult::TaskStoreCompaction {
+                            reclaimed_bytes: bytes,
+                        },
+                        Some(TaskDetails::TaskStoreCompaction {
+                            ref mut reclaimed_bytes,
+                        }),
+                    ) => {
+                        reclaimed_bytes.replace(*bytes);
+                    }
                     _ => (),
                 }
                 (TaskStatus::Succeeded, None, Some(*timestamp))
             }
-            TaskEvent::Failed { timestamp, error } => {
+            TaskEvent::Failed {
+                timestamp, error, ..
+            } => {
                 match details {
                     Some(TaskDetails::DocumentDeletion {
                         ref mut deleted_documents,
@@ -323,9 +739,36 @@ impl From<Task> for TaskView {
                     }) => {
                         deleted_documents.replace(0);
                     }
+                    Some(TaskDetails::Compaction {
+                        ref mut purged_documents,
+                    }) => {
+                        purged_documents.replace(0);
+                    }
+                    Some(TaskDetails::IndexRebuild {
+                        ref mut rebuilt_documents,
+                    }) => {
+                        rebuilt_documents.replace(0);
+                    }
+                    Some(TaskDetails::TaskStoreCompaction {
+                        ref mut reclaimed_bytes,
+                    }) => {
+                        reclaimed_bytes.replace(0);
+                    }
+                    Some(TaskDetails::NamespaceDeletion {
+                        ref mut deleted_indexes,
+                        ref mut deleted_documents,
+                        ..
+                    }) => {
+                        deleted_indexes.replace(0);
+                        deleted_documents.replace(0);
+                    }
                     Some(TaskDetails::DocumentAddition {
                         ref mut indexed_documents,
                         ..
+                    })
+                    | Some(TaskDetails::DocumentFetch {
+                        ref mut indexed_documents,
+                        ..
                     }) => {
                         indexed_documents.replace(0);
                     }
@@ -346,6 +789,10 @@ impl From<Task> for TaskView {
         });
 
         let duration = finished_at.zip(started_at).map(|(tf, ts)| (tf - ts));
+        let engine_version = events
+            .last()
+            .and_then(TaskEvent::engine_version)
+            .map(String::from);
 
         let batch_uid = if AUTOBATCHING_ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
             let id = events.iter().find_map(|e| match e {
@@ -360,7 +807,10 @@ impl From<Task> for TaskView {
         Self {
             uid: id,
             index_uid,
+            index_uuid,
+            index_deleted: None,
             status,
+            progress: None,
             task_type,
             details,
             error,
@@ -369,6 +819,8 @@ impl From<Task> for TaskView {
             started_at,
             finished_at,
             batch_uid,
+            origin: None,
+            engine_version,
         }
     }
 }
@@ -383,14 +835,68 @@ pub struct TaskListView {
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SummarizedTaskView {
-    task_uid: TaskId,
+pub struct BatchView {
+    pub uid: BatchId,
     index_uid: Option<String>,
+    task_uids: Vec<TaskId>,
+    task_count: usize,
+    documents_count: u64,
+    outcome: BatchOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ResponseError>,
+    stop_reason: Option<BatchStopReason>,
+    /// The time the tasks of the batch were waited for before it was processed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debounce_ms: Option<u64>,
+    #[serde(serialize_with = "serialize_duration")]
+    duration: Option<Duration>,
+    #[serde(serialize_with = "time::serde::rfc3339::serialize")]
+    started_at: OffsetDateTime,
+    #[serde(serialize_with = "time::serde::rfc3339::serialize")]
+    finished_at: OffsetDateTime,
+}
+
+impl From<BatchResult> for BatchView {
+    fn from(result: BatchResult) -> Self {
+        Self {
+            uid: result.id,
+            index_uid: result.index_uid,
+            task_count: result.task_ids.len(),
+            task_uids: result.task_ids,
+            documents_count: result.documents_count,
+            outcome: result.outcome,
+            error: result.error,
+            stop_reason: result.stop_reason,
+            debounce_ms: result.debounce_ms,
+            duration: Some(result.finished_at - result.started_at),
+            started_at: result.started_at,
+            finished_at: result.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchListView {
+    pub results: Vec<BatchView>,
+    pub limit: usize,
+    pub from: Option<BatchId>,
+    pub next: Option<BatchId>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SummarizedTaskView {
+    pub task_uid: TaskId,
+    pub index_uid: Option<String>,
     status: TaskStatus,
     #[serde(rename = "type")]
     task_type: TaskType,
     #[serde(serialize_with = "time::serde::rfc3339::serialize")]
     enqueued_at: OffsetDateTime,
+    /// The pending tasks that a settings update makes fail, shown so that the conflict is seen
+    /// when the update is registered.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<SettingsWarning>,
 }
 
 impl From<Task> for SummarizedTaskView {
@@ -406,12 +912,64 @@ impl From<Task> for SummarizedTaskView {
             _ => unreachable!("The first event of a task must always be 'Created'"),
         };
 
+        // a task with nothing to process is registered as already succeeded.
+        let status = match other.events.last() {
+            Some(TaskEvent::Succeeded { .. }) => TaskStatus::Succeeded,
+            _ => TaskStatus::Enqueued,
+        };
+
+        let warnings = match other.content {
+            TaskContent::SettingsUpdate {
+                ref mut warnings, ..
+            } => std::mem::take(warnings),
+            _ => Vec::new(),
+        };
+
         Self {
             task_uid: other.id,
             index_uid: other.index_uid().map(String::from),
-            status: TaskStatus::Enqueued,
-            task_type: other.content.into(),
+            status,
+            task_type: TaskType::of(&other.content),
             enqueued_at,
+            warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_task_types_names() {
+        // these names are part of the API, changing one of them breaks the clients.
+        let names: Vec<_> = TaskType::ALL.iter().map(TaskType::name).collect();
+        assert_eq!(
+            names,
+            [
+                "documentAdditionOrUpdate",
+                "documentDeletion",
+                "documentEdition",
+                "documentsCompaction",
+                "settingsUpdate",
+                "settingsPreview",
+                "indexCreation",
+                "indexUpdate",
+                "indexDeletion",
+                "indexCheck",
+                "indexRebuild",
+                "namespaceDeletion",
+                "dumpCreation",
+                "taskStoreCompaction",
+            ]
+        );
+
+        for type_ in TaskType::ALL {
+            let serialized = serde_json::to_value(type_).unwrap();
+            assert_eq!(serialized, type_.name());
+            assert_eq!(type_.name().parse::<TaskType>(), Ok(type_));
+            assert_eq!(type_.name().to_uppercase().parse::<TaskType>(), Ok(type_));
         }
+        assert!("snapshotCreation".parse::<TaskType>().is_err());
     }
 }
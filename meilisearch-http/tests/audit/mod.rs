@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::time::sleep;
+
+use crate::common::server::default_settings;
+use crate::common::Server;
+
+use meilisearch_http::Opt;
+use meilisearch_lib::options::Sensitive;
+
+fn read_audit_log(path: &std::path::Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn audit_log_records_mutating_requests() {
+    let temp = tempfile::tempdir().unwrap();
+    let audit_log_path = temp.path().join("audit.log");
+
+    let options = Opt {
+        audit_log_path: Some(audit_log_path.clone()),
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+
+    let index = server.index("test");
+    let (response, code) = index.create(None).await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(0).await;
+
+    // searches are not recorded.
+    index.search_post(json!({ "q": "hello" })).await;
+
+    let (response, code) = index.update_settings(json!({ "stopWords": ["the"] })).await;
+    assert_eq!(code, 202, "{}", response);
+
+    // the entries are written in the background.
+    let mut entries = Vec::new();
+    for _ in 0..20 {
+        entries = read_audit_log(&audit_log_path);
+        if entries.len() >= 2 {
+            break;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    assert_eq!(entries.len(), 2, "{:?}", entries);
+
+    assert_eq!(entries[0]["method"], "POST");
+    assert_eq!(entries[0]["route"], "/indexes");
+    assert_eq!(entries[0]["indexUid"], "test");
+    assert_eq!(entries[0]["taskUid"], 0);
+    assert_eq!(entries[0]["keyUid"], Value::Null);
+
+    assert_eq!(entries[1]["method"], "PATCH");
+    assert_eq!(entries[1]["route"], "/indexes/{index_uid}/settings");
+    assert_eq!(entries[1]["path"], "/indexes/test/settings");
+    assert_eq!(entries[1]["indexUid"], "test");
+    assert_eq!(entries[1]["taskUid"], 1);
+}
+
+#[actix_rt::test]
+async fn audit_log_records_key_rotations() {
+    let temp = tempfile::tempdir().unwrap();
+    let audit_log_path = temp.path().join("audit.log");
+
+    let options = Opt {
+        audit_log_path: Some(audit_log_path.clone()),
+        master_key: Some(Sensitive::new("MASTER_KEY".to_string())),
+        ..default_settings(temp.path())
+    };
+    let mut server = Server::new_with_options(options).await.unwrap();
+    server.use_api_key("MASTER_KEY");
+
+    let content = json!({ "indexes": ["*"], "actions": ["search"], "expiresAt": null });
+    let (key, code) = server.add_api_key(content).await;
+    assert_eq!(code, 201, "{}", key);
+    let uid = key["uid"].as_str().unwrap();
+    let (response, code) = server.rotate_api_key(uid).await;
+    assert_eq!(code, 200, "{}", response);
+
+    // the entries are written in the background.
+    let mut entries = Vec::new();
+    for _ in 0..20 {
+        entries = read_audit_log(&audit_log_path);
+        if entries.len() >= 2 {
+            break;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    assert_eq!(entries.len(), 2, "{:?}", entries);
+    assert_eq!(entries[1]["method"], "POST");
+    assert_eq!(entries[1]["route"], "/keys/{key}/rotate");
+    assert_eq!(entries[1]["path"], format!("/keys/{}/rotate", uid));
+}
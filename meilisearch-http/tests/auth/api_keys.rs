@@ -1,5 +1,8 @@
-use crate::common::Server;
+use crate::common::{default_settings, Server};
+use ::time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
 use assert_json_diff::assert_json_include;
+use meilisearch_http::Opt;
+use meilisearch_lib::options::Sensitive;
 use serde_json::{json, Value};
 use std::{thread, time};
 
@@ -1432,3 +1435,140 @@ async fn error_access_api_key_routes_no_master_key_set() {
     assert_eq!(expected_code, code, "{:?}", &response);
     assert_eq!(response, expected_response);
 }
+
+fn options_with_master_key(dir: &std::path::Path) -> Opt {
+    Opt {
+        master_key: Some(Sensitive::new("MASTER_KEY".to_string())),
+        ..default_settings(dir)
+    }
+}
+
+#[actix_rt::test]
+async fn rotate_api_key() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = Opt {
+        key_rotation_overlap_sec: 2,
+        ..options_with_master_key(temp.path())
+    };
+    let mut server = Server::new_with_options(options).await.unwrap();
+    server.use_api_key("MASTER_KEY");
+
+    let content = json!({
+        "indexes": ["*"],
+        "actions": ["indexes.get"],
+        "expiresAt": "2050-11-13T00:00:00Z"
+    });
+    let (key, code) = server.add_api_key(content).await;
+    assert_eq!(201, code, "{:?}", &key);
+    let old_secret = key["key"].as_str().unwrap().to_string();
+    let uid = key["uid"].as_str().unwrap().to_string();
+
+    let (rotated, code) = server.rotate_api_key(&uid).await;
+    assert_eq!(200, code, "{:?}", &rotated);
+    let new_secret = rotated["key"].as_str().unwrap().to_string();
+    assert_ne!(new_secret, old_secret);
+    assert_eq!(rotated["uid"], key["uid"]);
+    assert_eq!(rotated["actions"], key["actions"]);
+    assert_eq!(rotated["indexes"], key["indexes"]);
+    assert_eq!(rotated["expiresAt"], key["expiresAt"]);
+    assert!(rotated["previousKeyValidUntil"].is_string());
+
+    // the new secret identifies the key.
+    let (response, code) = server.get_api_key(&new_secret).await;
+    assert_eq!(200, code, "{:?}", &response);
+    assert_eq!(response["uid"], key["uid"]);
+
+    // both secrets are accepted during the overlap window.
+    for secret in [&old_secret, &new_secret] {
+        server.use_api_key(secret);
+        let (response, code) = server.list_indexes(None, None).await;
+        assert_eq!(200, code, "{:?}", &response);
+    }
+
+    thread::sleep(time::Duration::new(3, 0));
+
+    // only the new secret is accepted after it.
+    server.use_api_key(&old_secret);
+    let (response, code) = server.list_indexes(None, None).await;
+    assert_eq!(403, code, "{:?}", &response);
+    assert_eq!(response["code"], "invalid_api_key");
+
+    server.use_api_key(&new_secret);
+    let (response, code) = server.list_indexes(None, None).await;
+    assert_eq!(200, code, "{:?}", &response);
+
+    server.use_api_key("MASTER_KEY");
+    let (response, code) = server.get_api_key(&uid).await;
+    assert_eq!(200, code, "{:?}", &response);
+    assert_eq!(response["key"], json!(new_secret));
+    assert!(response.get("previousKeyValidUntil").is_none());
+}
+
+#[actix_rt::test]
+async fn rotate_api_key_twice() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut server = Server::new_with_options(options_with_master_key(temp.path()))
+        .await
+        .unwrap();
+    server.use_api_key("MASTER_KEY");
+
+    let content = json!({ "indexes": ["*"], "actions": ["indexes.get"], "expiresAt": null });
+    let (key, code) = server.add_api_key(content).await;
+    assert_eq!(201, code, "{:?}", &key);
+    let uid = key["uid"].as_str().unwrap().to_string();
+
+    let (first, _) = server.rotate_api_key(&uid).await;
+    let (second, code) = server.rotate_api_key(&uid).await;
+    assert_eq!(200, code, "{:?}", &second);
+
+    // only the secret the key was rotated from last is still accepted.
+    let expected = [
+        (&key["key"], 403),
+        (&first["key"], 200),
+        (&second["key"], 200),
+    ];
+    for (secret, expected_code) in expected {
+        server.use_api_key(secret.as_str().unwrap());
+        let (response, code) = server.list_indexes(None, None).await;
+        assert_eq!(expected_code, code, "{:?}", &response);
+    }
+}
+
+#[actix_rt::test]
+async fn error_rotate_api_key_not_found() {
+    let mut server = Server::new_auth().await;
+    server.use_api_key("MASTER_KEY");
+
+    let (response, code) = server
+        .rotate_api_key("d0552b41-d0d5-4a22-8c1b-e3b2fe0d7cc3")
+        .await;
+    assert_eq!(404, code, "{:?}", &response);
+    assert_eq!(response["code"], "api_key_not_found");
+}
+
+#[actix_rt::test]
+async fn access_expired_key_within_leeway() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = Opt {
+        key_expiration_leeway_sec: 60,
+        ..options_with_master_key(temp.path())
+    };
+    let mut server = Server::new_with_options(options).await.unwrap();
+    server.use_api_key("MASTER_KEY");
+
+    let content = json!({
+        "indexes": ["*"],
+        "actions": ["indexes.get"],
+        "expiresAt": (OffsetDateTime::now_utc() + Duration::seconds(1)).format(&Rfc3339).unwrap(),
+    });
+    let (key, code) = server.add_api_key(content).await;
+    assert_eq!(201, code, "{:?}", &key);
+
+    // wait until the key is expired.
+    thread::sleep(time::Duration::new(2, 0));
+
+    // the key is still accepted, its expiration date could come from a clock ahead of ours.
+    server.use_api_key(key["key"].as_str().unwrap());
+    let (response, code) = server.list_indexes(None, None).await;
+    assert_eq!(200, code, "{:?}", &response);
+}
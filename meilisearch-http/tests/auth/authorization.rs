@@ -11,16 +11,37 @@ pub static AUTHORIZATIONS: Lazy<HashMap<(&'static str, &'static str), HashSet<&'
         hashmap! {
             ("POST",    "/indexes/products/search") =>                         hashset!{"search", "*"},
             ("GET",     "/indexes/products/search") =>                         hashset!{"search", "*"},
+            ("POST",    "/indexes/products/aggregate") =>                      hashset!{"search", "*"},
             ("POST",    "/indexes/products/documents") =>                      hashset!{"documents.add", "*"},
             ("GET",     "/indexes/products/documents") =>                      hashset!{"documents.get", "*"},
             ("GET",     "/indexes/products/documents/0") =>                    hashset!{"documents.get", "*"},
             ("DELETE",  "/indexes/products/documents/0") =>                    hashset!{"documents.delete", "*"},
+            ("POST",    "/indexes/products/documents/fetch") =>                hashset!{"documents.add", "*"},
+            ("POST",    "/indexes/products/documents/edit") =>                 hashset!{"documents.add", "*"},
+            ("POST",    "/indexes/products/documents/validate") =>             hashset!{"documents.add", "*"},
+            ("POST",    "/indexes/products/documents/uploads") =>              hashset!{"documents.add", "*"},
+            ("GET",     "/indexes/products/documents/uploads/00000000-0000-0000-0000-000000000000") => hashset!{"documents.add", "*"},
+            ("PUT",     "/indexes/products/documents/uploads/00000000-0000-0000-0000-000000000000") => hashset!{"documents.add", "*"},
+            ("POST",    "/indexes/products/documents/uploads/00000000-0000-0000-0000-000000000000/commit") => hashset!{"documents.add", "*"},
             ("GET",     "/tasks") =>                                           hashset!{"tasks.get", "*"},
             ("GET",     "/tasks?indexUid=products") =>                         hashset!{"tasks.get", "*"},
             ("GET",     "/tasks/0") =>                                         hashset!{"tasks.get", "*"},
+            ("GET",     "/tasks/types") =>                                     hashset!{"tasks.get", "*"},
+            ("POST",    "/tasks/0/cancel") =>                                  hashset!{"tasks.cancel", "*"},
+            ("POST",    "/tasks/force-reset-processing") =>                    hashset!{"tasks.forceReset", "*"},
+            ("POST",    "/tasks/compact") =>                                   hashset!{"tasks.compact", "*"},
+            ("GET",     "/batches") =>                                         hashset!{"tasks.get", "*"},
+            ("GET",     "/batches/0") =>                                       hashset!{"tasks.get", "*"},
+            ("GET",     "/config") =>                                          hashset!{"config.get", "*"},
+            ("PATCH",   "/config") =>                                          hashset!{"config.update", "*"},
             ("PATCH",   "/indexes/products/") =>                               hashset!{"indexes.update", "*"},
             ("GET",     "/indexes/products/") =>                               hashset!{"indexes.get", "*"},
             ("DELETE",  "/indexes/products/") =>                               hashset!{"indexes.delete", "*"},
+            ("POST",    "/indexes/products/check") =>                          hashset!{"indexes.update", "*"},
+            ("POST",    "/indexes/products/repair-stats") =>                   hashset!{"indexes.update", "*"},
+            ("POST",    "/indexes/products/rebuild") =>                        hashset!{"indexes.update", "*"},
+            ("POST",    "/indexes/products/close") =>                          hashset!{"indexes.update", "*"},
+            ("POST",    "/indexes/products/open") =>                           hashset!{"indexes.update", "*"},
             ("POST",    "/indexes") =>                                         hashset!{"indexes.create", "*"},
             ("GET",     "/indexes") =>                                         hashset!{"indexes.get", "*"},
             ("GET",     "/indexes/products/settings") =>                       hashset!{"settings.get", "*"},
@@ -35,6 +56,7 @@ pub static AUTHORIZATIONS: Lazy<HashMap<(&'static str, &'static str), HashSet<&'
             ("DELETE",  "/indexes/products/settings") =>                       hashset!{"settings.update", "*"},
             ("PATCH",   "/indexes/products/settings") =>                       hashset!{"settings.update", "*"},
             ("PATCH",   "/indexes/products/settings/typo-tolerance") =>        hashset!{"settings.update", "*"},
+            ("POST",    "/indexes/products/settings/preview") =>               hashset!{"settings.update", "*"},
             ("PUT",     "/indexes/products/settings/displayed-attributes") =>  hashset!{"settings.update", "*"},
             ("PUT",     "/indexes/products/settings/distinct-attribute") =>    hashset!{"settings.update", "*"},
             ("PUT",     "/indexes/products/settings/filterable-attributes") => hashset!{"settings.update", "*"},
@@ -44,14 +66,25 @@ pub static AUTHORIZATIONS: Lazy<HashMap<(&'static str, &'static str), HashSet<&'
             ("PUT",     "/indexes/products/settings/stop-words") =>            hashset!{"settings.update", "*"},
             ("PUT",     "/indexes/products/settings/synonyms") =>              hashset!{"settings.update", "*"},
             ("GET",     "/indexes/products/stats") =>                          hashset!{"stats.get", "*"},
+            ("GET",     "/indexes/products/stats?detailed=true") =>            hashset!{"*"},
+            ("GET",     "/indexes/products/analytics/searches") =>             hashset!{"analytics.get", "*"},
+            ("GET",     "/indexes/products/changes") =>                        hashset!{"documents.get", "*"},
             ("GET",     "/stats") =>                                           hashset!{"stats.get", "*"},
+            ("POST",    "/namespaces") =>                                      hashset!{"namespaces.create", "*"},
+            ("GET",     "/namespaces") =>                                      hashset!{"namespaces.get", "*"},
+            ("GET",     "/namespaces/tenant") =>                               hashset!{"namespaces.get", "*"},
+            ("GET",     "/namespaces/tenant/stats") =>                         hashset!{"namespaces.get", "*"},
+            ("DELETE",  "/namespaces/tenant") =>                               hashset!{"namespaces.delete", "*"},
             ("POST",    "/dumps") =>                                           hashset!{"dumps.create", "*"},
+            ("POST",    "/snapshots") =>                                       hashset!{"snapshots.create", "*"},
             ("GET",     "/version") =>                                         hashset!{"version", "*"},
-            ("PATCH",   "/keys/mykey/") =>                                     hashset!{"keys.update", "*"},
-            ("GET",     "/keys/mykey/") =>                                     hashset!{"keys.get", "*"},
-            ("DELETE",  "/keys/mykey/") =>                                     hashset!{"keys.delete", "*"},
-            ("POST",    "/keys") =>                                            hashset!{"keys.create", "*"},
-            ("GET",     "/keys") =>                                            hashset!{"keys.get", "*"},
+            ("POST",    "/admin/support-bundle") =>                            hashset!{"support.bundle", "*"},
+            ("PATCH",   "/keys/mykey/") =>                                     hashset!{"keys.update", "keys.*", "*"},
+            ("GET",     "/keys/mykey/") =>                                     hashset!{"keys.get", "keys.*", "*"},
+            ("DELETE",  "/keys/mykey/") =>                                     hashset!{"keys.delete", "keys.*", "*"},
+            ("POST",    "/keys/mykey/rotate") =>                               hashset!{"keys.update", "keys.*", "*"},
+            ("POST",    "/keys") =>                                            hashset!{"keys.create", "keys.*", "*"},
+            ("GET",     "/keys") =>                                            hashset!{"keys.get", "keys.*", "*"},
         }
     });
 
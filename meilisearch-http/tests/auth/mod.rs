@@ -1,6 +1,7 @@
 mod api_keys;
 mod authorization;
 mod payload;
+mod search_filters;
 mod tenant_token;
 
 use crate::common::Server;
@@ -37,6 +38,11 @@ impl Server {
         self.service.patch(url, content).await
     }
 
+    pub async fn rotate_api_key(&self, key: impl AsRef<str>) -> (Value, StatusCode) {
+        let url = format!("/keys/{}/rotate", key.as_ref());
+        self.service.post(url, json!({})).await
+    }
+
     pub async fn list_api_keys(&self) -> (Value, StatusCode) {
         let url = "/keys";
         self.service.get(url).await
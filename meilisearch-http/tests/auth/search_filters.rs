@@ -0,0 +1,178 @@
+use crate::common::Server;
+use ::time::format_description::well_known::Rfc3339;
+use serde_json::{json, Value};
+use time::{Duration, OffsetDateTime};
+
+fn documents() -> Value {
+    json!([
+        { "id": 1, "title": "Shazam!", "color": ["green", "blue"], "tenant": "acme" },
+        { "id": 2, "title": "Captain Marvel", "color": ["yellow", "blue"], "tenant": "acme" },
+        { "id": 3, "title": "Escape Room", "color": ["yellow", "red"], "tenant": "acme" },
+        { "id": 4, "title": "Glass", "color": ["blue", "red"], "tenant": "globex" },
+    ])
+}
+
+fn key_content(indexes: Value, search_filters: Value) -> Value {
+    json!({
+        "indexes": indexes,
+        "actions": ["search"],
+        "searchFilters": search_filters,
+        "expiresAt": (OffsetDateTime::now_utc() + Duration::days(1)).format(&Rfc3339).unwrap()
+    })
+}
+
+async fn server_with_index(uid: &str, filterable_attributes: Value) -> Server {
+    let mut server = Server::new_auth().await;
+    server.use_api_key("MASTER_KEY");
+    let index = server.index(uid);
+    index.add_documents(documents(), None).await;
+    index.wait_task(0).await;
+    index
+        .update_settings(json!({ "filterableAttributes": filterable_attributes }))
+        .await;
+    index.wait_task(1).await;
+    drop(index);
+    server
+}
+
+#[actix_rt::test]
+async fn search_filters_are_applied_to_every_search() {
+    let mut server = server_with_index("sales", json!(["color", "tenant"])).await;
+
+    let content = key_content(json!(["sales"]), json!({ "sales": "tenant = acme" }));
+    let (response, code) = server.add_api_key(content).await;
+    assert_eq!(code, 201, "{}", response);
+    assert_eq!(
+        response["searchFilters"],
+        json!({ "sales": "tenant = acme" })
+    );
+    let key = response["key"].as_str().unwrap().to_string();
+
+    server.use_api_key(&key);
+    let index = server.index("sales");
+    let (response, code) = index.search_post(json!({ "facets": ["tenant"] })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 3);
+    assert_eq!(response["estimatedTotalHits"], 3);
+    assert_eq!(
+        response["facetDistribution"]["tenant"],
+        json!({ "acme": 3 })
+    );
+
+    // the filter of the request can only narrow the documents of the key down.
+    let (response, code) = index
+        .search_post(json!({ "filter": "color = red OR tenant = globex" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 1);
+    assert_eq!(response["hits"][0]["id"], 3);
+
+    // the search filters can't be changed afterward.
+    server.use_api_key("MASTER_KEY");
+    let (response, code) = server
+        .patch_api_key(&key, json!({ "searchFilters": {} }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "immutable_field");
+}
+
+#[actix_rt::test]
+async fn search_filters_cannot_be_escaped() {
+    let mut server = server_with_index("sales", json!(["color", "tenant", "id"])).await;
+
+    let content = key_content(json!(["sales"]), json!({ "sales": "tenant = acme" }));
+    let (response, code) = server.add_api_key(content).await;
+    assert_eq!(code, 201, "{}", response);
+    let key = response["key"].as_str().unwrap().to_string();
+
+    server.use_api_key(&key);
+    let index = server.index("sales");
+    for filter in [
+        json!("tenant = globex OR id > 0"),
+        json!("NOT tenant = acme OR NOT tenant = globex"),
+        json!("(color = red OR (tenant = globex AND NOT (id = 1)))"),
+        json!("NOT (NOT (tenant = acme)) OR NOT tenant = acme"),
+        json!(["tenant = globex OR id > 0"]),
+        json!([["tenant = globex", "id > 0"]]),
+    ] {
+        let (response, code) = index.search_post(json!({ "filter": filter })).await;
+        assert_eq!(code, 200, "{} with {}", response, filter);
+        let hits = response["hits"].as_array().unwrap();
+        assert!(
+            hits.iter().all(|hit| hit["tenant"] == "acme"),
+            "{} escaped the search filters: {}",
+            filter,
+            response
+        );
+    }
+
+    // the unbalanced filters are rejected.
+    for filter in [
+        json!("color = red) OR (tenant = globex"),
+        json!("color = red)) OR ((tenant = globex"),
+        json!(["id > 0) OR (id > 0"]),
+    ] {
+        let (response, code) = index.search_post(json!({ "filter": filter })).await;
+        assert_eq!(code, 400, "{} with {}", response, filter);
+        assert_eq!(response["code"], "invalid_filter");
+    }
+}
+
+#[actix_rt::test]
+async fn wildcard_search_filters_apply_to_every_index() {
+    let mut server = server_with_index("sales", json!(["color", "tenant"])).await;
+
+    let content = key_content(
+        json!(["*"]),
+        json!({ "*": "tenant = acme", "sales": ["color = blue"] }),
+    );
+    let (response, code) = server.add_api_key(content).await;
+    assert_eq!(code, 201, "{}", response);
+    let key = response["key"].as_str().unwrap().to_string();
+
+    server.use_api_key(&key);
+    let (response, code) = server.index("sales").search_post(json!({})).await;
+    assert_eq!(code, 200, "{}", response);
+    // both the filter of `*` and the filter of the index apply.
+    assert_eq!(response["hits"].as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn invalid_search_filters_are_rejected() {
+    let mut server = server_with_index("sales", json!(["color"])).await;
+
+    // malformed filter.
+    let content = key_content(json!(["sales"]), json!({ "sales": 42 }));
+    let (response, code) = server.add_api_key(content).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_api_key_search_filters");
+
+    // invalid filters, even for an index that doesn't exist yet.
+    for filter in ["color = red) OR (color = blue", "color =", "color = 'red"] {
+        let content = key_content(json!(["products"]), json!({ "products": filter }));
+        let (response, code) = server.add_api_key(content).await;
+        assert_eq!(code, 400, "{}", response);
+        assert_eq!(response["code"], "invalid_api_key_search_filters");
+    }
+
+    // the attribute is not filterable on the existing index.
+    let content = key_content(json!(["sales"]), json!({ "sales": "tenant = acme" }));
+    let (response, code) = server.add_api_key(content).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_api_key_search_filters");
+
+    // the filter of an index that doesn't exist yet is only checked when searching.
+    let content = key_content(json!(["products"]), json!({ "products": "tenant = acme" }));
+    let (response, code) = server.add_api_key(content).await;
+    assert_eq!(code, 201, "{}", response);
+    let key = response["key"].as_str().unwrap().to_string();
+
+    let index = server.index("products");
+    index.add_documents(documents(), None).await;
+    index.wait_task(2).await;
+
+    server.use_api_key(&key);
+    let (response, code) = server.index("products").search_post(json!({})).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_filter");
+}
@@ -445,6 +445,19 @@ async fn error_search_forbidden_token() {
             "searchRules" => json!(["sales"]),
             "exp" => json!((OffsetDateTime::now_utc() - Duration::hours(1)).unix_timestamp())
         },
+        // invalid filter
+        hashmap! {
+            "searchRules" => json!({"sales": {"filter": "color = blue) OR (color = red"}}),
+            "exp" => Value::Null
+        },
+        hashmap! {
+            "searchRules" => json!({"*": {"filter": ["color ="]}}),
+            "exp" => Value::Null
+        },
+        hashmap! {
+            "searchRules" => json!({"*": {"filter": 42}}),
+            "exp" => Value::Null
+        },
     ];
 
     compute_forbidden_search!(tenant_tokens, ACCEPTED_KEYS);
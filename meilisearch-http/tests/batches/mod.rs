@@ -0,0 +1,75 @@
+use crate::common::Server;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn list_batches() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }]), None)
+        .await;
+    index.wait_task(0).await;
+    // no primary key can be infered from these documents.
+    index
+        .add_documents(json!([{ "content": "foo" }]), None)
+        .await;
+    index.wait_task(1).await;
+
+    let (response, code) = server.batches().await;
+    assert_eq!(code, 200);
+    let results = response["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+
+    assert_eq!(results[0]["uid"], 1);
+    assert_eq!(results[0]["outcome"], "failed");
+    assert!(results[0]["error"]["code"].is_string());
+
+    assert_eq!(results[1]["uid"], 0);
+    assert_eq!(results[1]["indexUid"], "test");
+    assert_eq!(results[1]["taskUids"], json!([0]));
+    assert_eq!(results[1]["taskCount"], 1);
+    assert_eq!(results[1]["documentsCount"], 2);
+    assert_eq!(results[1]["outcome"], "succeeded");
+    assert_eq!(results[1]["stopReason"], "noMoreTasks");
+    assert!(results[1]["duration"].is_string());
+    assert!(results[1].get("error").is_none());
+
+    let (response, code) = server.service.get("/batches?limit=1").await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 1);
+    assert_eq!(response["next"], 0);
+
+    let (response, code) = server.service.get("/batches/0").await;
+    assert_eq!(code, 200);
+    assert_eq!(response["outcome"], "succeeded");
+}
+
+#[actix_rt::test]
+async fn recent_batches_in_stats() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["recentBatches"].as_array().unwrap().len(), 1);
+    assert_eq!(response["recentBatches"][0]["uid"], 0);
+    assert_eq!(response["recentBatches"][0]["stopReason"], "notBatchable");
+}
+
+#[actix_rt::test]
+async fn error_get_unexisting_batch() {
+    let server = Server::new().await;
+    let (response, code) = server.service.get("/batches/1").await;
+
+    let expected_response = json!({
+        "message": "Batch `1` not found.",
+        "code": "batch_not_found",
+        "type": "invalid_request",
+        "link": "https://docs.meilisearch.com/errors#batch_not_found"
+    });
+
+    assert_eq!(response, expected_response);
+    assert_eq!(code, 404);
+}
@@ -0,0 +1,64 @@
+use actix_web::test;
+use serde_json::{json, Value};
+
+use crate::common::server::default_settings;
+use crate::common::Server;
+
+use meilisearch_http::Opt;
+
+fn streamed_records(body: &[u8]) -> Vec<Value> {
+    serde_json::Deserializer::from_slice(body)
+        .into_iter::<Value>()
+        .map(|record| record.unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn applied_batches_are_recorded() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = Opt {
+        enable_changefeed: true,
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }, { "id": 3 }]), None)
+        .await;
+    index.wait_task(0).await;
+    index.delete_batch(vec![1, 3]).await;
+    index.wait_task(1).await;
+    index.clear_all_documents().await;
+    index.wait_task(2).await;
+
+    let req = test::TestRequest::get().uri("/indexes/test/changes");
+    let (body, code) = server.service.request_raw(req).await;
+    assert_eq!(code, 200);
+    let records = streamed_records(&body);
+    assert_eq!(records.len(), 3, "{:?}", records);
+
+    assert_eq!(records[0]["operation"], "documentAddition");
+    assert_eq!(records[0]["taskIds"], json!([0]));
+    assert_eq!(records[0]["documentIds"], json!(["1", "2", "3"]));
+    assert_eq!(records[1]["operation"], "documentDeletion");
+    assert_eq!(records[1]["documentIds"], json!(["1", "3"]));
+    assert_eq!(records[2]["operation"], "clearAll");
+    assert_eq!(records[2]["allDocuments"], true);
+
+    // the consumers resume from the batch following the last one they read.
+    let next = records[1]["batchId"].as_u64().unwrap() + 1;
+    let req = test::TestRequest::get().uri(&format!("/indexes/test/changes?fromBatch={}", next));
+    let (body, code) = server.service.request_raw(req).await;
+    assert_eq!(code, 200);
+    assert_eq!(streamed_records(&body), vec![records[2].clone()]);
+}
+
+#[actix_rt::test]
+async fn error_changefeed_disabled() {
+    let server = Server::new().await;
+
+    let (response, code) = server.service.get("/indexes/test/changes").await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "bad_request");
+}
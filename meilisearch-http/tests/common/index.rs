@@ -147,6 +147,10 @@ impl Index<'_> {
             url.push_str(&format!("offset={}&", offset));
         }
 
+        if let Some(cursor) = options.cursor {
+            url.push_str(&format!("cursor={}&", cursor));
+        }
+
         if let Some(attributes_to_retrieve) = options.attributes_to_retrieve {
             url.push_str(&format!("fields={}&", attributes_to_retrieve.join(",")));
         }
@@ -174,6 +178,69 @@ impl Index<'_> {
             .await
     }
 
+    pub async fn fetch_documents(&self, body: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/documents/fetch", encode(self.uid.as_ref()));
+        self.service.post(url, body).await
+    }
+
+    pub async fn edit_documents(&self, body: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/documents/edit", encode(self.uid.as_ref()));
+        self.service.post(url, body).await
+    }
+
+    pub async fn validate_documents(
+        &self,
+        documents: Value,
+        primary_key: Option<&str>,
+    ) -> (Value, StatusCode) {
+        let url = match primary_key {
+            Some(key) => format!(
+                "/indexes/{}/documents/validate?primaryKey={}",
+                encode(self.uid.as_ref()),
+                key
+            ),
+            None => format!("/indexes/{}/documents/validate", encode(self.uid.as_ref())),
+        };
+        self.service.post(url, documents).await
+    }
+
+    pub async fn create_upload(&self, body: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/documents/uploads", encode(self.uid.as_ref()));
+        self.service.post(url, body).await
+    }
+
+    pub async fn get_upload(&self, upload_id: &str) -> (Value, StatusCode) {
+        let url = format!(
+            "/indexes/{}/documents/uploads/{}",
+            encode(self.uid.as_ref()),
+            upload_id
+        );
+        self.service.get(url).await
+    }
+
+    pub async fn append_to_upload(
+        &self,
+        upload_id: &str,
+        content_range: Option<&str>,
+        chunk: impl AsRef<[u8]>,
+    ) -> (Value, StatusCode) {
+        let url = format!(
+            "/indexes/{}/documents/uploads/{}",
+            encode(self.uid.as_ref()),
+            upload_id
+        );
+        self.service.put_chunk(url, content_range, chunk).await
+    }
+
+    pub async fn commit_upload(&self, upload_id: &str) -> (Value, StatusCode) {
+        let url = format!(
+            "/indexes/{}/documents/uploads/{}/commit",
+            encode(self.uid.as_ref()),
+            upload_id
+        );
+        self.service.post(url, Value::Null).await
+    }
+
     pub async fn settings(&self) -> (Value, StatusCode) {
         let url = format!("/indexes/{}/settings", encode(self.uid.as_ref()));
         self.service.get(url).await
@@ -189,11 +256,41 @@ impl Index<'_> {
         self.service.delete(url).await
     }
 
+    pub async fn check(&self, fast: bool) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/check?fast={}", encode(self.uid.as_ref()), fast);
+        self.service.post(url, Value::Null).await
+    }
+
+    pub async fn repair_stats(&self) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/repair-stats", encode(self.uid.as_ref()));
+        self.service.post(url, Value::Null).await
+    }
+
+    pub async fn rebuild(&self) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/rebuild", encode(self.uid.as_ref()));
+        self.service.post(url, Value::Null).await
+    }
+
+    pub async fn close(&self) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/close", encode(self.uid.as_ref()));
+        self.service.post(url, Value::Null).await
+    }
+
+    pub async fn open(&self) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/open", encode(self.uid.as_ref()));
+        self.service.post(url, Value::Null).await
+    }
+
     pub async fn stats(&self) -> (Value, StatusCode) {
         let url = format!("/indexes/{}/stats", encode(self.uid.as_ref()));
         self.service.get(url).await
     }
 
+    pub async fn detailed_stats(&self) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/stats?detailed=true", encode(self.uid.as_ref()));
+        self.service.get(url).await
+    }
+
     /// Performs both GET and POST search queries
     pub async fn search(
         &self,
@@ -219,6 +316,11 @@ impl Index<'_> {
         self.service.post(url, query).await
     }
 
+    pub async fn aggregate(&self, query: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/aggregate", encode(self.uid.as_ref()));
+        self.service.post(url, query).await
+    }
+
     pub async fn search_get(&self, query: Value) -> (Value, StatusCode) {
         let params = serde_url_params::to_string(&query).unwrap();
         let url = format!("/indexes/{}/search?{}", encode(self.uid.as_ref()), params);
@@ -252,5 +354,6 @@ pub struct GetDocumentOptions {
 pub struct GetAllDocumentsOptions {
     pub limit: Option<usize>,
     pub offset: Option<usize>,
+    pub cursor: Option<String>,
     pub attributes_to_retrieve: Option<Vec<&'static str>>,
 }
@@ -5,9 +5,8 @@ use std::path::Path;
 
 use actix_web::http::StatusCode;
 use byte_unit::{Byte, ByteUnit};
-use meilisearch_auth::AuthController;
-use meilisearch_http::setup_meilisearch;
-use meilisearch_lib::options::{IndexerOpts, MaxMemory};
+use meilisearch_http::{setup_auth_controller, setup_meilisearch, setup_replica};
+use meilisearch_lib::options::{IndexerOpts, MaxMemory, Sensitive};
 use once_cell::sync::Lazy;
 use serde_json::Value;
 use tempfile::TempDir;
@@ -38,7 +37,7 @@ impl Server {
         let options = default_settings(dir.path());
 
         let meilisearch = setup_meilisearch(&options).unwrap();
-        let auth = AuthController::new(&options.db_path, &options.master_key).unwrap();
+        let auth = setup_auth_controller(&options).unwrap();
         let service = Service {
             meilisearch,
             auth,
@@ -62,10 +61,10 @@ impl Server {
         }
 
         let mut options = default_settings(dir.path());
-        options.master_key = Some("MASTER_KEY".to_string());
+        options.master_key = Some(Sensitive::new("MASTER_KEY".to_string()));
 
         let meilisearch = setup_meilisearch(&options).unwrap();
-        let auth = AuthController::new(&options.db_path, &options.master_key).unwrap();
+        let auth = setup_auth_controller(&options).unwrap();
         let service = Service {
             meilisearch,
             auth,
@@ -80,8 +79,9 @@ impl Server {
     }
 
     pub async fn new_with_options(options: Opt) -> Result<Self, anyhow::Error> {
+        setup_replica(&options).await?;
         let meilisearch = setup_meilisearch(&options)?;
-        let auth = AuthController::new(&options.db_path, &options.master_key)?;
+        let auth = setup_auth_controller(&options)?;
         let service = Service {
             meilisearch,
             auth,
@@ -138,6 +138,10 @@ impl Server {
         self.service.get("/tasks").await
     }
 
+    pub async fn batches(&self) -> (Value, StatusCode) {
+        self.service.get("/batches").await
+    }
+
     pub async fn get_dump_status(&self, uid: &str) -> (Value, StatusCode) {
         self.service.get(format!("/dumps/{}/status", uid)).await
     }
@@ -153,10 +157,12 @@ pub fn default_settings(dir: impl AsRef<Path>) -> Opt {
         max_index_size: Byte::from_unit(100.0, ByteUnit::MiB).unwrap(),
         max_task_db_size: Byte::from_unit(1.0, ByteUnit::GiB).unwrap(),
         http_payload_size_limit: Byte::from_unit(10.0, ByteUnit::MiB).unwrap(),
-        snapshot_dir: ".".into(),
+        snapshot_dir: dir.as_ref().join("snapshots"),
         indexer_options: IndexerOpts {
             // memory has to be unlimited because several meilisearch are running in test context.
             max_indexing_memory: MaxMemory::unlimited(),
+            // no ratio is above 1, so that no compaction task shifts the uids of the tasks.
+            compaction_threshold: 1.0,
             ..Parser::parse_from(None as Option<&str>)
         },
         ..Parser::parse_from(None as Option<&str>)
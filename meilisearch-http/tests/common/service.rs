@@ -1,3 +1,4 @@
+use actix_web::http::header::HeaderMap;
 use actix_web::{http::StatusCode, test};
 use meilisearch_auth::AuthController;
 use meilisearch_lib::MeiliSearch;
@@ -36,6 +37,38 @@ impl Service {
         (response, status_code)
     }
 
+    /// Send a test post request with an `x-idempotency-key` header.
+    pub async fn post_idempotent(
+        &self,
+        url: impl AsRef<str>,
+        body: Value,
+        idempotency_key: &str,
+    ) -> (Value, StatusCode) {
+        let app = test::init_service(create_app!(
+            &self.meilisearch,
+            &self.auth,
+            true,
+            &self.options,
+            analytics::MockAnalytics::new(&self.options).0
+        ))
+        .await;
+
+        let mut req = test::TestRequest::post()
+            .uri(url.as_ref())
+            .set_json(&body)
+            .insert_header(("x-idempotency-key", idempotency_key));
+        if let Some(api_key) = &self.api_key {
+            req = req.insert_header(("Authorization", ["Bearer ", api_key].concat()));
+        }
+        let req = req.to_request();
+        let res = test::call_service(&app, req).await;
+        let status_code = res.status();
+
+        let body = test::read_body(res).await;
+        let response = serde_json::from_slice(&body).unwrap_or_default();
+        (response, status_code)
+    }
+
     /// Send a test post request from a text body, with a `content-type:application/json` header.
     pub async fn post_str(
         &self,
@@ -67,6 +100,52 @@ impl Service {
         (response, status_code)
     }
 
+    /// Send `req` and also returns the headers of the response.
+    pub async fn request(&self, mut req: test::TestRequest) -> (Value, StatusCode, HeaderMap) {
+        let app = test::init_service(create_app!(
+            &self.meilisearch,
+            &self.auth,
+            true,
+            &self.options,
+            analytics::MockAnalytics::new(&self.options).0
+        ))
+        .await;
+
+        if let Some(api_key) = &self.api_key {
+            req = req.insert_header(("Authorization", ["Bearer ", api_key].concat()));
+        }
+        let req = req.to_request();
+        let res = test::call_service(&app, req).await;
+        let status_code = res.status();
+        let headers = res.headers().clone();
+
+        let body = test::read_body(res).await;
+        let response = serde_json::from_slice(&body).unwrap_or_default();
+        (response, status_code, headers)
+    }
+
+    /// Send `req` and returns the raw body of the response.
+    pub async fn request_raw(&self, mut req: test::TestRequest) -> (Vec<u8>, StatusCode) {
+        let app = test::init_service(create_app!(
+            &self.meilisearch,
+            &self.auth,
+            true,
+            &self.options,
+            analytics::MockAnalytics::new(&self.options).0
+        ))
+        .await;
+
+        if let Some(api_key) = &self.api_key {
+            req = req.insert_header(("Authorization", ["Bearer ", api_key].concat()));
+        }
+        let req = req.to_request();
+        let res = test::call_service(&app, req).await;
+        let status_code = res.status();
+
+        let body = test::read_body(res).await;
+        (body.to_vec(), status_code)
+    }
+
     pub async fn get(&self, url: impl AsRef<str>) -> (Value, StatusCode) {
         let app = test::init_service(create_app!(
             &self.meilisearch,
@@ -113,6 +192,40 @@ impl Service {
         (response, status_code)
     }
 
+    /// Send a test put request with a raw body and an optional `content-range` header.
+    pub async fn put_chunk(
+        &self,
+        url: impl AsRef<str>,
+        content_range: Option<&str>,
+        body: impl AsRef<[u8]>,
+    ) -> (Value, StatusCode) {
+        let app = test::init_service(create_app!(
+            &self.meilisearch,
+            &self.auth,
+            true,
+            &self.options,
+            analytics::MockAnalytics::new(&self.options).0
+        ))
+        .await;
+
+        let mut req = test::TestRequest::put()
+            .uri(url.as_ref())
+            .set_payload(body.as_ref().to_vec());
+        if let Some(content_range) = content_range {
+            req = req.insert_header(("content-range", content_range));
+        }
+        if let Some(api_key) = &self.api_key {
+            req = req.insert_header(("Authorization", ["Bearer ", api_key].concat()));
+        }
+        let req = req.to_request();
+        let res = test::call_service(&app, req).await;
+        let status_code = res.status();
+
+        let body = test::read_body(res).await;
+        let response = serde_json::from_slice(&body).unwrap_or_default();
+        (response, status_code)
+    }
+
     pub async fn patch(&self, url: impl AsRef<str>, body: Value) -> (Value, StatusCode) {
         let app = test::init_service(create_app!(
             &self.meilisearch,
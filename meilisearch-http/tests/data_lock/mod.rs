@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use meilisearch_http::Opt;
+
+use crate::common::{default_settings, Server};
+
+/// Asserts that `error` tells the data directory is locked by this process.
+fn assert_in_use(error: anyhow::Error) {
+    let error = error.to_string();
+    assert!(
+        error.contains(&format!(
+            "already in use by the process {}",
+            std::process::id()
+        )),
+        "{}",
+        error
+    );
+}
+
+#[actix_rt::test]
+#[cfg_attr(target_os = "windows", ignore)]
+async fn second_instance_fails_fast() {
+    let temp = tempfile::tempdir().unwrap();
+    let server = Server::new_with_options(default_settings(temp.path()))
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let error = Server::new_with_options(default_settings(temp.path()))
+        .await
+        .err()
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_in_use(error);
+
+    // the first instance keeps serving its data directory.
+    let (response, code) = server.index("test").create(None).await;
+    assert_eq!(code, 202, "{}", response);
+    let response = server.index("test").wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+}
+
+#[actix_rt::test]
+#[cfg_attr(target_os = "windows", ignore)]
+async fn imports_and_reindexing_wait_for_the_server() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = default_settings(temp.path());
+    let _server = Server::new_with_options(options.clone()).await.unwrap();
+
+    // an import that would be skipped on a data directory that already exists still needs the
+    // lock.
+    let snapshot = Opt {
+        import_snapshot: Some(temp.path().join("missing.snapshot")),
+        ignore_missing_snapshot: true,
+        ignore_snapshot_if_db_exists: true,
+        ..default_settings(temp.path())
+    };
+    assert_in_use(Server::new_with_options(snapshot).await.err().unwrap());
+
+    let dump = Opt {
+        import_dump: Some(temp.path().join("missing.dump")),
+        ignore_missing_dump: true,
+        ignore_dump_if_db_exists: true,
+        ..default_settings(temp.path())
+    };
+    assert_in_use(Server::new_with_options(dump).await.err().unwrap());
+
+    let error = meilisearch_lib::reindex_dump(
+        temp.path().join("missing.dump"),
+        &options.db_path,
+        1,
+        options.max_index_size.get_bytes() as usize,
+        options.max_task_db_size.get_bytes() as usize,
+        &options.indexer_options,
+        |_| (),
+    )
+    .unwrap_err();
+    assert_in_use(error);
+}
@@ -1,5 +1,6 @@
-use crate::common::{GetAllDocumentsOptions, Server};
+use crate::common::{default_settings, GetAllDocumentsOptions, Server};
 use actix_web::test;
+use byte_unit::Byte;
 use meilisearch_http::{analytics, create_app};
 use serde_json::{json, Value};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
@@ -638,7 +639,7 @@ async fn error_document_add_create_index_bad_uid() {
     let (response, code) = index.add_documents(json!([{"id": 1}]), None).await;
 
     let expected_response = json!({
-        "message": "`883  fj!` is not a valid index uid. Index uid can be an integer or a string containing only alphanumeric characters, hyphens (-) and underscores (_).",
+        "message": "`883  fj!` is not a valid index uid: the character ' ' at position 3 is not allowed. Index uid must be between 1 and 400 bytes long, and can only contain the characters `a-z`, `A-Z`, `0-9`, `-` and `_`.",
         "code": "invalid_index_uid",
         "type": "invalid_request",
         "link": "https://docs.meilisearch.com/errors#invalid_index_uid"
@@ -655,7 +656,7 @@ async fn error_document_update_create_index_bad_uid() {
     let (response, code) = index.update_documents(json!([{"id": 1}]), None).await;
 
     let expected_response = json!({
-        "message": "`883  fj!` is not a valid index uid. Index uid can be an integer or a string containing only alphanumeric characters, hyphens (-) and underscores (_).",
+        "message": "`883  fj!` is not a valid index uid: the character ' ' at position 3 is not allowed. Index uid must be between 1 and 400 bytes long, and can only contain the characters `a-z`, `A-Z`, `0-9`, `-` and `_`.",
         "code": "invalid_index_uid",
         "type": "invalid_request",
         "link": "https://docs.meilisearch.com/errors#invalid_index_uid"
@@ -762,11 +763,202 @@ async fn replace_document() {
 }
 
 #[actix_rt::test]
-async fn add_no_documents() {
+async fn error_add_no_documents() {
     let server = Server::new().await;
     let index = server.index("test");
-    let (_response, code) = index.add_documents(json!([]), None).await;
+    let (response, code) = index.add_documents(json!([]), None).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(
+        response["message"],
+        "The json payload does not contain any document."
+    );
+    assert_eq!(response["code"], "missing_payload");
+
+    // no task was registered, and the index was not created.
+    let (response, _) = server.tasks().await;
+    assert!(response["results"].as_array().unwrap().is_empty());
+    let (_, code) = index.get().await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn add_no_documents_allowed() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let (response, code) = server
+        .service
+        .post("/indexes/test/documents?allowEmptyPayload=true", json!([]))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["status"], "succeeded");
+
+    let (response, code) = index.get_task(0).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["status"], "succeeded");
+    assert_eq!(response["details"]["receivedDocuments"], 0);
+    assert_eq!(response["details"]["indexedDocuments"], 0);
+
+    // the empty addition never creates the index.
+    let (_, code) = index.get().await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn add_documents_with_duplicate_ids_keeps_last_occurrence() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "first", "genre": "drama" },
+        { "id": 2, "title": "other" },
+        { "id": 1, "title": "last" },
+        { "id": 2, "title": "other again" },
+        { "id": 1, "title": "really last" },
+    ]);
+    let (_, code) = index.add_documents(documents, None).await;
+    assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["receivedDocuments"], 5);
+    assert_eq!(response["details"]["indexedDocuments"], 2);
+    assert_eq!(response["details"]["duplicateDocuments"], 3);
+
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!({ "id": 1, "title": "really last" }));
+    let (response, _) = index.get_document(2, None).await;
+    assert_eq!(response, json!({ "id": 2, "title": "other again" }));
+}
+
+#[actix_rt::test]
+async fn update_documents_with_duplicate_ids_merges_occurrences() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "first", "genre": "drama" },
+        { "id": 1, "title": "last", "year": 2000 },
+    ]);
+    let (_, code) = index.update_documents(documents, None).await;
     assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["indexedDocuments"], 1);
+    assert_eq!(response["details"]["duplicateDocuments"], 1);
+
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(
+        response,
+        json!({ "id": 1, "title": "last", "genre": "drama", "year": 2000 })
+    );
+}
+
+#[actix_rt::test]
+async fn add_documents_without_duplicates_has_no_duplicate_count() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }]), None)
+        .await;
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded");
+    assert!(response["details"].get("duplicateDocuments").is_none());
+}
+
+#[actix_rt::test]
+async fn error_add_documents_with_duplicate_ids_strict() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "first" },
+        { "id": "b", "title": "other" },
+        { "id": "b", "title": "other again" },
+        { "id": 1, "title": "last" },
+        { "id": 3 },
+    ]);
+    let (response, code) = server
+        .service
+        .post("/indexes/test/documents?rejectDuplicates=true", documents)
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+    assert_eq!(
+        response["error"]["message"],
+        json!("The payload contains several documents with the same primary key: `1`, `b`.")
+    );
+    assert_eq!(response["error"]["code"], json!("duplicate_documents"));
+    assert_eq!(response["error"]["type"], json!("invalid_request"));
+
+    // none of the documents of the payload were added.
+    let (_, code) = index.get_document(3, None).await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn add_documents_returning_the_diff() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }]), Some("id"))
+        .await;
+    index.wait_task(0).await;
+
+    let documents = json!([{ "id": 2 }, { "id": 3 }, { "id": 1 }]);
+    let (response, code) = server
+        .service
+        .post("/indexes/test/documents?returnDiff=summary", documents)
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(
+        response["details"]["diff"],
+        json!({ "created": 1, "updated": 2 })
+    );
+
+    let documents = json!([{ "id": 4 }, { "id": 3 }, { "id": 1 }]);
+    let (response, code) = server
+        .service
+        .put("/indexes/test/documents?returnDiff=full", documents)
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(
+        response["details"]["diff"],
+        json!({ "created": 1, "updated": 2, "replacedIds": ["1", "3"] })
+    );
+
+    // without the flag, no diff is computed.
+    index.add_documents(json!([{ "id": 5 }]), None).await;
+    let response = index.wait_task(3).await;
+    assert!(response["details"].get("diff").is_none(), "{}", response);
+}
+
+#[actix_rt::test]
+async fn add_documents_returning_a_full_diff_over_the_limit() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.max_full_diff_documents = 1;
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let documents = json!([{ "id": 1 }, { "id": 2 }]);
+    let (response, code) = server
+        .service
+        .post("/indexes/test/documents?returnDiff=full", documents)
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(
+        response["details"]["diff"],
+        json!({ "created": 2, "updated": 0 })
+    );
 }
 
 #[actix_rt::test]
@@ -1077,6 +1269,47 @@ async fn error_primary_key_inference() {
     assert_eq!(response["error"], expected_error);
 }
 
+#[actix_rt::test]
+async fn failed_tasks_have_specific_codes() {
+    let cases = [
+        (json!([{ "id": "foo & bar" }]), None, "invalid_document_id"),
+        (
+            json!([{ "title": "no id" }]),
+            Some("id"),
+            "missing_document_id",
+        ),
+        (
+            json!([{ "title": "no id" }]),
+            None,
+            "primary_key_inference_failed",
+        ),
+    ];
+    for (documents, primary_key, expected_code) in cases {
+        let server = Server::new().await;
+        let index = server.index("test");
+        if let Some(primary_key) = primary_key {
+            index.create(Some(primary_key)).await;
+        }
+        let (response, code) = index.add_documents(documents, None).await;
+        assert_eq!(code, 202, "{}", response);
+        let response = index.wait_task(response["taskUid"].as_u64().unwrap()).await;
+        assert_eq!(response["status"], "failed", "{}", response);
+        assert_eq!(response["error"]["code"], expected_code, "{}", response);
+        // the causes are only given when they tell more than the message.
+        assert!(response["error"].get("causes").is_none(), "{}", response);
+    }
+
+    // the primary key of an index with documents can't be changed.
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), Some("id")).await;
+    index.wait_task(0).await;
+    index.update(Some("other")).await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+    assert_eq!(response["error"]["code"], "primary_key_already_present");
+}
+
 #[actix_rt::test]
 async fn add_documents_with_primary_key_twice() {
     let server = Server::new().await;
@@ -1099,3 +1332,410 @@ async fn add_documents_with_primary_key_twice() {
     let (response, _code) = index.get_task(1).await;
     assert_eq!(response["status"], "succeeded");
 }
+
+#[actix_rt::test]
+async fn add_documents_over_pending_payload_quota() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.max_pending_payload_size_per_index = Some(Byte::from_bytes(1));
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let documents = json!([{ "id": 1, "content": "foobar" }]);
+    let (response, code) = index.add_documents(documents, None).await;
+    assert_eq!(code, 413, "{}", response);
+    assert_eq!(response["code"], "pending_payload_quota_exceeded");
+    assert_eq!(response["type"], "invalid_request");
+    assert_eq!(
+        response["link"],
+        "https://docs.meilisearch.com/errors#pending_payload_quota_exceeded"
+    );
+
+    // the refused addition was not registered.
+    let (response, code) = index.list_tasks().await;
+    assert_eq!(code, 200);
+    assert!(response["results"].as_array().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn pending_payloads_are_released_once_processed() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.max_pending_payload_size_per_index =
+        Some(Byte::from_bytes(1024 * 1024));
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let documents = json!([{ "id": 1, "content": "foobar" }]);
+    let (response, code) = index.add_documents(documents, None).await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded");
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["pendingPayloadSize"], 0);
+}
+
+#[actix_rt::test]
+async fn add_documents_over_hard_limits() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.max_document_fields = Some(3);
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let documents = json!([
+        { "id": 1, "title": "a" },
+        { "id": 2, "author": { "name": "b", "born": 1900 } },
+    ]);
+    let (response, code) = index.add_documents(documents, None).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "document_over_limit");
+    assert_eq!(response["type"], "invalid_request");
+    assert_eq!(
+        response["message"],
+        "The payload provided exceeds the limits on the documents: the document 1 has 4 fields, more than the maximum of 3."
+    );
+
+    // the refused addition was not registered.
+    let (response, code) = index.list_tasks().await;
+    assert_eq!(code, 200);
+    assert!(response["results"].as_array().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn add_documents_over_soft_limits() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.soft_max_document_fields = Some(2);
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let documents = json!([
+        { "id": 1, "title": "a" },
+        { "id": 2, "title": "b", "author": "c" },
+        { "id": 3, "title": "d", "year": 1900 },
+    ]);
+    let (response, code) = index.add_documents(documents, None).await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["indexedDocuments"], 3);
+    assert_eq!(response["details"]["warnings"], 2);
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 3);
+    assert_eq!(response["documentsOverSoftLimits"], 2);
+}
+
+#[actix_rt::test]
+async fn add_documents_in_chunks() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.document_chunk_size = Some(2);
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let documents = json!([
+        { "id": 1, "title": "a" },
+        { "id": 2, "title": "b" },
+        { "id": 3, "title": "c" },
+        { "id": 4, "title": "d" },
+        { "id": 5, "title": "e" },
+    ]);
+    let (response, code) = index.add_documents(documents, Some("id")).await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["receivedDocuments"], 5);
+    assert_eq!(response["details"]["indexedDocuments"], 5);
+    assert_eq!(response["details"]["totalChunks"], 3);
+    assert_eq!(response["details"]["processedChunks"], 3);
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 5);
+
+    // the update files of all the chunks were deleted.
+    let (response, _) = index.stats().await;
+    assert_eq!(response["pendingPayloadSize"], 0);
+}
+
+async fn add_documents_with_a_failing_chunk(defer_chunk_visibility: bool) -> Value {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.document_chunk_size = Some(2);
+    options.indexer_options.defer_chunk_visibility = defer_chunk_visibility;
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    // the second chunk contains the same id twice.
+    let documents = json!([
+        { "id": 1 },
+        { "id": 2 },
+        { "id": 3 },
+        { "id": 3 },
+        { "id": 4 },
+    ]);
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes/test/documents?primaryKey=id&rejectDuplicates=true",
+            documents,
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+    assert_eq!(response["error"]["code"], "duplicate_documents");
+    assert!(response["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Chunk 1 (numbered from 0) of the 3 chunks"));
+    assert_eq!(response["details"]["totalChunks"], 3);
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(code, 200);
+    response["results"].clone()
+}
+
+#[actix_rt::test]
+async fn failed_chunk_keeps_the_previous_chunks() {
+    let documents = add_documents_with_a_failing_chunk(false).await;
+    assert_eq!(documents, json!([{ "id": 1 }, { "id": 2 }]));
+}
+
+#[actix_rt::test]
+async fn failed_chunk_with_deferred_visibility_adds_nothing() {
+    let documents = add_documents_with_a_failing_chunk(true).await;
+    assert_eq!(documents, json!([]));
+}
+
+/// Registers a JSON document addition whose payload is `chunks`.
+async fn add_documents_from_stream(
+    server: &Server,
+    chunks: impl futures::Stream<Item = Result<bytes::Bytes, actix_web::error::PayloadError>>
+        + Send
+        + Sync
+        + Unpin
+        + 'static,
+) -> Result<(), meilisearch_lib::index_controller::error::IndexControllerError> {
+    use meilisearch_lib::index_controller::{DocumentAdditionFormat, Update};
+    use meilisearch_lib::milli::update::IndexDocumentsMethod;
+
+    let update = Update::DocumentAddition {
+        payload: Box::new(chunks),
+        primary_key: None,
+        method: IndexDocumentsMethod::ReplaceDocuments,
+        format: DocumentAdditionFormat::Json,
+        allow_index_creation: true,
+        allow_empty_payload: false,
+        reject_duplicates: false,
+        return_diff: None,
+        sanitize: false,
+    };
+    server
+        .service
+        .meilisearch
+        .register_update("test".to_string(), update)
+        .await
+        .map(drop)
+}
+
+/// Asserts that no task was registered, and that no update file was left behind.
+async fn assert_nothing_registered(server: &Server) {
+    let (response, code) = server.tasks().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["results"], json!([]));
+
+    let updates_files = server.service.options.db_path.join("updates/updates_files");
+    let files: Vec<_> = std::fs::read_dir(updates_files)
+        .map(|entries| entries.collect())
+        .unwrap_or_default();
+    assert!(files.is_empty(), "{:?}", files);
+}
+
+#[actix_rt::test]
+async fn interrupted_payload_registers_nothing() {
+    use actix_web::error::PayloadError;
+
+    let server = Server::new().await;
+    let chunks = futures::stream::iter([
+        Ok(bytes::Bytes::from_static(br#"[{ "id": 1, "content": "#)),
+        Err(PayloadError::Incomplete(None)),
+    ]);
+
+    assert!(add_documents_from_stream(&server, chunks).await.is_err());
+    assert_nothing_registered(&server).await;
+}
+
+#[actix_rt::test]
+async fn payload_timeout_registers_nothing() {
+    use futures::StreamExt;
+    use meilisearch_error::ErrorCode;
+
+    let temp = tempfile::tempdir().unwrap();
+    let options = meilisearch_http::Opt {
+        payload_timeout_sec: Some(1),
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+
+    // the client stops sending the payload midway.
+    let chunks = futures::stream::iter([Ok(bytes::Bytes::from_static(br#"[{ "id": 1"#))])
+        .chain(futures::stream::pending());
+
+    let error = add_documents_from_stream(&server, chunks)
+        .await
+        .unwrap_err();
+    assert_eq!(error.error_name(), "payload_timeout");
+    assert_eq!(
+        error.http_status(),
+        actix_web::http::StatusCode::REQUEST_TIMEOUT
+    );
+    assert_nothing_registered(&server).await;
+}
+
+#[actix_rt::test]
+async fn error_add_documents_with_control_characters() {
+    let server = Server::new().await;
+    let documents = json!([
+        { "id": 1, "title": "clean" },
+        { "id": 2, "title": "bell \u{7}" },
+    ]);
+    let (response, code) = server
+        .service
+        .post("/indexes/test/documents", documents)
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(
+        response["message"],
+        json!("The `json` payload provided is malformed: control character in the field `title` of the document 1. Add `sanitize=true` to the query parameters to replace the invalid characters with U+FFFD.")
+    );
+    assert_eq!(response["code"], json!("malformed_payload"));
+
+    let (response, _) = server.tasks().await;
+    assert_eq!(response["results"], json!([]));
+}
+
+#[actix_rt::test]
+async fn add_documents_sanitizing_control_characters() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "bell \u{7}", "tags": ["\u{0}", "clean"] },
+        { "id": 2, "title": "clean" },
+    ]);
+    let (response, code) = server
+        .service
+        .post("/indexes/test/documents?sanitize=true", documents)
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["sanitizedCharacters"], json!(2));
+
+    let (document, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(
+        document,
+        json!({ "id": 1, "title": "bell \u{FFFD}", "tags": ["\u{FFFD}", "clean"] })
+    );
+
+    // without the flag, the count isn't shown.
+    index.add_documents(json!([{ "id": 3 }]), None).await;
+    let response = index.wait_task(1).await;
+    assert!(
+        response["details"].get("sanitizedCharacters").is_none(),
+        "{}",
+        response
+    );
+}
+
+#[actix_rt::test]
+async fn add_documents_with_transforms() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    index
+        .add_documents(
+            json!([{ "id": 1, "description": "<p>Old</p>" }]),
+            Some("id"),
+        )
+        .await;
+    index.wait_task(0).await;
+
+    let (response, code) = index
+        .update_settings(json!({ "documentTransforms": [
+            { "type": "stripHtml", "field": "description" },
+            { "type": "copy", "field": "brand", "to": "brandLower" },
+            { "type": "toLowercase", "field": "brandLower" },
+            { "type": "concat", "fields": ["brand", "price"], "to": "label", "separator": " " },
+            { "type": "toLowercase", "field": "price" },
+        ]}))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(1).await;
+
+    let documents = json!([
+        { "id": 2, "description": "<p>A <b>fine</b> &amp; sturdy chair</p>", "brand": "ACME", "price": 12 },
+        { "id": 3, "description": "No markup" },
+    ]);
+    index.add_documents(documents, None).await;
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(
+        response["details"]["transforms"],
+        json!({ "applied": 5, "failedDocuments": 1 })
+    );
+
+    let (response, code) = index.get_document(2, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(
+        response,
+        json!({
+            "id": 2,
+            "description": "A fine & sturdy chair",
+            "brand": "ACME",
+            "brandLower": "acme",
+            "price": 12,
+            "label": "ACME 12",
+        })
+    );
+
+    // the documents added before the transforms were set are left as they are.
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!({ "id": 1, "description": "<p>Old</p>" }));
+}
+
+#[actix_rt::test]
+async fn error_invalid_document_transforms() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    index
+        .update_settings(
+            json!({ "documentTransforms": [{ "type": "concat", "fields": [], "to": "label" }] }),
+        )
+        .await;
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+    assert_eq!(response["error"]["code"], "invalid_document_transform");
+
+    let (response, code) = index
+        .update_settings(
+            json!({ "documentTransforms": [{ "type": "uppercase", "field": "title" }] }),
+        )
+        .await;
+    assert_eq!(code, 400, "{}", response);
+}
@@ -1,6 +1,6 @@
 use serde_json::json;
 
-use crate::common::{GetAllDocumentsOptions, Server};
+use crate::common::{default_settings, GetAllDocumentsOptions, Server};
 
 #[actix_rt::test]
 async fn delete_one_document_unexisting_index() {
@@ -145,3 +145,88 @@ async fn delete_no_document_batch() {
     assert_eq!(code, 200);
     assert_eq!(response["results"].as_array().unwrap().len(), 3);
 }
+
+#[actix_rt::test]
+async fn search_excludes_soft_deleted_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(
+            json!([
+                { "id": 0, "content": "foobar" },
+                { "id": 1, "content": "foobar" },
+                { "id": 2, "content": "foobar" },
+            ]),
+            Some("id"),
+        )
+        .await;
+    index.wait_task(0).await;
+
+    index.delete_document(1).await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded");
+    assert_eq!(response["details"]["deletedDocuments"], 1);
+
+    let (response, code) = index.search_post(json!({ "q": "foobar" })).await;
+    assert_eq!(code, 200, "{}", response);
+    let ids: Vec<_> = response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].clone())
+        .collect();
+    assert_eq!(ids, vec![json!(0), json!(2)]);
+    assert_eq!(response["estimatedTotalHits"], 2);
+
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 404, "{}", response);
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 2);
+
+    // a soft deleted document can be added back.
+    index
+        .add_documents(json!([{ "id": 1, "content": "foobar" }]), None)
+        .await;
+    index.wait_task(2).await;
+    let (response, code) = index.search_post(json!({ "q": "foobar" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 3);
+}
+
+#[actix_rt::test]
+async fn compaction_is_registered_above_threshold() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.compaction_threshold = 0.1;
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+    index
+        .add_documents(
+            json!([
+                { "id": 0, "content": "foobar" },
+                { "id": 1, "content": "foobar" },
+                { "id": 2, "content": "foobar" },
+            ]),
+            Some("id"),
+        )
+        .await;
+    index.wait_task(0).await;
+
+    index.delete_batch(vec![0, 1]).await;
+    index.wait_task(1).await;
+
+    let response = index.wait_task(2).await;
+    assert_eq!(response["type"], "documentsCompaction");
+    assert_eq!(response["status"], "succeeded");
+    assert_eq!(response["details"]["purgedDocuments"], 2);
+
+    let (response, code) = index.filtered_tasks(&["documentsCompaction"], &[]).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 1);
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 1);
+}
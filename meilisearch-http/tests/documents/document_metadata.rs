@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use meilisearch_http::Opt;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use crate::common::{default_settings, Server};
+
+async fn enable_metadata(server: &Server) {
+    let index = server.index("test");
+    let (response, code) = index
+        .update_settings(json!({ "documentMetadata": true }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(0).await;
+}
+
+async fn get_with_metadata(server: &Server, id: u64) -> Value {
+    let (response, code) = server
+        .service
+        .get(format!("/indexes/test/documents/{}?withMetadata=true", id))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    response
+}
+
+#[actix_rt::test]
+async fn metadata_disabled_by_default() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(0).await;
+
+    let document = get_with_metadata(&server, 1).await;
+    assert_eq!(document, json!({ "id": 1 }));
+}
+
+#[actix_rt::test]
+async fn replacing_document_only_changes_updated_at() {
+    let server = Server::new().await;
+    enable_metadata(&server).await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "first" }]), None)
+        .await;
+    index.wait_task(1).await;
+
+    let document = get_with_metadata(&server, 1).await;
+    let created_at = document["_createdAt"].as_i64().unwrap();
+    assert_eq!(document["_updatedAt"], created_at);
+
+    // the metadata are not part of the document.
+    let (document, _) = index.get_document(1, None).await;
+    assert_eq!(document, json!({ "id": 1, "title": "first" }));
+
+    // the timestamps have a resolution of one second.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    index
+        .add_documents(json!([{ "id": 1, "title": "second" }]), None)
+        .await;
+    index.wait_task(2).await;
+
+    let document = get_with_metadata(&server, 1).await;
+    assert_eq!(document["title"], "second");
+    assert_eq!(document["_createdAt"], created_at);
+    assert!(document["_updatedAt"].as_i64().unwrap() > created_at);
+}
+
+#[actix_rt::test]
+async fn filter_and_sort_on_metadata() {
+    let server = Server::new().await;
+    enable_metadata(&server).await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "filterableAttributes": ["genre"] }))
+        .await;
+    index.wait_task(1).await;
+    index
+        .add_documents(
+            json!([
+                { "id": 1, "genre": "drama" },
+                { "id": 2, "genre": "drama" },
+                { "id": 3, "genre": "comedy" },
+            ]),
+            None,
+        )
+        .await;
+    index.wait_task(2).await;
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    index
+        .update_documents(json!([{ "id": 1, "genre": "drama" }]), None)
+        .await;
+    index.wait_task(3).await;
+    let updated_at = get_with_metadata(&server, 1).await["_updatedAt"]
+        .as_i64()
+        .unwrap();
+
+    let (response, code) = index
+        .search_post(json!({ "filter": format!("_updatedAt >= {}", updated_at) }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"], json!([{ "id": 1, "genre": "drama" }]));
+
+    let (response, code) = index
+        .search_post(json!({ "sort": ["_updatedAt:desc"], "limit": 1 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"], json!([{ "id": 1, "genre": "drama" }]));
+
+    // the metadata are filtered by milli, like the other fields.
+    let (response, code) = index
+        .search_post(json!({ "filter": format!("genre = comedy OR _updatedAt >= {}", updated_at) }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["hits"],
+        json!([{ "id": 1, "genre": "drama" }, { "id": 3, "genre": "comedy" }])
+    );
+}
+
+#[actix_rt::test]
+async fn dump_preserves_metadata() {
+    let server = Server::new().await;
+    enable_metadata(&server).await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(1).await;
+    let metadata = get_with_metadata(&server, 1).await;
+
+    let (response, code) = server.service.post("/dumps", json!(null)).await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    let dump = std::fs::read_dir(&server.service.options.dumps_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().map_or(false, |ext| ext == "dump"))
+        .unwrap();
+
+    let dir = TempDir::new().unwrap();
+    let options = Opt {
+        import_dump: Some(dump),
+        ..default_settings(dir.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+    assert_eq!(get_with_metadata(&server, 1).await, metadata);
+}
@@ -0,0 +1,128 @@
+use serde_json::json;
+
+use crate::common::Server;
+
+#[actix_rt::test]
+async fn edit_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "brand": "ACME", "stock": 3 },
+        { "id": 2, "brand": "Globex", "stock": "many" },
+        { "id": 3, "brand": "Initech", "stock": 1, "legacy": true },
+    ]);
+    index.add_documents(documents, Some("id")).await;
+    index.wait_task(0).await;
+
+    let (response, code) = index
+        .edit_documents(json!({
+            "edits": [
+                { "op": "lowercase", "field": "brand" },
+                { "op": "increment", "field": "stock", "by": 1 },
+                { "op": "delete", "field": "legacy" },
+            ]
+        }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["type"], "documentEdition");
+    assert_eq!(response["details"]["matchedDocuments"], 3);
+    assert_eq!(response["details"]["editedDocuments"], 2);
+    // a string can't be incremented, the document is left untouched.
+    assert_eq!(response["details"]["invalidDocuments"], 1);
+
+    let (response, _) = index.get_document(1, None).await;
+    assert_eq!(response, json!({ "id": 1, "brand": "acme", "stock": 4 }));
+    let (response, _) = index.get_document(2, None).await;
+    assert_eq!(
+        response,
+        json!({ "id": 2, "brand": "Globex", "stock": "many" })
+    );
+    let (response, _) = index.get_document(3, None).await;
+    assert_eq!(response, json!({ "id": 3, "brand": "initech", "stock": 2 }));
+}
+
+#[actix_rt::test]
+async fn edit_filtered_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "filterableAttributes": ["brand"] }))
+        .await;
+    let documents = json!([
+        { "id": 1, "brand": "acme", "title": "Anvil" },
+        { "id": 2, "brand": "globex", "title": "Hammock" },
+    ]);
+    index.add_documents(documents, Some("id")).await;
+    index.wait_task(1).await;
+
+    let (response, code) = index
+        .edit_documents(json!({
+            "filter": "brand = acme",
+            "edits": [
+                { "op": "rename", "field": "title", "to": "name" },
+                { "op": "set", "field": "onSale", "value": true },
+            ]
+        }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["matchedDocuments"], 1);
+    assert_eq!(response["details"]["editedDocuments"], 1);
+
+    let (response, _) = index.get_document(1, None).await;
+    assert_eq!(
+        response,
+        json!({ "id": 1, "brand": "acme", "name": "Anvil", "onSale": true })
+    );
+    let (response, _) = index.get_document(2, None).await;
+    assert_eq!(
+        response,
+        json!({ "id": 2, "brand": "globex", "title": "Hammock" })
+    );
+}
+
+#[actix_rt::test]
+async fn error_edit_documents_invalid_edits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, code) = index.edit_documents(json!({ "edits": [] })).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_document_edition");
+
+    let (response, code) = index
+        .edit_documents(json!({ "edits": [{ "op": "rename", "field": "a", "to": "a" }] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_document_edition");
+
+    // the edits are not a programming language.
+    let (response, code) = index
+        .edit_documents(json!({ "edits": [{ "op": "eval", "field": "a", "code": "loop {}" }] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+}
+
+#[actix_rt::test]
+async fn error_edit_documents_primary_key() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "Anvil" }]), Some("id"))
+        .await;
+    index.wait_task(0).await;
+
+    let (response, code) = index
+        .edit_documents(json!({ "edits": [{ "op": "rename", "field": "id", "to": "ref" }] }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+    assert_eq!(response["error"]["code"], "invalid_document_edition");
+}
@@ -0,0 +1,116 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use serde_json::json;
+
+use crate::common::{GetAllDocumentsOptions, Server};
+
+/// Serves `body` with the given `status` to every request, and returns the url of the server.
+fn serve(status: &'static str, body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    format!("http://{}/documents.ndjson", addr)
+}
+
+const DOCUMENTS: &str =
+    "{\"id\": 1, \"title\": \"Carol\"}\n{\"id\": 2, \"title\": \"Wonder Woman\"}\n";
+
+#[actix_rt::test]
+async fn fetch_documents_from_url() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let url = serve("200 OK", DOCUMENTS);
+
+    let (response, code) = index.fetch_documents(json!({ "url": url })).await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["type"], "documentAdditionOrUpdate");
+    assert_eq!(response["details"]["url"], url);
+    assert_eq!(response["details"]["indexedDocuments"], 2);
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn error_fetch_documents_invalid_url() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, code) = index
+        .fetch_documents(json!({ "url": "file:///etc/passwd" }))
+        .await;
+
+    let expected_response = json!({
+        "message": "`file:///etc/passwd` is not a valid document fetch url. Only `http` and `https` urls are supported.",
+        "code": "invalid_document_fetch_url",
+        "type": "invalid_request",
+        "link": "https://docs.meilisearch.com/errors#invalid_document_fetch_url"
+    });
+
+    assert_eq!(code, 400);
+    assert_eq!(response, expected_response);
+}
+
+#[actix_rt::test]
+async fn error_fetch_documents_bad_status() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let url = serve("404 Not Found", "");
+
+    let (_response, code) = index.fetch_documents(json!({ "url": url })).await;
+    assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed");
+    assert_eq!(response["error"]["code"], "document_fetch_failed");
+    assert!(response["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("404 Not Found"));
+    assert_eq!(response["details"]["indexedDocuments"], 0);
+}
+
+#[actix_rt::test]
+async fn error_fetch_documents_checksum_mismatch() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let url = serve("200 OK", DOCUMENTS);
+
+    let (_response, code) = index
+        .fetch_documents(json!({ "url": url, "checksum": "0".repeat(64) }))
+        .await;
+    assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed");
+    assert_eq!(response["error"]["code"], "document_fetch_failed");
+
+    let (_response, code) = index.get().await;
+    // the index is created before the download starts.
+    assert_eq!(code, 200);
+    let (response, _code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert!(response["results"].as_array().unwrap().is_empty());
+}
@@ -400,3 +400,107 @@ async fn get_documents_displayed_attributes_is_ignored() {
     assert_eq!(response.as_object().unwrap().keys().count(), 16);
     assert!(response.as_object().unwrap().get("gender").is_some());
 }
+
+#[actix_rt::test]
+async fn get_all_documents_with_cursor() {
+    const DOCUMENTS: u64 = 10_000;
+
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents: Vec<_> = (0..DOCUMENTS).map(|id| json!({ "id": id })).collect();
+    index.add_documents(json!(documents), None).await;
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    // the pages follow each other without duplicates nor gaps.
+    let mut ids = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (response, code) = index
+            .get_all_documents(GetAllDocumentsOptions {
+                limit: Some(997),
+                cursor: cursor.take(),
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(response["total"], DOCUMENTS);
+        let results = response["results"].as_array().unwrap();
+        ids.extend(
+            results
+                .iter()
+                .map(|document| document["id"].as_u64().unwrap()),
+        );
+        match response["nextCursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    assert_eq!(ids, (0..DOCUMENTS).collect::<Vec<_>>());
+}
+
+#[actix_rt::test]
+async fn get_all_documents_with_cursor_of_a_deleted_document() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions {
+            limit: Some(5),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"][4]["id"], 4);
+    let cursor = response["nextCursor"].as_str().unwrap().to_string();
+
+    // the next page starts right after the document of the cursor, which is gone.
+    index.delete_document(4).await;
+    index.wait_task(1).await;
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions {
+            limit: Some(5),
+            cursor: Some(cursor),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["results"][0]["id"], 5);
+    assert_eq!(response["total"], 76);
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions {
+            limit: Some(100),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response["nextCursor"], json!(null));
+}
+
+#[actix_rt::test]
+async fn error_get_all_documents_with_invalid_cursor() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions {
+            offset: Some(5),
+            cursor: Some("00000004".to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_documents_cursor");
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions {
+            cursor: Some("nope".to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_documents_cursor");
+}
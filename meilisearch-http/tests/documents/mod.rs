@@ -1,3 +1,8 @@
 mod add_documents;
 mod delete_documents;
+mod document_metadata;
+mod edit_documents;
+mod fetch_documents;
 mod get_documents;
+mod upload_documents;
+mod validate_documents;
@@ -0,0 +1,149 @@
+use serde_json::json;
+
+use crate::common::{GetAllDocumentsOptions, Server};
+
+const DOCUMENTS: &str =
+    "{\"id\": 1, \"title\": \"Carol\"}\n{\"id\": 2, \"title\": \"Wonder Woman\"}\n";
+
+#[actix_rt::test]
+async fn upload_documents_in_chunks() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, code) = index.create_upload(json!({})).await;
+    assert_eq!(code, 201, "{}", response);
+    assert_eq!(response["indexUid"], "test");
+    assert_eq!(response["received"], 0);
+    let upload_id = response["uploadId"].as_str().unwrap().to_string();
+
+    let total = DOCUMENTS.len();
+    let (first, second) = DOCUMENTS.as_bytes().split_at(20);
+    let range = format!("bytes 0-19/{}", total);
+    let (response, code) = index
+        .append_to_upload(&upload_id, Some(&range), first)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["received"], 20);
+
+    // sending the same chunk again is a no-op.
+    let (response, code) = index
+        .append_to_upload(&upload_id, Some(&range), first)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["received"], 20);
+
+    let range = format!("bytes 20-{}/{}", total - 1, total);
+    let (response, code) = index
+        .append_to_upload(&upload_id, Some(&range), second)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["received"], total);
+    assert_eq!(response["total"], total);
+
+    let (response, code) = index.commit_upload(&upload_id).await;
+    assert_eq!(code, 202, "{}", response);
+    let task_uid = response["taskUid"].clone();
+
+    // committing again returns the same task.
+    let (response, code) = index.commit_upload(&upload_id).await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["taskUid"], task_uid);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["type"], "documentAdditionOrUpdate");
+    assert_eq!(response["details"]["indexedDocuments"], 2);
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 2);
+
+    let (response, code) = index
+        .append_to_upload(&upload_id, Some("bytes 0-1/*"), "{}")
+        .await;
+    assert_eq!(code, 409, "{}", response);
+    assert_eq!(response["code"], "upload_already_committed");
+}
+
+#[actix_rt::test]
+async fn error_upload_chunk_gap() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, _) = index.create_upload(json!({})).await;
+    let upload_id = response["uploadId"].as_str().unwrap().to_string();
+
+    let (response, code) = index
+        .append_to_upload(&upload_id, Some("bytes 10-11/*"), "{}")
+        .await;
+    assert_eq!(code, 409, "{}", response);
+    assert_eq!(response["code"], "upload_range_mismatch");
+
+    let (response, code) = index.get_upload(&upload_id).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["received"], 0);
+}
+
+#[actix_rt::test]
+async fn error_upload_invalid_content_range() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, _) = index.create_upload(json!({})).await;
+    let upload_id = response["uploadId"].as_str().unwrap().to_string();
+
+    let (response, code) = index.append_to_upload(&upload_id, None, "{}").await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_content_range");
+
+    // the range announces more bytes than the chunk contains.
+    let (response, code) = index
+        .append_to_upload(&upload_id, Some("bytes 0-9/*"), "{}")
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_content_range");
+}
+
+#[actix_rt::test]
+async fn error_commit_incomplete_upload() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, _) = index.create_upload(json!({})).await;
+    let upload_id = response["uploadId"].as_str().unwrap().to_string();
+
+    let (response, code) = index
+        .append_to_upload(&upload_id, Some("bytes 0-1/10"), "{}")
+        .await;
+    assert_eq!(code, 200, "{}", response);
+
+    let (response, code) = index.commit_upload(&upload_id).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "incomplete_upload");
+
+    let (response, code) = index.list_tasks().await;
+    assert_eq!(code, 200);
+    assert!(response["results"].as_array().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn error_unknown_upload() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, _) = index.create_upload(json!({})).await;
+    let upload_id = response["uploadId"].as_str().unwrap().to_string();
+
+    // an upload can only be accessed through its own index.
+    let (response, code) = server.index("other").get_upload(&upload_id).await;
+    assert_eq!(code, 404, "{}", response);
+    assert_eq!(response["code"], "upload_not_found");
+
+    let (response, code) = index
+        .commit_upload("00000000-0000-0000-0000-000000000000")
+        .await;
+    assert_eq!(code, 404, "{}", response);
+    assert_eq!(response["code"], "upload_not_found");
+}
@@ -0,0 +1,142 @@
+use byte_unit::{Byte, ByteUnit};
+use serde_json::json;
+
+use crate::common::{default_settings, Server};
+
+#[actix_rt::test]
+async fn validate_documents_reports_every_document() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "ok", "author": { "name": "a" } },
+        { "title": "no id" },
+        { "id": "foo & bar", "title": "invalid id" },
+        { "id": 2, "_geo": "foobar" },
+        { "id": 1, "title": "duplicate" },
+    ]);
+
+    let (response, code) = index.validate_documents(documents, Some("id")).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["valid"], false);
+    assert_eq!(response["primaryKey"], "id");
+    assert_eq!(response["documentsCount"], 5);
+    assert_eq!(response["documentsWithErrors"], 2);
+    assert_eq!(response["documentsWithWarnings"], 1);
+    assert_eq!(response["truncated"], false);
+
+    let reported = response["documents"].as_array().unwrap();
+    assert_eq!(reported.len(), 3, "{}", response);
+    assert_eq!(reported[0]["document"], 1);
+    assert_eq!(reported[0]["errors"][0]["code"], "missing_document_id");
+    assert_eq!(
+        reported[0]["errors"][0]["message"],
+        r#"Document doesn't have a `id` attribute: `{"title":"no id"}`."#
+    );
+    assert_eq!(reported[1]["document"], 2);
+    assert_eq!(reported[1]["errors"][0]["code"], "invalid_document_id");
+    assert_eq!(reported[2]["document"], 3);
+    assert_eq!(reported[2]["id"], "2");
+    assert!(reported[2].get("errors").is_none());
+
+    assert_eq!(response["duplicateIds"], json!({ "1": 2 }));
+    assert_eq!(
+        response["fields"]["id"],
+        json!({ "documents": 4, "types": ["number", "string"] })
+    );
+    assert_eq!(
+        response["fields"]["author.name"],
+        json!({ "documents": 1, "types": ["string"] })
+    );
+
+    // nothing was registered, and the index wasn't created.
+    let (response, _) = server.tasks().await;
+    assert_eq!(response["results"], json!([]));
+    let (_, code) = index.get().await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn validate_documents_uses_the_primary_key_of_the_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("docid")).await;
+    index.wait_task(0).await;
+
+    let documents = json!([{ "docid": "a", "id": "b" }, { "id": "c" }]);
+    let (response, code) = index.validate_documents(documents, Some("id")).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["primaryKey"], "docid");
+    assert_eq!(response["documentsWithErrors"], 1);
+    assert_eq!(response["documents"][0]["document"], 1);
+
+    let documents = json!([{ "title": "no primary key" }]);
+    let (response, code) = server
+        .index("other")
+        .validate_documents(documents, None)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["valid"], false);
+    assert_eq!(response["primaryKey"], json!(null));
+    assert_eq!(response["error"]["code"], "primary_key_inference_failed");
+}
+
+#[actix_rt::test]
+async fn validate_documents_rejecting_duplicates() {
+    let server = Server::new().await;
+    let documents = json!([{ "id": 1 }, { "id": 1 }]);
+
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes/test/documents/validate?primaryKey=id&rejectDuplicates=true",
+            documents,
+        )
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["valid"], false);
+    assert_eq!(response["error"]["code"], "duplicate_documents");
+}
+
+#[actix_rt::test]
+async fn error_validate_documents_payload() {
+    let server = Server::new().await;
+
+    let (response, code) = server
+        .service
+        .post_str(
+            "/indexes/test/documents/validate",
+            r#"[{ "id": 1, "title": }]"#,
+        )
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "malformed_payload");
+
+    let (response, code) = server
+        .index("test")
+        .validate_documents(json!([]), None)
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "missing_payload");
+}
+
+#[actix_rt::test]
+async fn error_validate_documents_payload_too_large() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = meilisearch_http::Opt {
+        max_validation_payload_size: Byte::from_unit(1.0, ByteUnit::KiB).unwrap(),
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let documents: Vec<_> = (0..100)
+        .map(|id| json!({ "id": id, "title": "a title long enough" }))
+        .collect();
+    let (response, code) = index.validate_documents(json!(documents), None).await;
+    assert_eq!(code, 413, "{}", response);
+    assert_eq!(response["code"], "payload_too_large");
+
+    // the cap only applies to the validation.
+    let (response, code) = index.add_documents(json!(documents), None).await;
+    assert_eq!(code, 202, "{}", response);
+}
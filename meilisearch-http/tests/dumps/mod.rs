@@ -54,14 +54,14 @@ async fn import_dump_v2_movie_raw() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({"displayedAttributes": ["*"], "searchableAttributes": ["*"], "filterableAttributes": [], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({"displayedAttributes": ["*"], "searchableAttributes": ["*"], "filterableAttributes": [], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -118,14 +118,14 @@ async fn import_dump_v2_movie_with_settings() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({ "displayedAttributes": ["title", "genres", "overview", "poster", "release_date"], "searchableAttributes": ["title", "overview"], "filterableAttributes": ["genres"], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": ["of", "the"], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": { "oneTypo": 5, "twoTypos": 9 }, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({ "displayedAttributes": ["title", "genres", "overview", "poster", "release_date"], "searchableAttributes": ["title", "overview"], "filterableAttributes": ["genres"], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": ["of", "the"], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": { "oneTypo": 5, "twoTypos": 9 }, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -182,14 +182,14 @@ async fn import_dump_v2_rubygems_with_settings() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"description": 53, "id": 53, "name": 53, "summary": 53, "total_downloads": 53, "version": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"description": 53, "id": 53, "name": 53, "summary": 53, "total_downloads": 53, "version": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({"displayedAttributes": ["name", "summary", "description", "version", "total_downloads"], "searchableAttributes": ["name", "summary"], "filterableAttributes": ["version"], "sortableAttributes": [], "rankingRules": ["typo", "words", "fame:desc", "proximity", "attribute", "exactness", "total_downloads:desc"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({"displayedAttributes": ["name", "summary", "description", "version", "total_downloads"], "searchableAttributes": ["name", "summary"], "filterableAttributes": ["version"], "sortableAttributes": [], "rankingRules": ["typo", "words", "fame:desc", "proximity", "attribute", "exactness", "total_downloads:desc"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -246,14 +246,14 @@ async fn import_dump_v3_movie_raw() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({"displayedAttributes": ["*"], "searchableAttributes": ["*"], "filterableAttributes": [], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({"displayedAttributes": ["*"], "searchableAttributes": ["*"], "filterableAttributes": [], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -310,14 +310,14 @@ async fn import_dump_v3_movie_with_settings() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({ "displayedAttributes": ["title", "genres", "overview", "poster", "release_date"], "searchableAttributes": ["title", "overview"], "filterableAttributes": ["genres"], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": ["of", "the"], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": { "oneTypo": 5, "twoTypos": 9 }, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({ "displayedAttributes": ["title", "genres", "overview", "poster", "release_date"], "searchableAttributes": ["title", "overview"], "filterableAttributes": ["genres"], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": ["of", "the"], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": { "oneTypo": 5, "twoTypos": 9 }, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -374,14 +374,14 @@ async fn import_dump_v3_rubygems_with_settings() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"description": 53, "id": 53, "name": 53, "summary": 53, "total_downloads": 53, "version": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"description": 53, "id": 53, "name": 53, "summary": 53, "total_downloads": 53, "version": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({"displayedAttributes": ["name", "summary", "description", "version", "total_downloads"], "searchableAttributes": ["name", "summary"], "filterableAttributes": ["version"], "sortableAttributes": [], "rankingRules": ["typo", "words", "fame:desc", "proximity", "attribute", "exactness", "total_downloads:desc"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({"displayedAttributes": ["name", "summary", "description", "version", "total_downloads"], "searchableAttributes": ["name", "summary"], "filterableAttributes": ["version"], "sortableAttributes": [], "rankingRules": ["typo", "words", "fame:desc", "proximity", "attribute", "exactness", "total_downloads:desc"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -438,14 +438,14 @@ async fn import_dump_v4_movie_raw() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({ "displayedAttributes": ["*"], "searchableAttributes": ["*"], "filterableAttributes": [], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({ "displayedAttributes": ["*"], "searchableAttributes": ["*"], "filterableAttributes": [], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -502,14 +502,14 @@ async fn import_dump_v4_movie_with_settings() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"genres": 53, "id": 53, "overview": 53, "poster": 53, "release_date": 53, "title": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({ "displayedAttributes": ["title", "genres", "overview", "poster", "release_date"], "searchableAttributes": ["title", "overview"], "filterableAttributes": ["genres"], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": ["of", "the"], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": { "oneTypo": 5, "twoTypos": 9 }, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({ "displayedAttributes": ["title", "genres", "overview", "poster", "release_date"], "searchableAttributes": ["title", "overview"], "filterableAttributes": ["genres"], "sortableAttributes": [], "rankingRules": ["words", "typo", "proximity", "attribute", "exactness"], "stopWords": ["of", "the"], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": { "oneTypo": 5, "twoTypos": 9 }, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -566,14 +566,14 @@ async fn import_dump_v4_rubygems_with_settings() {
     assert_eq!(code, 200);
     assert_eq!(
         stats,
-        json!({ "numberOfDocuments": 53, "isIndexing": false, "fieldDistribution": {"description": 53, "id": 53, "name": 53, "summary": 53, "total_downloads": 53, "version": 53 }})
+        json!({ "numberOfDocuments": 53, "isIndexing": false, "pendingPayloadSize": 0, "openPits": 0, "documentsOverSoftLimits": 0, "fieldDistribution": {"description": 53, "id": 53, "name": 53, "summary": 53, "total_downloads": 53, "version": 53 }, "readers": { "maxReaders": 126, "readersInUse": 0 }})
     );
 
     let (settings, code) = index.settings().await;
     assert_eq!(code, 200);
     assert_eq!(
         settings,
-        json!({ "displayedAttributes": ["name", "summary", "description", "version", "total_downloads"], "searchableAttributes": ["name", "summary"], "filterableAttributes": ["version"], "sortableAttributes": [], "rankingRules": ["typo", "words", "fame:desc", "proximity", "attribute", "exactness", "total_downloads:desc"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }})
+        json!({ "displayedAttributes": ["name", "summary", "description", "version", "total_downloads"], "searchableAttributes": ["name", "summary"], "filterableAttributes": ["version"], "sortableAttributes": [], "rankingRules": ["typo", "words", "fame:desc", "proximity", "attribute", "exactness", "total_downloads:desc"], "stopWords": [], "synonyms": {}, "distinctAttribute": null, "typoTolerance": {"enabled": true, "minWordSizeForTypos": {"oneTypo": 5, "twoTypos": 9}, "disableOnWords": [], "disableOnAttributes": [] }, "warmupQueries": [], "maxWordDerivations": 100, "maxPrefixExpansion": 1000, "maxValuesPerFacet": 100, "searchDefaults": {}, "documentMetadata": false, "nestedObjectBehavior": {}, "documentTransforms": [], "customSegmenters": {}, "prefixSearch": "indexingTime", "disablePrefixOnAttributes": [], "facetNormalization": {}})
     );
 
     let (tasks, code) = index.list_tasks().await;
@@ -605,3 +605,119 @@ async fn import_dump_v4_rubygems_with_settings() {
         json!({ "name": "vortex-of-agony", "summary": "You dont need to use nodejs or go, just install this plugin. It will crash your application at random", "description": "You dont need to use nodejs or go, just install this plugin. It will crash your application at random", "id": "159227", "version": "0.1.0", "total_downloads": "1007"})
     );
 }
+
+#[actix_rt::test]
+#[cfg_attr(target_os = "windows", ignore)]
+async fn reindex_dump_v4_movie_with_settings() {
+    let source_dir = tempfile::tempdir().unwrap();
+    let options = Opt {
+        import_dump: Some(GetDump::MoviesWithSettingsV4.path()),
+        ..default_settings(source_dir.path())
+    };
+    let source = Server::new_with_options(options).await.unwrap();
+
+    let temp = tempfile::tempdir().unwrap();
+    let options = default_settings(temp.path());
+    let mut progress = Vec::new();
+    let report = meilisearch_lib::reindex_dump(
+        GetDump::MoviesWithSettingsV4.path(),
+        &options.db_path,
+        2,
+        options.max_index_size.get_bytes() as usize,
+        options.max_task_db_size.get_bytes() as usize,
+        &options.indexer_options,
+        |index| progress.push(index.uid.clone()),
+    )
+    .unwrap();
+    assert!(report.is_success(), "{}", report);
+    assert_eq!(progress, ["indexUID"]);
+
+    // the data directory can't be built twice.
+    assert!(meilisearch_lib::reindex_dump(
+        GetDump::MoviesWithSettingsV4.path(),
+        &options.db_path,
+        2,
+        options.max_index_size.get_bytes() as usize,
+        options.max_task_db_size.get_bytes() as usize,
+        &options.indexer_options,
+        |_| (),
+    )
+    .is_err());
+
+    let server = Server::new_with_options(options).await.unwrap();
+
+    let (stats, code) = server.index("indexUID").stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(stats["numberOfDocuments"], json!(53));
+
+    let (source_settings, _) = source.index("indexUID").settings().await;
+    let (settings, code) = server.index("indexUID").settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(settings, source_settings);
+
+    for query in [
+        json!({ "q": "crime" }),
+        json!({ "q": "the lock", "limit": 5 }),
+        json!({ "q": "", "filter": "genres = Comedy" }),
+    ] {
+        let (expected, _) = source.index("indexUID").search_post(query.clone()).await;
+        let (response, code) = server.index("indexUID").search_post(query.clone()).await;
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(response["hits"], expected["hits"], "{}", query);
+    }
+}
+
+#[actix_rt::test]
+#[cfg_attr(target_os = "windows", ignore)]
+async fn dump_with_pending_tasks() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = index
+        .service
+        .patch(
+            "/indexes/test/settings?scheduleAt=2100-01-01T00:00:00Z",
+            json!({ "rankingRules": ["words"] }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(2).await;
+
+    let (response, code) = server
+        .service
+        .post("/dumps?includePendingTasks=true", json!(null))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(3).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["includePendingTasks"], true);
+
+    let dump = std::fs::read_dir(&server.service.options.dumps_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().map_or(false, |ext| ext == "dump"))
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let options = Opt {
+        import_dump: Some(dump),
+        ..default_settings(dir.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    // the scheduled update is registered again after the finished tasks, the dump not being one.
+    let (response, code) = index.get_task(1).await;
+    assert_eq!(code, 404, "{}", response);
+    let (response, code) = index.get_task(3).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["status"], "enqueued");
+    assert_eq!(response["type"], "settingsUpdate");
+    assert_eq!(response["details"]["scheduledAt"], "2100-01-01T00:00:00Z");
+
+    let (stats, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(stats["numberOfDocuments"], 1);
+}
@@ -0,0 +1,54 @@
+use serde_json::json;
+
+use crate::common::Server;
+
+#[actix_rt::test]
+async fn check_consistent_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "filterableAttributes": ["genre", "year"] }))
+        .await;
+    index.wait_task(0).await;
+
+    let documents = json!([
+        { "id": 1, "title": "Carol", "genre": "romance", "year": 2015 },
+        { "id": 2, "title": "Wonder Woman", "genre": "action", "year": 2017 },
+        { "id": 3, "title": "Life of Pi", "genre": "adventure" },
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_task(1).await;
+
+    for (uid, fast) in [(2, false), (3, true)] {
+        let (response, code) = index.check(fast).await;
+        assert_eq!(code, 202, "{}", response);
+        assert_eq!(response["taskUid"], uid);
+        assert_eq!(response["type"], "indexCheck");
+
+        let response = index.wait_task(uid).await;
+        assert_eq!(response["status"], "succeeded", "{}", response);
+        assert_eq!(response["details"]["fast"], fast);
+
+        // a consistent index only gets the summary.
+        let findings = response["details"]["findings"].as_array().unwrap();
+        assert_eq!(findings.len(), 1, "{}", response);
+        assert_eq!(findings[0]["severity"], "info");
+        assert_eq!(findings[0]["check"], "summary");
+    }
+
+    let (response, code) = index.filtered_tasks(&["indexCheck"], &[]).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn check_unexisting_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let (_, code) = index.check(false).await;
+    assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed");
+    assert_eq!(response["error"]["code"], "index_not_found");
+}
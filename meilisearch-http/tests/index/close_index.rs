@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::time::sleep;
+
+use crate::common::Server;
+
+#[actix_rt::test]
+async fn closed_index_keeps_its_tasks_enqueued() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "Carol" }]), None)
+        .await;
+    index.wait_task(0).await;
+
+    let (response, code) = index.close().await;
+    assert_eq!(code, 204, "{}", response);
+    // closing an index twice does nothing.
+    let (_, code) = index.close().await;
+    assert_eq!(code, 204);
+
+    let (response, code) = index.search_post(json!({ "q": "carol" })).await;
+    assert_eq!(code, 409, "{}", response);
+    assert_eq!(response["code"], "index_closed");
+
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["closedIndexes"], json!(["test"]));
+    assert!(response["indexes"].get("test").is_none(), "{}", response);
+
+    // the tasks of a closed index wait for it to be opened.
+    let (_, code) = index
+        .add_documents(json!([{ "id": 2, "title": "Wonder Woman" }]), None)
+        .await;
+    assert_eq!(code, 202);
+    sleep(Duration::from_secs(1)).await;
+    let (response, _) = index.get_task(1).await;
+    assert_eq!(response["status"], "enqueued", "{}", response);
+
+    let (response, code) = index.open().await;
+    assert_eq!(code, 204, "{}", response);
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    let (response, code) = index.search_post(json!({ "q": "carol" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"][0]["id"], 1);
+    let (response, _) = server.stats().await;
+    assert_eq!(response["closedIndexes"], json!([]));
+    assert_eq!(response["indexes"]["test"]["numberOfDocuments"], 2);
+}
+
+#[actix_rt::test]
+async fn closed_index_does_not_block_the_other_indexes() {
+    let server = Server::new().await;
+    let closed = server.index("closed");
+    closed.create(None).await;
+    closed.wait_task(0).await;
+    closed.close().await;
+
+    closed
+        .add_documents(json!([{ "id": 1, "title": "Carol" }]), None)
+        .await;
+    let other = server.index("other");
+    other
+        .add_documents(json!([{ "id": 1, "title": "Carol" }]), None)
+        .await;
+    let response = other.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    let (response, _) = closed.get_task(1).await;
+    assert_eq!(response["status"], "enqueued", "{}", response);
+}
+
+#[actix_rt::test]
+async fn close_unexisting_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let (response, code) = index.close().await;
+    assert_eq!(code, 404, "{}", response);
+    assert_eq!(response["code"], "index_not_found");
+    let (response, code) = index.open().await;
+    assert_eq!(code, 404, "{}", response);
+    assert_eq!(response["code"], "index_not_found");
+}
@@ -1,4 +1,6 @@
+use crate::common::server::default_settings;
 use crate::common::Server;
+use meilisearch_http::Opt;
 use serde_json::{json, Value};
 
 #[actix_rt::test]
@@ -80,10 +82,9 @@ async fn error_create_existing_index() {
     let (_, code) = index.create(Some("primary")).await;
 
     assert_eq!(code, 202);
+    index.wait_task(0).await;
 
-    index.create(Some("primary")).await;
-
-    let response = index.wait_task(1).await;
+    let (response, code) = index.create(Some("primary")).await;
 
     let expected_response = json!({
         "message": "Index `test` already exists.",
@@ -92,7 +93,41 @@ async fn error_create_existing_index() {
         "link":"https://docs.meilisearch.com/errors#index_already_exists"
     });
 
-    assert_eq!(response["error"], expected_response);
+    assert_eq!(response, expected_response);
+    assert_eq!(code, 409);
+
+    // no task was registered.
+    let (response, code) = index.get_task(1).await;
+    assert_eq!(code, 404, "{}", response);
+}
+
+#[actix_rt::test]
+async fn create_existing_index_if_not_exists() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("primary")).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes?ifNotExists=true",
+            json!({ "uid": "test", "primaryKey": "other" }),
+        )
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["uid"], "test");
+    assert_eq!(response["primaryKey"], "primary");
+
+    // the index is created as usual when it doesn't exist.
+    let (response, code) = server
+        .service
+        .post("/indexes?ifNotExists=true", json!({ "uid": "other" }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = server.index("other").wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["alreadyExisted"], false);
 }
 
 #[actix_rt::test]
@@ -102,7 +137,7 @@ async fn error_create_with_invalid_index_uid() {
     let (response, code) = index.create(None).await;
 
     let expected_response = json!({
-        "message": "`test test#!` is not a valid index uid. Index uid can be an integer or a string containing only alphanumeric characters, hyphens (-) and underscores (_).",
+        "message": "`test test#!` is not a valid index uid: the character ' ' at position 4 is not allowed. Index uid must be between 1 and 400 bytes long, and can only contain the characters `a-z`, `A-Z`, `0-9`, `-` and `_`.",
         "code": "invalid_index_uid",
         "type": "invalid_request",
         "link": "https://docs.meilisearch.com/errors#invalid_index_uid"
@@ -111,3 +146,96 @@ async fn error_create_with_invalid_index_uid() {
     assert_eq!(response, expected_response);
     assert_eq!(code, 400);
 }
+
+#[actix_rt::test]
+async fn create_index_with_name() {
+    let server = Server::new().await;
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes",
+            json!({ "uid": "movies-fr", "name": "Films français 🎬" }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    let response = server.index("movies-fr").wait_task(0).await;
+    assert_eq!(response["status"], "succeeded");
+    assert_eq!(response["details"]["name"], "Films français 🎬");
+
+    let (response, code) = server.index("movies-fr").get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["name"], "Films français 🎬");
+}
+
+#[actix_rt::test]
+async fn error_create_with_too_long_index_uid() {
+    let server = Server::new().await;
+    let uid = "a".repeat(401);
+    let (response, code) = server.index(&uid).create(None).await;
+
+    assert_eq!(code, 400);
+    assert_eq!(response["code"], "invalid_index_uid");
+    assert_eq!(
+        response["message"],
+        format!("`{}` is not a valid index uid: it is 401 bytes long. Index uid must be between 1 and 400 bytes long, and can only contain the characters `a-z`, `A-Z`, `0-9`, `-` and `_`.", uid)
+    );
+}
+
+#[actix_rt::test]
+async fn create_index_with_templates() {
+    let temp = tempfile::tempdir().unwrap();
+    let templates_path = temp.path().join("templates.json");
+    let templates = json!([
+        { "pattern": "logs-*", "settings": { "filterableAttributes": ["level"], "stopWords": ["the"] } },
+        { "pattern": "logs-app", "settings": { "stopWords": ["a"] } },
+    ]);
+    std::fs::write(&templates_path, templates.to_string()).unwrap();
+
+    let options = Opt {
+        index_templates: Some(templates_path),
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+
+    let index = server.index("logs-app");
+    index.create(None).await;
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["templates"], json!(["logs-*", "logs-app"]));
+    let (settings, _) = index.settings().await;
+    assert_eq!(settings["filterableAttributes"], json!(["level"]));
+    assert_eq!(settings["stopWords"], json!(["a"]));
+
+    // a reset goes back to the settings of the templates.
+    index
+        .update_settings(json!({ "filterableAttributes": ["app"], "stopWords": [] }))
+        .await;
+    index.wait_task(1).await;
+    index.delete_settings().await;
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    let (settings, _) = index.settings().await;
+    assert_eq!(settings["filterableAttributes"], json!(["level"]));
+    assert_eq!(settings["stopWords"], json!(["a"]));
+
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes",
+            json!({ "uid": "logs-raw", "skipTemplates": true }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let index = server.index("logs-raw");
+    index.wait_task(3).await;
+
+    let (response, _) = index.get().await;
+    assert_eq!(response["skipTemplates"], true);
+    assert!(response.get("templates").is_none(), "{}", response);
+    let (settings, _) = index.settings().await;
+    assert_eq!(settings["filterableAttributes"], json!([]));
+}
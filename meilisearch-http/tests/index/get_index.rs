@@ -1,4 +1,5 @@
 use crate::common::Server;
+use actix_web::test;
 use serde_json::json;
 use serde_json::Value;
 
@@ -20,7 +21,8 @@ async fn create_and_get_index() {
     assert!(response.get("updatedAt").is_some());
     assert_eq!(response["createdAt"], response["updatedAt"]);
     assert_eq!(response["primaryKey"], Value::Null);
-    assert_eq!(response.as_object().unwrap().len(), 4);
+    assert_eq!(response["name"], Value::Null);
+    assert_eq!(response.as_object().unwrap().len(), 5);
 }
 
 #[actix_rt::test]
@@ -71,6 +73,23 @@ async fn list_multiple_indexes() {
         .any(|entry| entry["uid"] == "test1" && entry["primaryKey"] == "key"));
 }
 
+#[actix_rt::test]
+async fn list_indexes_with_fields() {
+    let server = Server::new().await;
+    server.index("test").create(Some("id")).await;
+    server.index("test").wait_task(0).await;
+
+    let req = test::TestRequest::get().uri("/indexes?fields=uid,primaryKey,unknown");
+    let (response, code, headers) = server.service.request(req).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["results"],
+        json!([{ "uid": "test", "primaryKey": "id" }])
+    );
+    assert_eq!(response["total"], 1);
+    assert_eq!(headers["X-Meili-Unknown-Fields"], "unknown");
+}
+
 #[actix_rt::test]
 async fn get_and_paginate_indexes() {
     let server = Server::new().await;
@@ -201,3 +220,45 @@ async fn get_invalid_index_uid() {
             })
     );
 }
+
+#[actix_rt::test]
+async fn list_indexes_with_uid_prefix() {
+    let server = Server::new().await;
+    for (i, (uid, name)) in [
+        ("movies-en", Some("English movies")),
+        ("movies-fr", None),
+        ("books", Some("movies and books")),
+        ("songs", None),
+    ]
+    .iter()
+    .enumerate()
+    {
+        server
+            .service
+            .post("/indexes", json!({ "uid": uid, "name": name }))
+            .await;
+        server.index(uid).wait_task(i as u64).await;
+    }
+
+    let (response, code) = server.service.get("/indexes?uidPrefix=movies").await;
+    assert_eq!(code, 200, "{}", response);
+    let uids: Vec<_> = response["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|index| index["uid"].as_str().unwrap())
+        .collect();
+    assert_eq!(uids, vec!["books", "movies-en", "movies-fr"]);
+    assert_eq!(response["total"], 3);
+
+    // the names are matched too.
+    let (response, code) = server.service.get("/indexes?uidPrefix=English").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["results"][0]["uid"], "movies-en");
+    assert_eq!(response["results"][0]["name"], "English movies");
+    assert_eq!(response["total"], 1);
+
+    let (response, code) = server.service.get("/indexes?uidPrefix=nothing").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["total"], 0);
+}
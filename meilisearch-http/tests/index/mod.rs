@@ -1,5 +1,9 @@
+mod check_index;
+mod close_index;
 mod create_index;
 mod delete_index;
 mod get_index;
+mod rebuild_index;
+mod repair_stats;
 mod stats;
 mod update_index;
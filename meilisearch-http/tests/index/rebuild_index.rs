@@ -0,0 +1,79 @@
+use serde_json::json;
+
+use crate::common::{GetAllDocumentsOptions, Server};
+
+#[actix_rt::test]
+async fn rebuild_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "filterableAttributes": ["genre"] }))
+        .await;
+    index.wait_task(0).await;
+
+    let documents = json!([
+        { "id": 1, "title": "Carol", "genre": "romance" },
+        { "id": 2, "title": "Wonder Woman", "genre": "action" },
+        { "id": 3, "title": "Life of Pi", "genre": "adventure" },
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_task(1).await;
+    index.delete_document(3).await;
+    index.wait_task(2).await;
+
+    let (response, code) = index.rebuild().await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["type"], "indexRebuild");
+
+    let response = index.wait_task(3).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["rebuiltDocuments"], 2);
+
+    // the documents, the primary key and the settings survive the rebuild.
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "id");
+    let (response, _) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(
+        response["results"].as_array().unwrap().len(),
+        2,
+        "{}",
+        response
+    );
+    let (response, code) = index
+        .search_post(json!({ "filter": "genre = action" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"][0]["id"], 2);
+
+    // the tasks registered before the rebuild still belong to the index.
+    let (response, code) = index.list_tasks().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 4);
+    assert!(response["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|task| task["indexDeleted"] == false));
+
+    // the rebuilt index can be updated.
+    index
+        .add_documents(json!([{ "id": 4, "title": "Moana" }]), None)
+        .await;
+    let response = index.wait_task(4).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+}
+
+#[actix_rt::test]
+async fn rebuild_unexisting_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let (_, code) = index.rebuild().await;
+    assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed");
+    assert_eq!(response["error"]["code"], "index_not_found");
+}
@@ -0,0 +1,56 @@
+use serde_json::json;
+
+use crate::common::Server;
+
+#[actix_rt::test]
+async fn repair_consistent_stats() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "filterableAttributes": ["genre"] }))
+        .await;
+    index.wait_task(0).await;
+
+    let documents = json!([
+        { "id": 1, "title": "Carol", "genre": "romance" },
+        { "id": 2, "title": "Wonder Woman", "genre": "action" },
+        { "id": 3, "title": "Life of Pi" },
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_task(1).await;
+
+    let (response, code) = index.repair_stats().await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["taskUid"], 2);
+    assert_eq!(response["type"], "statsRepair");
+
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    // the stats of a healthy index are left as they are.
+    assert_eq!(response["details"]["repaired"], false);
+    assert_eq!(response["details"]["before"], response["details"]["after"]);
+    assert_eq!(
+        response["details"]["after"],
+        json!({
+            "numberOfDocuments": 3,
+            "fieldDistribution": { "genre": 2, "id": 3, "title": 3 },
+            "facetCardinalities": { "genre": 2 },
+        })
+    );
+
+    let (stats, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(stats["numberOfDocuments"], 3);
+}
+
+#[actix_rt::test]
+async fn repair_stats_of_unexisting_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let (_, code) = index.repair_stats().await;
+    assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "failed");
+    assert_eq!(response["error"]["code"], "index_not_found");
+}
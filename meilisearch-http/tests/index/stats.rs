@@ -49,6 +49,33 @@ async fn stats() {
     assert_eq!(response["fieldDistribution"]["age"], 1);
 }
 
+#[actix_rt::test]
+async fn detailed_stats() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "hello world" },
+        { "id": 2, "title": "hello" },
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert!(response.get("databases").is_none());
+
+    let (response, code) = index.detailed_stats().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["numberOfDocuments"], 2);
+
+    let databases = &response["databases"];
+    assert_eq!(databases["documents"]["numberOfEntries"], 2);
+    // `hello` and `world`.
+    assert_eq!(databases["wordDocids"]["numberOfEntries"], 2);
+    assert!(databases["wordDocids"]["size"].as_u64().unwrap() > 0);
+    assert_eq!(databases["facetIdF64Docids"]["numberOfEntries"], 0);
+}
+
 #[actix_rt::test]
 async fn error_get_stats_unexisting_index() {
     let server = Server::new().await;
@@ -31,7 +31,8 @@ async fn update_primary_key() {
     assert!(created_at < updated_at);
 
     assert_eq!(response["primaryKey"], "primary");
-    assert_eq!(response.as_object().unwrap().len(), 4);
+    assert_eq!(response["name"], json!(null));
+    assert_eq!(response.as_object().unwrap().len(), 5);
 }
 
 #[actix_rt::test]
@@ -103,3 +104,64 @@ async fn error_update_unexisting_index() {
 
     assert_eq!(response["error"], expected_response);
 }
+
+#[actix_rt::test]
+async fn update_name() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server
+        .service
+        .patch("/indexes/test", json!({ "name": "Test index" }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded");
+
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["name"], "Test index");
+    assert_eq!(response["primaryKey"], json!(null));
+    assert_eq!(response.as_object().unwrap().len(), 5);
+
+    // updating the primary key leaves the name untouched.
+    index.update(Some("id")).await;
+    index.wait_task(2).await;
+    let (response, _) = index.get().await;
+    assert_eq!(response["name"], "Test index");
+    assert_eq!(response["primaryKey"], "id");
+    assert_eq!(response.as_object().unwrap().len(), 5);
+}
+
+#[actix_rt::test]
+async fn update_skip_templates() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server
+        .service
+        .patch("/indexes/test", json!({ "skipTemplates": true }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded");
+
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["skipTemplates"], true);
+    assert_eq!(response.as_object().unwrap().len(), 6);
+
+    // an index using the templates doesn't show the field.
+    server
+        .service
+        .patch("/indexes/test", json!({ "skipTemplates": false }))
+        .await;
+    index.wait_task(2).await;
+    let (response, _) = index.get().await;
+    assert!(response.get("skipTemplates").is_none(), "{}", response);
+    assert_eq!(response.as_object().unwrap().len(), 5);
+}
@@ -1,13 +1,21 @@
+mod audit;
 mod auth;
+mod batches;
+mod changes;
 mod common;
 mod dashboard;
+mod data_lock;
 mod documents;
 mod dumps;
 mod index;
+mod namespaces;
+mod replica;
 mod search;
 mod settings;
+mod slow_query_log;
 mod snapshot;
 mod stats;
+mod support_bundle;
 mod tasks;
 
 // Tests are isolated by features in different modules to allow better readability, test
@@ -0,0 +1,179 @@
+use serde_json::json;
+
+use crate::common::Server;
+
+#[actix_rt::test]
+async fn create_index_in_namespace() {
+    let server = Server::new().await;
+
+    let (response, code) = server
+        .service
+        .post("/namespaces", json!({ "uid": "tenant" }))
+        .await;
+    assert_eq!(code, 201, "{}", response);
+    assert_eq!(response["uid"], "tenant");
+    assert!(response["createdAt"].is_string());
+
+    let (response, code) = server
+        .service
+        .post("/namespaces", json!({ "uid": "tenant" }))
+        .await;
+    assert_eq!(code, 409, "{}", response);
+    assert_eq!(response["code"], "namespace_already_exists");
+
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes",
+            json!({ "uid": "movies", "namespace": "tenant" }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    server.index("movies").wait_task(0).await;
+
+    let (response, code) = server.index("movies").get().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["namespace"], "tenant");
+
+    // the indexes of the default namespace don't show any namespace.
+    server.index("books").create(None).await;
+    server.index("books").wait_task(1).await;
+    let (response, _) = server.index("books").get().await;
+    assert!(response.get("namespace").is_none(), "{}", response);
+
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes",
+            json!({ "uid": "songs", "namespace": "unknown" }),
+        )
+        .await;
+    assert_eq!(code, 404, "{}", response);
+    assert_eq!(response["code"], "namespace_not_found");
+}
+
+#[actix_rt::test]
+async fn namespace_stats_and_deletion() {
+    let server = Server::new().await;
+    server
+        .service
+        .post("/namespaces", json!({ "uid": "tenant" }))
+        .await;
+
+    for (uid, documents) in [
+        ("movies", json!([{ "id": 1 }, { "id": 2 }])),
+        ("books", json!([{ "id": 1 }])),
+    ] {
+        server
+            .service
+            .post("/indexes", json!({ "uid": uid, "namespace": "tenant" }))
+            .await;
+        server.index(uid).add_documents(documents, None).await;
+    }
+    server
+        .index("songs")
+        .add_documents(json!([{ "id": 1 }]), None)
+        .await;
+    for task_uid in 0..5 {
+        server.index("songs").wait_task(task_uid).await;
+    }
+
+    let (response, code) = server.service.get("/namespaces/tenant/stats").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["numberOfIndexes"], 2);
+    assert_eq!(response["numberOfDocuments"], 3);
+    assert!(response["indexes"]["movies"].is_object());
+    assert!(response["indexes"].get("songs").is_none());
+
+    let (response, code) = server.service.delete("/namespaces/tenant").await;
+    assert_eq!(code, 202, "{}", response);
+    let task_uid = response["uid"].as_u64().unwrap();
+
+    let response = server.index("movies").wait_task(task_uid).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["type"], "namespaceDeletion");
+    assert_eq!(response["details"]["deletedIndexes"], 2);
+    assert_eq!(response["details"]["deletedDocuments"], 3);
+
+    let (_, code) = server.index("movies").get().await;
+    assert_eq!(code, 404);
+    let (_, code) = server.index("songs").get().await;
+    assert_eq!(code, 200);
+    let (response, code) = server.service.get("/namespaces/tenant").await;
+    assert_eq!(code, 404, "{}", response);
+
+    let (response, code) = server.service.delete("/namespaces/default").await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_namespace");
+}
+
+#[actix_rt::test]
+async fn keys_scoped_to_a_namespace() {
+    let mut server = Server::new_auth().await;
+    server.use_api_key("MASTER_KEY");
+
+    server
+        .service
+        .post("/namespaces", json!({ "uid": "tenant" }))
+        .await;
+    server
+        .service
+        .post(
+            "/indexes",
+            json!({ "uid": "movies", "namespace": "tenant" }),
+        )
+        .await;
+    server.index("books").create(None).await;
+    server.index("books").wait_task(1).await;
+
+    // the key is allowed on every index, but only within the namespace.
+    let (response, code) = server
+        .add_api_key(json!({
+            "indexes": ["*"],
+            "namespaces": ["tenant"],
+            "actions": ["*"],
+            "expiresAt": null,
+        }))
+        .await;
+    assert_eq!(code, 201, "{}", response);
+    assert_eq!(response["namespaces"], json!(["tenant"]));
+    server.use_api_key(response["key"].as_str().unwrap());
+
+    let (response, code) = server.index("movies").get().await;
+    assert_eq!(code, 200, "{}", response);
+    let (response, code) = server.index("books").get().await;
+    assert_eq!(code, 403, "{}", response);
+
+    let (response, code) = server.list_indexes(None, None).await;
+    assert_eq!(code, 200, "{}", response);
+    let uids: Vec<_> = response["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|index| index["uid"].clone())
+        .collect();
+    assert_eq!(uids, [json!("movies")]);
+
+    let (response, code) = server
+        .service
+        .post("/indexes", json!({ "uid": "songs" }))
+        .await;
+    assert_eq!(code, 403, "{}", response);
+
+    // scoping by index and by namespace compose.
+    server.use_api_key("MASTER_KEY");
+    let (response, _) = server
+        .add_api_key(json!({
+            "indexes": ["books"],
+            "namespaces": ["tenant"],
+            "actions": ["*"],
+            "expiresAt": null,
+        }))
+        .await;
+    server.use_api_key(response["key"].as_str().unwrap());
+
+    let (_, code) = server.index("movies").get().await;
+    assert_eq!(code, 403);
+    let (_, code) = server.index("books").get().await;
+    assert_eq!(code, 403);
+}
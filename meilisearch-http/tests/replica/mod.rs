@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::time::sleep;
+
+use crate::common::server::default_settings;
+use crate::common::Server;
+
+use meilisearch_http::Opt;
+
+#[actix_rt::test]
+async fn replica_serves_the_snapshots_of_its_primary() {
+    let primary_dir = tempfile::tempdir().unwrap();
+    let snapshot_dir = tempfile::tempdir().unwrap();
+    let options = Opt {
+        snapshot_dir: snapshot_dir.path().to_owned(),
+        snapshot_interval_sec: 1,
+        schedule_snapshot: true,
+        ..default_settings(primary_dir.path())
+    };
+    let primary = Server::new_with_options(options).await.unwrap();
+    let index = primary.index("test");
+    index.load_test_set().await;
+    index.wait_task(0).await;
+
+    sleep(Duration::from_secs(2)).await;
+
+    let replica_dir = tempfile::tempdir().unwrap();
+    let options = Opt {
+        replica_of: Some(
+            snapshot_dir
+                .path()
+                .join("db.snapshot")
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        replica_poll_interval_sec: 1,
+        ..default_settings(replica_dir.path())
+    };
+    let replica = Server::new_with_options(options).await.unwrap();
+
+    let (response, code) = replica.index("test").search_post(json!({ "q": "" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(!response["hits"].as_array().unwrap().is_empty());
+
+    // the tasks of the primary are listed.
+    let (response, code) = replica.tasks().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["results"][0]["status"], "succeeded");
+
+    // the writes are rejected.
+    let (response, code) = replica.index("test").create(None).await;
+    assert_eq!(code, 403, "{}", response);
+    assert_eq!(response["code"], "replica_read_only");
+    let (response, code) = replica
+        .index("test")
+        .add_documents(json!([{ "id": 1 }]), None)
+        .await;
+    assert_eq!(code, 403, "{}", response);
+
+    let (response, code) = replica.stats().await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response["replication"]["snapshotCreatedAt"].is_string());
+    assert!(response["replication"]["lagSec"].is_u64());
+
+    // a new snapshot of the primary is served once it is polled.
+    primary.index("books").create(None).await;
+    primary.index("books").wait_task(1).await;
+    sleep(Duration::from_secs(4)).await;
+
+    let (response, code) = replica.index("books").get().await;
+    assert_eq!(code, 200, "{}", response);
+    let (response, code) = replica.index("test").search_post(json!({ "q": "" })).await;
+    assert_eq!(code, 200, "{}", response);
+}
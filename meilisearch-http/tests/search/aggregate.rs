@@ -0,0 +1,126 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static PRODUCTS: Lazy<Value> = Lazy::new(|| {
+    json!([
+        { "id": 1, "category": "shoes", "price": 50, "color": "red" },
+        { "id": 2, "category": "shoes", "price": 70, "color": "blue" },
+        { "id": 3, "category": "hats", "price": 20, "color": "red" },
+        { "id": 4, "category": "shirts", "price": 30, "color": "red" },
+        { "id": 5, "category": "shirts", "price": 40, "color": "blue" },
+        { "id": 6, "category": "shirts", "price": 50, "color": "green" },
+    ])
+});
+
+async fn products_index(server: &Server) -> Index<'_> {
+    let index = server.index("products");
+    index
+        .update_settings(json!({"filterableAttributes": ["category", "price", "color"]}))
+        .await;
+    index.add_documents(PRODUCTS.clone(), None).await;
+    index.wait_task(1).await;
+    index
+}
+
+#[actix_rt::test]
+async fn aggregate_without_group() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index
+        .aggregate(json!({
+            "filter": "color = red",
+            "metrics": ["count", "min(price)", "max(price)", "sum(price)", "avg(price)"]
+        }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["truncated"], false);
+    assert_eq!(
+        response["groups"],
+        json!([{ "count": 3, "min(price)": 20.0, "max(price)": 50.0, "sum(price)": 100.0, "avg(price)": 100.0 / 3.0 }])
+    );
+}
+
+#[actix_rt::test]
+async fn aggregate_group_by() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index
+        .aggregate(json!({
+            "groupBy": "category",
+            "metrics": ["count", "avg(price)"]
+        }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["truncated"], false);
+    assert_eq!(
+        response["groups"],
+        json!([
+            { "group": "hats", "count": 1, "avg(price)": 20.0 },
+            { "group": "shirts", "count": 3, "avg(price)": 40.0 },
+            { "group": "shoes", "count": 2, "avg(price)": 60.0 },
+        ])
+    );
+}
+
+#[actix_rt::test]
+async fn aggregate_group_by_is_truncated() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+    index.update_settings(json!({"maxValuesPerFacet": 2})).await;
+    index.wait_task(2).await;
+
+    let (response, code) = index.aggregate(json!({ "groupBy": "category" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["truncated"], true);
+    assert_eq!(
+        response["groups"],
+        json!([
+            { "group": "hats", "count": 1 },
+            { "group": "shirts", "count": 3 },
+        ])
+    );
+}
+
+#[actix_rt::test]
+async fn error_aggregate_non_numeric_field() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index.aggregate(json!({ "metrics": ["sum(color)"] })).await;
+
+    let expected_response = json!({
+        "message": "Cannot compute `sum(color)`: the field `color` contains non-numeric values.",
+        "code": "invalid_aggregate_metric",
+        "type": "invalid_request",
+        "link": "https://docs.meilisearch.com/errors#invalid_aggregate_metric"
+    });
+    assert_eq!(code, 400);
+    assert_eq!(response, expected_response);
+}
+
+#[actix_rt::test]
+async fn error_aggregate_invalid_metric() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index
+        .aggregate(json!({ "metrics": ["median(price)"] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_aggregate_metric");
+}
+
+#[actix_rt::test]
+async fn error_aggregate_not_filterable_field() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index.aggregate(json!({ "groupBy": "id" })).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_aggregate_field");
+}
@@ -0,0 +1,63 @@
+use serde_json::json;
+
+use crate::common::server::default_settings;
+use crate::common::Server;
+
+use meilisearch_http::Opt;
+
+#[actix_rt::test]
+async fn search_analytics() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = Opt {
+        search_analytics: true,
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "Carol" }]), None)
+        .await;
+    index.wait_task(0).await;
+
+    for q in ["carol", "Carol", "wonder", "carol"] {
+        let (response, code) = index.search_post(json!({ "q": q })).await;
+        assert_eq!(code, 200, "{}", response);
+    }
+    index.search_post(json!({})).await;
+
+    let (response, code) = server
+        .service
+        .get("/indexes/test/analytics/searches?limit=1")
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["indexUid"], "test");
+    for window in ["lastHour", "lastDay"] {
+        let window = &response[window];
+        assert_eq!(window["searches"], 5, "{}", response);
+        assert_eq!(window["zeroResultSearches"], 1, "{}", response);
+        assert_eq!(window["topQueries"], json!([{ "q": "carol", "count": 3 }]));
+        assert_eq!(
+            window["topZeroResultQueries"],
+            json!([{ "q": "wonder", "count": 1 }])
+        );
+        assert!(window["latency"]["p50Ms"].is_u64(), "{}", response);
+    }
+
+    let (response, code) = server
+        .service
+        .get("/indexes/unknown/analytics/searches")
+        .await;
+    assert_eq!(code, 404, "{}", response);
+}
+
+#[actix_rt::test]
+async fn search_analytics_are_disabled_by_default() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server.service.get("/indexes/test/analytics/searches").await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "bad_request");
+}
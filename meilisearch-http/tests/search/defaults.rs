@@ -0,0 +1,97 @@
+use serde_json::json;
+
+use crate::common::Server;
+
+use super::DOCUMENTS;
+
+#[actix_rt::test]
+async fn search_defaults_are_merged_with_the_request() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let documents = DOCUMENTS.clone();
+    index.add_documents(documents, None).await;
+    let defaults = json!({ "attributesToRetrieve": ["title"], "limit": 2 });
+    let (response, code) = index
+        .update_settings(json!({ "searchDefaults": defaults }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(1).await;
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["searchDefaults"], defaults);
+
+    // the defaults apply to the parameters that are not sent.
+    index
+        .search(json!({}), |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            let hits = response["hits"].as_array().unwrap();
+            assert_eq!(hits.len(), 2);
+            assert_eq!(hits[0], json!({ "title": "Shazam!" }));
+        })
+        .await;
+
+    // the request wins over the defaults.
+    index
+        .search(json!({ "limit": 3 }), |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            let hits = response["hits"].as_array().unwrap();
+            assert_eq!(hits.len(), 3);
+            assert_eq!(hits[0], json!({ "title": "Shazam!" }));
+        })
+        .await;
+
+    // an explicit null resets the parameter to the engine default.
+    let (response, code) = index
+        .search_post(json!({ "attributesToRetrieve": null, "limit": null }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    let hits = response["hits"].as_array().unwrap();
+    assert_eq!(hits.len(), 5);
+    assert_eq!(hits[0], json!({ "title": "Shazam!", "id": "287947" }));
+}
+
+#[actix_rt::test]
+async fn reset_search_defaults() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    index.add_documents(DOCUMENTS.clone(), None).await;
+    index
+        .update_settings(json!({ "searchDefaults": { "limit": 1 } }))
+        .await;
+    index
+        .update_settings(json!({ "searchDefaults": null }))
+        .await;
+    index.wait_task(2).await;
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["searchDefaults"], json!({}));
+
+    let (response, code) = index.search_post(json!({})).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 5);
+}
+
+#[actix_rt::test]
+async fn error_invalid_search_defaults() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    for defaults in [
+        json!({ "marin": "hello" }),
+        json!({ "cropLength": "long" }),
+        // the override of the ranking rules is never persisted.
+        json!({ "rankingRulesOverride": ["words"] }),
+        json!({ "attributesToRetrieve": "title" }),
+        json!(["limit"]),
+    ] {
+        let (response, code) = index
+            .update_settings(json!({ "searchDefaults": defaults }))
+            .await;
+        assert_eq!(code, 400, "{}: {}", defaults, response);
+        assert_eq!(response["code"], "bad_request");
+    }
+}
@@ -23,6 +23,42 @@ async fn search_unexisting_index() {
         .await;
 }
 
+#[actix_rt::test]
+async fn search_index_being_created() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    // the settings update creates the index once it is processed, in a long time.
+    let (response, code) = server
+        .service
+        .patch(
+            "/indexes/test/settings?scheduleAt=2100-01-01T00:00:00Z",
+            json!({ "rankingRules": ["words"] }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let task_uid = response["taskUid"].as_u64().unwrap();
+
+    index
+        .search(json!({"q": "hello"}), |response, code| {
+            assert_eq!(code, 409, "{}", response);
+            assert_eq!(response["code"], "index_being_created");
+            assert!(response["message"]
+                .as_str()
+                .unwrap()
+                .contains(&format!("enqueued task {}", task_uid)));
+        })
+        .await;
+    let (response, code) = index.get_all_documents(Default::default()).await;
+    assert_eq!(code, 409, "{}", response);
+    assert_eq!(response["code"], "index_being_created");
+
+    // an index that nothing creates is still not found.
+    let (response, code) = server.index("other").search_post(json!({})).await;
+    assert_eq!(code, 404, "{}", response);
+    assert_eq!(response["code"], "index_not_found");
+}
+
 #[actix_rt::test]
 async fn search_unexisting_parameter() {
     let server = Server::new().await;
@@ -0,0 +1,150 @@
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+/// Returns the sorted ids of the hits matching `filter`, and the facet distribution of `brand`.
+async fn filtered(index: &Index<'_>, filter: Value) -> (Vec<u64>, Value) {
+    let (response, code) = index
+        .search_post(json!({ "filter": filter, "facets": ["brand"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    let mut ids: Vec<_> = response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect();
+    ids.sort_unstable();
+    (ids, response["facetDistribution"]["brand"].clone())
+}
+
+async fn products_index(server: &Server) -> Index<'_> {
+    let index = server.index("products");
+    index
+        .add_documents(
+            json!([
+                { "id": 1, "brand": "Apple" },
+                { "id": 2, "brand": "apple" },
+                { "id": 3, "brand": "apple" },
+                { "id": 4, "brand": "APPLE" },
+                { "id": 5, "brand": "Ａｐｐｌｅ" },
+                { "id": 6, "brand": "Samsung" },
+            ]),
+            None,
+        )
+        .await;
+    index.wait_task(0).await;
+    let (response, code) = index
+        .update_settings(json!({ "filterableAttributes": ["brand"] }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(1).await;
+    index
+}
+
+#[actix_rt::test]
+async fn values_are_compared_without_case_nor_compatibility_forms() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    // the values of the documents indexed before the setting are normalized as well.
+    let (response, code) = index
+        .update_settings(json!({
+            "facetNormalization": {
+                "brand": { "caseSensitivity": "insensitive", "unicodeNormalization": "nfkc" }
+            }
+        }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    // the distribution reports the most frequent original form of each value.
+    let (ids, distribution) = filtered(&index, json!("brand = APPLE")).await;
+    assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+    assert_eq!(distribution, json!({ "apple": 5 }));
+
+    let (ids, distribution) = filtered(&index, json!("brand != apple")).await;
+    assert_eq!(ids, vec![6]);
+    assert_eq!(distribution, json!({ "Samsung": 1 }));
+
+    // the documents keep their original values.
+    let (response, _) = index.get_document(5, None).await;
+    assert_eq!(response["brand"], "Ａｐｐｌｅ");
+
+    // the documents added afterward are normalized when they are indexed.
+    index
+        .add_documents(json!([{ "id": 7, "brand": "ＡＰＰＬＥ" }]), None)
+        .await;
+    index.wait_task(3).await;
+    let (ids, distribution) = filtered(&index, json!("brand = apple")).await;
+    assert_eq!(ids, vec![1, 2, 3, 4, 5, 7]);
+    assert_eq!(distribution, json!({ "apple": 6 }));
+}
+
+#[actix_rt::test]
+async fn case_sensitive_values_are_distinct() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index
+        .update_settings(json!({
+            "facetNormalization": { "brand": { "caseSensitivity": "sensitive" } }
+        }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(2).await;
+
+    let (ids, distribution) = filtered(&index, json!("brand = apple")).await;
+    assert_eq!(ids, vec![2, 3]);
+    assert_eq!(distribution, json!({ "apple": 2 }));
+
+    // without the NFKC normalization, the full-width letters are another value.
+    let (ids, _) = filtered(&index, json!("brand = Apple")).await;
+    assert_eq!(ids, vec![1]);
+
+    // the values are normalized again when the setting changes.
+    index
+        .update_settings(json!({
+            "facetNormalization": { "brand": { "caseSensitivity": "insensitive" } }
+        }))
+        .await;
+    index.wait_task(3).await;
+    let (ids, distribution) = filtered(&index, json!("brand = apple")).await;
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+    assert_eq!(distribution, json!({ "apple": 4 }));
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["facetNormalization"],
+        json!({ "brand": { "caseSensitivity": "insensitive", "unicodeNormalization": "none" } })
+    );
+}
+
+#[actix_rt::test]
+async fn conditions_combine_with_any_operator() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+    index
+        .update_settings(json!({
+            "filterableAttributes": ["brand", "id"],
+            "facetNormalization": { "brand": {} }
+        }))
+        .await;
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    let (ids, _) = filtered(&index, json!("brand = apple OR id = 6")).await;
+    assert_eq!(ids, vec![1, 2, 3, 4, 6]);
+
+    let (ids, _) = filtered(&index, json!("NOT brand = apple")).await;
+    assert_eq!(ids, vec![5, 6]);
+
+    let (ids, _) = filtered(&index, json!("brand IN [APPLE, samsung]")).await;
+    assert_eq!(ids, vec![1, 2, 3, 4, 6]);
+
+    let (ids, _) = filtered(&index, json!([["brand = Samsung", "id = 1"], "id != 6"])).await;
+    assert_eq!(ids, vec![1]);
+}
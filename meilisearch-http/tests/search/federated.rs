@@ -0,0 +1,130 @@
+use serde_json::{json, Value};
+
+use crate::common::Server;
+
+use super::DOCUMENTS;
+
+async fn federated_server() -> Server {
+    let server = Server::new().await;
+    let movies = server.index("movies");
+    movies.add_documents(DOCUMENTS.clone(), None).await;
+    movies.wait_task(0).await;
+
+    let books = server.index("books");
+    books
+        .add_documents(
+            json!([{ "id": 1, "title": "Dune" }, { "id": 2, "title": "Emma" }]),
+            None,
+        )
+        .await;
+    books.wait_task(1).await;
+
+    server
+}
+
+fn origins(response: &Value) -> Vec<(String, u64)> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| {
+            (
+                hit["_federation"]["indexUid"].as_str().unwrap().to_string(),
+                hit["_federation"]["position"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[actix_rt::test]
+async fn federated_search_interleaves_hits() {
+    let server = federated_server().await;
+
+    let (response, code) = server
+        .service
+        .post(
+            "/federated-search",
+            json!({ "queries": [{ "indexUid": "movies" }, { "indexUid": "books", "showRankingScore": true }] }),
+        )
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["estimatedTotalHits"], 7);
+
+    let expected = [
+        ("movies", 0),
+        ("books", 0),
+        ("movies", 1),
+        ("movies", 2),
+        ("books", 1),
+        ("movies", 3),
+        ("movies", 4),
+    ]
+    .map(|(index, position)| (index.to_string(), position));
+    assert_eq!(origins(&response), expected);
+
+    // the ranking score is only shown for the queries that asked for it.
+    assert_eq!(response["hits"][0].get("_rankingScore"), None);
+    assert_eq!(response["hits"][1]["_rankingScore"], 1.0);
+    assert_eq!(response["hits"][4]["_rankingScore"], 0.5);
+    assert_eq!(response["hits"][1]["_federation"]["queriesPosition"], 1);
+}
+
+#[actix_rt::test]
+async fn federated_search_pagination_is_stable() {
+    let server = federated_server().await;
+    let queries = json!([{ "indexUid": "movies" }, { "indexUid": "books" }]);
+
+    let (all, _) = server
+        .service
+        .post("/federated-search", json!({ "queries": queries }))
+        .await;
+
+    let mut pages = Vec::new();
+    for offset in [0, 3, 6] {
+        let (response, code) = server
+            .service
+            .post(
+                "/federated-search",
+                json!({ "queries": queries, "offset": offset, "limit": 3 }),
+            )
+            .await;
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(response["limit"], 3);
+        pages.extend(origins(&response));
+    }
+
+    assert_eq!(pages, origins(&all));
+}
+
+#[actix_rt::test]
+async fn federated_search_rejects_facets() {
+    let server = federated_server().await;
+
+    let (response, code) = server
+        .service
+        .post(
+            "/federated-search",
+            json!({ "queries": [{ "indexUid": "movies" }, { "indexUid": "books", "facets": ["title"] }] }),
+        )
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_federated_search");
+    assert_eq!(
+        response["message"],
+        "Facets are not supported by federated search, but the query on index `books` requests them."
+    );
+}
+
+#[actix_rt::test]
+async fn search_with_ranking_score() {
+    let server = federated_server().await;
+    let index = server.index("books");
+
+    index
+        .search(json!({ "showRankingScore": true }), |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(response["hits"][0]["_rankingScore"], 1.0);
+            assert_eq!(response["hits"][1]["_rankingScore"], 0.5);
+        })
+        .await;
+}
@@ -397,3 +397,95 @@ async fn displayedattr_2_smol() {
         })
     );
 }
+
+#[actix_rt::test]
+async fn format_nested_paths_in_displayed_attributes() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    index
+        .update_settings(json!({ "displayedAttributes": ["id", "tags", "reviews", "product"] }))
+        .await;
+
+    let documents = json!([
+        {
+            "id": 1,
+            "tags": ["waterproof", "hiking"],
+            "reviews": [
+                { "text": "waterproof indeed", "author": "waterproof fan", "rating": 5 },
+                { "text": "too small", "author": "bob", "rating": 3 },
+            ],
+            "product": {
+                "details": {
+                    "description": "a waterproof shell",
+                    "breathable": true,
+                    "lining": null,
+                },
+            },
+        },
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_task(1).await;
+
+    // the nested paths are formatted even though only their parent is displayed.
+    let (response, code) = index
+        .search_post(json!({ "q": "waterproof", "attributesToRetrieve": ["id"], "attributesToHighlight": ["tags", "reviews.text", "product.*"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["hits"][0]["_formatted"],
+        json!({
+            "id": 1,
+            "tags": ["<em>waterproof</em>", "hiking"],
+            "reviews": [
+                { "text": "<em>waterproof</em> indeed" },
+                { "text": "too small" },
+            ],
+            "product": {
+                "details": {
+                    "description": "a <em>waterproof</em> shell",
+                    "breathable": true,
+                    "lining": null,
+                },
+            },
+        })
+    );
+
+    // the siblings of the formatted fields are copied through untouched.
+    let (response, code) = index
+        .search_post(json!({ "q": "waterproof", "attributesToRetrieve": ["reviews"], "attributesToHighlight": ["reviews.text"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["hits"][0]["_formatted"],
+        json!({
+            "reviews": [
+                { "text": "<em>waterproof</em> indeed", "author": "waterproof fan", "rating": 5 },
+                { "text": "too small", "author": "bob", "rating": 3 },
+            ],
+        })
+    );
+
+    // each string of an array of objects is cropped.
+    let (response, code) = index
+        .search_post(json!({ "q": "waterproof", "attributesToRetrieve": ["id"], "attributesToCrop": ["reviews.text:1"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["hits"][0]["_formatted"],
+        json!({
+            "id": 1,
+            "reviews": [
+                { "text": "waterproof…" },
+                { "text": "too…" },
+            ],
+        })
+    );
+
+    // the paths that aren't displayed are still ignored.
+    let (response, code) = index
+        .search_post(json!({ "q": "waterproof", "attributesToRetrieve": ["id"], "attributesToHighlight": ["name.first"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"][0], json!({ "id": 1 }));
+}
@@ -0,0 +1,114 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static PRODUCTS: Lazy<Value> = Lazy::new(|| {
+    json!([
+        { "id": 1, "name": "running shoes", "brand": "swift", "price": 90 },
+        { "id": 2, "name": "trail shoes", "brand": "swift", "price": 120 },
+        { "id": 3, "name": "walking shoes", "brand": "swift", "price": 60 },
+        { "id": 4, "name": "tennis shoes", "brand": "ace", "price": 80 },
+        { "id": 5, "name": "shoes bag", "price": 20 },
+        { "id": 6, "name": "court shoes", "brand": "ace", "price": 70 },
+    ])
+});
+
+async fn products_index(server: &Server) -> Index<'_> {
+    let index = server.index("products");
+    index
+        .update_settings(json!({
+            "filterableAttributes": ["brand"],
+            "sortableAttributes": ["price"],
+        }))
+        .await;
+    index.add_documents(PRODUCTS.clone(), None).await;
+    index.wait_task(1).await;
+    index
+}
+
+/// The key of each group, with the ids of its hits.
+fn groups(response: &Value) -> Vec<(Value, Vec<u64>)> {
+    response["groups"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| {
+            let ids = group["hits"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|hit| hit["id"].as_u64().unwrap())
+                .collect();
+            (group["key"].clone(), ids)
+        })
+        .collect()
+}
+
+#[actix_rt::test]
+async fn search_grouped_by_attribute() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index
+        .search_post(json!({
+            "sort": ["price:desc"],
+            "groupBy": { "attribute": "brand", "limit": 2 },
+        }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    // the groups are ordered by their best hit, the document missing the brand is on its own.
+    assert_eq!(
+        groups(&response),
+        vec![
+            (json!("swift"), vec![2, 1]),
+            (json!("ace"), vec![4, 6]),
+            (Value::Null, vec![5]),
+        ]
+    );
+    assert_eq!(response["groups"][0]["estimatedTotalHits"], 3);
+    assert_eq!(response["groups"][1]["estimatedTotalHits"], 2);
+    assert_eq!(response["hits"], json!([]));
+    assert_eq!(response["estimatedTotalHits"], 6);
+
+    // the pagination applies to the groups.
+    let (response, code) = index
+        .search_post(json!({
+            "sort": ["price:desc"],
+            "groupBy": { "attribute": "brand" },
+            "offset": 1,
+            "limit": 1,
+        }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(groups(&response), vec![(json!("ace"), vec![4])]);
+}
+
+#[actix_rt::test]
+async fn search_grouped_by_invalid_attribute() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index
+        .search_post(json!({ "groupBy": { "attribute": "name" } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_search_group_by");
+
+    let (response, code) = index
+        .search_post(json!({ "groupBy": { "attribute": "brand", "limit": 0 } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_search_group_by");
+
+    index
+        .update_settings(json!({ "distinctAttribute": "brand" }))
+        .await;
+    index.wait_task(2).await;
+    let (response, code) = index
+        .search_post(json!({ "groupBy": { "attribute": "brand" } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_search_group_by");
+}
@@ -0,0 +1,107 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static RECIPES: Lazy<Value> = Lazy::new(|| {
+    json!([
+        { "id": 1, "title": "red apple pie", "kind": "dessert" },
+        { "id": 2, "title": "green apple cider", "kind": "drink" },
+        { "id": 3, "title": "apple pie with cinnamon", "kind": "dessert" },
+        { "id": 4, "title": "chocolate cake", "kind": "dessert" },
+        { "id": 5, "title": "carrot cake", "kind": "dessert" },
+        { "id": 6, "title": "the the the", "kind": "other" },
+    ])
+});
+
+async fn recipes_index(server: &Server) -> Index<'_> {
+    let index = server.index("recipes");
+    index
+        .update_settings(json!({
+            "searchableAttributes": ["title"],
+            "filterableAttributes": ["kind"],
+            "stopWords": ["the"],
+        }))
+        .await;
+    index.add_documents(RECIPES.clone(), None).await;
+    index.wait_task(1).await;
+    index
+}
+
+fn ids(response: &Value) -> Vec<u64> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn search_like_documents() {
+    let server = Server::new().await;
+    let index = recipes_index(&server).await;
+
+    let (response, code) = index.search_post(json!({ "likeDocumentIds": ["1"] })).await;
+    assert_eq!(code, 200, "{}", response);
+    // the seed is excluded, the documents sharing its rarest words come first.
+    assert_eq!(ids(&response), vec![3, 2]);
+    assert_eq!(response["estimatedTotalHits"], 2);
+    assert_eq!(response["query"], "red pie apple");
+
+    let (response, code) = index
+        .search_post(json!({ "likeDocumentIds": ["1"], "filter": "kind = drink" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![2]);
+
+    let (response, code) = index
+        .search_post(json!({ "likeDocumentIds": ["1"], "offset": 1, "limit": 1 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![2]);
+}
+
+#[actix_rt::test]
+async fn search_like_documents_without_significant_words() {
+    let server = Server::new().await;
+    let index = recipes_index(&server).await;
+
+    let (response, code) = index
+        .search_post(json!({ "likeDocumentIds": ["6"], "facets": ["kind"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"], json!([]));
+    assert_eq!(response["estimatedTotalHits"], 0);
+    assert_eq!(response["facetDistribution"], json!({}));
+    assert!(response["likeDocumentsWarning"].is_string());
+}
+
+#[actix_rt::test]
+async fn error_search_like_missing_documents() {
+    let server = Server::new().await;
+    let index = recipes_index(&server).await;
+
+    let (response, code) = index
+        .search_post(json!({ "likeDocumentIds": ["1", "42", "43"] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "like_document_not_found");
+    assert_eq!(
+        response["message"],
+        "Documents not found in `likeDocumentIds`: `42`, `43`."
+    );
+}
+
+#[actix_rt::test]
+async fn error_search_like_documents_with_query() {
+    let server = Server::new().await;
+    let index = recipes_index(&server).await;
+
+    let (response, code) = index
+        .search_post(json!({ "q": "apple", "likeDocumentIds": ["1"] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_search_like_documents");
+}
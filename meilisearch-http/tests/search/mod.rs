@@ -1,8 +1,22 @@
 // This modules contains all the test concerning search. Each particular feture of the search
 // should be tested in its own module to isolate tests and keep the tests readable.
 
+mod aggregate;
+mod analytics;
+mod defaults;
 mod errors;
+mod facet_normalization;
+mod federated;
 mod formatted;
+mod group_by;
+mod like_documents;
+mod nested_elements;
+mod nested_paths;
+mod points_in_time;
+mod prefix_search;
+mod ranking_rules_override;
+mod searchable_weights;
+mod stop_words;
 
 use crate::common::Server;
 use once_cell::sync::Lazy;
@@ -605,3 +619,94 @@ async fn search_is_hard_limited() {
         )
         .await;
 }
+
+#[actix_rt::test]
+async fn search_with_capped_query_expansion() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let documents = DOCUMENTS.clone();
+    index.add_documents(documents, None).await;
+    index.wait_task(0).await;
+
+    index
+        .search(json!({"q": "ca"}), |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(response["hits"].as_array().unwrap().len(), 1);
+            assert!(response.get("truncatedQueryExpansion").is_none());
+        })
+        .await;
+
+    index
+        .update_settings(json!({"maxPrefixExpansion": 0}))
+        .await;
+    index.wait_task(1).await;
+
+    // `ca` isn't a word of the index, it can only match through its prefix expansion.
+    index
+        .search(json!({"q": "ca"}), |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(response["hits"].as_array().unwrap().len(), 0);
+            assert_eq!(response["truncatedQueryExpansion"], true);
+        })
+        .await;
+}
+
+#[actix_rt::test]
+async fn search_with_boost() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    index
+        .update_settings(json!({"filterableAttributes": ["title"]}))
+        .await;
+
+    let documents = DOCUMENTS.clone();
+    index.add_documents(documents, None).await;
+    index.wait_task(1).await;
+
+    let (response, code) = index
+        .search_post(json!({
+            "boost": [
+                { "filter": "title = Glass", "weight": 1 },
+                { "filter": "title = 'Escape Room'", "weight": 2 },
+            ],
+            "showRankingScore": true,
+        }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    let hits = response["hits"].as_array().unwrap();
+    assert_eq!(hits.len(), 5);
+    assert_eq!(hits[0]["title"], "Escape Room");
+    assert_eq!(hits[0]["_rankingScoreBoost"], json!(2.0));
+    assert_eq!(hits[1]["title"], "Glass");
+    assert_eq!(hits[1]["_rankingScoreBoost"], json!(1.0));
+    assert!(hits[2].get("_rankingScoreBoost").is_none());
+    assert!(hits[0]["_rankingScore"].as_f64() > hits[1]["_rankingScore"].as_f64());
+}
+
+#[actix_rt::test]
+async fn search_with_invalid_boost() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    index
+        .update_settings(json!({"filterableAttributes": ["title"]}))
+        .await;
+
+    let documents = DOCUMENTS.clone();
+    index.add_documents(documents, None).await;
+    index.wait_task(1).await;
+
+    let (response, code) = index
+        .search_post(json!({ "boost": [{ "filter": "id = 1", "weight": 1 }] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_filter");
+
+    let (response, code) = index
+        .search_post(json!({ "boost": [{ "filter": "title = Glass", "weight": 0 }] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_search_boost");
+}
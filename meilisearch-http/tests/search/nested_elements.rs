@@ -0,0 +1,160 @@
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+/// Returns the sorted ids of the hits matching `filter`.
+async fn filtered_ids(index: &Index<'_>, filter: Value) -> Vec<u64> {
+    let (response, code) = index.search_post(json!({ "filter": filter })).await;
+    assert_eq!(code, 200, "{}", response);
+    let mut ids: Vec<_> = response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[actix_rt::test]
+async fn conditions_hold_within_one_element() {
+    let server = Server::new().await;
+    let index = server.index("products");
+    index
+        .add_documents(
+            json!([
+                {
+                    "id": 1,
+                    "variants": [{ "color": "red", "size": "M" }, { "color": "blue", "size": "L" }],
+                    "specs": [{ "material": "cotton", "weight": 200 }, { "material": "wool", "weight": 400 }],
+                },
+                {
+                    "id": 2,
+                    "variants": [{ "color": "red", "size": "L" }],
+                    "specs": [{ "material": "cotton", "weight": 400 }],
+                },
+                { "id": 3, "variants": [], "specs": [] },
+            ]),
+            None,
+        )
+        .await;
+    index.wait_task(0).await;
+
+    // the elements of the documents indexed before the setting are derived as well.
+    let (response, code) = index
+        .update_settings(json!({
+            "filterableAttributes": ["specs"],
+            "nestedObjectBehavior": { "variants": "element", "specs": "flat" },
+        }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    assert_eq!(
+        filtered_ids(&index, json!("variants.color = red AND variants.size = L")).await,
+        vec![2]
+    );
+    assert_eq!(
+        filtered_ids(
+            &index,
+            json!(["variants.color = blue", "variants.size = L"])
+        )
+        .await,
+        vec![1]
+    );
+    assert_eq!(
+        filtered_ids(&index, json!("variants.color = red")).await,
+        vec![1, 2]
+    );
+
+    // the flat attributes keep matching across their elements, and both kinds mix.
+    assert_eq!(
+        filtered_ids(
+            &index,
+            json!("specs.material = cotton AND specs.weight = 400")
+        )
+        .await,
+        vec![1, 2]
+    );
+    assert_eq!(
+        filtered_ids(
+            &index,
+            json!("variants.color = red AND variants.size = M AND specs.weight > 300")
+        )
+        .await,
+        vec![1]
+    );
+
+    // the documents added or deleted afterward are taken into account.
+    index
+        .add_documents(
+            json!([{ "id": 4, "variants": { "color": "RED", "size": "L" } }]),
+            None,
+        )
+        .await;
+    index.wait_task(2).await;
+    index.delete_document(2).await;
+    index.wait_task(3).await;
+    assert_eq!(
+        filtered_ids(&index, json!("variants.color = red AND variants.size = L")).await,
+        vec![4]
+    );
+
+    // back to the flattened behavior.
+    index
+        .update_settings(json!({
+            "filterableAttributes": ["specs", "variants"],
+            "nestedObjectBehavior": {},
+        }))
+        .await;
+    index.wait_task(4).await;
+    assert_eq!(
+        filtered_ids(&index, json!("variants.color = red AND variants.size = L")).await,
+        vec![1, 4]
+    );
+}
+
+#[actix_rt::test]
+async fn element_conditions_combine_with_any_operator() {
+    let server = Server::new().await;
+    let index = server.index("products");
+    index
+        .update_settings(json!({
+            "filterableAttributes": ["tags"],
+            "nestedObjectBehavior": { "variants": "element" },
+        }))
+        .await;
+    index
+        .add_documents(
+            json!([
+                { "id": 1, "tags": ["summer"], "variants": [{ "color": "red" }] },
+                { "id": 2, "tags": ["winter"], "variants": [{ "color": "blue" }] },
+            ]),
+            None,
+        )
+        .await;
+    index.wait_task(1).await;
+
+    assert_eq!(
+        filtered_ids(&index, json!("variants.color = red OR tags = winter")).await,
+        vec![1, 2]
+    );
+    assert_eq!(
+        filtered_ids(&index, json!([["variants.color = red", "tags = winter"]])).await,
+        vec![1, 2]
+    );
+    assert_eq!(
+        filtered_ids(&index, json!("NOT variants.color = red")).await,
+        vec![2]
+    );
+    assert_eq!(
+        filtered_ids(
+            &index,
+            json!("variants.color IN [red, blue] AND tags = summer")
+        )
+        .await,
+        vec![1]
+    );
+}
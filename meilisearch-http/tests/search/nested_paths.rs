@@ -0,0 +1,163 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static CUSTOMERS: Lazy<Value> = Lazy::new(|| {
+    json!([
+        {
+            "id": 1,
+            "name": "Tamo",
+            "address": { "city": "Paris", "zip": 75002 },
+            "orders": [{ "item": "sunglasses" }, { "item": "umbrella" }],
+        },
+        {
+            "id": 2,
+            "name": "Kero",
+            "address": { "city": "Lyon", "zip": 69001 },
+            "orders": [{ "item": "raincoat" }],
+        },
+        {
+            "id": 3,
+            "name": "Ross",
+            "address": { "city": "Paris", "zip": 75011 },
+            "orders": [],
+        },
+    ])
+});
+
+async fn customers_index<'a>(server: &'a Server, settings: Value) -> Index<'a> {
+    let index = server.index("customers");
+    index.update_settings(settings).await;
+    index.add_documents(CUSTOMERS.clone(), None).await;
+    index.wait_task(1).await;
+    index
+}
+
+fn ids(response: &Value) -> Vec<u64> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn path_wildcards_in_attribute_lists() {
+    let server = Server::new().await;
+    let index = customers_index(
+        &server,
+        json!({
+            "searchableAttributes": ["name", "address.*", "orders.*"],
+            "filterableAttributes": ["address.*"],
+            "sortableAttributes": ["address.zip"],
+        }),
+    )
+    .await;
+
+    // `address.*` designates the same fields as `address`.
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(
+        response["searchableAttributes"],
+        json!(["name", "address", "orders"])
+    );
+    assert_eq!(response["filterableAttributes"], json!(["address"]));
+
+    index
+        .search(
+            json!({ "filter": "address.city = Paris", "sort": ["address.zip:desc"] }),
+            |response, code| {
+                assert_eq!(code, 200, "{}", response);
+                assert_eq!(ids(&response), vec![3, 1]);
+            },
+        )
+        .await;
+
+    index
+        .search(
+            json!({ "filter": "address.zip > 70000", "sort": ["address.zip:asc"] }),
+            |response, code| {
+                assert_eq!(code, 200, "{}", response);
+                assert_eq!(ids(&response), vec![1, 3]);
+            },
+        )
+        .await;
+
+    // each object of an array is flattened.
+    index
+        .search(json!({ "q": "umbrella" }), |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(ids(&response), vec![1]);
+        })
+        .await;
+
+    // the documents keep their original shape.
+    index
+        .search(json!({ "q": "kero" }), |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(
+                response["hits"][0]["address"],
+                json!({ "city": "Lyon", "zip": 69001 })
+            );
+        })
+        .await;
+}
+
+#[actix_rt::test]
+async fn aggregate_nested_path() {
+    let server = Server::new().await;
+    let index = customers_index(&server, json!({ "filterableAttributes": ["address"] })).await;
+
+    let (response, code) = index
+        .aggregate(json!({ "groupBy": "address.city", "metrics": ["count", "max(address.zip)"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["groups"],
+        json!([
+            { "group": "Lyon", "count": 1, "max(address.zip)": 69001.0 },
+            { "group": "Paris", "count": 2, "max(address.zip)": 75011.0 },
+        ])
+    );
+}
+
+/// A key containing a literal dot and the equivalent nested path designate the same attribute:
+/// the values of both are indexed as `address.city`.
+#[actix_rt::test]
+async fn literal_dotted_key_and_nested_path_collide() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "filterableAttributes": ["address.city"] }))
+        .await;
+    let documents = json!([
+        { "id": 1, "address.city": "Lyon" },
+        { "id": 2, "address": { "city": "Paris" } },
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_task(1).await;
+
+    for (filter, expected) in [
+        ("address.city = Lyon", vec![1]),
+        ("address.city = Paris", vec![2]),
+        ("address.city = Lyon OR address.city = Paris", vec![1, 2]),
+    ] {
+        index
+            .search(json!({ "filter": filter }), |response, code| {
+                assert_eq!(code, 200, "{}", response);
+                assert_eq!(ids(&response), expected);
+            })
+            .await;
+    }
+
+    // the documents are returned as they were sent.
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!({ "id": 1, "address.city": "Lyon" }));
+    let (response, code) = index.get_document(2, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!({ "id": 2, "address": { "city": "Paris" } }));
+}
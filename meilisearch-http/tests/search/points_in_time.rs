@@ -0,0 +1,115 @@
+use serde_json::{json, Value};
+
+use crate::common::Server;
+
+fn ids(response: &Value) -> Vec<u64> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn pages_are_served_from_the_point_in_time() {
+    let server = Server::new().await;
+    let index = server.index("movies");
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }, { "id": 3 }]), None)
+        .await;
+    index.wait_task(0).await;
+
+    let (response, code) = index.search_post(json!({ "limit": 2, "pit": true })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![1, 2]);
+    let token = response["pitToken"].as_str().unwrap().to_string();
+
+    index
+        .add_documents(json!([{ "id": 0 }, { "id": 4 }]), None)
+        .await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    // the next page ignores the documents added in the meantime.
+    let (response, code) = index
+        .search_post(json!({ "offset": 2, "limit": 2, "pitToken": token }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![3]);
+    assert_eq!(response["pitToken"], json!(token));
+
+    let (response, code) = index
+        .search_get(json!({ "offset": 2, "limit": 2, "pitToken": token }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![3]);
+
+    let (response, _) = index.search_post(json!({ "offset": 2, "limit": 2 })).await;
+    assert_eq!(ids(&response), vec![2, 3]);
+    assert!(response.get("pitToken").is_none());
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["openPits"], 1);
+}
+
+#[actix_rt::test]
+async fn oldest_point_in_time_is_evicted() {
+    let server = Server::new().await;
+    let index = server.index("movies");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(0).await;
+
+    let mut tokens = Vec::new();
+    for _ in 0..5 {
+        let (response, code) = index.search_post(json!({ "pit": true })).await;
+        assert_eq!(code, 200, "{}", response);
+        tokens.push(response["pitToken"].as_str().unwrap().to_string());
+    }
+
+    let (response, _) = index.stats().await;
+    assert_eq!(response["openPits"], 4);
+
+    let (response, code) = index.search_post(json!({ "pitToken": tokens[0] })).await;
+    assert_eq!(code, 410, "{}", response);
+    assert_eq!(response["code"], "pit_expired");
+
+    let (response, code) = index.search_post(json!({ "pitToken": tokens[4] })).await;
+    assert_eq!(code, 200, "{}", response);
+}
+
+#[actix_rt::test]
+async fn error_invalid_point_in_time() {
+    let server = Server::new().await;
+    let index = server.index("movies");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = index.search_post(json!({ "pitToken": "unknown" })).await;
+    assert_eq!(code, 410, "{}", response);
+    assert_eq!(response["code"], "pit_expired");
+    assert_eq!(response["type"], "invalid_request");
+
+    let (response, code) = index
+        .search_post(json!({ "pit": true, "rankingRulesOverride": ["words"] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_pit");
+
+    let (response, code) = index
+        .update_settings(json!({ "searchDefaults": { "pit": true } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "bad_request");
+
+    let (response, code) = server
+        .service
+        .post(
+            "/federated-search",
+            json!({ "queries": [{ "indexUid": "movies", "pit": true }] }),
+        )
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_federated_search");
+}
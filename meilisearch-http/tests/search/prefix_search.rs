@@ -0,0 +1,112 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static PARTS: Lazy<Value> = Lazy::new(|| {
+    json!([
+        { "id": 1, "code": "12", "name": "washer" },
+        { "id": 2, "code": "1234", "name": "bolt" },
+        { "id": 3, "code": "99", "name": "phones" },
+        { "id": 4, "code": "55", "name": "phone" },
+    ])
+});
+
+async fn parts_index(server: &Server, settings: Value) -> Index<'_> {
+    let index = server.index("parts");
+    index.update_settings(settings).await;
+    index.add_documents(PARTS.clone(), None).await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    index
+}
+
+fn sorted_ids(response: &Value) -> Vec<u64> {
+    let mut ids: Vec<_> = response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[actix_rt::test]
+async fn search_disables_the_prefix_search() {
+    let server = Server::new().await;
+    let index = parts_index(&server, json!({})).await;
+
+    let (response, code) = index.search_post(json!({ "q": "12" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![1, 2]);
+
+    let (response, code) = index
+        .search_post(json!({ "q": "12", "prefixSearch": "disabled" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![1]);
+
+    let (response, code) = index
+        .search_get(json!({ "q": "12", "prefixSearch": "disabled" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![1]);
+}
+
+#[actix_rt::test]
+async fn search_overrides_the_setting() {
+    let server = Server::new().await;
+    let index = parts_index(&server, json!({ "prefixSearch": "disabled" })).await;
+
+    let (response, code) = index.search_post(json!({ "q": "12" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![1]);
+
+    let (response, code) = index
+        .search_post(json!({ "q": "12", "prefixSearch": "indexingTime" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![1, 2]);
+}
+
+#[actix_rt::test]
+async fn complete_last_word_still_matches_with_typos() {
+    let server = Server::new().await;
+    let index = parts_index(&server, json!({ "prefixSearch": "disabled" })).await;
+
+    // `phones` is one typo away from `phone`, which is long enough for one typo.
+    let (response, code) = index.search_post(json!({ "q": "phone" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![3, 4]);
+
+    index
+        .update_settings(json!({ "typoTolerance": { "enabled": false } }))
+        .await;
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    let (response, code) = index.search_post(json!({ "q": "phone" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![4]);
+}
+
+#[actix_rt::test]
+async fn prefix_search_disabled_on_attributes() {
+    let server = Server::new().await;
+    let index = parts_index(&server, json!({ "disablePrefixOnAttributes": ["code"] })).await;
+
+    let (response, code) = index.search_post(json!({ "q": "12" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![1]);
+
+    // the other attributes still match the last word as a prefix.
+    let (response, code) = index.search_post(json!({ "q": "bol" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_ids(&response), vec![2]);
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["disablePrefixOnAttributes"], json!(["code"]));
+    assert_eq!(response["prefixSearch"], json!("indexingTime"));
+}
@@ -0,0 +1,113 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static PRODUCTS: Lazy<Value> = Lazy::new(|| {
+    json!([
+        { "id": 1, "title": "red shoes", "price": 30 },
+        { "id": 2, "title": "red shoes and socks", "price": 10 },
+        { "id": 3, "title": "blue shoes", "price": 20 },
+    ])
+});
+
+async fn products_index(server: &Server) -> Index<'_> {
+    let index = server.index("products");
+    index
+        .update_settings(json!({ "sortableAttributes": ["price"] }))
+        .await;
+    index.add_documents(PRODUCTS.clone(), None).await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    index
+}
+
+fn ids(response: &Value) -> Vec<u64> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn override_applies_to_the_search_only() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index
+        .search_post(json!({ "rankingRulesOverride": ["price:desc"], "showRankingScore": true }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![1, 3, 2]);
+    // the ranking score follows the order of the override.
+    assert_eq!(response["hits"][0]["_rankingScore"], json!(1.0));
+
+    let (response, code) = index
+        .search_post(json!({ "q": "red shoes", "rankingRulesOverride": ["price:asc", "words"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![2, 3, 1]);
+
+    // the ranking rules of the index are left untouched.
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["rankingRules"],
+        json!([
+            "words",
+            "typo",
+            "proximity",
+            "attribute",
+            "sort",
+            "exactness"
+        ])
+    );
+    let (response, _) = index.search_post(json!({ "q": "red shoes" })).await;
+    assert_eq!(ids(&response)[0], 1);
+}
+
+#[actix_rt::test]
+async fn error_invalid_ranking_rules_override() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    for rules in [json!(["title:asc"]), json!(["unknown"]), json!("words")] {
+        let (response, code) = index
+            .search_post(json!({ "rankingRulesOverride": rules }))
+            .await;
+        assert_eq!(code, 400, "{}", response);
+        if rules.is_array() {
+            assert_eq!(response["code"], "invalid_ranking_rule", "{}", response);
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn error_ranking_rules_override_without_admin_key() {
+    let mut server = Server::new_auth().await;
+    server.use_api_key("MASTER_KEY");
+    products_index(&server).await;
+    let (response, code) = server
+        .add_api_key(json!({
+            "indexes": ["products"],
+            "actions": ["search"],
+            "expiresAt": null,
+        }))
+        .await;
+    assert_eq!(code, 201, "{}", response);
+    let key = response["key"].as_str().unwrap().to_string();
+
+    server.use_api_key(&key);
+    let index = server.index("products");
+    let (response, code) = index
+        .search_post(json!({ "rankingRulesOverride": ["price:desc"] }))
+        .await;
+    assert_eq!(code, 403, "{}", response);
+    assert_eq!(response["code"], "invalid_api_key");
+
+    let (response, code) = index.search_post(json!({})).await;
+    assert_eq!(code, 200, "{}", response);
+}
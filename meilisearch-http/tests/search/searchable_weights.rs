@@ -0,0 +1,108 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static PRODUCTS: Lazy<Value> = Lazy::new(|| {
+    json!([
+        { "id": 1, "title": "red shoes", "description": "a pair", "tags": "summer" },
+        { "id": 2, "title": "a pair", "description": "red shoes", "tags": "summer" },
+        { "id": 3, "title": "a pair", "description": "summer", "tags": "red shoes" },
+    ])
+});
+
+async fn products_index(server: &Server) -> Index<'_> {
+    let index = server.index("products");
+    index
+        .update_settings(json!({
+            "searchableAttributes": { "description": 1, "title": 3, "tags": 1 },
+        }))
+        .await;
+    index.add_documents(PRODUCTS.clone(), None).await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    index
+}
+
+fn ids(response: &Value) -> Vec<u64> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn heavier_attributes_rank_first() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index.search_post(json!({ "q": "red shoes" })).await;
+    assert_eq!(code, 200, "{}", response);
+    // the document matching in the title, of weight 3, outranks the ones matching in the
+    // attributes of weight 1.
+    assert_eq!(ids(&response)[0], 1);
+
+    // the weights of the search take precedence over the ones of the index.
+    let (response, code) = index
+        .search_post(json!({ "q": "red shoes", "searchableWeights": { "tags": 5 } }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![3, 1, 2]);
+}
+
+#[actix_rt::test]
+async fn get_settings_returns_the_form_that_was_set() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["searchableAttributes"],
+        json!({ "description": 1, "tags": 1, "title": 3 })
+    );
+
+    index
+        .update_settings(json!({ "searchableAttributes": ["title", "description"] }))
+        .await;
+    index.wait_task(2).await;
+    let (response, _) = index.settings().await;
+    assert_eq!(
+        response["searchableAttributes"],
+        json!(["title", "description"])
+    );
+
+    index
+        .update_settings(json!({ "searchableAttributes": { "title": 2 } }))
+        .await;
+    index.wait_task(3).await;
+    index.delete_settings().await;
+    index.wait_task(4).await;
+    let (response, _) = index.settings().await;
+    assert_eq!(response["searchableAttributes"], json!(["*"]));
+}
+
+#[actix_rt::test]
+async fn error_null_searchable_weight() {
+    let server = Server::new().await;
+    let index = products_index(&server).await;
+
+    index
+        .update_settings(json!({ "searchableAttributes": { "title": 0 } }))
+        .await;
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+    assert_eq!(
+        response["error"]["code"],
+        json!("invalid_searchable_weight")
+    );
+
+    let (response, code) = index
+        .search_post(json!({ "q": "red", "searchableWeights": { "title": 0 } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], json!("invalid_searchable_weight"));
+}
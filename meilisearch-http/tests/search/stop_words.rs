@@ -0,0 +1,89 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::common::index::Index;
+use crate::common::Server;
+
+static BANDS: Lazy<Value> = Lazy::new(|| {
+    json!([
+        { "id": 1, "title": "The Who" },
+        { "id": 2, "title": "Who is the best band" },
+        { "id": 3, "title": "The Rolling Stones" },
+    ])
+});
+
+async fn bands_index(server: &Server) -> Index<'_> {
+    let index = server.index("bands");
+    index
+        .update_settings(json!({ "stopWords": ["the", "who", "is", "a"] }))
+        .await;
+    index.add_documents(BANDS.clone(), None).await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    index
+}
+
+fn ids(response: &Value) -> Vec<u64> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn quoted_phrase_of_stop_words_matches_exactly() {
+    let server = Server::new().await;
+    let index = bands_index(&server).await;
+
+    // the document is only made of stop words.
+    let (response, code) = index.search_post(json!({ "q": "\"the who\"" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![1]);
+
+    let (response, code) = index
+        .search_get(json!({ "q": "\"The Rolling Stones\"" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![3]);
+}
+
+#[actix_rt::test]
+async fn stop_words_are_ignored_unless_asked() {
+    let server = Server::new().await;
+    let index = bands_index(&server).await;
+
+    let (response, code) = index.search_post(json!({ "q": "is stones" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![3]);
+
+    // the stop words are searched like the other words of the query.
+    let (response, code) = index
+        .search_post(json!({ "q": "is stones", "ignoreStopWords": false }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![2]);
+
+    let (response, code) = index
+        .search_get(json!({ "q": "is band", "ignoreStopWords": false }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![2]);
+}
+
+#[actix_rt::test]
+async fn phrases_follow_the_stop_words_setting() {
+    let server = Server::new().await;
+    let index = bands_index(&server).await;
+
+    // the stop words are indexed whatever the setting, so changing it doesn't index the
+    // documents again.
+    index.update_settings(json!({ "stopWords": [] })).await;
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    assert_eq!(response["details"]["reindexedDocuments"], 0);
+    let (response, code) = index.search_post(json!({ "q": "\"the who\"" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(ids(&response), vec![1]);
+}
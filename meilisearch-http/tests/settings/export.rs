@@ -0,0 +1,91 @@
+use serde_json::json;
+
+use crate::common::Server;
+
+#[actix_rt::test]
+async fn export_import_round_trip() {
+    let server = Server::new().await;
+    let index = server.index("source");
+    let (response, code) = index
+        .update_settings(json!({
+            "rankingRules": ["words", "sort", "typo", "proximity", "attribute", "exactness"],
+            "searchableAttributes": ["title", "overview"],
+            "stopWords": ["the", "a", "of"],
+            "synonyms": { "car": ["vehicle", "automobile"] },
+            "filterableAttributes": ["genre", "year"],
+        }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(0).await;
+
+    let (export, code) = server
+        .service
+        .get("/indexes/source/settings?format=export")
+        .await;
+    assert_eq!(code, 200, "{}", export);
+    assert_eq!(export["version"], 1);
+    assert_eq!(export["settings"]["stopWords"], json!(["a", "of", "the"]));
+    assert_eq!(
+        export["settings"]["synonyms"]["car"],
+        json!(["automobile", "vehicle"])
+    );
+    // the ranking rules are kept in their order.
+    assert_eq!(export["settings"]["rankingRules"][1], "sort");
+
+    let (response, code) = server
+        .service
+        .put("/indexes/target/settings/import", export.clone())
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let response = server.index("target").wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    let (reexport, code) = server
+        .service
+        .get("/indexes/target/settings?format=export")
+        .await;
+    assert_eq!(code, 200, "{}", reexport);
+    assert_eq!(reexport.to_string(), export.to_string());
+}
+
+#[actix_rt::test]
+async fn import_replaces_all_settings() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "stopWords": ["the"], "distinctAttribute": "sku" }))
+        .await;
+    index.wait_task(0).await;
+
+    let (response, code) = server
+        .service
+        .put(
+            "/indexes/test/settings/import",
+            json!({ "version": 1, "settings": { "stopWords": ["a"] } }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    index.wait_task(1).await;
+
+    let (settings, _) = index.settings().await;
+    assert_eq!(settings["stopWords"], json!(["a"]));
+    assert_eq!(settings["distinctAttribute"], json!(null));
+}
+
+#[actix_rt::test]
+async fn import_unsupported_version() {
+    let server = Server::new().await;
+    let (response, code) = server
+        .service
+        .put(
+            "/indexes/test/settings/import",
+            json!({ "version": 42, "settings": {} }),
+        )
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_settings_export");
+
+    // nothing was enqueued.
+    let (response, _) = server.tasks().await;
+    assert_eq!(response["results"], json!([]));
+}
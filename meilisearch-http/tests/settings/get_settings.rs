@@ -24,6 +24,16 @@ static DEFAULT_SETTINGS_VALUES: Lazy<HashMap<&'static str, Value>> = Lazy::new(|
     );
     map.insert("stop_words", json!([]));
     map.insert("synonyms", json!({}));
+    map.insert("warmup_queries", json!([]));
+    map.insert("max_word_derivations", json!(100));
+    map.insert("max_prefix_expansion", json!(1000));
+    map.insert("max_values_per_facet", json!(100));
+    map.insert("search_defaults", json!({}));
+    map.insert("document_metadata", json!(false));
+    map.insert("nested_object_behavior", json!({}));
+    map.insert("document_transforms", json!([]));
+    map.insert("custom_segmenters", json!({}));
+    map.insert("facet_normalization", json!({}));
     map
 });
 
@@ -43,7 +53,7 @@ async fn get_settings() {
     let (response, code) = index.settings().await;
     assert_eq!(code, 200);
     let settings = response.as_object().unwrap();
-    assert_eq!(settings.keys().len(), 9);
+    assert_eq!(settings.keys().len(), 21);
     assert_eq!(settings["displayedAttributes"], json!(["*"]));
     assert_eq!(settings["searchableAttributes"], json!(["*"]));
     assert_eq!(settings["filterableAttributes"], json!([]));
@@ -61,6 +71,18 @@ async fn get_settings() {
         ])
     );
     assert_eq!(settings["stopWords"], json!([]));
+    assert_eq!(settings["warmupQueries"], json!([]));
+    assert_eq!(settings["maxWordDerivations"], json!(100));
+    assert_eq!(settings["maxPrefixExpansion"], json!(1000));
+    assert_eq!(settings["maxValuesPerFacet"], json!(100));
+    assert_eq!(settings["searchDefaults"], json!({}));
+    assert_eq!(settings["documentMetadata"], json!(false));
+    assert_eq!(settings["nestedObjectBehavior"], json!({}));
+    assert_eq!(settings["documentTransforms"], json!([]));
+    assert_eq!(settings["customSegmenters"], json!({}));
+    assert_eq!(settings["prefixSearch"], json!("indexingTime"));
+    assert_eq!(settings["disablePrefixOnAttributes"], json!([]));
+    assert_eq!(settings["facetNormalization"], json!({}));
 }
 
 #[actix_rt::test]
@@ -171,6 +193,33 @@ async fn update_setting_unexisting_index() {
     assert_eq!(response["status"], "succeeded");
 }
 
+#[actix_rt::test]
+async fn update_settings_then_add_documents_to_unexisting_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    // both tasks are enqueued before the index exists.
+    let (_response, code) = index
+        .update_settings(json!({ "filterableAttributes": ["color"] }))
+        .await;
+    assert_eq!(code, 202);
+    let (_response, code) = index
+        .add_documents(json!([{ "id": 1, "color": "blue" }]), None)
+        .await;
+    assert_eq!(code, 202);
+
+    let response = index.wait_task(0).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "id");
+    let (response, _) = index.settings().await;
+    assert_eq!(response["filterableAttributes"], json!(["color"]));
+}
+
 #[actix_rt::test]
 async fn error_update_setting_unexisting_index_invalid_uid() {
     let server = Server::new().await;
@@ -179,7 +228,7 @@ async fn error_update_setting_unexisting_index_invalid_uid() {
     assert_eq!(code, 400);
 
     let expected = json!({
-        "message": "`test##!  ` is not a valid index uid. Index uid can be an integer or a string containing only alphanumeric characters, hyphens (-) and underscores (_).",
+        "message": "`test##!  ` is not a valid index uid: the character '#' at position 4 is not allowed. Index uid must be between 1 and 400 bytes long, and can only contain the characters `a-z`, `A-Z`, `0-9`, `-` and `_`.",
         "code": "invalid_index_uid",
         "type": "invalid_request",
         "link": "https://docs.meilisearch.com/errors#invalid_index_uid"});
@@ -264,7 +313,17 @@ test_setting_routes!(
     distinct_attribute,
     stop_words,
     ranking_rules,
-    synonyms
+    synonyms,
+    warmup_queries,
+    max_word_derivations,
+    max_prefix_expansion,
+    max_values_per_facet,
+    search_defaults,
+    document_metadata,
+    nested_object_behavior,
+    document_transforms,
+    custom_segmenters,
+    facet_normalization
 );
 
 #[actix_rt::test]
@@ -312,3 +371,73 @@ async fn set_and_reset_distinct_attribute_with_dedicated_route() {
 
     assert_eq!(response, json!(null));
 }
+
+#[actix_rt::test]
+async fn settings_update_reports_the_reindexed_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "color": "red" }, { "id": 2 }]), None)
+        .await;
+    index.wait_task(0).await;
+
+    index
+        .update_settings(json!({ "filterableAttributes": ["color"] }))
+        .await;
+    let response = index.wait_task(1).await;
+    assert_eq!(response["status"], "succeeded");
+    assert_eq!(response["details"]["reindexedDocuments"], 2);
+
+    // the displayed attributes don't need the documents to be indexed again.
+    index
+        .update_settings(json!({ "displayedAttributes": ["id"] }))
+        .await;
+    let response = index.wait_task(2).await;
+    assert_eq!(response["status"], "succeeded");
+    assert_eq!(response["details"]["reindexedDocuments"], 0);
+}
+
+#[actix_rt::test]
+async fn scheduled_settings_update_waits_until_due() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = index
+        .service
+        .patch(
+            "/indexes/test/settings?scheduleAt=2100-01-01T00:00:00Z",
+            json!({ "rankingRules": ["words"] }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let uid = response["uid"].as_u64().unwrap();
+
+    // a task registered after the scheduled one is processed first.
+    index.delete_settings().await;
+    let response = index.wait_task(uid + 1).await;
+    assert_eq!(response["status"], "succeeded");
+
+    let (response, code) = index.get_task(uid).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["status"], "enqueued");
+    assert_eq!(response["details"]["scheduledAt"], "2100-01-01T00:00:00Z");
+}
+
+#[actix_rt::test]
+async fn error_scheduled_settings_update_is_validated_right_away() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let (response, code) = index
+        .service
+        .patch(
+            "/indexes/test/settings?scheduleAt=2100-01-01T00:00:00Z",
+            json!({ "rankingRules": ["manyTheFish"] }),
+        )
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_ranking_rule");
+}
@@ -1,2 +1,4 @@
 mod distinct;
+mod export;
 mod get_settings;
+mod preview;
@@ -0,0 +1,66 @@
+use serde_json::json;
+
+use crate::common::Server;
+
+#[actix_rt::test]
+async fn preview_settings_on_a_sample() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "Shazam", "overview": "A boy becomes a hero" },
+        { "id": 2, "title": "Hero", "overview": "A swordsman" },
+        { "id": 3, "title": "Captain Marvel", "overview": "A pilot" },
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes/test/settings/preview",
+            json!({
+                "settings": { "searchableAttributes": ["title"] },
+                "queries": ["hero", "pilot"],
+            }),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let task = index.wait_task(1).await;
+    assert_eq!(task["status"], "succeeded", "{}", task);
+    assert_eq!(task["type"], "settingsPreview");
+
+    let details = &task["details"];
+    assert_eq!(details["sampleSize"], 1000);
+    assert_eq!(details["sampledDocuments"], 3);
+    let previews = details["previews"].as_array().unwrap();
+    assert_eq!(previews.len(), 2);
+    assert_eq!(previews[0]["q"], "hero");
+    assert_eq!(previews[0]["current"].as_array().unwrap().len(), 2);
+    assert_eq!(previews[0]["candidate"], json!(["2"]));
+    assert_eq!(previews[1]["candidate"], json!([]));
+
+    // the settings of the index are untouched.
+    let (settings, _) = index.settings().await;
+    assert_eq!(settings["searchableAttributes"], json!(["*"]));
+}
+
+#[actix_rt::test]
+async fn preview_settings_bad_parameters() {
+    let server = Server::new().await;
+    server.index("test").create(None).await;
+
+    let bodies = [
+        json!({ "settings": {}, "queries": [] }),
+        json!({ "settings": {}, "queries": ["hero"], "sampleSize": 0 }),
+        json!({ "settings": {}, "queries": ["hero"], "sampleSize": 100_000 }),
+        json!({ "settings": {}, "queries": ["hero"], "limit": 1000 }),
+    ];
+    for body in bodies {
+        let (response, code) = server
+            .service
+            .post("/indexes/test/settings/preview", body.clone())
+            .await;
+        assert_eq!(code, 400, "{}: {}", body, response);
+        assert_eq!(response["code"], "invalid_settings_preview", "{}", response);
+    }
+}
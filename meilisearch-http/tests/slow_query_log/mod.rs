@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::time::sleep;
+
+use crate::common::server::default_settings;
+use crate::common::Server;
+
+use meilisearch_http::Opt;
+
+fn read_slow_query_log(path: &std::path::Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn slow_searches_are_recorded() {
+    let temp = tempfile::tempdir().unwrap();
+    let slow_query_log_path = temp.path().join("slow-queries.log");
+
+    let options = Opt {
+        slow_query_log_path: Some(slow_query_log_path.clone()),
+        // every search is slow.
+        slow_query_log_threshold_ms: 0,
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "hello" }]), None)
+        .await;
+    index
+        .update_settings(json!({ "filterableAttributes": ["id"] }))
+        .await;
+    index.wait_task(1).await;
+
+    let (response, code) = index
+        .search_post(json!({ "q": "hello", "filter": "id = 1", "limit": 5 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+
+    // the records are written in the background.
+    let mut records = Vec::new();
+    for _ in 0..20 {
+        records = read_slow_query_log(&slow_query_log_path);
+        if !records.is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    assert_eq!(records.len(), 1, "{:?}", records);
+    assert_eq!(records[0]["indexUid"], "test");
+    assert_eq!(records[0]["q"], "hello");
+    assert_eq!(records[0]["filter"], "id = 1");
+    assert_eq!(records[0]["limit"], 5);
+    assert_eq!(records[0]["estimatedTotalHits"], 1);
+    assert_eq!(records[0]["timedOut"], false);
+    assert!(records[0]["durationMs"].is_u64());
+    assert!(records[0]["processingTimeMs"].is_u64());
+
+    // no search is slow anymore.
+    let (response, code) = server
+        .service
+        .patch(
+            "/config",
+            json!({ "slowQueryLog": { "thresholdMs": 60000 } }),
+        )
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["slowQueryLog"]["enabled"], true);
+    assert_eq!(response["slowQueryLog"]["thresholdMs"], 60000);
+    assert_eq!(response["slowQueryLog"]["droppedRecords"], 0);
+
+    let (response, _) = server.service.get("/config").await;
+    assert_eq!(response["slow_query_log_threshold_ms"], 60000);
+
+    index.search_post(json!({ "q": "hello" })).await;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(read_slow_query_log(&slow_query_log_path).len(), 1);
+}
+
+#[actix_rt::test]
+async fn update_threshold_of_disabled_slow_query_log() {
+    let server = Server::new().await;
+
+    let (response, code) = server
+        .service
+        .patch("/config", json!({ "slowQueryLog": { "thresholdMs": 10 } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "bad_request");
+}
@@ -81,3 +81,114 @@ async fn perform_snapshot() {
         server.index("test1").settings(),
     );
 }
+
+#[actix_rt::test]
+async fn verify_snapshot() {
+    let temp = tempfile::tempdir().unwrap();
+    let snapshot_dir = tempfile::tempdir().unwrap();
+
+    let options = Opt {
+        snapshot_dir: snapshot_dir.path().to_owned(),
+        snapshot_interval_sec: 1,
+        schedule_snapshot: true,
+        verify_after_write: true,
+        ..default_settings(temp.path())
+    };
+    let meta_env_size = options.max_task_db_size.get_bytes() as usize;
+    let index_size = options.max_index_size.get_bytes() as usize;
+
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+    index.load_test_set().await;
+    index.wait_task(0).await;
+
+    sleep(Duration::from_secs(2)).await;
+
+    let snapshot_path = snapshot_dir.path().join("db.snapshot");
+    let report =
+        meilisearch_lib::verify_snapshot(&snapshot_path, meta_env_size, index_size).unwrap();
+    assert!(report.is_valid(), "{}", report);
+    assert!(report.files.iter().any(|file| file.path == "data.mdb"));
+    assert_eq!(report.indexes.len(), 1);
+
+    // a truncated snapshot is reported as invalid.
+    let truncated = snapshot_dir.path().join("truncated.snapshot");
+    let content = std::fs::read(&snapshot_path).unwrap();
+    std::fs::write(&truncated, &content[..content.len() / 2]).unwrap();
+    let report = meilisearch_lib::verify_snapshot(&truncated, meta_env_size, index_size).unwrap();
+    assert!(!report.is_valid(), "{}", report);
+}
+
+#[actix_rt::test]
+async fn throttled_snapshot() {
+    let temp = tempfile::tempdir().unwrap();
+    let snapshot_dir = tempfile::tempdir().unwrap();
+
+    let options = Opt {
+        snapshot_dir: snapshot_dir.path().to_owned(),
+        snapshot_interval_sec: 1,
+        schedule_snapshot: true,
+        snapshot_io_budget_mb: Some(64),
+        ..default_settings(temp.path())
+    };
+
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+    index.load_test_set().await;
+    index.wait_task(0).await;
+
+    sleep(Duration::from_secs(2)).await;
+
+    // the stats report what the throttle did to the last snapshot.
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response["lastSnapshot"]["writtenBytes"].as_u64().unwrap() > 0);
+    assert!(response["lastSnapshot"]["averageBytesPerSec"].is_u64());
+    assert!(response["lastSnapshot"]["pausedMs"].is_u64());
+}
+
+#[actix_rt::test]
+async fn snapshot_schedule() {
+    let temp = tempfile::tempdir().unwrap();
+    let snapshot_dir = tempfile::tempdir().unwrap();
+
+    let options = Opt {
+        snapshot_dir: snapshot_dir.path().to_owned(),
+        snapshot_schedule: vec!["0 3 * * *".to_string(), "30 15 * * mon-fri".to_string()],
+        schedule_timezone: "CET-1CEST,M3.5.0,M10.5.0/3".to_string(),
+        ..default_settings(temp.path())
+    };
+
+    let server = Server::new_with_options(options).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // the stats show when the next scheduled snapshot is.
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200, "{}", response);
+    let next_snapshot_at = response["nextSnapshotAt"].as_str().unwrap();
+    let next_snapshot_at = time::OffsetDateTime::parse(
+        next_snapshot_at,
+        &time::format_description::well_known::Rfc3339,
+    )
+    .unwrap();
+    assert!(next_snapshot_at > time::OffsetDateTime::now_utc());
+    assert!(next_snapshot_at - time::OffsetDateTime::now_utc() <= time::Duration::days(1));
+    assert!(response.get("nextDumpAt").is_none());
+}
+
+#[actix_rt::test]
+async fn invalid_snapshot_schedule() {
+    let temp = tempfile::tempdir().unwrap();
+
+    let options = Opt {
+        snapshot_schedule: vec!["0 3 * * *".to_string(), "0 3 * * mon-fru".to_string()],
+        ..default_settings(temp.path())
+    };
+
+    // the server doesn't start with the offending expression.
+    let error = Server::new_with_options(options).await.err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "Invalid cron expression `0 3 * * mon-fru` at position 13: `fru` is not a valid value of the days of the week."
+    );
+}
@@ -1,6 +1,11 @@
+use std::time::Duration;
+
+use meilisearch_http::Opt;
 use serde_json::json;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::time::sleep;
 
+use crate::common::server::default_settings;
 use crate::common::Server;
 
 #[actix_rt::test]
@@ -72,4 +77,121 @@ async fn stats() {
     assert_eq!(response["indexes"]["test"]["fieldDistribution"]["id"], 2);
     assert_eq!(response["indexes"]["test"]["fieldDistribution"]["name"], 1);
     assert_eq!(response["indexes"]["test"]["fieldDistribution"]["age"], 1);
+    // every registered task was fetched by the scheduler.
+    assert_eq!(
+        response["schedulerLag"],
+        json!({ "unfetchedTasks": 0, "oldestUnfetchedTaskAgeMs": null })
+    );
+    assert_eq!(response["queuedJobs"], json!([]));
+    assert_eq!(response["quarantinedTasks"], 0);
+}
+
+#[actix_rt::test]
+async fn warmup_after_settings_update() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = Opt {
+        enable_warmup: true,
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["warmup"]["warmedUpIndexes"], 0);
+
+    index
+        .add_documents(json!([{ "id": 1, "title": "hello" }]), None)
+        .await;
+    index.wait_task(0).await;
+    index
+        .update_settings(json!({ "warmupQueries": ["hello", "world"] }))
+        .await;
+    index.wait_task(1).await;
+
+    // the warm-up runs in the background, give it some time to complete.
+    for _ in 0..50 {
+        let (response, _) = server.stats().await;
+        if response["warmup"]["warmedUpIndexes"] == 1 {
+            assert_eq!(response["warmup"]["warmupQueries"], 2);
+            assert_eq!(response["warmup"]["isWarmingUp"], false);
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("the index was not warmed up");
+}
+
+#[actix_rt::test]
+async fn search_queue_status() {
+    let temp = tempfile::tempdir().unwrap();
+    let options = Opt {
+        max_concurrent_searches: Some(2),
+        max_queued_searches: 10,
+        ..default_settings(temp.path())
+    };
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(0).await;
+
+    index
+        .search(json!({ "q": "1" }), |_, code| assert_eq!(code, 200))
+        .await;
+
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(
+        response["searchQueue"],
+        json!({
+            "inFlight": 0,
+            "queued": 0,
+            "maxConcurrentSearches": 2,
+            "maxQueuedSearches": 10,
+        })
+    );
+}
+
+#[actix_rt::test]
+async fn reader_slots() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut options = default_settings(temp.path());
+    options.indexer_options.max_readers = 32;
+    let server = Server::new_with_options(options).await.unwrap();
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(0).await;
+
+    index
+        .search(json!({ "q": "1" }), |_, code| assert_eq!(code, 200))
+        .await;
+
+    // the read transaction of the search is released before the response is sent.
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(
+        response["indexes"]["test"]["readers"],
+        json!({
+            "maxReaders": 32,
+            "readersInUse": 0,
+        })
+    );
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["readers"]["maxReaders"], 32);
+}
+
+#[actix_rt::test]
+async fn get_config_redacts_secrets() {
+    let mut server = Server::new_auth().await;
+    server.use_admin_key("MASTER_KEY").await;
+
+    let (response, code) = server.service.get("/config").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["master_key"], "[redacted]");
+    assert!(response.get("max_batch_size").is_some());
+    assert!(response.get("http_payload_size_limit").is_some());
+    assert!(!response.to_string().contains("MASTER_KEY"));
 }
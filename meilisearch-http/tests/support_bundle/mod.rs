@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use actix_web::test;
+use flate2::read::GzDecoder;
+use meilisearch_http::Opt;
+use meilisearch_lib::options::Sensitive;
+use serde_json::{json, Value};
+
+use crate::common::server::default_settings;
+use crate::common::Server;
+
+/// Planted in the master key, in documents, and in a fetch url. It must never be in a bundle.
+const PLANTED_SECRET: &str = "planted-secret-5f2b8c91";
+
+/// Returns the content of the files of a gzipped tarball, by name.
+fn unpack(archive: &[u8]) -> BTreeMap<String, String> {
+    let mut files = BTreeMap::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().display().to_string();
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        files.insert(name, content);
+    }
+    files
+}
+
+#[actix_rt::test]
+async fn support_bundle_contains_no_secret() {
+    let temp = tempfile::tempdir().unwrap();
+    let master_key = format!("master-key-{}", PLANTED_SECRET);
+    let options = Opt {
+        master_key: Some(Sensitive::new(master_key.clone())),
+        slow_query_log_path: Some(temp.path().join("slow-queries.log")),
+        // every search is slow.
+        slow_query_log_threshold_ms: 0,
+        ..default_settings(temp.path())
+    };
+    let mut server = Server::new_with_options(options).await.unwrap();
+    server.use_api_key(&master_key);
+
+    let index = server.index("products");
+    index
+        .add_documents(json!([{ "id": 1, "title": PLANTED_SECRET }]), Some("id"))
+        .await;
+    // fails with an error quoting the document.
+    index
+        .add_documents(json!([{ "title": PLANTED_SECRET }]), None)
+        .await;
+    // fails with an error quoting the url.
+    let url = format!("http://127.0.0.1:1/documents.json?token={}", PLANTED_SECRET);
+    index.fetch_documents(json!({ "url": url })).await;
+    index.wait_task(2).await;
+
+    let (response, code) = index.search_post(json!({ "q": "shoes" })).await;
+    assert_eq!(code, 200, "{}", response);
+
+    let req = test::TestRequest::post().uri("/admin/support-bundle");
+    let (archive, code) = server.service.request_raw(req).await;
+    assert_eq!(code, 200);
+
+    let files = unpack(&archive);
+    assert_eq!(
+        files.keys().collect::<Vec<_>>(),
+        [
+            "batches.ndjson",
+            "config.json",
+            "queue.json",
+            "slow-queries.ndjson",
+            "stats.json",
+            "tasks.ndjson",
+            "version.json",
+        ]
+    );
+    for (name, content) in &files {
+        assert!(
+            !content.contains(PLANTED_SECRET),
+            "{} contains the planted secret: {}",
+            name,
+            content
+        );
+    }
+
+    let tasks: Vec<Value> = files["tasks.ndjson"]
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(tasks.len(), 3);
+    assert_eq!(tasks[0]["status"], "failed", "{}", tasks[0]);
+    assert_eq!(tasks[0]["details"]["url"], "[redacted]");
+    assert!(tasks[0]["error"]["code"].is_string());
+    assert_eq!(tasks[1]["status"], "failed", "{}", tasks[1]);
+
+    let config: Value = serde_json::from_str(&files["config.json"]).unwrap();
+    assert_eq!(config["master_key"], "[redacted]");
+    let stats: Value = serde_json::from_str(&files["stats.json"]).unwrap();
+    assert_eq!(stats["indexes"]["products"]["numberOfDocuments"], 1);
+    let queue: Value = serde_json::from_str(&files["queue.json"]).unwrap();
+    assert_eq!(queue["lists"], json!([]));
+}
+
+#[actix_rt::test]
+async fn support_bundle_requires_the_support_bundle_action() {
+    let mut server = Server::new_auth().await;
+    server.use_admin_key("MASTER_KEY").await;
+    let (response, code) = server
+        .add_api_key(json!({
+            "indexes": ["*"],
+            "actions": ["stats.get", "config.get", "tasks.get"],
+            "expiresAt": null,
+        }))
+        .await;
+    assert_eq!(code, 201, "{}", response);
+
+    server.use_api_key(response["key"].as_str().unwrap());
+    let req = test::TestRequest::post().uri("/admin/support-bundle");
+    let (_, code) = server.service.request_raw(req).await;
+    assert_eq!(code, 403);
+}
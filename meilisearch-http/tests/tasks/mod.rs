@@ -1,7 +1,11 @@
+mod responses;
+
 use crate::common::Server;
+use actix_web::test;
 use serde_json::json;
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
+use tokio::time::{sleep, Duration};
 
 #[actix_rt::test]
 async fn error_get_unexisting_task_status() {
@@ -42,6 +46,21 @@ async fn get_task_status() {
     // TODO check resonse format, as per #48
 }
 
+#[actix_rt::test]
+async fn finished_tasks_show_the_engine_version() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.create(None).await;
+    index.wait_task(1).await;
+
+    for uid in [0, 1] {
+        let (response, code) = index.get_task(uid).await;
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(response["engineVersion"], env!("CARGO_PKG_VERSION"));
+    }
+}
+
 #[actix_rt::test]
 async fn list_tasks() {
     let server = Server::new().await;
@@ -59,6 +78,47 @@ async fn list_tasks() {
     assert_eq!(response["results"].as_array().unwrap().len(), 2);
 }
 
+fn streamed_uids(body: &[u8]) -> Vec<u64> {
+    serde_json::Deserializer::from_slice(body)
+        .into_iter::<serde_json::Value>()
+        .map(|task| task.unwrap()["uid"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn stream_tasks() {
+    let server = Server::new().await;
+    for uid in ["a", "b", "c", "d"] {
+        server.index(uid).create(None).await;
+    }
+    server.index("a").wait_task(3).await;
+
+    let req = test::TestRequest::get().uri("/tasks?stream=true");
+    let (body, code) = server.service.request_raw(req).await;
+    assert_eq!(code, 200);
+    assert_eq!(streamed_uids(&body), vec![3, 2, 1, 0]);
+
+    let req = test::TestRequest::get()
+        .uri("/tasks?limit=2&from=2&indexUid=a,b,c")
+        .insert_header(("Accept", "application/x-ndjson"));
+    let (body, code) = server.service.request_raw(req).await;
+    assert_eq!(code, 200);
+    assert_eq!(streamed_uids(&body), vec![2, 1]);
+}
+
+#[actix_rt::test]
+async fn error_list_too_many_tasks() {
+    let server = Server::new().await;
+
+    let (response, code) = server.service.get("/tasks?limit=100000").await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "bad_request");
+
+    let req = test::TestRequest::get().uri("/tasks?limit=100000&stream=true");
+    let (_, code) = server.service.request_raw(req).await;
+    assert_eq!(code, 200);
+}
+
 #[actix_rt::test]
 async fn list_tasks_with_star_filters() {
     let server = Server::new().await;
@@ -162,6 +222,36 @@ async fn list_tasks_type_filtered() {
     assert_eq!(response["results"].as_array().unwrap().len(), 2);
 }
 
+#[actix_rt::test]
+async fn list_task_types() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+    server.service.post("/dumps", json!(null)).await;
+    index.wait_task(1).await;
+
+    let (response, code) = server.service.get("/tasks/types").await;
+    assert_eq!(code, 200, "{}", response);
+    let types = response["results"].as_array().unwrap();
+    assert!(types.contains(&json!("dumpCreation")), "{}", response);
+
+    // every listed type is accepted by the filter, and is the type the tasks are shown with.
+    for type_ in types {
+        let url = format!("/tasks?type={}", type_.as_str().unwrap());
+        let (response, code) = server.service.get(url).await;
+        assert_eq!(code, 200, "{}", response);
+        for task in response["results"].as_array().unwrap() {
+            assert_eq!(&task["type"], type_, "{}", task);
+        }
+    }
+    let (response, _) = server.service.get("/tasks?type=dumpCreation").await;
+    assert_eq!(response["results"][0]["uid"], 1, "{}", response);
+
+    let (response, code) = server.service.get("/tasks?type=snapshotCreation").await;
+    assert_eq!(code, 400, "{}", response);
+}
+
 #[actix_rt::test]
 async fn list_tasks_status_and_type_filtered() {
     let server = Server::new().await;
@@ -231,3 +321,353 @@ async fn test_summarized_task_view() {
     let (response, _) = index.delete().await;
     assert_valid_summarized_task!(response, "indexDeletion", "test");
 }
+
+#[actix_rt::test]
+async fn tasks_of_deleted_index_are_marked() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+    index.delete().await;
+    index.wait_task(1).await;
+    // an index with the same uid is created again.
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_task(2).await;
+    server.service.post("/dumps", json!(null)).await;
+    index.wait_task(3).await;
+
+    let (response, code) = server.tasks().await;
+    assert_eq!(code, 200, "{}", response);
+    let deleted: Vec<_> = response["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| (task["uid"].clone(), task.get("indexDeleted").cloned()))
+        .collect();
+    assert_eq!(
+        deleted,
+        [
+            (json!(3), None),
+            (json!(2), Some(json!(false))),
+            (json!(1), Some(json!(true))),
+            (json!(0), Some(json!(true))),
+        ]
+    );
+
+    // the deletion was registered while the first index existed.
+    let (task, _) = server.service.get("/tasks/1").await;
+    assert!(task["indexUuid"].is_string(), "{}", task);
+    assert_eq!(task["indexDeleted"], true);
+    let (task, _) = server.service.get("/tasks/2").await;
+    assert!(task.get("indexUuid").is_none(), "{}", task);
+    assert_eq!(task["indexDeleted"], false);
+}
+
+#[actix_rt::test]
+async fn error_cancel_processed_task() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server.service.post("/tasks/0/cancel", json!(null)).await;
+
+    let expected_response = json!({
+        "message": "Task `0` cannot be canceled because it is already being processed or has been processed.",
+        "code": "task_not_cancelable",
+        "type": "invalid_request",
+        "link": "https://docs.meilisearch.com/errors#task_not_cancelable"
+    });
+
+    assert_eq!(response, expected_response);
+    assert_eq!(code, 409);
+}
+
+#[actix_rt::test]
+async fn error_cancel_unexisting_task() {
+    let server = Server::new().await;
+
+    let (response, code) = server.service.post("/tasks/1/cancel", json!(null)).await;
+
+    assert_eq!(response["code"], "task_not_found");
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn error_force_reset_without_processing_batch() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+
+    let (response, code) = server
+        .service
+        .post("/tasks/force-reset-processing", json!(null))
+        .await;
+
+    assert_eq!(response["message"], "No batch is being processed.");
+    assert_eq!(response["code"], "processing_batch_not_stuck");
+    assert_eq!(code, 409);
+
+    // the processed task is left untouched.
+    let (response, _) = index.get_task(0).await;
+    assert_eq!(response["status"], "succeeded");
+}
+
+#[actix_rt::test]
+async fn replayed_idempotency_key_returns_the_same_task() {
+    let server = Server::new().await;
+    let body = json!({ "uid": "test" });
+
+    let (response, code) = server
+        .service
+        .post_idempotent("/indexes", body.clone(), "create-test")
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    let task_uid = response["taskUid"].clone();
+
+    let (response, code) = server
+        .service
+        .post_idempotent("/indexes", body, "create-test")
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["uid"], task_uid);
+    assert_eq!(response["type"], "indexCreation");
+
+    let (response, code) = server.tasks().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["results"].as_array().unwrap().len(), 1);
+}
+
+#[actix_rt::test]
+async fn idempotency_keys_are_scoped_by_api_key() {
+    let mut server = Server::new_auth().await;
+    server.use_api_key("MASTER_KEY");
+    let (response, code) = server
+        .add_api_key(json!({
+            "indexes": ["*"],
+            "actions": ["indexes.create"],
+            "expiresAt": null,
+        }))
+        .await;
+    assert_eq!(code, 201, "{}", response);
+    let key = response["key"].as_str().unwrap().to_string();
+
+    let (response, code) = server
+        .service
+        .post_idempotent("/indexes", json!({ "uid": "test" }), "create-test")
+        .await;
+    assert_eq!(code, 202, "{}", response);
+
+    server.use_api_key(&key);
+    let (response, code) = server
+        .service
+        .post_idempotent("/indexes", json!({ "uid": "test" }), "create-test")
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["taskUid"], 1);
+}
+
+#[actix_rt::test]
+async fn error_invalid_idempotency_key() {
+    let server = Server::new().await;
+
+    let (response, code) = server
+        .service
+        .post_idempotent("/indexes", json!({ "uid": "test" }), "not a valid key")
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_idempotency_key");
+
+    let (response, _) = server.tasks().await;
+    assert!(response["results"].as_array().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn task_origin_is_only_shown_to_allowed_keys() {
+    let mut server = Server::new_auth().await;
+    server.service.options.trusted_proxy_header = Some("X-Forwarded-For".to_string());
+    server.use_api_key("MASTER_KEY");
+
+    let req = test::TestRequest::post()
+        .uri("/indexes")
+        .set_json(&json!({ "uid": "test" }))
+        .insert_header(("User-Agent", "origin-test/1.0"))
+        .insert_header(("X-Forwarded-For", "203.0.113.7, 10.0.0.1"));
+    let (response, code, _) = server.service.request(req).await;
+    assert_eq!(code, 202, "{}", response);
+
+    let (response, code) = server.service.get("/tasks/0").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["origin"],
+        json!({ "clientIp": "203.0.113.7", "userAgent": "origin-test/1.0" })
+    );
+
+    let (response, code) = server
+        .add_api_key(json!({
+            "indexes": ["*"],
+            "actions": ["tasks.get"],
+            "expiresAt": null,
+        }))
+        .await;
+    assert_eq!(code, 201, "{}", response);
+    let key = response["key"].as_str().unwrap().to_string();
+
+    let (response, code) = server
+        .add_api_key(json!({
+            "indexes": ["*"],
+            "actions": ["tasks.get", "tasks.readSensitive"],
+            "expiresAt": null,
+        }))
+        .await;
+    assert_eq!(code, 201, "{}", response);
+    let sensitive_key = response["key"].as_str().unwrap().to_string();
+
+    server.use_api_key(&key);
+    let (response, code) = server.service.get("/tasks/0").await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response.get("origin").is_none(), "{}", response);
+    let (response, code) = server.tasks().await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(
+        response["results"][0].get("origin").is_none(),
+        "{}",
+        response
+    );
+    // the fields can't show what the key isn't allowed to see.
+    let req = test::TestRequest::get().uri("/tasks?fields=uid,origin.clientIp");
+    let (response, code, headers) = server.service.request(req).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["results"][0], json!({ "uid": 0 }));
+    assert_eq!(headers["X-Meili-Unknown-Fields"], "origin.clientIp");
+
+    server.use_api_key(&sensitive_key);
+    let (response, code) = server.service.get("/tasks/0").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["origin"]["clientIp"], "203.0.113.7");
+}
+
+#[actix_rt::test]
+async fn list_tasks_with_fields() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }]), None)
+        .await;
+    index.wait_task(1).await;
+
+    let req = test::TestRequest::get()
+        .uri("/tasks?fields=uid,status,details.indexedDocuments,unknown,details.unknown&limit=1");
+    let (response, code, headers) = server.service.request(req).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["results"],
+        json!([{ "uid": 1, "status": "succeeded", "details": { "indexedDocuments": 2 } }])
+    );
+    // the pagination is left as it is.
+    assert_eq!(response["next"], 0);
+    assert_eq!(headers["X-Meili-Unknown-Fields"], "unknown,details.unknown");
+
+    // a field that one of the tasks has is not unknown.
+    let req = test::TestRequest::get().uri("/tasks?fields=uid,details.indexedDocuments");
+    let (response, _, headers) = server.service.request(req).await;
+    assert_eq!(response["results"][1], json!({ "uid": 0 }));
+    assert!(headers.get("X-Meili-Unknown-Fields").is_none());
+}
+
+#[actix_rt::test]
+async fn compact_tasks_database() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index.wait_task(0).await;
+    index.delete().await;
+    index.wait_task(1).await;
+
+    let (response, code) = server.service.post("/tasks/compact", json!(null)).await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["type"], "taskStoreCompaction");
+    let url = format!("/tasks/{}", response["taskUid"]);
+
+    // the tasks can't be read while the database is replaced, which is reported as temporary.
+    let mut task = None;
+    for _ in 0..100 {
+        let (response, code) = server.service.get(&url).await;
+        match code.as_u16() {
+            200 if response["status"] == "succeeded" || response["status"] == "failed" => {
+                task = Some(response);
+                break;
+            }
+            200 => (),
+            503 => assert_eq!(response["code"], "database_unavailable", "{}", response),
+            _ => panic!("unexpected response {}: {}", code, response),
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    let task = task.expect("the compaction never finished");
+    assert_eq!(task["status"], "succeeded", "{}", task);
+    assert!(task["details"]["reclaimedBytes"].is_u64(), "{}", task);
+
+    // the tasks registered before the compaction are still there, and new ones are registered.
+    let (response, code) = server.tasks().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["results"].as_array().unwrap().len(), 3);
+    let (response, code) = index.create(None).await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_task(3).await;
+    assert_eq!(response["status"], "succeeded", "{}", response);
+}
+
+#[actix_rt::test]
+async fn list_tasks_in_ascending_order() {
+    let server = Server::new().await;
+    for uid in ["a", "b", "c"] {
+        server.index(uid).create(None).await;
+    }
+    server.index("c").wait_task(2).await;
+
+    let uids = |response: &serde_json::Value| {
+        response["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|task| task["uid"].as_u64().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let (response, code) = server.service.get("/tasks?order=desc").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(uids(&response), vec![2, 1, 0]);
+
+    let (response, code) = server.service.get("/tasks?order=asc&limit=2").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(uids(&response), vec![0, 1]);
+    assert_eq!(response["from"], 0);
+    assert_eq!(response["next"], 2);
+
+    // the next page starts from the `next` task, in the same order.
+    let (response, code) = server.service.get("/tasks?order=asc&limit=2&from=2").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(uids(&response), vec![2]);
+    assert_eq!(response["next"], json!(null));
+
+    let (response, code) = server
+        .service
+        .get("/tasks?order=asc&limit=1&status=succeeded&indexUid=b,c")
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(uids(&response), vec![1]);
+
+    // all the tasks are processed, there is no pending task.
+    let (response, code) = server
+        .service
+        .get("/tasks?status=enqueued&order=asc&limit=1")
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(uids(&response).is_empty());
+
+    let (response, code) = server.service.get("/tasks?order=random").await;
+    assert_eq!(code, 400, "{}", response);
+}
@@ -0,0 +1,176 @@
+use actix_web::http::header::LOCATION;
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use crate::common::{default_settings, Server};
+
+const DOCUMENTS: &str =
+    "{\"id\": 1, \"title\": \"Carol\"}\n{\"id\": 2, \"title\": \"Wonder Woman\"}\n";
+
+/// Sends `req`, which must register a task, and checks the response follows the contract shared
+/// by all the routes registering a task. Returns the body of the response.
+async fn assert_task_accepted(server: &Server, req: TestRequest) -> Value {
+    let (response, code, headers) = server.service.request(req).await;
+    assert_eq!(code, 202, "{}", response);
+
+    let task_uid = response["taskUid"].as_u64().expect("missing taskUid");
+    assert_eq!(
+        headers.get(LOCATION).unwrap(),
+        format!("/tasks/{}", task_uid).as_str()
+    );
+
+    let mut fields: Vec<_> = response.as_object().unwrap().keys().cloned().collect();
+    fields.sort_unstable();
+    assert_eq!(
+        fields,
+        ["enqueuedAt", "indexUid", "status", "taskUid", "type"],
+        "{}",
+        response
+    );
+    assert_eq!(response["status"], "enqueued", "{}", response);
+
+    let (task, code) = server.service.get(format!("/tasks/{}", task_uid)).await;
+    assert_eq!(code, 200, "{}", task);
+    assert_eq!(task["indexUid"], response["indexUid"]);
+    assert_eq!(task["type"], response["type"]);
+    assert_eq!(task["enqueuedAt"], response["enqueuedAt"]);
+
+    response
+}
+
+#[actix_rt::test]
+async fn every_task_route_returns_a_summarized_task() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let response = assert_task_accepted(
+        &server,
+        TestRequest::post()
+            .uri("/indexes")
+            .set_json(json!({ "uid": "test" })),
+    )
+    .await;
+    assert_eq!(response["indexUid"], "test");
+    assert_eq!(response["type"], "indexCreation");
+    index.wait_task(0).await;
+
+    let (export, code) = server
+        .service
+        .get("/indexes/test/settings?format=export")
+        .await;
+    assert_eq!(code, 200, "{}", export);
+
+    let (upload, code) = index.create_upload(json!({})).await;
+    assert_eq!(code, 201, "{}", upload);
+    let upload_id = upload["uploadId"].as_str().unwrap().to_string();
+    let range = format!("bytes 0-{}/{}", DOCUMENTS.len() - 1, DOCUMENTS.len());
+    let (upload, code) = index
+        .append_to_upload(&upload_id, Some(&range), DOCUMENTS)
+        .await;
+    assert_eq!(code, 200, "{}", upload);
+
+    let (namespace, code) = server
+        .service
+        .post("/namespaces", json!({ "uid": "tenant" }))
+        .await;
+    assert_eq!(code, 201, "{}", namespace);
+
+    let documents = json!([{ "id": 1, "title": "Carol" }]);
+    let requests = [
+        TestRequest::patch()
+            .uri("/indexes/test")
+            .set_json(json!({ "primaryKey": "id" })),
+        TestRequest::post().uri("/indexes/test/check"),
+        TestRequest::post().uri("/indexes/test/rebuild"),
+        TestRequest::post()
+            .uri("/indexes/test/documents")
+            .set_json(&documents),
+        TestRequest::put()
+            .uri("/indexes/test/documents")
+            .set_json(&documents),
+        TestRequest::post()
+            .uri("/indexes/test/documents/fetch")
+            .set_json(json!({ "url": "http://127.0.0.1:1/documents.ndjson" })),
+        TestRequest::post().uri(&format!(
+            "/indexes/test/documents/uploads/{}/commit",
+            upload_id
+        )),
+        TestRequest::delete().uri("/indexes/test/documents/1"),
+        TestRequest::post()
+            .uri("/indexes/test/documents/delete-batch")
+            .set_json(json!([1, 2])),
+        TestRequest::delete().uri("/indexes/test/documents"),
+        TestRequest::patch()
+            .uri("/indexes/test/settings")
+            .set_json(json!({ "stopWords": ["the"] })),
+        TestRequest::put()
+            .uri("/indexes/test/settings/stop-words")
+            .set_json(json!(["a"])),
+        TestRequest::delete().uri("/indexes/test/settings/stop-words"),
+        TestRequest::put()
+            .uri("/indexes/test/settings/import")
+            .set_json(&export),
+        TestRequest::delete().uri("/indexes/test/settings"),
+        TestRequest::delete().uri("/indexes/test"),
+    ];
+    for req in requests {
+        let response = assert_task_accepted(&server, req).await;
+        assert_eq!(response["indexUid"], "test", "{}", response);
+    }
+
+    let response = assert_task_accepted(&server, TestRequest::post().uri("/dumps")).await;
+    assert_eq!(response["type"], "dumpCreation");
+    assert_eq!(response["indexUid"], Value::Null);
+
+    let response =
+        assert_task_accepted(&server, TestRequest::delete().uri("/namespaces/tenant")).await;
+    assert_eq!(response["type"], "namespaceDeletion");
+}
+
+#[actix_rt::test]
+async fn replayed_task_points_at_the_task() {
+    let server = Server::new().await;
+
+    let req = || {
+        TestRequest::post()
+            .uri("/indexes")
+            .insert_header(("x-idempotency-key", "create-test"))
+            .set_json(json!({ "uid": "test" }))
+    };
+    let response = assert_task_accepted(&server, req()).await;
+    let task_uid = response["taskUid"].clone();
+
+    let (response, code, headers) = server.service.request(req()).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["uid"], task_uid);
+    assert_eq!(
+        headers.get(LOCATION).unwrap(),
+        format!("/tasks/{}", task_uid).as_str()
+    );
+}
+
+#[actix_rt::test]
+async fn legacy_task_fields() {
+    let dir = TempDir::new().unwrap();
+    let mut options = default_settings(dir.path());
+    options.legacy_task_fields = true;
+    let server = Server::new_with_options(options).await.unwrap();
+
+    let (response, code, headers) = server
+        .service
+        .request(
+            TestRequest::post()
+                .uri("/indexes")
+                .set_json(json!({ "uid": "test" })),
+        )
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["taskUid"], 0);
+    assert_eq!(response["updateId"], 0);
+    assert_eq!(response["uid"], 0);
+    assert_eq!(response["indexUid"], "test");
+    assert_eq!(response["status"], "enqueued");
+    assert_eq!(response["type"], "indexCreation");
+    assert_eq!(headers.get(LOCATION).unwrap(), "/tasks/0");
+}
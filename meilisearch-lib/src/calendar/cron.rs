@@ -0,0 +1,384 @@
+//! The cron expressions of the calendars.
+//!
+//! An expression has the five fields of a crontab, separated by whitespace: the minute, the hour,
+//! the day of the month, the month and the day of the week. A field is a list of `*`, values and
+//! ranges separated by commas, each one with an optional `/step`. The months and the days of the
+//! week can also be named by their first three letters, and Sunday is both `0` and `7`. Like in
+//! cron, a day matches when either the day of the month or the day of the week matches, if both
+//! of them are restricted.
+
+use std::fmt;
+use std::str::FromStr;
+
+use time::{Date, PrimitiveDateTime, Time};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid cron expression `{expression}` at position {position}: {message}.")]
+pub struct CronError {
+    pub expression: String,
+    /// The position of the offending character in the expression, counted from 1.
+    pub position: usize,
+    pub message: String,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+struct FieldSpec {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    /// The names of the values, from `min` on.
+    names: &'static [&'static str],
+}
+
+const FIELDS: [FieldSpec; 5] = [
+    FieldSpec {
+        name: "minutes",
+        min: 0,
+        max: 59,
+        names: &[],
+    },
+    FieldSpec {
+        name: "hours",
+        min: 0,
+        max: 23,
+        names: &[],
+    },
+    FieldSpec {
+        name: "days of the month",
+        min: 1,
+        max: 31,
+        names: &[],
+    },
+    FieldSpec {
+        name: "months",
+        min: 1,
+        max: 12,
+        names: &MONTHS,
+    },
+    FieldSpec {
+        name: "days of the week",
+        min: 0,
+        max: 7,
+        names: &WEEKDAYS,
+    },
+];
+
+/// The values matched by a field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Field {
+    bits: u64,
+    /// Whether the field doesn't start with `*`, like in cron.
+    restricted: bool,
+}
+
+impl Field {
+    fn contains(&self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    expression: String,
+    minutes: Field,
+    hours: Field,
+    days: Field,
+    months: Field,
+    weekdays: Field,
+}
+
+impl CronExpression {
+    fn matches_date(&self, date: Date) -> bool {
+        if !self.months.contains(u8::from(date.month()) as u32) {
+            return false;
+        }
+        let day = self.days.contains(date.day() as u32);
+        let weekday = self
+            .weekdays
+            .contains(date.weekday().number_days_from_sunday() as u32);
+        match (self.days.restricted, self.weekdays.restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    /// The first local time matching the expression from the minute of `from` on, up to the day
+    /// of `until`.
+    pub fn next_match(
+        &self,
+        from: PrimitiveDateTime,
+        until: PrimitiveDateTime,
+    ) -> Option<PrimitiveDateTime> {
+        let mut date = from.date();
+        let (mut first_hour, mut first_minute) = (from.hour(), from.minute());
+        while date <= until.date() {
+            if self.matches_date(date) {
+                for hour in (first_hour..24).filter(|hour| self.hours.contains(*hour as u32)) {
+                    let first_minute = if hour == first_hour { first_minute } else { 0 };
+                    if let Some(minute) =
+                        (first_minute..60).find(|minute| self.minutes.contains(*minute as u32))
+                    {
+                        let time = Time::from_hms(hour, minute, 0).ok()?;
+                        return Some(PrimitiveDateTime::new(date, time));
+                    }
+                }
+            }
+            date = date.next_day()?;
+            first_hour = 0;
+            first_minute = 0;
+        }
+        None
+    }
+}
+
+impl FromStr for CronExpression {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |position: usize, message: String| CronError {
+            expression: s.to_string(),
+            position: position + 1,
+            message,
+        };
+
+        // the fields, with their position in the expression.
+        let mut fields = Vec::new();
+        let mut start = None;
+        for (i, c) in s.char_indices().chain(Some((s.len(), ' '))) {
+            match (c.is_whitespace(), start) {
+                (true, Some(from)) => {
+                    fields.push((from, &s[from..i]));
+                    start = None;
+                }
+                (false, None) => start = Some(i),
+                _ => (),
+            }
+        }
+        if fields.len() != FIELDS.len() {
+            let position = fields.get(FIELDS.len()).map_or(s.len(), |(from, _)| *from);
+            return Err(error(
+                position,
+                format!("expected 5 fields, found {}", fields.len()),
+            ));
+        }
+
+        let mut parsed = [Field::default(); 5];
+        for ((field, (offset, text)), spec) in parsed.iter_mut().zip(fields).zip(&FIELDS) {
+            *field =
+                parse_field(text, spec).map_err(|(at, message)| error(offset + at, message))?;
+        }
+        let [minutes, hours, days, months, mut weekdays] = parsed;
+        // Sunday is both 0 and 7.
+        if weekdays.contains(7) {
+            weekdays.bits |= 1;
+        }
+
+        Ok(Self {
+            expression: s.trim().to_string(),
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+        })
+    }
+}
+
+impl fmt::Display for CronExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.expression.fmt(f)
+    }
+}
+
+/// Parses a field, returning the offset of the error in the field and its message on failure.
+fn parse_field(text: &str, spec: &FieldSpec) -> Result<Field, (usize, String)> {
+    let mut field = Field::default();
+    let mut offset = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (part, None),
+        };
+        let (from, to) = match range.split_once('-') {
+            _ if range == "*" => (spec.min, spec.max),
+            Some((from, to)) => (
+                parse_value(from, offset, spec)?,
+                parse_value(to, offset + from.len() + 1, spec)?,
+            ),
+            // `5/15` starts at 5 and goes on until the end of the range of the field.
+            None if step.is_some() => (parse_value(range, offset, spec)?, spec.max),
+            None => {
+                let value = parse_value(range, offset, spec)?;
+                (value, value)
+            }
+        };
+        if from > to {
+            return Err((
+                offset,
+                format!("the range `{}` of the {} is reversed", range, spec.name),
+            ));
+        }
+        let step = match step {
+            Some(step) => step.parse().ok().filter(|step| *step > 0).ok_or_else(|| {
+                (
+                    offset + range.len() + 1,
+                    format!("`{}` is not a valid step", step),
+                )
+            })?,
+            None => 1,
+        };
+
+        for value in (from..=to).step_by(step) {
+            field.bits |= 1 << value;
+        }
+        offset += part.len() + 1;
+    }
+    field.restricted = !text.starts_with('*');
+    Ok(field)
+}
+
+fn parse_value(text: &str, offset: usize, spec: &FieldSpec) -> Result<u32, (usize, String)> {
+    if let Ok(value) = text.parse::<u32>() {
+        return match value {
+            value if (spec.min..=spec.max).contains(&value) => Ok(value),
+            value => Err((
+                offset,
+                format!(
+                    "{} is out of the range {}-{} of the {}",
+                    value, spec.min, spec.max, spec.name
+                ),
+            )),
+        };
+    }
+    match spec
+        .names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(text))
+    {
+        Some(position) => Ok(spec.min + position as u32),
+        None if text.is_empty() => Err((offset, format!("expected a value of the {}", spec.name))),
+        None => Err((
+            offset,
+            format!("`{}` is not a valid value of the {}", text, spec.name),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn parse(expression: &str) -> CronExpression {
+        expression.parse().unwrap()
+    }
+
+    fn error(expression: &str) -> (usize, String) {
+        let error = expression.parse::<CronExpression>().unwrap_err();
+        (error.position, error.message)
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            error("0 3 * *"),
+            (8, "expected 5 fields, found 4".to_string())
+        );
+        assert_eq!(
+            error("0 3 * * * *"),
+            (11, "expected 5 fields, found 6".to_string())
+        );
+        assert_eq!(
+            error("0 25 * * *"),
+            (3, "25 is out of the range 0-23 of the hours".to_string())
+        );
+        assert_eq!(
+            error("0 3 * * mon-fru"),
+            (
+                13,
+                "`fru` is not a valid value of the days of the week".to_string()
+            )
+        );
+        assert_eq!(
+            error("*/0 * * * *"),
+            (3, "`0` is not a valid step".to_string())
+        );
+        assert_eq!(
+            error("0 9-17,,20 * * *"),
+            (8, "expected a value of the hours".to_string())
+        );
+        assert_eq!(
+            error("0 17-9 * * *"),
+            (3, "the range `17-9` of the hours is reversed".to_string())
+        );
+        assert_eq!(
+            "0 0 1 jan-dex *"
+                .parse::<CronExpression>()
+                .unwrap_err()
+                .to_string(),
+            "Invalid cron expression `0 0 1 jan-dex *` at position 11: `dex` is not a valid value of the months."
+        );
+    }
+
+    #[test]
+    fn test_next_match() {
+        let until = datetime!(2030-01-01 0:00);
+        // every 15 minutes during the business hours of the weekdays.
+        let expression = parse("*/15 9-17 * * Mon-FRI");
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-03 17:46), until),
+            Some(datetime!(2022-06-06 9:00)),
+        );
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-06 9:00), until),
+            Some(datetime!(2022-06-06 9:00)),
+        );
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-06 9:01), until),
+            Some(datetime!(2022-06-06 9:15)),
+        );
+
+        // Sunday is also 7.
+        let expression = parse("30 3 * * 7");
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-01 0:00), until),
+            Some(datetime!(2022-06-05 3:30)),
+        );
+
+        // the first day of the month or any Friday.
+        let expression = parse("0 0 1 * 5");
+        assert_eq!(
+            expression.next_match(datetime!(2022-05-28 0:00), until),
+            Some(datetime!(2022-06-01 0:00)),
+        );
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-01 0:01), until),
+            Some(datetime!(2022-06-03 0:00)),
+        );
+
+        // a step from a value goes on until the end of the range.
+        let expression = parse("0 20/2 * * *");
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-01 21:00), until),
+            Some(datetime!(2022-06-01 22:00)),
+        );
+
+        // the 29th of February doesn't happen every year.
+        let expression = parse("0 0 29 feb *");
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-01 0:00), until),
+            Some(datetime!(2024-02-29 0:00)),
+        );
+        assert_eq!(
+            expression.next_match(datetime!(2022-06-01 0:00), datetime!(2023-06-01 0:00)),
+            None,
+        );
+    }
+}
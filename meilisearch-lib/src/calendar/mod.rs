@@ -0,0 +1,384 @@
+//! The calendars of the scheduled snapshots and dumps.
+//!
+//! A calendar is made of cron expressions evaluated in a time zone, and fires at every local time
+//! matching any of them. When the clocks are turned forward, some local times are skipped, and
+//! when they are turned back, some local times happen twice: the `DstPolicy` of the calendar
+//! decides whether they fire.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use parking_lot::Mutex;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, Time};
+use tokio::sync::RwLock;
+use tokio::time::sleep;
+
+pub use cron::{CronError, CronExpression};
+pub use tz::{LocalTime, TimeZone, TimeZoneError};
+
+use crate::dump;
+use crate::snapshot::SnapshotJob;
+use crate::tasks::task::TaskContent;
+use crate::tasks::{Job, Scheduler, TaskStore};
+
+mod cron;
+mod tz;
+
+/// How far the next fire time of an expression is searched for. It covers the 29th of February
+/// of the years following a century that isn't a leap year.
+const MAX_SEARCH_DAYS: i64 = 366 * 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DstPolicy {
+    /// The skipped local times don't fire, and the repeated ones only fire the first time they
+    /// happen.
+    Skip,
+    /// The skipped local times fire when the clocks are turned forward, and the repeated ones fire
+    /// both times they happen.
+    DoubleFire,
+}
+
+impl Default for DstPolicy {
+    fn default() -> Self {
+        Self::Skip
+    }
+}
+
+impl FromStr for DstPolicy {
+    type Err = CalendarError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "double-fire" => Ok(Self::DoubleFire),
+            _ => Err(CalendarError::InvalidDstPolicy(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalendarError {
+    #[error(transparent)]
+    Cron(#[from] CronError),
+    #[error(transparent)]
+    TimeZone(#[from] TimeZoneError),
+    #[error("`{0}` is not a valid DST policy, expected `skip` or `double-fire`.")]
+    InvalidDstPolicy(String),
+    #[error("The cron expression `{0}` never fires.")]
+    NeverFires(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    expressions: Vec<CronExpression>,
+    tz: TimeZone,
+    dst_policy: DstPolicy,
+}
+
+impl Calendar {
+    /// Parses the cron `expressions` of a calendar. An expression that never fires, like
+    /// `0 0 30 2 *`, is refused like an invalid one.
+    pub fn new(
+        expressions: &[impl AsRef<str>],
+        tz: TimeZone,
+        dst_policy: DstPolicy,
+    ) -> Result<Self, CalendarError> {
+        let expressions = expressions
+            .iter()
+            .map(|expression| expression.as_ref().parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        let calendar = Self {
+            expressions,
+            tz,
+            dst_policy,
+        };
+
+        let now = OffsetDateTime::now_utc();
+        if let Some(expression) = calendar
+            .expressions
+            .iter()
+            .find(|expression| calendar.next_fire_of(expression, now).is_none())
+        {
+            return Err(CalendarError::NeverFires(expression.to_string()));
+        }
+
+        Ok(calendar)
+    }
+
+    /// The first instant after `after` the calendar fires at.
+    pub fn next_fire_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.expressions
+            .iter()
+            .filter_map(|expression| self.next_fire_of(expression, after))
+            .min()
+    }
+
+    fn next_fire_of(
+        &self,
+        expression: &CronExpression,
+        after: OffsetDateTime,
+    ) -> Option<OffsetDateTime> {
+        // a local time happens at the latest when the local time is the smallest offset ahead of
+        // UTC: the local times before it all happened before `after`.
+        let earliest = after.to_offset(self.tz.min_offset());
+        let mut from = PrimitiveDateTime::new(
+            earliest.date(),
+            Time::from_hms(earliest.hour(), earliest.minute(), 0).ok()?,
+        );
+        let until = from + Duration::days(MAX_SEARCH_DAYS);
+
+        let mut next: Option<OffsetDateTime> = None;
+        while let Some(local) = expression.next_match(from, until) {
+            // likewise, a local time happens at the earliest when the local time is the largest
+            // offset ahead of UTC.
+            if next.map_or(false, |next| {
+                local.assume_offset(self.tz.max_offset()) > next
+            }) {
+                break;
+            }
+            let instants = match (self.tz.resolve(local), self.dst_policy) {
+                (LocalTime::Single(instant), _) => vec![instant],
+                (LocalTime::Repeated(first, _), DstPolicy::Skip) => vec![first],
+                (LocalTime::Repeated(first, second), DstPolicy::DoubleFire) => vec![first, second],
+                (LocalTime::Skipped(_), DstPolicy::Skip) => vec![],
+                (LocalTime::Skipped(at), DstPolicy::DoubleFire) => vec![at],
+            };
+            next = instants
+                .into_iter()
+                .filter(|instant| *instant > after)
+                .chain(next)
+                .min();
+            from = local + Duration::minutes(1);
+        }
+
+        next.map(|next| next.to_offset(time::UtcOffset::UTC))
+    }
+}
+
+/// The next instant a calendar fires at, shared by its service and the stats.
+#[derive(Debug, Clone, Default)]
+pub struct NextFire(Arc<Mutex<Option<OffsetDateTime>>>);
+
+impl NextFire {
+    pub fn get(&self) -> Option<OffsetDateTime> {
+        *self.0.lock()
+    }
+
+    fn set(&self, at: Option<OffsetDateTime>) {
+        *self.0.lock() = at;
+    }
+}
+
+/// The job scheduled by a calendar.
+pub enum ScheduledJob {
+    Snapshot(SnapshotJob),
+    Dump(TaskStore),
+}
+
+/// Schedules a job each time its calendar fires.
+pub struct CalendarService {
+    pub(crate) calendar: Calendar,
+    pub(crate) job: ScheduledJob,
+    pub(crate) next_fire: NextFire,
+    pub(crate) scheduler: Arc<RwLock<Scheduler>>,
+}
+
+impl CalendarService {
+    pub async fn run(self) {
+        let name = match self.job {
+            ScheduledJob::Snapshot(_) => "snapshot",
+            ScheduledJob::Dump(_) => "dump",
+        };
+        info!(
+            "The {} is scheduled in the time zone {}.",
+            name, self.calendar.tz
+        );
+
+        // the instant the calendar last fired at, in case the clock goes backward.
+        let mut last = OffsetDateTime::now_utc();
+        loop {
+            let now = OffsetDateTime::now_utc().max(last);
+            let at = match self.calendar.next_fire_after(now) {
+                Some(at) => at,
+                None => {
+                    warn!("The schedule of the {} doesn't fire anymore.", name);
+                    self.next_fire.set(None);
+                    return;
+                }
+            };
+            self.next_fire.set(Some(at));
+            let wait = at - OffsetDateTime::now_utc();
+            sleep(std::time::Duration::try_from(wait).unwrap_or_default()).await;
+            last = at;
+
+            match self.job {
+                // a rejected snapshot is logged by the scheduler, the next fire will retry.
+                ScheduledJob::Snapshot(ref job) => {
+                    let _ = self
+                        .scheduler
+                        .write()
+                        .await
+                        .schedule_job(Job::Snapshot(job.clone()));
+                }
+                ScheduledJob::Dump(ref task_store) => {
+                    let content = TaskContent::Dump {
+                        uid: dump::generate_uid(),
+                        include_pending_tasks: false,
+                    };
+                    match task_store
+                        .register_with_origin(content, None, None, None, None)
+                        .await
+                    {
+                        Ok(_) => self.scheduler.read().await.notify(),
+                        Err(e) => error!("Could not register the scheduled dump: {}", e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    const CENTRAL_EUROPE: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+
+    fn calendar(expressions: &[&str], tz: &str, dst_policy: DstPolicy) -> Calendar {
+        Calendar::new(expressions, tz.parse().unwrap(), dst_policy).unwrap()
+    }
+
+    /// The instants `calendar` fires at after `after`, up to `until`.
+    fn fires(
+        calendar: &Calendar,
+        mut after: OffsetDateTime,
+        until: OffsetDateTime,
+    ) -> Vec<OffsetDateTime> {
+        let mut fires = Vec::new();
+        while let Some(at) = calendar.next_fire_after(after).filter(|at| *at <= until) {
+            fires.push(at);
+            after = at;
+        }
+        fires
+    }
+
+    #[test]
+    fn test_next_fire_of_several_expressions() {
+        // every day at 03:00, and every hour during the business days.
+        let calendar = calendar(
+            &["0 3 * * *", "0 9-17 * * mon-fri"],
+            CENTRAL_EUROPE,
+            DstPolicy::Skip,
+        );
+        // friday at 15:30, in summer time.
+        assert_eq!(
+            calendar.next_fire_after(datetime!(2022-06-03 13:30 UTC)),
+            Some(datetime!(2022-06-03 14:00 UTC)),
+        );
+        // saturday at 03:00, after the last hour of friday.
+        assert_eq!(
+            calendar.next_fire_after(datetime!(2022-06-03 15:00 UTC)),
+            Some(datetime!(2022-06-04 1:00 UTC)),
+        );
+        // the instant a calendar fires at is not after itself.
+        assert_eq!(
+            calendar.next_fire_after(datetime!(2022-06-04 0:59:59 UTC)),
+            Some(datetime!(2022-06-04 1:00 UTC)),
+        );
+    }
+
+    #[test]
+    fn test_skipped_local_times() {
+        // 02:30 doesn't happen on the 27th of March 2022, the clocks go from 02:00 to 03:00.
+        let after = datetime!(2022-03-26 0:00 UTC);
+        let until = datetime!(2022-03-28 12:00 UTC);
+
+        let skip = calendar(&["30 2 * * *"], CENTRAL_EUROPE, DstPolicy::Skip);
+        assert_eq!(
+            fires(&skip, after, until),
+            vec![
+                datetime!(2022-03-26 1:30 UTC),
+                datetime!(2022-03-28 0:30 UTC)
+            ],
+        );
+
+        let double_fire = calendar(&["30 2 * * *"], CENTRAL_EUROPE, DstPolicy::DoubleFire);
+        assert_eq!(
+            fires(&double_fire, after, until),
+            vec![
+                datetime!(2022-03-26 1:30 UTC),
+                datetime!(2022-03-27 1:00 UTC),
+                datetime!(2022-03-28 0:30 UTC),
+            ],
+        );
+
+        // the skipped local times fire once, when the clocks are turned forward.
+        let double_fire = calendar(&["*/20 2 * * *"], CENTRAL_EUROPE, DstPolicy::DoubleFire);
+        assert_eq!(
+            fires(
+                &double_fire,
+                datetime!(2022-03-27 0:00 UTC),
+                datetime!(2022-03-27 12:00 UTC)
+            ),
+            vec![datetime!(2022-03-27 1:00 UTC)],
+        );
+    }
+
+    #[test]
+    fn test_repeated_local_times() {
+        // 02:30 happens twice on the 30th of October 2022, the clocks go from 03:00 to 02:00.
+        let after = datetime!(2022-10-29 12:00 UTC);
+        let until = datetime!(2022-10-30 12:00 UTC);
+
+        let skip = calendar(&["30 2 * * *"], CENTRAL_EUROPE, DstPolicy::Skip);
+        assert_eq!(
+            fires(&skip, after, until),
+            vec![datetime!(2022-10-30 0:30 UTC)],
+        );
+
+        let double_fire = calendar(&["30 2 * * *"], CENTRAL_EUROPE, DstPolicy::DoubleFire);
+        assert_eq!(
+            fires(&double_fire, after, until),
+            vec![
+                datetime!(2022-10-30 0:30 UTC),
+                datetime!(2022-10-30 1:30 UTC)
+            ],
+        );
+
+        // the second 02:30 is still found from between the two of them.
+        assert_eq!(
+            double_fire.next_fire_after(datetime!(2022-10-30 0:45 UTC)),
+            Some(datetime!(2022-10-30 1:30 UTC)),
+        );
+        assert_eq!(
+            skip.next_fire_after(datetime!(2022-10-30 0:45 UTC)),
+            Some(datetime!(2022-10-31 1:30 UTC)),
+        );
+    }
+
+    #[test]
+    fn test_invalid_calendars() {
+        let error = Calendar::new(
+            &["0 3 * * *", "0 24 * * *"],
+            TimeZone::utc(),
+            DstPolicy::Skip,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid cron expression `0 24 * * *` at position 3: 24 is out of the range 0-23 of the hours."
+        );
+
+        let error = Calendar::new(&["0 0 30 2 *"], TimeZone::utc(), DstPolicy::Skip).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The cron expression `0 0 30 2 *` never fires."
+        );
+
+        assert_eq!("skip".parse::<DstPolicy>().unwrap(), DstPolicy::Skip);
+        assert!("twice".parse::<DstPolicy>().is_err());
+    }
+}
@@ -0,0 +1,503 @@
+//! The time zones the calendars are evaluated in.
+//!
+//! A time zone is described by a POSIX `TZ` string, like `CET-1CEST,M3.5.0,M10.5.0/3` for Central
+//! Europe, which carries its daylight saving time rules instead of relying on a time zone
+//! database. `UTC` is a shorthand for `UTC0`. Like in the `TZ` variable, the offsets are counted
+//! west of Greenwich: `CET-1` is one hour ahead of UTC. A time zone with daylight saving time but
+//! without rules follows the rules of the United States.
+
+use std::fmt;
+use std::str::FromStr;
+
+use time::util::is_leap_year;
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid time zone `{tz}` at position {position}: {message}.")]
+pub struct TimeZoneError {
+    pub tz: String,
+    /// The position of the offending character in the time zone, counted from 1.
+    pub position: usize,
+    pub message: String,
+}
+
+/// The day of a transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransitionDay {
+    /// `Jn`: the day of the year, from 1 to 365, the 29th of February never being counted.
+    Julian(u16),
+    /// `n`: the day of the year, from 0 to 365.
+    Ordinal(u16),
+    /// `Mm.w.d`: the day `d` of the week, from Sunday, of the week `w` of the month `m`, the
+    /// fifth week being the last one of the month.
+    Weekday { month: Month, week: u8, weekday: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transition {
+    day: TransitionDay,
+    /// The local time of the transition, in seconds since the start of its day.
+    time: i64,
+}
+
+impl Transition {
+    /// The local date and time of the transition in `year`.
+    fn local(&self, year: i32) -> PrimitiveDateTime {
+        let date = match self.day {
+            TransitionDay::Julian(day) => {
+                let day = if is_leap_year(year) && day >= 60 {
+                    day + 1
+                } else {
+                    day
+                };
+                Date::from_ordinal_date(year, day)
+            }
+            TransitionDay::Ordinal(day) => {
+                Date::from_ordinal_date(year, (day + 1).min(time::util::days_in_year(year)))
+            }
+            TransitionDay::Weekday {
+                month,
+                week,
+                weekday,
+            } => Date::from_calendar_date(year, month, 1).and_then(|first| {
+                let first_weekday = first.weekday().number_days_from_sunday();
+                let mut day = 1 + (7 + weekday - first_weekday) % 7 + (week - 1) * 7;
+                while day > days_in_month(year, month) {
+                    day -= 7;
+                }
+                Date::from_calendar_date(year, month, day)
+            }),
+        };
+        // the days were checked when the time zone was parsed.
+        date.expect("invalid transition day").midnight() + Duration::seconds(self.time)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DaylightSaving {
+    offset: UtcOffset,
+    start: Transition,
+    end: Transition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeZone {
+    tz: String,
+    standard: UtcOffset,
+    daylight_saving: Option<DaylightSaving>,
+}
+
+/// The instants of a local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalTime {
+    Single(OffsetDateTime),
+    /// The local time happens twice, when the clocks are turned back.
+    Repeated(OffsetDateTime, OffsetDateTime),
+    /// The local time is skipped when the clocks are turned forward, at the given instant.
+    Skipped(OffsetDateTime),
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self {
+            tz: "UTC0".to_string(),
+            standard: UtcOffset::UTC,
+            daylight_saving: None,
+        }
+    }
+
+    fn offsets(&self) -> impl Iterator<Item = UtcOffset> + '_ {
+        std::iter::once(self.standard).chain(self.daylight_saving.as_ref().map(|dst| dst.offset))
+    }
+
+    /// The smallest offset of the time zone.
+    pub fn min_offset(&self) -> UtcOffset {
+        self.offsets().min_by_key(UtcOffset::whole_seconds).unwrap()
+    }
+
+    /// The largest offset of the time zone.
+    pub fn max_offset(&self) -> UtcOffset {
+        self.offsets().max_by_key(UtcOffset::whole_seconds).unwrap()
+    }
+
+    /// The instants the clocks are turned forward and back in `year`.
+    fn transitions(&self, year: i32) -> Option<(OffsetDateTime, OffsetDateTime)> {
+        let dst = self.daylight_saving.as_ref()?;
+        // the clocks show the standard time until the start of the daylight saving time.
+        let start = dst.start.local(year).assume_offset(self.standard);
+        let end = dst.end.local(year).assume_offset(dst.offset);
+        Some((start, end))
+    }
+
+    /// The offset of the local time at `instant`.
+    pub fn offset_at(&self, instant: OffsetDateTime) -> UtcOffset {
+        let (dst, (start, end)) = match self.daylight_saving {
+            Some(ref dst) => {
+                let year = instant.to_offset(self.standard).year();
+                (dst, self.transitions(year).unwrap())
+            }
+            None => return self.standard,
+        };
+        // in the southern hemisphere, the daylight saving time spans the new year.
+        let daylight_saving = if start < end {
+            start <= instant && instant < end
+        } else {
+            !(end <= instant && instant < start)
+        };
+        if daylight_saving {
+            dst.offset
+        } else {
+            self.standard
+        }
+    }
+
+    /// The local time at `instant`.
+    pub fn local(&self, instant: OffsetDateTime) -> PrimitiveDateTime {
+        let local = instant.to_offset(self.offset_at(instant));
+        PrimitiveDateTime::new(local.date(), local.time())
+    }
+
+    /// The instants of the local time `local`.
+    pub fn resolve(&self, local: PrimitiveDateTime) -> LocalTime {
+        let mut instants: Vec<_> = self
+            .offsets()
+            .map(|offset| local.assume_offset(offset))
+            .filter(|instant| self.offset_at(*instant) == instant.offset())
+            .collect();
+        instants.sort();
+        instants.dedup();
+        match instants[..] {
+            [instant] => LocalTime::Single(instant),
+            [first, second] => LocalTime::Repeated(first, second),
+            _ => LocalTime::Skipped(self.skipped_at(local)),
+        }
+    }
+
+    /// The instant the local time `local` is skipped at, when the clocks are turned forward.
+    fn skipped_at(&self, local: PrimitiveDateTime) -> OffsetDateTime {
+        let (dst, (start, end)) = match (
+            self.daylight_saving.as_ref(),
+            self.transitions(local.year()),
+        ) {
+            (Some(dst), Some(transitions)) => (dst, transitions),
+            _ => return local.assume_offset(self.standard),
+        };
+        [
+            (start, self.standard, dst.offset),
+            (end, dst.offset, self.standard),
+        ]
+        .into_iter()
+        .find(|(at, before, after)| {
+            let skipped_from = at.to_offset(*before);
+            let skipped_to = at.to_offset(*after);
+            PrimitiveDateTime::new(skipped_from.date(), skipped_from.time()) <= local
+                && local < PrimitiveDateTime::new(skipped_to.date(), skipped_to.time())
+        })
+        .map_or_else(|| local.assume_offset(self.standard), |(at, ..)| at)
+    }
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tz.fmt(f)
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = TimeZoneError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "UTC" {
+            return Ok(Self::utc());
+        }
+
+        let mut parser = Parser { s, position: 0 };
+        parser.name()?;
+        let standard = parser.offset()?;
+        let daylight_saving = match parser.peek() {
+            None => None,
+            Some(_) => {
+                parser.name()?;
+                let offset = match parser.peek() {
+                    Some(c) if c.is_ascii_digit() || c == b'+' || c == b'-' => parser.offset()?,
+                    // one hour ahead of the standard time by default.
+                    _ => UtcOffset::from_whole_seconds(standard.whole_seconds() + 3600)
+                        .map_err(|_| parser.error("the offset is out of range"))?,
+                };
+                let (start, end) = if parser.eat(b',') {
+                    let start = parser.transition()?;
+                    parser.expect(b',')?;
+                    (start, parser.transition()?)
+                } else {
+                    // the rules of the United States.
+                    let transition = |month, week| Transition {
+                        day: TransitionDay::Weekday {
+                            month,
+                            week,
+                            weekday: 0,
+                        },
+                        time: 7200,
+                    };
+                    (transition(Month::March, 2), transition(Month::November, 1))
+                };
+                Some(DaylightSaving { offset, start, end })
+            }
+        };
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected character"));
+        }
+
+        Ok(Self {
+            tz: s.to_string(),
+            standard,
+            daylight_saving,
+        })
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> TimeZoneError {
+        TimeZoneError {
+            tz: self.s.to_string(),
+            position: self.position + 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.position += 1;
+        }
+        eaten
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), TimeZoneError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", c as char)))
+        }
+    }
+
+    /// A name, of at least three letters or quoted between `<` and `>`.
+    fn name(&mut self) -> Result<(), TimeZoneError> {
+        let start = self.position;
+        let quoted = self.eat(b'<');
+        let is_name = |c: u8| match quoted {
+            true => c.is_ascii_alphanumeric() || c == b'+' || c == b'-',
+            false => c.is_ascii_alphabetic(),
+        };
+        while self.peek().map_or(false, is_name) {
+            self.position += 1;
+        }
+        let length = self.position - start - quoted as usize;
+        if quoted {
+            self.expect(b'>')?;
+        }
+        if length < 3 {
+            self.position = start;
+            return Err(self.error("expected a name of at least three letters"));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self, max: u32) -> Result<u32, TimeZoneError> {
+        let start = self.position;
+        let mut number = 0u32;
+        while let Some(digit @ b'0'..=b'9') = self.peek() {
+            number = number
+                .saturating_mul(10)
+                .saturating_add((digit - b'0') as u32);
+            self.position += 1;
+        }
+        if self.position == start {
+            return Err(self.error("expected a number"));
+        }
+        if number > max {
+            self.position = start;
+            return Err(self.error(&format!("{} is greater than {}", number, max)));
+        }
+        Ok(number)
+    }
+
+    /// A duration `[+|-]hh[:mm[:ss]]`, in seconds.
+    fn duration(&mut self, max_hours: u32) -> Result<i64, TimeZoneError> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+        let mut seconds = self.number(max_hours)? as i64 * 3600;
+        if self.eat(b':') {
+            seconds += self.number(59)? as i64 * 60;
+            if self.eat(b':') {
+                seconds += self.number(59)? as i64;
+            }
+        }
+        Ok(sign * seconds)
+    }
+
+    fn offset(&mut self) -> Result<UtcOffset, TimeZoneError> {
+        let start = self.position;
+        // the offsets of the `TZ` variable are counted west of Greenwich.
+        let seconds = -self.duration(24)?;
+        UtcOffset::from_whole_seconds(seconds as i32).map_err(|_| {
+            self.position = start;
+            self.error("the offset is out of range")
+        })
+    }
+
+    fn transition(&mut self) -> Result<Transition, TimeZoneError> {
+        let day = if self.eat(b'M') {
+            let start = self.position;
+            let month = self.number(12)?;
+            let month = Month::try_from(month as u8).map_err(|_| {
+                self.position = start;
+                self.error("expected a month from 1 to 12")
+            })?;
+            self.expect(b'.')?;
+            let start = self.position;
+            let week = self.number(5)?;
+            if week == 0 {
+                self.position = start;
+                return Err(self.error("expected a week from 1 to 5"));
+            }
+            self.expect(b'.')?;
+            let weekday = self.number(6)?;
+            TransitionDay::Weekday {
+                month,
+                week: week as u8,
+                weekday: weekday as u8,
+            }
+        } else if self.eat(b'J') {
+            let start = self.position;
+            match self.number(365)? {
+                0 => {
+                    self.position = start;
+                    return Err(self.error("expected a day from 1 to 365"));
+                }
+                day => TransitionDay::Julian(day as u16),
+            }
+        } else {
+            TransitionDay::Ordinal(self.number(365)? as u16)
+        };
+        // the transitions happen at 02:00 by default.
+        let time = if self.eat(b'/') {
+            self.duration(167)?
+        } else {
+            7200
+        };
+        Ok(Transition { day, time })
+    }
+}
+
+fn days_in_month(year: i32, month: Month) -> u8 {
+    match month {
+        Month::February if is_leap_year(year) => 29,
+        Month::February => 28,
+        Month::April | Month::June | Month::September | Month::November => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::{datetime, offset};
+
+    use super::*;
+
+    const CENTRAL_EUROPE: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |tz: &str| {
+            let error = tz.parse::<TimeZone>().unwrap_err();
+            (error.position, error.message)
+        };
+        assert_eq!(
+            error("CE-1"),
+            (1, "expected a name of at least three letters".to_string())
+        );
+        assert_eq!(error("CET"), (4, "expected a number".to_string()));
+        assert_eq!(
+            error("CET-1CEST,M13.5.0,M10.5.0/3"),
+            (12, "13 is greater than 12".to_string())
+        );
+        assert_eq!(
+            error("CET-1CEST,M3.5.0M10.5.0/3"),
+            (17, "expected `,`".to_string())
+        );
+        assert_eq!(
+            error("CET-1 "),
+            (6, "expected a name of at least three letters".to_string())
+        );
+        assert_eq!(
+            "CET-1CEST,M3.0.0,M10.5.0"
+                .parse::<TimeZone>()
+                .unwrap_err()
+                .to_string(),
+            "Invalid time zone `CET-1CEST,M3.0.0,M10.5.0` at position 14: expected a week from 1 to 5."
+        );
+    }
+
+    #[test]
+    fn test_offsets() {
+        let tz: TimeZone = CENTRAL_EUROPE.parse().unwrap();
+        assert_eq!(tz.min_offset(), offset!(+1));
+        assert_eq!(tz.max_offset(), offset!(+2));
+        // in 2022, the clocks were turned forward on the 27th of March at 01:00 UTC, and back on
+        // the 30th of October at 01:00 UTC.
+        assert_eq!(tz.offset_at(datetime!(2022-03-27 0:59 UTC)), offset!(+1));
+        assert_eq!(tz.offset_at(datetime!(2022-03-27 1:00 UTC)), offset!(+2));
+        assert_eq!(tz.offset_at(datetime!(2022-10-30 0:59 UTC)), offset!(+2));
+        assert_eq!(tz.offset_at(datetime!(2022-10-30 1:00 UTC)), offset!(+1));
+
+        // the daylight saving time of the southern hemisphere spans the new year.
+        let tz: TimeZone = "<+13>-13<+14>,M9.5.0/3,M4.1.0/4".parse().unwrap();
+        assert_eq!(tz.offset_at(datetime!(2022-01-01 0:00 UTC)), offset!(+14));
+        assert_eq!(tz.offset_at(datetime!(2022-06-01 0:00 UTC)), offset!(+13));
+
+        // the rules of the United States by default.
+        let tz: TimeZone = "EST5EDT".parse().unwrap();
+        assert_eq!(tz.offset_at(datetime!(2022-03-13 6:59 UTC)), offset!(-5));
+        assert_eq!(tz.offset_at(datetime!(2022-03-13 7:00 UTC)), offset!(-4));
+
+        let tz: TimeZone = "UTC".parse().unwrap();
+        assert_eq!(tz.offset_at(datetime!(2022-06-01 0:00 UTC)), UtcOffset::UTC);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let tz: TimeZone = CENTRAL_EUROPE.parse().unwrap();
+        assert_eq!(
+            tz.resolve(datetime!(2022-06-01 3:00)),
+            LocalTime::Single(datetime!(2022-06-01 1:00 UTC)),
+        );
+        assert_eq!(
+            tz.resolve(datetime!(2022-03-27 2:30)),
+            LocalTime::Skipped(datetime!(2022-03-27 1:00 UTC)),
+        );
+        assert_eq!(
+            tz.resolve(datetime!(2022-10-30 2:30)),
+            LocalTime::Repeated(
+                datetime!(2022-10-30 0:30 UTC),
+                datetime!(2022-10-30 1:30 UTC)
+            ),
+        );
+        assert_eq!(
+            tz.local(datetime!(2022-10-30 1:30 UTC)),
+            datetime!(2022-10-30 2:30)
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! The changefeed of the indexes, for the systems replicating their documents elsewhere.
+//!
+//! Once a batch changing the documents of an index is applied, a record listing the external ids
+//! of the added and deleted documents is appended to the log of its index, a file of JSON lines
+//! rotated by size. The ids of a huge batch are truncated, and the operations that don't know the
+//! documents they change, like the deletion of all the documents, are recorded as changing all of
+//! them. The records are read back from the batch a consumer stopped at.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::rotating_file::{rotated_path, RotatingFile};
+use crate::tasks::batch::BatchId;
+use crate::tasks::task::TaskId;
+
+/// The maximum number of document ids of a record, past which they are truncated.
+pub const MAX_CHANGE_RECORD_IDS: usize = 10_000;
+
+const CHANGES_DIR_NAME: &str = "changes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeOperation {
+    DocumentAddition,
+    DocumentDeletion,
+    ClearAll,
+    IndexDeletion,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeRecord {
+    pub batch_id: BatchId,
+    pub task_ids: Vec<TaskId>,
+    pub operation: ChangeOperation,
+    /// Whether the batch changed all the documents of the index, which are then not listed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub all_documents: bool,
+    /// The external ids of the changed documents, at most `MAX_CHANGE_RECORD_IDS` of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub document_ids: Vec<String>,
+    /// Whether some ids were left out of `document_ids`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub applied_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChangefeedConfig {
+    /// The size, in bytes, over which the log of an index is rotated.
+    pub max_file_size: u64,
+    /// The number of rotated log files kept for each index.
+    pub max_files: usize,
+}
+
+struct Logs {
+    path: PathBuf,
+    config: ChangefeedConfig,
+    /// The opened logs, by index uid.
+    files: Mutex<HashMap<String, RotatingFile>>,
+}
+
+/// The changefeed of the indexes, disabled by default.
+#[derive(Clone, Default)]
+pub struct Changefeed {
+    logs: Option<Arc<Logs>>,
+}
+
+impl Changefeed {
+    pub fn new(db_path: impl AsRef<Path>, config: ChangefeedConfig) -> Self {
+        Self {
+            logs: Some(Arc::new(Logs {
+                path: db_path.as_ref().join(CHANGES_DIR_NAME),
+                config,
+                files: Mutex::new(HashMap::new()),
+            })),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.logs.is_some()
+    }
+
+    /// Appends `record` to the log of `index_uid`.
+    pub fn record(&self, index_uid: &str, record: &ChangeRecord) -> io::Result<()> {
+        let logs = match self.logs {
+            Some(ref logs) => logs,
+            None => return Ok(()),
+        };
+        let mut files = logs.files.lock();
+        let file = match files.get_mut(index_uid) {
+            Some(file) => file,
+            None => {
+                let file = RotatingFile::new(
+                    logs.log_path(index_uid),
+                    logs.config.max_file_size,
+                    logs.config.max_files,
+                )?;
+                files.entry(index_uid.to_string()).or_insert(file)
+            }
+        };
+        file.write_json(record)
+    }
+
+    /// Reads at most `limit` records of `index_uid`, from the batch `from_batch` on, in the order
+    /// they were applied.
+    pub fn read(
+        &self,
+        index_uid: &str,
+        from_batch: BatchId,
+        limit: usize,
+    ) -> io::Result<Vec<ChangeRecord>> {
+        let logs = match self.logs {
+            Some(ref logs) => logs,
+            None => return Ok(Vec::new()),
+        };
+        let path = logs.log_path(index_uid);
+        // the oldest records are in the last rotated file.
+        let files = (1..=logs.config.max_files)
+            .rev()
+            .map(|n| rotated_path(&path, n))
+            .chain(Some(path.clone()));
+
+        let mut records = Vec::new();
+        for path in files {
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            for line in BufReader::new(file).lines() {
+                let record: ChangeRecord = serde_json::from_str(&line?)?;
+                if record.batch_id >= from_batch {
+                    records.push(record);
+                    if records.len() == limit {
+                        return Ok(records);
+                    }
+                }
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+impl Logs {
+    fn log_path(&self, index_uid: &str) -> PathBuf {
+        self.path.join(format!("{}.ndjson", index_uid))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(batch_id: BatchId, ids: &[&str]) -> ChangeRecord {
+        ChangeRecord {
+            batch_id,
+            task_ids: vec![batch_id],
+            operation: ChangeOperation::DocumentAddition,
+            all_documents: false,
+            document_ids: ids.iter().map(|id| id.to_string()).collect(),
+            truncated: false,
+            applied_at: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_read_across_rotations() {
+        let dir = tempfile::tempdir().unwrap();
+        let line_len = serde_json::to_vec(&record(0, &["a"])).unwrap().len() as u64 + 1;
+        let changefeed = Changefeed::new(
+            dir.path(),
+            ChangefeedConfig {
+                max_file_size: line_len * 2,
+                max_files: 2,
+            },
+        );
+
+        for batch_id in 0..7 {
+            changefeed
+                .record("movies", &record(batch_id, &["a"]))
+                .unwrap();
+        }
+        changefeed.record("books", &record(7, &["b"])).unwrap();
+
+        // the oldest records were rotated out.
+        let batches: Vec<_> = changefeed
+            .read("movies", 0, 100)
+            .unwrap()
+            .iter()
+            .map(|record| record.batch_id)
+            .collect();
+        assert_eq!(batches, vec![2, 3, 4, 5, 6]);
+
+        let records = changefeed.read("movies", 4, 2).unwrap();
+        assert_eq!(records, vec![record(4, &["a"]), record(5, &["a"])]);
+        assert_eq!(changefeed.read("books", 0, 100).unwrap().len(), 1);
+        assert!(changefeed.read("songs", 0, 100).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,46 @@
+//! The indexes that were closed.
+//!
+//! A closed index keeps its documents on disk, but its environment is closed and unmapped, so that
+//! an index that isn't used for a long time doesn't hold any memory. Its state is persisted in the
+//! metadata of the index; this set mirrors it in memory for the scheduler, which leaves the tasks
+//! of the closed indexes in the queue until they are opened again.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+#[derive(Clone, Default)]
+pub struct ClosedIndexes {
+    uids: Arc<RwLock<HashSet<String>>>,
+}
+
+impl ClosedIndexes {
+    pub fn new(uids: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            uids: Arc::new(RwLock::new(uids.into_iter().collect())),
+        }
+    }
+
+    pub fn contains(&self, index_uid: &str) -> bool {
+        self.uids.read().contains(index_uid)
+    }
+
+    pub fn insert(&self, index_uid: String) {
+        self.uids.write().insert(index_uid);
+    }
+
+    pub fn remove(&self, index_uid: &str) {
+        self.uids.write().remove(index_uid);
+    }
+
+    /// Replaces the closed indexes, once the metadata they mirror is replaced.
+    pub fn replace(&self, uids: impl IntoIterator<Item = String>) {
+        *self.uids.write() = uids.into_iter().collect();
+    }
+
+    /// The uids of the closed indexes, in no particular order.
+    pub fn uids(&self) -> Vec<String> {
+        self.uids.read().iter().cloned().collect()
+    }
+}
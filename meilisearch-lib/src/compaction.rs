@@ -0,0 +1,132 @@
+//! Compaction of the indexes.
+//!
+//! Document deletions are soft: the deleted documents are only marked as deleted, excluded from
+//! every result, and stay in the index until they are purged. Purging them is as expensive as a
+//! regular deletion, so it is deferred until the proportion of soft deleted documents of an index
+//! exceeds the compaction threshold. A compaction task is then registered for this index, and is
+//! processed by the scheduler like any other task.
+//!
+//! The document additions and editions don't purge the soft deleted documents: the internal ids
+//! of these documents stay reserved until the compaction, so that they are never reused. Only a
+//! soft deleted document added back is purged right before, as milli replaces it.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::{error, info};
+use parking_lot::Mutex;
+use tokio::sync::{mpsc, RwLock};
+
+use crate::index_resolver::IndexUid;
+use crate::tasks::task::TaskContent;
+use crate::tasks::{Scheduler, TaskStore};
+
+pub const DEFAULT_COMPACTION_THRESHOLD: f64 = 0.1;
+
+/// Handle to the compaction service. It does nothing if the compaction service is not running.
+#[derive(Clone, Default)]
+pub struct CompactionHandle {
+    sender: Option<mpsc::UnboundedSender<IndexUid>>,
+    threshold: f64,
+    /// The indexes for which a compaction task is already enqueued.
+    pending: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CompactionHandle {
+    pub fn new(threshold: f64) -> (Self, mpsc::UnboundedReceiver<IndexUid>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handle = Self {
+            sender: Some(sender),
+            threshold,
+            pending: Default::default(),
+        };
+
+        (handle, receiver)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Whether an index with this proportion of soft deleted documents must be compacted.
+    pub fn needs_compaction(&self, soft_deleted_ratio: f64) -> bool {
+        soft_deleted_ratio > self.threshold
+    }
+
+    /// Requests the registration of a compaction task for `index_uid`, unless one is already
+    /// enqueued.
+    pub fn schedule(&self, index_uid: IndexUid) {
+        if let Some(ref sender) = self.sender {
+            if self.pending.lock().insert(index_uid.to_string()) {
+                // the receiver is only dropped on shutdown.
+                let _ = sender.send(index_uid);
+            }
+        }
+    }
+
+    /// Must be called once the compaction task of `index_uid` is processed.
+    pub fn done(&self, index_uid: &str) {
+        self.pending.lock().remove(index_uid);
+    }
+}
+
+pub struct CompactionService {
+    pub(crate) task_store: TaskStore,
+    pub(crate) scheduler: Arc<RwLock<Scheduler>>,
+    pub(crate) handle: CompactionHandle,
+    pub(crate) receiver: mpsc::UnboundedReceiver<IndexUid>,
+}
+
+impl CompactionService {
+    pub async fn run(mut self) {
+        while let Some(index_uid) = self.receiver.recv().await {
+            let uid = index_uid.to_string();
+            let content = TaskContent::DocumentsCompaction { index_uid };
+            match self.task_store.register(content, None).await {
+                Ok(task) => {
+                    info!(
+                        "Registered compaction task {} for index `{}`.",
+                        task.id, uid
+                    );
+                    self.scheduler.read().await.notify();
+                }
+                Err(e) => {
+                    error!(
+                        "Could not register the compaction of index `{}`: {}",
+                        uid, e
+                    );
+                    self.handle.done(&uid);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compaction_is_scheduled_once() {
+        let (handle, mut receiver) = CompactionHandle::new(DEFAULT_COMPACTION_THRESHOLD);
+        assert!(!handle.needs_compaction(0.05));
+        assert!(handle.needs_compaction(0.2));
+
+        let uid = IndexUid::new("movies".to_string()).unwrap();
+        handle.schedule(uid.clone());
+        handle.schedule(uid.clone());
+        assert_eq!(receiver.try_recv().unwrap(), uid);
+        assert!(receiver.try_recv().is_err());
+
+        handle.done("movies");
+        handle.schedule(uid.clone());
+        assert_eq!(receiver.try_recv().unwrap(), uid);
+    }
+
+    #[test]
+    fn test_disabled_handle() {
+        let handle = CompactionHandle::default();
+        assert!(!handle.is_enabled());
+        handle.schedule(IndexUid::new("movies".to_string()).unwrap());
+    }
+}
@@ -5,8 +5,20 @@ use std::path::Path;
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use tar::{Archive, Builder};
 
+use crate::throttle::{IoThrottle, IoThrottleConfig, ThrottledWriter};
+
 pub fn to_tar_gz(src: impl AsRef<Path>, dest: impl AsRef<Path>) -> anyhow::Result<()> {
-    let mut f = File::create(dest)?;
+    let mut throttle = IoThrottle::new(IoThrottleConfig::default());
+    to_tar_gz_throttled(src, dest, &mut throttle)
+}
+
+/// Archives `src` into `dest`, the writes of the archive paced by `throttle`.
+pub fn to_tar_gz_throttled(
+    src: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    throttle: &mut IoThrottle,
+) -> anyhow::Result<()> {
+    let mut f = ThrottledWriter::new(File::create(dest)?, throttle);
     let gz_encoder = GzEncoder::new(&mut f, Compression::default());
     let mut tar_encoder = Builder::new(gz_encoder);
     tar_encoder.append_dir_all(".", src)?;
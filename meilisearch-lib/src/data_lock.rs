@@ -0,0 +1,217 @@
+//! The lock of a data directory.
+//!
+//! Two processes using the same data directory corrupt its environments. A process takes an
+//! exclusive lock on a file next to the data directory, named after it like the generations of a
+//! replica, for as long as it uses it: the server, along with the import of a dump or a snapshot
+//! it starts with, the loading of the snapshot of a primary, and the offline reindexing. The file
+//! is next to the data directory rather than in it, so that the snapshots, the dumps and the
+//! checks of an empty data directory don't see it.
+//!
+//! The lock is a `flock` on unix, and an exclusive opening of the file on windows, which the
+//! system releases when the process exits, even when it is killed. The process holding the lock
+//! records its PID and the time it took the lock in the file, to tell who holds it, and clears
+//! them when the lock is dropped. An owner still recorded while the lock is free was left by a
+//! process that is gone without releasing it: the stale lock is taken over.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// The process holding the lock, as recorded in the lock file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LockOwner {
+    pid: u32,
+    /// When the process took the lock.
+    #[serde(with = "time::serde::rfc3339")]
+    locked_at: OffsetDateTime,
+}
+
+impl LockOwner {
+    fn describe(&self) -> String {
+        let locked_at = self
+            .locked_at
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| self.locked_at.to_string());
+        format!("the process {} since {}", self.pid, locked_at)
+    }
+}
+
+/// The exclusive lock of a data directory, released when it is dropped.
+#[derive(Debug)]
+pub struct DataDirLock {
+    file: File,
+    path: PathBuf,
+}
+
+/// Returns the path of the lock file of the data directory `db_path`.
+fn lock_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.file_name().unwrap_or_default().to_owned();
+    name.push(".lock");
+    db_path.with_file_name(name)
+}
+
+impl DataDirLock {
+    /// Locks the data directory `db_path`, or fails right away if another process, or another
+    /// instance of this process, holds its lock.
+    pub fn acquire(db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let db_path = db_path.as_ref();
+        let path = lock_path(db_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = match open_locked(&path)
+            .with_context(|| format!("could not lock the data directory {:?}", db_path))?
+        {
+            Some(file) => file,
+            None => match read_owner(&path) {
+                Some(owner) => bail!(
+                    "the data directory {:?} is already in use by {}",
+                    db_path,
+                    owner.describe()
+                ),
+                None => bail!(
+                    "the data directory {:?} is already in use by another process",
+                    db_path
+                ),
+            },
+        };
+
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        if let Ok(owner) = serde_json::from_slice::<LockOwner>(&content) {
+            warn!(
+                "Taking over the stale lock of the data directory {:?}, left by {} which is gone.",
+                db_path,
+                owner.describe()
+            );
+        }
+        let owner = LockOwner {
+            pid: std::process::id(),
+            locked_at: OffsetDateTime::now_utc(),
+        };
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        serde_json::to_writer(&mut file, &owner)?;
+        file.flush()?;
+
+        Ok(Self { file, path })
+    }
+}
+
+impl Drop for DataDirLock {
+    fn drop(&mut self) {
+        // the lock itself is released with the file, the owner is cleared so that the lock isn't
+        // taken for a stale one.
+        if let Err(e) = self.file.set_len(0) {
+            warn!("Could not clear the lock file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Reads the owner recorded in the lock file, if there is one.
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    let content = fs::read(path).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Opens the lock file and locks it, returns `None` if it is locked already.
+#[cfg(unix)]
+fn open_locked(path: &Path) -> io::Result<Option<File>> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)?;
+    // SAFETY: the descriptor is valid as long as `file` lives, and `flock` doesn't keep it.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(Some(file));
+    }
+    let error = io::Error::last_os_error();
+    match error.kind() {
+        io::ErrorKind::WouldBlock => Ok(None),
+        _ => Err(error),
+    }
+}
+
+/// Opens the lock file and locks it, returns `None` if it is locked already.
+#[cfg(windows)]
+fn open_locked(path: &Path) -> io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    // the file is locked by opening it without sharing it.
+    match OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .share_mode(0)
+        .open(path)
+    {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lock_path() {
+        assert_eq!(
+            lock_path(Path::new("/var/lib/meili/data.ms")),
+            Path::new("/var/lib/meili/data.ms.lock")
+        );
+        assert_eq!(lock_path(Path::new("data.ms/")), Path::new("data.ms.lock"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exclusive_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("data.ms");
+
+        let lock = DataDirLock::acquire(&db_path).unwrap();
+        let error = DataDirLock::acquire(&db_path).unwrap_err().to_string();
+        assert!(
+            error.contains(&format!("in use by the process {}", std::process::id())),
+            "{}",
+            error
+        );
+
+        // the lock is released, and its owner cleared, once it is dropped.
+        drop(lock);
+        assert!(read_owner(&lock_path(&db_path)).is_none());
+        let _lock = DataDirLock::acquire(&db_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stale_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("data.ms");
+
+        // a process that is gone left its owner behind, without holding the lock anymore.
+        let stale = LockOwner {
+            pid: u32::MAX,
+            locked_at: OffsetDateTime::UNIX_EPOCH,
+        };
+        fs::write(lock_path(&db_path), serde_json::to_vec(&stale).unwrap()).unwrap();
+
+        let lock = DataDirLock::acquire(&db_path).unwrap();
+        let owner = read_owner(&lock_path(&db_path)).unwrap();
+        assert_eq!(owner.pid, std::process::id());
+        drop(lock);
+    }
+}
@@ -1,13 +1,26 @@
 use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Debug, Display};
 use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, Write};
 
 use meilisearch_error::{internal_error, Code, ErrorCode};
-use milli::documents::DocumentBatchBuilder;
+use milli::documents::{DocumentBatchBuilder, DocumentBatchReader};
+use milli::update::IndexDocumentsMethod;
+use serde_json::{Map, Value};
+
+pub use self::limits::{
+    check_limits, DocumentFields, DocumentLimits, DocumentOverLimit, FieldType, LimitExceeded,
+    PayloadLimits,
+};
+use self::sanitize::Sanitizer;
+pub use self::sanitize::{InvalidCharacter, InvalidCharacterKind, InvalidCharacters};
+
+mod limits;
+mod sanitize;
 
 type Result<T> = std::result::Result<T, DocumentFormatError>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum PayloadType {
     Ndjson,
     Json,
@@ -28,6 +41,8 @@ impl fmt::Display for PayloadType {
 pub enum DocumentFormatError {
     Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
     MalformedPayload(Box<milli::documents::Error>, PayloadType),
+    InvalidCharacter(InvalidCharacter, PayloadType),
+    DocumentOverLimit(DocumentOverLimit),
 }
 
 impl Display for DocumentFormatError {
@@ -52,11 +67,28 @@ impl Display for DocumentFormatError {
                 }
                 _ => write!(f, "The `{}` payload provided is malformed: `{}`.", b, me),
             },
+            Self::InvalidCharacter(c, b) => write!(
+                f,
+                "The `{}` payload provided is malformed: {}. Add `sanitize=true` to the query parameters to replace the invalid characters with U+FFFD.",
+                b, c
+            ),
+            Self::DocumentOverLimit(d) => write!(
+                f,
+                "The payload provided exceeds the limits on the documents: {}.",
+                d
+            ),
         }
     }
 }
 
-impl std::error::Error for DocumentFormatError {}
+impl std::error::Error for DocumentFormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Internal(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl From<(PayloadType, milli::documents::Error)> for DocumentFormatError {
     fn from((ty, error): (PayloadType, milli::documents::Error)) -> Self {
@@ -71,12 +103,18 @@ impl ErrorCode for DocumentFormatError {
     fn error_code(&self) -> Code {
         match self {
             DocumentFormatError::Internal(_) => Code::Internal,
-            DocumentFormatError::MalformedPayload(_, _) => Code::MalformedPayload,
+            DocumentFormatError::MalformedPayload(_, _)
+            | DocumentFormatError::InvalidCharacter(_, _) => Code::MalformedPayload,
+            DocumentFormatError::DocumentOverLimit(_) => Code::DocumentOverLimit,
         }
     }
 }
 
-internal_error!(DocumentFormatError: io::Error);
+internal_error!(
+    DocumentFormatError: io::Error,
+    serde_json::Error,
+    milli::documents::Error
+);
 
 /// reads csv from input and write an obkv batch to writer.
 pub fn read_csv(input: impl Read, writer: impl Write + Seek) -> Result<usize> {
@@ -126,3 +164,398 @@ pub fn read_json(input: impl Read, writer: impl Write + Seek) -> Result<usize> {
 
     Ok(count)
 }
+
+/// Reads a payload of `payload_type` from `input` like `read_json`, `read_ndjson` and `read_csv`,
+/// once its characters were checked. Returns the number of documents, and the number of
+/// characters that were replaced.
+pub fn read_payload(
+    payload_type: PayloadType,
+    input: impl Read,
+    writer: impl Write + Seek,
+    invalid_characters: InvalidCharacters,
+) -> Result<(usize, u64)> {
+    let mut sanitizer = Sanitizer::new(input, payload_type, invalid_characters);
+    let count = match payload_type {
+        PayloadType::Json => read_json(&mut sanitizer, writer),
+        PayloadType::Ndjson => read_ndjson(&mut sanitizer, writer),
+        PayloadType::Csv => read_csv(&mut sanitizer, writer),
+    };
+
+    match sanitizer.take_error() {
+        // the reading failed on the invalid character.
+        Some(invalid) => Err(DocumentFormatError::InvalidCharacter(invalid, payload_type)),
+        None => Ok((count?, sanitizer.replaced())),
+    }
+}
+
+/// Returns the name of the primary key milli infers from a batch: the first field whose name
+/// contains `id`.
+pub fn infer_primary_key(input: impl Read + Seek) -> Result<Option<String>> {
+    let reader = DocumentBatchReader::from_reader(input)?;
+    let mut fields: Vec<_> = reader.index().iter().collect();
+    fields.sort_by_key(|(id, _)| **id);
+
+    Ok(fields
+        .into_iter()
+        .map(|(_, name)| name)
+        .find(|name| name.to_lowercase().contains("id"))
+        .cloned())
+}
+
+/// Returns the primary key documents are added with: the primary key of their index, or else the
+/// one of the addition, or else the one milli infers from `first_payload`, the first payload of
+/// the addition, which is only read if needed.
+pub fn addition_primary_key<R, E>(
+    index_primary_key: Option<&str>,
+    primary_key: Option<String>,
+    first_payload: impl FnOnce() -> std::result::Result<Option<R>, E>,
+) -> std::result::Result<Option<String>, E>
+where
+    R: Read + Seek,
+    E: From<DocumentFormatError>,
+{
+    if let Some(primary_key) = index_primary_key {
+        return Ok(Some(primary_key.to_string()));
+    }
+    match primary_key {
+        Some(primary_key) => Ok(Some(primary_key)),
+        None => match first_payload()? {
+            Some(payload) => Ok(infer_primary_key(payload)?),
+            None => Ok(None),
+        },
+    }
+}
+
+/// Returns the primary key values that appear in several documents of a batch, with their
+/// number of occurrences.
+pub fn find_duplicates(
+    input: impl Read + Seek,
+    primary_key: &str,
+) -> Result<BTreeMap<String, usize>> {
+    Ok(count_ids(input, primary_key)?
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .collect())
+}
+
+/// Returns the number of occurrences of each primary key value of a batch.
+pub fn count_ids(input: impl Read + Seek, primary_key: &str) -> Result<HashMap<String, usize>> {
+    let mut reader = DocumentBatchReader::from_reader(input)?;
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    while let Some((index, document)) = reader.next_document_with_index()? {
+        let id = document
+            .iter()
+            .find(|(field_id, _)| index.name(*field_id).map(String::as_str) == Some(primary_key))
+            .and_then(|(_, value)| raw_document_id(value));
+        if let Some(id) = id {
+            *occurrences.entry(id).or_default() += 1;
+        }
+    }
+
+    Ok(occurrences)
+}
+
+/// Rewrites a batch so that each of the `duplicates` primary key values only appears once: the
+/// last occurrence wins when the documents are replaced, and the occurrences are merged in order
+/// when the documents are updated. The deduplicated documents are written after the other ones.
+///
+/// Returns the number of documents written.
+pub fn deduplicate(
+    input: impl Read + Seek,
+    writer: impl Write + Seek,
+    primary_key: &str,
+    duplicates: &BTreeMap<String, usize>,
+    method: IndexDocumentsMethod,
+) -> Result<usize> {
+    let mut reader = DocumentBatchReader::from_reader(input)?;
+    let mut builder = DocumentBatchBuilder::new(BufWriter::new(writer))?;
+    let mut deduplicated: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+    let mut buffer = Vec::new();
+
+    while let Some((index, document)) = reader.next_document_with_index()? {
+        let mut object = Map::new();
+        let mut id = None;
+        for (field_id, value) in document.iter() {
+            if let Some(name) = index.name(field_id) {
+                if name == primary_key {
+                    id = raw_document_id(value);
+                }
+                object.insert(name.to_string(), serde_json::from_slice(value)?);
+            }
+        }
+
+        match id.filter(|id| duplicates.contains_key(id)) {
+            Some(id) => match method {
+                IndexDocumentsMethod::ReplaceDocuments => {
+                    deduplicated.insert(id, object);
+                }
+                IndexDocumentsMethod::UpdateDocuments => {
+                    deduplicated.entry(id).or_default().extend(object);
+                }
+            },
+            None => {
+                buffer.clear();
+                serde_json::to_writer(&mut buffer, &object)?;
+                builder.extend_from_json(Cursor::new(&buffer))?;
+            }
+        }
+    }
+
+    for object in deduplicated.into_values() {
+        buffer.clear();
+        serde_json::to_writer(&mut buffer, &object)?;
+        builder.extend_from_json(Cursor::new(&buffer))?;
+    }
+
+    Ok(builder.finish()?)
+}
+
+/// Splits a batch into batches of at most `chunk_size` documents, written in order to `outputs`.
+/// There must be enough outputs for all the documents. Returns the number of documents written to
+/// each output.
+pub fn split(
+    input: impl Read + Seek,
+    chunk_size: usize,
+    outputs: impl IntoIterator<Item = impl Write + Seek>,
+) -> Result<Vec<usize>> {
+    let mut reader = DocumentBatchReader::from_reader(input)?;
+    let mut counts = Vec::new();
+    let mut buffer = Vec::new();
+
+    for output in outputs {
+        let mut builder = DocumentBatchBuilder::new(BufWriter::new(output))?;
+        for _ in 0..chunk_size {
+            let (index, document) = match reader.next_document_with_index()? {
+                Some(document) => document,
+                None => break,
+            };
+
+            let mut object = Map::new();
+            for (field_id, value) in document.iter() {
+                if let Some(name) = index.name(field_id) {
+                    object.insert(name.to_string(), serde_json::from_slice(value)?);
+                }
+            }
+
+            buffer.clear();
+            serde_json::to_writer(&mut buffer, &object)?;
+            builder.extend_from_json(Cursor::new(&buffer))?;
+        }
+        counts.push(builder.finish()?);
+    }
+
+    Ok(counts)
+}
+
+/// The external id of a document, from the value of its primary key, or `None` if milli refuses
+/// it: a document id is an integer, or a string only made of alphanumeric characters, hyphens and
+/// underscores once trimmed.
+pub fn document_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => {
+            let id = id.trim();
+            let valid = !id.is_empty()
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            valid.then(|| id.to_string())
+        }
+        Value::Number(id) if id.is_i64() => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// The external id of a document, from the raw value of its primary key. The values milli refuses
+/// as document ids are ignored.
+fn raw_document_id(value: &[u8]) -> Option<String> {
+    document_id(&serde_json::from_slice(value).ok()?)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn batch(documents: Value) -> Cursor<Vec<u8>> {
+        let mut batch = Cursor::new(Vec::new());
+        read_json(documents.to_string().as_bytes(), &mut batch).unwrap();
+        batch.set_position(0);
+        batch
+    }
+
+    fn documents(batch: Cursor<Vec<u8>>) -> Vec<Value> {
+        let mut reader = DocumentBatchReader::from_reader(batch).unwrap();
+        let mut documents = Vec::new();
+        while let Some((index, document)) = reader.next_document_with_index().unwrap() {
+            let object: Map<String, Value> = document
+                .iter()
+                .map(|(field_id, value)| {
+                    (
+                        index.name(field_id).unwrap().to_string(),
+                        serde_json::from_slice(value).unwrap(),
+                    )
+                })
+                .collect();
+            documents.push(Value::Object(object));
+        }
+        documents
+    }
+
+    fn deduplicated(documents_json: Value, method: IndexDocumentsMethod) -> Vec<Value> {
+        let duplicates = find_duplicates(batch(documents_json.clone()), "id").unwrap();
+        let mut output = Cursor::new(Vec::new());
+        deduplicate(
+            batch(documents_json),
+            &mut output,
+            "id",
+            &duplicates,
+            method,
+        )
+        .unwrap();
+        output.set_position(0);
+        documents(output)
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let documents = json!([
+            { "id": 1, "title": "a" },
+            { "id": "1", "title": "b" },
+            { "id": 2 },
+            { "id": 3 },
+            { "id": 3 },
+            { "id": 3 },
+            { "title": "no id" },
+        ]);
+        let duplicates = find_duplicates(batch(documents), "id").unwrap();
+        assert_eq!(
+            duplicates.into_iter().collect::<Vec<_>>(),
+            vec![("1".to_string(), 2), ("3".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_document_id() {
+        assert_eq!(
+            document_id(&json!(" movie-1_a ")).as_deref(),
+            Some("movie-1_a")
+        );
+        assert_eq!(document_id(&json!(-12)).as_deref(), Some("-12"));
+        assert_eq!(document_id(&json!("foo & bar")), None);
+        assert_eq!(document_id(&json!("  ")), None);
+        assert_eq!(document_id(&json!(1.5)), None);
+        assert_eq!(document_id(&json!(["1"])), None);
+    }
+
+    #[test]
+    fn test_infer_primary_key() {
+        let documents = json!([{ "title": "a", "movie_id": 1 }]);
+        assert_eq!(
+            infer_primary_key(batch(documents)).unwrap().as_deref(),
+            Some("movie_id")
+        );
+        let documents = json!([{ "title": "a" }]);
+        assert_eq!(infer_primary_key(batch(documents)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_deduplicate_replace_keeps_last_occurrence() {
+        let documents = json!([
+            { "id": 1, "title": "first", "genre": "drama" },
+            { "id": 2, "title": "other" },
+            { "id": 1, "title": "last" },
+        ]);
+        assert_eq!(
+            deduplicated(documents, IndexDocumentsMethod::ReplaceDocuments),
+            vec![
+                json!({ "id": 2, "title": "other" }),
+                json!({ "id": 1, "title": "last" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deduplicate_update_merges_occurrences() {
+        let documents = json!([
+            { "id": 1, "title": "first", "genre": "drama" },
+            { "id": 2, "title": "other" },
+            { "id": 1, "title": "last", "year": 2000 },
+        ]);
+        assert_eq!(
+            deduplicated(documents, IndexDocumentsMethod::UpdateDocuments),
+            vec![
+                json!({ "id": 2, "title": "other" }),
+                json!({ "id": 1, "title": "last", "genre": "drama", "year": 2000 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let documents = json!([
+            { "id": 1, "title": "a" },
+            { "id": 2 },
+            { "id": 3, "title": "c" },
+            { "id": 4 },
+            { "id": 5, "genre": "drama" },
+        ]);
+        let mut outputs = vec![Cursor::new(Vec::new()); 3];
+        let counts = split(batch(documents), 2, outputs.iter_mut()).unwrap();
+        assert_eq!(counts, vec![2, 2, 1]);
+
+        let chunks: Vec<_> = outputs
+            .into_iter()
+            .map(|mut output| {
+                output.set_position(0);
+                documents(output)
+            })
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![
+                vec![json!({ "id": 1, "title": "a" }), json!({ "id": 2 })],
+                vec![json!({ "id": 3, "title": "c" }), json!({ "id": 4 })],
+                vec![json!({ "id": 5, "genre": "drama" })],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_payload_checks_the_characters() {
+        let payload = br#"[{"id": 1, "title": "ok"}, {"id": 2, "title": "\ud800"}]"#;
+
+        let error = read_payload(
+            PayloadType::Json,
+            &payload[..],
+            Cursor::new(Vec::new()),
+            InvalidCharacters::Reject,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            DocumentFormatError::InvalidCharacter(
+                InvalidCharacter {
+                    document: Some(1),
+                    ..
+                },
+                _
+            )
+        ));
+
+        let mut output = Cursor::new(Vec::new());
+        let (count, replaced) = read_payload(
+            PayloadType::Json,
+            &payload[..],
+            &mut output,
+            InvalidCharacters::Replace,
+        )
+        .unwrap();
+        assert_eq!((count, replaced), (2, 1));
+        output.set_position(0);
+        assert_eq!(
+            documents(output)[1],
+            json!({ "id": 2, "title": "\u{FFFD}" })
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Limits on the documents of the payloads.
+//!
+//! A single document with thousands of fields bloats the fields ids map of its whole index, which
+//! slows down every search on it. The documents of a payload are checked once it is converted,
+//! before its update file is persisted: a document exceeding a hard limit refuses the payload,
+//! while the documents exceeding a soft limit are indexed and counted as warnings.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+
+use milli::documents::DocumentBatchReader;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{DocumentFormatError, Result};
+
+/// The field names are cut to this length in the errors.
+const MAX_FIELD_NAME_LEN: usize = 128;
+
+/// Limits on the documents of a payload. A limit left to `None` isn't checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DocumentLimits {
+    /// The maximum number of fields of a document, the nested fields included.
+    pub max_fields: Option<usize>,
+    /// The maximum length, in bytes, of the name of a field. The name of a nested field includes
+    /// the names of its parents, like `author.name`.
+    pub max_field_name_length: Option<usize>,
+    /// The maximum size, in bytes, of the field names and the JSON values of a document.
+    pub max_size: Option<u64>,
+}
+
+impl DocumentLimits {
+    fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the first limit `document` exceeds.
+    pub fn check(&self, document: &DocumentFields) -> Option<LimitExceeded> {
+        if let Some(max) = self.max_fields {
+            if document.names.len() > max {
+                return Some(LimitExceeded::Fields {
+                    count: document.names.len(),
+                    max,
+                });
+            }
+        }
+        if let Some(max) = self.max_field_name_length {
+            if let Some(name) = document.names.keys().find(|name| name.len() > max) {
+                return Some(LimitExceeded::FieldNameLength {
+                    field: cut(name),
+                    max,
+                });
+            }
+        }
+        if let Some(max) = self.max_size {
+            if document.size > max {
+                return Some(LimitExceeded::Size {
+                    size: document.size,
+                    max,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// The limits on the documents of the payloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PayloadLimits {
+    /// A document exceeding these limits refuses its payload.
+    pub hard: DocumentLimits,
+    /// The documents exceeding these limits are indexed, but counted as warnings.
+    pub soft: DocumentLimits,
+}
+
+impl PayloadLimits {
+    pub fn has_soft_limits(&self) -> bool {
+        !self.soft.is_unlimited()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Fields { count: usize, max: usize },
+    FieldNameLength { field: String, max: usize },
+    Size { size: u64, max: u64 },
+}
+
+/// The first document of a payload that exceeds a hard limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentOverLimit {
+    /// The position of the document in the payload, from 0.
+    pub document: usize,
+    pub limit: LimitExceeded,
+}
+
+impl fmt::Display for DocumentOverLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            LimitExceeded::Fields { count, max } => write!(
+                f,
+                "the document {} has {} fields, more than the maximum of {}",
+                self.document, count, max
+            ),
+            LimitExceeded::FieldNameLength { ref field, max } => write!(
+                f,
+                "the name of the field `{}` of the document {} is longer than the maximum of {} bytes",
+                field, self.document, max
+            ),
+            LimitExceeded::Size { size, max } => write!(
+                f,
+                "the document {} has a size of {} bytes, more than the maximum of {} bytes",
+                self.document, size, max
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl FieldType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Boolean,
+            Value::Number(_) => Self::Number,
+            Value::String(_) => Self::String,
+            Value::Array(_) => Self::Array,
+            Value::Object(_) => Self::Object,
+        }
+    }
+
+    /// The type of a JSON value of a batch, from its first byte.
+    fn of_raw(value: &[u8]) -> Self {
+        match value.first() {
+            Some(b'n') => Self::Null,
+            Some(b't' | b'f') => Self::Boolean,
+            Some(b'"') => Self::String,
+            Some(b'[') => Self::Array,
+            Some(b'{') => Self::Object,
+            _ => Self::Number,
+        }
+    }
+}
+
+/// The fields of a document, as counted by the limits.
+#[derive(Debug, Default)]
+pub struct DocumentFields {
+    /// The names of the fields, with the types of their values. A field nested in the objects of
+    /// an array may have several types.
+    pub names: BTreeMap<String, BTreeSet<FieldType>>,
+    /// The size of the field names and of the JSON values, in bytes.
+    pub size: u64,
+}
+
+impl DocumentFields {
+    /// Inserts the field `name`, of the JSON value `value` read from a batch, and the fields
+    /// nested in it.
+    pub fn insert(&mut self, name: &str, value: &[u8]) -> Result<()> {
+        self.size += (name.len() + value.len()) as u64;
+        // only the objects and the arrays can hold nested fields.
+        if let Some(b'{' | b'[') = value.first() {
+            self.insert_nested(name, &serde_json::from_slice(value)?);
+        }
+        self.insert_name(name.to_string(), FieldType::of_raw(value));
+        Ok(())
+    }
+
+    fn insert_name(&mut self, name: String, field_type: FieldType) {
+        self.names.entry(name).or_default().insert(field_type);
+    }
+
+    /// Inserts the fields nested in `value`, the value of the field `parent`. The objects of an
+    /// array share the same fields.
+    fn insert_nested(&mut self, parent: &str, value: &Value) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    let name = format!("{}.{}", parent, key);
+                    self.insert_nested(&name, value);
+                    self.insert_name(name, FieldType::of(value));
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    self.insert_nested(parent, value);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Checks the documents of the batch read from the start of `input` against `limits`. Returns the
+/// number of documents exceeding the soft limits, or an error naming the first document exceeding
+/// the hard limits.
+pub fn check_limits(mut input: impl Read + Seek, limits: &PayloadLimits) -> Result<u64> {
+    if *limits == PayloadLimits::default() {
+        return Ok(0);
+    }
+
+    input.seek(SeekFrom::Start(0))?;
+    let mut reader = DocumentBatchReader::from_reader(input)?;
+    let mut over_soft_limits = 0;
+    let mut position = 0;
+    while let Some((index, document)) = reader.next_document_with_index()? {
+        let mut fields = DocumentFields::default();
+        for (field_id, value) in document.iter() {
+            if let Some(name) = index.name(field_id) {
+                fields.insert(name, value)?;
+            }
+        }
+
+        if let Some(limit) = limits.hard.check(&fields) {
+            return Err(DocumentFormatError::DocumentOverLimit(DocumentOverLimit {
+                document: position,
+                limit,
+            }));
+        }
+        if limits.soft.check(&fields).is_some() {
+            over_soft_limits += 1;
+        }
+        position += 1;
+    }
+
+    Ok(over_soft_limits)
+}
+
+fn cut(name: &str) -> String {
+    match name.char_indices().nth(MAX_FIELD_NAME_LEN) {
+        Some((end, _)) => format!("{}…", &name[..end]),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::document_formats::read_json;
+
+    fn batch(documents: Value) -> Cursor<Vec<u8>> {
+        let mut batch = Cursor::new(Vec::new());
+        read_json(documents.to_string().as_bytes(), &mut batch).unwrap();
+        batch
+    }
+
+    #[test]
+    fn test_nested_fields_are_counted() {
+        let documents = json!([
+            { "id": 1, "title": "small" },
+            { "id": 2, "author": { "name": "a", "books": [{ "title": "b" }, { "title": "c" }] } },
+        ]);
+        let limits = PayloadLimits {
+            hard: DocumentLimits {
+                max_fields: Some(4),
+                ..Default::default()
+            },
+            soft: DocumentLimits {
+                max_fields: Some(2),
+                ..Default::default()
+            },
+        };
+        // `id`, `author`, `author.name`, `author.books` and `author.books.title`.
+        let error = check_limits(batch(documents.clone()), &limits).unwrap_err();
+        assert!(matches!(
+            error,
+            DocumentFormatError::DocumentOverLimit(DocumentOverLimit {
+                document: 1,
+                limit: LimitExceeded::Fields { count: 5, max: 4 },
+            })
+        ));
+
+        let limits = PayloadLimits {
+            hard: DocumentLimits::default(),
+            ..limits
+        };
+        assert_eq!(check_limits(batch(documents), &limits).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_field_name_length_and_size() {
+        let documents = json!([
+            { "id": 1, "title": "a title long enough" },
+            { "id": 2, "a_long_field_name": true },
+        ]);
+
+        let limits = PayloadLimits {
+            hard: DocumentLimits {
+                max_field_name_length: Some(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let error = check_limits(batch(documents.clone()), &limits).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The payload provided exceeds the limits on the documents: the name of the field `a_long_field_name` of the document 1 is longer than the maximum of 10 bytes."
+        );
+
+        // the first document has 29 bytes: `id`, `1`, `title` and `"a title long enough"`.
+        let limits = PayloadLimits {
+            soft: DocumentLimits {
+                max_size: Some(26),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(check_limits(batch(documents), &limits).unwrap(), 1);
+    }
+}
@@ -0,0 +1,759 @@
+//! Checks of the characters of the document payloads.
+//!
+//! serde_json accepts the unpaired UTF-16 surrogates of the `\u` escapes and the escaped control
+//! characters, and the csv reader accepts raw control characters, which then end up in the stored
+//! documents. A payload goes through a `Sanitizer` before it is read: the invalid characters are
+//! either refused, with the position of the first one, or replaced by U+FFFD.
+//!
+//! The sanitizer streams the payload through a byte state machine that only tracks the strings,
+//! the nesting of the containers and the field names of the documents: the clean payloads are
+//! merely copied.
+
+use std::fmt;
+use std::io::{self, Read};
+
+use super::PayloadType;
+
+/// The size of the chunks of the payload read at once.
+const READ_CHUNK: usize = 64 * 1024;
+/// The field names are cut to this length in the errors.
+const MAX_FIELD_NAME_LEN: usize = 128;
+/// U+FFFD, the replacement of the invalid characters in the raw text.
+const REPLACEMENT: &[u8] = "\u{FFFD}".as_bytes();
+/// U+FFFD, the replacement of the invalid characters in a JSON string.
+const ESCAPED_REPLACEMENT: &[u8] = b"\\ufffd";
+
+/// What happens to the invalid characters of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidCharacters {
+    /// The payload is refused.
+    Reject,
+    /// The invalid characters are replaced by U+FFFD.
+    Replace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidCharacterKind {
+    InvalidUtf8,
+    UnpairedSurrogate,
+    ControlCharacter,
+}
+
+/// The first invalid character of a payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCharacter {
+    pub kind: InvalidCharacterKind,
+    /// The position of the document in the payload, from 0. There is none for the characters
+    /// outside of the documents, like the header of a CSV payload.
+    pub document: Option<usize>,
+    /// The top-level field the character belongs to, if any.
+    pub field: Option<String>,
+}
+
+impl fmt::Display for InvalidCharacter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            InvalidCharacterKind::InvalidUtf8 => write!(f, "invalid UTF-8 sequence")?,
+            InvalidCharacterKind::UnpairedSurrogate => write!(f, "unpaired UTF-16 surrogate")?,
+            InvalidCharacterKind::ControlCharacter => write!(f, "control character")?,
+        }
+        match (self.document, &self.field) {
+            (Some(document), Some(field)) => {
+                write!(f, " in the field `{}` of the document {}", field, document)
+            }
+            (Some(document), None) => write!(f, " in the document {}", document),
+            (None, _) => write!(f, " outside of the documents"),
+        }
+    }
+}
+
+/// Where the characters of a payload are written, with the count of the replaced ones.
+struct Output<'a> {
+    buffer: &'a mut Vec<u8>,
+    mode: InvalidCharacters,
+    replaced: &'a mut u64,
+}
+
+impl Output<'_> {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Writes `replacement` in place of an invalid character, if they are replaced.
+    fn invalid(
+        &mut self,
+        kind: InvalidCharacterKind,
+        replacement: &[u8],
+    ) -> Result<(), InvalidCharacterKind> {
+        match self.mode {
+            InvalidCharacters::Reject => Err(kind),
+            InvalidCharacters::Replace => {
+                self.push(replacement);
+                *self.replaced += 1;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The format specific part of the checks, which only sees valid UTF-8 characters.
+trait Scanner {
+    /// Handles a character, `bytes` is its UTF-8 encoding.
+    fn char(&mut self, bytes: &[u8], out: &mut Output) -> Result<(), InvalidCharacterKind>;
+
+    /// Handles the end of the payload.
+    fn finish(&mut self, out: &mut Output);
+
+    /// The replacement of an invalid UTF-8 sequence at the current position.
+    fn replacement(&self) -> &'static [u8] {
+        REPLACEMENT
+    }
+
+    /// The document and the field of the current position.
+    fn location(&self) -> (Option<usize>, Option<String>);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonState {
+    Structure,
+    String,
+    /// In a backslash escape of a string, whose bytes are kept in `escape`.
+    Escape,
+}
+
+/// Checks the strings of a JSON or NDJSON payload. The documents are the top-level objects, or
+/// the objects of a top-level array.
+struct JsonScanner {
+    state: JsonState,
+    /// The containers the current position is in, `{` or `[`.
+    stack: Vec<u8>,
+    /// Whether the next string of the current object is a key.
+    expecting_key: bool,
+    /// Whether the current string is a key of a document.
+    in_field_name: bool,
+    field_name: Vec<u8>,
+    field: Option<String>,
+    documents: usize,
+    escape: Vec<u8>,
+}
+
+impl JsonScanner {
+    fn new() -> Self {
+        Self {
+            state: JsonState::Structure,
+            stack: Vec::new(),
+            expecting_key: false,
+            in_field_name: false,
+            field_name: Vec::new(),
+            field: None,
+            documents: 0,
+            escape: Vec::new(),
+        }
+    }
+
+    /// The depth of the documents: they are in a top-level array, or at the top-level.
+    fn document_depth(&self) -> usize {
+        usize::from(self.stack.first() == Some(&b'['))
+    }
+
+    fn structure(&mut self, byte: u8) {
+        match byte {
+            b'"' => {
+                self.state = JsonState::String;
+                self.in_field_name = self.expecting_key
+                    && self.stack.last() == Some(&b'{')
+                    && self.stack.len() == self.document_depth() + 1;
+                if self.in_field_name {
+                    self.field_name.clear();
+                    self.field = None;
+                }
+                self.expecting_key = false;
+            }
+            b'{' => {
+                if self.stack.len() == self.document_depth() {
+                    self.documents += 1;
+                    self.field = None;
+                }
+                self.stack.push(b'{');
+                self.expecting_key = true;
+            }
+            b'[' => {
+                self.stack.push(b'[');
+                self.expecting_key = false;
+            }
+            b'}' | b']' => {
+                self.stack.pop();
+                self.expecting_key = false;
+            }
+            b',' => self.expecting_key = self.stack.last() == Some(&b'{'),
+            _ => (),
+        }
+    }
+
+    fn string_char(&mut self, bytes: &[u8], out: &mut Output) -> Result<(), InvalidCharacterKind> {
+        match bytes {
+            [b'"'] => {
+                self.state = JsonState::Structure;
+                if self.in_field_name {
+                    self.field = Some(String::from_utf8_lossy(&self.field_name).into_owned());
+                    self.in_field_name = false;
+                }
+                out.push(bytes);
+            }
+            [b'\\'] => {
+                self.state = JsonState::Escape;
+                self.escape.clear();
+                self.escape.push(b'\\');
+            }
+            [b'\t'] => out.invalid(InvalidCharacterKind::ControlCharacter, b"\\t")?,
+            [b'\n'] => out.invalid(InvalidCharacterKind::ControlCharacter, b"\\n")?,
+            [b'\r'] => out.invalid(InvalidCharacterKind::ControlCharacter, b"\\r")?,
+            [byte] if is_control(*byte) => {
+                out.invalid(InvalidCharacterKind::ControlCharacter, ESCAPED_REPLACEMENT)?
+            }
+            _ => {
+                if self.in_field_name && self.field_name.len() < MAX_FIELD_NAME_LEN {
+                    self.field_name.extend_from_slice(bytes);
+                }
+                out.push(bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the pending escape as it is, it is not ours to check.
+    fn flush_escape(&mut self, out: &mut Output) {
+        self.state = JsonState::String;
+        if self.in_field_name && self.field_name.len() < MAX_FIELD_NAME_LEN {
+            self.field_name.extend_from_slice(&self.escape);
+        }
+        out.push(&self.escape);
+        self.escape.clear();
+    }
+
+    fn escape_char(&mut self, bytes: &[u8], out: &mut Output) -> Result<(), InvalidCharacterKind> {
+        let byte = match bytes {
+            [byte] => *byte,
+            // a malformed escape, that serde_json refuses.
+            _ => {
+                self.flush_escape(out);
+                return self.string_char(bytes, out);
+            }
+        };
+
+        match self.escape.len() {
+            1 => {
+                self.escape.push(byte);
+                match byte {
+                    b'u' => (),
+                    b'b' | b'f' => {
+                        self.escape.clear();
+                        self.state = JsonState::String;
+                        out.invalid(InvalidCharacterKind::ControlCharacter, ESCAPED_REPLACEMENT)?;
+                    }
+                    _ => self.flush_escape(out),
+                }
+            }
+            // the four hex digits of a `\u` escape.
+            2..=5 => {
+                self.escape.push(byte);
+                if !byte.is_ascii_hexdigit() {
+                    self.flush_escape(out);
+                } else if self.escape.len() == 6 {
+                    let code = code_unit(&self.escape[2..6]);
+                    match code {
+                        // a high surrogate must be followed by a low one.
+                        0xD800..=0xDBFF => (),
+                        0xDC00..=0xDFFF => {
+                            self.escape.clear();
+                            self.state = JsonState::String;
+                            out.invalid(
+                                InvalidCharacterKind::UnpairedSurrogate,
+                                ESCAPED_REPLACEMENT,
+                            )?;
+                        }
+                        code if code < 0x20 && ![0x09, 0x0A, 0x0D].contains(&code) => {
+                            self.escape.clear();
+                            self.state = JsonState::String;
+                            out.invalid(
+                                InvalidCharacterKind::ControlCharacter,
+                                ESCAPED_REPLACEMENT,
+                            )?;
+                        }
+                        0x7F => {
+                            self.escape.clear();
+                            self.state = JsonState::String;
+                            out.invalid(
+                                InvalidCharacterKind::ControlCharacter,
+                                ESCAPED_REPLACEMENT,
+                            )?;
+                        }
+                        _ => self.flush_escape(out),
+                    }
+                }
+            }
+            // the `\uDC00` that must follow a high surrogate.
+            len => {
+                let expected = match len {
+                    6 => byte == b'\\',
+                    7 => byte == b'u',
+                    _ => byte.is_ascii_hexdigit(),
+                };
+                if expected {
+                    self.escape.push(byte);
+                    if self.escape.len() < 12 {
+                        return Ok(());
+                    }
+                    if (0xDC00..=0xDFFF).contains(&code_unit(&self.escape[8..12])) {
+                        self.flush_escape(out);
+                        return Ok(());
+                    }
+                }
+
+                // the high surrogate is unpaired, what follows it is read again.
+                let mut rest = self.escape.split_off(6);
+                if !expected {
+                    rest.push(byte);
+                }
+                self.escape.clear();
+                self.state = JsonState::String;
+                out.invalid(InvalidCharacterKind::UnpairedSurrogate, ESCAPED_REPLACEMENT)?;
+                for byte in rest {
+                    self.char(&[byte], out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Scanner for JsonScanner {
+    fn char(&mut self, bytes: &[u8], out: &mut Output) -> Result<(), InvalidCharacterKind> {
+        match self.state {
+            JsonState::Structure => {
+                if let [byte] = bytes {
+                    self.structure(*byte);
+                }
+                out.push(bytes);
+                Ok(())
+            }
+            JsonState::String => self.string_char(bytes, out),
+            JsonState::Escape => self.escape_char(bytes, out),
+        }
+    }
+
+    fn finish(&mut self, out: &mut Output) {
+        // the payload is truncated, serde_json refuses it.
+        if self.state == JsonState::Escape {
+            self.flush_escape(out);
+        }
+    }
+
+    fn replacement(&self) -> &'static [u8] {
+        match self.state {
+            JsonState::Structure => REPLACEMENT,
+            JsonState::String | JsonState::Escape => ESCAPED_REPLACEMENT,
+        }
+    }
+
+    fn location(&self) -> (Option<usize>, Option<String>) {
+        if self.stack.len() > self.document_depth() {
+            (self.documents.checked_sub(1), self.field.clone())
+        } else {
+            (None, None)
+        }
+    }
+}
+
+/// Checks the fields of a CSV payload. The first record is the header, the following ones are
+/// the documents.
+#[derive(Default)]
+struct CsvScanner {
+    in_quotes: bool,
+    after_cr: bool,
+    record: usize,
+    column: usize,
+    header: Vec<String>,
+    cell: Vec<u8>,
+}
+
+impl CsvScanner {
+    fn end_cell(&mut self) {
+        if self.record == 0 {
+            let name = String::from_utf8_lossy(&self.cell);
+            // the type of the column follows its name: `price:number`.
+            let name = match name.rsplit_once(':') {
+                Some((name, "string" | "number")) => name,
+                _ => &name,
+            };
+            self.header.push(name.to_string());
+            self.cell.clear();
+        }
+        self.column += 1;
+    }
+
+    fn end_record(&mut self) {
+        self.end_cell();
+        self.record += 1;
+        self.column = 0;
+    }
+}
+
+impl Scanner for CsvScanner {
+    fn char(&mut self, bytes: &[u8], out: &mut Output) -> Result<(), InvalidCharacterKind> {
+        let after_cr = std::mem::take(&mut self.after_cr);
+        match bytes {
+            [b'"'] => self.in_quotes = !self.in_quotes,
+            [b','] if !self.in_quotes => self.end_cell(),
+            [b'\n'] if !self.in_quotes => {
+                if !after_cr {
+                    self.end_record();
+                }
+            }
+            [b'\r'] if !self.in_quotes => {
+                self.end_record();
+                self.after_cr = true;
+            }
+            [b'\t' | b'\n' | b'\r'] => (),
+            [byte] if is_control(*byte) => {
+                return out.invalid(InvalidCharacterKind::ControlCharacter, REPLACEMENT);
+            }
+            _ => {
+                if self.record == 0 && self.cell.len() < MAX_FIELD_NAME_LEN {
+                    self.cell.extend_from_slice(bytes);
+                }
+            }
+        }
+        out.push(bytes);
+        Ok(())
+    }
+
+    fn finish(&mut self, _out: &mut Output) {}
+
+    fn location(&self) -> (Option<usize>, Option<String>) {
+        match self.record {
+            0 => (None, None),
+            record => (Some(record - 1), self.header.get(self.column).cloned()),
+        }
+    }
+}
+
+fn is_control(byte: u8) -> bool {
+    byte < 0x20 || byte == 0x7F
+}
+
+fn code_unit(hex: &[u8]) -> u32 {
+    hex.iter().fold(0, |code, digit| {
+        code * 16 + (*digit as char).to_digit(16).unwrap_or(0)
+    })
+}
+
+/// The UTF-8 sequence being read.
+#[derive(Default)]
+struct Utf8Sequence {
+    bytes: [u8; 4],
+    len: usize,
+    expected: usize,
+}
+
+impl Utf8Sequence {
+    /// The bounds of the next continuation byte, which exclude the overlong encodings, the
+    /// surrogates and the code points above U+10FFFF.
+    fn continuation_bounds(&self) -> (u8, u8) {
+        match (self.len, self.bytes[0]) {
+            (1, 0xE0) => (0xA0, 0xBF),
+            (1, 0xED) => (0x80, 0x9F),
+            (1, 0xF0) => (0x90, 0xBF),
+            (1, 0xF4) => (0x80, 0x8F),
+            _ => (0x80, 0xBF),
+        }
+    }
+}
+
+/// A reader checking the characters of the payload it reads from.
+pub struct Sanitizer<R> {
+    inner: R,
+    mode: InvalidCharacters,
+    scanner: Box<dyn Scanner + Send>,
+    sequence: Utf8Sequence,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+    replaced: u64,
+    error: Option<InvalidCharacter>,
+    finished: bool,
+}
+
+impl<R: Read> Sanitizer<R> {
+    pub fn new(inner: R, payload_type: PayloadType, mode: InvalidCharacters) -> Self {
+        let scanner: Box<dyn Scanner + Send> = match payload_type {
+            PayloadType::Json | PayloadType::Ndjson => Box::new(JsonScanner::new()),
+            PayloadType::Csv => Box::new(CsvScanner::default()),
+        };
+        Self {
+            inner,
+            mode,
+            scanner,
+            sequence: Utf8Sequence::default(),
+            input: vec![0; READ_CHUNK],
+            output: Vec::new(),
+            position: 0,
+            replaced: 0,
+            error: None,
+            finished: false,
+        }
+    }
+
+    /// The number of characters that were replaced so far.
+    pub fn replaced(&self) -> u64 {
+        self.replaced
+    }
+
+    /// The invalid character that stopped the reading, if any.
+    pub fn take_error(&mut self) -> Option<InvalidCharacter> {
+        self.error.take()
+    }
+
+    fn byte(&mut self, byte: u8) -> Result<(), InvalidCharacterKind> {
+        let mut out = Output {
+            buffer: &mut self.output,
+            mode: self.mode,
+            replaced: &mut self.replaced,
+        };
+
+        if self.sequence.expected > 0 {
+            let (low, high) = self.sequence.continuation_bounds();
+            if (low..=high).contains(&byte) {
+                self.sequence.bytes[self.sequence.len] = byte;
+                self.sequence.len += 1;
+                if self.sequence.len == self.sequence.expected {
+                    let bytes = self.sequence.bytes;
+                    let len = self.sequence.len;
+                    self.sequence = Utf8Sequence::default();
+                    self.scanner.char(&bytes[..len], &mut out)?;
+                }
+                return Ok(());
+            }
+            // the sequence is truncated, the byte starts the next character.
+            self.sequence = Utf8Sequence::default();
+            out.invalid(
+                InvalidCharacterKind::InvalidUtf8,
+                self.scanner.replacement(),
+            )?;
+        }
+
+        let expected = match byte {
+            0x00..=0x7F => return self.scanner.char(&[byte], &mut out),
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => {
+                return out.invalid(
+                    InvalidCharacterKind::InvalidUtf8,
+                    self.scanner.replacement(),
+                )
+            }
+        };
+        self.sequence.bytes[0] = byte;
+        self.sequence.len = 1;
+        self.sequence.expected = expected;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), InvalidCharacterKind> {
+        let mut out = Output {
+            buffer: &mut self.output,
+            mode: self.mode,
+            replaced: &mut self.replaced,
+        };
+        if self.sequence.expected > 0 {
+            self.sequence = Utf8Sequence::default();
+            out.invalid(
+                InvalidCharacterKind::InvalidUtf8,
+                self.scanner.replacement(),
+            )?;
+        }
+        self.scanner.finish(&mut out);
+        Ok(())
+    }
+
+    fn fail(&mut self, kind: InvalidCharacterKind) -> io::Error {
+        let (document, field) = self.scanner.location();
+        let error = InvalidCharacter {
+            kind,
+            document,
+            field,
+        };
+        let e = io::Error::new(io::ErrorKind::InvalidData, error.to_string());
+        self.error = Some(error);
+        self.output.clear();
+        self.finished = true;
+        e
+    }
+
+    /// Fills the output with the next checked chunk of the payload, if any.
+    fn fill(&mut self) -> io::Result<()> {
+        self.output.clear();
+        self.position = 0;
+        while self.output.is_empty() && !self.finished {
+            let read = self.inner.read(&mut self.input)?;
+            if read == 0 {
+                self.finished = true;
+                if let Err(kind) = self.finish() {
+                    return Err(self.fail(kind));
+                }
+                break;
+            }
+
+            for i in 0..read {
+                if let Err(kind) = self.byte(self.input[i]) {
+                    return Err(self.fail(kind));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Sanitizer<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.output.len() {
+            if self.error.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid character",
+                ));
+            }
+            self.fill()?;
+        }
+
+        let available = &self.output[self.position..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sanitize(
+        payload: &[u8],
+        payload_type: PayloadType,
+        mode: InvalidCharacters,
+    ) -> Result<(Vec<u8>, u64), InvalidCharacter> {
+        let mut sanitizer = Sanitizer::new(payload, payload_type, mode);
+        let mut output = Vec::new();
+        match sanitizer.read_to_end(&mut output) {
+            Ok(_) => Ok((output, sanitizer.replaced())),
+            Err(_) => Err(sanitizer.take_error().unwrap()),
+        }
+    }
+
+    fn rejected(payload: &[u8], payload_type: PayloadType) -> InvalidCharacter {
+        sanitize(payload, payload_type, InvalidCharacters::Reject).unwrap_err()
+    }
+
+    fn replaced(payload: &[u8], payload_type: PayloadType) -> (String, u64) {
+        let (output, count) = sanitize(payload, payload_type, InvalidCharacters::Replace).unwrap();
+        (String::from_utf8(output).unwrap(), count)
+    }
+
+    #[test]
+    fn test_clean_payloads_are_copied() {
+        let payloads: [(&[u8], PayloadType); 3] = [
+            (
+                r#"[{"id": 1, "title": "caf\u00e9 \"crème\" \ud83d\ude00 😀", "tags": ["a\nb"]}]"#
+                    .as_bytes(),
+                PayloadType::Json,
+            ),
+            (
+                b"{\"id\": 1}\n{\"id\": 2, \"t\": \"\\t\"}\n",
+                PayloadType::Ndjson,
+            ),
+            (
+                "id,title\r\n1,\"multi\nline, \"\"quoted\"\"\"\n2,été\n".as_bytes(),
+                PayloadType::Csv,
+            ),
+        ];
+        for (payload, payload_type) in payloads {
+            let (output, count) = sanitize(payload, payload_type, InvalidCharacters::Reject)
+                .unwrap_or_else(|e| panic!("{}", e));
+            assert_eq!(output, payload);
+            assert_eq!(count, 0);
+        }
+    }
+
+    #[test]
+    fn test_json_invalid_characters_are_located() {
+        let error = rejected(
+            br#"[{"id": 1, "title": "ok"}, {"id": 2, "nested": {"a": "b"}, "title": "\ud800 alone"}]"#,
+            PayloadType::Json,
+        );
+        assert_eq!(error.kind, InvalidCharacterKind::UnpairedSurrogate);
+        assert_eq!(error.document, Some(1));
+        assert_eq!(error.field.as_deref(), Some("title"));
+
+        let error = rejected(
+            b"{\"id\": 1}\n{\"id\": 2}\n{\"id\": 3, \"text\": \"bell \x07\"}\n",
+            PayloadType::Ndjson,
+        );
+        assert_eq!(error.kind, InvalidCharacterKind::ControlCharacter);
+        assert_eq!(error.document, Some(2));
+        assert_eq!(error.field.as_deref(), Some("text"));
+
+        let error = rejected(br#"{"id": 1, "text": "\u0000"}"#, PayloadType::Json);
+        assert_eq!(error.kind, InvalidCharacterKind::ControlCharacter);
+        assert_eq!(error.document, Some(0));
+
+        let error = rejected(b"[{\"id\": 1, \"text\": \"\xC0\xAF\"}]", PayloadType::Json);
+        assert_eq!(error.kind, InvalidCharacterKind::InvalidUtf8);
+        assert_eq!(error.field.as_deref(), Some("text"));
+
+        // the encoded surrogates are not valid UTF-8 either.
+        let error = rejected(b"[{\"text\": \"\xED\xA0\x80\"}]", PayloadType::Json);
+        assert_eq!(error.kind, InvalidCharacterKind::InvalidUtf8);
+    }
+
+    #[test]
+    fn test_json_invalid_characters_are_replaced() {
+        let (output, count) = replaced(
+            b"[{\"a\": \"\\udc00 \\ud800\\u0041 \\ud83d\\ude00\", \"b\": \"x\x01y\tz\\b\", \"c\": \"\xFF\"}]",
+            PayloadType::Json,
+        );
+        assert_eq!(
+            output,
+            r#"[{"a": "\ufffd \ufffd\u0041 \ud83d\ude00", "b": "x\ufffdy\tz\ufffd", "c": "\ufffd"}]"#
+        );
+        assert_eq!(count, 6);
+
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value[0]["a"], "\u{FFFD} \u{FFFD}A 😀");
+
+        // an unpaired high surrogate right before the end of the string.
+        let (output, count) = replaced(br#"{"a": "\ud800"}"#, PayloadType::Json);
+        assert_eq!(output, r#"{"a": "\ufffd"}"#);
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_csv_invalid_characters() {
+        let error = rejected(
+            b"id,title:string,year:number\n1,ok,2000\n2,\"bad\x1B\",2001\n",
+            PayloadType::Csv,
+        );
+        assert_eq!(error.kind, InvalidCharacterKind::ControlCharacter);
+        assert_eq!(error.document, Some(1));
+        assert_eq!(error.field.as_deref(), Some("title"));
+
+        let error = rejected(b"id,title\n1,\xE9t\xE9\n", PayloadType::Csv);
+        assert_eq!(error.kind, InvalidCharacterKind::InvalidUtf8);
+        assert_eq!(error.document, Some(0));
+
+        let (output, count) = replaced(b"id,title\n1,a\x00b\xE9\n", PayloadType::Csv);
+        assert_eq!(output, "id,title\n1,a\u{FFFD}b\u{FFFD}\n");
+        assert_eq!(count, 2);
+    }
+}
@@ -6,6 +6,7 @@ use uuid::Uuid;
 
 use super::v4::{Task, TaskContent, TaskEvent};
 use crate::index::{Settings, Unchecked};
+use crate::index_resolver::error::IndexResolverError;
 use crate::index_resolver::IndexUid;
 use crate::tasks::task::{DocumentDeletion, TaskId, TaskResult};
 
@@ -37,6 +38,9 @@ impl From<v2::UpdateResult> for TaskResult {
         match other {
             v2::UpdateResult::DocumentsAddition(result) => TaskResult::DocumentAddition {
                 indexed_documents: result.nb_documents as u64,
+                duplicate_documents: 0,
+                diff: None,
+                transforms: None,
             },
             v2::UpdateResult::DocumentDeletion { deleted } => TaskResult::DocumentDeletion {
                 deleted_documents: deleted,
@@ -183,12 +187,16 @@ impl Failed {
     }
 }
 
-impl From<(UpdateStatus, String, TaskId)> for Task {
-    fn from((update, uid, task_id): (UpdateStatus, String, TaskId)) -> Self {
+impl TryFrom<(UpdateStatus, String, TaskId)> for Task {
+    type Error = IndexResolverError;
+
+    fn try_from(
+        (update, uid, task_id): (UpdateStatus, String, TaskId),
+    ) -> Result<Self, Self::Error> {
         // Dummy task
         let mut task = super::v4::Task {
             id: task_id,
-            index_uid: IndexUid::new_unchecked(uid),
+            index_uid: IndexUid::new(uid)?,
             content: super::v4::TaskContent::IndexDeletion,
             events: Vec::new(),
         };
@@ -200,6 +208,6 @@ impl From<(UpdateStatus, String, TaskId)> for Task {
             UpdateStatus::Failed(u) => u.update_task(&mut task),
         }
 
-        task
+        Ok(task)
     }
 }
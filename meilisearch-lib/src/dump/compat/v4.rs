@@ -23,8 +23,11 @@ impl From<Task> for crate::tasks::task::Task {
     fn from(other: Task) -> Self {
         Self {
             id: other.id,
+            index_uuid: None,
             content: NewTaskContent::from((other.index_uid, other.content)),
             events: other.events.into_iter().map(Into::into).collect(),
+            origin: None,
+            engine_version: None,
         }
     }
 }
@@ -62,10 +65,16 @@ impl From<TaskEvent> for NewTaskEvent {
                 batch_id,
             },
             TaskEvent::Processing(x) => NewTaskEvent::Processing(x),
-            TaskEvent::Succeded { result, timestamp } => {
-                NewTaskEvent::Succeeded { result, timestamp }
-            }
-            TaskEvent::Failed { error, timestamp } => NewTaskEvent::Failed { error, timestamp },
+            TaskEvent::Succeded { result, timestamp } => NewTaskEvent::Succeeded {
+                result,
+                timestamp,
+                engine_version: None,
+            },
+            TaskEvent::Failed { error, timestamp } => NewTaskEvent::Failed {
+                error,
+                timestamp,
+                engine_version: None,
+            },
         }
     }
 }
@@ -115,6 +124,12 @@ impl From<(IndexUid, TaskContent)> for NewTaskContent {
                 primary_key,
                 documents_count,
                 allow_index_creation,
+                reject_duplicates: false,
+                chunks: Vec::new(),
+                return_diff: None,
+                content_digests: Default::default(),
+                sanitized_characters: None,
+                warnings: None,
             },
             TaskContent::DocumentDeletion(deletion) => NewTaskContent::DocumentDeletion {
                 index_uid,
@@ -129,17 +144,28 @@ impl From<(IndexUid, TaskContent)> for NewTaskContent {
                 settings,
                 is_deletion,
                 allow_index_creation,
+                scheduled_at: None,
+                warnings: Vec::new(),
             },
             TaskContent::IndexDeletion => NewTaskContent::IndexDeletion { index_uid },
             TaskContent::IndexCreation { primary_key } => NewTaskContent::IndexCreation {
                 index_uid,
                 primary_key,
+                name: None,
+                namespace: None,
+                if_not_exists: false,
+                skip_templates: false,
             },
             TaskContent::IndexUpdate { primary_key } => NewTaskContent::IndexUpdate {
                 index_uid,
                 primary_key,
+                name: None,
+                skip_templates: None,
+            },
+            TaskContent::Dump { uid } => NewTaskContent::Dump {
+                uid,
+                include_pending_tasks: false,
             },
-            TaskContent::Dump { uid } => NewTaskContent::Dump { uid },
         }
     }
 }
@@ -8,7 +8,7 @@ pub type Result<T> = std::result::Result<T, DumpError>;
 #[derive(thiserror::Error, Debug)]
 pub enum DumpError {
     #[error("An internal error has occurred. `{0}`.")]
-    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+    Internal(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
     #[error("{0}")]
     IndexResolver(#[from] IndexResolverError),
 }
@@ -20,17 +20,18 @@ mod real {
 
     use log::{info, trace};
     use meilisearch_auth::AuthController;
-    use milli::heed::Env;
+    use serde_json::Value;
     use tokio::fs::create_dir_all;
 
     use crate::analytics;
-    use crate::compression::to_tar_gz;
+    use crate::compression::to_tar_gz_throttled;
     use crate::dump::error::{DumpError, Result};
     use crate::dump::{MetadataVersion, META_FILE_NAME};
     use crate::index_resolver::{
         index_store::IndexStore, meta_store::IndexMetaStore, IndexResolver,
     };
     use crate::tasks::TaskStore;
+    use crate::throttle::{IoThrottle, IoThrottleConfig, ThrottleReport};
     use crate::update_file_store::UpdateFileStore;
 
     pub struct DumpHandler<U, I> {
@@ -39,8 +40,14 @@ mod real {
         update_file_store: UpdateFileStore,
         task_store_size: usize,
         index_db_size: usize,
-        env: Arc<Env>,
+        task_store: TaskStore,
         index_resolver: Arc<IndexResolver<U, I>>,
+        /// The redacted effective configuration of the instance, recorded in the dumps.
+        config: Arc<Value>,
+        /// Whether the origins of the tasks are dumped.
+        task_origins: bool,
+        /// The throttle pacing the writes of the archive of the dumps.
+        io_throttle: IoThrottleConfig,
     }
 
     impl<U, I> DumpHandler<U, I>
@@ -54,8 +61,9 @@ mod real {
             update_file_store: UpdateFileStore,
             task_store_size: usize,
             index_db_size: usize,
-            env: Arc<Env>,
+            task_store: TaskStore,
             index_resolver: Arc<IndexResolver<U, I>>,
+            config: Arc<Value>,
         ) -> Self {
             Self {
                 dump_path,
@@ -63,12 +71,30 @@ mod real {
                 update_file_store,
                 task_store_size,
                 index_db_size,
-                env,
+                task_store,
                 index_resolver,
+                config,
+                task_origins: false,
+                io_throttle: IoThrottleConfig::default(),
             }
         }
 
-        pub async fn run(&self, uid: String) -> Result<()> {
+        /// Dumps the origins of the tasks along with the tasks.
+        pub fn set_task_origins(&mut self, task_origins: bool) {
+            self.task_origins = task_origins;
+        }
+
+        pub fn set_io_throttle(&mut self, io_throttle: IoThrottleConfig) {
+            self.io_throttle = io_throttle;
+        }
+
+        /// Creates the dump `uid`, returning what the throttle did to the writes of its archive.
+        /// The unfinished tasks are only dumped with `include_pending_tasks`.
+        pub async fn run(
+            &self,
+            uid: String,
+            include_pending_tasks: bool,
+        ) -> Result<ThrottleReport> {
             trace!("Performing dump.");
 
             create_dir_all(&self.dump_path).await?;
@@ -76,7 +102,11 @@ mod real {
             let temp_dump_dir = tokio::task::spawn_blocking(tempfile::TempDir::new).await??;
             let temp_dump_path = temp_dump_dir.path().to_owned();
 
-            let meta = MetadataVersion::new_v5(self.index_db_size, self.task_store_size);
+            let meta = MetadataVersion::new_v5(
+                self.index_db_size,
+                self.task_store_size,
+                Some(self.config.as_ref().clone()),
+            );
             let meta_path = temp_dump_path.join(META_FILE_NAME);
             // TODO: blocking
             let mut meta_file = File::create(&meta_path)?;
@@ -87,34 +117,37 @@ mod real {
 
             // TODO: this is blocking!!
             AuthController::dump(&self.db_path, &temp_dump_path)?;
-            TaskStore::dump(
-                self.env.clone(),
-                &temp_dump_path,
-                self.update_file_store.clone(),
-            )
-            .await?;
+            self.task_store
+                .dump(
+                    &temp_dump_path,
+                    self.update_file_store.clone(),
+                    self.task_origins,
+                    include_pending_tasks,
+                )
+                .await?;
             self.index_resolver.dump(&temp_dump_path).await?;
 
             let dump_path = self.dump_path.clone();
-            let dump_path = tokio::task::spawn_blocking(move || -> Result<PathBuf> {
-                // for now we simply copy the updates/updates_files
-                // FIXME: We may copy more files than necessary, if new files are added while we are
-                // performing the dump. We need a way to filter them out.
-
-                let temp_dump_file = tempfile::NamedTempFile::new_in(&dump_path)?;
-                to_tar_gz(temp_dump_path, temp_dump_file.path())
-                    .map_err(|e| DumpError::Internal(e.into()))?;
-
-                let dump_path = dump_path.join(uid).with_extension("dump");
-                temp_dump_file.persist(&dump_path)?;
-
-                Ok(dump_path)
-            })
-            .await??;
-
-            info!("Created dump in {:?}.", dump_path);
-
-            Ok(())
+            let mut throttle = IoThrottle::new(self.io_throttle.clone());
+            let (dump_path, report) =
+                tokio::task::spawn_blocking(move || -> Result<(PathBuf, ThrottleReport)> {
+                    let temp_dump_file = tempfile::NamedTempFile::new_in(&dump_path)?;
+                    to_tar_gz_throttled(temp_dump_path, temp_dump_file.path(), &mut throttle)
+                        .map_err(|e| DumpError::Internal(e.into()))?;
+
+                    let dump_path = dump_path.join(uid).with_extension("dump");
+                    temp_dump_file.persist(&dump_path)?;
+
+                    Ok((dump_path, throttle.report()))
+                })
+                .await??;
+
+            info!(
+                "Created dump in {:?}: {} bytes written at {} bytes/s on average, paused for {}ms.",
+                dump_path, report.written_bytes, report.average_bytes_per_sec, report.paused_ms
+            );
+
+            Ok(report)
         }
     }
 }
@@ -125,12 +158,14 @@ mod test {
     use std::path::PathBuf;
     use std::sync::Arc;
 
-    use milli::heed::Env;
     use nelson::Mocker;
+    use serde_json::Value;
 
     use crate::dump::error::Result;
     use crate::index_resolver::IndexResolver;
     use crate::index_resolver::{index_store::IndexStore, meta_store::IndexMetaStore};
+    use crate::tasks::TaskStore;
+    use crate::throttle::{IoThrottleConfig, ThrottleReport};
     use crate::update_file_store::UpdateFileStore;
 
     use super::*;
@@ -157,8 +192,9 @@ mod test {
             update_file_store: UpdateFileStore,
             task_store_size: usize,
             index_db_size: usize,
-            env: Arc<Env>,
+            task_store: TaskStore,
             index_resolver: Arc<IndexResolver<U, I>>,
+            config: Arc<Value>,
         ) -> Self {
             Self::Real(super::real::DumpHandler::new(
                 dump_path,
@@ -166,14 +202,34 @@ mod test {
                 update_file_store,
                 task_store_size,
                 index_db_size,
-                env,
+                task_store,
                 index_resolver,
+                config,
             ))
         }
-        pub async fn run(&self, uid: String) -> Result<()> {
+
+        pub fn set_task_origins(&mut self, task_origins: bool) {
+            if let Self::Real(real) = self {
+                real.set_task_origins(task_origins);
+            }
+        }
+
+        pub fn set_io_throttle(&mut self, io_throttle: IoThrottleConfig) {
+            if let Self::Real(real) = self {
+                real.set_io_throttle(io_throttle);
+            }
+        }
+
+        pub async fn run(
+            &self,
+            uid: String,
+            include_pending_tasks: bool,
+        ) -> Result<ThrottleReport> {
             match self {
-                DumpHandler::Real(real) => real.run(uid).await,
-                DumpHandler::Mock(mocker, _) => unsafe { mocker.get("run").call(uid) },
+                DumpHandler::Real(real) => real.run(uid, include_pending_tasks).await,
+                DumpHandler::Mock(mocker, _) => unsafe {
+                    mocker.get("run").call((uid, include_pending_tasks))
+                },
             }
         }
     }
@@ -20,8 +20,24 @@ pub fn load_dump(
     indexing_options: &IndexerOpts,
 ) -> anyhow::Result<()> {
     log::info!("Patching dump V2 to dump V3...");
-    let indexes_path = src.as_ref().join("indexes");
+    patch_indexes(src.as_ref().join("indexes"))?;
 
+    let update_dir = src.as_ref().join("updates");
+    let update_path = update_dir.join("data.jsonl");
+    patch_updates(update_dir, update_path)?;
+
+    super::v3::load_dump(
+        meta,
+        src,
+        dst,
+        index_db_size,
+        update_db_size,
+        indexing_options,
+    )
+}
+
+/// Patches the folders of the indexes of a dump V2, in `indexes_path`, into the ones of a dump V3.
+pub fn patch_indexes(indexes_path: impl AsRef<Path>) -> anyhow::Result<()> {
     let dir_entries = std::fs::read_dir(indexes_path)?;
     for entry in dir_entries {
         let entry = entry?;
@@ -37,18 +53,7 @@ pub fn load_dump(
         patch_settings(settings_path)?;
     }
 
-    let update_dir = src.as_ref().join("updates");
-    let update_path = update_dir.join("data.jsonl");
-    patch_updates(update_dir, update_path)?;
-
-    super::v3::load_dump(
-        meta,
-        src,
-        dst,
-        index_db_size,
-        update_db_size,
-        indexing_options,
-    )
+    Ok(())
 }
 
 fn patch_index_uuid_path(path: &Path) -> Option<PathBuf> {
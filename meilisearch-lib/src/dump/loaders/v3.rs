@@ -87,6 +87,12 @@ fn patch_index_meta(
                 uuid: entry.uuid,
                 // This is lost information, we patch it to 0;
                 creation_task_id: 0,
+                name: None,
+                namespace: None,
+                former_uuids: Vec::new(),
+                templates: Vec::new(),
+                skip_templates: false,
+                closed: false,
             };
             let entry = DumpEntry {
                 uid: entry.uid,
@@ -122,10 +128,8 @@ fn patch_updates(
                 .get(&entry.uuid)
                 .with_context(|| format!("Unknown index uuid: {}", entry.uuid))?
                 .clone();
-            serde_json::to_writer(
-                &mut dst_file,
-                &compat::v4::Task::from((entry.update, name, task_id as TaskId)),
-            )?;
+            let task = compat::v4::Task::try_from((entry.update, name, task_id as TaskId))?;
+            serde_json::to_writer(&mut dst_file, &task)?;
             dst_file.write_all(b"\n")?;
             Ok(())
         })?;
@@ -37,7 +37,7 @@ pub fn load_dump(
         indexing_options,
     )?;
     UpdateFileStore::load_dump(src.as_ref(), &dst)?;
-    TaskStore::load_dump(&src, env)?;
+    TaskStore::load_dump(&src, &UpdateFileStore::new(&dst)?, env)?;
     AuthController::load_dump(&src, &dst)?;
     analytics::copy_user_id(src.as_ref(), dst.as_ref());
 
@@ -1,9 +1,11 @@
+use std::collections::BTreeSet;
 use std::fs::File;
 use std::path::Path;
 
 use anyhow::bail;
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use time::OffsetDateTime;
 
 use tempfile::TempDir;
@@ -14,11 +16,13 @@ use crate::options::IndexerOpts;
 use self::loaders::{v2, v3, v4, v5};
 
 pub use handler::{generate_uid, DumpHandler};
+pub use reindex::{reindex_dump, IndexReindexReport, ReindexOutcome, ReindexReport};
 
 mod compat;
 pub mod error;
 mod handler;
 mod loaders;
+mod reindex;
 
 const META_FILE_NAME: &str = "metadata.json";
 
@@ -30,15 +34,19 @@ pub struct Metadata {
     update_db_size: usize,
     #[serde(with = "time::serde::rfc3339")]
     dump_date: OffsetDateTime,
+    /// The redacted effective configuration of the instance that created the dump.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    config: Option<Value>,
 }
 
 impl Metadata {
-    pub fn new(index_db_size: usize, update_db_size: usize) -> Self {
+    pub fn new(index_db_size: usize, update_db_size: usize, config: Option<Value>) -> Self {
         Self {
             db_version: env!("CARGO_PKG_VERSION").to_string(),
             index_db_size,
             update_db_size,
             dump_date: OffsetDateTime::now_utc(),
+            config,
         }
     }
 }
@@ -110,8 +118,8 @@ impl MetadataVersion {
         Ok(())
     }
 
-    pub fn new_v5(index_db_size: usize, update_db_size: usize) -> Self {
-        let meta = Metadata::new(index_db_size, update_db_size);
+    pub fn new_v5(index_db_size: usize, update_db_size: usize, config: Option<Value>) -> Self {
+        let meta = Metadata::new(index_db_size, update_db_size, config);
         Self::V5(meta)
     }
 
@@ -141,6 +149,18 @@ impl MetadataVersion {
             | MetadataVersion::V5(meta) => Some(&meta.dump_date),
         }
     }
+
+    /// The configuration recorded in the dump. Only the dumps created since the configuration is
+    /// recorded have one.
+    pub fn config(&self) -> Option<&Value> {
+        match self {
+            MetadataVersion::V1(_) => None,
+            MetadataVersion::V2(meta)
+            | MetadataVersion::V3(meta)
+            | MetadataVersion::V4(meta)
+            | MetadataVersion::V5(meta) => meta.config.as_ref(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -159,12 +179,21 @@ pub fn load_dump(
     index_db_size: usize,
     update_db_size: usize,
     indexer_opts: &IndexerOpts,
+    current_config: &Value,
 ) -> anyhow::Result<()> {
     let empty_db = crate::is_empty_db(&dst_path);
     let src_path_exists = src_path.as_ref().exists();
 
     if empty_db && src_path_exists {
         let (tmp_src, tmp_dst, meta) = extract_dump(&dst_path, &src_path)?;
+        if let Some(recorded) = meta.config() {
+            for (option, recorded, current) in config_diff(recorded, current_config) {
+                warn!(
+                    "The option `{}` was {} when the dump was created, and is now {}.",
+                    option, recorded, current
+                );
+            }
+        }
         meta.load_dump(
             tmp_src.path(),
             tmp_dst.path(),
@@ -190,6 +219,32 @@ pub fn load_dump(
     }
 }
 
+/// Returns the options whose value differs between two configurations, along with both values.
+fn config_diff<'a>(
+    recorded: &'a Value,
+    current: &'a Value,
+) -> Vec<(&'a str, &'a Value, &'a Value)> {
+    let (recorded, current) = match (recorded.as_object(), current.as_object()) {
+        (Some(recorded), Some(current)) => (recorded, current),
+        _ => return Vec::new(),
+    };
+
+    let options: BTreeSet<&str> = recorded
+        .keys()
+        .chain(current.keys())
+        .map(String::as_str)
+        .collect();
+
+    options
+        .into_iter()
+        .filter_map(|option| {
+            let recorded = recorded.get(option).unwrap_or(&Value::Null);
+            let current = current.get(option).unwrap_or(&Value::Null);
+            (recorded != current).then(|| (option, recorded, current))
+        })
+        .collect()
+}
+
 fn extract_dump(
     dst_path: impl AsRef<Path>,
     src_path: impl AsRef<Path>,
@@ -260,3 +315,36 @@ fn persist_dump(dst_path: impl AsRef<Path>, tmp_dst: TempDir) -> anyhow::Result<
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_config_diff() {
+        let recorded = json!({
+            "max_batch_size": 10,
+            "debounce_duration_sec": null,
+            "max_index_size": 100,
+        });
+        let current = json!({
+            "max_batch_size": 10,
+            "debounce_duration_sec": 5,
+            "http_payload_size_limit": 1000,
+        });
+
+        let diff = config_diff(&recorded, &current);
+        assert_eq!(
+            diff,
+            vec![
+                ("debounce_duration_sec", &json!(null), &json!(5)),
+                ("http_payload_size_limit", &json!(null), &json!(1000)),
+                ("max_index_size", &json!(100), &json!(null)),
+            ]
+        );
+
+        assert!(config_diff(&recorded, &recorded).is_empty());
+    }
+}
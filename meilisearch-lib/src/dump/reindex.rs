@@ -0,0 +1,251 @@
+//! Offline rebuild of the indexes of a dump.
+//!
+//! The dump is loaded like an import does, except for its indexes: they are set aside before the
+//! loaders run, so that the loaders only restore the metadata, the tasks, the update files and the
+//! keys, and are then built in parallel by a pool of worker threads. The workers share the indexing
+//! memory, each one indexing with its part of it on a single thread. An index whose build fails is
+//! reported and removed from the data directory, the other indexes are built regardless.
+
+use std::fmt;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use milli::heed::EnvOpenOptions;
+use milli::update::IndexerConfig;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::loaders::v2;
+use super::{extract_dump, persist_dump, MetadataVersion};
+use crate::data_lock::DataDirLock;
+use crate::index::Index;
+use crate::index_resolver::meta_store::HeedMetaStore;
+use crate::options::IndexerOpts;
+
+/// The folder of the extracted dump the indexes are moved to, out of the reach of the loaders.
+const SET_ASIDE_INDEXES: &str = "indexes.set-aside";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "status", content = "error")]
+pub enum ReindexOutcome {
+    Succeeded,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexReindexReport {
+    /// The uid of the index, or the name of its folder in the dump if it has none.
+    pub uid: String,
+    pub outcome: ReindexOutcome,
+    pub duration: Duration,
+}
+
+impl fmt::Display for IndexReindexReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            ReindexOutcome::Succeeded => write!(f, "{}: built in {:.2?}", self.uid, self.duration),
+            ReindexOutcome::Failed(error) => write!(
+                f,
+                "{}: failed after {:.2?}: {}",
+                self.uid, self.duration, error
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReindexReport {
+    /// The report of each index, by uid.
+    pub indexes: Vec<IndexReindexReport>,
+    pub duration: Duration,
+}
+
+impl ReindexReport {
+    pub fn is_success(&self) -> bool {
+        self.indexes
+            .iter()
+            .all(|index| index.outcome == ReindexOutcome::Succeeded)
+    }
+}
+
+impl fmt::Display for ReindexReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for index in &self.indexes {
+            writeln!(f, "{}", index)?;
+        }
+        let failed = self
+            .indexes
+            .iter()
+            .filter(|index| index.outcome != ReindexOutcome::Succeeded)
+            .count();
+        writeln!(
+            f,
+            "{} indexes built, {} failed, in {:.2?}",
+            self.indexes.len() - failed,
+            failed,
+            self.duration
+        )
+    }
+}
+
+/// Builds the data directory `dst_path` from the dump at `src_path`, building its indexes on
+/// `threads` worker threads. `progress` is called with the report of each index once it is
+/// built, in the order they finish.
+///
+/// The failure of an index doesn't fail the rebuild, it is listed in the returned report.
+pub fn reindex_dump(
+    src_path: impl AsRef<Path>,
+    dst_path: impl AsRef<Path>,
+    threads: usize,
+    index_db_size: usize,
+    meta_env_size: usize,
+    indexer_opts: &IndexerOpts,
+    mut progress: impl FnMut(&IndexReindexReport),
+) -> anyhow::Result<ReindexReport> {
+    let started = Instant::now();
+    if threads == 0 {
+        bail!("the reindexing needs at least one thread");
+    }
+    // the data directory is built while no server uses it.
+    let _data_lock = DataDirLock::acquire(&dst_path)?;
+    if !crate::is_empty_db(&dst_path) {
+        bail!(
+            "database already exists at {:?}, try to delete it or rename it",
+            dst_path.as_ref()
+        );
+    }
+    if !src_path.as_ref().exists() {
+        bail!("dump doesn't exist at {:?}", src_path.as_ref());
+    }
+
+    let (tmp_src, tmp_dst, meta) = extract_dump(&dst_path, &src_path)?;
+    let indexes_path = tmp_src.path().join("indexes");
+    // The loaders of the dumps V2 patch the indexes while loading them.
+    if let MetadataVersion::V2(_) = meta {
+        v2::patch_indexes(&indexes_path)?;
+    }
+    let set_aside = tmp_src.path().join(SET_ASIDE_INDEXES);
+    fs::rename(&indexes_path, &set_aside)?;
+    fs::create_dir_all(&indexes_path)?;
+
+    meta.load_dump(
+        tmp_src.path(),
+        tmp_dst.path(),
+        index_db_size,
+        meta_env_size,
+        indexer_opts,
+    )?;
+
+    let mut options = EnvOpenOptions::new();
+    options.map_size(meta_env_size);
+    options.max_dbs(100);
+    let meta_store = HeedMetaStore::new(Arc::new(options.open(tmp_dst.path())?))?;
+    let uids = meta_store.list()?;
+
+    let mut indexes = Vec::new();
+    for entry in set_aside.read_dir()? {
+        let path = entry?.path();
+        let dir_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let uid = uids
+            .iter()
+            .find(|(_, meta)| meta.uuid.to_string() == dir_name)
+            .map(|(uid, _)| uid.clone());
+        indexes.push((uid, dir_name, path));
+    }
+
+    let workers = threads.min(indexes.len()).max(1);
+    let queue = Arc::new(Mutex::new(indexes));
+    let (sender, receiver) = mpsc::channel();
+    let mut handles = Vec::with_capacity(workers);
+    for _ in 0..workers {
+        let config = worker_config(indexer_opts, workers)?;
+        let queue = queue.clone();
+        let sender = sender.clone();
+        let dst = tmp_dst.path().to_owned();
+        handles.push(thread::spawn(move || loop {
+            let next = queue.lock().pop();
+            let (uid, dir_name, path) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            let built = build_index(uid, dir_name, &path, &dst, index_db_size, &config);
+            if sender.send(built).is_err() {
+                break;
+            }
+        }));
+    }
+    drop(sender);
+
+    let mut reports = Vec::new();
+    for (uid, dir_name, report) in receiver {
+        progress(&report);
+        if report.outcome != ReindexOutcome::Succeeded {
+            // A partially built index is removed, so that it is not served.
+            let _ = fs::remove_dir_all(tmp_dst.path().join("indexes").join(&dir_name));
+            if let Some(uid) = uid {
+                meta_store.delete(uid)?;
+            }
+        }
+        reports.push(report);
+    }
+    for handle in handles {
+        // The workers catch the panics of the indexing, they can't panic.
+        let _ = handle.join();
+    }
+
+    // The environment must be closed before the data directory is moved.
+    meta_store.close();
+    drop(meta_store);
+    persist_dump(&dst_path, tmp_dst)?;
+
+    reports.sort_by(|a, b| a.uid.cmp(&b.uid));
+    Ok(ReindexReport {
+        indexes: reports,
+        duration: started.elapsed(),
+    })
+}
+
+/// The indexer configuration of a worker, which indexes on a single thread with its part of the
+/// indexing memory.
+fn worker_config(indexer_opts: &IndexerOpts, workers: usize) -> anyhow::Result<IndexerConfig> {
+    let mut config = IndexerConfig::try_from(indexer_opts)?;
+    config.max_memory = config.max_memory.map(|memory| memory / workers);
+    config.thread_pool = Some(rayon::ThreadPoolBuilder::new().num_threads(1).build()?);
+    Ok(config)
+}
+
+fn build_index(
+    uid: Option<String>,
+    dir_name: String,
+    path: &Path,
+    dst: &Path,
+    index_db_size: usize,
+    config: &IndexerConfig,
+) -> (Option<String>, String, IndexReindexReport) {
+    let started = Instant::now();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        Index::load_dump(path, dst, index_db_size, config)
+    }));
+    let outcome = match result {
+        Ok(Ok(())) => ReindexOutcome::Succeeded,
+        Ok(Err(e)) => ReindexOutcome::Failed(e.to_string()),
+        Err(_) => ReindexOutcome::Failed("the indexing panicked".to_string()),
+    };
+    let report = IndexReindexReport {
+        uid: uid.clone().unwrap_or_else(|| dir_name.clone()),
+        outcome,
+        duration: started.elapsed(),
+    };
+
+    (uid, dir_name, report)
+}
@@ -2,7 +2,8 @@ use std::error::Error;
 use std::fmt;
 
 use meilisearch_error::{Code, ErrorCode};
-use milli::UserError;
+use milli::heed::MdbError;
+use milli::{InternalError, UserError};
 
 #[derive(Debug)]
 pub struct MilliError<'a>(pub &'a milli::Error);
@@ -18,14 +19,18 @@ impl fmt::Display for MilliError<'_> {
 impl ErrorCode for MilliError<'_> {
     fn error_code(&self) -> Code {
         match self.0 {
+            milli::Error::InternalError(InternalError::Store(MdbError::ReadersFull)) => {
+                Code::TooManyConcurrentReads
+            }
             milli::Error::InternalError(_) => Code::Internal,
             milli::Error::IoError(_) => Code::Internal,
             milli::Error::UserError(ref error) => {
                 match error {
                     // TODO: wait for spec for new error codes.
-                    UserError::SerdeJson(_)
-                    | UserError::DocumentLimitReached
-                    | UserError::UnknownInternalDocumentId { .. } => Code::Internal,
+                    UserError::SerdeJson(_) | UserError::UnknownInternalDocumentId { .. } => {
+                        Code::Internal
+                    }
+                    UserError::DocumentLimitReached => Code::MaxDocumentsLimitReached,
                     UserError::InvalidStoreFile => Code::InvalidStore,
                     UserError::NoSpaceLeftOnDevice => Code::NoSpaceLeftOnDevice,
                     UserError::MaxDatabaseSizeReached => Code::DatabaseSizeLimitReached,
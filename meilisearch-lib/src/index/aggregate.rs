@@ -0,0 +1,392 @@
+//! Aggregations computed without retrieving any hit.
+//!
+//! An aggregation counts the documents matching a filter, optionally grouped by the values of a
+//! facet, and computes numeric metrics over them. Everything is computed on the facet databases
+//! within a single read transaction: the documents are never deserialized and no ranking rule is
+//! applied, which is what makes an aggregation much cheaper than a search with `limit=0`.
+//!
+//! The numeric metrics are computed over the facet values, so that a document with several values
+//! for a field contributes each of its values to the `min`, `max`, `sum` and `avg` of that field.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+use milli::heed::RoTxn;
+use milli::FieldId;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::derived::{is_derived, Derivation};
+use super::error::{AggregateError, Result};
+use super::index::Index;
+use super::search::parse_filter;
+
+fn default_metrics() -> Vec<String> {
+    vec![String::from("count")]
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AggregateQuery {
+    pub filter: Option<Value>,
+    pub group_by: Option<String>,
+    #[serde(default = "default_metrics")]
+    pub metrics: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AggregateRow {
+    /// The value of the `groupBy` facet for this group, absent when the query is not grouped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<Value>,
+    #[serde(flatten)]
+    pub metrics: Map<String, Value>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregateResult {
+    pub groups: Vec<AggregateRow>,
+    /// Whether some groups were left out because there were more than `maxValuesPerFacet` of
+    /// them.
+    pub truncated: bool,
+    pub processing_time_ms: u128,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Metric {
+    Count,
+    Min(String),
+    Max(String),
+    Sum(String),
+    Avg(String),
+}
+
+impl Metric {
+    fn field(&self) -> Option<&str> {
+        match self {
+            Metric::Count => None,
+            Metric::Min(field) | Metric::Max(field) | Metric::Sum(field) | Metric::Avg(field) => {
+                Some(field)
+            }
+        }
+    }
+
+    fn compute(&self, count: u64, stats: Option<&NumericStats>) -> Value {
+        let stats = stats.copied().unwrap_or_default();
+        match self {
+            Metric::Count => Value::from(count),
+            Metric::Min(_) => stats.min.map_or(Value::Null, Value::from),
+            Metric::Max(_) => stats.max.map_or(Value::Null, Value::from),
+            Metric::Sum(_) => Value::from(stats.sum),
+            Metric::Avg(_) if stats.count == 0 => Value::Null,
+            Metric::Avg(_) => Value::from(stats.sum / stats.count as f64),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = AggregateError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "count" {
+            return Ok(Metric::Count);
+        }
+
+        let invalid = || AggregateError::InvalidMetric(s.to_string());
+        let (function, rest) = s.split_once('(').ok_or_else(invalid)?;
+        let field = rest
+            .strip_suffix(')')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .ok_or_else(invalid)?
+            .to_string();
+
+        match function.trim() {
+            "min" => Ok(Metric::Min(field)),
+            "max" => Ok(Metric::Max(field)),
+            "sum" => Ok(Metric::Sum(field)),
+            "avg" => Ok(Metric::Avg(field)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Count => write!(f, "count"),
+            Metric::Min(field) => write!(f, "min({})", field),
+            Metric::Max(field) => write!(f, "max({})", field),
+            Metric::Sum(field) => write!(f, "sum({})", field),
+            Metric::Avg(field) => write!(f, "avg({})", field),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct NumericStats {
+    min: Option<f64>,
+    max: Option<f64>,
+    sum: f64,
+    count: u64,
+}
+
+impl NumericStats {
+    /// Accounts for `count` occurrences of `value`. The values must be pushed in ascending order.
+    fn push(&mut self, value: f64, count: u64) {
+        self.min.get_or_insert(value);
+        self.max = Some(value);
+        self.sum += value * count as f64;
+        self.count += count;
+    }
+}
+
+impl Index {
+    pub fn aggregate(&self, query: AggregateQuery) -> Result<AggregateResult> {
+        let before_aggregate = Instant::now();
+        let rtxn = self.read_txn()?;
+
+        let metrics = query
+            .metrics
+            .iter()
+            .map(|metric| metric.parse())
+            .collect::<std::result::Result<Vec<Metric>, _>>()?;
+
+        let filterable_fields = self.filterable_fields(&rtxn)?;
+        let fields_ids_map = self.fields_ids_map(&rtxn)?;
+        // a filterable field that is in no document yet has no field id, and the fields nested
+        // in a filterable object are filterable too, but for the derived field.
+        let field_id = |field: &str| -> Result<Option<FieldId>> {
+            if !is_derived(field)
+                && filterable_fields
+                    .iter()
+                    .any(|filterable| milli::is_faceted_by(field, filterable))
+            {
+                Ok(fields_ids_map.id(field))
+            } else {
+                Err(AggregateError::NotFilterable(field.to_string()).into())
+            }
+        };
+
+        let derivation = Derivation::read(self, &rtxn)?;
+        let filter = query
+            .filter
+            .as_ref()
+            .map(|filter| derivation.rewrite_filter(filter));
+        let mut candidates = match filter.as_ref().map(parse_filter).transpose()?.flatten() {
+            Some(filter) => filter.evaluate(&rtxn, self)?,
+            None => self.documents_ids(&rtxn)?,
+        };
+        candidates -= self.soft_deleted_documents(&rtxn)?;
+
+        let (groups, truncated) = match query.group_by {
+            Some(ref group_by) => {
+                let max_groups = self.max_values_per_facet(&rtxn)?;
+                match field_id(group_by)? {
+                    Some(fid) => self.facet_groups(&rtxn, fid, &candidates, max_groups)?,
+                    None => (Vec::new(), false),
+                }
+            }
+            None => (vec![(None, candidates.clone())], false),
+        };
+
+        // the numeric stats of each group, by metric field.
+        let mut stats: Vec<(&str, Vec<NumericStats>)> = Vec::new();
+        for metric in &metrics {
+            let field = match metric.field() {
+                Some(field) if !stats.iter().any(|(f, _)| *f == field) => field,
+                _ => continue,
+            };
+
+            let group_stats = match field_id(field)? {
+                Some(fid) => {
+                    let non_numeric = self.string_faceted_documents_ids(&rtxn, fid)?;
+                    if !non_numeric.is_disjoint(&candidates) {
+                        return Err(AggregateError::NonNumericField {
+                            metric: metric.to_string(),
+                            field: field.to_string(),
+                        }
+                        .into());
+                    }
+                    self.numeric_stats(&rtxn, fid, &groups)?
+                }
+                None => vec![NumericStats::default(); groups.len()],
+            };
+            stats.push((field, group_stats));
+        }
+
+        let groups = groups
+            .iter()
+            .enumerate()
+            .map(|(i, (group, docids))| {
+                let metrics = metrics
+                    .iter()
+                    .map(|metric| {
+                        let group_stats = metric.field().and_then(|field| {
+                            stats
+                                .iter()
+                                .find(|(f, _)| *f == field)
+                                .map(|(_, stats)| &stats[i])
+                        });
+                        let value = metric.compute(docids.len(), group_stats);
+                        (metric.to_string(), value)
+                    })
+                    .collect();
+
+                AggregateRow {
+                    group: group.clone(),
+                    metrics,
+                }
+            })
+            .collect();
+
+        Ok(AggregateResult {
+            groups,
+            truncated,
+            processing_time_ms: before_aggregate.elapsed().as_millis(),
+        })
+    }
+
+    /// Splits the candidates by the values of the facet `fid`, numbers first, then strings, both
+    /// in ascending order. Returns at most `max_groups` groups, along with whether groups were
+    /// left out.
+    pub(super) fn facet_groups(
+        &self,
+        rtxn: &RoTxn,
+        fid: FieldId,
+        candidates: &RoaringBitmap,
+        max_groups: usize,
+    ) -> Result<(Vec<(Option<Value>, RoaringBitmap)>, bool)> {
+        let mut groups = Vec::new();
+
+        let numbers = self.facet_id_f64_docids.range(
+            rtxn,
+            &((fid, 0, f64::MIN, f64::MIN)..=(fid, 0, f64::MAX, f64::MAX)),
+        )?;
+        for result in numbers {
+            let ((_, _, value, _), docids) = result?;
+            let docids = docids & candidates;
+            if !docids.is_empty() {
+                if groups.len() == max_groups {
+                    return Ok((groups, true));
+                }
+                groups.push((Some(Value::from(value)), docids));
+            }
+        }
+
+        // the level zero of the string facets is prefixed by the field id and the level.
+        let strings = self.facet_id_string_docids.prefix_iter(rtxn, &(fid, ""))?;
+        for result in strings {
+            let (_, (original, docids)) = result?;
+            let docids = docids & candidates;
+            if !docids.is_empty() {
+                if groups.len() == max_groups {
+                    return Ok((groups, true));
+                }
+                groups.push((Some(Value::from(original)), docids));
+            }
+        }
+
+        Ok((groups, false))
+    }
+
+    /// Computes the stats of the numeric facet `fid` for each group, in a single pass over the
+    /// values of the facet.
+    fn numeric_stats(
+        &self,
+        rtxn: &RoTxn,
+        fid: FieldId,
+        groups: &[(Option<Value>, RoaringBitmap)],
+    ) -> Result<Vec<NumericStats>> {
+        let mut stats = vec![NumericStats::default(); groups.len()];
+
+        let values = self.facet_id_f64_docids.range(
+            rtxn,
+            &((fid, 0, f64::MIN, f64::MIN)..=(fid, 0, f64::MAX, f64::MAX)),
+        )?;
+        for result in values {
+            let ((_, _, value, _), docids) = result?;
+            for ((_, group), stats) in groups.iter().zip(stats.iter_mut()) {
+                let count = docids.intersection_len(group);
+                if count > 0 {
+                    stats.push(value, count);
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_metrics() {
+        assert_eq!("count".parse::<Metric>().unwrap(), Metric::Count);
+        assert_eq!(
+            "min(price)".parse::<Metric>().unwrap(),
+            Metric::Min("price".to_string())
+        );
+        assert_eq!(
+            " avg( price ) ".parse::<Metric>().unwrap(),
+            Metric::Avg("price".to_string())
+        );
+        assert_eq!(
+            "sum(price)".parse::<Metric>().unwrap().to_string(),
+            "sum(price)"
+        );
+
+        for invalid in [
+            "",
+            "counts",
+            "min()",
+            "median(price)",
+            "max(price",
+            "sum price",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<Metric>(),
+                    Err(AggregateError::InvalidMetric(_))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_numeric_stats() {
+        let mut stats = NumericStats::default();
+        assert_eq!(
+            Metric::Avg("price".into()).compute(0, Some(&stats)),
+            Value::Null
+        );
+        assert_eq!(
+            Metric::Sum("price".into()).compute(0, None),
+            Value::from(0.0)
+        );
+
+        stats.push(2.0, 1);
+        stats.push(4.0, 2);
+        stats.push(10.0, 1);
+
+        assert_eq!(stats.min, Some(2.0));
+        assert_eq!(stats.max, Some(10.0));
+        assert_eq!(
+            Metric::Sum("price".into()).compute(3, Some(&stats)),
+            Value::from(20.0)
+        );
+        assert_eq!(
+            Metric::Avg("price".into()).compute(3, Some(&stats)),
+            Value::from(5.0)
+        );
+        assert_eq!(Metric::Count.compute(3, Some(&stats)), Value::from(3));
+    }
+}
@@ -0,0 +1,444 @@
+//! Integrity check of an index.
+//!
+//! The check walks the databases of an index within a single read transaction, and looks for the
+//! inconsistencies an unclean shutdown could leave behind: documents that are missing from the
+//! documents store or from the external ids map, postings referencing documents that don't exist,
+//! and facet databases that disagree with the faceted documents.
+//!
+//! A check never fails because of what it finds: every problem, including a database that can't
+//! be read, is reported as a finding. In fast mode, only the first entries of each database are
+//! examined, and the stats of the index are not counted again.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use milli::heed::RoTxn;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+
+use super::error::Result;
+use super::index::Index;
+
+/// The number of entries of each database examined by a fast check.
+pub const FAST_CHECK_SAMPLE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub severity: Severity,
+    /// The part of the index the finding is about.
+    pub check: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct IndexCheckReport {
+    pub fast: bool,
+    pub findings: Vec<Finding>,
+}
+
+impl IndexCheckReport {
+    fn push(&mut self, severity: Severity, check: &str, message: impl fmt::Display) {
+        self.findings.push(Finding {
+            severity,
+            check: check.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    /// The highest severity of the findings, if any.
+    pub fn severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+}
+
+type Check = fn(&Index, &RoTxn, &RoaringBitmap, &mut Sampler, &mut IndexCheckReport) -> Result<()>;
+
+impl Index {
+    pub fn check(&self, fast: bool) -> Result<IndexCheckReport> {
+        let rtxn = self.read_txn()?;
+        let mut report = IndexCheckReport {
+            fast,
+            findings: Vec::new(),
+        };
+
+        let documents_ids = match self.documents_ids(&rtxn) {
+            Ok(documents_ids) => documents_ids,
+            Err(e) => {
+                report.push(
+                    Severity::Error,
+                    "documents",
+                    format_args!("The documents ids can't be read: {}.", e),
+                );
+                return Ok(report);
+            }
+        };
+
+        let checks: [(&str, Check); 4] = [
+            ("documents", check_documents),
+            ("wordDocids", check_word_docids),
+            ("facets", check_facets),
+            ("stats", check_stats),
+        ];
+        for (name, check) in checks {
+            let mut sampler = Sampler::new(fast);
+            if let Err(e) = check(self, &rtxn, &documents_ids, &mut sampler, &mut report) {
+                report.push(
+                    Severity::Error,
+                    name,
+                    format_args!("The check could not complete: {}.", e),
+                );
+            }
+        }
+
+        let summary = if fast {
+            format!(
+                "{} documents. Only the first {} entries of each database were checked.",
+                documents_ids.len(),
+                FAST_CHECK_SAMPLE_SIZE
+            )
+        } else {
+            format!("{} documents.", documents_ids.len())
+        };
+        report.push(Severity::Info, "summary", summary);
+
+        Ok(report)
+    }
+}
+
+/// Counts the entries examined in a database, and tells when a fast check must stop.
+struct Sampler {
+    fast: bool,
+    examined: usize,
+}
+
+impl Sampler {
+    fn new(fast: bool) -> Self {
+        Self { fast, examined: 0 }
+    }
+
+    fn reset(&mut self) {
+        self.examined = 0;
+    }
+
+    /// Accounts for a new entry, and returns whether it is the last one to examine.
+    fn is_done(&mut self) -> bool {
+        self.examined += 1;
+        self.fast && self.examined >= FAST_CHECK_SAMPLE_SIZE
+    }
+}
+
+fn check_documents(
+    index: &Index,
+    rtxn: &RoTxn,
+    documents_ids: &RoaringBitmap,
+    sampler: &mut Sampler,
+    report: &mut IndexCheckReport,
+) -> Result<()> {
+    const CHECK: &str = "documents";
+
+    // the documents store is ordered by internal id.
+    let mut stored = RoaringBitmap::new();
+    let mut expected = documents_ids.clone();
+    for result in index.documents.iter(rtxn)? {
+        let (docid, _) = result?;
+        let docid = docid.get();
+        stored.insert(docid);
+        if sampler.is_done() {
+            expected.remove_range(docid.saturating_add(1)..);
+            break;
+        }
+    }
+
+    let unknown = &stored - documents_ids;
+    if let Some(first) = unknown.min() {
+        report.push(
+            Severity::Error,
+            CHECK,
+            format_args!(
+                "{} documents of the documents store are not in the documents ids, such as the document with internal id {}.",
+                unknown.len(),
+                first
+            ),
+        );
+    }
+    let missing = &expected - &stored;
+    if let Some(first) = missing.min() {
+        report.push(
+            Severity::Error,
+            CHECK,
+            format_args!(
+                "{} documents ids have no document in the documents store, such as the internal id {}.",
+                missing.len(),
+                first
+            ),
+        );
+    }
+
+    let external_ids = index.external_documents_ids(rtxn)?.to_hash_map();
+    if !sampler.fast && external_ids.len() as u64 != documents_ids.len() {
+        report.push(
+            Severity::Warning,
+            CHECK,
+            format_args!(
+                "The external ids map has {} entries, but the index has {} documents.",
+                external_ids.len(),
+                documents_ids.len()
+            ),
+        );
+    }
+
+    sampler.reset();
+    let mut seen = HashSet::new();
+    let mut dangling = Vec::new();
+    let mut duplicated = 0;
+    for (external, internal) in &external_ids {
+        if !documents_ids.contains(*internal) {
+            dangling.push(external.as_str());
+        }
+        if !seen.insert(*internal) {
+            duplicated += 1;
+        }
+        if sampler.is_done() {
+            break;
+        }
+    }
+
+    if let Some(first) = dangling.first() {
+        report.push(
+            Severity::Error,
+            CHECK,
+            format_args!(
+                "{} external ids reference documents that don't exist, such as `{}`.",
+                dangling.len(),
+                first
+            ),
+        );
+    }
+    if duplicated > 0 {
+        report.push(
+            Severity::Error,
+            CHECK,
+            format_args!(
+                "{} external ids reference a document that is already referenced by another external id.",
+                duplicated
+            ),
+        );
+    }
+
+    let soft_deleted = index.soft_deleted_documents(rtxn)?;
+    if !soft_deleted.is_subset(documents_ids) {
+        report.push(
+            Severity::Warning,
+            CHECK,
+            format_args!(
+                "{} soft deleted documents are not in the documents ids.",
+                (&soft_deleted - documents_ids).len()
+            ),
+        );
+    }
+
+    Ok(())
+}
+
+fn check_word_docids(
+    index: &Index,
+    rtxn: &RoTxn,
+    documents_ids: &RoaringBitmap,
+    sampler: &mut Sampler,
+    report: &mut IndexCheckReport,
+) -> Result<()> {
+    let mut dangling_words = 0;
+    let mut example = None;
+    for result in index.word_docids.iter(rtxn)? {
+        let (word, docids) = result?;
+        if !docids.is_subset(documents_ids) {
+            dangling_words += 1;
+            example.get_or_insert_with(|| word.to_string());
+        }
+        if sampler.is_done() {
+            break;
+        }
+    }
+
+    if let Some(example) = example {
+        report.push(
+            Severity::Error,
+            "wordDocids",
+            format_args!(
+                "The postings of {} words reference documents that don't exist, such as the word `{}`.",
+                dangling_words, example
+            ),
+        );
+    }
+
+    Ok(())
+}
+
+fn check_facets(
+    index: &Index,
+    rtxn: &RoTxn,
+    documents_ids: &RoaringBitmap,
+    sampler: &mut Sampler,
+    report: &mut IndexCheckReport,
+) -> Result<()> {
+    const CHECK: &str = "facets";
+
+    let fields_ids_map = index.fields_ids_map(rtxn)?;
+    let faceted: Vec<_> = index
+        .filterable_fields(rtxn)?
+        .into_iter()
+        .chain(index.sortable_fields(rtxn)?)
+        .collect();
+
+    // the fields nested in a faceted object are faceted too.
+    for (fid, field) in fields_ids_map.iter() {
+        if !faceted
+            .iter()
+            .any(|facet| milli::is_faceted_by(field, facet))
+        {
+            continue;
+        }
+
+        sampler.reset();
+        let mut complete = true;
+        let mut numbers = RoaringBitmap::new();
+        let entries = index.facet_id_f64_docids.range(
+            rtxn,
+            &((fid, 0, f64::MIN, f64::MIN)..=(fid, 0, f64::MAX, f64::MAX)),
+        )?;
+        for result in entries {
+            let (_, docids) = result?;
+            numbers |= docids;
+            if sampler.is_done() {
+                complete = false;
+                break;
+            }
+        }
+
+        sampler.reset();
+        let mut strings = RoaringBitmap::new();
+        // the level zero of the string facets is prefixed by the field id and the level.
+        for result in index.facet_id_string_docids.prefix_iter(rtxn, &(fid, ""))? {
+            let (_, (_, docids)) = result?;
+            strings |= docids;
+            if sampler.is_done() {
+                complete = false;
+                break;
+            }
+        }
+
+        for (kind, values, faceted) in [
+            (
+                "number",
+                &numbers,
+                index.number_faceted_documents_ids(rtxn, fid)?,
+            ),
+            (
+                "string",
+                &strings,
+                index.string_faceted_documents_ids(rtxn, fid)?,
+            ),
+        ] {
+            if !values.is_subset(documents_ids) {
+                report.push(
+                    Severity::Error,
+                    CHECK,
+                    format_args!(
+                        "The {} values of `{}` reference {} documents that don't exist.",
+                        kind,
+                        field,
+                        (values - documents_ids).len()
+                    ),
+                );
+            }
+            // a sample of the values can't be compared with all the faceted documents.
+            if complete && *values != faceted {
+                report.push(
+                    Severity::Warning,
+                    CHECK,
+                    format_args!(
+                        "The {} values of `{}` reference {} documents, but {} documents have {} values for this field.",
+                        kind,
+                        field,
+                        values.len(),
+                        faceted.len(),
+                        kind
+                    ),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_stats(
+    index: &Index,
+    rtxn: &RoTxn,
+    _documents_ids: &RoaringBitmap,
+    sampler: &mut Sampler,
+    report: &mut IndexCheckReport,
+) -> Result<()> {
+    // the stats can only be counted again from all the documents.
+    if sampler.fast {
+        return Ok(());
+    }
+
+    let (repair, _) = index.recount_stats(rtxn)?;
+    if !repair.is_consistent() {
+        report.push(
+            Severity::Warning,
+            "stats",
+            format_args!(
+                "The stored stats are off by up to {}: {} documents are counted, but the stats report {}. A stats repair replaces them.",
+                repair.discrepancy(),
+                repair.after.number_of_documents,
+                repair.before.number_of_documents
+            ),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report_severity() {
+        let mut report = IndexCheckReport::default();
+        assert_eq!(report.severity(), None);
+
+        report.push(Severity::Info, "summary", "0 documents.");
+        report.push(Severity::Error, "facets", "broken");
+        report.push(Severity::Warning, "documents", "suspicious");
+        assert_eq!(report.severity(), Some(Severity::Error));
+        assert_eq!(report.findings[1].check, "facets");
+    }
+
+    #[test]
+    fn test_sampler() {
+        let mut sampler = Sampler::new(true);
+        assert!((1..FAST_CHECK_SAMPLE_SIZE).all(|_| !sampler.is_done()));
+        assert!(sampler.is_done());
+        sampler.reset();
+        assert!(!sampler.is_done());
+
+        let mut sampler = Sampler::new(false);
+        assert!((0..FAST_CHECK_SAMPLE_SIZE * 2).all(|_| !sampler.is_done()));
+    }
+}
@@ -0,0 +1,843 @@
+//! The values the engine derives from the documents, indexed by milli.
+//!
+//! The values milli can't compute from the documents as they are, like the normalized facet
+//! values, the fields of the elements of the arrays of objects by position, the words segmented by
+//! the custom segmenters or the metadata of the documents, are derived from each document when it
+//! is indexed, along with a sequence number of its own, and stored along with it in the reserved top-level field `_meilisearch`. milli
+//! indexes them like the other fields, so the filters and sorts on them are evaluated by milli, on
+//! its facet databases, before the documents are ranked. The conditions of a filter on the
+//! attributes of the documents, or on their metadata, are rewritten into conditions on the derived
+//! values wherever they are in the filter, so that they combine with the rest of it with any
+//! operator.
+//!
+//! The derived field is filterable and sortable whatever the settings of the index, its segmented
+//! words are searchable, and it is hidden from the settings, the documents, the hits, the dumps
+//! and the stats. The value a document gives to it is replaced. On a partial update, the field is
+//! derived from the document merged with the fields it has in the index, as milli merges them.
+//! Changing a setting the derived values depend on derives the field of all the documents again,
+//! and indexes them again from scratch. When all the attributes are searchable, so are the
+//! derived values.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Seek, SeekFrom};
+
+use meilisearch_auth::CompiledFilter;
+use milli::documents::{DocumentBatchBuilder, DocumentBatchReader};
+use milli::heed::{RoTxn, RwTxn};
+use milli::update::{IndexDocumentsConfig, IndexDocumentsMethod, IndexerConfig};
+use milli::{obkv_to_json, Condition, FieldId, FieldsIdsMap, FilterCondition};
+use serde_json::{Map, Value};
+
+use super::edition::external_id;
+use super::error::Result;
+use super::facet_normalization::{self, FacetNormalization};
+use super::index::{
+    put_next_document_sequence, put_stop_words, read_custom_segmenters,
+    read_document_metadata_enabled, read_facet_normalization, read_nested_object_behavior,
+    read_next_document_sequence, read_soft_deleted_documents, read_stop_words, Document,
+};
+use super::metadata::{self, DocumentMetadata, MetadataDatabase};
+use super::nested::{self, read_document};
+use super::segments::{self, SEGMENTS_FIELD};
+use super::sequences;
+use crate::segmenters::CustomSegmenters;
+
+/// The reserved top-level field holding the values derived from a document.
+pub const DERIVED_FIELD: &str = "_meilisearch";
+
+/// Whether `field` is the derived field, or one of its fields.
+pub fn is_derived(field: &str) -> bool {
+    match field.strip_prefix(DERIVED_FIELD) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.'),
+        None => false,
+    }
+}
+
+/// Returns the ids of the fields of the documents, the derived fields left out.
+pub fn document_fields(fields_ids_map: &FieldsIdsMap) -> Vec<FieldId> {
+    fields_ids_map
+        .iter()
+        .filter(|(_, name)| !is_derived(name))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// The settings the derived values of an index depend on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Derivation {
+    normalizations: BTreeMap<String, FacetNormalization>,
+    /// The attributes whose arrays of objects are filtered element by element.
+    element_attributes: Vec<String>,
+    /// The number of positions of the elements of each of these attributes in the index.
+    element_positions: BTreeMap<String, usize>,
+    segmenters: CustomSegmenters,
+    /// The searchable attributes, whose words are segmented, all of them if `None`.
+    searchable: Option<Vec<String>>,
+    /// Whether the metadata of the documents are recorded.
+    metadata: bool,
+    /// The sequence number of the next derived document.
+    next_sequence: Cell<u32>,
+}
+
+impl Derivation {
+    pub fn read(index: &milli::Index, txn: &RoTxn) -> Result<Self> {
+        let element_attributes =
+            nested::element_attributes(&read_nested_object_behavior(index, txn)?);
+        let element_positions = nested::positions(&index.fields_ids_map(txn)?, &element_attributes);
+        let segmenters = read_custom_segmenters(index, txn)?;
+        // the searchable attributes only matter to the segmented words.
+        let searchable = match segmenters.is_empty() {
+            true => None,
+            false => segments::searchable_fields(index, txn)?,
+        };
+        Ok(Self {
+            normalizations: read_facet_normalization(index, txn)?,
+            element_attributes,
+            element_positions,
+            segmenters,
+            searchable,
+            metadata: read_document_metadata_enabled(index, txn)?,
+            next_sequence: Cell::new(read_next_document_sequence(index, txn)?.unwrap_or_default()),
+        })
+    }
+
+    /// Records the sequence number of the next derived document, once the derived documents are
+    /// indexed.
+    pub fn put_next_sequence(&self, index: &milli::Index, txn: &mut RwTxn) -> Result<()> {
+        Ok(put_next_document_sequence(
+            index,
+            txn,
+            self.next_sequence.get(),
+        )?)
+    }
+
+    /// How the facet values of each attribute are normalized.
+    pub fn normalizations(&self) -> &BTreeMap<String, FacetNormalization> {
+        &self.normalizations
+    }
+
+    /// Replaces the derived field of `document` with the values derived from its other fields,
+    /// and from its `metadata` when they are recorded. The document is given the next sequence
+    /// number.
+    pub fn derive(&self, document: &mut Document, metadata: Option<&DocumentMetadata>) {
+        document.remove(DERIVED_FIELD);
+        let mut derived = Map::new();
+        let sequence = self.next_sequence.get();
+        self.next_sequence.set(sequence + 1);
+        sequences::derive(sequence, &mut derived);
+        facet_normalization::derive(document, &self.normalizations, &mut derived);
+        nested::derive(document, &self.element_attributes, &mut derived);
+        if !self.segmenters.is_empty() {
+            let searchable: Option<Vec<_>> = self
+                .searchable
+                .as_ref()
+                .map(|fields| fields.iter().map(String::as_str).collect());
+            segments::derive(
+                document,
+                searchable.as_deref(),
+                &self.segmenters,
+                &mut derived,
+            );
+        }
+        if let Some(metadata) = metadata.filter(|_| self.metadata) {
+            metadata::derive(metadata, &mut derived);
+        }
+        // the field is there even when it is empty, so that it replaces the one of a document
+        // that is updated partially.
+        document.insert(DERIVED_FIELD.to_string(), Value::Object(derived));
+    }
+
+    /// Rewrites the conditions of `filter` on the attributes of the documents, or on their
+    /// metadata, into conditions on their derived values. The filter is returned as it is when
+    /// none of its conditions is rewritten, or when it doesn't parse, for milli to report the
+    /// error.
+    pub fn rewrite_filter(&self, filter: &Value) -> Value {
+        let tree = match CompiledFilter::parse(filter) {
+            Ok(Some(tree)) => tree,
+            _ => return filter.clone(),
+        };
+
+        let mut rewritten = false;
+        let tree = self.rewrite_elements(tree, &mut rewritten);
+        let tree = map_conditions(tree, &mut |condition| {
+            let comparison = Comparison::parse(condition)?;
+            let filter = match self.metadata {
+                true => metadata::rewrite(&comparison),
+                false => None,
+            }
+            .or_else(|| facet_normalization::rewrite(&comparison, &self.normalizations))?
+            .render()?;
+            rewritten = true;
+            Some(filter)
+        });
+        match rewritten {
+            true => Value::String(tree.to_string()),
+            false => filter.clone(),
+        }
+    }
+
+    /// Rewrites the criteria of `sort` on the metadata of the documents into criteria on their
+    /// derived values.
+    pub fn rewrite_sort(&self, sort: &[String]) -> Vec<String> {
+        sort.iter()
+            .map(|criterion| match self.metadata {
+                true => metadata::rewrite_sort(criterion).unwrap_or_else(|| criterion.clone()),
+                false => criterion.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Derivation {
+    /// Rewrites the conditions of `tree` on the fields of the elements of the `element`
+    /// attributes. The conditions on the same attribute combined with `AND` are rewritten
+    /// together, in place of the first of them, to hold within the same element.
+    fn rewrite_elements(&self, tree: CompiledFilter, rewritten: &mut bool) -> CompiledFilter {
+        if self.element_attributes.is_empty() {
+            return tree;
+        }
+
+        let filters = match tree {
+            CompiledFilter::Not(filter) => {
+                return CompiledFilter::Not(Box::new(self.rewrite_elements(*filter, rewritten)))
+            }
+            CompiledFilter::Or(filters) => {
+                return CompiledFilter::Or(
+                    filters
+                        .into_iter()
+                        .map(|filter| self.rewrite_elements(filter, rewritten))
+                        .collect(),
+                )
+            }
+            CompiledFilter::And(filters) => filters,
+            condition @ CompiledFilter::Condition(_) => vec![condition],
+        };
+
+        // the conditions on the elements of each attribute, and where they go in the filters.
+        let mut groups: BTreeMap<&str, (usize, Vec<Comparison>, Vec<CompiledFilter>)> =
+            BTreeMap::new();
+        let mut rest = Vec::with_capacity(filters.len());
+        for filter in filters {
+            let element_condition = match filter {
+                CompiledFilter::Condition(ref condition) => self.element_condition(condition),
+                _ => None,
+            };
+            match element_condition {
+                Some((attribute, comparison)) => {
+                    let (_, comparisons, conditions) =
+                        groups.entry(attribute).or_insert_with(|| {
+                            rest.push(CompiledFilter::And(Vec::new()));
+                            (rest.len() - 1, Vec::new(), Vec::new())
+                        });
+                    comparisons.push(comparison);
+                    conditions.push(filter);
+                }
+                None => rest.push(self.rewrite_elements(filter, rewritten)),
+            }
+        }
+        for (attribute, (position, comparisons, conditions)) in groups {
+            let positions = self
+                .element_positions
+                .get(attribute)
+                .copied()
+                .unwrap_or_default();
+            rest[position] = match nested::rewrite(attribute, &comparisons, positions) {
+                Some(filter) => {
+                    *rewritten = true;
+                    filter
+                }
+                None => CompiledFilter::And(conditions),
+            };
+        }
+
+        match rest.len() {
+            1 => rest.remove(0),
+            _ => CompiledFilter::And(rest),
+        }
+    }
+
+    /// Parses a condition on a field of the elements of an `element` attribute, into the attribute
+    /// and the comparison on the path of the field in the elements.
+    fn element_condition(&self, condition: &str) -> Option<(&str, Comparison)> {
+        let comparison = Comparison::parse(condition)?;
+        let (attribute, field) = nested::split_field(&comparison.field, &self.element_attributes)?;
+        let field = field.to_string();
+        Some((
+            attribute,
+            Comparison {
+                field,
+                operator: comparison.operator,
+            },
+        ))
+    }
+}
+
+/// Replaces each condition of `tree` with the filter `rewrite` returns for it, if any.
+fn map_conditions<F>(tree: CompiledFilter, rewrite: &mut F) -> CompiledFilter
+where
+    F: FnMut(&str) -> Option<CompiledFilter>,
+{
+    match tree {
+        CompiledFilter::Condition(condition) => {
+            rewrite(&condition).unwrap_or(CompiledFilter::Condition(condition))
+        }
+        CompiledFilter::Not(filter) => {
+            CompiledFilter::Not(Box::new(map_conditions(*filter, rewrite)))
+        }
+        CompiledFilter::And(filters) => CompiledFilter::And(
+            filters
+                .into_iter()
+                .map(|filter| map_conditions(filter, rewrite))
+                .collect(),
+        ),
+        CompiledFilter::Or(filters) => CompiledFilter::Or(
+            filters
+                .into_iter()
+                .map(|filter| map_conditions(filter, rewrite))
+                .collect(),
+        ),
+    }
+}
+
+/// A condition of a filter comparing a field with a value, or with a range of values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub field: String,
+    pub operator: Operator,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operator {
+    Equal(String),
+    NotEqual(String),
+    GreaterThan(String),
+    GreaterThanOrEqual(String),
+    LowerThan(String),
+    LowerThanOrEqual(String),
+    Between(String, String),
+    /// Equal to one of the values.
+    In(Vec<String>),
+}
+
+impl Comparison {
+    /// Parses a condition with the filter parser of milli, or as `field IN [a, b]`, which milli
+    /// doesn't know about. `None` if it isn't a comparison, like the geo conditions, or if it
+    /// doesn't parse.
+    pub fn parse(condition: &str) -> Option<Self> {
+        let (fid, op) = match FilterCondition::parse(condition) {
+            Ok(Some(FilterCondition::Condition { fid, op })) => (fid, op),
+            _ => return parse_in(condition),
+        };
+        let operator = match op {
+            Condition::Equal(value) => Operator::Equal(value.to_string()),
+            Condition::NotEqual(value) => Operator::NotEqual(value.to_string()),
+            Condition::GreaterThan(value) => Operator::GreaterThan(value.to_string()),
+            Condition::GreaterThanOrEqual(value) => Operator::GreaterThanOrEqual(value.to_string()),
+            Condition::LowerThan(value) => Operator::LowerThan(value.to_string()),
+            Condition::LowerThanOrEqual(value) => Operator::LowerThanOrEqual(value.to_string()),
+            Condition::Between { from, to } => Operator::Between(from.to_string(), to.to_string()),
+            #[allow(unreachable_patterns)]
+            _ => return None,
+        };
+        Some(Self {
+            field: fid.to_string(),
+            operator,
+        })
+    }
+
+    /// Renders the comparison for the filter parser of milli. `None` if one of its values holds
+    /// both kinds of quotes, and can't be quoted.
+    pub fn render(&self) -> Option<CompiledFilter> {
+        let field = quote(&self.field)?;
+        let condition = match self.operator {
+            Operator::Equal(ref value) => format!("{} = {}", field, quote(value)?),
+            Operator::NotEqual(ref value) => format!("{} != {}", field, quote(value)?),
+            Operator::GreaterThan(ref value) => format!("{} > {}", field, quote(value)?),
+            Operator::GreaterThanOrEqual(ref value) => format!("{} >= {}", field, quote(value)?),
+            Operator::LowerThan(ref value) => format!("{} < {}", field, quote(value)?),
+            Operator::LowerThanOrEqual(ref value) => format!("{} <= {}", field, quote(value)?),
+            Operator::Between(ref from, ref to) => {
+                format!("{} {} TO {}", field, quote(from)?, quote(to)?)
+            }
+            Operator::In(ref values) => {
+                let equals = values
+                    .iter()
+                    .map(|value| {
+                        Some(CompiledFilter::Condition(format!(
+                            "{} = {}",
+                            field,
+                            quote(value)?
+                        )))
+                    })
+                    .collect::<Option<_>>()?;
+                return Some(CompiledFilter::Or(equals));
+            }
+        };
+        Some(CompiledFilter::Condition(condition))
+    }
+}
+
+/// Parses the condition `field IN [a, b]`.
+fn parse_in(condition: &str) -> Option<Comparison> {
+    let (field, rest) = split_word(condition.trim_start())?;
+    let mut rest = rest
+        .trim_start()
+        .strip_prefix("IN")?
+        .trim_start()
+        .strip_prefix('[')?;
+    let mut values = Vec::new();
+    loop {
+        let (value, after) = split_word(rest.trim_start())?;
+        values.push(value);
+        let after = after.trim_start();
+        match after.strip_prefix(',') {
+            Some(after) => rest = after,
+            None => {
+                rest = after.strip_prefix(']')?;
+                break;
+            }
+        }
+    }
+    rest.trim().is_empty().then(|| Comparison {
+        field,
+        operator: Operator::In(values),
+    })
+}
+
+/// Splits a word, or a quoted string, off the start of `input`.
+fn split_word(input: &str) -> Option<(String, &str)> {
+    match input.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = input[1..].find(quote)? + 1;
+            Some((input[1..end].to_string(), &input[end + 1..]))
+        }
+        _ => {
+            let end = input
+                .find(|c: char| c.is_whitespace() || matches!(c, ',' | '[' | ']'))
+                .unwrap_or(input.len());
+            (end > 0).then(|| (input[..end].to_string(), &input[end..]))
+        }
+    }
+}
+
+/// Quotes `value` for the filter parser of milli, unless it is a plain word.
+fn quote(value: &str) -> Option<String> {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !matches!(value, "AND" | "OR" | "NOT" | "TO");
+    if plain {
+        Some(value.to_string())
+    } else if !value.contains('"') {
+        Some(format!("\"{}\"", value))
+    } else if !value.contains('\'') {
+        Some(format!("'{}'", value))
+    } else {
+        None
+    }
+}
+
+/// Writes the documents of the batch read from `content_file` to a new file, after applying
+/// `derive` to each of them.
+fn derive_file(
+    content_file: File,
+    mut derive: impl FnMut(&mut Document) -> Result<()>,
+) -> Result<File> {
+    let mut reader = DocumentBatchReader::from_reader(content_file)?;
+    let mut derived = tempfile::tempfile()?;
+    let mut builder = DocumentBatchBuilder::new(BufWriter::new(&mut derived))?;
+    let mut buffer = Vec::new();
+
+    while let Some((index, document)) = reader.next_document_with_index()? {
+        let mut object = Map::new();
+        for (field_id, value) in document.iter() {
+            if let Some(name) = index.name(field_id) {
+                object.insert(name.to_string(), serde_json::from_slice(value)?);
+            }
+        }
+        derive(&mut object)?;
+
+        buffer.clear();
+        serde_json::to_writer(&mut buffer, &object)?;
+        builder.extend_from_json(Cursor::new(&buffer))?;
+    }
+
+    builder.finish()?;
+    derived.seek(SeekFrom::Start(0))?;
+    Ok(derived)
+}
+
+/// Derives the field of the documents of an addition, payload by payload.
+pub struct AdditionDerivation {
+    derivation: Derivation,
+    method: IndexDocumentsMethod,
+    primary_key: String,
+    /// The documents updated by the previous payloads of the addition, merged with the fields
+    /// they had, as milli will merge them.
+    updated: HashMap<String, Document>,
+    metadata_db: MetadataDatabase,
+    /// The time of the addition, when the metadata of the documents are recorded.
+    now: Option<i64>,
+    /// The metadata of the documents of the addition, by external id.
+    metadata: HashMap<String, DocumentMetadata>,
+}
+
+impl AdditionDerivation {
+    pub fn new(
+        index: &milli::Index,
+        metadata_db: MetadataDatabase,
+        txn: &RoTxn,
+        method: IndexDocumentsMethod,
+        primary_key: String,
+        now: i64,
+    ) -> Result<Self> {
+        let derivation = Derivation::read(index, txn)?;
+        let now = derivation.metadata.then(|| now);
+        Ok(Self {
+            derivation,
+            method,
+            primary_key,
+            updated: HashMap::new(),
+            metadata_db,
+            now,
+            metadata: HashMap::new(),
+        })
+    }
+
+    /// Records the sequence number of the next derived document, and the metadata of the
+    /// documents of the addition, once they are indexed.
+    pub fn finish(self, index: &milli::Index, txn: &mut RwTxn) -> Result<()> {
+        self.derivation.put_next_sequence(index, txn)?;
+        metadata::put_by_external_id(index, self.metadata_db, txn, self.metadata)
+    }
+
+    /// Returns the file of the documents of `content_file`, with their derived field.
+    pub fn derive_file(
+        &mut self,
+        index: &milli::Index,
+        txn: &RoTxn,
+        content_file: File,
+    ) -> Result<File> {
+        let external_documents_ids = index.external_documents_ids(txn)?;
+        derive_file(content_file, |document| {
+            let id = match document.get(&self.primary_key).and_then(external_id) {
+                Some(id) => id,
+                // milli refuses the document.
+                None => return Ok(()),
+            };
+            let docid = external_documents_ids.get(&id);
+            let metadata = match self.now {
+                Some(now) => {
+                    let metadata = metadata::next(self.metadata_db, txn, docid, now)?;
+                    self.metadata.insert(id.clone(), metadata);
+                    Some(metadata)
+                }
+                None => None,
+            };
+
+            if let IndexDocumentsMethod::ReplaceDocuments = self.method {
+                self.derivation.derive(document, metadata.as_ref());
+                return Ok(());
+            }
+
+            let mut merged = match self.updated.remove(&id) {
+                Some(merged) => merged,
+                None => match docid {
+                    Some(docid) => read_document(index, txn, docid)?,
+                    None => Map::new(),
+                },
+            };
+            merged.extend(document.iter().map(|(k, v)| (k.clone(), v.clone())));
+            self.derivation.derive(&mut merged, metadata.as_ref());
+            document.insert(DERIVED_FIELD.to_string(), merged[DERIVED_FIELD].clone());
+            self.updated.insert(id, merged);
+            Ok(())
+        })
+    }
+}
+
+/// Makes the derived field filterable and sortable in milli, and its segmented words searchable
+/// without typos, keeps the stop words apart from milli, and derives the field of the documents
+/// indexed before it was, by the previous versions.
+pub fn prepare(
+    index: &milli::Index,
+    metadata_db: MetadataDatabase,
+    txn: &mut RwTxn,
+    indexer_config: &IndexerConfig,
+) -> Result<()> {
+    if is_prepared(index, txn)? {
+        return Ok(());
+    }
+    match index.number_of_documents(txn)? {
+        0 => set_derived_settings(index, txn, indexer_config),
+        _ => rederive(index, metadata_db, txn, indexer_config),
+    }
+}
+
+fn is_prepared(index: &milli::Index, txn: &RoTxn) -> Result<bool> {
+    let searchable = index
+        .user_defined_searchable_fields(txn)?
+        .map_or(true, |fields| fields.contains(&SEGMENTS_FIELD));
+    Ok(index.filterable_fields(txn)?.contains(DERIVED_FIELD)
+        && index.sortable_fields(txn)?.contains(DERIVED_FIELD)
+        && index.exact_attributes(txn)?.contains(&SEGMENTS_FIELD)
+        && index.stop_words(txn)?.is_none()
+        && read_next_document_sequence(index, txn)?.is_some()
+        && searchable)
+}
+
+fn set_derived_settings(
+    index: &milli::Index,
+    txn: &mut RwTxn,
+    indexer_config: &IndexerConfig,
+) -> Result<()> {
+    if is_prepared(index, txn)? {
+        return Ok(());
+    }
+    let mut filterable = index.filterable_fields(txn)?;
+    filterable.insert(DERIVED_FIELD.to_string());
+    let mut sortable = index.sortable_fields(txn)?;
+    sortable.insert(DERIVED_FIELD.to_string());
+    let mut exact: HashSet<_> = index
+        .exact_attributes(txn)?
+        .into_iter()
+        .map(String::from)
+        .collect();
+    exact.insert(SEGMENTS_FIELD.to_string());
+    let searchable = index
+        .user_defined_searchable_fields(txn)?
+        .filter(|fields| !fields.contains(&SEGMENTS_FIELD))
+        .map(|fields| {
+            let mut fields: Vec<_> = fields.into_iter().map(String::from).collect();
+            fields.push(SEGMENTS_FIELD.to_string());
+            fields
+        });
+    // the documents indexed from now on are given sequence numbers.
+    if read_next_document_sequence(index, txn)?.is_none() {
+        put_next_document_sequence(index, txn, 0)?;
+    }
+    // milli doesn't index the stop words it is given, they are kept apart.
+    let milli_stop_words = index.stop_words(txn)?.is_some();
+    if milli_stop_words {
+        let stop_words = read_stop_words(index, txn)?;
+        put_stop_words(index, txn, &stop_words)?;
+    }
+
+    let mut builder = milli::update::Settings::new(txn, index, indexer_config);
+    builder.set_filterable_fields(filterable);
+    builder.set_sortable_fields(sortable);
+    builder.set_exact_attributes(exact);
+    if let Some(searchable) = searchable {
+        builder.set_searchable_fields(searchable);
+    }
+    if milli_stop_words {
+        builder.reset_stop_words();
+    }
+    builder.execute(|_| ())?;
+    Ok(())
+}
+
+/// Derives the field of all the live documents of the index again, and indexes them again from
+/// scratch. The soft deleted documents are purged along the way, and the metadata of the live
+/// ones follow them to the internal ids they are given.
+pub fn rederive(
+    index: &milli::Index,
+    metadata_db: MetadataDatabase,
+    txn: &mut RwTxn,
+    indexer_config: &IndexerConfig,
+) -> Result<()> {
+    let derivation = Derivation::read(index, txn)?;
+    let recorded = metadata::by_external_id(index, metadata_db, txn)?;
+    let fields_ids_map = index.fields_ids_map(txn)?;
+    let all_fields: Vec<_> = fields_ids_map.iter().map(|(id, _)| id).collect();
+    let soft_deleted = read_soft_deleted_documents(index, txn)?;
+
+    let mut documents_file = tempfile::tempfile()?;
+    let mut documents = DocumentBatchBuilder::new(BufWriter::new(&mut documents_file))?;
+    let mut buffer = Vec::new();
+    for entry in index.all_documents(txn)? {
+        let (docid, obkv) = entry?;
+        if soft_deleted.contains(docid) {
+            continue;
+        }
+
+        let mut document = obkv_to_json(&all_fields, &fields_ids_map, obkv)?;
+        let metadata = metadata::get(metadata_db, txn, docid)?;
+        derivation.derive(&mut document, metadata.as_ref());
+
+        buffer.clear();
+        serde_json::to_writer(&mut buffer, &document)?;
+        documents.extend_from_json(Cursor::new(&buffer))?;
+    }
+    let count = documents.finish()?;
+
+    milli::update::ClearDocuments::new(txn, index).execute()?;
+    super::index::clear_soft_deleted_documents(index, txn)?;
+    metadata::clear(metadata_db, txn)?;
+    set_derived_settings(index, txn, indexer_config)?;
+
+    // milli refuses to index an empty batch of documents.
+    if count > 0 {
+        documents_file.seek(SeekFrom::Start(0))?;
+        let mut builder = milli::update::IndexDocuments::new(
+            txn,
+            index,
+            indexer_config,
+            IndexDocumentsConfig::default(),
+            |_| (),
+        )?;
+        builder.add_documents(DocumentBatchReader::from_reader(documents_file)?)?;
+        builder.execute()?;
+    }
+    derivation.put_next_sequence(index, txn)?;
+    metadata::put_by_external_id(index, metadata_db, txn, recorded)?;
+
+    Ok(())
+}
+
+/// Derives the field of each document read from an index, or from a dump of an index, in the
+/// batch `content_file`, along with the `metadata` it had.
+pub fn derive_documents(
+    derivation: &Derivation,
+    content_file: File,
+    metadata: impl Fn(&Document) -> Option<DocumentMetadata>,
+) -> Result<File> {
+    derive_file(content_file, |document| {
+        let metadata = metadata(document);
+        derivation.derive(document, metadata.as_ref());
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::index::FacetCaseSensitivity;
+
+    #[test]
+    fn test_is_derived() {
+        assert!(is_derived("_meilisearch"));
+        assert!(is_derived("_meilisearch.facets.brand"));
+        assert!(!is_derived("_meilisearch_brand"));
+        assert!(!is_derived("brand"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("apple").unwrap(), "apple");
+        assert_eq!(quote("-1.5").unwrap(), "-1.5");
+        assert_eq!(quote("new york").unwrap(), "\"new york\"");
+        assert_eq!(quote("^ab").unwrap(), "\"^ab\"");
+        assert_eq!(quote("OR").unwrap(), "\"OR\"");
+        assert_eq!(quote("say \"hi\"").unwrap(), "'say \"hi\"'");
+        assert_eq!(quote("it's \"hi\""), None);
+    }
+
+    #[test]
+    fn test_rewrite_filter() {
+        let derivation = Derivation {
+            normalizations: BTreeMap::from([(
+                "brand".to_string(),
+                FacetNormalization {
+                    case_sensitivity: FacetCaseSensitivity::Insensitive,
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+
+        // the conditions are rewritten wherever they are, and combine with any operator.
+        let filter = json!("brand = \"APPLE \" OR (price > 10 AND NOT brand != Samsung)");
+        assert_eq!(
+            derivation.rewrite_filter(&filter),
+            json!("(_meilisearch.facets.brand = apple) OR ((price > 10) AND (NOT (_meilisearch.facets.brand != samsung)))")
+        );
+        let filter = json!([["brand = Apple", "brand = Sony"], "price > 10"]);
+        assert_eq!(
+            derivation.rewrite_filter(&filter),
+            json!("((_meilisearch.facets.brand = apple) OR (_meilisearch.facets.brand = sony)) AND (price > 10)")
+        );
+
+        let filter = json!("brand IN [Apple, \"Sony Group\"] AND NOT brand IN [samsung]");
+        assert_eq!(
+            derivation.rewrite_filter(&filter),
+            json!("((_meilisearch.facets.brand = apple) OR (_meilisearch.facets.brand = \"sony group\")) AND (NOT ((_meilisearch.facets.brand = samsung)))")
+        );
+
+        // the other attributes are left as they are, even when their name contains the attribute.
+        let filter = json!("my_brand = Apple AND title = brand");
+        assert_eq!(derivation.rewrite_filter(&filter), filter);
+        // so is a filter that doesn't parse.
+        let filter = json!("brand = (");
+        assert_eq!(derivation.rewrite_filter(&filter), filter);
+    }
+
+    #[test]
+    fn test_rewrite_elements() {
+        let derivation = Derivation {
+            element_attributes: vec!["variants".to_string()],
+            element_positions: BTreeMap::from([("variants".to_string(), 2)]),
+            ..Default::default()
+        };
+        let element = |position, conditions: &[&str]| {
+            let conditions: Vec<_> = conditions
+                .iter()
+                .map(|condition| {
+                    format!(
+                        "(_meilisearch.elements.variants.{}.{})",
+                        position, condition
+                    )
+                })
+                .collect();
+            format!(
+                "((_meilisearch.elements.variants.count > {}) AND {})",
+                position,
+                conditions.join(" AND ")
+            )
+        };
+        let alternatives = |conditions: &[&str]| {
+            format!("{} OR {}", element(0, conditions), element(1, conditions))
+        };
+
+        // the conditions combined with `AND` hold within the same element, in place of the first
+        // of them.
+        let filter = json!("variants.color = red AND genre = drama AND variants.size = L");
+        assert_eq!(
+            derivation.rewrite_filter(&filter),
+            json!(format!(
+                "({}) AND (genre = drama)",
+                alternatives(&["color = red", "size = L"])
+            ))
+        );
+        let filter = json!(["variants.color = red", "variants.size 38 TO 40"]);
+        assert_eq!(
+            derivation.rewrite_filter(&filter),
+            json!(alternatives(&["color = red", "size 38 TO 40"]))
+        );
+
+        // the conditions in other parentheses, or combined with `OR`, hold within any element.
+        let filter = json!("variants.color = red OR variants.size = L");
+        assert_eq!(
+            derivation.rewrite_filter(&filter),
+            json!(format!(
+                "({}) OR ({})",
+                alternatives(&["color = red"]),
+                alternatives(&["size = L"])
+            ))
+        );
+        let filter = json!("NOT variants.color = red");
+        assert_eq!(
+            derivation.rewrite_filter(&filter),
+            json!(format!("NOT ({})", alternatives(&["color = red"])))
+        );
+
+        // the fields that merely start like an attribute are left as they are.
+        let filter = json!("variantsCount > 1 AND variants = red");
+        assert_eq!(derivation.rewrite_filter(&filter), filter);
+    }
+}
@@ -1,5 +1,6 @@
+use std::collections::HashMap;
 use std::fs::{create_dir_all, File};
-use std::io::{BufReader, Seek, SeekFrom, Write};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
 use std::path::Path;
 
 use anyhow::Context;
@@ -12,8 +13,12 @@ use serde::{Deserialize, Serialize};
 use crate::document_formats::read_ndjson;
 use crate::index::updates::apply_settings_to_builder;
 
+use super::derived::{self, is_derived, Derivation};
+use super::edition::external_id;
 use super::error::Result;
-use super::{index::Index, Settings, Unchecked};
+use super::index::{apply_meilisearch_settings, open_milli_index, Index};
+use super::metadata::{self, DocumentMetadata};
+use super::{Settings, Unchecked};
 
 #[derive(Serialize, Deserialize)]
 struct DumpMeta {
@@ -23,6 +28,15 @@ struct DumpMeta {
 
 const META_FILE_NAME: &str = "meta.json";
 const DATA_FILE_NAME: &str = "documents.jsonl";
+const METADATA_FILE_NAME: &str = "metadata.jsonl";
+
+/// The metadata of a document, as written in a dump.
+#[derive(Serialize, Deserialize)]
+struct DumpDocumentMetadata {
+    id: String,
+    #[serde(flatten)]
+    metadata: DocumentMetadata,
+}
 
 impl Index {
     pub fn dump(&self, path: impl AsRef<Path>) -> Result<()> {
@@ -33,6 +47,7 @@ impl Index {
         create_dir_all(&path)?;
 
         self.dump_documents(&txn, &path)?;
+        self.dump_document_metadata(&txn, &path)?;
         self.dump_meta(&txn, &path)?;
 
         Ok(())
@@ -44,15 +59,23 @@ impl Index {
 
         let documents = self.all_documents(txn)?;
         let fields_ids_map = self.fields_ids_map(txn)?;
+        let soft_deleted = self.soft_deleted_documents(txn)?;
 
         // dump documents
         let mut json_map = IndexMap::new();
         for document in documents {
-            let (_, reader) = document?;
+            let (id, reader) = document?;
+            if soft_deleted.contains(id) {
+                continue;
+            }
 
+            // the derived values are derived again when the dump is loaded.
             for (fid, bytes) in reader.iter() {
-                if let Some(name) = fields_ids_map.name(fid) {
-                    json_map.insert(name, serde_json::from_slice::<serde_json::Value>(bytes)?);
+                match fields_ids_map.name(fid) {
+                    Some(name) if !is_derived(name) => {
+                        json_map.insert(name, serde_json::from_slice::<serde_json::Value>(bytes)?);
+                    }
+                    _ => (),
                 }
             }
 
@@ -65,6 +88,18 @@ impl Index {
         Ok(())
     }
 
+    fn dump_document_metadata(&self, txn: &RoTxn, path: impl AsRef<Path>) -> Result<()> {
+        let metadata_file_path = path.as_ref().join(METADATA_FILE_NAME);
+        let mut metadata_file = File::create(&metadata_file_path)?;
+
+        for (id, metadata) in metadata::by_external_id(self, self.document_metadata, txn)? {
+            serde_json::to_writer(&mut metadata_file, &DumpDocumentMetadata { id, metadata })?;
+            metadata_file.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
     fn dump_meta(&self, txn: &RoTxn, path: impl AsRef<Path>) -> Result<()> {
         let meta_file_path = path.as_ref().join(META_FILE_NAME);
         let mut meta_file = File::create(&meta_file_path)?;
@@ -105,7 +140,7 @@ impl Index {
 
         let mut options = EnvOpenOptions::new();
         options.map_size(size);
-        let index = milli::Index::new(options, &dst_dir_path)?;
+        let (index, metadata_db) = open_milli_index(options, &dst_dir_path)?;
 
         let mut txn = index.write_txn()?;
 
@@ -120,6 +155,20 @@ impl Index {
 
         builder.execute(|_| ())?;
 
+        apply_meilisearch_settings(&index, &mut txn, &settings)?;
+        derived::prepare(&index, metadata_db, &mut txn, indexer_config)?;
+
+        // the dumps of the previous versions don't contain the metadata of the documents.
+        let mut recorded = HashMap::new();
+        let metadata_file_path = src.as_ref().join(METADATA_FILE_NAME);
+        if metadata_file_path.exists() {
+            let reader = BufReader::new(File::open(&metadata_file_path)?);
+            for line in reader.lines() {
+                let DumpDocumentMetadata { id, metadata } = serde_json::from_str(&line?)?;
+                recorded.insert(id, metadata);
+            }
+        }
+
         let document_file_path = src.as_ref().join(DATA_FILE_NAME);
         let reader = BufReader::new(File::open(&document_file_path)?);
 
@@ -134,8 +183,14 @@ impl Index {
 
         if !empty {
             tmp_doc_file.seek(SeekFrom::Start(0))?;
+            let derivation = Derivation::read(&index, &txn)?;
+            let primary_key = index.primary_key(&txn)?.map(String::from);
+            let documents = derived::derive_documents(&derivation, tmp_doc_file, |document| {
+                let id = document.get(primary_key.as_ref()?).and_then(external_id)?;
+                recorded.get(&id).copied()
+            })?;
 
-            let documents_reader = DocumentBatchReader::from_reader(tmp_doc_file)?;
+            let documents_reader = DocumentBatchReader::from_reader(documents)?;
 
             //If the document file is empty, we don't perform the document addition, to prevent
             //a primary key error to be thrown.
@@ -149,8 +204,11 @@ impl Index {
             )?;
             builder.add_documents(documents_reader)?;
             builder.execute()?;
+            derivation.put_next_sequence(&index, &mut txn)?;
         }
 
+        metadata::put_by_external_id(&index, metadata_db, &mut txn, recorded)?;
+
         txn.commit()?;
         index.prepare_for_closing().wait();
 
@@ -0,0 +1,396 @@
+//! Edition of the documents of an index in place.
+//!
+//! A document edition applies a list of declarative edits to the documents matching a filter.
+//! The edits are deliberately limited to operations that can't loop nor grow a document without
+//! bound: setting, renaming or deleting a field, changing the case of a string, and incrementing
+//! a number. The edited documents replace the original ones chunk after chunk, each chunk in its
+//! own transaction, so that editing millions of documents never holds a huge transaction. An
+//! edition that fails keeps the chunks it already edited.
+//!
+//! An edit that doesn't apply to the value of a field, such as incrementing a string, doesn't fail
+//! the whole edition: the document is left untouched, and counted as invalid.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Seek, SeekFrom};
+
+use log::{debug, info};
+use milli::documents::{DocumentBatchBuilder, DocumentBatchReader};
+use milli::update::{IndexDocumentsConfig, IndexDocumentsMethod, UpdateIndexingStep};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use time::OffsetDateTime;
+
+use super::derived::{self, Derivation};
+use super::error::{IndexError, Result};
+use super::index::Index;
+use super::metadata;
+use super::search::parse_filter;
+
+/// The number of documents edited in a single transaction.
+const EDITION_CHUNK_SIZE: usize = 10_000;
+
+/// An edit of a top-level field of the documents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase", deny_unknown_fields)]
+pub enum DocumentEdit {
+    /// Sets the field to `value`, whether the document has the field or not.
+    Set {
+        field: String,
+        value: Value,
+    },
+    /// Moves the value of the field to the field `to`, replacing the value `to` had.
+    Rename {
+        field: String,
+        to: String,
+    },
+    Delete {
+        field: String,
+    },
+    Lowercase {
+        field: String,
+    },
+    Uppercase {
+        field: String,
+    },
+    /// Adds `by`, which can be negative, to the number of the field.
+    Increment {
+        field: String,
+        by: Number,
+    },
+}
+
+impl DocumentEdit {
+    /// The fields whose value the edit changes.
+    fn fields(&self) -> impl Iterator<Item = &str> {
+        let (field, to) = match self {
+            DocumentEdit::Rename { field, to } => (field, Some(to)),
+            DocumentEdit::Set { field, .. }
+            | DocumentEdit::Delete { field }
+            | DocumentEdit::Lowercase { field }
+            | DocumentEdit::Uppercase { field }
+            | DocumentEdit::Increment { field, .. } => (field, None),
+        };
+        std::iter::once(field.as_str()).chain(to.map(String::as_str))
+    }
+
+    /// Applies the edit to `document`, and returns whether it changed the document. Returns
+    /// `None` if the value of the field isn't of a type the edit applies to. The edits of a field
+    /// that the document doesn't have change nothing, except `set`.
+    fn apply(&self, document: &mut Map<String, Value>) -> Option<bool> {
+        match self {
+            DocumentEdit::Set { field, value } => {
+                let previous = document.insert(field.clone(), value.clone());
+                Some(previous.as_ref() != Some(value))
+            }
+            DocumentEdit::Rename { field, to } => match document.remove(field) {
+                Some(value) => {
+                    document.insert(to.clone(), value);
+                    Some(true)
+                }
+                None => Some(false),
+            },
+            DocumentEdit::Delete { field } => Some(document.remove(field).is_some()),
+            DocumentEdit::Lowercase { field } => change_case(document, field, str::to_lowercase),
+            DocumentEdit::Uppercase { field } => change_case(document, field, str::to_uppercase),
+            DocumentEdit::Increment { field, by } => match document.get_mut(field) {
+                None | Some(Value::Null) => Some(false),
+                Some(Value::Number(number)) => {
+                    *number = add(number, by)?;
+                    Some(true)
+                }
+                Some(_) => None,
+            },
+        }
+    }
+}
+
+fn change_case(
+    document: &mut Map<String, Value>,
+    field: &str,
+    change: fn(&str) -> String,
+) -> Option<bool> {
+    match document.get_mut(field) {
+        None | Some(Value::Null) => Some(false),
+        Some(Value::String(s)) => {
+            let changed = change(s);
+            let edited = changed != *s;
+            *s = changed;
+            Some(edited)
+        }
+        Some(_) => None,
+    }
+}
+
+/// Adds two numbers, as integers when both are integers and the sum doesn't overflow. Returns
+/// `None` if the sum isn't a finite number.
+fn add(lhs: &Number, rhs: &Number) -> Option<Number> {
+    if let (Some(lhs), Some(rhs)) = (lhs.as_i64(), rhs.as_i64()) {
+        if let Some(sum) = lhs.checked_add(rhs) {
+            return Some(sum.into());
+        }
+    }
+    Number::from_f64(lhs.as_f64()? + rhs.as_f64()?)
+}
+
+/// Applies all the `edits` to `document`, in order. Returns whether the document changed, or
+/// `None` if one of the edits doesn't apply to it, in which case the document must be left as it
+/// was.
+fn apply_edits(edits: &[DocumentEdit], document: &mut Map<String, Value>) -> Option<bool> {
+    let mut edited = false;
+    for edit in edits {
+        edited |= edit.apply(document)?;
+    }
+    Some(edited)
+}
+
+/// Returns an error if `edits` can't be applied to any document, whatever the index.
+pub fn validate_edits(edits: &[DocumentEdit]) -> Result<()> {
+    if edits.is_empty() {
+        return Err(IndexError::InvalidDocumentEdition(
+            "`edits` must contain at least one edit.".to_string(),
+        ));
+    }
+
+    for edit in edits {
+        if edit.fields().any(str::is_empty) {
+            return Err(IndexError::InvalidDocumentEdition(
+                "The field of an edit can't be empty.".to_string(),
+            ));
+        }
+        if let DocumentEdit::Rename { field, to } = edit {
+            if field == to {
+                return Err(IndexError::InvalidDocumentEdition(format!(
+                    "The field `{}` can't be renamed to itself.",
+                    field
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The external id of a document, from the value of its primary key.
+pub(super) fn external_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DocumentsEditionResult {
+    /// The number of documents matching the filter.
+    pub matched_documents: u64,
+    /// The number of documents that were changed by the edits, and indexed again.
+    pub edited_documents: u64,
+    /// The number of documents left untouched because an edit didn't apply to them.
+    pub invalid_documents: u64,
+}
+
+impl Index {
+    /// Applies `edits` to the documents matching `filter`, to all the documents if there is no
+    /// filter. `progress` is called before each chunk with the number of matching documents seen
+    /// so far, and the number of matching documents.
+    pub fn edit_documents(
+        &self,
+        filter: Option<&Value>,
+        edits: &[DocumentEdit],
+        progress: impl Fn(usize, usize),
+    ) -> Result<DocumentsEditionResult> {
+        validate_edits(edits)?;
+
+        let mut txn = self.write_txn()?;
+        derived::prepare(
+            self,
+            self.document_metadata,
+            &mut txn,
+            self.indexer_config.as_ref(),
+        )?;
+        txn.commit()?;
+
+        let txn = self.read_txn()?;
+        let primary_key = match self.primary_key(&txn)? {
+            Some(primary_key) => primary_key.to_string(),
+            // an index without primary key has no documents.
+            None => return Ok(DocumentsEditionResult::default()),
+        };
+        if edits
+            .iter()
+            .any(|edit| edit.fields().any(|f| f == primary_key))
+        {
+            return Err(IndexError::InvalidDocumentEdition(format!(
+                "The edits can't change the primary key `{}` of the documents.",
+                primary_key
+            )));
+        }
+
+        let derivation = Derivation::read(self, &txn)?;
+        let filter = filter.map(|filter| derivation.rewrite_filter(filter));
+        let mut candidates = match filter.as_ref().map(parse_filter).transpose()?.flatten() {
+            Some(filter) => filter.evaluate(&txn, self)?,
+            None => self.documents_ids(&txn)?,
+        };
+        candidates -= self.soft_deleted_documents(&txn)?;
+        let record_metadata = self.document_metadata_enabled(&txn)?;
+        drop(txn);
+
+        let mut result = DocumentsEditionResult {
+            matched_documents: candidates.len(),
+            ..Default::default()
+        };
+        let candidates: Vec<_> = candidates.into_iter().collect();
+        for (i, chunk) in candidates.chunks(EDITION_CHUNK_SIZE).enumerate() {
+            progress(i * EDITION_CHUNK_SIZE, candidates.len());
+
+            let mut txn = self.write_txn()?;
+            let fields_ids_map = self.fields_ids_map(&txn)?;
+
+            let mut documents_file = tempfile::tempfile()?;
+            let mut documents = DocumentBatchBuilder::new(&mut documents_file)?;
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let mut edited_metadata = HashMap::new();
+            let mut buf = Vec::new();
+            for (docid, reader) in self.documents(&txn, chunk.iter().copied())? {
+                let mut document = Map::new();
+                for (fid, bytes) in reader.iter() {
+                    if let Some(name) = fields_ids_map.name(fid) {
+                        document.insert(name.to_string(), serde_json::from_slice(bytes)?);
+                    }
+                }
+
+                match apply_edits(edits, &mut document) {
+                    Some(true) => {
+                        let metadata = match record_metadata {
+                            true => Some(metadata::next(
+                                self.document_metadata,
+                                &txn,
+                                Some(docid),
+                                now,
+                            )?),
+                            false => None,
+                        };
+                        derivation.derive(&mut document, metadata.as_ref());
+                        if let Some((id, metadata)) = document
+                            .get(&primary_key)
+                            .and_then(external_id)
+                            .zip(metadata)
+                        {
+                            edited_metadata.insert(id, metadata);
+                        }
+                        serde_json::to_writer(&mut buf, &document)?;
+                        documents.extend_from_json(Cursor::new(&buf))?;
+                        buf.clear();
+                    }
+                    Some(false) => (),
+                    None => result.invalid_documents += 1,
+                }
+            }
+            let count = documents.finish()?;
+            // milli refuses to index an empty batch of documents.
+            if count == 0 {
+                continue;
+            }
+
+            documents_file.seek(SeekFrom::Start(0))?;
+            let config = IndexDocumentsConfig {
+                update_method: IndexDocumentsMethod::ReplaceDocuments,
+                ..Default::default()
+            };
+            let mut builder = milli::update::IndexDocuments::new(
+                &mut txn,
+                self,
+                self.indexer_config.as_ref(),
+                config,
+                |step: UpdateIndexingStep| debug!("update: {:?}", step),
+            )?;
+            builder.add_documents(DocumentBatchReader::from_reader(documents_file)?)?;
+            builder.execute()?;
+            derivation.put_next_sequence(self, &mut txn)?;
+
+            metadata::put_by_external_id(self, self.document_metadata, &mut txn, edited_metadata)?;
+
+            txn.commit()?;
+
+            result.edited_documents += count as u64;
+        }
+
+        info!("document edition done: {:?}", result);
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn edit(edit: Value) -> DocumentEdit {
+        serde_json::from_value(edit).unwrap()
+    }
+
+    fn document(document: Value) -> Map<String, Value> {
+        match document {
+            Value::Object(document) => document,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let edits = [
+            edit(json!({ "op": "lowercase", "field": "brand" })),
+            edit(json!({ "op": "rename", "field": "qty", "to": "stock" })),
+            edit(json!({ "op": "increment", "field": "stock", "by": -2 })),
+            edit(json!({ "op": "increment", "field": "price", "by": 0.5 })),
+            edit(json!({ "op": "delete", "field": "legacy" })),
+            edit(json!({ "op": "set", "field": "reviewed", "value": true })),
+        ];
+
+        let mut doc = document(json!({
+            "id": 1, "brand": "ACME", "qty": 10, "price": 2, "legacy": "x"
+        }));
+        assert_eq!(apply_edits(&edits, &mut doc), Some(true));
+        assert_eq!(
+            Value::Object(doc),
+            json!({ "id": 1, "brand": "acme", "stock": 8, "price": 2.5, "reviewed": true })
+        );
+
+        // the edits of missing fields change nothing.
+        let edits = [
+            edit(json!({ "op": "uppercase", "field": "brand" })),
+            edit(json!({ "op": "increment", "field": "stock", "by": 1 })),
+        ];
+        let mut doc = document(json!({ "id": 2 }));
+        assert_eq!(apply_edits(&edits, &mut doc), Some(false));
+
+        // an edit that doesn't apply to the value of the field makes the document invalid.
+        let mut doc = document(json!({ "id": 3, "stock": "many" }));
+        assert_eq!(apply_edits(&edits, &mut doc), None);
+        let mut doc = document(json!({ "id": 4, "stock": u64::MAX }));
+        assert_eq!(apply_edits(&edits, &mut doc), Some(true));
+    }
+
+    #[test]
+    fn test_invalid_edits() {
+        assert!(
+            serde_json::from_value::<DocumentEdit>(json!({ "op": "eval", "field": "a" })).is_err()
+        );
+        assert!(serde_json::from_value::<DocumentEdit>(
+            json!({ "op": "delete", "field": "a", "value": 1 })
+        )
+        .is_err());
+
+        assert!(validate_edits(&[]).is_err());
+        assert!(validate_edits(&[edit(json!({ "op": "delete", "field": "" }))]).is_err());
+        assert!(
+            validate_edits(&[edit(json!({ "op": "rename", "field": "a", "to": "a" }))]).is_err()
+        );
+        assert!(
+            validate_edits(&[edit(json!({ "op": "rename", "field": "a", "to": "b" }))]).is_ok()
+        );
+    }
+}
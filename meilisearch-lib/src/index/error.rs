@@ -3,6 +3,8 @@ use std::error::Error;
 use meilisearch_error::{internal_error, Code, ErrorCode};
 use serde_json::Value;
 
+use crate::document_formats::DocumentFormatError;
+use crate::segmenters::SegmenterError;
 use crate::{error::MilliError, update_file_store};
 
 pub type Result<T> = std::result::Result<T, IndexError>;
@@ -10,31 +12,116 @@ pub type Result<T> = std::result::Result<T, IndexError>;
 #[derive(Debug, thiserror::Error)]
 pub enum IndexError {
     #[error("An internal error has occurred. `{0}`.")]
-    Internal(Box<dyn Error + Send + Sync + 'static>),
+    Internal(#[source] Box<dyn Error + Send + Sync + 'static>),
     #[error("Document `{0}` not found.")]
     DocumentNotFound(String),
+    #[error("The payload contains several documents with the same primary key: {}.", format_ids(.0))]
+    DuplicateDocuments(Vec<String>),
+    #[error("The update file of the payload is corrupted: it was persisted with {expected_size} bytes and a sha256 of `{expected_sha256}`, but it now has {size} bytes and a sha256 of `{sha256}`.")]
+    CorruptedPayload {
+        expected_size: u64,
+        size: u64,
+        expected_sha256: String,
+        sha256: String,
+    },
     #[error("{0}")]
     Facet(#[from] FacetError),
     #[error("{0}")]
+    Aggregate(#[from] AggregateError),
+    #[error("{0}")]
     Milli(#[from] milli::Error),
+    #[error(
+        "Unsupported settings export version `{0}`, expected version `{}`.",
+        super::settings_export::SETTINGS_EXPORT_VERSION
+    )]
+    UnsupportedSettingsExportVersion(u32),
+    #[error("Invalid boost weight `{0}`: the weight of a boost must be a positive number.")]
+    InvalidSearchBoost(f64),
+    #[error("Documents not found in `likeDocumentIds`: {}.", format_ids(.0))]
+    LikeDocumentsNotFound(Vec<String>),
+    #[error("A search can't have both a `q` and `likeDocumentIds`: the query is built from the documents.")]
+    LikeDocumentsWithQuery,
+    #[error("Invalid document edition: {0}")]
+    InvalidDocumentEdition(String),
+    #[error("Invalid weight for the searchable attribute `{0}`: the weight of an attribute must be a positive integer.")]
+    InvalidSearchableWeight(String),
+    #[error("Too many reads of the index are running at once. Retry later.")]
+    TooManyConcurrentReads,
+    #[error("Attribute `{0}` is not sortable. Only sortable attributes can be used in the custom ranking rules of `rankingRulesOverride`.")]
+    UnsortableRankingRule(String),
+    #[error("Invalid document transform: {0}")]
+    InvalidDocumentTransform(String),
+    #[error("`{0}` is not a valid cursor. A cursor is the `nextCursor` of a previous page of documents.")]
+    InvalidDocumentsCursor(String),
+    #[error("The settings preview exceeded its time budget of {0}s.")]
+    SettingsPreviewTimeout(u64),
+    #[error("Invalid `groupBy`: {0}")]
+    InvalidSearchGroupBy(String),
+    #[error("{0}")]
+    Segmenter(#[from] SegmenterError),
+}
+
+impl From<milli::heed::Error> for IndexError {
+    fn from(other: milli::heed::Error) -> Self {
+        match other {
+            milli::heed::Error::Mdb(milli::heed::MdbError::ReadersFull) => {
+                Self::TooManyConcurrentReads
+            }
+            other => Self::Internal(Box::new(other)),
+        }
+    }
 }
 
 internal_error!(
     IndexError: std::io::Error,
-    milli::heed::Error,
     fst::Error,
     serde_json::Error,
     update_file_store::UpdateFileStoreError,
-    milli::documents::Error
+    milli::documents::Error,
+    DocumentFormatError
 );
 
+/// The number of document ids listed in an error message.
+const MAX_LISTED_IDS: usize = 20;
+
+fn format_ids(ids: &[String]) -> String {
+    let mut listed: Vec<_> = ids
+        .iter()
+        .take(MAX_LISTED_IDS)
+        .map(|id| format!("`{}`", id))
+        .collect();
+    if ids.len() > MAX_LISTED_IDS {
+        listed.push(format!("and {} more", ids.len() - MAX_LISTED_IDS));
+    }
+    listed.join(", ")
+}
+
 impl ErrorCode for IndexError {
     fn error_code(&self) -> Code {
         match self {
             IndexError::Internal(_) => Code::Internal,
             IndexError::DocumentNotFound(_) => Code::DocumentNotFound,
+            IndexError::DuplicateDocuments(_) => Code::DuplicateDocuments,
+            IndexError::CorruptedPayload { .. } => Code::CorruptedPayload,
             IndexError::Facet(e) => e.error_code(),
+            IndexError::Aggregate(e) => e.error_code(),
             IndexError::Milli(e) => MilliError(e).error_code(),
+            IndexError::UnsupportedSettingsExportVersion(_) => Code::InvalidSettingsExport,
+            IndexError::InvalidSearchBoost(_) => Code::InvalidSearchBoost,
+            IndexError::LikeDocumentsNotFound(_) => Code::LikeDocumentNotFound,
+            IndexError::LikeDocumentsWithQuery => Code::InvalidSearchLikeDocuments,
+            IndexError::InvalidDocumentEdition(_) => Code::InvalidDocumentEdition,
+            IndexError::InvalidSearchableWeight(_) => Code::InvalidSearchableWeight,
+            IndexError::TooManyConcurrentReads => Code::TooManyConcurrentReads,
+            IndexError::UnsortableRankingRule(_) => Code::InvalidRankingRule,
+            IndexError::InvalidDocumentTransform(_) => Code::InvalidDocumentTransform,
+            IndexError::InvalidDocumentsCursor(_) => Code::InvalidDocumentsCursor,
+            IndexError::SettingsPreviewTimeout(_) => Code::SettingsPreviewTimeout,
+            IndexError::InvalidSearchGroupBy(_) => Code::InvalidSearchGroupBy,
+            IndexError::Segmenter(SegmenterError::MissingSegmenter { .. }) => {
+                Code::MissingSegmenter
+            }
+            IndexError::Segmenter(_) => Code::InvalidCustomSegmenters,
         }
     }
 }
@@ -52,3 +139,24 @@ impl ErrorCode for FacetError {
         }
     }
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum AggregateError {
+    #[error("Invalid metric `{0}`: expected `count`, `min(field)`, `max(field)`, `sum(field)` or `avg(field)`.")]
+    InvalidMetric(String),
+    #[error("Cannot compute `{metric}`: the field `{field}` contains non-numeric values.")]
+    NonNumericField { metric: String, field: String },
+    #[error("Attribute `{0}` is not filterable. Only filterable attributes can be aggregated.")]
+    NotFilterable(String),
+}
+
+impl ErrorCode for AggregateError {
+    fn error_code(&self) -> Code {
+        match self {
+            AggregateError::InvalidMetric(_) | AggregateError::NonNumericField { .. } => {
+                Code::InvalidAggregateMetric
+            }
+            AggregateError::NotFilterable(_) => Code::InvalidAggregateField,
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! Normalization of the facet values of some attributes.
+//!
+//! milli compares the string facet values without regard to their case, but it knows nothing
+//! about their compatibility forms, like the full-width letters, and reports one of the original
+//! forms of a value in the facet distribution, whichever it indexed first.
+//!
+//! The `facetNormalization` setting tells, for an attribute, whether its values are compared with
+//! or without regard to their case, after an optional NFKC normalization. The normalized values of
+//! these attributes are derived from the documents when they are indexed, in the field
+//! `_meilisearch.facets.<attribute>`, and the original forms of their strings in the field
+//! `_meilisearch.facetForms.<attribute>`. milli lowercases the facet strings, so the derived
+//! strings are encoded to keep their case: an uppercase letter is written lowercase after a `^`,
+//! and a `^` is doubled. The documents keep their original values.
+//!
+//! The conditions of a filter comparing a normalized attribute with a value are rewritten into
+//! conditions on its normalized values, evaluated by milli like the rest of the filter, whether
+//! the attribute is filterable or not. Its facet distribution is counted by milli on its derived
+//! fields, and each value is reported in its most frequent original form. Changing the setting
+//! derives the values of all the documents again.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use unicode_normalization::UnicodeNormalization;
+
+use super::derived::{Comparison, Operator, DERIVED_FIELD};
+
+/// Whether the facet values of an attribute are compared with regard to their case.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FacetCaseSensitivity {
+    Sensitive,
+    Insensitive,
+}
+
+impl Default for FacetCaseSensitivity {
+    fn default() -> Self {
+        Self::Insensitive
+    }
+}
+
+/// The Unicode normalization applied to the facet values of an attribute.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UnicodeForm {
+    None,
+    /// The compatibility forms are folded, `ｱ` and `ア` or `ﬁ` and `fi` are the same value.
+    Nfkc,
+}
+
+impl Default for UnicodeForm {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// How the facet values of an attribute are normalized before being compared.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FacetNormalization {
+    #[serde(default)]
+    pub case_sensitivity: FacetCaseSensitivity,
+    #[serde(default)]
+    pub unicode_normalization: UnicodeForm,
+}
+
+impl FacetNormalization {
+    pub fn normalize(&self, value: &str) -> String {
+        let value = match self.unicode_normalization {
+            UnicodeForm::None => value.trim().to_string(),
+            UnicodeForm::Nfkc => value.trim().nfkc().collect(),
+        };
+        match self.case_sensitivity {
+            FacetCaseSensitivity::Sensitive => value,
+            FacetCaseSensitivity::Insensitive => value.to_lowercase(),
+        }
+    }
+}
+
+/// The derived field holding the normalized values of `attribute`, and the one holding the
+/// original forms of its strings.
+pub fn derived_fields(attribute: &str) -> (String, String) {
+    (
+        format!("{}.facets.{}", DERIVED_FIELD, attribute),
+        format!("{}.facetForms.{}", DERIVED_FIELD, attribute),
+    )
+}
+
+/// Encodes a string so that its case survives the lowercasing of milli.
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '^' {
+            encoded.push_str("^^");
+        } else if c.to_lowercase().eq(std::iter::once(c)) {
+            encoded.push(c);
+        } else {
+            encoded.push('^');
+            encoded.extend(c.to_lowercase());
+        }
+    }
+    encoded
+}
+
+/// Decodes an encoded string, but for the few letters whose lowercase doesn't go back to them.
+fn decode(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' => match chars.next() {
+                Some('^') | None => decoded.push('^'),
+                Some(c) => decoded.extend(c.to_uppercase()),
+            },
+            c => decoded.push(c),
+        }
+    }
+    decoded
+}
+
+/// Returns the value of `attribute` in `document`, the dots of its name going into the objects.
+fn lookup<'a>(document: &'a Map<String, Value>, attribute: &str) -> Option<&'a Value> {
+    let mut segments = attribute.split('.');
+    let mut value = document.get(segments.next()?)?;
+    for segment in segments {
+        value = value.as_object()?.get(segment)?;
+    }
+    Some(value)
+}
+
+/// Adds the normalized values of the normalized attributes of `document`, and the original forms
+/// of their strings, to its `derived` values.
+pub fn derive(
+    document: &Map<String, Value>,
+    normalizations: &BTreeMap<String, FacetNormalization>,
+    derived: &mut Map<String, Value>,
+) {
+    fn collect<'a>(value: &'a Value, values: &mut Vec<&'a Value>) {
+        match value {
+            Value::Array(array) => array.iter().for_each(|value| collect(value, values)),
+            Value::Null | Value::Object(_) => (),
+            value => values.push(value),
+        }
+    }
+
+    let mut facets = Map::new();
+    let mut forms = Map::new();
+    for (attribute, normalization) in normalizations {
+        let value = match lookup(document, attribute) {
+            Some(value) => value,
+            None => continue,
+        };
+        let mut originals = Vec::new();
+        collect(value, &mut originals);
+
+        let mut values = Vec::new();
+        let mut strings = Vec::new();
+        for original in originals {
+            // the numbers are kept as they are, so that they can be compared with a range.
+            let value = match original {
+                Value::String(original) => {
+                    strings.push(Value::String(encode(original)));
+                    Value::String(encode(&normalization.normalize(original)))
+                }
+                Value::Bool(original) => Value::String(original.to_string()),
+                original => original.clone(),
+            };
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        if !values.is_empty() {
+            facets.insert(attribute.clone(), Value::Array(values));
+        }
+        if !strings.is_empty() {
+            forms.insert(attribute.clone(), Value::Array(strings));
+        }
+    }
+
+    if !facets.is_empty() {
+        derived.insert("facets".to_string(), Value::Object(facets));
+    }
+    if !forms.is_empty() {
+        derived.insert("facetForms".to_string(), Value::Object(forms));
+    }
+}
+
+/// Rewrites a comparison of a normalized attribute into a comparison of its normalized values.
+pub fn rewrite(
+    comparison: &Comparison,
+    normalizations: &BTreeMap<String, FacetNormalization>,
+) -> Option<Comparison> {
+    let normalization = normalizations.get(&comparison.field)?;
+    let normalize = |value: &str| encode(&normalization.normalize(value));
+    let operator = match comparison.operator {
+        Operator::Equal(ref value) => Operator::Equal(normalize(value)),
+        Operator::NotEqual(ref value) => Operator::NotEqual(normalize(value)),
+        Operator::In(ref values) => {
+            Operator::In(values.iter().map(|value| normalize(value)).collect())
+        }
+        // milli only compares the numbers with a range.
+        ref operator => operator.clone(),
+    };
+    Some(Comparison {
+        field: derived_fields(&comparison.field).0,
+        operator,
+    })
+}
+
+/// Returns the facet distribution of a normalized attribute, from the distributions of its
+/// normalized `values` and of their original `forms`: each value is reported in its most frequent
+/// original form, the first one in the order of the strings on a tie. Only the first `max_values`
+/// values are reported.
+pub fn distribution(
+    normalization: &FacetNormalization,
+    values: BTreeMap<String, u64>,
+    forms: &BTreeMap<String, u64>,
+    max_values: usize,
+) -> BTreeMap<String, u64> {
+    // the most frequent original form of each normalized value.
+    let mut representatives: HashMap<String, (String, u64)> = HashMap::new();
+    for (form, &count) in forms {
+        let original = decode(form);
+        let value = encode(&normalization.normalize(&original));
+        match representatives.get(&value) {
+            Some(&(_, most_frequent)) if most_frequent >= count => (),
+            _ => {
+                representatives.insert(value, (original, count));
+            }
+        }
+    }
+
+    values
+        .into_iter()
+        .take(max_values)
+        .map(|(value, count)| match representatives.remove(&value) {
+            Some((representative, _)) => (representative, count),
+            None => (decode(&value), count),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn normalizations() -> BTreeMap<String, FacetNormalization> {
+        BTreeMap::from([
+            ("brand".to_string(), FacetNormalization::default()),
+            (
+                "maker.name".to_string(),
+                FacetNormalization {
+                    case_sensitivity: FacetCaseSensitivity::Insensitive,
+                    unicode_normalization: UnicodeForm::Nfkc,
+                },
+            ),
+            (
+                "sku".to_string(),
+                FacetNormalization {
+                    case_sensitivity: FacetCaseSensitivity::Sensitive,
+                    unicode_normalization: UnicodeForm::None,
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalizations = normalizations();
+        assert_eq!(normalizations["brand"].normalize(" Apple "), "apple");
+        assert_eq!(
+            normalizations["brand"].normalize("ＡＰＰＬＥ"),
+            "ａｐｐｌｅ"
+        );
+        assert_eq!(
+            normalizations["maker.name"].normalize("ＡＰＰＬＥ"),
+            "apple"
+        );
+        assert_eq!(normalizations["sku"].normalize("Ab-1"), "Ab-1");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("Ab^1"), "^ab^^1");
+        assert_eq!(encode("ab-1"), "ab-1");
+        // the encoded strings are left as they are by the lowercasing of milli.
+        assert_eq!(encode("ÀB^").to_lowercase(), encode("ÀB^"));
+        for value in ["Ab^1", "ab-1", "ÀB^", "^", "Σ"] {
+            assert_eq!(decode(&encode(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_derive() {
+        let document = json!({
+            "brand": ["Apple", "APPLE", "Samsung"],
+            "maker": { "name": "ｿﾆｰ" },
+            "sku": 12,
+            "title": "Phone",
+        });
+        let mut derived = Map::new();
+        derive(
+            document.as_object().unwrap(),
+            &normalizations(),
+            &mut derived,
+        );
+        let expected = json!({
+            "facets": {
+                "brand": ["apple", "samsung"],
+                "maker.name": ["ソニー"],
+                "sku": [12],
+            },
+            "facetForms": {
+                "brand": ["^apple", "^a^p^p^l^e", "^samsung"],
+                "maker.name": ["ｿﾆｰ"],
+            },
+        });
+        assert_eq!(Value::Object(derived), expected);
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let normalizations = normalizations();
+        let rewrite = |field: &str, operator| {
+            let comparison = Comparison {
+                field: field.to_string(),
+                operator,
+            };
+            rewrite(&comparison, &normalizations)
+        };
+
+        assert_eq!(
+            rewrite("brand", Operator::Equal("APPLE ".to_string())),
+            Some(Comparison {
+                field: "_meilisearch.facets.brand".to_string(),
+                operator: Operator::Equal("apple".to_string()),
+            })
+        );
+        assert_eq!(
+            rewrite("sku", Operator::NotEqual("Ab-1".to_string())),
+            Some(Comparison {
+                field: "_meilisearch.facets.sku".to_string(),
+                operator: Operator::NotEqual("^ab-1".to_string()),
+            })
+        );
+        assert_eq!(
+            rewrite("sku", Operator::GreaterThan("10".to_string())),
+            Some(Comparison {
+                field: "_meilisearch.facets.sku".to_string(),
+                operator: Operator::GreaterThan("10".to_string()),
+            })
+        );
+        assert_eq!(
+            rewrite(
+                "brand",
+                Operator::In(vec!["Apple".to_string(), "SONY".to_string()])
+            ),
+            Some(Comparison {
+                field: "_meilisearch.facets.brand".to_string(),
+                operator: Operator::In(vec!["apple".to_string(), "sony".to_string()]),
+            })
+        );
+        assert_eq!(rewrite("title", Operator::Equal("Phone".to_string())), None);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let values = BTreeMap::from([
+            ("apple".to_string(), 3),
+            ("samsung".to_string(), 2),
+            ("xiaomi".to_string(), 1),
+        ]);
+        // the forms are counted by milli on the encoded strings.
+        let forms = BTreeMap::from([
+            ("apple".to_string(), 1),
+            ("^apple".to_string(), 2),
+            ("samsung".to_string(), 1),
+            ("^samsung".to_string(), 1),
+        ]);
+        let normalization = FacetNormalization::default();
+        assert_eq!(
+            distribution(&normalization, values.clone(), &forms, 100),
+            BTreeMap::from([
+                ("Apple".to_string(), 3),
+                ("Samsung".to_string(), 2),
+                ("xiaomi".to_string(), 1),
+            ])
+        );
+        assert_eq!(
+            distribution(&normalization, values, &forms, 1),
+            BTreeMap::from([("Apple".to_string(), 3)])
+        );
+    }
+}
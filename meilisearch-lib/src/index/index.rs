@@ -1,28 +1,103 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs::create_dir_all;
 use std::marker::PhantomData;
-use std::ops::Deref;
+use std::ops::{Bound, Deref};
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use fst::IntoStreamer;
-use milli::heed::{EnvOpenOptions, RoTxn};
+use milli::heed::types::{ByteSlice, SerdeJson, Str};
+use milli::heed::{Database, EnvOpenOptions, RoTxn, RwTxn};
 use milli::update::{IndexerConfig, Setting};
-use milli::{obkv_to_json, FieldDistribution};
+use milli::{obkv_to_json, DocumentId, FieldDistribution, RoaringBitmapCodec, BEU32};
+use roaring::RoaringBitmap;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::segmenters::{CustomSegmenters, SegmenterId};
 use crate::EnvSizer;
 
+use super::derived::{document_fields, is_derived};
 use super::error::IndexError;
 use super::error::Result;
-use super::updates::{MinWordSizeTyposSetting, TypoSettings};
+use super::facet_normalization::FacetNormalization;
+use super::metadata::{self, MetadataDatabase};
+use super::nested::NestedObjectBehavior;
+use super::prefix_search::PrefixSearch;
+use super::readers::{ReadTxn, ReaderSlots, ReaderStats, SnapshotTxn};
+use super::search_defaults::SearchDefaults;
+use super::transforms::DocumentTransform;
+use super::updates::{MinWordSizeTyposSetting, SearchableAttributes, TypoSettings};
 use super::{Checked, Settings};
 
 pub type Document = Map<String, Value>;
 
+/// The position of a page in the listing of the documents of an index: the internal id of the
+/// last document of the previous page. The documents are listed in the order of their internal
+/// ids, so the next page starts right after it without skipping the previous ones, and still
+/// starts at the next document when this one was deleted in the meantime. It is handed out as an
+/// opaque string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DocumentsCursor(DocumentId);
+
+impl fmt::Display for DocumentsCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0.to_be_bytes()))
+    }
+}
+
+impl FromStr for DocumentsCursor {
+    type Err = IndexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut bytes = [0; 4];
+        hex::decode_to_slice(s, &mut bytes)
+            .map_err(|_| IndexError::InvalidDocumentsCursor(s.to_string()))?;
+        Ok(Self(DocumentId::from_be_bytes(bytes)))
+    }
+}
+
+/// A page of the listing of the documents of an index.
+#[derive(Debug)]
+pub struct DocumentsPage {
+    /// The number of documents of the index.
+    pub total: u64,
+    pub documents: Vec<Document>,
+    /// Where the next page starts, if there are documents after this page.
+    pub next_cursor: Option<DocumentsCursor>,
+}
+
+pub const DEFAULT_MAX_WORD_DERIVATIONS: usize = 100;
+pub const DEFAULT_MAX_PREFIX_EXPANSION: usize = 1000;
+pub const DEFAULT_MAX_VALUES_PER_FACET: usize = 100;
+
+/// The settings that milli knows nothing about are stored in the main database of the index,
+/// under these keys.
+mod main_key {
+    pub const WARMUP_QUERIES: &str = "meilisearch-warmup-queries";
+    pub const MAX_WORD_DERIVATIONS: &str = "meilisearch-max-word-derivations";
+    pub const MAX_PREFIX_EXPANSION: &str = "meilisearch-max-prefix-expansion";
+    pub const MAX_VALUES_PER_FACET: &str = "meilisearch-max-values-per-facet";
+    pub const SEARCH_DEFAULTS: &str = "meilisearch-search-defaults";
+    pub const SOFT_DELETED_DOCUMENTS: &str = "meilisearch-soft-deleted-documents";
+    pub const SOFT_DELETED_SEQUENCES: &str = "meilisearch-soft-deleted-sequences";
+    pub const NEXT_DOCUMENT_SEQUENCE: &str = "meilisearch-next-document-sequence";
+    pub const DOCUMENT_METADATA: &str = "meilisearch-document-metadata-enabled";
+    pub const NESTED_OBJECT_BEHAVIOR: &str = "meilisearch-nested-object-behavior";
+    pub const SEARCHABLE_WEIGHTS: &str = "meilisearch-searchable-weights";
+    pub const DOCUMENT_TRANSFORMS: &str = "meilisearch-document-transforms";
+    pub const DOCUMENTS_OVER_SOFT_LIMITS: &str = "meilisearch-documents-over-soft-limits";
+    pub const CUSTOM_SEGMENTERS: &str = "meilisearch-custom-segmenters";
+    pub const PREFIX_SEARCH: &str = "meilisearch-prefix-search";
+    pub const DISABLE_PREFIX_ON_ATTRIBUTES: &str = "meilisearch-disable-prefix-on-attributes";
+    pub const FACET_NORMALIZATION: &str = "meilisearch-facet-normalization";
+    pub const STOP_WORDS: &str = "meilisearch-stop-words";
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexMeta {
@@ -61,7 +136,31 @@ pub struct IndexStats {
     /// index returns it, since it is the `UpdateStore` that knows what index is currently indexing. It is
     /// later set to either true or false, we we retrieve the information from the `UpdateStore`
     pub is_indexing: Option<bool>,
+    /// The size, in bytes, of the payloads of the enqueued document additions. It is filled by
+    /// the `IndexController`, that keeps track of them.
+    pub pending_payload_size: u64,
+    /// The number of points in time open on the index, each holding one of its reader slots. It
+    /// is filled by the `IndexController`, that keeps track of them.
+    pub open_pits: usize,
+    /// The number of indexed documents that exceeded the soft limits on the documents when they
+    /// were added.
+    pub documents_over_soft_limits: u64,
     pub field_distribution: FieldDistribution,
+    /// The reader slots of the environment of the index.
+    pub readers: ReaderStats,
+    /// The breakdown of the index by internal database, only filled when it is explicitly
+    /// requested since it requires to read the whole index.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub databases: Option<BTreeMap<String, DatabaseStats>>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseStats {
+    pub number_of_entries: u64,
+    /// The size, in bytes, of the keys and values stored in the database. The pages overhead of
+    /// LMDB is not accounted for.
+    pub size: u64,
 }
 
 #[derive(Clone, derivative::Derivative)]
@@ -72,6 +171,10 @@ pub struct Index {
     pub inner: Arc<milli::Index>,
     #[derivative(Debug = "ignore")]
     pub indexer_config: Arc<IndexerConfig>,
+    /// The metadata of the documents, next to the databases of milli in the environment.
+    #[derivative(Debug = "ignore")]
+    pub(super) document_metadata: MetadataDatabase,
+    readers: Arc<ReaderSlots>,
 }
 
 impl Deref for Index {
@@ -86,6 +189,7 @@ impl Index {
     pub fn open(
         path: impl AsRef<Path>,
         size: usize,
+        max_readers: u32,
         uuid: Uuid,
         update_handler: Arc<IndexerConfig>,
     ) -> Result<Self> {
@@ -93,12 +197,34 @@ impl Index {
         create_dir_all(&path)?;
         let mut options = EnvOpenOptions::new();
         options.map_size(size);
-        let inner = Arc::new(milli::Index::new(options, &path)?);
-        Ok(Index {
-            inner,
+        options.max_readers(max_readers);
+        let (inner, document_metadata) = open_milli_index(options, &path)?;
+        let index = Index {
+            inner: Arc::new(inner),
             uuid,
             indexer_config: update_handler,
-        })
+            document_metadata,
+            readers: Arc::new(ReaderSlots::new(max_readers)),
+        };
+
+        // the words of the documents and of the queries must be segmented by the same segmenters.
+        let txn = index.read_txn()?;
+        index.custom_segmenters(&txn)?;
+        drop(txn);
+
+        Ok(index)
+    }
+
+    /// Opens a read transaction on the index, accounted for in its reader slots. It shadows the
+    /// one of milli, so that all the read transactions of the index are counted.
+    pub fn read_txn(&self) -> milli::heed::Result<ReadTxn> {
+        self.readers.read_txn(&self.env)
+    }
+
+    /// Opens a read transaction on the index that can outlive the index handle, to serve the
+    /// searches of a point in time. It holds one of the reader slots until it is dropped.
+    pub fn snapshot_txn(&self) -> milli::heed::Result<SnapshotTxn> {
+        SnapshotTxn::new(self.inner.clone(), self.readers.clone())
     }
 
     /// Asynchronously close the underlying index
@@ -107,16 +233,100 @@ impl Index {
     }
 
     pub fn stats(&self) -> Result<IndexStats> {
+        // the reader slots are counted before the stats take one.
+        let readers = self.readers.stats();
         let rtxn = self.read_txn()?;
 
         Ok(IndexStats {
             size: self.size(),
-            number_of_documents: self.number_of_documents(&rtxn)?,
+            number_of_documents: self.number_of_live_documents(&rtxn)?,
             is_indexing: None,
-            field_distribution: self.field_distribution(&rtxn)?,
+            pending_payload_size: 0,
+            open_pits: 0,
+            documents_over_soft_limits: self.documents_over_soft_limits(&rtxn)?,
+            field_distribution: self
+                .field_distribution(&rtxn)?
+                .into_iter()
+                .filter(|(field, _)| !is_derived(field))
+                .collect(),
+            readers,
+            databases: None,
         })
     }
 
+    /// Returns the number of entries and the size of each internal database of the index, read
+    /// within a single transaction. The names of the databases are stable, they are not the ones
+    /// used by milli.
+    pub fn database_stats(&self) -> Result<BTreeMap<String, DatabaseStats>> {
+        let rtxn = self.read_txn()?;
+
+        let mut stats = BTreeMap::new();
+        let mut insert = |name: &str, db_stats| {
+            stats.insert(name.to_string(), db_stats);
+        };
+
+        insert(
+            "main",
+            entries_stats(self.main.iter::<_, ByteSlice, ByteSlice>(&rtxn)?)?,
+        );
+        insert("documents", database_stats(&rtxn, self.documents)?);
+        insert("wordDocids", database_stats(&rtxn, self.word_docids)?);
+        insert(
+            "exactWordDocids",
+            database_stats(&rtxn, self.exact_word_docids)?,
+        );
+        insert(
+            "wordPrefixDocids",
+            database_stats(&rtxn, self.word_prefix_docids)?,
+        );
+        insert(
+            "exactWordPrefixDocids",
+            database_stats(&rtxn, self.exact_word_prefix_docids)?,
+        );
+        insert(
+            "docidWordPositions",
+            database_stats(&rtxn, self.docid_word_positions)?,
+        );
+        insert(
+            "wordPairProximityDocids",
+            database_stats(&rtxn, self.word_pair_proximity_docids)?,
+        );
+        insert(
+            "wordPrefixPairProximityDocids",
+            database_stats(&rtxn, self.word_prefix_pair_proximity_docids)?,
+        );
+        insert(
+            "wordPositionDocids",
+            database_stats(&rtxn, self.word_position_docids)?,
+        );
+        insert(
+            "wordPrefixPositionDocids",
+            database_stats(&rtxn, self.word_prefix_position_docids)?,
+        );
+        insert(
+            "fieldIdWordCountDocids",
+            database_stats(&rtxn, self.field_id_word_count_docids)?,
+        );
+        insert(
+            "facetIdF64Docids",
+            database_stats(&rtxn, self.facet_id_f64_docids)?,
+        );
+        insert(
+            "facetIdStringDocids",
+            database_stats(&rtxn, self.facet_id_string_docids)?,
+        );
+        insert(
+            "fieldIdDocidFacetF64s",
+            database_stats(&rtxn, self.field_id_docid_facet_f64s)?,
+        );
+        insert(
+            "fieldIdDocidFacetStrings",
+            database_stats(&rtxn, self.field_id_docid_facet_strings)?,
+        );
+
+        Ok(stats)
+    }
+
     pub fn meta(&self) -> Result<IndexMeta> {
         IndexMeta::new(self)
     }
@@ -134,13 +344,27 @@ impl Index {
             .displayed_fields(txn)?
             .map(|fields| fields.into_iter().map(String::from).collect());
 
-        let searchable_attributes = self
-            .user_defined_searchable_fields(txn)?
-            .map(|fields| fields.into_iter().map(String::from).collect());
+        // the derived field is searchable, filterable and sortable whatever the settings, and its
+        // words are matched without typos.
+        let searchable_attributes = self.user_defined_searchable_fields(txn)?.map(|fields| {
+            fields
+                .into_iter()
+                .filter(|field| !is_derived(field))
+                .map(String::from)
+                .collect()
+        });
 
-        let filterable_attributes = self.filterable_fields(txn)?.into_iter().collect();
+        let filterable_attributes = self
+            .filterable_fields(txn)?
+            .into_iter()
+            .filter(|field| !is_derived(field))
+            .collect();
 
-        let sortable_attributes = self.sortable_fields(txn)?.into_iter().collect();
+        let sortable_attributes = self
+            .sortable_fields(txn)?
+            .into_iter()
+            .filter(|field| !is_derived(field))
+            .collect();
 
         let criteria = self
             .criteria(txn)?
@@ -148,13 +372,7 @@ impl Index {
             .map(|c| c.to_string())
             .collect();
 
-        let stop_words = self
-            .stop_words(txn)?
-            .map(|stop_words| -> Result<BTreeSet<_>> {
-                Ok(stop_words.stream().into_strs()?.into_iter().collect())
-            })
-            .transpose()?
-            .unwrap_or_default();
+        let stop_words = read_stop_words(self, txn)?;
         let distinct_field = self.distinct_field(txn)?.map(String::from);
 
         // in milli each word in the synonyms map were split on their separator. Since we lost
@@ -185,6 +403,7 @@ impl Index {
         let disabled_attributes = self
             .exact_attributes(txn)?
             .into_iter()
+            .filter(|field| !is_derived(field))
             .map(String::from)
             .collect();
 
@@ -200,9 +419,10 @@ impl Index {
                 Some(attrs) => Setting::Set(attrs),
                 None => Setting::Reset,
             },
-            searchable_attributes: match searchable_attributes {
-                Some(attrs) => Setting::Set(attrs),
-                None => Setting::Reset,
+            searchable_attributes: match (searchable_attributes, self.searchable_weights(txn)?) {
+                (Some(_), Some(weights)) => Setting::Set(SearchableAttributes::Weighted(weights)),
+                (Some(attrs), None) => Setting::Set(SearchableAttributes::List(attrs)),
+                (None, _) => Setting::Reset,
             },
             filterable_attributes: Setting::Set(filterable_attributes),
             sortable_attributes: Setting::Set(sortable_attributes),
@@ -214,28 +434,252 @@ impl Index {
             },
             synonyms: Setting::Set(synonyms),
             typo_tolerance: Setting::Set(typo_tolerance),
+            warmup_queries: Setting::Set(self.warmup_queries(txn)?),
+            max_word_derivations: Setting::Set(self.max_word_derivations(txn)?),
+            max_prefix_expansion: Setting::Set(self.max_prefix_expansion(txn)?),
+            max_values_per_facet: Setting::Set(self.max_values_per_facet(txn)?),
+            search_defaults: Setting::Set(self.search_defaults_txn(txn)?),
+            document_metadata: Setting::Set(self.document_metadata_enabled(txn)?),
+            nested_object_behavior: Setting::Set(self.nested_object_behavior(txn)?),
+            document_transforms: Setting::Set(self.document_transforms(txn)?),
+            custom_segmenters: Setting::Set(
+                self.segmenter_ids(txn)?
+                    .into_iter()
+                    .map(|(language, id)| (language, id.name))
+                    .collect(),
+            ),
+            prefix_search: Setting::Set(self.prefix_search(txn)?),
+            disable_prefix_on_attributes: Setting::Set(self.disable_prefix_on_attributes(txn)?),
+            facet_normalization: Setting::Set(self.facet_normalization(txn)?),
             _kind: PhantomData,
         })
     }
 
-    /// Return the total number of documents contained in the index + the selected documents.
+    /// Returns the queries that are run against the index to warm it up.
+    pub fn warmup_queries(&self, txn: &RoTxn) -> Result<Vec<String>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<Vec<String>>>(txn, main_key::WARMUP_QUERIES)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the maximum number of typo derivations a query word can expand to.
+    pub fn max_word_derivations(&self, txn: &RoTxn) -> Result<usize> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<usize>>(txn, main_key::MAX_WORD_DERIVATIONS)?
+            .unwrap_or(DEFAULT_MAX_WORD_DERIVATIONS))
+    }
+
+    /// Returns the maximum number of words a query prefix can expand to.
+    pub fn max_prefix_expansion(&self, txn: &RoTxn) -> Result<usize> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<usize>>(txn, main_key::MAX_PREFIX_EXPANSION)?
+            .unwrap_or(DEFAULT_MAX_PREFIX_EXPANSION))
+    }
+
+    /// Returns the maximum number of distinct values returned for a facet.
+    pub fn max_values_per_facet(&self, txn: &RoTxn) -> Result<usize> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<usize>>(txn, main_key::MAX_VALUES_PER_FACET)?
+            .unwrap_or(DEFAULT_MAX_VALUES_PER_FACET))
+    }
+
+    /// Returns whether the metadata of the documents are recorded.
+    pub fn document_metadata_enabled(&self, txn: &RoTxn) -> Result<bool> {
+        read_document_metadata_enabled(self, txn)
+    }
+
+    /// Returns how the arrays of objects of each attribute are filtered.
+    pub fn nested_object_behavior(
+        &self,
+        txn: &RoTxn,
+    ) -> Result<BTreeMap<String, NestedObjectBehavior>> {
+        read_nested_object_behavior(self, txn)
+    }
+
+    /// Returns the weight of each searchable attribute, when the searchable attributes were set
+    /// with their weights.
+    pub fn searchable_weights(&self, txn: &RoTxn) -> Result<Option<BTreeMap<String, u32>>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<BTreeMap<String, u32>>>(txn, main_key::SEARCHABLE_WEIGHTS)?)
+    }
+
+    /// Returns the transforms applied to the documents when they are added.
+    pub fn document_transforms(&self, txn: &RoTxn) -> Result<Vec<DocumentTransform>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<Vec<DocumentTransform>>>(txn, main_key::DOCUMENT_TRANSFORMS)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the segmenters the documents were segmented with, by language.
+    fn segmenter_ids(&self, txn: &RoTxn) -> Result<BTreeMap<String, SegmenterId>> {
+        read_segmenter_ids(self, txn)
+    }
+
+    /// Returns the segmenters of the languages of the index. It fails if one of them is not
+    /// registered with the version the documents were segmented with.
+    pub fn custom_segmenters(&self, txn: &RoTxn) -> Result<CustomSegmenters> {
+        read_custom_segmenters(self, txn)
+    }
+
+    /// Returns whether the last word of the queries is matched as a prefix.
+    pub fn prefix_search(&self, txn: &RoTxn) -> Result<PrefixSearch> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<PrefixSearch>>(txn, main_key::PREFIX_SEARCH)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the attributes where the last word of the queries is never matched as a prefix.
+    pub fn disable_prefix_on_attributes(&self, txn: &RoTxn) -> Result<BTreeSet<String>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<BTreeSet<String>>>(
+                txn,
+                main_key::DISABLE_PREFIX_ON_ATTRIBUTES,
+            )?
+            .unwrap_or_default())
+    }
+
+    /// Returns how the facet values of each attribute are normalized.
+    pub fn facet_normalization(&self, txn: &RoTxn) -> Result<BTreeMap<String, FacetNormalization>> {
+        read_facet_normalization(self, txn)
+    }
+
+    /// Returns the parameters applied to every search request that doesn't override them.
+    pub fn search_defaults(&self) -> Result<SearchDefaults> {
+        let txn = self.read_txn()?;
+        self.search_defaults_txn(&txn)
+    }
+
+    fn search_defaults_txn(&self, txn: &RoTxn) -> Result<SearchDefaults> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<SearchDefaults>>(txn, main_key::SEARCH_DEFAULTS)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the documents that were deleted but are not purged from the index yet. They must be
+    /// excluded from any result.
+    pub fn soft_deleted_documents(&self, txn: &RoTxn) -> Result<RoaringBitmap> {
+        read_soft_deleted_documents(self, txn)
+    }
+
+    pub(super) fn put_soft_deleted_documents(
+        &self,
+        txn: &mut RwTxn,
+        docids: &RoaringBitmap,
+    ) -> Result<()> {
+        if docids.is_empty() {
+            self.main
+                .delete::<_, Str>(txn, main_key::SOFT_DELETED_DOCUMENTS)?;
+        } else {
+            self.main.put::<_, Str, RoaringBitmapCodec>(
+                txn,
+                main_key::SOFT_DELETED_DOCUMENTS,
+                docids,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the sequence numbers of the soft deleted documents, by which milli excludes them
+    /// from the searches.
+    pub fn soft_deleted_sequences(&self, txn: &RoTxn) -> Result<RoaringBitmap> {
+        Ok(self
+            .main
+            .get::<_, Str, RoaringBitmapCodec>(txn, main_key::SOFT_DELETED_SEQUENCES)?
+            .unwrap_or_default())
+    }
+
+    pub(super) fn put_soft_deleted_sequences(
+        &self,
+        txn: &mut RwTxn,
+        sequences: &RoaringBitmap,
+    ) -> Result<()> {
+        if sequences.is_empty() {
+            self.main
+                .delete::<_, Str>(txn, main_key::SOFT_DELETED_SEQUENCES)?;
+        } else {
+            self.main.put::<_, Str, RoaringBitmapCodec>(
+                txn,
+                main_key::SOFT_DELETED_SEQUENCES,
+                sequences,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of documents that exceeded the soft limits on the documents when they
+    /// were added to the index.
+    pub fn documents_over_soft_limits(&self, txn: &RoTxn) -> Result<u64> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<u64>>(txn, main_key::DOCUMENTS_OVER_SOFT_LIMITS)?
+            .unwrap_or_default())
+    }
+
+    pub(super) fn put_documents_over_soft_limits(&self, txn: &mut RwTxn, count: u64) -> Result<()> {
+        self.main.put::<_, Str, SerdeJson<u64>>(
+            txn,
+            main_key::DOCUMENTS_OVER_SOFT_LIMITS,
+            &count,
+        )?;
+        Ok(())
+    }
+
+    /// Returns the number of documents of the index, soft deleted documents excluded.
+    pub fn number_of_live_documents(&self, txn: &RoTxn) -> Result<u64> {
+        let soft_deleted = self.soft_deleted_documents(txn)?;
+        Ok(self.number_of_documents(txn)? - soft_deleted.len())
+    }
+
+    /// Returns the proportion of the documents of the index that are soft deleted.
+    pub fn soft_deleted_ratio(&self) -> Result<f64> {
+        let txn = self.read_txn()?;
+        let soft_deleted = self.soft_deleted_documents(&txn)?.len();
+        match self.number_of_documents(&txn)? {
+            0 => Ok(0.0),
+            total => Ok(soft_deleted as f64 / total as f64),
+        }
+    }
+
+    /// Return the page of `limit` documents starting `offset` documents after `cursor`, or after
+    /// the start of the index without cursor.
     pub fn retrieve_documents<S: AsRef<str>>(
         &self,
         offset: usize,
         limit: usize,
+        cursor: Option<DocumentsCursor>,
         attributes_to_retrieve: Option<Vec<S>>,
-    ) -> Result<(u64, Vec<Document>)> {
+    ) -> Result<DocumentsPage> {
         let txn = self.read_txn()?;
 
         let fields_ids_map = self.fields_ids_map(&txn)?;
-        let all_fields: Vec<_> = fields_ids_map.iter().map(|(id, _)| id).collect();
+        let all_fields = document_fields(&fields_ids_map);
 
-        let iter = self.documents.range(&txn, &(..))?.skip(offset).take(limit);
+        let start = match cursor {
+            Some(DocumentsCursor(id)) => Bound::Excluded(BEU32::new(id)),
+            None => Bound::Unbounded,
+        };
+        let soft_deleted = self.soft_deleted_documents(&txn)?;
+        let mut iter = self
+            .documents
+            .range(&txn, &(start, Bound::Unbounded))?
+            .filter(|entry| !matches!(entry, Ok((id, _)) if soft_deleted.contains(id.get())))
+            .skip(offset);
 
         let mut documents = Vec::new();
+        let mut last_id = None;
 
-        for entry in iter {
-            let (_id, obkv) = entry?;
+        for entry in iter.by_ref().take(limit) {
+            let (id, obkv) = entry?;
+            last_id = Some(id.get());
             let document = obkv_to_json(&all_fields, &fields_ids_map, obkv)?;
             let document = match &attributes_to_retrieve {
                 Some(attributes_to_retrieve) => permissive_json_pointer::select_values(
@@ -247,26 +691,41 @@ impl Index {
             documents.push(document);
         }
 
-        let number_of_documents = self.number_of_documents(&txn)?;
+        // the cursor is only handed out when there is a next page.
+        let next_cursor = iter.next().transpose()?.and(last_id).map(DocumentsCursor);
+        let total = self.number_of_live_documents(&txn)?;
 
-        Ok((number_of_documents, documents))
+        Ok(DocumentsPage {
+            total,
+            documents,
+            next_cursor,
+        })
     }
 
     pub fn retrieve_document<S: AsRef<str>>(
         &self,
         doc_id: String,
         attributes_to_retrieve: Option<Vec<S>>,
+        with_metadata: bool,
     ) -> Result<Document> {
         let txn = self.read_txn()?;
 
         let fields_ids_map = self.fields_ids_map(&txn)?;
-        let all_fields: Vec<_> = fields_ids_map.iter().map(|(id, _)| id).collect();
+        let all_fields = document_fields(&fields_ids_map);
 
+        let soft_deleted = self.soft_deleted_documents(&txn)?;
         let internal_id = self
             .external_documents_ids(&txn)?
             .get(doc_id.as_bytes())
+            .filter(|id| !soft_deleted.contains(*id))
             .ok_or_else(|| IndexError::DocumentNotFound(doc_id.clone()))?;
 
+        let metadata = if with_metadata {
+            metadata::get(self.document_metadata, &txn, internal_id)?
+        } else {
+            None
+        };
+
         let document = self
             .documents(&txn, std::iter::once(internal_id))?
             .into_iter()
@@ -275,7 +734,7 @@ impl Index {
             .ok_or(IndexError::DocumentNotFound(doc_id))?;
 
         let document = obkv_to_json(&all_fields, &fields_ids_map, document)?;
-        let document = match &attributes_to_retrieve {
+        let mut document = match &attributes_to_retrieve {
             Some(attributes_to_retrieve) => permissive_json_pointer::select_values(
                 &document,
                 attributes_to_retrieve.iter().map(|s| s.as_ref()),
@@ -283,6 +742,10 @@ impl Index {
             None => document,
         };
 
+        if let Some(metadata) = metadata {
+            metadata.insert_into(&mut document);
+        }
+
         Ok(document)
     }
 
@@ -302,6 +765,271 @@ impl Index {
     }
 }
 
+/// The number of databases of the environment of an index: the ones of milli, and the ones of the
+/// engine.
+const MAX_DBS: u32 = 32;
+
+/// Opens the milli index at `path`, along with the database of the metadata of its documents.
+///
+/// heed opens the environment of a path once, so the environment is opened with room for the
+/// databases of the engine before milli opens it, and milli reuses it.
+pub(super) fn open_milli_index(
+    options: EnvOpenOptions,
+    path: impl AsRef<Path>,
+) -> Result<(milli::Index, MetadataDatabase)> {
+    let mut env_options = options.clone();
+    env_options.max_dbs(MAX_DBS);
+    let env = env_options.open(&path)?;
+    let document_metadata = env.create_database(Some(metadata::DATABASE_NAME))?;
+    let index = milli::Index::new(options, &path)?;
+    drop(env);
+
+    Ok((index, document_metadata))
+}
+
+/// Applies the settings that are not milli settings to the index. They must be written next to
+/// the ones applied through the milli settings builder.
+pub(crate) fn apply_meilisearch_settings(
+    index: &milli::Index,
+    txn: &mut RwTxn,
+    settings: &Settings<Checked>,
+) -> milli::heed::Result<()> {
+    apply_main_setting(
+        index,
+        txn,
+        main_key::WARMUP_QUERIES,
+        &settings.warmup_queries,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::MAX_WORD_DERIVATIONS,
+        &settings.max_word_derivations,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::MAX_PREFIX_EXPANSION,
+        &settings.max_prefix_expansion,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::MAX_VALUES_PER_FACET,
+        &settings.max_values_per_facet,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::SEARCH_DEFAULTS,
+        &settings.search_defaults,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::DOCUMENT_METADATA,
+        &settings.document_metadata,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::NESTED_OBJECT_BEHAVIOR,
+        &settings.nested_object_behavior,
+    )?;
+    // the weights are forgotten along with the searchable attributes they were set with.
+    let searchable_weights = match settings.searchable_attributes {
+        Setting::Set(ref attributes) => match attributes.weights() {
+            Some(weights) => Setting::Set(weights),
+            None => Setting::Reset,
+        },
+        Setting::Reset => Setting::Reset,
+        Setting::NotSet => Setting::NotSet,
+    };
+    apply_main_setting(
+        index,
+        txn,
+        main_key::SEARCHABLE_WEIGHTS,
+        &searchable_weights,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::DOCUMENT_TRANSFORMS,
+        &settings.document_transforms,
+    )?;
+    // the version of the segmenters is stored along with their name.
+    let segmenter_ids = match settings.custom_segmenters {
+        Setting::Set(ref selection) => Setting::Set(SegmenterId::resolve(selection)),
+        Setting::Reset => Setting::Reset,
+        Setting::NotSet => Setting::NotSet,
+    };
+    apply_main_setting(index, txn, main_key::CUSTOM_SEGMENTERS, &segmenter_ids)?;
+    apply_main_setting(index, txn, main_key::PREFIX_SEARCH, &settings.prefix_search)?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::DISABLE_PREFIX_ON_ATTRIBUTES,
+        &settings.disable_prefix_on_attributes,
+    )?;
+    apply_main_setting(
+        index,
+        txn,
+        main_key::FACET_NORMALIZATION,
+        &settings.facet_normalization,
+    )?;
+    apply_main_setting(index, txn, main_key::STOP_WORDS, &settings.stop_words)
+}
+
+/// Returns how the facet values of each attribute of `index` are normalized.
+pub(super) fn read_facet_normalization(
+    index: &milli::Index,
+    txn: &RoTxn,
+) -> Result<BTreeMap<String, FacetNormalization>> {
+    Ok(index
+        .main
+        .get::<_, Str, SerdeJson<BTreeMap<String, FacetNormalization>>>(
+            txn,
+            main_key::FACET_NORMALIZATION,
+        )?
+        .unwrap_or_default())
+}
+
+/// Returns how the arrays of objects of each attribute of `index` are filtered.
+pub(super) fn read_nested_object_behavior(
+    index: &milli::Index,
+    txn: &RoTxn,
+) -> Result<BTreeMap<String, NestedObjectBehavior>> {
+    Ok(index
+        .main
+        .get::<_, Str, SerdeJson<BTreeMap<String, NestedObjectBehavior>>>(
+            txn,
+            main_key::NESTED_OBJECT_BEHAVIOR,
+        )?
+        .unwrap_or_default())
+}
+
+/// Returns the stop words of `index`, which were given to milli before they were kept apart.
+pub(super) fn read_stop_words(index: &milli::Index, txn: &RoTxn) -> Result<BTreeSet<String>> {
+    if let Some(stop_words) = index
+        .main
+        .get::<_, Str, SerdeJson<BTreeSet<String>>>(txn, main_key::STOP_WORDS)?
+    {
+        return Ok(stop_words);
+    }
+    match index.stop_words(txn)? {
+        Some(stop_words) => Ok(stop_words.stream().into_strs()?.into_iter().collect()),
+        None => Ok(BTreeSet::new()),
+    }
+}
+
+pub(super) fn put_stop_words(
+    index: &milli::Index,
+    txn: &mut RwTxn,
+    stop_words: &BTreeSet<String>,
+) -> milli::heed::Result<()> {
+    index
+        .main
+        .put::<_, Str, SerdeJson<BTreeSet<String>>>(txn, main_key::STOP_WORDS, stop_words)
+}
+
+fn read_segmenter_ids(index: &milli::Index, txn: &RoTxn) -> Result<BTreeMap<String, SegmenterId>> {
+    Ok(index
+        .main
+        .get::<_, Str, SerdeJson<BTreeMap<String, SegmenterId>>>(txn, main_key::CUSTOM_SEGMENTERS)?
+        .unwrap_or_default())
+}
+
+/// Returns the segmenters of the languages of `index`, which must be registered with the version
+/// the documents were segmented with.
+pub(super) fn read_document_metadata_enabled(index: &milli::Index, txn: &RoTxn) -> Result<bool> {
+    Ok(index
+        .main
+        .get::<_, Str, SerdeJson<bool>>(txn, main_key::DOCUMENT_METADATA)?
+        .unwrap_or_default())
+}
+
+pub(super) fn read_custom_segmenters(
+    index: &milli::Index,
+    txn: &RoTxn,
+) -> Result<CustomSegmenters> {
+    Ok(CustomSegmenters::load(&read_segmenter_ids(index, txn)?)?)
+}
+
+pub(super) fn read_soft_deleted_documents(
+    index: &milli::Index,
+    txn: &RoTxn,
+) -> Result<RoaringBitmap> {
+    Ok(index
+        .main
+        .get::<_, Str, RoaringBitmapCodec>(txn, main_key::SOFT_DELETED_DOCUMENTS)?
+        .unwrap_or_default())
+}
+
+/// Forgets the soft deleted documents of `index`, once milli cleared them.
+pub(super) fn clear_soft_deleted_documents(
+    index: &milli::Index,
+    txn: &mut RwTxn,
+) -> milli::heed::Result<()> {
+    index
+        .main
+        .delete::<_, Str>(txn, main_key::SOFT_DELETED_DOCUMENTS)?;
+    index
+        .main
+        .delete::<_, Str>(txn, main_key::SOFT_DELETED_SEQUENCES)
+        .map(drop)
+}
+
+/// Returns the sequence number the next derived document gets, `None` if the documents of the
+/// index were derived without one.
+pub(super) fn read_next_document_sequence(
+    index: &milli::Index,
+    txn: &RoTxn,
+) -> Result<Option<u32>> {
+    Ok(index
+        .main
+        .get::<_, Str, SerdeJson<u32>>(txn, main_key::NEXT_DOCUMENT_SEQUENCE)?)
+}
+
+pub(super) fn put_next_document_sequence(
+    index: &milli::Index,
+    txn: &mut RwTxn,
+    sequence: u32,
+) -> milli::heed::Result<()> {
+    index
+        .main
+        .put::<_, Str, SerdeJson<u32>>(txn, main_key::NEXT_DOCUMENT_SEQUENCE, &sequence)
+}
+
+fn database_stats<K, V>(rtxn: &RoTxn, db: Database<K, V>) -> Result<DatabaseStats> {
+    entries_stats(db.remap_types::<ByteSlice, ByteSlice>().iter(rtxn)?)
+}
+
+fn entries_stats<'t>(
+    entries: impl Iterator<Item = milli::heed::Result<(&'t [u8], &'t [u8])>>,
+) -> Result<DatabaseStats> {
+    let mut stats = DatabaseStats::default();
+    for entry in entries {
+        let (key, value) = entry?;
+        stats.number_of_entries += 1;
+        stats.size += (key.len() + value.len()) as u64;
+    }
+
+    Ok(stats)
+}
+
+fn apply_main_setting<T: Serialize>(
+    index: &milli::Index,
+    txn: &mut RwTxn,
+    key: &str,
+    setting: &Setting<T>,
+) -> milli::heed::Result<()> {
+    match setting {
+        Setting::Set(value) => index.main.put::<_, Str, SerdeJson<T>>(txn, key, value),
+        Setting::Reset => index.main.delete::<_, Str>(txn, key).map(drop),
+        Setting::NotSet => Ok(()),
+    }
+}
+
 /// When running tests, when a server instance is dropped, the environment is not actually closed,
 /// leaving a lot of open file descriptors.
 impl Drop for Index {
@@ -0,0 +1,156 @@
+//! Queries built from documents of the index.
+//!
+//! Instead of a text query, a search can look for the documents like some documents of the index,
+//! the seeds. The words of the searchable attributes of the seeds are weighted by their number of
+//! occurrences in the seeds and by their rarity in the index, and the most significant ones make
+//! the query, which is then ranked like any other.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use milli::heed::RoTxn;
+use milli::tokenizer::{Analyzer, AnalyzerConfig};
+use roaring::RoaringBitmap;
+use serde_json::Value;
+
+use super::derived::document_fields;
+use super::error::{IndexError, Result};
+use super::index::Index;
+use super::stop_words;
+
+/// The number of words of a query built from documents. milli ignores the words of a query
+/// beyond the tenth.
+pub const MAX_LIKE_QUERY_TERMS: usize = 10;
+
+/// The warning of a search like documents from which no query could be built.
+pub const NO_SIGNIFICANT_TERMS: &str = "The documents have no significant words to search for: their searchable attributes only contain stop words, or words found in all the documents.";
+
+/// Returns the internal ids of the documents `ids`, or an error listing those that are not in the
+/// index.
+pub fn seed_documents(index: &Index, rtxn: &RoTxn, ids: &[String]) -> Result<RoaringBitmap> {
+    let external_ids = index.external_documents_ids(rtxn)?;
+    let soft_deleted = index.soft_deleted_documents(rtxn)?;
+
+    let mut seeds = RoaringBitmap::new();
+    let mut missing = Vec::new();
+    for id in ids {
+        match external_ids
+            .get(id.as_bytes())
+            .filter(|docid| !soft_deleted.contains(*docid))
+        {
+            Some(docid) => {
+                seeds.insert(docid);
+            }
+            None => missing.push(id.clone()),
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(seeds)
+    } else {
+        Err(IndexError::LikeDocumentsNotFound(missing))
+    }
+}
+
+/// Returns the most significant words of the searchable attributes of `seeds`, the most
+/// significant first. A word is as significant as it occurs in the seeds and is rare in the
+/// index, the stop words and the words found in all the documents are never significant.
+pub fn significant_terms(
+    index: &Index,
+    rtxn: &RoTxn,
+    seeds: &RoaringBitmap,
+) -> Result<Vec<String>> {
+    // all the fields are searchable by default, the terms are never taken from the derived ones.
+    let document_fields = document_fields(&index.fields_ids_map(rtxn)?);
+    let searchable = match index.searchable_fields_ids(rtxn)? {
+        Some(fids) => fids
+            .into_iter()
+            .filter(|fid| document_fields.contains(fid))
+            .collect(),
+        None => document_fields,
+    };
+    let index_stop_words = stop_words::stop_words(index, rtxn)?;
+
+    let stop_words = fst::Set::default();
+    let mut config = AnalyzerConfig::default();
+    config.stop_words(&stop_words);
+    let analyzer = Analyzer::new(config);
+
+    let mut occurrences: HashMap<String, u64> = HashMap::new();
+    for (_, obkv) in index.documents(rtxn, seeds.iter())? {
+        for (fid, value) in obkv.iter() {
+            if !searchable.contains(&fid) {
+                continue;
+            }
+
+            let value: Value = serde_json::from_slice(value)?;
+            let mut texts = Vec::new();
+            collect_texts(&value, &mut texts);
+            for text in texts {
+                let analyzed = analyzer.analyze(&text);
+                for token in analyzed.tokens().filter(|token| token.is_word()) {
+                    *occurrences.entry(token.text().to_string()).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let documents_count = index.number_of_documents(rtxn)? as f64;
+    let mut terms = Vec::new();
+    for (word, count) in occurrences {
+        if index_stop_words.contains(&word) {
+            continue;
+        }
+        // the words that were not indexed can't be searched.
+        let frequency = match index.word_docids.get(rtxn, &word)? {
+            Some(docids) if !docids.is_empty() => docids.len() as f64,
+            _ => continue,
+        };
+        let rarity = (documents_count / frequency).ln();
+        if rarity > 0.0 {
+            terms.push((count as f64 * rarity, word));
+        }
+    }
+
+    terms.sort_by(|(lhs_weight, lhs), (rhs_weight, rhs)| {
+        rhs_weight
+            .partial_cmp(lhs_weight)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| lhs.cmp(rhs))
+    });
+
+    Ok(terms
+        .into_iter()
+        .take(MAX_LIKE_QUERY_TERMS)
+        .map(|(_, word)| word)
+        .collect())
+}
+
+/// Collects the strings and numbers of `value`, which are the values milli indexes as words.
+fn collect_texts(value: &Value, texts: &mut Vec<String>) {
+    match value {
+        Value::String(s) => texts.push(s.clone()),
+        Value::Number(n) => texts.push(n.to_string()),
+        Value::Array(values) => values.iter().for_each(|v| collect_texts(v, texts)),
+        Value::Object(map) => map.values().for_each(|v| collect_texts(v, texts)),
+        Value::Null | Value::Bool(_) => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_collect_texts() {
+        let mut texts = Vec::new();
+        collect_texts(
+            &json!({ "title": "hello", "tags": ["a", 2, null, true], "nested": { "x": "y" } }),
+            &mut texts,
+        );
+        texts.sort();
+        assert_eq!(texts, vec!["2", "a", "hello", "y"]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Metadata the engine maintains for each document.
+//!
+//! When the `documentMetadata` setting of an index is enabled, the time at which each document
+//! was first added (`_createdAt`) and last replaced or updated (`_updatedAt`) is recorded, as a
+//! unix timestamp. The metadata is not part of the documents: it is stored in a database of its
+//! own, in the environment of the index, by internal id.
+//!
+//! So that milli filters and sorts the documents by their metadata before it ranks them, the
+//! metadata of a document is also derived into its derived field when it is indexed, and the
+//! filters and sorts on the reserved names are rewritten into filters and sorts on the derived
+//! values.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use milli::heed::types::{OwnedType, SerdeJson};
+use milli::heed::{Database, RoTxn, RwTxn};
+use milli::{DocumentId, BEU32};
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::derived::{Comparison, DERIVED_FIELD};
+use super::error::Result;
+use super::index::Document;
+
+pub const CREATED_AT: &str = "_createdAt";
+pub const UPDATED_AT: &str = "_updatedAt";
+
+/// The name of the database of the metadata in the environment of an index.
+pub const DATABASE_NAME: &str = "meilisearch-document-metadata";
+
+/// The metadata of the documents, by internal id.
+pub type MetadataDatabase = Database<OwnedType<BEU32>, SerdeJson<DocumentMetadata>>;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentMetadata {
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl DocumentMetadata {
+    /// Adds the metadata to a document, under the reserved names.
+    pub fn insert_into(&self, document: &mut Document) {
+        document.insert(CREATED_AT.to_string(), self.created_at.into());
+        document.insert(UPDATED_AT.to_string(), self.updated_at.into());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataField {
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl MetadataField {
+    /// The name of the derived value of the field, in the derived field of the documents.
+    fn derived_key(self) -> &'static str {
+        match self {
+            Self::CreatedAt => "createdAt",
+            Self::UpdatedAt => "updatedAt",
+        }
+    }
+
+    fn derived_field(self) -> String {
+        format!("{}.{}", DERIVED_FIELD, self.derived_key())
+    }
+}
+
+impl FromStr for MetadataField {
+    type Err = ();
+
+    fn from_str(name: &str) -> std::result::Result<Self, ()> {
+        match name {
+            CREATED_AT => Ok(Self::CreatedAt),
+            UPDATED_AT => Ok(Self::UpdatedAt),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn get(
+    db: MetadataDatabase,
+    txn: &RoTxn,
+    docid: DocumentId,
+) -> milli::heed::Result<Option<DocumentMetadata>> {
+    db.get(txn, &BEU32::new(docid))
+}
+
+/// Returns the metadata of a document added, replaced or updated at `now`, whose internal id is
+/// `docid` if it is in the index already. A document keeps the creation time it already had.
+pub fn next(
+    db: MetadataDatabase,
+    txn: &RoTxn,
+    docid: Option<DocumentId>,
+    now: i64,
+) -> milli::heed::Result<DocumentMetadata> {
+    let created_at = match docid {
+        Some(docid) => get(db, txn, docid)?.map_or(now, |metadata| metadata.created_at),
+        None => now,
+    };
+    Ok(DocumentMetadata {
+        created_at,
+        updated_at: now,
+    })
+}
+
+pub fn delete(
+    db: MetadataDatabase,
+    txn: &mut RwTxn,
+    docids: &RoaringBitmap,
+) -> milli::heed::Result<()> {
+    for docid in docids {
+        db.delete(txn, &BEU32::new(docid))?;
+    }
+    Ok(())
+}
+
+pub fn clear(db: MetadataDatabase, txn: &mut RwTxn) -> milli::heed::Result<()> {
+    db.clear(txn)
+}
+
+/// Returns the metadata of the documents of the index, by external id.
+pub fn by_external_id(
+    index: &milli::Index,
+    db: MetadataDatabase,
+    txn: &RoTxn,
+) -> Result<HashMap<String, DocumentMetadata>> {
+    let mut metadata = HashMap::new();
+    for (external_id, docid) in index.external_documents_ids(txn)?.to_hash_map() {
+        if let Some(document_metadata) = get(db, txn, docid)? {
+            metadata.insert(external_id, document_metadata);
+        }
+    }
+    Ok(metadata)
+}
+
+/// Records the metadata of the documents by the internal ids milli gave them, once they are
+/// indexed. The documents milli refused are left out.
+pub fn put_by_external_id(
+    index: &milli::Index,
+    db: MetadataDatabase,
+    txn: &mut RwTxn,
+    metadata: HashMap<String, DocumentMetadata>,
+) -> Result<()> {
+    let external_documents_ids = index.external_documents_ids(txn)?.into_static();
+    for (external_id, document_metadata) in metadata {
+        if let Some(docid) = external_documents_ids.get(&external_id) {
+            db.put(txn, &BEU32::new(docid), &document_metadata)?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds the metadata of a document to its `derived` values.
+pub fn derive(metadata: &DocumentMetadata, derived: &mut Map<String, Value>) {
+    for (field, value) in [
+        (MetadataField::CreatedAt, metadata.created_at),
+        (MetadataField::UpdatedAt, metadata.updated_at),
+    ] {
+        derived.insert(field.derived_key().to_string(), value.into());
+    }
+}
+
+/// Rewrites a comparison on one of the reserved names into the same comparison on the derived
+/// value. `None` if the comparison is on another field.
+pub fn rewrite(comparison: &Comparison) -> Option<Comparison> {
+    let field: MetadataField = comparison.field.parse().ok()?;
+    Some(Comparison {
+        field: field.derived_field(),
+        operator: comparison.operator.clone(),
+    })
+}
+
+/// Rewrites a sort criterion on one of the reserved names, like `_updatedAt:desc`, into the same
+/// criterion on the derived value. `None` if the criterion is on another field.
+pub fn rewrite_sort(criterion: &str) -> Option<String> {
+    let (name, order) = criterion.rsplit_once(':')?;
+    let field: MetadataField = name.parse().ok()?;
+    Some(format!("{}:{}", field.derived_field(), order))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rewrite() {
+        let comparison = Comparison::parse("_updatedAt >= 100").unwrap();
+        assert_eq!(
+            rewrite(&comparison).unwrap(),
+            Comparison::parse("_meilisearch.updatedAt >= 100").unwrap()
+        );
+        let comparison = Comparison::parse("_createdAt 10 TO 20").unwrap();
+        assert_eq!(
+            rewrite(&comparison).unwrap(),
+            Comparison::parse("_meilisearch.createdAt 10 TO 20").unwrap()
+        );
+
+        // only the reserved names are rewritten, not the fields that contain them.
+        assert!(rewrite(&Comparison::parse("my_createdAt = 5").unwrap()).is_none());
+        assert!(rewrite(&Comparison::parse("_createdAtDay = 5").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_rewrite_sort() {
+        assert_eq!(
+            rewrite_sort("_updatedAt:desc").unwrap(),
+            "_meilisearch.updatedAt:desc"
+        );
+        assert!(rewrite_sort("my_updatedAt:desc").is_none());
+        assert!(rewrite_sort("_updatedAt").is_none());
+    }
+}
@@ -1,18 +1,63 @@
+pub use aggregate::{AggregateQuery, AggregateResult, AggregateRow};
+pub use check::{Finding, IndexCheckReport, Severity};
+pub use edition::{validate_edits, DocumentEdit, DocumentsEditionResult};
+pub use facet_normalization::{FacetCaseSensitivity, FacetNormalization, UnicodeForm};
+pub use nested::NestedObjectBehavior;
+pub use prefix_search::PrefixSearch;
+pub use preview::{
+    PreviewBudget, QueryPreview, SettingsPreview, DEFAULT_PREVIEW_LIMIT,
+    DEFAULT_PREVIEW_SAMPLE_SIZE, MAX_PREVIEW_LIMIT, MAX_PREVIEW_QUERIES, MAX_PREVIEW_SAMPLE_SIZE,
+};
+pub use readers::{ReaderStats, SnapshotTxn, DEFAULT_MAX_READERS};
 pub use search::{
-    SearchQuery, SearchResult, DEFAULT_CROP_LENGTH, DEFAULT_CROP_MARKER,
-    DEFAULT_HIGHLIGHT_POST_TAG, DEFAULT_HIGHLIGHT_PRE_TAG, DEFAULT_SEARCH_LIMIT,
+    SearchGroup, SearchGroupBy, SearchHit, SearchQuery, SearchResult, DEFAULT_CROP_LENGTH,
+    DEFAULT_CROP_MARKER, DEFAULT_GROUP_LIMIT, DEFAULT_HIGHLIGHT_POST_TAG,
+    DEFAULT_HIGHLIGHT_PRE_TAG, DEFAULT_SEARCH_LIMIT, HARD_RESULT_LIMIT,
+};
+pub use search_defaults::SearchDefaults;
+pub use settings_export::{SettingsExport, SETTINGS_EXPORT_VERSION};
+pub use stats_repair::{StatsCounts, StatsRepairReport, DISCREPANCY_WARNING_THRESHOLD};
+pub use transforms::{DocumentTransform, TransformsResult};
+pub use updates::{
+    apply_settings_to_builder, Checked, DocumentsAdditionResult, DocumentsDiff, DocumentsDiffMode,
+    DocumentsPayload, Facets, PayloadResult, SearchableAttributes, Settings, Unchecked,
+};
+pub use validation::{
+    validate_batch, DocumentReport, DocumentsValidation, FieldStats, MAX_REPORTED_DOCUMENTS,
+    MAX_REPORTED_DUPLICATES,
 };
-pub use updates::{apply_settings_to_builder, Checked, Facets, Settings, Unchecked};
 
+mod aggregate;
+mod check;
+mod derived;
 mod dump;
+mod edition;
 pub mod error;
+mod facet_normalization;
+mod like_documents;
+pub mod metadata;
+mod nested;
+mod prefix_search;
+mod preview;
+mod query_expansion;
+mod readers;
+mod rebuild;
 mod search;
+mod search_defaults;
+mod searchable_weights;
+mod segments;
+mod sequences;
+mod settings_export;
+mod stats_repair;
+mod stop_words;
+mod transforms;
 pub mod updates;
+mod validation;
 
 #[allow(clippy::module_inception)]
 mod index;
 
-pub use index::{Document, IndexMeta, IndexStats};
+pub use index::{DatabaseStats, Document, DocumentsCursor, DocumentsPage, IndexMeta, IndexStats};
 
 #[cfg(not(test))]
 pub use index::Index;
@@ -24,19 +69,28 @@ pub use test::MockIndex as Index;
 /// code for unit testing, in places where an index would normally be used.
 #[cfg(test)]
 pub mod test {
+    use std::collections::BTreeMap;
     use std::path::Path;
     use std::path::PathBuf;
     use std::sync::Arc;
 
     use milli::update::IndexerConfig;
-    use milli::update::{DocumentAdditionResult, DocumentDeletionResult, IndexDocumentsMethod};
+    use milli::update::{IndexDocumentsMethod, UpdateIndexingStep};
     use nelson::Mocker;
+    use serde_json::Value;
     use uuid::Uuid;
 
     use super::error::Result;
     use super::index::Index;
     use super::Document;
-    use super::{Checked, IndexMeta, IndexStats, SearchQuery, SearchResult, Settings};
+    use super::{
+        AggregateQuery, AggregateResult, Checked, DatabaseStats, DocumentEdit,
+        DocumentsAdditionResult, DocumentsCursor, DocumentsEditionResult, DocumentsPage,
+        DocumentsPayload, DocumentsValidation, IndexCheckReport, IndexMeta, IndexStats,
+        PreviewBudget, SearchDefaults, SearchQuery, SearchResult, Settings, SettingsPreview,
+        SnapshotTxn, StatsRepairReport, Unchecked,
+    };
+    use crate::document_formats::PayloadLimits;
     use crate::update_file_store::UpdateFileStore;
 
     #[derive(Clone)]
@@ -53,10 +107,11 @@ pub mod test {
         pub fn open(
             path: impl AsRef<Path>,
             size: usize,
+            max_readers: u32,
             uuid: Uuid,
             update_handler: Arc<IndexerConfig>,
         ) -> Result<Self> {
-            let index = Index::open(path, size, uuid, update_handler)?;
+            let index = Index::open(path, size, max_readers, uuid, update_handler)?;
             Ok(Self::Real(index))
         }
 
@@ -83,6 +138,13 @@ pub mod test {
             }
         }
 
+        pub fn database_stats(&self) -> Result<BTreeMap<String, DatabaseStats>> {
+            match self {
+                MockIndex::Real(index) => index.database_stats(),
+                MockIndex::Mock(m) => unsafe { m.get("database_stats").call(()) },
+            }
+        }
+
         pub fn meta(&self) -> Result<IndexMeta> {
             match self {
                 MockIndex::Real(index) => index.meta(),
@@ -100,11 +162,12 @@ pub mod test {
             &self,
             offset: usize,
             limit: usize,
+            cursor: Option<DocumentsCursor>,
             attributes_to_retrieve: Option<Vec<S>>,
-        ) -> Result<(u64, Vec<Document>)> {
+        ) -> Result<DocumentsPage> {
             match self {
                 MockIndex::Real(index) => {
-                    index.retrieve_documents(offset, limit, attributes_to_retrieve)
+                    index.retrieve_documents(offset, limit, cursor, attributes_to_retrieve)
                 }
                 MockIndex::Mock(_) => todo!(),
             }
@@ -114,9 +177,12 @@ pub mod test {
             &self,
             doc_id: String,
             attributes_to_retrieve: Option<Vec<S>>,
+            with_metadata: bool,
         ) -> Result<Document> {
             match self {
-                MockIndex::Real(index) => index.retrieve_document(doc_id, attributes_to_retrieve),
+                MockIndex::Real(index) => {
+                    index.retrieve_document(doc_id, attributes_to_retrieve, with_metadata)
+                }
                 MockIndex::Mock(_) => todo!(),
             }
         }
@@ -149,6 +215,45 @@ pub mod test {
             }
         }
 
+        pub fn perform_search_at(
+            &self,
+            query: SearchQuery,
+            snapshot: &SnapshotTxn,
+        ) -> Result<SearchResult> {
+            match self {
+                MockIndex::Real(index) => index.perform_search_at(query, snapshot),
+                MockIndex::Mock(_) => todo!(),
+            }
+        }
+
+        pub fn snapshot_txn(&self) -> milli::heed::Result<SnapshotTxn> {
+            match self {
+                MockIndex::Real(index) => index.snapshot_txn(),
+                MockIndex::Mock(_) => todo!(),
+            }
+        }
+
+        pub fn aggregate(&self, query: AggregateQuery) -> Result<AggregateResult> {
+            match self {
+                MockIndex::Real(index) => index.aggregate(query),
+                MockIndex::Mock(m) => unsafe { m.get("aggregate").call(query) },
+            }
+        }
+
+        pub fn check_filter(&self, filter: &Value) -> Result<()> {
+            match self {
+                MockIndex::Real(index) => index.check_filter(filter),
+                MockIndex::Mock(m) => unsafe { m.get("check_filter").call(filter.clone()) },
+            }
+        }
+
+        pub fn search_defaults(&self) -> Result<SearchDefaults> {
+            match self {
+                MockIndex::Real(index) => index.search_defaults(),
+                MockIndex::Mock(m) => unsafe { m.get("search_defaults").call(()) },
+            }
+        }
+
         pub fn dump(&self, path: impl AsRef<Path>) -> Result<()> {
             match self {
                 MockIndex::Real(index) => index.dump(path),
@@ -161,11 +266,12 @@ pub mod test {
             method: IndexDocumentsMethod,
             primary_key: Option<String>,
             file_store: UpdateFileStore,
-            contents: impl Iterator<Item = Uuid>,
-        ) -> Result<DocumentAdditionResult> {
+            contents: impl Iterator<Item = DocumentsPayload>,
+            progress: impl Fn(UpdateIndexingStep) + Sync,
+        ) -> Result<DocumentsAdditionResult> {
             match self {
                 MockIndex::Real(index) => {
-                    index.update_documents(method, primary_key, file_store, contents)
+                    index.update_documents(method, primary_key, file_store, contents, progress)
                 }
                 MockIndex::Mock(mocker) => unsafe {
                     mocker
@@ -175,6 +281,33 @@ pub mod test {
             }
         }
 
+        pub fn update_documents_atomically(
+            &self,
+            method: IndexDocumentsMethod,
+            primary_key: Option<String>,
+            file_store: UpdateFileStore,
+            contents: impl Iterator<Item = DocumentsPayload>,
+            progress: impl Fn(UpdateIndexingStep) + Sync,
+        ) -> Result<DocumentsAdditionResult> {
+            match self {
+                MockIndex::Real(index) => index.update_documents_atomically(
+                    method,
+                    primary_key,
+                    file_store,
+                    contents,
+                    progress,
+                ),
+                MockIndex::Mock(mocker) => unsafe {
+                    mocker.get("update_documents_atomically").call((
+                        method,
+                        primary_key,
+                        file_store,
+                        contents,
+                    ))
+                },
+            }
+        }
+
         pub fn update_settings(&self, settings: &Settings<Checked>) -> Result<()> {
             match self {
                 MockIndex::Real(index) => index.update_settings(settings),
@@ -189,10 +322,10 @@ pub mod test {
             }
         }
 
-        pub fn delete_documents(&self, ids: &[String]) -> Result<DocumentDeletionResult> {
+        pub fn delete_documents(&self, deletions: &[Vec<String>]) -> Result<Vec<u64>> {
             match self {
-                MockIndex::Real(index) => index.delete_documents(ids),
-                MockIndex::Mock(m) => unsafe { m.get("delete_documents").call(ids) },
+                MockIndex::Real(index) => index.delete_documents(deletions),
+                MockIndex::Mock(m) => unsafe { m.get("delete_documents").call(deletions) },
             }
         }
 
@@ -202,6 +335,106 @@ pub mod test {
                 MockIndex::Mock(m) => unsafe { m.get("clear_documents").call(()) },
             }
         }
+
+        pub fn compact(&self) -> Result<u64> {
+            match self {
+                MockIndex::Real(index) => index.compact(),
+                MockIndex::Mock(m) => unsafe { m.get("compact").call(()) },
+            }
+        }
+
+        pub fn edit_documents(
+            &self,
+            filter: Option<&Value>,
+            edits: &[DocumentEdit],
+            progress: impl Fn(usize, usize),
+        ) -> Result<DocumentsEditionResult> {
+            match self {
+                MockIndex::Real(index) => index.edit_documents(filter, edits, progress),
+                MockIndex::Mock(m) => unsafe { m.get("edit_documents").call(()) },
+            }
+        }
+
+        pub fn payload_external_ids(
+            &self,
+            payload: std::fs::File,
+            max: usize,
+        ) -> Result<(Vec<String>, bool)> {
+            match self {
+                MockIndex::Real(index) => index.payload_external_ids(payload, max),
+                MockIndex::Mock(m) => unsafe { m.get("payload_external_ids").call(max) },
+            }
+        }
+
+        pub fn check(&self, fast: bool) -> Result<IndexCheckReport> {
+            match self {
+                MockIndex::Real(index) => index.check(fast),
+                MockIndex::Mock(m) => unsafe { m.get("check").call(fast) },
+            }
+        }
+
+        pub fn repair_stats(&self, report_only: bool) -> Result<StatsRepairReport> {
+            match self {
+                MockIndex::Real(index) => index.repair_stats(report_only),
+                MockIndex::Mock(m) => unsafe { m.get("repair_stats").call(report_only) },
+            }
+        }
+
+        pub fn soft_deleted_ratio(&self) -> Result<f64> {
+            match self {
+                MockIndex::Real(index) => index.soft_deleted_ratio(),
+                MockIndex::Mock(m) => unsafe { m.get("soft_deleted_ratio").call(()) },
+            }
+        }
+
+        pub fn rebuild_into(
+            &self,
+            dst: &MockIndex,
+            progress: impl Fn(UpdateIndexingStep) + Sync,
+        ) -> Result<u64> {
+            match (self, dst) {
+                (MockIndex::Real(index), MockIndex::Real(dst)) => index.rebuild_into(dst, progress),
+                (MockIndex::Mock(m), _) => unsafe { m.get("rebuild_into").call(()) },
+                _ => panic!("a real index can't be rebuilt into a mocked index"),
+            }
+        }
+
+        pub fn preview_settings(
+            &self,
+            shadow_path: &Path,
+            candidate: &Settings<Unchecked>,
+            queries: &[String],
+            sample_size: usize,
+            limit: usize,
+            budget: PreviewBudget,
+        ) -> Result<SettingsPreview> {
+            match self {
+                MockIndex::Real(index) => index.preview_settings(
+                    shadow_path,
+                    candidate,
+                    queries,
+                    sample_size,
+                    limit,
+                    budget,
+                ),
+                MockIndex::Mock(m) => unsafe { m.get("preview_settings").call(()) },
+            }
+        }
+
+        pub fn validate_documents(
+            &self,
+            payload: std::fs::File,
+            primary_key: Option<String>,
+            limits: &PayloadLimits,
+            reject_duplicates: bool,
+        ) -> Result<DocumentsValidation> {
+            match self {
+                MockIndex::Real(index) => {
+                    index.validate_documents(payload, primary_key, limits, reject_duplicates)
+                }
+                MockIndex::Mock(m) => unsafe { m.get("validate_documents").call(primary_key) },
+            }
+        }
     }
 
     #[test]
@@ -0,0 +1,339 @@
+//! Filters on the elements of the arrays of objects.
+//!
+//! milli flattens the arrays of objects: `{"variants": [{"color": "red", "size": "M"}, {"color":
+//! "blue", "size": "L"}]}` is indexed as `variants.color: [red, blue]` and `variants.size: [M, L]`,
+//! so the filter `variants.color = red AND variants.size = L` matches the document even though no
+//! variant is both red and large.
+//!
+//! When the `nestedObjectBehavior` setting of an attribute is `element`, the fields of each
+//! element of the array are derived under the position of the element, along with the number of
+//! elements: `_meilisearch.elements.variants.0.color`, `_meilisearch.elements.variants.1.color`
+//! and `_meilisearch.elements.variants.count`. The conditions of a filter on the fields of the
+//! attribute that are combined with `AND`, within the same parentheses, are rewritten together
+//! into the alternative of the same conditions on the fields of each position, so that they must
+//! all hold within the same element, and milli evaluates them on its facet databases.
+//!
+//! The values of the attribute are indexed a second time, in as many facet fields as the elements
+//! have positions and fields, and the rewritten filters grow with the length of the longest array,
+//! which is why the setting is opt-in. The conditions on an `element` attribute don't require it
+//! to be filterable.
+
+use std::collections::BTreeMap;
+
+use meilisearch_auth::CompiledFilter;
+use milli::heed::RoTxn;
+use milli::{DocumentId, FieldsIdsMap};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use super::derived::{Comparison, Operator, DERIVED_FIELD};
+use super::error::Result;
+
+/// How the arrays of objects of an attribute are filtered.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum NestedObjectBehavior {
+    /// The fields of all the elements are merged, as milli does.
+    Flat,
+    /// The conditions on the fields must hold within one element.
+    Element,
+}
+
+/// The fields of an element, by their path relative to the element. A field holding an array has
+/// several values.
+pub type Element = BTreeMap<String, Vec<Value>>;
+
+/// Returns the attributes whose behavior is `element`.
+pub fn element_attributes(behaviors: &BTreeMap<String, NestedObjectBehavior>) -> Vec<String> {
+    behaviors
+        .iter()
+        .filter(|(_, behavior)| **behavior == NestedObjectBehavior::Element)
+        .map(|(attribute, _)| attribute.clone())
+        .collect()
+}
+
+/// Returns the elements of `attribute` in `document`, in the order of its array.
+fn document_elements(document: &Map<String, Value>, attribute: &str) -> Option<Vec<Element>> {
+    let mut segments = attribute.split('.');
+    let mut value = document.get(segments.next()?)?;
+    for segment in segments {
+        value = value.as_object()?.get(segment)?;
+    }
+    match value {
+        Value::Array(values) => Some(values.iter().map(element).collect()),
+        Value::Object(_) => Some(vec![element(value)]),
+        _ => None,
+    }
+}
+
+fn element(value: &Value) -> Element {
+    fn flatten(path: &str, value: &Value, element: &mut Element) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    let path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    flatten(&path, value, element);
+                }
+            }
+            Value::Array(values) => values
+                .iter()
+                .for_each(|value| flatten(path, value, element)),
+            Value::Null => (),
+            // the elements that are not objects have no field.
+            _ if path.is_empty() => (),
+            scalar => element
+                .entry(path.to_string())
+                .or_default()
+                .push(scalar.clone()),
+        }
+    }
+
+    let mut element = Element::new();
+    flatten("", value, &mut element);
+    element
+}
+
+/// The name of the derived field holding the elements of `attribute`.
+fn elements_field(attribute: &str) -> String {
+    format!("{}.elements.{}", DERIVED_FIELD, attribute)
+}
+
+/// Derives the fields of the elements of the `attributes` of `document`, by position, into the
+/// `derived` values.
+pub fn derive(
+    document: &Map<String, Value>,
+    attributes: &[String],
+    derived: &mut Map<String, Value>,
+) {
+    let mut elements = Map::new();
+    for attribute in attributes {
+        let document_elements = match document_elements(document, attribute) {
+            Some(elements) => elements,
+            None => continue,
+        };
+
+        let mut positions = Map::new();
+        positions.insert(
+            "count".to_string(),
+            Value::Number(Number::from(document_elements.len())),
+        );
+        for (position, element) in document_elements.into_iter().enumerate() {
+            let fields = element
+                .into_iter()
+                .map(|(field, mut values)| match values.len() {
+                    1 => (field, values.remove(0)),
+                    _ => (field, Value::Array(values)),
+                })
+                .collect();
+            positions.insert(position.to_string(), Value::Object(fields));
+        }
+        // milli joins the keys holding a dot like the nested objects.
+        elements.insert(attribute.clone(), Value::Object(positions));
+    }
+    if !elements.is_empty() {
+        derived.insert("elements".to_string(), Value::Object(elements));
+    }
+}
+
+/// Returns the number of positions of the elements of each of the `attributes` that have a field
+/// in the index.
+pub fn positions(fields_ids_map: &FieldsIdsMap, attributes: &[String]) -> BTreeMap<String, usize> {
+    attributes
+        .iter()
+        .map(|attribute| {
+            let prefix = format!("{}.", elements_field(attribute));
+            let positions = fields_ids_map
+                .iter()
+                .filter_map(|(_, name)| {
+                    let rest = name.strip_prefix(&prefix)?;
+                    let position = rest.split('.').next()?.parse::<usize>().ok()?;
+                    Some(position + 1)
+                })
+                .max()
+                .unwrap_or_default();
+            (attribute.clone(), positions)
+        })
+        .collect()
+}
+
+/// Splits a field name into the `element` attribute it belongs to and the path of the field in
+/// the elements. The longest attribute wins.
+pub fn split_field<'a, 'b>(name: &'a str, attributes: &'b [String]) -> Option<(&'b str, &'a str)> {
+    attributes
+        .iter()
+        .filter_map(|attribute| {
+            let field = name.strip_prefix(attribute.as_str())?.strip_prefix('.')?;
+            (!field.is_empty()).then(|| (attribute.as_str(), field))
+        })
+        .max_by_key(|(attribute, _)| attribute.len())
+}
+
+/// Rewrites the `comparisons` on the fields of the elements of `attribute`, by their path in the
+/// elements, into the alternative of the same comparisons on each of the `positions` of the
+/// elements. `None` if a value can't be quoted.
+pub fn rewrite(
+    attribute: &str,
+    comparisons: &[Comparison],
+    positions: usize,
+) -> Option<CompiledFilter> {
+    let elements_field = elements_field(attribute);
+    // the first position is always there, for the filter not to be empty: the elements without
+    // any field only hold the `!=` comparisons.
+    let alternatives = (0..positions.max(1))
+        .map(|position| {
+            let count = Comparison {
+                field: format!("{}.count", elements_field),
+                operator: Operator::GreaterThan(position.to_string()),
+            };
+            let conditions = std::iter::once(count.render())
+                .chain(comparisons.iter().map(|comparison| {
+                    Comparison {
+                        field: format!("{}.{}.{}", elements_field, position, comparison.field),
+                        operator: comparison.operator.clone(),
+                    }
+                    .render()
+                }))
+                .collect::<Option<_>>()?;
+            Some(CompiledFilter::And(conditions))
+        })
+        .collect::<Option<_>>()?;
+    Some(CompiledFilter::Or(alternatives))
+}
+
+pub(super) fn read_document(
+    index: &milli::Index,
+    txn: &RoTxn,
+    docid: DocumentId,
+) -> Result<Map<String, Value>> {
+    let fields_ids_map = index.fields_ids_map(txn)?;
+    let mut document = Map::new();
+    if let Some((_, reader)) = index
+        .documents(txn, std::iter::once(docid))?
+        .into_iter()
+        .next()
+    {
+        for (fid, bytes) in reader.iter() {
+            if let Some(name) = fields_ids_map.name(fid) {
+                document.insert(name.to_string(), serde_json::from_slice(bytes)?);
+            }
+        }
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn attributes() -> Vec<String> {
+        vec!["variants".to_string(), "shop.stocks".to_string()]
+    }
+
+    fn derived(document: Value) -> Value {
+        let mut derived = Map::new();
+        derive(document.as_object().unwrap(), &attributes(), &mut derived);
+        Value::Object(derived)
+    }
+
+    #[test]
+    fn test_derive() {
+        let elements = derived(json!({
+            "variants": [
+                { "color": "red", "size": { "eu": 38, "us": [5, 6] } },
+                "not an object",
+                { "color": null },
+            ],
+            "shop": { "stocks": { "city": "Paris" } },
+            "title": "Shoes",
+        }));
+        let expected = json!({
+            "elements": {
+                "variants": {
+                    "count": 3,
+                    "0": { "color": "red", "size.eu": 38, "size.us": [5, 6] },
+                    "1": {},
+                    "2": {},
+                },
+                "shop.stocks": {
+                    "count": 1,
+                    "0": { "city": "Paris" },
+                },
+            }
+        });
+        assert_eq!(elements, expected);
+
+        assert_eq!(derived(json!({ "title": "no variants" })), json!({}));
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut fields_ids_map = FieldsIdsMap::new();
+        for name in [
+            "variants.color",
+            "_meilisearch.elements.variants.count",
+            "_meilisearch.elements.variants.0.color",
+            "_meilisearch.elements.variants.2.size.eu",
+            "_meilisearch.elements.shop.stocks.0.city",
+        ] {
+            fields_ids_map.insert(name).unwrap();
+        }
+
+        let positions = positions(&fields_ids_map, &attributes());
+        assert_eq!(positions["variants"], 3);
+        assert_eq!(positions["shop.stocks"], 1);
+
+        let positions = positions(&fields_ids_map, &["shop".to_string()]);
+        assert_eq!(positions["shop"], 0);
+    }
+
+    #[test]
+    fn test_split_field() {
+        let attributes = attributes();
+        assert_eq!(
+            split_field("variants.size.eu", &attributes),
+            Some(("variants", "size.eu"))
+        );
+        assert_eq!(
+            split_field("shop.stocks.city", &attributes),
+            Some(("shop.stocks", "city"))
+        );
+        assert_eq!(split_field("variants", &attributes), None);
+        assert_eq!(split_field("variantsCount", &attributes), None);
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let comparisons = [
+            Comparison {
+                field: "color".to_string(),
+                operator: Operator::Equal("red".to_string()),
+            },
+            Comparison {
+                field: "size".to_string(),
+                operator: Operator::Between("38".to_string(), "40".to_string()),
+            },
+        ];
+        assert_eq!(
+            rewrite("variants", &comparisons, 2).unwrap().to_string(),
+            "((_meilisearch.elements.variants.count > 0) \
+             AND (_meilisearch.elements.variants.0.color = red) \
+             AND (_meilisearch.elements.variants.0.size 38 TO 40)) \
+             OR ((_meilisearch.elements.variants.count > 1) \
+             AND (_meilisearch.elements.variants.1.color = red) \
+             AND (_meilisearch.elements.variants.1.size 38 TO 40))"
+        );
+        assert_eq!(
+            rewrite("variants", &comparisons[..1], 0)
+                .unwrap()
+                .to_string(),
+            "((_meilisearch.elements.variants.count > 0) \
+             AND (_meilisearch.elements.variants.0.color = red))"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Control of the prefix matching of the last query word.
+//!
+//! milli matches the last word of a query as the prefix of longer words while the user is still
+//! typing it, so that `12` matches `1234`. When the prefix search is disabled, by the
+//! `prefixSearch` setting or by the search, milli is given the query with a trailing space, and
+//! the last word is a complete term like the other ones. It is still matched with typos, unless
+//! the typo tolerance is disabled too.
+//!
+//! milli can't disable the prefix search on some attributes only. The documents that only have
+//! the last word as the prefix of longer words in the attributes of `disablePrefixOnAttributes`
+//! are excluded from its hits, like the documents that don't match the conditions on the
+//! metadata. A document matching the last word with a typo is excluded as well when it also has
+//! such longer words and nothing else matching the last word.
+
+use std::collections::{BTreeSet, HashSet};
+
+use fst::{Automaton, IntoStreamer};
+use milli::heed::RoTxn;
+use milli::tokenizer::{Analyzer, AnalyzerConfig};
+use milli::FieldId;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+
+use super::error::Result;
+
+/// Whether the last word of the queries is matched as a prefix.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PrefixSearch {
+    /// The last word matches the words it is a prefix of, using the prefixes milli computes when
+    /// indexing the documents.
+    IndexingTime,
+    /// The last word is a complete term.
+    Disabled,
+}
+
+impl Default for PrefixSearch {
+    fn default() -> Self {
+        Self::IndexingTime
+    }
+}
+
+/// Returns the last word of `query`, normalized like the words milli indexes, if milli considers
+/// it as a prefix.
+fn last_prefix(query: &str) -> Option<String> {
+    // like milli, the last word is a prefix only while the user is still typing it.
+    if !query.chars().last().map_or(false, char::is_alphanumeric) {
+        return None;
+    }
+    let stop_words = fst::Set::default();
+    let mut config = AnalyzerConfig::default();
+    config.stop_words(&stop_words);
+    let analyzer = Analyzer::new(config);
+    let analyzed = analyzer.analyze(query);
+    let last = analyzed
+        .tokens()
+        .filter(|token| token.is_word())
+        .last()
+        .map(|token| token.text().to_string());
+    last
+}
+
+/// Whether the prefix search is disabled on the field `name`, by itself or by the object holding
+/// it.
+fn is_disabled(name: &str, attributes: &BTreeSet<String>) -> bool {
+    attributes.iter().any(|attribute| {
+        name == attribute
+            || name
+                .strip_prefix(attribute.as_str())
+                .map_or(false, |rest| rest.starts_with('.'))
+    })
+}
+
+/// Returns the documents whose only match of the last word of `query` are longer words in the
+/// `attributes` where the prefix search is disabled.
+pub fn prefix_only_documents(
+    index: &milli::Index,
+    txn: &RoTxn,
+    query: &str,
+    attributes: &BTreeSet<String>,
+) -> Result<RoaringBitmap> {
+    let last = match last_prefix(query) {
+        Some(last) if !attributes.is_empty() => last,
+        _ => return Ok(RoaringBitmap::new()),
+    };
+    let fields_ids_map = index.fields_ids_map(txn)?;
+    let disabled: HashSet<FieldId> = fields_ids_map
+        .iter()
+        .filter(|(_, name)| is_disabled(name, attributes))
+        .map(|(id, _)| id)
+        .collect();
+    if disabled.is_empty() {
+        return Ok(RoaringBitmap::new());
+    }
+
+    // the words of the attributes without typos are in a database of their own.
+    let word_docids = |word: &str| -> Result<RoaringBitmap> {
+        let docids = index.word_docids.get(txn, word)?.unwrap_or_default();
+        let exact = index.exact_word_docids.get(txn, word)?.unwrap_or_default();
+        Ok(docids | exact)
+    };
+    let words_fst = index.words_fst(txn)?;
+    let longer_words = words_fst
+        .search(fst::automaton::Str::new(&last).starts_with())
+        .into_stream()
+        .into_strs()?;
+    let mut candidates = RoaringBitmap::new();
+    for word in longer_words.iter().filter(|word| **word != last) {
+        candidates |= word_docids(word)?;
+    }
+    candidates -= word_docids(&last)?;
+
+    let mut prefix_only = RoaringBitmap::new();
+    for docid in candidates {
+        let mut matches_elsewhere = false;
+        for entry in index
+            .docid_word_positions
+            .range(txn, &((docid, last.as_str())..))?
+        {
+            let ((entry_docid, word), positions) = entry?;
+            if entry_docid != docid || !word.starts_with(last.as_str()) {
+                break;
+            }
+            if positions.iter().any(|position| {
+                let (field_id, _) = milli::relative_from_absolute_position(position);
+                !disabled.contains(&field_id)
+            }) {
+                matches_elsewhere = true;
+                break;
+            }
+        }
+        if !matches_elsewhere {
+            prefix_only.insert(docid);
+        }
+    }
+    Ok(prefix_only)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_last_prefix() {
+        assert_eq!(last_prefix("red Sho").as_deref(), Some("sho"));
+        assert_eq!(last_prefix("12"), Some("12".to_string()));
+        // a word followed by a space or a quote is complete.
+        assert_eq!(last_prefix("red sho "), None);
+        assert_eq!(last_prefix("\"red shoes\""), None);
+        assert_eq!(last_prefix(""), None);
+    }
+
+    #[test]
+    fn test_is_disabled() {
+        let attributes = ["code", "address"]
+            .iter()
+            .map(|attribute| attribute.to_string())
+            .collect();
+        assert!(is_disabled("code", &attributes));
+        assert!(is_disabled("address.zip", &attributes));
+        assert!(!is_disabled("codes", &attributes));
+        assert!(!is_disabled("title", &attributes));
+    }
+}
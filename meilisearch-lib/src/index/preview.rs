@@ -0,0 +1,234 @@
+//! Preview of a settings change on a sample of the documents of an index.
+//!
+//! A random sample of the documents is indexed into a shadow index, with the settings of the index
+//! updated with the candidate settings, and the sample queries are executed on both the index,
+//! restricted to the sampled documents, and the shadow index. The environment of the shadow index
+//! is opened with the size of the disk budget, so that the indexing of the sample fails rather than
+//! growing past it, and the index itself is only read: its settings are never changed.
+
+use std::io::{Cursor, Seek, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use milli::documents::{DocumentBatchBuilder, DocumentBatchReader};
+use milli::heed::RoTxn;
+use milli::update::IndexDocumentsConfig;
+use rand::seq::IteratorRandom;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use uuid::Uuid;
+
+use super::derived::{self, Derivation};
+use super::edition::external_id;
+use super::error::{IndexError, Result};
+use super::index::{apply_meilisearch_settings, Index};
+use super::metadata;
+use super::search::execute_excluding;
+use super::stop_words;
+use super::updates::apply_settings_to_builder;
+use super::{Settings, Unchecked, DEFAULT_MAX_READERS};
+
+pub const DEFAULT_PREVIEW_SAMPLE_SIZE: usize = 1000;
+pub const MAX_PREVIEW_SAMPLE_SIZE: usize = 10_000;
+pub const DEFAULT_PREVIEW_LIMIT: usize = 10;
+pub const MAX_PREVIEW_LIMIT: usize = 100;
+pub const MAX_PREVIEW_QUERIES: usize = 100;
+
+/// The first hits of a sample query, before and after the settings change.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct QueryPreview {
+    pub q: String,
+    /// The ids of the first hits of the index, among the sampled documents.
+    pub current: Vec<String>,
+    /// The ids of the first hits of the shadow index, with the candidate settings.
+    pub candidate: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsPreview {
+    pub sampled_documents: u64,
+    pub queries: Vec<QueryPreview>,
+}
+
+/// The resources a preview may use.
+#[derive(Debug, Clone, Copy)]
+pub struct PreviewBudget {
+    /// The size of the environment of the shadow index, in bytes.
+    pub disk: usize,
+    pub time: Duration,
+}
+
+impl Index {
+    /// Previews the settings of this index updated with `candidate` on a sample of `sample_size`
+    /// documents, indexed into a shadow index created at `shadow_path`. The caller deletes the
+    /// shadow index afterward.
+    pub fn preview_settings(
+        &self,
+        shadow_path: &Path,
+        candidate: &Settings<Unchecked>,
+        queries: &[String],
+        sample_size: usize,
+        limit: usize,
+        budget: PreviewBudget,
+    ) -> Result<SettingsPreview> {
+        let shadow = Index::open(
+            shadow_path,
+            budget.disk,
+            DEFAULT_MAX_READERS,
+            Uuid::new_v4(),
+            self.indexer_config.clone(),
+        )?;
+        let result = self.preview_into(&shadow, candidate, queries, sample_size, limit, budget);
+        shadow.close();
+
+        result
+    }
+
+    fn preview_into(
+        &self,
+        shadow: &Index,
+        candidate: &Settings<Unchecked>,
+        queries: &[String],
+        sample_size: usize,
+        limit: usize,
+        budget: PreviewBudget,
+    ) -> Result<SettingsPreview> {
+        let started = Instant::now();
+        let check_budget = || match started.elapsed() > budget.time {
+            true => Err(IndexError::SettingsPreviewTimeout(budget.time.as_secs())),
+            false => Ok(()),
+        };
+
+        let rtxn = self.read_txn()?;
+        let documents_ids = self.documents_ids(&rtxn)?;
+        let live = &documents_ids - &self.soft_deleted_documents(&rtxn)?;
+        let sample: RoaringBitmap = live
+            .iter()
+            .choose_multiple(&mut rand::thread_rng(), sample_size)
+            .into_iter()
+            .collect();
+
+        let mut wtxn = shadow.write_txn()?;
+
+        // The candidate settings are applied over the current ones, as an update would.
+        let current = self.settings_txn(&rtxn)?.into_unchecked().check();
+        let candidate = candidate.clone().check();
+        let mut builder =
+            milli::update::Settings::new(&mut wtxn, shadow, shadow.indexer_config.as_ref());
+        let primary_key = self.primary_key(&rtxn)?.map(String::from);
+        if let Some(ref primary_key) = primary_key {
+            builder.set_primary_key(primary_key.clone());
+        }
+        apply_settings_to_builder(&current, &mut builder);
+        apply_settings_to_builder(&candidate, &mut builder);
+        builder.execute(|_| ())?;
+        apply_meilisearch_settings(shadow, &mut wtxn, &current)?;
+        apply_meilisearch_settings(shadow, &mut wtxn, &candidate)?;
+        derived::prepare(
+            shadow,
+            shadow.document_metadata,
+            &mut wtxn,
+            shadow.indexer_config.as_ref(),
+        )?;
+        let derivation = Derivation::read(shadow, &wtxn)?;
+
+        let fields_ids_map = self.fields_ids_map(&rtxn)?;
+        let mut documents_file = tempfile::tempfile()?;
+        let mut documents = DocumentBatchBuilder::new(&mut documents_file)?;
+        let mut json_map = Map::new();
+        let mut buf = Vec::new();
+        for (docid, reader) in self.documents(&rtxn, sample.iter())? {
+            for (fid, bytes) in reader.iter() {
+                if let Some(name) = fields_ids_map.name(fid) {
+                    json_map.insert(name.to_string(), serde_json::from_slice(bytes)?);
+                }
+            }
+            let metadata = metadata::get(self.document_metadata, &rtxn, docid)?;
+            derivation.derive(&mut json_map, metadata.as_ref());
+
+            serde_json::to_writer(&mut buf, &json_map)?;
+            documents.extend_from_json(Cursor::new(&buf))?;
+
+            json_map.clear();
+            buf.clear();
+        }
+        // milli refuses to index an empty batch of documents.
+        if documents.finish()? > 0 {
+            documents_file.seek(SeekFrom::Start(0))?;
+            let mut builder = milli::update::IndexDocuments::new(
+                &mut wtxn,
+                shadow,
+                shadow.indexer_config.as_ref(),
+                IndexDocumentsConfig::default(),
+                |_| (),
+            )?;
+            builder.add_documents(DocumentBatchReader::from_reader(documents_file)?)?;
+            builder.execute()?;
+        }
+        derivation.put_next_sequence(shadow, &mut wtxn)?;
+        wtxn.commit()?;
+
+        // milli can't restrict a search to some documents, the documents left out of the sample
+        // are excluded from the hits of the index.
+        let excluded = documents_ids - &sample;
+        let shadow_rtxn = shadow.read_txn()?;
+        let mut previews = Vec::with_capacity(queries.len());
+        for q in queries {
+            check_budget()?;
+            previews.push(QueryPreview {
+                q: q.clone(),
+                current: first_hits(self, &rtxn, q, &excluded, limit, primary_key.as_deref())?,
+                candidate: first_hits(
+                    shadow,
+                    &shadow_rtxn,
+                    q,
+                    &RoaringBitmap::new(),
+                    limit,
+                    primary_key.as_deref(),
+                )?,
+            });
+        }
+
+        Ok(SettingsPreview {
+            sampled_documents: sample.len(),
+            queries: previews,
+        })
+    }
+}
+
+/// The ids of the first `limit` hits of `q` on `index`, the `excluded` documents left out.
+fn first_hits(
+    index: &Index,
+    rtxn: &RoTxn,
+    q: &str,
+    excluded: &RoaringBitmap,
+    limit: usize,
+    primary_key: Option<&str>,
+) -> Result<Vec<String>> {
+    let fields_ids_map = index.fields_ids_map(rtxn)?;
+    let primary_key = match primary_key.and_then(|name| fields_ids_map.id(name)) {
+        Some(primary_key) => primary_key,
+        None => return Ok(Vec::new()),
+    };
+
+    // the stop words are taken out of the query, as they are by a search.
+    let stop_words = stop_words::stop_words(index, rtxn)?;
+    let mut search = index.search(rtxn);
+    search.query(stop_words::remove_stop_words(q, &stop_words));
+    search.limit(limit);
+    let result = execute_excluding(&mut search, excluded, 0, limit)?;
+
+    let mut ids = Vec::with_capacity(result.documents_ids.len());
+    for (_, obkv) in index.documents(rtxn, result.documents_ids)? {
+        if let Some(bytes) = obkv.get(primary_key) {
+            ids.extend(external_id(&serde_json::from_slice(bytes)?));
+        }
+    }
+
+    Ok(ids)
+}
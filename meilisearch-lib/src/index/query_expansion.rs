@@ -0,0 +1,391 @@
+//! Bounds on the expansion of the query words.
+//!
+//! Each word of a query is expanded by milli into the words of the index that are within the
+//! allowed number of typos, and the last word into all the words it is a prefix of. For very short
+//! words over a large dictionary, these derivations can reach hundreds of thousands of words. The
+//! derivations of each word are capped, keeping the ones found in the most documents.
+//!
+//! milli can't be given the derivations of the words, it still expands them all. The documents
+//! that only match a word by the derivations left out are excluded from its hits instead, like the
+//! documents that only match the last word in the attributes without prefix search.
+//!
+//! The derivations are found on bytes rather than on chars, which makes the typos of non-ASCII
+//! words count slightly more than they do in milli.
+
+use std::collections::BTreeSet;
+
+use fst::{Automaton, IntoStreamer, Set};
+use milli::heed::RoTxn;
+use milli::tokenizer::{Analyzer, AnalyzerConfig};
+use roaring::RoaringBitmap;
+
+use super::error::Result;
+
+/// The caps on the expansion of the query words, as configured in the index settings.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpansionCaps {
+    pub max_word_derivations: usize,
+    pub max_prefix_expansion: usize,
+}
+
+/// The typo tolerance configuration of the index, that determines how many typos a word is
+/// allowed.
+#[derive(Debug, Clone, Copy)]
+pub struct TypoConfig {
+    pub authorize_typos: bool,
+    pub min_word_len_one_typo: u8,
+    pub min_word_len_two_typos: u8,
+}
+
+impl TypoConfig {
+    fn max_typos(&self, word: &str) -> usize {
+        let len = word.chars().count();
+        if !self.authorize_typos || len < self.min_word_len_one_typo as usize {
+            0
+        } else if len < self.min_word_len_two_typos as usize {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+/// The derivations of a query word, split by the caps.
+#[derive(Debug, Default, PartialEq)]
+pub struct Derivations {
+    /// The word itself, if it is in the index, and its most frequent derivations.
+    pub kept: BTreeSet<String>,
+    /// The derivations left out by the caps.
+    pub dropped: BTreeSet<String>,
+}
+
+/// Returns the words of `query`, and whether milli considers them as a prefix.
+fn query_words(query: &str) -> Vec<(String, bool)> {
+    let stop_words = Set::default();
+    let mut config = AnalyzerConfig::default();
+    config.stop_words(&stop_words);
+    let analyzer = Analyzer::new(config);
+    let analyzed = analyzer.analyze(query);
+    let mut words: Vec<_> = analyzed
+        .tokens()
+        .filter(|token| token.is_word())
+        .map(|token| (token.text().to_string(), false))
+        .collect();
+
+    // like milli, we only consider the last word as a prefix if the user is still typing it.
+    if query.chars().last().map_or(false, char::is_alphanumeric) {
+        if let Some((_, is_prefix)) = words.last_mut() {
+            *is_prefix = true;
+        }
+    }
+    words
+}
+
+/// Returns the derivations of `word`, with at most `max_word_derivations` of its typo derivations
+/// and `max_prefix_expansion` of its prefix derivations kept, the ones of the highest
+/// `frequency`.
+pub fn word_derivations<D, F>(
+    words_fst: &Set<D>,
+    word: &str,
+    is_prefix: bool,
+    caps: ExpansionCaps,
+    typo_config: TypoConfig,
+    mut frequency: F,
+) -> Result<Derivations>
+where
+    D: AsRef<[u8]>,
+    F: FnMut(&str) -> Result<u64>,
+{
+    let mut derivations = Derivations::default();
+
+    let max_typos = typo_config.max_typos(word);
+    if max_typos > 0 {
+        let automaton = Levenshtein::new(word, max_typos);
+        let typos = words_fst.search(automaton).into_stream().into_strs()?;
+        split_by_frequency(
+            typos,
+            word,
+            caps.max_word_derivations,
+            &mut frequency,
+            &mut derivations,
+        )?;
+    }
+
+    if is_prefix {
+        let automaton = fst::automaton::Str::new(word).starts_with();
+        let longer_words = words_fst.search(automaton).into_stream().into_strs()?;
+        split_by_frequency(
+            longer_words,
+            word,
+            caps.max_prefix_expansion,
+            &mut frequency,
+            &mut derivations,
+        )?;
+    }
+
+    // a derivation kept by one of the caps is matched by milli whatever the other one.
+    let Derivations { kept, dropped } = &mut derivations;
+    dropped.retain(|derivation| !kept.contains(derivation));
+    if words_fst.contains(word) {
+        kept.insert(word.to_string());
+    }
+
+    Ok(derivations)
+}
+
+/// Keeps the `cap` most frequent of `words`, the ones found in the most documents, and drops the
+/// others. `word` itself is not one of its derivations.
+fn split_by_frequency<F>(
+    words: Vec<String>,
+    word: &str,
+    cap: usize,
+    frequency: &mut F,
+    derivations: &mut Derivations,
+) -> Result<()>
+where
+    F: FnMut(&str) -> Result<u64>,
+{
+    let mut words = words
+        .into_iter()
+        .filter(|derivation| derivation != word)
+        .map(|derivation| Ok((frequency(&derivation)?, derivation)))
+        .collect::<Result<Vec<_>>>()?;
+    words.sort_by(|(lhs_frequency, lhs), (rhs_frequency, rhs)| {
+        rhs_frequency.cmp(lhs_frequency).then_with(|| lhs.cmp(rhs))
+    });
+
+    let dropped = words.split_off(cap.min(words.len()));
+    derivations
+        .kept
+        .extend(words.into_iter().map(|(_, derivation)| derivation));
+    derivations
+        .dropped
+        .extend(dropped.into_iter().map(|(_, derivation)| derivation));
+    Ok(())
+}
+
+/// Returns the documents that only match a word of `query` by the derivations the caps leave out,
+/// `None` if the caps leave out none.
+pub fn truncated_documents(
+    index: &milli::Index,
+    txn: &RoTxn,
+    query: &str,
+    caps: ExpansionCaps,
+    typo_config: TypoConfig,
+) -> Result<Option<RoaringBitmap>> {
+    // the words of the attributes without typos are in a database of their own.
+    let word_docids = |word: &str| -> Result<RoaringBitmap> {
+        let docids = index.word_docids.get(txn, word)?.unwrap_or_default();
+        let exact = index.exact_word_docids.get(txn, word)?.unwrap_or_default();
+        Ok(docids | exact)
+    };
+    let words_fst = index.words_fst(txn)?;
+
+    let mut truncated: Option<RoaringBitmap> = None;
+    for (word, is_prefix) in query_words(query) {
+        let derivations =
+            word_derivations(&words_fst, &word, is_prefix, caps, typo_config, |word| {
+                Ok(word_docids(word)?.len())
+            })?;
+        if derivations.dropped.is_empty() {
+            continue;
+        }
+
+        let mut documents = RoaringBitmap::new();
+        for derivation in &derivations.dropped {
+            documents |= word_docids(derivation)?;
+        }
+        for derivation in &derivations.kept {
+            documents -= word_docids(derivation)?;
+        }
+        *truncated.get_or_insert_with(RoaringBitmap::new) |= documents;
+    }
+
+    Ok(truncated)
+}
+
+/// An automaton matching the words within a maximum edit distance of a word.
+struct Levenshtein<'a> {
+    word: &'a [u8],
+    max_distance: usize,
+}
+
+impl<'a> Levenshtein<'a> {
+    fn new(word: &'a str, max_distance: usize) -> Self {
+        Self {
+            word: word.as_bytes(),
+            max_distance,
+        }
+    }
+}
+
+impl Automaton for Levenshtein<'_> {
+    /// The last row of the Wagner-Fischer matrix, or `None` once no match is possible anymore.
+    type State = Option<Vec<usize>>;
+
+    fn start(&self) -> Self::State {
+        Some((0..=self.word.len()).collect())
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        state
+            .as_ref()
+            .and_then(|row| row.last())
+            .map_or(false, |&distance| distance <= self.max_distance)
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        state.is_some()
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        let row = state.as_ref()?;
+        let mut next = Vec::with_capacity(row.len());
+        next.push(row[0] + 1);
+        for (i, &c) in self.word.iter().enumerate() {
+            let cost = if c == byte { 0 } else { 1 };
+            let distance = (row[i] + cost).min(row[i + 1] + 1).min(next[i] + 1);
+            next.push(distance);
+        }
+
+        if next.iter().any(|&distance| distance <= self.max_distance) {
+            Some(next)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const TYPO_CONFIG: TypoConfig = TypoConfig {
+        authorize_typos: true,
+        min_word_len_one_typo: 5,
+        min_word_len_two_typos: 9,
+    };
+
+    /// Builds a dictionary of `n` pseudo random lowercase words.
+    fn synthetic_dictionary(n: usize) -> Set<Vec<u8>> {
+        let mut seed: u64 = 42;
+        let mut words: Vec<String> = (0..n)
+            .map(|_| {
+                let len = 3 + (seed % 8) as usize;
+                (0..len)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        (b'a' + (seed >> 33) as u8 % 26) as char
+                    })
+                    .collect()
+            })
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+        Set::from_iter(words).unwrap()
+    }
+
+    fn words(words: &[&str]) -> BTreeSet<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_levenshtein_automaton() {
+        let words = Set::from_iter(["hallo", "hello", "hellos", "help", "world"]).unwrap();
+        let matches = words
+            .search(Levenshtein::new("hello", 1))
+            .into_stream()
+            .into_strs()
+            .unwrap();
+        assert_eq!(matches, vec!["hallo", "hello", "hellos"]);
+    }
+
+    #[test]
+    fn test_query_words() {
+        assert_eq!(
+            query_words("hello wor"),
+            vec![("hello".to_string(), false), ("wor".to_string(), true)]
+        );
+        // the last word is not a prefix anymore once followed by a space.
+        assert_eq!(query_words("hel "), vec![("hel".to_string(), false)]);
+    }
+
+    #[test]
+    fn test_nothing_dropped_under_the_caps() {
+        let fst = Set::from_iter(["hallo", "hello", "hellos", "help", "world"]).unwrap();
+        let caps = ExpansionCaps {
+            max_word_derivations: 10,
+            max_prefix_expansion: 10,
+        };
+
+        let derivations =
+            word_derivations(&fst, "hello", true, caps, TYPO_CONFIG, |_| Ok(1)).unwrap();
+        assert_eq!(derivations.kept, words(&["hallo", "hello", "hellos"]));
+        assert!(derivations.dropped.is_empty());
+    }
+
+    #[test]
+    fn test_most_frequent_derivations_are_kept() {
+        let fst = Set::from_iter(["hallo", "hello", "hellos", "help", "world"]).unwrap();
+        let frequencies: HashMap<_, _> = [("hallo", 6), ("hello", 1), ("hellos", 5), ("help", 8)]
+            .into_iter()
+            .collect();
+        let frequency = |word: &str| Ok(frequencies.get(word).copied().unwrap_or(0));
+
+        let caps = ExpansionCaps {
+            max_word_derivations: 10,
+            max_prefix_expansion: 1,
+        };
+        let derivations =
+            word_derivations(&fst, "hel", true, caps, TYPO_CONFIG, frequency).unwrap();
+        assert_eq!(derivations.kept, words(&["help"]));
+        assert_eq!(derivations.dropped, words(&["hello", "hellos"]));
+
+        // the word itself is always kept, and is not one of its derivations.
+        let caps = ExpansionCaps {
+            max_word_derivations: 1,
+            max_prefix_expansion: 10,
+        };
+        let derivations =
+            word_derivations(&fst, "hello", false, caps, TYPO_CONFIG, frequency).unwrap();
+        assert_eq!(derivations.kept, words(&["hallo", "hello"]));
+        assert_eq!(derivations.dropped, words(&["hellos"]));
+
+        // a derivation dropped by a cap but kept by the other one is kept.
+        let derivations =
+            word_derivations(&fst, "hello", true, caps, TYPO_CONFIG, frequency).unwrap();
+        assert_eq!(derivations.kept, words(&["hallo", "hello", "hellos"]));
+        assert!(derivations.dropped.is_empty());
+    }
+
+    #[test]
+    fn test_one_letter_query_over_large_dictionary_is_bounded() {
+        let fst = synthetic_dictionary(500_000);
+        let caps = ExpansionCaps {
+            max_word_derivations: 100,
+            max_prefix_expansion: 1000,
+        };
+        let frequency = |word: &str| Ok(word.len() as u64);
+
+        let derivations = word_derivations(&fst, "a", true, caps, TYPO_CONFIG, frequency).unwrap();
+        assert!(derivations.dropped.len() > 10 * caps.max_prefix_expansion);
+        assert_eq!(derivations.kept.len(), caps.max_prefix_expansion);
+
+        // the longest words, the most frequent here, are the ones kept.
+        let shortest_kept = derivations
+            .kept
+            .iter()
+            .map(|word| word.len())
+            .min()
+            .unwrap();
+        let longest_dropped = derivations
+            .dropped
+            .iter()
+            .map(|word| word.len())
+            .max()
+            .unwrap();
+        assert!(shortest_kept >= longest_dropped);
+    }
+}
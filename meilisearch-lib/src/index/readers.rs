@@ -0,0 +1,141 @@
+//! The reader slots of the environment of an index.
+//!
+//! LMDB gives each read transaction a slot of the reader table of the environment, whose size is
+//! fixed when the environment is opened. A read transaction can't be opened while all the slots
+//! are taken, so the read transactions of the index are counted, to be shown in the stats, and
+//! the error of a full reader table is reported as a temporary condition.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use milli::heed::{Env, RoTxn};
+use serde::Serialize;
+
+/// The number of reader slots of an environment opened without `--max-readers`. It is the default
+/// of LMDB.
+pub const DEFAULT_MAX_READERS: u32 = 126;
+
+#[derive(Debug)]
+pub struct ReaderSlots {
+    max: u32,
+    in_use: AtomicU32,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReaderStats {
+    /// The number of reader slots of the environment.
+    pub max_readers: u32,
+    /// The number of read transactions opened by the index that are not finished yet.
+    pub readers_in_use: u32,
+}
+
+impl ReaderSlots {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max,
+            in_use: AtomicU32::new(0),
+        }
+    }
+
+    /// Opens a read transaction on `env`, that holds one of the slots until it is dropped.
+    pub fn read_txn<'e>(&'e self, env: &'e Env) -> milli::heed::Result<ReadTxn<'e>> {
+        let txn = env.read_txn()?;
+        self.in_use.fetch_add(1, Ordering::Relaxed);
+        Ok(ReadTxn { txn, slots: self })
+    }
+
+    pub fn stats(&self) -> ReaderStats {
+        ReaderStats {
+            max_readers: self.max,
+            readers_in_use: self.in_use.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A read transaction accounted for in the reader slots of its environment.
+pub struct ReadTxn<'e> {
+    txn: RoTxn<'e>,
+    slots: &'e ReaderSlots,
+}
+
+impl<'e> Deref for ReadTxn<'e> {
+    type Target = RoTxn<'e>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+impl Drop for ReadTxn<'_> {
+    fn drop(&mut self) {
+        self.slots.in_use.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A read transaction that keeps the environment and the reader slots it borrows alive, so that
+/// it can outlive the request that opened it, like the snapshot of a point in time.
+pub struct SnapshotTxn {
+    // declared first, so that the transaction is dropped before what it borrows.
+    txn: ReadTxn<'static>,
+    _index: Arc<milli::Index>,
+    _slots: Arc<ReaderSlots>,
+}
+
+// SAFETY: heed opens the environments with `MDB_NOTLS`, so a read transaction isn't bound to the
+// thread that opened it, as long as it is used by one thread at a time, which `&mut` guarantees.
+unsafe impl Send for SnapshotTxn {}
+
+impl SnapshotTxn {
+    pub fn new(index: Arc<milli::Index>, slots: Arc<ReaderSlots>) -> milli::heed::Result<Self> {
+        let txn = slots.read_txn(&index.env)?;
+        // SAFETY: the transaction only borrows the environment of `index` and `slots`, which are
+        // kept alive behind their `Arc` for as long as it is.
+        let txn = unsafe { std::mem::transmute::<ReadTxn<'_>, ReadTxn<'static>>(txn) };
+        Ok(Self {
+            txn,
+            _index: index,
+            _slots: slots,
+        })
+    }
+}
+
+impl Deref for SnapshotTxn {
+    type Target = RoTxn<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use milli::heed::EnvOpenOptions;
+
+    use super::*;
+
+    #[test]
+    fn test_read_txn_holds_a_slot_until_dropped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100);
+        options.max_readers(8);
+        let env = options.open(tmp.path()).unwrap();
+
+        let slots = ReaderSlots::new(8);
+        let first = slots.read_txn(&env).unwrap();
+        let second = slots.read_txn(&env).unwrap();
+        assert_eq!(
+            slots.stats(),
+            ReaderStats {
+                max_readers: 8,
+                readers_in_use: 2
+            }
+        );
+
+        drop(first);
+        drop(second);
+        assert_eq!(slots.stats().readers_in_use, 0);
+    }
+}
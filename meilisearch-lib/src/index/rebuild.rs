@@ -0,0 +1,106 @@
+//! Rebuild of an index from its own documents.
+//!
+//! The documents, their metadata and the settings of an index are copied into a fresh, empty
+//! index, where the documents are indexed again from scratch. The source index is only read, so
+//! that it stays usable, and untouched, whatever happens to the rebuild.
+
+use std::io::{Cursor, Seek, SeekFrom};
+
+use log::{debug, info};
+use milli::documents::{DocumentBatchBuilder, DocumentBatchReader};
+use milli::update::{IndexDocumentsConfig, UpdateIndexingStep};
+use serde_json::Map;
+
+use super::derived::{self, Derivation};
+use super::error::Result;
+use super::index::{apply_meilisearch_settings, Index};
+use super::metadata;
+use super::updates::apply_settings_to_builder;
+
+impl Index {
+    /// Indexes the live documents of this index into `dst`, which must be empty, with the settings
+    /// and the primary key of this index. Returns the number of documents that were indexed.
+    pub fn rebuild_into(
+        &self,
+        dst: &Index,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
+    ) -> Result<u64> {
+        let rtxn = self.read_txn()?;
+        let mut wtxn = dst.write_txn()?;
+
+        // The settings go through the same conversion as in a dump, so that the settings left to
+        // their default value stay so.
+        let settings = self.settings_txn(&rtxn)?.into_unchecked().check();
+        let mut builder = milli::update::Settings::new(&mut wtxn, dst, dst.indexer_config.as_ref());
+        if let Some(primary_key) = self.primary_key(&rtxn)? {
+            builder.set_primary_key(primary_key.to_string());
+        }
+        apply_settings_to_builder(&settings, &mut builder);
+        builder.execute(|indexing_step| debug!("update: {:?}", indexing_step))?;
+        apply_meilisearch_settings(dst, &mut wtxn, &settings)?;
+        derived::prepare(
+            dst,
+            dst.document_metadata,
+            &mut wtxn,
+            dst.indexer_config.as_ref(),
+        )?;
+        let derivation = Derivation::read(dst, &wtxn)?;
+
+        let fields_ids_map = self.fields_ids_map(&rtxn)?;
+        let soft_deleted = self.soft_deleted_documents(&rtxn)?;
+
+        let mut documents_file = tempfile::tempfile()?;
+        let mut documents = DocumentBatchBuilder::new(&mut documents_file)?;
+        let mut json_map = Map::new();
+        let mut buf = Vec::new();
+        for document in self.all_documents(&rtxn)? {
+            let (id, reader) = document?;
+            if soft_deleted.contains(id) {
+                continue;
+            }
+
+            for (fid, bytes) in reader.iter() {
+                if let Some(name) = fields_ids_map.name(fid) {
+                    json_map.insert(name.to_string(), serde_json::from_slice(bytes)?);
+                }
+            }
+            let metadata = metadata::get(self.document_metadata, &rtxn, id)?;
+            derivation.derive(&mut json_map, metadata.as_ref());
+
+            serde_json::to_writer(&mut buf, &json_map)?;
+            documents.extend_from_json(Cursor::new(&buf))?;
+
+            json_map.clear();
+            buf.clear();
+        }
+        let count = documents.finish()?;
+
+        let mut rebuilt_documents = 0;
+        // milli refuses to index an empty batch of documents.
+        if count > 0 {
+            documents_file.seek(SeekFrom::Start(0))?;
+            let mut builder = milli::update::IndexDocuments::new(
+                &mut wtxn,
+                dst,
+                dst.indexer_config.as_ref(),
+                IndexDocumentsConfig::default(),
+                progress,
+            )?;
+            builder.add_documents(DocumentBatchReader::from_reader(documents_file)?)?;
+            rebuilt_documents = builder.execute()?.indexed_documents;
+        }
+        derivation.put_next_sequence(dst, &mut wtxn)?;
+
+        let recorded = metadata::by_external_id(self, self.document_metadata, &rtxn)?;
+        metadata::put_by_external_id(dst, dst.document_metadata, &mut wtxn, recorded)?;
+
+        wtxn.commit()?;
+
+        info!(
+            "rebuild of index {} done: {} documents indexed in index {}",
+            self.uuid, rebuilt_documents, dst.uuid
+        );
+
+        Ok(rebuilt_documents)
+    }
+}
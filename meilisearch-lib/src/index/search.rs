@@ -1,21 +1,35 @@
-use std::cmp::min;
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::cmp::{min, Ordering};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Deref;
 use std::str::FromStr;
 use std::time::Instant;
 
 use either::Either;
+use milli::heed::{RoTxn, RwTxn};
 use milli::tokenizer::{Analyzer, AnalyzerConfig};
 use milli::{
-    AscDesc, FieldId, FieldsIdsMap, Filter, FormatOptions, MatchBounds, MatcherBuilder, SortError,
+    AscDesc, Criterion, FieldId, FieldsIdsMap, Filter, FormatOptions, MatchBounds, MatcherBuilder,
+    SortError,
 };
 use regex::Regex;
+use roaring::RoaringBitmap;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use crate::index::error::FacetError;
+use crate::index::query_expansion::{self, ExpansionCaps, TypoConfig};
 
+use super::derived::{document_fields, is_derived, Derivation};
 use super::error::{IndexError, Result};
+use super::facet_normalization;
 use super::index::Index;
+use super::like_documents;
+use super::prefix_search::{self, PrefixSearch};
+use super::readers::{ReadTxn, SnapshotTxn};
+use super::searchable_weights;
+use super::sequences;
+use super::stop_words;
+use super::updates::strip_path_wildcard;
 
 pub type Document = serde_json::Map<String, Value>;
 type MatchesPosition = BTreeMap<String, Vec<MatchBounds>>;
@@ -25,6 +39,8 @@ pub const DEFAULT_CROP_LENGTH: fn() -> usize = || 10;
 pub const DEFAULT_CROP_MARKER: fn() -> String = || "…".to_string();
 pub const DEFAULT_HIGHLIGHT_PRE_TAG: fn() -> String = || "<em>".to_string();
 pub const DEFAULT_HIGHLIGHT_POST_TAG: fn() -> String = || "</em>".to_string();
+pub const DEFAULT_GROUP_LIMIT: fn() -> usize = || 1;
+pub const DEFAULT_IGNORE_STOP_WORDS: fn() -> bool = || true;
 
 /// The maximimum number of results that the engine
 /// will be able to return in one search call.
@@ -34,6 +50,15 @@ pub const HARD_RESULT_LIMIT: usize = 1000;
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct SearchQuery {
     pub q: Option<String>,
+    /// Whether the stop words of the query, out of its quoted phrases, are ignored. The stop words
+    /// of the quoted phrases are never ignored.
+    #[serde(default = "DEFAULT_IGNORE_STOP_WORDS")]
+    pub ignore_stop_words: bool,
+    /// Whether the last word of the query is matched as a prefix, in place of the `prefixSearch`
+    /// setting of the index.
+    pub prefix_search: Option<PrefixSearch>,
+    /// Searches the documents like these ones instead of a text query, these ones excluded.
+    pub like_document_ids: Option<Vec<String>>,
     pub offset: Option<usize>,
     #[serde(default = "DEFAULT_SEARCH_LIMIT")]
     pub limit: usize,
@@ -45,7 +70,26 @@ pub struct SearchQuery {
     // Default to false
     #[serde(default = "Default::default")]
     pub show_matches_position: bool,
+    #[serde(default = "Default::default")]
+    pub show_ranking_score: bool,
     pub filter: Option<Value>,
+    /// Filters whose matching documents are ranked before the others.
+    pub boost: Option<Vec<SearchBoost>>,
+    /// The weights of the searchable attributes for this search, in place of the ones of the
+    /// index.
+    pub searchable_weights: Option<BTreeMap<String, u32>>,
+    /// The ranking rules of this search, in place of the ones of the index. They are never
+    /// persisted.
+    pub ranking_rules_override: Option<Vec<String>>,
+    /// Groups the hits by the value of an attribute. The offset and the limit of the search then
+    /// apply to the groups.
+    pub group_by: Option<SearchGroupBy>,
+    /// Whether the search opens a point in time, whose token serves the next pages from the same
+    /// snapshot of the index.
+    #[serde(default)]
+    pub pit: bool,
+    /// The token of the point in time the search is served from.
+    pub pit_token: Option<String>,
     pub sort: Option<Vec<String>>,
     pub facets: Option<Vec<String>>,
     #[serde(default = "DEFAULT_HIGHLIGHT_PRE_TAG")]
@@ -56,6 +100,61 @@ pub struct SearchQuery {
     pub crop_marker: String,
 }
 
+impl Default for SearchQuery {
+    fn default() -> Self {
+        Self {
+            q: None,
+            ignore_stop_words: DEFAULT_IGNORE_STOP_WORDS(),
+            prefix_search: None,
+            like_document_ids: None,
+            offset: None,
+            limit: DEFAULT_SEARCH_LIMIT(),
+            attributes_to_retrieve: None,
+            attributes_to_crop: None,
+            crop_length: DEFAULT_CROP_LENGTH(),
+            attributes_to_highlight: None,
+            show_matches_position: false,
+            show_ranking_score: false,
+            filter: None,
+            boost: None,
+            searchable_weights: None,
+            ranking_rules_override: None,
+            group_by: None,
+            pit: false,
+            pit_token: None,
+            sort: None,
+            facets: None,
+            highlight_pre_tag: DEFAULT_HIGHLIGHT_PRE_TAG(),
+            highlight_post_tag: DEFAULT_HIGHLIGHT_POST_TAG(),
+            crop_marker: DEFAULT_CROP_MARKER(),
+        }
+    }
+}
+
+/// Ranks the documents matching `filter` before the others, whatever the ranking rules. A
+/// document matching several boosts is ranked with the one of highest `weight`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SearchBoost {
+    pub filter: Value,
+    pub weight: f64,
+}
+
+/// Keeps the `limit` best hits of each value of the filterable `attribute`. The groups are ordered
+/// by their best hit, so a sort orders them by the sorted value of their first hit.
+///
+/// A document with several values for the attribute is grouped under the first of them, numbers
+/// first, then strings, both in ascending order. The documents missing the attribute form their
+/// own group each. The hits can't be grouped on an index with a distinct attribute, which already
+/// keeps a single hit per value.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SearchGroupBy {
+    pub attribute: String,
+    #[serde(default = "DEFAULT_GROUP_LIMIT")]
+    pub limit: usize,
+}
+
 #[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct SearchHit {
     #[serde(flatten)]
@@ -64,6 +163,11 @@ pub struct SearchHit {
     pub formatted: Document,
     #[serde(rename = "_matchesPosition", skip_serializing_if = "Option::is_none")]
     pub matches_position: Option<MatchesPosition>,
+    #[serde(rename = "_rankingScore", skip_serializing_if = "Option::is_none")]
+    pub ranking_score: Option<f64>,
+    /// The weight of the boost that ranked the hit, shown along with its ranking score.
+    #[serde(rename = "_rankingScoreBoost", skip_serializing_if = "Option::is_none")]
+    pub ranking_score_boost: Option<f64>,
 }
 
 #[derive(Serialize, Debug, Clone, PartialEq)]
@@ -77,17 +181,152 @@ pub struct SearchResult {
     pub processing_time_ms: u128,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub facet_distribution: Option<BTreeMap<String, BTreeMap<String, u64>>>,
+    /// Whether the expansion of the query words was restricted because it exceeded the caps
+    /// configured on the index.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated_query_expansion: bool,
+    /// Why a search like some documents has no hits, when no query could be built from them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub like_documents_warning: Option<String>,
+    /// The token of the point in time the search was served from, to pass with the next pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pit_token: Option<String>,
+    /// The hits by group, when the search groups them. `hits` is then empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<SearchGroup>>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchGroup {
+    /// The value of the attribute shared by the hits of the group, `null` for a document missing
+    /// the attribute.
+    pub key: Value,
+    pub hits: Vec<SearchHit>,
+    /// The number of documents of the group matching the search.
+    pub estimated_total_hits: u64,
+}
+
+/// A group of the first hits of a search, with the rank of each of its documents among them.
+struct HitGroup {
+    key: Value,
+    documents: Vec<(usize, u32)>,
+    estimated_total_hits: u64,
 }
 
 impl Index {
     pub fn perform_search(&self, query: SearchQuery) -> Result<SearchResult> {
+        self.perform_search_in(query, None)
+    }
+
+    /// Performs the search on the snapshot of the index read by `snapshot`, rather than on its
+    /// current state.
+    pub fn perform_search_at(
+        &self,
+        query: SearchQuery,
+        snapshot: &SnapshotTxn,
+    ) -> Result<SearchResult> {
+        self.perform_search_in(query, Some(snapshot))
+    }
+
+    fn perform_search_in(
+        &self,
+        query: SearchQuery,
+        snapshot: Option<&SnapshotTxn>,
+    ) -> Result<SearchResult> {
         let before_search = Instant::now();
-        let rtxn = self.read_txn()?;
+        let rtxn = match (snapshot, &query.ranking_rules_override) {
+            (Some(txn), _) => SearchTxn::Snapshot(txn),
+            (None, Some(rules)) => SearchTxn::RankingRulesOverride(self.ranking_rules_txn(rules)?),
+            (None, None) => SearchTxn::Read(self.read_txn()?),
+        };
 
         let mut search = self.search(&rtxn);
 
-        if let Some(ref query) = query.q {
-            search.query(query);
+        let (q, like) = match query.like_document_ids {
+            Some(ref ids) => {
+                if query.q.is_some() {
+                    return Err(IndexError::LikeDocumentsWithQuery);
+                }
+                let seeds = like_documents::seed_documents(self, &rtxn, ids)?;
+                let terms = like_documents::significant_terms(self, &rtxn, &seeds)?;
+                if terms.is_empty() {
+                    return Ok(SearchResult {
+                        hits: Vec::new(),
+                        estimated_total_hits: 0,
+                        query: String::new(),
+                        limit: query.limit,
+                        offset: query.offset.unwrap_or_default(),
+                        processing_time_ms: before_search.elapsed().as_millis(),
+                        facet_distribution: query.facets.as_ref().map(|_| BTreeMap::new()),
+                        truncated_query_expansion: false,
+                        like_documents_warning: Some(
+                            like_documents::NO_SIGNIFICANT_TERMS.to_string(),
+                        ),
+                        pit_token: None,
+                        groups: query.group_by.as_ref().map(|_| Vec::new()),
+                    });
+                }
+                (Some(terms.join(" ")), Some((seeds, terms)))
+            }
+            None => (query.q.clone(), None),
+        };
+
+        // milli splits the words of the languages written without spaces into characters, the
+        // words of the query in the languages of the custom segmenters are given to it encoded,
+        // as their words are derived from the documents.
+        let custom_segmenters = self.custom_segmenters(&rtxn)?;
+        let searched_query = q.clone().unwrap_or_default();
+        let q = match q {
+            Some(q) if like.is_none() && !custom_segmenters.is_empty() => {
+                Some(custom_segmenters.encode_query(&q))
+            }
+            q => q,
+        };
+
+        // milli indexes the stop words at their positions, they are taken out of the query unless
+        // they are quoted or the search doesn't ignore them.
+        let q = match q {
+            Some(q) if like.is_none() && query.ignore_stop_words => {
+                let stop_words = stop_words::stop_words(self, &rtxn)?;
+                Some(stop_words::remove_stop_words(&q, &stop_words))
+            }
+            q => q,
+        };
+
+        let prefix_search_mode = match query.prefix_search {
+            Some(mode) => mode,
+            None => self.prefix_search(&rtxn)?,
+        };
+        let mut truncated_query_expansion = false;
+        let mut truncated_documents = RoaringBitmap::new();
+        let mut prefix_only_documents = RoaringBitmap::new();
+        if let Some(ref query) = q {
+            // the last word is a whole word without prefix search, like the terms of a query built
+            // from documents.
+            let truncated = match like.is_some() || prefix_search_mode == PrefixSearch::Disabled {
+                true => self.truncated_documents(&rtxn, &format!("{} ", query))?,
+                false => self.truncated_documents(&rtxn, query)?,
+            };
+            if let Some(documents) = truncated {
+                truncated_query_expansion = true;
+                truncated_documents = documents;
+            }
+
+            // a trailing space prevents milli from considering the last word as a prefix.
+            if prefix_search_mode == PrefixSearch::Disabled {
+                search.query(format!("{} ", query));
+            } else {
+                if like.is_none() {
+                    prefix_only_documents = prefix_search::prefix_only_documents(
+                        self,
+                        &rtxn,
+                        query,
+                        &self.disable_prefix_on_attributes(&rtxn)?,
+                    )?;
+                }
+                search.query(query);
+            }
         }
 
         // Make sure that a user can't get more documents than the hard limit,
@@ -98,13 +337,31 @@ impl Index {
         search.offset(offset);
         search.limit(limit);
 
-        if let Some(ref filter) = query.filter {
+        // the conditions on the normalized facet values, on the elements of the arrays of objects
+        // and on the metadata of the documents, and the sorts on their metadata, are evaluated by
+        // milli, on the derived values.
+        let derivation = Derivation::read(self, &rtxn)?;
+        let filter = query
+            .filter
+            .as_ref()
+            .map(|filter| derivation.rewrite_filter(filter));
+        let sort = query
+            .sort
+            .as_ref()
+            .map(|sort| derivation.rewrite_sort(sort));
+        // milli leaves the soft deleted documents out of its candidates, before it ranks them.
+        let filter = match sequences::live_filter(&self.soft_deleted_sequences(&rtxn)?) {
+            Some(live) => Some(and_filter(filter, live)),
+            None => filter,
+        };
+
+        if let Some(ref filter) = filter {
             if let Some(facets) = parse_filter(filter)? {
                 search.filter(facets);
             }
         }
 
-        if let Some(ref sort) = query.sort {
+        if let Some(ref sort) = sort {
             let sort = match sort.iter().map(|s| AscDesc::from_str(s)).collect() {
                 Ok(sorts) => sorts,
                 Err(asc_desc_error) => {
@@ -115,19 +372,102 @@ impl Index {
             search.sort_criteria(sort);
         }
 
+        let boosts = match query.boost {
+            Some(ref boosts) => self.boosted_documents(&rtxn, &derivation, boosts)?,
+            None => Vec::new(),
+        };
+        if let Some(ref group_by) = query.group_by {
+            self.check_group_by(&rtxn, group_by)?;
+        }
+        // the weights rank the documents by the attributes matching the query.
+        let field_weights = match q {
+            Some(_) => {
+                searchable_weights::field_weights(self, &rtxn, query.searchable_weights.as_ref())?
+            }
+            None => None,
+        };
+
+        let mut excluded = prefix_only_documents | truncated_documents;
+        if let Some((ref seeds, _)) = like {
+            excluded |= seeds;
+        }
+
+        let execute = |search: &mut milli::Search, offset, limit| match like {
+            Some((_, ref terms)) => execute_like(search, terms, &excluded, offset, limit),
+            None => execute_excluding(search, &excluded, offset, limit),
+        };
+        let ranked_again =
+            !boosts.is_empty() || field_weights.is_some() || query.group_by.is_some();
         let milli::SearchResult {
-            documents_ids,
+            mut documents_ids,
             matching_words,
             candidates,
             ..
-        } = search.execute()?;
+        } = if ranked_again {
+            // the hits ranked by milli are sorted afterward, so only the first hits allowed by the
+            // hard limit can be weighted, boosted or grouped.
+            search.offset(0);
+            search.limit(HARD_RESULT_LIMIT);
+            execute(&mut search, 0, HARD_RESULT_LIMIT)?
+        } else {
+            execute(&mut search, offset, limit)?
+        };
+
+        let stop_words = fst::Set::default();
+        let mut config = AnalyzerConfig::default();
+        config.stop_words(&stop_words);
+        let analyzer = Analyzer::new(config);
+
+        let mut formatter_builder = MatcherBuilder::from_matching_words(matching_words);
+
+        let mut hit_groups = None;
+        if ranked_again {
+            if let Some(ref weights) = field_weights {
+                searchable_weights::weigh_documents(
+                    self,
+                    &rtxn,
+                    &mut documents_ids,
+                    weights,
+                    &formatter_builder,
+                    &analyzer,
+                )?;
+            }
+            // the boosts come before all the ranking rules, the sort included.
+            boost_documents(&mut documents_ids, &boosts);
+            match query.group_by {
+                Some(ref group_by) => {
+                    let groups: Vec<_> = self
+                        .hit_groups(&rtxn, group_by, &candidates, &documents_ids)?
+                        .into_iter()
+                        .skip(offset)
+                        .take(limit)
+                        .collect();
+                    documents_ids = groups
+                        .iter()
+                        .flat_map(|group| group.documents.iter().map(|(_, id)| *id))
+                        .collect();
+                    hit_groups = Some(groups);
+                }
+                None => {
+                    documents_ids = documents_ids.into_iter().skip(offset).take(limit).collect()
+                }
+            }
+        }
+        // the rank of each hit among all the hits, for its ranking score.
+        let ranks: Vec<usize> = match hit_groups {
+            Some(ref groups) => groups
+                .iter()
+                .flat_map(|group| group.documents.iter().map(|(rank, _)| *rank))
+                .collect(),
+            None => (offset..offset + documents_ids.len()).collect(),
+        };
 
         let fields_ids_map = self.fields_ids_map(&rtxn).unwrap();
 
         let displayed_ids = self
             .displayed_fields_ids(&rtxn)?
             .map(|fields| fields.into_iter().collect::<BTreeSet<_>>())
-            .unwrap_or_else(|| fields_ids_map.iter().map(|(id, _)| id).collect());
+            .unwrap_or_else(|| document_fields(&fields_ids_map).into_iter().collect());
 
         let fids = |attrs: &BTreeSet<String>| {
             let mut ids = BTreeSet::new();
@@ -175,24 +515,31 @@ impl Index {
             &displayed_ids,
         );
 
-        let stop_words = fst::Set::default();
-        let mut config = AnalyzerConfig::default();
-        config.stop_words(&stop_words);
-        let analyzer = Analyzer::new(config);
-
-        let mut formatter_builder = MatcherBuilder::from_matching_words(matching_words);
         formatter_builder.crop_marker(query.crop_marker);
         formatter_builder.highlight_prefix(query.highlight_pre_tag);
         formatter_builder.highlight_suffix(query.highlight_post_tag);
 
-        let mut documents = Vec::new();
+        let estimated_total_hits = candidates.len();
+
+        // First generate the documents with all the displayed fields
+        let mut displayed_documents = Vec::with_capacity(documents_ids.len());
+        for (id, obkv) in self.documents(&rtxn, documents_ids)? {
+            displayed_documents.push((id, make_document(&displayed_ids, &fields_ids_map, obkv)?));
+        }
 
-        let documents_iter = self.documents(&rtxn, documents_ids)?;
+        let facet_distribution = match query.facets {
+            Some(ref fields) => {
+                Some(self.facet_distribution(&rtxn, &derivation, fields, &candidates)?)
+            }
+            None => None,
+        };
 
-        for (_id, obkv) in documents_iter {
-            // First generate a document with all the displayed fields
-            let displayed_document = make_document(&displayed_ids, &fields_ids_map, obkv)?;
+        // the formatting of the hits doesn't read the index anymore, the read transaction is
+        // released so that it doesn't hold a reader slot during the formatting.
+        drop(rtxn);
 
+        let mut documents = Vec::with_capacity(displayed_documents.len());
+        for (position, (id, displayed_document)) in displayed_documents.into_iter().enumerate() {
             // select the attributes to retrieve
             let attributes_to_retrieve = to_retrieve_ids
                 .iter()
@@ -218,36 +565,326 @@ impl Index {
                 document,
                 formatted,
                 matches_position,
+                ranking_score: query
+                    .show_ranking_score
+                    .then(|| ranking_score(ranks[position], estimated_total_hits)),
+                ranking_score_boost: query
+                    .show_ranking_score
+                    .then(|| boost_weight(&boosts, id))
+                    .flatten(),
             };
             documents.push(hit);
         }
 
-        let estimated_total_hits = candidates.len();
-
-        let facet_distribution = match query.facets {
-            Some(ref fields) => {
-                let mut facet_distribution = self.facets_distribution(&rtxn);
-                if fields.iter().all(|f| f != "*") {
-                    facet_distribution.facets(fields);
-                }
-                let distribution = facet_distribution.candidates(candidates).execute()?;
-
-                Some(distribution)
+        let (hits, groups) = match hit_groups {
+            Some(hit_groups) => {
+                let mut documents = documents.into_iter();
+                let groups = hit_groups
+                    .into_iter()
+                    .map(|group| SearchGroup {
+                        key: group.key,
+                        hits: documents.by_ref().take(group.documents.len()).collect(),
+                        estimated_total_hits: group.estimated_total_hits,
+                    })
+                    .collect();
+                (Vec::new(), Some(groups))
             }
-            None => None,
+            None => (documents, None),
         };
 
         let result = SearchResult {
-            hits: documents,
+            hits,
             estimated_total_hits,
-            query: query.q.clone().unwrap_or_default(),
+            query: searched_query,
             limit: query.limit,
             offset: query.offset.unwrap_or_default(),
             processing_time_ms: before_search.elapsed().as_millis(),
             facet_distribution,
+            truncated_query_expansion,
+            like_documents_warning: None,
+            pit_token: None,
+            groups,
         };
         Ok(result)
     }
+
+    /// Returns the number of documents of `candidates` having each value of the `fields`, all the
+    /// filterable attributes for `*`. The normalized attributes are counted on their derived
+    /// values, whether they are filterable or not.
+    fn facet_distribution(
+        &self,
+        rtxn: &RoTxn,
+        derivation: &Derivation,
+        fields: &[String],
+        candidates: &RoaringBitmap,
+    ) -> Result<BTreeMap<String, BTreeMap<String, u64>>> {
+        let fields: Vec<String> = match fields.iter().any(|f| f == "*") {
+            true => self
+                .filterable_fields(rtxn)?
+                .into_iter()
+                .filter(|field| !is_derived(field))
+                .collect(),
+            false => fields.to_vec(),
+        };
+        let normalizations = derivation.normalizations();
+        let mut facets = Vec::with_capacity(fields.len());
+        for field in &fields {
+            match normalizations.get(field) {
+                Some(_) => {
+                    let (values, forms) = facet_normalization::derived_fields(field);
+                    facets.push(values);
+                    facets.push(forms);
+                }
+                None => facets.push(field.clone()),
+            }
+        }
+
+        let mut facet_distribution = self.facets_distribution(rtxn);
+        facet_distribution.facets(facets);
+        let mut distribution = facet_distribution
+            .candidates(candidates.clone())
+            .execute()?;
+
+        let max_values = self.max_values_per_facet(rtxn)?;
+        let mut result = BTreeMap::new();
+        for field in fields {
+            let values = match normalizations.get(&field) {
+                Some(normalization) => {
+                    let (values, forms) = facet_normalization::derived_fields(&field);
+                    facet_normalization::distribution(
+                        normalization,
+                        distribution.remove(&values).unwrap_or_default(),
+                        &distribution.remove(&forms).unwrap_or_default(),
+                        max_values,
+                    )
+                }
+                None => match distribution.remove(&field) {
+                    Some(values) => values,
+                    None => continue,
+                },
+            };
+            result.insert(field, values);
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the weight of each boost with the documents matching its filter, by decreasing
+    /// weight. The filters are checked like the filter of the search.
+    fn boosted_documents(
+        &self,
+        rtxn: &RoTxn,
+        derivation: &Derivation,
+        boosts: &[SearchBoost],
+    ) -> Result<Vec<(f64, RoaringBitmap)>> {
+        let mut boosted = Vec::with_capacity(boosts.len());
+        for boost in boosts {
+            if !boost.weight.is_finite() || boost.weight <= 0.0 {
+                return Err(IndexError::InvalidSearchBoost(boost.weight));
+            }
+
+            let filter = derivation.rewrite_filter(&boost.filter);
+            let documents = match parse_filter(&filter)? {
+                Some(filter) => filter.evaluate(rtxn, self)?,
+                None => self.documents_ids(rtxn)?,
+            };
+            boosted.push((boost.weight, documents));
+        }
+        boosted.sort_by(|(lhs, _), (rhs, _)| rhs.partial_cmp(lhs).unwrap_or(Ordering::Equal));
+
+        Ok(boosted)
+    }
+
+    /// Returns an error if the hits of this index can't be grouped as `group_by` asks.
+    fn check_group_by(&self, rtxn: &RoTxn, group_by: &SearchGroupBy) -> Result<()> {
+        if group_by.limit == 0 {
+            return Err(IndexError::InvalidSearchGroupBy(
+                "the `limit` of the groups must be a positive integer.".to_string(),
+            ));
+        }
+        if let Some(distinct) = self.distinct_field(rtxn)? {
+            return Err(IndexError::InvalidSearchGroupBy(format!(
+                "the index has the distinct attribute `{}`, which already keeps a single hit per value.",
+                distinct
+            )));
+        }
+        // the fields nested in a filterable object are filterable too.
+        if !self
+            .filterable_fields(rtxn)?
+            .iter()
+            .any(|filterable| milli::is_faceted_by(&group_by.attribute, filterable))
+        {
+            return Err(IndexError::InvalidSearchGroupBy(format!(
+                "attribute `{}` is not filterable. Only filterable attributes can group the hits.",
+                group_by.attribute
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Groups `ranked`, the first hits of a search whose hits are `candidates`, by their value of
+    /// the attribute of `group_by`. The groups are ordered by their best hit, and keep their
+    /// `limit` best hits.
+    fn hit_groups(
+        &self,
+        rtxn: &RoTxn,
+        group_by: &SearchGroupBy,
+        candidates: &RoaringBitmap,
+        ranked: &[u32],
+    ) -> Result<Vec<HitGroup>> {
+        // an attribute that is in no document yet has no field id.
+        let values = match self.fields_ids_map(rtxn)?.id(&group_by.attribute) {
+            Some(fid) => self.facet_groups(rtxn, fid, candidates, usize::MAX)?.0,
+            None => Vec::new(),
+        };
+
+        // a document with several values is grouped under the first one.
+        let ranked_ids: RoaringBitmap = ranked.iter().copied().collect();
+        let mut value_of = HashMap::new();
+        for (position, (_, docids)) in values.iter().enumerate() {
+            for id in docids & &ranked_ids {
+                value_of.entry(id).or_insert(position);
+            }
+        }
+
+        let mut groups: Vec<HitGroup> = Vec::new();
+        let mut group_of_value = HashMap::new();
+        for (rank, &id) in ranked.iter().enumerate() {
+            let position = match value_of.get(&id) {
+                Some(&position) => position,
+                // a document missing the attribute forms its own group.
+                None => {
+                    groups.push(HitGroup {
+                        key: Value::Null,
+                        documents: vec![(rank, id)],
+                        estimated_total_hits: 1,
+                    });
+                    continue;
+                }
+            };
+            let group = *group_of_value.entry(position).or_insert_with(|| {
+                let (key, docids) = &values[position];
+                groups.push(HitGroup {
+                    key: key.clone().unwrap_or_default(),
+                    documents: Vec::new(),
+                    estimated_total_hits: docids.len(),
+                });
+                groups.len() - 1
+            });
+            if groups[group].documents.len() < group_by.limit {
+                groups[group].documents.push((rank, id));
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Returns a write transaction in which the ranking rules of the index are `rules`, checked
+    /// like the `rankingRules` setting. milli reads the ranking rules from the index, so the search
+    /// reads the index with this transaction, which is dropped without being committed. It waits
+    /// for the updates of the index being processed.
+    fn ranking_rules_txn(&self, rules: &[String]) -> Result<RwTxn> {
+        let mut wtxn = self.write_txn()?;
+        let sortable_fields = self.sortable_fields(&wtxn)?;
+        for rule in rules {
+            let criterion = Criterion::from_str(rule)
+                .map_err(|e| milli::Error::UserError(milli::UserError::CriterionError(e)))?;
+            if let Criterion::Asc(field) | Criterion::Desc(field) = criterion {
+                if !sortable_fields.contains(&field) {
+                    return Err(IndexError::UnsortableRankingRule(field));
+                }
+            }
+        }
+
+        let mut builder =
+            milli::update::Settings::new(&mut wtxn, self, self.indexer_config.as_ref());
+        builder.set_criteria(rules.to_vec());
+        builder.execute(|_| ())?;
+
+        Ok(wtxn)
+    }
+
+    /// Returns an error if `filter` can't be applied to this index, because it is malformed or
+    /// uses attributes that are not filterable.
+    pub fn check_filter(&self, filter: &Value) -> Result<()> {
+        let rtxn = self.read_txn()?;
+        let filter = Derivation::read(self, &rtxn)?.rewrite_filter(filter);
+        if let Some(filter) = parse_filter(&filter)? {
+            filter.evaluate(&rtxn, self)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the documents that only match the query by the derivations of its words left out
+    /// by the caps of the index, `None` if none is left out.
+    fn truncated_documents(&self, rtxn: &RoTxn, query: &str) -> Result<Option<RoaringBitmap>> {
+        let caps = ExpansionCaps {
+            max_word_derivations: self.max_word_derivations(rtxn)?,
+            max_prefix_expansion: self.max_prefix_expansion(rtxn)?,
+        };
+        let typo_config = TypoConfig {
+            authorize_typos: self.authorize_typos(rtxn)?,
+            min_word_len_one_typo: self.min_word_len_one_typo(rtxn)?,
+            min_word_len_two_typos: self.min_word_len_two_typos(rtxn)?,
+        };
+
+        query_expansion::truncated_documents(self, rtxn, query, caps, typo_config)
+    }
+}
+
+/// The transaction a search reads the index with.
+enum SearchTxn<'a> {
+    Read(ReadTxn<'a>),
+    /// Holds the ranking rules of the search, see `Index::ranking_rules_txn`.
+    RankingRulesOverride(RwTxn<'a, 'a>),
+    /// The snapshot of a point in time.
+    Snapshot(&'a SnapshotTxn),
+}
+
+impl<'a> Deref for SearchTxn<'a> {
+    type Target = RoTxn<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            SearchTxn::Read(txn) => txn,
+            SearchTxn::RankingRulesOverride(txn) => txn,
+            SearchTxn::Snapshot(txn) => txn,
+        }
+    }
+}
+
+/// milli only ranks the candidates of a search, so the ranking score of a hit is derived from its
+/// rank among them: the first hit has a score of 1, and the score decreases linearly down to the
+/// last candidate. The score is always between 0 and 1.
+fn ranking_score(rank: usize, candidates: u64) -> f64 {
+    if candidates == 0 {
+        return 0.0;
+    }
+    (1.0 - rank as f64 / candidates as f64).clamp(0.0, 1.0)
+}
+
+/// The weight of the heaviest boost matching `docid`, `boosts` being ordered by decreasing weight.
+fn boost_weight(boosts: &[(f64, RoaringBitmap)], docid: u32) -> Option<f64> {
+    boosts
+        .iter()
+        .find(|(_, documents)| documents.contains(docid))
+        .map(|(weight, _)| *weight)
+}
+
+/// Moves the boosted documents first, by decreasing weight. The sort is stable, so the documents
+/// with the same weight keep the order of the ranking rules.
+fn boost_documents(documents_ids: &mut [u32], boosts: &[(f64, RoaringBitmap)]) {
+    if boosts.is_empty() {
+        return;
+    }
+
+    documents_ids.sort_by(|lhs, rhs| {
+        let lhs = boost_weight(boosts, *lhs).unwrap_or(0.0);
+        let rhs = boost_weight(boosts, *rhs).unwrap_or(0.0);
+        rhs.partial_cmp(&lhs).unwrap_or(Ordering::Equal)
+    });
 }
 
 fn insert_geo_distance(sorts: &[String], document: &mut Document) {
@@ -276,37 +913,65 @@ fn compute_formatted_options(
     to_retrieve_ids: &BTreeSet<FieldId>,
     fields_ids_map: &FieldsIdsMap,
     displayed_ids: &BTreeSet<FieldId>,
-) -> BTreeMap<FieldId, FormatOptions> {
+) -> BTreeMap<String, FormatOptions> {
     let mut formatted_options = BTreeMap::new();
 
-    add_highlight_to_formatted_options(
-        &mut formatted_options,
-        attr_to_highlight,
-        fields_ids_map,
-        displayed_ids,
-    );
+    let displayed_names: Vec<_> = displayed_ids
+        .iter()
+        .map(|&fid| fields_ids_map.name(fid).expect("Missing field name"))
+        .collect();
+
+    add_highlight_to_formatted_options(&mut formatted_options, attr_to_highlight, &displayed_names);
 
     add_crop_to_formatted_options(
         &mut formatted_options,
         attr_to_crop,
         query_crop_length,
-        fields_ids_map,
-        displayed_ids,
+        &displayed_names,
     );
 
     // Should not return `_formatted` if no valid attributes to highlight/crop
     if !formatted_options.is_empty() {
-        add_non_formatted_ids_to_formatted_options(&mut formatted_options, to_retrieve_ids);
+        add_non_formatted_ids_to_formatted_options(
+            &mut formatted_options,
+            to_retrieve_ids,
+            fields_ids_map,
+        );
     }
 
     formatted_options
 }
 
+/// Returns the paths designated by an attribute of `attributesToHighlight` or `attributesToCrop`.
+///
+/// `*` designates all the displayed attributes, and the path wildcard `reviews.*` designates the
+/// same fields as `reviews`. A nested path like `reviews.text` is formatted when it is displayed,
+/// nested in a displayed attribute, or contains displayed attributes, the formatting being
+/// applied to each string it leads to, through the objects and arrays of the document.
+fn formatted_paths(attr: &str, displayed_names: &[&str]) -> Vec<String> {
+    if attr == "*" {
+        return displayed_names
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+    }
+
+    let path = strip_path_wildcard(attr.to_string());
+    let is_displayed = displayed_names.iter().any(|displayed| {
+        milli::is_faceted_by(&path, displayed) || milli::is_faceted_by(displayed, &path)
+    });
+
+    if is_displayed {
+        vec![path]
+    } else {
+        Vec::new()
+    }
+}
+
 fn add_highlight_to_formatted_options(
-    formatted_options: &mut BTreeMap<FieldId, FormatOptions>,
+    formatted_options: &mut BTreeMap<String, FormatOptions>,
     attr_to_highlight: &HashSet<String>,
-    fields_ids_map: &FieldsIdsMap,
-    displayed_ids: &BTreeSet<FieldId>,
+    displayed_names: &[&str],
 ) {
     for attr in attr_to_highlight {
         let new_format = FormatOptions {
@@ -314,27 +979,17 @@ fn add_highlight_to_formatted_options(
             crop: None,
         };
 
-        if attr == "*" {
-            for id in displayed_ids {
-                formatted_options.insert(*id, new_format);
-            }
-            break;
-        }
-
-        if let Some(id) = fields_ids_map.id(attr) {
-            if displayed_ids.contains(&id) {
-                formatted_options.insert(id, new_format);
-            }
+        for path in formatted_paths(attr, displayed_names) {
+            formatted_options.insert(path, new_format);
         }
     }
 }
 
 fn add_crop_to_formatted_options(
-    formatted_options: &mut BTreeMap<FieldId, FormatOptions>,
+    formatted_options: &mut BTreeMap<String, FormatOptions>,
     attr_to_crop: &[String],
     crop_length: usize,
-    fields_ids_map: &FieldsIdsMap,
-    displayed_ids: &BTreeSet<FieldId>,
+    displayed_names: &[&str],
 ) {
     for attr in attr_to_crop {
         let mut split = attr.rsplitn(2, ':');
@@ -346,41 +1001,31 @@ fn add_crop_to_formatted_options(
             None => (attr.as_str(), crop_length),
         };
 
-        if attr_name == "*" {
-            for id in displayed_ids {
-                formatted_options
-                    .entry(*id)
-                    .and_modify(|f| f.crop = Some(attr_len))
-                    .or_insert(FormatOptions {
-                        highlight: false,
-                        crop: Some(attr_len),
-                    });
-            }
-        }
-
-        if let Some(id) = fields_ids_map.id(attr_name) {
-            if displayed_ids.contains(&id) {
-                formatted_options
-                    .entry(id)
-                    .and_modify(|f| f.crop = Some(attr_len))
-                    .or_insert(FormatOptions {
-                        highlight: false,
-                        crop: Some(attr_len),
-                    });
-            }
+        for path in formatted_paths(attr_name, displayed_names) {
+            formatted_options
+                .entry(path)
+                .and_modify(|f| f.crop = Some(attr_len))
+                .or_insert(FormatOptions {
+                    highlight: false,
+                    crop: Some(attr_len),
+                });
         }
     }
 }
 
 fn add_non_formatted_ids_to_formatted_options(
-    formatted_options: &mut BTreeMap<FieldId, FormatOptions>,
+    formatted_options: &mut BTreeMap<String, FormatOptions>,
     to_retrieve_ids: &BTreeSet<FieldId>,
+    fields_ids_map: &FieldsIdsMap,
 ) {
     for id in to_retrieve_ids {
-        formatted_options.entry(*id).or_insert(FormatOptions {
-            highlight: false,
-            crop: None,
-        });
+        let name = fields_ids_map.name(*id).expect("Missing field name");
+        formatted_options
+            .entry(name.to_string())
+            .or_insert(FormatOptions {
+                highlight: false,
+                crop: None,
+            });
     }
 }
 
@@ -416,7 +1061,7 @@ fn format_fields<'a, A: AsRef<[u8]>>(
     field_ids_map: &FieldsIdsMap,
     builder: &MatcherBuilder,
     analyzer: &'a Analyzer<'a, A>,
-    formatted_options: &BTreeMap<FieldId, FormatOptions>,
+    formatted_options: &BTreeMap<String, FormatOptions>,
     compute_matches: bool,
     displayable_ids: &BTreeSet<FieldId>,
 ) -> Result<(Option<MatchesPosition>, Document)> {
@@ -434,8 +1079,7 @@ fn format_fields<'a, A: AsRef<[u8]>>(
         // highlighted.
         let format = formatted_options
             .iter()
-            .filter(|(field, _option)| {
-                let name = field_ids_map.name(**field).unwrap();
+            .filter(|(name, _option)| {
                 milli::is_faceted_by(name, key) || milli::is_faceted_by(key, name)
             })
             .map(|(_, option)| *option)
@@ -458,11 +1102,7 @@ fn format_fields<'a, A: AsRef<[u8]>>(
         }
     });
 
-    let selectors = formatted_options
-        .keys()
-        // This unwrap must be safe since we got the ids from the fields_ids_map just
-        // before.
-        .map(|&fid| field_ids_map.name(fid).unwrap());
+    let selectors = formatted_options.keys().map(String::as_str);
     let document = permissive_json_pointer::select_values(&document, selectors);
 
     Ok((matches_position, document))
@@ -559,7 +1199,90 @@ fn format_value<'a, A: AsRef<[u8]>>(
     }
 }
 
-fn parse_filter(facets: &Value) -> Result<Option<Filter>> {
+/// Combines `filter` with `other` with `AND`. A filter that is neither a string nor an array is
+/// returned as it is, for the error to be reported.
+fn and_filter(filter: Option<Value>, other: String) -> Value {
+    match filter {
+        None => Value::String(other),
+        Some(Value::Array(mut filters)) => {
+            filters.push(Value::String(other));
+            Value::Array(filters)
+        }
+        Some(filter @ Value::String(_)) => Value::Array(vec![filter, Value::String(other)]),
+        Some(filter) => filter,
+    }
+}
+
+/// Executes the search, excluding the `excluded` documents from the results. milli knows nothing
+/// about them, so when some of them are candidates, the search is executed again with the window
+/// of hits widened by the number of excluded candidates, and they are removed afterward.
+pub(super) fn execute_excluding(
+    search: &mut milli::Search,
+    excluded: &RoaringBitmap,
+    offset: usize,
+    limit: usize,
+) -> Result<milli::SearchResult> {
+    let mut result = search.execute()?;
+
+    let excluded_candidates = result.candidates.intersection_len(excluded) as usize;
+    if excluded_candidates > 0 {
+        search.offset(0);
+        search.limit(offset + limit + excluded_candidates);
+        result = search.execute()?;
+
+        result.documents_ids = result
+            .documents_ids
+            .into_iter()
+            .filter(|id| !excluded.contains(*id))
+            .skip(offset)
+            .take(limit)
+            .collect();
+        result.candidates -= excluded;
+    }
+
+    Ok(result)
+}
+
+/// Executes the search of the documents like other documents, whose most significant words are
+/// `terms`. milli only returns the documents containing the first word of a query, so the
+/// documents missing the first terms are searched afterward with the following terms, and ranked
+/// after the documents found before them.
+fn execute_like(
+    search: &mut milli::Search,
+    terms: &[String],
+    excluded: &RoaringBitmap,
+    offset: usize,
+    limit: usize,
+) -> Result<milli::SearchResult> {
+    let mut excluded = excluded.clone();
+    let mut result: Option<milli::SearchResult> = None;
+    let mut documents_ids = Vec::new();
+    let mut candidates = RoaringBitmap::new();
+
+    for first in 0..terms.len() {
+        let remaining = (offset + limit).saturating_sub(documents_ids.len());
+        // the terms are whole words, the trailing space prevents milli from considering the last
+        // one as a prefix.
+        search.query(format!("{} ", terms[first..].join(" ")));
+        search.offset(0);
+        search.limit(remaining);
+        let found = execute_excluding(search, &excluded, 0, remaining)?;
+
+        documents_ids.extend(found.documents_ids.iter().take(remaining));
+        excluded |= &found.candidates;
+        candidates |= &found.candidates;
+        // the words of the first search are all the terms.
+        result.get_or_insert(found);
+    }
+
+    let mut result = result.expect("a query built from documents has terms");
+    result.documents_ids = documents_ids.into_iter().skip(offset).take(limit).collect();
+    result.candidates = candidates;
+
+    Ok(result)
+}
+
+pub(super) fn parse_filter(facets: &Value) -> Result<Option<Filter>> {
     match facets {
         Value::String(expr) => {
             let condition = Filter::from_str(expr)?;
@@ -662,4 +1385,49 @@ mod test {
         insert_geo_distance(sorters, &mut document);
         assert_eq!(document.get("_geoDistance"), None);
     }
+
+    #[test]
+    fn test_boost_documents() {
+        let sponsored: RoaringBitmap = [2, 3].into_iter().collect();
+        let featured: RoaringBitmap = [3, 4].into_iter().collect();
+        let boosts = vec![(2.0, featured), (1.0, sponsored)];
+
+        let mut documents_ids = vec![0, 1, 2, 3, 4];
+        boost_documents(&mut documents_ids, &boosts);
+        // the heaviest boost of a document applies, the ranking breaks the ties.
+        assert_eq!(documents_ids, vec![3, 4, 2, 0, 1]);
+
+        assert_eq!(boost_weight(&boosts, 3), Some(2.0));
+        assert_eq!(boost_weight(&boosts, 2), Some(1.0));
+        assert_eq!(boost_weight(&boosts, 0), None);
+    }
+
+    #[test]
+    fn test_and_filter() {
+        let live = "_meilisearch.sequence >= 4".to_string();
+        assert_eq!(and_filter(None, live.clone()), json!(live));
+        assert_eq!(
+            and_filter(Some(json!("genre = drama")), live.clone()),
+            json!(["genre = drama", live])
+        );
+        assert_eq!(
+            and_filter(
+                Some(json!([["genre = drama", "genre = comedy"]])),
+                live.clone()
+            ),
+            json!([["genre = drama", "genre = comedy"], live])
+        );
+        // the invalid filters are left for milli to report.
+        assert_eq!(and_filter(Some(json!(42)), live), json!(42));
+    }
+
+    #[test]
+    fn test_ranking_score() {
+        assert_eq!(ranking_score(0, 4), 1.0);
+        assert_eq!(ranking_score(1, 4), 0.75);
+        assert_eq!(ranking_score(3, 4), 0.25);
+        // the candidates are estimated, a hit can be ranked past them.
+        assert_eq!(ranking_score(5, 4), 0.0);
+        assert_eq!(ranking_score(0, 0), 0.0);
+    }
 }
@@ -0,0 +1,174 @@
+//! Default search parameters of an index.
+//!
+//! The search defaults are any subset of the parameters of a search request, stored as an index
+//! setting so that every client gets the same results without having to send the same parameters.
+//! At query time, the parameters of the request are merged over the defaults: a parameter sent
+//! with the request always wins, and an explicit `null` resets the parameter to the default of the
+//! engine, whatever the default of the index.
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+use super::search::SearchQuery;
+
+/// The search defaults of an index, kept as they were sent so that they are returned verbatim.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(transparent)]
+pub struct SearchDefaults(Map<String, Value>);
+
+impl SearchDefaults {
+    /// Builds the query of a search request whose parameters are `request`.
+    pub fn query(&self, request: Map<String, Value>) -> serde_json::Result<SearchQuery> {
+        serde_json::from_value(Value::Object(merge_search_defaults(&self.0, request)))
+    }
+
+    pub fn into_inner(self) -> Map<String, Value> {
+        self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for SearchDefaults {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let defaults = Map::deserialize(deserializer)?;
+        // the ranking rules override of a search is never persisted.
+        if defaults.contains_key("rankingRulesOverride") {
+            return Err(de::Error::custom(
+                "`rankingRulesOverride` can't be a search default: the ranking rules of an index are set with the `rankingRules` setting.",
+            ));
+        }
+        // a point in time belongs to the pagination of a single client.
+        if let Some(param) = ["pit", "pitToken"]
+            .into_iter()
+            .find(|param| defaults.contains_key(*param))
+        {
+            return Err(de::Error::custom(format!(
+                "`{}` can't be a search default: a point in time is opened by a search request.",
+                param
+            )));
+        }
+        // the defaults are checked with the same rules as the parameters of a search request.
+        Self::default()
+            .query(defaults.clone())
+            .map_err(de::Error::custom)?;
+
+        Ok(Self(defaults))
+    }
+}
+
+/// Merges the parameters of a search request over the search defaults of an index. A parameter
+/// that is `null` in the request is removed, so that the engine default applies.
+pub fn merge_search_defaults(
+    defaults: &Map<String, Value>,
+    request: Map<String, Value>,
+) -> Map<String, Value> {
+    let mut params = defaults.clone();
+    for (name, value) in request {
+        if value.is_null() {
+            params.remove(&name);
+        } else {
+            params.insert(name, value);
+        }
+    }
+
+    params
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::index::DEFAULT_CROP_LENGTH;
+
+    fn map(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn test_merge_search_defaults() {
+        let defaults = json!({
+            "attributesToRetrieve": ["title"],
+            "cropLength": 5,
+        });
+
+        // (request, expected parameters)
+        let cases = [
+            // no parameter: the defaults apply.
+            (
+                json!({}),
+                json!({ "attributesToRetrieve": ["title"], "cropLength": 5 }),
+            ),
+            // the request wins over the defaults.
+            (
+                json!({ "cropLength": 20 }),
+                json!({ "attributesToRetrieve": ["title"], "cropLength": 20 }),
+            ),
+            // a parameter without default is kept.
+            (
+                json!({ "q": "hello" }),
+                json!({ "q": "hello", "attributesToRetrieve": ["title"], "cropLength": 5 }),
+            ),
+            // an explicit null resets the parameter to the engine default.
+            (
+                json!({ "attributesToRetrieve": null }),
+                json!({ "cropLength": 5 }),
+            ),
+            // a null for a parameter without default is dropped.
+            (
+                json!({ "filter": null }),
+                json!({ "attributesToRetrieve": ["title"], "cropLength": 5 }),
+            ),
+        ];
+
+        for (request, expected) in cases {
+            assert_eq!(
+                merge_search_defaults(&map(defaults.clone()), map(request.clone())),
+                map(expected),
+                "{}",
+                request
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_defaults_query() {
+        let defaults: SearchDefaults =
+            serde_json::from_value(json!({ "cropLength": 5, "limit": 3 })).unwrap();
+
+        let query = defaults.query(Map::new()).unwrap();
+        assert_eq!(query.crop_length, 5);
+        assert_eq!(query.limit, 3);
+
+        let query = defaults
+            .query(map(json!({ "cropLength": null, "limit": 10 })))
+            .unwrap();
+        assert_eq!(query.crop_length, DEFAULT_CROP_LENGTH());
+        assert_eq!(query.limit, 10);
+
+        // null is also accepted for the parameters that can't be null in a search request.
+        assert!(SearchDefaults::default()
+            .query(map(json!({ "cropLength": null })))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_search_defaults() {
+        for invalid in [
+            json!({ "unknown": true }),
+            json!({ "cropLength": "long" }),
+            json!({ "attributesToRetrieve": "title" }),
+            json!({ "rankingRulesOverride": ["words"] }),
+            json!({ "pit": true }),
+            json!(["cropLength"]),
+        ] {
+            assert!(
+                serde_json::from_value::<SearchDefaults>(invalid.clone()).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
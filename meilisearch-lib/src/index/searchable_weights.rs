@@ -0,0 +1,156 @@
+//! Weights of the searchable attributes.
+//!
+//! milli ranks the attributes by their position in the searchable attributes, with fixed steps
+//! between the positions. When the searchable attributes are set with their weights, milli is
+//! given them by decreasing weight, and the first hits ranked by milli are ranked again by the
+//! weight of their heaviest field matching the query, the ranking of milli breaking the ties. Like
+//! the sorts on the metadata, the weights are applied around the milli search, so only to the
+//! hits within the hard limit, and only when the `attribute` ranking rule is used.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+use milli::heed::RoTxn;
+use milli::tokenizer::Analyzer;
+use milli::{Criterion, FieldId, MatcherBuilder};
+use serde_json::Value;
+
+use super::error::Result;
+use super::index::Index;
+use super::updates::check_searchable_weights;
+
+/// The weight of the fields of the attributes that were given no weight.
+const DEFAULT_WEIGHT: u32 = 1;
+
+/// Returns the weight of each searchable field, the weights of the search taking precedence over
+/// the ones of the index, or `None` if the hits are not weighted.
+pub fn field_weights(
+    index: &Index,
+    rtxn: &RoTxn,
+    search_weights: Option<&BTreeMap<String, u32>>,
+) -> Result<Option<HashMap<FieldId, u32>>> {
+    if let Some(weights) = search_weights {
+        check_searchable_weights(weights)?;
+    }
+    let index_weights = index.searchable_weights(rtxn)?;
+    if search_weights.is_none() && index_weights.is_none() {
+        return Ok(None);
+    }
+    if !index.criteria(rtxn)?.contains(&Criterion::Attribute) {
+        return Ok(None);
+    }
+
+    let fields_ids_map = index.fields_ids_map(rtxn)?;
+    let searchable = match index.searchable_fields_ids(rtxn)? {
+        Some(fields) => fields,
+        None => fields_ids_map.iter().map(|(id, _)| id).collect(),
+    };
+
+    Ok(Some(
+        searchable
+            .into_iter()
+            .filter_map(|id| {
+                let name = fields_ids_map.name(id)?;
+                let weight = attribute_weight(name, search_weights)
+                    .or_else(|| attribute_weight(name, index_weights.as_ref()))
+                    .unwrap_or(DEFAULT_WEIGHT);
+                Some((id, weight))
+            })
+            .collect(),
+    ))
+}
+
+/// The weight of the attribute a field is, or is nested in. The most specific attribute wins.
+fn attribute_weight(field: &str, weights: Option<&BTreeMap<String, u32>>) -> Option<u32> {
+    weights?
+        .iter()
+        .filter(|(attribute, _)| {
+            field
+                .strip_prefix(attribute.as_str())
+                .map_or(false, |rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .max_by_key(|(attribute, _)| attribute.len())
+        .map(|(_, weight)| *weight)
+}
+
+/// Ranks the documents by the weight of their heaviest field matching the query. The sort is
+/// stable, so the ranking of milli breaks the ties.
+pub fn weigh_documents<'a, A: AsRef<[u8]>>(
+    index: &Index,
+    rtxn: &RoTxn,
+    documents_ids: &mut [u32],
+    weights: &HashMap<FieldId, u32>,
+    builder: &MatcherBuilder,
+    analyzer: &'a Analyzer<'a, A>,
+) -> Result<()> {
+    let mut document_weights = HashMap::with_capacity(documents_ids.len());
+    for (id, obkv) in index.documents(rtxn, documents_ids.iter().copied())? {
+        let mut heaviest = 0;
+        for (field, value) in obkv.iter() {
+            match weights.get(&field) {
+                Some(&weight) if weight > heaviest => {
+                    let value: Value = serde_json::from_slice(value)?;
+                    if matches_query(&value, builder, analyzer) {
+                        heaviest = weight;
+                    }
+                }
+                _ => (),
+            }
+        }
+        document_weights.insert(id, heaviest);
+    }
+
+    documents_ids.sort_by_key(|id| Reverse(document_weights.get(id).copied().unwrap_or(0)));
+    Ok(())
+}
+
+fn matches_query<'a, A: AsRef<[u8]>>(
+    value: &Value,
+    builder: &MatcherBuilder,
+    analyzer: &'a Analyzer<'a, A>,
+) -> bool {
+    let text_matches = |text: &str| {
+        let analyzed = analyzer.analyze(text);
+        let tokens: Vec<_> = analyzed.tokens().collect();
+        let mut matcher = builder.build(&tokens[..], text);
+        !matcher.matches().is_empty()
+    };
+
+    match value {
+        Value::String(text) => text_matches(text),
+        Value::Number(number) => text_matches(&number.to_string()),
+        Value::Array(values) => values
+            .iter()
+            .any(|value| matches_query(value, builder, analyzer)),
+        Value::Object(object) => object
+            .values()
+            .any(|value| matches_query(value, builder, analyzer)),
+        Value::Null | Value::Bool(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_attribute_weight() {
+        let weights: BTreeMap<_, _> = [
+            ("title".to_string(), 3),
+            ("author".to_string(), 2),
+            ("author.bio".to_string(), 1),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(attribute_weight("title", Some(&weights)), Some(3));
+        assert_eq!(attribute_weight("author.name", Some(&weights)), Some(2));
+        assert_eq!(attribute_weight("author.bio", Some(&weights)), Some(1));
+        assert_eq!(
+            attribute_weight("author.bio.short", Some(&weights)),
+            Some(1)
+        );
+        assert_eq!(attribute_weight("titles", Some(&weights)), None);
+        assert_eq!(attribute_weight("title", None), None);
+    }
+}
@@ -0,0 +1,166 @@
+//! The words of the documents in the languages of the custom segmenters.
+//!
+//! milli splits the text of the languages written without spaces into characters, so the words
+//! of the searchable fields in the languages of the `customSegmenters` setting are segmented by
+//! their segmenter when a document is indexed, encoded into words milli keeps whole, and derived
+//! into the field `_meilisearch.segments`. The field is searchable after the attributes of the
+//! documents, and typos are never allowed on its words, as an encoded word with a typo is another
+//! word. The words of a query in these languages are encoded the same way in the query given to
+//! milli, which matches them like the other words, the last one as a prefix, and ranks the
+//! documents by them with all the ranking rules. They are not highlighted, since they are matched
+//! in the derived field.
+
+use milli::heed::RoTxn;
+use serde_json::{Map, Value};
+
+use super::derived::is_derived;
+use super::error::Result;
+use crate::segmenters::{encode, CustomSegmenters};
+
+/// The derived field holding the encoded words of a document.
+pub const SEGMENTS_FIELD: &str = "_meilisearch.segments";
+
+/// Whether the field at `path` is selected by the `searchable` attributes, or is an object
+/// containing selected fields.
+fn selection(path: &str, searchable: Option<&[&str]>) -> (bool, bool) {
+    match searchable {
+        None => (true, false),
+        Some(attributes) => {
+            let selected = attributes.iter().any(|attribute| {
+                path == *attribute
+                    || path
+                        .strip_prefix(attribute)
+                        .map_or(false, |rest| rest.starts_with('.'))
+            });
+            let contains_selected = attributes.iter().any(|attribute| {
+                attribute
+                    .strip_prefix(path)
+                    .map_or(false, |rest| rest.starts_with('.'))
+            });
+            (selected, contains_selected)
+        }
+    }
+}
+
+/// Returns the strings of the `searchable` fields of `document`, all of them if `None`.
+pub(super) fn searchable_texts<'a>(
+    document: &'a serde_json::Map<String, Value>,
+    searchable: Option<&[&str]>,
+) -> Vec<&'a str> {
+    fn collect<'a>(
+        path: &str,
+        value: &'a Value,
+        searchable: Option<&[&str]>,
+        texts: &mut Vec<&'a str>,
+    ) {
+        let (selected, contains_selected) = selection(path, searchable);
+        if !selected && !contains_selected {
+            return;
+        }
+        match value {
+            Value::String(text) if selected => texts.push(text),
+            Value::Array(values) => values
+                .iter()
+                .for_each(|value| collect(path, value, searchable, texts)),
+            // the fields of a selected object are all selected.
+            Value::Object(object) => {
+                let searchable = if selected { None } else { searchable };
+                for (key, value) in object {
+                    let path = format!("{}.{}", path, key);
+                    collect(&path, value, searchable, texts);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut texts = Vec::new();
+    for (field, value) in document {
+        collect(field, value, searchable, &mut texts);
+    }
+    texts
+}
+
+/// Derives the encoded words of the `searchable` fields of `document`, all of them if `None`, into
+/// the `derived` values. The words of each string are kept apart, as milli keeps apart the strings
+/// of an array.
+pub fn derive(
+    document: &Map<String, Value>,
+    searchable: Option<&[&str]>,
+    segmenters: &CustomSegmenters,
+    derived: &mut Map<String, Value>,
+) {
+    let texts: Vec<_> = searchable_texts(document, searchable)
+        .into_iter()
+        .filter_map(|text| {
+            let words: Vec<_> = segmenters.words(text).iter().map(|w| encode(w)).collect();
+            (!words.is_empty()).then(|| Value::String(words.join(" ")))
+        })
+        .collect();
+    if !texts.is_empty() {
+        derived.insert("segments".to_string(), Value::Array(texts));
+    }
+}
+
+/// Returns the searchable fields of the index, all of them if `None`. The derived fields are left
+/// out.
+pub(super) fn searchable_fields(index: &milli::Index, txn: &RoTxn) -> Result<Option<Vec<String>>> {
+    Ok(index.searchable_fields(txn)?.map(|fields| {
+        fields
+            .into_iter()
+            .filter(|field| !is_derived(field))
+            .map(String::from)
+            .collect()
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn thai() -> CustomSegmenters {
+        let selection = BTreeMap::from([("tha".to_string(), "thai-dictionary".to_string())]);
+        CustomSegmenters::resolve(&selection).unwrap()
+    }
+
+    #[test]
+    fn test_derive() {
+        let document = json!({
+            "name": "ร้านอาหารไทย Siam",
+            "address": { "city": "กรุงเทพ", "street": "ตลาด" },
+            "tags": ["กาแฟ", 2],
+        });
+        let document = document.as_object().unwrap();
+        let derived = |searchable: Option<&[&str]>| {
+            let mut derived = Map::new();
+            derive(document, searchable, &thai(), &mut derived);
+            Value::Object(derived)
+        };
+        let encoded = |words: &[&str]| {
+            let words: Vec<_> = words.iter().map(|w| encode(w)).collect();
+            Value::String(words.join(" "))
+        };
+
+        let expected = json!({
+            "segments": [
+                encoded(&["ร้านอาหาร", "ไทย"]),
+                encoded(&["กรุงเทพ"]),
+                encoded(&["ตลาด"]),
+                encoded(&["กาแฟ"]),
+            ]
+        });
+        assert_eq!(derived(None), expected);
+
+        // only the searchable fields are segmented.
+        let expected = json!({
+            "segments": [encoded(&["ร้านอาหาร", "ไทย"]), encoded(&["กรุงเทพ"])]
+        });
+        assert_eq!(derived(Some(&["name", "address.city"][..])), expected);
+
+        assert_eq!(derived(Some(&["tags.label"][..])), json!({}));
+    }
+}
@@ -0,0 +1,100 @@
+//! The sequence numbers of the documents, by which milli leaves the soft deleted documents out of
+//! the searches.
+//!
+//! milli knows nothing about the soft deleted documents, and would rank them like the others. So
+//! each document is given a sequence number of its own when it is derived, in the field
+//! `_meilisearch.sequence`, and the sequence numbers of the documents are recorded when they are
+//! soft deleted. A search gives milli a filter on the ranges of sequence numbers between the ones
+//! of the soft deleted documents, so that milli takes them out of its candidates before it ranks
+//! them. The filter has a range per run of consecutive soft deleted documents, until the
+//! compaction purges them.
+
+use milli::heed::RoTxn;
+use roaring::RoaringBitmap;
+use serde_json::{Map, Value};
+
+use super::derived::DERIVED_FIELD;
+use super::error::Result;
+
+/// The name of the sequence number of a document, in its derived field.
+const SEQUENCE_KEY: &str = "sequence";
+
+/// The derived field holding the sequence number of a document.
+const SEQUENCE_FIELD: &str = "_meilisearch.sequence";
+
+/// Adds the sequence number of a document to its `derived` values.
+pub fn derive(sequence: u32, derived: &mut Map<String, Value>) {
+    derived.insert(SEQUENCE_KEY.to_string(), sequence.into());
+}
+
+/// Returns the sequence numbers of the documents `docids`.
+pub fn sequences(
+    index: &milli::Index,
+    txn: &RoTxn,
+    docids: &RoaringBitmap,
+) -> Result<RoaringBitmap> {
+    let mut sequences = RoaringBitmap::new();
+    let derived_field = match index.fields_ids_map(txn)?.id(DERIVED_FIELD) {
+        Some(fid) => fid,
+        None => return Ok(sequences),
+    };
+    for (_, reader) in index.documents(txn, docids.iter())? {
+        if let Some(bytes) = reader.get(derived_field) {
+            let derived: Value = serde_json::from_slice(bytes)?;
+            if let Some(sequence) = derived.get(SEQUENCE_KEY).and_then(Value::as_u64) {
+                sequences.insert(sequence as u32);
+            }
+        }
+    }
+    Ok(sequences)
+}
+
+/// Returns the filter keeping the documents whose sequence number is not one of `deleted`, `None`
+/// if there are none.
+pub fn live_filter(deleted: &RoaringBitmap) -> Option<String> {
+    if deleted.is_empty() {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for sequence in deleted.iter().map(u64::from) {
+        if sequence > start {
+            ranges.push(format!("{} {} TO {}", SEQUENCE_FIELD, start, sequence - 1));
+        }
+        start = sequence + 1;
+    }
+    ranges.push(format!("{} >= {}", SEQUENCE_FIELD, start));
+    Some(any_of(&ranges))
+}
+
+/// Combines `filters` with `OR`, nested by halves, so that milli evaluates a shallow tree whatever
+/// their number.
+fn any_of(filters: &[String]) -> String {
+    match filters {
+        [filter] => filter.clone(),
+        _ => {
+            let (left, right) = filters.split_at(filters.len() / 2);
+            format!("({}) OR ({})", any_of(left), any_of(right))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_live_filter() {
+        assert_eq!(live_filter(&RoaringBitmap::new()), None);
+        assert_eq!(
+            live_filter(&RoaringBitmap::from_iter([3])).unwrap(),
+            "(_meilisearch.sequence 0 TO 2) OR (_meilisearch.sequence >= 4)"
+        );
+        // a run of consecutive soft deleted documents is left out by a single range.
+        assert_eq!(
+            live_filter(&RoaringBitmap::from_iter([0, 1, 5, 6, 9])).unwrap(),
+            "(_meilisearch.sequence 2 TO 4) OR ((_meilisearch.sequence 7 TO 8) OR (_meilisearch.sequence >= 10))"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! A standalone, versioned file holding all the settings of an index.
+//!
+//! An export is meant to be kept under version control and applied to an index again, so it is
+//! canonical: two indexes with the same settings always have byte-identical exports. The keys of
+//! every object are sorted, and so are the arrays whose order is meaningless (the sets of
+//! attributes and words, and the synonyms of a word). The arrays whose order matters, like the
+//! ranking rules or the searchable attributes, are kept as they are.
+
+use std::marker::PhantomData;
+
+use milli::update::Setting;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::error::{IndexError, Result};
+use super::updates::{Checked, Settings, Unchecked};
+
+/// The version of the format of the exports produced by this version of Meilisearch.
+pub const SETTINGS_EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'static>"))]
+pub struct SettingsExport<T> {
+    pub version: u32,
+    pub settings: Settings<T>,
+}
+
+impl SettingsExport<Checked> {
+    pub fn new(mut settings: Settings<Checked>) -> Self {
+        if let Setting::Set(ref mut synonyms) = settings.synonyms {
+            synonyms.values_mut().for_each(|synonyms| synonyms.sort());
+        }
+
+        Self {
+            version: SETTINGS_EXPORT_VERSION,
+            settings,
+        }
+    }
+
+    /// The canonical JSON document of the export.
+    pub fn to_json(&self) -> Result<Value> {
+        let mut value = serde_json::to_value(self)?;
+        sort_keys(&mut value);
+        Ok(value)
+    }
+}
+
+impl SettingsExport<Unchecked> {
+    /// Returns the settings of the export, where a missing setting is reset, so that applying them
+    /// replaces all the settings of the index.
+    pub fn into_settings(self) -> Result<Settings<Unchecked>> {
+        if self.version != SETTINGS_EXPORT_VERSION {
+            return Err(IndexError::UnsupportedSettingsExportVersion(self.version));
+        }
+
+        fn or_reset<T>(setting: Setting<T>) -> Setting<T> {
+            match setting {
+                Setting::NotSet => Setting::Reset,
+                otherwise => otherwise,
+            }
+        }
+
+        let Settings {
+            displayed_attributes,
+            searchable_attributes,
+            filterable_attributes,
+            sortable_attributes,
+            ranking_rules,
+            stop_words,
+            synonyms,
+            distinct_attribute,
+            typo_tolerance,
+            warmup_queries,
+            max_word_derivations,
+            max_prefix_expansion,
+            max_values_per_facet,
+            search_defaults,
+            document_metadata,
+            nested_object_behavior,
+            document_transforms,
+            custom_segmenters,
+            prefix_search,
+            disable_prefix_on_attributes,
+            facet_normalization,
+            ..
+        } = self.settings;
+
+        Ok(Settings {
+            displayed_attributes: or_reset(displayed_attributes),
+            searchable_attributes: or_reset(searchable_attributes),
+            filterable_attributes: or_reset(filterable_attributes),
+            sortable_attributes: or_reset(sortable_attributes),
+            ranking_rules: or_reset(ranking_rules),
+            stop_words: or_reset(stop_words),
+            synonyms: or_reset(synonyms),
+            distinct_attribute: or_reset(distinct_attribute),
+            typo_tolerance: or_reset(typo_tolerance),
+            warmup_queries: or_reset(warmup_queries),
+            max_word_derivations: or_reset(max_word_derivations),
+            max_prefix_expansion: or_reset(max_prefix_expansion),
+            max_values_per_facet: or_reset(max_values_per_facet),
+            search_defaults: or_reset(search_defaults),
+            document_metadata: or_reset(document_metadata),
+            nested_object_behavior: or_reset(nested_object_behavior),
+            document_transforms: or_reset(document_transforms),
+            custom_segmenters: or_reset(custom_segmenters),
+            prefix_search: or_reset(prefix_search),
+            disable_prefix_on_attributes: or_reset(disable_prefix_on_attributes),
+            facet_normalization: or_reset(facet_normalization),
+            _kind: PhantomData,
+        })
+    }
+}
+
+/// Sorts the keys of all the objects of `value`, since the JSON objects keep their insertion
+/// order.
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = std::mem::take(object).into_iter().collect();
+            entries.sort_unstable_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            *object = entries
+                .into_iter()
+                .map(|(key, mut value)| {
+                    sort_keys(&mut value);
+                    (key, value)
+                })
+                .collect::<Map<_, _>>();
+        }
+        Value::Array(values) => values.iter_mut().for_each(sort_keys),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_export_is_canonical() {
+        let settings = Settings {
+            ranking_rules: Setting::Set(vec!["typo".to_string(), "words".to_string()]),
+            synonyms: Setting::Set(BTreeMap::from([(
+                "car".to_string(),
+                vec!["vehicle".to_string(), "automobile".to_string()],
+            )])),
+            distinct_attribute: Setting::Reset,
+            ..Default::default()
+        };
+
+        let export = SettingsExport::new(settings).to_json().unwrap();
+        assert_eq!(
+            export.to_string(),
+            json!({
+                "settings": {
+                    "distinctAttribute": null,
+                    "rankingRules": ["typo", "words"],
+                    "synonyms": { "car": ["automobile", "vehicle"] },
+                },
+                "version": 1,
+            })
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_import_resets_missing_settings() {
+        let export: SettingsExport<Unchecked> =
+            serde_json::from_value(json!({ "version": 1, "settings": { "stopWords": ["the"] } }))
+                .unwrap();
+        let settings = export.into_settings().unwrap();
+        assert_eq!(
+            settings.stop_words,
+            Setting::Set(["the".to_string()].into())
+        );
+        assert_eq!(settings.ranking_rules, Setting::Reset);
+        assert_eq!(settings.document_metadata, Setting::Reset);
+
+        let export: SettingsExport<Unchecked> =
+            serde_json::from_value(json!({ "version": 2, "settings": {} })).unwrap();
+        assert!(matches!(
+            export.into_settings(),
+            Err(IndexError::UnsupportedSettingsExportVersion(2))
+        ));
+    }
+}
@@ -0,0 +1,334 @@
+//! Repair of the stats of an index.
+//!
+//! milli keeps the ids of the documents, the field distribution, and the documents that have a
+//! value for each faceted field in its main database, next to the databases they are computed
+//! from. An unclean shutdown can leave them out of step with the documents store, and the stats of
+//! the index wrong. The repair counts them again from the documents store and the facet databases,
+//! within the write transaction that rewrites them, so that they are all replaced at once. In
+//! report-only mode, which the integrity check uses, nothing is written.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use milli::heed::types::{ByteSlice, SerdeJson, Str};
+use milli::heed::{RoTxn, RwTxn};
+use milli::{FieldDistribution, FieldId, RoaringBitmapCodec};
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+
+use super::error::Result;
+use super::index::Index;
+
+/// The discrepancy beyond which the stats of an index found wrong are logged as a warning.
+pub const DISCREPANCY_WARNING_THRESHOLD: u64 = 10;
+
+/// The keys milli stores the stats under in its main database.
+mod milli_key {
+    pub const DOCUMENTS_IDS: &str = "documents-ids";
+    pub const FIELD_DISTRIBUTION: &str = "fields-distribution";
+    /// Followed by the field id, in big endian.
+    pub const NUMBER_FACETED_DOCUMENTS_IDS_PREFIX: &str = "number-faceted-documents-ids";
+    pub const STRING_FACETED_DOCUMENTS_IDS_PREFIX: &str = "string-faceted-documents-ids";
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct StatsCounts {
+    pub number_of_documents: u64,
+    pub field_distribution: BTreeMap<String, u64>,
+    /// The number of documents that have a value for each faceted field.
+    pub facet_cardinalities: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct StatsRepairReport {
+    /// The stats as they were stored.
+    pub before: StatsCounts,
+    /// The stats as they were counted again.
+    pub after: StatsCounts,
+    /// Whether the stored stats were replaced, never in report-only mode.
+    pub repaired: bool,
+}
+
+fn difference(a: u64, b: u64) -> u64 {
+    a.max(b) - a.min(b)
+}
+
+impl StatsRepairReport {
+    pub fn is_consistent(&self) -> bool {
+        self.before == self.after
+    }
+
+    /// The largest difference between a stored count and the count it should be.
+    pub fn discrepancy(&self) -> u64 {
+        fn map_discrepancy(before: &BTreeMap<String, u64>, after: &BTreeMap<String, u64>) -> u64 {
+            before
+                .keys()
+                .chain(after.keys())
+                .map(|key| {
+                    let before = before.get(key).copied().unwrap_or_default();
+                    let after = after.get(key).copied().unwrap_or_default();
+                    difference(before, after)
+                })
+                .max()
+                .unwrap_or_default()
+        }
+
+        let (before, after) = (&self.before, &self.after);
+        difference(before.number_of_documents, after.number_of_documents)
+            .max(map_discrepancy(
+                &before.field_distribution,
+                &after.field_distribution,
+            ))
+            .max(map_discrepancy(
+                &before.facet_cardinalities,
+                &after.facet_cardinalities,
+            ))
+    }
+}
+
+/// The stats of an index, as milli stores them.
+pub(super) struct Stats {
+    documents_ids: RoaringBitmap,
+    soft_deleted: RoaringBitmap,
+    field_distribution: FieldDistribution,
+    /// The documents with a number value and the ones with a string value, by faceted field.
+    faceted: BTreeMap<FieldId, (RoaringBitmap, RoaringBitmap)>,
+}
+
+impl Stats {
+    fn counts(&self, faceted_fields: &BTreeMap<FieldId, String>) -> StatsCounts {
+        StatsCounts {
+            number_of_documents: (&self.documents_ids - &self.soft_deleted).len(),
+            field_distribution: self.field_distribution.clone(),
+            facet_cardinalities: self
+                .faceted
+                .iter()
+                .filter_map(|(fid, (numbers, strings))| {
+                    Some((faceted_fields.get(fid)?.clone(), (numbers | strings).len()))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the faceted fields of the index, the fields nested in a faceted object included.
+fn faceted_fields(index: &Index, txn: &RoTxn) -> Result<BTreeMap<FieldId, String>> {
+    let fields_ids_map = index.fields_ids_map(txn)?;
+    let faceted: BTreeSet<_> = index
+        .filterable_fields(txn)?
+        .into_iter()
+        .chain(index.sortable_fields(txn)?)
+        .collect();
+
+    Ok(fields_ids_map
+        .iter()
+        .filter(|(_, field)| {
+            faceted
+                .iter()
+                .any(|facet| milli::is_faceted_by(field, facet))
+        })
+        .map(|(fid, field)| (fid, field.to_string()))
+        .collect())
+}
+
+/// Returns the documents that have a number value and the ones that have a string value for the
+/// field `fid`, read from the level zero of the facet databases.
+fn facet_values_documents(
+    index: &Index,
+    txn: &RoTxn,
+    fid: FieldId,
+) -> Result<(RoaringBitmap, RoaringBitmap)> {
+    let mut numbers = RoaringBitmap::new();
+    let entries = index.facet_id_f64_docids.range(
+        txn,
+        &((fid, 0, f64::MIN, f64::MIN)..=(fid, 0, f64::MAX, f64::MAX)),
+    )?;
+    for result in entries {
+        let (_, docids) = result?;
+        numbers |= docids;
+    }
+
+    let mut strings = RoaringBitmap::new();
+    // the level zero of the string facets is prefixed by the field id and the level.
+    for result in index.facet_id_string_docids.prefix_iter(txn, &(fid, ""))? {
+        let (_, (_, docids)) = result?;
+        strings |= docids;
+    }
+
+    Ok((numbers, strings))
+}
+
+fn faceted_documents_ids_key(prefix: &str, fid: FieldId) -> Vec<u8> {
+    let mut key = prefix.as_bytes().to_vec();
+    key.extend_from_slice(&fid.to_be_bytes());
+    key
+}
+
+impl Index {
+    /// Counts the stats of the index again, and replaces the stored ones if they are wrong,
+    /// unless `report_only`.
+    pub fn repair_stats(&self, report_only: bool) -> Result<StatsRepairReport> {
+        if report_only {
+            let rtxn = self.read_txn()?;
+            return Ok(self.recount_stats(&rtxn)?.0);
+        }
+
+        let mut wtxn = self.write_txn()?;
+        let (mut report, counted) = self.recount_stats(&wtxn)?;
+        if !report.is_consistent() {
+            self.put_stats(&mut wtxn, &counted)?;
+            wtxn.commit()?;
+            report.repaired = true;
+        }
+        Ok(report)
+    }
+
+    /// Counts the stats of the index again, and compares them with the stored ones. The
+    /// discrepancies beyond `DISCREPANCY_WARNING_THRESHOLD` are logged.
+    pub(super) fn recount_stats(&self, txn: &RoTxn) -> Result<(StatsRepairReport, Stats)> {
+        let faceted_fields = faceted_fields(self, txn)?;
+        let stored = self.stored_stats(txn, &faceted_fields)?;
+        let counted = self.counted_stats(txn, &faceted_fields)?;
+        let report = StatsRepairReport {
+            before: stored.counts(&faceted_fields),
+            after: counted.counts(&faceted_fields),
+            repaired: false,
+        };
+
+        let discrepancy = report.discrepancy();
+        if discrepancy > DISCREPANCY_WARNING_THRESHOLD {
+            log::warn!(
+                "The stats of the index {} are off by up to {}: {} documents are stored, {} documents were counted. Stored stats: {}. Counted stats: {}.",
+                self.uuid,
+                discrepancy,
+                stored.documents_ids.len(),
+                counted.documents_ids.len(),
+                serde_json::to_string(&report.before).unwrap_or_default(),
+                serde_json::to_string(&report.after).unwrap_or_default(),
+            );
+        }
+
+        Ok((report, counted))
+    }
+
+    fn stored_stats(
+        &self,
+        txn: &RoTxn,
+        faceted_fields: &BTreeMap<FieldId, String>,
+    ) -> Result<Stats> {
+        let mut faceted = BTreeMap::new();
+        for &fid in faceted_fields.keys() {
+            faceted.insert(
+                fid,
+                (
+                    self.number_faceted_documents_ids(txn, fid)?,
+                    self.string_faceted_documents_ids(txn, fid)?,
+                ),
+            );
+        }
+
+        Ok(Stats {
+            documents_ids: self.documents_ids(txn)?,
+            soft_deleted: self.soft_deleted_documents(txn)?,
+            field_distribution: self.field_distribution(txn)?,
+            faceted,
+        })
+    }
+
+    fn counted_stats(
+        &self,
+        txn: &RoTxn,
+        faceted_fields: &BTreeMap<FieldId, String>,
+    ) -> Result<Stats> {
+        let fields_ids_map = self.fields_ids_map(txn)?;
+        let mut documents_ids = RoaringBitmap::new();
+        let mut field_distribution = FieldDistribution::new();
+        for result in self.documents.iter(txn)? {
+            let (docid, obkv) = result?;
+            documents_ids.insert(docid.get());
+            for (fid, _) in obkv.iter() {
+                if let Some(name) = fields_ids_map.name(fid) {
+                    *field_distribution.entry(name.to_string()).or_default() += 1;
+                }
+            }
+        }
+
+        let mut faceted = BTreeMap::new();
+        for &fid in faceted_fields.keys() {
+            let (numbers, strings) = facet_values_documents(self, txn, fid)?;
+            faceted.insert(fid, (numbers & &documents_ids, strings & &documents_ids));
+        }
+
+        Ok(Stats {
+            soft_deleted: self.soft_deleted_documents(txn)? & &documents_ids,
+            documents_ids,
+            field_distribution,
+            faceted,
+        })
+    }
+
+    fn put_stats(&self, txn: &mut RwTxn, stats: &Stats) -> Result<()> {
+        self.main.put::<_, Str, RoaringBitmapCodec>(
+            txn,
+            milli_key::DOCUMENTS_IDS,
+            &stats.documents_ids,
+        )?;
+        self.main.put::<_, Str, SerdeJson<FieldDistribution>>(
+            txn,
+            milli_key::FIELD_DISTRIBUTION,
+            &stats.field_distribution,
+        )?;
+        for (&fid, (numbers, strings)) in &stats.faceted {
+            for (prefix, docids) in [
+                (milli_key::NUMBER_FACETED_DOCUMENTS_IDS_PREFIX, numbers),
+                (milli_key::STRING_FACETED_DOCUMENTS_IDS_PREFIX, strings),
+            ] {
+                self.main.put::<_, ByteSlice, RoaringBitmapCodec>(
+                    txn,
+                    &faceted_documents_ids_key(prefix, fid),
+                    docids,
+                )?;
+            }
+        }
+        self.put_soft_deleted_documents(txn, &stats.soft_deleted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn counts(documents: u64, fields: &[(&str, u64)]) -> StatsCounts {
+        StatsCounts {
+            number_of_documents: documents,
+            field_distribution: fields
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect(),
+            facet_cardinalities: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_discrepancy() {
+        let report = StatsRepairReport {
+            before: counts(10, &[("id", 10), ("title", 8)]),
+            after: counts(10, &[("id", 10), ("title", 8)]),
+            repaired: false,
+        };
+        assert!(report.is_consistent());
+        assert_eq!(report.discrepancy(), 0);
+
+        // a field missing on one side counts as a count of 0.
+        let report = StatsRepairReport {
+            before: counts(12, &[("id", 12), ("title", 8)]),
+            after: counts(10, &[("id", 10), ("title", 8), ("year", 5)]),
+            repaired: true,
+        };
+        assert!(!report.is_consistent());
+        assert_eq!(report.discrepancy(), 5);
+    }
+}
@@ -0,0 +1,92 @@
+//! The stop words of the index, taken out of the queries rather than out of the documents.
+//!
+//! milli doesn't index the stop words it is given, so a phrase only made of stop words, like "The
+//! Who", couldn't be found. The stop words are kept in the main database of the index instead,
+//! and milli indexes them like the other words, at their positions, so the quoted phrases made of
+//! them are matched by milli like the other phrases. Changing the stop words doesn't index the
+//! documents again.
+//!
+//! The stop words out of the quoted phrases of a query are taken out of the query given to milli,
+//! unless the search doesn't ignore them, so they neither match nor rank the documents.
+
+use std::collections::BTreeSet;
+
+use milli::heed::RoTxn;
+use milli::tokenizer::{Analyzer, AnalyzerConfig};
+
+use super::error::Result;
+use super::index::read_stop_words;
+
+/// Returns the stop words of the index.
+pub fn stop_words(index: &milli::Index, txn: &RoTxn) -> Result<BTreeSet<String>> {
+    read_stop_words(index, txn)
+}
+
+/// Takes the stop words out of `query`, but for the ones of its quoted phrases. A query only made
+/// of stop words is left as it is, it would match every document otherwise.
+pub fn remove_stop_words(query: &str, stop_words: &BTreeSet<String>) -> String {
+    let empty = fst::Set::default();
+    let mut config = AnalyzerConfig::default();
+    config.stop_words(&empty);
+    let analyzer = Analyzer::new(config);
+
+    let mut removed = String::new();
+    let mut has_words = false;
+    let parts: Vec<_> = query.split('"').collect();
+    for (i, part) in parts.iter().enumerate() {
+        // an unclosed quote doesn't start a phrase.
+        let quoted = i % 2 == 1 && i + 1 < parts.len();
+        if quoted {
+            removed.push_str(&format!("\"{}\"", part));
+            has_words = true;
+            continue;
+        }
+        if i % 2 == 1 {
+            removed.push('"');
+        }
+        for token in analyzer.analyze(part).tokens() {
+            if token.is_word() && stop_words.contains(token.text()) {
+                continue;
+            }
+            has_words |= token.is_word();
+            removed.push_str(token.text());
+        }
+    }
+
+    match has_words {
+        true => removed,
+        false => query.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stop_word_set() -> BTreeSet<String> {
+        ["the", "who", "of", "is", "a"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_remove_stop_words() {
+        let stop_words = stop_word_set();
+
+        // the stop words of the quoted phrases are kept.
+        assert_eq!(
+            remove_stop_words("\"the who\" live", &stop_words),
+            "\"the who\" live"
+        );
+        assert_eq!(
+            remove_stop_words("lord of the rings ", &stop_words),
+            "lord   rings "
+        );
+        // an unclosed quote doesn't start a phrase.
+        assert_eq!(remove_stop_words("band \"the who", &stop_words), "band \" ");
+        // a query only made of stop words is left as it is.
+        assert_eq!(remove_stop_words("the who", &stop_words), "the who");
+        assert_eq!(remove_stop_words("", &stop_words), "");
+    }
+}
@@ -0,0 +1,367 @@
+//! The transforms applied to the documents when they are added.
+//!
+//! The `documentTransforms` setting of an index lists built-in transforms, applied in order to
+//! each document of a document addition before it is indexed, so that the fields derived from the
+//! other ones are computed the same way whichever client sends the documents. The transforms are
+//! pure and cheap: each one only reads and writes the top-level fields of the document it is
+//! applied to.
+//!
+//! A transform that can't be applied to a document, like `toLowercase` on a number, leaves the
+//! document as it is, and the next transforms are applied. The document is indexed anyway, and
+//! counted as failed in the details of its task. A transform on a missing or `null` field is
+//! skipped.
+//!
+//! The transforms are only applied when the documents are added: changing the setting doesn't
+//! transform the documents that are already in the index, they are transformed by the new
+//! transforms once they are added again.
+
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+
+use milli::documents::{DocumentBatchBuilder, DocumentBatchReader};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::error::{IndexError, Result};
+
+/// The HTML elements that separate the words of their content from the surrounding text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "br", "dd", "div", "dl", "dt", "footer", "h1", "h2", "h3", "h4", "h5",
+    "h6", "header", "hr", "li", "ol", "p", "section", "table", "td", "th", "tr", "ul",
+];
+
+/// The character entities decoded by `stripHtml`. `&amp;` is decoded last, so that `&amp;lt;`
+/// becomes `&lt;`.
+const ENTITIES: &[(&str, &str)] = &[
+    ("&lt;", "<"),
+    ("&gt;", ">"),
+    ("&quot;", "\""),
+    ("&#39;", "'"),
+    ("&apos;", "'"),
+    ("&nbsp;", " "),
+    ("&amp;", "&"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase", deny_unknown_fields)]
+pub enum DocumentTransform {
+    /// Removes the HTML tags of a string field, or of the strings of an array, and decodes their
+    /// character entities.
+    StripHtml { field: String },
+    /// Copies the value of `field` to the field `to`.
+    Copy { field: String, to: String },
+    /// Joins the values of the `fields` that are strings, numbers or booleans with `separator`,
+    /// into the string field `to`.
+    Concat {
+        fields: Vec<String>,
+        to: String,
+        #[serde(default)]
+        separator: String,
+    },
+    /// Lowercases a string field, or the strings of an array.
+    ToLowercase { field: String },
+}
+
+/// What applying a transform to a document did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Applied,
+    /// The fields the transform reads are missing.
+    Skipped,
+    /// The transform can't be applied to the values of the fields it reads.
+    Failed,
+}
+
+impl DocumentTransform {
+    fn apply(&self, document: &mut Map<String, Value>) -> Outcome {
+        match self {
+            Self::StripHtml { field } => map_strings(document, field, strip_html),
+            Self::ToLowercase { field } => map_strings(document, field, |s| s.to_lowercase()),
+            Self::Copy { field, to } => match document.get(field) {
+                None | Some(Value::Null) => Outcome::Skipped,
+                Some(value) => {
+                    let value = value.clone();
+                    document.insert(to.clone(), value);
+                    Outcome::Applied
+                }
+            },
+            Self::Concat {
+                fields,
+                to,
+                separator,
+            } => {
+                let mut parts = Vec::new();
+                for field in fields {
+                    match document.get(field) {
+                        None | Some(Value::Null) => (),
+                        Some(Value::String(s)) => parts.push(s.clone()),
+                        Some(value @ (Value::Number(_) | Value::Bool(_))) => {
+                            parts.push(value.to_string())
+                        }
+                        Some(Value::Array(_) | Value::Object(_)) => return Outcome::Failed,
+                    }
+                }
+                if parts.is_empty() {
+                    return Outcome::Skipped;
+                }
+                document.insert(to.clone(), Value::String(parts.join(separator)));
+                Outcome::Applied
+            }
+        }
+    }
+}
+
+/// Replaces the string of `field`, or the strings of its array, by their image through `f`. The
+/// field is left as it is when it holds anything else.
+fn map_strings(
+    document: &mut Map<String, Value>,
+    field: &str,
+    f: impl Fn(&str) -> String,
+) -> Outcome {
+    match document.get_mut(field) {
+        None | Some(Value::Null) => Outcome::Skipped,
+        Some(Value::String(s)) => {
+            *s = f(s);
+            Outcome::Applied
+        }
+        Some(Value::Array(values)) if values.iter().all(Value::is_string) => {
+            for value in values {
+                if let Value::String(s) = value {
+                    *s = f(s);
+                }
+            }
+            Outcome::Applied
+        }
+        Some(_) => Outcome::Failed,
+    }
+}
+
+/// Removes the tags of `html`. The tags of the block elements are replaced by a space, so that
+/// the words of consecutive paragraphs stay separated.
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let (before, tag) = rest.split_at(start);
+        text.push_str(before);
+        // a `<` that doesn't open a tag, like in `a < b`, is kept.
+        let opens_tag = tag[1..]
+            .chars()
+            .next()
+            .map_or(false, |c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        match tag.find('>').filter(|_| opens_tag) {
+            Some(end) => {
+                let name: String = tag[1..end]
+                    .trim_start_matches('/')
+                    .chars()
+                    .take_while(char::is_ascii_alphanumeric)
+                    .collect::<String>()
+                    .to_ascii_lowercase();
+                if BLOCK_ELEMENTS.contains(&name.as_str()) && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+                rest = &tag[end + 1..];
+            }
+            None => {
+                text.push('<');
+                rest = &tag[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+
+    let mut text = text.trim().to_string();
+    for (entity, decoded) in ENTITIES {
+        if text.contains(entity) {
+            text = text.replace(entity, decoded);
+        }
+    }
+    text
+}
+
+/// What the transforms did to the documents of a document addition.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct TransformsResult {
+    /// The number of transforms applied, summed over the documents.
+    pub applied: u64,
+    /// The number of documents on which at least one transform failed.
+    pub failed_documents: u64,
+}
+
+impl TransformsResult {
+    pub fn merge(&mut self, other: Self) {
+        self.applied += other.applied;
+        self.failed_documents += other.failed_documents;
+    }
+}
+
+/// Returns the first error the transforms would fail with once set.
+pub fn validate_transforms(transforms: &[DocumentTransform]) -> Result<()> {
+    for transform in transforms {
+        let fields = match transform {
+            DocumentTransform::StripHtml { field } | DocumentTransform::ToLowercase { field } => {
+                vec![field]
+            }
+            DocumentTransform::Copy { field, to } => vec![field, to],
+            DocumentTransform::Concat { fields, to, .. } => {
+                if fields.is_empty() {
+                    return Err(IndexError::InvalidDocumentTransform(
+                        "a `concat` transform must have at least one field to join.".to_string(),
+                    ));
+                }
+                fields.iter().chain(Some(to)).collect()
+            }
+        };
+        if fields.iter().any(|field| field.is_empty()) {
+            return Err(IndexError::InvalidDocumentTransform(
+                "the name of a field can't be empty.".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Applies `transforms` in order to each document of the batch read from `input`, and writes the
+/// transformed batch to `writer`.
+pub fn transform_batch(
+    input: impl Read + Seek,
+    writer: impl Write + Seek,
+    transforms: &[DocumentTransform],
+) -> Result<TransformsResult> {
+    let mut reader = DocumentBatchReader::from_reader(input)?;
+    let mut builder = DocumentBatchBuilder::new(BufWriter::new(writer))?;
+    let mut result = TransformsResult::default();
+    let mut buffer = Vec::new();
+
+    while let Some((index, document)) = reader.next_document_with_index()? {
+        let mut object = Map::new();
+        for (field_id, value) in document.iter() {
+            if let Some(name) = index.name(field_id) {
+                object.insert(name.to_string(), serde_json::from_slice(value)?);
+            }
+        }
+
+        let mut failed = false;
+        for transform in transforms {
+            match transform.apply(&mut object) {
+                Outcome::Applied => result.applied += 1,
+                Outcome::Skipped => (),
+                Outcome::Failed => failed = true,
+            }
+        }
+        if failed {
+            result.failed_documents += 1;
+        }
+
+        buffer.clear();
+        serde_json::to_writer(&mut buffer, &object)?;
+        builder.extend_from_json(Cursor::new(&buffer))?;
+    }
+
+    builder.finish()?;
+    Ok(result)
+}
+
+/// Applies `transforms` to the documents of the update file `content_file`, which is left as it
+/// is: the transformed documents are written aside. Returns the file of the documents to index,
+/// and what the transforms did, if there are any.
+pub fn transform_file(
+    content_file: File,
+    transforms: &[DocumentTransform],
+) -> Result<(File, Option<TransformsResult>)> {
+    if transforms.is_empty() {
+        return Ok((content_file, None));
+    }
+
+    let mut transformed = tempfile::tempfile()?;
+    let result = transform_batch(content_file, &mut transformed, transforms)?;
+    transformed.seek(SeekFrom::Start(0))?;
+    Ok((transformed, Some(result)))
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn apply(transforms: Value, document: Value) -> (Value, Vec<Outcome>) {
+        let transforms: Vec<DocumentTransform> = serde_json::from_value(transforms).unwrap();
+        let mut document = match document {
+            Value::Object(object) => object,
+            _ => panic!("not an object"),
+        };
+        let outcomes = transforms.iter().map(|t| t.apply(&mut document)).collect();
+        (Value::Object(document), outcomes)
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<p>A <b>bold</b> move</p><p>Fish &amp; chips, 1 &lt; 2</p>"),
+            "A bold move Fish & chips, 1 < 2"
+        );
+        assert_eq!(strip_html("a < b and c > d"), "a < b and c > d");
+        assert_eq!(strip_html("mi<i>lli</i>"), "milli");
+    }
+
+    #[test]
+    fn test_transforms_are_applied_in_order() {
+        let (document, outcomes) = apply(
+            json!([
+                { "type": "stripHtml", "field": "description" },
+                { "type": "copy", "field": "brand", "to": "brandLower" },
+                { "type": "toLowercase", "field": "brandLower" },
+                { "type": "concat", "fields": ["brand", "price", "missing"], "to": "label", "separator": " - " },
+                { "type": "toLowercase", "field": "missing" },
+            ]),
+            json!({ "description": "<p>Hello</p>", "brand": "ACME", "price": 12 }),
+        );
+        assert_eq!(
+            document,
+            json!({
+                "description": "Hello",
+                "brand": "ACME",
+                "brandLower": "acme",
+                "price": 12,
+                "label": "ACME - 12",
+            })
+        );
+        use Outcome::*;
+        assert_eq!(outcomes, [Applied, Applied, Applied, Applied, Skipped]);
+    }
+
+    #[test]
+    fn test_failed_transform_leaves_the_document() {
+        let (document, outcomes) = apply(
+            json!([
+                { "type": "toLowercase", "field": "price" },
+                { "type": "concat", "fields": ["tags"], "to": "label" },
+                { "type": "toLowercase", "field": "tags" },
+            ]),
+            json!({ "price": 12, "tags": ["A", "B"] }),
+        );
+        assert_eq!(document, json!({ "price": 12, "tags": ["a", "b"] }));
+        assert_eq!(
+            outcomes,
+            [Outcome::Failed, Outcome::Failed, Outcome::Applied]
+        );
+    }
+
+    #[test]
+    fn test_validate_transforms() {
+        let transforms: Vec<DocumentTransform> =
+            serde_json::from_value(json!([{ "type": "concat", "fields": [], "to": "label" }]))
+                .unwrap();
+        assert!(matches!(
+            validate_transforms(&transforms),
+            Err(IndexError::InvalidDocumentTransform(_))
+        ));
+
+        let unknown: std::result::Result<Vec<DocumentTransform>, _> =
+            serde_json::from_value(json!([{ "type": "uppercase", "field": "title" }]));
+        assert!(unknown.is_err());
+    }
+}
@@ -1,19 +1,33 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
 use std::marker::PhantomData;
 use std::num::NonZeroUsize;
+use std::str::FromStr;
 
 use log::{debug, info, trace};
 use milli::documents::DocumentBatchReader;
-use milli::update::{
-    DocumentAdditionResult, DocumentDeletionResult, IndexDocumentsConfig, IndexDocumentsMethod,
-    Setting,
-};
+use milli::update::{IndexDocumentsConfig, IndexDocumentsMethod, Setting, UpdateIndexingStep};
+use roaring::RoaringBitmap;
 use serde::{Deserialize, Serialize, Serializer};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
-use super::error::Result;
-use super::index::{Index, IndexMeta};
-use crate::update_file_store::UpdateFileStore;
+use super::derived::{self, AdditionDerivation, Derivation, DERIVED_FIELD};
+use super::edition::external_id;
+use super::error::{IndexError, Result};
+use super::facet_normalization::FacetNormalization;
+use super::index::{apply_meilisearch_settings, clear_soft_deleted_documents, Index, IndexMeta};
+use super::metadata;
+use super::nested::NestedObjectBehavior;
+use super::prefix_search::PrefixSearch;
+use super::search_defaults::SearchDefaults;
+use super::segments::SEGMENTS_FIELD;
+use super::sequences;
+use super::transforms::{self, validate_transforms, DocumentTransform, TransformsResult};
+use crate::document_formats;
+use crate::segmenters::CustomSegmenters;
+use crate::update_file_store::{UpdateFileDigest, UpdateFileStore};
 
 fn serialize_with_wildcard<S>(
     field: &Setting<Vec<String>>,
@@ -31,6 +45,74 @@ where
     .serialize(s)
 }
 
+fn serialize_searchable_with_wildcard<S>(
+    field: &Setting<SearchableAttributes>,
+    s: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let wildcard = SearchableAttributes::List(vec!["*".to_string()]);
+    match field {
+        Setting::Set(value) => Some(value),
+        Setting::Reset => Some(&wildcard),
+        Setting::NotSet => None,
+    }
+    .serialize(s)
+}
+
+/// The searchable attributes, as a list by decreasing importance, or with the weight of each
+/// attribute. milli is given the attributes by decreasing weight, and the weights are applied to
+/// the attribute ranking rule around the milli search.
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum SearchableAttributes {
+    List(Vec<String>),
+    Weighted(BTreeMap<String, u32>),
+}
+
+impl SearchableAttributes {
+    /// The attributes by decreasing importance, the attributes of equal weight by name.
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            Self::List(names) => names.clone(),
+            Self::Weighted(weights) => {
+                let mut names: Vec<_> = weights.iter().collect();
+                names.sort_by(|(_, lhs), (_, rhs)| rhs.cmp(lhs));
+                names.into_iter().map(|(name, _)| name.clone()).collect()
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::List(names) => names.len(),
+            Self::Weighted(weights) => weights.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn weights(&self) -> Option<&BTreeMap<String, u32>> {
+        match self {
+            Self::List(_) => None,
+            Self::Weighted(weights) => Some(weights),
+        }
+    }
+}
+
+/// Fails if an attribute has a null weight, the attributes that must not rank the documents are
+/// simply not searchable.
+pub fn check_searchable_weights(weights: &BTreeMap<String, u32>) -> Result<()> {
+    match weights.iter().find(|(_, weight)| **weight == 0) {
+        Some((attribute, _)) => Err(IndexError::InvalidSearchableWeight(attribute.clone())),
+        None => Ok(()),
+    }
+}
+
 #[derive(Clone, Default, Debug, Serialize, PartialEq)]
 pub struct Checked;
 
@@ -87,11 +169,11 @@ pub struct Settings<T> {
 
     #[serde(
         default,
-        serialize_with = "serialize_with_wildcard",
+        serialize_with = "serialize_searchable_with_wildcard",
         skip_serializing_if = "Setting::is_not_set"
     )]
     #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
-    pub searchable_attributes: Setting<Vec<String>>,
+    pub searchable_attributes: Setting<SearchableAttributes>,
 
     #[serde(default, skip_serializing_if = "Setting::is_not_set")]
     #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
@@ -114,6 +196,52 @@ pub struct Settings<T> {
     #[serde(default, skip_serializing_if = "Setting::is_not_set")]
     #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
     pub typo_tolerance: Setting<TypoSettings>,
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
+    pub warmup_queries: Setting<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
+    pub max_word_derivations: Setting<usize>,
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
+    pub max_prefix_expansion: Setting<usize>,
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
+    pub max_values_per_facet: Setting<usize>,
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(value = "Setting::NotSet"))]
+    pub search_defaults: Setting<SearchDefaults>,
+    /// Whether the `_createdAt` and `_updatedAt` metadata of the documents are recorded.
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
+    pub document_metadata: Setting<bool>,
+    /// How the arrays of objects of each attribute are filtered. Filtering the attributes set to
+    /// `element` element by element indexes their values a second time, by position.
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(value = "Setting::NotSet"))]
+    pub nested_object_behavior: Setting<BTreeMap<String, NestedObjectBehavior>>,
+    /// The transforms applied in order to the documents when they are added. Changing them
+    /// doesn't transform the documents already in the index.
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(value = "Setting::NotSet"))]
+    pub document_transforms: Setting<Vec<DocumentTransform>>,
+    /// The segmenter splitting the text of each language into words, by ISO 639-3 code, in place
+    /// of the tokenizer of milli.
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(value = "Setting::NotSet"))]
+    pub custom_segmenters: Setting<BTreeMap<String, String>>,
+    /// Whether the last word of the queries is matched as a prefix.
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(value = "Setting::NotSet"))]
+    pub prefix_search: Setting<PrefixSearch>,
+    /// The attributes where the last word of the queries is never matched as a prefix.
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(strategy = "test::setting_strategy()"))]
+    pub disable_prefix_on_attributes: Setting<BTreeSet<String>>,
+    /// How the facet values of each attribute are normalized before being compared.
+    #[serde(default, skip_serializing_if = "Setting::is_not_set")]
+    #[cfg_attr(test, proptest(value = "Setting::NotSet"))]
+    pub facet_normalization: Setting<BTreeMap<String, FacetNormalization>>,
 
     #[serde(skip)]
     pub _kind: PhantomData<T>,
@@ -131,10 +259,34 @@ impl Settings<Checked> {
             synonyms: Setting::Reset,
             distinct_attribute: Setting::Reset,
             typo_tolerance: Setting::Reset,
+            warmup_queries: Setting::Reset,
+            max_word_derivations: Setting::Reset,
+            max_prefix_expansion: Setting::Reset,
+            max_values_per_facet: Setting::Reset,
+            search_defaults: Setting::Reset,
+            document_metadata: Setting::Reset,
+            nested_object_behavior: Setting::Reset,
+            document_transforms: Setting::Reset,
+            custom_segmenters: Setting::Reset,
+            prefix_search: Setting::Reset,
+            disable_prefix_on_attributes: Setting::Reset,
+            facet_normalization: Setting::Reset,
             _kind: PhantomData,
         }
     }
 
+    /// Whether applying the settings reprocesses all the documents of the index.
+    pub fn reindexes_documents(&self) -> bool {
+        !self.searchable_attributes.is_not_set()
+            || !self.filterable_attributes.is_not_set()
+            || !self.sortable_attributes.is_not_set()
+            || !self.ranking_rules.is_not_set()
+            || !self.synonyms.is_not_set()
+            || !self.distinct_attribute.is_not_set()
+            || !self.nested_object_behavior.is_not_set()
+            || !self.custom_segmenters.is_not_set()
+    }
+
     pub fn into_unchecked(self) -> Settings<Unchecked> {
         let Self {
             displayed_attributes,
@@ -146,6 +298,18 @@ impl Settings<Checked> {
             synonyms,
             distinct_attribute,
             typo_tolerance,
+            warmup_queries,
+            max_word_derivations,
+            max_prefix_expansion,
+            max_values_per_facet,
+            search_defaults,
+            document_metadata,
+            nested_object_behavior,
+            document_transforms,
+            custom_segmenters,
+            prefix_search,
+            disable_prefix_on_attributes,
+            facet_normalization,
             ..
         } = self;
 
@@ -159,50 +323,150 @@ impl Settings<Checked> {
             synonyms,
             distinct_attribute,
             typo_tolerance,
+            warmup_queries,
+            max_word_derivations,
+            max_prefix_expansion,
+            max_values_per_facet,
+            search_defaults,
+            document_metadata,
+            nested_object_behavior,
+            document_transforms,
+            custom_segmenters,
+            prefix_search,
+            disable_prefix_on_attributes,
+            facet_normalization,
             _kind: PhantomData,
         }
     }
 }
 
 impl Settings<Unchecked> {
+    /// Returns the first error the settings would fail with once applied that can be found
+    /// without the index.
+    pub fn validate(&self) -> Result<()> {
+        if let Setting::Set(ref rules) = self.ranking_rules {
+            for rule in rules {
+                milli::Criterion::from_str(rule)
+                    .map_err(|e| milli::Error::UserError(milli::UserError::CriterionError(e)))?;
+            }
+        }
+        if let Setting::Set(SearchableAttributes::Weighted(ref weights)) =
+            self.searchable_attributes
+        {
+            check_searchable_weights(weights)?;
+        }
+        if let Setting::Set(ref transforms) = self.document_transforms {
+            validate_transforms(transforms)?;
+        }
+        if let Setting::Set(ref selection) = self.custom_segmenters {
+            CustomSegmenters::resolve(selection)?;
+        }
+        Ok(())
+    }
+
     pub fn check(self) -> Settings<Checked> {
         let displayed_attributes = match self.displayed_attributes {
             Setting::Set(fields) => {
                 if fields.iter().any(|f| f == "*") {
                     Setting::Reset
                 } else {
-                    Setting::Set(fields)
+                    Setting::Set(strip_path_wildcards(fields))
                 }
             }
             otherwise => otherwise,
         };
 
         let searchable_attributes = match self.searchable_attributes {
-            Setting::Set(fields) => {
+            Setting::Set(SearchableAttributes::List(fields)) => {
                 if fields.iter().any(|f| f == "*") {
                     Setting::Reset
                 } else {
-                    Setting::Set(fields)
+                    Setting::Set(SearchableAttributes::List(strip_path_wildcards(fields)))
+                }
+            }
+            Setting::Set(SearchableAttributes::Weighted(weights)) => {
+                if weights.contains_key("*") {
+                    Setting::Reset
+                } else {
+                    // the heaviest weight of the attributes that are the same once stripped wins.
+                    let mut stripped = BTreeMap::new();
+                    for (attribute, weight) in weights {
+                        let entry = stripped.entry(strip_path_wildcard(attribute)).or_insert(0);
+                        *entry = weight.max(*entry);
+                    }
+                    Setting::Set(SearchableAttributes::Weighted(stripped))
                 }
             }
             otherwise => otherwise,
         };
 
+        let filterable_attributes = match self.filterable_attributes {
+            Setting::Set(fields) => {
+                Setting::Set(fields.into_iter().map(strip_path_wildcard).collect())
+            }
+            otherwise => otherwise,
+        };
+
+        let sortable_attributes = match self.sortable_attributes {
+            Setting::Set(fields) => {
+                Setting::Set(fields.into_iter().map(strip_path_wildcard).collect())
+            }
+            otherwise => otherwise,
+        };
+
         Settings {
             displayed_attributes,
             searchable_attributes,
-            filterable_attributes: self.filterable_attributes,
-            sortable_attributes: self.sortable_attributes,
+            filterable_attributes,
+            sortable_attributes,
             ranking_rules: self.ranking_rules,
             stop_words: self.stop_words,
             synonyms: self.synonyms,
             distinct_attribute: self.distinct_attribute,
             typo_tolerance: self.typo_tolerance,
+            warmup_queries: self.warmup_queries,
+            max_word_derivations: self.max_word_derivations,
+            max_prefix_expansion: self.max_prefix_expansion,
+            max_values_per_facet: self.max_values_per_facet,
+            search_defaults: self.search_defaults,
+            document_metadata: self.document_metadata,
+            nested_object_behavior: self.nested_object_behavior,
+            document_transforms: self.document_transforms,
+            custom_segmenters: self.custom_segmenters,
+            prefix_search: self.prefix_search,
+            disable_prefix_on_attributes: self.disable_prefix_on_attributes,
+            facet_normalization: self.facet_normalization,
             _kind: PhantomData,
         }
     }
 }
 
+/// Turns the path wildcard `address.*` into `address`.
+///
+/// The documents are flattened when they are indexed: the field `city` of the object `address`
+/// is indexed as the attribute `address.city`, and an attribute list selects every field nested
+/// in the paths it contains, so `address.*` and `address` designate the same fields. A key
+/// containing a literal dot can't be told apart from the equivalent nested path: the values of a
+/// `"address.city"` key and of a `{ "address": { "city" } }` object are both indexed as the
+/// attribute `address.city`.
+pub(super) fn strip_path_wildcard(attribute: String) -> String {
+    match attribute.strip_suffix(".*") {
+        Some(path) if !path.is_empty() => path.to_string(),
+        _ => attribute,
+    }
+}
+
+/// Strips the path wildcards of an ordered attribute list, keeping the first occurrence of the
+/// attributes that end up duplicated.
+fn strip_path_wildcards(attributes: Vec<String>) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    attributes
+        .into_iter()
+        .map(strip_path_wildcard)
+        .filter(|attribute| seen.insert(attribute.clone()))
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "camelCase")]
@@ -211,7 +475,128 @@ pub struct Facets {
     pub min_level_size: Option<NonZeroUsize>,
 }
 
+/// How much of the difference between a document addition and the documents it replaces is
+/// recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub enum DocumentsDiffMode {
+    /// Only the number of created and updated documents.
+    Summary,
+    /// The numbers, and the ids of the updated documents.
+    Full,
+}
+
+/// The documents of a payload that were created, and the ones that replaced or updated an
+/// existing document.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentsDiff {
+    pub created: u64,
+    pub updated: u64,
+    /// The ids of the updated documents, only recorded in the full mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_ids: Option<Vec<String>>,
+}
+
+impl DocumentsDiff {
+    /// Adds the documents of `other`, the diff of a later chunk of the same addition.
+    pub fn merge(&mut self, other: DocumentsDiff) {
+        self.created += other.created;
+        self.updated += other.updated;
+        if let Some(ids) = other.replaced_ids {
+            self.replaced_ids.get_or_insert_with(Vec::new).extend(ids);
+        }
+    }
+}
+
+/// A payload of a document addition.
+#[derive(Debug, Clone)]
+pub struct DocumentsPayload {
+    pub content_uuid: Uuid,
+    /// Whether the payload must be refused when several of its documents have the same primary
+    /// key, instead of being deduplicated.
+    pub reject_duplicates: bool,
+    /// Whether the difference with the existing documents is computed, which costs an extra
+    /// pass over the payload.
+    pub diff: Option<DocumentsDiffMode>,
+    /// The digest of the update file when it was persisted, if it was recorded. The payload is
+    /// refused when its update file doesn't match it anymore.
+    pub digest: Option<UpdateFileDigest>,
+    /// The number of documents of the payload exceeding the soft limits on the documents, added
+    /// to the gauge of the index once the payload is indexed.
+    pub over_soft_limits: u64,
+}
+
+/// The outcome of a payload that was not refused.
+#[derive(Debug, Default)]
+pub struct PayloadResult {
+    /// The number of documents removed by the deduplication.
+    pub duplicate_documents: u64,
+    pub diff: Option<DocumentsDiff>,
+    /// What the document transforms of the index did to the documents of the payload, if the
+    /// index has any.
+    pub transforms: Option<TransformsResult>,
+}
+
+#[derive(Debug)]
+pub struct DocumentsAdditionResult {
+    pub indexed_documents: u64,
+    /// For each payload, in order, its outcome, or why it was refused.
+    pub payloads: Vec<Result<PayloadResult>>,
+}
+
+/// Returns why the update file of `payload` is refused if it doesn't match the digest recorded
+/// when it was persisted anymore.
+fn check_digest(
+    file_store: &UpdateFileStore,
+    payload: &DocumentsPayload,
+) -> Result<Option<IndexError>> {
+    let expected = match payload.digest {
+        Some(ref expected) => expected,
+        None => return Ok(None),
+    };
+    let digest = file_store.digest(payload.content_uuid)?;
+    if digest == *expected {
+        Ok(None)
+    } else {
+        Ok(Some(IndexError::CorruptedPayload {
+            expected_size: expected.size,
+            size: digest.size,
+            expected_sha256: expected.sha256.clone(),
+            sha256: digest.sha256,
+        }))
+    }
+}
+
 impl Index {
+    /// The external ids of the documents of the update file `payload`, at most `max` of them,
+    /// and whether some were left out.
+    pub fn payload_external_ids(&self, payload: File, max: usize) -> Result<(Vec<String>, bool)> {
+        let rtxn = self.read_txn()?;
+        let primary_key = match self.primary_key(&rtxn)? {
+            Some(primary_key) => primary_key.to_string(),
+            None => return Ok((Vec::new(), false)),
+        };
+
+        let mut reader = DocumentBatchReader::from_reader(payload)?;
+        let mut ids = Vec::new();
+        while let Some((fields, document)) = reader.next_document_with_index()? {
+            if ids.len() == max {
+                return Ok((ids, true));
+            }
+            let id = document
+                .iter()
+                .find(|(fid, _)| fields.name(*fid) == Some(&primary_key))
+                .map(|(_, bytes)| serde_json::from_slice(bytes))
+                .transpose()?;
+            ids.extend(id.as_ref().and_then(external_id));
+        }
+
+        Ok((ids, false))
+    }
+
     fn update_primary_key_txn<'a, 'b>(
         &'a self,
         txn: &mut milli::heed::RwTxn<'a, 'b>,
@@ -233,26 +618,113 @@ impl Index {
         Ok(res)
     }
 
-    /// Deletes `ids` from the index, and returns how many documents were deleted.
-    pub fn delete_documents(&self, ids: &[String]) -> Result<DocumentDeletionResult> {
+    /// Deletes the ids of each of `deletions` from the index, in a single transaction, and
+    /// returns how many documents each of them deleted. A document deleted by several deletions
+    /// is only counted by the first one.
+    /// Soft deletes the documents: they are only marked as deleted, and are purged from the index
+    /// later on, by a compaction. Their sequence numbers are recorded, for milli to leave them out
+    /// of the searches.
+    ///
+    /// The soft deleted documents keep their external ids until they are purged, so that milli
+    /// removes their own external ids when it purges them. A soft deleted document added back is
+    /// purged right before, as milli would merge it with the new one or replace it otherwise.
+    pub fn delete_documents(&self, deletions: &[Vec<String>]) -> Result<Vec<u64>> {
         let mut txn = self.write_txn()?;
-        let mut builder = milli::update::DeleteDocuments::new(&mut txn, self)?;
 
-        // We ignore unexisting document ids
-        ids.iter().for_each(|id| {
-            builder.delete_external_id(id);
-        });
+        let mut soft_deleted = self.soft_deleted_documents(&txn)?;
+        let previously_soft_deleted = soft_deleted.clone();
+        let external_documents_ids = self.external_documents_ids(&txn)?;
+        let deleted = deletions
+            .iter()
+            .map(|ids| {
+                // We ignore unexisting document ids
+                ids.iter()
+                    .filter_map(|id| external_documents_ids.get(id))
+                    .filter(|docid| soft_deleted.insert(*docid))
+                    .count() as u64
+            })
+            .collect();
+        drop(external_documents_ids);
 
-        let deleted = builder.execute()?;
+        let newly_deleted = &soft_deleted - &previously_soft_deleted;
+        let deleted_sequences =
+            self.soft_deleted_sequences(&txn)? | sequences::sequences(self, &txn, &newly_deleted)?;
+        self.put_soft_deleted_documents(&mut txn, &soft_deleted)?;
+        self.put_soft_deleted_sequences(&mut txn, &deleted_sequences)?;
+        metadata::delete(self.document_metadata, &mut txn, &newly_deleted)?;
 
         txn.commit()?;
 
         Ok(deleted)
     }
 
+    /// Physically removes the soft deleted documents from the index. Returns the number of
+    /// documents that were purged.
+    pub fn compact(&self) -> Result<u64> {
+        let mut txn = self.write_txn()?;
+        let purged = self.purge_soft_deleted_documents(&mut txn)?;
+        txn.commit()?;
+
+        info!("compaction done: {} documents purged", purged);
+
+        Ok(purged)
+    }
+
+    fn purge_soft_deleted_documents<'a, 'b>(
+        &'a self,
+        txn: &mut milli::heed::RwTxn<'a, 'b>,
+    ) -> Result<u64> {
+        let soft_deleted = self.soft_deleted_documents(txn)?;
+        if soft_deleted.is_empty() {
+            return Ok(0);
+        }
+
+        let mut builder = milli::update::DeleteDocuments::new(txn, self)?;
+        builder.delete_documents(&soft_deleted);
+        let deleted = builder.execute()?;
+        clear_soft_deleted_documents(self, txn)?;
+
+        Ok(deleted.deleted_documents)
+    }
+
+    /// Purges the soft deleted documents whose external ids are `ids`, before they are added back.
+    fn purge_added_back<'a, 'b>(
+        &'a self,
+        txn: &mut milli::heed::RwTxn<'a, 'b>,
+        ids: impl IntoIterator<Item = &String>,
+    ) -> Result<()> {
+        let soft_deleted = self.soft_deleted_documents(txn)?;
+        if soft_deleted.is_empty() {
+            return Ok(());
+        }
+
+        let external_documents_ids = self.external_documents_ids(txn)?;
+        let added_back: RoaringBitmap = ids
+            .into_iter()
+            .filter_map(|id| external_documents_ids.get(id))
+            .filter(|docid| soft_deleted.contains(*docid))
+            .collect();
+        drop(external_documents_ids);
+        if added_back.is_empty() {
+            return Ok(());
+        }
+
+        let deleted_sequences =
+            self.soft_deleted_sequences(txn)? - sequences::sequences(self, txn, &added_back)?;
+        let mut builder = milli::update::DeleteDocuments::new(txn, self)?;
+        builder.delete_documents(&added_back);
+        builder.execute()?;
+        self.put_soft_deleted_documents(txn, &(soft_deleted - added_back))?;
+        self.put_soft_deleted_sequences(txn, &deleted_sequences)?;
+
+        Ok(())
+    }
+
     pub fn clear_documents(&self) -> Result<()> {
         let mut txn = self.write_txn()?;
         milli::update::ClearDocuments::new(&mut txn, self).execute()?;
+        clear_soft_deleted_documents(self, &mut txn)?;
+        metadata::clear(self.document_metadata, &mut txn)?;
         txn.commit()?;
 
         Ok(())
@@ -263,8 +735,94 @@ impl Index {
         method: IndexDocumentsMethod,
         primary_key: Option<String>,
         file_store: UpdateFileStore,
-        contents: impl IntoIterator<Item = Uuid>,
-    ) -> Result<DocumentAdditionResult> {
+        contents: impl IntoIterator<Item = DocumentsPayload>,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
+    ) -> Result<DocumentsAdditionResult> {
+        self.add_documents(method, primary_key, file_store, contents, progress, false)
+    }
+
+    /// Adds the payloads like `update_documents`, but adds none of them when a payload is
+    /// refused: nothing is indexed, and the result tells which payloads were refused.
+    pub fn update_documents_atomically(
+        &self,
+        method: IndexDocumentsMethod,
+        primary_key: Option<String>,
+        file_store: UpdateFileStore,
+        contents: impl IntoIterator<Item = DocumentsPayload>,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
+    ) -> Result<DocumentsAdditionResult> {
+        self.add_documents(method, primary_key, file_store, contents, progress, true)
+    }
+
+    fn add_documents(
+        &self,
+        method: IndexDocumentsMethod,
+        primary_key: Option<String>,
+        file_store: UpdateFileStore,
+        contents: impl IntoIterator<Item = DocumentsPayload>,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
+        atomic: bool,
+    ) -> Result<DocumentsAdditionResult> {
+        // a corrupted update file is refused before anything is read from it, and the other
+        // payloads are added without it.
+        let mut corrupted = Vec::new();
+        let mut verified = Vec::new();
+        for payload in contents {
+            match check_digest(&file_store, &payload)? {
+                Some(error) => corrupted.push(Some(error)),
+                None => {
+                    corrupted.push(None);
+                    verified.push(payload);
+                }
+            }
+        }
+
+        if corrupted.iter().all(Option::is_none) {
+            return self.add_verified_documents(
+                method,
+                primary_key,
+                file_store,
+                verified,
+                progress,
+                atomic,
+            );
+        }
+
+        let mut result = if atomic || verified.is_empty() {
+            DocumentsAdditionResult {
+                indexed_documents: 0,
+                payloads: verified
+                    .iter()
+                    .map(|_| Ok(PayloadResult::default()))
+                    .collect(),
+            }
+        } else {
+            self.add_verified_documents(method, primary_key, file_store, verified, progress, false)?
+        };
+
+        let mut verified_payloads = result.payloads.into_iter();
+        result.payloads = corrupted
+            .into_iter()
+            .map(|error| match error {
+                Some(error) => Err(error),
+                None => verified_payloads
+                    .next()
+                    .expect("a result for each verified payload"),
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    fn add_verified_documents(
+        &self,
+        method: IndexDocumentsMethod,
+        primary_key: Option<String>,
+        file_store: UpdateFileStore,
+        contents: Vec<DocumentsPayload>,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
+        atomic: bool,
+    ) -> Result<DocumentsAdditionResult> {
         trace!("performing document addition");
         let mut txn = self.write_txn()?;
 
@@ -274,12 +832,135 @@ impl Index {
             }
         }
 
+        // milli infers the primary key from the first payload when the index doesn't have one.
+        let primary_key = document_formats::addition_primary_key(
+            self.primary_key(&txn)?,
+            None,
+            || -> Result<_> {
+                match contents.first() {
+                    Some(payload) => Ok(Some(file_store.get_update(payload.content_uuid)?)),
+                    None => Ok(None),
+                }
+            },
+        )?;
+
+        let mut diffs = match primary_key {
+            Some(ref primary_key) if contents.iter().any(|payload| payload.diff.is_some()) => {
+                self.documents_diffs(&txn, &file_store, &contents, primary_key)?
+            }
+            _ => Vec::new(),
+        };
+        diffs.resize(contents.len(), None);
+
+        let document_transforms = self.document_transforms(&txn)?;
+
+        derived::prepare(
+            self,
+            self.document_metadata,
+            &mut txn,
+            self.indexer_config.as_ref(),
+        )?;
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut derivation = match primary_key {
+            Some(ref primary_key) => Some(AdditionDerivation::new(
+                self,
+                self.document_metadata,
+                &txn,
+                method,
+                primary_key.clone(),
+                now,
+            )?),
+            None => None,
+        };
+
+        let over_soft_limits: Vec<_> = contents
+            .iter()
+            .map(|payload| payload.over_soft_limits)
+            .collect();
+
+        let mut payloads = Vec::with_capacity(contents.len());
+        let mut files = Vec::with_capacity(contents.len());
+        for (payload, diff) in contents.into_iter().zip(diffs) {
+            let (mut content_file, transforms) = transforms::transform_file(
+                file_store.get_update(payload.content_uuid)?,
+                &document_transforms,
+            )?;
+
+            let (primary_key, derivation) = match (&primary_key, derivation.as_mut()) {
+                (Some(primary_key), Some(derivation)) => (primary_key, derivation),
+                // without a primary key, milli refuses the documents anyway.
+                _ => {
+                    files.push(content_file);
+                    payloads.push(Ok(PayloadResult {
+                        transforms,
+                        ..Default::default()
+                    }));
+                    continue;
+                }
+            };
+
+            let ids = document_formats::count_ids(&mut content_file, primary_key)?;
+            content_file.seek(SeekFrom::Start(0))?;
+            let duplicates: BTreeMap<_, _> = ids
+                .iter()
+                .filter(|(_, count)| **count > 1)
+                .map(|(id, count)| (id.clone(), *count))
+                .collect();
+
+            if !duplicates.is_empty() && payload.reject_duplicates {
+                let ids = duplicates.into_keys().collect();
+                payloads.push(Err(IndexError::DuplicateDocuments(ids)));
+                continue;
+            }
+
+            self.purge_added_back(&mut txn, ids.keys())?;
+
+            // the documents are derived once they are deduplicated, as milli will index them.
+            if duplicates.is_empty() {
+                files.push(derivation.derive_file(self, &txn, content_file)?);
+                payloads.push(Ok(PayloadResult {
+                    duplicate_documents: 0,
+                    diff,
+                    transforms,
+                }));
+            } else {
+                let mut deduplicated = tempfile::tempfile()?;
+                document_formats::deduplicate(
+                    content_file,
+                    &mut deduplicated,
+                    primary_key,
+                    &duplicates,
+                    method,
+                )?;
+                deduplicated.seek(SeekFrom::Start(0))?;
+                files.push(derivation.derive_file(self, &txn, deduplicated)?);
+
+                let duplicate_documents = duplicates.values().map(|count| count - 1).sum::<usize>();
+                payloads.push(Ok(PayloadResult {
+                    duplicate_documents: duplicate_documents as u64,
+                    diff,
+                    transforms,
+                }));
+            }
+        }
+
+        if atomic && payloads.iter().any(Result::is_err) {
+            // the transaction is aborted when dropped.
+            return Ok(DocumentsAdditionResult {
+                indexed_documents: 0,
+                payloads,
+            });
+        }
+
         let config = IndexDocumentsConfig {
             update_method: method,
             ..Default::default()
         };
 
-        let indexing_callback = |indexing_step| debug!("update: {:?}", indexing_step);
+        let indexing_callback = |indexing_step: UpdateIndexingStep| {
+            debug!("update: {:?}", indexing_step);
+            progress(indexing_step);
+        };
         let mut builder = milli::update::IndexDocuments::new(
             &mut txn,
             self,
@@ -287,25 +968,115 @@ impl Index {
             config,
             indexing_callback,
         )?;
+        for file in files {
+            builder.add_documents(DocumentBatchReader::from_reader(file)?)?;
+        }
+        let addition = builder.execute()?;
 
-        for content_uuid in contents.into_iter() {
-            let content_file = file_store.get_update(content_uuid)?;
-            let reader = DocumentBatchReader::from_reader(content_file)?;
-            builder.add_documents(reader)?;
+        let over_soft_limits: u64 = payloads
+            .iter()
+            .zip(over_soft_limits)
+            .filter(|(result, _)| result.is_ok())
+            .map(|(_, count)| count)
+            .sum();
+        if over_soft_limits > 0 {
+            let total = self.documents_over_soft_limits(&txn)? + over_soft_limits;
+            self.put_documents_over_soft_limits(&mut txn, total)?;
         }
 
-        let addition = builder.execute()?;
+        if let Some(derivation) = derivation {
+            derivation.finish(self, &mut txn)?;
+        }
 
         txn.commit()?;
 
         info!("document addition done: {:?}", addition);
 
-        Ok(addition)
+        Ok(DocumentsAdditionResult {
+            indexed_documents: addition.indexed_documents,
+            payloads,
+        })
+    }
+
+    /// Computes the difference between the payloads and the documents of the index, for the
+    /// payloads up to the last one asking for it. A document added by an earlier payload of the
+    /// same addition counts as existing.
+    fn documents_diffs(
+        &self,
+        txn: &milli::heed::RoTxn,
+        file_store: &UpdateFileStore,
+        contents: &[DocumentsPayload],
+        primary_key: &str,
+    ) -> Result<Vec<Option<DocumentsDiff>>> {
+        let last = match contents.iter().rposition(|payload| payload.diff.is_some()) {
+            Some(last) => last,
+            None => return Ok(Vec::new()),
+        };
+
+        let existing = self.external_documents_ids(txn)?;
+        let soft_deleted = self.soft_deleted_documents(txn)?;
+        let exists = |id: &str| {
+            existing
+                .get(id)
+                .map_or(false, |docid| !soft_deleted.contains(docid))
+        };
+        let mut added = BTreeSet::new();
+        let mut diffs = Vec::with_capacity(last + 1);
+        for payload in &contents[..=last] {
+            let mut content_file = file_store.get_update(payload.content_uuid)?;
+            let ids = document_formats::count_ids(&mut content_file, primary_key)?;
+            // the payload is refused, it adds nothing.
+            if payload.reject_duplicates && ids.values().any(|count| *count > 1) {
+                diffs.push(None);
+                continue;
+            }
+
+            let mut diff = DocumentsDiff::default();
+            let mut replaced_ids = Vec::new();
+            for id in ids.into_keys() {
+                if exists(&id) || added.contains(&id) {
+                    diff.updated += 1;
+                    if payload.diff == Some(DocumentsDiffMode::Full) {
+                        replaced_ids.push(id.clone());
+                    }
+                } else {
+                    diff.created += 1;
+                }
+                added.insert(id);
+            }
+            if payload.diff == Some(DocumentsDiffMode::Full) {
+                replaced_ids.sort_unstable();
+                diff.replaced_ids = Some(replaced_ids);
+            }
+            diffs.push(payload.diff.map(|_| diff));
+        }
+
+        Ok(diffs)
     }
 
     pub fn update_settings(&self, settings: &Settings<Checked>) -> Result<()> {
+        if let Setting::Set(SearchableAttributes::Weighted(ref weights)) =
+            settings.searchable_attributes
+        {
+            check_searchable_weights(weights)?;
+        }
+        if let Setting::Set(ref transforms) = settings.document_transforms {
+            validate_transforms(transforms)?;
+        }
+        if let Setting::Set(ref selection) = settings.custom_segmenters {
+            CustomSegmenters::resolve(selection)?;
+        }
+
         // We must use the write transaction of the update here.
         let mut txn = self.write_txn()?;
+        derived::prepare(
+            self,
+            self.document_metadata,
+            &mut txn,
+            self.indexer_config.as_ref(),
+        )?;
+        let derivation = Derivation::read(self, &txn)?;
+
         let mut builder =
             milli::update::Settings::new(&mut txn, self, self.indexer_config.as_ref());
 
@@ -313,18 +1084,58 @@ impl Index {
 
         builder.execute(|indexing_step| debug!("update: {:?}", indexing_step))?;
 
+        apply_meilisearch_settings(self, &mut txn, settings)?;
+        // the recorded metadata are useless once the setting is disabled.
+        if matches!(
+            settings.document_metadata,
+            Setting::Set(false) | Setting::Reset
+        ) {
+            metadata::clear(self.document_metadata, &mut txn)?;
+        }
+        if Derivation::read(self, &txn)? != derivation {
+            derived::rederive(
+                self,
+                self.document_metadata,
+                &mut txn,
+                self.indexer_config.as_ref(),
+            )?;
+        }
         txn.commit()?;
 
         Ok(())
     }
 }
 
+/// Returns `fields` along with the derived field, which stays filterable and sortable.
+fn with_derived_field<'a>(fields: impl IntoIterator<Item = &'a String>) -> HashSet<String> {
+    fields
+        .into_iter()
+        .cloned()
+        .chain(std::iter::once(DERIVED_FIELD.to_string()))
+        .collect()
+}
+
+/// Returns `fields` along with the field of the segmented words, which stays searchable, after
+/// the attributes, and on whose words typos are never allowed.
+fn with_segments_field<'a, B>(fields: impl IntoIterator<Item = &'a String>) -> B
+where
+    B: FromIterator<String>,
+{
+    fields
+        .into_iter()
+        .cloned()
+        .chain(std::iter::once(SEGMENTS_FIELD.to_string()))
+        .collect()
+}
+
 pub fn apply_settings_to_builder(
     settings: &Settings<Checked>,
     builder: &mut milli::update::Settings,
 ) {
     match settings.searchable_attributes {
-        Setting::Set(ref names) => builder.set_searchable_fields(names.clone()),
+        Setting::Set(ref attributes) => {
+            builder.set_searchable_fields(with_segments_field(&attributes.names()))
+        }
         Setting::Reset => builder.reset_searchable_fields(),
         Setting::NotSet => (),
     }
@@ -336,16 +1147,14 @@ pub fn apply_settings_to_builder(
     }
 
     match settings.filterable_attributes {
-        Setting::Set(ref facets) => {
-            builder.set_filterable_fields(facets.clone().into_iter().collect())
-        }
-        Setting::Reset => builder.reset_filterable_fields(),
+        Setting::Set(ref facets) => builder.set_filterable_fields(with_derived_field(facets)),
+        Setting::Reset => builder.set_filterable_fields(with_derived_field(None)),
         Setting::NotSet => (),
     }
 
     match settings.sortable_attributes {
-        Setting::Set(ref fields) => builder.set_sortable_fields(fields.iter().cloned().collect()),
-        Setting::Reset => builder.reset_sortable_fields(),
+        Setting::Set(ref fields) => builder.set_sortable_fields(with_derived_field(fields)),
+        Setting::Reset => builder.set_sortable_fields(with_derived_field(None)),
         Setting::NotSet => (),
     }
 
@@ -355,12 +1164,6 @@ pub fn apply_settings_to_builder(
         Setting::NotSet => (),
     }
 
-    match settings.stop_words {
-        Setting::Set(ref stop_words) => builder.set_stop_words(stop_words.clone()),
-        Setting::Reset => builder.reset_stop_words(),
-        Setting::NotSet => (),
-    }
-
     match settings.synonyms {
         Setting::Set(ref synonyms) => builder.set_synonyms(synonyms.clone().into_iter().collect()),
         Setting::Reset => builder.reset_synonyms(),
@@ -410,10 +1213,8 @@ pub fn apply_settings_to_builder(
             }
 
             match value.disable_on_attributes {
-                Setting::Set(ref words) => {
-                    builder.set_exact_attributes(words.iter().cloned().collect())
-                }
-                Setting::Reset => builder.reset_exact_attributes(),
+                Setting::Set(ref words) => builder.set_exact_attributes(with_segments_field(words)),
+                Setting::Reset => builder.set_exact_attributes(with_segments_field(None)),
                 Setting::NotSet => (),
             }
         }
@@ -423,7 +1224,7 @@ pub fn apply_settings_to_builder(
             builder.reset_min_word_len_one_typo();
             builder.reset_min_word_len_two_typos();
             builder.reset_exact_words();
-            builder.reset_exact_attributes();
+            builder.set_exact_attributes(with_segments_field(None));
         }
         Setting::NotSet => (),
     }
@@ -448,7 +1249,9 @@ pub(crate) mod test {
         // test no changes
         let settings = Settings {
             displayed_attributes: Setting::Set(vec![String::from("hello")]),
-            searchable_attributes: Setting::Set(vec![String::from("hello")]),
+            searchable_attributes: Setting::Set(SearchableAttributes::List(vec![String::from(
+                "hello",
+            )])),
             filterable_attributes: Setting::NotSet,
             sortable_attributes: Setting::NotSet,
             ranking_rules: Setting::NotSet,
@@ -456,6 +1259,18 @@ pub(crate) mod test {
             synonyms: Setting::NotSet,
             distinct_attribute: Setting::NotSet,
             typo_tolerance: Setting::NotSet,
+            warmup_queries: Setting::NotSet,
+            max_word_derivations: Setting::NotSet,
+            max_prefix_expansion: Setting::NotSet,
+            max_values_per_facet: Setting::NotSet,
+            search_defaults: Setting::NotSet,
+            document_metadata: Setting::NotSet,
+            nested_object_behavior: Setting::NotSet,
+            document_transforms: Setting::NotSet,
+            custom_segmenters: Setting::NotSet,
+            prefix_search: Setting::NotSet,
+            disable_prefix_on_attributes: Setting::NotSet,
+            facet_normalization: Setting::NotSet,
             _kind: PhantomData::<Unchecked>,
         };
 
@@ -470,7 +1285,10 @@ pub(crate) mod test {
         // test no changes
         let settings = Settings {
             displayed_attributes: Setting::Set(vec![String::from("*")]),
-            searchable_attributes: Setting::Set(vec![String::from("hello"), String::from("*")]),
+            searchable_attributes: Setting::Set(SearchableAttributes::List(vec![
+                String::from("hello"),
+                String::from("*"),
+            ])),
             filterable_attributes: Setting::NotSet,
             sortable_attributes: Setting::NotSet,
             ranking_rules: Setting::NotSet,
@@ -478,6 +1296,18 @@ pub(crate) mod test {
             synonyms: Setting::NotSet,
             distinct_attribute: Setting::NotSet,
             typo_tolerance: Setting::NotSet,
+            warmup_queries: Setting::NotSet,
+            max_word_derivations: Setting::NotSet,
+            max_prefix_expansion: Setting::NotSet,
+            max_values_per_facet: Setting::NotSet,
+            search_defaults: Setting::NotSet,
+            document_metadata: Setting::NotSet,
+            nested_object_behavior: Setting::NotSet,
+            document_transforms: Setting::NotSet,
+            custom_segmenters: Setting::NotSet,
+            prefix_search: Setting::NotSet,
+            disable_prefix_on_attributes: Setting::NotSet,
+            facet_normalization: Setting::NotSet,
             _kind: PhantomData::<Unchecked>,
         };
 
@@ -485,4 +1315,399 @@ pub(crate) mod test {
         assert_eq!(checked.displayed_attributes, Setting::Reset);
         assert_eq!(checked.searchable_attributes, Setting::Reset);
     }
+
+    #[test]
+    fn test_setting_check_path_wildcards() {
+        let settings = Settings {
+            searchable_attributes: Setting::Set(SearchableAttributes::List(vec![
+                String::from("title"),
+                String::from("address.*"),
+                String::from("address"),
+            ])),
+            filterable_attributes: Setting::Set(
+                ["address.*", "genre"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            sortable_attributes: Setting::Set(
+                ["address.zip"].iter().map(|s| s.to_string()).collect(),
+            ),
+            ..Settings::default()
+        };
+
+        let checked = settings.check();
+        assert_eq!(
+            checked.searchable_attributes,
+            Setting::Set(SearchableAttributes::List(vec![
+                String::from("title"),
+                String::from("address")
+            ]))
+        );
+        assert_eq!(
+            checked.filterable_attributes,
+            Setting::Set(["address", "genre"].iter().map(|s| s.to_string()).collect())
+        );
+        assert_eq!(
+            checked.sortable_attributes,
+            Setting::Set(["address.zip"].iter().map(|s| s.to_string()).collect())
+        );
+    }
+
+    #[test]
+    fn test_setting_check_searchable_weights() {
+        let settings: Settings<Unchecked> = serde_json::from_value(serde_json::json!({
+            "searchableAttributes": { "description": 1, "title": 3, "address.*": 2, "tags": 1 },
+        }))
+        .unwrap();
+        assert!(settings.validate().is_ok());
+
+        let checked = settings.check();
+        let attributes = match checked.searchable_attributes {
+            Setting::Set(ref attributes) => attributes,
+            _ => panic!("the searchable attributes are set"),
+        };
+        assert_eq!(
+            attributes.names(),
+            vec!["title", "address", "description", "tags"]
+        );
+        // the form that was set is kept.
+        assert_eq!(
+            serde_json::to_value(&checked).unwrap()["searchableAttributes"],
+            serde_json::json!({ "address": 2, "description": 1, "tags": 1, "title": 3 })
+        );
+
+        let settings: Settings<Unchecked> = serde_json::from_value(serde_json::json!({
+            "searchableAttributes": { "title": 0 },
+        }))
+        .unwrap();
+        assert!(matches!(
+            settings.validate(),
+            Err(IndexError::InvalidSearchableWeight(attribute)) if attribute == "title"
+        ));
+    }
+
+    #[test]
+    fn test_truncated_update_file_is_refused() {
+        use std::sync::Arc;
+
+        use milli::update::IndexerConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let index = Index::open(
+            tmp.path().join("index"),
+            4096 * 1000,
+            crate::index::DEFAULT_MAX_READERS,
+            Uuid::new_v4(),
+            Arc::new(IndexerConfig::default()),
+        )
+        .unwrap();
+        let file_store = UpdateFileStore::new(tmp.path()).unwrap();
+
+        let mut payloads = Vec::new();
+        for documents in ["{\"id\": 1}\n{\"id\": 2}\n", "{\"id\": 3}\n"] {
+            let (content_uuid, mut update_file) = file_store.new_update().unwrap();
+            document_formats::read_ndjson(documents.as_bytes(), &mut *update_file).unwrap();
+            let digest = update_file.persist().unwrap();
+            payloads.push(DocumentsPayload {
+                content_uuid,
+                reject_duplicates: false,
+                diff: None,
+                digest: Some(digest),
+                over_soft_limits: 0,
+            });
+        }
+
+        // the first update file is chopped between the registration and the processing of the
+        // task, like after a power loss.
+        let path = tmp
+            .path()
+            .join("updates/updates_files")
+            .join(payloads[0].content_uuid.to_string());
+        let file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+        let expected_size = payloads[0].digest.as_ref().unwrap().size;
+        file.set_len(expected_size / 2).unwrap();
+
+        let result = index
+            .update_documents(
+                IndexDocumentsMethod::ReplaceDocuments,
+                Some("id".to_string()),
+                file_store.clone(),
+                payloads.clone(),
+                |_| (),
+            )
+            .unwrap();
+        assert_eq!(result.indexed_documents, 1);
+        match result.payloads[0] {
+            Err(IndexError::CorruptedPayload {
+                expected_size: expected,
+                size,
+                ..
+            }) => {
+                assert_eq!(expected, expected_size);
+                assert_eq!(size, expected_size / 2);
+            }
+            ref other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(result.payloads[1].is_ok());
+
+        // an atomic addition adds none of the payloads.
+        let result = index
+            .update_documents_atomically(
+                IndexDocumentsMethod::ReplaceDocuments,
+                Some("id".to_string()),
+                file_store,
+                payloads,
+                |_| (),
+            )
+            .unwrap();
+        assert_eq!(result.indexed_documents, 0);
+        assert!(result.payloads[0].is_err());
+        assert!(result.payloads[1].is_ok());
+    }
+
+    #[test]
+    fn test_custom_segmenters_round_trip() {
+        use std::sync::Arc;
+
+        use milli::update::IndexerConfig;
+
+        use crate::index::SearchQuery;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let open = || {
+            Index::open(
+                tmp.path().join("index"),
+                4096 * 1000,
+                crate::index::DEFAULT_MAX_READERS,
+                Uuid::new_v4(),
+                Arc::new(IndexerConfig::default()),
+            )
+        };
+        let index = open().unwrap();
+        let file_store = UpdateFileStore::new(tmp.path()).unwrap();
+
+        let settings: Settings<Unchecked> = serde_json::from_value(serde_json::json!({
+            "customSegmenters": { "tha": "thai-dictionary" },
+        }))
+        .unwrap();
+        settings.validate().unwrap();
+        index.update_settings(&settings.check()).unwrap();
+
+        let documents = [
+            r#"{"id": 1, "title": "ร้านอาหารไทย", "city": "Bangkok"}"#,
+            r#"{"id": 2, "title": "กาแฟไทย", "city": "Chiang Mai"}"#,
+            r#"{"id": 3, "title": "ร้านอาหาร", "city": "Chiang Mai"}"#,
+        ]
+        .join("\n");
+        let (content_uuid, mut update_file) = file_store.new_update().unwrap();
+        document_formats::read_ndjson(documents.as_bytes(), &mut *update_file).unwrap();
+        update_file.persist().unwrap();
+        let payload = DocumentsPayload {
+            content_uuid,
+            reject_duplicates: false,
+            diff: None,
+            digest: None,
+            over_soft_limits: 0,
+        };
+        index
+            .update_documents(
+                IndexDocumentsMethod::ReplaceDocuments,
+                Some("id".to_string()),
+                file_store,
+                vec![payload],
+                |_| (),
+            )
+            .unwrap();
+
+        // the ids of the hits, in the order they are ranked.
+        let search = |q: &str| -> Vec<u64> {
+            let query = SearchQuery {
+                q: Some(q.to_string()),
+                ..SearchQuery::default()
+            };
+            index
+                .perform_search(query)
+                .unwrap()
+                .hits
+                .iter()
+                .map(|hit| hit.document["id"].as_u64().unwrap())
+                .collect()
+        };
+        // the words of the queries are segmented like the ones of the documents.
+        assert_eq!(search("อาหารไทย"), Vec::<u64>::new());
+        // the documents matching all the words are ranked first.
+        assert_eq!(search("ร้านอาหารไทย"), vec![1, 3]);
+        // the last word is a prefix.
+        let mut ids = search("ร้าน");
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 3]);
+        // the words are searched along with the rest of the query.
+        assert_eq!(search("ร้านอาหาร chiang"), vec![3, 1]);
+        assert_eq!(
+            index
+                .perform_search(SearchQuery {
+                    q: Some("ร้านอาหาร chiang".to_string()),
+                    ..SearchQuery::default()
+                })
+                .unwrap()
+                .query,
+            "ร้านอาหาร chiang"
+        );
+
+        index.delete_documents(&[vec!["3".to_string()]]).unwrap();
+        assert_eq!(search("ร้านอาหาร"), vec![1]);
+
+        // an index segmented by a segmenter that is not compiled in can't be opened.
+        let mut txn = index.write_txn().unwrap();
+        let ids = std::collections::BTreeMap::from([(
+            "tha".to_string(),
+            crate::segmenters::SegmenterId {
+                name: "icu".to_string(),
+                version: 2,
+            },
+        )]);
+        index
+            .main
+            .put::<_, milli::heed::types::Str, milli::heed::types::SerdeJson<_>>(
+                &mut txn,
+                "meilisearch-custom-segmenters",
+                &ids,
+            )
+            .unwrap();
+        txn.commit().unwrap();
+        drop(index);
+        let error = open().unwrap_err();
+        assert!(matches!(
+            error,
+            IndexError::Segmenter(crate::segmenters::SegmenterError::MissingSegmenter { .. })
+        ));
+    }
+
+    #[test]
+    fn test_soft_deleted_documents_are_excluded() {
+        use std::sync::Arc;
+
+        use milli::update::IndexerConfig;
+        use serde_json::json;
+
+        use crate::index::{AggregateQuery, SearchQuery};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let index = Index::open(
+            tmp.path().join("index"),
+            4096 * 1000,
+            crate::index::DEFAULT_MAX_READERS,
+            Uuid::new_v4(),
+            Arc::new(IndexerConfig::default()),
+        )
+        .unwrap();
+        let file_store = UpdateFileStore::new(tmp.path()).unwrap();
+
+        let settings: Settings<Unchecked> = serde_json::from_value(json!({
+            "filterableAttributes": ["genre"],
+        }))
+        .unwrap();
+        index.update_settings(&settings.check()).unwrap();
+
+        let add = |documents: &str| {
+            let (content_uuid, mut update_file) = file_store.new_update().unwrap();
+            document_formats::read_ndjson(documents.as_bytes(), &mut *update_file).unwrap();
+            update_file.persist().unwrap();
+            let payload = DocumentsPayload {
+                content_uuid,
+                reject_duplicates: false,
+                diff: None,
+                digest: None,
+                over_soft_limits: 0,
+            };
+            index
+                .update_documents(
+                    IndexDocumentsMethod::ReplaceDocuments,
+                    Some("id".to_string()),
+                    file_store.clone(),
+                    vec![payload],
+                    |_| (),
+                )
+                .unwrap();
+        };
+        add(&[
+            r#"{"id": 1, "title": "dune", "genre": "scifi"}"#,
+            r#"{"id": 2, "title": "dune messiah", "genre": "scifi"}"#,
+            r#"{"id": 3, "title": "emma", "genre": "romance"}"#,
+        ]
+        .join("\n"));
+
+        index
+            .delete_documents(&[vec!["2".to_string(), "3".to_string()]])
+            .unwrap();
+        // a deleted document is added back, with another genre.
+        add(r#"{"id": 3, "title": "emma", "genre": "classic"}"#);
+
+        let check = || {
+            let result = index
+                .perform_search(SearchQuery {
+                    facets: Some(vec!["genre".to_string()]),
+                    ..SearchQuery::default()
+                })
+                .unwrap();
+            let mut ids: Vec<_> = result
+                .hits
+                .iter()
+                .map(|hit| hit.document["id"].as_u64().unwrap())
+                .collect();
+            ids.sort_unstable();
+            assert_eq!(ids, vec![1, 3]);
+            assert_eq!(
+                result.facet_distribution.unwrap()["genre"],
+                BTreeMap::from([("classic".to_string(), 1), ("scifi".to_string(), 1)])
+            );
+
+            let page = index
+                .retrieve_documents(0, 10, None, None::<Vec<String>>)
+                .unwrap();
+            assert_eq!(page.total, 2);
+            assert_eq!(page.documents.len(), 2);
+            assert!(matches!(
+                index.retrieve_document("2".to_string(), None::<Vec<String>>, false),
+                Err(IndexError::DocumentNotFound(_))
+            ));
+            let document = index
+                .retrieve_document("3".to_string(), None::<Vec<String>>, false)
+                .unwrap();
+            assert_eq!(document["genre"], json!("classic"));
+
+            let aggregate = index
+                .aggregate(AggregateQuery {
+                    filter: None,
+                    group_by: Some("genre".to_string()),
+                    metrics: vec!["count".to_string()],
+                })
+                .unwrap();
+            let groups: Vec<_> = aggregate
+                .groups
+                .iter()
+                .map(|row| (row.group.clone().unwrap(), row.metrics["count"].clone()))
+                .collect();
+            assert_eq!(
+                groups,
+                vec![(json!("classic"), json!(1)), (json!("scifi"), json!(1))]
+            );
+        };
+
+        // the addition only purged the soft deleted document it added back.
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.soft_deleted_documents(&rtxn).unwrap().len(), 1);
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 3);
+        drop(rtxn);
+        check();
+
+        // the compaction keeps the external id of the document added back.
+        assert_eq!(index.compact().unwrap(), 1);
+        let rtxn = index.read_txn().unwrap();
+        assert!(index.soft_deleted_documents(&rtxn).unwrap().is_empty());
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 2);
+        drop(rtxn);
+        check();
+    }
 }
@@ -0,0 +1,401 @@
+//! Dry run of a document addition.
+//!
+//! A payload is validated with the checks of a document addition, without registering a task: it
+//! is converted like the payload of an addition, given the primary key the addition would use, and
+//! transformed by the document transforms of its index. Where an addition stops at the first
+//! document it refuses, the validation goes through all of them, and reports each document with an
+//! error or a warning, along with the fields of the documents and the ids they share. Nothing is
+//! written to the index.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use meilisearch_error::ResponseError;
+use milli::documents::DocumentBatchReader;
+use milli::UserError;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::error::{IndexError, Result};
+use super::index::Index;
+use super::transforms;
+use crate::document_formats::{
+    self, DocumentFields, DocumentFormatError, DocumentOverLimit, FieldType, PayloadLimits,
+};
+
+/// The maximum number of documents listed in a validation.
+pub const MAX_REPORTED_DOCUMENTS: usize = 100;
+/// The maximum number of duplicate ids listed in a validation.
+pub const MAX_REPORTED_DUPLICATES: usize = 100;
+
+const GEO_FIELD: &str = "_geo";
+
+/// The errors and the warnings of a document.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentReport {
+    /// The position of the document in the payload, from 0.
+    pub document: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Why the addition would fail on the document.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ResponseError>,
+    /// What of the document wouldn't be indexed as expected.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl DocumentReport {
+    fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldStats {
+    /// The number of documents with the field.
+    pub documents: usize,
+    /// The types of the values of the field.
+    pub types: BTreeSet<FieldType>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentsValidation {
+    /// Whether the addition of the payload would succeed.
+    pub valid: bool,
+    pub primary_key: Option<String>,
+    pub documents_count: usize,
+    /// Why the addition would fail whatever the documents, like a primary key that can't be
+    /// inferred.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ResponseError>,
+    pub documents_with_errors: usize,
+    pub documents_with_warnings: usize,
+    /// The documents with errors or warnings, in order, at most `MAX_REPORTED_DOCUMENTS` of them.
+    pub documents: Vec<DocumentReport>,
+    /// Whether some documents were left out of `documents`.
+    pub truncated: bool,
+    /// The union of the fields of the documents, the nested ones included.
+    pub fields: BTreeMap<String, FieldStats>,
+    /// The ids shared by several documents, with their number of occurrences, at most
+    /// `MAX_REPORTED_DUPLICATES` of them. They are deduplicated by the addition, unless it rejects
+    /// the duplicates.
+    pub duplicate_ids: BTreeMap<String, usize>,
+}
+
+impl DocumentsValidation {
+    fn push(&mut self, report: DocumentReport) {
+        if report.is_empty() {
+            return;
+        }
+        if !report.errors.is_empty() {
+            self.documents_with_errors += 1;
+        }
+        if !report.warnings.is_empty() {
+            self.documents_with_warnings += 1;
+        }
+        if self.documents.len() < MAX_REPORTED_DOCUMENTS {
+            self.documents.push(report);
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+impl Index {
+    /// Validates the converted payload `payload` as if its documents were added to this index,
+    /// with `primary_key` if the index doesn't have one yet.
+    pub fn validate_documents(
+        &self,
+        mut payload: File,
+        primary_key: Option<String>,
+        limits: &PayloadLimits,
+        reject_duplicates: bool,
+    ) -> Result<DocumentsValidation> {
+        let rtxn = self.read_txn()?;
+        let first_payload = &mut payload;
+        let primary_key = document_formats::addition_primary_key(
+            self.primary_key(&rtxn)?,
+            primary_key,
+            || -> Result<_> { Ok(Some(first_payload)) },
+        )?;
+        payload.seek(SeekFrom::Start(0))?;
+
+        let (payload, _) = transforms::transform_file(payload, &self.document_transforms(&rtxn)?)?;
+        validate_batch(payload, primary_key.as_deref(), limits, reject_duplicates)
+    }
+}
+
+/// Validates the documents of the batch read from `input`, as if they were added with
+/// `primary_key`.
+pub fn validate_batch(
+    input: impl Read + Seek,
+    primary_key: Option<&str>,
+    limits: &PayloadLimits,
+    reject_duplicates: bool,
+) -> Result<DocumentsValidation> {
+    let mut reader = DocumentBatchReader::from_reader(input)?;
+    let mut validation = DocumentsValidation {
+        primary_key: primary_key.map(String::from),
+        ..Default::default()
+    };
+    let mut ids: HashMap<String, usize> = HashMap::new();
+
+    while let Some((index, document)) = reader.next_document_with_index()? {
+        let position = validation.documents_count;
+        validation.documents_count += 1;
+
+        let mut fields = DocumentFields::default();
+        let mut object = Map::new();
+        for (field_id, value) in document.iter() {
+            if let Some(name) = index.name(field_id) {
+                fields.insert(name, value)?;
+                object.insert(name.to_string(), serde_json::from_slice(value)?);
+            }
+        }
+
+        let mut report = DocumentReport {
+            document: position,
+            id: None,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        // a document over the hard limits refuses the whole payload.
+        if let Some(limit) = limits.hard.check(&fields) {
+            let over = DocumentOverLimit {
+                document: position,
+                limit,
+            };
+            report
+                .errors
+                .push(DocumentFormatError::DocumentOverLimit(over).into());
+        } else if let Some(limit) = limits.soft.check(&fields) {
+            let over = DocumentOverLimit {
+                document: position,
+                limit,
+            };
+            report.warnings.push(format!(
+                "The document exceeds the soft limits on the documents: {}.",
+                over
+            ));
+        }
+
+        if let Some(primary_key) = primary_key {
+            let error = match object.get(primary_key) {
+                Some(value) => match document_formats::document_id(value) {
+                    Some(id) => {
+                        *ids.entry(id.clone()).or_default() += 1;
+                        report.id = Some(id);
+                        None
+                    }
+                    None => Some(UserError::InvalidDocumentId {
+                        document_id: value.clone(),
+                    }),
+                },
+                None => Some(UserError::MissingDocumentId {
+                    primary_key: primary_key.to_string(),
+                    document: object.clone(),
+                }),
+            };
+            report.errors.extend(error.map(milli_error));
+        }
+
+        if let Some(geo) = object.get(GEO_FIELD) {
+            report.warnings.extend(check_geo(geo));
+        }
+
+        for (name, types) in fields.names {
+            let stats = validation.fields.entry(name).or_default();
+            stats.documents += 1;
+            stats.types.extend(types);
+        }
+        validation.push(report);
+    }
+
+    let mut duplicates: Vec<_> = ids.into_iter().filter(|(_, count)| *count > 1).collect();
+    duplicates.sort_unstable();
+    if primary_key.is_none() {
+        validation.error = Some(milli_error(UserError::MissingPrimaryKey));
+    } else if reject_duplicates && !duplicates.is_empty() {
+        let ids = duplicates.iter().map(|(id, _)| id.clone()).collect();
+        validation.error = Some(IndexError::DuplicateDocuments(ids).into());
+    }
+    validation.duplicate_ids = duplicates
+        .into_iter()
+        .take(MAX_REPORTED_DUPLICATES)
+        .collect();
+    validation.valid = validation.error.is_none() && validation.documents_with_errors == 0;
+
+    Ok(validation)
+}
+
+fn milli_error(error: UserError) -> ResponseError {
+    IndexError::Milli(error.into()).into()
+}
+
+/// Returns why the geo point `value` is ignored by milli, if it is: the indexing only keeps the
+/// geo points with a numeric latitude and longitude.
+fn check_geo(value: &Value) -> Option<String> {
+    let coordinate = |name: &str| value.get(name).and_then(Value::as_f64);
+    match (coordinate("lat"), coordinate("lng")) {
+        (Some(lat), Some(lng)) if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng) => {
+            None
+        }
+        (Some(_), Some(_)) => Some(format!(
+            "The `{}` field is out of range: the latitude must be between -90 and 90, and the longitude between -180 and 180.",
+            GEO_FIELD
+        )),
+        _ => Some(format!(
+            "The `{}` field is ignored: it must be an object with a numeric `lat` and `lng`.",
+            GEO_FIELD
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::document_formats::{read_json, DocumentLimits};
+
+    fn batch(documents: Value) -> Cursor<Vec<u8>> {
+        let mut batch = Cursor::new(Vec::new());
+        read_json(documents.to_string().as_bytes(), &mut batch).unwrap();
+        batch.set_position(0);
+        batch
+    }
+
+    fn code(error: ResponseError) -> String {
+        let error = serde_json::to_value(error).unwrap();
+        error["code"].as_str().unwrap().to_string()
+    }
+
+    fn error_codes(report: &DocumentReport) -> Vec<String> {
+        report
+            .errors
+            .iter()
+            .map(|error| code(error.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_document_is_reported() {
+        let documents = json!([
+            { "id": 1, "title": "ok" },
+            { "title": "no id" },
+            { "id": "foo & bar" },
+            { "id": 2, "_geo": "foobar" },
+            { "id": 1, "author": { "name": "a" } },
+        ]);
+        let validation = validate_batch(
+            batch(documents),
+            Some("id"),
+            &PayloadLimits::default(),
+            false,
+        )
+        .unwrap();
+
+        assert!(!validation.valid);
+        assert_eq!(validation.error, None);
+        assert_eq!(validation.documents_count, 5);
+        assert_eq!(validation.documents_with_errors, 2);
+        assert_eq!(validation.documents_with_warnings, 1);
+
+        let reported: Vec<_> = validation.documents.iter().map(|d| d.document).collect();
+        assert_eq!(reported, vec![1, 2, 3]);
+        assert_eq!(
+            error_codes(&validation.documents[0]),
+            vec!["missing_document_id"]
+        );
+        assert_eq!(
+            error_codes(&validation.documents[1]),
+            vec!["invalid_document_id"]
+        );
+        assert_eq!(validation.documents[2].id.as_deref(), Some("2"));
+
+        assert_eq!(
+            validation.duplicate_ids.into_iter().collect::<Vec<_>>(),
+            vec![("1".to_string(), 2)]
+        );
+        assert_eq!(validation.fields["id"].documents, 4);
+        assert_eq!(
+            validation.fields["id"].types,
+            BTreeSet::from([FieldType::Number, FieldType::String])
+        );
+        assert_eq!(validation.fields["author.name"].documents, 1);
+    }
+
+    #[test]
+    fn test_payload_errors() {
+        let documents = json!([{ "title": "a" }, { "id": 1 }, { "id": 1 }]);
+
+        let validation = validate_batch(
+            batch(documents.clone()),
+            None,
+            &PayloadLimits::default(),
+            false,
+        )
+        .unwrap();
+        assert!(!validation.valid);
+        assert_eq!(
+            validation.error.map(code).as_deref(),
+            Some("primary_key_inference_failed")
+        );
+
+        let validation = validate_batch(
+            batch(documents),
+            Some("id"),
+            &PayloadLimits::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            validation.error.map(code).as_deref(),
+            Some("duplicate_documents")
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let documents = json!([
+            { "id": 1, "a": 1, "b": 2 },
+            { "id": 2, "a": 1 },
+            { "id": 3 },
+        ]);
+        let limits = PayloadLimits {
+            hard: DocumentLimits {
+                max_fields: Some(2),
+                ..Default::default()
+            },
+            soft: DocumentLimits {
+                max_fields: Some(1),
+                ..Default::default()
+            },
+        };
+        let validation = validate_batch(batch(documents), Some("id"), &limits, false).unwrap();
+        assert!(!validation.valid);
+        assert_eq!(
+            error_codes(&validation.documents[0]),
+            vec!["document_over_limit"]
+        );
+        assert!(validation.documents[0].warnings.is_empty());
+        assert_eq!(validation.documents[1].warnings.len(), 1);
+        assert_eq!(validation.documents.len(), 2);
+    }
+
+    #[test]
+    fn test_check_geo() {
+        assert_eq!(check_geo(&json!({ "lat": 45.1, "lng": -3 })), None);
+        assert!(check_geo(&json!({ "lat": 95, "lng": 0 })).is_some());
+        assert!(check_geo(&json!({ "lat": "45", "lng": 0 })).is_some());
+        assert!(check_geo(&json!("foobar")).is_some());
+    }
+}
@@ -4,11 +4,15 @@ use meilisearch_error::Code;
 use meilisearch_error::{internal_error, ErrorCode};
 use tokio::task::JoinError;
 
+use super::uploads::UploadError;
 use super::DocumentAdditionFormat;
 use crate::document_formats::DocumentFormatError;
 use crate::dump::error::DumpError;
 use crate::index::error::IndexError;
+use crate::points_in_time::PitError;
+use crate::search_queue::SearchQueueError;
 use crate::tasks::error::TaskError;
+use crate::tasks::task::TaskId;
 use crate::update_file_store::UpdateFileStoreError;
 
 use crate::index_resolver::error::IndexResolverError;
@@ -21,23 +25,57 @@ pub enum IndexControllerError {
     MissingUid,
     #[error("{0}")]
     IndexResolver(#[from] IndexResolverError),
+    #[error("Index `{index_uid}` is being created by the enqueued task {task_uid}. Wait for this task to be processed before using the index.")]
+    IndexBeingCreated { index_uid: String, task_uid: TaskId },
     #[error("{0}")]
     IndexError(#[from] IndexError),
     #[error("An internal error has occurred. `{0}`.")]
-    Internal(Box<dyn Error + Send + Sync + 'static>),
+    Internal(#[source] Box<dyn Error + Send + Sync + 'static>),
     #[error("{0}")]
     TaskError(#[from] TaskError),
     #[error("{0}")]
     DumpError(#[from] DumpError),
     #[error("{0}")]
     DocumentFormatError(#[from] DocumentFormatError),
+    #[error("{0}")]
+    Upload(#[from] UploadError),
+    #[error("{0}")]
+    SearchQueue(#[from] SearchQueueError),
+    #[error("{0}")]
+    Pit(#[from] PitError),
     #[error("A {0} payload is missing.")]
     MissingPayload(DocumentAdditionFormat),
+    #[error("The {0} payload does not contain any document.")]
+    EmptyPayload(DocumentAdditionFormat),
     #[error("The provided payload reached the size limit.")]
     PayloadTooLarge,
+    #[error("No snapshot directory has been configured.")]
+    MissingSnapshotDir,
+    #[error(
+        "`{0}` is not a valid document fetch url. Only `http` and `https` urls are supported."
+    )]
+    InvalidDocumentFetchUrl(String),
+    #[error("The pending payloads of index `{index_uid}` exceed the quota of {quota} bytes. Wait for the enqueued document additions to be processed before adding more documents.")]
+    PendingPayloadQuotaExceeded { index_uid: String, quota: u64 },
+    #[error("The payload was not received within {0}s.")]
+    PayloadTimeout(u64),
+    #[error("Index `{index_uid}` is temporarily unavailable while an operation needs exclusive access to it. Retry in {retry_after}s.")]
+    IndexTemporarilyUnavailable { index_uid: String, retry_after: u64 },
+    #[error("The search could not be executed within {0}ms.")]
+    SearchTimeout(u128),
+    #[error("Invalid settings preview: {0}")]
+    InvalidSettingsPreview(String),
+    #[error(
+        "Facets are not supported by federated search, but the query on index `{0}` requests them."
+    )]
+    FederatedSearchFacets(String),
+    #[error("Points in time are not supported by federated search, but the query on index `{0}` requests one.")]
+    FederatedSearchPit(String),
+    #[error("Grouping the hits is not supported by federated search, but the query on index `{0}` groups them.")]
+    FederatedSearchGroupBy(String),
 }
 
-internal_error!(IndexControllerError: JoinError, UpdateFileStoreError);
+internal_error!(IndexControllerError: JoinError, UpdateFileStoreError, std::io::Error);
 
 impl From<actix_web::error::PayloadError> for IndexControllerError {
     fn from(other: actix_web::error::PayloadError) -> Self {
@@ -53,13 +91,42 @@ impl ErrorCode for IndexControllerError {
         match self {
             IndexControllerError::MissingUid => Code::BadRequest,
             IndexControllerError::IndexResolver(e) => e.error_code(),
+            IndexControllerError::IndexBeingCreated { .. } => Code::IndexBeingCreated,
             IndexControllerError::IndexError(e) => e.error_code(),
             IndexControllerError::Internal(_) => Code::Internal,
             IndexControllerError::TaskError(e) => e.error_code(),
             IndexControllerError::DocumentFormatError(e) => e.error_code(),
-            IndexControllerError::MissingPayload(_) => Code::MissingPayload,
+            IndexControllerError::Upload(e) => e.error_code(),
+            IndexControllerError::SearchQueue(e) => e.error_code(),
+            IndexControllerError::Pit(e) => e.error_code(),
+            IndexControllerError::MissingPayload(_) | IndexControllerError::EmptyPayload(_) => {
+                Code::MissingPayload
+            }
             IndexControllerError::PayloadTooLarge => Code::PayloadTooLarge,
             IndexControllerError::DumpError(e) => e.error_code(),
+            IndexControllerError::MissingSnapshotDir => Code::Internal,
+            IndexControllerError::InvalidDocumentFetchUrl(_) => Code::InvalidDocumentFetchUrl,
+            IndexControllerError::PendingPayloadQuotaExceeded { .. } => {
+                Code::PendingPayloadQuotaExceeded
+            }
+            IndexControllerError::FederatedSearchFacets(_)
+            | IndexControllerError::FederatedSearchPit(_)
+            | IndexControllerError::FederatedSearchGroupBy(_) => Code::InvalidFederatedSearch,
+            IndexControllerError::PayloadTimeout(_) => Code::PayloadTimeout,
+            IndexControllerError::SearchTimeout(_) => Code::SearchTimeout,
+            IndexControllerError::InvalidSettingsPreview(_) => Code::InvalidSettingsPreview,
+            IndexControllerError::IndexTemporarilyUnavailable { .. } => {
+                Code::IndexTemporarilyUnavailable
+            }
+        }
+    }
+
+    fn retry_after(&self) -> Option<u64> {
+        match self {
+            IndexControllerError::IndexTemporarilyUnavailable { retry_after, .. } => {
+                Some(*retry_after)
+            }
+            _ => self.error_code().retry_after(),
         }
     }
 }
@@ -0,0 +1,173 @@
+//! Search across several indexes at once.
+//!
+//! A federated search runs a query against each of its indexes, and merges their hits in a single
+//! list ordered by ranking score. The ranking score of a hit only depends on its rank among the
+//! candidates of its own query, which makes the hits of the different indexes comparable. Ties are
+//! broken by the position of the query in the request, then by the position of the hit in the
+//! results of its query, so that identical requests always return the hits in the same order and
+//! can be paginated.
+
+use std::cmp::Ordering;
+
+use serde::Serialize;
+
+use crate::index::{SearchHit, SearchQuery, SearchResult};
+
+pub const DEFAULT_FEDERATED_SEARCH_LIMIT: fn() -> usize = || 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FederatedQuery {
+    pub index_uid: String,
+    pub query: SearchQuery,
+}
+
+/// Where a hit of a federated search comes from.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Federation {
+    pub index_uid: String,
+    /// The position of the query in the request.
+    pub queries_position: usize,
+    /// The position of the hit in the results of its query.
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FederatedHit {
+    #[serde(flatten)]
+    pub hit: SearchHit,
+    #[serde(rename = "_federation")]
+    pub federation: Federation,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FederatedSearchResult {
+    pub hits: Vec<FederatedHit>,
+    pub estimated_total_hits: u64,
+    pub limit: usize,
+    pub offset: usize,
+    pub processing_time_ms: u128,
+}
+
+/// Merges the results of the queries of a federated search, in the order of the queries, and
+/// returns the `limit` hits following the first `offset` ones. The hits must have a ranking score.
+pub fn merge_results(
+    results: Vec<(String, SearchResult)>,
+    offset: usize,
+    limit: usize,
+) -> Vec<FederatedHit> {
+    let mut hits: Vec<_> = results
+        .into_iter()
+        .enumerate()
+        .flat_map(|(queries_position, (index_uid, result))| {
+            result
+                .hits
+                .into_iter()
+                .enumerate()
+                .map(move |(position, hit)| FederatedHit {
+                    hit,
+                    federation: Federation {
+                        index_uid: index_uid.clone(),
+                        queries_position,
+                        position,
+                    },
+                })
+        })
+        .collect();
+
+    hits.sort_by(|lhs, rhs| {
+        let score = |hit: &FederatedHit| hit.hit.ranking_score.unwrap_or_default();
+        score(rhs)
+            .partial_cmp(&score(lhs))
+            .unwrap_or(Ordering::Equal)
+            .then(
+                lhs.federation
+                    .queries_position
+                    .cmp(&rhs.federation.queries_position),
+            )
+            .then(lhs.federation.position.cmp(&rhs.federation.position))
+    });
+
+    hits.into_iter().skip(offset).take(limit).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn result(scores: &[f64]) -> SearchResult {
+        let hits = scores
+            .iter()
+            .map(|score| SearchHit {
+                document: json!({ "score": score }).as_object().unwrap().clone(),
+                formatted: Default::default(),
+                matches_position: None,
+                ranking_score: Some(*score),
+                ranking_score_boost: None,
+            })
+            .collect();
+
+        SearchResult {
+            hits,
+            estimated_total_hits: scores.len() as u64,
+            query: String::new(),
+            limit: 20,
+            offset: 0,
+            processing_time_ms: 0,
+            facet_distribution: None,
+            truncated_query_expansion: false,
+            like_documents_warning: None,
+            pit_token: None,
+            groups: None,
+        }
+    }
+
+    fn origins(hits: &[FederatedHit]) -> Vec<(&str, usize)> {
+        hits.iter()
+            .map(|hit| (hit.federation.index_uid.as_str(), hit.federation.position))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_interleaves_by_score() {
+        let results = vec![
+            ("movies".to_string(), result(&[1.0, 0.5, 0.25])),
+            ("books".to_string(), result(&[1.0, 0.75])),
+        ];
+
+        let hits = merge_results(results, 0, 20);
+        assert_eq!(
+            origins(&hits),
+            [
+                ("movies", 0),
+                ("books", 0),
+                ("books", 1),
+                ("movies", 1),
+                ("movies", 2)
+            ]
+        );
+        assert_eq!(hits[2].federation.queries_position, 1);
+    }
+
+    #[test]
+    fn test_merge_pagination_is_stable() {
+        let results = || {
+            vec![
+                ("movies".to_string(), result(&[1.0, 0.5])),
+                ("books".to_string(), result(&[1.0, 0.5])),
+            ]
+        };
+
+        let all = merge_results(results(), 0, 20);
+        let first_page = merge_results(results(), 0, 3);
+        let second_page = merge_results(results(), 3, 3);
+        assert_eq!([first_page, second_page].concat(), all);
+        assert_eq!(
+            origins(&all),
+            [("movies", 0), ("books", 0), ("movies", 1), ("books", 1)]
+        );
+    }
+}
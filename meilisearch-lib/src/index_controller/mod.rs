@@ -1,10 +1,11 @@
 use meilisearch_auth::SearchRules;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
-use std::io::Cursor;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use actix_web::error::PayloadError;
 use bytes::Bytes;
@@ -18,33 +19,80 @@ use tokio::task::spawn_blocking;
 use tokio::time::sleep;
 use uuid::Uuid;
 
-use crate::document_formats::{read_csv, read_json, read_ndjson};
+use crate::calendar::{Calendar, CalendarService, NextFire, ScheduledJob};
+use crate::changefeed::{ChangeRecord, Changefeed, ChangefeedConfig};
+use crate::compaction::{CompactionHandle, CompactionService};
+use crate::data_lock::DataDirLock;
+use crate::document_formats::{
+    self, check_limits, read_payload, InvalidCharacters, PayloadLimits, PayloadType,
+};
 use crate::dump::{self, load_dump, DumpHandler};
 use crate::index::{
-    Checked, Document, IndexMeta, IndexStats, SearchQuery, SearchResult, Settings, Unchecked,
+    validate_batch, validate_edits, AggregateQuery, AggregateResult, Checked, Document,
+    DocumentEdit, DocumentsCursor, DocumentsDiff, DocumentsDiffMode, DocumentsPage,
+    DocumentsValidation, Index, IndexMeta, IndexStats, SearchDefaults, SearchQuery, SearchResult,
+    Settings, Unchecked, HARD_RESULT_LIMIT, MAX_PREVIEW_LIMIT, MAX_PREVIEW_QUERIES,
+    MAX_PREVIEW_SAMPLE_SIZE,
 };
 use crate::options::{IndexerOpts, SchedulerConfig};
-use crate::snapshot::{load_snapshot, SnapshotService};
+use crate::pending_payloads::PendingPayloads;
+use crate::points_in_time::{PointsInTime, DEFAULT_MAX_OPEN_PITS_PER_INDEX, DEFAULT_PIT_TTL};
+use crate::replica::{ReplicaHandle, ReplicaService, ReplicationStatus, SnapshotSource};
+use crate::search_queue::{
+    SearchQueue, SearchQueueStatus, DEFAULT_MAX_QUEUED_SEARCHES, DEFAULT_SEARCH_QUEUE_MAX_WAIT,
+};
+use crate::snapshot::{load_snapshot, SnapshotJob, SnapshotService};
+use crate::tasks::batch::{BatchId, BatchResult};
 use crate::tasks::error::TaskError;
-use crate::tasks::task::{DocumentDeletion, Task, TaskContent, TaskId};
+use crate::tasks::idempotency::{IdempotencyKey, Registration};
+use crate::tasks::progress::{TaskProgress, TaskProgresses};
+use crate::tasks::task::{
+    DocumentDeletion, SettingsWarning, Task, TaskContent, TaskId, TaskOrigin, TaskResult,
+};
 use crate::tasks::{
-    BatchHandler, EmptyBatchHandler, Scheduler, SnapshotHandler, TaskFilter, TaskStore,
+    BatchHandler, EmptyBatchHandler, Job, PendingCreations, QueueSnapshot, QueuedJob, Scheduler,
+    SchedulerLag, SnapshotHandler, TaskEventStream, TaskFilter, TaskOrder, TaskStore,
+    TaskStoreCompactionHandler,
 };
+use crate::throttle::{IoThrottleConfig, LatencyWatch, ThrottleReport};
 use error::Result;
 
 use self::error::IndexControllerError;
+use crate::index_locks::IndexLocks;
+use crate::index_resolver::error::IndexResolverError;
 use crate::index_resolver::index_store::{IndexStore, MapIndexStore};
-use crate::index_resolver::meta_store::{HeedMetaStore, IndexMetaStore};
+pub use crate::index_resolver::meta_store::DEFAULT_NAMESPACE;
+use crate::index_resolver::meta_store::{self, HeedMetaStore, IndexMetaStore};
+pub use crate::index_resolver::templates::IndexTemplates;
 pub use crate::index_resolver::IndexUid;
 use crate::index_resolver::{create_index_resolver, IndexResolver};
-use crate::update_file_store::UpdateFileStore;
+use crate::update_file_store::{UpdateFileDigest, UpdateFileStore};
+use crate::warmup::{WarmupHandle, WarmupService, WarmupStatus};
+
+use self::federated_search::merge_results;
+pub use self::federated_search::{
+    FederatedHit, FederatedQuery, FederatedSearchResult, Federation, DEFAULT_FEDERATED_SEARCH_LIMIT,
+};
+use self::uploads::{ContentRange, UploadError, UploadSessionView, UploadSessions};
 
 pub mod error;
+mod federated_search;
+mod settings_warnings;
+pub mod uploads;
 pub mod versioning;
 
 /// Concrete implementation of the IndexController, exposed by meilisearch-lib
 pub type MeiliSearch = IndexController<HeedMetaStore, MapIndexStore>;
 
+/// The file journaling the task registrations, in the database directory.
+const TASKS_JOURNAL_FILE_NAME: &str = "tasks.journal";
+
+/// The number of batch results shown in the global stats.
+const RECENT_BATCHES_COUNT: usize = 10;
+
+/// The maximum size, in bytes, of a payload to validate, if none is configured.
+pub const DEFAULT_MAX_VALIDATION_PAYLOAD_SIZE: usize = 10 * 1024 * 1024;
+
 pub type Payload = Box<
     dyn Stream<Item = std::result::Result<Bytes, PayloadError>> + Send + Sync + 'static + Unpin,
 >;
@@ -62,21 +110,94 @@ pub struct IndexMetadata {
     #[serde(skip)]
     pub uuid: Uuid,
     pub uid: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The namespace of the index, unless it is the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The patterns of the index templates that contributed to the settings of the index when it
+    /// was created.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<String>,
+    /// Whether the index opted out of the index templates.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip_templates: bool,
     #[serde(flatten)]
     pub meta: IndexMeta,
 }
 
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Namespace {
+    pub uid: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NamespaceStats {
+    pub number_of_indexes: usize,
+    pub number_of_documents: u64,
+    pub database_size: u64,
+    pub indexes: BTreeMap<String, IndexStats>,
+}
+
 #[derive(Clone, Debug)]
 pub struct IndexSettings {
     pub uid: Option<String>,
     pub primary_key: Option<String>,
 }
 
+/// A payload converted to an update file.
+struct PersistedDocuments {
+    content_uuid: Uuid,
+    documents_count: usize,
+    digest: UpdateFileDigest,
+    /// The number of invalid characters that were replaced.
+    sanitized_characters: u64,
+    /// The number of documents exceeding the soft limits.
+    over_soft_limits: u64,
+}
+
 pub struct IndexController<U, I> {
     pub index_resolver: Arc<IndexResolver<U, I>>,
     scheduler: Arc<RwLock<Scheduler>>,
     task_store: TaskStore,
     pub update_file_store: UpdateFileStore,
+    /// Template of the job used to create snapshots on demand, if a snapshot directory is set.
+    snapshot_job: Option<SnapshotJob>,
+    warmup: WarmupHandle,
+    upload_sessions: UploadSessions,
+    search_queue: SearchQueue,
+    points_in_time: PointsInTime,
+    /// The maximum time to receive the payload of a document addition.
+    payload_timeout: Option<Duration>,
+    /// The maximum time to execute a search, once it got its search permit.
+    search_timeout: Option<Duration>,
+    /// The redacted effective configuration of the instance.
+    config: Arc<serde_json::Value>,
+    /// How long the task registered with an idempotency key is returned for this key.
+    idempotency_window: Duration,
+    /// The maximum number of documents of a chunk of a document addition, if the payloads are
+    /// split into chunks.
+    document_chunk_size: Option<usize>,
+    /// The limits on the documents of the payloads.
+    payload_limits: PayloadLimits,
+    /// The maximum size, in bytes, of a payload to validate.
+    max_validation_payload_size: usize,
+    /// The recent latency of the searches, which the adaptive IO throttle backs off on.
+    search_latency: LatencyWatch,
+    /// The status of the replication, if the instance is a read-only replica.
+    replica: Option<ReplicaHandle>,
+    /// The indexes locked by an operation needing exclusive access to them, whose searches are
+    /// refused.
+    index_locks: IndexLocks,
+    /// When the next scheduled snapshot and dump are, if they are scheduled with a calendar.
+    next_snapshot_at: NextFire,
+    next_dump_at: NextFire,
+    /// The lock of the data directory, released once the last clone of the controller is dropped.
+    data_lock: Arc<DataDirLock>,
 }
 
 /// Need a custom implementation for clone because deriving require that U and I are clone.
@@ -87,17 +208,47 @@ impl<U, I> Clone for IndexController<U, I> {
             scheduler: self.scheduler.clone(),
             update_file_store: self.update_file_store.clone(),
             task_store: self.task_store.clone(),
+            snapshot_job: self.snapshot_job.clone(),
+            warmup: self.warmup.clone(),
+            upload_sessions: self.upload_sessions.clone(),
+            search_queue: self.search_queue.clone(),
+            points_in_time: self.points_in_time.clone(),
+            payload_timeout: self.payload_timeout,
+            search_timeout: self.search_timeout,
+            config: self.config.clone(),
+            idempotency_window: self.idempotency_window,
+            document_chunk_size: self.document_chunk_size,
+            payload_limits: self.payload_limits,
+            max_validation_payload_size: self.max_validation_payload_size,
+            search_latency: self.search_latency.clone(),
+            replica: self.replica.clone(),
+            index_locks: self.index_locks.clone(),
+            next_snapshot_at: self.next_snapshot_at.clone(),
+            next_dump_at: self.next_dump_at.clone(),
+            data_lock: self.data_lock.clone(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
 pub enum DocumentAdditionFormat {
     Json,
     Csv,
     Ndjson,
 }
 
+impl DocumentAdditionFormat {
+    fn payload_type(self) -> PayloadType {
+        match self {
+            DocumentAdditionFormat::Json => PayloadType::Json,
+            DocumentAdditionFormat::Csv => PayloadType::Csv,
+            DocumentAdditionFormat::Ndjson => PayloadType::Ndjson,
+        }
+    }
+}
+
 impl fmt::Display for DocumentAdditionFormat {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -115,6 +266,37 @@ pub struct Stats {
     #[serde(serialize_with = "time::serde::rfc3339::option::serialize")]
     pub last_update: Option<OffsetDateTime>,
     pub indexes: BTreeMap<String, IndexStats>,
+    /// The indexes that are closed, whose stats can't be read without opening them.
+    pub closed_indexes: BTreeSet<String>,
+    pub warmup: WarmupStatus,
+    pub search_queue: SearchQueueStatus,
+    pub scheduler_lag: SchedulerLag,
+    /// The jobs, like the snapshots, waiting to be processed before the next tasks.
+    pub queued_jobs: Vec<QueuedJob>,
+    /// The number of tasks quarantined since the start, after failing their batches repeatedly.
+    pub quarantined_tasks: u64,
+    /// What the IO throttle did to the last snapshot, to tune its budget.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_snapshot: Option<ThrottleReport>,
+    /// How far the instance lags behind its primary, if it is a replica.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replication: Option<ReplicationStatus>,
+    /// When the next snapshot scheduled by the snapshot calendar is.
+    #[serde(
+        serialize_with = "time::serde::rfc3339::option::serialize",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub next_snapshot_at: Option<OffsetDateTime>,
+    /// When the next dump scheduled by the dump calendar is.
+    #[serde(
+        serialize_with = "time::serde::rfc3339::option::serialize",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub next_dump_at: Option<OffsetDateTime>,
+    /// The results of the last processed batches, most recent first. They are serialized by the
+    /// http crate, along with their duration.
+    #[serde(skip)]
+    pub recent_batches: Vec<BatchResult>,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -128,6 +310,9 @@ pub enum Update {
         /// Indicates whether the update was a deletion
         is_deletion: bool,
         allow_index_creation: bool,
+        /// The update isn't processed before this date. The settings are still validated right
+        /// away.
+        scheduled_at: Option<OffsetDateTime>,
     },
     DocumentAddition {
         #[derivative(Debug = "ignore")]
@@ -136,13 +321,55 @@ pub enum Update {
         method: IndexDocumentsMethod,
         format: DocumentAdditionFormat,
         allow_index_creation: bool,
+        /// Whether a payload without any document is accepted. Such an addition is registered as
+        /// an already succeeded task, and never creates the index.
+        allow_empty_payload: bool,
+        /// Whether the task must fail when several documents of the payload have the same
+        /// primary key, instead of deduplicating them.
+        reject_duplicates: bool,
+        /// Whether the created and updated documents are recorded in the result of the task.
+        return_diff: Option<DocumentsDiffMode>,
+        /// Whether the invalid characters of the payload are replaced by U+FFFD instead of
+        /// refusing the payload.
+        sanitize: bool,
+    },
+    DocumentAdditionFromUrl {
+        url: String,
+        format: DocumentAdditionFormat,
+        checksum: Option<String>,
+        primary_key: Option<String>,
+        method: IndexDocumentsMethod,
+        allow_index_creation: bool,
     },
     DeleteIndex,
     CreateIndex {
         primary_key: Option<String>,
+        name: Option<String>,
+        namespace: Option<String>,
+        /// Whether the creation succeeds without doing anything if the index already exists.
+        if_not_exists: bool,
+        /// Whether the index opts out of the index templates.
+        skip_templates: bool,
     },
     UpdateIndex {
         primary_key: Option<String>,
+        name: Option<String>,
+        skip_templates: Option<bool>,
+    },
+    CheckIndex {
+        fast: bool,
+    },
+    RepairStats,
+    RebuildIndex,
+    PreviewSettings {
+        settings: Settings<Unchecked>,
+        queries: Vec<String>,
+        sample_size: usize,
+        limit: usize,
+    },
+    EditDocuments {
+        filter: Option<serde_json::Value>,
+        edits: Vec<DocumentEdit>,
     },
 }
 
@@ -156,10 +383,31 @@ pub struct IndexControllerBuilder {
     ignore_snapshot_if_db_exists: bool,
     ignore_missing_snapshot: bool,
     schedule_snapshot: bool,
+    verify_snapshot_after_write: bool,
     dump_src: Option<PathBuf>,
     dump_dst: Option<PathBuf>,
     ignore_dump_if_db_exists: bool,
     ignore_missing_dump: bool,
+    enable_warmup: bool,
+    warmup_sample_queries: bool,
+    max_concurrent_searches: Option<usize>,
+    max_queued_searches: Option<usize>,
+    search_queue_max_wait: Option<Duration>,
+    pit_ttl: Option<Duration>,
+    max_open_pits_per_index: Option<usize>,
+    index_templates: Option<IndexTemplates>,
+    changefeed: Option<ChangefeedConfig>,
+    payload_timeout: Option<Duration>,
+    max_validation_payload_size: Option<usize>,
+    search_timeout: Option<Duration>,
+    config: Option<serde_json::Value>,
+    dump_task_origins: bool,
+    ignore_version_mismatch: bool,
+    io_budget: Option<u64>,
+    io_adaptive_latency_threshold: Option<Duration>,
+    replica_of: Option<(SnapshotSource, Duration)>,
+    snapshot_calendar: Option<Calendar>,
+    dump_calendar: Option<Calendar>,
 }
 
 impl IndexControllerBuilder {
@@ -167,7 +415,7 @@ impl IndexControllerBuilder {
         self,
         db_path: impl AsRef<Path>,
         indexer_options: IndexerOpts,
-        scheduler_config: SchedulerConfig,
+        mut scheduler_config: SchedulerConfig,
     ) -> anyhow::Result<MeiliSearch> {
         let index_size = self
             .max_index_size
@@ -176,7 +424,29 @@ impl IndexControllerBuilder {
             .max_task_store_size
             .ok_or_else(|| anyhow::anyhow!("Missing update database size"))?;
 
-        if let Some(ref path) = self.import_snapshot {
+        let config = match self.config {
+            Some(config) => config,
+            None => default_config(
+                &indexer_options,
+                &scheduler_config,
+                index_size,
+                task_store_size,
+            )?,
+        };
+
+        // the data directory is locked before anything is loaded in it.
+        let data_lock = Arc::new(DataDirLock::acquire(db_path.as_ref())?);
+
+        // the data directory of a replica is loaded from the snapshot of its primary beforehand.
+        let replica = match self.replica_of {
+            Some((ref source, _)) => Some(ReplicaService::handle(db_path.as_ref(), source)?),
+            None => None,
+        };
+        scheduler_config.read_only = replica.is_some();
+
+        if replica.is_some() {
+            versioning::check_version_file(db_path.as_ref())?;
+        } else if let Some(ref path) = self.import_snapshot {
             log::info!("Loading from snapshot {:?}", path);
             load_snapshot(
                 db_path.as_ref(),
@@ -193,6 +463,7 @@ impl IndexControllerBuilder {
                 index_size,
                 task_store_size,
                 &indexer_options,
+                &config,
             )?;
         } else if db_path.as_ref().exists() {
             // Directory could be pre-created without any database in.
@@ -210,64 +481,251 @@ impl IndexControllerBuilder {
         // Create or overwrite the version file for this DB
         versioning::create_version_file(db_path.as_ref())?;
 
-        let index_resolver = Arc::new(create_index_resolver(
+        let mut index_resolver = create_index_resolver(
             &db_path,
             index_size,
             &indexer_options,
             meta_env.clone(),
             update_file_store.clone(),
-        )?);
+        )?;
+
+        let (warmup, warmup_receiver) = if self.enable_warmup {
+            let (warmup, receiver) = WarmupHandle::new(self.warmup_sample_queries);
+            (warmup, Some(receiver))
+        } else {
+            (WarmupHandle::default(), None)
+        };
+        index_resolver.set_warmup(warmup.clone());
+        let (compaction, compaction_receiver) =
+            CompactionHandle::new(indexer_options.compaction_threshold);
+        index_resolver.set_compaction(compaction.clone());
+        let pending_payloads = PendingPayloads::new(
+            indexer_options
+                .max_pending_payload_size_per_index
+                .map(|size| size.get_bytes() as u64),
+        );
+        index_resolver.set_pending_payloads(pending_payloads.clone());
+        let progress = TaskProgresses::default();
+        index_resolver.set_progress(progress.clone());
+        index_resolver.set_defer_chunk_visibility(indexer_options.defer_chunk_visibility);
+        index_resolver.set_max_full_diff_documents(indexer_options.max_full_diff_documents);
+        if let Some(templates) = self.index_templates {
+            index_resolver.set_templates(templates);
+        }
+        if let Some(changefeed) = self.changefeed {
+            index_resolver.set_changefeed(Changefeed::new(&db_path, changefeed));
+        }
+        index_resolver.load_closed_indexes()?;
+        let closed_indexes = index_resolver.closed.clone();
+        let index_locks = IndexLocks::default();
+        let index_resolver = Arc::new(index_resolver);
 
         let dump_path = self
             .dump_dst
             .ok_or_else(|| anyhow::anyhow!("Missing dump directory path"))?;
 
-        let dump_handler = Arc::new(DumpHandler::new(
+        let config = Arc::new(config);
+        // a replica doesn't register any task to journal.
+        let task_store = match replica {
+            Some(_) => TaskStore::new(meta_env)?,
+            None => {
+                TaskStore::with_journal(meta_env, db_path.as_ref().join(TASKS_JOURNAL_FILE_NAME))?
+            }
+        };
+        rebuild_pending_payloads(&task_store, &update_file_store, &pending_payloads)?;
+        let pending_creations = PendingCreations::default();
+        for task in task_store.unfinished_tasks()? {
+            pending_creations.register(&task);
+        }
+        if !self.ignore_version_mismatch {
+            versioning::check_tasks_version(&task_store.unfinished_tasks()?)?;
+        }
+        let mut dump_handler = DumpHandler::new(
             dump_path,
             db_path.as_ref().into(),
             update_file_store.clone(),
             task_store_size,
             index_size,
-            meta_env.clone(),
+            task_store.clone(),
             index_resolver.clone(),
-        ));
-        let task_store = TaskStore::new(meta_env)?;
+            config.clone(),
+        );
+        dump_handler.set_task_origins(self.dump_task_origins);
+        let search_latency = LatencyWatch::default();
+        let io_throttle = IoThrottleConfig {
+            budget: self.io_budget,
+            adaptive_latency_threshold: self.io_adaptive_latency_threshold,
+            search_latency: search_latency.clone(),
+        };
+        dump_handler.set_io_throttle(io_throttle.clone());
+        let dump_handler = Arc::new(dump_handler);
 
         // register all the batch handlers for use with the scheduler.
         let handlers: Vec<Arc<dyn BatchHandler + Sync + Send + 'static>> = vec![
             index_resolver.clone(),
             dump_handler,
+            Arc::new(TaskStoreCompactionHandler::new(
+                db_path.as_ref().to_path_buf(),
+                task_store_size,
+                task_store.clone(),
+                index_resolver.meta_store(),
+            )),
             Arc::new(SnapshotHandler),
             // dummy handler to catch all empty batches
             Arc::new(EmptyBatchHandler),
         ];
-        let scheduler = Scheduler::new(task_store.clone(), handlers, scheduler_config)?;
+        let scheduler = Scheduler::new(
+            task_store.clone(),
+            handlers,
+            scheduler_config,
+            progress,
+            closed_indexes,
+            index_locks.clone(),
+            pending_creations,
+        )?;
+
+        let compaction_service = CompactionService {
+            task_store: task_store.clone(),
+            scheduler: scheduler.clone(),
+            handle: compaction,
+            receiver: compaction_receiver,
+        };
+        tokio::task::spawn_local(compaction_service.run());
+
+        let snapshot_job = self.snapshot_dir.clone().map(|snapshot_path| {
+            let mut job = SnapshotJob::new(
+                snapshot_path,
+                db_path.as_ref().to_path_buf(),
+                task_store_size,
+                index_size,
+                self.verify_snapshot_after_write,
+            );
+            job.set_throttle(io_throttle);
+            job.set_index_locks(index_locks.clone());
+            job
+        });
+
+        if let (Some((source, poll_interval)), Some(handle)) = (self.replica_of, replica.clone()) {
+            let replica_service = ReplicaService {
+                source,
+                poll_interval,
+                db_path: db_path.as_ref().to_path_buf(),
+                meta_env_size: task_store_size,
+                index_resolver: index_resolver.clone(),
+                task_store: task_store.clone(),
+                handle,
+            };
+
+            tokio::task::spawn_local(replica_service.run());
+        }
 
-        if self.schedule_snapshot {
+        // a replica serves the snapshots of its primary, it doesn't make any.
+        if self.schedule_snapshot && replica.is_none() {
             let snapshot_period = self
                 .snapshot_interval
                 .ok_or_else(|| anyhow::anyhow!("Snapshot interval not provided."))?;
-            let snapshot_path = self
-                .snapshot_dir
+            // the scheduled snapshots share the report of their throttle with the manual ones.
+            let job = snapshot_job
+                .clone()
                 .ok_or_else(|| anyhow::anyhow!("Snapshot path not provided."))?;
 
             let snapshot_service = SnapshotService {
-                db_path: db_path.as_ref().to_path_buf(),
                 snapshot_period,
-                snapshot_path,
-                index_size,
-                meta_env_size: task_store_size,
+                job,
                 scheduler: scheduler.clone(),
             };
 
             tokio::task::spawn_local(snapshot_service.run());
         }
 
+        let next_snapshot_at = NextFire::default();
+        let next_dump_at = NextFire::default();
+        if replica.is_none() {
+            if let Some(calendar) = self.snapshot_calendar {
+                let job = snapshot_job
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("Snapshot path not provided."))?;
+                let calendar_service = CalendarService {
+                    calendar,
+                    job: ScheduledJob::Snapshot(job),
+                    next_fire: next_snapshot_at.clone(),
+                    scheduler: scheduler.clone(),
+                };
+                tokio::task::spawn_local(calendar_service.run());
+            }
+
+            if let Some(calendar) = self.dump_calendar {
+                let calendar_service = CalendarService {
+                    calendar,
+                    job: ScheduledJob::Dump(task_store.clone()),
+                    next_fire: next_dump_at.clone(),
+                    scheduler: scheduler.clone(),
+                };
+                tokio::task::spawn_local(calendar_service.run());
+            }
+        }
+
+        let upload_sessions = UploadSessions::new(
+            &db_path,
+            Duration::from_secs(indexer_options.upload_session_ttl_sec),
+        )?;
+        tokio::task::spawn_local(upload_sessions.clone().run_gc());
+
+        let idempotency_window = Duration::from_secs(indexer_options.idempotency_key_window_sec);
+        if replica.is_none() {
+            tokio::task::spawn_local(run_idempotency_keys_gc(
+                task_store.clone(),
+                idempotency_window,
+            ));
+        }
+
+        if let Some(receiver) = warmup_receiver {
+            let warmup_service = WarmupService {
+                index_resolver: index_resolver.clone(),
+                handle: warmup.clone(),
+                receiver,
+            };
+
+            tokio::task::spawn_local(warmup_service.run());
+        }
+
+        let search_queue = SearchQueue::new(
+            self.max_concurrent_searches.unwrap_or_else(num_cpus::get),
+            self.max_queued_searches
+                .unwrap_or(DEFAULT_MAX_QUEUED_SEARCHES),
+            self.search_queue_max_wait
+                .unwrap_or(DEFAULT_SEARCH_QUEUE_MAX_WAIT),
+        );
+
         Ok(IndexController {
             index_resolver,
             scheduler,
             update_file_store,
             task_store,
+            snapshot_job,
+            warmup,
+            upload_sessions,
+            search_queue,
+            points_in_time: PointsInTime::new(
+                self.pit_ttl.unwrap_or(DEFAULT_PIT_TTL),
+                self.max_open_pits_per_index
+                    .unwrap_or(DEFAULT_MAX_OPEN_PITS_PER_INDEX),
+            ),
+            payload_timeout: self.payload_timeout,
+            search_timeout: self.search_timeout,
+            config,
+            idempotency_window,
+            document_chunk_size: indexer_options.document_chunk_size,
+            payload_limits: indexer_options.payload_limits(),
+            max_validation_payload_size: self
+                .max_validation_payload_size
+                .unwrap_or(DEFAULT_MAX_VALIDATION_PAYLOAD_SIZE),
+            search_latency,
+            replica,
+            index_locks,
+            next_snapshot_at,
+            next_dump_at,
+            data_lock,
         })
     }
 
@@ -315,12 +773,39 @@ impl IndexControllerBuilder {
         self
     }
 
+    /// Serve the snapshots of the primary at `source` as a read-only replica, polling a new one
+    /// every `poll_interval`. The data directory must have been loaded with
+    /// `replica::load_latest_snapshot`.
+    pub fn set_replica_of(&mut self, source: SnapshotSource, poll_interval: Duration) -> &mut Self {
+        self.replica_of = Some((source, poll_interval));
+        self
+    }
+
+    /// Schedule the snapshots at the fire times of `calendar`, on top of the periodic ones.
+    pub fn set_snapshot_calendar(&mut self, calendar: Calendar) -> &mut Self {
+        self.snapshot_calendar = Some(calendar);
+        self
+    }
+
+    /// Schedule a dump at each fire time of `calendar`.
+    pub fn set_dump_calendar(&mut self, calendar: Calendar) -> &mut Self {
+        self.dump_calendar = Some(calendar);
+        self
+    }
+
     /// Set the index controller builder's schedule snapshot.
     pub fn set_schedule_snapshot(&mut self) -> &mut Self {
         self.schedule_snapshot = true;
         self
     }
 
+    /// Verify each snapshot after it is written, and fail it instead of replacing the previous
+    /// snapshot if it is invalid.
+    pub fn set_verify_snapshot_after_write(&mut self, verify: bool) -> &mut Self {
+        self.verify_snapshot_after_write = verify;
+        self
+    }
+
     /// Set the index controller builder's dump src.
     pub fn set_dump_src(&mut self, dump_src: PathBuf) -> &mut Self {
         self.dump_src.replace(dump_src);
@@ -340,10 +825,139 @@ impl IndexControllerBuilder {
     }
 
     /// Set the index controller builder's ignore missing dump.
+    /// Warm up the indexes on startup and after their settings are updated.
+    pub fn set_enable_warmup(&mut self, enable_warmup: bool) -> &mut Self {
+        self.enable_warmup = enable_warmup;
+        self
+    }
+
+    /// Set the maximum number of searches executed at the same time. Defaults to the number of
+    /// cores.
+    pub fn set_max_concurrent_searches(&mut self, max_concurrent_searches: usize) -> &mut Self {
+        self.max_concurrent_searches
+            .replace(max_concurrent_searches);
+        self
+    }
+
+    /// Set the maximum number of searches waiting to be executed.
+    pub fn set_max_queued_searches(&mut self, max_queued_searches: usize) -> &mut Self {
+        self.max_queued_searches.replace(max_queued_searches);
+        self
+    }
+
+    /// Set the maximum duration a search waits to be executed.
+    pub fn set_search_queue_max_wait(&mut self, search_queue_max_wait: Duration) -> &mut Self {
+        self.search_queue_max_wait.replace(search_queue_max_wait);
+        self
+    }
+
+    /// Set how long a point in time serves the searches passing its token.
+    pub fn set_pit_ttl(&mut self, pit_ttl: Duration) -> &mut Self {
+        self.pit_ttl.replace(pit_ttl);
+        self
+    }
+
+    /// Set the maximum number of points in time open on an index at once.
+    pub fn set_max_open_pits_per_index(&mut self, max_open_pits_per_index: usize) -> &mut Self {
+        self.max_open_pits_per_index
+            .replace(max_open_pits_per_index);
+        self
+    }
+
+    /// Set the maximum time to receive the payload of a document addition.
+    pub fn set_payload_timeout(&mut self, payload_timeout: Duration) -> &mut Self {
+        self.payload_timeout.replace(payload_timeout);
+        self
+    }
+
+    /// Set the maximum size of a payload to validate.
+    pub fn set_max_validation_payload_size(&mut self, size: usize) -> &mut Self {
+        self.max_validation_payload_size.replace(size);
+        self
+    }
+
+    /// Set the maximum time to execute a search.
+    pub fn set_search_timeout(&mut self, search_timeout: Duration) -> &mut Self {
+        self.search_timeout.replace(search_timeout);
+        self
+    }
+
+    /// Use the last queries made on an index to warm it up when it has no `warmupQueries`.
+    pub fn set_warmup_sample_queries(&mut self, warmup_sample_queries: bool) -> &mut Self {
+        self.warmup_sample_queries = warmup_sample_queries;
+        self
+    }
+
     pub fn set_ignore_missing_dump(&mut self, ignore_missing_dump: bool) -> &mut Self {
         self.ignore_missing_dump = ignore_missing_dump;
         self
     }
+
+    /// Set the effective configuration of the instance, recorded in the dumps and returned by
+    /// `IndexController::config`. The secrets it contains must already be redacted. It defaults to
+    /// the options known by the index controller.
+    pub fn set_config(&mut self, config: serde_json::Value) -> &mut Self {
+        self.config.replace(config);
+        self
+    }
+
+    /// Set the index templates applied to the settings of the indexes.
+    pub fn set_index_templates(&mut self, index_templates: IndexTemplates) -> &mut Self {
+        self.index_templates.replace(index_templates);
+        self
+    }
+
+    /// Record the documents changed by each applied batch in the changefeed of its index.
+    pub fn set_changefeed(&mut self, changefeed: ChangefeedConfig) -> &mut Self {
+        self.changefeed.replace(changefeed);
+        self
+    }
+
+    /// Dump the origins of the tasks, which hold the IP addresses and user agents of the clients
+    /// that registered them.
+    pub fn set_dump_task_origins(&mut self, dump_task_origins: bool) -> &mut Self {
+        self.dump_task_origins = dump_task_origins;
+        self
+    }
+
+    /// Process the unfinished tasks registered by a newer version of Meilisearch instead of
+    /// refusing to start.
+    pub fn set_ignore_version_mismatch(&mut self, ignore_version_mismatch: bool) -> &mut Self {
+        self.ignore_version_mismatch = ignore_version_mismatch;
+        self
+    }
+
+    /// Paces the writes of the snapshots and dumps to `io_budget` bytes per second.
+    pub fn set_io_budget(&mut self, io_budget: Option<u64>) -> &mut Self {
+        self.io_budget = io_budget;
+        self
+    }
+
+    /// Cuts the IO budget of the snapshots and dumps while the recent search latency exceeds
+    /// `threshold`.
+    pub fn set_io_adaptive_latency_threshold(&mut self, threshold: Option<Duration>) -> &mut Self {
+        self.io_adaptive_latency_threshold = threshold;
+        self
+    }
+}
+
+/// The effective configuration made of the options known by the index controller.
+fn default_config(
+    indexer_options: &IndexerOpts,
+    scheduler_config: &SchedulerConfig,
+    index_size: usize,
+    task_store_size: usize,
+) -> serde_json::Result<serde_json::Value> {
+    let mut config = serde_json::to_value(indexer_options)?;
+    if let (Some(config), serde_json::Value::Object(scheduler)) = (
+        config.as_object_mut(),
+        serde_json::to_value(scheduler_config)?,
+    ) {
+        config.extend(scheduler);
+        config.insert("max_index_size".to_string(), index_size.into());
+        config.insert("max_task_db_size".to_string(), task_store_size.into());
+    }
+    Ok(config)
 }
 
 impl<U, I> IndexController<U, I>
@@ -355,8 +969,44 @@ where
         IndexControllerBuilder::default()
     }
 
-    pub async fn register_update(&self, uid: String, update: Update) -> Result<Task> {
+    pub async fn register_update(&self, uid: String, update: Update) -> Result<Task>
+    where
+        U: 'static,
+        I: 'static,
+    {
+        let registration = self
+            .register_update_with_key(uid, update, None, None)
+            .await?;
+        Ok(registration.into_task())
+    }
+
+    /// Registers `update` like `register_update`, unless a task was already registered with the
+    /// idempotency key `key` within the idempotency window, in which case this task is returned
+    /// and `update` is dropped. The registered task records `origin`.
+    pub async fn register_update_with_key(
+        &self,
+        uid: String,
+        update: Update,
+        key: Option<IdempotencyKey>,
+        origin: Option<TaskOrigin>,
+    ) -> Result<Registration>
+    where
+        U: 'static,
+        I: 'static,
+    {
         let index_uid = IndexUid::new(uid)?;
+        // a replayed request doesn't need its payload to be read again. The key is checked again
+        // when the task is registered, in case of concurrent requests.
+        if let Some(ref key) = key {
+            let replayed = self
+                .task_store
+                .get_idempotent_task(key.clone(), self.idempotency_window)
+                .await?;
+            if let Some(task) = replayed {
+                return Ok(Registration::Replayed(task));
+            }
+        }
+
         let content = match update {
             Update::DeleteDocuments(ids) => TaskContent::DocumentDeletion {
                 index_uid,
@@ -370,115 +1020,863 @@ where
                 settings,
                 is_deletion,
                 allow_index_creation,
-            } => TaskContent::SettingsUpdate {
-                settings,
-                is_deletion,
-                allow_index_creation,
-                index_uid,
-            },
+                scheduled_at,
+            } => {
+                // a scheduled update would fail long after the request was answered.
+                if scheduled_at.is_some() {
+                    settings.validate()?;
+                }
+                let warnings = self.settings_warnings(&index_uid, &settings).await;
+                TaskContent::SettingsUpdate {
+                    settings,
+                    is_deletion,
+                    allow_index_creation,
+                    scheduled_at,
+                    warnings,
+                    index_uid,
+                }
+            }
             Update::DocumentAddition {
                 mut payload,
                 primary_key,
                 format,
                 method,
                 allow_index_creation,
+                allow_empty_payload,
+                reject_duplicates,
+                return_diff,
+                sanitize,
             } => {
-                let mut buffer = Vec::new();
-                while let Some(bytes) = payload.next().await {
-                    let bytes = bytes?;
-                    buffer.extend_from_slice(&bytes);
+                // don't even read the payload if the index can't accept any.
+                self.check_pending_payload_quota(&index_uid)?;
+
+                // nothing is written before the whole payload is received, so a payload that
+                // times out or is interrupted leaves nothing behind.
+                let buffer = self.read_payload(&mut payload, None).await?;
+                // check if the payload is empty, and return an error
+                if buffer.is_empty() {
+                    return Err(IndexControllerError::MissingPayload(format));
                 }
-                let (content_uuid, mut update_file) = self.update_file_store.new_update()?;
-                let documents_count = tokio::task::spawn_blocking(move || -> Result<_> {
-                    // check if the payload is empty, and return an error
-                    if buffer.is_empty() {
-                        return Err(IndexControllerError::MissingPayload(format));
-                    }
 
-                    let reader = Cursor::new(buffer);
-                    let count = match format {
-                        DocumentAdditionFormat::Json => read_json(reader, &mut *update_file)?,
-                        DocumentAdditionFormat::Csv => read_csv(reader, &mut *update_file)?,
-                        DocumentAdditionFormat::Ndjson => read_ndjson(reader, &mut *update_file)?,
+                // once the payload is received, the update file and its task are created
+                // together even if the request is cancelled, so that no update file is orphaned.
+                let this = self.clone();
+                let registration = async move {
+                    let invalid_characters = if sanitize {
+                        InvalidCharacters::Replace
+                    } else {
+                        InvalidCharacters::Reject
+                    };
+                    let PersistedDocuments {
+                        content_uuid,
+                        documents_count,
+                        digest,
+                        sanitized_characters,
+                        over_soft_limits,
+                    } = this
+                        .persist_documents(
+                            &index_uid,
+                            format,
+                            Cursor::new(buffer),
+                            invalid_characters,
+                        )
+                        .await?;
+                    let (content_uuid, chunks, content_digests) = this
+                        .chunk_documents(&index_uid, content_uuid, digest, documents_count)
+                        .await?;
+
+                    let content = TaskContent::DocumentAddition {
+                        content_uuid,
+                        merge_strategy: method,
+                        primary_key,
+                        documents_count,
+                        allow_index_creation,
+                        reject_duplicates,
+                        chunks,
+                        return_diff,
+                        content_digests,
+                        sanitized_characters: sanitize.then(|| sanitized_characters),
+                        warnings: this
+                            .payload_limits
+                            .has_soft_limits()
+                            .then(|| over_soft_limits),
+                        index_uid,
                     };
 
-                    update_file.persist()?;
+                    if documents_count == 0 {
+                        // there is nothing to index, so the update file is not needed.
+                        this.discard_documents(content_uuid).await;
+                        if !allow_empty_payload {
+                            return Err(IndexControllerError::EmptyPayload(format));
+                        }
+
+                        let result = TaskResult::DocumentAddition {
+                            indexed_documents: 0,
+                            duplicate_documents: 0,
+                            diff: return_diff.map(|mode| DocumentsDiff {
+                                replaced_ids: (mode == DocumentsDiffMode::Full).then(Vec::new),
+                                ..Default::default()
+                            }),
+                            transforms: None,
+                        };
+                        let index_uuid = this.current_index_uuid(&content).await?;
+                        let registration = this
+                            .task_store
+                            .register_with_origin(
+                                content,
+                                index_uuid,
+                                Some(result),
+                                key.map(|key| (key, this.idempotency_window)),
+                                origin,
+                            )
+                            .await?;
+                        if let Registration::Created(ref task) = registration {
+                            this.scheduler.read().await.announce_registered(task);
+                        }
+                        return Ok(registration);
+                    }
 
-                    Ok(count)
-                })
-                .await??;
+                    let registration = this.register_task_with_key(content, key, origin).await?;
+                    Ok::<_, IndexControllerError>(registration)
+                };
 
-                TaskContent::DocumentAddition {
-                    content_uuid,
+                return tokio::task::spawn_local(registration).await?;
+            }
+            Update::DocumentAdditionFromUrl {
+                url,
+                format,
+                checksum,
+                primary_key,
+                method,
+                allow_index_creation,
+            } => {
+                match reqwest::Url::parse(&url) {
+                    Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => (),
+                    _ => return Err(IndexControllerError::InvalidDocumentFetchUrl(url)),
+                }
+
+                TaskContent::DocumentAdditionFromUrl {
+                    index_uid,
+                    url,
+                    format,
+                    checksum,
                     merge_strategy: method,
                     primary_key,
-                    documents_count,
                     allow_index_creation,
-                    index_uid,
                 }
             }
             Update::DeleteIndex => TaskContent::IndexDeletion { index_uid },
-            Update::CreateIndex { primary_key } => TaskContent::IndexCreation {
+            Update::CreateIndex {
                 primary_key,
-                index_uid,
-            },
-            Update::UpdateIndex { primary_key } => TaskContent::IndexUpdate {
+                name,
+                namespace,
+                if_not_exists,
+                skip_templates,
+            } => {
+                // the creation of an existing index is rejected right away, an index created
+                // after this check makes the task fail when it is processed.
+                if !if_not_exists && self.index_exists(index_uid.as_str().to_string()).await? {
+                    return Err(
+                        IndexResolverError::IndexAlreadyExists(index_uid.into_inner()).into(),
+                    );
+                }
+
+                if let Some(ref namespace) = namespace {
+                    if !self.index_resolver.namespace_exists(namespace).await? {
+                        return Err(
+                            IndexResolverError::UnexistingNamespace(namespace.clone()).into()
+                        );
+                    }
+                }
+
+                TaskContent::IndexCreation {
+                    primary_key,
+                    name,
+                    namespace,
+                    if_not_exists,
+                    skip_templates,
+                    index_uid,
+                }
+            }
+            Update::UpdateIndex {
                 primary_key,
+                name,
+                skip_templates,
+            } => TaskContent::IndexUpdate {
+                primary_key,
+                name,
+                skip_templates,
                 index_uid,
             },
+            Update::CheckIndex { fast } => TaskContent::IndexCheck { index_uid, fast },
+            Update::RepairStats => TaskContent::StatsRepair { index_uid },
+            Update::RebuildIndex => TaskContent::IndexRebuild { index_uid },
+            Update::PreviewSettings {
+                settings,
+                queries,
+                sample_size,
+                limit,
+            } => {
+                validate_settings_preview(&queries, sample_size, limit)?;
+                settings.validate()?;
+                TaskContent::SettingsPreview {
+                    index_uid,
+                    settings,
+                    queries,
+                    sample_size,
+                    limit,
+                }
+            }
+            Update::EditDocuments { filter, edits } => {
+                // the edits are checked right away, the filter can only be checked against the
+                // index, if it exists.
+                validate_edits(&edits)?;
+                if let Some(ref filter) = filter {
+                    if self.index_exists(index_uid.as_str().to_string()).await? {
+                        self.check_filter(index_uid.as_str().to_string(), filter.clone())
+                            .await?;
+                    }
+                }
+
+                TaskContent::DocumentEdition {
+                    index_uid,
+                    filter,
+                    edits,
+                }
+            }
         };
 
-        let task = self.task_store.register(content).await?;
-        self.scheduler.read().await.notify();
+        let is_clear = matches!(
+            content,
+            TaskContent::DocumentDeletion {
+                deletion: DocumentDeletion::Clear,
+                ..
+            }
+        );
+        let registration = self.register_task_with_key(content, key, origin).await?;
+        if let (true, Registration::Created(ref task)) = (is_clear, &registration) {
+            self.supersede_documents(task).await;
+        }
 
-        Ok(task)
+        Ok(registration)
     }
 
-    pub async fn register_dump_task(&self) -> Result<Task> {
-        let uid = dump::generate_uid();
-        let content = TaskContent::Dump { uid };
-        let task = self.task_store.register(content).await?;
-        self.scheduler.read().await.notify();
-        Ok(task)
+    /// Completes the pending document additions that the clear `clear` makes useless, and
+    /// discards their documents. The clear is registered whatever happens here.
+    async fn supersede_documents(&self, clear: &Task) {
+        let superseded = self
+            .scheduler
+            .write()
+            .await
+            .supersede_documents(clear)
+            .await;
+        match superseded {
+            Ok(tasks) => {
+                for task in tasks {
+                    for content_uuid in task.pending_content_uuids() {
+                        self.discard_documents(content_uuid).await;
+                    }
+                }
+            }
+            Err(e) => log::error!("Error superseding the tasks cleared by {}: {}", clear.id, e),
+        }
     }
 
-    pub async fn get_task(&self, id: TaskId, filter: Option<TaskFilter>) -> Result<Task> {
-        let task = self.scheduler.read().await.get_task(id, filter).await?;
-        Ok(task)
+    /// Returns an error if the pending payloads of `index_uid` already reached their quota.
+    fn check_pending_payload_quota(&self, index_uid: &str) -> Result<()> {
+        let pending_payloads = &self.index_resolver.pending_payloads;
+        if pending_payloads.is_full(index_uid) {
+            return Err(IndexControllerError::PendingPayloadQuotaExceeded {
+                index_uid: index_uid.to_string(),
+                quota: pending_payloads.quota().unwrap_or_default(),
+            });
+        }
+
+        Ok(())
     }
 
-    pub async fn get_index_task(&self, index_uid: String, task_id: TaskId) -> Result<Task> {
-        let creation_task_id = self
-            .index_resolver
-            .get_index_creation_task_id(index_uid.clone())
-            .await?;
-        if task_id < creation_task_id {
-            return Err(TaskError::UnexistingTask(task_id).into());
+    /// Converts the documents read from `reader` to a new update file, checks them against the
+    /// limits on the documents, and accounts for the update file in the pending payloads of
+    /// `index_uid`.
+    async fn persist_documents(
+        &self,
+        index_uid: &str,
+        format: DocumentAdditionFormat,
+        reader: impl Read + Send + 'static,
+        invalid_characters: InvalidCharacters,
+    ) -> Result<PersistedDocuments> {
+        let (content_uuid, mut update_file) = self.update_file_store.new_update()?;
+        let limits = self.payload_limits;
+        let (documents_count, digest, replaced, over_soft_limits) =
+            tokio::task::spawn_blocking(move || -> Result<_> {
+                let (count, replaced) = read_payload(
+                    format.payload_type(),
+                    reader,
+                    &mut *update_file,
+                    invalid_characters,
+                )?;
+
+                // a document over the hard limits refuses the payload before it is persisted.
+                let over_soft_limits = check_limits(BufReader::new(&mut *update_file), &limits)?;
+
+                let digest = update_file.persist()?;
+
+                Ok((count, digest, replaced, over_soft_limits))
+            })
+            .await??;
+
+        let pending_payloads = &self.index_resolver.pending_payloads;
+        if !pending_payloads.try_track(index_uid, content_uuid, digest.size) {
+            if let Err(e) = self.update_file_store.delete(content_uuid).await {
+                log::error!("Error deleting refused update file: {}", e);
+            }
+            return Err(IndexControllerError::PendingPayloadQuotaExceeded {
+                index_uid: index_uid.to_string(),
+                quota: pending_payloads.quota().unwrap_or_default(),
+            });
         }
 
-        let mut filter = TaskFilter::default();
-        filter.filter_index(index_uid);
-        let task = self
-            .scheduler
-            .read()
-            .await
-            .get_task(task_id, Some(filter))
-            .await?;
-
-        Ok(task)
+        Ok(PersistedDocuments {
+            content_uuid,
+            documents_count,
+            digest,
+            sanitized_characters: replaced,
+            over_soft_limits,
+        })
     }
 
-    pub async fn list_tasks(
+    /// Splits the update file `content_uuid`, of `documents_count` documents, into update files of
+    /// at most `document_chunk_size` documents that replace it, if it has more documents. Returns
+    /// the update file of the first chunk, the update files of the following chunks, and the
+    /// digests of all the update files.
+    async fn chunk_documents(
         &self,
-        filter: Option<TaskFilter>,
-        limit: Option<usize>,
+        index_uid: &str,
+        content_uuid: Uuid,
+        digest: UpdateFileDigest,
+        documents_count: usize,
+    ) -> Result<(Uuid, Vec<Uuid>, BTreeMap<Uuid, UpdateFileDigest>)> {
+        let chunk_size = match self.document_chunk_size.map(|size| size.max(1)) {
+            Some(size) if documents_count > size => size,
+            _ => {
+                let digests = BTreeMap::from([(content_uuid, digest)]);
+                return Ok((content_uuid, Vec::new(), digests));
+            }
+        };
+
+        let number_of_chunks = (documents_count + chunk_size - 1) / chunk_size;
+        let mut uuids = Vec::with_capacity(number_of_chunks);
+        let mut files = Vec::with_capacity(number_of_chunks);
+        for _ in 0..number_of_chunks {
+            let (uuid, file) = self.update_file_store.new_update()?;
+            uuids.push(uuid);
+            files.push(file);
+        }
+
+        let input = self.update_file_store.get_update(content_uuid)?;
+        let split = tokio::task::spawn_blocking(move || -> Result<_> {
+            document_formats::split(input, chunk_size, files.iter_mut().map(|file| &mut **file))?;
+            files
+                .into_iter()
+                .map(|file| Ok(file.persist()?))
+                .collect::<Result<Vec<_>>>()
+        })
+        .await
+        .map_err(IndexControllerError::from)
+        .and_then(|result| result);
+
+        // the chunks replace the whole payload, which isn't needed anymore.
+        self.discard_documents(content_uuid).await;
+        let digests = match split {
+            Ok(digests) => digests,
+            Err(e) => {
+                for uuid in uuids {
+                    self.discard_documents(uuid).await;
+                }
+                return Err(e);
+            }
+        };
+
+        let pending_payloads = &self.index_resolver.pending_payloads;
+        for (&uuid, digest) in uuids.iter().zip(&digests) {
+            pending_payloads.track(index_uid, uuid, digest.size);
+        }
+
+        let digests = uuids.iter().copied().zip(digests).collect();
+        let chunks = uuids.split_off(1);
+        Ok((uuids[0], chunks, digests))
+    }
+
+    /// Receives a whole payload, within the payload timeout if any. A payload larger than
+    /// `max_size` is refused as soon as it exceeds it.
+    async fn read_payload(
+        &self,
+        payload: &mut Payload,
+        max_size: Option<usize>,
+    ) -> Result<Vec<u8>> {
+        let read = async {
+            let mut buffer = Vec::new();
+            while let Some(bytes) = payload.next().await {
+                buffer.extend_from_slice(&bytes?);
+                if max_size.map_or(false, |max| buffer.len() > max) {
+                    return Err(IndexControllerError::PayloadTooLarge);
+                }
+            }
+            Ok::<_, IndexControllerError>(buffer)
+        };
+
+        match self.payload_timeout {
+            Some(duration) => tokio::time::timeout(duration, read)
+                .await
+                .map_err(|_| IndexControllerError::PayloadTimeout(duration.as_secs()))?,
+            None => read.await,
+        }
+    }
+
+    /// Deletes an update file that won't be processed, and releases its pending payload.
+    async fn discard_documents(&self, content_uuid: Uuid) {
+        if let Err(e) = self.update_file_store.delete(content_uuid).await {
+            log::error!("Error deleting discarded update file: {}", e);
+        }
+        self.index_resolver.pending_payloads.release(content_uuid);
+    }
+
+    /// The uuid of the index targeted by `content`, if it exists.
+    async fn current_index_uuid(&self, content: &TaskContent) -> Result<Option<Uuid>> {
+        match content.index_uid() {
+            Some(index_uid) => {
+                let meta = self
+                    .index_resolver
+                    .get_index_meta(index_uid.to_string())
+                    .await?;
+                Ok(meta.map(|meta| meta.uuid))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn register_task(&self, content: TaskContent) -> Result<Task> {
+        let registration = self.register_task_with_key(content, None, None).await?;
+        Ok(registration.into_task())
+    }
+
+    async fn register_task_with_key(
+        &self,
+        content: TaskContent,
+        key: Option<IdempotencyKey>,
+        origin: Option<TaskOrigin>,
+    ) -> Result<Registration> {
+        let content_uuids: Vec<_> = match content {
+            TaskContent::DocumentAddition {
+                content_uuid,
+                ref chunks,
+                ..
+            } => std::iter::once(content_uuid)
+                .chain(chunks.iter().copied())
+                .collect(),
+            _ => Vec::new(),
+        };
+        let index_uuid = match self.current_index_uuid(&content).await {
+            Ok(index_uuid) => index_uuid,
+            Err(e) => {
+                for &content_uuid in &content_uuids {
+                    self.discard_documents(content_uuid).await;
+                }
+                return Err(e);
+            }
+        };
+        let registration = self
+            .task_store
+            .register_with_origin(
+                content,
+                index_uuid,
+                None,
+                key.map(|key| (key, self.idempotency_window)),
+                origin,
+            )
+            .await;
+        match registration {
+            Ok(Registration::Created(task)) => {
+                let scheduler = self.scheduler.read().await;
+                scheduler.pending_creations().register(&task);
+                scheduler.announce_registered(&task);
+                scheduler.notify();
+                Ok(Registration::Created(task))
+            }
+            // a concurrent request with the same key registered its task first.
+            Ok(Registration::Replayed(task)) => {
+                for &content_uuid in &content_uuids {
+                    self.discard_documents(content_uuid).await;
+                }
+                Ok(Registration::Replayed(task))
+            }
+            Err(e) => {
+                // the update file of a task that doesn't exist would never be deleted.
+                for &content_uuid in &content_uuids {
+                    self.discard_documents(content_uuid).await;
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Opens a resumable upload session for a document addition to `uid`.
+    pub fn create_upload(
+        &self,
+        uid: String,
+        format: DocumentAdditionFormat,
+        primary_key: Option<String>,
+        allow_index_creation: bool,
+    ) -> Result<UploadSessionView> {
+        let index_uid = IndexUid::new(uid)?;
+        let view = self.upload_sessions.create(
+            index_uid.into_inner(),
+            format,
+            primary_key,
+            allow_index_creation,
+        )?;
+        Ok(view)
+    }
+
+    pub async fn get_upload(&self, uid: String, upload_id: Uuid) -> Result<UploadSessionView> {
+        let session = self.upload_sessions.get(&uid, upload_id)?;
+        let view = session.lock().await.view();
+        Ok(view)
+    }
+
+    /// Appends a chunk to the upload session `upload_id`.
+    pub async fn append_to_upload(
+        &self,
+        uid: String,
+        upload_id: Uuid,
+        range: ContentRange,
+        mut payload: Payload,
+    ) -> Result<UploadSessionView> {
+        let session = self.upload_sessions.get(&uid, upload_id)?;
+        let chunk = self.read_payload(&mut payload, None).await?;
+
+        let mut session = session.lock().await;
+        let view = self
+            .upload_sessions
+            .append(&mut session, range, chunk)
+            .await?;
+        Ok(view)
+    }
+
+    /// Registers the document addition of the upload session `upload_id`. Committing a session
+    /// that is already committed returns the task registered the first time.
+    pub async fn commit_upload(
+        &self,
+        uid: String,
+        upload_id: Uuid,
+        origin: Option<TaskOrigin>,
+    ) -> Result<Task> {
+        let session = self.upload_sessions.get(&uid, upload_id)?;
+        let mut session = session.lock().await;
+
+        if let Some(task_id) = session.task_id() {
+            return self.get_task(task_id, None).await;
+        }
+
+        session.check_complete()?;
+        if session.received() == 0 {
+            return Err(IndexControllerError::MissingPayload(session.format));
+        }
+
+        let index_uid = IndexUid::new(session.index_uid.clone())?;
+        self.check_pending_payload_quota(&index_uid)?;
+        let file =
+            File::open(self.upload_sessions.data_path(upload_id)).map_err(UploadError::from)?;
+        let PersistedDocuments {
+            content_uuid,
+            documents_count,
+            digest,
+            over_soft_limits,
+            ..
+        } = self
+            .persist_documents(
+                &index_uid,
+                session.format,
+                BufReader::new(file),
+                InvalidCharacters::Reject,
+            )
+            .await?;
+        if documents_count == 0 {
+            self.discard_documents(content_uuid).await;
+            return Err(IndexControllerError::EmptyPayload(session.format));
+        }
+        let (content_uuid, chunks, content_digests) = self
+            .chunk_documents(&index_uid, content_uuid, digest, documents_count)
+            .await?;
+
+        let content = TaskContent::DocumentAddition {
+            content_uuid,
+            merge_strategy: IndexDocumentsMethod::ReplaceDocuments,
+            primary_key: session.primary_key.clone(),
+            documents_count,
+            allow_index_creation: session.allow_index_creation,
+            reject_duplicates: false,
+            chunks,
+            return_diff: None,
+            content_digests,
+            sanitized_characters: None,
+            warnings: self
+                .payload_limits
+                .has_soft_limits()
+                .then(|| over_soft_limits),
+            index_uid,
+        };
+        let task = self
+            .register_task_with_key(content, None, origin)
+            .await?
+            .into_task();
+        self.upload_sessions.committed(&mut session, task.id);
+
+        Ok(task)
+    }
+
+    /// Registers the deletion of the namespace `namespace` and of all its indexes.
+    pub async fn register_namespace_deletion(
+        &self,
+        namespace: String,
+        origin: Option<TaskOrigin>,
+    ) -> Result<Task> {
+        if namespace == DEFAULT_NAMESPACE {
+            return Err(IndexResolverError::DefaultNamespaceDeletion.into());
+        }
+        if !self.index_resolver.namespace_exists(&namespace).await? {
+            return Err(IndexResolverError::UnexistingNamespace(namespace).into());
+        }
+
+        let content = TaskContent::NamespaceDeletion { namespace };
+        let task = self
+            .task_store
+            .register_with_origin(content, None, None, None, origin)
+            .await?
+            .into_task();
+        let scheduler = self.scheduler.read().await;
+        scheduler.announce_registered(&task);
+        scheduler.notify();
+        Ok(task)
+    }
+
+    /// Registers the creation of a dump. With `include_pending_tasks`, the tasks still waiting
+    /// to be processed are dumped too, and processed by the instance the dump is imported in.
+    pub async fn register_dump_task(
+        &self,
+        origin: Option<TaskOrigin>,
+        include_pending_tasks: bool,
+    ) -> Result<Task> {
+        let uid = dump::generate_uid();
+        let content = TaskContent::Dump {
+            uid,
+            include_pending_tasks,
+        };
+        let task = self
+            .task_store
+            .register_with_origin(content, None, None, None, origin)
+            .await?
+            .into_task();
+        let scheduler = self.scheduler.read().await;
+        scheduler.announce_registered(&task);
+        scheduler.notify();
+        Ok(task)
+    }
+
+    /// Registers the compaction of the tasks database. The tasks can't be read nor registered
+    /// while its batch is processed.
+    pub async fn register_task_store_compaction(&self, origin: Option<TaskOrigin>) -> Result<Task> {
+        let task = self
+            .task_store
+            .register_with_origin(TaskContent::TaskStoreCompaction, None, None, None, origin)
+            .await?
+            .into_task();
+        let scheduler = self.scheduler.read().await;
+        scheduler.announce_registered(&task);
+        scheduler.notify();
+        Ok(task)
+    }
+
+    /// Schedules the creation of a snapshot in the snapshot directory, its writes paced by the IO
+    /// throttle unless `throttled` is false. It fails when too many jobs are already waiting to be
+    /// processed.
+    pub async fn register_snapshot(&self, throttled: bool) -> Result<()> {
+        let job = self
+            .snapshot_job
+            .as_ref()
+            .ok_or(IndexControllerError::MissingSnapshotDir)?;
+        let job = match throttled {
+            true => job.clone(),
+            false => job.unthrottled(),
+        };
+        self.scheduler
+            .write()
+            .await
+            .schedule_job(Job::Snapshot(job))?;
+        Ok(())
+    }
+
+    /// Aborts the batch being processed when it is stuck, failing its tasks. Returns the failed
+    /// tasks.
+    pub async fn force_reset_processing(&self) -> Result<Vec<Task>> {
+        let tasks = self
+            .scheduler
+            .write()
+            .await
+            .force_reset_processing()
+            .await?;
+
+        for content_uuid in tasks.iter().flat_map(Task::pending_content_uuids) {
+            if let Err(e) = self.update_file_store.delete(content_uuid).await {
+                log::error!("Error deleting update file of aborted task: {}", e);
+            }
+            self.index_resolver.pending_payloads.release(content_uuid);
+        }
+
+        Ok(tasks)
+    }
+
+    /// Cancels an enqueued task. Tasks that are already being processed can't be canceled.
+    pub async fn cancel_task(&self, id: TaskId, filter: Option<TaskFilter>) -> Result<Task> {
+        let task = self.scheduler.write().await.cancel_task(id, filter).await?;
+
+        for content_uuid in task.pending_content_uuids() {
+            if let Err(e) = self.update_file_store.delete(content_uuid).await {
+                log::error!("Error deleting update file of canceled task: {}", e);
+            }
+            self.index_resolver.pending_payloads.release(content_uuid);
+        }
+
+        Ok(task)
+    }
+
+    /// The redacted effective configuration of the instance.
+    pub fn config(&self) -> &serde_json::Value {
+        &self.config
+    }
+
+    /// Whether the documents changed by the applied batches are recorded in a changefeed.
+    pub fn is_changefeed_enabled(&self) -> bool {
+        self.index_resolver.changefeed.is_enabled()
+    }
+
+    /// Returns at most `limit` records of the changefeed of `index_uid`, from the batch
+    /// `from_batch` on.
+    pub async fn list_changes(
+        &self,
+        index_uid: String,
+        from_batch: BatchId,
+        limit: usize,
+    ) -> Result<Vec<ChangeRecord>> {
+        let changefeed = self.index_resolver.changefeed.clone();
+        let changes = spawn_blocking(move || changefeed.read(&index_uid, from_batch, limit))
+            .await?
+            .map_err(IndexResolverError::from)?;
+        Ok(changes)
+    }
+
+    /// Whether the instance is a read-only replica, which rejects the writes.
+    pub fn is_replica(&self) -> bool {
+        self.replica.is_some()
+    }
+
+    pub async fn get_task(&self, id: TaskId, filter: Option<TaskFilter>) -> Result<Task> {
+        let task = self.scheduler.read().await.get_task(id, filter).await?;
+        Ok(task)
+    }
+
+    /// Returns a stream of the state transitions of the tasks from now on, which ends with the
+    /// scheduler. A subscriber that doesn't keep up is told how many transitions it missed.
+    pub async fn subscribe_task_events(&self) -> TaskEventStream {
+        self.scheduler.read().await.subscribe()
+    }
+
+    /// Waits for the task `id` to be finished, and returns it. Fails with a timeout error holding
+    /// the last state of the task once `timeout` elapsed.
+    pub async fn wait_for_task(&self, id: TaskId, timeout: Option<Duration>) -> Result<Task> {
+        // the scheduler must not stay locked while the task is processed.
+        let wait = self.scheduler.read().await.wait_for_task(id, timeout);
+        Ok(wait.await?)
+    }
+
+    /// Waits for all the tasks `ids` to be finished, and returns them in the same order.
+    pub async fn wait_for_tasks(
+        &self,
+        ids: &[TaskId],
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Task>> {
+        let wait = self.scheduler.read().await.wait_for_tasks(ids, timeout);
+        Ok(wait.await?)
+    }
+
+    /// Returns the tasks being processed and the tasks waiting to be processed.
+    pub async fn queue_snapshot(&self) -> QueueSnapshot {
+        self.scheduler.read().await.queue_snapshot()
+    }
+
+    /// The progress of the task `id`, if it is being processed.
+    pub async fn task_progress(&self, id: TaskId) -> Option<TaskProgress> {
+        self.scheduler.read().await.task_progress(id)
+    }
+
+    /// Returns the ids of the tasks of `tasks` whose index was deleted since, even if an index with
+    /// the same uid was created again. Each index is only resolved once.
+    pub async fn tasks_of_deleted_indexes(&self, tasks: &[Task]) -> Result<HashSet<TaskId>> {
+        let mut metas = HashMap::new();
+        for index_uid in tasks.iter().filter_map(Task::index_uid) {
+            if !metas.contains_key(index_uid) {
+                let meta = self
+                    .index_resolver
+                    .get_index_meta(index_uid.to_string())
+                    .await?;
+                metas.insert(index_uid, meta);
+            }
+        }
+
+        Ok(tasks
+            .iter()
+            .filter(|task| match task.index_uid() {
+                Some(index_uid) => is_index_deleted(task, metas[index_uid].as_ref()),
+                None => false,
+            })
+            .map(|task| task.id)
+            .collect())
+    }
+
+    pub async fn get_index_task(&self, index_uid: String, task_id: TaskId) -> Result<Task> {
+        let creation_task_id = self
+            .index_resolver
+            .get_index_creation_task_id(index_uid.clone())
+            .await?;
+        if task_id < creation_task_id {
+            return Err(TaskError::UnexistingTask(task_id).into());
+        }
+
+        let mut filter = TaskFilter::default();
+        filter.filter_index(index_uid);
+        let task = self
+            .scheduler
+            .read()
+            .await
+            .get_task(task_id, Some(filter))
+            .await?;
+
+        Ok(task)
+    }
+
+    /// Lists the tasks in `order`, starting from the task `offset` included.
+    pub async fn list_tasks(
+        &self,
+        filter: Option<TaskFilter>,
+        limit: Option<usize>,
         offset: Option<TaskId>,
+        order: TaskOrder,
     ) -> Result<Vec<Task>> {
         let tasks = self
             .scheduler
             .read()
             .await
-            .list_tasks(offset, filter, limit)
+            .list_tasks(offset, filter, limit, order)
             .await?;
 
         Ok(tasks)
@@ -506,19 +1904,59 @@ where
                 Some(offset.unwrap_or_default() + task_id),
                 Some(filter),
                 limit,
+                TaskOrder::Descending,
             )
             .await?;
 
         Ok(tasks)
     }
 
+    pub async fn get_batch_result(
+        &self,
+        id: BatchId,
+        indexes: Option<HashSet<String>>,
+    ) -> Result<BatchResult> {
+        let result = self
+            .scheduler
+            .read()
+            .await
+            .get_batch_result(id, indexes)
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn list_batch_results(
+        &self,
+        indexes: Option<HashSet<String>>,
+        limit: Option<usize>,
+        from: Option<BatchId>,
+    ) -> Result<Vec<BatchResult>> {
+        let results = self
+            .scheduler
+            .read()
+            .await
+            .list_batch_results(from, indexes, limit)
+            .await?;
+        Ok(results)
+    }
+
     pub async fn list_indexes(&self) -> Result<Vec<IndexMetadata>> {
         let indexes = self.index_resolver.list().await?;
+        let mut names = self.index_resolver.index_names().await?;
+        let mut namespaces = self.index_resolver.index_namespaces().await?;
+        let mut templates = self.index_resolver.index_templates_meta().await?;
         let mut ret = Vec::new();
         for (uid, index) in indexes {
             let meta = index.meta()?;
+            let (index_templates, skip_templates) = templates.remove(&uid).unwrap_or_default();
             let meta = IndexMetadata {
                 uuid: index.uuid(),
+                name: names.remove(&uid),
+                namespace: namespaces
+                    .remove(&uid)
+                    .filter(|namespace| namespace != DEFAULT_NAMESPACE),
+                templates: index_templates,
+                skip_templates,
                 uid,
                 meta,
             };
@@ -528,6 +1966,76 @@ where
         Ok(ret)
     }
 
+    /// The pending tasks of `index_uid` that the settings update `settings` would make fail. The
+    /// warnings are advisory: the update is registered even when they can't be computed.
+    async fn settings_warnings(
+        &self,
+        index_uid: &IndexUid,
+        settings: &Settings<Unchecked>,
+    ) -> Vec<SettingsWarning> {
+        if !settings_warnings::changes_filterable_attributes(settings) {
+            return Vec::new();
+        }
+        // an index that doesn't exist yet has no filterable attributes.
+        let current = self
+            .settings(index_uid.to_string())
+            .await
+            .unwrap_or_default();
+        let task_store = self.task_store.clone();
+        let pending = async move {
+            let pending = spawn_blocking(move || task_store.unfinished_tasks()).await??;
+            Ok::<_, IndexControllerError>(pending)
+        };
+        let pending = match pending.await {
+            Ok(pending) => pending,
+            Err(e) => {
+                log::warn!(
+                    "Could not check the settings update against the pending tasks: {}",
+                    e
+                );
+                return Vec::new();
+            }
+        };
+        settings_warnings::settings_warnings(index_uid.as_str(), &current, settings, &pending)
+    }
+
+    /// Returns the index `uid` for a search or a document route. When the index doesn't exist but
+    /// an enqueued task creates it, fails with an `IndexBeingCreated` error holding this task, so
+    /// that the client can wait for it instead of assuming a typo in the uid.
+    async fn get_index_or_pending(&self, uid: String) -> Result<Index> {
+        let index_uid = match self.index_resolver.get_index(uid).await {
+            Ok(index) => return Ok(index),
+            Err(IndexResolverError::UnexistingIndex(index_uid)) => index_uid,
+            Err(e) => return Err(e.into()),
+        };
+        let pending = self
+            .scheduler
+            .read()
+            .await
+            .pending_creations()
+            .get(&index_uid);
+        if let Some(task_uid) = pending {
+            // the task may have been processed between its registration and its tracking.
+            match self.task_store.get_task(task_uid, None).await {
+                Ok(task) if !task.is_finished() => {
+                    return Err(IndexControllerError::IndexBeingCreated {
+                        index_uid,
+                        task_uid,
+                    })
+                }
+                Ok(task) => {
+                    self.scheduler
+                        .read()
+                        .await
+                        .pending_creations()
+                        .finished(&[task]);
+                }
+                Err(e) => log::warn!("Could not read the task {}: {}", task_uid, e),
+            }
+        }
+        Err(IndexResolverError::UnexistingIndex(index_uid).into())
+    }
+
     pub async fn settings(&self, uid: String) -> Result<Settings<Checked>> {
         let index = self.index_resolver.get_index(uid).await?;
         let settings = spawn_blocking(move || index.settings()).await??;
@@ -540,13 +2048,15 @@ where
         uid: String,
         offset: usize,
         limit: usize,
+        cursor: Option<DocumentsCursor>,
         attributes_to_retrieve: Option<Vec<String>>,
-    ) -> Result<(u64, Vec<Document>)> {
-        let index = self.index_resolver.get_index(uid).await?;
-        let result =
-            spawn_blocking(move || index.retrieve_documents(offset, limit, attributes_to_retrieve))
-                .await??;
-        Ok(result)
+    ) -> Result<DocumentsPage> {
+        let index = self.get_index_or_pending(uid).await?;
+        let page = spawn_blocking(move || {
+            index.retrieve_documents(offset, limit, cursor, attributes_to_retrieve)
+        })
+        .await??;
+        Ok(page)
     }
 
     pub async fn document(
@@ -554,38 +2064,350 @@ where
         uid: String,
         doc_id: String,
         attributes_to_retrieve: Option<Vec<String>>,
+        with_metadata: bool,
     ) -> Result<Document> {
-        let index = self.index_resolver.get_index(uid).await?;
-        let document =
-            spawn_blocking(move || index.retrieve_document(doc_id, attributes_to_retrieve))
-                .await??;
+        let index = self.get_index_or_pending(uid).await?;
+        let document = spawn_blocking(move || {
+            index.retrieve_document(doc_id, attributes_to_retrieve, with_metadata)
+        })
+        .await??;
         Ok(document)
     }
 
     pub async fn search(&self, uid: String, query: SearchQuery) -> Result<SearchResult> {
+        if let Some(remaining) = self.index_locks.remaining(&uid) {
+            return Err(IndexControllerError::IndexTemporarilyUnavailable {
+                index_uid: uid,
+                // at least a second, the lock may be released at any time.
+                retry_after: remaining.as_secs_f64().ceil().max(1.0) as u64,
+            });
+        }
+        self.warmup.record_query(&uid, &query);
+        let index = self.get_index_or_pending(uid).await?;
+        let point_in_time = self.points_in_time.snapshot(&index, &query)?;
+        let permit = self.search_queue.acquire().await?;
+        let search_latency = self.search_latency.clone();
+        let search = spawn_blocking(move || {
+            let started = Instant::now();
+            let result = match point_in_time {
+                Some((token, snapshot)) => {
+                    let result = index.perform_search_at(query, &snapshot.lock());
+                    result.map(|result| SearchResult {
+                        pit_token: Some(token),
+                        ..result
+                    })
+                }
+                None => index.perform_search(query),
+            };
+            search_latency.record(started.elapsed());
+            // the permit is released as soon as the search is done, even if the request was
+            // cancelled in the meantime.
+            drop(permit);
+            result
+        });
+
+        // milli can't interrupt a search, so a search past its deadline still runs to completion
+        // on its thread, holding its permit, but its result is dropped.
+        let result = match self.search_timeout {
+            Some(duration) => tokio::time::timeout(duration, search)
+                .await
+                .map_err(|_| IndexControllerError::SearchTimeout(duration.as_millis()))?,
+            None => search.await,
+        }??;
+        Ok(result)
+    }
+
+    /// Runs each query on its index, and merges their hits in a single list. The `offset` and
+    /// `limit` of the queries are ignored in favor of the ones of the federated search.
+    pub async fn federated_search(
+        &self,
+        queries: Vec<FederatedQuery>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<FederatedSearchResult> {
+        let before_search = Instant::now();
+
+        if let Some(query) = queries.iter().find(|query| query.query.facets.is_some()) {
+            return Err(IndexControllerError::FederatedSearchFacets(
+                query.index_uid.clone(),
+            ));
+        }
+        if let Some(query) = queries
+            .iter()
+            .find(|query| query.query.pit || query.query.pit_token.is_some())
+        {
+            return Err(IndexControllerError::FederatedSearchPit(
+                query.index_uid.clone(),
+            ));
+        }
+        if let Some(query) = queries.iter().find(|query| query.query.group_by.is_some()) {
+            return Err(IndexControllerError::FederatedSearchGroupBy(
+                query.index_uid.clone(),
+            ));
+        }
+
+        let offset = offset.min(HARD_RESULT_LIMIT);
+        let limit = limit.min(HARD_RESULT_LIMIT - offset);
+
+        let mut results = Vec::with_capacity(queries.len());
+        let mut shown_ranking_scores = Vec::with_capacity(queries.len());
+        let mut estimated_total_hits = 0;
+        for FederatedQuery {
+            index_uid,
+            mut query,
+        } in queries
+        {
+            shown_ranking_scores.push(query.show_ranking_score);
+            // any hit of the requested page may come from the first hits of any query.
+            query.offset = None;
+            query.limit = offset + limit;
+            query.show_ranking_score = true;
+
+            let result = self.search(index_uid.clone(), query).await?;
+            estimated_total_hits += result.estimated_total_hits;
+            results.push((index_uid, result));
+        }
+
+        let mut hits = merge_results(results, offset, limit);
+        for hit in &mut hits {
+            if !shown_ranking_scores[hit.federation.queries_position] {
+                hit.hit.ranking_score = None;
+            }
+        }
+
+        Ok(FederatedSearchResult {
+            hits,
+            estimated_total_hits,
+            limit,
+            offset,
+            processing_time_ms: before_search.elapsed().as_millis(),
+        })
+    }
+
+    pub async fn search_defaults(&self, uid: String) -> Result<SearchDefaults> {
+        let index = self.get_index_or_pending(uid).await?;
+        let defaults = spawn_blocking(move || index.search_defaults()).await??;
+        Ok(defaults)
+    }
+
+    pub async fn aggregate(&self, uid: String, query: AggregateQuery) -> Result<AggregateResult> {
         let index = self.index_resolver.get_index(uid).await?;
-        let result = spawn_blocking(move || index.perform_search(query)).await??;
+        let result = spawn_blocking(move || index.aggregate(query)).await??;
         Ok(result)
     }
 
+    /// Validates a documents payload as if it was added to the index `uid`, without registering
+    /// a task. The index doesn't need to exist.
+    pub async fn validate_documents(
+        &self,
+        uid: String,
+        mut payload: Payload,
+        format: DocumentAdditionFormat,
+        primary_key: Option<String>,
+        reject_duplicates: bool,
+        sanitize: bool,
+    ) -> Result<DocumentsValidation> {
+        let index_uid = IndexUid::new(uid)?;
+        let buffer = self
+            .read_payload(&mut payload, Some(self.max_validation_payload_size))
+            .await?;
+        if buffer.is_empty() {
+            return Err(IndexControllerError::MissingPayload(format));
+        }
+
+        let index = match self.index_resolver.get_index(index_uid.into_inner()).await {
+            Ok(index) => Some(index),
+            Err(IndexResolverError::UnexistingIndex(_)) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let limits = self.payload_limits;
+        let invalid_characters = if sanitize {
+            InvalidCharacters::Replace
+        } else {
+            InvalidCharacters::Reject
+        };
+
+        let validation = spawn_blocking(move || -> Result<_> {
+            let mut converted = tempfile::tempfile()?;
+            let (count, _) = read_payload(
+                format.payload_type(),
+                Cursor::new(buffer),
+                &mut converted,
+                invalid_characters,
+            )?;
+            if count == 0 {
+                return Err(IndexControllerError::EmptyPayload(format));
+            }
+            converted.seek(SeekFrom::Start(0))?;
+
+            let validation = match index {
+                Some(index) => {
+                    index.validate_documents(converted, primary_key, &limits, reject_duplicates)?
+                }
+                None => {
+                    let first_payload = &mut converted;
+                    let primary_key = document_formats::addition_primary_key(
+                        None,
+                        primary_key,
+                        || -> Result<_> { Ok(Some(first_payload)) },
+                    )?;
+                    converted.seek(SeekFrom::Start(0))?;
+                    validate_batch(
+                        converted,
+                        primary_key.as_deref(),
+                        &limits,
+                        reject_duplicates,
+                    )?
+                }
+            };
+            Ok(validation)
+        })
+        .await??;
+
+        Ok(validation)
+    }
+
+    /// Returns an error if `filter` can't be applied to the index.
+    pub async fn check_filter(&self, uid: String, filter: serde_json::Value) -> Result<()> {
+        let index = self.index_resolver.get_index(uid).await?;
+        spawn_blocking(move || index.check_filter(&filter)).await??;
+        Ok(())
+    }
+
+    pub async fn index_exists(&self, uid: String) -> Result<bool> {
+        Ok(self.index_resolver.get_index_meta(uid).await?.is_some())
+    }
+
     pub async fn get_index(&self, uid: String) -> Result<IndexMetadata> {
         let index = self.index_resolver.get_index(uid.clone()).await?;
+        let resolver_meta = self
+            .index_resolver
+            .get_index_meta(uid.clone())
+            .await?
+            .ok_or_else(|| IndexResolverError::UnexistingIndex(uid.clone()))?;
         let uuid = index.uuid();
         let meta = spawn_blocking(move || index.meta()).await??;
-        let meta = IndexMetadata { uuid, uid, meta };
+        let meta = IndexMetadata {
+            uuid,
+            uid,
+            name: resolver_meta.name,
+            namespace: resolver_meta
+                .namespace
+                .filter(|namespace| namespace != DEFAULT_NAMESPACE),
+            templates: resolver_meta.templates,
+            skip_templates: resolver_meta.skip_templates,
+            meta,
+        };
         Ok(meta)
     }
 
-    pub async fn get_index_stats(&self, uid: String) -> Result<IndexStats> {
+    /// Closes the index `uid`, along with its points in time. It rejects the searches and its tasks
+    /// wait in the queue until it is opened again.
+    pub async fn close_index(&self, uid: String) -> Result<()> {
+        let uuid = self.index_resolver.close_index(uid).await?;
+        self.points_in_time.close_index(uuid);
+        Ok(())
+    }
+
+    /// Opens the index `uid` again, and lets the scheduler process the tasks that waited for it.
+    pub async fn open_index(&self, uid: String) -> Result<()> {
+        self.index_resolver.open_index(uid).await?;
+        self.scheduler.read().await.notify();
+        Ok(())
+    }
+
+    /// Returns the namespace of the index `uid`. An index that doesn't exist yet belongs to the
+    /// default namespace.
+    pub async fn index_namespace(&self, uid: String) -> Result<String> {
+        let namespace = self.index_resolver.get_index_meta(uid).await?.map_or_else(
+            || DEFAULT_NAMESPACE.to_string(),
+            |meta| meta.namespace().to_string(),
+        );
+        Ok(namespace)
+    }
+
+    pub async fn create_namespace(&self, uid: String) -> Result<Namespace> {
+        let meta = self.index_resolver.create_namespace(uid.clone()).await?;
+        Ok(Namespace {
+            uid,
+            created_at: meta.created_at,
+        })
+    }
+
+    pub async fn get_namespace(&self, uid: String) -> Result<Namespace> {
+        self.list_namespaces()
+            .await?
+            .into_iter()
+            .find(|namespace| namespace.uid == uid)
+            .ok_or_else(|| IndexResolverError::UnexistingNamespace(uid).into())
+    }
+
+    /// Returns the namespaces that were created, the default namespace excluded.
+    pub async fn list_namespaces(&self) -> Result<Vec<Namespace>> {
+        let namespaces = self
+            .index_resolver
+            .list_namespaces()
+            .await?
+            .into_iter()
+            .map(|(uid, meta)| Namespace {
+                uid,
+                created_at: meta.created_at,
+            })
+            .collect();
+        Ok(namespaces)
+    }
+
+    /// Sums the documents and sizes of the indexes of the namespace `uid`.
+    pub async fn get_namespace_stats(&self, uid: String) -> Result<NamespaceStats> {
+        if !self.index_resolver.namespace_exists(&uid).await? {
+            return Err(IndexResolverError::UnexistingNamespace(uid).into());
+        }
+
+        let mut stats = NamespaceStats {
+            number_of_indexes: 0,
+            number_of_documents: 0,
+            database_size: 0,
+            indexes: BTreeMap::new(),
+        };
+        let closed = self.index_resolver.closed_indexes();
+        for index_uid in self.index_resolver.namespace_indexes(&uid).await? {
+            // the stats of a closed index can't be read without opening it.
+            if closed.contains(&index_uid) {
+                continue;
+            }
+            let index_stats = self.get_index_stats(index_uid.clone(), false).await?;
+            stats.number_of_indexes += 1;
+            stats.number_of_documents += index_stats.number_of_documents;
+            stats.database_size += index_stats.size;
+            stats.indexes.insert(index_uid, index_stats);
+        }
+
+        Ok(stats)
+    }
+
+    /// Returns the stats of an index, with the breakdown by internal database if `detailed` is
+    /// set.
+    pub async fn get_index_stats(&self, uid: String, detailed: bool) -> Result<IndexStats> {
         let processing_tasks = self.scheduler.read().await.get_processing_tasks().await?;
         // Check if the currently indexing update is from our index.
         let is_indexing = processing_tasks
             .first()
             .map_or(false, |task| task.index_uid().map_or(false, |u| u == uid));
 
+        let pending_payload_size = self.index_resolver.pending_payloads.size(&uid);
         let index = self.index_resolver.get_index(uid).await?;
-        let mut stats = spawn_blocking(move || index.stats()).await??;
+        let open_pits = self.points_in_time.count(index.uuid());
+        let mut stats = spawn_blocking::<_, Result<IndexStats>>(move || {
+            let mut stats = index.stats()?;
+            if detailed {
+                stats.databases = Some(index.database_stats()?);
+            }
+            Ok(stats)
+        })
+        .await??;
         stats.is_indexing = Some(is_indexing);
+        stats.pending_payload_size = pending_payload_size;
+        stats.open_pits = open_pits;
 
         Ok(stats)
     }
@@ -601,6 +2423,7 @@ where
                 continue;
             }
 
+            let open_pits = self.points_in_time.count(index.uuid());
             let (mut stats, meta) =
                 spawn_blocking::<_, Result<(IndexStats, IndexMeta)>>(move || {
                     Ok((index.stats()?, index.meta()?))
@@ -618,18 +2441,130 @@ where
                 .first()
                 .and_then(|p| p.index_uid().map(|u| u == index_uid))
                 .or(Some(false));
+            stats.pending_payload_size = self.index_resolver.pending_payloads.size(&index_uid);
+            stats.open_pits = open_pits;
 
             indexes.insert(index_uid, stats);
         }
 
+        // the batches that don't belong to an index are only shown to the keys allowed on every
+        // index.
+        let batch_indexes = (!search_rules.is_index_authorized("*")).then(|| {
+            search_rules
+                .clone()
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect()
+        });
+        let recent_batches = self
+            .list_batch_results(batch_indexes, Some(RECENT_BATCHES_COUNT), None)
+            .await?;
+
+        let (scheduler_lag, queued_jobs, quarantined_tasks) = {
+            let scheduler = self.scheduler.read().await;
+            (
+                scheduler.lag(),
+                scheduler.queued_jobs(),
+                scheduler.quarantined_tasks(),
+            )
+        };
+
+        let closed_indexes = self
+            .index_resolver
+            .closed_indexes()
+            .into_iter()
+            .filter(|index_uid| search_rules.is_index_authorized(index_uid))
+            .collect();
+
         Ok(Stats {
             database_size,
             last_update: last_task,
             indexes,
+            closed_indexes,
+            warmup: self.warmup.status(),
+            search_queue: self.search_queue.status(),
+            scheduler_lag,
+            queued_jobs,
+            quarantined_tasks,
+            last_snapshot: self
+                .snapshot_job
+                .as_ref()
+                .and_then(SnapshotJob::last_report),
+            replication: self.replica.as_ref().map(ReplicaHandle::status),
+            next_snapshot_at: self.next_snapshot_at.get(),
+            next_dump_at: self.next_dump_at.get(),
+            recent_batches,
         })
     }
 }
 
+/// Accounts for the update files of the document additions that were enqueued before the last
+/// shutdown.
+/// Whether the index targeted by `task` was deleted, `current` being the index that currently has
+/// its uid. The tasks registered before the index existed, which don't know its uuid, belong to it
+/// only if they were not registered before its creation.
+/// The longest pause between two collections of the expired idempotency keys.
+const MAX_IDEMPOTENCY_GC_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Periodically forgets the idempotency keys older than `window`.
+async fn run_idempotency_keys_gc(task_store: TaskStore, window: Duration) {
+    let interval = window.clamp(Duration::from_secs(1), MAX_IDEMPOTENCY_GC_INTERVAL);
+    loop {
+        sleep(interval).await;
+        match task_store.delete_expired_idempotency_keys(window).await {
+            Ok(0) => (),
+            Ok(deleted) => log::debug!("Forgot {} expired idempotency keys.", deleted),
+            Err(e) => log::error!("Error while forgetting the expired idempotency keys: {}", e),
+        }
+    }
+}
+
+fn is_index_deleted(task: &Task, current: Option<&meta_store::IndexMeta>) -> bool {
+    match (current, task.index_uuid) {
+        (None, _) => true,
+        (Some(meta), Some(uuid)) => meta.uuid != uuid && !meta.former_uuids.contains(&uuid),
+        (Some(meta), None) => task.id < meta.creation_task_id,
+    }
+}
+
+/// Checks the bounds of a settings preview, which is processed long after it is registered.
+fn validate_settings_preview(queries: &[String], sample_size: usize, limit: usize) -> Result<()> {
+    let error = if queries.is_empty() {
+        "at least one query is required.".to_string()
+    } else if queries.len() > MAX_PREVIEW_QUERIES {
+        format!("at most {} queries are allowed.", MAX_PREVIEW_QUERIES)
+    } else if !(1..=MAX_PREVIEW_SAMPLE_SIZE).contains(&sample_size) {
+        format!(
+            "`sampleSize` must be between 1 and {}.",
+            MAX_PREVIEW_SAMPLE_SIZE
+        )
+    } else if !(1..=MAX_PREVIEW_LIMIT).contains(&limit) {
+        format!("`limit` must be between 1 and {}.", MAX_PREVIEW_LIMIT)
+    } else {
+        return Ok(());
+    };
+    Err(IndexControllerError::InvalidSettingsPreview(error))
+}
+
+fn rebuild_pending_payloads(
+    task_store: &TaskStore,
+    update_file_store: &UpdateFileStore,
+    pending_payloads: &PendingPayloads,
+) -> anyhow::Result<()> {
+    for task in task_store.unfinished_tasks()? {
+        if let TaskContent::DocumentAddition { ref index_uid, .. } = task.content {
+            for content_uuid in task.pending_content_uuids() {
+                match update_file_store.get_size(content_uuid) {
+                    Ok(size) => pending_payloads.track(index_uid, content_uuid, size),
+                    Err(e) => log::warn!("Missing update file for task {}: {}", task.id, e),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn get_arc_ownership_blocking<T>(mut item: Arc<T>) -> T {
     loop {
         match Arc::try_unwrap(item) {
@@ -646,9 +2581,11 @@ pub async fn get_arc_ownership_blocking<T>(mut item: Arc<T>) -> T {
 #[cfg(test)]
 mod test {
     use futures::future::ok;
+    use meilisearch_error::ErrorCode;
     use mockall::predicate::eq;
     use nelson::Mocker;
 
+    use crate::closed_indexes::ClosedIndexes;
     use crate::index::error::Result as IndexResult;
     use crate::index::Index;
     use crate::index::{
@@ -661,7 +2598,9 @@ mod test {
     use super::*;
 
     impl IndexController<MockIndexMetaStore, MockIndexStore> {
+        /// Mocks a controller whose uploads and lock are in `dir`, which must outlive it.
         pub fn mock(
+            dir: &Path,
             index_resolver: Arc<IndexResolver<MockIndexMetaStore, MockIndexStore>>,
             task_store: TaskStore,
             update_file_store: UpdateFileStore,
@@ -672,16 +2611,90 @@ mod test {
                 task_store,
                 update_file_store,
                 scheduler,
+                snapshot_job: None,
+                warmup: WarmupHandle::default(),
+                search_queue: SearchQueue::default(),
+                points_in_time: PointsInTime::default(),
+                config: Default::default(),
+                upload_sessions: UploadSessions::new(dir.join("uploads"), Duration::from_secs(60))
+                    .unwrap(),
+                payload_timeout: None,
+                search_timeout: None,
+                idempotency_window: Duration::from_secs(60),
+                document_chunk_size: None,
+                payload_limits: PayloadLimits::default(),
+                max_validation_payload_size: DEFAULT_MAX_VALIDATION_PAYLOAD_SIZE,
+                search_latency: LatencyWatch::default(),
+                replica: None,
+                index_locks: IndexLocks::default(),
+                next_snapshot_at: NextFire::default(),
+                next_dump_at: NextFire::default(),
+                data_lock: Arc::new(DataDirLock::acquire(dir.join("db")).unwrap()),
             }
         }
     }
 
+    #[test]
+    fn test_is_index_deleted() {
+        let task = |id, index_uuid| Task {
+            id,
+            index_uuid,
+            content: TaskContent::IndexDeletion {
+                index_uid: IndexUid::new_unchecked("test"),
+            },
+            events: Vec::new(),
+            origin: None,
+            engine_version: None,
+        };
+        let uuid = Uuid::new_v4();
+        let current = meta_store::IndexMeta {
+            uuid,
+            creation_task_id: 5,
+            name: None,
+            namespace: None,
+            former_uuids: Vec::new(),
+            templates: Vec::new(),
+            skip_templates: false,
+            closed: false,
+        };
+
+        assert!(is_index_deleted(&task(6, Some(uuid)), None));
+        assert!(!is_index_deleted(&task(6, Some(uuid)), Some(&current)));
+        // the index was deleted, and created again with the same uid.
+        assert!(is_index_deleted(
+            &task(6, Some(Uuid::new_v4())),
+            Some(&current)
+        ));
+        // the tasks registered before the index was rebuilt.
+        let former_uuid = Uuid::new_v4();
+        let rebuilt = meta_store::IndexMeta {
+            former_uuids: vec![former_uuid],
+            templates: Vec::new(),
+            skip_templates: false,
+            ..current.clone()
+        };
+        assert!(!is_index_deleted(
+            &task(6, Some(former_uuid)),
+            Some(&rebuilt)
+        ));
+        assert!(is_index_deleted(
+            &task(6, Some(former_uuid)),
+            Some(&current)
+        ));
+        // the tasks registered before the index existed.
+        assert!(is_index_deleted(&task(4, None), Some(&current)));
+        assert!(!is_index_deleted(&task(5, None), Some(&current)));
+    }
+
     #[actix_rt::test]
     async fn test_search_simple() {
         let index_uid = "test";
         let index_uuid = Uuid::new_v4();
         let query = SearchQuery {
             q: Some(String::from("hello world")),
+            ignore_stop_words: true,
+            prefix_search: None,
+            like_document_ids: None,
             offset: Some(10),
             limit: 0,
             attributes_to_retrieve: Some(vec!["string".to_owned()].into_iter().collect()),
@@ -689,7 +2702,14 @@ mod test {
             crop_length: 18,
             attributes_to_highlight: None,
             show_matches_position: true,
+            show_ranking_score: false,
             filter: None,
+            boost: None,
+            searchable_weights: None,
+            ranking_rules_override: None,
+            group_by: None,
+            pit: false,
+            pit_token: None,
             sort: None,
             facets: None,
             highlight_pre_tag: DEFAULT_HIGHLIGHT_PRE_TAG(),
@@ -705,6 +2725,10 @@ mod test {
             offset: 0,
             processing_time_ms: 50,
             facet_distribution: None,
+            truncated_query_expansion: false,
+            like_documents_warning: None,
+            pit_token: None,
+            groups: None,
         };
 
         let mut uuid_store = MockIndexMetaStore::new();
@@ -717,6 +2741,12 @@ mod test {
                     Some(crate::index_resolver::meta_store::IndexMeta {
                         uuid: index_uuid,
                         creation_task_id: 0,
+                        name: None,
+                        namespace: None,
+                        former_uuids: Vec::new(),
+                        templates: Vec::new(),
+                        skip_templates: false,
+                        closed: false,
                     }),
                 )))
             });
@@ -755,10 +2785,20 @@ mod test {
             task_store.clone(),
             vec![index_resolver.clone()],
             SchedulerConfig::default(),
+            TaskProgresses::default(),
+            ClosedIndexes::default(),
+            IndexLocks::default(),
+            PendingCreations::default(),
         )
         .unwrap();
-        let index_controller =
-            IndexController::mock(index_resolver, task_store, update_file_store, scheduler);
+        let dir = tempfile::tempdir().unwrap();
+        let index_controller = IndexController::mock(
+            dir.path(),
+            index_resolver,
+            task_store,
+            update_file_store,
+            scheduler,
+        );
 
         let r = index_controller
             .search(index_uid.to_owned(), query.clone())
@@ -766,4 +2806,48 @@ mod test {
             .unwrap();
         assert_eq!(r, result);
     }
+
+    #[actix_rt::test]
+    async fn test_search_locked_index() {
+        // the index is never resolved while it is locked.
+        let index_resolver = Arc::new(IndexResolver::new(
+            MockIndexMetaStore::new(),
+            MockIndexStore::new(),
+            UpdateFileStore::mock(Mocker::default()),
+        ));
+        let task_store = TaskStore::mock(nelson::Mocker::default());
+        let update_file_store = UpdateFileStore::mock(Mocker::default());
+        let scheduler = Scheduler::new(
+            task_store.clone(),
+            vec![index_resolver.clone()],
+            SchedulerConfig::default(),
+            TaskProgresses::default(),
+            ClosedIndexes::default(),
+            IndexLocks::default(),
+            PendingCreations::default(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let index_controller = IndexController::mock(
+            dir.path(),
+            index_resolver,
+            task_store,
+            update_file_store,
+            scheduler,
+        );
+
+        let _lock = index_controller
+            .index_locks
+            .acquire("test", "snapshot", Duration::from_millis(2500))
+            .unwrap();
+        let error = index_controller
+            .search("test".to_string(), SearchQuery::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            IndexControllerError::IndexTemporarilyUnavailable { retry_after: 3, .. }
+        ));
+        assert_eq!(error.retry_after(), Some(3));
+    }
 }
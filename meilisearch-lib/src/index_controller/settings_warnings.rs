@@ -0,0 +1,367 @@
+//! The warnings of the settings updates making attributes unfilterable.
+//!
+//! When a settings update removes attributes from the filterable attributes of an index, the
+//! pending tasks of the index whose filter references one of them are listed in the update, so
+//! that the operator sees the conflict when registering it. Nothing is blocked: a listed task
+//! only fails if it is processed after the update. The filters are stored as they were sent, so
+//! their attributes are extracted with a tokenizer of the filter syntax, which is lenient: an
+//! invalid filter only yields the attributes it could make out.
+//!
+//! The sortable attributes are only used by the searches, no task references them.
+
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+use crate::index::{Checked, Settings, Unchecked};
+use crate::tasks::task::{SettingsWarning, Task, TaskContent};
+
+const KEYWORDS: [&str; 7] = ["AND", "OR", "NOT", "TO", "EXISTS", "IN", "IS"];
+/// The keywords following the attribute of a condition.
+const CONDITION_KEYWORDS: [&str; 4] = ["EXISTS", "IN", "IS", "NOT"];
+const GEO_FUNCTIONS: [&str; 2] = ["_geoRadius", "_geoBoundingBox"];
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// A word, or a quoted string, which is never a keyword.
+    Word {
+        value: String,
+        quoted: bool,
+    },
+    Operator(&'a str),
+    Punctuation(char),
+}
+
+impl Token<'_> {
+    fn is_keyword(&self, keywords: &[&str]) -> bool {
+        match self {
+            Token::Word {
+                value,
+                quoted: false,
+            } => keywords.iter().any(|k| value.eq_ignore_ascii_case(k)),
+            _ => false,
+        }
+    }
+
+    /// The attribute, or value, this token names.
+    fn name(&self) -> Option<&str> {
+        match self {
+            Token::Word { .. } if self.is_keyword(&KEYWORDS) => None,
+            Token::Word { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = expression;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if "()[],".contains(c) {
+            tokens.push(Token::Punctuation(c));
+            rest = &rest[1..];
+        } else if let Some(operator) = ["!=", ">=", "<=", "=", ">", "<"]
+            .into_iter()
+            .find(|operator| rest.starts_with(operator))
+        {
+            tokens.push(Token::Operator(&rest[..operator.len()]));
+            rest = &rest[operator.len()..];
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            let mut escaped = false;
+            let mut end = rest.len();
+            for (i, d) in rest.char_indices().skip(1) {
+                if escaped {
+                    value.push(d);
+                    escaped = false;
+                } else if d == '\\' {
+                    escaped = true;
+                } else if d == c {
+                    end = i + 1;
+                    break;
+                } else {
+                    value.push(d);
+                }
+            }
+            tokens.push(Token::Word {
+                value,
+                quoted: true,
+            });
+            rest = &rest[end..];
+        } else if c == '!' {
+            // a lone `!` is not an operator, it is skipped.
+            rest = &rest[1..];
+        } else {
+            let end = rest
+                .find(|d: char| d.is_whitespace() || "()[],=!<>'\"".contains(d))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word {
+                value: rest[..end].to_string(),
+                quoted: false,
+            });
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+/// Adds the attributes the filter `expression` references to `attributes`.
+fn expression_attributes(expression: &str, attributes: &mut BTreeSet<String>) {
+    let tokens = tokenize(expression);
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1);
+        if GEO_FUNCTIONS.iter().any(|f| token.name() == Some(*f))
+            && next == Some(&Token::Punctuation('('))
+        {
+            attributes.insert("_geo".to_string());
+            continue;
+        }
+        let name = match token.name() {
+            Some(name) => name,
+            None => continue,
+        };
+        // the value of a comparison is not an attribute.
+        let previous = i.checked_sub(1).and_then(|i| tokens.get(i));
+        if matches!(previous, Some(Token::Operator(_))) {
+            continue;
+        }
+        let is_attribute = match next {
+            // `attribute = value`
+            Some(Token::Operator(_)) => true,
+            // `attribute EXISTS`, `attribute IN [...]`, `attribute NOT IN [...]`...
+            Some(next) if next.is_keyword(&CONDITION_KEYWORDS) => true,
+            // `attribute from TO to`
+            Some(next) if next.name().is_some() => tokens
+                .get(i + 2)
+                .map_or(false, |token| token.is_keyword(&["TO"])),
+            _ => false,
+        };
+        if is_attribute {
+            attributes.insert(name.to_string());
+        }
+    }
+}
+
+/// The attributes `filter` references. A filter is an expression, or an array of expressions
+/// and arrays of expressions.
+pub fn filter_attributes(filter: &Value) -> BTreeSet<String> {
+    let mut attributes = BTreeSet::new();
+    let mut values = vec![filter];
+    while let Some(value) = values.pop() {
+        match value {
+            Value::String(expression) => expression_attributes(expression, &mut attributes),
+            Value::Array(array) => values.extend(array),
+            _ => (),
+        }
+    }
+    attributes
+}
+
+/// Whether `settings` changes the filterable attributes.
+pub fn changes_filterable_attributes(settings: &Settings<Unchecked>) -> bool {
+    !settings.filterable_attributes.is_not_set()
+}
+
+/// The pending tasks of `pending`, the unfinished tasks, that reference attributes removed from
+/// the filterable attributes of `index_uid` by the settings update `update`. `current` are the
+/// settings of the index, the settings updates pending for the index can make other attributes
+/// filterable before `update` is processed.
+pub fn settings_warnings(
+    index_uid: &str,
+    current: &Settings<Checked>,
+    update: &Settings<Unchecked>,
+    pending: &[Task],
+) -> Vec<SettingsWarning> {
+    let pending: Vec<_> = pending
+        .iter()
+        .filter(|task| task.index_uid() == Some(index_uid) && !task.is_finished())
+        .collect();
+
+    let mut filterable: BTreeSet<String> = current
+        .filterable_attributes
+        .as_ref()
+        .set()
+        .cloned()
+        .unwrap_or_default();
+    for task in &pending {
+        if let TaskContent::SettingsUpdate { settings, .. } = &task.content {
+            if let Some(attributes) = settings.filterable_attributes.as_ref().set() {
+                filterable.extend(attributes.iter().cloned());
+            }
+        }
+    }
+
+    // a reset makes every attribute unfilterable.
+    let remaining = update
+        .filterable_attributes
+        .as_ref()
+        .set()
+        .cloned()
+        .unwrap_or_default();
+    let removed: BTreeSet<_> = match update.filterable_attributes.is_not_set() {
+        true => BTreeSet::new(),
+        false => filterable.difference(&remaining).cloned().collect(),
+    };
+    if removed.is_empty() {
+        return Vec::new();
+    }
+
+    pending
+        .into_iter()
+        .filter_map(|task| {
+            let filter = match &task.content {
+                TaskContent::DocumentEdition {
+                    filter: Some(filter),
+                    ..
+                } => filter,
+                _ => return None,
+            };
+            let attributes: BTreeSet<_> = filter_attributes(filter)
+                .intersection(&removed)
+                .cloned()
+                .collect();
+            (!attributes.is_empty()).then(|| SettingsWarning {
+                task_uid: task.id,
+                attributes,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use milli::update::Setting;
+    use serde_json::json;
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::index_resolver::IndexUid;
+    use crate::tasks::task::TaskEvent;
+
+    fn attributes(filter: Value) -> Vec<String> {
+        filter_attributes(&filter).into_iter().collect()
+    }
+
+    #[test]
+    fn test_filter_attributes() {
+        let cases = [
+            (json!("genre = horror"), vec!["genre"]),
+            (
+                json!("genre != 'science fiction' AND year>=2000"),
+                vec!["genre", "year"],
+            ),
+            (
+                json!("NOT (price 10 TO 20 OR rating < 3)"),
+                vec!["price", "rating"],
+            ),
+            (json!("'release date' = \"2022\""), vec!["release date"]),
+            // a value looking like an attribute is not one.
+            (
+                json!("genre = year AND author.name = 'a = b'"),
+                vec!["author.name", "genre"],
+            ),
+            (json!("_geoRadius(45.4, 9.1, 2000)"), vec!["_geo"]),
+            (
+                json!("tags EXISTS OR colors NOT IN [red, blue] OR id IS NULL"),
+                vec!["colors", "id", "tags"],
+            ),
+            (
+                json!(["genre = horror", ["year = 2000", "rating > 4"]]),
+                vec!["genre", "rating", "year"],
+            ),
+            // an invalid filter only yields the attributes that could be made out.
+            (json!("genre = 'unterminated AND year = 2"), vec!["genre"]),
+            (json!("! = = TO"), vec![]),
+            (json!(42), vec![]),
+        ];
+        for (filter, expected) in cases {
+            assert_eq!(attributes(filter.clone()), expected, "{}", filter);
+        }
+    }
+
+    fn task(id: u32, content: TaskContent) -> Task {
+        Task {
+            id,
+            index_uuid: None,
+            content,
+            events: vec![TaskEvent::Created(OffsetDateTime::now_utc())],
+            origin: None,
+            engine_version: None,
+        }
+    }
+
+    fn edition(index_uid: &str, filter: Value) -> TaskContent {
+        TaskContent::DocumentEdition {
+            index_uid: IndexUid::new_unchecked(index_uid),
+            filter: Some(filter),
+            edits: Vec::new(),
+        }
+    }
+
+    fn filterable(attributes: &[&str]) -> Setting<BTreeSet<String>> {
+        Setting::Set(attributes.iter().map(|a| a.to_string()).collect())
+    }
+
+    fn settings_update(index_uid: &str, attributes: &[&str]) -> TaskContent {
+        TaskContent::SettingsUpdate {
+            index_uid: IndexUid::new_unchecked(index_uid),
+            settings: Settings {
+                filterable_attributes: filterable(attributes),
+                ..Default::default()
+            },
+            is_deletion: false,
+            allow_index_creation: true,
+            scheduled_at: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_settings_warnings() {
+        let current = Settings {
+            filterable_attributes: filterable(&["genre", "year"]),
+            ..Settings::default()
+        };
+        let pending = vec![
+            task(1, edition("movies", json!("genre = horror"))),
+            task(2, edition("movies", json!("year > 2000 AND rating > 3"))),
+            // made filterable by a pending settings update.
+            task(3, settings_update("movies", &["rating"])),
+            task(4, edition("movies", json!("rating > 4"))),
+            // the tasks of another index are not concerned.
+            task(5, edition("books", json!("genre = horror"))),
+        ];
+
+        let update = Settings {
+            filterable_attributes: filterable(&["genre"]),
+            ..Default::default()
+        };
+        let warnings = settings_warnings("movies", &current, &update, &pending);
+        let expected = vec![
+            SettingsWarning {
+                task_uid: 2,
+                attributes: ["rating".to_string(), "year".to_string()].into(),
+            },
+            SettingsWarning {
+                task_uid: 4,
+                attributes: ["rating".to_string()].into(),
+            },
+        ];
+        assert_eq!(warnings, expected);
+
+        // a reset removes every filterable attribute.
+        let reset = Settings {
+            filterable_attributes: Setting::Reset,
+            ..Default::default()
+        };
+        let warnings = settings_warnings("movies", &current, &reset, &pending);
+        let uids: Vec<_> = warnings.iter().map(|w| w.task_uid).collect();
+        assert_eq!(uids, [1, 2, 4]);
+
+        // an update leaving the filterable attributes as they are warns about nothing.
+        let warnings = settings_warnings("movies", &current, &Settings::default(), &pending);
+        assert!(warnings.is_empty());
+    }
+}
@@ -17,7 +17,7 @@ pub enum UpdateLoopError {
     #[error("Task `{0}` not found.")]
     UnexistingUpdate(u64),
     #[error("An internal error has occurred. `{0}`.")]
-    Internal(Box<dyn Error + Send + Sync + 'static>),
+    Internal(#[source] Box<dyn Error + Send + Sync + 'static>),
     #[error(
         "update store was shut down due to a fatal error, please check your logs for more info."
     )]
@@ -0,0 +1,540 @@
+//! Resumable document uploads.
+//!
+//! An upload session receives the payload of a document addition in several chunks, each of them
+//! sent with a `Content-Range`, so that a client can resume an interrupted upload from the last
+//! byte the server received instead of starting over. The chunks are appended to a file of the
+//! uploads directory, and the payload is only parsed once the session is committed, at which point
+//! it goes through the same path as a regular document addition.
+//!
+//! The sessions are only kept in memory: a session that is inactive for longer than the TTL is
+//! removed along with its file, and the files of the sessions of a previous run are removed on
+//! start. Only the files named after an upload are removed, the rest of the directory is left
+//! alone.
+
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_dir, remove_file, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, warn};
+use meilisearch_error::{internal_error, Code, ErrorCode};
+use parking_lot::Mutex;
+use serde::Serialize;
+use time::OffsetDateTime;
+use tokio::task::JoinError;
+use uuid::Uuid;
+
+use super::DocumentAdditionFormat;
+use crate::tasks::task::TaskId;
+
+const UPLOADS_PATH: &str = "updates/uploads";
+/// The longest pause between two collections of the expired sessions.
+const MAX_GC_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type Result<T> = std::result::Result<T, UploadError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    #[error("Upload `{0}` not found.")]
+    UploadNotFound(Uuid),
+    #[error("`{0}` is not a valid content range. The `Content-Range` header must be of the form `bytes <start>-<end>/<total>`, where `<total>` can be `*` if unknown.")]
+    InvalidContentRange(String),
+    #[error("The `Content-Range` header announces a chunk of {expected} bytes, but the chunk is {found} bytes long.")]
+    ChunkSizeMismatch { expected: u64, found: u64 },
+    #[error("The `Content-Range` header announces an upload of {found} bytes, but an upload of {expected} bytes was previously announced.")]
+    TotalSizeMismatch { expected: u64, found: u64 },
+    #[error("The chunk ends at byte {end}, past the upload of {total} bytes that was announced.")]
+    RangePastTotal { end: u64, total: u64 },
+    #[error("The chunk starts at byte {start}, but only {received} bytes of the upload were received. Resume the upload from byte {received}.")]
+    RangeMismatch { start: u64, received: u64 },
+    #[error("Upload `{0}` is already committed and can't receive any more chunks.")]
+    AlreadyCommitted(Uuid),
+    #[error("Upload `{id}` is incomplete: {received} bytes out of {total} were received.")]
+    IncompleteUpload { id: Uuid, received: u64, total: u64 },
+    #[error("An internal error has occurred. `{0}`.")]
+    Internal(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+internal_error!(UploadError: std::io::Error, JoinError);
+
+impl ErrorCode for UploadError {
+    fn error_code(&self) -> Code {
+        match self {
+            UploadError::UploadNotFound(_) => Code::UploadNotFound,
+            UploadError::InvalidContentRange(_)
+            | UploadError::ChunkSizeMismatch { .. }
+            | UploadError::TotalSizeMismatch { .. }
+            | UploadError::RangePastTotal { .. } => Code::InvalidContentRange,
+            UploadError::RangeMismatch { .. } => Code::UploadRangeMismatch,
+            UploadError::AlreadyCommitted(_) => Code::UploadAlreadyCommitted,
+            UploadError::IncompleteUpload { .. } => Code::IncompleteUpload,
+            UploadError::Internal(_) => Code::Internal,
+        }
+    }
+}
+
+/// The range of a chunk, as sent in the `Content-Range` header. Both bounds are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentRange {
+    pub start: u64,
+    pub end: u64,
+    /// The total size of the upload, if known by the client.
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    /// The size of the chunk, in bytes.
+    pub fn size(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+impl FromStr for ContentRange {
+    type Err = UploadError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || UploadError::InvalidContentRange(s.to_string());
+
+        let (range, total) = s
+            .trim()
+            .strip_prefix("bytes ")
+            .and_then(|rest| rest.split_once('/'))
+            .ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let start: u64 = start.trim().parse().map_err(|_| invalid())?;
+        let end: u64 = end.trim().parse().map_err(|_| invalid())?;
+        let total = match total.trim() {
+            "*" => None,
+            total => Some(total.parse::<u64>().map_err(|_| invalid())?),
+        };
+
+        if end < start || total.map_or(false, |total| end >= total) {
+            return Err(invalid());
+        }
+
+        Ok(Self { start, end, total })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadSessionView {
+    pub upload_id: Uuid,
+    pub index_uid: String,
+    /// The number of bytes received so far, which is also where the next chunk must start.
+    pub received: u64,
+    pub total: Option<u64>,
+    #[serde(serialize_with = "time::serde::rfc3339::serialize")]
+    pub expires_at: OffsetDateTime,
+}
+
+pub struct UploadSession {
+    pub id: Uuid,
+    pub index_uid: String,
+    pub format: DocumentAdditionFormat,
+    pub primary_key: Option<String>,
+    pub allow_index_creation: bool,
+    received: u64,
+    total: Option<u64>,
+    /// Shared with the entry of the session, see [`SessionEntry`].
+    expires_at: Arc<Mutex<OffsetDateTime>>,
+    /// The document addition task registered when the session was committed.
+    task_id: Option<TaskId>,
+}
+
+impl UploadSession {
+    pub fn view(&self) -> UploadSessionView {
+        UploadSessionView {
+            upload_id: self.id,
+            index_uid: self.index_uid.clone(),
+            received: self.received,
+            total: self.total,
+            expires_at: *self.expires_at.lock(),
+        }
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn task_id(&self) -> Option<TaskId> {
+        self.task_id
+    }
+
+    /// Checks that every byte announced by the client was received.
+    pub fn check_complete(&self) -> Result<()> {
+        match self.total {
+            Some(total) if total != self.received => Err(UploadError::IncompleteUpload {
+                id: self.id,
+                received: self.received,
+                total,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the part of `chunk` that must be appended to the upload, if any. A chunk that was
+    /// already received, entirely or partly, is accepted, so that a client can safely resend a
+    /// chunk it didn't get an answer for.
+    fn new_bytes<'a>(&self, range: &ContentRange, chunk: &'a [u8]) -> Result<&'a [u8]> {
+        if self.task_id.is_some() {
+            return Err(UploadError::AlreadyCommitted(self.id));
+        }
+        if chunk.len() as u64 != range.size() {
+            return Err(UploadError::ChunkSizeMismatch {
+                expected: range.size(),
+                found: chunk.len() as u64,
+            });
+        }
+        if let (Some(found), Some(expected)) = (range.total, self.total) {
+            if found != expected {
+                return Err(UploadError::TotalSizeMismatch { expected, found });
+            }
+        }
+        if range.start > self.received {
+            return Err(UploadError::RangeMismatch {
+                start: range.start,
+                received: self.received,
+            });
+        }
+        if let Some(total) = range.total.or(self.total) {
+            if range.end >= total {
+                return Err(UploadError::RangePastTotal {
+                    end: range.end,
+                    total,
+                });
+            }
+        }
+
+        let already_received = (self.received - range.start).min(range.size()) as usize;
+        Ok(&chunk[already_received..])
+    }
+}
+
+/// A session, along with what is checked before handing it out. They are kept out of the lock of
+/// the session, so that they are checked even while the session is in use.
+struct SessionEntry {
+    index_uid: String,
+    expires_at: Arc<Mutex<OffsetDateTime>>,
+    session: Arc<tokio::sync::Mutex<UploadSession>>,
+}
+
+/// The upload sessions in progress.
+#[derive(Clone)]
+pub struct UploadSessions {
+    path: PathBuf,
+    ttl: Duration,
+    sessions: Arc<Mutex<HashMap<Uuid, SessionEntry>>>,
+}
+
+impl UploadSessions {
+    /// Creates the uploads directory, removing the files of the sessions of a previous run.
+    pub fn new(db_path: impl AsRef<Path>, ttl: Duration) -> Result<Self> {
+        let path = db_path.as_ref().join(UPLOADS_PATH);
+        create_dir_all(&path)?;
+        for entry in read_dir(&path)? {
+            let entry = entry?;
+            let is_upload = entry.file_type()?.is_file()
+                && entry
+                    .file_name()
+                    .to_str()
+                    .map_or(false, |name| Uuid::parse_str(name).is_ok());
+            if is_upload {
+                if let Err(e) = remove_file(entry.path()) {
+                    warn!(
+                        "Could not remove the payload {:?} of a previous run: {}",
+                        entry.path(),
+                        e
+                    );
+                }
+            }
+        }
+
+        Ok(Self {
+            path,
+            ttl,
+            sessions: Default::default(),
+        })
+    }
+
+    fn expires_at(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc() + self.ttl
+    }
+
+    pub fn data_path(&self, id: Uuid) -> PathBuf {
+        self.path.join(id.to_string())
+    }
+
+    pub fn create(
+        &self,
+        index_uid: String,
+        format: DocumentAdditionFormat,
+        primary_key: Option<String>,
+        allow_index_creation: bool,
+    ) -> Result<UploadSessionView> {
+        let id = Uuid::new_v4();
+        File::create(self.data_path(id))?;
+
+        let expires_at = Arc::new(Mutex::new(self.expires_at()));
+        let session = UploadSession {
+            id,
+            index_uid: index_uid.clone(),
+            format,
+            primary_key,
+            allow_index_creation,
+            received: 0,
+            total: None,
+            expires_at: expires_at.clone(),
+            task_id: None,
+        };
+        let view = session.view();
+        let entry = SessionEntry {
+            index_uid,
+            expires_at,
+            session: Arc::new(tokio::sync::Mutex::new(session)),
+        };
+        self.sessions.lock().insert(id, entry);
+
+        Ok(view)
+    }
+
+    /// Returns the session `id` of the index `index_uid`, unless it expired.
+    pub fn get(&self, index_uid: &str, id: Uuid) -> Result<Arc<tokio::sync::Mutex<UploadSession>>> {
+        let sessions = self.sessions.lock();
+        match sessions.get(&id) {
+            Some(entry)
+                if entry.index_uid == index_uid
+                    && *entry.expires_at.lock() > OffsetDateTime::now_utc() =>
+            {
+                Ok(entry.session.clone())
+            }
+            _ => Err(UploadError::UploadNotFound(id)),
+        }
+    }
+
+    /// Appends the chunk to the upload, and extends the expiration of the session.
+    pub async fn append(
+        &self,
+        session: &mut UploadSession,
+        range: ContentRange,
+        chunk: Vec<u8>,
+    ) -> Result<UploadSessionView> {
+        let new_bytes = session.new_bytes(&range, &chunk)?.len();
+        if new_bytes > 0 {
+            let path = self.data_path(session.id);
+            tokio::task::spawn_blocking(move || -> Result<()> {
+                let mut file = OpenOptions::new().append(true).open(path)?;
+                file.write_all(&chunk[chunk.len() - new_bytes..])?;
+                file.sync_data()?;
+                Ok(())
+            })
+            .await??;
+            session.received += new_bytes as u64;
+        }
+
+        if range.total.is_some() {
+            session.total = range.total;
+        }
+        *session.expires_at.lock() = self.expires_at();
+
+        Ok(session.view())
+    }
+
+    /// Marks the session as committed, and removes the uploaded payload, that was converted to an
+    /// update file. The session itself is kept until it expires, so that committing it again
+    /// returns the same task.
+    pub fn committed(&self, session: &mut UploadSession, task_id: TaskId) {
+        session.task_id = Some(task_id);
+        *session.expires_at.lock() = self.expires_at();
+        if let Err(e) = std::fs::remove_file(self.data_path(session.id)) {
+            warn!(
+                "Could not remove the payload of upload `{}`: {}",
+                session.id, e
+            );
+        }
+    }
+
+    /// Removes the expired sessions along with their files.
+    pub fn remove_expired(&self) {
+        let now = OffsetDateTime::now_utc();
+        let mut sessions = self.sessions.lock();
+        let expired: Vec<Uuid> = sessions
+            .iter()
+            // a session that is in use is removed once it is released.
+            .filter(|(_, entry)| {
+                *entry.expires_at.lock() <= now && entry.session.try_lock().is_ok()
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            sessions.remove(&id);
+            let path = self.data_path(id);
+            if path.exists() {
+                if let Err(e) = std::fs::remove_file(path) {
+                    warn!("Could not remove the payload of upload `{}`: {}", id, e);
+                }
+            }
+            debug!("Upload `{}` expired.", id);
+        }
+    }
+
+    /// Periodically removes the expired sessions.
+    pub async fn run_gc(self) {
+        let interval = self.ttl.min(MAX_GC_INTERVAL);
+        loop {
+            tokio::time::sleep(interval).await;
+            self.remove_expired();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            "bytes 0-99/200".parse::<ContentRange>().unwrap(),
+            ContentRange {
+                start: 0,
+                end: 99,
+                total: Some(200)
+            }
+        );
+        assert_eq!(
+            "bytes 100-199/*".parse::<ContentRange>().unwrap().total,
+            None
+        );
+        assert_eq!(
+            "bytes 100-199/*".parse::<ContentRange>().unwrap().size(),
+            100
+        );
+
+        for invalid in [
+            "",
+            "0-99/200",
+            "bytes 0-99",
+            "bytes 99-0/200",
+            "bytes 0-200/200",
+            "bytes a-b/*",
+            "items 0-99/*",
+        ] {
+            assert!(invalid.parse::<ContentRange>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_append_chunks_idempotently() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = UploadSessions::new(dir.path(), Duration::from_secs(60)).unwrap();
+        let view = sessions
+            .create("movies".into(), DocumentAdditionFormat::Ndjson, None, true)
+            .unwrap();
+        let session = sessions.get("movies", view.upload_id).unwrap();
+        assert!(sessions.get("books", view.upload_id).is_err());
+        let mut session = session.lock().await;
+
+        let range = |s: &str| s.parse::<ContentRange>().unwrap();
+        sessions
+            .append(&mut session, range("bytes 0-4/10"), b"hello".to_vec())
+            .await
+            .unwrap();
+        // the same chunk is sent again.
+        sessions
+            .append(&mut session, range("bytes 0-4/10"), b"hello".to_vec())
+            .await
+            .unwrap();
+        assert!(session.check_complete().is_err());
+        // a chunk overlapping the received bytes.
+        let view = sessions
+            .append(&mut session, range("bytes 3-9/10"), b"loworld".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(view.received, 10);
+        session.check_complete().unwrap();
+
+        let err = sessions
+            .append(&mut session, range("bytes 12-13/*"), b"!!".to_vec())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            UploadError::RangeMismatch {
+                start: 12,
+                received: 10
+            }
+        ));
+
+        // a chunk can't end past the announced total, even when it doesn't repeat it.
+        let err = sessions
+            .append(&mut session, range("bytes 10-11/*"), b"!!".to_vec())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            UploadError::RangePastTotal { end: 11, total: 10 }
+        ));
+
+        let content = std::fs::read(sessions.data_path(session.id)).unwrap();
+        assert_eq!(content, b"helloworld");
+
+        sessions.committed(&mut session, 3);
+        assert_eq!(session.task_id(), Some(3));
+        assert!(!sessions.data_path(session.id).exists());
+        assert!(matches!(
+            sessions
+                .append(&mut session, range("bytes 10-11/*"), b"!!".to_vec())
+                .await,
+            Err(UploadError::AlreadyCommitted(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_expired_sessions_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = UploadSessions::new(dir.path(), Duration::ZERO).unwrap();
+        let view = sessions
+            .create("movies".into(), DocumentAdditionFormat::Json, None, true)
+            .unwrap();
+        assert!(sessions.data_path(view.upload_id).exists());
+
+        assert!(sessions.get("movies", view.upload_id).is_err());
+        sessions.remove_expired();
+        assert!(!sessions.data_path(view.upload_id).exists());
+    }
+
+    #[actix_rt::test]
+    async fn test_session_in_use_is_scoped_to_its_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = UploadSessions::new(dir.path(), Duration::from_secs(60)).unwrap();
+        let view = sessions
+            .create("movies".into(), DocumentAdditionFormat::Json, None, true)
+            .unwrap();
+
+        let session = sessions.get("movies", view.upload_id).unwrap();
+        let _in_use = session.lock().await;
+        assert!(matches!(
+            sessions.get("books", view.upload_id),
+            Err(UploadError::UploadNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_only_the_payloads_of_a_previous_run_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(UPLOADS_PATH);
+        create_dir_all(&path).unwrap();
+        let payload = path.join(Uuid::new_v4().to_string());
+        File::create(&payload).unwrap();
+        File::create(path.join("keep")).unwrap();
+
+        UploadSessions::new(dir.path(), Duration::from_secs(60)).unwrap();
+        assert!(!payload.exists());
+        assert!(path.join("keep").exists());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::tasks::task::TaskId;
+
 #[derive(thiserror::Error, Debug)]
 pub enum VersionFileError {
     #[error(
@@ -16,4 +18,9 @@ pub enum VersionFileError {
         minor: String,
         patch: String,
     },
+    #[error(
+        "Task `{task_id}` was registered by Meilisearch v{version}, which is newer than the current engine version: {}. Start Meilisearch v{version} or newer to process it, or use `--ignore-version-mismatch` to process it with the current version anyway.",
+        env!("CARGO_PKG_VERSION").to_string()
+    )]
+    NewerTask { task_id: TaskId, version: String },
 }
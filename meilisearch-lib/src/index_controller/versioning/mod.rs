@@ -3,6 +3,7 @@ use std::io::ErrorKind;
 use std::path::Path;
 
 use self::error::VersionFileError;
+use crate::tasks::task::Task;
 
 mod error;
 
@@ -54,3 +55,65 @@ pub fn check_version_file(db_path: &Path) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Ensures that none of the unfinished `tasks` was registered by a newer version of Meilisearch,
+/// whose task contents this engine could misinterpret.
+pub fn check_tasks_version(tasks: &[Task]) -> anyhow::Result<()> {
+    let newer = tasks.iter().find_map(|task| {
+        let version = task.engine_version.as_deref()?;
+        is_newer_version(version).then(|| (task.id, version))
+    });
+    match newer {
+        Some((task_id, version)) => Err(VersionFileError::NewerTask {
+            task_id,
+            version: version.to_string(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Whether `version`, as `major.minor.patch` with an optional pre-release suffix, is newer than
+/// the version of this engine. A malformed version is not newer.
+fn is_newer_version(version: &str) -> bool {
+    fn parse(version: &str) -> Option<(u64, u64, u64)> {
+        let version = version.split('-').next()?;
+        let mut components = version.split('.').map(|c| c.parse().ok());
+        let version = (
+            components.next()??,
+            components.next()??,
+            components.next()??,
+        );
+        components.next().is_none().then(|| version)
+    }
+
+    let current = parse(env!("CARGO_PKG_VERSION"));
+    match (parse(version), current) {
+        (Some(version), Some(current)) => version > current,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_newer_version() {
+        let current = format!("{}.{}.{}", VERSION_MAJOR, VERSION_MINOR, VERSION_PATCH);
+        assert!(!is_newer_version(&current));
+        assert!(is_newer_version(&format!(
+            "{}.{}.{}",
+            VERSION_MAJOR,
+            VERSION_MINOR,
+            VERSION_PATCH.parse::<u64>().unwrap() + 1
+        )));
+        assert!(is_newer_version(&format!(
+            "{}.0.0-rc.1",
+            VERSION_MAJOR.parse::<u64>().unwrap() + 1
+        )));
+        assert!(!is_newer_version("0.0.1"));
+        assert!(!is_newer_version("not a version"));
+        assert!(!is_newer_version("999.0"));
+    }
+}
@@ -0,0 +1,293 @@
+//! The advisory locks of the indexes.
+//!
+//! An operation needing exclusive access to an index for a while, like its copy by a snapshot,
+//! locks it for the time it expects to need. While an index is locked, its searches are refused
+//! with the time left before the lock is expected to be released, and the scheduler leaves the
+//! tasks of the index in the queue, like those of a closed index, until it is notified of the
+//! release. A lock is only held until its deadline: the lock of an operation that never releases
+//! it, because it hangs or vanished, is forcibly released, and the error is logged.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::error;
+use parking_lot::{Condvar, Mutex};
+
+use crate::tasks::notifier::NotificationSender;
+
+/// The time a lock can be held past the time its holder expected to need it.
+const LOCK_GRACE_PERIOD: Duration = Duration::from_secs(30);
+/// The maximum time waited for the release of the lock of another holder.
+const MAX_ACQUIRE_WAIT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Index `{index_uid}` could not be locked within {waited}s, it is still locked by a {holder}."
+)]
+pub struct IndexLockTimeout {
+    index_uid: String,
+    holder: &'static str,
+    waited: u64,
+}
+
+struct Lock {
+    id: u64,
+    /// The operation holding the lock.
+    holder: &'static str,
+    acquired_at: Instant,
+    /// When the holder expects to release the lock.
+    expected_release: Instant,
+    /// When the lock is released, whether its holder released it or not.
+    deadline: Instant,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// The locks, by index uid.
+    locks: Mutex<HashMap<String, Lock>>,
+    next_id: AtomicU64,
+    /// Wakes the operations waiting for a lock up once a lock is released.
+    released: Condvar,
+    /// Notifies the scheduler that the tasks of an index can be processed again.
+    scheduler: Mutex<Option<NotificationSender>>,
+}
+
+#[derive(Clone, Default)]
+pub struct IndexLocks {
+    inner: Arc<Inner>,
+}
+
+impl IndexLocks {
+    /// Sends a notification with `sender` whenever a lock is released.
+    pub(crate) fn notify_releases_to(&self, sender: NotificationSender) {
+        *self.inner.scheduler.lock() = Some(sender);
+    }
+
+    /// Locks `index_uid` on behalf of `holder`, which expects to need it for `expected`. Waits
+    /// for the release of the lock of another holder for at most `MAX_ACQUIRE_WAIT`.
+    pub fn acquire(
+        &self,
+        index_uid: &str,
+        holder: &'static str,
+        expected: Duration,
+    ) -> Result<IndexLockGuard, IndexLockTimeout> {
+        self.acquire_within(index_uid, holder, expected, MAX_ACQUIRE_WAIT)
+    }
+
+    fn acquire_within(
+        &self,
+        index_uid: &str,
+        holder: &'static str,
+        expected: Duration,
+        max_wait: Duration,
+    ) -> Result<IndexLockGuard, IndexLockTimeout> {
+        let started = Instant::now();
+        let give_up_at = started + max_wait;
+        let mut locks = self.inner.locks.lock();
+        loop {
+            let now = Instant::now();
+            self.release_expired(&mut locks, now);
+            let wait_until = match locks.get(index_uid) {
+                None => break,
+                Some(lock) if now >= give_up_at => {
+                    return Err(IndexLockTimeout {
+                        index_uid: index_uid.to_string(),
+                        holder: lock.holder,
+                        waited: max_wait.as_secs(),
+                    })
+                }
+                Some(lock) => lock.deadline.min(give_up_at),
+            };
+            self.inner.released.wait_until(&mut locks, wait_until);
+        }
+
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        locks.insert(
+            index_uid.to_string(),
+            Lock {
+                id,
+                holder,
+                acquired_at: now,
+                expected_release: now + expected,
+                deadline: now + expected * 2 + LOCK_GRACE_PERIOD,
+            },
+        );
+
+        Ok(IndexLockGuard {
+            locks: self.clone(),
+            index_uid: index_uid.to_string(),
+            id,
+        })
+    }
+
+    /// The time left before the lock of `index_uid` is expected to be released, if it is locked.
+    pub fn remaining(&self, index_uid: &str) -> Option<Duration> {
+        let mut locks = self.inner.locks.lock();
+        let now = Instant::now();
+        self.release_expired(&mut locks, now);
+        locks
+            .get(index_uid)
+            .map(|lock| lock.expected_release.saturating_duration_since(now))
+    }
+
+    pub fn is_locked(&self, index_uid: &str) -> bool {
+        self.remaining(index_uid).is_some()
+    }
+
+    /// The first deadline of the locks, when a lock is released even if its holder didn't
+    /// release it.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.inner
+            .locks
+            .lock()
+            .values()
+            .map(|lock| lock.deadline)
+            .min()
+    }
+
+    /// Forcibly releases the locks past their deadline.
+    fn release_expired(&self, locks: &mut HashMap<String, Lock>, now: Instant) {
+        let count = locks.len();
+        locks.retain(|index_uid, lock| {
+            let expired = lock.deadline <= now;
+            if expired {
+                error!(
+                    "The lock of index `{}` taken by a {} {}s ago was not released before its deadline, it is forcibly released.",
+                    index_uid,
+                    lock.holder,
+                    now.duration_since(lock.acquired_at).as_secs(),
+                );
+            }
+            !expired
+        });
+        if locks.len() < count {
+            self.released();
+        }
+    }
+
+    fn released(&self) {
+        self.inner.released.notify_all();
+        if let Some(ref scheduler) = *self.inner.scheduler.lock() {
+            scheduler.notify();
+        }
+    }
+}
+
+impl fmt::Debug for IndexLocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.inner.locks.lock().keys())
+            .finish()
+    }
+}
+
+/// Releases the lock of an index once dropped.
+#[must_use]
+pub struct IndexLockGuard {
+    locks: IndexLocks,
+    index_uid: String,
+    id: u64,
+}
+
+impl Drop for IndexLockGuard {
+    fn drop(&mut self) {
+        let mut locks = self.locks.inner.locks.lock();
+        // the lock was forcibly released if it is gone, or taken again by someone else.
+        if locks
+            .get(&self.index_uid)
+            .map_or(false, |lock| lock.id == self.id)
+        {
+            locks.remove(&self.index_uid);
+            self.locks.released();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_released_on_drop() {
+        let locks = IndexLocks::default();
+        let guard = locks
+            .acquire("test", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        assert!(locks.is_locked("test"));
+        assert!(!locks.is_locked("other"));
+        let remaining = locks.remaining("test").unwrap();
+        assert!(remaining > Duration::from_secs(9) && remaining <= Duration::from_secs(10));
+
+        drop(guard);
+        assert!(!locks.is_locked("test"));
+        assert_eq!(locks.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_acquire_gives_up_on_a_held_lock() {
+        let locks = IndexLocks::default();
+        let _guard = locks
+            .acquire("test", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        let error = locks
+            .acquire_within(
+                "test",
+                "snapshot",
+                Duration::from_secs(10),
+                Duration::from_millis(10),
+            )
+            .unwrap_err();
+        assert_eq!(error.holder, "snapshot");
+    }
+
+    #[test]
+    fn test_acquire_waits_for_the_release() {
+        let locks = IndexLocks::default();
+        let guard = locks
+            .acquire("test", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            drop(guard);
+        });
+        let _guard = locks
+            .acquire("test", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        release.join().unwrap();
+    }
+
+    #[test]
+    fn test_expired_lock_is_forcibly_released() {
+        let locks = IndexLocks::default();
+        let guard = locks
+            .acquire("test", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        // the holder vanished long ago.
+        locks.inner.locks.lock().get_mut("test").unwrap().deadline = Instant::now();
+        assert!(!locks.is_locked("test"));
+
+        // the release of the expired lock doesn't release the lock taken since.
+        let _new = locks
+            .acquire("test", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        drop(guard);
+        assert!(locks.is_locked("test"));
+    }
+
+    #[actix_rt::test]
+    async fn test_release_notifies_the_scheduler() {
+        let (notifier, mut receiver) = crate::tasks::notifier::channel();
+        let locks = IndexLocks::default();
+        locks.notify_releases_to(notifier.sender());
+
+        let guard = locks
+            .acquire("test", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        drop(guard);
+        assert_eq!(receiver.changed().await, Some(1));
+    }
+}
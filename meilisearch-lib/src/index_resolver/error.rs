@@ -5,6 +5,7 @@ use tokio::sync::mpsc::error::SendError as MpscSendError;
 use tokio::sync::oneshot::error::RecvError as OneshotRecvError;
 use uuid::Uuid;
 
+use super::fetch::FetchError;
 use crate::{error::MilliError, index::error::IndexError};
 
 pub type Result<T> = std::result::Result<T, IndexResolverError>;
@@ -17,16 +18,71 @@ pub enum IndexResolverError {
     IndexAlreadyExists(String),
     #[error("Index `{0}` not found.")]
     UnexistingIndex(String),
+    #[error("Index `{0}` is closed. Open it before using it.")]
+    IndexClosed(String),
+    #[error("Namespace `{0}` already exists.")]
+    NamespaceAlreadyExists(String),
+    #[error("Namespace `{0}` not found.")]
+    UnexistingNamespace(String),
+    #[error("The default namespace cannot be deleted.")]
+    DefaultNamespaceDeletion,
     #[error("A primary key is already present. It's impossible to update it")]
     ExistingPrimaryKey,
+    #[error("Not enough disk space to rebuild the index: {required} bytes are required, but only {available} bytes are available.")]
+    NotEnoughSpaceToRebuild { required: u64, available: u64 },
+    #[error("Not enough disk space to preview the settings: {required} bytes are required, but only {available} bytes are available.")]
+    NotEnoughSpaceToPreview { required: u64, available: u64 },
+    #[error("The database of the indexes is being compacted. Retry later.")]
+    DatabaseUnavailable,
+    #[error("Chunk {chunk} (numbered from 0) of the {chunks} chunks of the document addition could not be indexed: {source}")]
+    ChunkFailed {
+        chunk: usize,
+        chunks: usize,
+        source: Box<IndexResolverError>,
+    },
     #[error("An internal error has occurred. `{0}`.")]
-    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+    Internal(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
     #[error("The creation of the `{0}` index has failed due to `Index uuid is already assigned`.")]
     UuidAlreadyExists(Uuid),
     #[error("{0}")]
     Milli(#[from] milli::Error),
-    #[error("`{0}` is not a valid index uid. Index uid can be an integer or a string containing only alphanumeric characters, hyphens (-) and underscores (_).")]
-    BadlyFormatted(String),
+    #[error("{0}")]
+    Fetch(#[from] FetchError),
+    #[error("`{uid}` is not a valid index uid: {reason}. Index uid must be between 1 and 400 bytes long, and can only contain the characters `a-z`, `A-Z`, `0-9`, `-` and `_`.")]
+    BadlyFormatted {
+        uid: String,
+        reason: InvalidIndexUid,
+    },
+    #[error("`{name}` is not a valid namespace: {reason}. A namespace must be between 1 and 400 bytes long, and can only contain the characters `a-z`, `A-Z`, `0-9`, `-` and `_`.")]
+    BadlyFormattedNamespace {
+        name: String,
+        reason: InvalidIndexUid,
+    },
+}
+
+/// Why an index uid was refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidIndexUid {
+    Empty,
+    TooLong(usize),
+    /// A forbidden character, and its position in the uid, in characters.
+    InvalidChar(char, usize),
+}
+
+impl fmt::Display for InvalidIndexUid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidIndexUid::Empty => write!(f, "it is empty"),
+            InvalidIndexUid::TooLong(len) => write!(f, "it is {} bytes long", len),
+            InvalidIndexUid::InvalidChar(c, position) => {
+                write!(
+                    f,
+                    "the character {:?} at position {} is not allowed",
+                    c, position
+                )
+            }
+        }
+    }
 }
 
 impl<T> From<MpscSendError<T>> for IndexResolverError
@@ -44,9 +100,15 @@ impl From<OneshotRecvError> for IndexResolverError {
     }
 }
 
+/// The heed errors are the ones of the index, whose reader table can be full.
+impl From<milli::heed::Error> for IndexResolverError {
+    fn from(other: milli::heed::Error) -> Self {
+        Self::IndexError(other.into())
+    }
+}
+
 internal_error!(
-    IndexResolverError: milli::heed::Error,
-    uuid::Error,
+    IndexResolverError: uuid::Error,
     std::io::Error,
     tokio::task::JoinError,
     serde_json::Error
@@ -58,11 +120,21 @@ impl ErrorCode for IndexResolverError {
             IndexResolverError::IndexError(e) => e.error_code(),
             IndexResolverError::IndexAlreadyExists(_) => Code::IndexAlreadyExists,
             IndexResolverError::UnexistingIndex(_) => Code::IndexNotFound,
+            IndexResolverError::IndexClosed(_) => Code::IndexClosed,
+            IndexResolverError::NamespaceAlreadyExists(_) => Code::NamespaceAlreadyExists,
+            IndexResolverError::UnexistingNamespace(_) => Code::NamespaceNotFound,
+            IndexResolverError::DefaultNamespaceDeletion => Code::InvalidNamespace,
             IndexResolverError::ExistingPrimaryKey => Code::PrimaryKeyAlreadyPresent,
+            IndexResolverError::NotEnoughSpaceToRebuild { .. }
+            | IndexResolverError::NotEnoughSpaceToPreview { .. } => Code::NoSpaceLeftOnDevice,
+            IndexResolverError::DatabaseUnavailable => Code::DatabaseUnavailable,
+            IndexResolverError::ChunkFailed { source, .. } => source.error_code(),
             IndexResolverError::Internal(_) => Code::Internal,
             IndexResolverError::UuidAlreadyExists(_) => Code::CreateIndex,
             IndexResolverError::Milli(e) => MilliError(e).error_code(),
-            IndexResolverError::BadlyFormatted(_) => Code::InvalidIndexUid,
+            IndexResolverError::BadlyFormatted { .. } => Code::InvalidIndexUid,
+            IndexResolverError::BadlyFormattedNamespace { .. } => Code::InvalidNamespace,
+            IndexResolverError::Fetch(e) => e.error_code(),
         }
     }
 }
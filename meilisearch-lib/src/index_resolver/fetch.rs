@@ -0,0 +1,388 @@
+//! The download of the documents of the tasks importing documents from a URL.
+//!
+//! The URL is given by the client, so the fetcher refuses to reach the addresses of the network
+//! of the server: the host is resolved, and the loopback, private, link-local and other reserved
+//! addresses are refused, unless the host is one of the `--fetch-allowed-hosts`. The request is
+//! sent to the address that was checked, and the redirections are followed one at a time, each
+//! of them checked the same way.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufReader, Seek, SeekFrom, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use meilisearch_error::{internal_error, Code, ErrorCode};
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+use tokio::task::{spawn_blocking, JoinError};
+use uuid::Uuid;
+
+use crate::document_formats::{read_csv, read_json, read_ndjson, DocumentFormatError};
+use crate::index_controller::DocumentAdditionFormat;
+use crate::options::IndexerOpts;
+use crate::update_file_store::{UpdateFileStore, UpdateFileStoreError};
+
+/// Redirections beyond this number are refused.
+const MAX_REDIRECTS: usize = 5;
+
+pub type Result<T> = std::result::Result<T, FetchError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+    #[error("Fetching the documents failed: {0}.")]
+    Request(#[from] reqwest::Error),
+    #[error("`{0}` is not a valid URL to fetch documents from. Only the `http` and `https` URLs are accepted.")]
+    InvalidUrl(String),
+    #[error("Fetching the documents from `{url}` is not allowed: `{host}` is not a public address. Add the host to `--fetch-allowed-hosts` to allow it.")]
+    ForbiddenHost { url: String, host: String },
+    #[error("Fetching the documents from `{url}` failed: the host could not be resolved.")]
+    UnresolvedHost { url: String },
+    #[error(
+        "Fetching the documents failed: more than {} redirections were followed from `{url}`.",
+        MAX_REDIRECTS
+    )]
+    TooManyRedirects { url: String },
+    #[error(
+        "Fetching the documents from `{url}` failed: the server responded with status `{status}`."
+    )]
+    Status { url: String, status: StatusCode },
+    #[error("The documents at `{url}` are larger than the limit of {max_size} bytes.")]
+    TooLarge { url: String, max_size: u64 },
+    #[error("The checksum of the documents fetched from `{url}` is `{found}`, but `{expected}` was expected.")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        found: String,
+    },
+    #[error("{0}")]
+    DocumentFormat(#[from] DocumentFormatError),
+    #[error("An internal error has occurred. `{0}`.")]
+    Internal(#[source] Box<dyn Error + Send + Sync + 'static>),
+}
+
+internal_error!(FetchError: std::io::Error, UpdateFileStoreError, JoinError);
+
+impl ErrorCode for FetchError {
+    fn error_code(&self) -> Code {
+        match self {
+            FetchError::InvalidUrl(_) | FetchError::ForbiddenHost { .. } => {
+                Code::InvalidDocumentFetchUrl
+            }
+            FetchError::Request(_)
+            | FetchError::UnresolvedHost { .. }
+            | FetchError::TooManyRedirects { .. }
+            | FetchError::Status { .. }
+            | FetchError::ChecksumMismatch { .. } => Code::DocumentFetchFailed,
+            FetchError::TooLarge { .. } => Code::PayloadTooLarge,
+            FetchError::DocumentFormat(e) => e.error_code(),
+            FetchError::Internal(_) => Code::Internal,
+        }
+    }
+}
+
+/// Downloads the documents of the tasks importing documents from a URL.
+#[derive(Clone)]
+pub struct DocumentFetcher {
+    max_size: u64,
+    timeout: Duration,
+    /// The hosts whose addresses are not checked, in lowercase.
+    allowed_hosts: Arc<HashSet<String>>,
+}
+
+/// A fetcher with the default limits of the indexer options.
+impl Default for DocumentFetcher {
+    fn default() -> Self {
+        let opts = IndexerOpts::default();
+        Self::new(
+            opts.max_fetch_size.get_bytes() as u64,
+            Duration::from_secs(opts.fetch_timeout_sec),
+            opts.fetch_allowed_hosts,
+        )
+    }
+}
+
+impl DocumentFetcher {
+    pub fn new(
+        max_size: u64,
+        timeout: Duration,
+        allowed_hosts: impl IntoIterator<Item = String>,
+    ) -> Self {
+        let allowed_hosts = allowed_hosts
+            .into_iter()
+            .map(|host| host.trim().to_lowercase())
+            .collect();
+
+        Self {
+            max_size,
+            timeout,
+            allowed_hosts: Arc::new(allowed_hosts),
+        }
+    }
+
+    /// Checks `url`, and returns its host along with the address to send the request to.
+    async fn resolve(&self, url: &Url) -> Result<(String, SocketAddr)> {
+        let invalid = || FetchError::InvalidUrl(url.to_string());
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(invalid());
+        }
+        let host = url.host_str().ok_or_else(invalid)?;
+        let port = url.port_or_known_default().ok_or_else(invalid)?;
+        // the brackets of an IPv6 address are not part of it.
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|_| FetchError::UnresolvedHost {
+                    url: url.to_string(),
+                })?
+                .collect(),
+        };
+
+        let allowed = self.allowed_hosts.contains(&host.to_lowercase());
+        // a host with one reserved address among public ones is refused too, since any of them
+        // could be the one that is reached.
+        if !allowed {
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(FetchError::ForbiddenHost {
+                    url: url.to_string(),
+                    host: format!("{} ({})", host, addr.ip()),
+                });
+            }
+        }
+
+        match addrs.first() {
+            Some(addr) => Ok((host.to_string(), *addr)),
+            None => Err(FetchError::UnresolvedHost {
+                url: url.to_string(),
+            }),
+        }
+    }
+
+    /// Sends a request to `url`, following the redirections after checking each of them.
+    async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        let mut url = Url::parse(url).map_err(|_| FetchError::InvalidUrl(url.to_string()))?;
+        for _ in 0..=MAX_REDIRECTS {
+            let (host, addr) = self.resolve(&url).await?;
+            // the client only reaches the address that was checked, a second resolution of the
+            // host could give another one.
+            let client = reqwest::Client::builder()
+                .timeout(self.timeout)
+                .redirect(Policy::none())
+                .resolve(&host, addr)
+                .build()?;
+            let response = client.get(url.clone()).send().await?;
+
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok());
+            match location {
+                Some(location) if response.status().is_redirection() => {
+                    url = url
+                        .join(location)
+                        .map_err(|_| FetchError::InvalidUrl(location.to_string()))?;
+                }
+                _ => return Ok(response),
+            }
+        }
+
+        Err(FetchError::TooManyRedirects {
+            url: url.to_string(),
+        })
+    }
+
+    /// Downloads the documents at `url`, and writes them to a new update file. Returns the uuid of
+    /// the update file along with the number of documents it contains.
+    ///
+    /// The raw documents are downloaded to a temporary file in the update files directory, that
+    /// is removed once the documents are converted, or as soon as anything goes wrong.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        format: DocumentAdditionFormat,
+        checksum: Option<&str>,
+        file_store: &UpdateFileStore,
+    ) -> Result<(Uuid, usize)> {
+        let mut response = self.get(url).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(FetchError::Status {
+                url: url.to_string(),
+                status,
+            });
+        }
+
+        let too_large = || FetchError::TooLarge {
+            url: url.to_string(),
+            max_size: self.max_size,
+        };
+        if response
+            .content_length()
+            .map_or(false, |len| len > self.max_size)
+        {
+            return Err(too_large());
+        }
+
+        let mut download = file_store.new_download()?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        while let Some(chunk) = response.chunk().await? {
+            // the content length announced by the server can't be trusted.
+            size += chunk.len() as u64;
+            if size > self.max_size {
+                return Err(too_large());
+            }
+            hasher.update(&chunk);
+            download.write_all(&chunk)?;
+        }
+
+        if let Some(expected) = checksum {
+            let found = hex::encode(hasher.finalize());
+            if !found.eq_ignore_ascii_case(expected) {
+                return Err(FetchError::ChecksumMismatch {
+                    url: url.to_string(),
+                    expected: expected.to_string(),
+                    found,
+                });
+            }
+        }
+
+        let file_store = file_store.clone();
+        spawn_blocking(move || -> Result<_> {
+            download.seek(SeekFrom::Start(0))?;
+            let reader = BufReader::new(download);
+            let (uuid, mut update_file) = file_store.new_update()?;
+            let count = match format {
+                DocumentAdditionFormat::Json => read_json(reader, &mut *update_file)?,
+                DocumentAdditionFormat::Csv => read_csv(reader, &mut *update_file)?,
+                DocumentAdditionFormat::Ndjson => read_ndjson(reader, &mut *update_file)?,
+            };
+            update_file.persist()?;
+
+            Ok((uuid, count))
+        })
+        .await?
+    }
+}
+
+/// Whether `ip` is a public address, that a fetch can reach without being allowed.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "this network", 0.0.0.0/8.
+        || a == 0
+        // the shared address space of the carriers, 100.64.0.0/10.
+        || (a == 100 && (64..128).contains(&b))
+        // the benchmarking networks, 198.18.0.0/15.
+        || (a == 198 && (18..20).contains(&b))
+        // the reserved addresses, 240.0.0.0/4.
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let [first, ..] = ip.segments();
+    // the IPv4 addresses mapped or embedded in IPv6 are checked as IPv4 addresses.
+    if let Some(ip) = ip.to_ipv4() {
+        return is_public_v4(ip);
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // the unique local addresses, fc00::/7.
+        || (first & 0xfe00) == 0xfc00
+        // the link-local addresses, fe80::/10.
+        || (first & 0xffc0) == 0xfe80
+        // the documentation addresses, 2001:db8::/32.
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        for public in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_public(public.parse().unwrap()), "{}", public);
+        }
+        for reserved in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(reserved.parse().unwrap()), "{}", reserved);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_resolve() {
+        let fetcher = DocumentFetcher::new(1024, Duration::from_secs(1), vec![]);
+        let url = |url: &str| Url::parse(url).unwrap();
+
+        assert!(matches!(
+            fetcher.resolve(&url("file:///etc/passwd")).await,
+            Err(FetchError::InvalidUrl(_))
+        ));
+        for forbidden in [
+            "http://127.0.0.1:7700/keys",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/",
+            "http://localhost:7700/",
+        ] {
+            assert!(
+                matches!(
+                    fetcher.resolve(&url(forbidden)).await,
+                    Err(FetchError::ForbiddenHost { .. })
+                ),
+                "{}",
+                forbidden
+            );
+        }
+
+        // the allowed hosts are not checked.
+        let fetcher = DocumentFetcher::new(
+            1024,
+            Duration::from_secs(1),
+            vec!["LocalHost".to_string(), "10.0.0.2".to_string()],
+        );
+        let (host, addr) = fetcher
+            .resolve(&url("http://localhost:7700/"))
+            .await
+            .unwrap();
+        assert_eq!(host, "localhost");
+        assert!(addr.ip().is_loopback());
+        assert_eq!(addr.port(), 7700);
+        let (_, addr) = fetcher.resolve(&url("http://10.0.0.2/")).await.unwrap();
+        assert_eq!(addr, "10.0.0.2:80".parse().unwrap());
+    }
+}
@@ -4,6 +4,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use milli::update::IndexerConfig;
+use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
 use tokio::fs;
 use tokio::sync::RwLock;
 use tokio::task::spawn_blocking;
@@ -21,12 +22,18 @@ pub trait IndexStore {
     async fn create(&self, uuid: Uuid) -> Result<Index>;
     async fn get(&self, uuid: Uuid) -> Result<Option<Index>>;
     async fn delete(&self, uuid: Uuid) -> Result<Option<Index>>;
+    /// Closes the environment of the index `uuid`, once the operations using it are finished. It
+    /// is opened again by the next `get`.
+    async fn close(&self, uuid: Uuid) -> Result<()>;
+    /// The free space, in bytes, of the disk the indexes are stored on, `None` if it is unknown.
+    async fn available_space(&self) -> Result<Option<u64>>;
 }
 
 pub struct MapIndexStore {
     index_store: AsyncMap<Uuid, Index>,
     path: PathBuf,
     index_size: usize,
+    max_readers: u32,
     indexer_config: Arc<IndexerConfig>,
 }
 
@@ -43,9 +50,18 @@ impl MapIndexStore {
             index_store,
             path,
             index_size,
+            max_readers: indexer_opts.max_readers,
             indexer_config,
         })
     }
+
+    /// Closes all the opened indexes, which are opened again when they are next used. The
+    /// environments are closed once the indexes aren't used anymore.
+    pub async fn close_all(&self) {
+        for (_, index) in self.index_store.write().await.drain() {
+            index.close();
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -64,9 +80,10 @@ impl IndexStore for MapIndexStore {
         }
 
         let index_size = self.index_size;
+        let max_readers = self.max_readers;
         let update_handler = self.indexer_config.clone();
         let index = spawn_blocking(move || -> Result<Index> {
-            let index = Index::open(path, index_size, uuid, update_handler)?;
+            let index = Index::open(path, index_size, max_readers, uuid, update_handler)?;
             Ok(index)
         })
         .await??;
@@ -89,10 +106,12 @@ impl IndexStore for MapIndexStore {
                 }
 
                 let index_size = self.index_size;
+                let max_readers = self.max_readers;
                 let update_handler = self.indexer_config.clone();
-                let index =
-                    spawn_blocking(move || Index::open(path, index_size, uuid, update_handler))
-                        .await??;
+                let index = spawn_blocking(move || {
+                    Index::open(path, index_size, max_readers, uuid, update_handler)
+                })
+                .await??;
                 self.index_store.write().await.insert(uuid, index.clone());
                 Ok(Some(index))
             }
@@ -105,4 +124,33 @@ impl IndexStore for MapIndexStore {
         let index = self.index_store.write().await.remove(&uuid);
         Ok(index)
     }
+
+    async fn close(&self, uuid: Uuid) -> Result<()> {
+        if let Some(index) = self.index_store.write().await.remove(&uuid) {
+            index.close();
+        }
+        Ok(())
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        let path = self.path.clone();
+        Ok(spawn_blocking(move || available_space(&path)).await?)
+    }
+}
+
+/// Returns the free space of the disk mounted on the longest prefix of `path`.
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    if !System::IS_SUPPORTED {
+        return None;
+    }
+
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut system = System::new_with_specifics(RefreshKind::new().with_disks_list());
+    system.refresh_disks();
+    system
+        .disks()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::{create_dir_all, File};
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
@@ -7,9 +7,11 @@ use std::sync::Arc;
 use milli::heed::types::{SerdeBincode, Str};
 use milli::heed::{CompactionOption, Database, Env};
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use super::error::{IndexResolverError, Result};
+use crate::meta_env::Closable;
 use crate::tasks::task::TaskId;
 use crate::EnvSizer;
 
@@ -19,7 +21,17 @@ pub struct DumpEntry {
     pub index_meta: IndexMeta,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct NamespaceDumpEntry {
+    pub name: String,
+    pub namespace_meta: NamespaceMeta,
+}
+
 const UUIDS_DB_PATH: &str = "index_uuids";
+const NAMESPACES_DUMP_FILE: &str = "namespaces.jsonl";
+
+/// The namespace of the indexes created without one. It always exists, and is never stored.
+pub const DEFAULT_NAMESPACE: &str = "default";
 
 #[async_trait::async_trait]
 #[cfg_attr(test, mockall::automock)]
@@ -30,6 +42,15 @@ pub trait IndexMetaStore: Sized {
     async fn delete(&self, uid: String) -> Result<Option<IndexMeta>>;
     async fn list(&self) -> Result<Vec<(String, IndexMeta)>>;
     async fn insert(&self, name: String, meta: IndexMeta) -> Result<()>;
+    async fn set_name(&self, uid: String, name: Option<String>) -> Result<()>;
+    async fn set_skip_templates(&self, uid: String, skip_templates: bool) -> Result<()>;
+    async fn set_closed(&self, uid: String, closed: bool) -> Result<()>;
+    /// Makes `uid` point to the environment `uuid`, and returns the uuid it pointed to.
+    async fn swap_uuid(&self, uid: String, uuid: Uuid) -> Result<Uuid>;
+    async fn insert_namespace(&self, name: String, meta: NamespaceMeta) -> Result<()>;
+    async fn get_namespace(&self, name: String) -> Result<Option<NamespaceMeta>>;
+    async fn list_namespaces(&self) -> Result<Vec<(String, NamespaceMeta)>>;
+    async fn delete_namespace(&self, name: String) -> Result<Option<NamespaceMeta>>;
     async fn snapshot(&self, path: PathBuf) -> Result<HashSet<Uuid>>;
     async fn get_size(&self) -> Result<u64>;
     async fn dump(&self, path: PathBuf) -> Result<()>;
@@ -39,15 +60,52 @@ pub trait IndexMetaStore: Sized {
 pub struct IndexMeta {
     pub uuid: Uuid,
     pub creation_task_id: TaskId,
+    /// The human friendly name of the index. The dumps made before it was introduced don't
+    /// contain it.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The namespace the index was created in, `None` for the default namespace. The dumps made
+    /// before it was introduced don't contain it.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// The uuids of the environments the index was rebuilt from, oldest first. The tasks
+    /// registered with one of them target this index.
+    #[serde(default)]
+    pub former_uuids: Vec<Uuid>,
+    /// The patterns of the index templates that contributed to the settings of the index when it
+    /// was created. The dumps made before they were introduced don't contain them.
+    #[serde(default)]
+    pub templates: Vec<String>,
+    /// Whether the index opted out of the index templates.
+    #[serde(default)]
+    pub skip_templates: bool,
+    /// Whether the index was closed. The environment of a closed index is never opened, and its
+    /// tasks wait for it to be opened again.
+    #[serde(default)]
+    pub closed: bool,
 }
 
-#[derive(Clone)]
-pub struct HeedMetaStore {
+impl IndexMeta {
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NamespaceMeta {
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// The databases of the meta store, which are created again when the meta environment is opened
+/// again.
+struct MetaDbs {
     env: Arc<Env>,
     db: Database<Str, SerdeBincode<IndexMeta>>,
+    namespaces: Database<Str, SerdeBincode<NamespaceMeta>>,
 }
 
-impl Drop for HeedMetaStore {
+impl Drop for MetaDbs {
     fn drop(&mut self) {
         if Arc::strong_count(&self.env) == 1 {
             self.env.as_ref().clone().prepare_for_closing();
@@ -55,15 +113,51 @@ impl Drop for HeedMetaStore {
     }
 }
 
+impl MetaDbs {
+    fn new(env: Arc<Env>) -> Result<Self> {
+        let db = env.create_database(Some("uuids"))?;
+        let namespaces = env.create_database(Some("namespaces"))?;
+        Ok(Self {
+            env,
+            db,
+            namespaces,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct HeedMetaStore {
+    dbs: Closable<MetaDbs>,
+}
+
 impl HeedMetaStore {
     pub fn new(env: Arc<milli::heed::Env>) -> Result<Self> {
-        let db = env.create_database(Some("uuids"))?;
-        Ok(Self { env, db })
+        Ok(Self {
+            dbs: Closable::new(MetaDbs::new(env)?),
+        })
+    }
+
+    fn dbs(&self) -> Result<Arc<MetaDbs>> {
+        self.dbs
+            .get()
+            .ok_or(IndexResolverError::DatabaseUnavailable)
+    }
+
+    /// Closes the store, once the calls using it are finished. Its calls fail until it is opened
+    /// again.
+    pub fn close(&self) {
+        self.dbs.close();
+    }
+
+    /// Opens the store again, on the meta environment `env`.
+    pub fn open(&self, env: Arc<Env>) -> Result<()> {
+        self.dbs.open(MetaDbs::new(env)?);
+        Ok(())
     }
 
     fn get(&self, name: &str) -> Result<Option<IndexMeta>> {
-        let env = self.env.clone();
-        let db = self.db;
+        let dbs = self.dbs()?;
+        let (env, db) = (&dbs.env, dbs.db);
         let txn = env.read_txn()?;
         match db.get(&txn, name)? {
             Some(meta) => Ok(Some(meta)),
@@ -71,9 +165,9 @@ impl HeedMetaStore {
         }
     }
 
-    fn delete(&self, uid: String) -> Result<Option<IndexMeta>> {
-        let env = self.env.clone();
-        let db = self.db;
+    pub(crate) fn delete(&self, uid: String) -> Result<Option<IndexMeta>> {
+        let dbs = self.dbs()?;
+        let (env, db) = (&dbs.env, dbs.db);
         let mut txn = env.write_txn()?;
         match db.get(&txn, &uid)? {
             Some(meta) => {
@@ -85,9 +179,9 @@ impl HeedMetaStore {
         }
     }
 
-    fn list(&self) -> Result<Vec<(String, IndexMeta)>> {
-        let env = self.env.clone();
-        let db = self.db;
+    pub(crate) fn list(&self) -> Result<Vec<(String, IndexMeta)>> {
+        let dbs = self.dbs()?;
+        let (env, db) = (&dbs.env, dbs.db);
         let txn = env.read_txn()?;
         let mut entries = Vec::new();
         for entry in db.iter(&txn)? {
@@ -98,8 +192,8 @@ impl HeedMetaStore {
     }
 
     pub(crate) fn insert(&self, name: String, meta: IndexMeta) -> Result<()> {
-        let env = self.env.clone();
-        let db = self.db;
+        let dbs = self.dbs()?;
+        let (env, db) = (&dbs.env, dbs.db);
         let mut txn = env.write_txn()?;
 
         if db.get(&txn, &name)?.is_some() {
@@ -111,37 +205,138 @@ impl HeedMetaStore {
         Ok(())
     }
 
+    fn set_name(&self, uid: String, name: Option<String>) -> Result<()> {
+        let dbs = self.dbs()?;
+        let (env, db) = (&dbs.env, dbs.db);
+        let mut txn = env.write_txn()?;
+
+        let mut meta = db
+            .get(&txn, &uid)?
+            .ok_or_else(|| IndexResolverError::UnexistingIndex(uid.clone()))?;
+        meta.name = name;
+        db.put(&mut txn, &uid, &meta)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn set_skip_templates(&self, uid: String, skip_templates: bool) -> Result<()> {
+        let dbs = self.dbs()?;
+        let (env, db) = (&dbs.env, dbs.db);
+        let mut txn = env.write_txn()?;
+
+        let mut meta = db
+            .get(&txn, &uid)?
+            .ok_or_else(|| IndexResolverError::UnexistingIndex(uid.clone()))?;
+        meta.skip_templates = skip_templates;
+        db.put(&mut txn, &uid, &meta)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn set_closed(&self, uid: String, closed: bool) -> Result<()> {
+        let dbs = self.dbs()?;
+        let (env, db) = (&dbs.env, dbs.db);
+        let mut txn = env.write_txn()?;
+
+        let mut meta = db
+            .get(&txn, &uid)?
+            .ok_or_else(|| IndexResolverError::UnexistingIndex(uid.clone()))?;
+        meta.closed = closed;
+        db.put(&mut txn, &uid, &meta)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn swap_uuid(&self, uid: String, uuid: Uuid) -> Result<Uuid> {
+        let dbs = self.dbs()?;
+        let mut txn = dbs.env.write_txn()?;
+
+        let mut meta = dbs
+            .db
+            .get(&txn, &uid)?
+            .ok_or_else(|| IndexResolverError::UnexistingIndex(uid.clone()))?;
+        let former_uuid = std::mem::replace(&mut meta.uuid, uuid);
+        meta.former_uuids.push(former_uuid);
+        dbs.db.put(&mut txn, &uid, &meta)?;
+        txn.commit()?;
+        Ok(former_uuid)
+    }
+
+    fn insert_namespace(&self, name: String, meta: NamespaceMeta) -> Result<()> {
+        let dbs = self.dbs()?;
+        let mut txn = dbs.env.write_txn()?;
+
+        if name == DEFAULT_NAMESPACE || dbs.namespaces.get(&txn, &name)?.is_some() {
+            return Err(IndexResolverError::NamespaceAlreadyExists(name));
+        }
+
+        dbs.namespaces.put(&mut txn, &name, &meta)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn get_namespace(&self, name: &str) -> Result<Option<NamespaceMeta>> {
+        let dbs = self.dbs()?;
+        let txn = dbs.env.read_txn()?;
+        Ok(dbs.namespaces.get(&txn, name)?)
+    }
+
+    fn list_namespaces(&self) -> Result<Vec<(String, NamespaceMeta)>> {
+        let dbs = self.dbs()?;
+        let txn = dbs.env.read_txn()?;
+        let mut entries = Vec::new();
+        for entry in dbs.namespaces.iter(&txn)? {
+            let (name, meta) = entry?;
+            entries.push((name.to_string(), meta));
+        }
+        Ok(entries)
+    }
+
+    fn delete_namespace(&self, name: String) -> Result<Option<NamespaceMeta>> {
+        let dbs = self.dbs()?;
+        let mut txn = dbs.env.write_txn()?;
+        let meta = dbs.namespaces.get(&txn, &name)?;
+        if meta.is_some() {
+            dbs.namespaces.delete(&mut txn, &name)?;
+            txn.commit()?;
+        }
+        Ok(meta)
+    }
+
     fn snapshot(&self, mut path: PathBuf) -> Result<HashSet<Uuid>> {
+        let dbs = self.dbs()?;
         // Write transaction to acquire a lock on the database.
-        let txn = self.env.write_txn()?;
+        let txn = dbs.env.write_txn()?;
         let mut entries = HashSet::new();
-        for entry in self.db.iter(&txn)? {
+        for entry in dbs.db.iter(&txn)? {
             let (_, IndexMeta { uuid, .. }) = entry?;
             entries.insert(uuid);
         }
 
-        // only perform snapshot if there are indexes
-        if !entries.is_empty() {
+        // only perform snapshot if there are indexes or namespaces
+        if !entries.is_empty() || !dbs.namespaces.is_empty(&txn)? {
             path.push(UUIDS_DB_PATH);
             create_dir_all(&path).unwrap();
             path.push("data.mdb");
-            self.env.copy_to_path(path, CompactionOption::Enabled)?;
+            dbs.env.copy_to_path(path, CompactionOption::Enabled)?;
         }
         Ok(entries)
     }
 
     fn get_size(&self) -> Result<u64> {
-        Ok(self.env.size())
+        let dbs = self.dbs()?;
+        Ok(dbs.env.size())
     }
 
     pub fn dump(&self, path: PathBuf) -> Result<()> {
+        let dbs = self.dbs()?;
         let dump_path = path.join(UUIDS_DB_PATH);
         create_dir_all(&dump_path)?;
         let dump_file_path = dump_path.join("data.jsonl");
         let mut dump_file = File::create(&dump_file_path)?;
 
-        let txn = self.env.read_txn()?;
-        for entry in self.db.iter(&txn)? {
+        let txn = dbs.env.read_txn()?;
+        for entry in dbs.db.iter(&txn)? {
             let (uid, index_meta) = entry?;
             let uid = uid.to_string();
 
@@ -150,6 +345,17 @@ impl HeedMetaStore {
             dump_file.write_all(b"\n").unwrap();
         }
 
+        let mut namespaces_file = File::create(dump_path.join(NAMESPACES_DUMP_FILE))?;
+        for entry in dbs.namespaces.iter(&txn)? {
+            let (name, namespace_meta) = entry?;
+            let entry = NamespaceDumpEntry {
+                name: name.to_string(),
+                namespace_meta,
+            };
+            serde_json::to_writer(&mut namespaces_file, &entry)?;
+            namespaces_file.write_all(b"\n")?;
+        }
+
         Ok(())
     }
 
@@ -159,7 +365,7 @@ impl HeedMetaStore {
         let mut indexes = BufReader::new(indexes);
         let mut line = String::new();
 
-        let db = Self::new(env)?;
+        let db = MetaDbs::new(env)?;
         let mut txn = db.env.write_txn()?;
 
         loop {
@@ -174,12 +380,39 @@ impl HeedMetaStore {
 
             line.clear();
         }
+
+        // the dumps made before the namespaces were introduced don't contain them.
+        let src_namespaces = src.as_ref().join(UUIDS_DB_PATH).join(NAMESPACES_DUMP_FILE);
+        if src_namespaces.exists() {
+            for line in BufReader::new(File::open(&src_namespaces)?).lines() {
+                let NamespaceDumpEntry {
+                    name,
+                    namespace_meta,
+                } = serde_json::from_str(&line?)?;
+                db.namespaces.put(&mut txn, &name, &namespace_meta)?;
+            }
+        }
+
         txn.commit()?;
 
         Ok(())
     }
 }
 
+/// Reads the uids of the indexes of the meta environment `env`, by index uuid, without a meta
+/// store.
+pub(crate) fn read_index_uids(env: &Env) -> Result<HashMap<Uuid, String>> {
+    let mut uids = HashMap::new();
+    let txn = env.read_txn()?;
+    if let Some(db) = env.open_database::<Str, SerdeBincode<IndexMeta>>(Some("uuids"))? {
+        for entry in db.iter(&txn)? {
+            let (uid, meta) = entry?;
+            uids.insert(meta.uuid, uid.to_string());
+        }
+    }
+    Ok(uids)
+}
+
 #[async_trait::async_trait]
 impl IndexMetaStore for HeedMetaStore {
     async fn get(&self, name: String) -> Result<(String, Option<IndexMeta>)> {
@@ -202,6 +435,46 @@ impl IndexMetaStore for HeedMetaStore {
         tokio::task::spawn_blocking(move || this.insert(name, meta)).await?
     }
 
+    async fn set_name(&self, uid: String, name: Option<String>) -> Result<()> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.set_name(uid, name)).await?
+    }
+
+    async fn set_skip_templates(&self, uid: String, skip_templates: bool) -> Result<()> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.set_skip_templates(uid, skip_templates)).await?
+    }
+
+    async fn set_closed(&self, uid: String, closed: bool) -> Result<()> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.set_closed(uid, closed)).await?
+    }
+
+    async fn swap_uuid(&self, uid: String, uuid: Uuid) -> Result<Uuid> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.swap_uuid(uid, uuid)).await?
+    }
+
+    async fn insert_namespace(&self, name: String, meta: NamespaceMeta) -> Result<()> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.insert_namespace(name, meta)).await?
+    }
+
+    async fn get_namespace(&self, name: String) -> Result<Option<NamespaceMeta>> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.get_namespace(&name)).await?
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<(String, NamespaceMeta)>> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.list_namespaces()).await?
+    }
+
+    async fn delete_namespace(&self, name: String) -> Result<Option<NamespaceMeta>> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.delete_namespace(name)).await?
+    }
+
     async fn snapshot(&self, path: PathBuf) -> Result<HashSet<Uuid>> {
         let this = self.clone();
         tokio::task::spawn_blocking(move || this.snapshot(path)).await?
@@ -216,3 +489,133 @@ impl IndexMetaStore for HeedMetaStore {
         Ok(tokio::task::spawn_blocking(move || this.dump(path)).await??)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use milli::heed::EnvOpenOptions;
+
+    use super::*;
+
+    fn meta_store(path: &Path) -> HeedMetaStore {
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100);
+        options.max_dbs(2);
+        let env = Arc::new(options.open(path).unwrap());
+        HeedMetaStore::new(env).unwrap()
+    }
+
+    #[test]
+    fn test_dump_index_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = meta_store(dir.path());
+        let meta = IndexMeta {
+            uuid: Uuid::new_v4(),
+            creation_task_id: 0,
+            name: None,
+            namespace: None,
+            former_uuids: Vec::new(),
+            templates: Vec::new(),
+            skip_templates: false,
+            closed: false,
+        };
+        store.insert("movies".to_string(), meta).unwrap();
+        store
+            .set_name("movies".to_string(), Some("Films 🎬".to_string()))
+            .unwrap();
+
+        let dump_dir = tempfile::tempdir().unwrap();
+        store.dump(dump_dir.path().to_path_buf()).unwrap();
+
+        let dst = tempfile::tempdir().unwrap();
+        let loaded = meta_store(dst.path());
+        HeedMetaStore::load_dump(dump_dir.path(), loaded.dbs().unwrap().env.clone()).unwrap();
+        let meta = loaded.get("movies").unwrap().unwrap();
+        assert_eq!(meta.name.as_deref(), Some("Films 🎬"));
+    }
+
+    #[test]
+    fn test_load_dump_entry_without_name() {
+        let entry: DumpEntry = serde_json::from_str(
+            r#"{"uid":"movies","index_meta":{"uuid":"4f3e1fa4-7bfa-4b1d-b3c7-1f6b2bd3d4a2","creation_task_id":1}}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.index_meta.name, None);
+        assert_eq!(entry.index_meta.namespace(), DEFAULT_NAMESPACE);
+        assert_eq!(entry.index_meta.creation_task_id, 1);
+    }
+
+    #[test]
+    fn test_dump_namespaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = meta_store(dir.path());
+        let namespace_meta = || NamespaceMeta {
+            created_at: OffsetDateTime::now_utc(),
+        };
+        store
+            .insert_namespace("tenant".to_string(), namespace_meta())
+            .unwrap();
+        assert!(matches!(
+            store.insert_namespace("tenant".to_string(), namespace_meta()),
+            Err(IndexResolverError::NamespaceAlreadyExists(_))
+        ));
+        assert!(matches!(
+            store.insert_namespace(DEFAULT_NAMESPACE.to_string(), namespace_meta()),
+            Err(IndexResolverError::NamespaceAlreadyExists(_))
+        ));
+        let meta = IndexMeta {
+            uuid: Uuid::new_v4(),
+            creation_task_id: 0,
+            name: None,
+            namespace: Some("tenant".to_string()),
+            former_uuids: Vec::new(),
+            templates: Vec::new(),
+            skip_templates: false,
+            closed: false,
+        };
+        store.insert("movies".to_string(), meta).unwrap();
+
+        let dump_dir = tempfile::tempdir().unwrap();
+        store.dump(dump_dir.path().to_path_buf()).unwrap();
+
+        let dst = tempfile::tempdir().unwrap();
+        let loaded = meta_store(dst.path());
+        HeedMetaStore::load_dump(dump_dir.path(), loaded.dbs().unwrap().env.clone()).unwrap();
+        assert!(loaded.get_namespace("tenant").unwrap().is_some());
+        let meta = loaded.get("movies").unwrap().unwrap();
+        assert_eq!(meta.namespace(), "tenant");
+    }
+
+    #[test]
+    fn test_swap_uuid() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = meta_store(dir.path());
+        let uuid = Uuid::new_v4();
+        let meta = IndexMeta {
+            uuid,
+            creation_task_id: 3,
+            name: Some("Movies".to_string()),
+            namespace: None,
+            former_uuids: Vec::new(),
+            templates: Vec::new(),
+            skip_templates: false,
+            closed: false,
+        };
+        store.insert("movies".to_string(), meta).unwrap();
+
+        let new_uuid = Uuid::new_v4();
+        assert_eq!(
+            store.swap_uuid("movies".to_string(), new_uuid).unwrap(),
+            uuid
+        );
+        let meta = store.get("movies").unwrap().unwrap();
+        assert_eq!(meta.uuid, new_uuid);
+        assert_eq!(meta.former_uuids, vec![uuid]);
+        assert_eq!(meta.creation_task_id, 3);
+        assert_eq!(meta.name.as_deref(), Some("Movies"));
+
+        assert!(matches!(
+            store.swap_uuid("books".to_string(), Uuid::new_v4()),
+            Err(IndexResolverError::UnexistingIndex(_))
+        ));
+    }
+}
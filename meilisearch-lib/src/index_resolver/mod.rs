@@ -1,35 +1,64 @@
 pub mod error;
+pub mod fetch;
 pub mod index_store;
 pub mod meta_store;
+pub mod templates;
 
+use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use error::{IndexResolverError, Result};
+use error::{IndexResolverError, InvalidIndexUid, Result};
+use fetch::DocumentFetcher;
 use index_store::{IndexStore, MapIndexStore};
 use meilisearch_error::ResponseError;
 use meta_store::{HeedMetaStore, IndexMetaStore};
 use milli::heed::Env;
-use milli::update::{DocumentDeletionResult, IndexerConfig};
+use milli::update::IndexerConfig;
 use serde::{Deserialize, Serialize};
+use templates::{reset_to_templates, IndexTemplates, ResolvedTemplates};
 use time::OffsetDateTime;
 use tokio::task::spawn_blocking;
 use uuid::Uuid;
 
-use crate::index::{error::Result as IndexResult, Index};
+use crate::changefeed::{ChangeOperation, ChangeRecord, Changefeed, MAX_CHANGE_RECORD_IDS};
+use crate::closed_indexes::ClosedIndexes;
+use crate::compaction::CompactionHandle;
+use crate::index::{
+    error::{IndexError, Result as IndexResult},
+    DocumentsDiff, DocumentsDiffMode, DocumentsPayload, Index, PreviewBudget, Settings,
+    SettingsPreview, TransformsResult, Unchecked,
+};
 use crate::options::IndexerOpts;
+use crate::pending_payloads::PendingPayloads;
+use crate::tasks::batch::{Batch, BatchContent};
+use crate::tasks::progress::TaskProgresses;
 use crate::tasks::task::{DocumentDeletion, Task, TaskContent, TaskEvent, TaskId, TaskResult};
 use crate::update_file_store::UpdateFileStore;
+use crate::warmup::WarmupHandle;
 
-use self::meta_store::IndexMeta;
+use self::meta_store::{IndexMeta, NamespaceMeta, DEFAULT_NAMESPACE};
 
 pub type HardStateIndexResolver = IndexResolver<HeedMetaStore, MapIndexStore>;
 
+/// The default maximum number of documents of an addition whose diff lists the replaced
+/// documents.
+pub const DEFAULT_MAX_FULL_DIFF_DOCUMENTS: usize = 1000;
+
+/// The size of the environment of the shadow index of a settings preview.
+const SETTINGS_PREVIEW_DISK_BUDGET: usize = 1024 * 1024 * 1024;
+/// The time a settings preview may take. The sample is indexed in one go, the budget is checked
+/// before each of the sample queries.
+const SETTINGS_PREVIEW_TIME_BUDGET: Duration = Duration::from_secs(60);
+
 /// An index uid is composed of only ascii alphanumeric characters, - and _, between 1 and 400
-/// bytes long
+/// bytes long. It can only be built through `IndexUid::new`, `TryFrom<String>` or `FromStr`,
+/// which all validate it, and it is validated again when deserialized.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct IndexUid(#[cfg_attr(test, proptest(regex("[a-zA-Z0-9_-]{1,400}")))] String);
 
@@ -42,22 +71,49 @@ pub fn create_index_resolver(
 ) -> anyhow::Result<HardStateIndexResolver> {
     let uuid_store = HeedMetaStore::new(meta_env)?;
     let index_store = MapIndexStore::new(&path, index_size, indexer_opts)?;
-    Ok(IndexResolver::new(uuid_store, index_store, file_store))
+    let mut index_resolver = IndexResolver::new(uuid_store, index_store, file_store);
+    index_resolver.fetcher = DocumentFetcher::new(
+        indexer_opts.max_fetch_size.get_bytes() as u64,
+        Duration::from_secs(indexer_opts.fetch_timeout_sec),
+        indexer_opts.fetch_allowed_hosts.clone(),
+    );
+    Ok(index_resolver)
+}
+
+/// Index uids and namespaces are made of ascii alphanumeric characters, - and _, and are between 1
+/// and 400 bytes long.
+fn check_uid(uid: &str) -> std::result::Result<(), InvalidIndexUid> {
+    let invalid_char = uid
+        .chars()
+        .enumerate()
+        .find(|(_, c)| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'));
+
+    match invalid_char {
+        Some((position, c)) => Err(InvalidIndexUid::InvalidChar(c, position)),
+        None if uid.is_empty() => Err(InvalidIndexUid::Empty),
+        None if uid.len() > 400 => Err(InvalidIndexUid::TooLong(uid.len())),
+        None => Ok(()),
+    }
+}
+
+/// Returns `name` if it is a valid namespace.
+pub fn check_namespace(name: String) -> Result<String> {
+    match check_uid(&name) {
+        Ok(()) => Ok(name),
+        Err(reason) => Err(IndexResolverError::BadlyFormattedNamespace { name, reason }),
+    }
 }
 
 impl IndexUid {
     pub fn new(uid: String) -> Result<Self> {
-        if !uid
-            .chars()
-            .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_')
-            || !(1..=400).contains(&uid.len())
-        {
-            Err(IndexResolverError::BadlyFormatted(uid))
-        } else {
-            Ok(Self(uid))
+        match check_uid(&uid) {
+            Ok(()) => Ok(Self(uid)),
+            Err(reason) => Err(IndexResolverError::BadlyFormatted { uid, reason }),
         }
     }
 
+    /// Builds an index uid without validating it, for the tests only.
+    #[cfg(test)]
     pub fn new_unchecked(s: impl AsRef<str>) -> Self {
         Self(s.as_ref().to_string())
     }
@@ -80,11 +136,11 @@ impl std::ops::Deref for IndexUid {
     }
 }
 
-impl TryInto<IndexUid> for String {
+impl TryFrom<String> for IndexUid {
     type Error = IndexResolverError;
 
-    fn try_into(self) -> Result<IndexUid> {
-        IndexUid::new(self)
+    fn try_from(uid: String) -> Result<IndexUid> {
+        IndexUid::new(uid)
     }
 }
 
@@ -100,6 +156,20 @@ pub struct IndexResolver<U, I> {
     index_uuid_store: U,
     index_store: I,
     pub file_store: UpdateFileStore,
+    pub(crate) warmup: WarmupHandle,
+    pub(crate) fetcher: DocumentFetcher,
+    pub(crate) compaction: CompactionHandle,
+    pub(crate) pending_payloads: PendingPayloads,
+    pub(crate) progress: TaskProgresses,
+    /// Whether the chunks of a document addition are indexed all at once rather than one after
+    /// the other.
+    pub(crate) defer_chunk_visibility: bool,
+    /// The maximum number of documents of an addition whose diff lists the replaced documents.
+    pub(crate) max_full_diff_documents: usize,
+    pub(crate) templates: IndexTemplates,
+    /// The indexes that were closed, shared with the scheduler.
+    pub(crate) closed: ClosedIndexes,
+    pub(crate) changefeed: Changefeed,
 }
 
 impl IndexResolver<HeedMetaStore, MapIndexStore> {
@@ -120,6 +190,38 @@ impl IndexResolver<HeedMetaStore, MapIndexStore> {
 
         Ok(())
     }
+
+    /// Returns the store of the index metadata, which shares the meta environment.
+    pub fn meta_store(&self) -> HeedMetaStore {
+        self.index_uuid_store.clone()
+    }
+
+    /// Loads the set of the closed indexes from their metadata.
+    pub fn load_closed_indexes(&mut self) -> Result<()> {
+        let closed = self
+            .index_uuid_store
+            .list()?
+            .into_iter()
+            .filter(|(_, meta)| meta.closed)
+            .map(|(uid, _)| uid);
+        self.closed = ClosedIndexes::new(closed);
+        Ok(())
+    }
+
+    /// Serves the metadata of the meta environment `env` and the indexes of its data directory,
+    /// in place of the current ones. The requests holding the current ones finish on them.
+    pub async fn swap_meta_env(&self, env: Arc<Env>) -> Result<()> {
+        self.index_uuid_store.open(env)?;
+        let closed = self
+            .index_uuid_store
+            .list()?
+            .into_iter()
+            .filter(|(_, meta)| meta.closed)
+            .map(|(uid, _)| uid);
+        self.closed.replace(closed);
+        self.index_store.close_all().await;
+        Ok(())
+    }
 }
 
 impl<U, I> IndexResolver<U, I>
@@ -132,21 +234,135 @@ where
             index_uuid_store,
             index_store,
             file_store,
+            warmup: WarmupHandle::default(),
+            fetcher: DocumentFetcher::default(),
+            compaction: CompactionHandle::default(),
+            pending_payloads: PendingPayloads::default(),
+            progress: TaskProgresses::default(),
+            defer_chunk_visibility: false,
+            max_full_diff_documents: DEFAULT_MAX_FULL_DIFF_DOCUMENTS,
+            templates: IndexTemplates::default(),
+            closed: ClosedIndexes::default(),
+            changefeed: Changefeed::default(),
+        }
+    }
+
+    /// Sets the handle used to request the warm-up of an index once its settings are updated.
+    pub fn set_warmup(&mut self, warmup: WarmupHandle) {
+        self.warmup = warmup;
+    }
+
+    /// Sets the handle used to request the compaction of an index once it has too many soft
+    /// deleted documents.
+    pub fn set_compaction(&mut self, compaction: CompactionHandle) {
+        self.compaction = compaction;
+    }
+
+    /// Sets the accounting of the update files of the enqueued document additions.
+    pub fn set_pending_payloads(&mut self, pending_payloads: PendingPayloads) {
+        self.pending_payloads = pending_payloads;
+    }
+
+    /// Sets where the progress of the document additions being processed is reported.
+    pub fn set_progress(&mut self, progress: TaskProgresses) {
+        self.progress = progress;
+    }
+
+    /// Sets whether the chunks of a document addition are indexed all at once, so that none of
+    /// its documents are visible before all of them are indexed.
+    pub fn set_defer_chunk_visibility(&mut self, defer: bool) {
+        self.defer_chunk_visibility = defer;
+    }
+
+    /// Sets the maximum number of documents of an addition whose diff lists the replaced
+    /// documents.
+    pub fn set_max_full_diff_documents(&mut self, max: usize) {
+        self.max_full_diff_documents = max;
+    }
+
+    /// Sets the index templates applied to the settings of the indexes.
+    pub fn set_templates(&mut self, templates: IndexTemplates) {
+        self.templates = templates;
+    }
+
+    /// Sets the changefeed the documents changed by the applied batches are recorded in.
+    pub fn set_changefeed(&mut self, changefeed: Changefeed) {
+        self.changefeed = changefeed;
+    }
+
+    /// The diff computed for an addition of `documents_count` documents asking for `mode`: the
+    /// full diff of a too large addition is reduced to its summary.
+    fn diff_mode(
+        &self,
+        mode: Option<DocumentsDiffMode>,
+        documents_count: usize,
+    ) -> Option<DocumentsDiffMode> {
+        match mode {
+            Some(DocumentsDiffMode::Full) if documents_count > self.max_full_diff_documents => {
+                Some(DocumentsDiffMode::Summary)
+            }
+            mode => mode,
+        }
+    }
+
+    /// Registers a compaction of the index if its proportion of soft deleted documents exceeds the
+    /// compaction threshold.
+    pub(crate) async fn schedule_compaction_if_needed(&self, index_uid: &IndexUid) {
+        let index = match self.get_index(index_uid.to_string()).await {
+            Ok(index) => index,
+            // the index may have been deleted in the meantime.
+            Err(_) => return,
+        };
+
+        match spawn_blocking(move || index.soft_deleted_ratio()).await {
+            Ok(Ok(ratio)) if self.compaction.needs_compaction(ratio) => {
+                self.compaction.schedule(index_uid.clone())
+            }
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => log::error!("error computing the soft deleted ratio: {}", e),
+            Err(e) => log::error!("error computing the soft deleted ratio: {}", e),
         }
     }
 
     pub async fn process_document_addition_batch(&self, mut tasks: Vec<Task>) -> Vec<Task> {
-        fn get_content_uuid(task: &Task) -> Uuid {
+        // a document addition split into chunks is always alone in its batch.
+        if let [task] = tasks.as_mut_slice() {
+            if task.content_uuids().len() > 1 {
+                let event = match self.process_chunks(task).await {
+                    Ok(event) => event,
+                    Err(e) => TaskEvent::failed(e.into()),
+                };
+                task.events.push(event);
+                return tasks;
+            }
+        }
+
+        let get_payload = |task: &Task| -> DocumentsPayload {
             match task {
                 Task {
-                    content: TaskContent::DocumentAddition { content_uuid, .. },
+                    content:
+                        TaskContent::DocumentAddition {
+                            content_uuid,
+                            reject_duplicates,
+                            documents_count,
+                            return_diff,
+                            content_digests,
+                            warnings,
+                            ..
+                        },
                     ..
-                } => *content_uuid,
+                } => DocumentsPayload {
+                    content_uuid: *content_uuid,
+                    reject_duplicates: *reject_duplicates,
+                    diff: self.diff_mode(*return_diff, *documents_count),
+                    digest: content_digests.get(content_uuid).cloned(),
+                    over_soft_limits: warnings.unwrap_or_default(),
+                },
                 _ => panic!("unexpected task in the document addition batch"),
             }
-        }
+        };
 
-        let content_uuids = tasks.iter().map(get_content_uuid).collect::<Vec<_>>();
+        let payloads = tasks.iter().map(get_payload).collect::<Vec<_>>();
 
         match tasks.first() {
             Some(Task {
@@ -178,45 +394,60 @@ where
                     Err(e) => {
                         let error = ResponseError::from(e);
                         for task in tasks.iter_mut() {
-                            task.events.push(TaskEvent::Failed {
-                                error: error.clone(),
-                                timestamp: now,
-                            });
+                            task.events.push(TaskEvent::failed_at(error.clone(), now));
                         }
                         return tasks;
                     }
                 };
 
                 let file_store = self.file_store.clone();
+                let progress = self
+                    .progress
+                    .reporter(tasks.iter().map(|task| task.id).collect());
                 let result = spawn_blocking(move || {
                     index.update_documents(
                         method,
                         primary_key,
                         file_store,
-                        content_uuids.into_iter(),
+                        payloads.into_iter(),
+                        |step| progress.report(step),
                     )
                 })
                 .await;
 
-                let event = match result {
-                    Ok(Ok(result)) => TaskEvent::Succeeded {
-                        timestamp: OffsetDateTime::now_utc(),
-                        result: TaskResult::DocumentAddition {
-                            indexed_documents: result.indexed_documents,
-                        },
-                    },
-                    Ok(Err(e)) => TaskEvent::Failed {
-                        timestamp: OffsetDateTime::now_utc(),
-                        error: e.into(),
-                    },
-                    Err(e) => TaskEvent::Failed {
-                        timestamp: OffsetDateTime::now_utc(),
-                        error: IndexResolverError::from(e).into(),
-                    },
-                };
-
-                for task in tasks.iter_mut() {
-                    task.events.push(event.clone());
+                let timestamp = OffsetDateTime::now_utc();
+                match result {
+                    Ok(Ok(result)) => {
+                        for (task, payload) in tasks.iter_mut().zip(result.payloads) {
+                            let event = match payload {
+                                Ok(payload) => TaskEvent::succeeded_at(
+                                    TaskResult::DocumentAddition {
+                                        indexed_documents: result.indexed_documents,
+                                        duplicate_documents: payload.duplicate_documents,
+                                        diff: payload.diff,
+                                        transforms: payload.transforms,
+                                    },
+                                    timestamp,
+                                ),
+                                Err(e) => TaskEvent::failed_at(e.into(), timestamp),
+                            };
+                            task.events.push(event);
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        let error = ResponseError::from(e);
+                        for task in tasks.iter_mut() {
+                            task.events
+                                .push(TaskEvent::failed_at(error.clone(), timestamp));
+                        }
+                    }
+                    Err(e) => {
+                        let error = ResponseError::from(IndexResolverError::from(e));
+                        for task in tasks.iter_mut() {
+                            task.events
+                                .push(TaskEvent::failed_at(error.clone(), timestamp));
+                        }
+                    }
                 }
 
                 tasks
@@ -225,22 +456,232 @@ where
         }
     }
 
-    pub async fn process_task(&self, task: &Task) -> Result<TaskResult> {
-        match &task.content {
-            TaskContent::DocumentAddition { .. } => panic!("updates should be handled by batch"),
-            TaskContent::DocumentDeletion {
-                deletion: DocumentDeletion::Ids(ids),
+    /// Indexes the next chunk of the document addition `task`, which was split into chunks, and
+    /// returns the event recording it. Once the last chunk is indexed, the event completes the
+    /// task with the documents of all its chunks. When the visibility of the chunks is deferred,
+    /// all the chunks that remain are indexed at once instead.
+    async fn process_chunks(&self, task: &Task) -> Result<TaskEvent> {
+        let (
+            merge_strategy,
+            primary_key,
+            allow_index_creation,
+            reject_duplicates,
+            diff,
+            index_uid,
+            content_digests,
+            warnings,
+        ) = match task.content {
+            TaskContent::DocumentAddition {
+                merge_strategy,
+                ref primary_key,
+                allow_index_creation,
+                reject_duplicates,
+                documents_count,
+                return_diff,
+                ref index_uid,
+                ref content_digests,
+                warnings,
+                ..
+            } => (
+                merge_strategy,
+                primary_key.clone(),
+                allow_index_creation,
+                reject_duplicates,
+                self.diff_mode(return_diff, documents_count),
                 index_uid,
-            } => {
-                let ids = ids.clone();
-                let index = self.get_index(index_uid.clone().into_inner()).await?;
+                content_digests,
+                warnings,
+            ),
+            _ => panic!("unexpected task in the document addition batch"),
+        };
+
+        let index = if allow_index_creation {
+            self.get_or_create_index(index_uid.clone(), task.id).await?
+        } else {
+            self.get_index(index_uid.as_str().to_string()).await?
+        };
 
-                let DocumentDeletionResult {
-                    deleted_documents, ..
-                } = spawn_blocking(move || index.delete_documents(&ids)).await??;
+        let chunks = task.content_uuids();
+        let first = task.processed_chunks();
+        let end = if self.defer_chunk_visibility {
+            chunks.len()
+        } else {
+            first + 1
+        };
+        // the warnings of the whole payload are accounted for with its last chunk.
+        let last = chunks.len() - 1;
+        let payloads: Vec<_> = chunks[first..end]
+            .iter()
+            .enumerate()
+            .map(|(i, content_uuid)| DocumentsPayload {
+                content_uuid: *content_uuid,
+                reject_duplicates,
+                diff,
+                digest: content_digests.get(content_uuid).cloned(),
+                over_soft_limits: if first + i == last {
+                    warnings.unwrap_or_default()
+                } else {
+                    0
+                },
+            })
+            .collect();
 
-                Ok(TaskResult::DocumentDeletion { deleted_documents })
+        let file_store = self.file_store.clone();
+        let progress = self.progress.reporter(vec![task.id]);
+        let defer = self.defer_chunk_visibility;
+        let result = spawn_blocking(move || {
+            if defer {
+                index.update_documents_atomically(
+                    merge_strategy,
+                    primary_key,
+                    file_store,
+                    payloads.into_iter(),
+                    |step| progress.report(step),
+                )
+            } else {
+                index.update_documents(
+                    merge_strategy,
+                    primary_key,
+                    file_store,
+                    payloads.into_iter(),
+                    |step| progress.report(step),
+                )
+            }
+        })
+        .await?;
+
+        let chunk_failed = |chunk, error: IndexResolverError| IndexResolverError::ChunkFailed {
+            chunk,
+            chunks: chunks.len(),
+            source: Box::new(error),
+        };
+        let result = result.map_err(|e| chunk_failed(first, e.into()))?;
+
+        let mut duplicate_documents = 0;
+        let mut chunks_diff: Option<DocumentsDiff> = None;
+        let mut chunks_transforms: Option<TransformsResult> = None;
+        for (chunk, payload) in (first..).zip(result.payloads) {
+            match payload {
+                Ok(payload) => {
+                    duplicate_documents += payload.duplicate_documents;
+                    if let Some(diff) = payload.diff {
+                        chunks_diff.get_or_insert_with(Default::default).merge(diff);
+                    }
+                    if let Some(transforms) = payload.transforms {
+                        chunks_transforms
+                            .get_or_insert_with(Default::default)
+                            .merge(transforms);
+                    }
+                }
+                Err(e) => return Err(chunk_failed(chunk, e.into())),
+            }
+        }
+
+        if end < chunks.len() {
+            return Ok(TaskEvent::ChunkProcessed {
+                chunk: first,
+                indexed_documents: result.indexed_documents,
+                duplicate_documents,
+                diff: chunks_diff,
+                transforms: chunks_transforms,
+                timestamp: OffsetDateTime::now_utc(),
+            });
+        }
+
+        let mut indexed_documents = result.indexed_documents;
+        // the diffs of the earlier chunks come first, so that the replaced ids stay in order.
+        let mut diff: Option<DocumentsDiff> = None;
+        let mut transforms = chunks_transforms;
+        for event in &task.events {
+            if let TaskEvent::ChunkProcessed {
+                indexed_documents: indexed,
+                duplicate_documents: duplicates,
+                diff: chunk_diff,
+                transforms: chunk_transforms,
+                ..
+            } = event
+            {
+                indexed_documents += indexed;
+                duplicate_documents += duplicates;
+                if let Some(chunk_diff) = chunk_diff {
+                    diff.get_or_insert_with(Default::default)
+                        .merge(chunk_diff.clone());
+                }
+                if let Some(chunk_transforms) = chunk_transforms {
+                    transforms
+                        .get_or_insert_with(Default::default)
+                        .merge(*chunk_transforms);
+                }
+            }
+        }
+        if let Some(chunks_diff) = chunks_diff {
+            diff.get_or_insert_with(Default::default).merge(chunks_diff);
+        }
+
+        Ok(TaskEvent::succeeded(TaskResult::DocumentAddition {
+            indexed_documents,
+            duplicate_documents,
+            diff,
+            transforms,
+        }))
+    }
+
+    /// Performs the deletions by id of `tasks`, which all target the same index, as a single
+    /// deletion. The tasks all fail if the deletion fails.
+    pub async fn process_document_deletion_batch(&self, mut tasks: Vec<Task>) -> Vec<Task> {
+        let mut deletions = Vec::with_capacity(tasks.len());
+        for task in &tasks {
+            match task.content {
+                TaskContent::DocumentDeletion {
+                    deletion: DocumentDeletion::Ids(ref ids),
+                    ..
+                } => deletions.push(ids.clone()),
+                _ => panic!("unexpected task in the document deletion batch"),
             }
+        }
+
+        let index_uid = match tasks.first().and_then(Task::index_uid) {
+            Some(index_uid) => index_uid.to_string(),
+            None => return tasks,
+        };
+
+        let result = match self.get_index(index_uid).await {
+            Ok(index) => spawn_blocking(move || index.delete_documents(&deletions))
+                .await
+                .map_err(IndexResolverError::from)
+                .and_then(|result| result.map_err(IndexResolverError::from)),
+            Err(e) => Err(e),
+        };
+
+        let timestamp = OffsetDateTime::now_utc();
+        match result {
+            Ok(deleted) => {
+                for (task, deleted_documents) in tasks.iter_mut().zip(deleted) {
+                    task.events.push(TaskEvent::succeeded_at(
+                        TaskResult::DocumentDeletion { deleted_documents },
+                        timestamp,
+                    ));
+                }
+            }
+            Err(e) => {
+                let error = ResponseError::from(e);
+                for task in tasks.iter_mut() {
+                    task.events
+                        .push(TaskEvent::failed_at(error.clone(), timestamp));
+                }
+            }
+        }
+
+        tasks
+    }
+
+    pub async fn process_task(&self, task: &Task) -> Result<TaskResult> {
+        match &task.content {
+            TaskContent::DocumentAddition { .. } => panic!("updates should be handled by batch"),
+            TaskContent::DocumentDeletion {
+                deletion: DocumentDeletion::Ids(_),
+                ..
+            } => panic!("deletions by id should be handled by batch"),
             TaskContent::DocumentDeletion {
                 deletion: DocumentDeletion::Clear,
                 index_uid,
@@ -260,17 +701,39 @@ where
                 is_deletion,
                 allow_index_creation,
                 index_uid,
+                ..
             } => {
+                // a settings update enqueued before the first document addition of its index
+                // creates the index, like the addition would have, when it is allowed to. The
+                // tasks of an index are never reordered, so it can't wait for the addition.
                 let index = if *is_deletion || !*allow_index_creation {
                     self.get_index(index_uid.clone().into_inner()).await?
                 } else {
                     self.get_or_create_index(index_uid.clone(), task.id).await?
                 };
 
-                let settings = settings.clone();
-                spawn_blocking(move || index.update_settings(&settings.check())).await??;
+                let mut settings = settings.clone();
+                if *is_deletion {
+                    // a reset setting goes back to the value the templates give it.
+                    if let Some(templates) = self.index_templates(index_uid.as_str()).await? {
+                        settings = reset_to_templates(&settings, &templates);
+                    }
+                }
+                let settings = settings.check();
+                let reindexed_documents = spawn_blocking(move || -> IndexResult<u64> {
+                    let reindexed_documents = if settings.reindexes_documents() {
+                        index.stats()?.number_of_documents
+                    } else {
+                        0
+                    };
+                    index.update_settings(&settings)?;
+                    Ok(reindexed_documents)
+                })
+                .await??;
 
-                Ok(TaskResult::Other)
+                Ok(TaskResult::SettingsUpdate {
+                    reindexed_documents,
+                })
             }
             TaskContent::IndexDeletion { index_uid } => {
                 let index = self.delete_index(index_uid.clone().into_inner()).await?;
@@ -284,23 +747,64 @@ where
             }
             TaskContent::IndexCreation {
                 primary_key,
+                name,
+                namespace,
+                if_not_exists,
+                skip_templates,
                 index_uid,
             } => {
-                let index = self.create_index(index_uid.clone(), task.id).await?;
+                let index = match self
+                    .create_index(
+                        index_uid.clone(),
+                        name.clone(),
+                        namespace.clone(),
+                        task.id,
+                        *skip_templates,
+                    )
+                    .await
+                {
+                    // the index was created after the task was registered.
+                    Err(IndexResolverError::IndexAlreadyExists(_)) if *if_not_exists => {
+                        return Ok(TaskResult::IndexCreation {
+                            already_existed: true,
+                        })
+                    }
+                    result => result?,
+                };
 
                 if let Some(primary_key) = primary_key {
                     let primary_key = primary_key.clone();
                     spawn_blocking(move || index.update_primary_key(primary_key)).await??;
                 }
 
-                Ok(TaskResult::Other)
+                if *if_not_exists {
+                    Ok(TaskResult::IndexCreation {
+                        already_existed: false,
+                    })
+                } else {
+                    Ok(TaskResult::Other)
+                }
             }
             TaskContent::IndexUpdate {
                 primary_key,
+                name,
+                skip_templates,
                 index_uid,
             } => {
                 let index = self.get_index(index_uid.clone().into_inner()).await?;
 
+                if let Some(name) = name {
+                    self.index_uuid_store
+                        .set_name(index_uid.clone().into_inner(), Some(name.clone()))
+                        .await?;
+                }
+
+                if let Some(skip_templates) = skip_templates {
+                    self.index_uuid_store
+                        .set_skip_templates(index_uid.clone().into_inner(), *skip_templates)
+                        .await?;
+                }
+
                 if let Some(primary_key) = primary_key {
                     let primary_key = primary_key.clone();
                     spawn_blocking(move || index.update_primary_key(primary_key)).await??;
@@ -308,35 +812,220 @@ where
 
                 Ok(TaskResult::Other)
             }
+            TaskContent::DocumentsCompaction { index_uid } => {
+                let index = self.get_index(index_uid.clone().into_inner()).await?;
+                let deleted_documents = spawn_blocking(move || index.compact()).await??;
+
+                Ok(TaskResult::DocumentDeletion { deleted_documents })
+            }
+            TaskContent::IndexCheck { index_uid, fast } => {
+                let index = self.get_index(index_uid.clone().into_inner()).await?;
+                let fast = *fast;
+                let report = spawn_blocking(move || index.check(fast)).await??;
+
+                Ok(TaskResult::IndexCheck { report })
+            }
+            TaskContent::StatsRepair { index_uid } => {
+                let index = self.get_index(index_uid.clone().into_inner()).await?;
+                let report = spawn_blocking(move || index.repair_stats(false)).await??;
+
+                Ok(TaskResult::StatsRepair { report })
+            }
+            TaskContent::IndexRebuild { index_uid } => {
+                let rebuilt_documents = self
+                    .rebuild_index(index_uid.clone().into_inner(), task.id)
+                    .await?;
+
+                Ok(TaskResult::IndexRebuild { rebuilt_documents })
+            }
+            TaskContent::SettingsPreview {
+                index_uid,
+                settings,
+                queries,
+                sample_size,
+                limit,
+            } => {
+                let preview = self
+                    .preview_settings(
+                        index_uid.clone().into_inner(),
+                        settings.clone(),
+                        queries.clone(),
+                        *sample_size,
+                        *limit,
+                    )
+                    .await?;
+
+                Ok(TaskResult::SettingsPreview { preview })
+            }
+            TaskContent::DocumentAdditionFromUrl {
+                index_uid,
+                url,
+                format,
+                checksum,
+                merge_strategy,
+                primary_key,
+                allow_index_creation,
+            } => {
+                let index = if *allow_index_creation {
+                    self.get_or_create_index(index_uid.clone(), task.id).await?
+                } else {
+                    self.get_index(index_uid.clone().into_inner()).await?
+                };
+
+                let (content_uuid, _) = self
+                    .fetcher
+                    .fetch(url, *format, checksum.as_deref(), &self.file_store)
+                    .await?;
+
+                let method = *merge_strategy;
+                let primary_key = primary_key.clone();
+                let file_store = self.file_store.clone();
+                let progress = self.progress.reporter(vec![task.id]);
+                let result = spawn_blocking(move || {
+                    index.update_documents(
+                        method,
+                        primary_key,
+                        file_store,
+                        std::iter::once(DocumentsPayload {
+                            content_uuid,
+                            reject_duplicates: false,
+                            diff: None,
+                            digest: None,
+                            over_soft_limits: 0,
+                        }),
+                        |step| progress.report(step),
+                    )
+                })
+                .await;
+
+                if let Err(e) = self.file_store.delete(content_uuid).await {
+                    log::error!("error deleting update file: {}", e);
+                }
+
+                let addition = result??;
+                let (duplicate_documents, transforms) = match addition.payloads.into_iter().next() {
+                    Some(payload) => {
+                        let payload = payload?;
+                        (payload.duplicate_documents, payload.transforms)
+                    }
+                    None => (0, None),
+                };
+                Ok(TaskResult::DocumentAddition {
+                    indexed_documents: addition.indexed_documents,
+                    duplicate_documents,
+                    diff: None,
+                    transforms,
+                })
+            }
+            TaskContent::DocumentEdition {
+                index_uid,
+                filter,
+                edits,
+            } => {
+                let index = self.get_index(index_uid.clone().into_inner()).await?;
+                let filter = filter.clone();
+                let edits = edits.clone();
+                let progress = self.progress.reporter(vec![task.id]);
+                let edition = spawn_blocking(move || {
+                    index.edit_documents(filter.as_ref(), &edits, |seen, total| {
+                        progress.report_edition(seen, total)
+                    })
+                })
+                .await??;
+
+                Ok(TaskResult::DocumentEdition {
+                    matched_documents: edition.matched_documents,
+                    edited_documents: edition.edited_documents,
+                    invalid_documents: edition.invalid_documents,
+                })
+            }
+            TaskContent::NamespaceDeletion { namespace } => {
+                let mut deleted_indexes = 0;
+                let mut deleted_documents = 0;
+                for index_uid in self.namespace_indexes(namespace).await? {
+                    let index = self.delete_index(index_uid).await?;
+                    deleted_indexes += 1;
+                    deleted_documents += spawn_blocking(move || -> IndexResult<u64> {
+                        Ok(index.stats()?.number_of_documents)
+                    })
+                    .await??;
+                }
+
+                self.index_uuid_store
+                    .delete_namespace(namespace.clone())
+                    .await?
+                    .ok_or_else(|| IndexResolverError::UnexistingNamespace(namespace.clone()))?;
+
+                Ok(TaskResult::NamespaceDeletion {
+                    deleted_indexes,
+                    deleted_documents,
+                })
+            }
             _ => unreachable!("Invalid task for index resolver"),
         }
     }
 
     pub async fn dump(&self, path: impl AsRef<Path>) -> Result<()> {
-        for (_, index) in self.list().await? {
-            index.dump(&path)?;
+        for (_, IndexMeta { uuid, closed, .. }) in self.index_uuid_store.list().await? {
+            if let Some(index) = self.index_store.get(uuid).await? {
+                index.dump(&path)?;
+                // a closed index is only opened for the time of its dump.
+                if closed {
+                    self.index_store.close(uuid).await?;
+                }
+            }
         }
         self.index_uuid_store.dump(path.as_ref().to_owned()).await?;
         Ok(())
     }
 
-    async fn create_index(&self, uid: IndexUid, creation_task_id: TaskId) -> Result<Index> {
+    async fn create_index(
+        &self,
+        uid: IndexUid,
+        name: Option<String>,
+        namespace: Option<String>,
+        creation_task_id: TaskId,
+        skip_templates: bool,
+    ) -> Result<Index> {
+        let namespace = namespace.filter(|namespace| namespace != DEFAULT_NAMESPACE);
+        if let Some(ref namespace) = namespace {
+            if !self.namespace_exists(namespace).await? {
+                return Err(IndexResolverError::UnexistingNamespace(namespace.clone()));
+            }
+        }
+
         match self.index_uuid_store.get(uid.into_inner()).await? {
             (uid, Some(_)) => Err(IndexResolverError::IndexAlreadyExists(uid)),
             (uid, None) => {
+                let templates = if skip_templates {
+                    None
+                } else {
+                    self.templates.resolve(&uid)
+                };
                 let uuid = Uuid::new_v4();
                 let index = self.index_store.create(uuid).await?;
-                match self
-                    .index_uuid_store
-                    .insert(
-                        uid,
-                        IndexMeta {
-                            uuid,
-                            creation_task_id,
-                        },
-                    )
-                    .await
-                {
+                let created = async {
+                    if let Some(ref templates) = templates {
+                        let settings = templates.settings().check();
+                        let index = index.clone();
+                        spawn_blocking(move || index.update_settings(&settings)).await??;
+                    }
+
+                    let meta = IndexMeta {
+                        uuid,
+                        creation_task_id,
+                        name,
+                        namespace,
+                        former_uuids: Vec::new(),
+                        templates: templates
+                            .map(|templates| templates.patterns)
+                            .unwrap_or_default(),
+                        skip_templates,
+                    };
+                    self.index_uuid_store.insert(uid, meta).await
+                }
+                .await;
+                match created {
                     Err(e) => {
                         match self.index_store.delete(uuid).await {
                             Ok(Some(index)) => {
@@ -353,19 +1042,107 @@ where
         }
     }
 
+    /// Rebuilds the index `uid` in a new environment, that replaces the current one once the
+    /// rebuild succeeded. The current environment is left untouched if the rebuild fails.
+    async fn rebuild_index(&self, uid: String, task_id: TaskId) -> Result<u64> {
+        let index = self.get_index(uid.clone()).await?;
+
+        // The new environment grows up to the size of the current one, and indexing the documents
+        // needs about as much temporary space.
+        let required = index.size().saturating_mul(2);
+        if let Some(available) = self.index_store.available_space().await? {
+            if available < required {
+                return Err(IndexResolverError::NotEnoughSpaceToRebuild {
+                    required,
+                    available,
+                });
+            }
+        }
+
+        let uuid = Uuid::new_v4();
+        let rebuilt = self.index_store.create(uuid).await?;
+        let progress = self.progress.reporter(vec![task_id]);
+        let result = async {
+            let src = index.clone();
+            let rebuilt_documents =
+                spawn_blocking(move || src.rebuild_into(&rebuilt, |step| progress.report(step)))
+                    .await??;
+            // The tasks processed from now on use the new environment.
+            self.index_uuid_store.swap_uuid(uid, uuid).await?;
+            Ok::<_, IndexResolverError>(rebuilt_documents)
+        }
+        .await;
+
+        let discarded = match result {
+            Ok(_) => index.uuid(),
+            Err(_) => uuid,
+        };
+        match self.index_store.delete(discarded).await {
+            Ok(Some(index)) => index.close(),
+            Ok(None) => (),
+            Err(e) => log::error!("Error while deleting index {}: {:?}", discarded, e),
+        }
+
+        result
+    }
+
+    async fn preview_settings(
+        &self,
+        uid: String,
+        settings: Settings<Unchecked>,
+        queries: Vec<String>,
+        sample_size: usize,
+        limit: usize,
+    ) -> Result<SettingsPreview> {
+        let index = self.get_index(uid).await?;
+        let budget = PreviewBudget {
+            disk: SETTINGS_PREVIEW_DISK_BUDGET,
+            time: SETTINGS_PREVIEW_TIME_BUDGET,
+        };
+
+        spawn_blocking(move || {
+            // The shadow index is deleted with its directory.
+            let shadow_dir = tempfile::tempdir()?;
+            // Indexing the sample needs about as much temporary space as the shadow index.
+            let required = (budget.disk as u64).saturating_mul(2);
+            if let Some(available) = index_store::available_space(shadow_dir.path()) {
+                if available < required {
+                    return Err(IndexResolverError::NotEnoughSpaceToPreview {
+                        required,
+                        available,
+                    });
+                }
+            }
+            let preview = index.preview_settings(
+                shadow_dir.path(),
+                &settings,
+                &queries,
+                sample_size,
+                limit,
+                budget,
+            )?;
+            Ok(preview)
+        })
+        .await?
+    }
+
     /// Get or create an index with name `uid`.
     pub async fn get_or_create_index(&self, uid: IndexUid, task_id: TaskId) -> Result<Index> {
-        match self.create_index(uid, task_id).await {
+        match self.create_index(uid, None, None, task_id, false).await {
             Ok(index) => Ok(index),
             Err(IndexResolverError::IndexAlreadyExists(uid)) => self.get_index(uid).await,
             Err(e) => Err(e),
         }
     }
 
+    /// Returns the indexes that are open.
     pub async fn list(&self) -> Result<Vec<(String, Index)>> {
         let uuids = self.index_uuid_store.list().await?;
         let mut indexes = Vec::new();
-        for (name, IndexMeta { uuid, .. }) in uuids {
+        for (name, IndexMeta { uuid, closed, .. }) in uuids {
+            if closed {
+                continue;
+            }
             match self.index_store.get(uuid).await? {
                 Some(index) => indexes.push((name, index)),
                 None => {
@@ -380,19 +1157,59 @@ where
 
     pub async fn delete_index(&self, uid: String) -> Result<Index> {
         match self.index_uuid_store.delete(uid.clone()).await? {
-            Some(IndexMeta { uuid, .. }) => match self.index_store.delete(uuid).await? {
-                Some(index) => {
-                    index.clone().close();
-                    Ok(index)
+            Some(IndexMeta { uuid, closed, .. }) => {
+                if closed {
+                    // the environment of a closed index is opened again to be deleted.
+                    self.closed.remove(&uid);
+                    self.index_store.get(uuid).await?;
                 }
-                None => Err(IndexResolverError::UnexistingIndex(uid)),
-            },
+                match self.index_store.delete(uuid).await? {
+                    Some(index) => {
+                        index.clone().close();
+                        Ok(index)
+                    }
+                    None => Err(IndexResolverError::UnexistingIndex(uid)),
+                }
+            }
             None => Err(IndexResolverError::UnexistingIndex(uid)),
         }
     }
 
+    /// Closes the index `uid`: its environment is closed once the operations using it are
+    /// finished, and it stays closed, restarts included, until it is opened again. Closing an
+    /// index that is already closed does nothing.
+    pub async fn close_index(&self, uid: String) -> Result<Uuid> {
+        let (uid, meta) = self.index_uuid_store.get(uid).await?;
+        let meta = meta.ok_or_else(|| IndexResolverError::UnexistingIndex(uid.clone()))?;
+        if !meta.closed {
+            self.index_uuid_store.set_closed(uid.clone(), true).await?;
+            self.closed.insert(uid);
+            self.index_store.close(meta.uuid).await?;
+        }
+        Ok(meta.uuid)
+    }
+
+    /// Opens the index `uid` again. Its environment is opened by the next operation using it.
+    pub async fn open_index(&self, uid: String) -> Result<()> {
+        let (uid, meta) = self.index_uuid_store.get(uid).await?;
+        let meta = meta.ok_or_else(|| IndexResolverError::UnexistingIndex(uid.clone()))?;
+        if meta.closed {
+            self.index_uuid_store.set_closed(uid.clone(), false).await?;
+            self.closed.remove(&uid);
+        }
+        Ok(())
+    }
+
+    /// Returns the uids of the closed indexes.
+    pub fn closed_indexes(&self) -> Vec<String> {
+        self.closed.uids()
+    }
+
     pub async fn get_index(&self, uid: String) -> Result<Index> {
         match self.index_uuid_store.get(uid).await? {
+            (name, Some(IndexMeta { closed: true, .. })) => {
+                Err(IndexResolverError::IndexClosed(name))
+            }
             (name, Some(IndexMeta { uuid, .. })) => {
                 match self.index_store.get(uuid).await? {
                     Some(index) => Ok(index),
@@ -408,6 +1225,217 @@ where
         }
     }
 
+    /// Records the documents changed by the applied `batch` in the changefeed of its index. The
+    /// ids of the added documents are read from the update files of the batch.
+    pub(crate) async fn record_changes(&self, batch: &Batch) -> Result<()> {
+        let batch_id = match batch.id {
+            Some(batch_id) if self.changefeed.is_enabled() => batch_id,
+            _ => return Ok(()),
+        };
+        let applied: Vec<&Task> = match batch.content {
+            BatchContent::DocumentsAdditionBatch(ref tasks)
+            | BatchContent::DocumentDeletionBatch(ref tasks) => tasks.iter().collect(),
+            BatchContent::IndexUpdate(ref task) => vec![task],
+            _ => return Ok(()),
+        };
+        let applied: Vec<&Task> = applied
+            .into_iter()
+            .filter(|task| {
+                matches!(
+                    task.events.last(),
+                    Some(TaskEvent::Succeeded { .. } | TaskEvent::ChunkProcessed { .. })
+                )
+            })
+            .collect();
+        let index_uid = match applied.first().and_then(|task| task.index_uid()) {
+            Some(index_uid) => index_uid.to_string(),
+            None => return Ok(()),
+        };
+
+        let mut ids = Vec::new();
+        let mut truncated = false;
+        let mut all_documents = false;
+        let mut operation = None;
+        for task in &applied {
+            match task.content {
+                TaskContent::DocumentAddition { .. } => {
+                    operation = Some(ChangeOperation::DocumentAddition)
+                }
+                TaskContent::DocumentDeletion {
+                    deletion: DocumentDeletion::Ids(ref deleted),
+                    ..
+                } => {
+                    operation.get_or_insert(ChangeOperation::DocumentDeletion);
+                    let room = MAX_CHANGE_RECORD_IDS.saturating_sub(ids.len());
+                    truncated |= deleted.len() > room;
+                    ids.extend(deleted.iter().take(room).cloned());
+                }
+                TaskContent::DocumentDeletion {
+                    deletion: DocumentDeletion::Clear,
+                    ..
+                } => {
+                    operation = Some(ChangeOperation::ClearAll);
+                    all_documents = true;
+                }
+                TaskContent::IndexDeletion { .. } => {
+                    operation = Some(ChangeOperation::IndexDeletion);
+                    all_documents = true;
+                }
+                _ => (),
+            }
+        }
+        let operation = match operation {
+            Some(operation) => operation,
+            None => return Ok(()),
+        };
+
+        if operation == ChangeOperation::DocumentAddition {
+            let index = self.get_index(index_uid.clone()).await?;
+            let mut payloads = Vec::new();
+            for uuid in applied.iter().flat_map(|task| task.batch_content_uuids()) {
+                payloads.push(self.file_store.get_update(uuid).map_err(IndexError::from)?);
+            }
+            let (added, added_truncated) = spawn_blocking(move || -> Result<_> {
+                let mut ids = Vec::new();
+                for payload in payloads {
+                    let room = MAX_CHANGE_RECORD_IDS - ids.len();
+                    let (payload_ids, truncated) = index.payload_external_ids(payload, room)?;
+                    ids.extend(payload_ids);
+                    if truncated {
+                        return Ok((ids, true));
+                    }
+                }
+                Ok((ids, false))
+            })
+            .await??;
+            ids = added;
+            truncated = added_truncated;
+        }
+
+        let record = ChangeRecord {
+            batch_id,
+            task_ids: applied.iter().map(|task| task.id).collect(),
+            operation,
+            all_documents,
+            // the ids are meaningless once all the documents changed.
+            document_ids: if all_documents { Vec::new() } else { ids },
+            truncated: truncated && !all_documents,
+            applied_at: OffsetDateTime::now_utc(),
+        };
+        let changefeed = self.changefeed.clone();
+        spawn_blocking(move || changefeed.record(&index_uid, &record)).await??;
+
+        Ok(())
+    }
+
+    /// Returns the name of every index that has one, by index uid.
+    pub async fn index_names(&self) -> Result<HashMap<String, String>> {
+        let names = self
+            .index_uuid_store
+            .list()
+            .await?
+            .into_iter()
+            .filter_map(|(uid, meta)| Some((uid, meta.name?)))
+            .collect();
+
+        Ok(names)
+    }
+
+    pub async fn get_index_name(&self, index_uid: String) -> Result<Option<String>> {
+        let (uid, meta) = self.index_uuid_store.get(index_uid).await?;
+        meta.map(|IndexMeta { name, .. }| name)
+            .ok_or(IndexResolverError::UnexistingIndex(uid))
+    }
+
+    /// Returns the settings the templates give to the index `uid`, unless it opted out of them.
+    async fn index_templates(&self, uid: &str) -> Result<Option<ResolvedTemplates>> {
+        if self.templates.is_empty() {
+            return Ok(None);
+        }
+        match self.get_index_meta(uid.to_string()).await? {
+            Some(meta) if !meta.skip_templates => Ok(self.templates.resolve(uid)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the patterns of the templates that contributed to the settings of every index
+    /// created from templates, and whether each index opted out of them, by index uid.
+    pub async fn index_templates_meta(&self) -> Result<HashMap<String, (Vec<String>, bool)>> {
+        let templates = self
+            .index_uuid_store
+            .list()
+            .await?
+            .into_iter()
+            .map(|(uid, meta)| (uid, (meta.templates, meta.skip_templates)))
+            .collect();
+
+        Ok(templates)
+    }
+
+    /// The metadata of the index `index_uid`, if it exists.
+    pub async fn get_index_meta(&self, index_uid: String) -> Result<Option<IndexMeta>> {
+        let (_, meta) = self.index_uuid_store.get(index_uid).await?;
+        Ok(meta)
+    }
+
+    /// Creates an empty namespace.
+    pub async fn create_namespace(&self, name: String) -> Result<NamespaceMeta> {
+        let name = check_namespace(name)?;
+        let meta = NamespaceMeta {
+            created_at: OffsetDateTime::now_utc(),
+        };
+        self.index_uuid_store
+            .insert_namespace(name, meta.clone())
+            .await?;
+        Ok(meta)
+    }
+
+    /// Returns the namespaces that were created, the default namespace excluded.
+    pub async fn list_namespaces(&self) -> Result<Vec<(String, NamespaceMeta)>> {
+        self.index_uuid_store.list_namespaces().await
+    }
+
+    pub async fn namespace_exists(&self, name: &str) -> Result<bool> {
+        if name == DEFAULT_NAMESPACE {
+            return Ok(true);
+        }
+        let meta = self
+            .index_uuid_store
+            .get_namespace(name.to_string())
+            .await?;
+        Ok(meta.is_some())
+    }
+
+    /// Returns the uids of the indexes of the namespace `name`.
+    pub async fn namespace_indexes(&self, name: &str) -> Result<Vec<String>> {
+        let uids = self
+            .index_uuid_store
+            .list()
+            .await?
+            .into_iter()
+            .filter(|(_, meta)| meta.namespace() == name)
+            .map(|(uid, _)| uid)
+            .collect();
+
+        Ok(uids)
+    }
+
+    /// Returns the namespace of every index, by index uid.
+    pub async fn index_namespaces(&self) -> Result<HashMap<String, String>> {
+        let namespaces = self
+            .index_uuid_store
+            .list()
+            .await?
+            .into_iter()
+            .map(|(uid, meta)| {
+                let namespace = meta.namespace().to_string();
+                (uid, namespace)
+            })
+            .collect();
+
+        Ok(namespaces)
+    }
+
     pub async fn get_index_creation_task_id(&self, index_uid: String) -> Result<TaskId> {
         let (uid, meta) = self.index_uuid_store.get(index_uid).await?;
         meta.map(
@@ -421,6 +1449,63 @@ where
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// The canonical definition of a valid index uid.
+    fn is_valid_uid(uid: &str) -> bool {
+        (1..=400).contains(&uid.len())
+            && uid
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    }
+
+    proptest! {
+        #[test]
+        fn test_index_uid_validation(uid in "\\PC{0,10}|[a-zA-Z0-9_-]{0,410}|[a-z!é _-]{0,20}") {
+            let result = IndexUid::new(uid.clone());
+            prop_assert_eq!(result.is_ok(), is_valid_uid(&uid));
+            prop_assert_eq!(uid.parse::<IndexUid>().is_ok(), result.is_ok());
+            prop_assert_eq!(IndexUid::try_from(uid.clone()).is_ok(), result.is_ok());
+
+            // a deserialized index uid is validated as well.
+            let deserialized = serde_json::from_value::<IndexUid>(serde_json::json!(uid));
+            prop_assert_eq!(deserialized.is_ok(), result.is_ok());
+            if let Ok(index_uid) = result {
+                prop_assert_eq!(index_uid.as_str(), uid.as_str());
+                prop_assert_eq!(serde_json::to_value(&index_uid).unwrap(), serde_json::json!(uid));
+            }
+        }
+
+        #[test]
+        fn test_generated_index_uids_are_valid(index_uid in any::<IndexUid>()) {
+            prop_assert!(IndexUid::new(index_uid.clone().into_inner()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_index_uid_validation_errors() {
+        let reason = |uid: &str| match IndexUid::new(uid.to_string()) {
+            Err(IndexResolverError::BadlyFormatted { reason, .. }) => Some(reason),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => None,
+        };
+
+        assert_eq!(reason("movies_2022-fr"), None);
+        assert_eq!(reason(""), Some(InvalidIndexUid::Empty));
+        assert_eq!(
+            reason(&"a".repeat(401)),
+            Some(InvalidIndexUid::TooLong(401))
+        );
+        assert_eq!(
+            reason("test test#!"),
+            Some(InvalidIndexUid::InvalidChar(' ', 4))
+        );
+        assert_eq!(reason("éa!"), Some(InvalidIndexUid::InvalidChar('é', 0)));
+        assert_eq!(reason("ab😀"), Some(InvalidIndexUid::InvalidChar('😀', 2)));
+    }
+
     // use std::{collections::BTreeMap, vec::IntoIter};
     //
     // use super::*;
@@ -0,0 +1,303 @@
+//! Index templates: fragments of settings applied to the indexes whose uid matches a pattern.
+//!
+//! The templates are declared in a JSON file given on startup, as a list of `pattern` and
+//! `settings` objects. A pattern is either an index uid, or a prefix followed by `*`. The templates
+//! matching an index are layered: they are merged setting by setting, from the least specific to
+//! the most specific one, so that a more specific template overrides the settings it declares and
+//! inherits the others. The longest pattern is the most specific, an exact uid being more specific
+//! than a prefix of the same length. Between patterns equally specific, the one declared first
+//! wins.
+//!
+//! The templates are applied to the settings of an index when it is created, and when its settings
+//! are reset: a reset setting goes back to the value of the templates rather than to its default.
+//! An index can opt out of the templates, which are then never applied to it again.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::index::{Settings, Unchecked};
+
+/// The settings whose reset is serialized as `["*"]` rather than `null`.
+const WILDCARD_SETTINGS: &[&str] = &["displayedAttributes", "searchableAttributes"];
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IndexTemplate {
+    pub pattern: String,
+    /// The settings of the template, as they are written in a settings update.
+    pub settings: Map<String, Value>,
+}
+
+impl IndexTemplate {
+    /// Returns the specificity of the pattern if it matches `index_uid`.
+    fn matches(&self, index_uid: &str) -> Option<(usize, bool)> {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => index_uid.starts_with(prefix).then(|| (prefix.len(), false)),
+            None => (self.pattern == index_uid).then(|| (self.pattern.len(), true)),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let literal = self.pattern.strip_suffix('*').unwrap_or(&self.pattern);
+        let valid = literal
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if self.pattern.is_empty() || !valid {
+            anyhow::bail!(
+                "`{}` is not a valid template pattern. A pattern is an index uid, optionally followed by `*`.",
+                self.pattern
+            );
+        }
+        serde_json::from_value::<Settings<Unchecked>>(Value::Object(self.settings.clone()))
+            .with_context(|| format!("Invalid settings for the template `{}`", self.pattern))?;
+        Ok(())
+    }
+}
+
+/// The index templates, in their declaration order.
+#[derive(Debug, Clone, Default)]
+pub struct IndexTemplates(Vec<IndexTemplate>);
+
+/// The settings the templates give to an index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedTemplates {
+    /// The patterns of the templates that contributed, from the least specific one.
+    pub patterns: Vec<String>,
+    pub settings: Map<String, Value>,
+}
+
+impl ResolvedTemplates {
+    pub fn settings(&self) -> Settings<Unchecked> {
+        // the settings of each template were checked when the templates were loaded.
+        serde_json::from_value(Value::Object(self.settings.clone()))
+            .expect("the settings of the templates are valid")
+    }
+}
+
+impl IndexTemplates {
+    pub fn new(templates: Vec<IndexTemplate>) -> anyhow::Result<Self> {
+        for template in &templates {
+            template.validate()?;
+        }
+        Ok(Self(templates))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Loads the templates declared in the JSON file at `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read the index templates at {:?}", path))?;
+        let templates = serde_json::from_str(&content)
+            .with_context(|| format!("Could not parse the index templates at {:?}", path))?;
+        Self::new(templates)
+    }
+
+    /// Returns the settings the templates give to the index `index_uid`, if any template matches
+    /// it.
+    pub fn resolve(&self, index_uid: &str) -> Option<ResolvedTemplates> {
+        resolve_templates(&self.0, index_uid)
+    }
+}
+
+/// Merges the settings of the `templates` matching `index_uid`, the most specific one last.
+pub fn resolve_templates(
+    templates: &[IndexTemplate],
+    index_uid: &str,
+) -> Option<ResolvedTemplates> {
+    let mut matching: Vec<_> = templates
+        .iter()
+        .enumerate()
+        .filter_map(|(position, template)| {
+            let specificity = template.matches(index_uid)?;
+            Some((specificity, position, template))
+        })
+        .collect();
+    if matching.is_empty() {
+        return None;
+    }
+    // the least specific first, and between patterns equally specific, the last declared first.
+    matching.sort_by(|(a, a_position, _), (b, b_position, _)| {
+        a.cmp(b).then(b_position.cmp(a_position))
+    });
+
+    let mut resolved = ResolvedTemplates {
+        patterns: Vec::with_capacity(matching.len()),
+        settings: Map::new(),
+    };
+    for (_, _, template) in matching {
+        if resolved.patterns.last() != Some(&template.pattern) {
+            resolved.patterns.push(template.pattern.clone());
+        }
+        for (name, value) in &template.settings {
+            resolved.settings.insert(name.clone(), value.clone());
+        }
+    }
+    Some(resolved)
+}
+
+/// Replaces the settings reset by the settings update `settings` with the value the templates
+/// give them.
+pub fn reset_to_templates(
+    settings: &Settings<Unchecked>,
+    templates: &ResolvedTemplates,
+) -> Settings<Unchecked> {
+    let mut update = match serde_json::to_value(settings) {
+        Ok(Value::Object(update)) => update,
+        _ => unreachable!("settings are serialized as an object"),
+    };
+    for (name, value) in update.iter_mut() {
+        let is_reset = value.is_null()
+            || (WILDCARD_SETTINGS.contains(&name.as_str()) && *value == Value::from(vec!["*"]));
+        if is_reset {
+            if let Some(template_value) = templates.settings.get(name) {
+                *value = template_value.clone();
+            }
+        }
+    }
+    serde_json::from_value(Value::Object(update)).expect("the settings of the templates are valid")
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn templates(templates: Value) -> Vec<IndexTemplate> {
+        let templates: Vec<IndexTemplate> = serde_json::from_value(templates).unwrap();
+        IndexTemplates::new(templates.clone()).unwrap();
+        templates
+    }
+
+    #[test]
+    fn test_resolve_templates() {
+        let declared = templates(json!([
+            { "pattern": "*", "settings": { "rankingRules": ["words"], "stopWords": ["the"] } },
+            { "pattern": "logs-*", "settings": { "filterableAttributes": ["level"], "stopWords": [] } },
+            { "pattern": "logs-app-*", "settings": { "filterableAttributes": ["level", "app"] } },
+            { "pattern": "logs-app-a", "settings": { "searchableAttributes": ["message"] } },
+            // as long as `logs-app-a`, but a prefix is less specific than an exact uid.
+            { "pattern": "logs-app-a*", "settings": { "searchableAttributes": ["title"] } },
+            // the same pattern as a template declared before it, which wins.
+            { "pattern": "logs-*", "settings": { "filterableAttributes": [], "displayedAttributes": ["level"] } },
+        ]));
+
+        let cases = [
+            (
+                "movies",
+                Some((
+                    vec!["*"],
+                    json!({ "rankingRules": ["words"], "stopWords": ["the"] }),
+                )),
+            ),
+            (
+                "logs-web",
+                Some((
+                    vec!["*", "logs-*"],
+                    json!({
+                        "rankingRules": ["words"],
+                        "stopWords": [],
+                        "filterableAttributes": ["level"],
+                        "displayedAttributes": ["level"],
+                    }),
+                )),
+            ),
+            (
+                "logs-app-b",
+                Some((
+                    vec!["*", "logs-*", "logs-app-*"],
+                    json!({
+                        "rankingRules": ["words"],
+                        "stopWords": [],
+                        "filterableAttributes": ["level", "app"],
+                        "displayedAttributes": ["level"],
+                    }),
+                )),
+            ),
+            (
+                "logs-app-a",
+                Some((
+                    vec!["*", "logs-*", "logs-app-*", "logs-app-a*", "logs-app-a"],
+                    json!({
+                        "rankingRules": ["words"],
+                        "stopWords": [],
+                        "filterableAttributes": ["level", "app"],
+                        "displayedAttributes": ["level"],
+                        "searchableAttributes": ["message"],
+                    }),
+                )),
+            ),
+        ];
+        for (uid, expected) in cases {
+            let resolved = resolve_templates(&declared, uid);
+            let expected = expected.map(|(patterns, settings)| ResolvedTemplates {
+                patterns: patterns.into_iter().map(String::from).collect(),
+                settings: settings.as_object().unwrap().clone(),
+            });
+            assert_eq!(resolved, expected, "{}", uid);
+        }
+
+        assert_eq!(resolve_templates(&declared[1..], "movies"), None);
+    }
+
+    #[test]
+    fn test_reset_to_templates() {
+        let declared = templates(json!([
+            {
+                "pattern": "logs-*",
+                "settings": { "stopWords": ["the"], "synonyms": { "a": ["b"] }, "displayedAttributes": ["message"] },
+            },
+        ]));
+        let resolved = resolve_templates(&declared, "logs-web").unwrap();
+
+        let cases = [
+            // a full reset goes back to the settings of the templates.
+            (
+                Settings::cleared().into_unchecked(),
+                json!({
+                    "stopWords": ["the"],
+                    "synonyms": { "a": ["b"] },
+                    "displayedAttributes": ["message"],
+                    "filterableAttributes": null,
+                }),
+            ),
+            (
+                serde_json::from_value(json!({ "stopWords": null, "rankingRules": null })).unwrap(),
+                json!({ "stopWords": ["the"], "rankingRules": null }),
+            ),
+            // the settings that are set are left as they are.
+            (
+                serde_json::from_value(json!({ "stopWords": ["a"] })).unwrap(),
+                json!({ "stopWords": ["a"] }),
+            ),
+        ];
+        for (settings, expected) in cases {
+            let reset = reset_to_templates(&settings, &resolved);
+            let reset = serde_json::to_value(&reset).unwrap();
+            for (name, value) in expected.as_object().unwrap() {
+                assert_eq!(&reset[name], value, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for invalid in [
+            json!([{ "pattern": "", "settings": {} }]),
+            json!([{ "pattern": "logs-*-app", "settings": {} }]),
+            json!([{ "pattern": "logs", "settings": { "unknown": true } }]),
+            json!([{ "pattern": "logs", "settings": { "stopWords": "the" } }]),
+        ] {
+            let templates: Vec<IndexTemplate> = serde_json::from_value(invalid.clone()).unwrap();
+            assert!(IndexTemplates::new(templates).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -3,19 +3,36 @@ pub mod error;
 pub mod options;
 
 mod analytics;
+pub mod calendar;
+pub mod changefeed;
+mod closed_indexes;
+mod compaction;
+mod data_lock;
 mod dump;
 pub mod index;
 pub mod index_controller;
+pub mod index_locks;
 mod index_resolver;
+mod meta_env;
+mod pending_payloads;
+mod points_in_time;
+pub mod replica;
+pub mod rotating_file;
+mod search_queue;
+pub mod segmenters;
 mod snapshot;
 pub mod tasks;
+pub mod throttle;
 mod update_file_store;
+mod warmup;
 
 use std::path::Path;
 
+pub use dump::{reindex_dump, IndexReindexReport, ReindexOutcome, ReindexReport};
 pub use index_controller::{IndexUid, MeiliSearch};
 pub use milli;
 pub use milli::heed;
+pub use snapshot::{verify_snapshot, FileReport, FileStatus, SnapshotReport};
 
 mod compression;
 pub mod document_formats;
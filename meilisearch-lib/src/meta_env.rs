@@ -0,0 +1,246 @@
+//! The environment of the `data.mdb` file at the root of the database, which holds the metadata
+//! of the indexes and the tasks.
+//!
+//! The file never shrinks: the pages freed by the deleted tasks and indexes are only reused by the
+//! next writes. A task store compaction replaces it with a compacted copy of itself while
+//! Meilisearch is running. The stores built on the environment are closed in the meantime, so that
+//! nothing reads nor writes the environment, and their calls fail with an error telling to retry
+//! later. The compaction is processed as a batch, so no other batch is processed meanwhile.
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use milli::heed::CompactionOption;
+use parking_lot::RwLock;
+
+use crate::index_controller::open_meta_env;
+use crate::index_resolver::index_store::available_space;
+use crate::index_resolver::meta_store::HeedMetaStore;
+use crate::tasks::error::{Result, TaskError};
+use crate::tasks::TaskStore;
+
+const META_ENV_FILE_NAME: &str = "data.mdb";
+/// The compacted copy of the meta environment, until it replaces it.
+const COMPACTED_FILE_NAME: &str = "data.mdb.compacted";
+/// How often a store being closed checks whether the calls using it are finished.
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A store built on the meta environment, which is closed while the environment is replaced.
+/// The clones share the same store.
+pub struct Closable<T> {
+    inner: Arc<RwLock<Option<Arc<T>>>>,
+}
+
+impl<T> Clone for Closable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Closable<T> {
+    pub fn new(store: T) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Some(Arc::new(store)))),
+        }
+    }
+
+    /// Returns the store, or `None` while it is closed.
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.inner.read().clone()
+    }
+
+    /// Closes the store, and returns it once the calls that got it before are finished. Blocks the
+    /// current thread. Returns `None` if the store was already closed.
+    pub fn close(&self) -> Option<T> {
+        let mut store = self.inner.write().take()?;
+        loop {
+            match Arc::try_unwrap(store) {
+                Ok(store) => return Some(store),
+                Err(shared) => {
+                    store = shared;
+                    thread::sleep(CLOSE_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    /// Opens the store again, with `store`.
+    pub fn open(&self, store: T) {
+        *self.inner.write() = Some(Arc::new(store));
+    }
+
+    pub fn downgrade(&self) -> WeakClosable<T> {
+        WeakClosable {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+}
+
+/// A `Closable` that doesn't keep the store alive once all the `Closable`s are dropped.
+pub struct WeakClosable<T> {
+    inner: Weak<RwLock<Option<Arc<T>>>>,
+}
+
+impl<T> WeakClosable<T> {
+    pub fn upgrade(&self) -> Option<Closable<T>> {
+        self.inner.upgrade().map(|inner| Closable { inner })
+    }
+}
+
+/// Replaces the meta environment of the database at `db_path` with a compacted copy of itself,
+/// and returns the number of bytes reclaimed. The stores are closed until the copy replaced the
+/// environment. Blocks the current thread.
+pub fn compact(
+    db_path: &Path,
+    env_size: usize,
+    task_store: &TaskStore,
+    meta_store: &HeedMetaStore,
+) -> Result<u64> {
+    let path = db_path.join(META_ENV_FILE_NAME);
+    let size = fs::metadata(&path)?.len();
+    // the copy is at most as large as the current file.
+    if let Some(available) = available_space(db_path) {
+        if available < size {
+            return Err(TaskError::NotEnoughSpaceToCompact {
+                required: size,
+                available,
+            });
+        }
+    }
+
+    task_store.close();
+    meta_store.close();
+    let compacted = copy_compacted(db_path, env_size);
+
+    // the stores are opened again whether the copy replaced the environment or not.
+    let env = Arc::new(open_meta_env(db_path, env_size)?);
+    meta_store
+        .open(env.clone())
+        .map_err(|e| TaskError::Internal(Box::new(e)))?;
+    task_store.open(env)?;
+    compacted?;
+
+    Ok(size.saturating_sub(fs::metadata(&path)?.len()))
+}
+
+/// Copies the environment of the database at `db_path` without its free pages, and replaces the
+/// environment with the copy. No store must be using the environment.
+fn copy_compacted(db_path: &Path, env_size: usize) -> Result<()> {
+    let compacted_path = db_path.join(COMPACTED_FILE_NAME);
+    // the copy left by a compaction that was interrupted.
+    if compacted_path.exists() {
+        fs::remove_file(&compacted_path)?;
+    }
+
+    let env = open_meta_env(db_path, env_size)?;
+    env.copy_to_path(&compacted_path, CompactionOption::Enabled)?;
+    // the environment must be closed before its file is replaced, otherwise opening it again
+    // would return the environment of the replaced file.
+    env.prepare_for_closing().wait();
+    fs::rename(&compacted_path, db_path.join(META_ENV_FILE_NAME))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::index_resolver::meta_store::{IndexMeta, IndexMetaStore};
+    use crate::tasks::task::TaskContent;
+
+    const ENV_SIZE: usize = 100 * 1024 * 1024;
+
+    #[test]
+    fn test_closed_store_is_returned_once_unused() {
+        let store = Closable::new(42);
+        let used = store.get().unwrap();
+
+        let closing = store.clone();
+        let handle = thread::spawn(move || closing.close());
+        // the store is closed right away, even though it is still used.
+        while store.get().is_some() {
+            thread::yield_now();
+        }
+        drop(used);
+        assert_eq!(handle.join().unwrap(), Some(42));
+
+        assert_eq!(store.close(), None);
+        store.open(43);
+        assert_eq!(*store.get().unwrap(), 43);
+    }
+
+    #[actix_rt::test]
+    async fn test_compact_while_reading_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Arc::new(open_meta_env(dir.path(), ENV_SIZE).unwrap());
+        let task_store = TaskStore::new(env.clone()).unwrap();
+        let meta_store = HeedMetaStore::new(env).unwrap();
+        let task = task_store
+            .register(TaskContent::TaskStoreCompaction, None)
+            .await
+            .unwrap();
+
+        // the deleted indexes leave free pages behind them.
+        for i in 0..1000 {
+            let meta = IndexMeta {
+                uuid: Uuid::new_v4(),
+                creation_task_id: i,
+                name: Some("a name long enough to fill the pages".repeat(4)),
+                namespace: None,
+                former_uuids: Vec::new(),
+                templates: Vec::new(),
+                skip_templates: false,
+                closed: false,
+            };
+            meta_store.insert(format!("index-{}", i), meta).unwrap();
+        }
+        for i in 0..1000 {
+            IndexMetaStore::delete(&meta_store, format!("index-{}", i))
+                .await
+                .unwrap();
+        }
+
+        let done = Arc::new(AtomicBool::new(false));
+        let compaction = {
+            let path = dir.path().to_path_buf();
+            let (task_store, meta_store) = (task_store.clone(), meta_store.clone());
+            let done = done.clone();
+            tokio::task::spawn_blocking(move || {
+                let result = compact(&path, ENV_SIZE, &task_store, &meta_store);
+                done.store(true, Ordering::SeqCst);
+                result
+            })
+        };
+
+        // the reads made while the environment is replaced fail with an error telling to retry.
+        while !done.load(Ordering::SeqCst) {
+            match task_store.get_task(task.id, None).await {
+                Ok(found) => assert_eq!(found.id, task.id),
+                Err(e) => assert!(matches!(e, TaskError::DatabaseUnavailable), "{}", e),
+            }
+        }
+        let reclaimed = compaction.await.unwrap().unwrap();
+        assert!(reclaimed > 0);
+
+        assert_eq!(
+            task_store.get_task(task.id, None).await.unwrap().id,
+            task.id
+        );
+        assert!(IndexMetaStore::list(&meta_store).await.unwrap().is_empty());
+
+        task_store.close();
+        assert!(matches!(
+            task_store.get_task(task.id, None).await,
+            Err(TaskError::DatabaseUnavailable)
+        ));
+    }
+}
@@ -4,9 +4,14 @@ use std::{convert::TryFrom, num::ParseIntError, ops::Deref, str::FromStr};
 use byte_unit::{Byte, ByteError};
 use clap::Parser;
 use milli::update::IndexerConfig;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use sysinfo::{RefreshKind, System, SystemExt};
 
+use crate::compaction::DEFAULT_COMPACTION_THRESHOLD;
+use crate::document_formats::{DocumentLimits, PayloadLimits};
+use crate::index::DEFAULT_MAX_READERS;
+use crate::index_resolver::DEFAULT_MAX_FULL_DIFF_DOCUMENTS;
+
 #[derive(Debug, Clone, Parser, Serialize)]
 pub struct IndexerOpts {
     /// The amount of documents to skip before printing
@@ -37,6 +42,126 @@ pub struct IndexerOpts {
     /// It defaults to half of the available threads.
     #[clap(long, env = "MEILI_MAX_INDEXING_THREADS", default_value_t)]
     pub max_indexing_threads: MaxThreads,
+
+    /// The maximum size, in bytes, of the documents fetched from a URL.
+    #[clap(long, env = "MEILI_MAX_FETCH_SIZE", default_value = "1 GiB")]
+    pub max_fetch_size: Byte,
+
+    /// The maximum duration, in seconds, of the download of documents fetched from a URL.
+    #[clap(long, env = "MEILI_FETCH_TIMEOUT_SEC", default_value = "300")]
+    pub fetch_timeout_sec: u64,
+
+    /// The hosts, separated by `,`, that documents can be fetched from even though they resolve
+    /// to a loopback, private, link-local or otherwise reserved address. Documents are only
+    /// fetched from public addresses by default.
+    #[clap(
+        long,
+        env = "MEILI_FETCH_ALLOWED_HOSTS",
+        use_value_delimiter = true,
+        value_delimiter = ','
+    )]
+    pub fetch_allowed_hosts: Vec<String>,
+
+    /// The duration, in seconds, after which an inactive document upload session expires.
+    #[clap(long, env = "MEILI_UPLOAD_SESSION_TTL_SEC", default_value = "86400")]
+    pub upload_session_ttl_sec: u64,
+
+    /// The duration, in seconds, during which a request reusing the idempotency key of a previous
+    /// request gets the task of that request back instead of registering a new one.
+    #[clap(
+        long,
+        env = "MEILI_IDEMPOTENCY_KEY_WINDOW_SEC",
+        default_value = "86400"
+    )]
+    pub idempotency_key_window_sec: u64,
+
+    /// The proportion of soft deleted documents of an index above which a compaction task is
+    /// registered to purge them.
+    #[clap(long, env = "MEILI_COMPACTION_THRESHOLD", default_value = "0.1")]
+    pub compaction_threshold: f64,
+
+    /// The maximum size of the payloads of the enqueued document additions of an index. Document
+    /// additions to an index whose pending payloads exceed this size are refused.
+    #[clap(long, env = "MEILI_MAX_PENDING_PAYLOAD_SIZE_PER_INDEX")]
+    pub max_pending_payload_size_per_index: Option<Byte>,
+
+    /// The maximum number of documents of a chunk of a document addition. The payloads with more
+    /// documents are split into chunks when they are received, and the chunks are indexed one
+    /// after the other, each in its own batch. An addition interrupted by a restart resumes from
+    /// its first chunk that wasn't indexed yet.
+    ///
+    /// The documents of a chunk can be searched as soon as it is indexed, before the other chunks
+    /// of the payload, unless `--defer-chunk-visibility` is set.
+    #[clap(long, env = "MEILI_DOCUMENT_CHUNK_SIZE")]
+    pub document_chunk_size: Option<usize>,
+
+    /// Indexes all the chunks of a document addition at once, so that none of its documents can
+    /// be searched before all of them are indexed. The additions don't resume from their last
+    /// indexed chunk anymore: an interrupted addition is indexed again from its first chunk.
+    #[clap(long, env = "MEILI_DEFER_CHUNK_VISIBILITY")]
+    pub defer_chunk_visibility: bool,
+
+    /// The maximum number of documents of an addition whose diff, asked with `returnDiff=full`,
+    /// lists the ids of the replaced documents. The diff of a larger addition only counts the
+    /// created and updated documents.
+    #[clap(long, env = "MEILI_MAX_FULL_DIFF_DOCUMENTS", default_value = "1000")]
+    pub max_full_diff_documents: usize,
+
+    /// The maximum number of read transactions opened at the same time on an index. The searches
+    /// and the other reads of an index that exceed it fail with a `too_many_concurrent_reads`
+    /// error, and can be retried.
+    #[clap(long, env = "MEILI_MAX_READERS", default_value = "126")]
+    pub max_readers: u32,
+
+    /// The maximum number of fields of a document, the nested fields included. The document
+    /// additions with a document exceeding it are refused.
+    #[clap(long, env = "MEILI_MAX_DOCUMENT_FIELDS")]
+    pub max_document_fields: Option<usize>,
+
+    /// The maximum length, in bytes, of the name of a field of a document, with the names of its
+    /// parents for a nested field. The document additions with a document exceeding it are refused.
+    #[clap(long, env = "MEILI_MAX_FIELD_NAME_LENGTH")]
+    pub max_field_name_length: Option<usize>,
+
+    /// The maximum size of a document, counting its field names and its JSON values. The document
+    /// additions with a document exceeding it are refused.
+    #[clap(long, env = "MEILI_MAX_DOCUMENT_SIZE")]
+    pub max_document_size: Option<Byte>,
+
+    /// Like `--max-document-fields`, but the documents exceeding it are indexed and counted in the
+    /// `warnings` of their task and in the stats of their index.
+    #[clap(long, env = "MEILI_SOFT_MAX_DOCUMENT_FIELDS")]
+    pub soft_max_document_fields: Option<usize>,
+
+    /// Like `--max-field-name-length`, but the documents exceeding it are indexed and counted in
+    /// the `warnings` of their task and in the stats of their index.
+    #[clap(long, env = "MEILI_SOFT_MAX_FIELD_NAME_LENGTH")]
+    pub soft_max_field_name_length: Option<usize>,
+
+    /// Like `--max-document-size`, but the documents exceeding it are indexed and counted in the
+    /// `warnings` of their task and in the stats of their index.
+    #[clap(long, env = "MEILI_SOFT_MAX_DOCUMENT_SIZE")]
+    pub soft_max_document_size: Option<Byte>,
+}
+
+impl IndexerOpts {
+    /// The limits on the documents of the payloads.
+    pub fn payload_limits(&self) -> PayloadLimits {
+        PayloadLimits {
+            hard: DocumentLimits {
+                max_fields: self.max_document_fields,
+                max_field_name_length: self.max_field_name_length,
+                max_size: self.max_document_size.map(|size| size.get_bytes() as u64),
+            },
+            soft: DocumentLimits {
+                max_fields: self.soft_max_document_fields,
+                max_field_name_length: self.soft_max_field_name_length,
+                max_size: self
+                    .soft_max_document_size
+                    .map(|size| size.get_bytes() as u64),
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone, Parser, Default, Serialize)]
@@ -62,6 +187,72 @@ pub struct SchedulerConfig {
     /// starting to process a batch of updates.
     #[clap(long, requires = "enable-auto-batching", hide = true)]
     pub debounce_duration_sec: Option<u64>,
+
+    /// Maximum age in seconds of a task before its batch is processed
+    ///
+    /// The scheduler stops debouncing and processes a batch as soon as the oldest pending task
+    /// was enqueued for `max_task_age_before_batch_sec` seconds, so that the last tasks of a burst
+    /// don't wait for the next one.
+    #[clap(long, requires = "enable-auto-batching", hide = true)]
+    pub max_task_age_before_batch_sec: Option<u64>,
+
+    /// Adapts the debounce of each batch to the rate at which the tasks of its index arrive,
+    /// between `min_debounce_duration_ms` and `max_debounce_duration_ms`, instead of always
+    /// waiting for `debounce_duration_sec`. The faster the tasks arrive, the shorter the wait.
+    #[clap(long, requires = "enable-auto-batching", hide = true)]
+    pub adaptive_debounce: bool,
+
+    /// The shortest debounce in milliseconds chosen by the adaptive debounce.
+    #[clap(long, requires = "adaptive-debounce", hide = true, default_value = "0")]
+    pub min_debounce_duration_ms: u64,
+
+    /// The longest debounce in milliseconds chosen by the adaptive debounce, which is the one of
+    /// the indexes receiving few tasks.
+    #[clap(
+        long,
+        requires = "adaptive-debounce",
+        hide = true,
+        default_value = "10000"
+    )]
+    pub max_debounce_duration_ms: u64,
+
+    /// Completes the document additions and updates of an index that are enqueued right before a
+    /// deletion of all its documents without processing them, since their documents would be
+    /// deleted anyway. Such an addition doesn't create its index nor set its primary key.
+    #[clap(long, env = "MEILI_SUPERSEDE_CLEARED_DOCUMENTS")]
+    pub supersede_cleared_documents: bool,
+
+    /// The number of seconds without any sign of life from the batch being processed after which
+    /// it can be reset with the `POST /tasks/force-reset-processing` route.
+    #[clap(
+        long,
+        env = "MEILI_STUCK_BATCH_GRACE_PERIOD_SEC",
+        default_value = "600"
+    )]
+    pub stuck_batch_grace_period_sec: u64,
+
+    /// The maximum number of jobs, like the snapshots, waiting to be processed. A job scheduled
+    /// while as many jobs are waiting is rejected. A value of 0 is interpreted as 1.
+    #[clap(long, env = "MEILI_MAX_QUEUED_JOBS", default_value = "16")]
+    pub max_queued_jobs: usize,
+
+    /// Fails the task leading a batch with a `quarantined_after_repeated_failures` error once the
+    /// batches it led failed `quarantine-after-failures` times in a row, so that a task crashing
+    /// its batch doesn't block the next tasks of its index forever. The failures caused by the
+    /// environment, like a full disk, are not counted.
+    #[clap(long, env = "MEILI_QUARANTINE_POISON_TASKS")]
+    pub quarantine_poison_tasks: bool,
+
+    /// The number of failures in a row of the batches led by a task after which it is
+    /// quarantined. A value of 0 is interpreted as 1.
+    #[clap(long, env = "MEILI_QUARANTINE_AFTER_FAILURES", default_value = "3")]
+    pub quarantine_after_failures: u32,
+
+    /// Whether the tasks are left unprocessed, like on a replica, which only serves the tasks of
+    /// the snapshots of its primary.
+    #[clap(skip)]
+    #[serde(skip)]
+    pub read_only: bool,
 }
 
 impl TryFrom<&IndexerOpts> for IndexerConfig {
@@ -90,10 +281,75 @@ impl Default for IndexerOpts {
             max_nb_chunks: None,
             max_indexing_memory: MaxMemory::default(),
             max_indexing_threads: MaxThreads::default(),
+            max_fetch_size: Byte::from_bytes(1024 * 1024 * 1024),
+            fetch_timeout_sec: 300,
+            fetch_allowed_hosts: Vec::new(),
+            upload_session_ttl_sec: 86400,
+            idempotency_key_window_sec: 86400,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+            max_pending_payload_size_per_index: None,
+            document_chunk_size: None,
+            defer_chunk_visibility: false,
+            max_full_diff_documents: DEFAULT_MAX_FULL_DIFF_DOCUMENTS,
+            max_readers: DEFAULT_MAX_READERS,
+            max_document_fields: None,
+            max_field_name_length: None,
+            max_document_size: None,
+            soft_max_document_fields: None,
+            soft_max_field_name_length: None,
+            soft_max_document_size: None,
         }
     }
 }
 
+/// A secret option, like the master key. It is serialized and debugged as a redacted
+/// placeholder, so that it can't leak through the effective configuration, the dumps, or the logs.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Sensitive<T>(T);
+
+pub const REDACTED: &str = "[redacted]";
+
+impl<T> Sensitive<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the secret value. It must never end up in a response or a log.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Sensitive<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: FromStr> FromStr for Sensitive<T> {
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        T::from_str(s).map(Self)
+    }
+}
+
+impl<T> fmt::Debug for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> Serialize for Sensitive<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
 /// A type used to detect the max memory available and use 2/3 of it.
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct MaxMemory(Option<Byte>);
@@ -181,3 +437,19 @@ impl Deref for MaxThreads {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sensitive_is_redacted() {
+        let key: Sensitive<String> = "MASTER_KEY".parse().unwrap();
+        assert_eq!(key.expose(), "MASTER_KEY");
+        assert_eq!(format!("{:?}", key), REDACTED);
+        assert_eq!(
+            serde_json::to_value(Some(key)).unwrap(),
+            serde_json::json!(REDACTED)
+        );
+    }
+}
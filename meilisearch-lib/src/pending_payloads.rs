@@ -0,0 +1,146 @@
+//! Accounting of the pending payloads.
+//!
+//! The payload of a document addition is written to an update file when the task is registered,
+//! and only removed once the task is processed. Without any limit, a single index with a long
+//! queue of large additions can fill the disk for every other index. We keep track of the size of
+//! the update files of each index, and, when a quota is configured, refuse the document additions
+//! that would bring an index above it.
+//!
+//! The accounting is only kept in memory, and is rebuilt from the unfinished tasks on startup.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+#[derive(Default)]
+struct Accounting {
+    /// The total size of the pending update files of each index.
+    by_index: HashMap<String, u64>,
+    /// The index and size of each pending update file.
+    files: HashMap<Uuid, (String, u64)>,
+}
+
+#[derive(Clone, Default)]
+pub struct PendingPayloads {
+    /// The maximum size of the pending update files of an index, if any.
+    quota: Option<u64>,
+    accounting: Arc<Mutex<Accounting>>,
+}
+
+impl PendingPayloads {
+    pub fn new(quota: Option<u64>) -> Self {
+        Self {
+            quota,
+            accounting: Default::default(),
+        }
+    }
+
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+    }
+
+    /// Returns the total size of the pending update files of `index_uid`.
+    pub fn size(&self, index_uid: &str) -> u64 {
+        self.accounting
+            .lock()
+            .by_index
+            .get(index_uid)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether `index_uid` already reached its quota, and can't accept any new payload.
+    pub fn is_full(&self, index_uid: &str) -> bool {
+        self.quota
+            .map_or(false, |quota| self.size(index_uid) >= quota)
+    }
+
+    /// Accounts for the update file `uuid` of `size` bytes, unless it would bring `index_uid`
+    /// above its quota. Returns whether the file was accounted for.
+    pub fn try_track(&self, index_uid: &str, uuid: Uuid, size: u64) -> bool {
+        let mut accounting = self.accounting.lock();
+        let current = accounting
+            .by_index
+            .get(index_uid)
+            .copied()
+            .unwrap_or_default();
+        if self.quota.map_or(false, |quota| current + size > quota) {
+            return false;
+        }
+
+        accounting.insert(index_uid, uuid, size);
+        true
+    }
+
+    /// Accounts for the update file `uuid` of `size` bytes, regardless of the quota.
+    pub fn track(&self, index_uid: &str, uuid: Uuid, size: u64) {
+        self.accounting.lock().insert(index_uid, uuid, size);
+    }
+
+    /// Must be called once the update file `uuid` is removed.
+    pub fn release(&self, uuid: Uuid) {
+        let mut accounting = self.accounting.lock();
+        if let Some((index_uid, size)) = accounting.files.remove(&uuid) {
+            if let Some(total) = accounting.by_index.get_mut(&index_uid) {
+                *total = total.saturating_sub(size);
+                if *total == 0 {
+                    accounting.by_index.remove(&index_uid);
+                }
+            }
+        }
+    }
+}
+
+impl Accounting {
+    fn insert(&mut self, index_uid: &str, uuid: Uuid, size: u64) {
+        if self
+            .files
+            .insert(uuid, (index_uid.to_string(), size))
+            .is_none()
+        {
+            *self.by_index.entry(index_uid.to_string()).or_default() += size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pending_payloads_accounting() {
+        let payloads = PendingPayloads::new(None);
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        payloads.track("movies", first, 100);
+        payloads.track("movies", second, 50);
+        // a file is only accounted for once.
+        payloads.track("movies", second, 50);
+        assert_eq!(payloads.size("movies"), 150);
+        assert_eq!(payloads.size("books"), 0);
+
+        payloads.release(first);
+        assert_eq!(payloads.size("movies"), 50);
+        payloads.release(first);
+        assert_eq!(payloads.size("movies"), 50);
+        payloads.release(second);
+        assert_eq!(payloads.size("movies"), 0);
+        assert!(!payloads.is_full("movies"));
+    }
+
+    #[test]
+    fn test_pending_payloads_quota() {
+        let payloads = PendingPayloads::new(Some(100));
+        assert!(payloads.try_track("movies", Uuid::new_v4(), 60));
+        assert!(!payloads.try_track("movies", Uuid::new_v4(), 60));
+        // the quota is per index.
+        assert!(payloads.try_track("books", Uuid::new_v4(), 60));
+
+        let last = Uuid::new_v4();
+        assert!(payloads.try_track("movies", last, 40));
+        assert!(payloads.is_full("movies"));
+        payloads.release(last);
+        assert!(!payloads.is_full("movies"));
+    }
+}
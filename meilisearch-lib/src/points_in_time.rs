@@ -0,0 +1,154 @@
+//! Points in time, to paginate the results of a search while the index is updated.
+//!
+//! The pages of a search are separate requests: a document added between two pages shifts the
+//! results, so that some hits are shown twice and others never. A search with `pit` opens a read
+//! transaction on the index, and returns its token: the searches passing this token back as
+//! `pitToken` are served from the snapshot of the index seen by the transaction, whatever the
+//! updates processed in the meantime.
+//!
+//! Each point in time pins one of the reader slots of the index, and keeps the pages of the
+//! snapshot from being reused by the updates, so they are bounded: a point in time expires after a
+//! fixed time, and opening a point in time on an index that already has the maximum number of them
+//! evicts the oldest one. The expired points in time are closed by the next call to the store.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use meilisearch_error::{Code, ErrorCode};
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use crate::index::error::IndexError;
+use crate::index::{Index, SearchQuery, SnapshotTxn};
+
+pub const DEFAULT_PIT_TTL: Duration = Duration::from_secs(60);
+pub const DEFAULT_MAX_OPEN_PITS_PER_INDEX: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PitError {
+    #[error("The point in time `{0}` expired or was evicted by a more recent one. Restart the pagination without `pitToken`.")]
+    Expired(String),
+    #[error("`pit` and `pitToken` can't be used with `rankingRulesOverride`.")]
+    WithRankingRulesOverride,
+    #[error("{0}")]
+    Index(#[from] IndexError),
+}
+
+impl ErrorCode for PitError {
+    fn error_code(&self) -> Code {
+        match self {
+            PitError::Expired(_) => Code::PitExpired,
+            PitError::WithRankingRulesOverride => Code::InvalidPit,
+            PitError::Index(e) => e.error_code(),
+        }
+    }
+}
+
+/// The snapshot of a point in time. The searches using it lock it, since a read transaction must
+/// not be used by several threads at once.
+pub type PitSnapshot = Arc<Mutex<SnapshotTxn>>;
+
+struct PointInTime {
+    token: String,
+    expires_at: Instant,
+    snapshot: PitSnapshot,
+}
+
+#[derive(Clone)]
+pub struct PointsInTime {
+    /// The open points in time of each index, by index uuid, from the oldest.
+    open: Arc<Mutex<BTreeMap<Uuid, VecDeque<PointInTime>>>>,
+    ttl: Duration,
+    max_per_index: usize,
+}
+
+impl Default for PointsInTime {
+    fn default() -> Self {
+        Self::new(DEFAULT_PIT_TTL, DEFAULT_MAX_OPEN_PITS_PER_INDEX)
+    }
+}
+
+impl PointsInTime {
+    pub fn new(ttl: Duration, max_per_index: usize) -> Self {
+        Self {
+            open: Default::default(),
+            ttl,
+            // a search with `pit` always gets a point in time.
+            max_per_index: max_per_index.max(1),
+        }
+    }
+
+    /// Returns the snapshot `query` must be served from along with its token, if it opens a
+    /// point in time or passes the token of one.
+    pub fn snapshot(
+        &self,
+        index: &Index,
+        query: &SearchQuery,
+    ) -> Result<Option<(String, PitSnapshot)>, PitError> {
+        if !query.pit && query.pit_token.is_none() {
+            return Ok(None);
+        }
+        if query.ranking_rules_override.is_some() {
+            return Err(PitError::WithRankingRulesOverride);
+        }
+
+        match query.pit_token {
+            Some(ref token) => {
+                let snapshot = self.get(index.uuid(), token)?;
+                Ok(Some((token.clone(), snapshot)))
+            }
+            None => self.open(index).map(Some),
+        }
+    }
+
+    /// Returns the number of open points in time on the index `index_uuid`.
+    pub fn count(&self, index_uuid: Uuid) -> usize {
+        let mut open = self.open.lock();
+        close_expired(&mut open);
+        open.get(&index_uuid).map_or(0, VecDeque::len)
+    }
+
+    /// Closes the points in time of the index `index_uuid`, whose snapshots keep its environment
+    /// open.
+    pub fn close_index(&self, index_uuid: Uuid) {
+        self.open.lock().remove(&index_uuid);
+    }
+
+    fn open(&self, index: &Index) -> Result<(String, PitSnapshot), PitError> {
+        let snapshot = Arc::new(Mutex::new(index.snapshot_txn().map_err(IndexError::from)?));
+        let token = Uuid::new_v4().to_string();
+
+        let mut open = self.open.lock();
+        close_expired(&mut open);
+        let pits = open.entry(index.uuid()).or_default();
+        while pits.len() >= self.max_per_index {
+            pits.pop_front();
+        }
+        pits.push_back(PointInTime {
+            token: token.clone(),
+            expires_at: Instant::now() + self.ttl,
+            snapshot: snapshot.clone(),
+        });
+
+        Ok((token, snapshot))
+    }
+
+    fn get(&self, index_uuid: Uuid, token: &str) -> Result<PitSnapshot, PitError> {
+        let mut open = self.open.lock();
+        close_expired(&mut open);
+        open.get(&index_uuid)
+            .and_then(|pits| pits.iter().find(|pit| pit.token == token))
+            .map(|pit| pit.snapshot.clone())
+            .ok_or_else(|| PitError::Expired(token.to_string()))
+    }
+}
+
+/// Drops the expired points in time. Their snapshot is closed once the searches using it are done.
+fn close_expired(open: &mut BTreeMap<Uuid, VecDeque<PointInTime>>) {
+    let now = Instant::now();
+    for pits in open.values_mut() {
+        pits.retain(|pit| pit.expires_at > now);
+    }
+    open.retain(|_, pits| !pits.is_empty());
+}
@@ -0,0 +1,500 @@
+//! Read-only replicas fed by the snapshots of a primary instance.
+//!
+//! The data directory of a replica is a symlink to the generation it serves, one of the numbered
+//! directories of `<data directory>.replica`. A replica polls the latest snapshot of its primary,
+//! and loads every new one into the next generation, before pointing the symlink at it and
+//! opening the stores on it. The stores and the indexes are swapped without being closed first:
+//! the requests that got the previous ones finish on them, and the environments of the previous
+//! generation are closed once they are done. A generation is never served twice, so that heed,
+//! which caches the environments by path, never hands the environment of a replaced one.
+//!
+//! The API keys are read from the snapshot loaded when the replica starts, the keys created on
+//! the primary afterward are only known by the replica once it is restarted.
+
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context};
+use log::{error, info};
+use parking_lot::RwLock;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::time::sleep;
+
+use crate::data_lock::DataDirLock;
+use crate::index_controller::open_meta_env;
+use crate::index_controller::versioning::{check_version_file, VERSION_FILE_NAME};
+use crate::index_resolver::HardStateIndexResolver;
+use crate::snapshot::load_snapshot;
+use crate::tasks::TaskStore;
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The file of the generations directory recording what the replica serves.
+const STATE_FILE_NAME: &str = "state.json";
+
+/// Where the snapshots of the primary are read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotSource {
+    Path(PathBuf),
+    Url(String),
+}
+
+impl SnapshotSource {
+    pub fn parse(source: &str) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            SnapshotSource::Url(source.to_string())
+        } else {
+            SnapshotSource::Path(PathBuf::from(source))
+        }
+    }
+}
+
+impl fmt::Display for SnapshotSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotSource::Path(path) => write!(f, "{}", path.display()),
+            SnapshotSource::Url(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+/// What identifies the version of the snapshot of the source, to only fetch a new one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum SnapshotVersion {
+    /// The modification time of the snapshot file, in nanoseconds since the epoch.
+    Modified(u128),
+    ETag(String),
+    LastModified(String),
+}
+
+/// What the replica serves, persisted across the restarts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplicaState {
+    generation: u64,
+    version: Option<SnapshotVersion>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    snapshot_created_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicationStatus {
+    /// The snapshot the replica polls.
+    pub source: String,
+    /// When the primary created the loaded snapshot.
+    #[serde(serialize_with = "time::serde::rfc3339::option::serialize")]
+    pub snapshot_created_at: Option<OffsetDateTime>,
+    /// The age of the loaded snapshot, in seconds, which is how far the replica lags behind its
+    /// primary.
+    pub lag_sec: Option<u64>,
+    #[serde(serialize_with = "time::serde::rfc3339::option::serialize")]
+    pub last_poll_at: Option<OffsetDateTime>,
+    /// Why the last poll failed, if it did.
+    pub last_error: Option<String>,
+}
+
+/// The status of the replication, shared by the replica service and the index controller.
+#[derive(Debug, Clone)]
+pub struct ReplicaHandle {
+    status: Arc<RwLock<ReplicationStatus>>,
+}
+
+impl ReplicaHandle {
+    fn new(source: &SnapshotSource, snapshot_created_at: Option<OffsetDateTime>) -> Self {
+        Self {
+            status: Arc::new(RwLock::new(ReplicationStatus {
+                source: source.to_string(),
+                snapshot_created_at,
+                lag_sec: None,
+                last_poll_at: None,
+                last_error: None,
+            })),
+        }
+    }
+
+    pub fn status(&self) -> ReplicationStatus {
+        let mut status = self.status.read().clone();
+        status.lag_sec = status
+            .snapshot_created_at
+            .map(|at| (OffsetDateTime::now_utc() - at).whole_seconds().max(0) as u64);
+        status
+    }
+
+    fn polled(&self, result: &anyhow::Result<Option<OffsetDateTime>>) {
+        let mut status = self.status.write();
+        status.last_poll_at = Some(OffsetDateTime::now_utc());
+        match result {
+            Ok(Some(created_at)) => {
+                status.snapshot_created_at = Some(*created_at);
+                status.last_error = None;
+            }
+            Ok(None) => status.last_error = None,
+            Err(e) => status.last_error = Some(e.to_string()),
+        }
+    }
+}
+
+/// The generations of the data directory of a replica.
+struct Generations {
+    db_path: PathBuf,
+    root: PathBuf,
+}
+
+impl Generations {
+    fn new(db_path: &Path) -> Self {
+        let mut root_name = db_path.file_name().unwrap_or_default().to_owned();
+        root_name.push(".replica");
+        Self {
+            db_path: db_path.to_owned(),
+            root: db_path.with_file_name(root_name),
+        }
+    }
+
+    fn path(&self, generation: u64) -> PathBuf {
+        self.root.join(generation.to_string())
+    }
+
+    fn read_state(&self) -> anyhow::Result<Option<ReplicaState>> {
+        match fs::read(self.root.join(STATE_FILE_NAME)) {
+            Ok(state) => Ok(Some(serde_json::from_slice(&state)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_state(&self, state: &ReplicaState) -> anyhow::Result<()> {
+        let mut file = tempfile::NamedTempFile::new_in(&self.root)?;
+        serde_json::to_writer(&mut file, state)?;
+        file.flush()?;
+        file.persist(self.root.join(STATE_FILE_NAME))?;
+        Ok(())
+    }
+
+    /// Points the data directory at `generation`, replacing the symlink atomically.
+    #[cfg(unix)]
+    fn serve(&self, generation: u64) -> anyhow::Result<()> {
+        let link = self.root.join("serving");
+        if link.symlink_metadata().is_ok() {
+            fs::remove_file(&link)?;
+        }
+        // the target is relative to the directory of the link, which is the one of the root.
+        let root_name = self.root.file_name().context("invalid data directory")?;
+        std::os::unix::fs::symlink(Path::new(root_name).join(generation.to_string()), &link)?;
+        fs::rename(&link, &self.db_path)?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn serve(&self, _generation: u64) -> anyhow::Result<()> {
+        bail!("the replicas are only supported on unix systems")
+    }
+
+    /// Removes the generations other than `generation`. The environments still open on them
+    /// keep their files until they are closed.
+    fn remove_others(&self, generation: u64) -> anyhow::Result<()> {
+        for entry in self.root.read_dir()? {
+            let path = entry?.path();
+            let is_generation = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.parse::<u64>().is_ok());
+            if is_generation && path != self.path(generation) {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the snapshot archived at `archive` into the generation after `state`, and returns the
+    /// state of the replica once it serves it.
+    fn load(
+        &self,
+        archive: &Path,
+        state: &ReplicaState,
+        version: SnapshotVersion,
+    ) -> anyhow::Result<ReplicaState> {
+        let generation = state.generation + 1;
+        let path = self.path(generation);
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        load_snapshot(&path, archive, false, false)?;
+        if let Err(e) = check_version_file(&path) {
+            fs::remove_dir_all(&path)?;
+            return Err(e.context("the snapshot can't be served by this version of Meilisearch"));
+        }
+        // the files of a snapshot are dated by the primary when it creates it.
+        let snapshot_created_at = fs::metadata(path.join(VERSION_FILE_NAME))?
+            .modified()
+            .ok()
+            .map(OffsetDateTime::from);
+
+        Ok(ReplicaState {
+            generation,
+            version: Some(version),
+            snapshot_created_at,
+        })
+    }
+}
+
+/// Fetches the snapshot of `source` into `dest`, unless its version is `current`. Returns the
+/// version of the fetched snapshot.
+async fn fetch(
+    source: &SnapshotSource,
+    current: Option<&SnapshotVersion>,
+    dest: &Path,
+) -> anyhow::Result<Option<SnapshotVersion>> {
+    match source {
+        SnapshotSource::Path(path) => {
+            let modified = fs::metadata(path)
+                .with_context(|| format!("the snapshot at {:?} can't be read", path))?
+                .modified()?
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_nanos();
+            let version = SnapshotVersion::Modified(modified);
+            if current == Some(&version) {
+                return Ok(None);
+            }
+            let (path, dest) = (path.clone(), dest.to_owned());
+            tokio::task::spawn_blocking(move || fs::copy(path, dest)).await??;
+            Ok(Some(version))
+        }
+        SnapshotSource::Url(url) => {
+            let mut request = reqwest::Client::new().get(url);
+            request = match current {
+                Some(SnapshotVersion::ETag(etag)) => request.header(IF_NONE_MATCH, etag),
+                Some(SnapshotVersion::LastModified(date)) => {
+                    request.header(IF_MODIFIED_SINCE, date)
+                }
+                _ => request,
+            };
+            let mut response = request.send().await?;
+            if response.status() == StatusCode::NOT_MODIFIED {
+                return Ok(None);
+            }
+            if !response.status().is_success() {
+                bail!(
+                    "fetching the snapshot at `{}` failed with status `{}`",
+                    url,
+                    response.status()
+                );
+            }
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(String::from)
+            };
+            let version = match (header(ETAG), header(LAST_MODIFIED)) {
+                (Some(etag), _) => SnapshotVersion::ETag(etag),
+                (None, Some(date)) => SnapshotVersion::LastModified(date),
+                // without a validator, the snapshot is loaded on every poll.
+                (None, None) => SnapshotVersion::Modified(
+                    SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)?
+                        .as_nanos(),
+                ),
+            };
+            if current == Some(&version) {
+                return Ok(None);
+            }
+
+            let mut file = fs::File::create(dest)?;
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk)?;
+            }
+            file.sync_all()?;
+            Ok(Some(version))
+        }
+    }
+}
+
+/// Loads the latest snapshot of `source` into the data directory `db_path` of a replica, before
+/// the replica is started. The previous generation is served if the snapshot can't be loaded.
+pub async fn load_latest_snapshot(db_path: &Path, source: &SnapshotSource) -> anyhow::Result<()> {
+    // the lock is released once the snapshot is loaded, and taken again by the server.
+    let _data_lock = DataDirLock::acquire(db_path)?;
+    let generations = Generations::new(db_path);
+    let is_link = db_path
+        .symlink_metadata()
+        .map_or(false, |meta| meta.file_type().is_symlink());
+    if !is_link {
+        if !crate::is_empty_db(db_path) {
+            bail!(
+                "database already exists at {:?}, a replica only serves the snapshots of its primary",
+                db_path
+            );
+        }
+        if db_path.exists() {
+            fs::remove_dir(db_path)?;
+        }
+    }
+    fs::create_dir_all(&generations.root)?;
+
+    let state = generations.read_state()?.unwrap_or_default();
+    // the generation served before the restart is kept if its snapshot is still the latest.
+    let current = if is_link {
+        state.version.as_ref()
+    } else {
+        None
+    };
+    let archive = tempfile::NamedTempFile::new_in(&generations.root)?;
+    let loaded = match fetch(source, current, archive.path()).await {
+        Ok(Some(version)) => {
+            let archive = archive.path().to_owned();
+            tokio::task::spawn_blocking(move || {
+                let state = generations.load(&archive, &state, version)?;
+                anyhow::Ok((generations, state))
+            })
+            .await?
+        }
+        Ok(None) => return Ok(()),
+        Err(e) => Err(e),
+    };
+
+    match loaded {
+        Ok((generations, state)) => {
+            generations.serve(state.generation)?;
+            generations.write_state(&state)?;
+            generations.remove_others(state.generation)?;
+            info!(
+                "Serving the snapshot of {} created at {:?}.",
+                source, state.snapshot_created_at
+            );
+            Ok(())
+        }
+        Err(e) if is_link => {
+            error!(
+                "The latest snapshot of {} couldn't be loaded, the previous one is served: {}",
+                source, e
+            );
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Polls the snapshot of the primary, and serves every new one.
+pub(crate) struct ReplicaService {
+    pub(crate) source: SnapshotSource,
+    pub(crate) poll_interval: Duration,
+    pub(crate) db_path: PathBuf,
+    pub(crate) meta_env_size: usize,
+    pub(crate) index_resolver: Arc<HardStateIndexResolver>,
+    pub(crate) task_store: TaskStore,
+    pub(crate) handle: ReplicaHandle,
+}
+
+impl ReplicaService {
+    /// The handle of the replica serving the data directory `db_path`, which must have been
+    /// loaded with `load_latest_snapshot`.
+    pub(crate) fn handle(db_path: &Path, source: &SnapshotSource) -> anyhow::Result<ReplicaHandle> {
+        let state = Generations::new(db_path).read_state()?.with_context(|| {
+            format!(
+                "the data directory {:?} of the replica wasn't loaded from a snapshot",
+                db_path
+            )
+        })?;
+        Ok(ReplicaHandle::new(source, state.snapshot_created_at))
+    }
+
+    pub async fn run(self) {
+        info!(
+            "Replicating {} every {}s.",
+            self.source,
+            self.poll_interval.as_secs()
+        );
+        loop {
+            sleep(self.poll_interval).await;
+            let result = self.poll().await;
+            if let Err(ref e) = result {
+                error!("Replicating {} failed: {:#}", self.source, e);
+            }
+            self.handle.polled(&result);
+        }
+    }
+
+    /// Serves the latest snapshot of the source if it is new, and returns its creation date.
+    async fn poll(&self) -> anyhow::Result<Option<OffsetDateTime>> {
+        let generations = Generations::new(&self.db_path);
+        let state = generations.read_state()?.unwrap_or_default();
+        let archive = tempfile::NamedTempFile::new_in(&generations.root)?;
+        let version = match fetch(&self.source, state.version.as_ref(), archive.path()).await? {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+
+        let meta_env_size = self.meta_env_size;
+        let archive = archive.path().to_owned();
+        let (generations, state, env) = tokio::task::spawn_blocking(move || {
+            let state = generations.load(&archive, &state, version)?;
+            // the environment is opened by the path of the generation, which the data directory
+            // resolves to once it is served.
+            let env = Arc::new(open_meta_env(
+                &generations.path(state.generation),
+                meta_env_size,
+            )?);
+            generations.serve(state.generation)?;
+            anyhow::Ok((generations, state, env))
+        })
+        .await??;
+
+        self.task_store.open(env.clone())?;
+        self.index_resolver.swap_meta_env(env).await?;
+        generations.write_state(&state)?;
+        generations.remove_others(state.generation)?;
+        info!(
+            "Serving the snapshot of {} created at {:?}.",
+            self.source, state.snapshot_created_at
+        );
+
+        Ok(state.snapshot_created_at)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_source() {
+        assert_eq!(
+            SnapshotSource::parse("https://primary:7700/snapshots/data.ms.snapshot"),
+            SnapshotSource::Url("https://primary:7700/snapshots/data.ms.snapshot".to_string())
+        );
+        assert_eq!(
+            SnapshotSource::parse("/mnt/snapshots/data.ms.snapshot"),
+            SnapshotSource::Path(PathBuf::from("/mnt/snapshots/data.ms.snapshot"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_generations_are_swapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("data.ms");
+        let generations = Generations::new(&db_path);
+        fs::create_dir_all(generations.path(1)).unwrap();
+        fs::create_dir_all(generations.path(2)).unwrap();
+        fs::write(generations.path(2).join("file"), "second").unwrap();
+
+        generations.serve(1).unwrap();
+        assert!(!db_path.join("file").exists());
+        generations.serve(2).unwrap();
+        assert_eq!(fs::read_to_string(db_path.join("file")).unwrap(), "second");
+
+        generations.remove_others(2).unwrap();
+        assert!(!generations.path(1).exists());
+        assert!(generations.path(2).exists());
+    }
+}
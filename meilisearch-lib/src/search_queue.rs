@@ -0,0 +1,174 @@
+//! Limitation of the concurrent searches.
+//!
+//! Every search runs on the blocking thread pool, so that an unbounded number of concurrent
+//! searches all slow each other down until they time out at the client. Instead, a search must
+//! hold one of a fixed number of permits while it is executed. The searches that can't get a
+//! permit wait for one in a bounded FIFO queue, and are refused when this queue is full or when
+//! they waited for too long, so that the client can retry later.
+//!
+//! The permit is only held while the query is executed by milli, and released before the results
+//! are serialized.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use meilisearch_error::{Code, ErrorCode};
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+pub const DEFAULT_MAX_QUEUED_SEARCHES: usize = 1000;
+pub const DEFAULT_SEARCH_QUEUE_MAX_WAIT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchQueueError {
+    #[error("Too many search requests are waiting to be processed. Retry later.")]
+    QueueFull,
+    #[error("The search request waited more than {0}ms to be processed. Retry later.")]
+    Timeout(u128),
+}
+
+impl ErrorCode for SearchQueueError {
+    fn error_code(&self) -> Code {
+        Code::TooManySearchRequests
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQueueStatus {
+    /// The number of searches being executed.
+    pub in_flight: usize,
+    /// The number of searches waiting for a permit.
+    pub queued: usize,
+    pub max_concurrent_searches: usize,
+    pub max_queued_searches: usize,
+}
+
+#[derive(Clone)]
+pub struct SearchQueue {
+    permits: Arc<Semaphore>,
+    max_concurrent: usize,
+    max_queued: usize,
+    max_wait: Duration,
+    queued: Arc<AtomicUsize>,
+}
+
+impl Default for SearchQueue {
+    fn default() -> Self {
+        Self::new(
+            num_cpus::get(),
+            DEFAULT_MAX_QUEUED_SEARCHES,
+            DEFAULT_SEARCH_QUEUE_MAX_WAIT,
+        )
+    }
+}
+
+impl SearchQueue {
+    pub fn new(max_concurrent: usize, max_queued: usize, max_wait: Duration) -> Self {
+        // a pool without any permit would refuse every search.
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            max_queued,
+            max_wait,
+            queued: Default::default(),
+        }
+    }
+
+    /// Waits for a search permit. The search must hold the returned permit while it is executed.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit, SearchQueueError> {
+        // the free permits are always handed to the queued searches first, so a search can only
+        // get one right away when no other search is waiting.
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let _place = QueuePlace::take(&self.queued, self.max_queued)?;
+        match timeout(self.max_wait, self.permits.clone().acquire_owned()).await {
+            Ok(permit) => Ok(permit.expect("the search permits are never closed")),
+            Err(_) => Err(SearchQueueError::Timeout(self.max_wait.as_millis())),
+        }
+    }
+
+    pub fn status(&self) -> SearchQueueStatus {
+        SearchQueueStatus {
+            in_flight: self.max_concurrent - self.permits.available_permits(),
+            queued: self.queued.load(Ordering::Relaxed),
+            max_concurrent_searches: self.max_concurrent,
+            max_queued_searches: self.max_queued,
+        }
+    }
+}
+
+/// A place in the queue, freed when the search gets its permit, times out, or is cancelled.
+struct QueuePlace<'a>(&'a AtomicUsize);
+
+impl<'a> QueuePlace<'a> {
+    fn take(queued: &'a AtomicUsize, max_queued: usize) -> Result<Self, SearchQueueError> {
+        queued
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |queued| {
+                (queued < max_queued).then(|| queued + 1)
+            })
+            .map_err(|_| SearchQueueError::QueueFull)?;
+        Ok(Self(queued))
+    }
+}
+
+impl Drop for QueuePlace<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn test_search_queue() {
+        let queue = SearchQueue::new(1, 1, Duration::from_millis(50));
+        let permit = queue.acquire().await.unwrap();
+        assert_eq!(queue.status().in_flight, 1);
+
+        // the second search waits for the permit, and the third one can't even be queued.
+        let waiting = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire().await.map(drop) })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(queue.status().queued, 1);
+        assert!(matches!(
+            queue.acquire().await,
+            Err(SearchQueueError::QueueFull)
+        ));
+
+        assert!(matches!(
+            waiting.await.unwrap(),
+            Err(SearchQueueError::Timeout(50))
+        ));
+        assert_eq!(queue.status().queued, 0);
+
+        drop(permit);
+        assert_eq!(queue.status().in_flight, 0);
+        assert!(queue.acquire().await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_queued_search_gets_released_permit() {
+        let queue = SearchQueue::new(1, 1, Duration::from_secs(5));
+        let permit = queue.acquire().await.unwrap();
+
+        let waiting = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire().await.map(drop) })
+        };
+        tokio::task::yield_now().await;
+        drop(permit);
+
+        assert!(waiting.await.unwrap().is_ok());
+        assert_eq!(queue.status().in_flight, 0);
+    }
+}
@@ -0,0 +1,406 @@
+//! The custom segmenters of the languages written without spaces between their words.
+//!
+//! milli splits the text into words with its own tokenizer, which can't be extended, and which
+//! splits the scripts written without spaces, like Thai or Khmer, into characters. A `Segmenter`
+//! splits the text of some languages into words instead. The segmenters are plugins compiled in
+//! behind cargo features, registered by name in a global registry, and selected for the languages
+//! of an index by its `customSegmenters` setting, like `{"tha": "thai-dictionary"}`.
+//!
+//! The language of a text is detected from its script, so only the languages written in a script
+//! of their own can be segmented. The words of the text in these languages are encoded into words
+//! the tokenizer of milli keeps whole, and given to milli: the words of the documents in a field
+//! derived from them when they are indexed, and the words of a query in place of the text they
+//! were segmented from. The same segmenter must segment the documents and the queries: the name
+//! and the version of the segmenters are stored in the index, and an index whose segmenters are
+//! not compiled in is refused.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(test, feature = "thai-dictionary-segmenter"))]
+pub mod thai_dictionary;
+
+/// The languages that can be segmented, by ISO 639-3 code, with the Unicode blocks of their
+/// script.
+const LANGUAGE_SCRIPTS: &[(&str, &[(char, char)])] = &[
+    ("tha", &[('\u{0E00}', '\u{0E7F}')]),
+    ("lao", &[('\u{0E80}', '\u{0EFF}')]),
+    ("mya", &[('\u{1000}', '\u{109F}')]),
+    ("khm", &[('\u{1780}', '\u{17FF}'), ('\u{19E0}', '\u{19FF}')]),
+];
+
+/// Splits the text of some languages into words.
+pub trait Segmenter: Send + Sync {
+    /// The name the segmenter is registered and selected with.
+    fn name(&self) -> &'static str;
+
+    /// The version of the segmentation. It must change whenever the words of a text do, since the
+    /// words of the documents recorded with another version could not be found anymore.
+    fn version(&self) -> u32;
+
+    /// The languages segmented, by ISO 639-3 code.
+    fn languages(&self) -> &[&'static str];
+
+    /// The byte ranges of the words of `text`, written in `language`.
+    fn segment(&self, text: &str, language: &str) -> Vec<Range<usize>>;
+}
+
+static REGISTRY: Lazy<RwLock<BTreeMap<&'static str, Arc<dyn Segmenter>>>> = Lazy::new(|| {
+    let mut registry: BTreeMap<_, Arc<dyn Segmenter>> = BTreeMap::new();
+    #[cfg(any(test, feature = "thai-dictionary-segmenter"))]
+    registry.insert(
+        thai_dictionary::NAME,
+        Arc::new(thai_dictionary::ThaiDictionarySegmenter),
+    );
+    RwLock::new(registry)
+});
+
+/// Registers `segmenter` under its name, in place of the segmenter that had this name.
+pub fn register(segmenter: Arc<dyn Segmenter>) {
+    REGISTRY.write().insert(segmenter.name(), segmenter);
+}
+
+/// The name and the version of the registered segmenters.
+pub fn registered() -> Vec<SegmenterId> {
+    REGISTRY
+        .read()
+        .values()
+        .map(|segmenter| SegmenterId::of(segmenter.as_ref()))
+        .collect()
+}
+
+fn get(name: &str) -> Option<Arc<dyn Segmenter>> {
+    REGISTRY.read().get(name).cloned()
+}
+
+/// Returns the language of the script of `c`, if it is one of the languages that can be
+/// segmented.
+fn language_of(c: char) -> Option<&'static str> {
+    LANGUAGE_SCRIPTS
+        .iter()
+        .find(|(_, blocks)| {
+            blocks
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&c))
+        })
+        .map(|(language, _)| *language)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SegmenterError {
+    #[error("Unknown segmenter `{name}` for the language `{language}`. The segmenters of this instance are: {}.", format_registered())]
+    UnknownSegmenter { language: String, name: String },
+    #[error("The segmenter `{name}` doesn't segment the language `{language}`.")]
+    UnsupportedLanguage { language: String, name: String },
+    #[error(
+        "The language `{0}` can't be segmented. The languages that can be segmented are: {}.",
+        format_languages()
+    )]
+    UnsupportedScript(String),
+    #[error("The index requires the segmenter `{name}` version {version} for the language `{language}`, but {}.", match .found { Some(found) => format!("version {} is the one of this instance", found), None => "it is not compiled in this instance".to_string() })]
+    MissingSegmenter {
+        language: String,
+        name: String,
+        version: u32,
+        found: Option<u32>,
+    },
+}
+
+fn format_registered() -> String {
+    let names: Vec<_> = registered()
+        .into_iter()
+        .map(|id| format!("`{}`", id.name))
+        .collect();
+    match names.is_empty() {
+        true => "none".to_string(),
+        false => names.join(", "),
+    }
+}
+
+fn format_languages() -> String {
+    LANGUAGE_SCRIPTS
+        .iter()
+        .map(|(language, _)| format!("`{}`", language))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The segmenter a language of an index was segmented with, as stored in the index.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SegmenterId {
+    pub name: String,
+    pub version: u32,
+}
+
+impl SegmenterId {
+    fn of(segmenter: &dyn Segmenter) -> Self {
+        Self {
+            name: segmenter.name().to_string(),
+            version: segmenter.version(),
+        }
+    }
+
+    /// The segmenters selected for each language, with the version of their registered
+    /// segmenter. A segmenter that is not registered, which `CustomSegmenters::resolve` refuses,
+    /// is given the version 0.
+    pub fn resolve(selection: &BTreeMap<String, String>) -> BTreeMap<String, SegmenterId> {
+        selection
+            .iter()
+            .map(|(language, name)| {
+                let version = get(name).map_or(0, |segmenter| segmenter.version());
+                let id = SegmenterId {
+                    name: name.clone(),
+                    version,
+                };
+                (language.clone(), id)
+            })
+            .collect()
+    }
+}
+
+/// Encodes a word with the letters `a` to `p`, two per byte, into a word the tokenizer of milli
+/// keeps whole. The encoding of the prefix of a word is a prefix of the encoding of the word, so
+/// that milli matches the last word of a query as a prefix.
+pub fn encode(word: &str) -> String {
+    word.bytes()
+        .flat_map(|byte| [byte >> 4, byte & 0x0F])
+        .map(|half| (b'a' + half) as char)
+        .collect()
+}
+
+/// The segmenters of the languages of an index.
+#[derive(Clone, Default)]
+pub struct CustomSegmenters(BTreeMap<String, Arc<dyn Segmenter>>);
+
+impl CustomSegmenters {
+    /// Returns the registered segmenters selected for each language by the `customSegmenters`
+    /// setting.
+    pub fn resolve(selection: &BTreeMap<String, String>) -> Result<Self, SegmenterError> {
+        let mut segmenters = BTreeMap::new();
+        for (language, name) in selection {
+            if !LANGUAGE_SCRIPTS.iter().any(|(l, _)| l == language) {
+                return Err(SegmenterError::UnsupportedScript(language.clone()));
+            }
+            let segmenter = get(name).ok_or_else(|| SegmenterError::UnknownSegmenter {
+                language: language.clone(),
+                name: name.clone(),
+            })?;
+            if !segmenter.languages().contains(&language.as_str()) {
+                return Err(SegmenterError::UnsupportedLanguage {
+                    language: language.clone(),
+                    name: name.clone(),
+                });
+            }
+            segmenters.insert(language.clone(), segmenter);
+        }
+        Ok(Self(segmenters))
+    }
+
+    /// Returns the segmenters an index was segmented with, which must be registered with the same
+    /// version.
+    pub fn load(ids: &BTreeMap<String, SegmenterId>) -> Result<Self, SegmenterError> {
+        let mut segmenters = BTreeMap::new();
+        for (language, id) in ids {
+            let segmenter = get(&id.name).filter(|segmenter| segmenter.version() == id.version);
+            match segmenter {
+                Some(segmenter) => segmenters.insert(language.clone(), segmenter),
+                None => {
+                    return Err(SegmenterError::MissingSegmenter {
+                        language: language.clone(),
+                        name: id.name.clone(),
+                        version: id.version,
+                        found: get(&id.name).map(|segmenter| segmenter.version()),
+                    })
+                }
+            };
+        }
+        Ok(Self(segmenters))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The byte ranges of the runs of `text` in the languages of the segmenters, with their
+    /// language.
+    fn runs<'a>(&'a self, text: &str) -> Vec<(Range<usize>, &'a str)> {
+        let mut runs: Vec<(Range<usize>, &str)> = Vec::new();
+        for (i, c) in text.char_indices() {
+            let language = match language_of(c).and_then(|l| self.0.get_key_value(l)) {
+                Some((language, _)) => language.as_str(),
+                None => continue,
+            };
+            match runs.last_mut() {
+                Some((range, l)) if range.end == i && *l == language => range.end += c.len_utf8(),
+                _ => runs.push((i..i + c.len_utf8(), language)),
+            }
+        }
+        runs
+    }
+
+    /// The words of `run`, a run of text written in `language`, lowercased.
+    fn run_words(&self, run: &str, language: &str) -> Vec<String> {
+        self.0[language]
+            .segment(run, language)
+            .into_iter()
+            .filter_map(|range| {
+                let word = run.get(range)?.trim();
+                (!word.is_empty()).then(|| word.to_lowercase())
+            })
+            .collect()
+    }
+
+    /// The words of `text` in the languages of the segmenters, lowercased.
+    pub fn words(&self, text: &str) -> Vec<String> {
+        self.runs(text)
+            .into_iter()
+            .flat_map(|(run, language)| self.run_words(&text[run], language))
+            .collect()
+    }
+
+    /// Replaces the text of `query` in the languages of the segmenters with its encoded words.
+    /// The query still ends with the last of them if it ended with the text, for milli to match
+    /// it as a prefix.
+    pub fn encode_query(&self, query: &str) -> String {
+        let mut encoded = String::with_capacity(query.len() * 2);
+        let mut end = 0;
+        for (run, language) in self.runs(query) {
+            encoded.push_str(&query[end..run.start]);
+            for word in self.run_words(&query[run.clone()], language) {
+                encoded.push(' ');
+                encoded.push_str(&encode(&word));
+            }
+            if run.end < query.len() {
+                encoded.push(' ');
+            }
+            end = run.end;
+        }
+        encoded.push_str(&query[end..]);
+        encoded
+    }
+}
+
+impl PartialEq for CustomSegmenters {
+    fn eq(&self, other: &Self) -> bool {
+        let ids = |segmenters: &Self| -> Vec<(String, SegmenterId)> {
+            segmenters
+                .0
+                .iter()
+                .map(|(language, segmenter)| {
+                    (language.clone(), SegmenterId::of(segmenter.as_ref()))
+                })
+                .collect()
+        };
+        ids(self) == ids(other)
+    }
+}
+
+impl fmt::Debug for CustomSegmenters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.0
+                    .iter()
+                    .map(|(language, segmenter)| (language, segmenter.name())),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn thai() -> CustomSegmenters {
+        let selection = BTreeMap::from([("tha".to_string(), "thai-dictionary".to_string())]);
+        CustomSegmenters::resolve(&selection).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let resolve = |language: &str, name: &str| {
+            let selection = BTreeMap::from([(language.to_string(), name.to_string())]);
+            CustomSegmenters::resolve(&selection).unwrap_err()
+        };
+        assert!(matches!(
+            resolve("tha", "icu"),
+            SegmenterError::UnknownSegmenter { .. }
+        ));
+        assert!(matches!(
+            resolve("khm", "thai-dictionary"),
+            SegmenterError::UnsupportedLanguage { .. }
+        ));
+        assert_eq!(
+            resolve("fra", "thai-dictionary").to_string(),
+            "The language `fra` can't be segmented. The languages that can be segmented are: `tha`, `lao`, `mya`, `khm`."
+        );
+    }
+
+    #[test]
+    fn test_load_refuses_other_versions() {
+        let ids = BTreeMap::from([(
+            "tha".to_string(),
+            SegmenterId {
+                name: "thai-dictionary".to_string(),
+                version: 999,
+            },
+        )]);
+        assert_eq!(
+            CustomSegmenters::load(&ids).unwrap_err().to_string(),
+            "The index requires the segmenter `thai-dictionary` version 999 for the language `tha`, but version 1 is the one of this instance."
+        );
+
+        let ids = BTreeMap::from([(
+            "tha".to_string(),
+            SegmenterId {
+                name: "icu".to_string(),
+                version: 1,
+            },
+        )]);
+        assert_eq!(
+            CustomSegmenters::load(&ids).unwrap_err().to_string(),
+            "The index requires the segmenter `icu` version 1 for the language `tha`, but it is not compiled in this instance."
+        );
+
+        let ids = SegmenterId::resolve(&BTreeMap::from([(
+            "tha".to_string(),
+            "thai-dictionary".to_string(),
+        )]));
+        assert!(CustomSegmenters::load(&ids).is_ok());
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("ok"), "gpgl");
+        assert_eq!(encode("ไทย"), "oaljieoalijhoalikc");
+        assert!(encode("ไทย").starts_with(&encode("ไท")));
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let segmenters = thai();
+        // "Thai food" in Thai, then "market" in Thai as the last word.
+        let query = segmenters.encode_query("อาหารไทย bangkok ตลาด");
+        let expected = format!(
+            " {} {}  bangkok  {}",
+            encode("อาหาร"),
+            encode("ไทย"),
+            encode("ตลาด")
+        );
+        assert_eq!(query, expected);
+
+        let query = segmenters.encode_query("อาหารไทย bangkok");
+        let expected = format!(" {} {}  bangkok", encode("อาหาร"), encode("ไทย"));
+        assert_eq!(query, expected);
+
+        // the languages without a segmenter are left to milli.
+        let query = CustomSegmenters::default().encode_query("อาหารไทย bangkok");
+        assert_eq!(query, "อาหารไทย bangkok");
+    }
+}
@@ -0,0 +1,114 @@
+//! An example segmenter of Thai, splitting the text into the longest words of a small dictionary.
+//!
+//! It is meant as a model for the real segmenters: its dictionary only holds a few common words,
+//! and the text between them is kept as a single word.
+
+use std::ops::Range;
+
+use super::Segmenter;
+
+pub const NAME: &str = "thai-dictionary";
+
+const WORDS: &[&str] = &[
+    "กรุงเทพ",
+    "กาแฟ",
+    "กิน",
+    "ขอบคุณ",
+    "ข้าว",
+    "ครับ",
+    "ค่ะ",
+    "ดี",
+    "ตลาด",
+    "ถูก",
+    "ที่",
+    "น้ำ",
+    "ผลไม้",
+    "ภาษา",
+    "มี",
+    "ราคา",
+    "ร้าน",
+    "ร้านอาหาร",
+    "สวัสดี",
+    "หนังสือ",
+    "อร่อย",
+    "อาหาร",
+    "เมือง",
+    "แพง",
+    "และ",
+    "ใน",
+    "ไทย",
+];
+
+/// Whether `c` is a vowel or a tone mark written above or below the previous consonant, that
+/// can't start a word.
+fn is_combining(c: char) -> bool {
+    matches!(c, '\u{0E31}' | '\u{0E34}'..='\u{0E3A}' | '\u{0E47}'..='\u{0E4E}')
+}
+
+pub struct ThaiDictionarySegmenter;
+
+impl Segmenter for ThaiDictionarySegmenter {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn languages(&self) -> &[&'static str] {
+        &["tha"]
+    }
+
+    fn segment(&self, text: &str, _language: &str) -> Vec<Range<usize>> {
+        let mut words = Vec::new();
+        // the start of the text that is not in the dictionary.
+        let mut unknown = None;
+        let mut i = 0;
+        while let Some(c) = text[i..].chars().next() {
+            let longest = WORDS
+                .iter()
+                .filter(|word| text[i..].starts_with(*word))
+                // a word doesn't end before the marks of its last consonant.
+                .filter(|word| !text[i + word.len()..].starts_with(is_combining))
+                .map(|word| word.len())
+                .max();
+            match longest {
+                Some(len) => {
+                    words.extend(unknown.take().map(|start| start..i));
+                    words.push(i..i + len);
+                    i += len;
+                }
+                None => {
+                    unknown.get_or_insert(i);
+                    i += c.len_utf8();
+                }
+            }
+        }
+        words.extend(unknown.map(|start| start..text.len()));
+        words
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn segment(text: &str) -> Vec<&str> {
+        ThaiDictionarySegmenter
+            .segment(text, "tha")
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_segment() {
+        // the longest word wins.
+        assert_eq!(segment("ร้านอาหารไทยอร่อย"), vec!["ร้านอาหาร", "ไทย", "อร่อย"]);
+        // the text that is not in the dictionary is kept as a word.
+        assert_eq!(segment("กาแฟเย็น"), vec!["กาแฟ", "เย็น"]);
+        assert_eq!(segment("เย็นกาแฟ"), vec!["เย็น", "กาแฟ"]);
+        assert_eq!(segment(""), Vec::<&str>::new());
+    }
+}
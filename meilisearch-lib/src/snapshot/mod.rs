@@ -0,0 +1,314 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use fs_extra::dir::{self, CopyOptions};
+use log::{info, trace};
+use meilisearch_auth::open_auth_store_env;
+use parking_lot::Mutex;
+use tokio::sync::RwLock;
+use tokio::time::sleep;
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+use crate::compression::{from_tar_gz, to_tar_gz_throttled};
+use crate::index_controller::open_meta_env;
+use crate::index_controller::versioning::VERSION_FILE_NAME;
+use crate::index_locks::IndexLocks;
+use crate::index_resolver::meta_store::read_index_uids;
+use crate::tasks::{Job, Scheduler};
+use crate::throttle::{IoThrottle, IoThrottleConfig, ThrottleReport};
+
+pub use verify::{verify_snapshot, FileReport, FileStatus, SnapshotReport, MANIFEST_FILE_NAME};
+
+mod verify;
+
+/// The rate an index is expected to be copied at, in bytes per second, to estimate how long it is
+/// locked by its copy.
+const INDEX_COPY_RATE: u64 = 100 * 1024 * 1024;
+
+pub struct SnapshotService {
+    pub(crate) snapshot_period: Duration,
+    /// The job scheduled every period.
+    pub(crate) job: SnapshotJob,
+    pub(crate) scheduler: Arc<RwLock<Scheduler>>,
+}
+
+impl SnapshotService {
+    pub async fn run(self) {
+        info!(
+            "Snapshot scheduled every {}s.",
+            self.snapshot_period.as_secs()
+        );
+        loop {
+            // a rejected snapshot is logged by the scheduler, the next period will retry.
+            let _ = self
+                .scheduler
+                .write()
+                .await
+                .schedule_job(Job::Snapshot(self.job.clone()));
+            sleep(self.snapshot_period).await;
+        }
+    }
+}
+
+pub fn load_snapshot(
+    db_path: impl AsRef<Path>,
+    snapshot_path: impl AsRef<Path>,
+    ignore_snapshot_if_db_exists: bool,
+    ignore_missing_snapshot: bool,
+) -> anyhow::Result<()> {
+    let empty_db = crate::is_empty_db(&db_path);
+    let snapshot_path_exists = snapshot_path.as_ref().exists();
+
+    if empty_db && snapshot_path_exists {
+        match from_tar_gz(snapshot_path, &db_path) {
+            // the manifest is only needed to verify the snapshot, it isn't part of the database.
+            Ok(()) => match fs::remove_file(db_path.as_ref().join(MANIFEST_FILE_NAME)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            Err(e) => {
+                //clean created db folder
+                std::fs::remove_dir_all(&db_path)?;
+                Err(e)
+            }
+        }
+    } else if !empty_db && !ignore_snapshot_if_db_exists {
+        bail!(
+            "database already exists at {:?}, try to delete it or rename it",
+            db_path
+                .as_ref()
+                .canonicalize()
+                .unwrap_or_else(|_| db_path.as_ref().to_owned())
+        )
+    } else if !snapshot_path_exists && !ignore_missing_snapshot {
+        bail!("snapshot doesn't exist at {:?}", snapshot_path.as_ref())
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotJob {
+    dest_path: PathBuf,
+    src_path: PathBuf,
+
+    meta_env_size: usize,
+    index_size: usize,
+    /// Whether the archive is verified before it replaces the previous snapshot.
+    verify_after_write: bool,
+    throttle: IoThrottleConfig,
+    /// What the throttle did to the last snapshot, shared by the clones of the job.
+    last_report: Arc<Mutex<Option<ThrottleReport>>>,
+    /// The locks taken on the indexes while they are copied.
+    index_locks: IndexLocks,
+}
+
+impl SnapshotJob {
+    pub(crate) fn new(
+        dest_path: PathBuf,
+        src_path: PathBuf,
+        meta_env_size: usize,
+        index_size: usize,
+        verify_after_write: bool,
+    ) -> Self {
+        Self {
+            dest_path,
+            src_path,
+            meta_env_size,
+            index_size,
+            verify_after_write,
+            throttle: IoThrottleConfig::default(),
+            last_report: Arc::default(),
+            index_locks: IndexLocks::default(),
+        }
+    }
+
+    pub(crate) fn set_throttle(&mut self, throttle: IoThrottleConfig) {
+        self.throttle = throttle;
+    }
+
+    pub(crate) fn set_index_locks(&mut self, index_locks: IndexLocks) {
+        self.index_locks = index_locks;
+    }
+
+    /// This job, with its writes never throttled.
+    pub(crate) fn unthrottled(&self) -> Self {
+        Self {
+            throttle: self.throttle.unlimited(),
+            ..self.clone()
+        }
+    }
+
+    /// What the throttle did to the last snapshot written by this job or one of its clones.
+    pub(crate) fn last_report(&self) -> Option<ThrottleReport> {
+        *self.last_report.lock()
+    }
+
+    pub async fn run(self) -> anyhow::Result<()> {
+        tokio::task::spawn_blocking(|| self.run_sync()).await??;
+
+        Ok(())
+    }
+
+    fn run_sync(self) -> anyhow::Result<()> {
+        trace!("Performing snapshot.");
+
+        let snapshot_dir = self.dest_path.clone();
+        std::fs::create_dir_all(&snapshot_dir)?;
+        let temp_snapshot_dir = tempfile::tempdir()?;
+        let temp_snapshot_path = temp_snapshot_dir.path();
+        let mut throttle = IoThrottle::new(self.throttle.clone());
+
+        self.snapshot_version_file(temp_snapshot_path)?;
+        self.snapshot_meta_env(temp_snapshot_path)?;
+        self.snapshot_file_store(temp_snapshot_path)?;
+        self.snapshot_indexes(temp_snapshot_path, &mut throttle)?;
+        self.snapshot_auth(temp_snapshot_path)?;
+        // the other copies are made at full speed, the pause brings the throughput back under
+        // the budget before the archive is written.
+        let written = throttle.report().written_bytes;
+        throttle.consume(dir_size(temp_snapshot_path).saturating_sub(written));
+        verify::write_manifest(temp_snapshot_path)?;
+
+        let db_name = self
+            .src_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("data.ms")
+            .to_string();
+
+        let snapshot_path = self.dest_path.join(format!("{}.snapshot", db_name));
+        let temp_snapshot_file = tempfile::NamedTempFile::new_in(&snapshot_dir)?;
+        let temp_snapshot_file_path = temp_snapshot_file.path().to_owned();
+        to_tar_gz_throttled(temp_snapshot_path, &temp_snapshot_file_path, &mut throttle)?;
+
+        if self.verify_after_write {
+            let report = verify_snapshot(
+                &temp_snapshot_file_path,
+                self.meta_env_size,
+                self.index_size,
+            )?;
+            if !report.is_valid() {
+                bail!("The snapshot failed its verification:\n{}", report);
+            }
+        }
+
+        let _file = temp_snapshot_file.persist(&snapshot_path)?;
+
+        #[cfg(unix)]
+        {
+            use std::fs::Permissions;
+            use std::os::unix::fs::PermissionsExt;
+
+            let perm = Permissions::from_mode(0o644);
+            _file.set_permissions(perm)?;
+        }
+
+        let report = throttle.report();
+        info!(
+            "Created snapshot in {:?}: {} bytes written at {} bytes/s on average, paused for {}ms.",
+            snapshot_path, report.written_bytes, report.average_bytes_per_sec, report.paused_ms
+        );
+        *self.last_report.lock() = Some(report);
+
+        Ok(())
+    }
+
+    fn snapshot_version_file(&self, path: &Path) -> anyhow::Result<()> {
+        let dst = path.join(VERSION_FILE_NAME);
+        let src = self.src_path.join(VERSION_FILE_NAME);
+
+        fs::copy(src, dst)?;
+
+        Ok(())
+    }
+
+    fn snapshot_meta_env(&self, path: &Path) -> anyhow::Result<()> {
+        let env = open_meta_env(&self.src_path, self.meta_env_size)?;
+
+        let dst = path.join("data.mdb");
+        env.copy_to_path(dst, milli::heed::CompactionOption::Enabled)?;
+
+        Ok(())
+    }
+
+    fn snapshot_file_store(&self, path: &Path) -> anyhow::Result<()> {
+        // for now we simply copy the updates/updates_files
+        // FIXME(marin): We may copy more files than necessary, if new files are added while we are
+        // performing the snapshop. We need a way to filter them out.
+
+        let dst = path.join("updates");
+        fs::create_dir_all(&dst)?;
+        let options = CopyOptions::default();
+        dir::copy(self.src_path.join("updates/updates_files"), dst, &options)?;
+
+        Ok(())
+    }
+
+    fn snapshot_indexes(&self, path: &Path, throttle: &mut IoThrottle) -> anyhow::Result<()> {
+        let indexes_path = self.src_path.join("indexes/");
+        let dst = path.join("indexes/");
+        // the directories of the indexes are named after their uuid, but they are locked by uid.
+        let uids = read_index_uids(&open_meta_env(&self.src_path, self.meta_env_size)?)?;
+
+        for entry in WalkDir::new(indexes_path).max_depth(1).into_iter().skip(1) {
+            let entry = entry?;
+            let name = entry.file_name();
+            let uid = name
+                .to_str()
+                .and_then(|name| Uuid::parse_str(name).ok())
+                .and_then(|uuid| uids.get(&uuid));
+            let dst = dst.join(name);
+
+            std::fs::create_dir_all(&dst)?;
+
+            let dst = dst.join("data.mdb");
+
+            let copy = {
+                // an index without uid is being deleted, nothing else uses it.
+                let expected = Duration::from_secs(dir_size(entry.path()) / INDEX_COPY_RATE + 1);
+                let _lock = uid
+                    .map(|uid| self.index_locks.acquire(uid, "snapshot", expected))
+                    .transpose()?;
+
+                let mut options = milli::heed::EnvOpenOptions::new();
+                options.map_size(self.index_size);
+                let index = milli::Index::new(options, entry.path())?;
+                index
+                    .env
+                    .copy_to_path(dst, milli::heed::CompactionOption::Enabled)?
+            };
+            // the indexes are the bulk of a snapshot, each copy is paced before the next one.
+            throttle.consume(copy.metadata()?.len());
+        }
+
+        Ok(())
+    }
+
+    fn snapshot_auth(&self, path: &Path) -> anyhow::Result<()> {
+        let auth_path = self.src_path.join("auth");
+        let dst = path.join("auth");
+        std::fs::create_dir_all(&dst)?;
+        let dst = dst.join("data.mdb");
+
+        let env = open_auth_store_env(&auth_path)?;
+        env.copy_to_path(dst, milli::heed::CompactionOption::Enabled)?;
+
+        Ok(())
+    }
+}
+
+/// The size of the files of `dir`, in bytes.
+fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
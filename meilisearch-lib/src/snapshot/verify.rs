@@ -0,0 +1,387 @@
+//! Verification of a snapshot.
+//!
+//! Every snapshot contains a manifest with the SHA-256 of each of its files. A snapshot is
+//! verified by extracting it in a temporary directory, checking its files against the manifest,
+//! opening the LMDB environments that are intact, and running the fast integrity check of each
+//! index. The task store and the auth store are opened read-only. milli can't open an index
+//! read-only, so the indexes are opened from the extracted copy, which is thrown away afterward.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use flate2::read::GzDecoder;
+use milli::heed::flags::Flags;
+use milli::heed::types::{ByteSlice, DecodeIgnore};
+use milli::heed::EnvOpenOptions;
+use milli::update::IndexerConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tar::Archive;
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+use crate::index::{Index, IndexCheckReport, Severity, DEFAULT_MAX_READERS};
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The SHA-256 of the files of a snapshot, hex encoded, by path relative to the snapshot root.
+type Manifest = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum FileStatus {
+    Ok,
+    /// The file is in the manifest, but not in the archive.
+    Missing,
+    /// The file is in the archive, but not in the manifest.
+    Unexpected,
+    Corrupted {
+        expected: String,
+        found: String,
+    },
+    /// The file matches the manifest, but can't be read as what it should be.
+    Invalid {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReport {
+    pub path: String,
+    #[serde(flatten)]
+    pub status: FileStatus,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotReport {
+    pub files: Vec<FileReport>,
+    /// The integrity check of each index, by uuid.
+    pub indexes: BTreeMap<String, IndexCheckReport>,
+}
+
+impl SnapshotReport {
+    fn push(&mut self, path: impl Into<String>, status: FileStatus) {
+        self.files.push(FileReport {
+            path: path.into(),
+            status,
+        });
+    }
+
+    fn set_status(&mut self, path: &str, status: FileStatus) {
+        match self.files.iter_mut().find(|file| file.path == path) {
+            Some(file) => file.status = status,
+            None => self.push(path, status),
+        }
+    }
+
+    /// A snapshot is valid when all of its files are intact, and none of its indexes has an error.
+    pub fn is_valid(&self) -> bool {
+        self.files.iter().all(|file| file.status == FileStatus::Ok)
+            && self
+                .indexes
+                .values()
+                .all(|report| report.severity() < Some(Severity::Error))
+    }
+}
+
+impl fmt::Display for SnapshotReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in &self.files {
+            match &file.status {
+                FileStatus::Ok => writeln!(f, "{}: ok", file.path)?,
+                FileStatus::Missing => writeln!(f, "{}: missing from the archive", file.path)?,
+                FileStatus::Unexpected => writeln!(f, "{}: missing from the manifest", file.path)?,
+                FileStatus::Corrupted { expected, found } => writeln!(
+                    f,
+                    "{}: corrupted, expected sha256 {} but found {}",
+                    file.path, expected, found
+                )?,
+                FileStatus::Invalid { error } => writeln!(f, "{}: invalid, {}", file.path, error)?,
+            }
+        }
+
+        for (uuid, report) in &self.indexes {
+            for finding in &report.findings {
+                if finding.severity >= Severity::Warning {
+                    writeln!(
+                        f,
+                        "index {}: {:?} in {}, {}",
+                        uuid, finding.severity, finding.check, finding.message
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the manifest of the snapshot whose files are in `dir`.
+pub fn write_manifest(dir: &Path) -> anyhow::Result<()> {
+    let mut manifest = Manifest::new();
+    for (path, relative) in snapshot_files(dir)? {
+        manifest.insert(relative, hash_file(&path)?);
+    }
+
+    let file = File::create(dir.join(MANIFEST_FILE_NAME))?;
+    serde_json::to_writer_pretty(file, &manifest)?;
+
+    Ok(())
+}
+
+/// Verifies the snapshot at `path`. An error is only returned when the verification itself
+/// can't be performed, the problems of the snapshot are reported in the returned report.
+pub fn verify_snapshot(
+    path: impl AsRef<Path>,
+    meta_env_size: usize,
+    index_size: usize,
+) -> anyhow::Result<SnapshotReport> {
+    let path = path.as_ref();
+    let dir = tempfile::tempdir()?;
+    let mut report = SnapshotReport::default();
+
+    // the entries that could be extracted from a truncated archive are still checked.
+    if let Err(e) = extract(path, dir.path()) {
+        report.push(
+            path.display().to_string(),
+            FileStatus::Invalid {
+                error: format!("the archive can't be extracted: {}", e),
+            },
+        );
+    }
+
+    let manifest: Manifest = match File::open(dir.path().join(MANIFEST_FILE_NAME)) {
+        Ok(file) => match serde_json::from_reader(file) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let error = format!("the manifest can't be read: {}", e);
+                report.push(MANIFEST_FILE_NAME, FileStatus::Invalid { error });
+                return Ok(report);
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            report.push(MANIFEST_FILE_NAME, FileStatus::Missing);
+            return Ok(report);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut files: BTreeMap<_, _> = snapshot_files(dir.path())?
+        .into_iter()
+        .map(|(path, relative)| (relative, path))
+        .collect();
+
+    for (relative, expected) in &manifest {
+        let status = match files.remove(relative) {
+            None => FileStatus::Missing,
+            Some(path) => match hash_file(&path) {
+                Ok(found) if &found == expected => FileStatus::Ok,
+                Ok(found) => FileStatus::Corrupted {
+                    expected: expected.clone(),
+                    found,
+                },
+                Err(e) => FileStatus::Invalid {
+                    error: e.to_string(),
+                },
+            },
+        };
+        report.push(relative.clone(), status);
+    }
+
+    for relative in files.into_keys() {
+        report.push(relative, FileStatus::Unexpected);
+    }
+
+    // only the environments that are intact are opened, a truncated environment could crash the
+    // process once mapped in memory.
+    let intact_envs: Vec<_> = report
+        .files
+        .iter()
+        .filter(|file| file.status == FileStatus::Ok && file.path.ends_with("data.mdb"))
+        .map(|file| file.path.clone())
+        .collect();
+
+    for relative in intact_envs {
+        let env_path = dir.path().join(&relative);
+        let env_path = env_path.parent().unwrap_or_else(|| dir.path());
+
+        let result = match relative.strip_prefix("indexes/") {
+            Some(index) => {
+                let uuid = index.trim_end_matches("/data.mdb");
+                check_index(env_path, uuid, index_size).map(|check| {
+                    report.indexes.insert(uuid.to_string(), check);
+                })
+            }
+            None => check_env(env_path, meta_env_size),
+        };
+
+        if let Err(e) = result {
+            let error = format!("the environment can't be opened: {}", e);
+            report.set_status(&relative, FileStatus::Invalid { error });
+        }
+    }
+
+    Ok(report)
+}
+
+fn extract(src: &Path, dest: &Path) -> anyhow::Result<()> {
+    let mut archive = Archive::new(GzDecoder::new(File::open(src)?));
+    for entry in archive.entries()? {
+        entry?.unpack_in(dest)?;
+    }
+
+    Ok(())
+}
+
+/// The files of the snapshot in `dir`, along with their path relative to `dir`. The manifest
+/// itself is ignored.
+fn snapshot_files(dir: &Path) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry
+            .path()
+            .strip_prefix(dir)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if relative != MANIFEST_FILE_NAME {
+            files.push((entry.into_path(), relative));
+        }
+    }
+
+    Ok(files)
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Opens an environment read-only, and reads the names of all its databases.
+fn check_env(path: &Path, size: usize) -> anyhow::Result<()> {
+    let mut options = EnvOpenOptions::new();
+    options.map_size(size);
+    options.max_dbs(20);
+    // Safety: the environment is only ever read, and nothing else uses the extracted copy.
+    unsafe {
+        options.flag(Flags::MdbRdOnly);
+    }
+    let env = options.open(path)?;
+
+    let main = env
+        .open_database::<ByteSlice, DecodeIgnore>(None)?
+        .ok_or_else(|| anyhow::anyhow!("the main database is missing"))?;
+    let rtxn = env.read_txn()?;
+    for entry in main.iter(&rtxn)? {
+        entry?;
+    }
+
+    Ok(())
+}
+
+fn check_index(path: &Path, uuid: &str, size: usize) -> anyhow::Result<IndexCheckReport> {
+    let uuid = Uuid::parse_str(uuid)?;
+    let index = Index::open(
+        path,
+        size,
+        DEFAULT_MAX_READERS,
+        uuid,
+        Arc::new(IndexerConfig::default()),
+    )?;
+    let report = index.check(true)?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    fn snapshot(dir: &Path) -> tempfile::TempPath {
+        let snapshot = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        crate::compression::to_tar_gz(dir, &snapshot).unwrap();
+        snapshot
+    }
+
+    fn statuses(report: &SnapshotReport) -> Vec<(&str, &FileStatus)> {
+        report
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), &file.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_intact_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("updates")).unwrap();
+        fs::write(dir.path().join("VERSION"), "0.28.0").unwrap();
+        fs::write(dir.path().join("updates/payload"), "[]").unwrap();
+        write_manifest(dir.path()).unwrap();
+
+        let report = verify_snapshot(snapshot(dir.path()), 0, 0).unwrap();
+        assert!(report.is_valid(), "{}", report);
+        assert_eq!(
+            statuses(&report),
+            [
+                ("VERSION", &FileStatus::Ok),
+                ("updates/payload", &FileStatus::Ok)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_altered_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("VERSION"), "0.28.0").unwrap();
+        fs::write(dir.path().join("missing"), "").unwrap();
+        write_manifest(dir.path()).unwrap();
+
+        fs::write(dir.path().join("VERSION"), "0.27.0").unwrap();
+        fs::remove_file(dir.path().join("missing")).unwrap();
+        fs::write(dir.path().join("unexpected"), "").unwrap();
+
+        let report = verify_snapshot(snapshot(dir.path()), 0, 0).unwrap();
+        assert!(!report.is_valid());
+        let statuses = statuses(&report);
+        assert!(matches!(
+            statuses[0],
+            ("VERSION", FileStatus::Corrupted { .. })
+        ));
+        assert_eq!(
+            statuses[1..],
+            [
+                ("missing", &FileStatus::Missing),
+                ("unexpected", &FileStatus::Unexpected)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_snapshot_without_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("VERSION"), "0.28.0").unwrap();
+
+        let report = verify_snapshot(snapshot(dir.path()), 0, 0).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(
+            statuses(&report),
+            [(MANIFEST_FILE_NAME, &FileStatus::Missing)]
+        );
+    }
+}
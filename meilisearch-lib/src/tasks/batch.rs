@@ -1,16 +1,22 @@
+use meilisearch_error::ResponseError;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 use crate::snapshot::SnapshotJob;
 
-use super::task::{Task, TaskEvent};
+use super::task::{Task, TaskContent, TaskEvent, TaskId};
 
 pub type BatchId = u32;
 
 #[derive(Debug)]
 pub enum BatchContent {
     DocumentsAdditionBatch(Vec<Task>),
+    /// Deletions of documents by id, of a single index, that are performed as a single deletion.
+    DocumentDeletionBatch(Vec<Task>),
     IndexUpdate(Task),
     Dump(Task),
+    /// The compaction of the tasks database, which can't be processed along with any other task.
+    TaskStoreCompaction(Task),
     Snapshot(SnapshotJob),
     // Symbolizes a empty batch. This can occur when we were woken, but there wasn't any work to do.
     Empty,
@@ -19,18 +25,24 @@ pub enum BatchContent {
 impl BatchContent {
     pub fn first(&self) -> Option<&Task> {
         match self {
-            BatchContent::DocumentsAdditionBatch(ts) => ts.first(),
-            BatchContent::Dump(t) | BatchContent::IndexUpdate(t) => Some(t),
+            BatchContent::DocumentsAdditionBatch(ts) | BatchContent::DocumentDeletionBatch(ts) => {
+                ts.first()
+            }
+            BatchContent::Dump(t)
+            | BatchContent::IndexUpdate(t)
+            | BatchContent::TaskStoreCompaction(t) => Some(t),
             BatchContent::Snapshot(_) | BatchContent::Empty => None,
         }
     }
 
     pub fn push_event(&mut self, event: TaskEvent) {
         match self {
-            BatchContent::DocumentsAdditionBatch(ts) => {
+            BatchContent::DocumentsAdditionBatch(ts) | BatchContent::DocumentDeletionBatch(ts) => {
                 ts.iter_mut().for_each(|t| t.events.push(event.clone()))
             }
-            BatchContent::IndexUpdate(t) | BatchContent::Dump(t) => t.events.push(event),
+            BatchContent::IndexUpdate(t)
+            | BatchContent::Dump(t)
+            | BatchContent::TaskStoreCompaction(t) => t.events.push(event),
             BatchContent::Snapshot(_) | BatchContent::Empty => (),
         }
     }
@@ -43,6 +55,8 @@ pub struct Batch {
     pub id: Option<BatchId>,
     pub created_at: OffsetDateTime,
     pub content: BatchContent,
+    /// Why the scheduler stopped adding tasks to this batch, for the batches of tasks.
+    pub stop_reason: Option<BatchStopReason>,
 }
 
 impl Batch {
@@ -51,12 +65,17 @@ impl Batch {
             id,
             created_at: OffsetDateTime::now_utc(),
             content,
+            stop_reason: None,
         }
     }
     pub fn len(&self) -> usize {
         match self.content {
-            BatchContent::DocumentsAdditionBatch(ref ts) => ts.len(),
-            BatchContent::IndexUpdate(_) | BatchContent::Dump(_) | BatchContent::Snapshot(_) => 1,
+            BatchContent::DocumentsAdditionBatch(ref ts)
+            | BatchContent::DocumentDeletionBatch(ref ts) => ts.len(),
+            BatchContent::IndexUpdate(_)
+            | BatchContent::Dump(_)
+            | BatchContent::TaskStoreCompaction(_)
+            | BatchContent::Snapshot(_) => 1,
             BatchContent::Empty => 0,
         }
     }
@@ -70,6 +89,118 @@ impl Batch {
             id: None,
             created_at: OffsetDateTime::now_utc(),
             content: BatchContent::Empty,
+            stop_reason: None,
         }
     }
 }
+
+/// Why the scheduler stopped adding tasks to a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchStopReason {
+    /// The task can't be batched with any other task.
+    NotBatchable,
+    /// The batch reached the maximum number of tasks per batch.
+    MaxBatchSize,
+    /// The batch reached the maximum number of documents per batch.
+    MaxDocumentsPerBatch,
+    /// The next enqueued task of the index can't be processed along with the tasks of the batch.
+    IncompatibleTask,
+    /// There was no other enqueued task for the index.
+    NoMoreTasks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchOutcome {
+    Succeeded,
+    /// Some tasks of the batch succeeded, and the others failed.
+    PartiallyFailed,
+    Failed,
+}
+
+/// The summary of a processed batch, persisted in the task store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResult {
+    pub id: BatchId,
+    pub index_uid: Option<String>,
+    pub task_ids: Vec<TaskId>,
+    /// The number of documents received by the document additions of the batch.
+    pub documents_count: u64,
+    pub outcome: BatchOutcome,
+    /// The error of the first failed task of the batch.
+    pub error: Option<ResponseError>,
+    pub stop_reason: Option<BatchStopReason>,
+    /// The time in milliseconds the update loop waited for the tasks of the batch before
+    /// processing it, if it debounced.
+    #[serde(default)]
+    pub debounce_ms: Option<u64>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub finished_at: OffsetDateTime,
+}
+
+impl BatchResult {
+    /// Summarizes a processed batch that started at `started_at`. Returns `None` for the batches
+    /// that contain no persisted task.
+    pub fn new(batch: &Batch, started_at: OffsetDateTime) -> Option<Self> {
+        let id = batch.id?;
+        let tasks: Vec<&Task> = match batch.content {
+            BatchContent::DocumentsAdditionBatch(ref tasks)
+            | BatchContent::DocumentDeletionBatch(ref tasks) => tasks.iter().collect(),
+            BatchContent::IndexUpdate(ref task)
+            | BatchContent::Dump(ref task)
+            | BatchContent::TaskStoreCompaction(ref task) => vec![task],
+            BatchContent::Snapshot(_) | BatchContent::Empty => return None,
+        };
+
+        let mut succeeded = 0;
+        let mut error = None;
+        let mut documents_count = 0;
+        for task in &tasks {
+            match task.events.last() {
+                // the batch of a chunk succeeded when the chunk was indexed.
+                Some(TaskEvent::Succeeded { .. } | TaskEvent::ChunkProcessed { .. }) => {
+                    succeeded += 1
+                }
+                Some(TaskEvent::Failed { error: e, .. }) => {
+                    error.get_or_insert_with(|| e.clone());
+                }
+                _ => (),
+            }
+            if let TaskContent::DocumentAddition {
+                documents_count: count,
+                ..
+            } = task.content
+            {
+                documents_count += count as u64;
+            }
+        }
+
+        let outcome = if succeeded == tasks.len() {
+            BatchOutcome::Succeeded
+        } else if succeeded == 0 {
+            BatchOutcome::Failed
+        } else {
+            BatchOutcome::PartiallyFailed
+        };
+
+        Some(Self {
+            id,
+            index_uid: tasks
+                .first()
+                .and_then(|task| task.index_uid())
+                .map(ToString::to_string),
+            task_ids: tasks.iter().map(|task| task.id).collect(),
+            documents_count,
+            outcome,
+            error,
+            stop_reason: batch.stop_reason,
+            debounce_ms: None,
+            started_at,
+            finished_at: OffsetDateTime::now_utc(),
+        })
+    }
+}
@@ -0,0 +1,420 @@
+//! A benchmark of the batching of the scheduler, printing a table of the reference scenarios:
+//!
+//! ```text
+//! cargo test -p meilisearch-lib --features scheduler-bench -- --nocapture scenarios
+//! ```
+//!
+//! The workloads are generated from a seed, and replayed against the task queue and `make_batch`
+//! with a virtual clock: the processing of a batch is simulated by a cost model instead of
+//! indexing anything. A scenario runs in milliseconds and gives the same numbers on every machine,
+//! so a change to the batching can show its numbers before and after.
+
+use std::collections::{BTreeMap, HashMap};
+
+use milli::update::IndexDocumentsMethod;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::index_resolver::IndexUid;
+use crate::options::SchedulerConfig;
+
+use super::scheduler::{make_batch, Processing, TaskQueue};
+use super::task::{DocumentDeletion, Task, TaskContent, TaskEvent, TaskId};
+
+/// A task waiting longer than this, in milliseconds, is starved.
+const STARVATION_MS: u64 = 10_000;
+
+/// A xorshift generator, so that a workload only depends on its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// A number in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in `[low, high]`.
+    fn range(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next_u64() % (high - low + 1)
+    }
+}
+
+/// How the tasks arrive, in milliseconds of the virtual clock.
+#[derive(Debug, Clone, Copy)]
+enum Arrivals {
+    /// A task every `interval`.
+    Steady { interval: u64 },
+    /// Exponentially distributed intervals, of `mean` on average.
+    Poisson { mean: u64 },
+    /// `size` tasks `interval` apart, then nothing for `pause`.
+    Bursts {
+        size: usize,
+        interval: u64,
+        pause: u64,
+    },
+}
+
+/// The relative weights of the kinds of tasks.
+#[derive(Debug, Clone, Copy)]
+struct TaskMix {
+    additions: u32,
+    updates: u32,
+    deletions: u32,
+    settings: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Addition,
+    Update,
+    Deletion,
+    Settings,
+}
+
+impl TaskMix {
+    fn pick(&self, rng: &mut Rng) -> Kind {
+        let total = self.additions + self.updates + self.deletions + self.settings;
+        let mut n = rng.range(0, total as u64 - 1) as u32;
+        for (weight, kind) in [
+            (self.additions, Kind::Addition),
+            (self.updates, Kind::Update),
+            (self.deletions, Kind::Deletion),
+        ] {
+            if n < weight {
+                return kind;
+            }
+            n -= weight;
+        }
+        Kind::Settings
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Workload {
+    name: &'static str,
+    seed: u64,
+    tasks: usize,
+    indexes: usize,
+    /// The share of the tasks sent to the first index, the others being spread evenly on the
+    /// other indexes.
+    hot_index_share: f64,
+    arrivals: Arrivals,
+    mix: TaskMix,
+    /// The bounds of the number of documents of an addition, an update or a deletion.
+    documents: (u64, u64),
+}
+
+/// A task of a workload, along with what the simulation needs to know about it.
+struct Arrival {
+    /// The virtual time the task is registered at.
+    at: u64,
+    index: usize,
+    kind: Kind,
+    documents: u64,
+    task: Task,
+}
+
+impl Workload {
+    fn generate(&self) -> Vec<Arrival> {
+        let mut rng = Rng::new(self.seed);
+        let mut at = 0;
+        (0..self.tasks)
+            .map(|i| {
+                at += match self.arrivals {
+                    Arrivals::Steady { interval } => interval,
+                    Arrivals::Poisson { mean } => {
+                        (-(mean as f64) * (1.0 - rng.next_f64()).ln()).round() as u64
+                    }
+                    Arrivals::Bursts { size, pause, .. } if i % size == 0 && i != 0 => pause,
+                    Arrivals::Bursts { interval, .. } => interval,
+                };
+                let index = if self.indexes == 1 || rng.next_f64() < self.hot_index_share {
+                    0
+                } else {
+                    rng.range(1, self.indexes as u64 - 1) as usize
+                };
+                let kind = self.mix.pick(&mut rng);
+                let documents = rng.range(self.documents.0, self.documents.1);
+                let task = gen_task(i as TaskId, at, index, kind, documents);
+                Arrival {
+                    at,
+                    index,
+                    kind,
+                    documents,
+                    task,
+                }
+            })
+            .collect()
+    }
+}
+
+fn gen_task(id: TaskId, at: u64, index: usize, kind: Kind, documents: u64) -> Task {
+    let index_uid = IndexUid::new_unchecked(format!("index{}", index));
+    let content = match kind {
+        Kind::Addition | Kind::Update => TaskContent::DocumentAddition {
+            index_uid,
+            content_uuid: Uuid::new_v4(),
+            merge_strategy: match kind {
+                Kind::Addition => IndexDocumentsMethod::ReplaceDocuments,
+                _ => IndexDocumentsMethod::UpdateDocuments,
+            },
+            primary_key: None,
+            documents_count: documents as usize,
+            allow_index_creation: true,
+            reject_duplicates: false,
+            chunks: Vec::new(),
+            return_diff: None,
+            content_digests: Default::default(),
+            sanitized_characters: None,
+            warnings: None,
+        },
+        Kind::Deletion => TaskContent::DocumentDeletion {
+            index_uid,
+            deletion: DocumentDeletion::Ids((0..documents).map(|id| id.to_string()).collect()),
+        },
+        Kind::Settings => TaskContent::SettingsUpdate {
+            index_uid,
+            settings: Default::default(),
+            is_deletion: false,
+            allow_index_creation: true,
+            scheduled_at: None,
+            warnings: Vec::new(),
+        },
+    };
+    let enqueued_at = OffsetDateTime::UNIX_EPOCH + time::Duration::milliseconds(at as i64);
+    Task {
+        id,
+        index_uuid: None,
+        content,
+        events: vec![TaskEvent::Created(enqueued_at)],
+        origin: None,
+        engine_version: None,
+    }
+}
+
+/// The simulated time a batch takes to be processed, in milliseconds.
+#[derive(Debug, Clone, Copy)]
+struct CostModel {
+    per_batch: u64,
+    /// In microseconds, for the additions, the updates and the deletions.
+    per_document_us: u64,
+    per_settings_update: u64,
+}
+
+impl CostModel {
+    fn cost(&self, tasks: &[&Arrival]) -> u64 {
+        let work: u64 = tasks
+            .iter()
+            .map(|task| match task.kind {
+                Kind::Settings => self.per_settings_update,
+                _ => task.documents * self.per_document_us / 1000,
+            })
+            .sum();
+        self.per_batch + work
+    }
+}
+
+/// Drives the task queue with a workload. The scheduler is woken up by the first task arriving
+/// while it is idle, and waits for `debounce` milliseconds before making its first batch. It then
+/// makes a batch after the other, as long as there are tasks in the queue.
+struct Harness {
+    config: SchedulerConfig,
+    debounce: u64,
+    cost: CostModel,
+}
+
+#[derive(Debug, PartialEq)]
+struct Report {
+    name: &'static str,
+    tasks: usize,
+    batches: usize,
+    mean_batch_size: f64,
+    /// The time the tasks waited in the queue, from their arrival to the start of their batch.
+    p50_wait: u64,
+    p99_wait: u64,
+    max_wait: u64,
+    /// The number of tasks that waited for more than `STARVATION_MS`.
+    starved: usize,
+    /// The highest mean wait of the tasks of an index.
+    worst_index_mean_wait: u64,
+    /// The time the last batch ended at.
+    makespan: u64,
+}
+
+impl Harness {
+    fn run(&self, workload: &Workload) -> Report {
+        let arrivals = workload.generate();
+        let by_id: HashMap<TaskId, &Arrival> = arrivals.iter().map(|a| (a.task.id, a)).collect();
+        let mut queue = TaskQueue::default();
+        let mut waits = Vec::with_capacity(arrivals.len());
+        let mut waits_by_index: BTreeMap<usize, Vec<u64>> = BTreeMap::new();
+        let mut batches = 0;
+        let mut next = 0;
+        let mut clock = 0;
+
+        loop {
+            while let Some(arrival) = arrivals.get(next).filter(|a| a.at <= clock) {
+                queue.insert(arrival.task.clone());
+                next += 1;
+            }
+            if queue.is_empty() {
+                match arrivals.get(next) {
+                    Some(arrival) => clock = arrival.at + self.debounce,
+                    None => break,
+                }
+                continue;
+            }
+
+            let (processing, _) = make_batch(&mut queue, &self.config);
+            assert!(processing != Processing::Nothing, "the queue was not empty");
+            let tasks: Vec<_> = processing.ids().map(|id| by_id[&id]).collect();
+            for task in &tasks {
+                let wait = clock - task.at;
+                waits.push(wait);
+                waits_by_index.entry(task.index).or_default().push(wait);
+            }
+            batches += 1;
+            clock += self.cost.cost(&tasks);
+        }
+        assert_eq!(waits.len(), arrivals.len(), "every task is processed once");
+
+        waits.sort_unstable();
+        let percentile = |q: f64| waits[((waits.len() - 1) as f64 * q).round() as usize];
+        Report {
+            name: workload.name,
+            tasks: waits.len(),
+            batches,
+            mean_batch_size: waits.len() as f64 / batches as f64,
+            p50_wait: percentile(0.5),
+            p99_wait: percentile(0.99),
+            max_wait: waits.last().copied().unwrap_or_default(),
+            starved: waits.iter().filter(|wait| **wait > STARVATION_MS).count(),
+            worst_index_mean_wait: waits_by_index
+                .values()
+                .map(|waits| waits.iter().sum::<u64>() / waits.len() as u64)
+                .max()
+                .unwrap_or_default(),
+            makespan: clock,
+        }
+    }
+}
+
+/// The reference scenarios. A change to the batching should show how it affects them.
+fn reference_scenarios() -> Vec<Workload> {
+    vec![
+        Workload {
+            name: "steady trickle",
+            seed: 1,
+            tasks: 2_000,
+            indexes: 1,
+            hot_index_share: 1.0,
+            arrivals: Arrivals::Steady { interval: 50 },
+            mix: TaskMix {
+                additions: 9,
+                updates: 1,
+                deletions: 0,
+                settings: 0,
+            },
+            documents: (10, 100),
+        },
+        Workload {
+            name: "bursts on 10 indexes",
+            seed: 2,
+            tasks: 5_000,
+            indexes: 10,
+            hot_index_share: 0.1,
+            arrivals: Arrivals::Bursts {
+                size: 500,
+                interval: 1,
+                pause: 5_000,
+            },
+            mix: TaskMix {
+                additions: 6,
+                updates: 2,
+                deletions: 1,
+                settings: 1,
+            },
+            documents: (100, 5_000),
+        },
+        Workload {
+            name: "hot index",
+            seed: 3,
+            tasks: 5_000,
+            indexes: 5,
+            hot_index_share: 0.8,
+            arrivals: Arrivals::Poisson { mean: 5 },
+            mix: TaskMix {
+                additions: 8,
+                updates: 1,
+                deletions: 1,
+                settings: 0,
+            },
+            documents: (1_000, 20_000),
+        },
+    ]
+}
+
+fn reference_harness() -> Harness {
+    Harness {
+        config: SchedulerConfig {
+            enable_auto_batching: true,
+            max_documents_per_batch: Some(100_000),
+            ..Default::default()
+        },
+        debounce: 100,
+        cost: CostModel {
+            per_batch: 50,
+            per_document_us: 20,
+            per_settings_update: 500,
+        },
+    }
+}
+
+#[test]
+fn scenarios() {
+    let harness = reference_harness();
+    println!(
+        "{:<22} {:>6} {:>8} {:>9} {:>10} {:>10} {:>10} {:>8} {:>12} {:>10}",
+        "scenario",
+        "tasks",
+        "batches",
+        "avg size",
+        "p50 wait",
+        "p99 wait",
+        "max wait",
+        "starved",
+        "worst index",
+        "makespan"
+    );
+    for workload in reference_scenarios() {
+        let report = harness.run(&workload);
+        println!(
+            "{:<22} {:>6} {:>8} {:>9.2} {:>8}ms {:>8}ms {:>8}ms {:>8} {:>10}ms {:>8}ms",
+            report.name,
+            report.tasks,
+            report.batches,
+            report.mean_batch_size,
+            report.p50_wait,
+            report.p99_wait,
+            report.max_wait,
+            report.starved,
+            report.worst_index_mean_wait,
+            report.makespan,
+        );
+        // the numbers can only be compared if a workload is always the same.
+        assert_eq!(harness.run(&workload), report);
+    }
+}
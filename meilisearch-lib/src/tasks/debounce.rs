@@ -0,0 +1,138 @@
+//! The adaptive debounce: the time the update loop waits for new tasks before processing a batch
+//! follows the rate at which the tasks of its index arrive.
+//!
+//! The interval between two tasks of an index is averaged with an exponentially weighted moving
+//! average, and the debounce is the time a few more tasks are expected to take to arrive, bounded
+//! by the configured minimum and maximum. A burst shortens the wait, since the batch fills anyway,
+//! while a trickle of tasks lengthens it up to the maximum, so that the tasks are not processed one
+//! by one.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+/// The weight of the last interval in the average.
+const SMOOTHING: f64 = 0.3;
+/// The number of arrivals a batch waits for.
+const WAITED_ARRIVALS: f64 = 4.0;
+/// An index without any arrival for that long is forgotten. It is waited for as long as possible
+/// anyway.
+const FORGET_AFTER: Duration = Duration::from_secs(3600);
+
+struct ArrivalRate {
+    last_arrival: OffsetDateTime,
+    /// The average interval between two arrivals, in seconds. Unknown until the second arrival.
+    mean_interval: Option<f64>,
+}
+
+pub struct AdaptiveDebounce {
+    min: Duration,
+    max: Duration,
+    /// The arrival rates by index, the tasks without an index being tracked together.
+    indexes: HashMap<Option<String>, ArrivalRate>,
+}
+
+impl AdaptiveDebounce {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max: max.max(min),
+            indexes: HashMap::new(),
+        }
+    }
+
+    /// Records the arrival of a task of `index_uid` at `at`.
+    pub fn arrived(&mut self, index_uid: Option<&str>, at: OffsetDateTime) {
+        self.indexes.retain(|_, rate| {
+            Duration::try_from(at - rate.last_arrival).map_or(true, |idle| idle < FORGET_AFTER)
+        });
+
+        match self.indexes.get_mut(&index_uid.map(String::from)) {
+            Some(rate) => {
+                // the tasks are fetched in order, but a clock going back must not break the mean.
+                let interval = (at - rate.last_arrival).as_seconds_f64().max(0.0);
+                let mean = match rate.mean_interval {
+                    Some(mean) => SMOOTHING * interval + (1.0 - SMOOTHING) * mean,
+                    None => interval,
+                };
+                rate.mean_interval = Some(mean);
+                rate.last_arrival = rate.last_arrival.max(at);
+            }
+            None => {
+                let rate = ArrivalRate {
+                    last_arrival: at,
+                    mean_interval: None,
+                };
+                self.indexes.insert(index_uid.map(String::from), rate);
+            }
+        }
+    }
+
+    /// The time to wait for the tasks of `index_uid` before processing their batch. An index
+    /// whose rate is unknown yet is waited for as long as possible.
+    pub fn debounce(&self, index_uid: Option<&str>) -> Duration {
+        let mean_interval = self
+            .indexes
+            .get(&index_uid.map(String::from))
+            .and_then(|rate| rate.mean_interval);
+        match mean_interval {
+            Some(mean) => {
+                let wait = mean * WAITED_ARRIVALS;
+                if wait >= self.max.as_secs_f64() {
+                    self.max
+                } else {
+                    Duration::from_secs_f64(wait).max(self.min)
+                }
+            }
+            None => self.max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_debounce_follows_the_arrival_rate() {
+        let min = Duration::from_millis(100);
+        let max = Duration::from_secs(10);
+        let mut debounce = AdaptiveDebounce::new(min, max);
+        let start = OffsetDateTime::now_utc();
+
+        // nothing is known about the index yet.
+        assert_eq!(debounce.debounce(Some("burst")), max);
+        debounce.arrived(Some("burst"), start);
+        assert_eq!(debounce.debounce(Some("burst")), max);
+
+        // a burst of a task every 10ms.
+        for i in 1..50 {
+            debounce.arrived(Some("burst"), start + time::Duration::milliseconds(10 * i));
+        }
+        assert_eq!(debounce.debounce(Some("burst")), min);
+
+        // a task every second.
+        for i in 0..50 {
+            debounce.arrived(Some("steady"), start + time::Duration::seconds(i));
+        }
+        let steady = debounce.debounce(Some("steady"));
+        assert!(steady > Duration::from_millis(3900), "{:?}", steady);
+        assert!(steady < Duration::from_millis(4100), "{:?}", steady);
+
+        // a trickle of a task every minute.
+        for i in 0..5 {
+            debounce.arrived(None, start + time::Duration::minutes(i));
+        }
+        assert_eq!(debounce.debounce(None), max);
+
+        // the burst stops, the rate of the index slows down.
+        let later = start + time::Duration::milliseconds(490);
+        debounce.arrived(Some("burst"), later + time::Duration::seconds(30));
+        assert!(debounce.debounce(Some("burst")) > min);
+
+        // the indexes idle for too long are forgotten.
+        debounce.arrived(Some("other"), start + time::Duration::hours(2));
+        assert_eq!(debounce.indexes.len(), 1);
+    }
+}
@@ -3,7 +3,8 @@ use tokio::task::JoinError;
 
 use crate::update_file_store::UpdateFileStoreError;
 
-use super::task::TaskId;
+use super::batch::BatchId;
+use super::task::{Task, TaskId};
 
 pub type Result<T> = std::result::Result<T, TaskError>;
 
@@ -11,8 +12,53 @@ pub type Result<T> = std::result::Result<T, TaskError>;
 pub enum TaskError {
     #[error("Task `{0}` not found.")]
     UnexistingTask(TaskId),
+    #[error("Task `{0}` cannot be canceled because it is already being processed or has been processed.")]
+    TaskNotCancelable(TaskId),
+    #[error("Task `{0}` was canceled before being processed.")]
+    TaskCanceled(TaskId),
+    #[error("Task `{0}` was aborted by an administrator because its batch was stuck.")]
+    AbortedByAdmin(TaskId),
+    #[error("Task `{id}` was quarantined after the processing of its batch failed {failures} times in a row, so that the next tasks of its index can be processed. Last failure: {last_failure}")]
+    QuarantinedAfterRepeatedFailures {
+        id: TaskId,
+        failures: u32,
+        last_failure: String,
+    },
+    #[error("The processing of the batch panicked: {0}")]
+    BatchPanicked(String),
+    #[error("No batch is being processed.")]
+    NoProcessingBatch,
+    #[error("The batch being processed showed a sign of life {silence}s ago. It can only be reset once it stays silent for {grace_period}s.")]
+    ProcessingBatchAlive { silence: u64, grace_period: u64 },
+    #[error("Batch `{0}` not found.")]
+    UnexistingBatch(BatchId),
+    #[error("A batch can't be prepared while {} is outstanding.", batch_name(.0))]
+    BatchOutstanding(Option<BatchId>),
+    #[error("Can't finish {}: no batch is outstanding.", batch_name(.0))]
+    NoOutstandingBatch(Option<BatchId>),
+    #[error("Can't finish {} while {} is outstanding.", batch_name(.finished), batch_name(.outstanding))]
+    WrongBatchFinished {
+        finished: Option<BatchId>,
+        outstanding: Option<BatchId>,
+    },
+    #[error("`{0}` is not a valid idempotency key. It should be made of 1 to 256 visible ascii characters.")]
+    InvalidIdempotencyKey(String),
+    #[error("The tasks were written by a more recent version of Meilisearch, in the task format version {found}, while this version only supports up to version {supported}. Downgrading Meilisearch is not supported, use a dump to load your data instead.")]
+    UnsupportedTaskFormat { found: u8, supported: u8 },
+    #[error("Too many tasks are being registered at once. Retry later.")]
+    TooManyRegistrations,
+    #[error(
+        "The {kind} can't be queued: {max} jobs are already waiting to be processed. Retry later."
+    )]
+    TooManyQueuedJobs { kind: &'static str, max: usize },
+    #[error("The tasks database is being compacted. Retry later.")]
+    DatabaseUnavailable,
+    #[error("Not enough disk space to compact the tasks database: {required} bytes are required, but only {available} bytes are available.")]
+    NotEnoughSpaceToCompact { required: u64, available: u64 },
+    #[error("Task `{}` was still not finished when the wait for it timed out.", .0.id)]
+    WaitTimeout(Box<Task>),
     #[error("Internal error: {0}")]
-    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+    Internal(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
 }
 
 internal_error!(
@@ -23,10 +69,44 @@ internal_error!(
     UpdateFileStoreError
 );
 
+impl TaskError {
+    /// Whether the error failing a batch comes from its tasks, rather than from the environment of
+    /// the instance, like a full disk or an unavailable database, which fails every batch alike.
+    pub fn is_task_local(&self) -> bool {
+        matches!(self, TaskError::BatchPanicked(_))
+    }
+}
+
+/// The jobs, and the empty batches, have no id.
+fn batch_name(id: &Option<BatchId>) -> String {
+    match id {
+        Some(id) => format!("batch `{}`", id),
+        None => "a batch without id".to_string(),
+    }
+}
+
 impl ErrorCode for TaskError {
     fn error_code(&self) -> Code {
         match self {
             TaskError::UnexistingTask(_) => Code::TaskNotFound,
+            TaskError::TaskNotCancelable(_) => Code::TaskNotCancelable,
+            TaskError::TaskCanceled(_) => Code::TaskCanceled,
+            TaskError::AbortedByAdmin(_) => Code::TaskAbortedByAdmin,
+            TaskError::QuarantinedAfterRepeatedFailures { .. } => Code::TaskQuarantined,
+            TaskError::BatchPanicked(_) => Code::Internal,
+            TaskError::NoProcessingBatch => Code::ProcessingBatchNotStuck,
+            TaskError::ProcessingBatchAlive { .. } => Code::ProcessingBatchNotStuck,
+            TaskError::UnexistingBatch(_) => Code::BatchNotFound,
+            TaskError::BatchOutstanding(_)
+            | TaskError::NoOutstandingBatch(_)
+            | TaskError::WrongBatchFinished { .. } => Code::Internal,
+            TaskError::InvalidIdempotencyKey(_) => Code::InvalidIdempotencyKey,
+            TaskError::UnsupportedTaskFormat { .. } => Code::Internal,
+            TaskError::TooManyRegistrations => Code::TooManyTaskRegistrations,
+            TaskError::TooManyQueuedJobs { .. } => Code::TooManyQueuedJobs,
+            TaskError::DatabaseUnavailable => Code::DatabaseUnavailable,
+            TaskError::NotEnoughSpaceToCompact { .. } => Code::NoSpaceLeftOnDevice,
+            TaskError::WaitTimeout(_) => Code::TaskWaitTimeout,
             TaskError::Internal(_) => Code::Internal,
         }
     }
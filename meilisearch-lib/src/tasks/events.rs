@@ -0,0 +1,237 @@
+//! The lifecycle events of the tasks.
+//!
+//! The scheduler announces the state transitions of the tasks on a broadcast channel, right after
+//! they are saved in the store, so that a subscriber never hears about a state it can't read back.
+//! A subscriber that doesn't keep up misses the oldest events instead of slowing the scheduler
+//! down, and is told how many it missed. The streams end once the scheduler is dropped.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::batch::BatchId;
+use super::task::{Task, TaskEvent, TaskId};
+
+/// The number of events kept for a subscriber before it misses the oldest ones.
+const EVENTS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskOutcome {
+    Succeeded,
+    Failed,
+}
+
+/// A state transition of a task, or the number of events a subscriber missed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskLifecycleEvent {
+    Registered {
+        task_id: TaskId,
+    },
+    Batched {
+        task_id: TaskId,
+        batch_id: BatchId,
+    },
+    Processing {
+        task_id: TaskId,
+    },
+    Finished {
+        task_id: TaskId,
+        outcome: TaskOutcome,
+    },
+    /// The subscriber was too slow, and missed this many events.
+    Lagged(u64),
+}
+
+impl TaskLifecycleEvent {
+    /// The task of the event, none for `Lagged`.
+    pub fn task_id(&self) -> Option<TaskId> {
+        match self {
+            Self::Registered { task_id }
+            | Self::Batched { task_id, .. }
+            | Self::Processing { task_id }
+            | Self::Finished { task_id, .. } => Some(*task_id),
+            Self::Lagged(_) => None,
+        }
+    }
+
+    /// The events of the transition `task` just went through, read from its last events.
+    fn of(task: &Task) -> Vec<Self> {
+        let task_id = task.id;
+        match task.events.as_slice() {
+            [.., TaskEvent::Succeeded { .. }] => vec![Self::Finished {
+                task_id,
+                outcome: TaskOutcome::Succeeded,
+            }],
+            [.., TaskEvent::Failed { .. }] => vec![Self::Finished {
+                task_id,
+                outcome: TaskOutcome::Failed,
+            }],
+            // a batch is saved once its tasks are processing, along with the batch they are in.
+            [.., TaskEvent::Batched { batch_id, .. }, TaskEvent::Processing(_)] => vec![
+                Self::Batched {
+                    task_id,
+                    batch_id: *batch_id,
+                },
+                Self::Processing { task_id },
+            ],
+            [.., TaskEvent::Processing(_)] => vec![Self::Processing { task_id }],
+            [.., TaskEvent::Batched { batch_id, .. }] => vec![Self::Batched {
+                task_id,
+                batch_id: *batch_id,
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The sending side of the lifecycle events, owned by the scheduler.
+pub struct TaskEvents {
+    sender: broadcast::Sender<TaskLifecycleEvent>,
+}
+
+impl Default for TaskEvents {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENTS_CAPACITY).0,
+        }
+    }
+}
+
+impl TaskEvents {
+    fn send(&self, event: TaskLifecycleEvent) {
+        // the announcement only fails when nobody is subscribed.
+        let _ = self.sender.send(event);
+    }
+
+    /// Announces the registration of `task`, once it is saved. A task registered already finished
+    /// is announced finished too.
+    pub fn registered(&self, task: &Task) {
+        self.send(TaskLifecycleEvent::Registered { task_id: task.id });
+        if task.is_finished() {
+            self.updated(std::slice::from_ref(task));
+        }
+    }
+
+    /// Announces the transitions of `tasks`, once they are saved.
+    pub fn updated(&self, tasks: &[Task]) {
+        for event in tasks.iter().flat_map(TaskLifecycleEvent::of) {
+            self.send(event);
+        }
+    }
+
+    /// Returns a stream of the events announced from now on.
+    pub fn subscribe(&self) -> TaskEventStream {
+        let mut receiver = self.sender.subscribe();
+        let events = async_stream::stream! {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => yield event,
+                    Err(RecvError::Lagged(missed)) => yield TaskLifecycleEvent::Lagged(missed),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+        TaskEventStream {
+            inner: Box::pin(events),
+        }
+    }
+}
+
+/// The lifecycle events announced since the subscription, ending with the scheduler.
+pub struct TaskEventStream {
+    inner: Pin<Box<dyn Stream<Item = TaskLifecycleEvent> + Send>>,
+}
+
+impl Stream for TaskEventStream {
+    type Item = TaskLifecycleEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::index_resolver::IndexUid;
+    use crate::tasks::error::TaskError;
+    use crate::tasks::task::{TaskContent, TaskResult};
+
+    fn task(id: TaskId, events: Vec<TaskEvent>) -> Task {
+        let mut all = vec![TaskEvent::Created(OffsetDateTime::now_utc())];
+        all.extend(events);
+        Task {
+            id,
+            index_uuid: None,
+            content: TaskContent::IndexDeletion {
+                index_uid: IndexUid::new_unchecked("test"),
+            },
+            events: all,
+            origin: None,
+            engine_version: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_lifecycle_events() {
+        let events = TaskEvents::default();
+        let stream = events.subscribe();
+
+        let now = OffsetDateTime::now_utc();
+        events.registered(&task(0, Vec::new()));
+        let batched = vec![
+            TaskEvent::Batched {
+                timestamp: now,
+                batch_id: 0,
+            },
+            TaskEvent::Processing(now),
+        ];
+        events.updated(&[task(0, batched.clone())]);
+        let mut finished = batched;
+        finished.push(TaskEvent::succeeded(TaskResult::Other));
+        events.updated(&[task(0, finished)]);
+        drop(events);
+
+        let received: Vec<_> = stream.collect().await;
+        assert_eq!(
+            received,
+            vec![
+                TaskLifecycleEvent::Registered { task_id: 0 },
+                TaskLifecycleEvent::Batched {
+                    task_id: 0,
+                    batch_id: 0
+                },
+                TaskLifecycleEvent::Processing { task_id: 0 },
+                TaskLifecycleEvent::Finished {
+                    task_id: 0,
+                    outcome: TaskOutcome::Succeeded
+                },
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_lagging_subscriber() {
+        let events = TaskEvents::default();
+        let mut stream = events.subscribe();
+
+        let failed = TaskEvent::failed(TaskError::TaskCanceled(0).into());
+        for id in 0..EVENTS_CAPACITY as TaskId + 2 {
+            events.updated(&[task(id, vec![failed.clone()])]);
+        }
+
+        // the oldest events are replaced by the number of events missed.
+        assert_eq!(stream.next().await, Some(TaskLifecycleEvent::Lagged(2)));
+        assert_eq!(
+            stream.next().await.and_then(|event| event.task_id()),
+            Some(2)
+        );
+        drop(events);
+        assert_eq!(stream.skip(EVENTS_CAPACITY - 1).next().await, None);
+    }
+}
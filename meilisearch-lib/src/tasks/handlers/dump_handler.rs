@@ -18,14 +18,18 @@ where
     async fn process_batch(&self, mut batch: Batch) -> Batch {
         match &batch.content {
             BatchContent::Dump(Task {
-                content: TaskContent::Dump { uid },
+                content:
+                    TaskContent::Dump {
+                        uid,
+                        include_pending_tasks,
+                    },
                 ..
             }) => {
-                match self.run(uid.clone()).await {
-                    Ok(_) => {
+                match self.run(uid.clone(), *include_pending_tasks).await {
+                    Ok(throttle) => {
                         batch
                             .content
-                            .push_event(TaskEvent::succeeded(TaskResult::Other));
+                            .push_event(TaskEvent::succeeded(TaskResult::Dump { throttle }));
                     }
                     Err(e) => batch.content.push_event(TaskEvent::failed(e.into())),
                 }
@@ -43,6 +47,7 @@ mod test {
     use crate::dump::error::{DumpError, Result as DumpResult};
     use crate::index_resolver::{index_store::MockIndexStore, meta_store::MockIndexMetaStore};
     use crate::tasks::handlers::test::task_to_batch;
+    use crate::throttle::ThrottleReport;
 
     use super::*;
 
@@ -78,9 +83,9 @@ mod test {
 
                 let mocker = Mocker::default();
                 if should_accept {
-                    mocker.when::<String, DumpResult<()>>("run")
+                    mocker.when::<(String, bool), DumpResult<ThrottleReport>>("run")
                     .once()
-                    .then(|_| Ok(()));
+                    .then(|_| Ok(ThrottleReport::default()));
                 }
 
                 let dump_handler = DumpHandler::<MockIndexMetaStore, MockIndexStore>::mock(mocker);
@@ -109,7 +114,7 @@ mod test {
 
                 let mocker = Mocker::default();
                 if should_accept {
-                    mocker.when::<String, DumpResult<()>>("run")
+                    mocker.when::<(String, bool), DumpResult<ThrottleReport>>("run")
                     .once()
                     .then(|_| Err(DumpError::Internal("error".into())));
                 }
@@ -1,7 +1,7 @@
 use crate::index_resolver::IndexResolver;
 use crate::index_resolver::{index_store::IndexStore, meta_store::IndexMetaStore};
 use crate::tasks::batch::{Batch, BatchContent};
-use crate::tasks::task::TaskEvent;
+use crate::tasks::task::{Task, TaskContent, TaskEvent};
 use crate::tasks::BatchHandler;
 
 #[async_trait::async_trait]
@@ -13,7 +13,9 @@ where
     fn accept(&self, batch: &Batch) -> bool {
         matches!(
             batch.content,
-            BatchContent::DocumentsAdditionBatch(_) | BatchContent::IndexUpdate(_)
+            BatchContent::DocumentsAdditionBatch(_)
+                | BatchContent::DocumentDeletionBatch(_)
+                | BatchContent::IndexUpdate(_)
         )
     }
 
@@ -24,6 +26,11 @@ where
                     .process_document_addition_batch(std::mem::take(tasks))
                     .await;
             }
+            BatchContent::DocumentDeletionBatch(ref mut tasks) => {
+                *tasks = self
+                    .process_document_deletion_batch(std::mem::take(tasks))
+                    .await;
+            }
             BatchContent::IndexUpdate(ref mut task) => match self.process_task(task).await {
                 Ok(success) => task.events.push(TaskEvent::succeeded(success)),
                 Err(err) => task.events.push(TaskEvent::failed(err.into())),
@@ -35,29 +42,81 @@ where
     }
 
     async fn finish(&self, batch: &Batch) {
-        if let BatchContent::DocumentsAdditionBatch(ref tasks) = batch.content {
-            for task in tasks {
-                if let Some(content_uuid) = task.get_content_uuid() {
-                    if let Err(e) = self.file_store.delete(content_uuid).await {
-                        log::error!("error deleting update file: {}", e);
+        // the ids of the added documents are read from the update files, before they are deleted.
+        if let Err(e) = self.record_changes(batch).await {
+            log::error!(
+                "could not record the changes of batch {:?}: {}",
+                batch.id,
+                e
+            );
+        }
+
+        match batch.content {
+            BatchContent::DocumentsAdditionBatch(ref tasks) => {
+                for task in tasks {
+                    // the update file of the chunk that was just indexed isn't needed anymore,
+                    // even if the other chunks of the task are not indexed yet.
+                    for content_uuid in task.batch_content_uuids() {
+                        if let Err(e) = self.file_store.delete(content_uuid).await {
+                            log::error!("error deleting update file: {}", e);
+                        }
+                        self.pending_payloads.release(content_uuid);
                     }
                 }
             }
+            BatchContent::DocumentDeletionBatch(ref tasks) if self.compaction.is_enabled() => {
+                // The tasks of the batch target the same index, and succeed or fail together.
+                if let Some(Task {
+                    content: TaskContent::DocumentDeletion { ref index_uid, .. },
+                    ref events,
+                    ..
+                }) = tasks.first()
+                {
+                    if matches!(events.last(), Some(TaskEvent::Succeeded { .. })) {
+                        self.schedule_compaction_if_needed(index_uid).await;
+                    }
+                }
+            }
+            BatchContent::IndexUpdate(ref task) => {
+                let succeeded = matches!(task.events.last(), Some(TaskEvent::Succeeded { .. }));
+                match task.content {
+                    // A settings update may have reindexed the whole index, and evicted it from
+                    // the page cache. A rebuilt index is a new index, that was never warmed up.
+                    TaskContent::SettingsUpdate { ref index_uid, .. }
+                    | TaskContent::IndexRebuild { ref index_uid }
+                        if succeeded =>
+                    {
+                        self.warmup.schedule(index_uid.to_string());
+                    }
+                    TaskContent::DocumentsCompaction { ref index_uid } => {
+                        self.compaction.done(index_uid);
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::compaction::CompactionHandle;
+    use crate::index::{error::Result as IndexResult, Index, Settings};
+    use crate::index_resolver::meta_store::IndexMeta;
     use crate::index_resolver::{index_store::MockIndexStore, meta_store::MockIndexMetaStore};
+    use crate::tasks::task::TaskResult;
     use crate::tasks::{
         handlers::test::task_to_batch,
-        task::{Task, TaskContent},
+        task::{DocumentDeletion, Task, TaskContent},
     };
     use crate::update_file_store::{Result as FileStoreResult, UpdateFileStore};
+    use crate::warmup::WarmupHandle;
     use crate::IndexUid;
 
     use super::*;
+    use futures::future::ok;
+    use meilisearch_error::{Code, ResponseError};
     use milli::update::IndexDocumentsMethod;
     use nelson::Mocker;
     use proptest::prelude::*;
@@ -78,8 +137,10 @@ mod test {
 
             match batch.content {
                 BatchContent::DocumentsAdditionBatch(_)
+                    | BatchContent::DocumentDeletionBatch(_)
                     | BatchContent::IndexUpdate(_) => assert!(index_resolver.accept(&batch)),
                 BatchContent::Dump(_)
+                    | BatchContent::TaskStoreCompaction(_)
                     | BatchContent::Snapshot(_)
                     | BatchContent::Empty => assert!(!index_resolver.accept(&batch)),
             }
@@ -104,15 +165,24 @@ mod test {
 
         let task = Task {
             id: 1,
+            index_uuid: None,
             content: TaskContent::DocumentAddition {
                 content_uuid,
                 merge_strategy: IndexDocumentsMethod::ReplaceDocuments,
                 primary_key: None,
                 documents_count: 100,
                 allow_index_creation: true,
+                reject_duplicates: false,
+                chunks: Vec::new(),
+                return_diff: None,
+                content_digests: Default::default(),
+                sanitized_characters: None,
+                warnings: None,
                 index_uid: IndexUid::new_unchecked("test"),
             },
             events: Vec::new(),
+            origin: None,
+            engine_version: None,
         };
 
         let batch = task_to_batch(task);
@@ -120,6 +190,111 @@ mod test {
         index_resolver.finish(&batch).await;
     }
 
+    #[actix_rt::test]
+    async fn finisher_schedules_warmup_on_settings_update() {
+        let index_store = MockIndexStore::new();
+        let meta_store = MockIndexMetaStore::new();
+        let update_file_store = UpdateFileStore::mock(Mocker::default());
+        let mut index_resolver = IndexResolver::new(meta_store, index_store, update_file_store);
+        let (warmup, mut receiver) = WarmupHandle::new(false);
+        index_resolver.set_warmup(warmup);
+
+        let mut task = Task {
+            id: 1,
+            index_uuid: None,
+            content: TaskContent::SettingsUpdate {
+                index_uid: IndexUid::new_unchecked("test"),
+                settings: Settings::default(),
+                is_deletion: false,
+                allow_index_creation: true,
+                scheduled_at: None,
+                warnings: Vec::new(),
+            },
+            events: vec![TaskEvent::failed(ResponseError::from_msg(
+                "error".to_string(),
+                Code::Internal,
+            ))],
+            origin: None,
+            engine_version: None,
+        };
+
+        index_resolver.finish(&task_to_batch(task.clone())).await;
+        assert!(receiver.try_recv().is_err());
+
+        task.events = vec![TaskEvent::succeeded(TaskResult::Other)];
+        index_resolver.finish(&task_to_batch(task)).await;
+        assert_eq!(receiver.try_recv().unwrap(), "test");
+    }
+
+    #[actix_rt::test]
+    async fn finisher_schedules_compaction_after_deletion() {
+        let index_uuid = Uuid::new_v4();
+        let mut meta_store = MockIndexMetaStore::new();
+        meta_store.expect_get().returning(move |uid| {
+            Box::pin(ok((
+                uid,
+                Some(IndexMeta {
+                    uuid: index_uuid,
+                    creation_task_id: 0,
+                    name: None,
+                    namespace: None,
+                    former_uuids: Vec::new(),
+                    templates: Vec::new(),
+                    skip_templates: false,
+                    closed: false,
+                }),
+            )))
+        });
+        let mut index_store = MockIndexStore::new();
+        index_store.expect_get().returning(|_| {
+            let mocker = Mocker::default();
+            mocker
+                .when::<(), IndexResult<f64>>("soft_deleted_ratio")
+                .then(|_| Ok(0.5));
+            Box::pin(ok(Some(Index::mock(mocker))))
+        });
+        let update_file_store = UpdateFileStore::mock(Mocker::default());
+        let mut index_resolver = IndexResolver::new(meta_store, index_store, update_file_store);
+        let (compaction, mut receiver) = CompactionHandle::new(0.1);
+        index_resolver.set_compaction(compaction);
+
+        let task = Task {
+            id: 1,
+            index_uuid: None,
+            content: TaskContent::DocumentDeletion {
+                index_uid: IndexUid::new_unchecked("test"),
+                deletion: DocumentDeletion::Ids(vec!["1".to_string()]),
+            },
+            events: vec![TaskEvent::succeeded(TaskResult::DocumentDeletion {
+                deleted_documents: 1,
+            })],
+            origin: None,
+            engine_version: None,
+        };
+
+        // a single compaction is registered until the previous one is done.
+        index_resolver.finish(&task_to_batch(task.clone())).await;
+        index_resolver.finish(&task_to_batch(task.clone())).await;
+        assert_eq!(receiver.try_recv().unwrap().as_str(), "test");
+        assert!(receiver.try_recv().is_err());
+
+        let compaction = Task {
+            id: 2,
+            index_uuid: None,
+            content: TaskContent::DocumentsCompaction {
+                index_uid: IndexUid::new_unchecked("test"),
+            },
+            events: vec![TaskEvent::succeeded(TaskResult::DocumentDeletion {
+                deleted_documents: 1,
+            })],
+            origin: None,
+            engine_version: None,
+        };
+        index_resolver.finish(&task_to_batch(compaction)).await;
+        index_resolver.finish(&task_to_batch(task)).await;
+        assert_eq!(receiver.try_recv().unwrap().as_str(), "test");
+    }
+
     #[actix_rt::test]
     #[should_panic]
     async fn panic_when_passed_unsupported_batch() {
@@ -131,10 +306,14 @@ mod test {
 
         let task = Task {
             id: 1,
+            index_uuid: None,
             content: TaskContent::Dump {
                 uid: String::from("hello"),
+                include_pending_tasks: false,
             },
             events: Vec::new(),
+            origin: None,
+            engine_version: None,
         };
 
         let batch = task_to_batch(task);
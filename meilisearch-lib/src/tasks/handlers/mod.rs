@@ -2,6 +2,7 @@ pub mod dump_handler;
 pub mod empty_handler;
 mod index_resolver_handler;
 pub mod snapshot_handler;
+pub mod task_store_compaction_handler;
 
 #[cfg(test)]
 mod test {
@@ -9,7 +10,7 @@ mod test {
 
     use crate::tasks::{
         batch::{Batch, BatchContent},
-        task::{Task, TaskContent},
+        task::{DocumentDeletion, Task, TaskContent},
     };
 
     pub fn task_to_batch(task: Task) -> Batch {
@@ -17,18 +18,32 @@ mod test {
             TaskContent::DocumentAddition { .. } => {
                 BatchContent::DocumentsAdditionBatch(vec![task])
             }
+            TaskContent::DocumentDeletion {
+                deletion: DocumentDeletion::Ids(_),
+                ..
+            } => BatchContent::DocumentDeletionBatch(vec![task]),
             TaskContent::DocumentDeletion { .. }
             | TaskContent::SettingsUpdate { .. }
             | TaskContent::IndexDeletion { .. }
             | TaskContent::IndexCreation { .. }
-            | TaskContent::IndexUpdate { .. } => BatchContent::IndexUpdate(task),
+            | TaskContent::IndexUpdate { .. }
+            | TaskContent::DocumentAdditionFromUrl { .. }
+            | TaskContent::DocumentsCompaction { .. }
+            | TaskContent::IndexCheck { .. }
+            | TaskContent::StatsRepair { .. }
+            | TaskContent::IndexRebuild { .. }
+            | TaskContent::SettingsPreview { .. }
+            | TaskContent::DocumentEdition { .. }
+            | TaskContent::NamespaceDeletion { .. } => BatchContent::IndexUpdate(task),
             TaskContent::Dump { .. } => BatchContent::Dump(task),
+            TaskContent::TaskStoreCompaction => BatchContent::TaskStoreCompaction(task),
         };
 
         Batch {
             id: Some(1),
             created_at: OffsetDateTime::now_utc(),
             content,
+            stop_reason: None,
         }
     }
 }
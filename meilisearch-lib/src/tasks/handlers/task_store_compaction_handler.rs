@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use crate::index_resolver::meta_store::HeedMetaStore;
+use crate::meta_env;
+use crate::tasks::batch::{Batch, BatchContent};
+use crate::tasks::error::TaskError;
+use crate::tasks::task::{TaskEvent, TaskResult};
+use crate::tasks::{BatchHandler, TaskStore};
+
+/// Replaces the meta environment, which holds the tasks, with a compacted copy of itself.
+pub struct TaskStoreCompactionHandler {
+    db_path: PathBuf,
+    env_size: usize,
+    task_store: TaskStore,
+    meta_store: HeedMetaStore,
+}
+
+impl TaskStoreCompactionHandler {
+    pub fn new(
+        db_path: PathBuf,
+        env_size: usize,
+        task_store: TaskStore,
+        meta_store: HeedMetaStore,
+    ) -> Self {
+        Self {
+            db_path,
+            env_size,
+            task_store,
+            meta_store,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BatchHandler for TaskStoreCompactionHandler {
+    fn accept(&self, batch: &Batch) -> bool {
+        matches!(batch.content, BatchContent::TaskStoreCompaction(_))
+    }
+
+    async fn process_batch(&self, mut batch: Batch) -> Batch {
+        let db_path = self.db_path.clone();
+        let env_size = self.env_size;
+        let task_store = self.task_store.clone();
+        let meta_store = self.meta_store.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            meta_env::compact(&db_path, env_size, &task_store, &meta_store)
+        })
+        .await;
+
+        match result.map_err(TaskError::from).and_then(|result| result) {
+            Ok(reclaimed_bytes) => {
+                log::info!(
+                    "Compacted the tasks database, {} bytes were reclaimed.",
+                    reclaimed_bytes
+                );
+                batch
+                    .content
+                    .push_event(TaskEvent::succeeded(TaskResult::TaskStoreCompaction {
+                        reclaimed_bytes,
+                    }));
+            }
+            Err(e) => batch.content.push_event(TaskEvent::failed(e.into())),
+        }
+
+        batch
+    }
+
+    async fn finish(&self, _: &Batch) {}
+}
@@ -0,0 +1,97 @@
+//! Idempotency keys of the task registrations.
+//!
+//! A client retrying a request that registers a task can't tell whether its first attempt was
+//! registered. It can send an idempotency key along with its requests: the task registered with a
+//! key is remembered for the idempotency window, and the requests using the same key within this
+//! window get this task back instead of registering a new one. The keys are scoped by API key, so
+//! that two tenants using the same key never see each other's tasks.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::error::{Result, TaskError};
+use super::task::{Task, TaskId};
+
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    /// The uid of the API key that sent the idempotency key, `None` for the master key or when
+    /// no master key is set.
+    scope: Option<Uuid>,
+    key: String,
+}
+
+impl IdempotencyKey {
+    /// Returns an error unless `key` is made of 1 to 256 visible ascii characters.
+    pub fn new(scope: Option<Uuid>, key: String) -> Result<Self> {
+        let is_valid = !key.is_empty()
+            && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH
+            && key.bytes().all(|b| b.is_ascii_graphic());
+
+        if is_valid {
+            Ok(Self { scope, key })
+        } else {
+            Err(TaskError::InvalidIdempotencyKey(key))
+        }
+    }
+
+    /// The key under which the task registered with this idempotency key is stored.
+    pub(crate) fn storage_key(&self) -> String {
+        match self.scope {
+            Some(uid) => format!("{}/{}", uid, self.key),
+            None => format!("*/{}", self.key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IdempotencyEntry {
+    pub task_id: TaskId,
+    #[serde(with = "time::serde::rfc3339")]
+    pub registered_at: OffsetDateTime,
+}
+
+impl IdempotencyEntry {
+    pub fn is_expired(&self, window: Duration, now: OffsetDateTime) -> bool {
+        self.registered_at + window <= now
+    }
+}
+
+/// The outcome of the registration of a task with an idempotency key.
+#[derive(Debug)]
+pub enum Registration {
+    /// The task was registered.
+    Created(Task),
+    /// The idempotency key was already used within the window, this is the task registered with
+    /// it.
+    Replayed(Task),
+}
+
+impl Registration {
+    pub fn into_task(self) -> Task {
+        match self {
+            Self::Created(task) | Self::Replayed(task) => task,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_keys_are_scoped() {
+        let uid = Uuid::new_v4();
+        let scoped = IdempotencyKey::new(Some(uid), "retry-1".to_string()).unwrap();
+        let master = IdempotencyKey::new(None, "retry-1".to_string()).unwrap();
+        assert_ne!(scoped.storage_key(), master.storage_key());
+
+        assert!(IdempotencyKey::new(None, String::new()).is_err());
+        assert!(IdempotencyKey::new(None, "with space".to_string()).is_err());
+        assert!(IdempotencyKey::new(None, "a".repeat(257)).is_err());
+    }
+}
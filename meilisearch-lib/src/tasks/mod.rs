@@ -1,9 +1,14 @@
 use async_trait::async_trait;
 
+pub use events::{TaskEventStream, TaskLifecycleEvent, TaskOutcome};
 pub use handlers::empty_handler::EmptyBatchHandler;
 pub use handlers::snapshot_handler::SnapshotHandler;
-pub use scheduler::Scheduler;
-pub use task_store::TaskFilter;
+pub use handlers::task_store_compaction_handler::TaskStoreCompactionHandler;
+pub use pending_creations::PendingCreations;
+pub use scheduler::{
+    Job, QueueSnapshot, QueuedJob, QueuedTask, QueuedTaskList, Scheduler, SchedulerLag,
+};
+pub use task_store::{TaskFilter, TaskOrder};
 
 #[cfg(test)]
 pub use task_store::test::MockTaskStore as TaskStore;
@@ -14,12 +19,21 @@ use batch::Batch;
 use error::Result;
 
 pub mod batch;
+#[cfg(all(test, feature = "scheduler-bench"))]
+mod bench;
+mod debounce;
 pub mod error;
+pub mod events;
 mod handlers;
+pub mod idempotency;
+pub(crate) mod notifier;
+mod pending_creations;
+pub mod progress;
 mod scheduler;
 pub mod task;
 mod task_store;
 pub mod update_loop;
+pub mod waiter;
 
 #[cfg_attr(test, mockall::automock(type Error=test::DebugError;))]
 #[async_trait]
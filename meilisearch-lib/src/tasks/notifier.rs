@@ -0,0 +1,140 @@
+//! Notifications of the update loop.
+//!
+//! Every notification bumps a sequence number, and the update loop wakes up whenever the sequence
+//! number is ahead of the last one it handled. Several notifications sent while the loop is busy
+//! are coalesced into a single wake up, but a notification sent at any point after the loop read
+//! the sequence number is always seen by its next wait, whatever happens in between.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+struct Inner {
+    sequence: AtomicU64,
+    closed: AtomicBool,
+    notify: Notify,
+}
+
+/// Returns a notifier and the receiver of its notifications.
+pub fn channel() -> (Notifier, NotificationReceiver) {
+    let inner = Arc::new(Inner {
+        sequence: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+        notify: Notify::new(),
+    });
+    let receiver = NotificationReceiver {
+        inner: inner.clone(),
+        last_seen: 0,
+    };
+
+    (Notifier { inner }, receiver)
+}
+
+impl Inner {
+    fn notify(&self) -> u64 {
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+        // A permit is stored when the receiver is not waiting, so that its next wait returns
+        // immediately.
+        self.notify.notify_one();
+        sequence
+    }
+}
+
+/// Notifies the update loop that there may be work to do. The receiver stops waiting once the
+/// notifier is dropped.
+pub struct Notifier {
+    inner: Arc<Inner>,
+}
+
+impl Notifier {
+    /// Sends a notification, and returns its sequence number.
+    pub fn notify(&self) -> u64 {
+        self.inner.notify()
+    }
+
+    /// A sender of notifications for the components outside of the scheduler. Unlike the
+    /// notifier, dropping it doesn't stop the receiver.
+    pub fn sender(&self) -> NotificationSender {
+        NotificationSender {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NotificationSender {
+    inner: Arc<Inner>,
+}
+
+impl NotificationSender {
+    pub fn notify(&self) -> u64 {
+        self.inner.notify()
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_one();
+    }
+}
+
+pub struct NotificationReceiver {
+    inner: Arc<Inner>,
+    /// The sequence number of the last notification returned by `changed`.
+    last_seen: u64,
+}
+
+impl NotificationReceiver {
+    /// Waits for a notification sent after the last one returned, and returns its sequence
+    /// number. Returns `None` once the notifier is dropped and every notification was handled.
+    pub async fn changed(&mut self) -> Option<u64> {
+        loop {
+            let sequence = self.inner.sequence.load(Ordering::SeqCst);
+            if sequence > self.last_seen {
+                self.last_seen = sequence;
+                return Some(sequence);
+            }
+
+            if self.inner.closed.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            self.inner.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn test_notifications_are_never_missed() {
+        let (notifier, mut receiver) = channel();
+
+        // the notifications sent while the receiver is busy are coalesced.
+        notifier.notify();
+        notifier.notify();
+        assert_eq!(receiver.changed().await, Some(2));
+
+        // a notification sent right after the receiver started waiting wakes it up.
+        let wait = tokio::spawn(async move {
+            let sequence = receiver.changed().await;
+            (receiver, sequence)
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        notifier.notify();
+        let (mut receiver, sequence) = wait.await.unwrap();
+        assert_eq!(sequence, Some(3));
+
+        // the notifications sent before the notifier was dropped are still handled.
+        notifier.notify();
+        drop(notifier);
+        assert_eq!(receiver.changed().await, Some(4));
+        assert_eq!(receiver.changed().await, None);
+    }
+}
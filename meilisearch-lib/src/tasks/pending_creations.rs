@@ -0,0 +1,139 @@
+//! The indexes that enqueued tasks create.
+//!
+//! A request on a missing index that a pending task creates, like a search sent right after the
+//! creation of its index, is told to wait for this task instead of failing as if the uid was
+//! mistyped. The pending tasks creating an index are tracked when they are registered, and
+//! forgotten once they are finished, so that this lookup doesn't read the task store.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use super::task::{Task, TaskContent, TaskId};
+
+#[derive(Clone, Default)]
+pub struct PendingCreations {
+    /// The ids of the pending tasks creating each index.
+    tasks: Arc<RwLock<HashMap<String, BTreeSet<TaskId>>>>,
+}
+
+impl PendingCreations {
+    /// Tracks `task` if it is pending and creates its index when it doesn't exist.
+    pub fn register(&self, task: &Task) {
+        if task.is_finished() || !creates_index(&task.content) {
+            return;
+        }
+        if let Some(index_uid) = task.index_uid() {
+            self.tasks
+                .write()
+                .entry(index_uid.to_string())
+                .or_default()
+                .insert(task.id);
+        }
+    }
+
+    /// Forgets the finished tasks of `tasks`.
+    pub fn finished(&self, tasks: &[Task]) {
+        let mut pending = self.tasks.write();
+        for task in tasks.iter().filter(|task| task.is_finished()) {
+            if let Some(index_uid) = task.index_uid() {
+                if let Some(ids) = pending.get_mut(index_uid) {
+                    ids.remove(&task.id);
+                    if ids.is_empty() {
+                        pending.remove(index_uid);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The first pending task creating `index_uid`, if any.
+    pub fn get(&self, index_uid: &str) -> Option<TaskId> {
+        self.tasks
+            .read()
+            .get(index_uid)
+            .and_then(|ids| ids.iter().next().copied())
+    }
+}
+
+/// Whether the task creates its index when it doesn't exist.
+fn creates_index(content: &TaskContent) -> bool {
+    match content {
+        TaskContent::IndexCreation { .. } => true,
+        TaskContent::DocumentAddition {
+            allow_index_creation,
+            ..
+        }
+        | TaskContent::DocumentAdditionFromUrl {
+            allow_index_creation,
+            ..
+        } => *allow_index_creation,
+        TaskContent::SettingsUpdate {
+            allow_index_creation,
+            is_deletion,
+            ..
+        } => *allow_index_creation && !is_deletion,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::index_resolver::IndexUid;
+    use crate::tasks::task::{TaskEvent, TaskResult};
+
+    fn task(id: TaskId, content: TaskContent) -> Task {
+        Task {
+            id,
+            index_uuid: None,
+            content,
+            events: vec![TaskEvent::Created(OffsetDateTime::now_utc())],
+            origin: None,
+            engine_version: None,
+        }
+    }
+
+    fn creation(id: TaskId, index_uid: &str) -> Task {
+        let content = TaskContent::IndexCreation {
+            index_uid: IndexUid::new_unchecked(index_uid),
+            primary_key: None,
+            name: None,
+            namespace: None,
+            if_not_exists: false,
+            skip_templates: false,
+        };
+        task(id, content)
+    }
+
+    #[test]
+    fn test_pending_creations() {
+        let pending = PendingCreations::default();
+        pending.register(&creation(1, "movies"));
+        pending.register(&creation(2, "movies"));
+        pending.register(&task(
+            3,
+            TaskContent::IndexDeletion {
+                index_uid: IndexUid::new_unchecked("books"),
+            },
+        ));
+        assert_eq!(pending.get("movies"), Some(1));
+        assert_eq!(pending.get("books"), None);
+
+        // the tasks that are still pending are kept.
+        pending.finished(&[creation(2, "movies")]);
+        assert_eq!(pending.get("movies"), Some(1));
+
+        let mut finished = creation(1, "movies");
+        finished
+            .events
+            .push(TaskEvent::succeeded(TaskResult::IndexCreation {
+                already_existed: false,
+            }));
+        pending.finished(&[finished]);
+        assert_eq!(pending.get("movies"), Some(2));
+    }
+}
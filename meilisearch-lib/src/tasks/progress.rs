@@ -0,0 +1,219 @@
+//! Progress of the tasks being processed.
+//!
+//! Indexing a large payload can keep a task processing for a long time. milli reports the steps of
+//! its indexing pipeline through a callback, which we turn into the progress of the tasks of the
+//! batch. The progress is only kept in memory, for as long as the batch is processed: it is never
+//! written to the task store, and the callback only takes the lock at the start of a phase, or
+//! when the last update is older than `REPORT_INTERVAL`, whatever the number of steps milli
+//! reports.
+//!
+//! Each report is also a heartbeat of the batch being processed: a batch whose heartbeat stopped
+//! for long enough is considered stuck, and can be reset by an administrator.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use milli::update::UpdateIndexingStep;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+
+use super::task::TaskId;
+
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum IndexingPhase {
+    /// The documents are read from the payloads and assigned their internal ids.
+    PreparingDocuments,
+    /// The documents are merged with the documents already in the index.
+    MergingDocuments,
+    /// The words, facets and positions of the documents are extracted and written.
+    IndexingDocuments,
+    /// The extracted data is merged in the databases of the index.
+    WritingDatabases,
+    /// The documents matching the filter of a document edition are edited, and indexed again,
+    /// chunk after chunk.
+    EditingDocuments,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskProgress {
+    pub phase: IndexingPhase,
+    /// Only known when the phase has a known amount of work.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f32>,
+}
+
+fn percent(seen: usize, total: usize) -> Option<f32> {
+    (total > 0).then(|| (seen.min(total) as f32 / total as f32 * 100.0).floor())
+}
+
+impl From<UpdateIndexingStep> for TaskProgress {
+    fn from(step: UpdateIndexingStep) -> Self {
+        match step {
+            UpdateIndexingStep::RemapDocumentAddition { .. } => Self {
+                phase: IndexingPhase::PreparingDocuments,
+                percent: None,
+            },
+            UpdateIndexingStep::ComputeIdsAndMergeDocuments {
+                documents_seen,
+                total_documents,
+            } => Self {
+                phase: IndexingPhase::MergingDocuments,
+                percent: percent(documents_seen, total_documents),
+            },
+            UpdateIndexingStep::IndexDocuments {
+                documents_seen,
+                total_documents,
+            } => Self {
+                phase: IndexingPhase::IndexingDocuments,
+                percent: percent(documents_seen, total_documents),
+            },
+            UpdateIndexingStep::MergeDataIntoFinalDatabase {
+                databases_seen,
+                total_databases,
+            } => Self {
+                phase: IndexingPhase::WritingDatabases,
+                percent: percent(databases_seen, total_databases),
+            },
+        }
+    }
+}
+
+/// The progress of the tasks being processed.
+#[derive(Clone, Default)]
+pub struct TaskProgresses {
+    progresses: Arc<RwLock<HashMap<TaskId, TaskProgress>>>,
+    /// The last time the batch being processed showed a sign of life.
+    heartbeat: Arc<Mutex<Option<Instant>>>,
+}
+
+impl TaskProgresses {
+    pub fn get(&self, id: TaskId) -> Option<TaskProgress> {
+        self.progresses.read().get(&id).cloned()
+    }
+
+    pub fn beat(&self) {
+        *self.heartbeat.lock() = Some(Instant::now());
+    }
+
+    pub fn last_beat(&self) -> Option<Instant> {
+        *self.heartbeat.lock()
+    }
+
+    /// Returns a reporter setting the progress of `tasks`, which are processed together. Their
+    /// progress is removed when the reporter is dropped.
+    pub fn reporter(&self, tasks: Vec<TaskId>) -> ProgressReporter {
+        ProgressReporter {
+            progresses: self.clone(),
+            tasks,
+            last_report: Mutex::new(None),
+        }
+    }
+
+    fn set(&self, tasks: &[TaskId], progress: TaskProgress) {
+        let mut progresses = self.progresses.write();
+        for id in tasks {
+            progresses.insert(*id, progress.clone());
+        }
+    }
+
+    fn remove(&self, tasks: &[TaskId]) {
+        let mut progresses = self.progresses.write();
+        for id in tasks {
+            progresses.remove(id);
+        }
+    }
+}
+
+pub struct ProgressReporter {
+    progresses: TaskProgresses,
+    tasks: Vec<TaskId>,
+    last_report: Mutex<Option<(IndexingPhase, Instant)>>,
+}
+
+impl ProgressReporter {
+    pub fn report(&self, step: UpdateIndexingStep) {
+        self.report_progress(TaskProgress::from(step));
+    }
+
+    /// Reports that `documents_seen` of the `total_documents` matching a document edition were
+    /// edited.
+    pub fn report_edition(&self, documents_seen: usize, total_documents: usize) {
+        self.report_progress(TaskProgress {
+            phase: IndexingPhase::EditingDocuments,
+            percent: percent(documents_seen, total_documents),
+        });
+    }
+
+    fn report_progress(&self, progress: TaskProgress) {
+        let now = Instant::now();
+
+        {
+            let mut last_report = self.last_report.lock();
+            match *last_report {
+                Some((phase, at))
+                    if phase == progress.phase && now.duration_since(at) < REPORT_INTERVAL =>
+                {
+                    return
+                }
+                _ => *last_report = Some((progress.phase, now)),
+            }
+        }
+
+        self.progresses.set(&self.tasks, progress);
+        self.progresses.beat();
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.progresses.remove(&self.tasks);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reports_are_rate_limited() {
+        let progresses = TaskProgresses::default();
+        let reporter = progresses.reporter(vec![1, 2]);
+
+        reporter.report(UpdateIndexingStep::IndexDocuments {
+            documents_seen: 1,
+            total_documents: 4,
+        });
+        let expected = TaskProgress {
+            phase: IndexingPhase::IndexingDocuments,
+            percent: Some(25.0),
+        };
+        assert_eq!(progresses.get(1), Some(expected.clone()));
+        assert_eq!(progresses.get(2), Some(expected.clone()));
+
+        // the progress within a phase is only updated once in a while.
+        reporter.report(UpdateIndexingStep::IndexDocuments {
+            documents_seen: 2,
+            total_documents: 4,
+        });
+        assert_eq!(progresses.get(1), Some(expected));
+
+        // but a new phase is always reported.
+        reporter.report(UpdateIndexingStep::MergeDataIntoFinalDatabase {
+            databases_seen: 0,
+            total_databases: 10,
+        });
+        assert_eq!(
+            progresses.get(1).map(|p| p.phase),
+            Some(IndexingPhase::WritingDatabases)
+        );
+
+        drop(reporter);
+        assert_eq!(progresses.get(1), None);
+        assert_eq!(progresses.get(2), None);
+    }
+}
@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
-use std::collections::{hash_map::Entry, BinaryHeap, HashMap, VecDeque};
+use std::collections::{hash_map::Entry, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::future::Future;
 use std::ops::{Deref, DerefMut};
 use std::slice;
 use std::sync::Arc;
@@ -7,24 +8,66 @@ use std::time::Duration;
 
 use atomic_refcell::AtomicRefCell;
 use milli::update::IndexDocumentsMethod;
+use serde::Serialize;
 use time::OffsetDateTime;
-use tokio::sync::{watch, RwLock};
+use tokio::sync::RwLock;
 
+use crate::closed_indexes::ClosedIndexes;
+use crate::index_locks::IndexLocks;
 use crate::options::SchedulerConfig;
 use crate::snapshot::SnapshotJob;
 
-use super::batch::{Batch, BatchContent};
-use super::error::Result;
-use super::task::{Task, TaskContent, TaskEvent, TaskId};
-use super::update_loop::UpdateLoop;
-use super::{BatchHandler, TaskFilter, TaskStore};
+use super::batch::{Batch, BatchContent, BatchId, BatchResult, BatchStopReason};
+use super::debounce::AdaptiveDebounce;
+use super::error::{Result, TaskError};
+use super::events::{TaskEventStream, TaskEvents};
+use super::notifier::{self, Notifier};
+use super::pending_creations::PendingCreations;
+use super::progress::{TaskProgress, TaskProgresses};
+use super::task::{DocumentDeletion, Task, TaskContent, TaskEvent, TaskId, TaskResult};
+use super::update_loop::{BatchAbort, UpdateLoop};
+use super::waiter::TaskWaiter;
+use super::{BatchHandler, TaskFilter, TaskOrder, TaskStore};
+
+/// A lag growing for this many consecutive batches is logged as a warning: the scheduler keeps
+/// preparing batches without fetching the tasks registered in the meantime.
+const LAG_GROWTH_WARNING_BATCHES: usize = 10;
 
 #[derive(Eq, Debug, Clone, Copy)]
 enum TaskType {
-    DocumentAddition { number: usize },
-    DocumentUpdate { number: usize },
+    DocumentAddition {
+        number: usize,
+    },
+    DocumentUpdate {
+        number: usize,
+    },
+    /// A document addition split into chunks. Its chunks are indexed one after the other, each in
+    /// its own batch, so it is never batched with other tasks.
+    ChunkedDocumentAddition,
+    /// A deletion of all the documents of an index.
+    DocumentClear,
+    /// A deletion of documents by id. The consecutive deletions of an index are merged into a
+    /// single deletion.
+    DocumentDeletion,
     IndexUpdate,
     Dump,
+    /// A compaction of the tasks database. It waits for the tasks of the indexes, like the dumps.
+    TaskStoreCompaction,
+}
+
+impl TaskType {
+    fn name(&self) -> &'static str {
+        match self {
+            TaskType::DocumentAddition { .. } => "documentAddition",
+            TaskType::DocumentUpdate { .. } => "documentUpdate",
+            TaskType::ChunkedDocumentAddition => "chunkedDocumentAddition",
+            TaskType::DocumentClear => "documentClear",
+            TaskType::DocumentDeletion => "documentDeletion",
+            TaskType::IndexUpdate => "indexUpdate",
+            TaskType::Dump => "dump",
+            TaskType::TaskStoreCompaction => "taskStoreCompaction",
+        }
+    }
 }
 
 /// Two tasks are equal if they have the same type.
@@ -34,14 +77,63 @@ impl PartialEq for TaskType {
             (self, other),
             (Self::DocumentAddition { .. }, Self::DocumentAddition { .. })
                 | (Self::DocumentUpdate { .. }, Self::DocumentUpdate { .. })
+                | (Self::DocumentDeletion, Self::DocumentDeletion)
         )
     }
 }
 
+impl From<&TaskContent> for TaskType {
+    fn from(content: &TaskContent) -> Self {
+        match content {
+            TaskContent::DocumentAddition { chunks, .. } if !chunks.is_empty() => {
+                TaskType::ChunkedDocumentAddition
+            }
+            TaskContent::DocumentAddition {
+                documents_count,
+                merge_strategy: IndexDocumentsMethod::ReplaceDocuments,
+                ..
+            } => TaskType::DocumentAddition {
+                number: *documents_count,
+            },
+            TaskContent::DocumentAddition {
+                documents_count,
+                merge_strategy: IndexDocumentsMethod::UpdateDocuments,
+                ..
+            } => TaskType::DocumentUpdate {
+                number: *documents_count,
+            },
+            TaskContent::Dump { .. } => TaskType::Dump,
+            TaskContent::TaskStoreCompaction => TaskType::TaskStoreCompaction,
+            TaskContent::DocumentDeletion {
+                deletion: DocumentDeletion::Clear,
+                ..
+            } => TaskType::DocumentClear,
+            TaskContent::DocumentDeletion {
+                deletion: DocumentDeletion::Ids(_),
+                ..
+            } => TaskType::DocumentDeletion,
+            TaskContent::SettingsUpdate { .. }
+            | TaskContent::IndexDeletion { .. }
+            | TaskContent::IndexCreation { .. }
+            | TaskContent::IndexUpdate { .. }
+            | TaskContent::DocumentAdditionFromUrl { .. }
+            | TaskContent::DocumentsCompaction { .. }
+            | TaskContent::IndexCheck { .. }
+            | TaskContent::StatsRepair { .. }
+            | TaskContent::IndexRebuild { .. }
+            | TaskContent::SettingsPreview { .. }
+            | TaskContent::DocumentEdition { .. }
+            | TaskContent::NamespaceDeletion { .. } => TaskType::IndexUpdate,
+            _ => unreachable!("unhandled task type"),
+        }
+    }
+}
+
 #[derive(Eq, Debug, Clone, Copy)]
 struct PendingTask {
     kind: TaskType,
     id: TaskId,
+    enqueued_at: Option<OffsetDateTime>,
 }
 
 impl PartialEq for PendingTask {
@@ -99,20 +191,32 @@ impl PartialEq for TaskList {
 
 impl Eq for TaskList {}
 
+/// The queue is a max-heap, so the greatest task list is scheduled first. Within the same kind of
+/// list, this is the one with the lowest pending task id, and an empty list is lower than any other
+/// so that it is never scheduled before a list that has work to do. The dumps and the namespace
+/// deletions are ordered together, since they both wait for the tasks of the indexes.
 impl Ord for TaskList {
     fn cmp(&self, other: &Self) -> Ordering {
+        use TaskListIdentifier::*;
+
         match (&self.id, &other.id) {
-            (TaskListIdentifier::Index(_), TaskListIdentifier::Index(_)) => {
-                match (self.peek(), other.peek()) {
-                    (None, None) => Ordering::Equal,
-                    (None, Some(_)) => Ordering::Less,
-                    (Some(_), None) => Ordering::Greater,
-                    (Some(lhs), Some(rhs)) => lhs.cmp(rhs),
-                }
-            }
-            (TaskListIdentifier::Index(_), TaskListIdentifier::Dump) => Ordering::Less,
-            (TaskListIdentifier::Dump, TaskListIdentifier::Index(_)) => Ordering::Greater,
-            (TaskListIdentifier::Dump, TaskListIdentifier::Dump) => {
+            (Index(_), Index(_))
+            | (IndexCheck(_), IndexCheck(_))
+            | (Namespace(_), Namespace(_))
+            | (Namespace(_), Dump)
+            | (Dump, Namespace(_)) => match (self.peek(), other.peek()) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Less,
+                (Some(_), None) => Ordering::Greater,
+                (Some(lhs), Some(rhs)) => lhs.cmp(rhs),
+            },
+            // the integrity checks and the stats repairs are only processed once there is nothing
+            // else to do.
+            (IndexCheck(_), _) => Ordering::Less,
+            (_, IndexCheck(_)) => Ordering::Greater,
+            (Index(_), Dump | Namespace(_)) => Ordering::Less,
+            (Dump | Namespace(_), Index(_)) => Ordering::Greater,
+            (Dump, Dump) => {
                 unreachable!("There should be only one Dump task list")
             }
         }
@@ -128,9 +232,26 @@ impl PartialOrd for TaskList {
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 enum TaskListIdentifier {
     Index(String),
+    /// The integrity checks and the stats repairs of an index. They don't modify the documents of
+    /// the index, and are kept apart from its other tasks so that they never delay them.
+    IndexCheck(String),
+    /// The deletions of a namespace. Like the dumps, they wait for the tasks of the indexes.
+    Namespace(String),
+    /// The dumps and the compactions of the tasks database.
     Dump,
 }
 
+impl std::fmt::Display for TaskListIdentifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskListIdentifier::Index(uid) => write!(f, "index:{}", uid),
+            TaskListIdentifier::IndexCheck(uid) => write!(f, "indexCheck:{}", uid),
+            TaskListIdentifier::Namespace(namespace) => write!(f, "namespace:{}", namespace),
+            TaskListIdentifier::Dump => write!(f, "dump"),
+        }
+    }
+}
+
 impl From<&Task> for TaskListIdentifier {
     fn from(task: &Task) -> Self {
         match &task.content {
@@ -139,51 +260,67 @@ impl From<&Task> for TaskListIdentifier {
             | TaskContent::SettingsUpdate { index_uid, .. }
             | TaskContent::IndexDeletion { index_uid }
             | TaskContent::IndexCreation { index_uid, .. }
-            | TaskContent::IndexUpdate { index_uid, .. } => {
+            | TaskContent::IndexUpdate { index_uid, .. }
+            | TaskContent::DocumentAdditionFromUrl { index_uid, .. }
+            | TaskContent::DocumentsCompaction { index_uid }
+            | TaskContent::IndexRebuild { index_uid }
+            | TaskContent::SettingsPreview { index_uid, .. }
+            | TaskContent::DocumentEdition { index_uid, .. } => {
                 TaskListIdentifier::Index(index_uid.as_str().to_string())
             }
-            TaskContent::Dump { .. } => TaskListIdentifier::Dump,
+            TaskContent::IndexCheck { index_uid, .. } | TaskContent::StatsRepair { index_uid } => {
+                TaskListIdentifier::IndexCheck(index_uid.as_str().to_string())
+            }
+            TaskContent::Dump { .. } | TaskContent::TaskStoreCompaction => TaskListIdentifier::Dump,
+            TaskContent::NamespaceDeletion { namespace } => {
+                TaskListIdentifier::Namespace(namespace.clone())
+            }
         }
     }
 }
 
 #[derive(Default)]
-struct TaskQueue {
+pub(super) struct TaskQueue {
     /// Maps index uids to their TaskList, for quick access
     index_tasks: HashMap<TaskListIdentifier, Arc<AtomicRefCell<TaskList>>>,
     /// A queue that orders TaskList by the priority of their fist update
     queue: BinaryHeap<Arc<AtomicRefCell<TaskList>>>,
+    /// The indexes whose task lists are left in the queue until they are opened again.
+    closed: ClosedIndexes,
+    /// The indexes whose task lists are left in the queue until they are unlocked.
+    locks: IndexLocks,
 }
 
 impl TaskQueue {
-    fn insert(&mut self, task: Task) {
+    fn new(closed: ClosedIndexes, locks: IndexLocks) -> Self {
+        Self {
+            closed,
+            locks,
+            ..Default::default()
+        }
+    }
+
+    /// Whether `list` belongs to an index that is closed, or locked by an operation needing
+    /// exclusive access to it.
+    fn is_deferred(&self, list: &TaskList) -> bool {
+        match &list.id {
+            TaskListIdentifier::Index(uid) | TaskListIdentifier::IndexCheck(uid) => {
+                self.closed.contains(uid) || self.locks.is_locked(uid)
+            }
+            TaskListIdentifier::Namespace(_) | TaskListIdentifier::Dump => false,
+        }
+    }
+
+    pub(super) fn insert(&mut self, task: Task) {
         let id = task.id;
         let uid = TaskListIdentifier::from(&task);
 
-        let kind = match task.content {
-            TaskContent::DocumentAddition {
-                documents_count,
-                merge_strategy: IndexDocumentsMethod::ReplaceDocuments,
-                ..
-            } => TaskType::DocumentAddition {
-                number: documents_count,
-            },
-            TaskContent::DocumentAddition {
-                documents_count,
-                merge_strategy: IndexDocumentsMethod::UpdateDocuments,
-                ..
-            } => TaskType::DocumentUpdate {
-                number: documents_count,
-            },
-            TaskContent::Dump { .. } => TaskType::Dump,
-            TaskContent::DocumentDeletion { .. }
-            | TaskContent::SettingsUpdate { .. }
-            | TaskContent::IndexDeletion { .. }
-            | TaskContent::IndexCreation { .. }
-            | TaskContent::IndexUpdate { .. } => TaskType::IndexUpdate,
-            _ => unreachable!("unhandled task type"),
+        let kind = TaskType::from(&task.content);
+        let task = PendingTask {
+            kind,
+            id,
+            enqueued_at: task.enqueued_at(),
         };
-        let task = PendingTask { kind, id };
 
         match self.index_tasks.entry(uid) {
             Entry::Occupied(entry) => {
@@ -193,7 +330,8 @@ impl TaskQueue {
                 let mut list = entry.get().borrow_mut();
 
                 // We only need the first element to be lower than the one we want to
-                // insert to preserve the order in the queue.
+                // insert to preserve the order in the queue: the list is ordered by its head, and
+                // it must not change while the list is in the queue.
                 assert!(list.peek().map(|old_id| id >= old_id.id).unwrap_or(true));
 
                 list.push(task);
@@ -208,10 +346,39 @@ impl TaskQueue {
         }
     }
 
+    /// Puts in the queue a task that was already batched, or that was delayed, ahead of the other
+    /// tasks of its list since its id is lower than theirs.
+    fn requeue(&mut self, task: Task) {
+        let uid = TaskListIdentifier::from(&task);
+        match self.index_tasks.get(&uid) {
+            Some(list) => {
+                list.borrow_mut().push(PendingTask {
+                    kind: TaskType::from(&task.content),
+                    id: task.id,
+                    enqueued_at: task.enqueued_at(),
+                });
+                // the head of the list changed.
+                self.reorder();
+            }
+            None => self.insert(task),
+        }
+    }
+
     /// Passes a context with a view to the task list of the next index to schedule. It is
-    /// guaranteed that the first id from task list will be the lowest pending task id.
+    /// guaranteed that the first id from task list will be the lowest pending task id. The lists
+    /// of the closed and locked indexes are skipped, and keep their position in the queue.
     fn head_mut<R>(&mut self, mut f: impl FnMut(&mut TaskList) -> R) -> Option<R> {
-        let head = self.queue.pop()?;
+        let mut skipped = Vec::new();
+        let head = loop {
+            match self.queue.pop() {
+                Some(list) if self.is_deferred(&list.borrow()) => skipped.push(list),
+                Some(list) => break Some(list),
+                None => break None,
+            }
+        };
+        // the skipped lists weren't modified, so they are put back where they were.
+        self.queue.extend(skipped);
+        let head = head?;
         let result = {
             let mut ref_head = head.borrow_mut();
             f(&mut *ref_head)
@@ -226,22 +393,263 @@ impl TaskQueue {
         Some(result)
     }
 
+    /// The index of the task list the next batch is made of, if any. The lists that don't belong
+    /// to an index have no index uid.
+    fn head_index_uid(&self) -> Option<Option<String>> {
+        let head = self
+            .queue
+            .iter()
+            .filter(|list| !self.is_deferred(&list.borrow()))
+            .max()?;
+        let index_uid = match &head.borrow().id {
+            TaskListIdentifier::Index(uid) | TaskListIdentifier::IndexCheck(uid) => {
+                Some(uid.clone())
+            }
+            TaskListIdentifier::Namespace(_) | TaskListIdentifier::Dump => None,
+        };
+        Some(index_uid)
+    }
+
+    /// Removes a pending task from its task list. Returns whether the task was found in the
+    /// queue.
+    fn remove(&mut self, task: &Task) -> bool {
+        let uid = TaskListIdentifier::from(task);
+        let removed = match self.index_tasks.get(&uid) {
+            Some(list) => {
+                let mut list = list.borrow_mut();
+                let len = list.len();
+                let tasks = std::mem::take(&mut list.tasks);
+                list.tasks = tasks.into_iter().filter(|t| t.id != task.id).collect();
+                list.len() != len
+            }
+            None => false,
+        };
+
+        if removed {
+            if self.index_tasks[&uid].borrow().is_empty() {
+                self.index_tasks.remove(&uid);
+            }
+            self.reorder();
+        }
+
+        removed
+    }
+
+    /// Removes the document additions and updates that are pending right before the clear
+    /// `clear` in its task list, and returns their ids. Only the additions and updates that directly
+    /// precede the clear are removed, any other task in between stops the search. The tasks that
+    /// were already batched are not in the queue anymore, and are never removed.
+    fn supersede(&mut self, clear: &Task) -> Vec<TaskId> {
+        let uid = TaskListIdentifier::from(clear);
+        let superseded: Vec<_> = match self.index_tasks.get(&uid) {
+            Some(list) => {
+                let mut list = list.borrow_mut();
+                let mut tasks = std::mem::take(&mut list.tasks).into_vec();
+                tasks.sort_unstable_by_key(|t| t.id);
+
+                let superseded = match tasks.iter().position(|t| t.id == clear.id) {
+                    Some(position) => {
+                        let start = tasks[..position]
+                            .iter()
+                            .rposition(|t| {
+                                !matches!(
+                                    t.kind,
+                                    TaskType::DocumentAddition { .. }
+                                        | TaskType::DocumentUpdate { .. }
+                                )
+                            })
+                            .map_or(0, |i| i + 1);
+                        tasks.drain(start..position).map(|t| t.id).collect()
+                    }
+                    None => Vec::new(),
+                };
+
+                list.tasks = tasks.into_iter().collect();
+                superseded
+            }
+            None => Vec::new(),
+        };
+
+        if !superseded.is_empty() {
+            self.reorder();
+        }
+
+        superseded
+    }
+
+    /// The head of a list may have changed, so the queue must be rebuilt to keep the lists
+    /// correctly ordered.
+    fn reorder(&mut self) {
+        self.queue = self
+            .queue
+            .drain()
+            .filter(|list| !list.borrow().is_empty())
+            .collect();
+    }
+
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty() && self.index_tasks.is_empty()
     }
+
+    /// The date the oldest pending task was enqueued at. The first task of a list is its oldest,
+    /// so only the heads of the lists are looked at.
+    fn oldest_enqueued_at(&self) -> Option<OffsetDateTime> {
+        self.index_tasks
+            .values()
+            .filter(|list| !self.is_deferred(&list.borrow()))
+            .filter_map(|list| list.borrow().peek().and_then(|task| task.enqueued_at))
+            .min()
+    }
+
+    /// Returns the pending tasks of every task list, the lists being ordered by their first task.
+    fn snapshot(&self) -> Vec<QueuedTaskList> {
+        let mut lists: Vec<_> = self
+            .index_tasks
+            .values()
+            .map(|list| {
+                let list = list.borrow();
+                let mut tasks: Vec<_> = list.iter().collect();
+                tasks.sort_unstable_by_key(|t| t.id);
+                QueuedTaskList {
+                    list: list.id.to_string(),
+                    tasks: tasks
+                        .into_iter()
+                        .map(|t| QueuedTask {
+                            uid: t.id,
+                            kind: t.kind.name(),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        lists.sort_unstable_by_key(|list| list.tasks.first().map(|t| t.uid));
+        lists
+    }
+}
+
+/// A task waiting in the queue of the scheduler.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedTask {
+    pub uid: TaskId,
+    /// How the task is batched.
+    pub kind: &'static str,
+}
+
+/// The tasks waiting in a task list, in the order they will be processed. The tasks of an index,
+/// the integrity checks of an index, the deletions of a namespace, and the dumps are each queued
+/// in a list of their own.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedTaskList {
+    pub list: String,
+    pub tasks: Vec<QueuedTask>,
+}
+
+/// A job processed before the tasks. Unlike the tasks, the jobs are not persisted: they are lost
+/// on restart.
+#[derive(Debug, Clone)]
+pub enum Job {
+    Snapshot(SnapshotJob),
+}
+
+impl Job {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Job::Snapshot(_) => "snapshot",
+        }
+    }
+}
+
+/// A job waiting in the queue of the scheduler.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedJob {
+    pub kind: &'static str,
+    #[serde(with = "time::serde::rfc3339")]
+    pub enqueued_at: OffsetDateTime,
+}
+
+/// The state of the queue of the scheduler at a given time.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueSnapshot {
+    /// The tasks of the batch being processed.
+    pub processing: Vec<TaskId>,
+    /// The task lists, in the order they will be processed.
+    pub lists: Vec<QueuedTaskList>,
+    /// The jobs waiting to be processed, in the order they will be processed.
+    pub jobs: Vec<QueuedJob>,
+}
+
+/// How far the scheduler is behind the registered tasks. The tasks are fetched from the store
+/// before being batched, so the lag is only ever more than a few tasks when the scheduler is
+/// stuck.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulerLag {
+    /// The gap between the id of the newest registered task and the id of the next task to fetch.
+    pub unfetched_tasks: u32,
+    /// How long ago, in milliseconds, the oldest task not fetched yet was enqueued.
+    pub oldest_unfetched_task_age_ms: Option<u64>,
+}
+
+/// Tracks the lag over the consecutive batches, to warn when it keeps growing.
+#[derive(Default)]
+struct LagWatch {
+    last: u32,
+    growths: usize,
+}
+
+/// Whether the batch returned by `prepare` was finished. A batch is only prepared once the previous
+/// one is finished, so that the tasks of two batches are never processed at the same time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BatchState {
+    Idle,
+    /// The jobs, and the empty batches, have no id.
+    BatchOut {
+        batch_id: Option<BatchId>,
+    },
 }
 
 pub struct Scheduler {
-    // TODO: currently snapshots are non persistent tasks, and are treated differently.
-    snapshots: VecDeque<SnapshotJob>,
+    /// The jobs waiting to be processed, along with the date they were scheduled at.
+    jobs: VecDeque<(Job, OffsetDateTime)>,
     tasks: TaskQueue,
+    /// The tasks scheduled for later, which are only put in the queue once they are due.
+    delayed: Vec<Task>,
 
     store: TaskStore,
     processing: Processing,
+    state: BatchState,
     next_fetched_task_id: TaskId,
     config: SchedulerConfig,
+    /// The progress reported by the handlers for the tasks they process.
+    progress: TaskProgresses,
+    /// Aborts the batch being processed.
+    abort: BatchAbort,
     /// Notifies the update loop that a new task was received
-    notifier: watch::Sender<()>,
+    notifier: Notifier,
+    /// Announces the state transitions of the tasks to the subscribers, the waiters included.
+    events: TaskEvents,
+    lag_watch: LagWatch,
+    /// Tracks the arrival rate of the tasks of each index, when the debounce is adaptive.
+    debounce: Option<AdaptiveDebounce>,
+    /// The indexes created by pending tasks. The tasks are tracked when they are registered, and
+    /// forgotten when the scheduler announces them finished.
+    pending_creations: PendingCreations,
+    poison: PoisonWatch,
+}
+
+/// Tracks the batches that failed because of their tasks, to quarantine the tasks that keep
+/// failing the batches they lead.
+#[derive(Default)]
+struct PoisonWatch {
+    /// The number of failures in a row of the batches led by each task. A task is forgotten once
+    /// a batch it leads is finished.
+    failures: HashMap<TaskId, u32>,
+    /// The number of tasks quarantined since the start.
+    quarantined: u64,
 }
 
 impl Scheduler {
@@ -249,10 +657,26 @@ impl Scheduler {
         store: TaskStore,
         performers: Vec<Arc<dyn BatchHandler + Sync + Send + 'static>>,
         mut config: SchedulerConfig,
+        progress: TaskProgresses,
+        closed: ClosedIndexes,
+        locks: IndexLocks,
+        pending_creations: PendingCreations,
     ) -> Result<Arc<RwLock<Self>>> {
-        let (notifier, rcv) = watch::channel(());
+        let (notifier, rcv) = notifier::channel();
+        // the tasks of an index are left in the queue while it is locked, the scheduler is
+        // notified once they can be processed again.
+        locks.notify_releases_to(notifier.sender());
 
         let debounce_time = config.debounce_duration_sec;
+        let max_task_age = config.max_task_age_before_batch_sec;
+        let adaptive_debounce = config.adaptive_debounce;
+        let read_only = config.read_only;
+        let debounce = config.adaptive_debounce.then(|| {
+            AdaptiveDebounce::new(
+                Duration::from_millis(config.min_debounce_duration_ms),
+                Duration::from_millis(config.max_debounce_duration_ms),
+            )
+        });
 
         // Disable autobatching
         if !config.enable_auto_batching {
@@ -260,14 +684,23 @@ impl Scheduler {
         }
 
         let this = Self {
-            snapshots: VecDeque::new(),
-            tasks: TaskQueue::default(),
+            jobs: VecDeque::new(),
+            tasks: TaskQueue::new(closed, locks),
+            delayed: Vec::new(),
 
             store,
             processing: Processing::Nothing,
+            state: BatchState::Idle,
             next_fetched_task_id: 0,
             config,
+            progress,
+            abort: BatchAbort::default(),
             notifier,
+            events: TaskEvents::default(),
+            lag_watch: LagWatch::default(),
+            debounce,
+            pending_creations,
+            poison: PoisonWatch::default(),
         };
 
         // Notify update loop to start processing pending updates immediately after startup.
@@ -279,30 +712,175 @@ impl Scheduler {
             this.clone(),
             performers,
             debounce_time.filter(|&v| v > 0).map(Duration::from_secs),
+            adaptive_debounce,
+            max_task_age.map(Duration::from_secs),
             rcv,
         );
 
-        tokio::task::spawn_local(update_loop.run());
+        if !read_only {
+            tokio::task::spawn_local(update_loop.run());
+        }
 
         Ok(this)
     }
 
     fn register_task(&mut self, task: Task) {
         assert!(!task.is_finished());
-        self.tasks.insert(task);
+        match task.scheduled_at() {
+            Some(at) if at > OffsetDateTime::now_utc() => self.delayed.push(task),
+            _ => self.tasks.insert(task),
+        }
     }
 
-    /// Clears the processing list, this method should be called when the processing of a batch is finished.
-    pub fn finish(&mut self) {
-        self.processing = Processing::Nothing;
+    /// Puts the scheduled tasks that are due in the queue. They are processed before the tasks of
+    /// their list registered after them.
+    fn release_due_tasks(&mut self) {
+        let now = OffsetDateTime::now_utc();
+        let (due, delayed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.delayed)
+            .into_iter()
+            .partition(|task| task.scheduled_at().map_or(true, |at| at <= now));
+        self.delayed = delayed;
+        for task in due {
+            self.tasks.requeue(task);
+        }
+    }
+
+    /// The date the first task scheduled for later becomes due.
+    pub fn next_scheduled_at(&self) -> Option<OffsetDateTime> {
+        self.delayed.iter().filter_map(Task::scheduled_at).min()
+    }
+
+    /// The first deadline of the locks of the indexes, past which the tasks of a locked index are
+    /// processed even if its lock wasn't released.
+    pub fn next_lock_deadline(&self) -> Option<std::time::Instant> {
+        self.tasks.locks.next_deadline()
+    }
+
+    /// Clears the processing list once the processing of the batch `batch_id` is finished. Only
+    /// the batch returned by the last call to `prepare` can be finished, and only once: anything
+    /// else is refused, and leaves the scheduler as it is.
+    pub fn finish(&mut self, batch_id: Option<BatchId>) -> Result<()> {
+        match self.state {
+            BatchState::BatchOut {
+                batch_id: outstanding,
+            } if outstanding == batch_id => {
+                self.state = BatchState::Idle;
+                self.processing = Processing::Nothing;
+                // the id of a batch is the id of the task leading it.
+                if let Some(id) = batch_id {
+                    self.poison.failures.remove(&id);
+                }
+                Ok(())
+            }
+            BatchState::BatchOut {
+                batch_id: outstanding,
+            } => {
+                log::error!(
+                    "finish() was called for batch {:?} while batch {:?} is outstanding",
+                    batch_id,
+                    outstanding
+                );
+                Err(TaskError::WrongBatchFinished {
+                    finished: batch_id,
+                    outstanding,
+                })
+            }
+            BatchState::Idle => {
+                log::error!(
+                    "finish() was called for batch {:?} while no batch is outstanding",
+                    batch_id
+                );
+                Err(TaskError::NoOutstandingBatch(batch_id))
+            }
+        }
+    }
+
+    /// Gives up the outstanding batch `batch_id`, whose processing failed with `error`, and puts
+    /// its unfinished tasks back in the queue. When the quarantine is enabled, the task leading the
+    /// batch is failed instead once the batches it led failed `quarantine_after_failures` times
+    /// in a row because of their tasks, so that the next tasks of its index are processed. The
+    /// failures caused by the environment are not counted, and interrupt the series.
+    pub async fn give_up(&mut self, batch_id: Option<BatchId>, error: &TaskError) -> Result<()> {
+        let ids: Vec<_> = self.processing.ids().collect();
+        self.finish(batch_id)?;
+
+        let mut tasks = Vec::new();
+        for id in ids {
+            let task = self.store.get_task(id, None).await?;
+            if !task.is_finished() {
+                tasks.push(task);
+            }
+        }
+
+        let leader = match batch_id {
+            Some(id) if self.config.quarantine_poison_tasks && error.is_task_local() => {
+                let failures = self.poison.failures.entry(id).or_default();
+                *failures += 1;
+                (*failures >= self.config.quarantine_after_failures.max(1)).then(|| (id, *failures))
+            }
+            Some(id) => {
+                self.poison.failures.remove(&id);
+                None
+            }
+            None => None,
+        };
+
+        if let Some((id, failures)) = leader {
+            if let Some(position) = tasks.iter().position(|task| task.id == id) {
+                let mut task = tasks.remove(position);
+                log::error!(
+                    "Task {} was quarantined after the processing of its batch failed {} times in a row, the next tasks of its index are processed without it. Last failure: {}",
+                    id,
+                    failures,
+                    error
+                );
+                let error = TaskError::QuarantinedAfterRepeatedFailures {
+                    id,
+                    failures,
+                    last_failure: error.to_string(),
+                };
+                task.events.push(TaskEvent::failed(error.into()));
+                let quarantined = self.store.update_tasks(vec![task]).await?;
+                self.announce(&quarantined);
+                self.poison.failures.remove(&id);
+                self.poison.quarantined += 1;
+            }
+        }
+
+        for task in tasks {
+            self.tasks.requeue(task);
+        }
+        // the batches are retried right away only when the series of failures is bounded, the
+        // others are retried with the next notification.
+        if self.config.quarantine_poison_tasks && error.is_task_local() {
+            self.notify_if_not_empty();
+        }
+
+        Ok(())
+    }
+
+    /// The number of tasks quarantined since the start.
+    pub fn quarantined_tasks(&self) -> u64 {
+        self.poison.quarantined
+    }
+
+    /// Puts the document additions of `content` that still have chunks to index back in the
+    /// queue, so that their next chunk is indexed by the next batch of their index.
+    pub fn requeue_chunked_additions(&mut self, content: &BatchContent) {
+        if let BatchContent::DocumentsAdditionBatch(tasks) = content {
+            for task in tasks.iter().filter(|task| !task.is_finished()) {
+                self.tasks.requeue(task.clone());
+                self.notify();
+            }
+        }
     }
 
     pub fn notify(&self) {
-        let _ = self.notifier.send(());
+        self.notifier.notify();
     }
 
     fn notify_if_not_empty(&self) {
-        if !self.snapshots.is_empty() || !self.tasks.is_empty() {
+        if !self.jobs.is_empty() || !self.tasks.is_empty() {
             self.notify();
         }
     }
@@ -311,20 +889,83 @@ impl Scheduler {
         match content {
             BatchContent::DocumentsAdditionBatch(tasks) => {
                 let tasks = self.store.update_tasks(tasks).await?;
+                self.announce(&tasks);
                 Ok(BatchContent::DocumentsAdditionBatch(tasks))
             }
+            BatchContent::DocumentDeletionBatch(tasks) => {
+                let tasks = self.store.update_tasks(tasks).await?;
+                self.announce(&tasks);
+                Ok(BatchContent::DocumentDeletionBatch(tasks))
+            }
             BatchContent::IndexUpdate(t) => {
                 let mut tasks = self.store.update_tasks(vec![t]).await?;
+                self.announce(&tasks);
                 Ok(BatchContent::IndexUpdate(tasks.remove(0)))
             }
             BatchContent::Dump(t) => {
                 let mut tasks = self.store.update_tasks(vec![t]).await?;
+                self.announce(&tasks);
                 Ok(BatchContent::Dump(tasks.remove(0)))
             }
+            BatchContent::TaskStoreCompaction(t) => {
+                let mut tasks = self.store.update_tasks(vec![t]).await?;
+                self.announce(&tasks);
+                Ok(BatchContent::TaskStoreCompaction(tasks.remove(0)))
+            }
             other => Ok(other),
         }
     }
 
+    /// Announces the state transitions of `tasks`, which were just saved in the store.
+    fn announce(&self, tasks: &[Task]) {
+        self.pending_creations.finished(tasks);
+        self.events.updated(tasks);
+    }
+
+    /// Announces the registration of `task`, which was just saved in the store.
+    pub fn announce_registered(&self, task: &Task) {
+        self.events.registered(task);
+    }
+
+    /// Returns a stream of the state transitions of the tasks from now on: their registration,
+    /// batching, processing and end. It ends once the scheduler is dropped.
+    pub fn subscribe(&self) -> TaskEventStream {
+        self.events.subscribe()
+    }
+
+    /// The indexes created by pending tasks.
+    pub fn pending_creations(&self) -> &PendingCreations {
+        &self.pending_creations
+    }
+
+    /// Returns a waiter for the tasks, which is woken up when the scheduler finishes them.
+    pub fn task_waiter(&self) -> TaskWaiter {
+        TaskWaiter::new(self.store.clone(), Some(self.subscribe()))
+    }
+
+    /// Waits for the task `id` to be finished, and returns it. Fails with a `WaitTimeout` error
+    /// holding the last state of the task once `timeout` elapsed.
+    ///
+    /// The returned future doesn't borrow the scheduler: the lock on the scheduler must be
+    /// released before awaiting it, since the update loop needs it to process the task.
+    pub fn wait_for_task(
+        &self,
+        id: TaskId,
+        timeout: Option<Duration>,
+    ) -> impl Future<Output = Result<Task>> {
+        self.task_waiter().wait_for_task(id, timeout)
+    }
+
+    /// Waits for all the tasks `ids` to be finished, like `wait_for_task`, and returns them in
+    /// the same order.
+    pub fn wait_for_tasks<'a>(
+        &self,
+        ids: &'a [TaskId],
+        timeout: Option<Duration>,
+    ) -> impl Future<Output = Result<Vec<Task>>> + 'a {
+        self.task_waiter().wait_for_tasks(ids, timeout)
+    }
+
     pub async fn get_task(&self, id: TaskId, filter: Option<TaskFilter>) -> Result<Task> {
         self.store.get_task(id, filter).await
     }
@@ -334,8 +975,30 @@ impl Scheduler {
         offset: Option<TaskId>,
         filter: Option<TaskFilter>,
         limit: Option<usize>,
+        order: TaskOrder,
     ) -> Result<Vec<Task>> {
-        self.store.list_tasks(offset, filter, limit).await
+        self.store.list_tasks(offset, filter, limit, order).await
+    }
+
+    pub async fn put_batch_result(&self, result: BatchResult) -> Result<()> {
+        self.store.put_batch_result(result).await
+    }
+
+    pub async fn get_batch_result(
+        &self,
+        id: BatchId,
+        indexes: Option<HashSet<String>>,
+    ) -> Result<BatchResult> {
+        self.store.get_batch_result(id, indexes).await
+    }
+
+    pub async fn list_batch_results(
+        &self,
+        from: Option<BatchId>,
+        indexes: Option<HashSet<String>>,
+        limit: Option<usize>,
+    ) -> Result<Vec<BatchResult>> {
+        self.store.list_batch_results(from, indexes, limit).await
     }
 
     pub async fn get_processing_tasks(&self) -> Result<Vec<Task>> {
@@ -349,78 +1012,319 @@ impl Scheduler {
         Ok(tasks)
     }
 
-    pub fn schedule_snapshot(&mut self, job: SnapshotJob) {
-        self.snapshots.push_back(job);
-        self.notify();
+    /// Returns the tasks being processed and the tasks waiting to be processed.
+    pub fn queue_snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            processing: self.processing.ids().collect(),
+            lists: self.tasks.snapshot(),
+            jobs: self.queued_jobs(),
+        }
     }
 
-    async fn fetch_pending_tasks(&mut self) -> Result<()> {
-        self.store
-            .fetch_unfinished_tasks(Some(self.next_fetched_task_id))
-            .await?
-            .into_iter()
-            .for_each(|t| {
-                self.next_fetched_task_id = t.id + 1;
-                self.register_task(t);
-            });
-
-        Ok(())
+    /// Returns the jobs waiting to be processed, in the order they will be processed.
+    pub fn queued_jobs(&self) -> Vec<QueuedJob> {
+        self.jobs
+            .iter()
+            .map(|(job, enqueued_at)| QueuedJob {
+                kind: job.kind(),
+                enqueued_at: *enqueued_at,
+            })
+            .collect()
     }
 
-    /// Prepare the next batch, and set `processing` to the ids in that batch.
-    pub async fn prepare(&mut self) -> Result<Batch> {
-        // If there is a job to process, do it first.
-        if let Some(job) = self.snapshots.pop_front() {
-            // There is more work to do, notify the update loop
-            self.notify_if_not_empty();
-            let batch = Batch::new(None, BatchContent::Snapshot(job));
-            return Ok(batch);
+    /// Returns how far the scheduler is behind the registered tasks.
+    pub fn lag(&self) -> SchedulerLag {
+        match self.store.unfetched_tasks() {
+            Some((newest, oldest_enqueued_at)) => {
+                let age = OffsetDateTime::now_utc() - oldest_enqueued_at;
+                SchedulerLag {
+                    unfetched_tasks: (newest + 1).saturating_sub(self.next_fetched_task_id),
+                    oldest_unfetched_task_age_ms: Some(age.whole_milliseconds().max(0) as u64),
+                }
+            }
+            None => SchedulerLag::default(),
         }
+    }
 
-        // Try to fill the queue with pending tasks.
-        self.fetch_pending_tasks().await?;
+    /// Warns when the lag grew for `LAG_GROWTH_WARNING_BATCHES` consecutive batches, and again
+    /// every as many batches as long as it keeps growing.
+    fn watch_lag(&mut self) {
+        let lag = self.lag();
+        if lag.unfetched_tasks > self.lag_watch.last {
+            self.lag_watch.growths += 1;
+        } else {
+            self.lag_watch.growths = 0;
+        }
+        self.lag_watch.last = lag.unfetched_tasks;
+
+        if self.lag_watch.growths > 0 && self.lag_watch.growths % LAG_GROWTH_WARNING_BATCHES == 0 {
+            log::warn!(
+                "The scheduler lag grew for {} consecutive batches: {} registered tasks are not fetched yet, the oldest one for {}ms. The next task to fetch is the task {}.",
+                self.lag_watch.growths,
+                lag.unfetched_tasks,
+                lag.oldest_unfetched_task_age_ms.unwrap_or_default(),
+                self.next_fetched_task_id,
+            );
+        }
+    }
 
-        self.processing = make_batch(&mut self.tasks, &self.config);
+    /// The progress of the task `id`, as long as it is processed.
+    pub fn task_progress(&self, id: TaskId) -> Option<TaskProgress> {
+        if self.processing.ids().any(|processing| processing == id) {
+            self.progress.get(id)
+        } else {
+            None
+        }
+    }
 
-        log::debug!("prepared batch with {} tasks", self.processing.len());
+    /// Aborts the batch being processed, which fails its tasks with an `AbortedByAdmin` error, when
+    /// its handler hasn't shown any sign of life for the configured grace period. Returns the
+    /// failed tasks.
+    pub async fn force_reset_processing(&mut self) -> Result<Vec<Task>> {
+        let ids: Vec<_> = self.processing.ids().collect();
+        if ids.is_empty() {
+            return Err(TaskError::NoProcessingBatch);
+        }
 
-        if !self.processing.is_nothing() {
-            let (processing, mut content) = self
-                .store
-                .get_processing_tasks(std::mem::take(&mut self.processing))
-                .await?;
+        let grace_period = Duration::from_secs(self.config.stuck_batch_grace_period_sec);
+        let silence = self
+            .progress
+            .last_beat()
+            .map_or(Duration::MAX, |beat| beat.elapsed());
+        if silence < grace_period {
+            return Err(TaskError::ProcessingBatchAlive {
+                silence: silence.as_secs(),
+                grace_period: grace_period.as_secs(),
+            });
+        }
 
-            // The batch id is the id of the first update it contains. At this point we must have a
-            // valid batch that contains at least 1 task.
-            let id = match content.first() {
-                Some(Task { id, .. }) => *id,
-                _ => panic!("invalid batch"),
-            };
+        self.abort.abort();
 
-            content.push_event(TaskEvent::Batched {
-                batch_id: id,
-                timestamp: OffsetDateTime::now_utc(),
-            });
+        let mut tasks = Vec::new();
+        for id in ids {
+            let mut task = self.store.get_task(id, None).await?;
+            if !task.is_finished() {
+                task.events
+                    .push(TaskEvent::failed(TaskError::AbortedByAdmin(id).into()));
+                tasks.push(task);
+            }
+        }
+        let tasks = self.store.update_tasks(tasks).await?;
+        self.announce(&tasks);
 
-            self.processing = processing;
+        if let BatchState::BatchOut { batch_id } = self.state {
+            self.finish(batch_id)?;
+        }
+        self.notify();
 
-            let batch = Batch::new(Some(id), content);
+        Ok(tasks)
+    }
 
-            // There is more work to do, notify the update loop
-            self.notify_if_not_empty();
+    /// Cancels a task that was not picked for processing yet. The task is removed from the
+    /// pending queue and marked as failed with a `task_canceled` error.
+    pub async fn cancel_task(&mut self, id: TaskId, filter: Option<TaskFilter>) -> Result<Task> {
+        let mut task = self.store.get_task(id, filter).await?;
 
-            Ok(batch)
-        } else {
-            Ok(Batch::empty())
+        if task.is_finished() || self.processing.ids().any(|processing| processing == id) {
+            return Err(TaskError::TaskNotCancelable(id));
         }
-    }
-}
+
+        self.tasks.remove(&task);
+        self.delayed.retain(|delayed| delayed.id != id);
+        task.events
+            .push(TaskEvent::failed(TaskError::TaskCanceled(id).into()));
+
+        let mut tasks = self.store.update_tasks(vec![task]).await?;
+        self.announce(&tasks);
+
+        Ok(tasks.remove(0))
+    }
+
+    /// Completes the document additions and updates that are pending right before the clear
+    /// `clear`, since the clear would delete their documents anyway. They succeed without indexing
+    /// anything, and their result refers to the clear. Returns the completed tasks, whose update
+    /// files can be deleted. Does nothing unless `supersede_cleared_documents` is enabled.
+    pub async fn supersede_documents(&mut self, clear: &Task) -> Result<Vec<Task>> {
+        if !self.config.supersede_cleared_documents {
+            return Ok(Vec::new());
+        }
+
+        // The clear, and the tasks registered before it, may not have been fetched yet.
+        self.fetch_pending_tasks().await?;
+
+        let mut tasks = Vec::new();
+        for id in self.tasks.supersede(clear) {
+            let mut task = self.store.get_task(id, None).await?;
+            task.events
+                .push(TaskEvent::succeeded(TaskResult::Superseded {
+                    superseded_by: clear.id,
+                }));
+            tasks.push(task);
+        }
+
+        if tasks.is_empty() {
+            return Ok(tasks);
+        }
+
+        let tasks = self.store.update_tasks(tasks).await?;
+        self.announce(&tasks);
+        Ok(tasks)
+    }
+
+    /// The date the oldest task waiting to be batched was enqueued at, the tasks registered since
+    /// the last batch included.
+    pub async fn oldest_pending_enqueued_at(&mut self) -> Result<Option<OffsetDateTime>> {
+        self.fetch_pending_tasks().await?;
+        Ok(self.tasks.oldest_enqueued_at())
+    }
+
+    /// Schedules `job` to be processed before the next tasks. The job is rejected when the
+    /// maximum number of jobs are already waiting, which means they are scheduled faster than
+    /// they are processed.
+    pub fn schedule_job(&mut self, job: Job) -> Result<()> {
+        let max = self.config.max_queued_jobs.max(1);
+        if self.jobs.len() >= max {
+            let queued: Vec<_> = self.jobs.iter().map(|(job, _)| job.kind()).collect();
+            log::warn!(
+                "Rejected a {} job: {} jobs are already waiting to be processed: {:?}.",
+                job.kind(),
+                self.jobs.len(),
+                queued,
+            );
+            return Err(TaskError::TooManyQueuedJobs {
+                kind: job.kind(),
+                max,
+            });
+        }
+
+        self.jobs.push_back((job, OffsetDateTime::now_utc()));
+        self.notify();
+        Ok(())
+    }
+
+    /// Registers the unfinished tasks that were not fetched yet, read from the oldest one.
+    async fn fetch_pending_tasks(&mut self) -> Result<()> {
+        self.store
+            .fetch_unfinished_tasks(Some(self.next_fetched_task_id))
+            .await?
+            .into_iter()
+            .for_each(|t| {
+                self.next_fetched_task_id = t.id + 1;
+                if let Some((debounce, at)) = self.debounce.as_mut().zip(t.enqueued_at()) {
+                    debounce.arrived(t.index_uid(), at);
+                }
+                self.register_task(t);
+            });
+
+        Ok(())
+    }
+
+    /// The debounce chosen for the next batch by the adaptive debounce, if it is enabled and there
+    /// is a batch to prepare.
+    pub fn adaptive_debounce(&self) -> Option<Duration> {
+        let debounce = self.debounce.as_ref()?;
+        let index_uid = self.tasks.head_index_uid()?;
+        Some(debounce.debounce(index_uid.as_deref()))
+    }
+
+    /// The handle aborting the batch returned by the last call to `prepare`.
+    pub fn batch_abort(&self) -> BatchAbort {
+        self.abort.clone()
+    }
+
+    /// Prepare the next batch, and set `processing` to the ids in that batch. The batch must be
+    /// finished before the next one is prepared: a batch prepared while another one is outstanding
+    /// is refused.
+    pub async fn prepare(&mut self) -> Result<Batch> {
+        if let BatchState::BatchOut { batch_id } = self.state {
+            log::error!(
+                "prepare() was called while batch {:?} is outstanding",
+                batch_id
+            );
+            return Err(TaskError::BatchOutstanding(batch_id));
+        }
+
+        let batch = self.prepare_batch().await?;
+        self.state = BatchState::BatchOut { batch_id: batch.id };
+
+        Ok(batch)
+    }
+
+    async fn prepare_batch(&mut self) -> Result<Batch> {
+        // The batch is given a fresh heartbeat, and a handle of its own to be aborted.
+        self.abort = BatchAbort::default();
+        self.progress.beat();
+
+        // If there is a job to process, do it first.
+        if let Some((job, _)) = self.jobs.pop_front() {
+            self.store.flush_journal().await?;
+            // There is more work to do, notify the update loop
+            self.notify_if_not_empty();
+            let content = match job {
+                Job::Snapshot(job) => BatchContent::Snapshot(job),
+            };
+            return Ok(Batch::new(None, content));
+        }
+
+        // Try to fill the queue with pending tasks.
+        self.fetch_pending_tasks().await?;
+        self.release_due_tasks();
+        self.watch_lag();
+
+        let (processing, stop_reason) = make_batch(&mut self.tasks, &self.config);
+        self.processing = processing;
+
+        log::debug!("prepared batch with {} tasks", self.processing.len());
+
+        if !self.processing.is_nothing() {
+            // The dumps read the tasks straight from the store.
+            if matches!(self.processing, Processing::Dump(_)) {
+                self.store.flush_journal().await?;
+            }
+
+            let (processing, mut content) = self
+                .store
+                .get_processing_tasks(std::mem::take(&mut self.processing))
+                .await?;
+
+            // The batch id is the id of the first update it contains. The batch is empty if none
+            // of its tasks could be found in the store, in which case there is nothing to process.
+            let id = match content.first() {
+                Some(Task { id, .. }) => *id,
+                None => {
+                    self.processing = Processing::Nothing;
+                    self.notify_if_not_empty();
+                    return Ok(Batch::empty());
+                }
+            };
+
+            content.push_event(TaskEvent::Batched {
+                batch_id: id,
+                timestamp: OffsetDateTime::now_utc(),
+            });
+
+            self.processing = processing;
+
+            let mut batch = Batch::new(Some(id), content);
+            batch.stop_reason = Some(stop_reason);
+
+            // There is more work to do, notify the update loop
+            self.notify_if_not_empty();
+
+            Ok(batch)
+        } else {
+            Ok(Batch::empty())
+        }
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub enum Processing {
     DocumentAdditions(Vec<TaskId>),
+    DocumentDeletions(Vec<TaskId>),
     IndexUpdate(TaskId),
     Dump(TaskId),
+    TaskStoreCompaction(TaskId),
     /// Variant used when there is nothing to process.
     Nothing,
 }
@@ -454,16 +1358,22 @@ impl Processing {
 
     pub fn ids(&self) -> impl Iterator<Item = TaskId> + '_ {
         match self {
-            Processing::DocumentAdditions(v) => ProcessingIter::Many(v.iter()),
-            Processing::IndexUpdate(id) | Processing::Dump(id) => ProcessingIter::Single(Some(*id)),
+            Processing::DocumentAdditions(v) | Processing::DocumentDeletions(v) => {
+                ProcessingIter::Many(v.iter())
+            }
+            Processing::IndexUpdate(id)
+            | Processing::Dump(id)
+            | Processing::TaskStoreCompaction(id) => ProcessingIter::Single(Some(*id)),
             Processing::Nothing => ProcessingIter::Single(None),
         }
     }
 
     pub fn len(&self) -> usize {
         match self {
-            Processing::DocumentAdditions(v) => v.len(),
-            Processing::IndexUpdate(_) | Processing::Dump(_) => 1,
+            Processing::DocumentAdditions(v) | Processing::DocumentDeletions(v) => v.len(),
+            Processing::IndexUpdate(_)
+            | Processing::Dump(_)
+            | Processing::TaskStoreCompaction(_) => 1,
             Processing::Nothing => 0,
         }
     }
@@ -473,33 +1383,67 @@ impl Processing {
     }
 }
 
-fn make_batch(tasks: &mut TaskQueue, config: &SchedulerConfig) -> Processing {
+/// Makes the next batch from the queue, and returns it along with the reason why no other task
+/// was added to it.
+///
+/// The tasks of a task list are always processed in the order of their ids: a batch is only ever
+/// made of the tasks at the head of a single list, and these tasks are all of the same kind. Only
+/// the document additions, the document updates, or the deletions of documents by id, of an index
+/// are batched together.
+pub(super) fn make_batch(
+    tasks: &mut TaskQueue,
+    config: &SchedulerConfig,
+) -> (Processing, BatchStopReason) {
     let mut doc_count = 0;
     tasks
         .head_mut(|list| match list.peek().copied() {
             Some(PendingTask {
-                kind: TaskType::IndexUpdate,
+                kind: TaskType::IndexUpdate | TaskType::DocumentClear,
                 id,
+                ..
             }) => {
                 list.pop();
-                Processing::IndexUpdate(id)
+                (Processing::IndexUpdate(id), BatchStopReason::NotBatchable)
             }
             Some(PendingTask {
                 kind: TaskType::Dump,
                 id,
+                ..
             }) => {
                 list.pop();
-                Processing::Dump(id)
+                (Processing::Dump(id), BatchStopReason::NotBatchable)
+            }
+            Some(PendingTask {
+                kind: TaskType::TaskStoreCompaction,
+                id,
+                ..
+            }) => {
+                list.pop();
+                (
+                    Processing::TaskStoreCompaction(id),
+                    BatchStopReason::NotBatchable,
+                )
+            }
+            Some(PendingTask {
+                kind: TaskType::ChunkedDocumentAddition,
+                id,
+                ..
+            }) => {
+                list.pop();
+                (
+                    Processing::DocumentAdditions(vec![id]),
+                    BatchStopReason::NotBatchable,
+                )
             }
             Some(PendingTask { kind, .. }) => {
                 let mut task_list = Vec::new();
-                loop {
+                let stop_reason = loop {
                     match list.peek() {
                         Some(pending) if pending.kind == kind => {
                             // We always need to process at least one task for the scheduler to make progress.
                             if task_list.len() >= config.max_batch_size.unwrap_or(usize::MAX).max(1)
                             {
-                                break;
+                                break BatchStopReason::MaxBatchSize;
                             }
                             let pending = list.pop().unwrap();
                             task_list.push(pending.id);
@@ -516,25 +1460,32 @@ fn make_batch(tasks: &mut TaskQueue, config: &SchedulerConfig) -> Processing {
                                     if doc_count
                                         >= config.max_documents_per_batch.unwrap_or(usize::MAX)
                                     {
-                                        break;
+                                        break BatchStopReason::MaxDocumentsPerBatch;
                                     }
                                 }
                                 _ => (),
                             }
                         }
-                        _ => break,
+                        Some(_) => break BatchStopReason::IncompatibleTask,
+                        None => break BatchStopReason::NoMoreTasks,
                     }
-                }
-                Processing::DocumentAdditions(task_list)
+                };
+                let processing = match kind {
+                    TaskType::DocumentDeletion => Processing::DocumentDeletions(task_list),
+                    _ => Processing::DocumentAdditions(task_list),
+                };
+                (processing, stop_reason)
             }
-            None => Processing::Nothing,
+            None => (Processing::Nothing, BatchStopReason::NoMoreTasks),
         })
-        .unwrap_or(Processing::Nothing)
+        .unwrap_or((Processing::Nothing, BatchStopReason::NoMoreTasks))
 }
 
 #[cfg(test)]
 mod test {
     use milli::update::IndexDocumentsMethod;
+    use proptest::prelude::*;
+    use tempfile::TempDir;
     use uuid::Uuid;
 
     use crate::{index_resolver::IndexUid, tasks::task::TaskContent};
@@ -544,11 +1495,48 @@ mod test {
     fn gen_task(id: TaskId, content: TaskContent) -> Task {
         Task {
             id,
+            index_uuid: None,
             content,
             events: vec![],
+            origin: None,
+            engine_version: None,
         }
     }
 
+    /// Returns a scheduler on an empty task store, with the directory of the store.
+    fn test_scheduler(config: SchedulerConfig) -> (TempDir, Scheduler) {
+        use milli::heed::EnvOpenOptions;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = Arc::new(options.open(tmp.path()).unwrap());
+        let store = TaskStore::new(env).unwrap();
+
+        let (notifier, _) = notifier::channel();
+        let scheduler = Scheduler {
+            jobs: VecDeque::new(),
+            tasks: TaskQueue::default(),
+            delayed: Vec::new(),
+            store,
+            processing: Processing::Nothing,
+            state: BatchState::Idle,
+            next_fetched_task_id: 0,
+            config,
+            progress: TaskProgresses::default(),
+            abort: BatchAbort::default(),
+            notifier,
+            events: TaskEvents::default(),
+            lag_watch: LagWatch::default(),
+            debounce: None,
+            pending_creations: PendingCreations::default(),
+            poison: PoisonWatch::default(),
+        };
+
+        (tmp, scheduler)
+    }
+
     #[test]
     #[rustfmt::skip]
     fn register_updates_multiples_indexes() {
@@ -584,6 +1572,12 @@ mod test {
             primary_key: Some("test".to_string()),
             documents_count: 0,
             allow_index_creation: true,
+            reject_duplicates: false,
+            chunks: Vec::new(),
+            return_diff: None,
+            content_digests: Default::default(),
+            sanitized_characters: None,
+            warnings: None,
             index_uid: IndexUid::new_unchecked(index_uid),
         }
     }
@@ -600,32 +1594,1198 @@ mod test {
         queue.insert(gen_task(5, TaskContent::IndexDeletion { index_uid: IndexUid::new_unchecked("test1")}));
         queue.insert(gen_task(6, gen_doc_addition_task_content("test2")));
         queue.insert(gen_task(7, gen_doc_addition_task_content("test1")));
-        queue.insert(gen_task(8, TaskContent::Dump { uid: "adump".to_owned() }));
+        queue.insert(gen_task(8, TaskContent::Dump { uid: "adump".to_owned(), include_pending_tasks: false }));
+        queue.insert(gen_task(9, TaskContent::TaskStoreCompaction));
 
         let config = SchedulerConfig::default();
 
-        // Make sure that the dump is processed before everybody else.
-        let batch = make_batch(&mut queue, &config);
+        // Make sure that the dump and the compaction are processed before everybody else.
+        let (batch, _) = make_batch(&mut queue, &config);
         assert_eq!(batch, Processing::Dump(8));
 
-        let batch = make_batch(&mut queue, &config);
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::TaskStoreCompaction(9));
+
+        let (batch, _) = make_batch(&mut queue, &config);
         assert_eq!(batch, Processing::DocumentAdditions(vec![0, 4]));
 
-        let batch = make_batch(&mut queue, &config);
+        let (batch, _) = make_batch(&mut queue, &config);
         assert_eq!(batch, Processing::DocumentAdditions(vec![1]));
 
-        let batch = make_batch(&mut queue, &config);
+        let (batch, _) = make_batch(&mut queue, &config);
         assert_eq!(batch, Processing::IndexUpdate(2));
 
-        let batch = make_batch(&mut queue, &config);
+        let (batch, _) = make_batch(&mut queue, &config);
         assert_eq!(batch, Processing::DocumentAdditions(vec![3, 6]));
 
-        let batch = make_batch(&mut queue, &config);
+        let (batch, _) = make_batch(&mut queue, &config);
         assert_eq!(batch, Processing::IndexUpdate(5));
 
-        let batch = make_batch(&mut queue, &config);
+        let (batch, _) = make_batch(&mut queue, &config);
         assert_eq!(batch, Processing::DocumentAdditions(vec![7]));
 
         assert!(queue.is_empty());
     }
+
+    #[test]
+    fn test_chunked_additions_are_processed_chunk_by_chunk() {
+        let chunked = |id| {
+            let mut content = gen_doc_addition_task_content("test1");
+            if let TaskContent::DocumentAddition { ref mut chunks, .. } = content {
+                chunks.push(Uuid::new_v4());
+            }
+            gen_task(id, content)
+        };
+
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+        queue.insert(chunked(1));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(3, gen_doc_addition_task_content("test2")));
+
+        let config = SchedulerConfig::default();
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![0]));
+
+        // the chunked addition is never batched with other additions.
+        let (batch, reason) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![1]));
+        assert_eq!(reason, BatchStopReason::NotBatchable);
+
+        // once its first chunk is processed, it goes back ahead of the other tasks of its index.
+        queue.requeue(chunked(1));
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![1]));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![2]));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![3]));
+
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_lists_of_locked_indexes_are_skipped() {
+        let locks = IndexLocks::default();
+        let mut queue = TaskQueue::new(ClosedIndexes::default(), locks.clone());
+        queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(1, gen_doc_addition_task_content("test2")));
+
+        let config = SchedulerConfig::default();
+
+        let guard = locks
+            .acquire("test1", "snapshot", Duration::from_secs(10))
+            .unwrap();
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![1]));
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::Nothing);
+
+        drop(guard);
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![0]));
+
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_lists_of_closed_indexes_are_skipped() {
+        let closed = ClosedIndexes::new(vec!["test1".to_string()]);
+        let mut queue = TaskQueue::new(closed.clone(), IndexLocks::default());
+        queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(1, TaskContent::IndexCheck { index_uid: IndexUid::new_unchecked("test1"), fast: true }));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test2")));
+        queue.insert(gen_task(3, gen_doc_addition_task_content("test3")));
+
+        let config = SchedulerConfig::default();
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![2]));
+
+        // once opened, the index gets its turn back before the lists enqueued after it.
+        closed.remove("test1");
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![0]));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![3]));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::IndexUpdate(1));
+
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_index_checks_have_the_lowest_priority() {
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, TaskContent::IndexCheck { index_uid: IndexUid::new_unchecked("test1"), fast: false }));
+        queue.insert(gen_task(1, TaskContent::IndexCheck { index_uid: IndexUid::new_unchecked("test2"), fast: true }));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(3, TaskContent::IndexDeletion { index_uid: IndexUid::new_unchecked("test2")}));
+
+        let config = SchedulerConfig::default();
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![2]));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::IndexUpdate(3));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::IndexUpdate(0));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::IndexUpdate(1));
+
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_stats_repairs_wait_for_the_other_tasks_of_the_index() {
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, TaskContent::StatsRepair { index_uid: IndexUid::new_unchecked("test1") }));
+        queue.insert(gen_task(1, gen_doc_addition_task_content("test1")));
+
+        let config = SchedulerConfig::default();
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![1]));
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::IndexUpdate(0));
+
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_make_batch_stop_reason() {
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(1, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(3, TaskContent::IndexDeletion { index_uid: IndexUid::new_unchecked("test1")}));
+        queue.insert(gen_task(4, gen_doc_addition_task_content("test2")));
+
+        let config = SchedulerConfig { max_batch_size: Some(2), ..Default::default() };
+
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![0, 1]), BatchStopReason::MaxBatchSize));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![2]), BatchStopReason::IncompatibleTask));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::IndexUpdate(3), BatchStopReason::NotBatchable));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![4]), BatchStopReason::NoMoreTasks));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_settings_update_enqueued_before_the_first_addition() {
+        // neither task can find the index: the settings update, processed first, creates it
+        // like a document addition would, since it is allowed to.
+        let settings = |allow_index_creation| TaskContent::SettingsUpdate {
+            settings: Default::default(),
+            is_deletion: false,
+            allow_index_creation,
+            scheduled_at: None,
+            warnings: Vec::new(),
+            index_uid: IndexUid::new_unchecked("test1"),
+        };
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, settings(true)));
+        queue.insert(gen_task(1, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(3, settings(false)));
+        queue.insert(gen_task(4, gen_doc_addition_task_content("test1")));
+
+        let config = SchedulerConfig::default();
+
+        // the tasks are never reordered around a settings update, whether it may create the index
+        // or not, so that the documents are always indexed with the settings enqueued before them.
+        assert_eq!(make_batch(&mut queue, &config), (Processing::IndexUpdate(0), BatchStopReason::NotBatchable));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![1, 2]), BatchStopReason::IncompatibleTask));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::IndexUpdate(3), BatchStopReason::NotBatchable));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![4]), BatchStopReason::NoMoreTasks));
+        assert!(queue.is_empty());
+    }
+
+    fn gen_doc_deletion_task_content(index_uid: &str, id: &str) -> TaskContent {
+        TaskContent::DocumentDeletion {
+            index_uid: IndexUid::new_unchecked(index_uid),
+            deletion: DocumentDeletion::Ids(vec![id.to_string()]),
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_make_batch_of_deletions() {
+        let mut queue = TaskQueue::default();
+        for id in 0..5 {
+            queue.insert(gen_task(id, gen_doc_deletion_task_content("test1", &id.to_string())));
+        }
+        queue.insert(gen_task(5, TaskContent::IndexDeletion { index_uid: IndexUid::new_unchecked("test1")}));
+        queue.insert(gen_task(6, gen_doc_deletion_task_content("test1", "6")));
+        queue.insert(gen_task(7, gen_doc_deletion_task_content("test1", "7")));
+        queue.insert(gen_task(8, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(9, gen_doc_deletion_task_content("test1", "9")));
+
+        let config = SchedulerConfig { max_batch_size: Some(4), ..Default::default() };
+
+        // a run of deletions collapses into a single batch, up to the maximum batch size.
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentDeletions(vec![0, 1, 2, 3]), BatchStopReason::MaxBatchSize));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentDeletions(vec![4]), BatchStopReason::IncompatibleTask));
+        // but an index deletion is still processed alone.
+        assert_eq!(make_batch(&mut queue, &config), (Processing::IndexUpdate(5), BatchStopReason::NotBatchable));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentDeletions(vec![6, 7]), BatchStopReason::IncompatibleTask));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![8]), BatchStopReason::IncompatibleTask));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentDeletions(vec![9]), BatchStopReason::NoMoreTasks));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_supersede_pending_additions() {
+        let clear = gen_task(6, TaskContent::DocumentDeletion { index_uid: IndexUid::new_unchecked("test1"), deletion: DocumentDeletion::Clear });
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(1, TaskContent::SettingsUpdate { settings: Default::default(), is_deletion: false, allow_index_creation: true, scheduled_at: None, warnings: Vec::new(), index_uid: IndexUid::new_unchecked("test1") }));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(3, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(4, gen_doc_addition_task_content("test2")));
+        queue.insert(gen_task(5, gen_doc_addition_task_content("test1")));
+
+        // the first addition is already batched.
+        let config = SchedulerConfig::default();
+        assert_eq!(make_batch(&mut queue, &config).0, Processing::DocumentAdditions(vec![0]));
+
+        queue.insert(clear.clone());
+        queue.insert(gen_task(7, gen_doc_addition_task_content("test1")));
+
+        // only the additions of the same index that directly precede the clear are superseded.
+        assert_eq!(queue.supersede(&clear), vec![2, 3, 5]);
+
+        assert_eq!(make_batch(&mut queue, &config).0, Processing::IndexUpdate(1));
+        assert_eq!(make_batch(&mut queue, &config).0, Processing::DocumentAdditions(vec![4]));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::IndexUpdate(6), BatchStopReason::NotBatchable));
+        assert_eq!(make_batch(&mut queue, &config).0, Processing::DocumentAdditions(vec![7]));
+        assert!(queue.is_empty());
+
+        // nothing is superseded once the clear is gone.
+        assert!(queue.supersede(&clear).is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_make_batch_zero_documents() {
+        let gen_queue = || {
+            let mut queue = TaskQueue::default();
+            queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+            queue.insert(gen_task(1, gen_doc_addition_task_content("test1")));
+            queue.insert(gen_task(2, gen_doc_addition_task_content("test1")));
+            queue
+        };
+
+        // tasks without documents don't count towards the maximum number of documents.
+        let mut queue = gen_queue();
+        let config = SchedulerConfig { max_documents_per_batch: Some(10), ..Default::default() };
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![0, 1, 2]), BatchStopReason::NoMoreTasks));
+
+        // a batch always contains at least one task, even when no document is allowed.
+        let mut queue = gen_queue();
+        let config = SchedulerConfig { max_documents_per_batch: Some(0), ..Default::default() };
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![0]), BatchStopReason::MaxDocumentsPerBatch));
+        assert_eq!(make_batch(&mut queue, &config), (Processing::DocumentAdditions(vec![1]), BatchStopReason::MaxDocumentsPerBatch));
+    }
+
+    #[test]
+    fn test_empty_task_list_is_scheduled_last() {
+        let empty = TaskList::new(TaskListIdentifier::Index("test1".to_string()));
+        let mut list = TaskList::new(TaskListIdentifier::Index("test2".to_string()));
+        list.push(PendingTask {
+            kind: TaskType::IndexUpdate,
+            id: 12,
+            enqueued_at: None,
+        });
+
+        assert!(list > empty);
+        assert_eq!(empty.cmp(&empty), Ordering::Equal);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Operation {
+        Insert {
+            index: u8,
+            kind: u8,
+            documents_count: usize,
+        },
+        MakeBatch,
+    }
+
+    fn operation_strategy() -> impl Strategy<Value = Operation> {
+        prop_oneof![
+            3 => (0..3u8, 0..6u8, 0..20usize).prop_map(|(index, kind, documents_count)| {
+                Operation::Insert {
+                    index,
+                    kind,
+                    documents_count,
+                }
+            }),
+            1 => Just(Operation::MakeBatch),
+        ]
+    }
+
+    /// Generates a task of one of these kinds: 0 is a document addition, 1 a document update, 2 an
+    /// index deletion, 3 an index check, 4 a dump, and 5 a deletion of documents by id.
+    fn gen_task_of_kind(id: TaskId, index: u8, kind: u8, documents_count: usize) -> Task {
+        let index_uid = IndexUid::new_unchecked(format!("test{}", index));
+        let content = match kind {
+            0 | 1 => TaskContent::DocumentAddition {
+                content_uuid: Uuid::new_v4(),
+                merge_strategy: if kind == 0 {
+                    IndexDocumentsMethod::ReplaceDocuments
+                } else {
+                    IndexDocumentsMethod::UpdateDocuments
+                },
+                primary_key: None,
+                documents_count,
+                allow_index_creation: true,
+                reject_duplicates: false,
+                chunks: Vec::new(),
+                return_diff: None,
+                content_digests: Default::default(),
+                sanitized_characters: None,
+                warnings: None,
+                index_uid,
+            },
+            2 => TaskContent::IndexDeletion { index_uid },
+            3 => TaskContent::IndexCheck {
+                index_uid,
+                fast: true,
+            },
+            4 => TaskContent::Dump {
+                uid: id.to_string(),
+                include_pending_tasks: false,
+            },
+            _ => TaskContent::DocumentDeletion {
+                index_uid,
+                deletion: DocumentDeletion::Ids(vec![id.to_string()]),
+            },
+        };
+        gen_task(id, content)
+    }
+
+    proptest! {
+        #[test]
+        fn test_interleaved_tasks_ordering(
+            operations in proptest::collection::vec(operation_strategy(), 0..100),
+            max_batch_size in proptest::option::of(0..5usize),
+            max_documents_per_batch in proptest::option::of(0..50usize),
+        ) {
+            let config = SchedulerConfig {
+                max_batch_size,
+                max_documents_per_batch,
+                ..Default::default()
+            };
+            let mut queue = TaskQueue::default();
+            // the task list, kind and number of documents of each inserted task.
+            let mut inserted = HashMap::new();
+            let mut batches = Vec::new();
+
+            for operation in operations {
+                match operation {
+                    Operation::Insert { index, kind, documents_count } => {
+                        let task = gen_task_of_kind(inserted.len() as TaskId, index, kind, documents_count);
+                        inserted.insert(task.id, (TaskListIdentifier::from(&task), kind, documents_count));
+                        queue.insert(task);
+                    }
+                    Operation::MakeBatch => batches.push(make_batch(&mut queue, &config).0),
+                }
+            }
+
+            // the queue is processed to exhaustion.
+            loop {
+                match make_batch(&mut queue, &config).0 {
+                    Processing::Nothing => break,
+                    batch => batches.push(batch),
+                }
+            }
+            prop_assert!(queue.is_empty());
+
+            let mut last_emitted = HashMap::new();
+            let mut emitted = HashSet::new();
+            for batch in batches {
+                let ids: Vec<_> = batch.ids().collect();
+                let kinds: Vec<_> = ids.iter().map(|id| inserted[id].1).collect();
+
+                match batch {
+                    Processing::Nothing => continue,
+                    Processing::DocumentAdditions(_) => {
+                        prop_assert!(!ids.is_empty());
+                        prop_assert!(kinds.iter().all(|kind| *kind == kinds[0]), "{:?}", kinds);
+                        prop_assert!(kinds[0] <= 1, "{:?}", kinds);
+                        prop_assert!(ids.len() <= max_batch_size.unwrap_or(usize::MAX).max(1));
+
+                        // only the last task of a batch can reach the maximum number of documents.
+                        let documents: usize = ids[..ids.len() - 1].iter().map(|id| inserted[id].2).sum();
+                        prop_assert!(documents < max_documents_per_batch.unwrap_or(usize::MAX));
+                    }
+                    Processing::DocumentDeletions(_) => {
+                        prop_assert!(kinds.iter().all(|kind| *kind == 5), "{:?}", kinds);
+                        prop_assert!(ids.len() <= max_batch_size.unwrap_or(usize::MAX).max(1));
+                    }
+                    Processing::IndexUpdate(_) => prop_assert!(kinds[0] == 2 || kinds[0] == 3),
+                    Processing::Dump(_) | Processing::TaskStoreCompaction(_) => prop_assert_eq!(kinds[0], 4),
+                }
+
+                // a batch never spans several task lists.
+                let list = &inserted[&ids[0]].0;
+                for id in ids {
+                    prop_assert_eq!(&inserted[&id].0, list);
+                    if let Some(last) = last_emitted.insert(list.clone(), id) {
+                        prop_assert!(last < id, "task {} emitted after task {}", id, last);
+                    }
+                    prop_assert!(emitted.insert(id), "task {} emitted twice", id);
+                }
+            }
+
+            prop_assert_eq!(emitted.len(), inserted.len());
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_remove_pending_task() {
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(1, gen_doc_addition_task_content("test2")));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test1")));
+
+        assert!(queue.remove(&gen_task(0, gen_doc_addition_task_content("test1"))));
+        // removing the same task twice has no effect.
+        assert!(!queue.remove(&gen_task(0, gen_doc_addition_task_content("test1"))));
+        assert!(queue.remove(&gen_task(2, gen_doc_addition_task_content("test1"))));
+
+        let config = SchedulerConfig::default();
+
+        let (batch, _) = make_batch(&mut queue, &config);
+        assert_eq!(batch, Processing::DocumentAdditions(vec![1]));
+
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_queue_snapshot() {
+        let mut queue = TaskQueue::default();
+        queue.insert(gen_task(0, gen_doc_addition_task_content("test1")));
+        queue.insert(gen_task(1, TaskContent::Dump { uid: "dump".to_string(), include_pending_tasks: false }));
+        queue.insert(gen_task(2, gen_doc_addition_task_content("test2")));
+        queue.insert(gen_task(3, TaskContent::IndexDeletion { index_uid: IndexUid::new_unchecked("test1") }));
+
+        let queued = |uid, kind| QueuedTask { uid, kind };
+        assert_eq!(
+            queue.snapshot(),
+            vec![
+                QueuedTaskList { list: "index:test1".to_string(), tasks: vec![queued(0, "documentAddition"), queued(3, "indexUpdate")] },
+                QueuedTaskList { list: "dump".to_string(), tasks: vec![queued(1, "dump")] },
+                QueuedTaskList { list: "index:test2".to_string(), tasks: vec![queued(2, "documentAddition")] },
+            ]
+        );
+
+        // the batched tasks are not in the queue anymore, and the lists are reordered.
+        make_batch(&mut queue, &SchedulerConfig::default());
+        let lists = queue.snapshot();
+        assert_eq!(lists.len(), 3);
+        assert_eq!(lists[2].list, "index:test1");
+        assert_eq!(lists[2].tasks, vec![queued(3, "indexUpdate")]);
+    }
+
+    #[actix_rt::test]
+    async fn test_prepare_skips_tasks_missing_from_the_store() {
+        use milli::heed::types::{DecodeIgnore, OwnedType};
+        use milli::heed::{byteorder::BE, zerocopy::U32, EnvOpenOptions};
+
+        let (tmp, mut scheduler) = test_scheduler(SchedulerConfig {
+            enable_auto_batching: true,
+            ..Default::default()
+        });
+        let store = scheduler.store.clone();
+        // opening the environment of the store again with the same options hands it out.
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = options.open(tmp.path()).unwrap();
+        // the tasks disappear from the store after they were fetched into the queue, as if the
+        // tasks database was restored.
+        let delete_from_store = |id: TaskId| {
+            let tasks = env
+                .open_database::<OwnedType<U32<BE>>, DecodeIgnore>(Some("tasks"))
+                .unwrap()
+                .unwrap();
+            let mut wtxn = env.write_txn().unwrap();
+            assert!(tasks.delete(&mut wtxn, &U32::new(id)).unwrap());
+            wtxn.commit().unwrap();
+        };
+
+        for _ in 0..3 {
+            store
+                .register(gen_doc_addition_task_content("test"), None)
+                .await
+                .unwrap();
+        }
+        scheduler.fetch_pending_tasks().await.unwrap();
+        delete_from_store(0);
+
+        // the missing task is skipped, and the rest of the batch is processed.
+        let batch = scheduler.prepare().await.unwrap();
+        assert_eq!(batch.id, Some(1));
+        assert_eq!(batch.len(), 2);
+        assert_eq!(
+            scheduler.processing,
+            Processing::DocumentAdditions(vec![1, 2])
+        );
+        scheduler.finish(batch.id).unwrap();
+
+        store
+            .register(gen_doc_addition_task_content("test"), None)
+            .await
+            .unwrap();
+        scheduler.fetch_pending_tasks().await.unwrap();
+        delete_from_store(3);
+
+        // nothing remains to be processed.
+        let batch = scheduler.prepare().await.unwrap();
+        assert!(matches!(batch.content, BatchContent::Empty));
+        assert_eq!(scheduler.processing, Processing::Nothing);
+    }
+
+    #[actix_rt::test]
+    async fn test_force_reset_processing() {
+        let (_tmp, mut scheduler) = test_scheduler(SchedulerConfig {
+            enable_auto_batching: true,
+            stuck_batch_grace_period_sec: 3600,
+            ..Default::default()
+        });
+        let store = scheduler.store.clone();
+
+        assert!(matches!(
+            scheduler.force_reset_processing().await,
+            Err(TaskError::NoProcessingBatch)
+        ));
+
+        for _ in 0..2 {
+            store
+                .register(gen_doc_addition_task_content("test"), None)
+                .await
+                .unwrap();
+        }
+        scheduler.prepare().await.unwrap();
+        let abort = scheduler.batch_abort();
+
+        // the batch was just prepared, its heartbeat is fresh.
+        assert!(matches!(
+            scheduler.force_reset_processing().await,
+            Err(TaskError::ProcessingBatchAlive { .. })
+        ));
+        assert!(!abort.is_aborted());
+
+        scheduler.config.stuck_batch_grace_period_sec = 0;
+        let tasks = scheduler.force_reset_processing().await.unwrap();
+        assert_eq!(tasks.iter().map(|t| t.id).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(abort.is_aborted());
+        assert_eq!(scheduler.processing, Processing::Nothing);
+        for id in 0..2 {
+            let task = store.get_task(id, None).await.unwrap();
+            assert!(matches!(task.events.last(), Some(TaskEvent::Failed { .. })));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_batch_contract_violations_are_refused() {
+        let (_tmp, mut scheduler) = test_scheduler(SchedulerConfig {
+            enable_auto_batching: true,
+            ..Default::default()
+        });
+        let store = scheduler.store.clone();
+
+        for index_uid in ["test", "test", "other"] {
+            store
+                .register(gen_doc_addition_task_content(index_uid), None)
+                .await
+                .unwrap();
+        }
+
+        // nothing was prepared.
+        assert!(matches!(
+            scheduler.finish(None),
+            Err(TaskError::NoOutstandingBatch(None))
+        ));
+
+        let batch = scheduler.prepare().await.unwrap();
+        assert_eq!(batch.id, Some(0));
+        let abort = scheduler.batch_abort();
+
+        // the outstanding batch is left as it is, its tasks are not handed out a second time.
+        assert!(matches!(
+            scheduler.prepare().await,
+            Err(TaskError::BatchOutstanding(Some(0)))
+        ));
+        assert_eq!(
+            scheduler.processing,
+            Processing::DocumentAdditions(vec![0, 1])
+        );
+        assert!(matches!(
+            scheduler.finish(Some(2)),
+            Err(TaskError::WrongBatchFinished {
+                finished: Some(2),
+                outstanding: Some(0),
+            })
+        ));
+        assert_eq!(
+            scheduler.processing,
+            Processing::DocumentAdditions(vec![0, 1])
+        );
+        assert!(!abort.is_aborted());
+
+        scheduler.finish(Some(0)).unwrap();
+        assert_eq!(scheduler.processing, Processing::Nothing);
+        assert!(matches!(
+            scheduler.finish(Some(0)),
+            Err(TaskError::NoOutstandingBatch(Some(0)))
+        ));
+
+        let batch = scheduler.prepare().await.unwrap();
+        assert_eq!(batch.id, Some(2));
+        assert_eq!(scheduler.processing, Processing::DocumentAdditions(vec![2]));
+    }
+
+    #[actix_rt::test]
+    async fn test_poison_tasks_are_quarantined() {
+        let (_tmp, mut scheduler) = test_scheduler(SchedulerConfig {
+            quarantine_poison_tasks: true,
+            quarantine_after_failures: 2,
+            ..Default::default()
+        });
+        let store = scheduler.store.clone();
+
+        for index_uid in ["poison", "poison", "other"] {
+            store
+                .register(gen_doc_addition_task_content(index_uid), None)
+                .await
+                .unwrap();
+        }
+
+        let panicked = TaskError::BatchPanicked("poison".to_string());
+        let batch = scheduler.prepare().await.unwrap();
+        assert_eq!(batch.id, Some(0));
+        scheduler.give_up(batch.id, &panicked).await.unwrap();
+        assert!(!store.get_task(0, None).await.unwrap().is_finished());
+
+        // the failure of the environment interrupts the series of failures.
+        let batch = scheduler.prepare().await.unwrap();
+        assert_eq!(batch.id, Some(0));
+        let environment = TaskError::Internal("No space left on device".into());
+        scheduler.give_up(batch.id, &environment).await.unwrap();
+        assert!(!store.get_task(0, None).await.unwrap().is_finished());
+        assert_eq!(scheduler.quarantined_tasks(), 0);
+
+        for failures in 1..=2 {
+            let batch = scheduler.prepare().await.unwrap();
+            assert_eq!(batch.id, Some(0));
+            scheduler.give_up(batch.id, &panicked).await.unwrap();
+            assert_eq!(
+                store.get_task(0, None).await.unwrap().is_finished(),
+                failures == 2
+            );
+        }
+        assert_eq!(scheduler.quarantined_tasks(), 1);
+        let task = store.get_task(0, None).await.unwrap();
+        match task.events.last() {
+            Some(TaskEvent::Failed { error, .. }) => assert_eq!(
+                serde_json::to_value(error).unwrap()["code"],
+                "quarantined_after_repeated_failures"
+            ),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // the next tasks of the index are processed without the quarantined task.
+        let batch = scheduler.prepare().await.unwrap();
+        assert_eq!(batch.id, Some(1));
+        assert!(!store.get_task(1, None).await.unwrap().is_finished());
+    }
+
+    #[actix_rt::test]
+    async fn test_queued_jobs_are_bounded() {
+        let (tmp, mut scheduler) = test_scheduler(SchedulerConfig {
+            max_queued_jobs: 2,
+            ..Default::default()
+        });
+
+        let job = || {
+            let snapshot =
+                SnapshotJob::new(tmp.path().join("snapshots"), tmp.path().into(), 0, 0, false);
+            Job::Snapshot(snapshot)
+        };
+        scheduler.schedule_job(job()).unwrap();
+        scheduler.schedule_job(job()).unwrap();
+        assert!(matches!(
+            scheduler.schedule_job(job()),
+            Err(TaskError::TooManyQueuedJobs {
+                kind: "snapshot",
+                max: 2
+            })
+        ));
+
+        let queued = scheduler.queued_jobs();
+        assert_eq!(queued.len(), 2);
+        assert!(queued.iter().all(|job| job.kind == "snapshot"));
+        assert!(queued[0].enqueued_at <= queued[1].enqueued_at);
+
+        // a job processed makes room for the next one.
+        let batch = scheduler.prepare().await.unwrap();
+        assert!(matches!(batch.content, BatchContent::Snapshot(_)));
+        scheduler.schedule_job(job()).unwrap();
+        assert_eq!(scheduler.queue_snapshot().jobs.len(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_scheduled_tasks_are_delayed_until_due() {
+        let (_tmp, mut scheduler) = test_scheduler(SchedulerConfig {
+            enable_auto_batching: true,
+            ..Default::default()
+        });
+        let store = scheduler.store.clone();
+
+        let at = OffsetDateTime::now_utc() + time::Duration::hours(1);
+        let settings = TaskContent::SettingsUpdate {
+            settings: Default::default(),
+            is_deletion: false,
+            allow_index_creation: true,
+            scheduled_at: Some(at),
+            warnings: Vec::new(),
+            index_uid: IndexUid::new_unchecked("test"),
+        };
+        store.register(settings, None).await.unwrap();
+        store
+            .register(gen_doc_addition_task_content("test"), None)
+            .await
+            .unwrap();
+
+        // the settings update is not due yet, the addition registered after it goes first.
+        let batch = scheduler.prepare().await.unwrap();
+        assert_eq!(scheduler.processing, Processing::DocumentAdditions(vec![1]));
+        assert_eq!(scheduler.next_scheduled_at(), Some(at));
+        scheduler.finish(batch.id).unwrap();
+
+        let batch = scheduler.prepare().await.unwrap();
+        assert!(matches!(batch.content, BatchContent::Empty));
+        scheduler.finish(batch.id).unwrap();
+
+        // the settings update becomes due.
+        match scheduler.delayed[0].content {
+            TaskContent::SettingsUpdate {
+                ref mut scheduled_at,
+                ..
+            } => *scheduled_at = Some(OffsetDateTime::now_utc()),
+            _ => unreachable!(),
+        }
+        scheduler.prepare().await.unwrap();
+        assert_eq!(scheduler.processing, Processing::IndexUpdate(0));
+        assert_eq!(scheduler.next_scheduled_at(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_every_registered_task_is_processed() {
+        use milli::heed::EnvOpenOptions;
+
+        use crate::tasks::{EmptyBatchHandler, MockBatchHandler};
+
+        const REGISTERING_TASKS: u32 = 4;
+        const TASKS_PER_REGISTERING_TASK: u32 = 10;
+
+        // the debounce is virtual, the clock advances whenever the runtime is idle.
+        tokio::time::pause();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = Arc::new(options.open(tmp.path()).unwrap());
+        let store = TaskStore::new(env).unwrap();
+
+        let mut handler = MockBatchHandler::new();
+        handler
+            .expect_accept()
+            .returning(|batch| matches!(batch.content, BatchContent::IndexUpdate(_)));
+        handler.expect_process_batch().returning(|mut batch| {
+            batch
+                .content
+                .push_event(TaskEvent::succeeded(TaskResult::Other));
+            batch
+        });
+        handler.expect_finish().returning(|_| ());
+
+        let config = SchedulerConfig {
+            debounce_duration_sec: Some(3600),
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(
+            store.clone(),
+            vec![Arc::new(handler), Arc::new(EmptyBatchHandler)],
+            config,
+            TaskProgresses::default(),
+            ClosedIndexes::default(),
+            IndexLocks::default(),
+            PendingCreations::default(),
+        )
+        .unwrap();
+
+        // the tasks are registered while the update loop is waiting, debouncing, or processing.
+        let mut registrations = Vec::new();
+        for i in 0..REGISTERING_TASKS {
+            let store = store.clone();
+            let scheduler = scheduler.clone();
+            registrations.push(tokio::task::spawn_local(async move {
+                for _ in 0..TASKS_PER_REGISTERING_TASK {
+                    let content = TaskContent::IndexDeletion {
+                        index_uid: IndexUid::new_unchecked(format!("test{}", i)),
+                    };
+                    store.register(content, None).await.unwrap();
+                    scheduler.read().await.notify();
+                    tokio::task::yield_now().await;
+                }
+            }));
+        }
+        for registration in registrations {
+            registration.await.unwrap();
+        }
+
+        let total = REGISTERING_TASKS * TASKS_PER_REGISTERING_TASK;
+        let all_processed = async {
+            loop {
+                let mut processed = 0;
+                for id in 0..total {
+                    if store.get_task(id, None).await.unwrap().is_finished() {
+                        processed += 1;
+                    }
+                }
+                if processed == total {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_secs(3600)).await;
+            }
+        };
+        // every batch waits for one debounce, the margin leaves room for the clock advancing while
+        // the store is busy.
+        let deadline = Duration::from_secs(2 * 3600 * (total as u64 + 1));
+        tokio::time::timeout(deadline, all_processed)
+            .await
+            .expect("some tasks were never processed");
+    }
+
+    #[actix_rt::test]
+    async fn test_old_tasks_are_batched_without_waiting_for_the_debounce() {
+        use std::sync::Mutex;
+
+        use milli::heed::EnvOpenOptions;
+
+        use crate::tasks::{EmptyBatchHandler, MockBatchHandler};
+
+        // the clock is virtual, it advances whenever the runtime is idle.
+        tokio::time::pause();
+        let start = tokio::time::Instant::now();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = Arc::new(options.open(tmp.path()).unwrap());
+        let store = TaskStore::new(env).unwrap();
+
+        let processed_at = Arc::new(Mutex::new(None));
+        let mut handler = MockBatchHandler::new();
+        handler
+            .expect_accept()
+            .returning(|batch| matches!(batch.content, BatchContent::IndexUpdate(_)));
+        let processed = processed_at.clone();
+        handler.expect_process_batch().returning(move |mut batch| {
+            processed
+                .lock()
+                .unwrap()
+                .get_or_insert(tokio::time::Instant::now());
+            batch
+                .content
+                .push_event(TaskEvent::succeeded(TaskResult::Other));
+            batch
+        });
+        handler.expect_finish().returning(|_| ());
+
+        let config = SchedulerConfig {
+            debounce_duration_sec: Some(3600),
+            max_task_age_before_batch_sec: Some(1),
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(
+            store.clone(),
+            vec![Arc::new(handler), Arc::new(EmptyBatchHandler)],
+            config,
+            TaskProgresses::default(),
+            ClosedIndexes::default(),
+            IndexLocks::default(),
+            PendingCreations::default(),
+        )
+        .unwrap();
+
+        // a lonely task, never followed by another notification.
+        let content = TaskContent::IndexDeletion {
+            index_uid: IndexUid::new_unchecked("test"),
+        };
+        store.register(content, None).await.unwrap();
+        scheduler.read().await.notify();
+
+        for _ in 0..600 {
+            if store.get_task(0, None).await.unwrap().is_finished() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+
+        let processed_at = processed_at
+            .lock()
+            .unwrap()
+            .expect("the task was never processed");
+        // the debounce would have kept the task waiting for an hour.
+        assert!(processed_at - start < Duration::from_secs(600));
+    }
+
+    #[actix_rt::test]
+    async fn test_wait_for_tasks() {
+        use milli::heed::EnvOpenOptions;
+
+        use crate::tasks::{EmptyBatchHandler, MockBatchHandler};
+
+        // the clock is virtual, it advances whenever the runtime is idle.
+        tokio::time::pause();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = Arc::new(options.open(tmp.path()).unwrap());
+        let store = TaskStore::new(env).unwrap();
+
+        // the deletions of the `failing` index fail, the other ones succeed.
+        let mut handler = MockBatchHandler::new();
+        handler
+            .expect_accept()
+            .returning(|batch| matches!(batch.content, BatchContent::IndexUpdate(_)));
+        handler.expect_process_batch().returning(|mut batch| {
+            let event = match batch.content {
+                BatchContent::IndexUpdate(ref task) if task.index_uid() == Some("failing") => {
+                    TaskEvent::failed(TaskError::UnexistingTask(task.id).into())
+                }
+                _ => TaskEvent::succeeded(TaskResult::Other),
+            };
+            batch.content.push_event(event);
+            batch
+        });
+        handler.expect_finish().returning(|_| ());
+
+        let scheduler = Scheduler::new(
+            store.clone(),
+            vec![Arc::new(handler), Arc::new(EmptyBatchHandler)],
+            SchedulerConfig::default(),
+            TaskProgresses::default(),
+            ClosedIndexes::default(),
+            IndexLocks::default(),
+            PendingCreations::default(),
+        )
+        .unwrap();
+
+        for index_uid in ["test", "failing"] {
+            let content = TaskContent::IndexDeletion {
+                index_uid: IndexUid::new_unchecked(index_uid),
+            };
+            store.register(content, None).await.unwrap();
+        }
+        scheduler.read().await.notify();
+
+        let wait = scheduler.read().await.wait_for_task(0, None);
+        let task = wait.await.unwrap();
+        assert!(matches!(
+            task.events.last(),
+            Some(TaskEvent::Succeeded { .. })
+        ));
+
+        let ids = [1, 0];
+        let wait = scheduler
+            .read()
+            .await
+            .wait_for_tasks(&ids, Some(Duration::from_secs(60)));
+        let tasks = wait.await.unwrap();
+        assert_eq!(tasks.iter().map(|t| t.id).collect::<Vec<_>>(), vec![1, 0]);
+        assert!(matches!(
+            tasks[0].events.last(),
+            Some(TaskEvent::Failed { .. })
+        ));
+        assert!(matches!(
+            tasks[1].events.last(),
+            Some(TaskEvent::Succeeded { .. })
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_subscribe_to_task_events() {
+        use futures::StreamExt;
+        use milli::heed::EnvOpenOptions;
+
+        use crate::tasks::events::{TaskLifecycleEvent, TaskOutcome};
+        use crate::tasks::{EmptyBatchHandler, MockBatchHandler};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = Arc::new(options.open(tmp.path()).unwrap());
+        let store = TaskStore::new(env).unwrap();
+
+        // the deletions of the `failing` index fail, the other ones succeed.
+        let mut handler = MockBatchHandler::new();
+        handler
+            .expect_accept()
+            .returning(|batch| matches!(batch.content, BatchContent::IndexUpdate(_)));
+        handler.expect_process_batch().returning(|mut batch| {
+            let event = match batch.content {
+                BatchContent::IndexUpdate(ref task) if task.index_uid() == Some("failing") => {
+                    TaskEvent::failed(TaskError::UnexistingTask(task.id).into())
+                }
+                _ => TaskEvent::succeeded(TaskResult::Other),
+            };
+            batch.content.push_event(event);
+            batch
+        });
+        handler.expect_finish().returning(|_| ());
+
+        let scheduler = Scheduler::new(
+            store.clone(),
+            vec![Arc::new(handler), Arc::new(EmptyBatchHandler)],
+            SchedulerConfig::default(),
+            TaskProgresses::default(),
+            ClosedIndexes::default(),
+            IndexLocks::default(),
+            PendingCreations::default(),
+        )
+        .unwrap();
+        let mut events = scheduler.read().await.subscribe();
+
+        for index_uid in ["test", "failing"] {
+            let content = TaskContent::IndexDeletion {
+                index_uid: IndexUid::new_unchecked(index_uid),
+            };
+            let task = store.register(content, None).await.unwrap();
+            scheduler.read().await.announce_registered(&task);
+        }
+        scheduler.read().await.notify();
+
+        let mut received = Vec::new();
+        while received.len() < 8 {
+            received.push(events.next().await.unwrap());
+        }
+        assert_eq!(
+            received,
+            vec![
+                TaskLifecycleEvent::Registered { task_id: 0 },
+                TaskLifecycleEvent::Registered { task_id: 1 },
+                TaskLifecycleEvent::Batched {
+                    task_id: 0,
+                    batch_id: 0
+                },
+                TaskLifecycleEvent::Processing { task_id: 0 },
+                TaskLifecycleEvent::Finished {
+                    task_id: 0,
+                    outcome: TaskOutcome::Succeeded
+                },
+                TaskLifecycleEvent::Batched {
+                    task_id: 1,
+                    batch_id: 1
+                },
+                TaskLifecycleEvent::Processing { task_id: 1 },
+                TaskLifecycleEvent::Finished {
+                    task_id: 1,
+                    outcome: TaskOutcome::Failed
+                },
+            ]
+        );
+
+        // every event is announced once the task is saved in the store.
+        let task = store.get_task(1, None).await.unwrap();
+        assert!(task.is_finished());
+    }
+
+    #[actix_rt::test]
+    async fn test_wait_for_task_timeout() {
+        use milli::heed::EnvOpenOptions;
+
+        use crate::tasks::{EmptyBatchHandler, MockBatchHandler};
+
+        tokio::time::pause();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = Arc::new(options.open(tmp.path()).unwrap());
+        let store = TaskStore::new(env).unwrap();
+
+        let mut handler = MockBatchHandler::new();
+        handler
+            .expect_accept()
+            .returning(|batch| matches!(batch.content, BatchContent::IndexUpdate(_)));
+        handler.expect_process_batch().returning(|mut batch| {
+            batch
+                .content
+                .push_event(TaskEvent::succeeded(TaskResult::Other));
+            batch
+        });
+        handler.expect_finish().returning(|_| ());
+
+        // the task waits for the debounce long after the wait timed out.
+        let config = SchedulerConfig {
+            debounce_duration_sec: Some(3600),
+            ..Default::default()
+        };
+        let scheduler = Scheduler::new(
+            store.clone(),
+            vec![Arc::new(handler), Arc::new(EmptyBatchHandler)],
+            config,
+            TaskProgresses::default(),
+            ClosedIndexes::default(),
+            IndexLocks::default(),
+            PendingCreations::default(),
+        )
+        .unwrap();
+
+        let content = TaskContent::IndexDeletion {
+            index_uid: IndexUid::new_unchecked("test"),
+        };
+        store.register(content, None).await.unwrap();
+        scheduler.read().await.notify();
+
+        let wait = scheduler
+            .read()
+            .await
+            .wait_for_task(0, Some(Duration::from_secs(1)));
+        match wait.await {
+            Err(TaskError::WaitTimeout(task)) => {
+                assert_eq!(task.id, 0);
+                assert!(!task.is_finished());
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use meilisearch_error::ResponseError;
 use milli::update::{DocumentAdditionResult, IndexDocumentsMethod};
 use serde::{Deserialize, Serialize};
@@ -5,17 +7,86 @@ use time::OffsetDateTime;
 use uuid::Uuid;
 
 use super::batch::BatchId;
-use crate::index::{Settings, Unchecked};
+use crate::index::{
+    DocumentEdit, DocumentsDiff, DocumentsDiffMode, IndexCheckReport, Settings, SettingsPreview,
+    StatsRepairReport, TransformsResult, Unchecked,
+};
+use crate::index_controller::DocumentAdditionFormat;
 use crate::index_resolver::IndexUid;
+use crate::throttle::ThrottleReport;
+use crate::update_file_store::UpdateFileDigest;
 
 pub type TaskId = u32;
 
+/// The version of this engine, recorded in the tasks it registers and in the events ending the
+/// tasks it processes.
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub enum TaskResult {
-    DocumentAddition { indexed_documents: u64 },
-    DocumentDeletion { deleted_documents: u64 },
-    ClearAll { deleted_documents: u64 },
+    DocumentAddition {
+        indexed_documents: u64,
+        /// The number of documents of the payload merged with another document with the same
+        /// primary key.
+        #[serde(default)]
+        duplicate_documents: u64,
+        /// The created and updated documents, when the addition asked for them.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        diff: Option<DocumentsDiff>,
+        /// What the document transforms of the index did, when it has any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transforms: Option<TransformsResult>,
+    },
+    DocumentDeletion {
+        deleted_documents: u64,
+    },
+    ClearAll {
+        deleted_documents: u64,
+    },
+    IndexCheck {
+        report: IndexCheckReport,
+    },
+    StatsRepair {
+        report: StatsRepairReport,
+    },
+    /// The task was completed without being processed, because a later task made it useless.
+    Superseded {
+        superseded_by: TaskId,
+    },
+    NamespaceDeletion {
+        deleted_indexes: u64,
+        deleted_documents: u64,
+    },
+    IndexRebuild {
+        rebuilt_documents: u64,
+    },
+    SettingsPreview {
+        preview: SettingsPreview,
+    },
+    /// The result of the index creations that tolerate an existing index.
+    IndexCreation {
+        already_existed: bool,
+    },
+    SettingsUpdate {
+        /// The number of documents of the index when the settings were applied, if the settings
+        /// reindex the documents, 0 otherwise.
+        reindexed_documents: u64,
+    },
+    DocumentEdition {
+        matched_documents: u64,
+        edited_documents: u64,
+        /// The number of matching documents left untouched because an edit didn't apply to them.
+        invalid_documents: u64,
+    },
+    TaskStoreCompaction {
+        /// The size the tasks database file shrank by.
+        reclaimed_bytes: u64,
+    },
+    Dump {
+        /// What the IO throttle did to the writes of the archive.
+        throttle: ThrottleReport,
+    },
     Other,
 }
 
@@ -23,6 +94,9 @@ impl From<DocumentAdditionResult> for TaskResult {
     fn from(other: DocumentAdditionResult) -> Self {
         Self::DocumentAddition {
             indexed_documents: other.indexed_documents,
+            duplicate_documents: 0,
+            diff: None,
+            transforms: None,
         }
     }
 }
@@ -51,27 +125,69 @@ pub enum TaskEvent {
         #[cfg_attr(test, proptest(strategy = "test::datetime_strategy()"))]
         #[serde(with = "time::serde::rfc3339")]
         timestamp: OffsetDateTime,
+        /// The version of the engine that processed the task, `None` for the tasks processed
+        /// before it was recorded.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        engine_version: Option<String>,
     },
     Failed {
         error: ResponseError,
         #[cfg_attr(test, proptest(strategy = "test::datetime_strategy()"))]
         #[serde(with = "time::serde::rfc3339")]
         timestamp: OffsetDateTime,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        engine_version: Option<String>,
+    },
+    /// A chunk of a document addition split into chunks was indexed and committed, the task goes
+    /// on with its next chunk. The last chunk completes the task instead.
+    ChunkProcessed {
+        chunk: usize,
+        indexed_documents: u64,
+        duplicate_documents: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        diff: Option<DocumentsDiff>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transforms: Option<TransformsResult>,
+        #[cfg_attr(test, proptest(strategy = "test::datetime_strategy()"))]
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
     },
 }
 
 impl TaskEvent {
     pub fn succeeded(result: TaskResult) -> Self {
+        Self::succeeded_at(result, OffsetDateTime::now_utc())
+    }
+
+    pub fn failed(error: ResponseError) -> Self {
+        Self::failed_at(error, OffsetDateTime::now_utc())
+    }
+
+    /// A task processed by this engine succeeded at `timestamp`.
+    pub fn succeeded_at(result: TaskResult, timestamp: OffsetDateTime) -> Self {
         Self::Succeeded {
             result,
-            timestamp: OffsetDateTime::now_utc(),
+            timestamp,
+            engine_version: Some(ENGINE_VERSION.to_string()),
         }
     }
 
-    pub fn failed(error: ResponseError) -> Self {
+    /// A task processed by this engine failed at `timestamp`.
+    pub fn failed_at(error: ResponseError, timestamp: OffsetDateTime) -> Self {
         Self::Failed {
             error,
-            timestamp: OffsetDateTime::now_utc(),
+            timestamp,
+            engine_version: Some(ENGINE_VERSION.to_string()),
+        }
+    }
+
+    /// The version of the engine that ended the task with this event.
+    pub fn engine_version(&self) -> Option<&str> {
+        match self {
+            Self::Succeeded { engine_version, .. } | Self::Failed { engine_version, .. } => {
+                engine_version.as_deref()
+            }
+            _ => None,
         }
     }
 }
@@ -84,12 +200,50 @@ impl TaskEvent {
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct Task {
     pub id: TaskId,
+    /// The uuid of the index targeted by the task, if it existed when the task was registered.
+    /// It tells apart the tasks of an index from the tasks of a deleted index with the same uid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, proptest(value = "None"))]
+    pub index_uuid: Option<Uuid>,
     /// The name of the index the task is targeting. If it isn't targeting any index (i.e Dump task)
     /// then this is None
     // TODO: when next forward breaking dumps, it would be a good idea to move this field inside of
     // the TaskContent.
     pub content: TaskContent,
     pub events: Vec<TaskEvent>,
+    /// Who registered the task, if it was registered by a request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, proptest(value = "None"))]
+    pub origin: Option<TaskOrigin>,
+    /// The version of the engine that registered the task, `None` for the tasks registered before
+    /// it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, proptest(value = "None"))]
+    pub engine_version: Option<String>,
+}
+
+/// A pending task referencing attributes that a settings update makes unfilterable. The task
+/// fails if it is processed after the update.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsWarning {
+    pub task_uid: TaskId,
+    pub attributes: BTreeSet<String>,
+}
+
+/// The request that registered a task.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskOrigin {
+    /// The uid of the API key of the request, `None` for the master key or when no master key is
+    /// set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_uid: Option<Uuid>,
+    /// The IP address of the client, read from the trusted proxy header when one is configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
 }
 
 impl Task {
@@ -104,26 +258,111 @@ impl Task {
         })
     }
 
-    /// Return the content_uuid of the `Task` if there is one.
-    pub fn get_content_uuid(&self) -> Option<Uuid> {
+    /// The date the task was registered at.
+    pub fn enqueued_at(&self) -> Option<OffsetDateTime> {
+        match self.events.first() {
+            Some(TaskEvent::Created(timestamp)) => Some(*timestamp),
+            _ => None,
+        }
+    }
+
+    /// The date the task must not be processed before, if it was scheduled.
+    pub fn scheduled_at(&self) -> Option<OffsetDateTime> {
+        match self.content {
+            TaskContent::SettingsUpdate { scheduled_at, .. } => scheduled_at,
+            _ => None,
+        }
+    }
+
+    /// Return the uuids of the update files of the `Task`, in the order they are processed. A
+    /// document addition split into chunks has an update file per chunk.
+    pub fn content_uuids(&self) -> Vec<Uuid> {
         match self {
             Task {
-                content: TaskContent::DocumentAddition { content_uuid, .. },
+                content:
+                    TaskContent::DocumentAddition {
+                        content_uuid,
+                        chunks,
+                        ..
+                    },
                 ..
-            } => Some(*content_uuid),
-            _ => None,
+            } => std::iter::once(*content_uuid)
+                .chain(chunks.iter().copied())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Replaces the update file `from` of the `Task` with the update file `to`.
+    pub fn replace_content_uuid(&mut self, from: Uuid, to: Uuid) {
+        if let TaskContent::DocumentAddition {
+            content_uuid,
+            chunks,
+            ..
+        } = &mut self.content
+        {
+            std::iter::once(content_uuid)
+                .chain(chunks.iter_mut())
+                .filter(|uuid| **uuid == from)
+                .for_each(|uuid| *uuid = to);
         }
     }
 
+    /// Return the uuids of the update files of the chunks that were not processed yet.
+    pub fn pending_content_uuids(&self) -> Vec<Uuid> {
+        let mut uuids = self.content_uuids();
+        uuids.drain(..self.processed_chunks().min(uuids.len()));
+        uuids
+    }
+
+    /// Return the uuids of the update files handled by the last batch of the task: the ones of the
+    /// chunk it indexed, or all the remaining ones once the task is finished.
+    pub fn batch_content_uuids(&self) -> Vec<Uuid> {
+        if self.is_finished() {
+            self.pending_content_uuids()
+        } else {
+            let chunk = self.processed_chunks().checked_sub(1);
+            let uuids = self.content_uuids();
+            chunk
+                .and_then(|chunk| uuids.get(chunk).copied())
+                .into_iter()
+                .collect()
+        }
+    }
+
+    /// Return the number of chunks of a document addition split into chunks that were already
+    /// indexed and committed.
+    pub fn processed_chunks(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, TaskEvent::ChunkProcessed { .. }))
+            .count()
+    }
+
     pub fn index_uid(&self) -> Option<&str> {
-        match &self.content {
+        self.content.index_uid()
+    }
+}
+
+impl TaskContent {
+    pub fn index_uid(&self) -> Option<&str> {
+        match self {
             TaskContent::DocumentAddition { index_uid, .. }
             | TaskContent::DocumentDeletion { index_uid, .. }
             | TaskContent::SettingsUpdate { index_uid, .. }
             | TaskContent::IndexDeletion { index_uid }
             | TaskContent::IndexCreation { index_uid, .. }
-            | TaskContent::IndexUpdate { index_uid, .. } => Some(index_uid.as_str()),
-            TaskContent::Dump { .. } => None,
+            | TaskContent::IndexUpdate { index_uid, .. }
+            | TaskContent::DocumentAdditionFromUrl { index_uid, .. }
+            | TaskContent::DocumentsCompaction { index_uid }
+            | TaskContent::IndexCheck { index_uid, .. }
+            | TaskContent::StatsRepair { index_uid }
+            | TaskContent::IndexRebuild { index_uid }
+            | TaskContent::SettingsPreview { index_uid, .. }
+            | TaskContent::DocumentEdition { index_uid, .. } => Some(index_uid.as_str()),
+            TaskContent::Dump { .. }
+            | TaskContent::NamespaceDeletion { .. }
+            | TaskContent::TaskStoreCompaction => None,
         }
     }
 }
@@ -148,6 +387,32 @@ pub enum TaskContent {
         primary_key: Option<String>,
         documents_count: usize,
         allow_index_creation: bool,
+        /// Whether the task must fail, rather than deduplicate the documents, when several
+        /// documents of the payload have the same primary key.
+        #[serde(default)]
+        reject_duplicates: bool,
+        /// The update files of the chunks following the first one, whose update file is
+        /// `content_uuid`, when the payload was split into chunks. Each chunk is indexed by its own
+        /// batch, the duplicates are only looked for within a chunk.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        #[cfg_attr(test, proptest(value = "Vec::new()"))]
+        chunks: Vec<Uuid>,
+        /// Whether the created and updated documents are recorded in the result of the task.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        return_diff: Option<DocumentsDiffMode>,
+        /// The digests of the update files when they were persisted, checked before they are
+        /// indexed. The tasks registered before the digests were recorded have none.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        #[cfg_attr(test, proptest(value = "BTreeMap::new()"))]
+        content_digests: BTreeMap<Uuid, UpdateFileDigest>,
+        /// The number of invalid characters of the payload that were replaced by U+FFFD, when the
+        /// addition asked for it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sanitized_characters: Option<u64>,
+        /// The number of documents of the payload exceeding the soft limits, when soft limits
+        /// were set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        warnings: Option<u64>,
     },
     DocumentDeletion {
         index_uid: IndexUid,
@@ -159,6 +424,19 @@ pub enum TaskContent {
         /// Indicates whether the task was a deletion
         is_deletion: bool,
         allow_index_creation: bool,
+        /// The task isn't processed before this date.
+        #[serde(
+            default,
+            with = "time::serde::rfc3339::option",
+            skip_serializing_if = "Option::is_none"
+        )]
+        #[cfg_attr(test, proptest(value = "None"))]
+        scheduled_at: Option<OffsetDateTime>,
+        /// The pending tasks that reference attributes the update makes unfilterable, found when
+        /// the task was registered.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        #[cfg_attr(test, proptest(value = "Vec::new()"))]
+        warnings: Vec<SettingsWarning>,
     },
     IndexDeletion {
         index_uid: IndexUid,
@@ -166,14 +444,93 @@ pub enum TaskContent {
     IndexCreation {
         index_uid: IndexUid,
         primary_key: Option<String>,
+        #[serde(default)]
+        name: Option<String>,
+        /// The namespace the index is created in, the default namespace if `None`.
+        #[serde(default)]
+        namespace: Option<String>,
+        /// Whether the task succeeds without doing anything when the index already exists,
+        /// instead of failing.
+        #[serde(default)]
+        if_not_exists: bool,
+        /// Whether the index opts out of the index templates.
+        #[serde(default)]
+        skip_templates: bool,
     },
     IndexUpdate {
         index_uid: IndexUid,
         primary_key: Option<String>,
+        /// The new name of the index, left unchanged if `None`.
+        #[serde(default)]
+        name: Option<String>,
+        /// Opts the index out of the index templates, or back in. Left unchanged if `None`.
+        #[serde(default)]
+        skip_templates: Option<bool>,
     },
     Dump {
         uid: String,
+        /// Whether the unfinished tasks and their update files are dumped too, so that the
+        /// restored instance processes them.
+        #[serde(default)]
+        include_pending_tasks: bool,
     },
+    /// A document addition whose documents are downloaded from `url` when the task is processed.
+    DocumentAdditionFromUrl {
+        index_uid: IndexUid,
+        url: String,
+        format: DocumentAdditionFormat,
+        /// The expected sha256 of the downloaded file, hex encoded.
+        checksum: Option<String>,
+        #[cfg_attr(test, proptest(strategy = "test::index_document_method_strategy()"))]
+        merge_strategy: IndexDocumentsMethod,
+        primary_key: Option<String>,
+        allow_index_creation: bool,
+    },
+    /// Purges the soft deleted documents of an index. Registered by the engine itself.
+    DocumentsCompaction {
+        index_uid: IndexUid,
+    },
+    /// Checks the integrity of an index, without modifying it. In `fast` mode, only a sample of
+    /// each database is checked.
+    IndexCheck {
+        index_uid: IndexUid,
+        fast: bool,
+    },
+    /// Counts the stats of an index again from its documents and facets, and replaces the stored
+    /// ones if they are wrong.
+    StatsRepair {
+        index_uid: IndexUid,
+    },
+    /// Indexes the documents of an index again, with its current settings, in a new index that
+    /// then replaces it.
+    IndexRebuild {
+        index_uid: IndexUid,
+    },
+    /// Runs `queries` on a sample of `sample_size` documents of an index, with its current settings
+    /// and with these settings updated with `settings`, in a shadow index. The settings of the
+    /// index are left untouched.
+    SettingsPreview {
+        index_uid: IndexUid,
+        settings: Settings<Unchecked>,
+        queries: Vec<String>,
+        sample_size: usize,
+        limit: usize,
+    },
+    /// Deletes all the indexes of a namespace, then the namespace itself.
+    NamespaceDeletion {
+        namespace: String,
+    },
+    /// Applies `edits` to the documents matching `filter`, all the documents if `None`.
+    DocumentEdition {
+        index_uid: IndexUid,
+        #[cfg_attr(test, proptest(value = "None"))]
+        filter: Option<serde_json::Value>,
+        #[cfg_attr(test, proptest(value = "Vec::new()"))]
+        edits: Vec<DocumentEdit>,
+    },
+    /// Replaces the tasks database with a compacted copy of itself, to give the pages freed by
+    /// the deleted tasks and indexes back to the disk.
+    TaskStoreCompaction,
 }
 
 #[cfg(test)]
@@ -192,4 +549,29 @@ mod test {
     pub(super) fn datetime_strategy() -> impl Strategy<Value = OffsetDateTime> {
         Just(OffsetDateTime::now_utc())
     }
+
+    #[test]
+    fn test_failed_event_keeps_the_error_chain() {
+        use crate::index_resolver::error::IndexResolverError;
+
+        #[derive(Debug, thiserror::Error)]
+        #[error("could not write the documents database")]
+        struct WriteError(#[source] std::io::Error);
+
+        let io_error = std::io::Error::new(std::io::ErrorKind::Other, "no space left on device");
+        let error = IndexResolverError::ChunkFailed {
+            chunk: 1,
+            chunks: 3,
+            source: Box::new(IndexResolverError::Internal(Box::new(WriteError(io_error)))),
+        };
+
+        let event = TaskEvent::failed(error.into());
+        let event = serde_json::to_value(&event).unwrap();
+        assert_eq!(event["Failed"]["error"]["code"], "internal");
+        // the messages already part of the message of the error are not repeated.
+        assert_eq!(
+            event["Failed"]["error"]["causes"],
+            serde_json::json!(["no space left on device"])
+        );
+    }
 }
@@ -0,0 +1,190 @@
+//! On-disk encoding of the tasks.
+//!
+//! A task is stored as a version byte followed by the JSON serialization of the task in this
+//! version of the format. The tasks written before the format was versioned are bare JSON
+//! objects: they are version 0, their first byte, `{`, can't be mistaken for a version.
+//!
+//! Decoding a task written in an older version upgrades its JSON, one version after the other,
+//! up to the current version before deserializing it. Changing the serialization of a `Task` in
+//! a way the current version can't deserialize requires bumping `CURRENT_VERSION` and adding the
+//! upgrade from the previous version to `UPGRADES`. The `corpus` directory holds tasks written in
+//! each version, they must all stay decodable.
+
+use std::borrow::Cow;
+
+use milli::heed::{BytesDecode, BytesEncode};
+use serde_json::Value;
+
+use crate::tasks::task::Task;
+
+/// The version of the format the tasks are written in.
+pub const CURRENT_VERSION: u8 = 2;
+
+/// `UPGRADES[v]` upgrades the JSON of a task written in version `v` to version `v + 1`.
+const UPGRADES: [fn(&mut Value); CURRENT_VERSION as usize] = [upgrade_from_v0, upgrade_from_v1];
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("the task is empty")]
+    Empty,
+    #[error("the task is written in the unknown format version {0}")]
+    UnknownVersion(u8),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// The tasks written before the format was versioned are laid out like in version 1, the fields
+/// added since then have default values.
+fn upgrade_from_v0(_task: &mut Value) {}
+
+/// Version 2 added the chunks of the document additions, and the `ChunkProcessed` event. The
+/// tasks written in version 1 have neither.
+fn upgrade_from_v1(_task: &mut Value) {}
+
+pub fn decode(bytes: &[u8]) -> Result<Task, DecodeError> {
+    let (version, json) = match bytes.split_first() {
+        Some((b'{', _)) => (0, bytes),
+        Some((&version, json)) => (version, json),
+        None => return Err(DecodeError::Empty),
+    };
+
+    if version == CURRENT_VERSION {
+        Ok(serde_json::from_slice(json)?)
+    } else if version < CURRENT_VERSION {
+        let mut task: Value = serde_json::from_slice(json)?;
+        for upgrade in &UPGRADES[version as usize..] {
+            upgrade(&mut task);
+        }
+        Ok(serde_json::from_value(task)?)
+    } else {
+        Err(DecodeError::UnknownVersion(version))
+    }
+}
+
+pub struct TaskCodec;
+
+impl<'a> BytesEncode<'a> for TaskCodec {
+    type EItem = Task;
+
+    fn bytes_encode(task: &Self::EItem) -> Option<Cow<[u8]>> {
+        let mut bytes = vec![CURRENT_VERSION];
+        serde_json::to_writer(&mut bytes, task).ok()?;
+        Some(Cow::Owned(bytes))
+    }
+}
+
+impl<'a> BytesDecode<'a> for TaskCodec {
+    type DItem = Task;
+
+    fn bytes_decode(bytes: &'a [u8]) -> Option<Self::DItem> {
+        decode(bytes)
+            .map_err(|e| log::error!("Could not decode a task: {}.", e))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Tasks written in each version of the format.
+    const CORPUS: &[(&str, &[u8])] = &[
+        (
+            "v0/document_addition",
+            include_bytes!("corpus/v0/document_addition.task"),
+        ),
+        ("v0/dump", include_bytes!("corpus/v0/dump.task")),
+        (
+            "v0/index_creation",
+            include_bytes!("corpus/v0/index_creation.task"),
+        ),
+        (
+            "v0/settings_update",
+            include_bytes!("corpus/v0/settings_update.task"),
+        ),
+        (
+            "v1/clear_documents",
+            include_bytes!("corpus/v1/clear_documents.task"),
+        ),
+        (
+            "v1/document_addition_from_url",
+            include_bytes!("corpus/v1/document_addition_from_url.task"),
+        ),
+        (
+            "v1/document_deletion",
+            include_bytes!("corpus/v1/document_deletion.task"),
+        ),
+        (
+            "v1/index_check",
+            include_bytes!("corpus/v1/index_check.task"),
+        ),
+        (
+            "v1/index_rebuild",
+            include_bytes!("corpus/v1/index_rebuild.task"),
+        ),
+        (
+            "v1/namespace_deletion",
+            include_bytes!("corpus/v1/namespace_deletion.task"),
+        ),
+        (
+            "v2/chunked_document_addition",
+            include_bytes!("corpus/v2/chunked_document_addition.task"),
+        ),
+        (
+            "v2/document_edition",
+            include_bytes!("corpus/v2/document_edition.task"),
+        ),
+        (
+            "v2/stats_repair",
+            include_bytes!("corpus/v2/stats_repair.task"),
+        ),
+        (
+            "v2/task_store_compaction",
+            include_bytes!("corpus/v2/task_store_compaction.task"),
+        ),
+    ];
+
+    #[test]
+    fn test_corpus_is_decodable() {
+        for (name, bytes) in CORPUS {
+            let task = decode(bytes).unwrap_or_else(|e| panic!("{}: {}", name, e));
+
+            // the task is written back in the current version.
+            let encoded = TaskCodec::bytes_encode(&task).unwrap();
+            assert_eq!(encoded[0], CURRENT_VERSION, "{}", name);
+            assert_eq!(decode(&encoded).unwrap(), task, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_new_fields_of_older_versions_are_defaulted() {
+        let task = decode(include_bytes!("corpus/v0/document_addition.task")).unwrap();
+        assert_eq!(task.index_uuid, None);
+        assert!(matches!(
+            task.content,
+            crate::tasks::task::TaskContent::DocumentAddition {
+                reject_duplicates: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_unknown_versions_are_rejected() {
+        let mut bytes = vec![CURRENT_VERSION + 1];
+        bytes.extend_from_slice(include_bytes!("corpus/v0/dump.task"));
+        assert!(matches!(
+            decode(&bytes),
+            Err(DecodeError::UnknownVersion(v)) if v == CURRENT_VERSION + 1
+        ));
+        assert!(matches!(decode(&[]), Err(DecodeError::Empty)));
+    }
+
+    #[test]
+    fn test_invalid_index_uids_are_rejected() {
+        let json = String::from_utf8(include_bytes!("corpus/v0/index_creation.task").to_vec())
+            .unwrap()
+            .replace("\"books\"", "\"my books!\"");
+        assert!(matches!(decode(json.as_bytes()), Err(DecodeError::Json(_))));
+    }
+}
@@ -0,0 +1,461 @@
+//! The task registrations that are not yet written to the task store.
+//!
+//! Registering a task directly in the task store needs its write transaction, which the update
+//! loop can hold for a long time while it writes the results of a large batch. Instead, the
+//! registrations are appended to a small journal, kept in memory and fsynced to a file, and a
+//! background thread folds them into the task store. The readers of the task store see the tasks
+//! of the journal as if they were already in the store.
+//!
+//! The journal is bounded: once it is full, the registrations wait for the flusher to make room,
+//! and are rejected if it takes too long, so that the clients can retry later instead of waiting
+//! indefinitely. When the journal is opened, the registrations left over by a crash are folded
+//! into the task store before anything reads it.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use super::Store;
+use crate::meta_env::{Closable, WeakClosable};
+use crate::tasks::error::TaskError;
+use crate::tasks::idempotency::IdempotencyEntry;
+use crate::tasks::task::{Task, TaskId};
+use crate::tasks::Result;
+
+/// The maximum number of registrations waiting to be folded into the task store.
+const CAPACITY: usize = 1024;
+/// How long a registration waits for room in a full journal before being rejected.
+const FULL_JOURNAL_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often the flusher checks whether the task store it folds into was dropped.
+const FLUSHER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct JournalEntry {
+    pub task: Task,
+    /// The storage key of the idempotency key the task was registered with.
+    pub idempotency: Option<(String, IdempotencyEntry)>,
+}
+
+pub struct Journal {
+    path: PathBuf,
+    capacity: usize,
+    state: Mutex<JournalState>,
+    /// Notified when a registration is appended.
+    appended: Condvar,
+    /// Notified when registrations are folded into the task store.
+    folded: Condvar,
+    /// Held while folding, so that the registrations are only folded once at a time.
+    folding: Mutex<()>,
+}
+
+pub struct JournalState {
+    file: File,
+    next_task_id: TaskId,
+    entries: BTreeMap<TaskId, JournalEntry>,
+}
+
+impl JournalState {
+    /// Reserves the id of the next registered task.
+    pub fn next_task_id(&mut self) -> TaskId {
+        let id = self.next_task_id;
+        self.next_task_id += 1;
+        id
+    }
+
+    pub fn get(&self, id: TaskId) -> Option<&Task> {
+        self.entries.get(&id).map(|entry| &entry.task)
+    }
+
+    /// Returns the most recent idempotency entry registered with `key`.
+    pub(crate) fn idempotency_entry(&self, key: &str) -> Option<&IdempotencyEntry> {
+        self.entries
+            .values()
+            .rev()
+            .filter_map(|entry| entry.idempotency.as_ref())
+            .find(|(k, _)| k == key)
+            .map(|(_, entry)| entry)
+    }
+}
+
+impl Journal {
+    /// Opens the journal at `path`, folds the registrations it contains into `store`, and
+    /// starts the thread folding the next registrations.
+    pub fn open(path: impl AsRef<Path>, store: &Closable<Store>) -> Result<Arc<Self>> {
+        Self::open_with_capacity(path, store, CAPACITY)
+    }
+
+    fn open_with_capacity(
+        path: impl AsRef<Path>,
+        closable: &Closable<Store>,
+        capacity: usize,
+    ) -> Result<Arc<Self>> {
+        let store = closable.get().ok_or(TaskError::DatabaseUnavailable)?;
+        let path = path.as_ref().to_path_buf();
+        let entries = read_entries(&path)?;
+        if !entries.is_empty() {
+            log::info!(
+                "Replaying {} task registrations from the journal.",
+                entries.len()
+            );
+            fold_into(&store, entries.values())?;
+        }
+
+        let file = create_file(&path)?;
+        let mut wtxn = store.wtxn()?;
+        let next_task_id = store.next_task_id(&mut wtxn)?;
+        wtxn.abort()?;
+
+        let journal = Arc::new(Self {
+            path,
+            capacity,
+            state: Mutex::new(JournalState {
+                file,
+                next_task_id,
+                entries: BTreeMap::new(),
+            }),
+            appended: Condvar::new(),
+            folded: Condvar::new(),
+            folding: Mutex::new(()),
+        });
+
+        let (weak_journal, weak_store) = (Arc::downgrade(&journal), closable.downgrade());
+        std::thread::Builder::new()
+            .name("task-journal-flusher".to_string())
+            .spawn(move || run_flusher(weak_journal, weak_store))?;
+
+        Ok(journal)
+    }
+
+    /// Locks the journal once it has room for a registration. Returns an error if the journal
+    /// stays full for too long.
+    pub fn lock_for_registration(&self) -> Result<MutexGuard<JournalState>> {
+        let deadline = Instant::now() + FULL_JOURNAL_TIMEOUT;
+        let mut state = self.lock();
+        while state.entries.len() >= self.capacity {
+            let timeout = match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => timeout,
+                _ => return Err(TaskError::TooManyRegistrations),
+            };
+            state = self
+                .folded
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        Ok(state)
+    }
+
+    /// Folds the registrations of the journal into `store`, and pauses the next ones until the
+    /// returned guard is dropped: the store then holds every registered task, and no task is
+    /// registered while it is read.
+    pub fn pause_registrations(&self, store: &Store) -> Result<MutexGuard<JournalState>> {
+        let _folding = self.folding.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = self.lock();
+        if !state.entries.is_empty() {
+            fold_into(store, state.entries.values())?;
+            state.entries.clear();
+            self.rewrite_file(&mut state)?;
+            self.folded.notify_all();
+        }
+
+        Ok(state)
+    }
+
+    /// Appends `entry` to the journal. It is durable once this function returns.
+    pub(crate) fn append(&self, state: &mut JournalState, entry: JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        state.file.write_all(&line)?;
+        state.file.sync_data()?;
+
+        state.entries.insert(entry.task.id, entry);
+        self.appended.notify_one();
+
+        Ok(())
+    }
+
+    pub fn lock(&self) -> MutexGuard<JournalState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the task `id` if it is still in the journal.
+    pub fn get(&self, id: TaskId) -> Option<Task> {
+        self.lock().get(id).cloned()
+    }
+
+    /// Returns the tasks still in the journal, in ascending order.
+    pub fn tasks(&self) -> Vec<Task> {
+        self.lock()
+            .entries
+            .values()
+            .map(|entry| entry.task.clone())
+            .collect()
+    }
+
+    /// Folds all the registrations of the journal into `store`.
+    pub fn flush(&self, store: &Store) -> Result<()> {
+        let _folding = self.folding.lock().unwrap_or_else(|e| e.into_inner());
+
+        let entries: Vec<_> = {
+            let state = self.lock();
+            state
+                .entries
+                .values()
+                .map(|entry| (entry.task.clone(), entry.idempotency.clone()))
+                .collect()
+        };
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        // The tasks stay visible in the journal until they are committed in the store, so the
+        // readers always find them in either of the two.
+        let mut wtxn = store.wtxn()?;
+        for (task, idempotency) in &entries {
+            put_if_absent(store, &mut wtxn, task, idempotency.as_ref())?;
+        }
+        wtxn.commit()?;
+
+        let mut state = self.lock();
+        for (task, _) in &entries {
+            state.entries.remove(&task.id);
+        }
+        self.rewrite_file(&mut state)?;
+        self.folded.notify_all();
+
+        Ok(())
+    }
+
+    /// Replaces the journal file by one containing only the registrations not folded yet.
+    fn rewrite_file(&self, state: &mut JournalState) -> Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        for entry in state.entries.values() {
+            serde_json::to_writer(&mut tmp, entry)?;
+            tmp.write_all(b"\n")?;
+        }
+        tmp.into_inner().map_err(|e| e.into_error())?.sync_data()?;
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        state.file = OpenOptions::new().append(true).open(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Waits for a registration to be appended, or for `timeout`. Returns whether the journal
+    /// holds registrations to fold.
+    fn wait_for_entries(&self, timeout: Duration) -> bool {
+        let state = self.lock();
+        let (state, _) = self
+            .appended
+            .wait_timeout_while(state, timeout, |state| state.entries.is_empty())
+            .unwrap_or_else(|e| e.into_inner());
+        !state.entries.is_empty()
+    }
+}
+
+fn run_flusher(journal: Weak<Journal>, store: WeakClosable<Store>) {
+    loop {
+        let has_entries = match journal.upgrade() {
+            Some(journal) => journal.wait_for_entries(FLUSHER_POLL_INTERVAL),
+            None => break,
+        };
+
+        if has_entries {
+            match (journal.upgrade(), store.upgrade()) {
+                (Some(journal), Some(store)) => match store.get() {
+                    Some(store) => {
+                        if let Err(e) = journal.flush(&store) {
+                            error!("Error while folding the task journal into the store: {}", e);
+                            std::thread::sleep(FLUSHER_POLL_INTERVAL);
+                        }
+                    }
+                    // the store is closed while the meta environment is compacted.
+                    None => std::thread::sleep(FLUSHER_POLL_INTERVAL),
+                },
+                _ => break,
+            }
+        }
+    }
+}
+
+/// Writes `task` to the store, unless the update loop already wrote a more recent version of it.
+fn put_if_absent(
+    store: &Store,
+    wtxn: &mut milli::heed::RwTxn,
+    task: &Task,
+    idempotency: Option<&(String, IdempotencyEntry)>,
+) -> Result<()> {
+    if store.get(wtxn, task.id)?.is_none() {
+        store.put(wtxn, task)?;
+    }
+    if let Some((key, entry)) = idempotency {
+        store.put_idempotency_entry(wtxn, key, entry)?;
+    }
+
+    Ok(())
+}
+
+fn fold_into<'a>(store: &Store, entries: impl Iterator<Item = &'a JournalEntry>) -> Result<()> {
+    let mut wtxn = store.wtxn()?;
+    for entry in entries {
+        put_if_absent(store, &mut wtxn, &entry.task, entry.idempotency.as_ref())?;
+    }
+    wtxn.commit()?;
+
+    Ok(())
+}
+
+fn read_entries(path: &Path) -> Result<BTreeMap<TaskId, JournalEntry>> {
+    let mut entries = BTreeMap::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+        Err(e) => return Err(e.into()),
+    };
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        match serde_json::from_str::<JournalEntry>(&line) {
+            Ok(entry) => {
+                entries.insert(entry.task.id, entry);
+            }
+            // Only the last registration can be incomplete, if the crash happened while it was
+            // written. It was never acknowledged, so it is safe to ignore.
+            Err(e) => {
+                warn!(
+                    "Ignoring an incomplete registration in the task journal: {}",
+                    e
+                );
+                break;
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn create_file(path: &Path) -> Result<File> {
+    let file = File::create(path)?;
+    file.sync_all()?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index_resolver::IndexUid;
+    use crate::tasks::task::{TaskContent, TaskEvent};
+    use crate::tasks::task_store::store::test::tmp_env;
+
+    fn entry(id: TaskId) -> JournalEntry {
+        JournalEntry {
+            task: Task {
+                id,
+                index_uuid: None,
+                content: TaskContent::IndexDeletion {
+                    index_uid: IndexUid::new_unchecked("test"),
+                },
+                events: vec![TaskEvent::Created(time::OffsetDateTime::now_utc())],
+                origin: None,
+                engine_version: None,
+            },
+            idempotency: None,
+        }
+    }
+
+    #[test]
+    fn test_replay_journal_on_open() {
+        let tmp = tmp_env();
+        let store = Closable::new(Store::new(tmp.env()).unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.journal");
+
+        // a crash left two registrations in the journal, the second one being torn.
+        let mut content = serde_json::to_vec(&entry(0)).unwrap();
+        content.push(b'\n');
+        content.extend_from_slice(&serde_json::to_vec(&entry(1)).unwrap()[..10]);
+        std::fs::write(&path, content).unwrap();
+
+        let journal = Journal::open(&path, &store).unwrap();
+        let store = store.get().unwrap();
+
+        let rtxn = store.rtxn().unwrap();
+        assert!(store.get(&rtxn, 0).unwrap().is_some());
+        assert!(store.get(&rtxn, 1).unwrap().is_none());
+        drop(rtxn);
+
+        assert_eq!(journal.lock_for_registration().unwrap().next_task_id(), 1);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_full_journal_rejects_registrations() {
+        let tmp = tmp_env();
+        let store = Closable::new(Store::new(tmp.env()).unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.journal");
+
+        let journal = Journal::open_with_capacity(&path, &store, 2).unwrap();
+        let store = store.get().unwrap();
+
+        // the write transaction held by a batch update prevents the flusher from making room.
+        let wtxn = store.wtxn().unwrap();
+        for _ in 0..2 {
+            let mut state = journal.lock_for_registration().unwrap();
+            let id = state.next_task_id();
+            journal.append(&mut state, entry(id)).unwrap();
+        }
+        assert!(matches!(
+            journal.lock_for_registration(),
+            Err(TaskError::TooManyRegistrations)
+        ));
+        assert_eq!(journal.tasks().len(), 2);
+        wtxn.abort().unwrap();
+
+        journal.flush(&store).unwrap();
+        assert!(journal.tasks().is_empty());
+        let mut state = journal.lock_for_registration().unwrap();
+        assert_eq!(state.next_task_id(), 2);
+    }
+
+    #[test]
+    fn test_pause_registrations() {
+        let tmp = tmp_env();
+        let store = Closable::new(Store::new(tmp.env()).unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.journal");
+
+        let journal = Journal::open(&path, &store).unwrap();
+        let store = store.get().unwrap();
+        {
+            let mut state = journal.lock_for_registration().unwrap();
+            let id = state.next_task_id();
+            journal.append(&mut state, entry(id)).unwrap();
+        }
+
+        let paused = journal.pause_registrations(&store).unwrap();
+        let rtxn = store.rtxn().unwrap();
+        assert!(store.get(&rtxn, 0).unwrap().is_some());
+        drop(rtxn);
+
+        // a registration waits for the registrations to be resumed.
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let registering = journal.clone();
+        let handle = std::thread::spawn(move || {
+            let mut state = registering.lock_for_registration().unwrap();
+            sender.send(state.next_task_id()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(paused);
+        assert_eq!(receiver.recv().unwrap(), 1);
+        handle.join().unwrap();
+    }
+}
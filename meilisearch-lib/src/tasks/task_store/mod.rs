@@ -1,32 +1,72 @@
+mod codec;
+mod journal;
 mod store;
+mod unfetched;
 
+use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use log::debug;
-use milli::heed::{Env, RwTxn};
+use milli::heed::{Env, RoTxn, RwTxn};
+use serde::Deserialize;
 use time::OffsetDateTime;
+use uuid::Uuid;
 
-use super::batch::BatchContent;
+use super::batch::{BatchContent, BatchId, BatchResult};
 use super::error::TaskError;
+use super::idempotency::{IdempotencyEntry, IdempotencyKey, Registration};
 use super::scheduler::Processing;
-use super::task::{Task, TaskContent, TaskId};
+use super::task::{Task, TaskContent, TaskId, TaskOrigin, TaskResult, ENGINE_VERSION};
 use super::Result;
+use crate::meta_env::Closable;
 use crate::tasks::task::TaskEvent;
 use crate::update_file_store::UpdateFileStore;
+use journal::{Journal, JournalEntry, JournalState};
+use unfetched::UnfetchedTasks;
 
 #[cfg(test)]
 pub use store::test::MockStore as Store;
 #[cfg(not(test))]
 pub use store::Store;
 
+/// The order of the ids of the listed tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TaskOrder {
+    #[serde(rename = "asc")]
+    Ascending,
+    #[serde(rename = "desc")]
+    Descending,
+}
+
+impl TaskOrder {
+    /// How a task is compared to the next listed one.
+    fn ordering(self) -> Ordering {
+        match self {
+            TaskOrder::Ascending => Ordering::Less,
+            TaskOrder::Descending => Ordering::Greater,
+        }
+    }
+
+    /// Whether `id` is listed from the task `from` in this order.
+    fn reaches(self, from: TaskId, id: TaskId) -> bool {
+        match self {
+            TaskOrder::Ascending => id >= from,
+            TaskOrder::Descending => id <= from,
+        }
+    }
+}
+
 /// Defines constraints to be applied when querying for Tasks from the store.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct TaskFilter {
     indexes: Option<HashSet<String>>,
-    filter_fn: Option<Box<dyn Fn(&Task) -> bool + Sync + Send + 'static>>,
+    /// Only lists the tasks that are not finished.
+    unfinished: bool,
+    filter_fn: Option<Arc<dyn Fn(&Task) -> bool + Sync + Send + 'static>>,
 }
 
 impl TaskFilter {
@@ -44,6 +84,20 @@ impl TaskFilter {
         self.indexes.as_ref()
     }
 
+    /// Whether `task` is listed by `TaskStore::list_tasks` with this filter.
+    fn lists(&self, task: &Task) -> bool {
+        let index_matches = match self.indexes {
+            Some(ref indexes) => task
+                .index_uid()
+                .map_or(false, |index_uid| indexes.contains(index_uid)),
+            None => true,
+        };
+
+        index_matches
+            && !(self.unfinished && task.is_finished())
+            && self.filter_fn.as_ref().map_or(true, |f| f(task))
+    }
+
     /// Adds an index to the filter, so the filter must match this index.
     pub fn filter_index(&mut self, index: String) {
         self.indexes
@@ -51,62 +105,259 @@ impl TaskFilter {
             .insert(index);
     }
 
+    /// Only lists the tasks that are not finished. The store keeps track of these tasks, so that
+    /// they are listed without reading the finished ones.
+    pub fn filter_unfinished(&mut self) {
+        self.unfinished = true;
+    }
+
     pub fn filter_fn(&mut self, f: impl Fn(&Task) -> bool + Sync + Send + 'static) {
-        self.filter_fn.replace(Box::new(f));
+        self.filter_fn.replace(Arc::new(f));
     }
 }
 
 pub struct TaskStore {
-    store: Arc<Store>,
+    /// The store is closed while the meta environment is replaced by a compacted copy of itself.
+    store: Closable<Store>,
+    /// The registrations not yet written to the store, when the registrations are journaled.
+    journal: Option<Arc<Journal>>,
+    /// The registered tasks the scheduler didn't fetch yet.
+    unfetched: Arc<UnfetchedTasks>,
 }
 
 impl Clone for TaskStore {
     fn clone(&self) -> Self {
         Self {
             store: self.store.clone(),
+            journal: self.journal.clone(),
+            unfetched: self.unfetched.clone(),
         }
     }
 }
 
 impl TaskStore {
     pub fn new(env: Arc<milli::heed::Env>) -> Result<Self> {
-        let store = Arc::new(Store::new(env)?);
-        Ok(Self { store })
+        let store = Closable::new(Store::new(env)?);
+        Ok(Self {
+            store,
+            journal: None,
+            unfetched: Default::default(),
+        })
+    }
+
+    /// Creates a task store whose registrations are appended to the journal at `journal_path`,
+    /// instead of waiting for the write transaction of the store. The registrations left in the
+    /// journal by a previous run are written to the store first.
+    pub fn with_journal(
+        env: Arc<milli::heed::Env>,
+        journal_path: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let store = Closable::new(Store::new(env)?);
+        let journal = Journal::open(journal_path, &store)?;
+        Ok(Self {
+            store,
+            journal: Some(journal),
+            unfetched: Default::default(),
+        })
+    }
+
+    fn store(&self) -> Result<Arc<Store>> {
+        self.store.get().ok_or(TaskError::DatabaseUnavailable)
+    }
+
+    /// Closes the store, once the calls using it are finished. The calls reading or registering
+    /// tasks fail until it is opened again, while the registrations already in the journal stay
+    /// there.
+    pub fn close(&self) {
+        self.store.close();
+    }
+
+    /// Opens the store again, on the meta environment `env`.
+    pub fn open(&self, env: Arc<Env>) -> Result<()> {
+        self.store.open(Store::new(env)?);
+        Ok(())
+    }
+
+    /// Writes the journaled registrations to the store. The dumps and snapshots read the tasks
+    /// straight from the store, so this must be called before making one.
+    pub async fn flush_journal(&self) -> Result<()> {
+        if let Some(journal) = self.journal.clone() {
+            let store = self.store()?;
+            tokio::task::spawn_blocking(move || journal.flush(&store)).await??;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the tasks of the journal. They must be read before the store: a task that is
+    /// folded into the store in between is then found in the store.
+    fn journaled_tasks(&self) -> Vec<Task> {
+        self.journal
+            .as_ref()
+            .map_or_else(Vec::new, |journal| journal.tasks())
     }
 
-    pub async fn register(&self, content: TaskContent) -> Result<Task> {
+    /// Registers a task, `index_uuid` being the uuid of the index it targets if it exists.
+    pub async fn register(&self, content: TaskContent, index_uuid: Option<Uuid>) -> Result<Task> {
+        let registration = self
+            .register_with_origin(content, index_uuid, None, None, None)
+            .await?;
+        Ok(registration.into_task())
+    }
+
+    /// Registers a task that has nothing to process, and that is already succeeded with `result`.
+    /// The task is never seen by the scheduler.
+    pub async fn register_succeeded(
+        &self,
+        content: TaskContent,
+        index_uuid: Option<Uuid>,
+        result: TaskResult,
+    ) -> Result<Task> {
+        let registration = self
+            .register_with_origin(content, index_uuid, Some(result), None, None)
+            .await?;
+        Ok(registration.into_task())
+    }
+
+    /// Registers a task like `register`, or like `register_succeeded` if `result` is set, unless
+    /// a task was registered with the idempotency key `key` less than `window` ago. This task is
+    /// returned instead.
+    pub async fn register_idempotent(
+        &self,
+        content: TaskContent,
+        index_uuid: Option<Uuid>,
+        result: Option<TaskResult>,
+        key: IdempotencyKey,
+        window: Duration,
+    ) -> Result<Registration> {
+        self.register_with_origin(content, index_uuid, result, Some((key, window)), None)
+            .await
+    }
+
+    /// Registers a task like `register_idempotent` if `idempotency` is set, or like `register`
+    /// and `register_succeeded` otherwise, recording the `origin` of the request registering it.
+    pub async fn register_with_origin(
+        &self,
+        content: TaskContent,
+        index_uuid: Option<Uuid>,
+        result: Option<TaskResult>,
+        idempotency: Option<(IdempotencyKey, Duration)>,
+        origin: Option<TaskOrigin>,
+    ) -> Result<Registration> {
         debug!("registering update: {:?}", content);
-        let store = self.store.clone();
-        let task = tokio::task::spawn_blocking(move || -> Result<Task> {
+        let store = self.store()?;
+        if let Some(journal) = self.journal.clone() {
+            let registration = tokio::task::spawn_blocking(move || {
+                register_in_journal(
+                    &journal,
+                    &store,
+                    content,
+                    index_uuid,
+                    result,
+                    idempotency,
+                    origin,
+                )
+            })
+            .await??;
+            self.unfetched_registration(&registration);
+            return Ok(registration);
+        }
+
+        let registration = tokio::task::spawn_blocking(move || -> Result<Registration> {
             let mut txn = store.wtxn()?;
+            // the key is checked in the transaction that registers the task, so that two
+            // concurrent requests with the same key can't both register a task.
+            if let Some((ref key, window)) = idempotency {
+                if let Some(task) = idempotent_task(&store, &txn, None, key, window)? {
+                    return Ok(Registration::Replayed(task));
+                }
+            }
+
             let next_task_id = store.next_task_id(&mut txn)?;
-            let created_at = TaskEvent::Created(OffsetDateTime::now_utc());
-            let task = Task {
+            let now = OffsetDateTime::now_utc();
+            let mut task = Task {
                 id: next_task_id,
+                index_uuid,
                 content,
-                events: vec![created_at],
+                events: vec![TaskEvent::Created(now)],
+                origin,
+                engine_version: Some(ENGINE_VERSION.to_string()),
             };
+            if let Some(result) = result {
+                task.events.push(TaskEvent::succeeded(result));
+            }
 
             store.put(&mut txn, &task)?;
+            if let Some((key, _)) = idempotency {
+                let entry = IdempotencyEntry {
+                    task_id: task.id,
+                    registered_at: now,
+                };
+                store.put_idempotency_entry(&mut txn, &key.storage_key(), &entry)?;
+            }
             txn.commit()?;
 
-            Ok(task)
+            Ok(Registration::Created(task))
         })
         .await??;
+        self.unfetched_registration(&registration);
+
+        Ok(registration)
+    }
+
+    fn unfetched_registration(&self, registration: &Registration) {
+        if let Registration::Created(task) = registration {
+            self.unfetched.registered(task);
+        }
+    }
+
+    /// Returns the id of the newest registered task the scheduler didn't fetch yet, and the date
+    /// the oldest one was enqueued at.
+    pub fn unfetched_tasks(&self) -> Option<(TaskId, OffsetDateTime)> {
+        self.unfetched.summary()
+    }
+
+    /// Returns the task registered with the idempotency key `key` less than `window` ago.
+    pub async fn get_idempotent_task(
+        &self,
+        key: IdempotencyKey,
+        window: Duration,
+    ) -> Result<Option<Task>> {
+        let store = self.store()?;
+        let journal = self.journal.clone();
+        tokio::task::spawn_blocking(move || {
+            let state = journal.as_ref().map(|journal| journal.lock());
+            let txn = store.rtxn()?;
+            idempotent_task(&store, &txn, state.as_deref(), &key, window)
+        })
+        .await?
+    }
 
-        Ok(task)
+    /// Forgets the idempotency keys registered more than `window` ago. Returns how many were
+    /// forgotten.
+    pub async fn delete_expired_idempotency_keys(&self, window: Duration) -> Result<usize> {
+        let store = self.store()?;
+        tokio::task::spawn_blocking(move || {
+            let mut txn = store.wtxn()?;
+            let deleted = store.delete_expired_idempotency_entries(&mut txn, window)?;
+            txn.commit()?;
+            Ok(deleted)
+        })
+        .await?
     }
 
     pub fn register_raw_update(&self, wtxn: &mut RwTxn, task: &Task) -> Result<()> {
-        self.store.put(wtxn, task)?;
+        self.store()?.put(wtxn, task)?;
         Ok(())
     }
 
     pub async fn get_task(&self, id: TaskId, filter: Option<TaskFilter>) -> Result<Task> {
-        let store = self.store.clone();
+        let store = self.store()?;
+        let journal = self.journal.clone();
         let task = tokio::task::spawn_blocking(move || -> Result<_> {
+            let journaled = journal.and_then(|journal| journal.get(id));
             let txn = store.rtxn()?;
-            let task = store.get(&txn, id)?;
+            let task = store.get(&txn, id)?.or(journaled);
             Ok(task)
         })
         .await??
@@ -124,38 +375,78 @@ impl TaskStore {
     /// This methods takes a `Processing` which contains the next task ids to process, and returns
     /// the coresponding tasks along with the ownership to the passed processing.
     ///
+    /// The tasks that can't be found in the store are skipped, and removed from the returned
+    /// processing. If none of them can be found, `Processing::Nothing` is returned.
+    ///
     /// We need get_processing_tasks to take ownership over `Processing` because we need it to be
     /// valid for 'static.
     pub async fn get_processing_tasks(
         &self,
         processing: Processing,
     ) -> Result<(Processing, BatchContent)> {
-        let store = self.store.clone();
+        let store = self.store()?;
+        let journaled = self.journaled_tasks();
         let tasks = tokio::task::spawn_blocking(move || -> Result<_> {
             let txn = store.rtxn()?;
 
-            let content = match processing {
-                Processing::DocumentAdditions(ref ids) => {
+            let get = |id: TaskId| -> Result<Option<Task>> {
+                let task = match store.get(&txn, id)? {
+                    Some(task) => Some(task),
+                    None => journaled.iter().find(|task| task.id == id).cloned(),
+                };
+                if task.is_none() {
+                    log::error!(
+                        "Task `{}` was scheduled for processing but is missing from the task store, it is skipped. \
+                        The task queue and the task store are out of sync, which happens if the tasks database \
+                        was restored or modified while the scheduler was running.",
+                        id
+                    );
+                }
+                Ok(task)
+            };
+
+            let (processing, content) = match processing {
+                Processing::DocumentAdditions(ref ids) | Processing::DocumentDeletions(ref ids) => {
+                    let mut found_ids = Vec::new();
                     let mut tasks = Vec::new();
 
-                    for id in ids.iter() {
-                        let task = store
-                            .get(&txn, *id)?
-                            .ok_or(TaskError::UnexistingTask(*id))?;
-                        tasks.push(task);
+                    for id in ids.iter().copied() {
+                        if let Some(task) = get(id)? {
+                            found_ids.push(id);
+                            tasks.push(task);
+                        }
+                    }
+
+                    if tasks.is_empty() {
+                        (Processing::Nothing, BatchContent::Empty)
+                    } else if matches!(processing, Processing::DocumentDeletions(_)) {
+                        (
+                            Processing::DocumentDeletions(found_ids),
+                            BatchContent::DocumentDeletionBatch(tasks),
+                        )
+                    } else {
+                        (
+                            Processing::DocumentAdditions(found_ids),
+                            BatchContent::DocumentsAdditionBatch(tasks),
+                        )
                     }
-                    BatchContent::DocumentsAdditionBatch(tasks)
-                }
-                Processing::IndexUpdate(id) => {
-                    let task = store.get(&txn, id)?.ok_or(TaskError::UnexistingTask(id))?;
-                    BatchContent::IndexUpdate(task)
-                }
-                Processing::Dump(id) => {
-                    let task = store.get(&txn, id)?.ok_or(TaskError::UnexistingTask(id))?;
-                    debug_assert!(matches!(task.content, TaskContent::Dump { .. }));
-                    BatchContent::Dump(task)
                 }
-                Processing::Nothing => BatchContent::Empty,
+                Processing::IndexUpdate(id) => match get(id)? {
+                    Some(task) => (processing, BatchContent::IndexUpdate(task)),
+                    None => (Processing::Nothing, BatchContent::Empty),
+                },
+                Processing::Dump(id) => match get(id)? {
+                    Some(task) => {
+                        debug_assert!(matches!(task.content, TaskContent::Dump { .. }));
+                        (processing, BatchContent::Dump(task))
+                    }
+                    None => (Processing::Nothing, BatchContent::Empty),
+                },
+                Processing::TaskStoreCompaction(id) => match get(id)? {
+                    Some(task) => (processing, BatchContent::TaskStoreCompaction(task)),
+                    None => (Processing::Nothing, BatchContent::Empty),
+                },
+                Processing::Nothing => (processing, BatchContent::Empty),
             };
 
             Ok((processing, content))
@@ -166,7 +457,7 @@ impl TaskStore {
     }
 
     pub async fn update_tasks(&self, tasks: Vec<Task>) -> Result<Vec<Task>> {
-        let store = self.store.clone();
+        let store = self.store()?;
 
         let tasks = tokio::task::spawn_blocking(move || -> Result<_> {
             let mut txn = store.wtxn()?;
@@ -185,54 +476,174 @@ impl TaskStore {
     }
 
     pub async fn fetch_unfinished_tasks(&self, offset: Option<TaskId>) -> Result<Vec<Task>> {
-        let store = self.store.clone();
+        let store = self.store()?;
+        let journaled = self.journaled_tasks();
+        let unfetched = self.unfetched.clone();
 
         tokio::task::spawn_blocking(move || {
             let txn = store.rtxn()?;
+            // every task registered up to the newest one seen by the fetch is either fetched, or
+            // was finished before being fetched.
+            let newest = store
+                .last_task_id(&txn)?
+                .max(journaled.iter().map(|task| task.id).max());
+            if let Some(newest) = newest {
+                unfetched.fetched(newest);
+            }
             let tasks = store.fetch_unfinished_tasks(&txn, offset)?;
-            Ok(tasks)
+            let journaled = journaled
+                .into_iter()
+                .filter(|task| task.id >= offset.unwrap_or_default() && !task.is_finished());
+            Ok(merge_journaled(tasks, journaled, Ordering::Less))
         })
         .await?
     }
 
+    /// Returns all the unfinished tasks. Unlike `fetch_unfinished_tasks`, it blocks the current
+    /// thread, and is only meant to be called on startup.
+    pub fn unfinished_tasks(&self) -> Result<Vec<Task>> {
+        let journaled = self.journaled_tasks();
+        let store = self.store()?;
+        let txn = store.rtxn()?;
+        let tasks = store.fetch_unfinished_tasks(&txn, None)?;
+        let journaled = journaled.into_iter().filter(|task| !task.is_finished());
+        Ok(merge_journaled(tasks, journaled, Ordering::Less))
+    }
+
+    /// Lists the tasks in `order`, starting from the task `offset` included.
     pub async fn list_tasks(
         &self,
         offset: Option<TaskId>,
         filter: Option<TaskFilter>,
         limit: Option<usize>,
+        order: TaskOrder,
     ) -> Result<Vec<Task>> {
-        let store = self.store.clone();
+        let store = self.store()?;
+        let mut journaled = self.journaled_tasks();
+        if order == TaskOrder::Descending {
+            journaled.reverse();
+        }
+        let journaled: Vec<_> = journaled
+            .into_iter()
+            .filter(|task| offset.map_or(true, |offset| order.reaches(offset, task.id)))
+            .filter(|task| filter.as_ref().map_or(true, |filter| filter.lists(task)))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
 
         tokio::task::spawn_blocking(move || {
             let txn = store.rtxn()?;
-            let tasks = store.list_tasks(&txn, offset, filter, limit)?;
+            let tasks = store.list_tasks(&txn, offset, filter, limit, order)?;
+            let mut tasks = merge_journaled(tasks, journaled, order.ordering());
+            tasks.truncate(limit.unwrap_or(usize::MAX));
             Ok(tasks)
         })
         .await?
     }
 
+    pub async fn put_batch_result(&self, result: BatchResult) -> Result<()> {
+        let store = self.store()?;
+
+        tokio::task::spawn_blocking(move || {
+            let mut txn = store.wtxn()?;
+            store.put_batch_result(&mut txn, &result)?;
+            txn.commit()?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Returns the result of the batch `id`. When `indexes` is set, the batch must belong to one
+    /// of these indexes.
+    pub async fn get_batch_result(
+        &self,
+        id: BatchId,
+        indexes: Option<HashSet<String>>,
+    ) -> Result<BatchResult> {
+        let store = self.store()?;
+        let result = tokio::task::spawn_blocking(move || -> Result<_> {
+            let txn = store.rtxn()?;
+            let result = store.get_batch_result(&txn, id)?;
+            Ok(result)
+        })
+        .await??
+        .ok_or(TaskError::UnexistingBatch(id))?;
+
+        match indexes {
+            Some(indexes) => result
+                .index_uid
+                .as_ref()
+                .map_or(false, |index_uid| indexes.contains(index_uid))
+                .then(|| result)
+                .ok_or(TaskError::UnexistingBatch(id)),
+            None => Ok(result),
+        }
+    }
+
+    pub async fn list_batch_results(
+        &self,
+        from: Option<BatchId>,
+        indexes: Option<HashSet<String>>,
+        limit: Option<usize>,
+    ) -> Result<Vec<BatchResult>> {
+        let store = self.store()?;
+
+        tokio::task::spawn_blocking(move || {
+            let txn = store.rtxn()?;
+            let results = store.list_batch_results(&txn, from, indexes.as_ref(), limit)?;
+            Ok(results)
+        })
+        .await?
+    }
+
+    /// Dumps the finished tasks, and with `pending_tasks` the unfinished ones along with their
+    /// update files. The origins of the tasks are only dumped with `with_origins`.
     pub async fn dump(
-        env: Arc<Env>,
+        &self,
         dir_path: impl AsRef<Path>,
         update_file_store: UpdateFileStore,
+        with_origins: bool,
+        pending_tasks: bool,
     ) -> Result<()> {
-        let store = Self::new(env)?;
         let update_dir = dir_path.as_ref().join("updates");
         let updates_file = update_dir.join("data.jsonl");
-        let tasks = store.list_tasks(None, None, None).await?;
+        let store = self.store()?;
+        let journal = self.journal.clone();
 
         let dir_path = dir_path.as_ref().to_path_buf();
         tokio::task::spawn_blocking(move || -> Result<()> {
+            // the registrations are paused while the tasks are read, so that the dumped tasks are
+            // the ones registered before a single point in time.
+            let paused = journal
+                .as_ref()
+                .map(|journal| journal.pause_registrations(&store))
+                .transpose()?;
+            let txn = store.rtxn()?;
+            let tasks = store.list_tasks(&txn, None, None, None, TaskOrder::Descending)?;
+            drop(txn);
+            drop(paused);
+
             std::fs::create_dir(&update_dir)?;
             let updates_file = std::fs::File::create(updates_file)?;
             let mut updates_file = BufWriter::new(updates_file);
 
-            for task in tasks {
+            for mut task in tasks {
+                // the unfinished dumps, this one included, are not dumped: the restored instance
+                // would create them again.
+                let pending = !task.is_finished();
+                if pending && (!pending_tasks || matches!(task.content, TaskContent::Dump { .. })) {
+                    continue;
+                }
+
+                if !with_origins {
+                    task.origin = None;
+                }
                 serde_json::to_writer(&mut updates_file, &task)?;
                 updates_file.write_all(b"\n")?;
 
-                if !task.is_finished() {
-                    if let Some(content_uuid) = task.get_content_uuid() {
+                // the update files of the pending tasks are not deleted while the dump is
+                // processed, since no other batch is processed in the meantime.
+                if pending {
+                    for content_uuid in task.pending_content_uuids() {
                         update_file_store.dump(content_uuid, &dir_path)?;
                     }
                 }
@@ -245,8 +656,16 @@ impl TaskStore {
         Ok(())
     }
 
-    pub fn load_dump(src: impl AsRef<Path>, env: Arc<Env>) -> anyhow::Result<()> {
-        // create a dummy update field store, since it is not needed right now.
+    /// Loads the tasks of a dump, whose update files are already loaded in `update_file_store`.
+    ///
+    /// The finished tasks keep their ids, while the pending ones are registered again after all of
+    /// them, in the order they were registered in: their ids change, but they are processed in the
+    /// same order. An update file already claimed by another pending task is copied to a new one.
+    pub fn load_dump(
+        src: impl AsRef<Path>,
+        update_file_store: &UpdateFileStore,
+        env: Arc<Env>,
+    ) -> anyhow::Result<()> {
         let store = Self::new(env.clone())?;
 
         let src_update_path = src.as_ref().join("updates");
@@ -256,8 +675,34 @@ impl TaskStore {
         let stream = serde_json::Deserializer::from_reader(update_data).into_iter::<Task>();
 
         let mut wtxn = env.write_txn()?;
+        let mut pending = Vec::new();
         for entry in stream {
-            store.register_raw_update(&mut wtxn, &entry?)?;
+            let task = entry?;
+            if task.is_finished() {
+                store.register_raw_update(&mut wtxn, &task)?;
+            } else if !matches!(task.content, TaskContent::Dump { .. }) {
+                // the dumps of the previous versions hold the dumps still enqueued, which are not
+                // created again.
+                pending.push(task);
+            }
+        }
+
+        pending.sort_unstable_by_key(|task| task.id);
+        let mut claimed = HashSet::new();
+        for mut task in pending {
+            task.id = store.store()?.next_task_id(&mut wtxn)?;
+            // the task is enqueued again, its processed chunks being part of the dumped index.
+            task.events.retain(|event| {
+                !matches!(event, TaskEvent::Batched { .. } | TaskEvent::Processing(_))
+            });
+            for content_uuid in task.pending_content_uuids() {
+                if !claimed.insert(content_uuid) {
+                    let copy = update_file_store.copy(content_uuid)?;
+                    task.replace_content_uuid(content_uuid, copy);
+                    claimed.insert(copy);
+                }
+            }
+            store.register_raw_update(&mut wtxn, &task)?;
         }
         wtxn.commit()?;
 
@@ -265,6 +710,116 @@ impl TaskStore {
     }
 }
 
+/// Registers a task in the journal, the journal being locked for the whole registration so that
+/// the task ids are given in order.
+fn register_in_journal(
+    journal: &Journal,
+    store: &Store,
+    content: TaskContent,
+    index_uuid: Option<Uuid>,
+    result: Option<TaskResult>,
+    idempotency: Option<(IdempotencyKey, Duration)>,
+    origin: Option<TaskOrigin>,
+) -> Result<Registration> {
+    let mut state = journal.lock_for_registration()?;
+    if let Some((ref key, window)) = idempotency {
+        let txn = store.rtxn()?;
+        if let Some(task) = idempotent_task(store, &txn, Some(&state), key, window)? {
+            return Ok(Registration::Replayed(task));
+        }
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let mut task = Task {
+        id: state.next_task_id(),
+        index_uuid,
+        content,
+        events: vec![TaskEvent::Created(now)],
+        origin,
+        engine_version: Some(ENGINE_VERSION.to_string()),
+    };
+    if let Some(result) = result {
+        task.events.push(TaskEvent::succeeded(result));
+    }
+
+    let idempotency = idempotency.map(|(key, _)| {
+        let entry = IdempotencyEntry {
+            task_id: task.id,
+            registered_at: now,
+        };
+        (key.storage_key(), entry)
+    });
+    journal.append(
+        &mut state,
+        JournalEntry {
+            task: task.clone(),
+            idempotency,
+        },
+    )?;
+
+    Ok(Registration::Created(task))
+}
+
+/// Returns the task registered with `key`, unless it was registered more than `window` ago. The
+/// registrations of the `journal` are more recent than the ones of the store.
+fn idempotent_task(
+    store: &Store,
+    txn: &RoTxn,
+    journal: Option<&JournalState>,
+    key: &IdempotencyKey,
+    window: Duration,
+) -> Result<Option<Task>> {
+    let key = key.storage_key();
+    let entry = match journal.and_then(|journal| journal.idempotency_entry(&key)) {
+        Some(entry) => Some(entry.clone()),
+        None => store.get_idempotency_entry(txn, &key)?,
+    };
+
+    match entry {
+        Some(entry) if !entry.is_expired(window, OffsetDateTime::now_utc()) => {
+            match store.get(txn, entry.task_id)? {
+                Some(task) => Ok(Some(task)),
+                None => Ok(journal
+                    .and_then(|journal| journal.get(entry.task_id))
+                    .cloned()),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Merges the tasks of the store with the tasks of the journal, both sorted in the `order` of
+/// their ids. A task found in both is the most recent in the store, since the update loop writes
+/// it there directly.
+fn merge_journaled(
+    tasks: Vec<Task>,
+    journaled: impl IntoIterator<Item = Task>,
+    order: Ordering,
+) -> Vec<Task> {
+    let mut tasks = tasks.into_iter().peekable();
+    let mut journaled = journaled.into_iter().peekable();
+    let mut merged = Vec::new();
+
+    loop {
+        let next = match (tasks.peek(), journaled.peek()) {
+            (Some(task), Some(journaled_task)) => match task.id.cmp(&journaled_task.id) {
+                Ordering::Equal => {
+                    journaled.next();
+                    tasks.next()
+                }
+                cmp if cmp == order => tasks.next(),
+                _ => journaled.next(),
+            },
+            (Some(_), None) => tasks.next(),
+            (None, Some(_)) => journaled.next(),
+            (None, None) => break,
+        };
+        merged.extend(next);
+    }
+
+    merged
+}
+
 #[cfg(test)]
 pub mod test {
     use crate::{
@@ -299,18 +854,61 @@ pub mod test {
             Ok(Self::Real(TaskStore::new(env)?))
         }
 
-        pub async fn dump(
+        pub fn with_journal(
             env: Arc<milli::heed::Env>,
+            journal_path: impl AsRef<Path>,
+        ) -> Result<Self> {
+            Ok(Self::Real(TaskStore::with_journal(env, journal_path)?))
+        }
+
+        pub async fn dump(
+            &self,
             path: impl AsRef<Path>,
             update_file_store: UpdateFileStore,
+            with_origins: bool,
+            pending_tasks: bool,
         ) -> Result<()> {
-            TaskStore::dump(env, path, update_file_store).await
+            match self {
+                Self::Real(s) => {
+                    s.dump(path, update_file_store, with_origins, pending_tasks)
+                        .await
+                }
+                Self::Mock(m) => unsafe {
+                    m.get("dump").call((
+                        path.as_ref().to_owned(),
+                        update_file_store,
+                        with_origins,
+                        pending_tasks,
+                    ))
+                },
+            }
+        }
+
+        pub fn close(&self) {
+            match self {
+                Self::Real(s) => s.close(),
+                Self::Mock(m) => unsafe { m.get("close").call(()) },
+            }
+        }
+
+        pub fn open(&self, env: Arc<Env>) -> Result<()> {
+            match self {
+                Self::Real(s) => s.open(env),
+                Self::Mock(m) => unsafe { m.get("open").call(env) },
+            }
         }
 
         pub fn mock(mocker: Mocker) -> Self {
             Self::Mock(Arc::new(mocker))
         }
 
+        pub async fn flush_journal(&self) -> Result<()> {
+            match self {
+                Self::Real(s) => s.flush_journal().await,
+                Self::Mock(m) => unsafe { m.get("flush_journal").call(()) },
+            }
+        }
+
         pub async fn update_tasks(&self, tasks: Vec<Task>) -> Result<Vec<Task>> {
             match self {
                 Self::Real(s) => s.update_tasks(tasks).await,
@@ -344,25 +942,155 @@ pub mod test {
             }
         }
 
+        pub fn unfinished_tasks(&self) -> Result<Vec<Task>> {
+            match self {
+                Self::Real(s) => s.unfinished_tasks(),
+                Self::Mock(m) => unsafe { m.get("unfinished_tasks").call(()) },
+            }
+        }
+
+        pub fn unfetched_tasks(&self) -> Option<(TaskId, OffsetDateTime)> {
+            match self {
+                Self::Real(s) => s.unfetched_tasks(),
+                // the mocked stores don't register any task.
+                Self::Mock(_m) => None,
+            }
+        }
+
         pub async fn list_tasks(
             &self,
             from: Option<TaskId>,
             filter: Option<TaskFilter>,
             limit: Option<usize>,
+            order: TaskOrder,
         ) -> Result<Vec<Task>> {
             match self {
-                Self::Real(s) => s.list_tasks(from, filter, limit).await,
-                Self::Mock(m) => unsafe { m.get("list_tasks").call((from, filter, limit)) },
+                Self::Real(s) => s.list_tasks(from, filter, limit, order).await,
+                Self::Mock(m) => unsafe { m.get("list_tasks").call((from, filter, limit, order)) },
+            }
+        }
+
+        pub async fn put_batch_result(&self, result: BatchResult) -> Result<()> {
+            match self {
+                Self::Real(s) => s.put_batch_result(result).await,
+                Self::Mock(m) => unsafe { m.get("put_batch_result").call(result) },
+            }
+        }
+
+        pub async fn get_batch_result(
+            &self,
+            id: BatchId,
+            indexes: Option<HashSet<String>>,
+        ) -> Result<BatchResult> {
+            match self {
+                Self::Real(s) => s.get_batch_result(id, indexes).await,
+                Self::Mock(m) => unsafe { m.get("get_batch_result").call((id, indexes)) },
             }
         }
 
-        pub async fn register(&self, content: TaskContent) -> Result<Task> {
+        pub async fn list_batch_results(
+            &self,
+            from: Option<BatchId>,
+            indexes: Option<HashSet<String>>,
+            limit: Option<usize>,
+        ) -> Result<Vec<BatchResult>> {
             match self {
-                Self::Real(s) => s.register(content).await,
+                Self::Real(s) => s.list_batch_results(from, indexes, limit).await,
+                Self::Mock(m) => unsafe {
+                    m.get("list_batch_results").call((from, indexes, limit))
+                },
+            }
+        }
+
+        pub async fn register(
+            &self,
+            content: TaskContent,
+            index_uuid: Option<Uuid>,
+        ) -> Result<Task> {
+            match self {
+                Self::Real(s) => s.register(content, index_uuid).await,
                 Self::Mock(_m) => todo!(),
             }
         }
 
+        pub async fn register_succeeded(
+            &self,
+            content: TaskContent,
+            index_uuid: Option<Uuid>,
+            result: TaskResult,
+        ) -> Result<Task> {
+            match self {
+                Self::Real(s) => s.register_succeeded(content, index_uuid, result).await,
+                Self::Mock(m) => unsafe {
+                    m.get("register_succeeded")
+                        .call((content, index_uuid, result))
+                },
+            }
+        }
+
+        pub async fn register_idempotent(
+            &self,
+            content: TaskContent,
+            index_uuid: Option<Uuid>,
+            result: Option<TaskResult>,
+            key: IdempotencyKey,
+            window: Duration,
+        ) -> Result<Registration> {
+            match self {
+                Self::Real(s) => {
+                    s.register_idempotent(content, index_uuid, result, key, window)
+                        .await
+                }
+                Self::Mock(m) => unsafe {
+                    m.get("register_idempotent")
+                        .call((content, index_uuid, result, key, window))
+                },
+            }
+        }
+
+        pub async fn register_with_origin(
+            &self,
+            content: TaskContent,
+            index_uuid: Option<Uuid>,
+            result: Option<TaskResult>,
+            idempotency: Option<(IdempotencyKey, Duration)>,
+            origin: Option<TaskOrigin>,
+        ) -> Result<Registration> {
+            match self {
+                Self::Real(s) => {
+                    s.register_with_origin(content, index_uuid, result, idempotency, origin)
+                        .await
+                }
+                Self::Mock(m) => unsafe {
+                    m.get("register_with_origin").call((
+                        content,
+                        index_uuid,
+                        result,
+                        idempotency,
+                        origin,
+                    ))
+                },
+            }
+        }
+
+        pub async fn get_idempotent_task(
+            &self,
+            key: IdempotencyKey,
+            window: Duration,
+        ) -> Result<Option<Task>> {
+            match self {
+                Self::Real(s) => s.get_idempotent_task(key, window).await,
+                Self::Mock(m) => unsafe { m.get("get_idempotent_task").call((key, window)) },
+            }
+        }
+
+        pub async fn delete_expired_idempotency_keys(&self, window: Duration) -> Result<usize> {
+            match self {
+                Self::Real(s) => s.delete_expired_idempotency_keys(window).await,
+                Self::Mock(m) => unsafe { m.get("delete_expired_idempotency_keys").call(window) },
+            }
+        }
+
         pub fn register_raw_update(&self, wtxn: &mut RwTxn, task: &Task) -> Result<()> {
             match self {
                 Self::Real(s) => s.register_raw_update(wtxn, task),
@@ -370,8 +1098,12 @@ pub mod test {
             }
         }
 
-        pub fn load_dump(path: impl AsRef<Path>, env: Arc<Env>) -> anyhow::Result<()> {
-            TaskStore::load_dump(path, env)
+        pub fn load_dump(
+            path: impl AsRef<Path>,
+            update_file_store: &UpdateFileStore,
+            env: Arc<Env>,
+        ) -> anyhow::Result<()> {
+            TaskStore::load_dump(path, update_file_store, env)
         }
     }
 
@@ -386,11 +1118,18 @@ pub mod test {
 
         let gen_task = |id: TaskId| Task {
             id,
+            index_uuid: None,
             content: TaskContent::IndexCreation {
                 primary_key: None,
+                name: None,
+                namespace: None,
+                if_not_exists: false,
+                skip_templates: false,
                 index_uid: IndexUid::new_unchecked("test"),
             },
             events: Vec::new(),
+            origin: None,
+            engine_version: None,
         };
 
         let mut runner = TestRunner::new(Config::default());
@@ -417,4 +1156,133 @@ pub mod test {
             })
             .unwrap();
     }
+
+    #[actix_rt::test]
+    async fn test_replayed_idempotency_key() {
+        let tmp = tmp_env();
+        let store = TaskStore::new(tmp.env()).unwrap();
+        let window = Duration::from_secs(60);
+        let content = || TaskContent::IndexDeletion {
+            index_uid: IndexUid::new_unchecked("test"),
+        };
+        let key = |scope| IdempotencyKey::new(scope, "retry".to_string()).unwrap();
+
+        let first = store
+            .register_idempotent(content(), None, None, key(None), window)
+            .await
+            .unwrap();
+        assert!(matches!(first, Registration::Created(_)));
+
+        let replayed = store
+            .register_idempotent(content(), None, None, key(None), window)
+            .await
+            .unwrap();
+        assert!(matches!(replayed, Registration::Replayed(ref task) if task.id == 0));
+
+        // the same key sent with another API key registers a new task.
+        let scoped = store
+            .register_idempotent(content(), None, None, key(Some(Uuid::new_v4())), window)
+            .await
+            .unwrap();
+        assert!(matches!(scoped, Registration::Created(ref task) if task.id == 1));
+
+        // once the window is over, the key registers a new task.
+        let expired = store
+            .register_idempotent(content(), None, None, key(None), Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(matches!(expired, Registration::Created(ref task) if task.id == 2));
+        assert_eq!(
+            store
+                .delete_expired_idempotency_keys(Duration::ZERO)
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_dump_pending_tasks() {
+        use milli::update::IndexDocumentsMethod;
+
+        let tmp = tmp_env();
+        let dir = tempfile::tempdir().unwrap();
+        let store = TaskStore::with_journal(tmp.env(), dir.path().join("tasks.journal")).unwrap();
+        let update_file_store = UpdateFileStore::new(dir.path().join("db")).unwrap();
+
+        let (content_uuid, mut update_file) = update_file_store.new_update().unwrap();
+        crate::document_formats::read_ndjson(&b"{\"id\": 1}\n"[..], &mut *update_file).unwrap();
+        update_file.persist().unwrap();
+        let addition = || TaskContent::DocumentAddition {
+            content_uuid,
+            merge_strategy: IndexDocumentsMethod::ReplaceDocuments,
+            primary_key: Some("id".to_string()),
+            documents_count: 1,
+            allow_index_creation: true,
+            reject_duplicates: false,
+            chunks: Vec::new(),
+            return_diff: None,
+            content_digests: Default::default(),
+            sanitized_characters: None,
+            warnings: None,
+            index_uid: IndexUid::new_unchecked("test"),
+        };
+        let deletion = TaskContent::IndexDeletion {
+            index_uid: IndexUid::new_unchecked("other"),
+        };
+        let dump = TaskContent::Dump {
+            uid: "dump".to_string(),
+            include_pending_tasks: true,
+        };
+
+        // the two additions share their update file.
+        store.register(addition(), None).await.unwrap();
+        store.register(dump, None).await.unwrap();
+        store.register(addition(), None).await.unwrap();
+        store
+            .register_succeeded(deletion, None, TaskResult::Other)
+            .await
+            .unwrap();
+
+        let load = |pending_tasks| {
+            let store = store.clone();
+            let update_file_store = update_file_store.clone();
+            async move {
+                let dump_dir = tempfile::tempdir().unwrap();
+                store
+                    .dump(dump_dir.path(), update_file_store, false, pending_tasks)
+                    .await
+                    .unwrap();
+
+                let (tmp, dst) = (tmp_env(), tempfile::tempdir().unwrap());
+                UpdateFileStore::load_dump(dump_dir.path(), dst.path()).unwrap();
+                let update_file_store = UpdateFileStore::new(dst.path()).unwrap();
+                TaskStore::load_dump(dump_dir.path(), &update_file_store, tmp.env()).unwrap();
+                let tasks = TaskStore::new(tmp.env())
+                    .unwrap()
+                    .list_tasks(None, None, None, TaskOrder::Ascending)
+                    .await
+                    .unwrap();
+                (tasks, update_file_store, tmp, dst)
+            }
+        };
+
+        let (tasks, ..) = load(false).await;
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [3]);
+
+        // the pending tasks are registered again after the finished ones, in the same order.
+        let (tasks, update_file_store, _tmp, _dst) = load(true).await;
+        assert_eq!(
+            tasks.iter().map(|task| task.id).collect::<Vec<_>>(),
+            [3, 4, 5]
+        );
+        assert!(!tasks[1].is_finished() && !tasks[2].is_finished());
+        assert_eq!(tasks[1].content_uuids(), [content_uuid]);
+        // the update file claimed by the first addition is copied for the second one.
+        let copy = tasks[2].content_uuids()[0];
+        assert_ne!(copy, content_uuid);
+        for uuid in [content_uuid, copy] {
+            assert!(update_file_store.get_update(uuid).is_ok());
+        }
+    }
 }
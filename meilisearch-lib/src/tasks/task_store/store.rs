@@ -4,27 +4,47 @@ type BEU32 = milli::heed::zerocopy::U32<milli::heed::byteorder::BE>;
 
 const INDEX_UIDS_TASK_IDS: &str = "index-uids-task-ids";
 const TASKS: &str = "tasks";
+const UNFINISHED_TASK_IDS: &str = "unfinished-task-ids";
+const BATCH_RESULTS: &str = "batch-results";
+const IDEMPOTENCY_KEYS: &str = "idempotency-keys";
+const METADATA: &str = "task-store-metadata";
+
+const TASK_FORMAT_VERSION_KEY: &str = "task-format-version";
 
 use std::collections::HashSet;
 use std::ops::Bound::{Excluded, Unbounded};
-use std::result::Result as StdResult;
 use std::sync::Arc;
+use std::time::Duration;
 
-use milli::heed::types::{OwnedType, SerdeJson, Str};
+use milli::heed::types::{OwnedType, SerdeJson, Str, Unit};
 use milli::heed::{Database, Env, RoTxn, RwTxn};
 use milli::heed_codec::RoaringBitmapCodec;
 use roaring::RoaringBitmap;
+use time::OffsetDateTime;
 
+use crate::tasks::batch::{BatchId, BatchResult};
+use crate::tasks::error::TaskError;
+use crate::tasks::idempotency::IdempotencyEntry;
 use crate::tasks::task::{Task, TaskId};
 
 use super::super::Result;
-use super::TaskFilter;
+use super::codec::{TaskCodec, CURRENT_VERSION};
+use super::{TaskFilter, TaskOrder};
 
 pub struct Store {
     env: Arc<Env>,
     /// Maps an index uid to the set of tasks ids associated to it.
     index_uid_task_ids: Database<Str, RoaringBitmapCodec>,
-    tasks: Database<OwnedType<BEU32>, SerdeJson<Task>>,
+    tasks: Database<OwnedType<BEU32>, TaskCodec>,
+    /// The ids of the tasks that are not finished, so that the pending tasks are found without
+    /// reading the finished ones.
+    unfinished_task_ids: Database<OwnedType<BEU32>, Unit>,
+    /// The summaries of the processed batches, by batch id.
+    batch_results: Database<OwnedType<BEU32>, SerdeJson<BatchResult>>,
+    /// The tasks registered with an idempotency key, by scoped idempotency key.
+    idempotency_keys: Database<Str, SerdeJson<IdempotencyEntry>>,
+    /// The version of the format of the most recent tasks, under `TASK_FORMAT_VERSION_KEY`.
+    metadata: Database<Str, OwnedType<u8>>,
 }
 
 impl Drop for Store {
@@ -44,12 +64,70 @@ impl Store {
     pub fn new(env: Arc<milli::heed::Env>) -> Result<Self> {
         let index_uid_task_ids = env.create_database(Some(INDEX_UIDS_TASK_IDS))?;
         let tasks = env.create_database(Some(TASKS))?;
+        let batch_results = env.create_database(Some(BATCH_RESULTS))?;
+        let idempotency_keys = env.create_database(Some(IDEMPOTENCY_KEYS))?;
+        let metadata = env.create_database(Some(METADATA))?;
+        let (unfinished_task_ids, created) = match env.open_database(Some(UNFINISHED_TASK_IDS))? {
+            Some(db) => (db, false),
+            None => (env.create_database(Some(UNFINISHED_TASK_IDS))?, true),
+        };
 
-        Ok(Self {
+        let store = Self {
             env,
             index_uid_task_ids,
             tasks,
-        })
+            unfinished_task_ids,
+            batch_results,
+            idempotency_keys,
+            metadata,
+        };
+        store.check_task_format_version()?;
+        // The tasks written by a version that didn't track the unfinished tasks are indexed once.
+        if created {
+            store.index_unfinished_tasks()?;
+        }
+
+        Ok(store)
+    }
+
+    /// Returns an error if the tasks were written by a more recent version of Meilisearch, in a
+    /// format this version can't read. Otherwise, records that the tasks are now written in the
+    /// current format.
+    fn check_task_format_version(&self) -> Result<()> {
+        let mut wtxn = self.wtxn()?;
+        match self.metadata.get(&wtxn, TASK_FORMAT_VERSION_KEY)? {
+            Some(version) if version > CURRENT_VERSION => {
+                return Err(TaskError::UnsupportedTaskFormat {
+                    found: version,
+                    supported: CURRENT_VERSION,
+                })
+            }
+            Some(version) if version == CURRENT_VERSION => (),
+            _ => {
+                self.metadata
+                    .put(&mut wtxn, TASK_FORMAT_VERSION_KEY, &CURRENT_VERSION)?;
+            }
+        }
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    fn index_unfinished_tasks(&self) -> Result<()> {
+        let mut wtxn = self.wtxn()?;
+        let mut unfinished = Vec::new();
+        for result in self.tasks.iter(&wtxn)? {
+            let (id, task) = result?;
+            if !task.is_finished() {
+                unfinished.push(id);
+            }
+        }
+        for id in unfinished {
+            self.unfinished_task_ids.put(&mut wtxn, &id, &())?;
+        }
+        wtxn.commit()?;
+
+        Ok(())
     }
 
     pub fn wtxn(&self) -> Result<RwTxn> {
@@ -65,17 +143,27 @@ impl Store {
     /// The required `mut txn` acts as a reservation system. It guarantees that as long as you commit
     /// the task to the store in the same transaction, no one else will hav this task id.
     pub fn next_task_id(&self, txn: &mut RwTxn) -> Result<TaskId> {
+        Ok(self.last_task_id(txn)?.map_or(0, |id| id + 1))
+    }
+
+    /// Returns the id of the newest task of the store.
+    pub fn last_task_id(&self, txn: &RoTxn) -> Result<Option<TaskId>> {
         let id = self
             .tasks
             .lazily_decode_data()
             .last(txn)?
-            .map(|(id, _)| id.get() + 1)
-            .unwrap_or(0);
+            .map(|(id, _)| id.get());
         Ok(id)
     }
 
     pub fn put(&self, txn: &mut RwTxn, task: &Task) -> Result<()> {
         self.tasks.put(txn, &BEU32::new(task.id), task)?;
+        if task.is_finished() {
+            self.unfinished_task_ids.delete(txn, &BEU32::new(task.id))?;
+        } else {
+            self.unfinished_task_ids
+                .put(txn, &BEU32::new(task.id), &())?;
+        }
         // only add the task to the indexes index if it has an index_uid
         if let Some(index_uid) = task.index_uid() {
             let mut tasks_set = self
@@ -99,31 +187,30 @@ impl Store {
     /// Returns the unfinished tasks starting from the given taskId in ascending order.
     pub fn fetch_unfinished_tasks(&self, txn: &RoTxn, from: Option<TaskId>) -> Result<Vec<Task>> {
         // We must NEVER re-enqueue an already processed task! It's content uuid would point to an unexisting file.
-        //
-        // TODO(marin): This may create some latency when the first batch lazy loads the pending updates.
-        let from = from.unwrap_or_default();
-
-        let result: StdResult<Vec<_>, milli::heed::Error> = self
-            .tasks
-            .range(txn, &(BEU32::new(from)..))?
-            .map(|r| r.map(|(_, t)| t))
-            .filter(|result| result.as_ref().map_or(true, |t| !t.is_finished()))
-            .collect();
-
-        result.map_err(Into::into)
+        let mut filter = TaskFilter::default();
+        filter.filter_unfinished();
+        self.list_tasks(txn, from, Some(filter), None, TaskOrder::Ascending)
     }
 
-    /// Returns all the tasks starting from the given taskId and going in descending order.
+    /// Returns the tasks starting from the given taskId included, in the given order. Without a
+    /// taskId, the descending listing starts from the newest task and the ascending one from the
+    /// oldest.
+    ///
+    /// The tasks are read lazily, from the ids of the tasks of the filtered indexes, or of the
+    /// unfinished tasks when the filter only lists them: finding the oldest pending task only
+    /// reads this task.
     pub fn list_tasks(
         &self,
         txn: &RoTxn,
         from: Option<TaskId>,
         filter: Option<TaskFilter>,
         limit: Option<usize>,
+        order: TaskOrder,
     ) -> Result<Vec<Task>> {
-        let from = match from {
-            Some(from) => from,
-            None => self.tasks.last(txn)?.map_or(0, |(id, _)| id.get()),
+        let from = match (from, order) {
+            (Some(from), _) => from,
+            (None, TaskOrder::Ascending) => 0,
+            (None, TaskOrder::Descending) => self.last_task_id(txn)?.unwrap_or_default(),
         };
 
         let filter_fn = |task: &Task| {
@@ -133,46 +220,160 @@ impl Store {
                 .map_or(true, |f| f(task))
         };
 
-        let result: Result<Vec<_>> = match filter.as_ref().and_then(|f| f.filtered_indexes()) {
-            Some(indexes) => self
-                .compute_candidates(txn, indexes, from)?
-                .filter(|result| result.as_ref().map_or(true, filter_fn))
-                .take(limit.unwrap_or(usize::MAX))
-                .collect(),
-            None => self
-                .tasks
-                .rev_range(txn, &(..=BEU32::new(from)))?
-                .map(|r| r.map(|(_, t)| t).map_err(Into::into))
-                .filter(|result| result.as_ref().map_or(true, filter_fn))
-                .take(limit.unwrap_or(usize::MAX))
-                .collect(),
+        self.candidates(txn, from, filter.as_ref(), order)?
+            .filter(|result| result.as_ref().map_or(true, filter_fn))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    pub fn put_batch_result(&self, txn: &mut RwTxn, result: &BatchResult) -> Result<()> {
+        self.batch_results
+            .put(txn, &BEU32::new(result.id), result)?;
+        Ok(())
+    }
+
+    pub fn get_batch_result(&self, txn: &RoTxn, id: BatchId) -> Result<Option<BatchResult>> {
+        let result = self.batch_results.get(txn, &BEU32::new(id))?;
+        Ok(result)
+    }
+
+    /// Returns the batch results starting from the given batch id and going in descending order.
+    /// When `indexes` is set, only the batches of these indexes are returned.
+    pub fn list_batch_results(
+        &self,
+        txn: &RoTxn,
+        from: Option<BatchId>,
+        indexes: Option<&HashSet<String>>,
+        limit: Option<usize>,
+    ) -> Result<Vec<BatchResult>> {
+        let from = match from {
+            Some(from) => from,
+            None => self.batch_results.last(txn)?.map_or(0, |(id, _)| id.get()),
+        };
+
+        let filter_fn = |result: &BatchResult| match indexes {
+            Some(indexes) => result
+                .index_uid
+                .as_ref()
+                .map_or(false, |index_uid| indexes.contains(index_uid)),
+            None => true,
         };
 
-        result.map_err(Into::into)
+        self.batch_results
+            .rev_range(txn, &(..=BEU32::new(from)))?
+            .map(|r| r.map(|(_, result)| result).map_err(Into::into))
+            .filter(|result| result.as_ref().map_or(true, filter_fn))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
     }
 
-    fn compute_candidates<'a>(
-        &'a self,
-        txn: &'a RoTxn,
-        indexes: &HashSet<String>,
-        from: TaskId,
-    ) -> Result<impl Iterator<Item = Result<Task>> + 'a> {
-        let mut candidates = RoaringBitmap::new();
+    pub fn get_idempotency_entry(
+        &self,
+        txn: &RoTxn,
+        key: &str,
+    ) -> Result<Option<IdempotencyEntry>> {
+        Ok(self.idempotency_keys.get(txn, key)?)
+    }
 
-        for index_uid in indexes {
-            if let Some(tasks_set) = self.index_uid_task_ids.get(txn, index_uid)? {
-                candidates |= tasks_set;
+    pub fn put_idempotency_entry(
+        &self,
+        txn: &mut RwTxn,
+        key: &str,
+        entry: &IdempotencyEntry,
+    ) -> Result<()> {
+        self.idempotency_keys.put(txn, key, entry)?;
+        Ok(())
+    }
+
+    /// Deletes the idempotency keys registered more than `window` ago, and returns how many were
+    /// deleted.
+    pub fn delete_expired_idempotency_entries(
+        &self,
+        txn: &mut RwTxn,
+        window: Duration,
+    ) -> Result<usize> {
+        let now = OffsetDateTime::now_utc();
+        let mut deleted = 0;
+        let mut iter = self.idempotency_keys.iter_mut(txn)?;
+        while let Some(result) = iter.next() {
+            let (_, entry) = result?;
+            if entry.is_expired(window, now) {
+                // safety: nothing borrowed from the database is kept after the deletion.
+                unsafe { iter.del_current()? };
+                deleted += 1;
             }
         }
 
-        candidates.remove_range((Excluded(from), Unbounded));
+        Ok(deleted)
+    }
 
-        let iter = candidates
-            .into_iter()
-            .rev()
-            .filter_map(|id| self.get(txn, id).transpose());
+    /// The tasks of the indexes of `filter`, and only the unfinished ones if it only lists them,
+    /// starting from `from` included and in `order`.
+    fn candidates<'a>(
+        &'a self,
+        txn: &'a RoTxn,
+        from: TaskId,
+        filter: Option<&TaskFilter>,
+        order: TaskOrder,
+    ) -> Result<Box<dyn Iterator<Item = Result<Task>> + 'a>> {
+        let indexes = filter.and_then(TaskFilter::filtered_indexes);
+        let unfinished = filter.map_or(false, |filter| filter.unfinished);
+        let ids: Box<dyn Iterator<Item = Result<TaskId>> + 'a> = match (indexes, unfinished) {
+            (None, false) => {
+                let tasks: Box<dyn Iterator<Item = _> + 'a> = match order {
+                    TaskOrder::Ascending => Box::new(self.tasks.range(txn, &(BEU32::new(from)..))?),
+                    TaskOrder::Descending => {
+                        Box::new(self.tasks.rev_range(txn, &(..=BEU32::new(from)))?)
+                    }
+                };
+                return Ok(Box::new(
+                    tasks.map(|r| r.map(|(_, task)| task).map_err(Into::into)),
+                ));
+            }
+            (None, true) => {
+                let ids: Box<dyn Iterator<Item = _> + 'a> = match order {
+                    TaskOrder::Ascending => {
+                        Box::new(self.unfinished_task_ids.range(txn, &(BEU32::new(from)..))?)
+                    }
+                    TaskOrder::Descending => Box::new(
+                        self.unfinished_task_ids
+                            .rev_range(txn, &(..=BEU32::new(from)))?,
+                    ),
+                };
+                Box::new(ids.map(|r| r.map(|(id, _)| id.get()).map_err(Into::into)))
+            }
+            (Some(indexes), unfinished) => {
+                let mut candidates = RoaringBitmap::new();
+                for index_uid in indexes {
+                    if let Some(tasks_set) = self.index_uid_task_ids.get(txn, index_uid)? {
+                        candidates |= tasks_set;
+                    }
+                }
+                if unfinished {
+                    let mut unfinished_ids = RoaringBitmap::new();
+                    for result in self.unfinished_task_ids.iter(txn)? {
+                        unfinished_ids.insert(result?.0.get());
+                    }
+                    candidates &= unfinished_ids;
+                }
+
+                match order {
+                    TaskOrder::Ascending => {
+                        candidates.remove_range((Unbounded, Excluded(from)));
+                        Box::new(candidates.into_iter().map(Ok))
+                    }
+                    TaskOrder::Descending => {
+                        candidates.remove_range((Excluded(from), Unbounded));
+                        Box::new(candidates.into_iter().rev().map(Ok))
+                    }
+                }
+            }
+        };
 
-        Ok(iter)
+        Ok(Box::new(ids.filter_map(move |id| match id {
+            Ok(id) => self.get(txn, id).transpose(),
+            Err(e) => Some(Err(e)),
+        })))
     }
 }
 
@@ -240,6 +441,13 @@ pub mod test {
             }
         }
 
+        pub fn last_task_id(&self, txn: &RoTxn) -> Result<Option<TaskId>> {
+            match self {
+                MockStore::Real(index) => index.last_task_id(txn),
+                MockStore::Fake(_) => todo!(),
+            }
+        }
+
         pub fn put(&self, txn: &mut RwTxn, task: &Task) -> Result<()> {
             match self {
                 MockStore::Real(index) => index.put(txn, task),
@@ -271,9 +479,71 @@ pub mod test {
             from: Option<TaskId>,
             filter: Option<TaskFilter>,
             limit: Option<usize>,
+            order: TaskOrder,
         ) -> Result<Vec<Task>> {
             match self {
-                MockStore::Real(index) => index.list_tasks(txn, from, filter, limit),
+                MockStore::Real(index) => index.list_tasks(txn, from, filter, limit, order),
+                MockStore::Fake(_) => todo!(),
+            }
+        }
+
+        pub fn put_batch_result(&self, txn: &mut RwTxn, result: &BatchResult) -> Result<()> {
+            match self {
+                MockStore::Real(index) => index.put_batch_result(txn, result),
+                MockStore::Fake(_) => todo!(),
+            }
+        }
+
+        pub fn get_batch_result(&self, txn: &RoTxn, id: BatchId) -> Result<Option<BatchResult>> {
+            match self {
+                MockStore::Real(index) => index.get_batch_result(txn, id),
+                MockStore::Fake(_) => todo!(),
+            }
+        }
+
+        pub fn list_batch_results(
+            &self,
+            txn: &RoTxn,
+            from: Option<BatchId>,
+            indexes: Option<&HashSet<String>>,
+            limit: Option<usize>,
+        ) -> Result<Vec<BatchResult>> {
+            match self {
+                MockStore::Real(index) => index.list_batch_results(txn, from, indexes, limit),
+                MockStore::Fake(_) => todo!(),
+            }
+        }
+
+        pub fn get_idempotency_entry(
+            &self,
+            txn: &RoTxn,
+            key: &str,
+        ) -> Result<Option<IdempotencyEntry>> {
+            match self {
+                MockStore::Real(index) => index.get_idempotency_entry(txn, key),
+                MockStore::Fake(_) => todo!(),
+            }
+        }
+
+        pub fn put_idempotency_entry(
+            &self,
+            txn: &mut RwTxn,
+            key: &str,
+            entry: &IdempotencyEntry,
+        ) -> Result<()> {
+            match self {
+                MockStore::Real(index) => index.put_idempotency_entry(txn, key, entry),
+                MockStore::Fake(_) => todo!(),
+            }
+        }
+
+        pub fn delete_expired_idempotency_entries(
+            &self,
+            txn: &mut RwTxn,
+            window: Duration,
+        ) -> Result<usize> {
+            match self {
+                MockStore::Real(index) => index.delete_expired_idempotency_entries(txn, window),
                 MockStore::Fake(_) => todo!(),
             }
         }
@@ -287,10 +557,13 @@ pub mod test {
         let tasks = (0..100)
             .map(|_| Task {
                 id: rand::random(),
+                index_uuid: None,
                 content: TaskContent::IndexDeletion {
                     index_uid: IndexUid::new_unchecked("test"),
                 },
                 events: vec![],
+                origin: None,
+                engine_version: None,
             })
             .collect::<Vec<_>>();
 
@@ -303,7 +576,9 @@ pub mod test {
         let mut filter = TaskFilter::default();
         filter.filter_index("test".into());
 
-        let tasks = store.list_tasks(&txn, None, Some(filter), None).unwrap();
+        let tasks = store
+            .list_tasks(&txn, None, Some(filter), None, TaskOrder::Descending)
+            .unwrap();
 
         assert!(tasks
             .iter()
@@ -319,18 +594,24 @@ pub mod test {
 
         let task_1 = Task {
             id: 1,
+            index_uuid: None,
             content: TaskContent::IndexDeletion {
                 index_uid: IndexUid::new_unchecked("test"),
             },
             events: vec![],
+            origin: None,
+            engine_version: None,
         };
 
         let task_2 = Task {
             id: 0,
+            index_uuid: None,
             content: TaskContent::IndexDeletion {
                 index_uid: IndexUid::new_unchecked("test1"),
             },
             events: vec![],
+            origin: None,
+            engine_version: None,
         };
 
         let mut txn = store.wtxn().unwrap();
@@ -340,7 +621,9 @@ pub mod test {
         let mut filter = TaskFilter::default();
         filter.filter_index("test".into());
 
-        let tasks = store.list_tasks(&txn, None, Some(filter), None).unwrap();
+        let tasks = store
+            .list_tasks(&txn, None, Some(filter), None, TaskOrder::Descending)
+            .unwrap();
 
         txn.abort().unwrap();
         assert_eq!(tasks.len(), 1);
@@ -349,17 +632,23 @@ pub mod test {
         // same thing but invert the ids
         let task_1 = Task {
             id: 0,
+            index_uuid: None,
             content: TaskContent::IndexDeletion {
                 index_uid: IndexUid::new_unchecked("test"),
             },
             events: vec![],
+            origin: None,
+            engine_version: None,
         };
         let task_2 = Task {
             id: 1,
+            index_uuid: None,
             content: TaskContent::IndexDeletion {
                 index_uid: IndexUid::new_unchecked("test1"),
             },
             events: vec![],
+            origin: None,
+            engine_version: None,
         };
 
         let mut txn = store.wtxn().unwrap();
@@ -369,7 +658,9 @@ pub mod test {
         let mut filter = TaskFilter::default();
         filter.filter_index("test".into());
 
-        let tasks = store.list_tasks(&txn, None, Some(filter), None).unwrap();
+        let tasks = store
+            .list_tasks(&txn, None, Some(filter), None, TaskOrder::Descending)
+            .unwrap();
 
         assert_eq!(tasks.len(), 1);
         assert_eq!(
@@ -377,4 +668,216 @@ pub mod test {
             "test"
         );
     }
+
+    #[test]
+    fn test_list_batch_results() {
+        use crate::tasks::batch::BatchOutcome;
+        use time::OffsetDateTime;
+
+        let tmp = tmp_env();
+        let store = Store::new(tmp.env()).unwrap();
+
+        let gen_result = |id: BatchId, index_uid: Option<&str>| BatchResult {
+            id,
+            index_uid: index_uid.map(ToString::to_string),
+            task_ids: vec![id],
+            documents_count: 0,
+            outcome: BatchOutcome::Succeeded,
+            error: None,
+            stop_reason: None,
+            debounce_ms: None,
+            started_at: OffsetDateTime::now_utc(),
+            finished_at: OffsetDateTime::now_utc(),
+        };
+
+        let mut txn = store.wtxn().unwrap();
+        store
+            .put_batch_result(&mut txn, &gen_result(0, Some("test")))
+            .unwrap();
+        store
+            .put_batch_result(&mut txn, &gen_result(3, None))
+            .unwrap();
+        store
+            .put_batch_result(&mut txn, &gen_result(5, Some("test1")))
+            .unwrap();
+        store
+            .put_batch_result(&mut txn, &gen_result(8, Some("test")))
+            .unwrap();
+
+        let ids = |results: Vec<BatchResult>| results.iter().map(|r| r.id).collect::<Vec<_>>();
+
+        let results = store.list_batch_results(&txn, None, None, None).unwrap();
+        assert_eq!(ids(results), vec![8, 5, 3, 0]);
+
+        let results = store
+            .list_batch_results(&txn, Some(4), None, Some(1))
+            .unwrap();
+        assert_eq!(ids(results), vec![3]);
+
+        let indexes = HashSet::from(["test".to_string()]);
+        let results = store
+            .list_batch_results(&txn, None, Some(&indexes), None)
+            .unwrap();
+        assert_eq!(ids(results), vec![8, 0]);
+
+        assert_eq!(store.get_batch_result(&txn, 5).unwrap().unwrap().id, 5);
+        assert!(store.get_batch_result(&txn, 6).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_list_tasks_in_both_orders() {
+        use crate::tasks::task::{TaskEvent, TaskResult};
+
+        let tmp = tmp_env();
+        let store = Store::new(tmp.env()).unwrap();
+
+        let gen_task = |id: TaskId, finished: bool| {
+            let index_uid = if id % 2 == 0 { "test" } else { "other" };
+            let mut events = vec![TaskEvent::Created(OffsetDateTime::now_utc())];
+            if finished {
+                events.push(TaskEvent::succeeded(TaskResult::Other));
+            }
+            Task {
+                id,
+                index_uuid: None,
+                content: TaskContent::IndexDeletion {
+                    index_uid: IndexUid::new_unchecked(index_uid),
+                },
+                events,
+                origin: None,
+                engine_version: None,
+            }
+        };
+
+        let mut txn = store.wtxn().unwrap();
+        for id in 0..6 {
+            store
+                .put(&mut txn, &gen_task(id, id != 3 && id != 4 && id != 5))
+                .unwrap();
+        }
+
+        let ids = |tasks: Vec<Task>| tasks.iter().map(|t| t.id).collect::<Vec<_>>();
+        let list = |from, filter, limit, order| {
+            ids(store.list_tasks(&txn, from, filter, limit, order).unwrap())
+        };
+        let unfinished = || {
+            let mut filter = TaskFilter::default();
+            filter.filter_unfinished();
+            Some(filter)
+        };
+        let test_index = |unfinished| {
+            let mut filter = TaskFilter::default();
+            filter.filter_index("test".into());
+            if unfinished {
+                filter.filter_unfinished();
+            }
+            Some(filter)
+        };
+
+        assert_eq!(
+            list(None, None, None, TaskOrder::Descending),
+            vec![5, 4, 3, 2, 1, 0]
+        );
+        assert_eq!(
+            list(None, None, None, TaskOrder::Ascending),
+            vec![0, 1, 2, 3, 4, 5]
+        );
+        // the listing starts from the given task, included, in both orders.
+        assert_eq!(
+            list(Some(2), None, Some(2), TaskOrder::Descending),
+            vec![2, 1]
+        );
+        assert_eq!(
+            list(Some(2), None, Some(2), TaskOrder::Ascending),
+            vec![2, 3]
+        );
+
+        assert_eq!(
+            list(None, unfinished(), Some(1), TaskOrder::Ascending),
+            vec![3]
+        );
+        assert_eq!(
+            list(None, unfinished(), None, TaskOrder::Descending),
+            vec![5, 4, 3]
+        );
+        assert_eq!(
+            list(Some(4), unfinished(), None, TaskOrder::Ascending),
+            vec![4, 5]
+        );
+
+        assert_eq!(
+            list(None, test_index(false), None, TaskOrder::Ascending),
+            vec![0, 2, 4]
+        );
+        assert_eq!(
+            list(Some(3), test_index(false), None, TaskOrder::Descending),
+            vec![2, 0]
+        );
+        assert_eq!(
+            list(None, test_index(true), None, TaskOrder::Ascending),
+            vec![4]
+        );
+
+        // a finished task is no longer listed with the unfinished ones.
+        store.put(&mut txn, &gen_task(3, true)).unwrap();
+        assert_eq!(
+            ids(store.fetch_unfinished_tasks(&txn, None).unwrap()),
+            vec![4, 5]
+        );
+        assert_eq!(
+            ids(store.fetch_unfinished_tasks(&txn, Some(5)).unwrap()),
+            vec![5]
+        );
+    }
+
+    #[test]
+    fn test_tasks_written_before_versioning_are_read() {
+        use milli::heed::types::ByteSlice;
+
+        let tmp = tmp_env();
+        let store = Store::new(tmp.env()).unwrap();
+
+        let mut txn = store.wtxn().unwrap();
+        store
+            .tasks
+            .remap_data_type::<ByteSlice>()
+            .put(
+                &mut txn,
+                &BEU32::new(0),
+                include_bytes!("corpus/v0/dump.task"),
+            )
+            .unwrap();
+
+        let task = store.get(&txn, 0).unwrap().unwrap();
+        assert!(matches!(task.content, TaskContent::Dump { .. }));
+
+        // once written again, the task is in the current format.
+        store.put(&mut txn, &task).unwrap();
+        let bytes = store
+            .tasks
+            .remap_data_type::<ByteSlice>()
+            .get(&txn, &BEU32::new(0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(bytes[0], CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_downgrades_are_refused() {
+        let tmp = tmp_env();
+        let store = Store::new(tmp.env()).unwrap();
+
+        let mut txn = store.wtxn().unwrap();
+        store
+            .metadata
+            .put(&mut txn, TASK_FORMAT_VERSION_KEY, &(CURRENT_VERSION + 1))
+            .unwrap();
+        txn.commit().unwrap();
+        drop(store);
+
+        assert!(matches!(
+            Store::new(tmp.env()),
+            Err(TaskError::UnsupportedTaskFormat { found, .. }) if found == CURRENT_VERSION + 1
+        ));
+    }
 }
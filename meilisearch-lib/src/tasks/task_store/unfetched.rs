@@ -0,0 +1,100 @@
+//! The tasks registered in the store but not fetched by the scheduler yet.
+//!
+//! The scheduler fetches the unfinished tasks registered since its last fetch each time it
+//! prepares a batch. The registrations are tracked in memory, so that the lag of the scheduler can
+//! be reported without scanning the store. A fetch forgets the registrations it saw, the ones of
+//! the tasks canceled before being fetched included.
+
+use std::collections::BTreeMap;
+
+use parking_lot::Mutex;
+use time::OffsetDateTime;
+
+use crate::tasks::task::{Task, TaskId};
+
+#[derive(Default)]
+pub struct UnfetchedTasks {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// The tasks up to this id were seen by a fetch.
+    fetched_up_to: Option<TaskId>,
+    /// The date the unfinished tasks registered since the last fetch were enqueued at, by id.
+    enqueued_at: BTreeMap<TaskId, OffsetDateTime>,
+}
+
+impl UnfetchedTasks {
+    /// Records the registration of `task`. It is ignored if a fetch already saw it, which happens
+    /// when the fetch reads the store right after the task is written.
+    pub fn registered(&self, task: &Task) {
+        if task.is_finished() {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        if inner.fetched_up_to.map_or(false, |id| task.id <= id) {
+            return;
+        }
+        let enqueued_at = task.enqueued_at().unwrap_or_else(OffsetDateTime::now_utc);
+        inner.enqueued_at.insert(task.id, enqueued_at);
+    }
+
+    /// Forgets the tasks up to `up_to`, which were seen by a fetch.
+    pub fn fetched(&self, up_to: TaskId) {
+        let mut inner = self.inner.lock();
+        inner.fetched_up_to = inner.fetched_up_to.max(Some(up_to));
+        inner.enqueued_at = inner.enqueued_at.split_off(&(up_to + 1));
+    }
+
+    /// Returns the id of the newest task not fetched yet, and the date the oldest one was enqueued
+    /// at.
+    pub fn summary(&self) -> Option<(TaskId, OffsetDateTime)> {
+        let inner = self.inner.lock();
+        let (&newest, _) = inner.enqueued_at.iter().next_back()?;
+        let (_, &oldest) = inner.enqueued_at.iter().next()?;
+        Some((newest, oldest))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::Duration;
+
+    use super::*;
+    use crate::tasks::task::{TaskContent, TaskEvent};
+
+    fn task(id: TaskId, enqueued_at: OffsetDateTime) -> Task {
+        Task {
+            id,
+            index_uuid: None,
+            content: TaskContent::Dump {
+                uid: id.to_string(),
+                include_pending_tasks: false,
+            },
+            events: vec![TaskEvent::Created(enqueued_at)],
+            origin: None,
+            engine_version: None,
+        }
+    }
+
+    #[test]
+    fn test_fetched_tasks_are_forgotten() {
+        let now = OffsetDateTime::now_utc();
+        let unfetched = UnfetchedTasks::default();
+        assert_eq!(unfetched.summary(), None);
+
+        unfetched.registered(&task(0, now - Duration::seconds(10)));
+        unfetched.registered(&task(1, now - Duration::seconds(5)));
+        unfetched.registered(&task(2, now));
+        assert_eq!(unfetched.summary(), Some((2, now - Duration::seconds(10))));
+
+        unfetched.fetched(1);
+        assert_eq!(unfetched.summary(), Some((2, now)));
+
+        // a task seen by the fetch before its registration was recorded.
+        unfetched.fetched(3);
+        unfetched.registered(&task(3, now));
+        assert_eq!(unfetched.summary(), None);
+    }
+}
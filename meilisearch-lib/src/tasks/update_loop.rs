@@ -1,23 +1,61 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+use futures::FutureExt;
 use time::OffsetDateTime;
-use tokio::sync::{watch, RwLock};
-use tokio::time::interval_at;
+use tokio::sync::{Notify, RwLock};
+use tokio::time::{sleep_until, Instant};
 
-use super::batch::Batch;
-use super::error::Result;
+use super::batch::{Batch, BatchResult};
+use super::error::{Result, TaskError};
+use super::notifier::NotificationReceiver;
 use super::{BatchHandler, Scheduler};
 use crate::tasks::task::TaskEvent;
 
+/// Aborts the batch being processed. The update loop stops waiting for its handler, and leaves the
+/// results of its tasks to whoever aborted it.
+#[derive(Clone, Default)]
+pub struct BatchAbort {
+    aborted: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl BatchAbort {
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+    }
+
+    async fn aborted(&self) {
+        // the future is created before checking the flag, so that it is woken up by an abort
+        // happening in between.
+        let notified = self.notify.notified();
+        if !self.is_aborted() {
+            notified.await;
+        }
+    }
+}
+
 /// The update loop sequentially performs batches of updates by asking the scheduler for a batch,
 /// and handing it to the `TaskPerformer`.
 pub struct UpdateLoop {
     scheduler: Arc<RwLock<Scheduler>>,
     performers: Vec<Arc<dyn BatchHandler + Send + Sync + 'static>>,
 
-    notifier: Option<watch::Receiver<()>>,
+    notifier: Option<NotificationReceiver>,
     debounce_duration: Option<Duration>,
+    /// Whether the debounce of each batch is chosen by the scheduler, from the arrival rate of the
+    /// tasks of its index, rather than being `debounce_duration`.
+    adaptive_debounce: bool,
+    /// The age of the oldest pending task over which a batch is processed without waiting for a
+    /// notification or for the end of the debounce.
+    max_task_age: Option<Duration>,
 }
 
 impl UpdateLoop {
@@ -25,12 +63,16 @@ impl UpdateLoop {
         scheduler: Arc<RwLock<Scheduler>>,
         performers: Vec<Arc<dyn BatchHandler + Send + Sync + 'static>>,
         debuf_duration: Option<Duration>,
-        notifier: watch::Receiver<()>,
+        adaptive_debounce: bool,
+        max_task_age: Option<Duration>,
+        notifier: NotificationReceiver,
     ) -> Self {
         Self {
             scheduler,
             performers,
             debounce_duration: debuf_duration,
+            adaptive_debounce,
+            max_task_age,
             notifier: Some(notifier),
         }
     }
@@ -39,23 +81,97 @@ impl UpdateLoop {
         let mut notifier = self.notifier.take().unwrap();
 
         loop {
-            if notifier.changed().await.is_err() {
-                break;
+            // The notifications sent from now on wake the loop up again once this batch is
+            // processed, even those sent while debouncing.
+            let deadline = self.batch_deadline().await;
+            tokio::select! {
+                changed = notifier.changed() => if changed.is_none() {
+                    break;
+                },
+                _ = sleep_until_deadline(deadline) => (),
             }
 
-            if let Some(t) = self.debounce_duration {
-                let mut interval = interval_at(tokio::time::Instant::now() + t, t);
-                interval.tick().await;
+            let debounce = if self.adaptive_debounce {
+                self.scheduler.read().await.adaptive_debounce()
+            } else {
+                self.debounce_duration
+            };
+            if let Some(t) = debounce {
+                let end = Instant::now() + t;
+                let end = match self.batch_deadline().await {
+                    Some(deadline) => end.min(deadline),
+                    None => end,
+                };
+                sleep_until(end).await;
             };
 
-            if let Err(e) = self.process_next_batch().await {
+            if let Err(e) = self.process_next_batch(debounce).await {
                 log::error!("an error occured while processing an update batch: {}", e);
             }
         }
     }
 
-    async fn process_next_batch(&self) -> Result<()> {
-        let mut batch = { self.scheduler.write().await.prepare().await? };
+    /// The instant a batch must be processed without waiting for a notification: when the oldest
+    /// pending task becomes too old to wait any longer for its batch, if there is a maximum age,
+    /// when the first task scheduled for later becomes due, or when the first lock of an index
+    /// reaches its deadline.
+    async fn batch_deadline(&self) -> Option<Instant> {
+        let mut scheduler = self.scheduler.write().await;
+        let oldest = match scheduler.oldest_pending_enqueued_at().await {
+            Ok(oldest) => oldest,
+            Err(e) => {
+                log::error!("an error occured while fetching the pending tasks: {}", e);
+                return None;
+            }
+        };
+        let scheduled_at = scheduler.next_scheduled_at();
+        // the tasks of a locked index are processed once its lock is forcibly released.
+        let lock_deadline = scheduler.next_lock_deadline().map(Instant::from_std);
+        drop(scheduler);
+
+        let now = OffsetDateTime::now_utc();
+        let too_old = self.max_task_age.zip(oldest).map(|(max_task_age, oldest)| {
+            // a task enqueued in the future, according to the clock, is as young as can be.
+            let age = Duration::try_from(now - oldest).unwrap_or_default();
+            Instant::now() + max_task_age.saturating_sub(age)
+        });
+        let due = scheduled_at
+            .map(|at| Instant::now() + Duration::try_from(at - now).unwrap_or_default());
+
+        [too_old, due, lock_deadline].into_iter().flatten().min()
+    }
+
+    /// Processes the next batch, whose tasks were waited for during `debounce`.
+    async fn process_next_batch(&self, debounce: Option<Duration>) -> Result<()> {
+        let (batch, abort) = {
+            let mut scheduler = self.scheduler.write().await;
+            let batch = scheduler.prepare().await?;
+            (batch, scheduler.batch_abort())
+        };
+
+        let batch_id = batch.id;
+        let result = self.process_batch(batch, debounce, &abort).await;
+        if let Err(ref error) = result {
+            // the batch is given up, so that the next one can be prepared. An aborted batch was
+            // already finished by whoever aborted it.
+            let mut scheduler = self.scheduler.write().await;
+            if !abort.is_aborted() {
+                if let Err(e) = scheduler.give_up(batch_id, error).await {
+                    log::error!("could not give up batch {:?}: {}", batch_id, e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Processes `batch`, returned by the scheduler, until it is finished.
+    async fn process_batch(
+        &self,
+        mut batch: Batch,
+        debounce: Option<Duration>,
+        abort: &BatchAbort,
+    ) -> Result<()> {
         let performer = self
             .performers
             .iter()
@@ -63,9 +179,8 @@ impl UpdateLoop {
             .expect("No performer found for batch")
             .clone();
 
-        batch
-            .content
-            .push_event(TaskEvent::Processing(OffsetDateTime::now_utc()));
+        let started_at = OffsetDateTime::now_utc();
+        batch.content.push_event(TaskEvent::Processing(started_at));
 
         batch.content = {
             self.scheduler
@@ -75,9 +190,34 @@ impl UpdateLoop {
                 .await?
         };
 
-        let batch = performer.process_batch(batch).await;
+        let batch_id = batch.id;
+        // a panic of the performer fails the batch instead of the update loop.
+        let processed = AssertUnwindSafe(performer.process_batch(batch)).catch_unwind();
+        let batch = tokio::select! {
+            batch = processed => batch.map_err(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                TaskError::BatchPanicked(message)
+            })?,
+            _ = abort.aborted() => {
+                log::warn!("batch {:?} was aborted while being processed", batch_id);
+                return Ok(());
+            }
+        };
+
+        if self.adaptive_debounce {
+            log::info!(
+                "batch {:?} waited for its tasks during an adaptive debounce of {:?}",
+                batch_id,
+                debounce.unwrap_or_default()
+            );
+        }
 
-        self.handle_batch_result(batch, performer).await?;
+        self.handle_batch_result(batch, started_at, debounce, performer, abort)
+            .await?;
 
         Ok(())
     }
@@ -86,18 +226,41 @@ impl UpdateLoop {
     ///
     /// When a task is processed, the result of the process is pushed to its event list. The
     /// `handle_batch_result` make sure that the new state is saved to the store.
-    /// The tasks are then removed from the processing queue.
+    /// The tasks are then removed from the processing queue, and a summary of the batch is saved.
     async fn handle_batch_result(
         &self,
         mut batch: Batch,
+        started_at: OffsetDateTime,
+        debounce: Option<Duration>,
         performer: Arc<dyn BatchHandler + Sync + Send + 'static>,
+        abort: &BatchAbort,
     ) -> Result<()> {
         let mut scheduler = self.scheduler.write().await;
+        // the batch was aborted while its result was waiting for the scheduler, its tasks were
+        // already failed.
+        if abort.is_aborted() {
+            return Ok(());
+        }
         let content = scheduler.update_tasks(batch.content).await?;
-        scheduler.finish();
-        drop(scheduler);
+        scheduler.requeue_chunked_additions(&content);
+        scheduler.finish(batch.id)?;
         batch.content = content;
+        if let Some(mut result) = BatchResult::new(&batch, started_at) {
+            result.debounce_ms = debounce.map(|debounce| debounce.as_millis() as u64);
+            // the tasks are already saved, losing the summary of their batch is not worth failing.
+            if let Err(e) = scheduler.put_batch_result(result).await {
+                log::error!("could not save the result of batch {:?}: {}", batch.id, e);
+            }
+        }
+        drop(scheduler);
         performer.finish(&batch).await;
         Ok(())
     }
 }
+
+async fn sleep_until_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,155 @@
+//! Waiting for tasks to be processed.
+//!
+//! A waiter subscribes to the lifecycle events of the tasks, and only reads the tasks from the
+//! store again when one of the tasks it waits for is announced finished. A waiter without the
+//! events, or whose scheduler is gone, polls the store with an exponential backoff instead.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::time::{sleep, timeout_at, Instant};
+
+use super::error::{Result, TaskError};
+use super::events::{TaskEventStream, TaskLifecycleEvent};
+use super::task::{Task, TaskId};
+use super::TaskStore;
+
+/// The interval between the first polls of the store.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// The interval between the polls of the store never grows above this one.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct TaskWaiter {
+    store: TaskStore,
+    events: Option<TaskEventStream>,
+}
+
+impl TaskWaiter {
+    /// Creates a waiter reading the tasks from `store`, woken up by the lifecycle `events` if
+    /// any.
+    pub fn new(store: TaskStore, events: Option<TaskEventStream>) -> Self {
+        Self { store, events }
+    }
+
+    /// Waits for the task `id` to be finished, and returns it. Fails with a `WaitTimeout` error
+    /// holding the last state of the task once `timeout` elapsed.
+    pub async fn wait_for_task(self, id: TaskId, timeout: Option<Duration>) -> Result<Task> {
+        let mut tasks = self.wait_for_tasks(&[id], timeout).await?;
+        Ok(tasks.remove(0))
+    }
+
+    /// Waits for all the tasks `ids` to be finished, and returns them in the same order. Fails
+    /// with a `WaitTimeout` error holding the last state of the first unfinished task once
+    /// `timeout` elapsed.
+    pub async fn wait_for_tasks(
+        mut self,
+        ids: &[TaskId],
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Task>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut poll_interval = MIN_POLL_INTERVAL;
+
+        loop {
+            // the events were subscribed before the store was first read, and they are kept from
+            // then on, so that no finished task is missed.
+            let mut tasks = Vec::with_capacity(ids.len());
+            for &id in ids {
+                tasks.push(self.store.get_task(id, None).await?);
+            }
+            let pending = match tasks.iter().position(|task| !task.is_finished()) {
+                Some(pending) => pending,
+                None => return Ok(tasks),
+            };
+
+            // returns whether the announcements stopped.
+            let wake = async {
+                match self.events {
+                    Some(ref mut events) => loop {
+                        match events.next().await {
+                            // a task waited for was finished, or some events were missed: the
+                            // store is read again.
+                            Some(TaskLifecycleEvent::Finished { task_id, .. })
+                                if ids.contains(&task_id) =>
+                            {
+                                break false
+                            }
+                            Some(TaskLifecycleEvent::Lagged(_)) => break false,
+                            Some(_) => continue,
+                            None => break true,
+                        }
+                    },
+                    None => {
+                        sleep(poll_interval).await;
+                        poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL);
+                        false
+                    }
+                }
+            };
+
+            let closed = match deadline {
+                Some(deadline) => match timeout_at(deadline, wake).await {
+                    Ok(closed) => closed,
+                    Err(_) => {
+                        return Err(TaskError::WaitTimeout(Box::new(tasks.swap_remove(pending))))
+                    }
+                },
+                None => wake.await,
+            };
+            if closed {
+                self.events = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use milli::heed::EnvOpenOptions;
+
+    use super::*;
+    use crate::index_resolver::IndexUid;
+    use crate::tasks::task::{TaskContent, TaskEvent, TaskResult};
+
+    #[actix_rt::test]
+    async fn test_wait_without_announcements_polls_the_store() {
+        // the clock is virtual, it advances whenever the runtime is idle.
+        tokio::time::pause();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100000);
+        options.max_dbs(1000);
+        let env = Arc::new(options.open(tmp.path()).unwrap());
+        let store = TaskStore::new(env).unwrap();
+
+        let content = TaskContent::IndexDeletion {
+            index_uid: IndexUid::new_unchecked("test"),
+        };
+        let mut task = store.register(content, None).await.unwrap();
+
+        // the task is finished behind the back of the waiter.
+        let finishing = {
+            let store = store.clone();
+            tokio::task::spawn_local(async move {
+                sleep(Duration::from_secs(30)).await;
+                task.events.push(TaskEvent::succeeded(TaskResult::Other));
+                store.update_tasks(vec![task]).await.unwrap();
+            })
+        };
+
+        let waiter = TaskWaiter::new(store.clone(), None);
+        let task = waiter
+            .wait_for_task(0, Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        assert!(task.is_finished());
+        finishing.await.unwrap();
+
+        // the task is already finished, the wait returns right away.
+        let waiter = TaskWaiter::new(store, None);
+        let tasks = waiter.wait_for_tasks(&[0], Some(Duration::ZERO)).await;
+        assert!(tasks.is_ok());
+    }
+}
@@ -0,0 +1,227 @@
+//! IO throttling of the snapshots and dumps writers.
+//!
+//! The copy of the databases into a snapshot or a dump competes with the searches for the disk.
+//! The writers are paced by a token bucket filled at the configured budget, in bytes per second,
+//! and holding at most a second worth of tokens: a write that empties the bucket pauses the writer
+//! until the bucket is refilled. In the adaptive mode, the budget is cut while the recent latency
+//! of the searches exceeds a threshold, so that the writers back off when the searches suffer.
+//!
+//! The LMDB environments are copied by LMDB itself, which can't be paced: their copy is accounted
+//! for once it is done, and the pause that follows brings the average throughput back under the
+//! budget.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// The part of the budget left to the writers while the searches are slow.
+const ADAPTIVE_BACKOFF_DIVISOR: f64 = 4.0;
+/// The size of the writes the throttled writers are paced by.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The recent latency of the searches, as an exponential moving average.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyWatch {
+    recent_micros: Arc<AtomicU64>,
+}
+
+impl LatencyWatch {
+    pub fn record(&self, latency: Duration) {
+        let sample = latency.as_micros() as u64;
+        // each search weighs for an eighth of the average.
+        let _ = self
+            .recent_micros
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |recent| {
+                Some(recent - recent / 8 + sample / 8)
+            });
+    }
+
+    pub fn recent(&self) -> Duration {
+        Duration::from_micros(self.recent_micros.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IoThrottleConfig {
+    /// The budget of the writers, in bytes per second. Unlimited if `None`.
+    pub budget: Option<u64>,
+    /// The recent search latency above which the writers back off, if the throttle is adaptive.
+    pub adaptive_latency_threshold: Option<Duration>,
+    pub search_latency: LatencyWatch,
+}
+
+impl IoThrottleConfig {
+    /// The configuration of a writer that is never throttled, like a snapshot needed to restore
+    /// an instance.
+    pub fn unlimited(&self) -> Self {
+        Self {
+            budget: None,
+            adaptive_latency_threshold: None,
+            search_latency: self.search_latency.clone(),
+        }
+    }
+}
+
+/// What the throttle did to a writer, to tune the budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "camelCase")]
+pub struct ThrottleReport {
+    pub written_bytes: u64,
+    /// The written bytes over the whole duration of the writing, pauses included.
+    pub average_bytes_per_sec: u64,
+    pub paused_ms: u64,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    /// Takes `bytes` tokens from the bucket filled at `rate` bytes per second, returning how long
+    /// the writer must pause for the bucket to be refilled.
+    fn take(&mut self, bytes: u64, rate: f64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * rate).min(rate) - bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+pub struct IoThrottle {
+    config: IoThrottleConfig,
+    bucket: Bucket,
+    started: Instant,
+    written: u64,
+    paused: Duration,
+}
+
+impl IoThrottle {
+    pub fn new(config: IoThrottleConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            bucket: Bucket {
+                tokens: config.budget.unwrap_or_default() as f64,
+                last: now,
+            },
+            config,
+            started: now,
+            written: 0,
+            paused: Duration::ZERO,
+        }
+    }
+
+    /// The budget in effect, in bytes per second.
+    fn rate(&self) -> Option<f64> {
+        let budget = self.config.budget.filter(|budget| *budget > 0)? as f64;
+        match self.config.adaptive_latency_threshold {
+            Some(threshold) if self.config.search_latency.recent() > threshold => {
+                Some(budget / ADAPTIVE_BACKOFF_DIVISOR)
+            }
+            _ => Some(budget),
+        }
+    }
+
+    /// Accounts for `bytes` written, pausing the calling thread if they exceed the budget.
+    pub fn consume(&mut self, bytes: u64) {
+        self.written += bytes;
+        if let Some(rate) = self.rate() {
+            let pause = self.bucket.take(bytes, rate, Instant::now());
+            if !pause.is_zero() {
+                thread::sleep(pause);
+                self.paused += pause;
+            }
+        }
+    }
+
+    pub fn report(&self) -> ThrottleReport {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        ThrottleReport {
+            written_bytes: self.written,
+            average_bytes_per_sec: match elapsed > 0.0 {
+                true => (self.written as f64 / elapsed) as u64,
+                false => 0,
+            },
+            paused_ms: self.paused.as_millis() as u64,
+        }
+    }
+}
+
+/// A writer paced by a throttle.
+pub struct ThrottledWriter<'a, W> {
+    inner: W,
+    throttle: &'a mut IoThrottle,
+}
+
+impl<'a, W: Write> ThrottledWriter<'a, W> {
+    pub fn new(inner: W, throttle: &'a mut IoThrottle) -> Self {
+        Self { inner, throttle }
+    }
+}
+
+impl<W: Write> Write for ThrottledWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let buf = &buf[..buf.len().min(CHUNK_SIZE)];
+        let written = self.inner.write(buf)?;
+        self.throttle.consume(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bucket_pacing() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 1000.0,
+            last: start,
+        };
+
+        // the bucket holds a second worth of tokens.
+        assert_eq!(bucket.take(1000, 1000.0, start), Duration::ZERO);
+        assert_eq!(bucket.take(500, 1000.0, start), Duration::from_millis(500));
+        // the pause refilled the bucket.
+        let resumed = start + Duration::from_millis(500);
+        assert_eq!(bucket.take(0, 1000.0, resumed), Duration::ZERO);
+        // the tokens don't pile up past a second worth of them.
+        let later = resumed + Duration::from_secs(10);
+        assert_eq!(bucket.take(1000, 1000.0, later), Duration::ZERO);
+        assert_eq!(bucket.take(1000, 1000.0, later), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_adaptive_backoff() {
+        let search_latency = LatencyWatch::default();
+        let throttle = IoThrottle::new(IoThrottleConfig {
+            budget: Some(4000),
+            adaptive_latency_threshold: Some(Duration::from_millis(100)),
+            search_latency: search_latency.clone(),
+        });
+        assert_eq!(throttle.rate(), Some(4000.0));
+
+        for _ in 0..32 {
+            search_latency.record(Duration::from_millis(500));
+        }
+        assert!(search_latency.recent() > Duration::from_millis(100));
+        assert_eq!(throttle.rate(), Some(1000.0));
+
+        let unlimited = IoThrottle::new(throttle.config.unlimited());
+        assert_eq!(unlimited.rate(), None);
+    }
+}
@@ -1,10 +1,12 @@
 use std::fs::{create_dir_all, File};
-use std::io::{self, BufReader, BufWriter, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 
 use milli::documents::DocumentBatchReader;
+use serde::{Deserialize, Serialize};
 use serde_json::Map;
+use sha2::{Digest, Sha256};
 use tempfile::{NamedTempFile, PersistError};
 use uuid::Uuid;
 
@@ -47,11 +49,56 @@ into_update_store_error!(
     milli::documents::Error
 );
 
+/// The size and the checksum of an update file, as it was persisted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateFileDigest {
+    pub size: u64,
+    /// The sha256 of the content, hex encoded.
+    pub sha256: String,
+}
+
+impl UpdateFileDigest {
+    fn of(mut reader: impl Read) -> io::Result<Self> {
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut reader, &mut hasher)?;
+        Ok(Self {
+            size,
+            sha256: hex::encode(hasher.finalize()),
+        })
+    }
+}
+
 impl UpdateFile {
-    pub fn persist(self) -> Result<()> {
-        self.file.persist(&self.path)?;
-        Ok(())
+    /// Moves the update file to its final path, and returns its digest.
+    ///
+    /// The content is synced to the disk before the file is renamed, and the rename before this
+    /// method returns, so that after a crash an update file is either complete or missing, and a
+    /// task never refers to a file that isn't on the disk.
+    pub fn persist(self) -> Result<UpdateFileDigest> {
+        let mut file = self.file;
+        file.flush()?;
+        file.seek(SeekFrom::Start(0))?;
+        let digest = UpdateFileDigest::of(BufReader::new(file.as_file_mut()))?;
+        file.as_file().sync_all()?;
+
+        file.persist(&self.path)?;
+        if let Some(dir) = self.path.parent() {
+            sync_dir(dir)?;
+        }
+
+        Ok(digest)
+    }
+}
+
+/// Syncs the entries of the directory `path` to the disk, so that a file created or renamed in
+/// it survives a crash.
+fn sync_dir(path: &Path) -> io::Result<()> {
+    // directories can't be opened as files on windows.
+    if cfg!(unix) {
+        File::open(path)?.sync_all()?;
     }
+    Ok(())
 }
 
 impl Deref for UpdateFile {
@@ -122,6 +169,12 @@ mod store {
             Ok((uuid, update_file))
         }
 
+        /// Creates a temporary file to download raw documents to. The file is removed when
+        /// dropped.
+        pub fn new_download(&self) -> Result<NamedTempFile> {
+            Ok(NamedTempFile::new_in(&self.path)?)
+        }
+
         /// Returns the file corresponding to the requested uuid.
         pub fn get_update(&self, uuid: Uuid) -> Result<File> {
             let path = self.path.join(uuid.to_string());
@@ -139,6 +192,16 @@ mod store {
             Ok(())
         }
 
+        /// Copies the update file `uuid` to a new update file, whose uuid is returned.
+        pub fn copy(&self, uuid: Uuid) -> Result<Uuid> {
+            let copy = Uuid::new_v4();
+            std::fs::copy(
+                self.path.join(uuid.to_string()),
+                self.path.join(copy.to_string()),
+            )?;
+            Ok(copy)
+        }
+
         /// Peforms a dump of the given update file uuid into the provided dump path.
         pub fn dump(&self, uuid: Uuid, dump_path: impl AsRef<Path>) -> Result<()> {
             let uuid_string = uuid.to_string();
@@ -176,6 +239,12 @@ mod store {
             Ok(self.get_update(uuid)?.metadata()?.len())
         }
 
+        /// Computes the digest of the update file `uuid` as it is now on the disk.
+        pub fn digest(&self, uuid: Uuid) -> Result<UpdateFileDigest> {
+            let file = self.get_update(uuid)?;
+            Ok(UpdateFileDigest::of(BufReader::new(file))?)
+        }
+
         pub async fn delete(&self, uuid: Uuid) -> Result<()> {
             let path = self.path.join(uuid.to_string());
             tokio::fs::remove_file(path).await?;
@@ -218,6 +287,13 @@ mod test {
             }
         }
 
+        pub fn new_download(&self) -> Result<NamedTempFile> {
+            match self {
+                MockUpdateFileStore::Real(s) => s.new_download(),
+                MockUpdateFileStore::Mock(_) => todo!(),
+            }
+        }
+
         pub fn get_update(&self, uuid: Uuid) -> Result<File> {
             match self {
                 MockUpdateFileStore::Real(s) => s.get_update(uuid),
@@ -232,6 +308,13 @@ mod test {
             }
         }
 
+        pub fn copy(&self, uuid: Uuid) -> Result<Uuid> {
+            match self {
+                MockUpdateFileStore::Real(s) => s.copy(uuid),
+                MockUpdateFileStore::Mock(_) => todo!(),
+            }
+        }
+
         pub fn dump(&self, uuid: Uuid, dump_path: impl AsRef<Path>) -> Result<()> {
             match self {
                 MockUpdateFileStore::Real(s) => s.dump(uuid, dump_path),
@@ -246,6 +329,13 @@ mod test {
             }
         }
 
+        pub fn digest(&self, uuid: Uuid) -> Result<UpdateFileDigest> {
+            match self {
+                MockUpdateFileStore::Real(s) => s.digest(uuid),
+                MockUpdateFileStore::Mock(_) => todo!(),
+            }
+        }
+
         pub async fn delete(&self, uuid: Uuid) -> Result<()> {
             match self {
                 MockUpdateFileStore::Real(s) => s.delete(uuid).await,
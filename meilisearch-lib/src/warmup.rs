@@ -0,0 +1,219 @@
+//! Warm-up of the indexes.
+//!
+//! The first searches after a restart or a reindexation are much slower than the following ones
+//! because none of the index pages are in the page cache yet. When enabled, the warm-up service
+//! runs a set of representative queries against an index on startup and each time its settings
+//! are updated, discarding the results. Those queries are either the `warmupQueries` setting of
+//! the index, or, if none are configured, a sample of the last queries made on the index.
+//!
+//! The warm-up never goes through the scheduler: it runs in its own task, one query at a time,
+//! and yields between queries so that it never delays real work.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+use tokio::time::sleep;
+
+use crate::index::SearchQuery;
+use crate::index_resolver::index_store::IndexStore;
+use crate::index_resolver::meta_store::IndexMetaStore;
+use crate::index_resolver::IndexResolver;
+
+/// Number of queries kept per index when sampling the real queries.
+const SAMPLED_QUERIES_PER_INDEX: usize = 20;
+/// Pause between two warm-up queries, so the warm-up never hogs the search threads.
+const PAUSE_BETWEEN_QUERIES: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WarmupStatus {
+    pub is_warming_up: bool,
+    pub warmed_up_indexes: u64,
+    pub warmup_queries: u64,
+    #[serde(serialize_with = "time::serde::rfc3339::option::serialize")]
+    pub last_warmup: Option<OffsetDateTime>,
+}
+
+/// Handle to the warm-up service. It does nothing if the warm-up is disabled.
+#[derive(Clone, Default)]
+pub struct WarmupHandle {
+    sender: Option<mpsc::UnboundedSender<String>>,
+    status: Arc<RwLock<WarmupStatus>>,
+    /// The last queries made on each index, only recorded when query sampling is enabled.
+    sampled_queries: Option<Arc<Mutex<HashMap<String, VecDeque<String>>>>>,
+}
+
+impl WarmupHandle {
+    pub fn new(sample_queries: bool) -> (Self, mpsc::UnboundedReceiver<String>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handle = Self {
+            sender: Some(sender),
+            status: Default::default(),
+            sampled_queries: sample_queries.then(Default::default),
+        };
+
+        (handle, receiver)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Requests a warm-up of the index `index_uid`.
+    pub fn schedule(&self, index_uid: String) {
+        if let Some(ref sender) = self.sender {
+            // the receiver is only dropped on shutdown.
+            let _ = sender.send(index_uid);
+        }
+    }
+
+    /// Records a query made on `index_uid`, so that it can be replayed by the warm-up.
+    pub fn record_query(&self, index_uid: &str, query: &SearchQuery) {
+        if let (Some(sampled), Some(q)) = (&self.sampled_queries, &query.q) {
+            let mut sampled = sampled.lock();
+            let queries = sampled.entry(index_uid.to_string()).or_default();
+            if !queries.contains(q) {
+                if queries.len() == SAMPLED_QUERIES_PER_INDEX {
+                    queries.pop_front();
+                }
+                queries.push_back(q.clone());
+            }
+        }
+    }
+
+    pub fn status(&self) -> WarmupStatus {
+        self.status.read().clone()
+    }
+
+    fn sampled_queries(&self, index_uid: &str) -> Vec<String> {
+        self.sampled_queries
+            .as_ref()
+            .and_then(|sampled| {
+                sampled
+                    .lock()
+                    .get(index_uid)
+                    .map(|q| q.iter().cloned().collect())
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub struct WarmupService<U, I> {
+    pub(crate) index_resolver: Arc<IndexResolver<U, I>>,
+    pub(crate) handle: WarmupHandle,
+    pub(crate) receiver: mpsc::UnboundedReceiver<String>,
+}
+
+impl<U, I> WarmupService<U, I>
+where
+    U: IndexMetaStore + Send + Sync + 'static,
+    I: IndexStore + Send + Sync + 'static,
+{
+    pub async fn run(mut self) {
+        match self.index_resolver.list().await {
+            Ok(indexes) => indexes
+                .into_iter()
+                .for_each(|(uid, _)| self.handle.schedule(uid)),
+            Err(e) => warn!("Could not list the indexes to warm up: {}", e),
+        }
+
+        while let Some(index_uid) = self.receiver.recv().await {
+            self.warmup(index_uid).await;
+        }
+    }
+
+    async fn warmup(&self, index_uid: String) {
+        let index = match self.index_resolver.get_index(index_uid.clone()).await {
+            Ok(index) => index,
+            // the index may have been deleted since the warm-up was requested.
+            Err(e) => return debug!("Skipping warm-up of index `{}`: {}", index_uid, e),
+        };
+
+        let settings_index = index.clone();
+        let mut queries = match spawn_blocking(move || settings_index.settings()).await {
+            Ok(Ok(settings)) => settings.warmup_queries.set().unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        if queries.is_empty() {
+            queries = self.handle.sampled_queries(&index_uid);
+        }
+        if queries.is_empty() {
+            return;
+        }
+
+        info!(
+            "Warming up index `{}` with {} queries.",
+            index_uid,
+            queries.len()
+        );
+        self.handle.status.write().is_warming_up = true;
+        let started = Instant::now();
+
+        for q in queries {
+            let index = index.clone();
+            let query = SearchQuery {
+                q: Some(q),
+                ..SearchQuery::default()
+            };
+            if let Ok(Err(e)) = spawn_blocking(move || index.perform_search(query)).await {
+                debug!("Warm-up query on index `{}` failed: {}", index_uid, e);
+            }
+            self.handle.status.write().warmup_queries += 1;
+            sleep(PAUSE_BETWEEN_QUERIES).await;
+        }
+
+        let mut status = self.handle.status.write();
+        status.is_warming_up = false;
+        status.warmed_up_indexes += 1;
+        status.last_warmup = Some(OffsetDateTime::now_utc());
+        info!(
+            "Index `{}` warmed up in {:.2?}.",
+            index_uid,
+            started.elapsed()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn query(q: &str) -> SearchQuery {
+        SearchQuery {
+            q: Some(q.to_string()),
+            ..SearchQuery::default()
+        }
+    }
+
+    #[test]
+    fn test_sampled_queries_are_bounded() {
+        let (handle, _receiver) = WarmupHandle::new(true);
+        for i in 0..SAMPLED_QUERIES_PER_INDEX + 5 {
+            handle.record_query("movies", &query(&i.to_string()));
+        }
+        handle.record_query("movies", &query("24"));
+
+        let sampled = handle.sampled_queries("movies");
+        assert_eq!(sampled.len(), SAMPLED_QUERIES_PER_INDEX);
+        assert_eq!(sampled.first().unwrap(), "5");
+        assert!(handle.sampled_queries("books").is_empty());
+    }
+
+    #[test]
+    fn test_no_sampling_when_disabled() {
+        let (handle, _receiver) = WarmupHandle::new(false);
+        handle.record_query("movies", &query("hello"));
+        assert!(handle.sampled_queries("movies").is_empty());
+
+        let handle = WarmupHandle::default();
+        assert!(!handle.is_enabled());
+        handle.schedule("movies".to_string());
+    }
+}